@@ -30,14 +30,24 @@ fn generate_protobuf() -> Result<(), Box<dyn Error>> {
                 "../../../message/flow_log.proto",
                 "../../../message/stats.proto",
                 "../../../message/k8s_event.proto",
+                "../../../message/alert_event.proto",
             ],
             &["../../../message"],
         )?;
+    tonic_build::configure()
+        .out_dir("src/proto")
+        .type_attribute(".agent_debug", "#[derive(serde::Serialize)]")
+        .compile(&["../../../message/agent_debug.proto"], &["../../../message"])?;
     tonic_build::configure()
         .build_server(false)
         .out_dir("src/proto/integration")
         .compile(
-            &["../../../message/opentelemetry/opentelemetry/proto/trace/v1/trace.proto"],
+            &[
+                "../../../message/opentelemetry/opentelemetry/proto/trace/v1/trace.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/collector/trace/v1/trace_service.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/collector/metrics/v1/metrics_service.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/collector/logs/v1/logs_service.proto",
+            ],
             &["../../../message/opentelemetry"],
         )?;
 
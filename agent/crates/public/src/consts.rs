@@ -26,6 +26,7 @@ pub const CONTAINER_NAME: &str = "deepflow-agent";
 pub const DEFAULT_INGESTER_PORT: u16 = 30033;
 pub const DEFAULT_CONTROLLER_PORT: u16 = 30035;
 pub const DEFAULT_CONTROLLER_TLS_PORT: u16 = 30135;
+pub const DEFAULT_LOCAL_API_PORT: u16 = 30037;
 
 pub const NORMAL_EXIT_WITH_RESTART: i32 = 3;
 pub const TRIDENT_MEMORY_LIMIT: u64 = 0;
@@ -175,6 +176,7 @@ pub const ARP_HEADER_SIZE: usize = 28;
 pub const IPV4_HEADER_SIZE: usize = 20;
 pub const IPV6_HEADER_SIZE: usize = 40;
 pub const UDP_HEADER_SIZE: usize = 8;
+pub const ICMP_HEADER_SIZE: usize = 8;
 pub const VXLAN_HEADER_SIZE: usize = 8;
 pub const GRE_HEADER_SIZE: usize = 12;
 pub const ERSPAN_HEADER_SIZE: usize = 12;
@@ -183,7 +185,12 @@ pub const ERSPAN_II_HEADER_SIZE: usize = 8;
 pub const ERSPAN_III_HEADER_SIZE: usize = 12;
 pub const ERSPAN_III_SUBHEADER_SIZE: usize = 8;
 pub const GENEVE_HEADER_SIZE: usize = 8;
+pub const GTPU_HEADER_SIZE: usize = 8;
+pub const GTPU_OPTIONAL_HEADER_SIZE: usize = 4;
 pub const TCP_HEADER_SIZE: usize = 20;
+pub const SCTP_HEADER_SIZE: usize = 12;
+pub const SCTP_CHUNK_HEADER_SIZE: usize = 4;
+pub const SCTP_DATA_CHUNK_HEADER_SIZE: usize = 16;
 
 // min packet size
 pub const IPV4_PACKET_SIZE: usize = ETH_HEADER_SIZE + IPV4_HEADER_SIZE; // 34
@@ -409,6 +416,66 @@ pub const GENEVE_OPTION_LENGTH_MASK: u8 = 0x3f;
 pub const GENEVE_VERSION_SHIFT: u8 = 6;
 pub const GENEVE_VNI_SHIFT: u32 = 8;
 
+pub const GENEVE_OPTION_HEADER_SIZE: usize = 4;
+pub const GENEVE_OPTION_CLASS_OFFSET: usize = 0;
+pub const GENEVE_OPTION_TYPE_OFFSET: usize = 2;
+pub const GENEVE_OPTION_LENGTH_OFFSET: usize = 3;
+pub const GENEVE_OPTION_DATA_LENGTH_MASK: u8 = 0x1f;
+// AWS Gateway Load Balancer
+pub const GENEVE_OPTION_CLASS_AWS: u16 = 0x0108;
+pub const GENEVE_OPTION_TYPE_GWLBE_ID: u8 = 1;
+pub const GENEVE_OPTION_TYPE_ATTACHMENT_ID: u8 = 2;
+pub const GENEVE_OPTION_TYPE_FLOW_COOKIE: u8 = 3;
+
+pub const GTPU_FLAGS_OFFSET: usize = 0;
+pub const GTPU_MSG_TYPE_OFFSET: usize = 1;
+pub const GTPU_TEID_OFFSET: usize = 4;
+pub const GTPU_NEXT_EXT_TYPE_OFFSET: usize = 11;
+
+pub const GTPU_VERSION_SHIFT: u8 = 5;
+pub const GTPU_FLAGS_PT_MASK: u8 = 0x10;
+pub const GTPU_FLAGS_EXT_MASK: u8 = 0x04;
+pub const GTPU_FLAGS_OPTIONAL_MASK: u8 = 0x07; // E, S and PN
+pub const GTPU_MSG_TYPE_GPDU: u8 = 0xff;
+
+pub const VXLAN_GPE_FLAGS_OFFSET: usize = 0;
+pub const VXLAN_GPE_NEXT_PROTOCOL_OFFSET: usize = 3;
+pub const VXLAN_GPE_VNI_OFFSET: usize = 4;
+pub const VXLAN_GPE_FLAGS_I_MASK: u8 = 0x08;
+pub const VXLAN_GPE_FLAGS_P_MASK: u8 = 0x04;
+pub const VXLAN_GPE_VERSION_MASK: u8 = 0x30;
+
+pub const NSH_HEADER_MIN_SIZE: usize = 8; // base header + service path header
+pub const NSH_LENGTH_OFFSET: usize = 0;
+pub const NSH_NEXT_PROTOCOL_OFFSET: usize = 3;
+pub const NSH_SERVICE_PATH_OFFSET: usize = 4;
+pub const NSH_SERVICE_INDEX_OFFSET: usize = 7;
+pub const NSH_LENGTH_MASK: u16 = 0x3f;
+pub const NSH_VERSION_SHIFT: u8 = 6;
+pub const NSH_SPI_SHIFT: u32 = 8;
+
+pub const MPLS_LABEL_SIZE: usize = 4;
+pub const MPLS_CONTROL_WORD_SIZE: usize = 4;
+pub const MPLS_LABEL_SHIFT: u32 = 12;
+pub const MPLS_BOTTOM_OF_STACK_MASK: u32 = 0x100;
+pub const MPLS_LABEL_STACK_MAX_DEPTH: usize = 16;
+
+pub const PPPOE_HEADER_SIZE: usize = 6;
+pub const PPP_PROTOCOL_SIZE: usize = 2;
+pub const PPPOE_VERSION_TYPE_OFFSET: usize = 0;
+pub const PPPOE_CODE_OFFSET: usize = 1;
+pub const PPPOE_SESSION_ID_OFFSET: usize = 2;
+pub const PPPOE_VERSION_TYPE: u8 = 0x11;
+pub const PPPOE_CODE_SESSION_DATA: u8 = 0;
+pub const PPP_PROTOCOL_IPV4: u16 = 0x0021;
+pub const PPP_PROTOCOL_IPV6: u16 = 0x0057;
+
+// next protocol of VXLAN-GPE and NSH
+pub const GPE_NEXT_PROTOCOL_IPV4: u8 = 1;
+pub const GPE_NEXT_PROTOCOL_IPV6: u8 = 2;
+pub const GPE_NEXT_PROTOCOL_ETHERNET: u8 = 3;
+pub const GPE_NEXT_PROTOCOL_NSH: u8 = 4;
+
 pub const IP_IHL_OFFSET: usize = 0;
 pub const IP6_PROTO_OFFSET: usize = 6;
 pub const IP6_SIP_OFFSET: usize = 20; // 用于解析tunnel，仅使用后四个字节
@@ -417,6 +484,8 @@ pub const UDP_DPORT_OFFSET: usize = 2;
 pub const VXLAN_FLAGS_OFFSET_DECAP: usize = 0;
 pub const VXLAN_VNI_OFFSET_DECAP: usize = 4;
 pub const ERSPAN_ID_OFFSET: usize = 0; // erspan2和3共用，4字节取0x3ff
+pub const ERSPAN_III_TIMESTAMP_OFFSET: usize = 4;
+pub const ERSPAN_III_HW_ID_OFFSET: usize = 10; // 2字节中间6位
 pub const ERSPAN_III_FLAGS_OFFSET: usize = 11;
 
 // IpAddr mask
@@ -431,6 +500,8 @@ pub const L7_PROTOCOL_INFERENCE_TTL: usize = 60;
 // RawPcap
 pub const PCAP_MAGIC: u32 = 0xa1b2c3d4;
 pub const RECORD_HEADER_LEN: usize = 16;
+// block type of pcapng section header block
+pub const PCAPNG_MAGIC: u32 = 0x0a0d0d0a;
 
 // GRPC
 pub const GRPC_DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
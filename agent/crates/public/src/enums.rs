@@ -32,10 +32,17 @@ impl EthernetType {
     pub const IPV4: Self = Self(0x0800);
     pub const ARP: Self = Self(0x0806);
     pub const IPV6: Self = Self(0x86DD);
+    pub const MPLS_UNICAST: Self = Self(0x8847);
+    pub const MPLS_MULTICAST: Self = Self(0x8848);
+    pub const PPPOE_DISCOVERY: Self = Self(0x8863);
+    pub const PPPOE_SESSION: Self = Self(0x8864);
     pub const DOT1Q: Self = Self(0x8100);
     pub const TRANSPARENT_ETHERNET_BRIDGING: Self = Self(0x6558);
     pub const QINQ: Self = Self(0x88a8);
     pub const LINK_LAYER_DISCOVERY: Self = Self(0x88cc);
+    pub const PTP: Self = Self(0x88f7);
+    pub const FCOE: Self = Self(0x8906);
+    pub const FCOE_INITIALIZATION: Self = Self(0x8914);
 }
 
 impl Default for EthernetType {
@@ -76,6 +83,7 @@ pub struct IpProtocol(u8);
 impl IpProtocol {
     pub const IPV6_HOP_BY_HOP: Self = Self(0);
     pub const ICMPV4: Self = Self(1);
+    pub const IGMP: Self = Self(2);
     pub const IPV4: Self = Self(4);
     pub const TCP: Self = Self(6);
     pub const UDP: Self = Self(17);
@@ -89,6 +97,7 @@ impl IpProtocol {
     pub const NO_NEXT_HEADER: Self = Self(59);
     pub const IPV6_DESTINATION: Self = Self(60);
     pub const IPIP: Self = Self(94);
+    pub const SCTP: Self = Self(132);
 }
 
 impl Default for IpProtocol {
@@ -128,6 +137,7 @@ pub enum L4Protocol {
     Tcp = 1,
     Udp = 2,
     Icmp = 3,
+    Sctp = 4,
 }
 
 impl From<IpProtocol> for L4Protocol {
@@ -136,6 +146,7 @@ impl From<IpProtocol> for L4Protocol {
             IpProtocol::TCP => Self::Tcp,
             IpProtocol::UDP => Self::Udp,
             IpProtocol::ICMPV4 | IpProtocol::ICMPV6 => Self::Icmp,
+            IpProtocol::SCTP => Self::Sctp,
             _ => Self::Unknown,
         }
     }
@@ -150,6 +161,8 @@ impl From<String> for L4Protocol {
             Self::Tcp
         } else if l4_protocol_str.eq("ip_udp") {
             Self::Udp
+        } else if l4_protocol_str.eq("ip_sctp") {
+            Self::Sctp
         } else {
             Self::Unknown
         }
@@ -162,10 +175,14 @@ impl Default for L4Protocol {
     }
 }
 
+// Capture network types of IDC are numbered in [1, CAPTURE_NETWORK_TYPE_MAX) except 3,
+// large ISP deployments may have more capture points than 256
+pub const CAPTURE_NETWORK_TYPE_MAX: usize = 4096;
+
 #[derive(Serialize, Debug, Clone, Copy, Hash, PartialEq, Eq, Ord)]
 pub enum CaptureNetworkType {
     Any,
-    Idc(u8),
+    Idc(u16),
     Cloud,
     Max,
     Unknown,
@@ -184,8 +201,8 @@ impl TryFrom<u16> for CaptureNetworkType {
             0 => Ok(CaptureNetworkType::Any),
             3 => Ok(CaptureNetworkType::Cloud),
             0xffff => Ok(CaptureNetworkType::Unknown),
-            v if v < 256 => Ok(CaptureNetworkType::Idc(v as u8)),
-            _ => Err("CaptureNetworkType not in [0, 256)"),
+            v if (v as usize) < CAPTURE_NETWORK_TYPE_MAX => Ok(CaptureNetworkType::Idc(v)),
+            _ => Err("CaptureNetworkType not in [0, 4096)"),
         }
     }
 }
@@ -194,9 +211,9 @@ impl From<CaptureNetworkType> for u16 {
     fn from(t: CaptureNetworkType) -> u16 {
         match t {
             CaptureNetworkType::Any => 0,
-            CaptureNetworkType::Idc(v) => v as u16,
+            CaptureNetworkType::Idc(v) => v,
             CaptureNetworkType::Cloud => 3,
-            CaptureNetworkType::Max => 256,
+            CaptureNetworkType::Max => CAPTURE_NETWORK_TYPE_MAX as u16,
             CaptureNetworkType::Unknown => 0xffff,
         }
     }
@@ -373,6 +390,24 @@ pub enum LinuxSllPacketType {
     FastRoute = 6, // FastRoute frame
 }
 
+// according to ARPHRD_* in linux/if_arp.h sll_hatype, other types are handled as ethernet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u16)]
+pub enum LinkType {
+    #[default]
+    Ethernet = 1,
+    Ieee80211Radio = 803, // radiotap header + 802.11 frame
+}
+
+impl From<u16> for LinkType {
+    fn from(t: u16) -> Self {
+        match t {
+            803 => Self::Ieee80211Radio,
+            _ => Self::Ethernet,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem::size_of;
@@ -395,6 +430,8 @@ mod tests {
         assert_eq!(ip, 58);
         assert_eq!(58, ip);
         assert_eq!(Ok(IpProtocol::UDP), IpProtocol::try_from(17u8));
+        assert_eq!(Ok(IpProtocol::SCTP), IpProtocol::try_from(132u8));
+        assert_eq!(L4Protocol::from(IpProtocol::SCTP), L4Protocol::Sctp);
     }
 
     #[test]
@@ -32,6 +32,11 @@ pub enum Error {
     LibpcapError(String),
     #[error("vhost user error {0}")]
     VhostUserError(String),
+    #[error("dpdk error {0}")]
+    DpdkError(String),
+    #[cfg(target_os = "linux")]
+    #[error("af_xdp error {0}")]
+    AfXdpError(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
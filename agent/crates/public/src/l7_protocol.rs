@@ -40,6 +40,7 @@ pub enum L7Protocol {
     // HTTP
     Http1 = 20,
     Http2 = 21,
+    WebSocket = 22,
 
     // RPC
     Dubbo = 40,
@@ -61,6 +62,9 @@ pub enum L7Protocol {
     MongoDB = 81,
     Memcached = 82,
 
+    // Industrial
+    Modbus = 90,
+
     // MQ
     Kafka = 100,
     MQTT = 101,
@@ -69,10 +73,16 @@ pub enum L7Protocol {
     NATS = 104,
     Pulsar = 105,
     ZMTP = 106,
+    STOMP = 107,
 
     // INFRA
     DNS = 120,
     TLS = 121,
+    QUIC = 122,
+    Kerberos = 123,
+    SMB = 124,
+    NFS = 125,
+    Diameter = 126,
 
     Custom = 127,
 
@@ -90,6 +100,9 @@ impl L7Protocol {
             | Self::Dubbo
             | Self::SofaRPC
             | Self::SomeIp
+            | Self::STOMP
+            | Self::Modbus
+            | Self::Diameter
             | Self::Custom => true,
             _ => false,
         }
@@ -103,6 +116,7 @@ impl From<String> for L7Protocol {
         match l7_protocol_str.as_str() {
             "http" | "https" => Self::Http1,
             "http2" => Self::Http2,
+            "websocket" => Self::WebSocket,
             "dubbo" => Self::Dubbo,
             "grpc" => Self::Grpc,
             "fastcgi" => Self::FastCGI,
@@ -122,9 +136,16 @@ impl From<String> for L7Protocol {
             "nats" => Self::NATS,
             "pulsar" => Self::Pulsar,
             "zmtp" => Self::ZMTP,
+            "stomp" => Self::STOMP,
             "dns" => Self::DNS,
             "oracle" => Self::Oracle,
             "tls" => Self::TLS,
+            "quic" => Self::QUIC,
+            "kerberos" => Self::Kerberos,
+            "smb" => Self::SMB,
+            "nfs" => Self::NFS,
+            "modbus" => Self::Modbus,
+            "diameter" => Self::Diameter,
             "some/ip" | "someip" => Self::SomeIp,
             _ => Self::Unknown,
         }
@@ -19,7 +19,10 @@ use std::fmt;
 use std::io::{Result, Write};
 use std::time::Duration;
 
-use crate::consts::RECORD_HEADER_LEN;
+use serde::Deserialize;
+
+use crate::consts::{PCAPNG_MAGIC, RECORD_HEADER_LEN};
+use crate::enums::LinkType;
 
 pub const SECONDS_IN_MINUTE: u64 = 60;
 
@@ -34,6 +37,7 @@ pub struct Packet<'a> {
     pub if_index: isize,
     pub capture_length: isize,
     pub data: &'a mut [u8],
+    pub link_type: LinkType,
     // Some scene packet will be copied and stored in raw, and referenced by data
     pub raw: Option<*mut u8>,
 }
@@ -53,16 +57,21 @@ impl Drop for Packet<'_> {
 
 pub struct MiniPacket {
     pub packet: Vec<u8>,
+    // length of the packet on wire, packet may be truncated by payload slice
+    pub packet_len: u32,
     pub timestamp: Duration,
     pub flow_id: u64,
     pub acl_gids: Vec<u16>,
     pub second_in_minute: u8,
+    pub if_index: u32,
 }
 
 impl fmt::Debug for MiniPacket {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MiniPacket")
             .field("packet_len", &self.packet.len())
+            .field("packet_len_on_wire", &self.packet_len)
+            .field("if_index", &self.if_index)
             .field("timestamp", &self.timestamp)
             .field("second_in_minute", &self.second_in_minute)
             .field("flow_id", &self.flow_id)
@@ -76,6 +85,15 @@ impl MiniPacket {
         self.packet.len() + RECORD_HEADER_LEN
     }
 
+    // Flow ID and ACL group IDs of pcap policies, used to correlate packets with flow logs
+    pub fn comment(&self) -> String {
+        format!("flow_id={} acl_gids={:?}", self.flow_id, self.acl_gids)
+    }
+
+    pub fn pcapng_record_len(&self) -> usize {
+        enhanced_packet_block_len(self.packet.len(), self.comment().len())
+    }
+
     pub fn start_time_in_minute(&self) -> Duration {
         let second_in_minute = self.second_in_minute as u64;
         Duration::from_secs(
@@ -97,3 +115,190 @@ pub fn write_record_header(
     writer.write(pkt_len.to_le_bytes().as_slice())?;
     Ok(())
 }
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PcapFormat {
+    #[default]
+    Pcap,
+    Pcapng,
+}
+
+// Reference https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-02.html
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const PCAPNG_ENHANCED_PACKET_BLOCK: u32 = 6;
+const PCAPNG_OPT_ENDOFOPT: u16 = 0;
+const PCAPNG_OPT_COMMENT: u16 = 1;
+const PCAPNG_OPT_IF_NAME: u16 = 2;
+const PCAPNG_OPT_IF_TSRESOL: u16 = 9;
+// 10^-9, timestamps are in nanoseconds
+const PCAPNG_TSRESOL_NANOS: u8 = 9;
+const PCAPNG_LINKTYPE_ETHERNET: u16 = 1;
+// block type, block total length at both ends
+const PCAPNG_BLOCK_OVERHEAD: usize = 12;
+const PCAPNG_SECTION_HEADER_BLOCK_LEN: usize = PCAPNG_BLOCK_OVERHEAD + 16;
+
+fn pcapng_padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+fn pcapng_option_len(value_len: usize) -> usize {
+    4 + value_len + pcapng_padding(value_len)
+}
+
+// options of a block, ended with opt_endofopt if any option exists
+fn pcapng_options_len(value_lens: &[usize]) -> usize {
+    let len: usize = value_lens
+        .iter()
+        .filter(|l| **l > 0)
+        .map(|l| pcapng_option_len(*l))
+        .sum();
+    if len > 0 {
+        len + pcapng_option_len(0)
+    } else {
+        0
+    }
+}
+
+fn write_pcapng_options(writer: &mut impl Write, options: &[(u16, &[u8])]) -> Result<()> {
+    let mut written = false;
+    for (code, value) in options.iter().filter(|(_, v)| !v.is_empty()) {
+        writer.write_all(&code.to_le_bytes())?;
+        writer.write_all(&(value.len() as u16).to_le_bytes())?;
+        writer.write_all(value)?;
+        writer.write_all(&[0; 3][..pcapng_padding(value.len())])?;
+        written = true;
+    }
+    if written {
+        writer.write_all(&PCAPNG_OPT_ENDOFOPT.to_le_bytes())?;
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn enhanced_packet_block_len(raw_pkt_len: usize, comment_len: usize) -> usize {
+    PCAPNG_BLOCK_OVERHEAD
+        + 20
+        + raw_pkt_len
+        + pcapng_padding(raw_pkt_len)
+        + pcapng_options_len(&[comment_len])
+}
+
+pub fn write_pcapng_section_header(writer: &mut impl Write) -> Result<()> {
+    let block_len = PCAPNG_SECTION_HEADER_BLOCK_LEN as u32;
+    writer.write_all(&PCAPNG_MAGIC.to_le_bytes())?;
+    writer.write_all(&block_len.to_le_bytes())?;
+    writer.write_all(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes())?;
+    // version 1.0
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    // section length is not specified
+    writer.write_all(&(-1i64).to_le_bytes())?;
+    writer.write_all(&block_len.to_le_bytes())?;
+    Ok(())
+}
+
+// Interface IDs in enhanced packet blocks are the order of interface description blocks
+// in the section, starting from 0
+pub fn write_pcapng_interface_description(
+    writer: &mut impl Write,
+    name: &str,
+    snap_len: u32,
+) -> Result<()> {
+    let tsresol = [PCAPNG_TSRESOL_NANOS];
+    let block_len =
+        (PCAPNG_BLOCK_OVERHEAD + 8 + pcapng_options_len(&[name.len(), tsresol.len()])) as u32;
+    writer.write_all(&PCAPNG_INTERFACE_DESCRIPTION_BLOCK.to_le_bytes())?;
+    writer.write_all(&block_len.to_le_bytes())?;
+    writer.write_all(&PCAPNG_LINKTYPE_ETHERNET.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.write_all(&snap_len.to_le_bytes())?;
+    write_pcapng_options(
+        writer,
+        &[
+            (PCAPNG_OPT_IF_NAME, name.as_bytes()),
+            (PCAPNG_OPT_IF_TSRESOL, &tsresol),
+        ],
+    )?;
+    writer.write_all(&block_len.to_le_bytes())?;
+    Ok(())
+}
+
+pub fn write_pcapng_enhanced_packet(
+    writer: &mut impl Write,
+    interface_id: u32,
+    ts: Duration,
+    raw_pkt: &[u8],
+    pkt_len: u32,
+    comment: &str,
+) -> Result<()> {
+    let block_len = enhanced_packet_block_len(raw_pkt.len(), comment.len()) as u32;
+    let ts = ts.as_nanos() as u64;
+    writer.write_all(&PCAPNG_ENHANCED_PACKET_BLOCK.to_le_bytes())?;
+    writer.write_all(&block_len.to_le_bytes())?;
+    writer.write_all(&interface_id.to_le_bytes())?;
+    writer.write_all(&((ts >> 32) as u32).to_le_bytes())?;
+    writer.write_all(&(ts as u32).to_le_bytes())?;
+    writer.write_all(&(raw_pkt.len() as u32).to_le_bytes())?;
+    writer.write_all(&pkt_len.to_le_bytes())?;
+    writer.write_all(raw_pkt)?;
+    writer.write_all(&[0; 3][..pcapng_padding(raw_pkt.len())])?;
+    write_pcapng_options(writer, &[(PCAPNG_OPT_COMMENT, comment.as_bytes())])?;
+    writer.write_all(&block_len.to_le_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_len(block: &[u8]) -> usize {
+        u32::from_le_bytes(block[4..8].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn pcapng_blocks() {
+        let mut buf = vec![];
+        write_pcapng_section_header(&mut buf).unwrap();
+        assert_eq!(buf.len(), PCAPNG_SECTION_HEADER_BLOCK_LEN);
+        assert_eq!(block_len(&buf), buf.len());
+
+        let mut idb = vec![];
+        write_pcapng_interface_description(&mut idb, "eth0", 65535).unwrap();
+        // 20 bytes header and trailer, if_name 8, if_tsresol 8, opt_endofopt 4
+        assert_eq!(idb.len(), 40);
+        assert_eq!(block_len(&idb), idb.len());
+        assert_eq!(&idb[idb.len() - 4..], &idb[4..8]);
+
+        let packet = MiniPacket {
+            packet: vec![0xff; 61],
+            packet_len: 1514,
+            timestamp: Duration::new(1700000000, 123456789),
+            flow_id: 42,
+            acl_gids: vec![3, 5],
+            second_in_minute: 20,
+            if_index: 2,
+        };
+        let mut epb = vec![];
+        write_pcapng_enhanced_packet(
+            &mut epb,
+            0,
+            packet.timestamp,
+            &packet.packet,
+            packet.packet_len,
+            &packet.comment(),
+        )
+        .unwrap();
+        assert_eq!(epb.len(), packet.pcapng_record_len());
+        assert_eq!(epb.len() % 4, 0);
+        assert_eq!(block_len(&epb), epb.len());
+        let ts = (u32::from_le_bytes(epb[12..16].try_into().unwrap()) as u64) << 32
+            | u32::from_le_bytes(epb[16..20].try_into().unwrap()) as u64;
+        assert_eq!(ts, 1700000000_123456789);
+        assert_eq!(&epb[24..28], &1514u32.to_le_bytes());
+        let comment = b"flow_id=42 acl_gids=[3, 5]";
+        let offset = 28 + 64 + 4;
+        assert_eq!(&epb[offset..offset + comment.len()], comment);
+    }
+}
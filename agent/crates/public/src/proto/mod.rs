@@ -15,6 +15,8 @@
  */
 
 pub mod agent;
+pub mod agent_debug;
+pub mod alert_event;
 pub mod common;
 pub mod flow_log;
 pub mod integration;
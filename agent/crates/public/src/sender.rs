@@ -31,6 +31,11 @@ pub trait Sendable: Debug + Send + 'static {
     }
     // Serialize data to key-value and append to a string
     fn to_kv_string(&self, _: &mut String) {}
+    // The flow which the data belongs to, data of the same flow are kept in order by senders
+    // partitioning the data
+    fn flow_id(&self) -> Option<u64> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy, IntoPrimitive)]
@@ -28,6 +28,15 @@ use super::error::{Error, Result};
 const SIOCETHTOOL: u64 = 0x8946;
 #[cfg(any(target_env = "musl", target_env = ""))]
 const SIOCETHTOOL: i32 = 0x8946;
+// ioctl hardware timestamping config requests
+#[cfg(target_env = "gnu")]
+const SIOCSHWTSTAMP: u64 = 0x89b0;
+#[cfg(any(target_env = "musl", target_env = ""))]
+const SIOCSHWTSTAMP: i32 = 0x89b0;
+#[cfg(target_env = "gnu")]
+const SIOCGHWTSTAMP: u64 = 0x89b1;
+#[cfg(any(target_env = "musl", target_env = ""))]
+const SIOCGHWTSTAMP: i32 = 0x89b1;
 
 // ethtool stats related constants.
 const ETH_GSTRING_LEN: usize = 32;
@@ -35,6 +44,12 @@ const ETH_SS_FEATURES: u32 = 4;
 const ETHTOOL_GSTRINGS: u32 = 0x1b;
 const ETHTOOL_GSSET_INFO: u32 = 0x37; /* Get string set info */
 const ETHTOOL_GFEATURES: u32 = 0x3a; /* Get device offload settings */
+const ETHTOOL_GET_TS_INFO: u32 = 0x41; /* Get time stamping and PHC info */
+
+// timestamping flags, refer to include/uapi/linux/net_tstamp.h
+pub const SOF_TIMESTAMPING_RX_HARDWARE: u32 = 1 << 2;
+pub const SOF_TIMESTAMPING_RAW_HARDWARE: u32 = 1 << 6;
+const HWTSTAMP_FILTER_ALL: i32 = 1;
 
 // Maximum size of an interface name
 const IFNAMSIZ: usize = 16;
@@ -79,6 +94,26 @@ struct Gfeatures {
     pub blocks: [GetFeaturesBlock; MAX_FEATURE_BLOCKS],
 }
 
+#[derive(Debug, Default)]
+#[repr(C)]
+struct TsInfo {
+    cmd: u32,
+    so_timestamping: u32,
+    phc_index: i32,
+    tx_types: u32,
+    tx_reserved: [u32; 3],
+    rx_filters: u32,
+    rx_reserved: [u32; 3],
+}
+
+#[derive(Debug, Default)]
+#[repr(C)]
+struct HwtstampConfig {
+    flags: i32,
+    tx_type: i32,
+    rx_filter: i32,
+}
+
 #[derive(Debug)]
 #[repr(C)]
 struct IfReq {
@@ -100,6 +135,76 @@ fn ethtool_ioctl(fd: i32, if_name: [u8; IFNAMSIZ], data_ptr: usize) -> Result<i3
     Err(Error::Errno(Errno::from_i32(code)))
 }
 
+/// enables hardware timestamping of all received packets on the interface.
+///
+/// Returns error if the driver does not support hardware rx timestamps, in which case
+/// sockets fall back to software timestamps.
+pub fn enable_hardware_rx_timestamp(if_name: &str) -> Result<()> {
+    let fd = socket(
+        AddressFamily::Inet,
+        SockType::Datagram,
+        SockFlag::empty(),
+        None,
+    )?;
+    let result = set_hardware_rx_timestamp(fd, if_name);
+    let _ = nix::unistd::close(fd);
+    result
+}
+
+fn set_hardware_rx_timestamp(fd: i32, if_name: &str) -> Result<()> {
+    let mut req_name = [0u8; IFNAMSIZ];
+    if if_name.len() >= IFNAMSIZ {
+        return Err(Error::Ethtool(format!(
+            "interface({}) name length({}) >= IFNAMSIZ({})",
+            if_name,
+            if_name.len(),
+            IFNAMSIZ
+        )));
+    }
+    req_name
+        .get_mut(..if_name.len())
+        .unwrap()
+        .copy_from_slice(if_name.as_bytes());
+
+    let mut ts_info = TsInfo {
+        cmd: ETHTOOL_GET_TS_INFO,
+        ..Default::default()
+    };
+    ethtool_ioctl(fd, req_name, &mut ts_info as *mut TsInfo as usize)?;
+    let required = SOF_TIMESTAMPING_RX_HARDWARE | SOF_TIMESTAMPING_RAW_HARDWARE;
+    if ts_info.so_timestamping & required != required {
+        return Err(Error::Ethtool(format!(
+            "interface({}) does not support hardware rx timestamp, so_timestamping: {:#x}",
+            if_name, ts_info.so_timestamping
+        )));
+    }
+    if ts_info.rx_filters & (1 << HWTSTAMP_FILTER_ALL) == 0 {
+        return Err(Error::Ethtool(format!(
+            "interface({}) does not support timestamping all received packets, rx_filters: {:#x}",
+            if_name, ts_info.rx_filters
+        )));
+    }
+
+    // keep tx settings of other applications, e.g. ptp4l
+    let mut config = HwtstampConfig::default();
+    let mut ifr = IfReq {
+        ifr_name: req_name,
+        ifr_data: &mut config as *mut HwtstampConfig as usize,
+    };
+    if unsafe { ioctl(fd, SIOCGHWTSTAMP, &mut ifr) } != 0 {
+        // SIOCGHWTSTAMP is not implemented by older drivers
+        config = HwtstampConfig::default();
+    }
+    if config.rx_filter == HWTSTAMP_FILTER_ALL {
+        return Ok(());
+    }
+    config.rx_filter = HWTSTAMP_FILTER_ALL;
+    if unsafe { ioctl(fd, SIOCSHWTSTAMP, &mut ifr) } != 0 {
+        return Err(Error::Errno(Errno::last()));
+    }
+    Ok(())
+}
+
 /// shows supported features name and their index by interface name.
 pub fn get_link_features(if_name: &str) -> Result<Vec<(String, usize)>> {
     let fd = socket(
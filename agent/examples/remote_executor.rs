@@ -39,6 +39,7 @@ fn main() {
         0,
         DEFAULT_TIMEOUT,
         "".to_owned(),
+        Default::default(),
         vec!["127.0.0.1".to_owned()],
         exc.clone(),
         &stats_collector,
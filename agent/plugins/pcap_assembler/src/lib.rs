@@ -22,7 +22,7 @@ use std::thread::JoinHandle;
 use std::time::Duration;
 
 use public::counter::{Counter, RefCountable};
-use public::packet::MiniPacket;
+use public::packet::{MiniPacket, PcapFormat};
 use public::proto::trident::PcapBatch;
 use public::queue::{DebugSender, Receiver};
 use public::sender::{SendMessageType, Sendable};
@@ -59,6 +59,7 @@ impl PcapAssembler {
         _: u64,
         _: u32,
         _: Duration,
+        _: PcapFormat,
         _: DebugSender<BoxedPcapBatch>,
         _: Receiver<MiniPacket>,
         _: Arc<AtomicI64>,
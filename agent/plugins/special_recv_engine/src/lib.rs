@@ -20,7 +20,7 @@ use std::sync::Arc;
 
 use public::counter;
 use public::debug::QueueDebugger;
-use public::error::Result;
+use public::error::{Error, Result};
 use public::packet;
 
 #[derive(Default)]
@@ -52,11 +52,30 @@ impl Libpcap {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DpdkProcessType {
+    // initializes EAL and creates the ring and mempool for the vswitch to attach
+    Primary,
+    // attaches to the ring and mempool created by the vswitch
+    #[default]
+    Secondary,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DpdkOptions {
+    pub process_type: DpdkProcessType,
+    pub eal_args: Vec<String>,
+    pub ring_name: String,
+    pub mempool_name: String,
+}
+
 pub struct Dpdk;
 
 impl Dpdk {
-    pub fn new(_: Option<String>, _: Option<String>, _: usize) -> Self {
-        unimplemented!();
+    pub fn new(_: &DpdkOptions, _: usize) -> Result<Self> {
+        Err(Error::DpdkError(
+            "dpdk capture is not supported in this edition".into(),
+        ))
     }
 
     pub unsafe fn read(&mut self) -> Result<packet::Packet> {
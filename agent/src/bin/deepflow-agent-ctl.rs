@@ -16,7 +16,7 @@
 
 use std::{
     collections::HashSet,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     time::Duration,
 };
 #[cfg(target_os = "linux")]
@@ -29,12 +29,19 @@ use clap::{ArgEnum, Parser, Subcommand};
 use flate2::write::ZlibDecoder;
 
 use deepflow_agent::debug::{
-    Beacon, Client, Message, Module, PolicyMessage, RpcMessage, DEBUG_QUEUE_IDLE_TIMEOUT,
-    DEEPFLOW_AGENT_BEACON,
+    Beacon, Client, Message, Module, MulticastMessage, NeighborMessage, PolicyMessage, RpcMessage,
+    DEBUG_QUEUE_IDLE_TIMEOUT, DEEPFLOW_AGENT_BEACON,
 };
 #[cfg(target_os = "linux")]
 use deepflow_agent::debug::{EbpfMessage, PlatformMessage};
-use public::{consts::DEFAULT_CONTROLLER_PORT, debug::QueueMessage};
+use public::{
+    consts::{DEFAULT_CONTROLLER_PORT, DEFAULT_LOCAL_API_PORT},
+    debug::QueueMessage,
+    proto::agent_debug::{
+        inspector_client::InspectorClient, CounterRequest, FlowRequest, LogLevelRequest,
+        PacketTraceRequest, PluginRequest, PolicyRequest, QueueRequest,
+    },
+};
 
 const ERR_PORT_MSG: &str = "error: The following required arguments were not provided:
     \t--port <PORT> required arguments were not provided";
@@ -68,8 +75,93 @@ enum ControllerCmd {
     #[cfg(target_os = "linux")]
     /// get information about the ebpf
     Ebpf(EbpfCmd),
+    /// get multicast group membership learned from IGMP and MLD reports
+    Multicast,
+    /// get IP to MAC bindings learned from ARP and NDP, and conflicting MAC claims
+    Neighbor,
     /// get information about the deepflow-agent
     List,
+    /// inspect the deepflow-agent with its local debug api
+    Inspect(InspectCmd),
+}
+
+#[derive(Parser)]
+struct InspectCmd {
+    /// local debug api grpc port of deepflow-agent
+    #[clap(long, parse(try_from_str), default_value_t = DEFAULT_LOCAL_API_PORT)]
+    api_port: u16,
+    #[clap(subcommand)]
+    subcmd: InspectSubCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum InspectSubCmd {
+    /// show counters last reported by modules
+    ///
+    /// eg: deepflow-agent-ctl inspect counters --module flow-map
+    Counters {
+        /// only modules with this name prefix
+        #[clap(long, default_value = "")]
+        module: String,
+    },
+    /// show queue depths
+    ///
+    /// eg: deepflow-agent-ctl inspect queues --name tagged-flow
+    Queues {
+        /// only queues with names containing this string
+        #[clap(long, default_value = "")]
+        name: String,
+    },
+    /// dump active flows in flow maps
+    ///
+    /// eg: deepflow-agent-ctl inspect flows --ip 10.1.1.2 --port 80
+    Flows {
+        /// max flows dumped from each flow map, 1000 if 0
+        #[clap(long, parse(try_from_str), default_value_t = 0)]
+        limit: u32,
+        /// only flows with this source or destination ip
+        #[clap(long, default_value = "")]
+        ip: String,
+        /// only flows with this source or destination port
+        #[clap(long, parse(try_from_str), default_value_t = 0)]
+        port: u16,
+    },
+    /// show plugins loaded by flow maps
+    Plugins,
+    /// show acls and policy hits
+    Policies,
+    /// change log levels temporarily, revert if levels is empty
+    ///
+    /// eg: deepflow-agent-ctl inspect log-level --levels flow_generator::protocol_logs::mysql=debug --duration 600
+    LogLevel {
+        /// levels like `info,module::path=debug`
+        #[clap(long, default_value = "")]
+        levels: String,
+        /// seconds before reverting, 300 if 0
+        #[clap(long, parse(try_from_str), default_value_t = 0)]
+        duration: u32,
+    },
+    /// log packets between two endpoints in agent logs, stop tracing if both ips are empty
+    ///
+    /// eg: deepflow-agent-ctl inspect packet-trace --ip-0 10.1.1.2 --ip-1 10.1.1.3 --port-1 3306 --protocol tcp
+    PacketTrace {
+        #[clap(long, default_value = "")]
+        ip_0: String,
+        /// any port if 0
+        #[clap(long, parse(try_from_str), default_value_t = 0)]
+        port_0: u16,
+        #[clap(long, default_value = "")]
+        ip_1: String,
+        /// any port if 0
+        #[clap(long, parse(try_from_str), default_value_t = 0)]
+        port_1: u16,
+        /// tcp, udp, icmp, icmpv6, sctp or protocol number, any if empty
+        #[clap(long, default_value = "")]
+        protocol: String,
+        /// seconds before stopping, 300 if 0
+        #[clap(long, parse(try_from_str), default_value_t = 0)]
+        duration: u32,
+    },
 }
 
 #[derive(Parser)]
@@ -299,6 +391,9 @@ impl Controller {
             ControllerCmd::Policy(c) => self.policy(c),
             #[cfg(target_os = "linux")]
             ControllerCmd::Ebpf(c) => self.ebpf(c),
+            ControllerCmd::Multicast => self.multicast(),
+            ControllerCmd::Neighbor => self.neighbor(),
+            ControllerCmd::Inspect(c) => self.inspect(c),
         }
     }
 
@@ -782,6 +877,238 @@ impl Controller {
             }
         }
     }
+
+    /*
+    $ deepflow-agent-ctl -p 42700 multicast
+    TAP PORT                       GROUP            MEMBER           SOURCE   LAST REPORT
+    LMAC@0@0a:01:01:02             239.1.1.1        10.1.1.2         IGMPv3   1700000000
+    */
+    fn multicast(&self) -> Result<()> {
+        if self.port.is_none() {
+            return Err(anyhow!(ERR_PORT_MSG));
+        }
+        let mut client = self.new_client()?;
+        client.send_to(Message {
+            module: Module::Multicast,
+            msg: MulticastMessage::Groups(None),
+        })?;
+        println!(
+            "{:<30} {:<16} {:<16} {:<8} {}",
+            "TAP PORT", "GROUP", "MEMBER", "SOURCE", "LAST REPORT"
+        );
+        loop {
+            let Ok(res) = client.recv::<MulticastMessage>() else {
+                continue;
+            };
+            match res {
+                MulticastMessage::Groups(Some(entries)) => {
+                    for (tap_port, group, member, source, last_report) in entries {
+                        println!(
+                            "{:<30} {:<16} {:<16} {:<8} {}",
+                            tap_port, group, member, source, last_report
+                        );
+                    }
+                }
+                MulticastMessage::Groups(None) => return Err(anyhow!("no multicast group")),
+                MulticastMessage::Fin => return Ok(()),
+            }
+        }
+    }
+
+    /*
+    $ deepflow-agent-ctl -p 42700 neighbor
+    TAP TYPE IP                                       MAC                LAST SEEN    CONFLICT MAC       CONFLICTS
+    tor      10.1.1.2                                 0a:01:01:02:00:01  1700000000   0a:01:01:02:00:02  1
+    */
+    fn neighbor(&self) -> Result<()> {
+        if self.port.is_none() {
+            return Err(anyhow!(ERR_PORT_MSG));
+        }
+        let mut client = self.new_client()?;
+        client.send_to(Message {
+            module: Module::Neighbor,
+            msg: NeighborMessage::Entries(None),
+        })?;
+        println!(
+            "{:<8} {:<40} {:<18} {:<12} {:<18} {}",
+            "TAP TYPE", "IP", "MAC", "LAST SEEN", "CONFLICT MAC", "CONFLICTS"
+        );
+        loop {
+            let Ok(res) = client.recv::<NeighborMessage>() else {
+                continue;
+            };
+            match res {
+                NeighborMessage::Entries(Some(entries)) => {
+                    for (tap_type, ip, mac, last_seen, conflict_mac, conflict_count) in entries {
+                        println!(
+                            "{:<8} {:<40} {:<18} {:<12} {:<18} {}",
+                            tap_type, ip, mac, last_seen, conflict_mac, conflict_count
+                        );
+                    }
+                }
+                NeighborMessage::Entries(None) => return Err(anyhow!("no neighbor entry")),
+                NeighborMessage::Fin => return Ok(()),
+            }
+        }
+    }
+
+    /*
+    $ deepflow-agent-ctl inspect queues
+    NAME                                               INDEX    PENDING    IN         OUT        OVERWRITTEN
+    1-tagged-flow-to-quadruple-generator               0        0          1024       1024       0
+    */
+    fn inspect(&self, c: InspectCmd) -> Result<()> {
+        let addr = SocketAddr::from((self.addr, c.api_port));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async move {
+            let mut client = InspectorClient::connect(format!("http://{}", addr)).await?;
+            match c.subcmd {
+                InspectSubCmd::Counters { module } => {
+                    let resp = client
+                        .get_counters(CounterRequest { module })
+                        .await?
+                        .into_inner();
+                    for group in resp.groups {
+                        let tags = group
+                            .tags
+                            .iter()
+                            .map(|t| format!("{}={}", t.key, t.value))
+                            .collect::<Vec<_>>();
+                        println!(
+                            "{} {{{}}} at {}",
+                            group.module,
+                            tags.join(", "),
+                            group.timestamp
+                        );
+                        for m in group.metrics {
+                            println!("    {:<40} {}", m.name, m.value);
+                        }
+                    }
+                }
+                InspectSubCmd::Queues { name } => {
+                    let resp = client.get_queues(QueueRequest { name }).await?.into_inner();
+                    println!(
+                        "{:<50} {:<8} {:<10} {:<10} {:<10} {}",
+                        "NAME", "INDEX", "PENDING", "IN", "OUT", "OVERWRITTEN"
+                    );
+                    for q in resp.queues {
+                        println!(
+                            "{:<50} {:<8} {:<10} {:<10} {:<10} {}",
+                            q.name, q.index, q.pending, q.input, q.output, q.overwritten
+                        );
+                    }
+                }
+                InspectSubCmd::Flows { limit, ip, port } => {
+                    let resp = client
+                        .get_flows(FlowRequest {
+                            limit,
+                            ip,
+                            port: port as u32,
+                        })
+                        .await?
+                        .into_inner();
+                    println!(
+                        "{:<4} {:<20} {:<8} {:<46} {:<46} {:<6} {:<24} {:<12} {}",
+                        "MAP",
+                        "FLOW ID",
+                        "TYPE",
+                        "SRC",
+                        "DST",
+                        "PROTO",
+                        "STATE",
+                        "PACKETS",
+                        "BYTES"
+                    );
+                    for f in resp.flows.iter() {
+                        println!(
+                            "{:<4} {:<20} {:<8} {:<46} {:<46} {:<6} {:<24} {:<12} {}",
+                            f.flow_map_id,
+                            f.flow_id,
+                            f.capture_network_type,
+                            SocketAddr::from((f.ip_src.parse::<IpAddr>()?, f.port_src as u16)),
+                            SocketAddr::from((f.ip_dst.parse::<IpAddr>()?, f.port_dst as u16)),
+                            f.protocol,
+                            f.state,
+                            format!("{}/{}", f.packet_tx, f.packet_rx),
+                            format!("{}/{}", f.byte_tx, f.byte_rx),
+                        );
+                    }
+                    println!(
+                        "\n{} flows from {} flow maps",
+                        resp.flows.len(),
+                        resp.flow_maps
+                    );
+                }
+                InspectSubCmd::Plugins => {
+                    let resp = client.get_plugins(PluginRequest {}).await?.into_inner();
+                    for p in resp.plugins {
+                        println!(
+                            "flow map {} {} plugin {} {}",
+                            p.flow_map_id, p.plugin_type, p.name, p.export_func
+                        );
+                        for m in p.metrics {
+                            println!("    {:<40} {}", m.name, m.value);
+                        }
+                    }
+                }
+                InspectSubCmd::Policies => {
+                    let resp = client.get_policies(PolicyRequest {}).await?.into_inner();
+                    println!(
+                        "FirstPath Hits: {}, FastPath Hits: {}",
+                        resp.first_path_hits, resp.fast_path_hits
+                    );
+                    for acl in resp.acls {
+                        println!("{}", acl);
+                    }
+                }
+                InspectSubCmd::LogLevel { levels, duration } => {
+                    let resp = client
+                        .set_log_level(LogLevelRequest {
+                            levels,
+                            duration_secs: duration,
+                        })
+                        .await?
+                        .into_inner();
+                    if resp.expire_at > 0 {
+                        println!("log level: {} until {}", resp.levels, resp.expire_at);
+                    } else {
+                        println!("log level: {}", resp.levels);
+                    }
+                }
+                InspectSubCmd::PacketTrace {
+                    ip_0,
+                    port_0,
+                    ip_1,
+                    port_1,
+                    protocol,
+                    duration,
+                } => {
+                    let resp = client
+                        .trace_packets(PacketTraceRequest {
+                            ip_0,
+                            port_0: port_0 as u32,
+                            ip_1,
+                            port_1: port_1 as u32,
+                            protocol,
+                            duration_secs: duration,
+                        })
+                        .await?
+                        .into_inner();
+                    if resp.filter.is_empty() {
+                        println!("packet trace stopped");
+                    } else {
+                        println!(
+                            "packet trace of {} until {}, packets are logged in agent logs",
+                            resp.filter, resp.expire_at
+                        );
+                    }
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+    }
 }
 
 fn main() {
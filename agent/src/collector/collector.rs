@@ -32,6 +32,7 @@ use log::{debug, info, warn};
 
 use super::{
     consts::{QUEUE_BATCH_SIZE, RCV_TIMEOUT},
+    top_talkers::TopTalkers,
     types::{AppMeterWithFlow, FlowMeterWithFlow, MiniFlow},
     MetricsType, FLOW_METRICS_PEER_DST, FLOW_METRICS_PEER_SRC,
 };
@@ -137,6 +138,8 @@ struct StashKey {
     // request-reponse time span
     time_span: u32,
     biz_type: u8,
+    // edge fast_id only keeps the low 8 bits of CaptureNetworkType in RESERVED of tap port
+    tap_type_high: u8,
 }
 
 impl Default for StashKey {
@@ -150,6 +153,7 @@ impl Default for StashKey {
             endpoint_hash: 0,
             time_span: 0,
             biz_type: 0,
+            tap_type_high: 0,
         }
     }
 }
@@ -323,6 +327,7 @@ impl StashKey {
             endpoint_hash,
             biz_type: tagger.biz_type,
             time_span: tagger.time_span,
+            tap_type_high: (u16::from(tagger.tap_type) >> 8) as u8,
         }
     }
 }
@@ -339,6 +344,8 @@ struct Stash {
     global_thread_id: u8,
     doc_flag: DocumentFlag,
     context: Context,
+    // only for the minute collector
+    top_talkers: Option<TopTalkers>,
 }
 
 impl Stash {
@@ -374,6 +381,7 @@ impl Stash {
             stash_init_capacity,
             doc_flag,
             context: ctx,
+            top_talkers: None,
         }
     }
 
@@ -486,6 +494,10 @@ impl Stash {
             }
         }
 
+        if self.context.metric_type == MetricsType::MINUTE {
+            self.add_top_talker(&acc_flow, config);
+        }
+
         if !acc_flow.is_active_host0 && !acc_flow.is_active_host1 && !config.inactive_ip_enabled {
             self.counter.drop_inactive.fetch_add(1, Ordering::Relaxed);
             return;
@@ -494,6 +506,46 @@ impl Stash {
         self.fill_l4_stats(&acc_flow, &acc_flow.flow.directions, config);
     }
 
+    fn add_top_talker(&mut self, acc_flow: &FlowMeterWithFlow, config: &CollectorConfig) {
+        let conf = &config.top_talkers;
+        if !conf.enabled || conf.top_n == 0 {
+            self.top_talkers = None;
+            return;
+        }
+        let flow = &acc_flow.flow;
+        // Only flow whose signal_source is Packet or XFlow has traffic
+        if flow.signal_source != SignalSource::Packet && flow.signal_source != SignalSource::XFlow {
+            return;
+        }
+        if !matches!(&self.top_talkers, Some(t) if t.top_n() == conf.top_n && t.agent_id() == config.agent_id)
+        {
+            self.top_talkers = Some(TopTalkers::new(conf.top_n, config.agent_id));
+        }
+        if let Some(top_talkers) = self.top_talkers.as_mut() {
+            top_talkers.add(flow, &acc_flow.flow_meter, ignore_server_port(flow, false));
+        }
+    }
+
+    fn flush_top_talkers(&mut self) {
+        let Some(top_talkers) = self.top_talkers.as_mut() else {
+            return;
+        };
+        let mut docs = top_talkers
+            .drain(self.global_thread_id, self.start_time.as_secs() as u32)
+            .into_iter()
+            .map(|doc| BoxedDocument(Box::new(doc)))
+            .collect::<Vec<_>>();
+        if docs.is_empty() {
+            return;
+        }
+        if let Err(e) = self.sender.send_all(&mut docs) {
+            warn!(
+                "{} queue failed to send top talkers, because {:?}",
+                self.context.name, e
+            );
+        }
+    }
+
     fn fill_l4_stats(
         &mut self,
         acc_flow: &FlowMeterWithFlow,
@@ -872,6 +924,7 @@ impl Stash {
     }
 
     fn flush_stats(&mut self) {
+        self.flush_top_talkers();
         self.history_length.rotate_right(1);
         self.history_length[0] = self.inner.len();
 
@@ -923,10 +976,12 @@ impl Stash {
 
 // server_port is ignored when is_active_service and inactive_server_port_enabled is turned off
 // is_active_service and SFlow,NetFlow data, ignoring service port
-// ignore the server for non-TCP/UDP traffic
+// ignore the server for non-TCP/UDP/SCTP traffic
 fn ignore_server_port(flow: &MiniFlow, inactive_server_port_enabled: bool) -> bool {
     (!flow.is_active_service && !inactive_server_port_enabled)
-        || (flow.flow_key.proto != IpProtocol::TCP && flow.flow_key.proto != IpProtocol::UDP)
+        || (flow.flow_key.proto != IpProtocol::TCP
+            && flow.flow_key.proto != IpProtocol::UDP
+            && flow.flow_key.proto != IpProtocol::SCTP)
 }
 
 fn get_single_tagger(
@@ -1562,6 +1617,9 @@ mod tests {
         tagger.tap_type = CaptureNetworkType::Idc(200);
         let key = StashKey::new(&tagger, Ipv4Addr::UNSPECIFIED.into(), None, 0);
         assert_eq!(map.insert(key), true);
+        tagger.tap_type = CaptureNetworkType::Idc(200 + 256);
+        let key = StashKey::new(&tagger, Ipv4Addr::UNSPECIFIED.into(), None, 0);
+        assert_eq!(map.insert(key), true);
         tagger.direction = Direction::ClientToServer;
         let key = StashKey::new(&tagger, Ipv4Addr::UNSPECIFIED.into(), None, 0);
         assert_eq!(map.insert(key), true);
@@ -30,6 +30,7 @@ use rand::prelude::{Rng, SeedableRng, SmallRng};
 
 use super::consts::*;
 
+use super::flow_correlator::FlowCorrelator;
 use crate::collector::types::U16Set;
 use crate::common::Timestamp;
 use crate::common::{
@@ -39,7 +40,10 @@ use crate::common::{
 };
 use crate::config::handler::CollectorAccess;
 use crate::rpc::get_timestamp;
-use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+use crate::utils::{
+    namespace_quota::{Admission, NamespaceLimiter},
+    stats::{Counter, CounterType, CounterValue, RefCountable},
+};
 use public::{
     buffer::BatchedBox,
     queue::{DebugSender, Error, Receiver},
@@ -54,6 +58,7 @@ pub struct FlowAggrCounter {
     drop_before_window: AtomicU64,
     out: AtomicU64,
     drop_in_throttle: AtomicU64,
+    drop_in_namespace_quota: AtomicU64,
     stash_total_len: AtomicU64,
     stash_total_capacity: AtomicU64,
     stash_shrinks: AtomicU64,
@@ -70,6 +75,8 @@ pub struct FlowAggrThread {
 
     running: Arc<AtomicBool>,
     ntp_diff: Arc<AtomicI64>,
+    namespace_limiter: Arc<NamespaceLimiter>,
+    flow_correlator: Arc<FlowCorrelator>,
 
     metrics: Arc<FlowAggrCounter>,
 }
@@ -82,6 +89,8 @@ impl FlowAggrThread {
         config: CollectorAccess,
         delay: Duration,
         ntp_diff: Arc<AtomicI64>,
+        namespace_limiter: Arc<NamespaceLimiter>,
+        flow_correlator: Arc<FlowCorrelator>,
     ) -> (Self, Arc<FlowAggrCounter>) {
         let running = Arc::new(AtomicBool::new(false));
         let metrics = Arc::new(FlowAggrCounter::default());
@@ -95,6 +104,8 @@ impl FlowAggrThread {
                 delay,
                 running,
                 ntp_diff,
+                namespace_limiter,
+                flow_correlator,
                 metrics: metrics.clone(),
             },
             metrics,
@@ -114,6 +125,8 @@ impl FlowAggrThread {
             self.config.clone(),
             self.delay,
             self.ntp_diff.clone(),
+            self.namespace_limiter.clone(),
+            self.flow_correlator.clone(),
             self.metrics.clone(),
         );
         self.thread_handle = Some(
@@ -152,6 +165,7 @@ pub struct FlowAggr {
     flow_stashs: VecDeque<HashMap<u64, Box<TaggedFlow>>>,
     stash_init_capacity: usize,
     slot_count: usize,
+    delay: Duration,
 
     flush_timeout: Duration,
     last_flush_time: Duration,
@@ -160,6 +174,8 @@ pub struct FlowAggr {
     running: Arc<AtomicBool>,
 
     ntp_diff: Arc<AtomicI64>,
+    namespace_limiter: Arc<NamespaceLimiter>,
+    flow_correlator: Arc<FlowCorrelator>,
     metrics: Arc<FlowAggrCounter>,
 }
 
@@ -175,6 +191,8 @@ impl FlowAggr {
         config: CollectorAccess,
         delay: Duration,
         ntp_diff: Arc<AtomicI64>,
+        namespace_limiter: Arc<NamespaceLimiter>,
+        flow_correlator: Arc<FlowCorrelator>,
         metrics: Arc<FlowAggrCounter>,
     ) -> Self {
         let slot_count = TIMESTAMP_SLOT_COUNT + delay.as_secs() as usize;
@@ -194,13 +212,20 @@ impl FlowAggr {
             running,
             metrics,
             ntp_diff,
+            namespace_limiter,
+            flow_correlator,
             slot_count,
+            delay,
         }
     }
 
-    fn minute_merge(&mut self, f: Arc<BatchedBox<TaggedFlow>>) {
+    // Merges the reports of an active flow in the report interval, the flow log is sent when
+    // the flow closes or the interval ends, so the counters of the flow logs are deltas in
+    // the intervals
+    fn interval_merge(&mut self, f: Arc<BatchedBox<TaggedFlow>>, interval: u64) {
         let f = f.as_ref();
-        let flow_time = Timestamp::from_secs(f.flow.start_time_in_minute());
+        // flows are stashed in the slot of the second the interval ends
+        let flow_time = Timestamp::from_secs(f.flow.start_time_in_interval(interval) + interval);
         if flow_time < self.slot_start_time {
             debug!("flow drop before slot start time. flow stat time: {:?}, slot start time is {:?}, delay is {:?}", flow_time, self.slot_start_time, self.slot_start_time - flow_time);
             self.metrics
@@ -209,6 +234,14 @@ impl FlowAggr {
             return;
         }
 
+        // the intervals ended before the tolerable delay are flushed
+        let stat_time = Timestamp::from_secs(f.flow.flow_stat_time.as_secs());
+        if stat_time >= self.slot_start_time + self.delay {
+            let flush_count =
+                (stat_time - self.delay - self.slot_start_time).as_secs() as usize + 1;
+            self.flush_slots(flush_count);
+        }
+
         let mut time_slot = (flow_time - self.slot_start_time).as_secs() as usize;
         if time_slot >= self.slot_count {
             let flush_count = time_slot - self.slot_count + 1;
@@ -228,19 +261,19 @@ impl FlowAggr {
             flow.sequential_merge(&f);
             if flow.flow.close_type != CloseType::ForcedReport {
                 if let Some(closed_flow) = flow_stash.remove(&flow_id) {
-                    self.send_flow(closed_flow);
+                    self.send_flow(closed_flow, interval);
                 }
             }
         } else {
             if f.flow.close_type != CloseType::ForcedReport {
-                self.send_flow(Box::new(f.as_ref().clone()));
+                self.send_flow(Box::new(f.as_ref().clone()), interval);
             } else {
                 flow_stash.insert(f.flow.flow_id, Box::new(f.as_ref().clone()));
             }
         }
     }
 
-    fn send_flow(&mut self, mut f: Box<TaggedFlow>) {
+    fn send_flow(&mut self, mut f: Box<TaggedFlow>, interval: u64) {
         // We use acl_gid to mark which flows are configured with PCAP storage policies.
         // Since acl_gid is used for both PCAP and NPB functions, only the acl_gid used by PCAP is sent here.
         let mut acl_gids = U16Set::new();
@@ -263,12 +296,12 @@ impl FlowAggr {
         f.flow.acl_gids = Vec::from(acl_gids.list());
 
         if !f.flow.is_new_flow {
-            f.flow.start_time = Timestamp::from_secs(f.flow.start_time_in_minute());
+            f.flow.start_time = Timestamp::from_secs(f.flow.start_time_in_interval(interval));
         }
 
         if f.flow.close_type == CloseType::ForcedReport {
             // Align time to seconds
-            f.flow.end_time = Timestamp::from_secs(f.flow.start_time.as_secs() + SECONDS_IN_MINUTE);
+            f.flow.end_time = Timestamp::from_secs(f.flow.start_time.as_secs() + interval);
         }
 
         self.metrics.out.fetch_add(1, Ordering::Relaxed);
@@ -278,20 +311,55 @@ impl FlowAggr {
         self.output.flush_cache_without_throttling(&now);
         if f.flow.hit_pcap_policy() {
             self.output.send_without_throttling(f);
-        } else {
-            if !self.output.send_with_throttling(f) {
+            return;
+        }
+
+        let config = self.config.load();
+        if !self.flow_correlator.correlate(
+            &config.flow_log_deduplication,
+            &mut f.flow,
+            interval,
+            now,
+        ) {
+            return;
+        }
+
+        let (key, peers) = (&f.flow.flow_key, &f.flow.flow_metrics_peers);
+        let admission = self.namespace_limiter.acquire(
+            &config.namespace_quotas,
+            [
+                (peers[0].l3_epc_id, key.ip_src),
+                (peers[1].l3_epc_id, key.ip_dst),
+            ],
+            peers[0].byte_count + peers[1].byte_count,
+            now,
+        );
+        match admission {
+            Admission::Dropped => {
                 self.metrics
-                    .drop_in_throttle
+                    .drop_in_namespace_quota
                     .fetch_add(1, Ordering::Relaxed);
             }
+            // flows of system namespaces are not throttled
+            Admission::Prioritized => self.output.send_without_throttling(f),
+            Admission::Accepted => {
+                if !self.output.send_with_throttling(f) {
+                    self.metrics
+                        .drop_in_throttle
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
         }
     }
 
     fn flush_front_slot_and_rotate(&mut self) {
         let mut flow_stash = self.flow_stashs.pop_front().unwrap();
 
+        let config = self.config.load();
         for (_, flow) in flow_stash.drain() {
-            self.send_flow(flow);
+            let interval =
+                config.l4_log_report_intervals[u16::from(flow.flow.flow_key.tap_type) as usize];
+            self.send_flow(flow, interval);
         }
 
         let stash_cap = self.flow_stashs.capacity();
@@ -351,7 +419,9 @@ impl FlowAggr {
                                 [u16::from(tagged_flow.flow.flow_key.tap_type) as usize]
                             || tagged_flow.flow.need_to_store
                         {
-                            self.minute_merge(tagged_flow);
+                            let interval = config.l4_log_report_intervals
+                                [u16::from(tagged_flow.flow.flow_key.tap_type) as usize];
+                            self.interval_merge(tagged_flow, interval);
                         }
                     }
                     self.calc_stash_counters();
@@ -391,6 +461,11 @@ impl RefCountable for FlowAggrCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.drop_in_throttle.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "drop-in-namespace-quota",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.drop_in_namespace_quota.swap(0, Ordering::Relaxed)),
+            ),
             (
                 "stash-total-len",
                 CounterType::Counted,
@@ -0,0 +1,273 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use crate::common::{
+    flow::{CloseType, Flow, SignalSource},
+    tap_port::TapPort,
+};
+use crate::config::{FlowLogDeduplication, FlowLogDeduplicationMode};
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+use public::enums::CaptureNetworkType;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct FlowCorrelatorCounter {
+    linked: AtomicU64,
+    suppressed: AtomicU64,
+    sessions: AtomicU64,
+}
+
+impl RefCountable for FlowCorrelatorCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "linked",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.linked.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "suppressed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.suppressed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sessions",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.sessions.load(Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// 5-tuple of the session regardless of the direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SessionKey {
+    ends: [(IpAddr, u16); 2],
+    protocol: u8,
+}
+
+impl From<&Flow> for SessionKey {
+    fn from(flow: &Flow) -> Self {
+        let key = &flow.flow_key;
+        let (a, b) = ((key.ip_src, key.port_src), (key.ip_dst, key.port_dst));
+        Self {
+            ends: if a <= b { [a, b] } else { [b, a] },
+            protocol: u8::from(key.proto),
+        }
+    }
+}
+
+// real client and server behind NAT, learned from TOA or VIP
+type NatInfo = [(u8, IpAddr, u16); 2];
+
+fn nat_info(flow: &Flow) -> NatInfo {
+    let peers = &flow.flow_metrics_peers;
+    [
+        (
+            peers[0].nat_source,
+            peers[0].nat_real_ip,
+            peers[0].nat_real_port,
+        ),
+        (
+            peers[1].nat_source,
+            peers[1].nat_real_ip,
+            peers[1].nat_real_port,
+        ),
+    ]
+}
+
+struct Session {
+    // the tap reporting the session first
+    tap_type: CaptureNetworkType,
+    tap_port: TapPort,
+    flow_id: u64,
+    nat_info: NatInfo,
+    expire_at: Duration,
+}
+
+#[derive(Default)]
+struct SessionTable {
+    sessions: HashMap<SessionKey, Session>,
+    next_cleanup: Duration,
+}
+
+// Correlates the flow logs of a session captured at more than one tap of the agent, e.g. the
+// pod veth and the node NIC, which are reported by different flow maps and collector threads.
+//
+// The tap reporting the session first owns it, flow logs of the other taps are linked to the
+// owner or suppressed. Copies with different NAT information are not duplicates and are sent as
+// they are, sessions with NAT applied between the taps do not match in the first place because
+// of the different 5-tuples.
+#[derive(Default)]
+pub struct FlowCorrelator {
+    table: Mutex<SessionTable>,
+    counter: Arc<FlowCorrelatorCounter>,
+}
+
+impl FlowCorrelator {
+    pub fn get_counter_handle(&self) -> Arc<FlowCorrelatorCounter> {
+        self.counter.clone()
+    }
+
+    // Returns false if the flow log is a duplicate to be suppressed, the flow is marked with
+    // the flow_id of the owner if the duplicate is linked.
+    //
+    // interval is the report interval of the active flow, the session is kept until the next
+    // report of the owner is due.
+    pub fn correlate(
+        &self,
+        config: &FlowLogDeduplication,
+        flow: &mut Flow,
+        interval: u64,
+        now: Duration,
+    ) -> bool {
+        if config.mode == FlowLogDeduplicationMode::Disabled
+            || flow.signal_source != SignalSource::Packet
+        {
+            return true;
+        }
+
+        let key = SessionKey::from(&*flow);
+        let mut expire_at = now + config.timeout;
+        if flow.close_type == CloseType::ForcedReport {
+            expire_at += Duration::from_secs(interval);
+        }
+
+        let mut table = self.table.lock().unwrap();
+        if now >= table.next_cleanup {
+            table.sessions.retain(|_, s| s.expire_at > now);
+            table.next_cleanup = now + CLEANUP_INTERVAL;
+        }
+        if !table.sessions.get(&key).is_some_and(|s| s.expire_at > now) {
+            table.sessions.insert(
+                key,
+                Session {
+                    tap_type: flow.flow_key.tap_type,
+                    tap_port: flow.flow_key.tap_port,
+                    flow_id: flow.flow_id,
+                    nat_info: nat_info(flow),
+                    expire_at,
+                },
+            );
+            self.counter
+                .sessions
+                .store(table.sessions.len() as u64, Ordering::Relaxed);
+            return true;
+        }
+        let session = table.sessions.get_mut(&key).unwrap();
+        if session.tap_type == flow.flow_key.tap_type && session.tap_port == flow.flow_key.tap_port
+        {
+            session.flow_id = flow.flow_id;
+            session.expire_at = expire_at;
+            return true;
+        }
+        if session.nat_info != nat_info(flow) {
+            return true;
+        }
+
+        match config.mode {
+            FlowLogDeduplicationMode::Link => {
+                flow.duplicate_of_flow_id = session.flow_id;
+                self.counter.linked.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            _ => {
+                self.counter.suppressed.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::flow::FlowKey;
+
+    fn flow(tap_port: u64, flow_id: u64, reversed: bool) -> Flow {
+        let (client, server) = (
+            ("10.0.0.1".parse().unwrap(), 34567),
+            ("10.0.0.2".parse().unwrap(), 80),
+        );
+        let ((ip_src, port_src), (ip_dst, port_dst)) = if reversed {
+            (server, client)
+        } else {
+            (client, server)
+        };
+        Flow {
+            flow_key: FlowKey {
+                tap_type: CaptureNetworkType::Cloud,
+                tap_port: TapPort(tap_port),
+                ip_src,
+                ip_dst,
+                port_src,
+                port_dst,
+                ..Default::default()
+            },
+            flow_id,
+            signal_source: SignalSource::Packet,
+            close_type: CloseType::TcpFin,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn correlate_duplicates() {
+        let correlator = FlowCorrelator::default();
+        let mut config = FlowLogDeduplication {
+            mode: FlowLogDeduplicationMode::Link,
+            timeout: Duration::from_secs(10),
+        };
+        let now = Duration::from_secs(100);
+
+        let mut owner = flow(1, 1000, false);
+        assert!(correlator.correlate(&config, &mut owner, 60, now));
+        assert_eq!(owner.duplicate_of_flow_id, 0);
+
+        // the same session at another tap, regardless of the direction
+        let mut duplicate = flow(2, 2000, true);
+        assert!(correlator.correlate(&config, &mut duplicate, 60, now));
+        assert_eq!(duplicate.duplicate_of_flow_id, 1000);
+
+        // copies with different NAT information are not duplicates
+        let mut nat = flow(3, 3000, false);
+        nat.flow_metrics_peers[0].nat_source = TapPort::NAT_SOURCE_TOA;
+        assert!(correlator.correlate(&config, &mut nat, 60, now));
+        assert_eq!(nat.duplicate_of_flow_id, 0);
+
+        config.mode = FlowLogDeduplicationMode::Suppress;
+        assert!(!correlator.correlate(&config, &mut flow(2, 2000, false), 60, now));
+        assert!(correlator.correlate(&config, &mut flow(1, 1000, false), 60, now));
+
+        // the session expires after the timeout, and is owned by the next tap reporting it
+        let now = now + Duration::from_secs(10);
+        assert!(correlator.correlate(&config, &mut flow(2, 2000, false), 60, now));
+        assert!(!correlator.correlate(&config, &mut flow(1, 1000, false), 60, now));
+
+        config.mode = FlowLogDeduplicationMode::Disabled;
+        assert!(correlator.correlate(&config, &mut flow(1, 1000, false), 60, now));
+    }
+}
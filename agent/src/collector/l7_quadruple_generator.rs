@@ -710,6 +710,8 @@ impl L7QuadrupleGenerator {
                     server_error: stats.err_server_count,
                     timeout: stats.err_timeout,
                 },
+                payload_size: stats.payload_size,
+                latency_sketch: stats.rrt_sketch,
             },
         }
     }
@@ -17,8 +17,10 @@
 mod collector;
 mod consts;
 pub(crate) mod flow_aggr;
+pub(crate) mod flow_correlator;
 pub(crate) mod l7_quadruple_generator;
 pub(crate) mod quadruple_generator;
+mod top_talkers;
 pub(crate) mod types;
 
 use std::net::IpAddr;
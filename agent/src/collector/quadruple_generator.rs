@@ -417,6 +417,7 @@ impl SubQuadGen {
 
             if acc_flow.flow.flow_key.proto == IpProtocol::TCP
                 || acc_flow.flow.flow_key.proto == IpProtocol::UDP
+                || acc_flow.flow.flow_key.proto == IpProtocol::SCTP
             {
                 acc_flow.flow_meter.flow_load.load =
                     connection.get_concurrent(acc_flow.time_in_second.into(), &mut acc_flow.key);
@@ -522,9 +523,10 @@ impl SubQuadGen {
         let stash = &mut self.stashs[slot];
         let connection = &mut self.connections[slot];
 
-        // Only count the number of concurrent connections between TCP and UDP with the signal_source of packet
+        // Only count the number of concurrent connections of TCP, UDP and SCTP with the signal_source of packet
         if (tagged_flow.flow.flow_key.proto == IpProtocol::TCP
-            || tagged_flow.flow.flow_key.proto == IpProtocol::UDP)
+            || tagged_flow.flow.flow_key.proto == IpProtocol::UDP
+            || tagged_flow.flow.flow_key.proto == IpProtocol::SCTP)
             && tagged_flow.flow.signal_source == SignalSource::Packet
         {
             if tagged_flow.flow.is_new_flow
@@ -937,21 +939,27 @@ impl QuadrupleGenerator {
         if tagged_flow.flow.signal_source == SignalSource::Packet
             || tagged_flow.flow.signal_source == SignalSource::XFlow
         {
+            // each packet of a sampled flow stands for `sampling_rate` packets
+            let rate = tagged_flow.flow.sampling_rate.max(1) as u64;
             flow_meter.traffic = Traffic {
-                packet_tx: src.packet_count,
-                packet_rx: dst.packet_count,
-                byte_tx: src.byte_count,
-                byte_rx: dst.byte_count,
-                l3_byte_tx: src.l3_byte_count,
-                l3_byte_rx: dst.l3_byte_count,
-                l4_byte_tx: src.l4_byte_count,
-                l4_byte_rx: dst.l4_byte_count,
+                packet_tx: src.packet_count * rate,
+                packet_rx: dst.packet_count * rate,
+                byte_tx: src.byte_count * rate,
+                byte_rx: dst.byte_count * rate,
+                l3_byte_tx: src.l3_byte_count * rate,
+                l3_byte_rx: dst.l3_byte_count * rate,
+                l4_byte_tx: src.l4_byte_count * rate,
+                l4_byte_rx: dst.l4_byte_count * rate,
                 new_flow: tagged_flow.flow.is_new_flow as u64,
                 closed_flow: (tagged_flow.flow.close_type != CloseType::ForcedReport) as u64,
                 l7_request: 0,
                 l7_response: 0,
-                syn: perf_stats.map(|s| s.tcp.syn_count).unwrap_or_default(),
-                synack: perf_stats.map(|s| s.tcp.synack_count).unwrap_or_default(),
+                syn: perf_stats
+                    .map(|s| s.tcp.syn_count.saturating_mul(rate as u32))
+                    .unwrap_or_default(),
+                synack: perf_stats
+                    .map(|s| s.tcp.synack_count.saturating_mul(rate as u32))
+                    .unwrap_or_default(),
                 direction_score: tagged_flow.flow.direction_score,
             };
 
@@ -1094,6 +1102,8 @@ impl QuadrupleGenerator {
         key[OFFSET_TAP_PORT + 5] = u16::from(tagged_flow.flow.flow_key.tap_type) as u8;
         key[OFFSET_TAP_PORT + 6] = tunnel_type as u8;
         key[OFFSET_TAP_PORT + 7] = tagged_flow.flow.tap_side as u8;
+        // the high byte of tap_type, which is larger than 255 in large IDC deployments
+        key[OFFSET_RESERVED] = (u16::from(tagged_flow.flow.flow_key.tap_type) >> 8) as u8;
         key[OFFSET_PROTOCOL] = u8::from(tagged_flow.flow.flow_key.proto);
         // 对于sflow, netflow流量，仅当确定目的IP是服务端时，将目的端口作为查询key
         if tagged_flow.flow.signal_source == SignalSource::Packet
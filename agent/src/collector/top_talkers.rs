@@ -0,0 +1,314 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+    net::IpAddr,
+};
+
+use super::types::MiniFlow;
+use crate::{
+    common::flow::SignalSource,
+    metric::{
+        document::{Code, Document, DocumentFlag, Tagger},
+        meter::{FlowMeter, Meter, TalkerMeter, TalkerType},
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Counter {
+    bytes: u64,
+    // packets are only counted since the key is monitored
+    packets: u64,
+    bytes_error: u64,
+}
+
+// Space-saving algorithm (Metwally et al.), which finds the heavy hitters of a stream with a fixed
+// number of counters. A new key takes over the smallest counter when all are used, so the bytes of
+// a key is overestimated by at most the bytes of the counter it took over.
+struct SpaceSaving<K> {
+    capacity: usize,
+    counters: HashMap<K, Counter>,
+    // counters ordered by bytes, the first one is taken over by new keys
+    order: BTreeSet<(u64, K)>,
+}
+
+impl<K: Copy + Hash + Ord> SpaceSaving<K> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            counters: HashMap::with_capacity(capacity),
+            order: BTreeSet::new(),
+        }
+    }
+
+    fn add(&mut self, key: K, bytes: u64, packets: u64) {
+        if let Some(c) = self.counters.get_mut(&key) {
+            self.order.remove(&(c.bytes, key));
+            c.bytes += bytes;
+            c.packets += packets;
+            self.order.insert((c.bytes, key));
+            return;
+        }
+        let mut counter = Counter {
+            bytes,
+            packets,
+            bytes_error: 0,
+        };
+        if self.counters.len() >= self.capacity {
+            if let Some((min_bytes, min_key)) = self.order.pop_first() {
+                self.counters.remove(&min_key);
+                counter.bytes += min_bytes;
+                counter.bytes_error = min_bytes;
+            }
+        }
+        self.order.insert((counter.bytes, key));
+        self.counters.insert(key, counter);
+    }
+
+    fn top(&self, n: usize) -> impl Iterator<Item = (K, Counter)> + '_ {
+        self.order
+            .iter()
+            .rev()
+            .take(n)
+            .map(|(_, k)| (*k, self.counters[k]))
+    }
+
+    fn clear(&mut self) {
+        self.counters.clear();
+        self.order.clear();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct HostKey {
+    ip: IpAddr,
+    l3_epc_id: i16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct ServiceKey {
+    ip: IpAddr,
+    l3_epc_id: i16,
+    protocol: u8,
+    port: u16,
+}
+
+// Top source, destination and service talkers by bytes of a minute, reported as talker documents
+// so that the overview of the traffic needs no aggregation of all flows.
+//
+// Each collector thread reports its own top talkers, the same talker in the documents of different
+// threads should be summed up.
+pub struct TopTalkers {
+    top_n: usize,
+    agent_id: u16,
+    sources: SpaceSaving<HostKey>,
+    destinations: SpaceSaving<HostKey>,
+    services: SpaceSaving<ServiceKey>,
+}
+
+impl TopTalkers {
+    // counters kept for each top talker, more counters make the estimation more accurate
+    const COUNTERS_PER_TALKER: usize = 10;
+
+    pub fn new(top_n: usize, agent_id: u16) -> Self {
+        let capacity = top_n * Self::COUNTERS_PER_TALKER;
+        Self {
+            top_n,
+            agent_id,
+            sources: SpaceSaving::new(capacity),
+            destinations: SpaceSaving::new(capacity),
+            services: SpaceSaving::new(capacity),
+        }
+    }
+
+    pub fn top_n(&self) -> usize {
+        self.top_n
+    }
+
+    pub fn agent_id(&self) -> u16 {
+        self.agent_id
+    }
+
+    // flow_key.ip_src is the client, and the service is ignored if it is not active
+    pub fn add(&mut self, flow: &MiniFlow, flow_meter: &FlowMeter, ignore_server_port: bool) {
+        let traffic = &flow_meter.traffic;
+        let bytes = traffic.byte_tx + traffic.byte_rx;
+        let packets = traffic.packet_tx + traffic.packet_rx;
+        if bytes == 0 {
+            return;
+        }
+        let key = &flow.flow_key;
+        let (src_epc, dst_epc) = (
+            flow.peers[0].l3_epc_id as i16,
+            flow.peers[1].l3_epc_id as i16,
+        );
+        self.sources.add(
+            HostKey {
+                ip: key.ip_src,
+                l3_epc_id: src_epc,
+            },
+            bytes,
+            packets,
+        );
+        self.destinations.add(
+            HostKey {
+                ip: key.ip_dst,
+                l3_epc_id: dst_epc,
+            },
+            bytes,
+            packets,
+        );
+        if !ignore_server_port {
+            self.services.add(
+                ServiceKey {
+                    ip: key.ip_dst,
+                    l3_epc_id: dst_epc,
+                    protocol: u8::from(key.proto),
+                    port: key.port_dst,
+                },
+                bytes,
+                packets,
+            );
+        }
+    }
+
+    fn to_document(
+        talker_type: TalkerType,
+        rank: usize,
+        counter: Counter,
+        mut tagger: Tagger,
+        timestamp: u32,
+    ) -> Document {
+        tagger.signal_source = SignalSource::Packet;
+        let mut doc = Document::new(Meter::Talker(TalkerMeter {
+            talker_type,
+            rank: rank as u32 + 1,
+            bytes: counter.bytes,
+            packets: counter.packets,
+            bytes_error: counter.bytes_error,
+        }));
+        doc.timestamp = timestamp;
+        doc.tagger = tagger;
+        doc.flags = DocumentFlag::NONE;
+        doc
+    }
+
+    // Reports the top talkers of the minute and starts counting the next minute
+    pub fn drain(&mut self, global_thread_id: u8, timestamp: u32) -> Vec<Document> {
+        let agent_id = self.agent_id;
+        // hosts and services share the code so that the server stores them in one table,
+        // the protocol and server port of hosts are 0
+        let host_tagger = |key: HostKey| Tagger {
+            code: Code::IP | Code::L3_EPC_ID | Code::PROTOCOL | Code::SERVER_PORT | Code::VTAP_ID,
+            ip: key.ip,
+            is_ipv6: key.ip.is_ipv6(),
+            l3_epc_id: key.l3_epc_id,
+            global_thread_id,
+            agent_id,
+            ..Default::default()
+        };
+        let mut docs = vec![];
+        for (talker_type, talkers) in [
+            (TalkerType::Source, &self.sources),
+            (TalkerType::Destination, &self.destinations),
+        ] {
+            for (rank, (key, counter)) in talkers.top(self.top_n).enumerate() {
+                docs.push(Self::to_document(
+                    talker_type,
+                    rank,
+                    counter,
+                    host_tagger(key),
+                    timestamp,
+                ));
+            }
+        }
+        for (rank, (key, counter)) in self.services.top(self.top_n).enumerate() {
+            let tagger = Tagger {
+                protocol: key.protocol.into(),
+                server_port: key.port,
+                ..host_tagger(HostKey {
+                    ip: key.ip,
+                    l3_epc_id: key.l3_epc_id,
+                })
+            };
+            docs.push(Self::to_document(
+                TalkerType::Service,
+                rank,
+                counter,
+                tagger,
+                timestamp,
+            ));
+        }
+
+        self.sources.clear();
+        self.destinations.clear();
+        self.services.clear();
+        docs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn space_saving_exact() {
+        let mut ss = SpaceSaving::new(4);
+        for (key, bytes) in [(1, 100), (2, 300), (3, 200), (1, 250)] {
+            ss.add(key, bytes, 1);
+        }
+        let top = ss.top(2).collect::<Vec<_>>();
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].0, 1);
+        assert_eq!(
+            top[0].1,
+            Counter {
+                bytes: 350,
+                packets: 2,
+                bytes_error: 0
+            }
+        );
+        assert_eq!(top[1].0, 2);
+    }
+
+    #[test]
+    fn space_saving_heavy_hitters() {
+        let mut ss = SpaceSaving::new(10);
+        // 1000 keys of 10 bytes with 3 heavy hitters among them
+        for i in 0..1000u32 {
+            ss.add(i + 100, 10, 1);
+            if i % 10 == 0 {
+                ss.add(1, 1000, 1);
+                ss.add(2, 500, 1);
+                ss.add(3, 200, 1);
+            }
+        }
+        let top = ss.top(3).collect::<Vec<_>>();
+        assert_eq!(
+            top.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        for (key, real) in [(1, 100000), (2, 50000), (3, 20000)] {
+            let (_, c) = top.iter().find(|(k, _)| *k == key).unwrap();
+            assert!(c.bytes >= real);
+            assert!(c.bytes - c.bytes_error <= real);
+        }
+        ss.clear();
+        assert_eq!(ss.top(3).count(), 0);
+    }
+}
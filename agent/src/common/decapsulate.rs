@@ -39,6 +39,10 @@ pub enum TunnelType {
     Geneve = DecapType::Geneve as u8,
     Erspan = DecapType::Geneve as u8 + 1,
     Teb = DecapType::Geneve as u8 + 2,
+    GtpU = DecapType::Geneve as u8 + 3,
+    VxlanGpe = DecapType::Geneve as u8 + 4,
+    Mpls = DecapType::Geneve as u8 + 5,
+    Pppoe = DecapType::Geneve as u8 + 6,
 }
 
 impl From<DecapType> for TunnelType {
@@ -61,6 +65,10 @@ impl From<i32> for TunnelType {
             2 => TunnelType::Ipip,
             3 => TunnelType::TencentGre,
             4 => TunnelType::Geneve,
+            7 => TunnelType::GtpU,
+            8 => TunnelType::VxlanGpe,
+            9 => TunnelType::Mpls,
+            10 => TunnelType::Pppoe,
             _ => TunnelType::None,
         }
     }
@@ -76,6 +84,10 @@ impl fmt::Display for TunnelType {
             TunnelType::Geneve => write!(f, "Geneve"),
             TunnelType::Erspan => write!(f, "ERSPAN"),
             TunnelType::Teb => write!(f, "TEB"),
+            TunnelType::GtpU => write!(f, "GTP-U"),
+            TunnelType::VxlanGpe => write!(f, "VXLAN-GPE"),
+            TunnelType::Mpls => write!(f, "MPLS"),
+            TunnelType::Pppoe => write!(f, "PPPoE"),
         }
     }
 }
@@ -173,6 +185,22 @@ impl fmt::Display for TunnelTypeBitmap {
         }
         if self.has(TunnelType::Teb) {
             write!(f, "{}{}", separation, TunnelType::Teb)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::GtpU) {
+            write!(f, "{}{}", separation, TunnelType::GtpU)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::VxlanGpe) {
+            write!(f, "{}{}", separation, TunnelType::VxlanGpe)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::Mpls) {
+            write!(f, "{}{}", separation, TunnelType::Mpls)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::Pppoe) {
+            write!(f, "{}{}", separation, TunnelType::Pppoe)?;
         }
         write!(f, "")
     }
@@ -187,10 +215,89 @@ const LE_VXLAN_PROTO_UDP_DPORT2: u16 = 0x1821; // 0x2118(8472)'s LittleEndian
 const LE_VXLAN_PROTO_UDP_DPORT3: u16 = 0x801A; // 0x1A80(6784)'s LittleEndian
 const LE_TRANSPARENT_ETHERNET_BRIDGEING: u16 = 0x5865; // 0x6558(25944)'s LittleEndian
 const LE_GENEVE_PROTO_UDP_DPORT: u16 = 0xc117; // 0x17c1(6081)'s LittleEndian
+const LE_GTPU_PROTO_UDP_DPORT: u16 = 0x6808; // 0x0868(2152)'s LittleEndian
+const LE_VXLAN_GPE_PROTO_UDP_DPORT: u16 = 0xB612; // 0x12B6(4790)'s LittleEndian
 
 const VXLAN_FLAGS: u8 = 8;
 const TUNNEL_TIER_LIMIT: u8 = 2;
 
+// Geneve option TLVs of AWS Gateway Load Balancer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GeneveOptions {
+    pub gwlbe_id: u64,
+    pub attachment_id: u64,
+    pub flow_cookie: u32,
+}
+
+impl GeneveOptions {
+    fn decapsulate(&mut self, options: &[u8]) {
+        let mut offset = 0;
+        while offset + GENEVE_OPTION_HEADER_SIZE <= options.len() {
+            let class = bytes::read_u16_be(&options[offset + GENEVE_OPTION_CLASS_OFFSET..]);
+            let option_type = options[offset + GENEVE_OPTION_TYPE_OFFSET];
+            let data_length = ((options[offset + GENEVE_OPTION_LENGTH_OFFSET]
+                & GENEVE_OPTION_DATA_LENGTH_MASK) as usize)
+                << 2;
+            let data_offset = offset + GENEVE_OPTION_HEADER_SIZE;
+            if data_offset + data_length > options.len() {
+                return;
+            }
+            let data = &options[data_offset..data_offset + data_length];
+            if class == GENEVE_OPTION_CLASS_AWS {
+                // 最高位为critical标志
+                match option_type & 0x7f {
+                    GENEVE_OPTION_TYPE_GWLBE_ID if data.len() >= 8 => {
+                        self.gwlbe_id = bytes::read_u64_be(data)
+                    }
+                    GENEVE_OPTION_TYPE_ATTACHMENT_ID if data.len() >= 8 => {
+                        self.attachment_id = bytes::read_u64_be(data)
+                    }
+                    GENEVE_OPTION_TYPE_FLOW_COOKIE if data.len() >= 4 => {
+                        self.flow_cookie = bytes::read_u32_be(data)
+                    }
+                    _ => (),
+                }
+            }
+            offset = data_offset + data_length;
+        }
+    }
+}
+
+// GRE key/sequence and ERSPAN header fields, used to tell apart mirror sources
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ErspanInfo {
+    pub version: u8, // 1, 2 or 3 for ERSPAN type I, II or III
+    pub session_id: u16,
+    // ERSPAN III only
+    pub hardware_id: u8,
+    pub timestamp: u32,
+    pub timestamp_granularity: u8,
+    pub gre_key: Option<u32>,
+    pub gre_seq: Option<u32>,
+}
+
+// NSH service path header carried by VXLAN-GPE
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NshInfo {
+    pub spi: u32,
+    pub si: u8,
+}
+
+pub const MPLS_LABEL_STACK_LIMIT: usize = 4;
+
+// MPLS label stack from top to bottom, only the first MPLS_LABEL_STACK_LIMIT labels are kept
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MplsInfo {
+    pub labels: [u32; MPLS_LABEL_STACK_LIMIT],
+    pub depth: u8,
+}
+
+impl MplsInfo {
+    pub fn labels(&self) -> &[u32] {
+        &self.labels[..(self.depth as usize).min(MPLS_LABEL_STACK_LIMIT)]
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TunnelInfo {
     pub src: Ipv4Addr,
@@ -201,6 +308,10 @@ pub struct TunnelInfo {
     pub tunnel_type: TunnelType,
     pub tier: u8,
     pub is_ipv6: bool,
+    pub geneve_options: GeneveOptions,
+    pub erspan: ErspanInfo,
+    pub nsh: NshInfo,
+    pub mpls: MplsInfo,
 }
 
 impl Default for TunnelInfo {
@@ -214,6 +325,10 @@ impl Default for TunnelInfo {
             tunnel_type: TunnelType::default(),
             tier: 0,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         }
     }
 }
@@ -240,7 +355,7 @@ impl TunnelInfo {
 
     pub fn decapsulate_udp(
         &mut self,
-        packet: &[u8],
+        packet: &mut [u8],
         l2_len: usize,
         tunnel_types: &TunnelTypeBitmap,
     ) -> usize {
@@ -259,6 +374,12 @@ impl TunnelInfo {
             LE_GENEVE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Geneve) => {
                 self.decapsulate_geneve(packet, l2_len)
             }
+            LE_GTPU_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::GtpU) => {
+                self.decapsulate_gtpu(packet, l2_len, false)
+            }
+            LE_VXLAN_GPE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::VxlanGpe) => {
+                self.decapsulate_vxlan_gpe(packet, l2_len, false)
+            }
             _ => 0,
         }
     }
@@ -301,6 +422,24 @@ impl TunnelInfo {
         size
     }
 
+    // return (key, sequence) of GRE header
+    fn decapsulate_gre_options(gre_header: &[u8], flags: u16) -> (Option<u32>, Option<u32>) {
+        let mut offset = GRE_HEADER_SIZE_DECAP;
+        if flags & GRE_FLAGS_CSUM_MASK != 0 {
+            offset += GRE_CSUM_LEN;
+        }
+        let mut key = None;
+        if flags & GRE_FLAGS_KEY_MASK != 0 {
+            key = Some(bytes::read_u32_be(&gre_header[offset..]));
+            offset += GRE_KEY_LEN;
+        }
+        let mut seq = None;
+        if flags & GRE_FLAGS_SEQ_MASK != 0 {
+            seq = Some(bytes::read_u32_be(&gre_header[offset..]));
+        }
+        (key, seq)
+    }
+
     pub fn decapsulate_erspan(
         &mut self,
         packet: &[u8],
@@ -318,6 +457,10 @@ impl TunnelInfo {
                     self.decapsulate_addr(l3_packet);
                     self.decapsulate_mac(packet);
                     self.tunnel_type = TunnelType::Erspan;
+                    self.erspan = ErspanInfo {
+                        version: 1,
+                        ..Default::default()
+                    };
                 }
                 self.tier += 1;
                 ip_header_size + GRE_HEADER_SIZE_DECAP + ERSPAN_I_HEADER_SIZE
@@ -333,6 +476,15 @@ impl TunnelInfo {
                     self.id = bytes::read_u32_be(
                         &l3_packet[ip_header_size + gre_header_size + ERSPAN_ID_OFFSET..],
                     ) & 0x3ff;
+                    let (gre_key, gre_seq) =
+                        Self::decapsulate_gre_options(&l3_packet[ip_header_size..], flags);
+                    self.erspan = ErspanInfo {
+                        version: 2,
+                        session_id: self.id as u16,
+                        gre_key,
+                        gre_seq,
+                        ..Default::default()
+                    };
                 }
                 self.tier += 1;
                 ip_header_size + gre_header_size + ERSPAN_II_HEADER_SIZE
@@ -340,19 +492,35 @@ impl TunnelInfo {
             LE_ERSPAN_PROTO_TYPE_III => {
                 let gre_header_size =
                     GRE_HEADER_SIZE_DECAP + TunnelInfo::calc_gre_option_size(flags);
+                let erspan_header = &l3_packet[ip_header_size + gre_header_size..];
+                if erspan_header.len() < ERSPAN_III_HEADER_SIZE {
+                    return 0;
+                }
                 // 仅保存最外层的隧道信息
                 if self.tier == 0 {
                     self.decapsulate_addr(l3_packet);
                     self.decapsulate_mac(packet);
                     self.tunnel_type = TunnelType::Erspan;
-                    self.id = bytes::read_u32_be(
-                        &l3_packet[ip_header_size + gre_header_size + ERSPAN_ID_OFFSET..],
-                    ) & 0x3ff;
+                    self.id = bytes::read_u32_be(&erspan_header[ERSPAN_ID_OFFSET..]) & 0x3ff;
+                    let (gre_key, gre_seq) =
+                        Self::decapsulate_gre_options(&l3_packet[ip_header_size..], flags);
+                    let hardware_id =
+                        (bytes::read_u16_be(&erspan_header[ERSPAN_III_HW_ID_OFFSET..]) >> 4) & 0x3f;
+                    self.erspan = ErspanInfo {
+                        version: 3,
+                        session_id: self.id as u16,
+                        hardware_id: hardware_id as u8,
+                        timestamp: bytes::read_u32_be(
+                            &erspan_header[ERSPAN_III_TIMESTAMP_OFFSET..],
+                        ),
+                        timestamp_granularity: (erspan_header[ERSPAN_III_FLAGS_OFFSET] >> 1) & 0x3,
+                        gre_key,
+                        gre_seq,
+                    };
                 }
                 self.tier += 1;
 
-                let flag =
-                    l3_packet[ip_header_size + gre_header_size + ERSPAN_III_FLAGS_OFFSET] & 0x1;
+                let flag = erspan_header[ERSPAN_III_FLAGS_OFFSET] & 0x1;
                 if flag == 0 {
                     return ip_header_size + gre_header_size + ERSPAN_III_HEADER_SIZE;
                 }
@@ -507,6 +675,8 @@ impl TunnelInfo {
             self.decapsulate_mac(packet);
             self.tunnel_type = TunnelType::Geneve;
             self.id = tunnel_id;
+            self.geneve_options
+                .decapsulate(&l4_payload[GENEVE_HEADER_SIZE..geneve_header_size]);
         }
         self.tier += 1;
 
@@ -577,6 +747,203 @@ impl TunnelInfo {
         )
     }
 
+    // return (TEID, header size), header size is 0 if the payload is not a G-PDU
+    fn decapsulate_gtpu_header(l4_payload: &[u8]) -> (u32, usize) {
+        if l4_payload.len() < GTPU_HEADER_SIZE {
+            return (0, 0);
+        }
+
+        let flags = l4_payload[GTPU_FLAGS_OFFSET];
+        // GTPv1 with PT set, only G-PDU carries user traffic
+        if flags >> GTPU_VERSION_SHIFT != 1
+            || flags & GTPU_FLAGS_PT_MASK == 0
+            || l4_payload[GTPU_MSG_TYPE_OFFSET] != GTPU_MSG_TYPE_GPDU
+        {
+            return (0, 0);
+        }
+        let teid = bytes::read_u32_be(&l4_payload[GTPU_TEID_OFFSET..]);
+        if flags & GTPU_FLAGS_OPTIONAL_MASK == 0 {
+            return (teid, GTPU_HEADER_SIZE);
+        }
+
+        let mut header_size = GTPU_HEADER_SIZE + GTPU_OPTIONAL_HEADER_SIZE;
+        if l4_payload.len() < header_size {
+            return (0, 0);
+        }
+        if flags & GTPU_FLAGS_EXT_MASK == 0 {
+            return (teid, header_size);
+        }
+        // 扩展头长度以4字节为单位，最后一个字节为下一个扩展头类型
+        let mut next_ext_type = l4_payload[GTPU_NEXT_EXT_TYPE_OFFSET];
+        while next_ext_type != 0 {
+            if header_size >= l4_payload.len() {
+                return (0, 0);
+            }
+            let ext_size = (l4_payload[header_size] as usize) << 2;
+            if ext_size == 0 || header_size + ext_size > l4_payload.len() {
+                return (0, 0);
+            }
+            next_ext_type = l4_payload[header_size + ext_size - 1];
+            header_size += ext_size;
+        }
+
+        (teid, header_size)
+    }
+
+    pub fn decapsulate_gtpu(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        underlay_ipv6: bool,
+    ) -> usize {
+        let underlay_ip_header_size = if underlay_ipv6 {
+            IPV6_HEADER_SIZE
+        } else {
+            IPV4_HEADER_SIZE
+        };
+        let l3_packet = &packet[l2_len..];
+        if l3_packet.len() < underlay_ip_header_size + UDP_HEADER_SIZE + GTPU_HEADER_SIZE {
+            return 0;
+        }
+
+        let l4_payload = &l3_packet[underlay_ip_header_size + UDP_HEADER_SIZE..];
+        let (teid, gtpu_header_size) = Self::decapsulate_gtpu_header(l4_payload);
+        if gtpu_header_size == 0 || gtpu_header_size >= l4_payload.len() {
+            return 0;
+        }
+        // G-PDU承载的是不带L2层的IP报文
+        let overlay_eth_type = match l4_payload[gtpu_header_size] >> 4 {
+            4 => EthernetType::IPV4,
+            6 => EthernetType::IPV6,
+            _ => return 0,
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            if underlay_ipv6 {
+                self.decapsulate_v6_addr(l3_packet);
+                self.is_ipv6 = true;
+            } else {
+                self.decapsulate_addr(l3_packet);
+            }
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::GtpU;
+            self.id = teid;
+        }
+        self.tier += 1;
+
+        // 同IPIP，将l2层头放在overlay ip头前
+        let start = underlay_ip_header_size + UDP_HEADER_SIZE + gtpu_header_size;
+        packet.copy_within(0..l2_len, start);
+        bytes::write_u16_be(
+            &mut packet[start + l2_len - 2..],
+            u16::from(overlay_eth_type),
+        );
+        // l2已经做过解析，这个去除掉已经解析的l2长度
+        start - l2_len
+    }
+
+    // return (next protocol, header size, service path), header size is 0 if NSH is invalid
+    fn decapsulate_nsh_header(nsh_header: &[u8]) -> (u8, usize, NshInfo) {
+        if nsh_header.len() < NSH_HEADER_MIN_SIZE
+            || nsh_header[NSH_LENGTH_OFFSET] >> NSH_VERSION_SHIFT != 0
+        {
+            return (0, 0, NshInfo::default());
+        }
+        let header_size = ((bytes::read_u16_be(&nsh_header[NSH_LENGTH_OFFSET..]) & NSH_LENGTH_MASK)
+            as usize)
+            << 2;
+        if header_size < NSH_HEADER_MIN_SIZE || header_size > nsh_header.len() {
+            return (0, 0, NshInfo::default());
+        }
+        (
+            nsh_header[NSH_NEXT_PROTOCOL_OFFSET],
+            header_size,
+            NshInfo {
+                spi: bytes::read_u32_be(&nsh_header[NSH_SERVICE_PATH_OFFSET..]) >> NSH_SPI_SHIFT,
+                si: nsh_header[NSH_SERVICE_INDEX_OFFSET],
+            },
+        )
+    }
+
+    pub fn decapsulate_vxlan_gpe(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        underlay_ipv6: bool,
+    ) -> usize {
+        let underlay_ip_header_size = if underlay_ipv6 {
+            IPV6_HEADER_SIZE
+        } else {
+            IPV4_HEADER_SIZE
+        };
+        let gpe_offset = underlay_ip_header_size + UDP_HEADER_SIZE;
+        let l3_packet = &packet[l2_len..];
+        if l3_packet.len() < gpe_offset + VXLAN_HEADER_SIZE {
+            return 0;
+        }
+
+        let gpe_header = &l3_packet[gpe_offset..];
+        let flags = gpe_header[VXLAN_GPE_FLAGS_OFFSET];
+        if flags & VXLAN_GPE_VERSION_MASK != 0
+            || flags & VXLAN_GPE_FLAGS_I_MASK == 0
+            || flags & VXLAN_GPE_FLAGS_P_MASK == 0
+        {
+            return 0;
+        }
+        let mut next_protocol = gpe_header[VXLAN_GPE_NEXT_PROTOCOL_OFFSET];
+        let mut overlay_offset = gpe_offset + VXLAN_HEADER_SIZE;
+        let mut nsh = NshInfo::default();
+        if next_protocol == GPE_NEXT_PROTOCOL_NSH {
+            let (nsh_next_protocol, nsh_header_size, nsh_info) =
+                Self::decapsulate_nsh_header(&l3_packet[overlay_offset..]);
+            if nsh_header_size == 0 {
+                return 0;
+            }
+            next_protocol = nsh_next_protocol;
+            nsh = nsh_info;
+            overlay_offset += nsh_header_size;
+        }
+        let overlay_eth_type = match next_protocol {
+            GPE_NEXT_PROTOCOL_IPV4 => Some(EthernetType::IPV4),
+            GPE_NEXT_PROTOCOL_IPV6 => Some(EthernetType::IPV6),
+            GPE_NEXT_PROTOCOL_ETHERNET => None,
+            _ => return 0,
+        };
+        if overlay_offset >= l3_packet.len() {
+            return 0;
+        }
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            if underlay_ipv6 {
+                self.decapsulate_v6_addr(l3_packet);
+                self.is_ipv6 = true;
+            } else {
+                self.decapsulate_addr(l3_packet);
+            }
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::VxlanGpe;
+            self.id = bytes::read_u32_be(&gpe_header[VXLAN_GPE_VNI_OFFSET..]) >> 8;
+            self.nsh = nsh;
+        }
+        self.tier += 1;
+
+        match overlay_eth_type {
+            // 承载的是不带L2层的IP报文，同IPIP，将l2层头放在overlay ip头前
+            Some(eth_type) => {
+                packet.copy_within(0..l2_len, overlay_offset);
+                bytes::write_u16_be(
+                    &mut packet[overlay_offset + l2_len - 2..],
+                    u16::from(eth_type),
+                );
+                overlay_offset - l2_len
+            }
+            // return offset start from L3
+            None => overlay_offset,
+        }
+    }
+
     pub fn decapsulate_v6_geneve(&mut self, packet: &[u8], l2_len: usize) -> usize {
         let l3_packet = &packet[l2_len..];
         if l3_packet.len() < UDP6_PACKET_SIZE + GENEVE_HEADER_SIZE {
@@ -596,6 +963,8 @@ impl TunnelInfo {
             self.tunnel_type = TunnelType::Geneve;
             self.id = tunnel_id;
             self.is_ipv6 = true;
+            self.geneve_options
+                .decapsulate(&l4_payload[GENEVE_HEADER_SIZE..geneve_header_size]);
         }
         self.tier += 1;
 
@@ -631,7 +1000,7 @@ impl TunnelInfo {
 
     pub fn decapsulate_v6_udp(
         &mut self,
-        packet: &[u8],
+        packet: &mut [u8],
         l2_len: usize,
         tunnel_types: &TunnelTypeBitmap,
     ) -> usize {
@@ -650,6 +1019,12 @@ impl TunnelInfo {
             LE_GENEVE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Geneve) => {
                 self.decapsulate_v6_geneve(packet, l2_len)
             }
+            LE_GTPU_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::GtpU) => {
+                self.decapsulate_gtpu(packet, l2_len, true)
+            }
+            LE_VXLAN_GPE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::VxlanGpe) => {
+                self.decapsulate_vxlan_gpe(packet, l2_len, true)
+            }
             _ => 0,
         }
     }
@@ -688,6 +1063,112 @@ impl TunnelInfo {
         }
     }
 
+    // MPLS标签栈前没有IP头，返回值为相对L2层开始位置的偏移
+    pub fn decapsulate_mpls(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        tunnel_types: &TunnelTypeBitmap,
+    ) -> usize {
+        if !tunnel_types.has(TunnelType::Mpls) || self.tier == TUNNEL_TIER_LIMIT {
+            return 0;
+        }
+
+        let mut mpls = MplsInfo::default();
+        let mut offset = l2_len;
+        let bottom_label = loop {
+            if offset + MPLS_LABEL_SIZE > packet.len()
+                || mpls.depth as usize == MPLS_LABEL_STACK_MAX_DEPTH
+            {
+                return 0;
+            }
+            let entry = bytes::read_u32_be(&packet[offset..]);
+            offset += MPLS_LABEL_SIZE;
+            let label = entry >> MPLS_LABEL_SHIFT;
+            if (mpls.depth as usize) < MPLS_LABEL_STACK_LIMIT {
+                mpls.labels[mpls.depth as usize] = label;
+            }
+            mpls.depth += 1;
+            if entry & MPLS_BOTTOM_OF_STACK_MASK != 0 {
+                break label;
+            }
+        };
+        if offset >= packet.len() {
+            return 0;
+        }
+        let overlay_eth_type = match packet[offset] >> 4 {
+            4 => Some(EthernetType::IPV4),
+            6 => Some(EthernetType::IPV6),
+            // 伪线控制字，其后为以太网帧
+            0 if offset + MPLS_CONTROL_WORD_SIZE + ETH_HEADER_SIZE < packet.len() => None,
+            _ => return 0,
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::Mpls;
+            self.id = bottom_label;
+            self.mpls = mpls;
+        }
+        self.tier += 1;
+
+        match overlay_eth_type {
+            // 将l2层头放在overlay ip头前
+            Some(eth_type) => {
+                let start = offset - l2_len;
+                packet.copy_within(0..l2_len, start);
+                bytes::write_u16_be(&mut packet[offset - 2..], u16::from(eth_type));
+                start
+            }
+            None => offset + MPLS_CONTROL_WORD_SIZE,
+        }
+    }
+
+    // PPPoE会话头前没有IP头，返回值为相对L2层开始位置的偏移
+    pub fn decapsulate_pppoe(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        tunnel_types: &TunnelTypeBitmap,
+    ) -> usize {
+        if !tunnel_types.has(TunnelType::Pppoe) || self.tier == TUNNEL_TIER_LIMIT {
+            return 0;
+        }
+
+        let offset = l2_len + PPPOE_HEADER_SIZE + PPP_PROTOCOL_SIZE;
+        if offset >= packet.len() {
+            return 0;
+        }
+        let pppoe = &packet[l2_len..];
+        if pppoe[PPPOE_VERSION_TYPE_OFFSET] != PPPOE_VERSION_TYPE
+            || pppoe[PPPOE_CODE_OFFSET] != PPPOE_CODE_SESSION_DATA
+        {
+            return 0;
+        }
+        let session_id = bytes::read_u16_be(&pppoe[PPPOE_SESSION_ID_OFFSET..]);
+        // LCP等控制报文不解封装
+        let overlay_eth_type = match bytes::read_u16_be(&pppoe[PPPOE_HEADER_SIZE..]) {
+            PPP_PROTOCOL_IPV4 => EthernetType::IPV4,
+            PPP_PROTOCOL_IPV6 => EthernetType::IPV6,
+            _ => return 0,
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::Pppoe;
+            self.id = session_id as u32;
+        }
+        self.tier += 1;
+
+        // 将l2层头放在overlay ip头前
+        let start = offset - l2_len;
+        packet.copy_within(0..l2_len, start);
+        bytes::write_u16_be(&mut packet[offset - 2..], u16::from(overlay_eth_type));
+        start
+    }
+
     pub fn is_valid(self) -> bool {
         self.tunnel_type != TunnelType::None
     }
@@ -798,6 +1279,13 @@ mod tests {
             tunnel_type: TunnelType::Erspan,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo {
+                version: 1,
+                ..Default::default()
+            },
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_erspan1.pcap"),
@@ -825,7 +1313,7 @@ mod tests {
     #[test]
     fn test_decapsulate_erspan_ii() {
         let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Erspan]);
-        let expected = TunnelInfo {
+        let mut expected = TunnelInfo {
             src: Ipv4Addr::new(2, 2, 2, 2),
             dst: Ipv4Addr::new(1, 1, 1, 1),
             mac_src: 0xf1e20101,
@@ -834,6 +1322,15 @@ mod tests {
             tunnel_type: TunnelType::Erspan,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo {
+                version: 2,
+                session_id: 100,
+                gre_seq: Some(0x702),
+                ..Default::default()
+            },
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap"),
@@ -853,6 +1350,7 @@ mod tests {
         let packet = packets[1].as_mut_slice();
         let mut actual = TunnelInfo::default();
         let offset = actual.decapsulate(packet, l2_len, &bitmap);
+        expected.erspan.gre_seq = Some(0x704);
 
         assert_eq!(offset, expected_offset);
         assert_eq!(actual, expected);
@@ -870,6 +1368,14 @@ mod tests {
             tunnel_type: TunnelType::Erspan,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo {
+                version: 3,
+                gre_seq: Some(0x665),
+                ..Default::default()
+            },
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap"),
@@ -887,6 +1393,40 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_decapsulate_erspan_iii_keyed() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Erspan]);
+        // ethernet + ipv4 + gre with key and sequence + erspan III + overlay ethernet
+        let mut packet = vec![
+            0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x2f, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x30, 0x00, 0x22, 0xeb, 0x00, 0x00, 0xab, 0xcd,
+            0x00, 0x00, 0x00, 0x10, 0x20, 0x00, 0x01, 0x55, 0x11, 0x22, 0x33, 0x44, 0x00, 0x00,
+            0x02, 0xa6, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x02,
+            0x08, 0x00,
+        ];
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, 14, &bitmap);
+        assert_eq!(
+            offset,
+            IPV4_HEADER_SIZE + GRE_HEADER_SIZE_DECAP + 8 + ERSPAN_III_HEADER_SIZE
+        );
+        assert_eq!(actual.id, 0x155);
+        assert_eq!(
+            actual.erspan,
+            ErspanInfo {
+                version: 3,
+                session_id: 0x155,
+                hardware_id: 0x2a,
+                timestamp: 0x11223344,
+                timestamp_granularity: 3,
+                gre_key: Some(0xabcd),
+                gre_seq: Some(0x10),
+            }
+        );
+    }
+
     #[test]
     fn test_decapsulate_vxlan() {
         let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Vxlan]);
@@ -899,6 +1439,10 @@ mod tests {
             tunnel_type: TunnelType::Vxlan,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap"),
@@ -928,6 +1472,10 @@ mod tests {
             tunnel_type: TunnelType::TencentGre,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let expected_overlay = [
             0x00, 0x00, 0x00, 0x00, 0x02, 0x85, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00,
@@ -965,6 +1513,10 @@ mod tests {
             tunnel_type: TunnelType::Teb,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("vmware-gre-teb.pcap"),
@@ -994,6 +1546,10 @@ mod tests {
             tunnel_type: TunnelType::Vxlan,
             tier: 1,
             is_ipv6: true,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ip6-vxlan.pcap"), None).into();
@@ -1020,6 +1576,10 @@ mod tests {
             tunnel_type: TunnelType::Ipip,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ipip.pcap"), None).into();
@@ -1068,6 +1628,10 @@ mod tests {
             tunnel_type: TunnelType::Geneve,
             tier: 1,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("geneve.pcap"), None).into();
@@ -1078,4 +1642,191 @@ mod tests {
         assert_eq!(offset, IPV4_HEADER_SIZE + 24);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_decapsulate_geneve_aws_options() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Geneve]);
+        // ethernet + ipv4 + udp(6081) + geneve with GWLBE ID, attachment ID and flow cookie
+        // options + overlay ethernet
+        let mut packet = vec![
+            0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x5e, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0xd4, 0x31, 0x17, 0xc1, 0x00, 0x4a, 0x00, 0x00,
+            0x08, 0x00, 0x65, 0x58, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x01, 0x02, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x01, 0x08, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x56, 0x78, 0x01, 0x08, 0x03, 0x01, 0xde, 0xad, 0xbe, 0xef, 0x02, 0x00,
+            0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x08, 0x00,
+        ];
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, 14, &bitmap);
+        assert_eq!(
+            offset,
+            IPV4_HEADER_SIZE + UDP_HEADER_SIZE + GENEVE_HEADER_SIZE + 32
+        );
+        assert_eq!(actual.tunnel_type, TunnelType::Geneve);
+        assert_eq!(
+            actual.geneve_options,
+            GeneveOptions {
+                gwlbe_id: 0x1234,
+                attachment_id: 0x5678,
+                flow_cookie: 0xdeadbeef,
+            }
+        );
+    }
+
+    #[test]
+    fn test_decapsulate_vxlan_gpe_nsh() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::VxlanGpe]);
+        // ethernet + ipv4 + udp(4790) + vxlan-gpe + nsh with MD type 1 + overlay ipv4
+        let mut packet = vec![
+            0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x50, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0xd4, 0x31, 0x12, 0xb6, 0x00, 0x3c, 0x00, 0x00,
+            0x0c, 0x00, 0x00, 0x04, 0x00, 0x00, 0x64, 0x00, 0x0f, 0xc6, 0x01, 0x01, 0x00, 0x01,
+            0x23, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06,
+            0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ];
+        let l2_len = 14;
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, l2_len, &bitmap);
+        assert_eq!(
+            offset,
+            IPV4_HEADER_SIZE + UDP_HEADER_SIZE + VXLAN_HEADER_SIZE + 24 - l2_len
+        );
+        assert_eq!(actual.tunnel_type, TunnelType::VxlanGpe);
+        assert_eq!(actual.id, 100);
+        assert_eq!(
+            actual.nsh,
+            NshInfo {
+                spi: 0x123,
+                si: 0xfe
+            }
+        );
+
+        let overlay = &packet[l2_len + offset..];
+        assert_eq!(
+            &overlay[ETH_HEADER_SIZE - 2..ETH_HEADER_SIZE + 1],
+            &[0x08, 0x00, 0x45]
+        );
+    }
+
+    #[test]
+    fn test_decapsulate_mpls() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Mpls]);
+        // ethernet + mpls label 100 and 2000 + overlay ipv4
+        let mut packet = vec![
+            0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x88, 0x47,
+            0x00, 0x06, 0x40, 0x40, 0x00, 0x7d, 0x01, 0x40, 0x45, 0x00, 0x00, 0x14, 0x00, 0x00,
+            0x40, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ];
+        let l2_len = 14;
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate_mpls(&mut packet, l2_len, &bitmap);
+        assert_eq!(offset, 2 * MPLS_LABEL_SIZE);
+        assert_eq!(actual.tunnel_type, TunnelType::Mpls);
+        assert_eq!(actual.id, 2000);
+        assert_eq!(actual.mpls.labels(), &[100, 2000]);
+        assert_eq!(actual.mac_src, 0x33445566);
+
+        let overlay = &packet[offset..];
+        assert_eq!(
+            &overlay[..ETH_HEADER_SIZE + 1],
+            &[
+                0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00,
+                0x45
+            ]
+        );
+
+        // MPLS is not decapsulated unless configured
+        let mut actual = TunnelInfo::default();
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Vxlan]);
+        assert_eq!(actual.decapsulate_mpls(&mut packet, l2_len, &bitmap), 0);
+    }
+
+    #[test]
+    fn test_decapsulate_pppoe() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Pppoe]);
+        // ethernet + pppoe session 0x1234 + ppp ipv4 + overlay ipv4
+        let mut packet = vec![
+            0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x88, 0x64,
+            0x11, 0x00, 0x12, 0x34, 0x00, 0x16, 0x00, 0x21, 0x45, 0x00, 0x00, 0x14, 0x00, 0x00,
+            0x40, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ];
+        let l2_len = 14;
+
+        let mut lcp = packet.clone();
+        lcp[20..22].copy_from_slice(&[0xc0, 0x21]);
+        let mut actual = TunnelInfo::default();
+        assert_eq!(actual.decapsulate_pppoe(&mut lcp, l2_len, &bitmap), 0);
+        assert_eq!(actual.tunnel_type, TunnelType::None);
+
+        let offset = actual.decapsulate_pppoe(&mut packet, l2_len, &bitmap);
+        assert_eq!(offset, PPPOE_HEADER_SIZE + PPP_PROTOCOL_SIZE);
+        assert_eq!(actual.tunnel_type, TunnelType::Pppoe);
+        assert_eq!(actual.id, 0x1234);
+        assert_eq!(actual.mac_src, 0x33445566);
+
+        let overlay = &packet[offset..];
+        assert_eq!(
+            &overlay[..ETH_HEADER_SIZE + 1],
+            &[
+                0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00,
+                0x45
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decapsulate_gtpu() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::GtpU]);
+        let expected = TunnelInfo {
+            src: Ipv4Addr::new(10, 0, 0, 1),
+            dst: Ipv4Addr::new(10, 0, 0, 2),
+            mac_src: 0x33445566,
+            mac_dst: 0x99aabbcc,
+            id: 0x12345678,
+            tunnel_type: TunnelType::GtpU,
+            tier: 1,
+            is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            erspan: ErspanInfo::default(),
+            nsh: NshInfo::default(),
+            mpls: MplsInfo::default(),
+        };
+        // ethernet + ipv4 + udp(2152) + G-PDU with PDU session container + overlay ipv4
+        let mut packet = vec![
+            0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x48, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x08, 0x68, 0x08, 0x68, 0x00, 0x34, 0x00, 0x00,
+            0x34, 0xff, 0x00, 0x24, 0x12, 0x34, 0x56, 0x78, 0x00, 0x00, 0x00, 0x85, 0x01, 0x00,
+            0x01, 0x00, 0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00,
+            0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ];
+        let l2_len = 14;
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, l2_len, &bitmap);
+        let expected_offset = IPV4_HEADER_SIZE + UDP_HEADER_SIZE + GTPU_HEADER_SIZE + 8 - l2_len;
+        assert_eq!(offset, expected_offset);
+        assert_eq!(actual, expected);
+
+        // 伪造的L2层紧接overlay ip头
+        let overlay = &packet[l2_len + offset..];
+        assert_eq!(
+            &overlay[..ETH_HEADER_SIZE],
+            &[0x00, 0x11, 0x99, 0xaa, 0xbb, 0xcc, 0x00, 0x22, 0x33, 0x44, 0x55, 0x66, 0x08, 0x00]
+        );
+        assert_eq!(overlay[ETH_HEADER_SIZE], 0x45);
+
+        // GTP-U echo request is not decapsulated
+        let mut echo = packet.clone();
+        echo[ETH_HEADER_SIZE + IPV4_HEADER_SIZE + UDP_HEADER_SIZE + GTPU_MSG_TYPE_OFFSET] = 0x01;
+        let mut actual = TunnelInfo::default();
+        assert_eq!(actual.decapsulate(&mut echo, l2_len, &bitmap), 0);
+        assert!(!actual.is_valid());
+    }
 }
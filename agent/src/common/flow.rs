@@ -31,7 +31,7 @@ use serde::{Serialize, Serializer};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use super::super::ebpf::{MSG_REQUEST, MSG_REQUEST_END, MSG_RESPONSE, MSG_RESPONSE_END};
 use super::{
-    decapsulate::TunnelType,
+    decapsulate::{MplsInfo, TunnelType},
     enums::{CaptureNetworkType, EthernetType, IpProtocol, TcpFlags},
     tap_port::TapPort,
     TaggedFlow,
@@ -39,7 +39,10 @@ use super::{
 
 use crate::{
     common::{endpoint::EPC_INTERNET, timestamp_to_micros, Timestamp},
-    metric::document::Direction,
+    metric::{
+        document::Direction,
+        meter::{AppLatencySketch, AppPayloadSize},
+    },
 };
 use crate::{
     flow_generator::protocol_logs::to_string_format,
@@ -216,6 +219,32 @@ impl Default for SignalSource {
     }
 }
 
+// The protocol by which the receivers on the capture interface joined the multicast group
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MulticastSource {
+    #[default]
+    None = 0,
+    IgmpV1 = 1,
+    IgmpV2 = 2,
+    IgmpV3 = 3,
+    MldV1 = 4,
+    MldV2 = 5,
+}
+
+impl fmt::Display for MulticastSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::IgmpV1 => write!(f, "IGMPv1"),
+            Self::IgmpV2 => write!(f, "IGMPv2"),
+            Self::IgmpV3 => write!(f, "IGMPv3"),
+            Self::MldV1 => write!(f, "MLDv1"),
+            Self::MldV2 => write!(f, "MLDv2"),
+        }
+    }
+}
+
 #[derive(Serialize, Debug, Clone)]
 pub struct TunnelField {
     #[serde(rename = "tunnel_tx_ip_0")]
@@ -243,6 +272,20 @@ pub struct TunnelField {
     pub tier: u8,
     #[serde(skip)]
     pub is_ipv6: bool,
+    // Geneve options of AWS Gateway Load Balancer
+    #[serde(rename = "tunnel_gwlbe_id")]
+    pub gwlbe_id: u64,
+    #[serde(rename = "tunnel_gwlb_attachment_id")]
+    pub gwlb_attachment_id: u64,
+    #[serde(rename = "tunnel_gwlb_flow_cookie")]
+    pub gwlb_flow_cookie: u32,
+    // NSH service path of VXLAN-GPE
+    #[serde(rename = "tunnel_nsh_spi")]
+    pub nsh_spi: u32,
+    #[serde(rename = "tunnel_nsh_si")]
+    pub nsh_si: u8,
+    #[serde(rename = "tunnel_mpls_labels", serialize_with = "mpls_labels_to_seq")]
+    pub mpls: MplsInfo,
 }
 
 pub fn mac_low32_to_string<S>(d: &u32, serializer: S) -> Result<S::Ok, S::Error>
@@ -252,6 +295,13 @@ where
     serializer.serialize_str(&format!("{:08x}", d))
 }
 
+fn mpls_labels_to_seq<S>(d: &MplsInfo, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(d.labels())
+}
+
 impl Default for TunnelField {
     fn default() -> Self {
         TunnelField {
@@ -268,6 +318,12 @@ impl Default for TunnelField {
             tunnel_type: TunnelType::default(),
             tier: 0,
             is_ipv6: false,
+            gwlbe_id: 0,
+            gwlb_attachment_id: 0,
+            gwlb_flow_cookie: 0,
+            nsh_spi: 0,
+            nsh_si: 0,
+            mpls: MplsInfo::default(),
         }
     }
 }
@@ -316,6 +372,12 @@ impl From<TunnelField> for flow_log::TunnelField {
             tunnel_type: f.tunnel_type as u32,
             tier: f.tier as u32,
             is_ipv6: 0,
+            gwlbe_id: f.gwlbe_id,
+            gwlb_attachment_id: f.gwlb_attachment_id,
+            gwlb_flow_cookie: f.gwlb_flow_cookie,
+            nsh_spi: f.nsh_spi,
+            nsh_si: f.nsh_si as u32,
+            mpls_labels: f.mpls.labels().to_vec(),
         }
     }
 }
@@ -324,12 +386,27 @@ impl From<TunnelField> for flow_log::TunnelField {
 pub struct TcpPerfCountsPeer {
     pub retrans_count: u32,
     pub zero_win_count: u32,
+    pub zero_win_duration: u32, // us
+    pub out_of_order_count: u32,
+    pub sack_count: u32,
+    // advertised in SYN or SYN/ACK, not reset every statistical period
+    pub mss: u16,
+    pub win_scale: u8,
 }
 
 impl TcpPerfCountsPeer {
     pub fn sequential_merge(&mut self, other: &TcpPerfCountsPeer) {
         self.retrans_count += other.retrans_count;
         self.zero_win_count += other.zero_win_count;
+        self.zero_win_duration += other.zero_win_duration;
+        self.out_of_order_count += other.out_of_order_count;
+        self.sack_count += other.sack_count;
+        if other.mss > 0 {
+            self.mss = other.mss;
+        }
+        if other.win_scale > 0 {
+            self.win_scale = other.win_scale;
+        }
     }
 }
 
@@ -338,6 +415,11 @@ impl From<TcpPerfCountsPeer> for flow_log::TcpPerfCountsPeer {
         flow_log::TcpPerfCountsPeer {
             retrans_count: p.retrans_count,
             zero_win_count: p.zero_win_count,
+            zero_win_duration: p.zero_win_duration,
+            out_of_order_count: p.out_of_order_count,
+            sack_count: p.sack_count,
+            mss: p.mss as u32,
+            win_scale: p.win_scale as u32,
         }
     }
 }
@@ -377,6 +459,8 @@ pub struct TcpPerfStats {
     pub counts_peers: [TcpPerfCountsPeer; 2],
     #[serde(skip)]
     pub total_retrans_count: u32,
+    // both sides sent SACK permitted in the handshake
+    pub sack_permitted: bool,
 }
 
 pub fn serialize_tcp_perf_counts<S>(
@@ -392,12 +476,32 @@ where
         pub retrans_rx: u32,
         pub zero_win_tx: u32,
         pub zero_win_rx: u32,
+        pub zero_win_duration_tx: u32,
+        pub zero_win_duration_rx: u32,
+        pub out_of_order_tx: u32,
+        pub out_of_order_rx: u32,
+        pub sack_tx: u32,
+        pub sack_rx: u32,
+        pub mss_tx: u16,
+        pub mss_rx: u16,
+        pub win_scale_tx: u8,
+        pub win_scale_rx: u8,
     }
     let s = Ser {
         retrans_tx: v[0].retrans_count,
         retrans_rx: v[1].retrans_count,
         zero_win_tx: v[0].zero_win_count,
         zero_win_rx: v[1].zero_win_count,
+        zero_win_duration_tx: v[0].zero_win_duration,
+        zero_win_duration_rx: v[1].zero_win_duration,
+        out_of_order_tx: v[0].out_of_order_count,
+        out_of_order_rx: v[1].out_of_order_count,
+        sack_tx: v[0].sack_count,
+        sack_rx: v[1].sack_count,
+        mss_tx: v[0].mss,
+        mss_rx: v[1].mss,
+        win_scale_tx: v[0].win_scale,
+        win_scale_rx: v[1].win_scale,
     };
     serializer.serialize_newtype_struct("tcp_perf_counts", &s)
 }
@@ -441,6 +545,7 @@ impl TcpPerfStats {
         self.counts_peers[0].sequential_merge(&other.counts_peers[0]);
         self.counts_peers[1].sequential_merge(&other.counts_peers[1]);
         self.total_retrans_count += other.total_retrans_count;
+        self.sack_permitted |= other.sack_permitted;
     }
 
     pub fn reverse(&mut self) {
@@ -470,6 +575,7 @@ impl From<TcpPerfStats> for flow_log::TcpPerfStats {
             cit_max: p.cit_max,
             syn_count: p.syn_count,
             synack_count: p.synack_count,
+            sack_permitted: p.sack_permitted,
             ..Default::default()
         }
     }
@@ -598,6 +704,10 @@ pub struct L7PerfStats {
     pub rrt_sum: u64,   // us RRT(Request Response Time)
     pub rrt_max: u32,   // us agent保证在3600s以内
     pub tls_rtt: u32,
+    #[serde(skip)]
+    pub payload_size: AppPayloadSize,
+    #[serde(skip)]
+    pub rrt_sketch: AppLatencySketch,
 }
 
 impl L7PerfStats {
@@ -613,6 +723,8 @@ impl L7PerfStats {
             self.rrt_max = other.rrt_max
         }
         self.tls_rtt += other.tls_rtt;
+        self.payload_size.sequential_merge(&other.payload_size);
+        self.rrt_sketch.sequential_merge(&other.rrt_sketch);
     }
 
     pub fn merge_perf(
@@ -966,6 +1078,8 @@ pub struct Flow {
 
     /* L2 */
     pub vlan: u16,
+    // S-TAG of QinQ traffic, `vlan` keeps the C-TAG
+    pub outer_vlan: u16,
     pub eth_type: EthernetType,
 
     /* TCP Perf Data*/
@@ -996,6 +1110,45 @@ pub struct Flow {
     pub pod_id: u32,
     pub request_domain: String,
     pub need_to_store: bool,
+
+    // ICMP errors referring to this flow, the count is reset every statistical period
+    pub icmp_error_count: u32,
+    pub icmp_error_type: u8,
+    pub icmp_error_code: u8,
+
+    // set for flows sent to a multicast group joined on the capture interface
+    pub multicast_source: MulticastSource,
+
+    // set for flows of which source or destination IP is claimed by more than one MAC
+    pub ip_conflict: bool,
+
+    // bits of Ipv6ExtHeaders seen in packets of the flow
+    pub ipv6_ext_headers: u16,
+
+    // 1 in N packets or flows is captured, 0 means not sampled
+    pub sampling_rate: u32,
+
+    // bits of FlowAnomaly the flow is part of, e.g. port scan
+    pub anomaly_flags: u8,
+
+    // local processes of the sockets on source (0) and destination (1) side, 0 if unknown
+    pub process_id_0: u32,
+    pub process_id_1: u32,
+    pub process_kname_0: String,
+    pub process_kname_1: String,
+
+    // path of the UNIX domain socket traced by eBPF, whose ip and port are 127.0.0.1:0
+    pub unix_socket_path: String,
+
+    // TCP health of the local socket from eBPF tracepoints, cumulative since the connection
+    // is established
+    pub kernel_tcp_retrans: u32,
+    pub kernel_tcp_drops: u32,
+    pub kernel_tcp_srtt: u32, // microseconds, 0 if unknown
+    pub kernel_tcp_state: u8, // TCP_ESTABLISHED, TCP_CLOSE, etc. in kernel, 0 if unknown
+
+    // flow_id of the same session captured at another tap of the agent, 0 if none
+    pub duplicate_of_flow_id: u64,
 }
 
 fn tunnel_is_none(t: &TunnelField) -> bool {
@@ -1004,9 +1157,15 @@ fn tunnel_is_none(t: &TunnelField) -> bool {
 
 impl Flow {
     pub fn start_time_in_minute(&self) -> u64 {
-        let second_in_minute = self.start_time.as_secs() % SECONDS_IN_MINUTE;
-        (self.flow_stat_time.as_secs() - second_in_minute) / SECONDS_IN_MINUTE * SECONDS_IN_MINUTE
-            + second_in_minute
+        self.start_time_in_interval(SECONDS_IN_MINUTE)
+    }
+
+    // start of the report interval containing flow_stat_time, the intervals of a flow are
+    // aligned to the second it starts in, in seconds
+    pub fn start_time_in_interval(&self, interval: u64) -> u64 {
+        let second_in_interval = self.start_time.as_secs() % interval;
+        (self.flow_stat_time.as_secs() - second_in_interval) / interval * interval
+            + second_in_interval
     }
 
     fn swap_flow_ip_and_real_ip(&mut self) {
@@ -1042,6 +1201,40 @@ impl Flow {
         if other.vlan > 0 {
             self.vlan = other.vlan
         }
+        if other.outer_vlan > 0 {
+            self.outer_vlan = other.outer_vlan
+        }
+        if other.multicast_source != MulticastSource::None {
+            self.multicast_source = other.multicast_source;
+        }
+        self.ip_conflict |= other.ip_conflict;
+        self.ipv6_ext_headers |= other.ipv6_ext_headers;
+        self.sampling_rate = self.sampling_rate.max(other.sampling_rate);
+        self.anomaly_flags |= other.anomaly_flags;
+        if other.process_id_0 > 0 {
+            self.process_id_0 = other.process_id_0;
+            self.process_kname_0 = other.process_kname_0.clone();
+        }
+        if other.process_id_1 > 0 {
+            self.process_id_1 = other.process_id_1;
+            self.process_kname_1 = other.process_kname_1.clone();
+        }
+        if !other.unix_socket_path.is_empty() {
+            self.unix_socket_path = other.unix_socket_path.clone();
+        }
+        self.kernel_tcp_retrans = self.kernel_tcp_retrans.max(other.kernel_tcp_retrans);
+        self.kernel_tcp_drops = self.kernel_tcp_drops.max(other.kernel_tcp_drops);
+        if other.kernel_tcp_srtt > 0 {
+            self.kernel_tcp_srtt = other.kernel_tcp_srtt;
+        }
+        if other.kernel_tcp_state > 0 {
+            self.kernel_tcp_state = other.kernel_tcp_state;
+        }
+        if other.icmp_error_count > 0 {
+            self.icmp_error_count += other.icmp_error_count;
+            self.icmp_error_type = other.icmp_error_type;
+            self.icmp_error_code = other.icmp_error_code;
+        }
 
         if other.last_keepalive_seq != 0 {
             self.last_keepalive_seq = other.last_keepalive_seq;
@@ -1081,6 +1274,8 @@ impl Flow {
         self.tunnel.reverse();
         self.flow_key.reverse();
         self.flow_metrics_peers.swap(0, 1);
+        swap(&mut self.process_id_0, &mut self.process_id_1);
+        swap(&mut self.process_kname_0, &mut self.process_kname_1);
         self.direction_score = 0;
     }
 
@@ -1200,14 +1395,20 @@ impl fmt::Display for Flow {
             "flow_id:{} signal_source:{:?} tunnel:{} close_type:{:?} is_active_service:{} is_new_flow:{} queue_hash:{} \
         syn_seq:{} synack_seq:{} last_keepalive_seq:{} last_keepalive_ack:{} flow_stat_time:{:?} \
         \t start_time:{:?} end_time:{:?} duration:{:?} \
-        \t vlan:{} eth_type:{:?} reversed:{} otel_service:{:?} otel_instance:{:?} request_domain:{:?} flow_key:{} \
+        \t vlan:{} outer_vlan:{} eth_type:{:?} reversed:{} otel_service:{:?} otel_instance:{:?} request_domain:{:?} flow_key:{} \
+        \t icmp_error_count:{} icmp_error_type:{} icmp_error_code:{} multicast_source:{} ip_conflict:{} ipv6_ext_headers:{:#x} sampling_rate:{} anomaly_flags:{:#x} \
+        \t process_id_0:{} process_kname_0:{:?} process_id_1:{} process_kname_1:{:?} unix_socket_path:{:?} \
+        \t kernel_tcp_retrans:{} kernel_tcp_drops:{} kernel_tcp_srtt:{} kernel_tcp_state:{} \
         \n\t flow_metrics_peers_src:{:?} \
         \n\t flow_metrics_peers_dst:{:?} \
         \n\t flow_perf_stats:{:?}",
             self.flow_id, self.signal_source, self.tunnel, self.close_type, self.is_active_service, self.is_new_flow, self.queue_hash,
             self.syn_seq, self.synack_seq, self.last_keepalive_seq, self.last_keepalive_ack, self.flow_stat_time,
             self.start_time, self.end_time, self.duration,
-            self.vlan, self.eth_type, self.reversed, self.otel_service, self.otel_instance, self.request_domain, self.flow_key,
+            self.vlan, self.outer_vlan, self.eth_type, self.reversed, self.otel_service, self.otel_instance, self.request_domain, self.flow_key,
+            self.icmp_error_count, self.icmp_error_type, self.icmp_error_code, self.multicast_source, self.ip_conflict, self.ipv6_ext_headers, self.sampling_rate, self.anomaly_flags,
+            self.process_id_0, self.process_kname_0, self.process_id_1, self.process_kname_1, self.unix_socket_path,
+            self.kernel_tcp_retrans, self.kernel_tcp_drops, self.kernel_tcp_srtt, self.kernel_tcp_state,
             self.flow_metrics_peers[0],
             self.flow_metrics_peers[1],
             self.flow_perf_stats
@@ -1237,6 +1438,7 @@ impl From<Flow> for flow_log::Flow {
             duration: f.duration.as_nanos() as u64,
             eth_type: u16::from(f.eth_type) as u32,
             vlan: f.vlan as u32,
+            outer_vlan: f.outer_vlan as u32,
             has_perf_stats: f.flow_perf_stats.is_some() as u32,
             perf_stats: f.flow_perf_stats.map(|stats| stats.into()),
             close_type: f.close_type as u32,
@@ -1252,6 +1454,24 @@ impl From<Flow> for flow_log::Flow {
             acl_gids: f.acl_gids.into_iter().map(|g| g as u32).collect(),
             direction_score: f.direction_score as u32,
             request_domain: f.request_domain,
+            icmp_error_count: f.icmp_error_count,
+            icmp_error_type: f.icmp_error_type as u32,
+            icmp_error_code: f.icmp_error_code as u32,
+            multicast_source: f.multicast_source as u32,
+            ip_conflict: f.ip_conflict,
+            ipv6_ext_headers: f.ipv6_ext_headers as u32,
+            sampling_rate: f.sampling_rate,
+            anomaly_flags: f.anomaly_flags as u32,
+            process_id_0: f.process_id_0,
+            process_id_1: f.process_id_1,
+            process_kname_0: f.process_kname_0,
+            process_kname_1: f.process_kname_1,
+            unix_socket_path: f.unix_socket_path,
+            kernel_tcp_retrans: f.kernel_tcp_retrans,
+            kernel_tcp_drops: f.kernel_tcp_drops,
+            kernel_tcp_srtt: f.kernel_tcp_srtt,
+            kernel_tcp_state: f.kernel_tcp_state as u32,
+            duplicate_of_flow_id: f.duplicate_of_flow_id,
         }
     }
 }
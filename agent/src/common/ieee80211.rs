@@ -0,0 +1,231 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::ops::Range;
+
+use super::consts::{ETH_TYPE_LEN, MAC_ADDR_LEN};
+
+use crate::utils::bytes::{read_u16_le, read_u32_le};
+
+// Reference https://www.radiotap.org/ and IEEE 802.11-2020 9.2
+const RADIOTAP_HEADER_SIZE: usize = 8;
+const RADIOTAP_LENGTH_OFFSET: usize = 2;
+const RADIOTAP_PRESENT_OFFSET: usize = 4;
+const RADIOTAP_PRESENT_TSFT: u32 = 1 << 0;
+const RADIOTAP_PRESENT_FLAGS: u32 = 1 << 1;
+const RADIOTAP_PRESENT_EXT: u32 = 1 << 31;
+const RADIOTAP_TSFT_SIZE: usize = 8;
+const RADIOTAP_FLAGS_FCS: u8 = 0x10;
+const IEEE80211_FCS_SIZE: usize = 4;
+
+const IEEE80211_HEADER_SIZE: usize = 24;
+const IEEE80211_ADDR4_SIZE: usize = 6;
+const IEEE80211_QOS_CONTROL_SIZE: usize = 2;
+const IEEE80211_HT_CONTROL_SIZE: usize = 4;
+const IEEE80211_ADDR1_OFFSET: usize = 4;
+const IEEE80211_ADDR2_OFFSET: usize = 10;
+const IEEE80211_ADDR3_OFFSET: usize = 16;
+const IEEE80211_ADDR4_OFFSET: usize = 24;
+
+const IEEE80211_TYPE_MASK: u8 = 0x0c;
+const IEEE80211_TYPE_DATA: u8 = 0x08;
+const IEEE80211_SUBTYPE_QOS: u8 = 0x80;
+const IEEE80211_SUBTYPE_NO_DATA: u8 = 0x40;
+const IEEE80211_FLAGS_TO_DS: u8 = 0x01;
+const IEEE80211_FLAGS_FROM_DS: u8 = 0x02;
+const IEEE80211_FLAGS_PROTECTED: u8 = 0x40;
+const IEEE80211_FLAGS_ORDER: u8 = 0x80;
+
+const LLC_SNAP_HEADER_SIZE: usize = 8; // includes ethernet type
+const LLC_SNAP_PREFIX: [u8; 3] = [0xaa, 0xaa, 0x03];
+
+// Frames with the protected flag are dropped unless a decryptor is provided
+pub trait Decryptor {
+    // Decrypts `body` in place, returns the range of plaintext (starting with LLC) in `body`
+    fn decrypt(&self, header: &[u8], body: &mut [u8]) -> Option<Range<usize>>;
+}
+
+// returns (radiotap length, has fcs)
+fn parse_radiotap(packet: &[u8]) -> Option<(usize, bool)> {
+    if packet.len() < RADIOTAP_HEADER_SIZE || packet[0] != 0 {
+        return None;
+    }
+    let length = read_u16_le(&packet[RADIOTAP_LENGTH_OFFSET..]) as usize;
+    if length < RADIOTAP_HEADER_SIZE || length > packet.len() {
+        return None;
+    }
+    let present = read_u32_le(&packet[RADIOTAP_PRESENT_OFFSET..]);
+    if present & RADIOTAP_PRESENT_FLAGS == 0 {
+        return Some((length, false));
+    }
+
+    // skip the extended present bitmaps
+    let mut offset = RADIOTAP_PRESENT_OFFSET;
+    let mut bitmap = present;
+    while bitmap & RADIOTAP_PRESENT_EXT != 0 {
+        offset += 4;
+        if offset + 4 > length {
+            return None;
+        }
+        bitmap = read_u32_le(&packet[offset..]);
+    }
+    offset += 4;
+    if present & RADIOTAP_PRESENT_TSFT != 0 {
+        // TSFT is aligned to 8 bytes
+        offset = (offset + RADIOTAP_TSFT_SIZE - 1) & !(RADIOTAP_TSFT_SIZE - 1);
+        offset += RADIOTAP_TSFT_SIZE;
+    }
+    if offset >= length {
+        return None;
+    }
+    Some((length, packet[offset] & RADIOTAP_FLAGS_FCS != 0))
+}
+
+// Strips radiotap and 802.11 headers, rewrites the data frame into an ethernet frame in place.
+// Returns the range of the ethernet frame in `packet`.
+pub fn decapsulate_radiotap(
+    packet: &mut [u8],
+    decryptor: Option<&dyn Decryptor>,
+) -> Option<Range<usize>> {
+    let (radiotap_len, has_fcs) = parse_radiotap(packet)?;
+    let mut end = packet.len();
+    if has_fcs {
+        end = end.checked_sub(IEEE80211_FCS_SIZE)?;
+    }
+    if end < radiotap_len + IEEE80211_HEADER_SIZE {
+        return None;
+    }
+    let frame = &mut packet[radiotap_len..end];
+
+    let (frame_type, flags) = (frame[0], frame[1]);
+    if frame_type & IEEE80211_TYPE_MASK != IEEE80211_TYPE_DATA
+        || frame_type & IEEE80211_SUBTYPE_NO_DATA != 0
+    {
+        return None;
+    }
+    let to_ds = flags & IEEE80211_FLAGS_TO_DS != 0;
+    let from_ds = flags & IEEE80211_FLAGS_FROM_DS != 0;
+    let mut header_len = IEEE80211_HEADER_SIZE;
+    if to_ds && from_ds {
+        header_len += IEEE80211_ADDR4_SIZE;
+    }
+    if frame_type & IEEE80211_SUBTYPE_QOS != 0 {
+        header_len += IEEE80211_QOS_CONTROL_SIZE;
+        if flags & IEEE80211_FLAGS_ORDER != 0 {
+            header_len += IEEE80211_HT_CONTROL_SIZE;
+        }
+    }
+    if frame.len() < header_len {
+        return None;
+    }
+
+    let (dst_offset, src_offset) = match (to_ds, from_ds) {
+        (false, false) => (IEEE80211_ADDR1_OFFSET, IEEE80211_ADDR2_OFFSET),
+        (false, true) => (IEEE80211_ADDR1_OFFSET, IEEE80211_ADDR3_OFFSET),
+        (true, false) => (IEEE80211_ADDR3_OFFSET, IEEE80211_ADDR2_OFFSET),
+        (true, true) => (IEEE80211_ADDR3_OFFSET, IEEE80211_ADDR4_OFFSET),
+    };
+    let mut macs = [0u8; MAC_ADDR_LEN * 2];
+    macs[..MAC_ADDR_LEN].copy_from_slice(&frame[dst_offset..dst_offset + MAC_ADDR_LEN]);
+    macs[MAC_ADDR_LEN..].copy_from_slice(&frame[src_offset..src_offset + MAC_ADDR_LEN]);
+
+    let (header, body) = frame.split_at_mut(header_len);
+    let llc = if flags & IEEE80211_FLAGS_PROTECTED != 0 {
+        let plaintext = decryptor?.decrypt(header, body)?;
+        if plaintext.end > body.len() {
+            return None;
+        }
+        plaintext
+    } else {
+        0..body.len()
+    };
+    if llc.len() < LLC_SNAP_HEADER_SIZE || body[llc.start..llc.start + 3] != LLC_SNAP_PREFIX {
+        return None;
+    }
+
+    // the ethernet type of LLC SNAP is kept, mac addresses are written before it
+    let eth_type_offset =
+        radiotap_len + header_len + llc.start + LLC_SNAP_HEADER_SIZE - ETH_TYPE_LEN;
+    let start = eth_type_offset - MAC_ADDR_LEN * 2;
+    packet[start..eth_type_offset].copy_from_slice(&macs);
+    Some(start..radiotap_len + header_len + llc.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::consts::ETH_HEADER_SIZE;
+
+    struct XorDecryptor;
+
+    impl Decryptor for XorDecryptor {
+        fn decrypt(&self, _: &[u8], body: &mut [u8]) -> Option<Range<usize>> {
+            // 4 bytes of IV before ciphertext
+            body[4..].iter_mut().for_each(|b| *b ^= 0xff);
+            Some(4..body.len())
+        }
+    }
+
+    fn radiotap_data_frame(flags: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            // radiotap with Flags field of FCS
+            0x00, 0x00, 0x09, 0x00, 0x02, 0x00, 0x00, 0x00, 0x10,
+        ];
+        // QoS data from DS
+        packet.extend_from_slice(&[0x88, flags | IEEE80211_FLAGS_FROM_DS, 0x00, 0x00]);
+        packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x01]); // addr1: dst
+        packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x0a]); // addr2: bssid
+        packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x02]); // addr3: src
+        packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]); // sequence and qos control
+        packet.extend_from_slice(body);
+        packet.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]); // fcs
+        packet
+    }
+
+    #[test]
+    fn decapsulate_data_frame() {
+        let mut body = vec![0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00];
+        body.extend_from_slice(&[0x45; 20]);
+        let mut packet = radiotap_data_frame(0, &body);
+
+        let range = decapsulate_radiotap(&mut packet, None).unwrap();
+        let eth = &packet[range];
+        assert_eq!(eth.len(), ETH_HEADER_SIZE + 20);
+        assert_eq!(&eth[..6], &[0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(&eth[6..12], &[0x00, 0x00, 0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(&eth[12..14], &[0x08, 0x00]);
+        assert_eq!(&eth[14..], &[0x45; 20]);
+    }
+
+    #[test]
+    fn decapsulate_protected_frame() {
+        let mut body = vec![0x00; 4];
+        body.extend(
+            [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x86, 0xdd]
+                .iter()
+                .chain([0x60; 40].iter())
+                .map(|b| b ^ 0xff),
+        );
+        let mut packet = radiotap_data_frame(IEEE80211_FLAGS_PROTECTED, &body);
+        assert!(decapsulate_radiotap(&mut packet.clone(), None).is_none());
+
+        let range = decapsulate_radiotap(&mut packet, Some(&XorDecryptor)).unwrap();
+        let eth = &packet[range];
+        assert_eq!(&eth[12..14], &[0x86, 0xdd]);
+        assert_eq!(&eth[14..], &[0x60; 40]);
+    }
+}
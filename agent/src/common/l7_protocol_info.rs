@@ -25,10 +25,11 @@ use crate::{
     common::l7_protocol_log::LogCache,
     flow_generator::{
         protocol_logs::{
-            fastcgi::FastCGIInfo, pb_adapter::L7ProtocolSendLog, AmqpInfo, BrpcInfo, DnsInfo,
-            DubboInfo, HttpInfo, KafkaInfo, MemcachedInfo, MongoDBInfo, MqttInfo, MysqlInfo,
-            NatsInfo, OpenWireInfo, OracleInfo, PostgreInfo, PulsarInfo, RedisInfo, SofaRpcInfo,
-            SomeIpInfo, TarsInfo, TlsInfo, ZmtpInfo,
+            fastcgi::FastCGIInfo, pb_adapter::L7ProtocolSendLog, Amqp10Info, AmqpInfo, BrpcInfo,
+            DiameterInfo, DnsInfo, DubboInfo, HttpInfo, KafkaInfo, KerberosInfo, MemcachedInfo,
+            ModbusInfo, MongoDBInfo, MqttInfo, MysqlInfo, NatsInfo, NfsInfo, OpenWireInfo,
+            OracleInfo, PostgreInfo, PulsarInfo, QuicInfo, RedisInfo, SmbInfo, SofaRpcInfo,
+            SomeIpInfo, StompInfo, TarsInfo, TlsInfo, WebSocketInfo, ZmtpInfo,
         },
         AppProtoHead, LogMessageType, Result,
     },
@@ -78,12 +79,21 @@ all_protocol_info!(
     NatsInfo(NatsInfo),
     PulsarInfo(PulsarInfo),
     ZmtpInfo(ZmtpInfo),
+    StompInfo(StompInfo),
     PostgreInfo(PostgreInfo),
     OpenWireInfo(OpenWireInfo),
     OracleInfo(OracleInfo),
     SofaRpcInfo(SofaRpcInfo),
     TlsInfo(TlsInfo),
     SomeIpInfo(SomeIpInfo),
+    QuicInfo(QuicInfo),
+    KerberosInfo(KerberosInfo),
+    SmbInfo(SmbInfo),
+    NfsInfo(NfsInfo),
+    WebSocketInfo(WebSocketInfo),
+    ModbusInfo(ModbusInfo),
+    Amqp10Info(Amqp10Info),
+    DiameterInfo(DiameterInfo),
     CustomInfo(CustomInfo),
     // add new protocol info below
 );
@@ -112,6 +122,12 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
         0
     }
 
+    // Size of the request or response message if known from the protocol header, e.g.
+    // Content-Length of HTTP, otherwise the captured payload size is used in L7 metrics.
+    fn get_payload_size(&self) -> Option<u32> {
+        None
+    }
+
     fn skip_send(&self) -> bool {
         false
     }
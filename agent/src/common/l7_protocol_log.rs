@@ -38,9 +38,10 @@ use crate::flow_generator::protocol_logs::plugin::custom_wrap::CustomWrapLog;
 use crate::flow_generator::protocol_logs::plugin::get_custom_log_parser;
 use crate::flow_generator::protocol_logs::sql::ObfuscateCache;
 use crate::flow_generator::protocol_logs::{
-    AmqpLog, BrpcLog, DnsLog, DubboLog, HttpLog, KafkaLog, MemcachedLog, MongoDBLog, MqttLog,
-    MysqlLog, NatsLog, OpenWireLog, OracleLog, PostgresqlLog, PulsarLog, RedisLog, SofaRpcLog,
-    SomeIpLog, TarsLog, TlsLog, ZmtpLog,
+    AmqpLog, BrpcLog, DiameterLog, DnsLog, DubboLog, HttpLog, KafkaLog, KerberosLog, MemcachedLog,
+    ModbusLog, MongoDBLog, MqttLog, MysqlLog, NatsLog, NfsLog, OpenWireLog, OracleLog,
+    PostgresqlLog, PulsarLog, QuicLog, RedisLog, SmbLog, SofaRpcLog, SomeIpLog, StompLog, TarsLog,
+    TlsLog, ZmtpLog,
 };
 
 use crate::flow_generator::{LogMessageType, Result};
@@ -182,9 +183,16 @@ impl_protocol_parser! {
         NATS(NatsLog),
         Pulsar(PulsarLog),
         ZMTP(ZmtpLog),
+        STOMP(StompLog),
         OpenWire(OpenWireLog),
         TLS(TlsLog),
         SomeIp(SomeIpLog),
+        QUIC(QuicLog),
+        Kerberos(KerberosLog),
+        SMB(SmbLog),
+        NFS(NfsLog),
+        Modbus(ModbusLog),
+        Diameter(DiameterLog),
         // add protocol below
     }
 }
@@ -257,6 +265,12 @@ pub trait L7ProtocolParserInterface {
     fn parsable_on_udp(&self) -> bool {
         true
     }
+    // l4是sctp时是否解析，用于快速过滤协议
+    // ==============================
+    // whether l4 is parsed when sctp, use for quickly protocol filter
+    fn parsable_on_sctp(&self) -> bool {
+        false
+    }
 
     // is parse default? use for config init.
     fn parse_default(&self) -> bool {
@@ -496,6 +510,9 @@ pub fn get_parse_bitmap(protocol: IpProtocol, l7_enabled: L7ProtocolBitmap) -> L
                 IpProtocol::UDP if i.parsable_on_udp() => {
                     bitmap.set_enabled(i.protocol());
                 }
+                IpProtocol::SCTP if i.parsable_on_sctp() => {
+                    bitmap.set_enabled(i.protocol());
+                }
                 _ => {}
             }
         }
@@ -55,6 +55,8 @@ pub struct LookupKey {
     pub backward_matched: Option<MatchedField>,
     pub fast_index: usize,
     pub tunnel_id: u32,
+    pub vlan: u16,
+    pub outer_vlan: u16,
     /********** For NAT (currently only TOA) ***********/
     pub dst_nat_port: u16,
     pub src_nat_port: u16,
@@ -105,6 +107,8 @@ impl Default for LookupKey {
             backward_matched: None,
             fast_index: 0,
             tunnel_id: 0,
+            vlan: 0,
+            outer_vlan: 0,
             src_nat_source: TapPort::NAT_SOURCE_NONE,
             src_nat_ip: Ipv4Addr::UNSPECIFIED.into(),
             src_nat_port: 0,
@@ -131,6 +135,7 @@ impl LookupKey {
         dst_epc: u16,
         src_port: u16,
         dst_port: u16,
+        vlan: u16,
     ) {
         f.set(MatchedFlag::CaptureNetworkType, u16::from(tap_type));
         f.set(MatchedFlag::Proto, u8::from(proto) as u16);
@@ -140,6 +145,7 @@ impl LookupKey {
         f.set(MatchedFlag::DstEpc, dst_epc);
         f.set(MatchedFlag::SrcPort, src_port);
         f.set(MatchedFlag::DstPort, dst_port);
+        f.set(MatchedFlag::Vlan, vlan);
     }
 
     pub fn generate_matched_field(&mut self, src_epc: u16, dst_epc: u16) {
@@ -163,6 +169,7 @@ impl LookupKey {
             dst_epc,
             self.src_port,
             self.dst_port,
+            self.vlan,
         );
         Self::set_matched_field(
             self.backward_matched.as_mut().unwrap(),
@@ -174,6 +181,7 @@ impl LookupKey {
             src_epc,
             self.dst_port,
             self.src_port,
+            self.vlan,
         );
     }
 
@@ -210,6 +218,10 @@ impl LookupKey {
         self.proto == IpProtocol::UDP
     }
 
+    pub fn is_sctp(&self) -> bool {
+        self.proto == IpProtocol::SCTP
+    }
+
     pub fn is_ipv4(&self) -> bool {
         self.eth_type == EthernetType::IPV4
     }
@@ -30,6 +30,7 @@ pub enum MatchedFlag {
     DstPort,
     Proto,
     CaptureNetworkType,
+    Vlan,
 }
 
 #[derive(Clone, Debug)]
@@ -141,13 +142,13 @@ impl fmt::Display for MatchedField {
 #[repr(C)]
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct MatchedFieldN<const N: usize> {
-    // split up because [u8; 2 * N + 10] is not valid
+    // split up because [u8; 2 * N + MATCHED_FIELD_OTHER_SIZE] is not valid
     src_ip: [u8; N],
     dst_ip: [u8; N],
-    others: [u8; 10],
+    others: [u8; MATCHED_FIELD_OTHER_SIZE],
 }
 
-const MATCHED_FIELD_OTHER_SIZE: usize = 10;
+const MATCHED_FIELD_OTHER_SIZE: usize = 13;
 
 impl<const N: usize> Default for MatchedFieldN<N> {
     fn default() -> Self {
@@ -178,6 +179,7 @@ impl<const N: usize> MatchedFieldN<N> {
             MatchedFlag::DstPort => 6,
             MatchedFlag::Proto => 8,
             MatchedFlag::CaptureNetworkType => 9,
+            MatchedFlag::Vlan => 11,
             MatchedFlag::SrcIp | MatchedFlag::DstIp => 0,
         }
     }
@@ -188,10 +190,12 @@ impl<const N: usize> MatchedFieldN<N> {
             MatchedFlag::SrcEpc
             | MatchedFlag::DstEpc
             | MatchedFlag::SrcPort
-            | MatchedFlag::DstPort => {
+            | MatchedFlag::DstPort
+            | MatchedFlag::CaptureNetworkType
+            | MatchedFlag::Vlan => {
                 u16::from_le_bytes(*<&[u8; 2]>::try_from(&self.others[offset..offset + 2]).unwrap())
             }
-            MatchedFlag::Proto | MatchedFlag::CaptureNetworkType => self.others[offset] as u16,
+            MatchedFlag::Proto => self.others[offset] as u16,
             _ => unimplemented!(),
         }
     }
@@ -202,12 +206,12 @@ impl<const N: usize> MatchedFieldN<N> {
             MatchedFlag::SrcEpc
             | MatchedFlag::DstEpc
             | MatchedFlag::SrcPort
-            | MatchedFlag::DstPort => {
+            | MatchedFlag::DstPort
+            | MatchedFlag::CaptureNetworkType
+            | MatchedFlag::Vlan => {
                 self.others[offset..offset + 2].copy_from_slice(value.to_le_bytes().as_slice())
             }
-            MatchedFlag::Proto | MatchedFlag::CaptureNetworkType => {
-                self.others[offset] = value as u8
-            }
+            MatchedFlag::Proto => self.others[offset] = value as u8,
             _ => unimplemented!(),
         }
     }
@@ -219,7 +223,7 @@ impl<const N: usize> MatchedFieldN<N> {
     pub fn set_bits(&mut self, bits: &Vec<usize>) {
         self.src_ip = [0; N];
         self.dst_ip = [0; N];
-        self.others = [0; 10];
+        self.others = [0; MATCHED_FIELD_OTHER_SIZE];
         for b in bits {
             let b = *b;
             if b < 8 * N {
@@ -227,7 +231,7 @@ impl<const N: usize> MatchedFieldN<N> {
             } else if b < 8 * 2 * N {
                 let b = b - 8 * N;
                 self.dst_ip[b >> 3] = 1 << (b & 7);
-            } else if b < 8 * (2 * N + 10) {
+            } else if b < 8 * (2 * N + MATCHED_FIELD_OTHER_SIZE) {
                 let b = b - 8 * 2 * N;
                 self.others[b >> 3] = 1 << (b & 7);
             } else {
@@ -288,7 +292,7 @@ impl<const N: usize> MatchedFieldN<N> {
         } else if offset < 8 * 2 * N {
             let offset = offset - 8 * N;
             self.dst_ip[offset >> 3] & (1 << (offset & 7)) == 0
-        } else if offset < 8 * (2 * N + 10) {
+        } else if offset < 8 * (2 * N + MATCHED_FIELD_OTHER_SIZE) {
             let offset = offset - 8 * 2 * N;
             self.others[offset >> 3] & (1 << (offset & 7)) == 0
         } else {
@@ -462,7 +466,7 @@ mod tests {
         );
 
         let matched = new_matched_field(
-            0xFF,
+            0x3FF,
             0x7,
             "172.22.12.20".parse().unwrap(),
             "124.15.214.2".parse().unwrap(),
@@ -471,7 +475,7 @@ mod tests {
         );
         assert_eq!(
             matched.get(MatchedFlag::CaptureNetworkType),
-            0xFF,
+            0x3FF,
             "MATCHED_TAP_TYPE error. {}",
             matched
         );
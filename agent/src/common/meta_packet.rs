@@ -16,12 +16,12 @@
 
 use std::any::Any;
 use std::fmt;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
 #[cfg(any(target_os = "linux", target_os = "android"))]
-use std::{error::Error, net::Ipv6Addr, ptr};
+use std::{error::Error, ptr};
 
 use bitflags::bitflags;
 use pnet::packet::{
@@ -143,6 +143,46 @@ bitflags! {
     pub struct EbpfFlags: u32 {
         const NONE = 0;
         const TLS = 1;
+        const UNIX_SOCKET = 1 << 1;
+    }
+}
+
+const IPV4_FRAG_OFFSET_MASK: u16 = 0x1FFF;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IpFragment {
+    pub id: u32,
+    pub offset: u16, // in bytes of the IP payload
+    pub more_fragments: bool,
+}
+
+// Reference RFC 8200 section 4, chains longer than the limit are not walked
+const IPV6_EXT_HEADER_LIMIT: usize = 8;
+const IPV6_ROUTING_TYPE_OFFSET: usize = 2;
+const IPV6_ROUTING_TYPE_0: u8 = 0; // deprecated by RFC 5095
+
+bitflags! {
+    // extension headers in the IPv6 header chain and anomalies of the chain
+    #[derive(Default)]
+    pub struct Ipv6ExtHeaders: u16 {
+        const HOP_BY_HOP = 1 << 0;
+        const ROUTING = 1 << 1;
+        const FRAGMENT = 1 << 2;
+        const DESTINATION = 1 << 3;
+        const AH = 1 << 4;
+        const ESP = 1 << 5;
+
+        const HOP_BY_HOP_NOT_FIRST = 1 << 8;
+        const ROUTING_TYPE_0 = 1 << 9;
+        const REPEATED = 1 << 10;
+        const TOO_MANY = 1 << 11;
+        const TRUNCATED = 1 << 12;
+
+        const UNUSUAL = Self::HOP_BY_HOP_NOT_FIRST.bits
+            | Self::ROUTING_TYPE_0.bits
+            | Self::REPEATED.bits
+            | Self::TOO_MANY.bits
+            | Self::TRUNCATED.bits;
     }
 }
 
@@ -197,6 +237,10 @@ pub struct MetaPacket<'a> {
 
     pub offset_ipv6_last_option: u16,
     pub offset_ipv6_fragment_option: u16,
+    pub ipv6_ext_headers: Ipv6ExtHeaders,
+    pub ip_fragment: Option<IpFragment>,
+    // 1 in N packets or flows is captured, 0 means not sampled
+    pub sampling_rate: u32,
 
     pub header_type: HeaderType,
     // 读取时不要直接用这个字段，用MetaPacket.GetPktSize()
@@ -219,6 +263,8 @@ pub struct MetaPacket<'a> {
     pub signal_source: SignalSource,
     pub payload_len: u16,
     pub vlan: u16,
+    // S-TAG of QinQ packets, `vlan` keeps the C-TAG
+    pub outer_vlan: u16,
     pub is_active_service: bool,
     pub queue_hash: u8,
 
@@ -285,6 +331,10 @@ impl<'a> MetaPacket<'a> {
         self.ebpf_flags.contains(EbpfFlags::TLS)
     }
 
+    pub fn is_unix_socket(&self) -> bool {
+        self.ebpf_flags.contains(EbpfFlags::UNIX_SOCKET)
+    }
+
     pub fn empty() -> MetaPacket<'a> {
         MetaPacket {
             ..Default::default()
@@ -430,71 +480,115 @@ impl<'a> MetaPacket<'a> {
         let original_offset = ETH_HEADER_SIZE + IPV6_HEADER_SIZE + l2_opt_size;
         let mut option_offset = original_offset;
         self.next_header = next_header;
-        let mut size_checker = packet.len() as isize - option_offset as isize;
-        loop {
-            if let Ok(header) = IpProtocol::try_from(next_header) {
-                match header {
-                    IpProtocol::AH => {
-                        if size_checker < 2 {
-                            break;
-                        }
-                        self.offset_ipv6_last_option = option_offset as u16;
-                        next_header = packet[option_offset];
-                        let length = (packet[option_offset + 1] as usize + 2) * 4;
-                        option_offset += length;
-                        size_checker -= length as isize;
-                        if size_checker < 0 {
-                            break;
-                        }
-                        continue;
+        self.ipv6_ext_headers = Ipv6ExtHeaders::empty();
+        for count in 0..=IPV6_EXT_HEADER_LIMIT {
+            let remain = packet.len() as isize - option_offset as isize;
+            let header = IpProtocol::from(next_header);
+            let (flag, length) = match header {
+                IpProtocol::AH => {
+                    if remain < 2 {
+                        self.ipv6_ext_headers |= Ipv6ExtHeaders::TRUNCATED;
+                        break;
                     }
-                    IpProtocol::IPV6_DESTINATION
-                    | IpProtocol::IPV6_HOP_BY_HOP
-                    | IpProtocol::IPV6_ROUTING => {
-                        size_checker -= 8;
-                        if size_checker < 0 {
-                            break;
-                        }
-                        self.offset_ipv6_last_option = option_offset as u16;
-                        next_header = packet[option_offset];
-                        let length = packet[option_offset + 1] as usize;
-                        option_offset += length * 8 + 8;
-                        size_checker -= length as isize * 8;
-                        if size_checker < 0 {
-                            break;
-                        }
-                        continue;
+                    (
+                        Ipv6ExtHeaders::AH,
+                        (packet[option_offset + 1] as usize + 2) * 4,
+                    )
+                }
+                IpProtocol::IPV6_DESTINATION
+                | IpProtocol::IPV6_HOP_BY_HOP
+                | IpProtocol::IPV6_ROUTING => {
+                    if remain < 8 {
+                        self.ipv6_ext_headers |= Ipv6ExtHeaders::TRUNCATED;
+                        break;
                     }
-                    IpProtocol::IPV6_FRAGMENT => {
-                        size_checker -= 8;
-                        if size_checker < 0 {
-                            break;
+                    let flag = match header {
+                        IpProtocol::IPV6_HOP_BY_HOP if count > 0 => {
+                            Ipv6ExtHeaders::HOP_BY_HOP | Ipv6ExtHeaders::HOP_BY_HOP_NOT_FIRST
                         }
-                        self.offset_ipv6_last_option = option_offset as u16;
-                        self.offset_ipv6_fragment_option = option_offset as u16;
-                        next_header = packet[option_offset];
-                        option_offset += 8;
-                        continue;
-                    }
-                    IpProtocol::ICMPV6 => {
-                        return (next_header, option_offset - original_offset);
-                    }
-                    IpProtocol::ESP => {
-                        self.offset_ipv6_last_option = option_offset as u16;
-                        option_offset += size_checker as usize;
-                        return (next_header, option_offset - original_offset);
+                        IpProtocol::IPV6_HOP_BY_HOP => Ipv6ExtHeaders::HOP_BY_HOP,
+                        IpProtocol::IPV6_ROUTING
+                            if packet[option_offset + IPV6_ROUTING_TYPE_OFFSET]
+                                == IPV6_ROUTING_TYPE_0 =>
+                        {
+                            Ipv6ExtHeaders::ROUTING | Ipv6ExtHeaders::ROUTING_TYPE_0
+                        }
+                        IpProtocol::IPV6_ROUTING => Ipv6ExtHeaders::ROUTING,
+                        _ => Ipv6ExtHeaders::DESTINATION,
+                    };
+                    (flag, packet[option_offset + 1] as usize * 8 + 8)
+                }
+                IpProtocol::IPV6_FRAGMENT => {
+                    if remain < IPV6_FRAGMENT_LEN as isize {
+                        self.ipv6_ext_headers |= Ipv6ExtHeaders::TRUNCATED;
+                        break;
                     }
-                    _ => (),
+                    self.offset_ipv6_fragment_option = option_offset as u16;
+                    (Ipv6ExtHeaders::FRAGMENT, IPV6_FRAGMENT_LEN)
                 }
+                IpProtocol::ESP => {
+                    self.ipv6_ext_headers |= Ipv6ExtHeaders::ESP;
+                    self.offset_ipv6_last_option = option_offset as u16;
+                    option_offset += remain.max(0) as usize;
+                    return (next_header, option_offset - original_offset);
+                }
+                // upper layer or unknown headers
+                _ => return (next_header, option_offset - original_offset),
+            };
+            if count == IPV6_EXT_HEADER_LIMIT {
+                self.ipv6_ext_headers |= Ipv6ExtHeaders::TOO_MANY;
+                break;
+            }
+            // destination options may appear twice, before routing and upper layer headers
+            let header_flag = flag - Ipv6ExtHeaders::UNUSUAL;
+            if self.ipv6_ext_headers.contains(header_flag)
+                && header_flag != Ipv6ExtHeaders::DESTINATION
+            {
+                self.ipv6_ext_headers |= Ipv6ExtHeaders::REPEATED;
             }
-            // header types unknown or not matched
-            return (next_header, option_offset - original_offset);
+            self.ipv6_ext_headers |= flag;
+            self.offset_ipv6_last_option = option_offset as u16;
+            next_header = packet[option_offset];
+            if remain < length as isize {
+                self.ipv6_ext_headers |= Ipv6ExtHeaders::TRUNCATED;
+                break;
+            }
+            option_offset += length;
         }
+        // the chain is not walked completely, the upper layer header is unknown
         self.offset_ipv6_last_option = 0;
         self.offset_ipv6_fragment_option = 0;
         (packet[IPV6_PROTO_OFFSET + l2_opt_size], 0)
     }
 
+    fn update_ipv6_fragment(&mut self, packet: &[u8]) {
+        let offset = self.offset_ipv6_fragment_option as usize;
+        if offset == 0 {
+            return;
+        }
+        let frag = read_u16_be(&packet[offset + IPV6_FRAG_OFFSET..]);
+        let fragment = IpFragment {
+            id: read_u32_be(&packet[offset + IPV6_FRAG_ID_OFFSET..]),
+            offset: frag & 0xFFF8,
+            more_fragments: frag & IPV6_FRAG_MORE_FRAGMENT != 0,
+        };
+        // atomic fragments are not fragmented actually, see RFC 6946
+        if fragment.offset != 0 || fragment.more_fragments {
+            self.ip_fragment = Some(fragment);
+        }
+    }
+
+    // IP payload after all IP options or extension headers, None if it is not captured completely
+    pub fn get_l3_payload(&self) -> Option<&[u8]> {
+        let ip_header_size = match self.lookup_key.eth_type {
+            EthernetType::IPV4 => IPV4_HEADER_SIZE,
+            EthernetType::IPV6 => IPV6_HEADER_SIZE,
+            _ => return None,
+        };
+        let start = ETH_HEADER_SIZE + ip_header_size + self.l2_l3_opt_size as usize;
+        self.raw.as_ref()?.get(start..self.packet_len as usize)
+    }
+
     pub fn get_pkt_size(&self) -> u16 {
         if self.packet_len < u16::MAX as u32 {
             self.packet_len as u16
@@ -518,14 +612,32 @@ impl<'a> MetaPacket<'a> {
         }
     }
 
-    // 目前仅支持获取UDP或TCP的Payload
+    // 目前仅支持获取UDP、TCP或SCTP的Payload
     pub fn get_l4_payload(&self) -> Option<&[u8]> {
-        if self.lookup_key.proto != IpProtocol::TCP && self.lookup_key.proto != IpProtocol::UDP {
+        if self.lookup_key.proto != IpProtocol::TCP
+            && self.lookup_key.proto != IpProtocol::UDP
+            && self.lookup_key.proto != IpProtocol::SCTP
+        {
             return None;
         }
         if self.tap_port.is_from(TapPort::FROM_EBPF) {
             return Some(&self.raw_from_ebpf[self.raw_from_ebpf_offset..]);
         }
+        if let ProtocolData::SctpData(sctp_data) = &self.protocol_data {
+            // the user data of the first DATA chunk, without chunk padding or bundled chunks
+            if sctp_data.data_len == 0 {
+                return None;
+            }
+            let start = self.header_type.min_packet_size()
+                + self.l2_l3_opt_size as usize
+                + sctp_data.data_offset as usize;
+            let raw = self.raw.as_ref()?;
+            if raw.len() <= start {
+                return None;
+            }
+            let end = raw.len().min(start + sctp_data.data_len as usize);
+            return Some(&raw[start..end]);
+        }
 
         let packet_header_size = self.header_type.min_packet_size()
             + self.l2_l3_opt_size as usize
@@ -583,7 +695,8 @@ impl<'a> MetaPacket<'a> {
             .map_err(|e| {
                 error::Error::ParsePacketFailed(format!("parse eth_type failed: {}", e))
             })?;
-        if eth_type == EthernetType::DOT1Q {
+        // the outer tag of 802.1ad QinQ is S-TAG (0x88a8) or DOT1Q
+        if eth_type == EthernetType::DOT1Q || eth_type == EthernetType::QINQ {
             vlan_tag_size = VLAN_HEADER_SIZE;
             size_checker -= VLAN_HEADER_SIZE as isize;
             if size_checker < 0 {
@@ -605,6 +718,7 @@ impl<'a> MetaPacket<'a> {
                 }
                 let vlan_tag =
                     read_u16_be(&packet[FIELD_OFFSET_ETH_TYPE + VLAN_HEADER_SIZE + ETH_TYPE_LEN..]);
+                self.outer_vlan = self.vlan;
                 self.vlan = vlan_tag & VLAN_ID_MASK;
                 eth_type = EthernetType::from(read_u16_be(
                     &packet[FIELD_OFFSET_ETH_TYPE + vlan_tag_size..],
@@ -612,6 +726,8 @@ impl<'a> MetaPacket<'a> {
             }
         }
         self.lookup_key.eth_type = eth_type;
+        self.lookup_key.vlan = self.vlan;
+        self.lookup_key.outer_vlan = self.outer_vlan;
         self.lookup_key.src_mac =
             MacAddr::try_from(&packet[FIELD_OFFSET_SA..FIELD_OFFSET_SA + MAC_ADDR_LEN]).unwrap();
         self.lookup_key.dst_mac =
@@ -685,6 +801,13 @@ impl<'a> MetaPacket<'a> {
                     return Ok(());
                 }
                 self.l3_payload_len = size_checker as u16;
+                self.update_ipv6_fragment(packet);
+                if matches!(self.ip_fragment, Some(f) if f.offset != 0) {
+                    // fragment
+                    self.npb_ignore_l4 = true;
+                    self.l4_payload_len = self.l3_payload_len;
+                    return Ok(());
+                }
             }
             EthernetType::IPV4 => {
                 size_checker -= HeaderType::Ipv4.min_header_size() as isize;
@@ -737,7 +860,14 @@ impl<'a> MetaPacket<'a> {
                 self.lookup_key.proto = ip_protocol;
 
                 let frag = read_u16_be(&packet[FIELD_OFFSET_FRAG + vlan_tag_size..]);
-                if frag & 0xFFF != 0 {
+                if frag & (IPV4_FRAG_MORE_FRAGMENT | IPV4_FRAG_OFFSET_MASK) != 0 {
+                    self.ip_fragment = Some(IpFragment {
+                        id: read_u16_be(&packet[FIELD_OFFSET_ID + vlan_tag_size..]) as u32,
+                        offset: (frag & IPV4_FRAG_OFFSET_MASK) << 3,
+                        more_fragments: frag & IPV4_FRAG_MORE_FRAGMENT != 0,
+                    });
+                }
+                if frag & IPV4_FRAG_OFFSET_MASK != 0 {
                     // fragment
                     self.header_type = HeaderType::Ipv4;
                     self.npb_ignore_l4 = true;
@@ -761,6 +891,7 @@ impl<'a> MetaPacket<'a> {
                 let icmp_type_index = FIELD_OFFSET_ICMP_TYPE_CODE + self.l2_l3_opt_size as usize;
                 let mut icmp_data = IcmpData::default();
                 icmp_data.icmp_type = packet[icmp_type_index];
+                icmp_data.icmp_code = packet[icmp_type_index + 1];
 
                 match IcmpType::new(
                     packet[FIELD_OFFSET_ICMP_TYPE_CODE + self.l2_l3_opt_size as usize],
@@ -785,6 +916,18 @@ impl<'a> MetaPacket<'a> {
                     }
                     _ => (),
                 }
+                match IcmpType::new(icmp_data.icmp_type) {
+                    IcmpTypes::DestinationUnreachable
+                    | IcmpTypes::SourceQuench
+                    | IcmpTypes::RedirectMessage
+                    | IcmpTypes::TimeExceeded
+                    | IcmpTypes::ParameterProblem => {
+                        icmp_data.error = packet
+                            .get(icmp_type_index + ICMP_HEADER_SIZE..)
+                            .and_then(|invoking| IcmpError::decode(invoking, false));
+                    }
+                    _ => (),
+                }
                 self.protocol_data = ProtocolData::IcmpData(icmp_data);
                 self.payload_len =
                     (self.packet_len as usize - (packet.len() - size_checker as usize)) as u16;
@@ -914,8 +1057,20 @@ impl<'a> MetaPacket<'a> {
                             icmp_data.echo_id_seq = read_u32_be(&packet[icmpv6_type_index + 4..]);
                             self.lookup_key.direction = PacketDirection::ServerToClient;
                         }
+                        Icmpv6Types::DestinationUnreachable
+                        | Icmpv6Types::PacketTooBig
+                        | Icmpv6Types::TimeExceeded
+                        | Icmpv6Types::ParameterProblem => {
+                            icmp_data.error = packet
+                                .get(icmpv6_type_index + ICMP_HEADER_SIZE..)
+                                .and_then(|invoking| IcmpError::decode(invoking, true));
+                        }
                         _ => {}
                     }
+                    icmp_data.icmp_code = packet
+                        .get(icmpv6_type_index + 1)
+                        .copied()
+                        .unwrap_or_default();
                     // 忽略link-local address并只考虑ND reply, i.e. neighbour advertisement
                     if let IpAddr::V6(ip) = self.lookup_key.src_ip {
                         self.nd_reply_or_arp_request =
@@ -927,6 +1082,29 @@ impl<'a> MetaPacket<'a> {
                     (self.packet_len - (packet.len() - size_checker as usize) as u32) as u16;
                 return Ok(());
             }
+            IpProtocol::SCTP => {
+                // the common header shares port offsets with TCP and UDP, and is followed by chunks
+                let l4_offset = packet.len() - size_checker as usize;
+                size_checker -= SCTP_HEADER_SIZE as isize;
+                if size_checker < 0 {
+                    self.npb_ignore_l4 = true;
+                    return Ok(());
+                }
+                self.l4_opt_size = SCTP_HEADER_SIZE as u32;
+                self.l4_payload_len = self
+                    .packet_len
+                    .saturating_sub((packet.len() - size_checker as usize) as u32)
+                    as u16;
+                // exclude capture padding beyond the IP total length
+                let l4_end = packet
+                    .len()
+                    .min(self.packet_len as usize)
+                    .max(l4_offset + SCTP_HEADER_SIZE);
+                let sctp_data = SctpData::decode(&packet[..l4_end], l4_offset);
+                // only user data carried by DATA chunks is treated as payload
+                self.payload_len = sctp_data.data_len;
+                self.protocol_data = ProtocolData::SctpData(sctp_data);
+            }
             _ => {
                 self.payload_len =
                     (self.packet_len - (packet.len() - size_checker as usize) as u32) as u16;
@@ -1099,6 +1277,9 @@ impl<'a> MetaPacket<'a> {
         } else {
             EbpfFlags::NONE
         };
+        if data.is_unix {
+            packet.ebpf_flags |= EbpfFlags::UNIX_SOCKET;
+        }
         packet.segment_flags = SegmentFlags::from(data.msg_type);
 
         // 目前只有 go uprobe http2 的方向判断能确保准确
@@ -1177,9 +1358,13 @@ impl<'a> MetaPacket<'a> {
     }
 
     pub fn to_owned_segment(&self) -> Box<dyn Segment> {
+        Box::new(self.to_owned_packet())
+    }
+
+    pub fn to_owned_packet(&self) -> MetaPacket<'static> {
         let raw = self.raw.as_ref().unwrap().to_vec();
 
-        Box::new(MetaPacket {
+        MetaPacket {
             lookup_key: self.lookup_key.clone(),
             raw: Some(RawPacket::from(raw)),
             packet_len: self.packet_len,
@@ -1194,8 +1379,9 @@ impl<'a> MetaPacket<'a> {
             header_type: self.header_type,
             l2_l3_opt_size: self.l2_l3_opt_size,
             l4_opt_size: self.l4_opt_size,
+            sampling_rate: self.sampling_rate,
             ..Default::default()
-        })
+        }
     }
 }
 
@@ -1338,13 +1524,173 @@ pub struct MetaPacketTcpHeader {
 #[derive(Clone, Debug, Default)]
 pub struct IcmpData {
     pub icmp_type: u8,
+    pub icmp_code: u8,
     pub echo_id_seq: u32,
+    // 差错报文中携带的原始报文五元组
+    pub error: Option<IcmpError>,
+}
+
+// The invoking packet carried by ICMP error messages, which is sent by the peer receiving the error
+#[derive(Clone, Debug, PartialEq)]
+pub struct IcmpError {
+    pub proto: IpProtocol,
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+impl IcmpError {
+    const IPV4_PROTO_OFFSET: usize = 9;
+    const IPV4_SRC_OFFSET: usize = 12;
+    const IPV4_DST_OFFSET: usize = 16;
+    const IPV6_NEXT_HEADER_OFFSET: usize = 6;
+    const IPV6_SRC_OFFSET: usize = 8;
+    const IPV6_DST_OFFSET: usize = 24;
+
+    // `invoking` is the ip header and the first 8 bytes of payload of the original packet
+    pub fn decode(invoking: &[u8], is_ipv6: bool) -> Option<Self> {
+        let (proto, src_ip, dst_ip, l4_offset): (_, IpAddr, IpAddr, _) = if is_ipv6 {
+            if invoking.len() < IPV6_HEADER_SIZE || invoking[0] >> 4 != 6 {
+                return None;
+            }
+            let src = <[u8; 16]>::try_from(&invoking[Self::IPV6_SRC_OFFSET..Self::IPV6_DST_OFFSET]);
+            let dst = <[u8; 16]>::try_from(&invoking[Self::IPV6_DST_OFFSET..IPV6_HEADER_SIZE]);
+            (
+                IpProtocol::from(invoking[Self::IPV6_NEXT_HEADER_OFFSET]),
+                Ipv6Addr::from(src.unwrap()).into(),
+                Ipv6Addr::from(dst.unwrap()).into(),
+                IPV6_HEADER_SIZE,
+            )
+        } else {
+            let ihl = ((invoking.first()? & 0xf) << 2) as usize;
+            if invoking[0] >> 4 != 4 || ihl < IPV4_HEADER_SIZE || invoking.len() < ihl {
+                return None;
+            }
+            (
+                IpProtocol::from(invoking[Self::IPV4_PROTO_OFFSET]),
+                Ipv4Addr::from(read_u32_be(&invoking[Self::IPV4_SRC_OFFSET..])).into(),
+                Ipv4Addr::from(read_u32_be(&invoking[Self::IPV4_DST_OFFSET..])).into(),
+                ihl,
+            )
+        };
+        let (src_port, dst_port) = match proto {
+            IpProtocol::TCP | IpProtocol::UDP | IpProtocol::SCTP
+                if invoking.len() >= l4_offset + 4 =>
+            {
+                (
+                    read_u16_be(&invoking[l4_offset..]),
+                    read_u16_be(&invoking[l4_offset + 2..]),
+                )
+            }
+            _ => (0, 0),
+        };
+        Some(Self {
+            proto,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+        })
+    }
+}
+
+bitflags! {
+    // bit index is the SCTP chunk type, see RFC 9260 section 3.2
+    #[derive(Default)]
+    pub struct SctpChunkFlags: u16 {
+        const DATA = 1 << 0;
+        const INIT = 1 << 1;
+        const INIT_ACK = 1 << 2;
+        const SACK = 1 << 3;
+        const HEARTBEAT = 1 << 4;
+        const HEARTBEAT_ACK = 1 << 5;
+        const ABORT = 1 << 6;
+        const SHUTDOWN = 1 << 7;
+        const SHUTDOWN_ACK = 1 << 8;
+        const ERROR = 1 << 9;
+        const COOKIE_ECHO = 1 << 10;
+        const COOKIE_ACK = 1 << 11;
+        const SHUTDOWN_COMPLETE = 1 << 14;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SctpData {
+    pub verification_tag: u32,
+    pub chunks: SctpChunkFlags,
+    // ABORT or SHUTDOWN_COMPLETE with the T bit set carries the sender's own tag
+    pub tag_reflected: bool,
+    // initiate tag of INIT or INIT_ACK, the tag the peer must use afterwards
+    pub initiate_tag: u32,
+    // TSN and payload protocol identifier of the first DATA chunk
+    pub tsn: u32,
+    pub ppid: u32,
+    // offset of the first DATA chunk's user data from the start of the SCTP header
+    pub data_offset: u16,
+    pub data_len: u16,
+}
+
+impl SctpData {
+    const CHUNK_TYPE_DATA: u8 = 0;
+    const CHUNK_TYPE_INIT: u8 = 1;
+    const CHUNK_TYPE_INIT_ACK: u8 = 2;
+    const CHUNK_TYPE_ABORT: u8 = 6;
+    const CHUNK_TYPE_SHUTDOWN_COMPLETE: u8 = 14;
+
+    const CHUNK_FLAG_T: u8 = 0x1;
+
+    fn decode(packet: &[u8], l4_offset: usize) -> Self {
+        let mut sctp_data = SctpData {
+            verification_tag: read_u32_be(&packet[l4_offset + 4..]),
+            ..Default::default()
+        };
+        let mut offset = l4_offset + SCTP_HEADER_SIZE;
+        while offset + SCTP_CHUNK_HEADER_SIZE <= packet.len() {
+            let chunk_type = packet[offset];
+            let chunk_flags = packet[offset + 1];
+            let chunk_len = read_u16_be(&packet[offset + 2..]) as usize;
+            if chunk_len < SCTP_CHUNK_HEADER_SIZE {
+                break;
+            }
+            if chunk_type < 16 {
+                sctp_data.chunks |= SctpChunkFlags::from_bits_truncate(1 << chunk_type);
+            }
+            match chunk_type {
+                Self::CHUNK_TYPE_DATA
+                    if sctp_data.data_len == 0
+                        && chunk_len > SCTP_DATA_CHUNK_HEADER_SIZE
+                        && offset + SCTP_DATA_CHUNK_HEADER_SIZE <= packet.len() =>
+                {
+                    sctp_data.tsn = read_u32_be(&packet[offset + 4..]);
+                    sctp_data.ppid = read_u32_be(&packet[offset + 12..]);
+                    sctp_data.data_offset =
+                        (offset - l4_offset + SCTP_DATA_CHUNK_HEADER_SIZE) as u16;
+                    sctp_data.data_len = (chunk_len - SCTP_DATA_CHUNK_HEADER_SIZE) as u16;
+                }
+                Self::CHUNK_TYPE_INIT | Self::CHUNK_TYPE_INIT_ACK
+                    if offset + SCTP_CHUNK_HEADER_SIZE + 4 <= packet.len() =>
+                {
+                    sctp_data.initiate_tag =
+                        read_u32_be(&packet[offset + SCTP_CHUNK_HEADER_SIZE..]);
+                }
+                Self::CHUNK_TYPE_ABORT | Self::CHUNK_TYPE_SHUTDOWN_COMPLETE => {
+                    sctp_data.tag_reflected = chunk_flags & Self::CHUNK_FLAG_T != 0;
+                }
+                _ => {}
+            }
+            // chunks are padded to 4 bytes
+            offset += (chunk_len + 3) & !3;
+        }
+        sctp_data
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum ProtocolData {
     TcpHeader(MetaPacketTcpHeader),
     IcmpData(IcmpData),
+    SctpData(SctpData),
 }
 
 impl Default for ProtocolData {
@@ -1380,4 +1726,169 @@ mod tests {
             pkt
         );
     }
+
+    #[test]
+    fn sctp_chunks() {
+        let mut packet = vec![
+            0x0f, 0x1c, 0x0f, 0x1c, // ports
+            0x11, 0x22, 0x33, 0x44, // verification tag
+            0x00, 0x00, 0x00, 0x00, // checksum
+            0x00, 0x03, 0x00, 0x15, // DATA, flags B|E, length 21
+            0x00, 0x00, 0x00, 0x07, // TSN
+            0x00, 0x00, 0x00, 0x00, // stream id and sequence
+            0x00, 0x00, 0x00, 0x2e, // PPID
+        ];
+        packet.extend_from_slice(b"hello\0\0\0");
+        packet.extend_from_slice(&[0x0e, 0x01, 0x00, 0x04]); // SHUTDOWN_COMPLETE with T bit
+
+        let sctp_data = SctpData::decode(&packet, 0);
+        assert_eq!(sctp_data.verification_tag, 0x11223344);
+        assert_eq!(
+            sctp_data.chunks,
+            SctpChunkFlags::DATA | SctpChunkFlags::SHUTDOWN_COMPLETE
+        );
+        assert!(sctp_data.tag_reflected);
+        assert_eq!(sctp_data.tsn, 7);
+        assert_eq!(sctp_data.ppid, 46);
+        let start = sctp_data.data_offset as usize;
+        assert_eq!(
+            &packet[start..start + sctp_data.data_len as usize],
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn qinq_vlans() {
+        let packet = vec![
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x02, // dst mac
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // src mac
+            0x88, 0xa8, 0x00, 0x64, // S-TAG 100
+            0x81, 0x00, 0x00, 0xc8, // C-TAG 200
+            0x08, 0x00, // ipv4
+            0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, //
+            0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, //
+            0x30, 0x39, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00, // udp 12345 -> 53
+        ];
+        let mut meta_packet = MetaPacket::default();
+        let len = packet.len();
+        assert!(meta_packet
+            .update(&packet[..], true, true, Duration::ZERO, len)
+            .is_ok());
+        assert_eq!(meta_packet.outer_vlan, 100);
+        assert_eq!(meta_packet.vlan, 200);
+        assert_eq!(meta_packet.vlan_tag_size as usize, 2 * VLAN_HEADER_SIZE);
+        assert_eq!(meta_packet.lookup_key.outer_vlan, 100);
+        assert_eq!(meta_packet.lookup_key.vlan, 200);
+        assert_eq!(meta_packet.lookup_key.eth_type, EthernetType::IPV4);
+        assert_eq!(meta_packet.lookup_key.dst_port, 53);
+    }
+
+    #[test]
+    fn ipv6_extension_headers() {
+        let mut packet = vec![
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x02, // dst mac
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // src mac
+            0x86, 0xdd, // ipv6
+            0x60, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x40, // next header hop-by-hop
+        ];
+        packet.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        packet.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        packet.extend_from_slice(&[
+            0x2b, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00, // hop-by-hop with PadN
+            0x2c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // routing type 0
+            0x11, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, // first fragment
+            0x30, 0x39, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00, // udp 12345 -> 53
+        ]);
+        let mut meta_packet = MetaPacket::default();
+        let len = packet.len();
+        assert!(meta_packet
+            .update(&packet[..], true, true, Duration::ZERO, len)
+            .is_ok());
+        assert_eq!(meta_packet.lookup_key.proto, IpProtocol::UDP);
+        assert_eq!(meta_packet.lookup_key.dst_port, 53);
+        assert_eq!(
+            meta_packet.ipv6_ext_headers,
+            Ipv6ExtHeaders::HOP_BY_HOP
+                | Ipv6ExtHeaders::ROUTING
+                | Ipv6ExtHeaders::ROUTING_TYPE_0
+                | Ipv6ExtHeaders::FRAGMENT
+        );
+
+        // non-first fragment is keyed without ports
+        packet[ETH_HEADER_SIZE + IPV6_HEADER_SIZE + 16 + IPV6_FRAG_OFFSET + 1] = 0xb8;
+        let mut meta_packet = MetaPacket::default();
+        assert!(meta_packet
+            .update(&packet[..], true, true, Duration::ZERO, len)
+            .is_ok());
+        assert_eq!(meta_packet.lookup_key.proto, IpProtocol::UDP);
+        assert_eq!(meta_packet.lookup_key.dst_port, 0);
+        assert!(meta_packet.npb_ignore_l4);
+
+        // the chain is not walked beyond the limit
+        let mut packet = packet[..ETH_HEADER_SIZE + IPV6_HEADER_SIZE].to_vec();
+        packet[IPV6_PROTO_OFFSET] = IpProtocol::IPV6_DESTINATION.into();
+        for _ in 0..=IPV6_EXT_HEADER_LIMIT {
+            packet.extend_from_slice(&[0x3c, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00]);
+        }
+        let mut meta_packet = MetaPacket::default();
+        let len = packet.len();
+        assert!(meta_packet
+            .update(&packet[..], true, true, Duration::ZERO, len)
+            .is_ok());
+        assert!(meta_packet
+            .ipv6_ext_headers
+            .contains(Ipv6ExtHeaders::TOO_MANY));
+        assert_eq!(meta_packet.lookup_key.proto, IpProtocol::IPV6_DESTINATION);
+    }
+
+    #[test]
+    fn icmp_destination_unreachable() {
+        let packet = vec![
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // dst mac
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x02, // src mac
+            0x08, 0x00, // ipv4
+            0x45, 0x00, 0x00, 0x38, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, //
+            0x0a, 0x00, 0x00, 0x02, 0x0a, 0x00, 0x00, 0x01, //
+            0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // port unreachable
+            0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, //
+            0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, //
+            0x30, 0x39, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00, // udp 12345 -> 53
+        ];
+        let mut meta_packet = MetaPacket::default();
+        let len = packet.len();
+        assert!(meta_packet
+            .update(&packet[..], true, true, Duration::ZERO, len)
+            .is_ok());
+        let ProtocolData::IcmpData(icmp_data) = &meta_packet.protocol_data else {
+            panic!("not icmp: {:?}", meta_packet.protocol_data);
+        };
+        assert_eq!(icmp_data.icmp_type, 3);
+        assert_eq!(icmp_data.icmp_code, 3);
+        assert_eq!(
+            icmp_data.error,
+            Some(IcmpError {
+                proto: IpProtocol::UDP,
+                src_ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+                dst_ip: Ipv4Addr::new(10, 0, 0, 2).into(),
+                src_port: 12345,
+                dst_port: 53,
+            })
+        );
+    }
+
+    #[test]
+    fn icmpv6_invoking_packet() {
+        let mut invoking = vec![0x60, 0x00, 0x00, 0x00, 0x00, 0x08, 0x06, 0x40];
+        invoking.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        invoking.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets());
+        invoking.extend_from_slice(&[0x1f, 0x90, 0xc3, 0x50]); // tcp 8080 -> 50000
+        let error = IcmpError::decode(&invoking, true).unwrap();
+        assert_eq!(error.proto, IpProtocol::TCP);
+        assert_eq!(error.src_ip, IpAddr::from(Ipv6Addr::LOCALHOST));
+        assert_eq!((error.src_port, error.dst_port), (8080, 50000));
+
+        // truncated header
+        assert!(IcmpError::decode(&invoking[..20], true).is_none());
+        assert!(IcmpError::decode(&invoking, false).is_none());
+    }
 }
@@ -21,6 +21,7 @@ pub mod endpoint;
 mod error;
 pub mod feature;
 pub mod flow;
+pub mod ieee80211;
 pub mod l7_protocol_info;
 pub mod l7_protocol_log;
 pub mod lookup_key;
@@ -85,9 +86,10 @@ pub enum FlowAclListenerId {
     Policy = 0,
     NpbBandWatcher = 1,
     EbpfDispatcher = 2,
+    NamespaceLimiter = 3,
     // There are multiple Dispatcher in Agent, and Dispatcher ID increases from FlowAclListenerId::Dispatcher.
     // FlowAclListenerId::Dispatcher must be the last one.
-    Dispatcher = 3,
+    Dispatcher = 4,
 }
 
 pub trait FlowAclListener: Send + Sync {
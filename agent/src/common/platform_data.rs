@@ -63,6 +63,7 @@ pub struct PlatformData {
     pub region_id: u32,
     pub pod_cluster_id: u32,
     pub pod_node_id: u32,
+    pub pod_ns_id: u32,
     pub if_type: IfType,
     pub device_type: u8,
     pub is_vip_interface: bool,
@@ -87,6 +88,7 @@ impl Default for PlatformData {
             region_id: 0,
             pod_cluster_id: 0,
             pod_node_id: 0,
+            pod_ns_id: 0,
             if_type: IfType::LAN,
             device_type: 0,
             is_vip_interface: false,
@@ -143,6 +145,7 @@ impl TryFrom<&agent::Interface> for PlatformData {
             region_id: p.region_id(),
             pod_cluster_id: p.pod_cluster_id(),
             pod_node_id: p.pod_node_id(),
+            pod_ns_id: p.pod_ns_id(),
             if_type: IfType::try_from(p.if_type() as u8).map_err(|e| {
                 Error::ParsePlatformData(format!(
                     "parse agent::Interface to platform data if_type failed: {}",
@@ -205,6 +208,7 @@ impl TryFrom<&trident::Interface> for PlatformData {
             region_id: p.region_id(),
             pod_cluster_id: p.pod_cluster_id(),
             pod_node_id: p.pod_node_id(),
+            pod_ns_id: p.pod_ns_id(),
             if_type: IfType::try_from(p.if_type() as u8).map_err(|e| {
                 Error::ParsePlatformData(format!(
                     "parse trident::Interface to platform data if_type failed: {}",
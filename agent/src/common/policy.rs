@@ -512,6 +512,7 @@ pub struct Acl {
     pub dst_port_ranges: Vec<PortRange>, // 0仅表示采集端口0
 
     pub proto: u16, // 256表示全采集, 0表示采集采集协议0
+    pub vlan: u16,  // 0表示全采集, 匹配QinQ的外层或内层VLAN
 
     pub npb_actions: Vec<NpbAction>,
 
@@ -665,6 +666,8 @@ impl Acl {
                         item.field.set(MatchedFlag::Proto, self.proto);
                         item.mask.set_mask(MatchedFlag::Proto, true);
                     }
+                    item.field.set(MatchedFlag::Vlan, self.vlan);
+                    item.mask.set_mask(MatchedFlag::Vlan, self.vlan > 0);
 
                     self.match_field.push(Arc::new(item));
                 }
@@ -738,6 +741,8 @@ impl Acl {
                         item.field.set(MatchedFlag::Proto, self.proto);
                         item.mask.set_mask(MatchedFlag::Proto, true);
                     }
+                    item.field.set(MatchedFlag::Vlan, self.vlan);
+                    item.mask.set_mask(MatchedFlag::Vlan, self.vlan > 0);
 
                     self.match_field6.push(Arc::new(item));
                 }
@@ -768,7 +773,7 @@ impl TryFrom<agent::FlowAcl> for Acl {
 
     fn try_from(a: agent::FlowAcl) -> Result<Self, Self::Error> {
         let tap_type = CaptureNetworkType::try_from(
-            (a.capture_network_type.unwrap_or_default() & 0xff) as u16,
+            (a.capture_network_type.unwrap_or_default() & 0xffff) as u16,
         );
         if tap_type.is_err() {
             return Err(format!(
@@ -825,6 +830,7 @@ impl TryFrom<agent::FlowAcl> for Acl {
             src_port_ranges: src_ports.unwrap().element().to_vec(),
             dst_port_ranges: dst_ports.unwrap().element().to_vec(),
             proto: (a.protocol.unwrap_or_default() & 0xffff) as u16,
+            vlan: (a.vlan.unwrap_or_default() & 0xfff) as u16,
             npb_actions: npb_actions.clone(),
             policy: Arc::new(PolicyData::new(npb_actions, a.id.unwrap_or_default())),
             ..Default::default()
@@ -837,7 +843,8 @@ impl TryFrom<trident::FlowAcl> for Acl {
     type Error = String;
 
     fn try_from(a: trident::FlowAcl) -> Result<Self, Self::Error> {
-        let tap_type = CaptureNetworkType::try_from((a.tap_type.unwrap_or_default() & 0xff) as u16);
+        let tap_type =
+            CaptureNetworkType::try_from((a.tap_type.unwrap_or_default() & 0xffff) as u16);
         if tap_type.is_err() {
             return Err(format!(
                 "Acl tap_type parse error: {:?}.\n",
@@ -893,6 +900,7 @@ impl TryFrom<trident::FlowAcl> for Acl {
             src_port_ranges: src_ports.unwrap().element().to_vec(),
             dst_port_ranges: dst_ports.unwrap().element().to_vec(),
             proto: (a.protocol.unwrap_or_default() & 0xffff) as u16,
+            vlan: (a.vlan.unwrap_or_default() & 0xfff) as u16,
             npb_actions: npb_actions.clone(),
             policy: Arc::new(PolicyData::new(npb_actions, a.id.unwrap_or_default())),
             ..Default::default()
@@ -902,11 +910,11 @@ impl TryFrom<trident::FlowAcl> for Acl {
 
 impl fmt::Display for Acl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Id:{} CaptureNetworkType:{} SrcGroups:{:?} DstGroups:{:?} SrcPortRange:[{}] DstPortRange:[{}] Proto:{} NpbActions:{}",
+        write!(f, "Id:{} CaptureNetworkType:{} SrcGroups:{:?} DstGroups:{:?} SrcPortRange:[{}] DstPortRange:[{}] Proto:{} Vlan:{} NpbActions:{}",
             self.id, self.tap_type, self.src_groups, self.dst_groups,
             self.src_port_ranges.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "),
             self.dst_port_ranges.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "),
-            self.proto, self.npb_actions.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(","))
+            self.proto, self.vlan, self.npb_actions.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(","))
     }
 }
 
@@ -101,6 +101,10 @@ impl Sendable for BoxedTaggedFlow {
         "l4_flow_log"
     }
 
+    fn flow_id(&self) -> Option<u64> {
+        Some(self.0.flow.flow_id)
+    }
+
     fn message_type(&self) -> SendMessageType {
         SendMessageType::TaggedFlow
     }
@@ -42,9 +42,13 @@ impl TapPort {
 
     // The higher the nat source value, the higher the priority.
     // NAT_SOURCE_VIP and NAT_SOURCE_CONTROLLER is obtained through policy query,
-    // NAT_SOURCE_TOA is obtained through TCP Options Address.
+    // NAT_SOURCE_TOA is obtained through TCP Options Address,
+    // NAT_SOURCE_IPVS is obtained through the kernel IPVS connection table,
+    // NAT_SOURCE_CONNTRACK is obtained through the kernel conntrack table.
     pub const NAT_SOURCE_NONE: u8 = 0;
+    pub const NAT_SOURCE_IPVS: u8 = 1;
     pub const NAT_SOURCE_VIP: u8 = 2;
+    pub const NAT_SOURCE_CONNTRACK: u8 = 3;
     pub const NAT_SOURCE_RTOA: u8 = 4;
     pub const NAT_SOURCE_TOA: u8 = 6;
 
@@ -190,6 +190,8 @@ impl fmt::Display for CaptureNetworkTyper {
 mod tests {
     use super::*;
 
+    use crate::common::enums::CAPTURE_NETWORK_TYPE_MAX;
+
     fn update_vlan(tap_typer: &mut CaptureNetworkTyper, vlan: u16, tap: u16) {
         let pb_tap_type = agent::CaptureNetworkType {
             capture_network_type: Some(tap as u32),
@@ -247,6 +249,24 @@ mod tests {
         verify_xflow(&mut tap_typer, "1.2.3.4", 20, 3);
     }
 
+    #[test]
+    fn tap_type_beyond_256() {
+        let mut tap_typer = CaptureNetworkTyper::new();
+        let pb_tap_type = agent::CaptureNetworkType {
+            capture_network_type: Some(1000),
+            vlan: Some(100),
+            packet_type: Some(agent::PacketType::Packet as i32),
+            source_ip: None,
+            capture_network_port: None,
+        };
+        tap_typer.on_tap_types_change(vec![pb_tap_type]);
+        verify_vlan(&tap_typer, 100, 1000);
+
+        // out of range tap types are ignored
+        update_vlan(&mut tap_typer, 200, CAPTURE_NETWORK_TYPE_MAX as u16);
+        assert!(tap_typer.get_tap_type_by_vlan(200).is_none());
+    }
+
     #[test]
     #[should_panic]
     fn assert_tap_typer_failed_vlan() {
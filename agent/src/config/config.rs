@@ -17,6 +17,7 @@
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs;
 use std::io;
 use std::net::{IpAddr, ToSocketAddrs};
@@ -39,7 +40,9 @@ use tokio::runtime::Runtime;
 
 use crate::common::l7_protocol_log::L7ProtocolParser;
 use crate::dispatcher::recv_engine::DEFAULT_BLOCK_SIZE;
-use crate::flow_generator::{DnsLog, MemcachedLog, OracleLog, TlsLog};
+use crate::flow_generator::{
+    DiameterLog, DnsLog, KerberosLog, MemcachedLog, ModbusLog, OracleLog, QuicLog, TlsLog,
+};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::platform::{get_container_id, OsAppTag, ProcessData};
 use crate::{
@@ -55,7 +58,8 @@ use crate::{
 
 use public::{
     bitmap::Bitmap,
-    consts::NPB_DEFAULT_PORT,
+    consts::{DEFAULT_LOCAL_API_PORT, NPB_DEFAULT_PORT},
+    packet::PcapFormat,
     proto::{
         agent::{self, DynamicConfig, SocketType, SysMemoryMetric, SystemLoadMetric},
         common,
@@ -128,6 +132,8 @@ pub struct Config {
     pub controller_port: u16,
     pub controller_tls_port: u16,
     pub controller_cert_file_prefix: String,
+    pub controller_client_cert_file: String,
+    pub controller_client_key_file: String,
     pub log_file: String,
     pub kubernetes_cluster_id: String,
     pub kubernetes_cluster_name: Option<String>,
@@ -281,6 +287,8 @@ impl Default for Config {
             controller_port: 30035,
             controller_tls_port: 30135,
             controller_cert_file_prefix: "".into(),
+            controller_client_cert_file: "".into(),
+            controller_client_key_file: "".into(),
             log_file: DEFAULT_LOG_FILE.into(),
             kubernetes_cluster_id: "".into(),
             kubernetes_cluster_name: Default::default(),
@@ -686,6 +694,7 @@ pub struct AfPacketTunning {
     pub ring_blocks: usize,
     pub packet_fanout_count: usize,
     pub packet_fanout_mode: u32,
+    pub hardware_timestamp_enabled: bool,
 }
 
 impl Default for AfPacketTunning {
@@ -696,6 +705,7 @@ impl Default for AfPacketTunning {
             ring_blocks: 128,
             packet_fanout_count: 1,
             packet_fanout_mode: 0,
+            hardware_timestamp_enabled: false,
         }
     }
 }
@@ -736,14 +746,32 @@ impl Default for AfPacket {
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DpdkProcessType {
+    Primary,
+    Secondary,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Dpdk {
     pub enabled: bool,
+    pub process_type: DpdkProcessType,
+    // only used in primary mode, secondary processes get EAL settings from the primary
+    pub eal_args: Vec<String>,
+    pub ring_name: String,
+    pub mempool_name: String,
 }
 
 impl Default for Dpdk {
     fn default() -> Self {
-        Self { enabled: false }
+        Self {
+            enabled: false,
+            process_type: DpdkProcessType::Secondary,
+            eal_args: vec![],
+            ring_name: "deepflow_agent_ring".to_string(),
+            mempool_name: "deepflow_agent_mempool".to_string(),
+        }
     }
 }
 
@@ -789,6 +817,90 @@ impl Default for PhysicalSwitch {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum XdpAttachMode {
+    // try driver mode first and fall back to generic mode
+    Auto,
+    Driver,
+    Generic,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum XdpBindMode {
+    // try zero copy first and fall back to copy mode
+    Auto,
+    ZeroCopy,
+    Copy,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AfXdp {
+    pub enabled: bool,
+    pub attach_mode: XdpAttachMode,
+    pub bind_mode: XdpBindMode,
+    // 0 means all rx queues of the interface
+    pub queue_count: usize,
+    pub frame_count: u32,
+    pub ring_size: u32,
+    // both directions of a flow are received on the same queue, required by parallel dispatchers
+    pub symmetric_rss: bool,
+}
+
+impl Default for AfXdp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            attach_mode: XdpAttachMode::Auto,
+            bind_mode: XdpBindMode::Auto,
+            queue_count: 0,
+            frame_count: 4096,
+            ring_size: 2048,
+            symmetric_rss: false,
+        }
+    }
+}
+
+impl AfXdp {
+    pub fn check(&self) -> Result<(), String> {
+        if !self.frame_count.is_power_of_two() {
+            return Err(format!(
+                "frame_count {} is not a power of two",
+                self.frame_count
+            ));
+        }
+        if !self.ring_size.is_power_of_two() {
+            return Err(format!(
+                "ring_size {} is not a power of two",
+                self.ring_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PcapReplay {
+    pub files: Vec<String>,
+    // replay at N times the original rate, 0 means as fast as possible
+    pub speed: u32,
+    // keep packet timestamps in the files instead of shifting them to the replay time
+    pub original_timestamps: bool,
+}
+
+impl Default for PcapReplay {
+    fn default() -> Self {
+        Self {
+            files: vec![],
+            speed: 1,
+            original_timestamps: false,
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct SpecialNetwork {
@@ -796,12 +908,15 @@ pub struct SpecialNetwork {
     pub libpcap: Libpcap,
     pub vhost_user: VhostUser,
     pub physical_switch: PhysicalSwitch,
+    pub af_xdp: AfXdp,
+    pub pcap_replay: PcapReplay,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct CbpfTunning {
     pub dispatcher_queue_enabled: bool,
+    pub parallel_dispatcher_enabled: bool,
     pub max_capture_packet_size: u32,
     pub raw_packet_buffer_block_size: usize,
     pub raw_packet_queue_size: usize,
@@ -812,6 +927,7 @@ impl Default for CbpfTunning {
     fn default() -> Self {
         Self {
             dispatcher_queue_enabled: false,
+            parallel_dispatcher_enabled: false,
             max_capture_packet_size: 65535,
             raw_packet_buffer_block_size: 65536,
             raw_packet_queue_size: 131072,
@@ -826,6 +942,8 @@ pub struct PreProcess {
     pub tunnel_decap_protocols: Vec<u8>,
     pub tunnel_trim_protocols: Vec<String>,
     pub packet_segmentation_reassembly: Vec<u16>,
+    pub ip_fragment_reassembly: IpFragmentReassembly,
+    pub packet_sampling: PacketSampling,
 }
 
 impl Default for PreProcess {
@@ -834,6 +952,53 @@ impl Default for PreProcess {
             tunnel_decap_protocols: vec![1, 2],
             tunnel_trim_protocols: vec![],
             packet_segmentation_reassembly: vec![],
+            ip_fragment_reassembly: IpFragmentReassembly::default(),
+            packet_sampling: PacketSampling::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct IpFragmentReassembly {
+    pub enabled: bool,
+    pub max_datagrams: usize,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for IpFragmentReassembly {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_datagrams: 1024,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PacketSamplingMode {
+    Disabled,
+    // capture every N-th packet
+    Count,
+    // capture all packets of 1/N of the flows
+    FlowHash,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PacketSampling {
+    pub mode: PacketSamplingMode,
+    pub rate: u32,
+}
+
+impl Default for PacketSampling {
+    fn default() -> Self {
+        Self {
+            mode: PacketSamplingMode::Disabled,
+            rate: 1,
         }
     }
 }
@@ -843,6 +1008,8 @@ impl Default for PreProcess {
 pub struct PhysicalMirror {
     pub default_capture_network_type: u16,
     pub packet_dedup_disabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub packet_dedup_window: Duration,
     pub private_cloud_gateway_traffic: bool,
 }
 
@@ -851,6 +1018,7 @@ impl Default for PhysicalMirror {
         Self {
             default_capture_network_type: 3,
             packet_dedup_disabled: false,
+            packet_dedup_window: Duration::from_millis(10),
             private_cloud_gateway_traffic: false,
         }
     }
@@ -879,6 +1047,18 @@ impl Default for EbpfSocketUprobeTls {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketUprobeDns {
+    pub enabled: bool,
+}
+
+impl Default for EbpfSocketUprobeDns {
+    fn default() -> Self {
+        Self { enabled: false }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfSocketUprobeGolang {
@@ -901,6 +1081,7 @@ impl Default for EbpfSocketUprobeGolang {
 pub struct EbpfSocketUprobe {
     pub golang: EbpfSocketUprobeGolang,
     pub tls: EbpfSocketUprobeTls,
+    pub dns: EbpfSocketUprobeDns,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -917,11 +1098,39 @@ impl Default for EbpfSocketKprobePorts {
     }
 }
 
+#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketKprobeUnixSocket {
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketKprobeIoUring {
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketKprobeTcpHealth {
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketKprobeDropMonitor {
+    pub enabled: bool,
+}
+
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfSocketKprobe {
     pub blacklist: EbpfSocketKprobePorts,
     pub whitelist: EbpfSocketKprobePorts,
+    pub unix_socket: EbpfSocketKprobeUnixSocket,
+    pub io_uring: EbpfSocketKprobeIoUring,
+    pub tcp_health: EbpfSocketKprobeTcpHealth,
+    pub drop_monitor: EbpfSocketKprobeDropMonitor,
 }
 
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -930,6 +1139,7 @@ pub struct EbpfSocketTunning {
     pub max_capture_rate: u64,
     pub syscall_trace_id_disabled: bool,
     pub map_prealloc_disabled: bool,
+    pub process_attribution_disabled: bool,
 }
 
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -958,10 +1168,32 @@ impl Default for EbpfFileIoEvent {
     }
 }
 
-#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfFileIoStats {
+    pub enabled: bool,
+    pub path_filters: Vec<String>,
+}
+
+impl Default for EbpfFileIoStats {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path_filters: vec![
+                "/var/lib/mysql".to_string(),
+                "/var/lib/postgresql".to_string(),
+                "/var/lib/mongodb".to_string(),
+                "/data".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfFile {
     pub io_event: EbpfFileIoEvent,
+    pub io_stats: EbpfFileIoStats,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
@@ -1007,6 +1239,8 @@ pub struct EbpfProfileMemory {
     pub disabled: bool,
     #[serde(with = "humantime_serde")]
     pub report_interval: Duration,
+    pub sampling_interval: u32,
+    pub max_samples_per_second: u32,
 }
 
 impl Default for EbpfProfileMemory {
@@ -1014,6 +1248,8 @@ impl Default for EbpfProfileMemory {
         Self {
             disabled: true,
             report_interval: Duration::from_secs(10),
+            sampling_interval: 1,
+            max_samples_per_second: 10000,
         }
     }
 }
@@ -1052,6 +1288,14 @@ pub struct EbpfProfile {
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SocketDataBuffer {
+    PerfBuffer,
+    // fall back to the perf buffers if not supported by the kernel
+    RingBuffer,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfTunning {
     pub collector_queue_size: usize,
@@ -1061,6 +1305,9 @@ pub struct EbpfTunning {
     pub max_socket_entries: u32,
     pub socket_map_reclaim_threshold: u32,
     pub max_trace_entries: u32,
+    pub map_max_entries: HashMap<String, u32>,
+    pub map_pressure_threshold: u32,
+    pub socket_data_buffer: SocketDataBuffer,
 }
 
 impl Default for EbpfTunning {
@@ -1073,6 +1320,9 @@ impl Default for EbpfTunning {
             max_socket_entries: 131072,
             socket_map_reclaim_threshold: 120000,
             max_trace_entries: 131072,
+            map_max_entries: HashMap::new(),
+            map_pressure_threshold: 95,
+            socket_data_buffer: SocketDataBuffer::PerfBuffer,
         }
     }
 }
@@ -1095,6 +1345,63 @@ impl Default for EbpfSocketPreprocess {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfBtf {
+    pub hub_location: String,
+    pub cache_dir: String,
+    #[serde(with = "humantime_serde")]
+    pub download_timeout: Duration,
+}
+
+impl Default for EbpfBtf {
+    fn default() -> Self {
+        Self {
+            hub_location: "".to_string(),
+            cache_dir: "/var/lib/deepflow-agent/btf".to_string(),
+            download_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfOverhead {
+    pub enabled: bool,
+    pub cpu_budget: u32,
+    #[serde(with = "humantime_serde")]
+    pub check_interval: Duration,
+}
+
+impl Default for EbpfOverhead {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cpu_budget: 0,
+            check_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfProcessEvents {
+    pub enabled: bool,
+    pub max_entries: u32,
+    #[serde(with = "humantime_serde")]
+    pub exited_retention: Duration,
+}
+
+impl Default for EbpfProcessEvents {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: 65536,
+            exited_retention: Duration::from_secs(60),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Ebpf {
@@ -1103,6 +1410,9 @@ pub struct Ebpf {
     pub file: EbpfFile,
     pub profile: EbpfProfile,
     pub tunning: EbpfTunning,
+    pub btf: EbpfBtf,
+    pub overhead: EbpfOverhead,
+    pub process_events: EbpfProcessEvents,
     #[serde(skip)]
     pub java_symbol_file_refresh_defer_interval: i32,
 }
@@ -1115,6 +1425,9 @@ impl Default for Ebpf {
             file: EbpfFile::default(),
             profile: EbpfProfile::default(),
             tunning: EbpfTunning::default(),
+            btf: EbpfBtf::default(),
+            overhead: EbpfOverhead::default(),
+            process_events: EbpfProcessEvents::default(),
             java_symbol_file_refresh_defer_interval: 60,
         }
     }
@@ -1308,6 +1621,97 @@ impl Default for PrometheusExtraLabels {
     }
 }
 
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PrometheusScrapeTarget {
+    pub job_name: String,
+    // e.g. http://10.1.2.3:9100/metrics
+    pub url: String,
+    // added to the scraped samples, overriding the labels of the same names
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PrometheusScrape {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub sample_limit: usize,
+    pub targets: Vec<PrometheusScrapeTarget>,
+}
+
+impl Default for PrometheusScrape {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(10),
+            sample_limit: 50000,
+            targets: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Statsd {
+    pub enabled: bool,
+    // udp port, 0 means not listening on udp
+    pub listen_port: u16,
+    // unix datagram socket, empty means not listening on unix socket
+    pub unix_socket_path: String,
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    // quantiles of timers, histograms and distributions in each flush interval
+    pub percentiles: Vec<u8>,
+    pub max_series: usize,
+}
+
+impl Default for Statsd {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_port: 8125,
+            unix_socket_path: "".to_string(),
+            flush_interval: Duration::from_secs(10),
+            percentiles: vec![50, 90, 99],
+            max_series: 100000,
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OtlpTenantRateLimit {
+    pub tenant: String,
+    pub rate_limit: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OtlpIngest {
+    pub enabled: bool,
+    // requests without the header are from the tenant of the peer ip
+    pub tenant_header: String,
+    // spans, data points or log records per second of each tenant, 0 means unlimited
+    pub tenant_rate_limit: u64,
+    pub tenant_rate_limits: Vec<OtlpTenantRateLimit>,
+}
+
+impl Default for OtlpIngest {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tenant_header: "X-Scope-OrgID".to_string(),
+            tenant_rate_limit: 0,
+            tenant_rate_limits: vec![],
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct FeatureControl {
@@ -1351,7 +1755,11 @@ pub struct Integration {
     pub listen_port: u16,
     pub compression: Compression,
     pub prometheus_extra_labels: PrometheusExtraLabels,
+    pub prometheus_scrape: PrometheusScrape,
+    pub otlp: OtlpIngest,
+    pub statsd: Statsd,
     pub feature_control: FeatureControl,
+    pub tls: MutualTls,
 }
 
 impl Default for Integration {
@@ -1361,7 +1769,120 @@ impl Default for Integration {
             listen_port: 38086,
             compression: Compression::default(),
             prometheus_extra_labels: PrometheusExtraLabels::default(),
+            prometheus_scrape: PrometheusScrape::default(),
+            otlp: OtlpIngest::default(),
+            statsd: Statsd::default(),
             feature_control: FeatureControl::default(),
+            tls: MutualTls::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnmpVersion {
+    #[default]
+    V2c,
+    V3,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnmpSecurityLevel {
+    #[default]
+    NoAuthNoPriv,
+    AuthNoPriv,
+    AuthPriv,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnmpAuthProtocol {
+    Md5,
+    #[default]
+    Sha,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnmpPrivacyProtocol {
+    #[default]
+    Aes,
+}
+
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SnmpDevice {
+    // sysName of the device is used if empty
+    pub name: String,
+    // ip or ip:port, the port is 161 by default
+    pub address: String,
+    pub version: SnmpVersion,
+    pub community: String,
+    pub username: String,
+    pub security_level: SnmpSecurityLevel,
+    pub auth_protocol: SnmpAuthProtocol,
+    pub auth_password: String,
+    pub privacy_protocol: SnmpPrivacyProtocol,
+    pub privacy_password: String,
+    pub context_name: String,
+}
+
+impl Default for SnmpDevice {
+    fn default() -> Self {
+        Self {
+            name: "".to_string(),
+            address: "".to_string(),
+            version: SnmpVersion::V2c,
+            community: "public".to_string(),
+            username: "".to_string(),
+            security_level: SnmpSecurityLevel::NoAuthNoPriv,
+            auth_protocol: SnmpAuthProtocol::Sha,
+            auth_password: "".to_string(),
+            privacy_protocol: SnmpPrivacyProtocol::Aes,
+            privacy_password: "".to_string(),
+            context_name: "".to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for SnmpDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnmpDevice")
+            .field("name", &self.name)
+            .field("address", &self.address)
+            .field("version", &self.version)
+            .field("username", &self.username)
+            .field("security_level", &self.security_level)
+            .field("auth_protocol", &self.auth_protocol)
+            .field("privacy_protocol", &self.privacy_protocol)
+            .field("context_name", &self.context_name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Snmp {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub retries: usize,
+    pub max_repetitions: u32,
+    pub devices: Vec<SnmpDevice>,
+}
+
+impl Default for Snmp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(5),
+            retries: 1,
+            max_repetitions: 25,
+            devices: vec![],
         }
     }
 }
@@ -1374,6 +1895,7 @@ pub struct Inputs {
     pub ebpf: Ebpf,
     pub resources: Resources,
     pub integration: Integration,
+    pub snmp: Snmp,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
@@ -1452,6 +1974,7 @@ pub struct PcapStream {
     pub total_buffer_size: u64,
     #[serde(with = "humantime_serde")]
     pub flush_interval: Duration,
+    pub format: PcapFormat,
 }
 
 impl Default for PcapStream {
@@ -1461,6 +1984,7 @@ impl Default for PcapStream {
             buffer_size_per_flow: 65536,
             total_buffer_size: 88304,
             flush_interval: Duration::from_secs(60),
+            format: PcapFormat::Pcap,
         }
     }
 }
@@ -1570,11 +2094,29 @@ pub struct TagFilterOperator {
 #[serde(default)]
 pub struct Filters {
     pub port_number_prefilters: HashMap<String, String>,
+    pub port_number_exclusions: HashMap<String, String>,
     pub tag_filters: HashMap<String, Vec<TagFilterOperator>>,
     pub unconcerned_dns_nxdomain_response_suffixes: Vec<String>,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SessionAggregateTimeout {
+    pub protocol: String,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for SessionAggregateTimeout {
+    fn default() -> Self {
+        Self {
+            protocol: "".to_string(),
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Timeouts {
     #[serde(with = "humantime_serde")]
@@ -1583,6 +2125,7 @@ pub struct Timeouts {
     pub udp_request_timeout: Duration,
     #[serde(with = "humantime_serde")]
     pub session_aggregate_window_duration: Duration,
+    pub session_aggregate: Vec<SessionAggregateTimeout>,
 }
 
 impl Default for Timeouts {
@@ -1591,6 +2134,7 @@ impl Default for Timeouts {
             tcp_request_timeout: Duration::from_secs(1800),
             udp_request_timeout: Duration::from_secs(150),
             session_aggregate_window_duration: Duration::from_secs(120),
+            session_aggregate: vec![],
         }
     }
 }
@@ -1653,6 +2197,33 @@ pub struct CustomFields {
     pub field_name: String,
 }
 
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct GrpcBodyField {
+    pub service: String,
+    pub field_name: String,
+    pub field_path: String,
+    pub in_response: bool,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HttpBodyField {
+    pub field_name: String,
+    pub json_path: String,
+    pub in_response: bool,
+    pub success_values: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlObfuscationMode {
+    Raw,
+    #[default]
+    Obfuscated,
+    Both,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct RequestLogTagExtraction {
@@ -1660,6 +2231,10 @@ pub struct RequestLogTagExtraction {
     pub http_endpoint: HttpEndpoint,
     pub custom_fields: HashMap<String, Vec<CustomFields>>,
     pub obfuscate_protocols: Vec<String>,
+    pub sql_obfuscation_mode: SqlObfuscationMode,
+    pub redis_key_patterns: Vec<String>,
+    pub grpc_body_fields: Vec<GrpcBodyField>,
+    pub http_body_fields: Vec<HttpBodyField>,
 }
 
 impl Default for RequestLogTagExtraction {
@@ -1669,6 +2244,10 @@ impl Default for RequestLogTagExtraction {
             http_endpoint: HttpEndpoint::default(),
             custom_fields: HashMap::new(),
             obfuscate_protocols: vec!["Redis".to_string()],
+            sql_obfuscation_mode: SqlObfuscationMode::default(),
+            redis_key_patterns: vec![],
+            grpc_body_fields: vec![],
+            http_body_fields: vec![],
         }
     }
 }
@@ -1679,6 +2258,7 @@ pub struct RequestLogTunning {
     pub payload_truncation: u32,
     pub session_aggregate_slot_capacity: usize,
     pub consistent_timestamp_in_l7_metrics: bool,
+    pub mysql_statement_cache_size: usize,
 }
 
 impl Default for RequestLogTunning {
@@ -1687,6 +2267,7 @@ impl Default for RequestLogTunning {
             payload_truncation: 1024,
             session_aggregate_slot_capacity: 1024,
             consistent_timestamp_in_l7_metrics: false,
+            mysql_statement_cache_size: 256,
         }
     }
 }
@@ -1698,9 +2279,68 @@ pub struct RequestLog {
     pub filters: Filters,
     pub timeouts: Timeouts,
     pub tag_extraction: RequestLogTagExtraction,
+    pub alerting: RequestLogAlerting,
+    pub sampling: RequestLogSampling,
     pub tunning: RequestLogTunning,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RequestLogAlerting {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    // services with fewer requests in the window are not evaluated
+    pub min_requests: u32,
+    // thresholds, 0 to disable
+    pub error_rate_threshold: u8,
+    #[serde(with = "humantime_serde")]
+    pub p95_latency_threshold: Duration,
+}
+
+impl Default for RequestLogAlerting {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: Duration::from_secs(60),
+            min_requests: 100,
+            error_rate_threshold: 5,
+            p95_latency_threshold: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RequestLogSampling {
+    pub enabled: bool,
+    // sessions slower than this are always kept
+    #[serde(with = "humantime_serde")]
+    pub slow_threshold: Duration,
+    // keep 1 in `sample_rate` of the successful fast sessions
+    pub sample_rate: u32,
+    pub rules: Vec<RequestLogSamplingRule>,
+}
+
+impl Default for RequestLogSampling {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            slow_threshold: Duration::from_secs(1),
+            sample_rate: 10,
+            rules: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RequestLogSamplingRule {
+    pub protocol: String,
+    pub server_port: u16,
+    pub sample_rate: u32,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct TimeWindow {
@@ -1719,13 +2359,40 @@ impl Default for TimeWindow {
     }
 }
 
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirectionHeuristic {
+    TcpHandshake,
+    ServerPorts,
+    EphemeralPort,
+    EbpfSocketRole,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct FlowGeneration {
     pub server_ports: Vec<u16>,
     pub cloud_traffic_ignore_mac: bool,
     pub ignore_l2_end: bool,
     pub idc_traffic_ignore_vlan: bool,
+    pub direction_heuristics: Vec<DirectionHeuristic>,
+}
+
+impl Default for FlowGeneration {
+    fn default() -> Self {
+        Self {
+            server_ports: vec![],
+            cloud_traffic_ignore_mac: false,
+            ignore_l2_end: false,
+            idc_traffic_ignore_vlan: false,
+            direction_heuristics: vec![
+                DirectionHeuristic::TcpHandshake,
+                DirectionHeuristic::ServerPorts,
+                DirectionHeuristic::EphemeralPort,
+                DirectionHeuristic::EbpfSocketRole,
+            ],
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
@@ -1752,6 +2419,27 @@ impl Default for ConntrackTimeouts {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ConntrackNatCorrelation {
+    pub enabled: bool,
+    pub max_entries: usize,
+    pub ipvs_enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub ipvs_sync_interval: Duration,
+}
+
+impl Default for ConntrackNatCorrelation {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: 65536,
+            ipvs_enabled: false,
+            ipvs_sync_interval: Duration::from_secs(5),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Conntrack {
@@ -1759,6 +2447,7 @@ pub struct Conntrack {
     pub flow_flush_interval: Duration,
     pub flow_generation: FlowGeneration,
     pub timeouts: ConntrackTimeouts,
+    pub nat_correlation: ConntrackNatCorrelation,
 }
 
 impl Default for Conntrack {
@@ -1767,6 +2456,7 @@ impl Default for Conntrack {
             flow_flush_interval: Duration::from_secs(1),
             flow_generation: FlowGeneration::default(),
             timeouts: ConntrackTimeouts::default(),
+            nat_correlation: ConntrackNatCorrelation::default(),
         }
     }
 }
@@ -1802,9 +2492,50 @@ impl Default for ProcessorsFlowLogTunning {
 pub struct ProcessorsFlowLog {
     pub time_window: TimeWindow,
     pub conntrack: Conntrack,
+    pub anomaly_detection: FlowAnomalyDetection,
     pub tunning: ProcessorsFlowLogTunning,
 }
 
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FlowPolicyRule {
+    pub name: String,
+    // any address if empty
+    pub src_cidrs: Vec<String>,
+    pub dst_cidrs: Vec<String>,
+    // e.g. "22,3389,6000-6100", any port if empty
+    pub dst_ports: String,
+    // tcp or udp, any protocol if empty
+    pub protocol: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FlowAnomalyDetection {
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    // thresholds in a window, 0 to disable
+    pub port_scan_threshold: u32,
+    pub syn_flood_threshold: u32,
+    pub connection_storm_threshold: u32,
+    // new flows matching any of the rules are policy violations
+    pub policy_rules: Vec<FlowPolicyRule>,
+}
+
+impl Default for FlowAnomalyDetection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: Duration::from_secs(10),
+            port_scan_threshold: 100,
+            syn_flood_threshold: 1000,
+            connection_storm_threshold: 5000,
+            policy_rules: vec![],
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Processors {
@@ -1988,6 +2719,35 @@ impl Default for Ntp {
     }
 }
 
+// Certificates and keys in PEM, the files take precedence over the inline certificates issued by
+// the controller. The files are reloaded when modified.
+#[derive(Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct MutualTls {
+    pub enabled: bool,
+    pub ca_file: String,
+    pub cert_file: String,
+    pub key_file: String,
+    pub ca: String,
+    pub cert: String,
+    pub key: String,
+    // the address of the peer is verified if empty
+    pub server_name: String,
+}
+
+// keep the private key out of the logs of config updates
+impl fmt::Debug for MutualTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MutualTls")
+            .field("enabled", &self.enabled)
+            .field("ca_file", &self.ca_file)
+            .field("cert_file", &self.cert_file)
+            .field("key_file", &self.key_file)
+            .field("server_name", &self.server_name)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Communication {
@@ -2002,6 +2762,7 @@ pub struct Communication {
     pub request_via_nat_ip: bool,
     pub proxy_controller_ip: String,
     pub proxy_controller_port: u16,
+    pub tls: MutualTls,
 }
 
 impl Default for Communication {
@@ -2015,6 +2776,7 @@ impl Default for Communication {
             ingester_port: 30033,
             grpc_buffer_size: 5 << 20,
             request_via_nat_ip: false,
+            tls: MutualTls::default(),
         }
     }
 }
@@ -2024,6 +2786,7 @@ impl Default for Communication {
 pub struct Log {
     #[serde(deserialize_with = "to_log_level")]
     pub log_level: log::Level,
+    pub module_log_levels: Vec<String>,
     pub log_file: String,
     pub log_backhaul_enabled: bool,
 }
@@ -2032,6 +2795,7 @@ impl Default for Log {
     fn default() -> Self {
         Self {
             log_level: log::Level::Info,
+            module_log_levels: vec![],
             log_file: "/var/log/deepflow_agent/deepflow_agent.log".to_string(),
             log_backhaul_enabled: true,
         }
@@ -2050,6 +2814,7 @@ pub struct Debug {
     pub enabled: bool,
     pub local_udp_port: u16,
     pub debug_metrics_enabled: bool,
+    pub local_api: LocalApi,
 }
 
 impl Default for Debug {
@@ -2058,6 +2823,25 @@ impl Default for Debug {
             local_udp_port: 0,
             debug_metrics_enabled: false,
             enabled: true,
+            local_api: LocalApi::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LocalApi {
+    pub enabled: bool,
+    pub grpc_port: u16,
+    pub http_port: u16,
+}
+
+impl Default for LocalApi {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            grpc_port: DEFAULT_LOCAL_API_PORT,
+            http_port: 0,
         }
     }
 }
@@ -2168,7 +2952,7 @@ where
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Socket {
     #[serde(deserialize_with = "to_agent_socket_type")]
@@ -2177,77 +2961,302 @@ pub struct Socket {
     pub npb_socket_type: agent::SocketType,
     pub raw_udp_qos_bypass: bool,
     pub multiple_sockets_to_ingester: bool,
+    pub disk_buffer: DiskBuffer,
+    pub compression: SenderCompression,
+    pub destinations: Vec<SenderDestination>,
+}
+
+impl Default for Socket {
+    fn default() -> Self {
+        Self {
+            data_socket_type: agent::SocketType::Tcp,
+            npb_socket_type: agent::SocketType::RawUdp,
+            raw_udp_qos_bypass: false,
+            multiple_sockets_to_ingester: false,
+            disk_buffer: DiskBuffer::default(),
+            compression: SenderCompression::default(),
+            destinations: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SenderDestination {
+    pub address: String,
+    pub port: u16,
+    // destinations with lower values are preferred
+    pub priority: u32,
+    pub weight: u32,
+}
+
+impl Default for SenderDestination {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            port: 30033,
+            priority: 0,
+            weight: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DiskBuffer {
+    pub enabled: bool,
+    pub directory: String,
+    #[serde(deserialize_with = "deser_u64_with_mega_unit")]
+    pub max_size: u64,
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+}
+
+impl Default for DiskBuffer {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "/var/log/deepflow_agent/buffer/".to_string(),
+            max_size: 1 << 30,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SenderCompressionAlgorithm {
+    #[default]
+    None,
+    Zstd,
+    Lz4,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SenderCompression {
+    pub algorithm: SenderCompressionAlgorithm,
+    pub zstd_level: i32,
+    // algorithms of data types overriding `algorithm`
+    pub data_types: HashMap<String, SenderCompressionAlgorithm>,
+}
+
+impl Default for SenderCompression {
+    fn default() -> Self {
+        Self {
+            algorithm: SenderCompressionAlgorithm::None,
+            zstd_level: 1,
+            data_types: HashMap::new(),
+        }
+    }
+}
+
+impl SenderCompressionAlgorithm {
+    // Algorithms advertised by the controller in the names of the configuration
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zstd" => Some(Self::Zstd),
+            "lz4" => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+}
+
+impl SenderCompression {
+    pub fn algorithm(&self, data_type: &str) -> SenderCompressionAlgorithm {
+        self.data_types
+            .get(data_type)
+            .copied()
+            .unwrap_or(self.algorithm)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FlowLogFilters {
+    pub l4_capture_network_types: Vec<i16>,
+    pub l7_capture_network_types: Vec<i16>,
+    pub l4_ignored_observation_points: Vec<u16>,
+    pub l7_ignored_observation_points: Vec<u16>,
+}
+
+impl Default for FlowLogFilters {
+    fn default() -> Self {
+        Self {
+            l4_capture_network_types: vec![0],
+            l7_capture_network_types: vec![],
+            l4_ignored_observation_points: vec![],
+            l7_ignored_observation_points: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Throttles {
+    pub l4_throttle: u64,
+    pub l7_throttle: u64,
+}
+
+impl Default for Throttles {
+    fn default() -> Self {
+        Self {
+            l4_throttle: 10000,
+            l7_throttle: 10000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OutputsFlowLogTunning {
+    pub collector_queue_size: usize,
+    pub collector_queue_count: usize,
+}
+
+impl Default for OutputsFlowLogTunning {
+    fn default() -> Self {
+        Self {
+            collector_queue_size: 65536,
+            collector_queue_count: 1,
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OutputsFlowLog {
+    pub filters: FlowLogFilters,
+    pub throttles: Throttles,
+    pub namespace_quotas: NamespaceQuotas,
+    pub deduplication: FlowLogDeduplication,
+    pub active_flow_report: ActiveFlowReport,
+    pub tunning: OutputsFlowLogTunning,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamespacePriorityClass {
+    System,
+    #[default]
+    Normal,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NamespaceQuota {
+    // 0 for unlimited
+    pub max_logs: u64,
+    // 0 for unlimited
+    #[serde(deserialize_with = "deser_u64_with_mega_unit")]
+    pub max_throughput: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NamespacePriority {
+    pub pod_ns_id: u32,
+    pub priority_class: NamespacePriorityClass,
 }
 
-impl Default for Socket {
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NamespaceQuotas {
+    pub enabled: bool,
+    pub system: NamespaceQuota,
+    pub normal: NamespaceQuota,
+    pub low: NamespaceQuota,
+    pub namespaces: Vec<NamespacePriority>,
+}
+
+impl Default for NamespaceQuotas {
     fn default() -> Self {
         Self {
-            data_socket_type: agent::SocketType::Tcp,
-            npb_socket_type: agent::SocketType::RawUdp,
-            raw_udp_qos_bypass: false,
-            multiple_sockets_to_ingester: false,
+            enabled: false,
+            system: NamespaceQuota::default(),
+            normal: NamespaceQuota {
+                max_logs: 2000,
+                max_throughput: 0,
+            },
+            low: NamespaceQuota {
+                max_logs: 200,
+                max_throughput: 0,
+            },
+            namespaces: vec![],
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-#[serde(default)]
-pub struct FlowLogFilters {
-    pub l4_capture_network_types: Vec<i16>,
-    pub l7_capture_network_types: Vec<i16>,
-    pub l4_ignored_observation_points: Vec<u16>,
-    pub l7_ignored_observation_points: Vec<u16>,
-}
+impl NamespaceQuotas {
+    // namespaces not configured are in the normal class
+    pub fn priority_class(&self, pod_ns_id: u32) -> NamespacePriorityClass {
+        self.namespaces
+            .iter()
+            .find(|n| n.pod_ns_id == pod_ns_id)
+            .map(|n| n.priority_class)
+            .unwrap_or_default()
+    }
 
-impl Default for FlowLogFilters {
-    fn default() -> Self {
-        Self {
-            l4_capture_network_types: vec![0],
-            l7_capture_network_types: vec![],
-            l4_ignored_observation_points: vec![],
-            l7_ignored_observation_points: vec![],
+    pub fn quota(&self, class: NamespacePriorityClass) -> &NamespaceQuota {
+        match class {
+            NamespacePriorityClass::System => &self.system,
+            NamespacePriorityClass::Normal => &self.normal,
+            NamespacePriorityClass::Low => &self.low,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlowLogDeduplicationMode {
+    #[default]
+    Disabled,
+    // duplicates are sent with the flow_id of the first flow log of the session
+    Link,
+    Suppress,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
-pub struct Throttles {
-    pub l4_throttle: u64,
-    pub l7_throttle: u64,
+pub struct FlowLogDeduplication {
+    pub mode: FlowLogDeduplicationMode,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
 }
 
-impl Default for Throttles {
+impl Default for FlowLogDeduplication {
     fn default() -> Self {
         Self {
-            l4_throttle: 10000,
-            l7_throttle: 10000,
+            mode: FlowLogDeduplicationMode::Disabled,
+            timeout: Duration::from_secs(10),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
-pub struct OutputsFlowLogTunning {
-    pub collector_queue_size: usize,
-    pub collector_queue_count: usize,
+pub struct ActiveFlowReport {
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    pub capture_network_type_intervals: Vec<CaptureNetworkTypeInterval>,
 }
 
-impl Default for OutputsFlowLogTunning {
+impl Default for ActiveFlowReport {
     fn default() -> Self {
         Self {
-            collector_queue_size: 65536,
-            collector_queue_count: 1,
+            interval: Duration::from_secs(60),
+            capture_network_type_intervals: vec![],
         }
     }
 }
 
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
 #[serde(default)]
-pub struct OutputsFlowLog {
-    pub filters: FlowLogFilters,
-    pub throttles: Throttles,
-    pub tunning: OutputsFlowLogTunning,
+pub struct CaptureNetworkTypeInterval {
+    pub capture_network_type: i16,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
@@ -2288,11 +3297,28 @@ impl Default for FlowMetricsTunning {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TopTalkers {
+    pub enabled: bool,
+    pub top_n: usize,
+}
+
+impl Default for TopTalkers {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            top_n: 10,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct FlowMetrics {
     pub enabled: bool,
     pub filters: FlowMetricsFilters,
+    pub top_talkers: TopTalkers,
     pub tunning: FlowMetricsTunning,
 }
 
@@ -2301,6 +3327,7 @@ impl Default for FlowMetrics {
         Self {
             enabled: true,
             filters: FlowMetricsFilters::default(),
+            top_talkers: TopTalkers::default(),
             tunning: FlowMetricsTunning::default(),
         }
     }
@@ -2335,6 +3362,9 @@ pub struct Npb {
     pub overlay_vlan_header_trimming: bool,
     #[serde(deserialize_with = "deser_u64_with_mega_unit")]
     pub max_tx_throughput: u64,
+    #[serde(deserialize_with = "deser_u64_with_mega_unit")]
+    pub max_tx_throughput_per_destination: u64,
+    pub payload_truncation: u32,
 }
 
 impl Default for Npb {
@@ -2348,6 +3378,8 @@ impl Default for Npb {
             custom_vxlan_flags: 0b1111_1111,
             overlay_vlan_header_trimming: false,
             max_tx_throughput: 1000 << 20,
+            max_tx_throughput_per_destination: 0,
+            payload_truncation: 0,
         }
     }
 }
@@ -2359,6 +3391,294 @@ pub struct Outputs {
     pub flow_log: OutputsFlowLog,
     pub flow_metrics: FlowMetrics,
     pub npb: Npb,
+    pub otlp_exporter: OtlpExporter,
+    pub kafka: Kafka,
+    pub clickhouse: ClickHouse,
+    pub file_exporter: FileExporter,
+    pub syslog: Syslog,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OtlpExporter {
+    pub enabled: bool,
+    // OTLP/gRPC endpoint of the collector, e.g. http://otel-collector:4317
+    pub endpoint: String,
+    // service.name of the spans without a service name extracted
+    pub service_name: String,
+    pub export_protocols: Vec<String>,
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub export_timeout: Duration,
+    pub queue_size: usize,
+}
+
+impl Default for OtlpExporter {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "".to_string(),
+            service_name: "deepflow-agent".to_string(),
+            export_protocols: vec![
+                "HTTP".to_string(),
+                "HTTP2".to_string(),
+                "MySQL".to_string(),
+                "PostgreSQL".to_string(),
+                "Custom".to_string(),
+            ],
+            batch_size: 512,
+            export_timeout: Duration::from_secs(10),
+            queue_size: 65536,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KafkaEncoding {
+    #[default]
+    Protobuf,
+    // same as the data files of the standalone mode
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KafkaCompression {
+    #[default]
+    None,
+    Gzip,
+    Snappy,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum KafkaSaslMechanism {
+    #[default]
+    #[serde(alias = "")]
+    None,
+    #[serde(rename = "PLAIN")]
+    Plain,
+    #[serde(rename = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[serde(rename = "SCRAM-SHA-512")]
+    ScramSha512,
+}
+
+#[derive(Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct KafkaSasl {
+    pub mechanism: KafkaSaslMechanism,
+    pub username: String,
+    pub password: String,
+}
+
+// keep the password out of the logs of config updates
+impl fmt::Debug for KafkaSasl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KafkaSasl")
+            .field("mechanism", &self.mechanism)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct KafkaTls {
+    pub enabled: bool,
+    // certificates of the system are used if empty
+    pub ca_file: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Kafka {
+    pub enabled: bool,
+    pub brokers: Vec<String>,
+    pub l4_flow_log_topic: String,
+    pub l7_flow_log_topic: String,
+    pub encoding: KafkaEncoding,
+    pub compression: KafkaCompression,
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub sasl: KafkaSasl,
+    pub tls: KafkaTls,
+}
+
+impl Default for Kafka {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brokers: vec![],
+            l4_flow_log_topic: "deepflow.l4-flow-log".to_string(),
+            l7_flow_log_topic: "deepflow.l7-flow-log".to_string(),
+            encoding: KafkaEncoding::Protobuf,
+            compression: KafkaCompression::None,
+            batch_size: 1024,
+            flush_interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            sasl: KafkaSasl::default(),
+            tls: KafkaTls::default(),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ClickHouse {
+    pub enabled: bool,
+    // HTTP interface of the ClickHouse server, e.g. http://clickhouse:8123
+    pub endpoint: String,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    pub async_insert: bool,
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub max_retries: u32,
+    #[serde(with = "humantime_serde")]
+    pub retry_backoff: Duration,
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+}
+
+impl Default for ClickHouse {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://127.0.0.1:8123".to_string(),
+            database: "deepflow_agent".to_string(),
+            username: "default".to_string(),
+            password: "".to_string(),
+            async_insert: true,
+            batch_size: 8192,
+            flush_interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            retry_backoff: Duration::from_secs(1),
+            ttl: Duration::from_secs(3 * 24 * 3600),
+        }
+    }
+}
+
+// keep the password out of the logs of config updates
+impl fmt::Debug for ClickHouse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClickHouse")
+            .field("enabled", &self.enabled)
+            .field("endpoint", &self.endpoint)
+            .field("database", &self.database)
+            .field("username", &self.username)
+            .field("async_insert", &self.async_insert)
+            .field("batch_size", &self.batch_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("timeout", &self.timeout)
+            .field("max_retries", &self.max_retries)
+            .field("retry_backoff", &self.retry_backoff)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileExporterTarget {
+    #[default]
+    File,
+    Stdout,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileExporterCompression {
+    #[default]
+    None,
+    Gzip,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FileExporter {
+    pub enabled: bool,
+    pub target: FileExporterTarget,
+    pub directory: String,
+    #[serde(deserialize_with = "deser_u32_with_mega_unit")]
+    pub max_file_size: u32,
+    #[serde(with = "humantime_serde")]
+    pub rotation_interval: Duration,
+    pub max_files: usize,
+    pub compression: FileExporterCompression,
+}
+
+impl Default for FileExporter {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: FileExporterTarget::File,
+            directory: "/var/log/deepflow_agent/export/".to_string(),
+            max_file_size: 100 << 20,
+            rotation_interval: Duration::from_secs(3600),
+            max_files: 24,
+            compression: FileExporterCompression::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyslogFormat {
+    #[default]
+    Rfc5424,
+    Cef,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyslogTransport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Syslog {
+    pub enabled: bool,
+    // host:port of the syslog server or SIEM
+    pub destination: String,
+    pub transport: SyslogTransport,
+    pub format: SyslogFormat,
+    pub facility: u8,
+    pub app_name: String,
+    // renames the fields of the events, a field is omitted if renamed to empty
+    pub field_mapping: HashMap<String, String>,
+    // 0 means unlimited
+    pub max_events_per_second: u32,
+    // 0 to disable new listening port events
+    #[serde(with = "humantime_serde")]
+    pub listening_port_scan_interval: Duration,
+}
+
+impl Default for Syslog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            destination: "".to_string(),
+            transport: SyslogTransport::Udp,
+            format: SyslogFormat::Rfc5424,
+            facility: 4,
+            app_name: "deepflow-agent".to_string(),
+            field_mapping: HashMap::new(),
+            max_events_per_second: 100,
+            listening_port_scan_interval: Duration::from_secs(60),
+        }
+    }
 }
 
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -2451,10 +3771,12 @@ impl From<&RuntimeConfig> for UserConfig {
                     ingester_port: rc.analyzer_port,
                     grpc_buffer_size: rc.yaml_config.grpc_buffer_size << 20,
                     request_via_nat_ip: false, // TODO: This configuration is not used
+                    tls: MutualTls::default(),
                 },
                 self_monitoring: SelfMonitoring {
                     log: Log {
                         log_level: rc.log_level,
+                        module_log_levels: vec![],
                         log_file: rc.yaml_config.log_file.clone(),
                         log_backhaul_enabled: rc.rsyslog_enabled,
                     },
@@ -2465,6 +3787,7 @@ impl From<&RuntimeConfig> for UserConfig {
                         enabled: rc.debug_enabled,
                         local_udp_port: rc.yaml_config.debug_listen_port,
                         debug_metrics_enabled: rc.yaml_config.enable_debug_stats,
+                        local_api: LocalApi::default(),
                     },
                     hostname: rc.host.clone(),
                     interval: Duration::from_secs(rc.stats_interval),
@@ -2679,6 +4002,7 @@ impl From<&RuntimeConfig> for UserConfig {
                             ring_blocks: rc.yaml_config.af_packet_blocks,
                             packet_fanout_count: rc.yaml_config.local_dispatcher_count,
                             packet_fanout_mode: rc.yaml_config.packet_fanout_mode,
+                            hardware_timestamp_enabled: false,
                         },
                         src_interfaces: vec![],
                         ..Default::default()
@@ -2686,6 +4010,7 @@ impl From<&RuntimeConfig> for UserConfig {
                     special_network: SpecialNetwork {
                         dpdk: Dpdk {
                             enabled: rc.yaml_config.dpdk_enabled,
+                            ..Default::default()
                         },
                         libpcap: Libpcap {
                             enabled: rc.yaml_config.libpcap_enabled,
@@ -2709,9 +4034,12 @@ impl From<&RuntimeConfig> for UserConfig {
                                 .map(|p| p.parse::<u16>().unwrap_or_default())
                                 .collect(),
                         },
+                        af_xdp: AfXdp::default(),
+                        pcap_replay: PcapReplay::default(),
                     },
                     tunning: CbpfTunning {
                         dispatcher_queue_enabled: rc.yaml_config.dispatcher_queue,
+                        parallel_dispatcher_enabled: false,
                         max_capture_packet_size: rc.capture_packet_size,
                         raw_packet_buffer_block_size: rc.yaml_config.analyzer_raw_packet_block_size,
                         raw_packet_queue_size: rc.yaml_config.analyzer_queue_size,
@@ -2721,10 +4049,13 @@ impl From<&RuntimeConfig> for UserConfig {
                         tunnel_decap_protocols: rc.decap_types.iter().map(|d| *d as u8).collect(),
                         tunnel_trim_protocols: rc.yaml_config.trim_tunnel_types.clone(),
                         packet_segmentation_reassembly: vec![],
+                        ip_fragment_reassembly: IpFragmentReassembly::default(),
+                        packet_sampling: PacketSampling::default(),
                     },
                     physical_mirror: PhysicalMirror {
                         default_capture_network_type: rc.yaml_config.default_tap_type as u16,
                         packet_dedup_disabled: rc.yaml_config.analyzer_dedup_disabled,
+                        packet_dedup_window: Duration::from_millis(10),
                         private_cloud_gateway_traffic: rc.yaml_config.cloud_gateway_traffic,
                     },
                 },
@@ -2741,6 +4072,7 @@ impl From<&RuntimeConfig> for UserConfig {
                             tls: EbpfSocketUprobeTls {
                                 enabled: rc.yaml_config.ebpf.uprobe_openssl_trace_enabled,
                             },
+                            dns: EbpfSocketUprobeDns::default(),
                         },
                         kprobe: EbpfSocketKprobe {
                             blacklist: EbpfSocketKprobePorts {
@@ -2749,6 +4081,10 @@ impl From<&RuntimeConfig> for UserConfig {
                             whitelist: EbpfSocketKprobePorts {
                                 ports: rc.yaml_config.ebpf.kprobe_whitelist.port_list.clone(),
                             },
+                            unix_socket: EbpfSocketKprobeUnixSocket::default(),
+                            io_uring: EbpfSocketKprobeIoUring::default(),
+                            tcp_health: EbpfSocketKprobeTcpHealth::default(),
+                            drop_monitor: EbpfSocketKprobeDropMonitor::default(),
                         },
                         tunning: EbpfSocketTunning {
                             max_capture_rate: rc.yaml_config.ebpf.global_ebpf_pps_threshold,
@@ -2757,6 +4093,7 @@ impl From<&RuntimeConfig> for UserConfig {
                                 .ebpf
                                 .syscall_trace_id_disabled,
                             map_prealloc_disabled: rc.yaml_config.ebpf.map_prealloc_disabled,
+                            process_attribution_disabled: false,
                         },
                         preprocess: EbpfSocketPreprocess {
                             out_of_order_reassembly_cache_size: rc
@@ -2780,6 +4117,7 @@ impl From<&RuntimeConfig> for UserConfig {
                             collect_mode: rc.yaml_config.ebpf.io_event_collect_mode,
                             minimal_duration: rc.yaml_config.ebpf.io_event_minimal_duration,
                         },
+                        ..Default::default()
                     },
                     profile: EbpfProfile {
                         on_cpu: EbpfProfileOnCpu {
@@ -2795,6 +4133,12 @@ impl From<&RuntimeConfig> for UserConfig {
                         memory: EbpfProfileMemory {
                             disabled: rc.yaml_config.ebpf.memory_profile.disabled,
                             report_interval: rc.yaml_config.ebpf.memory_profile.report_interval,
+                            sampling_interval: rc.yaml_config.ebpf.memory_profile.sampling_interval,
+                            max_samples_per_second: rc
+                                .yaml_config
+                                .ebpf
+                                .memory_profile
+                                .max_samples_per_second,
                         },
                         unwinding: Unwinding {
                             dwarf_disabled: rc.yaml_config.ebpf.dwarf_disabled,
@@ -2816,7 +4160,11 @@ impl From<&RuntimeConfig> for UserConfig {
                         socket_map_reclaim_threshold: rc.yaml_config.ebpf.socket_map_max_reclaim
                             as u32,
                         max_trace_entries: rc.yaml_config.ebpf.max_trace_entries as u32,
+                        ..Default::default()
                     },
+                    btf: EbpfBtf::default(),
+                    overhead: EbpfOverhead::default(),
+                    process_events: EbpfProcessEvents::default(),
                     java_symbol_file_refresh_defer_interval: rc
                         .yaml_config
                         .ebpf
@@ -2869,6 +4217,9 @@ impl From<&RuntimeConfig> for UserConfig {
                         label_length: rc.yaml_config.prometheus_extra_config.labels_limit as usize,
                         value_length: rc.yaml_config.prometheus_extra_config.values_limit as usize,
                     },
+                    prometheus_scrape: PrometheusScrape::default(),
+                    otlp: OtlpIngest::default(),
+                    statsd: Statsd::default(),
                     feature_control: FeatureControl {
                         profile_integration_disabled: rc
                             .yaml_config
@@ -2881,7 +4232,9 @@ impl From<&RuntimeConfig> for UserConfig {
                             .external_metric_integration_disabled,
                         log_integration_disabled: rc.yaml_config.external_log_integration_disabled,
                     },
+                    tls: MutualTls::default(),
                 },
+                snmp: Snmp::default(),
             },
             outputs: Outputs {
                 socket: Socket {
@@ -2893,6 +4246,9 @@ impl From<&RuntimeConfig> for UserConfig {
                         .unwrap_or(SocketType::Tcp),
                     raw_udp_qos_bypass: rc.yaml_config.enable_qos_bypass,
                     multiple_sockets_to_ingester: rc.yaml_config.multiple_sockets_to_ingester,
+                    disk_buffer: DiskBuffer::default(),
+                    compression: SenderCompression::default(),
+                    destinations: vec![],
                 },
                 flow_log: OutputsFlowLog {
                     filters: FlowLogFilters {
@@ -2921,6 +4277,9 @@ impl From<&RuntimeConfig> for UserConfig {
                         l4_throttle: rc.l4_log_collect_nps_threshold,
                         l7_throttle: rc.l7_log_collect_nps_threshold,
                     },
+                    namespace_quotas: NamespaceQuotas::default(),
+                    deduplication: FlowLogDeduplication::default(),
+                    active_flow_report: ActiveFlowReport::default(),
                     tunning: OutputsFlowLogTunning {
                         collector_queue_size: rc.yaml_config.flow_sender_queue_size,
                         collector_queue_count: rc.yaml_config.flow_sender_queue_count,
@@ -2935,6 +4294,7 @@ impl From<&RuntimeConfig> for UserConfig {
                         apm_metrics: rc.l7_metrics_enabled,
                         second_metrics: rc.vtap_flow_1s_enabled,
                     },
+                    top_talkers: TopTalkers::default(),
                     tunning: FlowMetricsTunning {
                         sender_queue_size: rc.yaml_config.collector_sender_queue_size,
                         sender_queue_count: rc.yaml_config.collector_sender_queue_count,
@@ -2952,7 +4312,14 @@ impl From<&RuntimeConfig> for UserConfig {
                     custom_vxlan_flags: rc.yaml_config.vxlan_flags,
                     overlay_vlan_header_trimming: rc.yaml_config.ignore_overlay_vlan,
                     max_tx_throughput: rc.npb_bps_threshold << 20,
+                    max_tx_throughput_per_destination: 0,
+                    payload_truncation: 0,
                 },
+                otlp_exporter: OtlpExporter::default(),
+                kafka: Kafka::default(),
+                clickhouse: ClickHouse::default(),
+                file_exporter: FileExporter::default(),
+                syslog: Syslog::default(),
             },
             processors: Processors {
                 packet: Packet {
@@ -2973,6 +4340,7 @@ impl From<&RuntimeConfig> for UserConfig {
                         buffer_size_per_flow: rc.yaml_config.pcap.flow_buffer_size,
                         total_buffer_size: rc.yaml_config.pcap.buffer_size,
                         flush_interval: rc.yaml_config.pcap.flush_interval,
+                        format: PcapFormat::Pcap,
                     },
                     toa: Toa {
                         sender_queue_size: rc.yaml_config.toa_sender_queue_size,
@@ -2999,6 +4367,7 @@ impl From<&RuntimeConfig> for UserConfig {
                     },
                     filters: Filters {
                         port_number_prefilters: rc.yaml_config.l7_protocol_ports.clone(),
+                        port_number_exclusions: HashMap::new(),
                         tag_filters: {
                             let mut output = HashMap::new();
                             for (k, v) in rc.yaml_config.l7_log_blacklist.iter() {
@@ -3027,6 +4396,7 @@ impl From<&RuntimeConfig> for UserConfig {
                         session_aggregate_window_duration: rc
                             .yaml_config
                             .l7_log_session_aggr_timeout,
+                        session_aggregate: vec![],
                     },
                     tag_extraction: RequestLogTagExtraction {
                         tracing_tag: TracingTag {
@@ -3094,7 +4464,13 @@ impl From<&RuntimeConfig> for UserConfig {
                             .l7_protocol_advanced_features
                             .obfuscate_enabled_protocols
                             .clone(),
+                        sql_obfuscation_mode: SqlObfuscationMode::default(),
+                        redis_key_patterns: vec![],
+                        grpc_body_fields: vec![],
+                        http_body_fields: vec![],
                     },
+                    alerting: RequestLogAlerting::default(),
+                    sampling: RequestLogSampling::default(),
                     tunning: RequestLogTunning {
                         payload_truncation: rc.l7_log_packet_size,
                         session_aggregate_slot_capacity: rc
@@ -3103,6 +4479,7 @@ impl From<&RuntimeConfig> for UserConfig {
                         consistent_timestamp_in_l7_metrics: rc
                             .yaml_config
                             .consistent_timestamp_in_l7_metrics,
+                        mysql_statement_cache_size: 256,
                     },
                 },
                 flow_log: ProcessorsFlowLog {
@@ -3117,6 +4494,7 @@ impl From<&RuntimeConfig> for UserConfig {
                             cloud_traffic_ignore_mac: rc.yaml_config.flow.ignore_tor_mac,
                             ignore_l2_end: rc.yaml_config.flow.ignore_l2_end,
                             idc_traffic_ignore_vlan: rc.yaml_config.flow.ignore_idc_vlan,
+                            ..Default::default()
                         },
                         timeouts: ConntrackTimeouts {
                             established: rc.yaml_config.flow.established_timeout,
@@ -3124,6 +4502,7 @@ impl From<&RuntimeConfig> for UserConfig {
                             opening_rst: rc.yaml_config.flow.opening_rst_timeout,
                             others: rc.yaml_config.flow.others_timeout,
                         },
+                        nat_correlation: ConntrackNatCorrelation::default(),
                     },
                     tunning: ProcessorsFlowLogTunning {
                         flow_map_hash_slots: rc.yaml_config.flow.hash_slots,
@@ -3149,10 +4528,14 @@ impl From<&RuntimeConfig> for UserConfig {
 const MB: u64 = 1048576;
 
 impl UserConfig {
-    const DEFAULT_DNS_PORTS: &'static str = "53,5353";
+    const DEFAULT_DNS_PORTS: &'static str = "53,853,5353";
     const DEFAULT_TLS_PORTS: &'static str = "443,6443";
     const DEFAULT_ORACLE_PORTS: &'static str = "1521";
     const DEFAULT_MEMCACHED_PORTS: &'static str = "11211";
+    const DEFAULT_QUIC_PORTS: &'static str = "443";
+    const DEFAULT_KERBEROS_PORTS: &'static str = "88";
+    const DEFAULT_MODBUS_PORTS: &'static str = "502";
+    const DEFAULT_DIAMETER_PORTS: &'static str = "3868";
     const PACKET_FANOUT_MODE_MAX: u32 = 7;
 
     pub fn get_fast_path_map_size(&self, mem_size: u64) -> usize {
@@ -3186,7 +4569,7 @@ impl UserConfig {
             .clone();
 
         let dns_str = L7ProtocolParser::DNS(DnsLog::default()).as_str();
-        // dns default only parse 53,5353 port. when l7_protocol_ports config without DNS, need to reserve the dns default config.
+        // dns default only parse 53,853,5353 port. when l7_protocol_ports config without DNS, need to reserve the dns default config.
         if !self
             .processors
             .request_log
@@ -3235,6 +4618,59 @@ impl UserConfig {
                 Self::DEFAULT_MEMCACHED_PORTS.to_string(),
             );
         }
+        let quic_str = L7ProtocolParser::QUIC(QuicLog::default()).as_str();
+        // quic default only parse 443 port. when l7_protocol_ports config without QUIC, need to reserve the quic default config.
+        if !self
+            .processors
+            .request_log
+            .filters
+            .port_number_prefilters
+            .contains_key(quic_str)
+        {
+            new.insert(quic_str.to_string(), Self::DEFAULT_QUIC_PORTS.to_string());
+        }
+        let kerberos_str = L7ProtocolParser::Kerberos(KerberosLog::default()).as_str();
+        // kerberos default only parse 88 port. when l7_protocol_ports config without KERBEROS, need to reserve the kerberos default config.
+        if !self
+            .processors
+            .request_log
+            .filters
+            .port_number_prefilters
+            .contains_key(kerberos_str)
+        {
+            new.insert(
+                kerberos_str.to_string(),
+                Self::DEFAULT_KERBEROS_PORTS.to_string(),
+            );
+        }
+        let modbus_str = L7ProtocolParser::Modbus(ModbusLog::default()).as_str();
+        // modbus default only parse 502 port. when l7_protocol_ports config without MODBUS, need to reserve the modbus default config.
+        if !self
+            .processors
+            .request_log
+            .filters
+            .port_number_prefilters
+            .contains_key(modbus_str)
+        {
+            new.insert(
+                modbus_str.to_string(),
+                Self::DEFAULT_MODBUS_PORTS.to_string(),
+            );
+        }
+        let diameter_str = L7ProtocolParser::Diameter(DiameterLog::default()).as_str();
+        // diameter default only parse 3868 port. when l7_protocol_ports config without DIAMETER, need to reserve the diameter default config.
+        if !self
+            .processors
+            .request_log
+            .filters
+            .port_number_prefilters
+            .contains_key(diameter_str)
+        {
+            new.insert(
+                diameter_str.to_string(),
+                Self::DEFAULT_DIAMETER_PORTS.to_string(),
+            );
+        }
 
         new
     }
@@ -3256,6 +4692,36 @@ impl UserConfig {
                 parse_u16_range_list_to_bitmap(port_range, false).unwrap(),
             ));
         }
+        /*
+            exclusions are removed from the ports above, or from all ports if not configured
+            format example:
+
+                port_number_exclusions:
+                    "HTTP": "9092"
+                ...
+        */
+        for (protocol_name, port_range) in self
+            .processors
+            .request_log
+            .filters
+            .port_number_exclusions
+            .iter()
+        {
+            let excluded = parse_u16_range_list_to_bitmap(port_range, false).unwrap();
+            let index = match port_bitmap.iter().position(|p| &p.0 == protocol_name) {
+                Some(index) => index,
+                None => {
+                    port_bitmap.push((protocol_name.clone(), Bitmap::new(u16::MAX as usize, true)));
+                    port_bitmap.len() - 1
+                }
+            };
+            let bitmap = &mut port_bitmap[index].1;
+            for port in 0..=u16::MAX as usize {
+                if excluded.get(port).unwrap_or_default() {
+                    let _ = bitmap.set(port, false);
+                }
+            }
+        }
         port_bitmap.sort_unstable_by_key(|p| p.0.clone());
         port_bitmap
     }
@@ -3513,6 +4979,8 @@ pub struct MemoryProfile {
     pub regex: String,
     #[serde(with = "humantime_serde")]
     pub report_interval: Duration,
+    pub sampling_interval: u32,
+    pub max_samples_per_second: u32,
 }
 
 impl Default for MemoryProfile {
@@ -3521,6 +4989,8 @@ impl Default for MemoryProfile {
             disabled: true,
             regex: "^java".to_string(),
             report_interval: Duration::from_secs(10),
+            sampling_interval: 1,
+            max_samples_per_second: 10000,
         }
     }
 }
@@ -3888,10 +5358,14 @@ pub struct YamlConfig {
 }
 
 impl YamlConfig {
-    const DEFAULT_DNS_PORTS: &'static str = "53,5353";
+    const DEFAULT_DNS_PORTS: &'static str = "53,853,5353";
     const DEFAULT_TLS_PORTS: &'static str = "443,6443";
     const DEFAULT_ORACLE_PORTS: &'static str = "1521";
     const DEFAULT_MEMCACHED_PORTS: &'static str = "11211";
+    const DEFAULT_QUIC_PORTS: &'static str = "443";
+    const DEFAULT_KERBEROS_PORTS: &'static str = "88";
+    const DEFAULT_MODBUS_PORTS: &'static str = "502";
+    const DEFAULT_DIAMETER_PORTS: &'static str = "3868";
     const PACKET_FANOUT_MODE_MAX: u32 = 7;
     const DEFAULT_L7_PROTOCOL_ENABLED: [&'static str; 7] =
         ["HTTP", "HTTP2", "MySQL", "Redis", "Kafka", "DNS", "TLS"];
@@ -4062,6 +5536,8 @@ impl YamlConfig {
             .memory_profile
             .report_interval
             .clamp(Duration::from_secs(1), Duration::from_secs(60));
+        c.ebpf.memory_profile.sampling_interval =
+            c.ebpf.memory_profile.sampling_interval.clamp(1, 1000000);
         if !(8..=1024).contains(&c.ebpf.syscall_out_of_order_cache_size) {
             c.ebpf.syscall_out_of_order_cache_size = 16;
         }
@@ -4146,7 +5622,7 @@ impl YamlConfig {
         let mut new = self.l7_protocol_ports.clone();
 
         let dns_str = L7ProtocolParser::DNS(DnsLog::default()).as_str();
-        // dns default only parse 53,5353 port. when l7_protocol_ports config without DNS, need to reserve the dns default config.
+        // dns default only parse 53,853,5353 port. when l7_protocol_ports config without DNS, need to reserve the dns default config.
         if !self.l7_protocol_ports.contains_key(dns_str) {
             new.insert(dns_str.to_string(), Self::DEFAULT_DNS_PORTS.to_string());
         }
@@ -4171,6 +5647,35 @@ impl YamlConfig {
                 Self::DEFAULT_MEMCACHED_PORTS.to_string(),
             );
         }
+        let quic_str = L7ProtocolParser::QUIC(QuicLog::default()).as_str();
+        // quic default only parse 443 port. when l7_protocol_ports config without QUIC, need to reserve the quic default config.
+        if !self.l7_protocol_ports.contains_key(quic_str) {
+            new.insert(quic_str.to_string(), Self::DEFAULT_QUIC_PORTS.to_string());
+        }
+        let kerberos_str = L7ProtocolParser::Kerberos(KerberosLog::default()).as_str();
+        // kerberos default only parse 88 port. when l7_protocol_ports config without KERBEROS, need to reserve the kerberos default config.
+        if !self.l7_protocol_ports.contains_key(kerberos_str) {
+            new.insert(
+                kerberos_str.to_string(),
+                Self::DEFAULT_KERBEROS_PORTS.to_string(),
+            );
+        }
+        let modbus_str = L7ProtocolParser::Modbus(ModbusLog::default()).as_str();
+        // modbus default only parse 502 port. when l7_protocol_ports config without MODBUS, need to reserve the modbus default config.
+        if !self.l7_protocol_ports.contains_key(modbus_str) {
+            new.insert(
+                modbus_str.to_string(),
+                Self::DEFAULT_MODBUS_PORTS.to_string(),
+            );
+        }
+        let diameter_str = L7ProtocolParser::Diameter(DiameterLog::default()).as_str();
+        // diameter default only parse 3868 port. when l7_protocol_ports config without DIAMETER, need to reserve the diameter default config.
+        if !self.l7_protocol_ports.contains_key(diameter_str) {
+            new.insert(
+                diameter_str.to_string(),
+                Self::DEFAULT_DIAMETER_PORTS.to_string(),
+            );
+        }
 
         new
     }
@@ -4478,10 +5983,10 @@ pub struct RuntimeConfig {
     pub npb_bps_threshold: u64,
     #[serde(deserialize_with = "bool_from_int")]
     pub collector_enabled: bool,
-    pub l4_log_store_tap_types: Vec<u8>,
+    pub l4_log_store_tap_types: Vec<u16>,
     #[serde(skip)]
     pub app_proto_log_enabled: bool,
-    pub l7_log_store_tap_types: Vec<u8>,
+    pub l7_log_store_tap_types: Vec<u16>,
     #[serde(deserialize_with = "tap_side_vec_de")]
     pub l4_log_ignore_tap_sides: Vec<TapSide>,
     #[serde(deserialize_with = "tap_side_vec_de")]
@@ -4829,7 +6334,7 @@ impl TryFrom<trident::Config> for RuntimeConfig {
                         warn!("invalid tap type: {}", i);
                         None
                     } else {
-                        Some(i as u8)
+                        Some(i as u16)
                     }
                 })
                 .collect(),
@@ -4882,7 +6387,7 @@ impl TryFrom<trident::Config> for RuntimeConfig {
                         warn!("invalid tap type: {}", i);
                         None
                     } else {
-                        Some(i as u8)
+                        Some(i as u16)
                     }
                 })
                 .collect(),
@@ -4960,7 +6465,7 @@ where
             TunnelType::try_from(t).map_err(|_| {
                 de::Error::invalid_value(
                     Unexpected::Unsigned(t as u64),
-                    &"None|Vxlan|Ipip|TencentGre|Geneve|ErspanOrTeb",
+                    &"None|Vxlan|Ipip|TencentGre|Geneve|ErspanOrTeb|GtpU|VxlanGpe|Mpls|Pppoe",
                 )
             })
         })
@@ -5168,6 +6673,35 @@ log_backhaul_enabled: false
             .expect("Failed to parse template.yaml to UserConfig");
     }
 
+    #[test]
+    fn port_number_exclusions() {
+        let mut config = UserConfig::default();
+        let filters = &mut config.processors.request_log.filters;
+        filters
+            .port_number_prefilters
+            .insert("MySQL".to_string(), "3306-3310".to_string());
+        filters
+            .port_number_exclusions
+            .insert("MySQL".to_string(), "3310".to_string());
+        filters
+            .port_number_exclusions
+            .insert("HTTP".to_string(), "9092".to_string());
+
+        let port_bitmap = config.get_protocol_port_parse_bitmap();
+        let enabled = |name: &str, port: usize| {
+            port_bitmap
+                .iter()
+                .find(|p| p.0 == name)
+                .map(|p| p.1.get(port).unwrap())
+                .unwrap()
+        };
+        assert!(enabled("MySQL", 3306));
+        assert!(!enabled("MySQL", 3310));
+        assert!(!enabled("MySQL", 9092));
+        assert!(enabled("HTTP", 80));
+        assert!(!enabled("HTTP", 9092));
+    }
+
     #[test]
     fn parse_tcp_header() {
         let yaml = r#"
@@ -28,8 +28,8 @@ use arc_swap::{access::Map, ArcSwap};
 use base64::{prelude::BASE64_STANDARD, Engine};
 use bytesize::ByteSize;
 use flexi_logger::{
-    writers::FileLogWriter, Age, Cleanup, Criterion, FileSpec, FlexiLoggerError, LoggerHandle,
-    Naming,
+    writers::FileLogWriter, Age, Cleanup, Criterion, FileSpec, FlexiLoggerError, LogSpecification,
+    LoggerHandle, Naming,
 };
 use http2::get_expected_headers;
 use log::{debug, info, warn, Level};
@@ -47,9 +47,13 @@ use tokio::runtime::Runtime;
 use super::config::{Ebpf, EbpfFileIoEvent, ProcessMatcher, SymbolTable};
 use super::{
     config::{
-        ApiResources, Config, ExtraLogFields, ExtraLogFieldsInfo, HttpEndpoint,
-        HttpEndpointMatchRule, OracleConfig, PcapStream, PortConfig, TagFilterOperator, UserConfig,
-        YamlConfig,
+        ActiveFlowReport, ApiResources, ClickHouse, Config, DiskBuffer, ExtraLogFields,
+        ExtraLogFieldsInfo, FileExporter, FlowAnomalyDetection, FlowLogDeduplication,
+        GrpcBodyField, HttpBodyField, HttpEndpoint, HttpEndpointMatchRule, IpFragmentReassembly,
+        Kafka, LocalApi, MutualTls, NamespaceQuotas, OracleConfig, OtlpExporter, PcapStream,
+        PortConfig, PrometheusScrape, RequestLogAlerting, RequestLogSampling, SenderCompression,
+        SenderCompressionAlgorithm, SenderDestination, Snmp, SqlObfuscationMode, Statsd, Syslog,
+        TagFilterOperator, TopTalkers, UserConfig, YamlConfig,
     },
     ConfigError, KubernetesPollerType,
 };
@@ -59,10 +63,15 @@ use crate::flow_generator::protocol_logs::decode_new_rpc_trace_context_with_type
 use crate::rpc::Session;
 use crate::{
     common::{
-        decapsulate::TunnelTypeBitmap, enums::CaptureNetworkType, l7_protocol_log::L7ProtocolBitmap,
+        decapsulate::TunnelTypeBitmap,
+        enums::{CaptureNetworkType, CAPTURE_NETWORK_TYPE_MAX},
+        l7_protocol_log::{L7ProtocolBitmap, L7ProtocolParser},
     },
     exception::ExceptionHandler,
-    flow_generator::{protocol_logs::SOFA_NEW_RPC_TRACE_CTX_KEY, FlowTimeout, TcpTimeout},
+    flow_generator::{
+        protocol_logs::SOFA_NEW_RPC_TRACE_CTX_KEY, DirectionHeuristics, FlowTimeout, PolicyRule,
+        TcpTimeout,
+    },
     handler::PacketHandlerBuilder,
     metric::document::TapSide,
     trident::{AgentComponents, RunningMode},
@@ -82,10 +91,14 @@ use crate::{
     platform::{kubernetes::Poller, ApiWatcher, GenericPoller},
     utils::environment::is_tt_pod,
 };
-use crate::{trident::AgentId, utils::cgroups::is_kernel_available_for_cgroups};
+use crate::{
+    trident::AgentId,
+    utils::{cgroups::is_kernel_available_for_cgroups, log_level::LOG_LEVELS},
+};
 
 use public::bitmap::Bitmap;
-use public::l7_protocol::L7Protocol;
+use public::l7_protocol::{L7Protocol, L7ProtocolChecker};
+use public::packet::SECONDS_IN_MINUTE;
 use public::proto::agent::{self, AgentType, DynamicConfig, PacketCaptureType};
 use public::utils::net::MacAddr;
 
@@ -128,6 +141,10 @@ pub type MetricServerAccess = Access<MetricServerConfig>;
 
 pub type PortAccess = Access<PortConfig>;
 
+pub type SnmpAccess = Access<SnmpConfig>;
+
+pub type XflowAccess = Access<XflowConfig>;
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct CollectorConfig {
     pub enabled: bool,
@@ -135,13 +152,18 @@ pub struct CollectorConfig {
     pub inactive_ip_enabled: bool,
     pub vtap_flow_1s_enabled: bool,
     pub l4_log_collect_nps_threshold: u64,
-    pub l4_log_store_tap_types: [bool; 256],
+    pub l4_log_store_tap_types: [bool; CAPTURE_NETWORK_TYPE_MAX],
     pub l4_log_ignore_tap_sides: [bool; TapSide::MAX as usize + 1],
+    // seconds between the l4 flow logs of an active flow, by tap type
+    pub l4_log_report_intervals: [u64; CAPTURE_NETWORK_TYPE_MAX],
     pub l7_metrics_enabled: bool,
     pub agent_type: AgentType,
     pub agent_id: u16,
     pub cloud_gateway_traffic: bool,
     pub packet_delay: Duration,
+    pub top_talkers: TopTalkers,
+    pub namespace_quotas: NamespaceQuotas,
+    pub flow_log_deduplication: FlowLogDeduplication,
 }
 
 impl fmt::Debug for CollectorConfig {
@@ -178,6 +200,15 @@ impl fmt::Debug for CollectorConfig {
                     })
                     .collect::<Vec<_>>(),
             )
+            .field(
+                "l4_log_report_intervals",
+                &self
+                    .l4_log_report_intervals
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, i)| *i != SECONDS_IN_MINUTE)
+                    .collect::<Vec<_>>(),
+            )
             .field(
                 "l4_log_collect_nps_threshold",
                 &self.l4_log_collect_nps_threshold,
@@ -187,6 +218,9 @@ impl fmt::Debug for CollectorConfig {
             .field("agent_id", &self.agent_id)
             .field("cloud_gateway_traffic", &self.cloud_gateway_traffic)
             .field("packet_delay", &self.packet_delay)
+            .field("top_talkers", &self.top_talkers)
+            .field("namespace_quotas", &self.namespace_quotas)
+            .field("flow_log_deduplication", &self.flow_log_deduplication)
             .finish()
     }
 }
@@ -230,6 +264,16 @@ pub struct SenderConfig {
     pub server_tx_bandwidth_threshold: u64,
     pub bandwidth_probe_interval: Duration,
     pub enabled: bool,
+    pub kafka: Kafka,
+    pub clickhouse: ClickHouse,
+    pub file_exporter: FileExporter,
+    pub syslog: Syslog,
+    pub tls: MutualTls,
+    pub disk_buffer: DiskBuffer,
+    pub compression: SenderCompression,
+    // compression algorithms the ingesters can decompress, advertised by the controller
+    pub ingester_compression_algorithms: Vec<SenderCompressionAlgorithm>,
+    pub destinations: Vec<SenderDestination>,
 }
 
 impl Default for SenderConfig {
@@ -252,6 +296,10 @@ pub struct NpbConfig {
     pub socket_type: agent::SocketType,
     pub ignore_overlay_vlan: bool,
     pub queue_size: usize,
+    // bits per second to each tunnel destination, 0 means unlimited
+    pub destination_bps_threshold: u64,
+    // maximum l4 payload size of forwarded packets, 0 means no truncation
+    pub payload_truncation: usize,
 }
 
 impl Default for NpbConfig {
@@ -420,11 +468,34 @@ impl PluginConfig {
     }
 }
 
-fn generate_tap_types_array(types: &[i16]) -> [bool; 256] {
-    let mut tap_types = [false; 256];
+fn generate_report_intervals(conf: &ActiveFlowReport) -> [u64; CAPTURE_NETWORK_TYPE_MAX] {
+    let to_secs = |interval: Duration| {
+        let secs = interval.as_secs();
+        if secs < 1 || secs > SECONDS_IN_MINUTE {
+            warn!(
+                "invalid active flow report interval {:?}, must in range [1s, 60s]",
+                interval
+            );
+        }
+        secs.clamp(1, SECONDS_IN_MINUTE)
+    };
+    let mut intervals = [to_secs(conf.interval); CAPTURE_NETWORK_TYPE_MAX];
+    for i in conf.capture_network_type_intervals.iter() {
+        let t = i.capture_network_type;
+        if t < 0 || (t as u16) >= u16::from(CaptureNetworkType::Max) {
+            warn!("invalid tap type: {}", t);
+            continue;
+        }
+        intervals[t as usize] = to_secs(i.interval);
+    }
+    intervals
+}
+
+fn generate_tap_types_array(types: &[i16]) -> [bool; CAPTURE_NETWORK_TYPE_MAX] {
+    let mut tap_types = [false; CAPTURE_NETWORK_TYPE_MAX];
     for &t in types {
         if t == -1 {
-            return [false; 256];
+            return [false; CAPTURE_NETWORK_TYPE_MAX];
         } else if t < 0 || (t as u16) >= u16::from(CaptureNetworkType::Max) {
             warn!("invalid tap type: {}", t);
         } else {
@@ -440,7 +511,7 @@ pub struct FlowConfig {
     pub agent_type: AgentType,
     pub cloud_gateway_traffic: bool,
     pub collector_enabled: bool,
-    pub l7_log_tap_types: [bool; 256],
+    pub l7_log_tap_types: [bool; CAPTURE_NETWORK_TYPE_MAX],
 
     pub capacity: u32,
     pub hash_slots: u32,
@@ -481,9 +552,16 @@ pub struct FlowConfig {
 
     pub obfuscate_enabled_protocols: L7ProtocolBitmap,
     pub server_ports: Vec<u16>,
+    pub direction_heuristics: DirectionHeuristics,
     pub consistent_timestamp_in_l7_metrics: bool,
 
     pub packet_segmentation_reassembly: HashSet<u16>,
+    pub ip_fragment_reassembly: IpFragmentReassembly,
+    pub process_attribution_enabled: bool,
+    pub tcp_health_enabled: bool,
+    pub conntrack_nat_enabled: bool,
+    pub anomaly_detection: FlowAnomalyDetection,
+    pub policy_rules: Vec<PolicyRule>,
 }
 
 impl From<(&UserConfig, &DynamicConfig)> for FlowConfig {
@@ -653,6 +731,14 @@ impl From<(&UserConfig, &DynamicConfig)> for FlowConfig {
                 .flow_generation
                 .server_ports
                 .clone(),
+            direction_heuristics: DirectionHeuristics::from(
+                conf.processors
+                    .flow_log
+                    .conntrack
+                    .flow_generation
+                    .direction_heuristics
+                    .as_slice(),
+            ),
             consistent_timestamp_in_l7_metrics: conf
                 .processors
                 .request_log
@@ -666,6 +752,27 @@ impl From<(&UserConfig, &DynamicConfig)> for FlowConfig {
                     .clone()
                     .into_iter(),
             ),
+            ip_fragment_reassembly: conf.inputs.cbpf.preprocess.ip_fragment_reassembly,
+            process_attribution_enabled: !conf.inputs.ebpf.disabled
+                && !conf.inputs.ebpf.socket.tunning.process_attribution_disabled,
+            tcp_health_enabled: !conf.inputs.ebpf.disabled
+                && conf.inputs.ebpf.socket.kprobe.tcp_health.enabled,
+            conntrack_nat_enabled: conf.processors.flow_log.conntrack.nat_correlation.enabled,
+            anomaly_detection: conf.processors.flow_log.anomaly_detection.clone(),
+            policy_rules: conf
+                .processors
+                .flow_log
+                .anomaly_detection
+                .policy_rules
+                .iter()
+                .filter_map(|rule| match PolicyRule::try_from(rule) {
+                    Ok(rule) => Some(rule),
+                    Err(e) => {
+                        warn!("invalid flow policy rule {:?}: {}", rule, e);
+                        None
+                    }
+                })
+                .collect(),
         }
     }
 }
@@ -722,10 +829,19 @@ impl fmt::Debug for FlowConfig {
             // .field("l7_protocol_parse_port_bitmap", &self.l7_protocol_parse_port_bitmap)
             .field("plugins", &self.plugins)
             .field("server_ports", &self.server_ports)
+            .field("direction_heuristics", &self.direction_heuristics)
             .field(
                 "packet_segmentation_reassembly",
                 &self.packet_segmentation_reassembly,
             )
+            .field("ip_fragment_reassembly", &self.ip_fragment_reassembly)
+            .field(
+                "process_attribution_enabled",
+                &self.process_attribution_enabled,
+            )
+            .field("tcp_health_enabled", &self.tcp_health_enabled)
+            .field("conntrack_nat_enabled", &self.conntrack_nat_enabled)
+            .field("anomaly_detection", &self.anomaly_detection)
             .finish()
     }
 }
@@ -963,20 +1079,232 @@ impl From<&Vec<String>> for DnsNxdomainTrie {
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GrpcBodyFieldRule {
+    // package.Service, empty matches all services
+    pub service: String,
+    pub field_name: String,
+    // field numbers from the outermost message, e.g. `1.3` is [1, 3]
+    pub field_path: Vec<u32>,
+    pub in_response: bool,
+}
+
+impl TryFrom<&GrpcBodyField> for GrpcBodyFieldRule {
+    type Error = String;
+
+    fn try_from(f: &GrpcBodyField) -> Result<Self, Self::Error> {
+        if f.field_name.is_empty() {
+            return Err("empty field_name".to_string());
+        }
+        let field_path = f
+            .field_path
+            .split('.')
+            .map(|n| match n.trim().parse::<u32>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("invalid field number {:?}", n)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            service: f.service.clone(),
+            field_name: f.field_name.clone(),
+            field_path,
+            in_response: f.in_response,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpBodyFieldRule {
+    pub field_name: String,
+    // JSON pointer converted from JSON path, e.g. `$.data[0].id` is `/data/0/id`
+    pub json_pointer: String,
+    pub in_response: bool,
+    // if not empty, a 2xx response with other values is regarded as server error
+    pub success_values: Vec<String>,
+}
+
+impl HttpBodyFieldRule {
+    // only `.name` and `[index]` are supported
+    fn json_pointer(path: &str) -> Result<String, String> {
+        let Some(mut path) = path.trim().strip_prefix('$') else {
+            return Err("json_path should start with `$`".to_string());
+        };
+        let mut pointer = String::new();
+        while !path.is_empty() {
+            let token = if let Some(p) = path.strip_prefix('.') {
+                let end = p.find(['.', '[']).unwrap_or(p.len());
+                path = &p[end..];
+                &p[..end]
+            } else if let Some(p) = path.strip_prefix('[') {
+                let end = p.find(']').ok_or("unclosed `[`")?;
+                path = &p[end + 1..];
+                &p[..end]
+            } else {
+                return Err(format!("unexpected {:?}", path));
+            };
+            if token.is_empty() {
+                return Err("empty field name or index".to_string());
+            }
+            pointer.push('/');
+            pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        }
+        Ok(pointer)
+    }
+}
+
+impl TryFrom<&HttpBodyField> for HttpBodyFieldRule {
+    type Error = String;
+
+    fn try_from(f: &HttpBodyField) -> Result<Self, Self::Error> {
+        if f.field_name.is_empty() {
+            return Err("empty field_name".to_string());
+        }
+        Ok(Self {
+            field_name: f.field_name.clone(),
+            json_pointer: Self::json_pointer(&f.json_path)?,
+            in_response: f.in_response,
+            success_values: f.success_values.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct L7LogSamplingRule {
+    // None matches all protocols
+    pub protocol: Option<L7Protocol>,
+    // 0 matches all server ports
+    pub server_port: u16,
+    pub sample_rate: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct L7LogSamplingConfig {
+    pub enabled: bool,
+    pub slow_threshold: Duration,
+    pub sample_rate: u32,
+    pub rules: Vec<L7LogSamplingRule>,
+}
+
+impl Default for L7LogSamplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            slow_threshold: Duration::from_secs(1),
+            sample_rate: 1,
+            rules: vec![],
+        }
+    }
+}
+
+impl L7LogSamplingConfig {
+    // The first matching rule decides the sample rate, 1 means no sampling
+    pub fn sample_rate(&self, protocol: L7Protocol, server_port: u16) -> u32 {
+        if !self.enabled {
+            return 1;
+        }
+        self.rules
+            .iter()
+            .find(|r| {
+                r.protocol.map(|p| p == protocol).unwrap_or(true)
+                    && (r.server_port == 0 || r.server_port == server_port)
+            })
+            .map(|r| r.sample_rate)
+            .unwrap_or(self.sample_rate)
+            .max(1)
+    }
+}
+
+impl From<&RequestLogSampling> for L7LogSamplingConfig {
+    fn from(c: &RequestLogSampling) -> Self {
+        Self {
+            enabled: c.enabled,
+            slow_threshold: c.slow_threshold,
+            sample_rate: c.sample_rate,
+            rules: c
+                .rules
+                .iter()
+                .filter_map(|r| {
+                    let protocol = if r.protocol.is_empty() {
+                        None
+                    } else {
+                        match L7ProtocolParser::try_from(r.protocol.as_str()) {
+                            Ok(p) => Some(p.protocol()),
+                            Err(_) => {
+                                warn!("Unsupported request log sampling protocol: {}", r.protocol);
+                                return None;
+                            }
+                        }
+                    };
+                    Some(L7LogSamplingRule {
+                        protocol,
+                        server_port: r.server_port,
+                        sample_rate: r.sample_rate,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OtlpExporterConfig {
+    pub enabled: bool,
+    pub endpoint: String,
+    pub service_name: String,
+    pub export_protocols: L7ProtocolBitmap,
+    pub batch_size: usize,
+    pub export_timeout: Duration,
+}
+
+impl Default for OtlpExporterConfig {
+    fn default() -> Self {
+        (&OtlpExporter::default()).into()
+    }
+}
+
+impl From<&OtlpExporter> for OtlpExporterConfig {
+    fn from(c: &OtlpExporter) -> Self {
+        let mut export_protocols = L7ProtocolBitmap::from(c.export_protocols.as_slice());
+        // gRPC is parsed by the HTTP2 parser
+        if export_protocols.is_enabled(L7Protocol::Http2) {
+            export_protocols.set_enabled(L7Protocol::Grpc);
+        }
+        Self {
+            // spans can not be exported without an endpoint
+            enabled: c.enabled && !c.endpoint.is_empty(),
+            endpoint: c.endpoint.clone(),
+            service_name: c.service_name.clone(),
+            export_protocols,
+            batch_size: c.batch_size.max(1),
+            export_timeout: c.export_timeout,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct LogParserConfig {
     pub l7_log_collect_nps_threshold: u64,
     pub l7_log_session_aggr_timeout: Duration,
+    pub l7_log_session_aggr_protocol_timeouts: HashMap<L7Protocol, Duration>,
     pub l7_log_session_slot_capacity: usize,
     pub l7_log_dynamic: L7LogDynamicConfig,
     pub l7_log_ignore_tap_sides: [bool; TapSide::MAX as usize + 1],
     pub http_endpoint_disabled: bool,
     pub http_endpoint_trie: HttpEndpointTrie,
     pub obfuscate_enabled_protocols: L7ProtocolBitmap,
+    pub sql_obfuscation_mode: SqlObfuscationMode,
     pub l7_log_blacklist: HashMap<String, Vec<TagFilterOperator>>,
     pub l7_log_blacklist_trie: HashMap<L7Protocol, BlacklistTrie>,
     pub unconcerned_dns_nxdomain_response_suffixes: Vec<String>,
     pub unconcerned_dns_nxdomain_trie: DnsNxdomainTrie,
+    pub redis_key_patterns: Vec<String>,
+    pub mysql_statement_cache_size: usize,
+    pub grpc_body_fields: Vec<GrpcBodyFieldRule>,
+    pub http_body_fields: Vec<HttpBodyFieldRule>,
+    pub alerting: RequestLogAlerting,
+    pub l7_log_sampling: L7LogSamplingConfig,
+    pub namespace_quotas: NamespaceQuotas,
+    pub otlp_exporter: OtlpExporterConfig,
 }
 
 impl Default for LogParserConfig {
@@ -984,20 +1312,40 @@ impl Default for LogParserConfig {
         Self {
             l7_log_collect_nps_threshold: 0,
             l7_log_session_aggr_timeout: Duration::ZERO,
+            l7_log_session_aggr_protocol_timeouts: HashMap::new(),
             l7_log_session_slot_capacity: 1024,
             l7_log_dynamic: L7LogDynamicConfig::default(),
             l7_log_ignore_tap_sides: [false; TapSide::MAX as usize + 1],
             http_endpoint_disabled: false,
             http_endpoint_trie: HttpEndpointTrie::new(),
             obfuscate_enabled_protocols: L7ProtocolBitmap::default(),
+            sql_obfuscation_mode: SqlObfuscationMode::default(),
             l7_log_blacklist: HashMap::new(),
             l7_log_blacklist_trie: HashMap::new(),
             unconcerned_dns_nxdomain_response_suffixes: vec![],
             unconcerned_dns_nxdomain_trie: DnsNxdomainTrie::default(),
+            redis_key_patterns: vec![],
+            mysql_statement_cache_size: 256,
+            grpc_body_fields: vec![],
+            http_body_fields: vec![],
+            alerting: RequestLogAlerting::default(),
+            l7_log_sampling: L7LogSamplingConfig::default(),
+            namespace_quotas: NamespaceQuotas::default(),
+            otlp_exporter: OtlpExporterConfig::default(),
         }
     }
 }
 
+impl LogParserConfig {
+    // the session aggregate window must cover the longest protocol timeout
+    pub fn l7_log_session_aggr_max_timeout(&self) -> Duration {
+        self.l7_log_session_aggr_protocol_timeouts
+            .values()
+            .copied()
+            .fold(self.l7_log_session_aggr_timeout, Duration::max)
+    }
+}
+
 impl fmt::Debug for LogParserConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("LogParserConfig")
@@ -1009,6 +1357,10 @@ impl fmt::Debug for LogParserConfig {
                 "l7_log_session_aggr_timeout",
                 &self.l7_log_session_aggr_timeout,
             )
+            .field(
+                "l7_log_session_aggr_protocol_timeouts",
+                &self.l7_log_session_aggr_protocol_timeouts,
+            )
             .field(
                 "l7_log_session_slot_capacity",
                 &self.l7_log_session_slot_capacity,
@@ -1034,6 +1386,18 @@ impl fmt::Debug for LogParserConfig {
                 "unconcerned_dns_nxdomain_trie",
                 &self.unconcerned_dns_nxdomain_response_suffixes,
             )
+            .field("sql_obfuscation_mode", &self.sql_obfuscation_mode)
+            .field("redis_key_patterns", &self.redis_key_patterns)
+            .field(
+                "mysql_statement_cache_size",
+                &self.mysql_statement_cache_size,
+            )
+            .field("grpc_body_fields", &self.grpc_body_fields)
+            .field("http_body_fields", &self.http_body_fields)
+            .field("alerting", &self.alerting)
+            .field("l7_log_sampling", &self.l7_log_sampling)
+            .field("namespace_quotas", &self.namespace_quotas)
+            .field("otlp_exporter", &self.otlp_exporter)
             .finish()
     }
 }
@@ -1046,6 +1410,7 @@ pub struct DebugConfig {
     pub controller_port: u16,
     pub listen_port: u16,
     pub agent_mode: RunningMode,
+    pub local_api: LocalApi,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -1083,7 +1448,7 @@ pub struct EbpfConfig {
     pub l7_log_session_timeout: Duration,
     pub l7_protocol_inference_max_fail_count: usize,
     pub l7_protocol_inference_ttl: usize,
-    pub l7_log_tap_types: [bool; 256],
+    pub l7_log_tap_types: [bool; CAPTURE_NETWORK_TYPE_MAX],
     pub ctrl_mac: MacAddr,
     pub l7_protocol_enabled_bitmap: L7ProtocolBitmap,
     pub l7_protocol_parse_port_bitmap: Arc<Vec<(String, Bitmap)>>,
@@ -1473,6 +1838,22 @@ pub struct MetricServerConfig {
     pub port: u16,
     pub compressed: bool,
     pub profile_compressed: bool,
+    pub prometheus_scrape: PrometheusScrape,
+    pub statsd: Statsd,
+    pub tls: MutualTls,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnmpConfig {
+    pub agent_id: u16,
+    pub snmp: Snmp,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XflowConfig {
+    pub agent_id: u16,
+    pub sflow_ports: Vec<u16>,
+    pub netflow_ports: Vec<u16>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -1500,6 +1881,8 @@ pub struct ModuleConfig {
     pub agent_type: AgentType,
     pub metric_server: MetricServerConfig,
     pub port_config: PortConfig,
+    pub snmp: SnmpConfig,
+    pub xflow: XflowConfig,
 }
 
 impl Default for ModuleConfig {
@@ -1688,6 +2071,26 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                 standalone_data_file_size: conf.global.standalone_mode.max_data_file_size,
                 standalone_data_file_dir: conf.global.standalone_mode.data_file_dir.clone(),
                 enabled: conf.outputs.flow_metrics.enabled,
+                kafka: conf.outputs.kafka.clone(),
+                clickhouse: conf.outputs.clickhouse.clone(),
+                file_exporter: conf.outputs.file_exporter.clone(),
+                syslog: conf.outputs.syslog.clone(),
+                tls: conf.global.communication.tls.clone(),
+                disk_buffer: conf.outputs.socket.disk_buffer.clone(),
+                compression: conf.outputs.socket.compression.clone(),
+                ingester_compression_algorithms: dynamic_config
+                    .ingester_compression_algorithms
+                    .iter()
+                    .filter_map(|name| SenderCompressionAlgorithm::from_name(name))
+                    .collect(),
+                destinations: conf
+                    .outputs
+                    .socket
+                    .destinations
+                    .iter()
+                    .filter(|d| !d.address.is_empty())
+                    .cloned()
+                    .collect(),
             },
             npb: NpbConfig {
                 mtu: conf.outputs.npb.max_mtu,
@@ -1701,6 +2104,8 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                 dedup_enabled: conf.outputs.npb.traffic_global_dedup,
                 socket_type: conf.outputs.socket.npb_socket_type,
                 queue_size: conf.outputs.flow_metrics.tunning.sender_queue_size,
+                destination_bps_threshold: conf.outputs.npb.max_tx_throughput_per_destination,
+                payload_truncation: conf.outputs.npb.payload_truncation as usize,
             },
             collector: CollectorConfig {
                 enabled: conf.outputs.flow_metrics.enabled,
@@ -1718,6 +2123,9 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                 l4_log_store_tap_types: generate_tap_types_array(
                     &conf.outputs.flow_log.filters.l4_capture_network_types,
                 ),
+                l4_log_report_intervals: generate_report_intervals(
+                    &conf.outputs.flow_log.active_flow_report,
+                ),
                 l4_log_ignore_tap_sides: {
                     let mut tap_sides = [false; TapSide::MAX as usize + 1];
                     for t in conf
@@ -1742,6 +2150,9 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                     .flow_log
                     .time_window
                     .max_tolerable_packet_delay,
+                top_talkers: conf.outputs.flow_metrics.top_talkers,
+                namespace_quotas: conf.outputs.flow_log.namespace_quotas.clone(),
+                flow_log_deduplication: conf.outputs.flow_log.deduplication,
             },
             handler: HandlerConfig {
                 npb_dedup_enabled: conf.outputs.npb.traffic_global_dedup,
@@ -1831,6 +2242,24 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                     .request_log
                     .timeouts
                     .session_aggregate_window_duration,
+                l7_log_session_aggr_protocol_timeouts: conf
+                    .processors
+                    .request_log
+                    .timeouts
+                    .session_aggregate
+                    .iter()
+                    .filter_map(|t| match L7ProtocolParser::try_from(t.protocol.as_str()) {
+                        Ok(p) => Some((
+                            p.protocol(),
+                            t.timeout
+                                .clamp(Duration::from_secs(10), Duration::from_secs(3600)),
+                        )),
+                        Err(_) => {
+                            warn!("Unsupported session aggregate protocol: {}", t.protocol);
+                            None
+                        }
+                    })
+                    .collect(),
                 l7_log_session_slot_capacity: conf
                     .processors
                     .request_log
@@ -1916,6 +2345,11 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                         .obfuscate_protocols
                         .as_slice(),
                 ),
+                sql_obfuscation_mode: conf
+                    .processors
+                    .request_log
+                    .tag_extraction
+                    .sql_obfuscation_mode,
                 l7_log_blacklist: conf.processors.request_log.filters.tag_filters.clone(),
                 l7_log_blacklist_trie: {
                     let mut blacklist_trie = HashMap::new();
@@ -1942,6 +2376,49 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                         .filters
                         .unconcerned_dns_nxdomain_response_suffixes,
                 ),
+                redis_key_patterns: conf
+                    .processors
+                    .request_log
+                    .tag_extraction
+                    .redis_key_patterns
+                    .clone(),
+                mysql_statement_cache_size: conf
+                    .processors
+                    .request_log
+                    .tunning
+                    .mysql_statement_cache_size,
+                grpc_body_fields: conf
+                    .processors
+                    .request_log
+                    .tag_extraction
+                    .grpc_body_fields
+                    .iter()
+                    .filter_map(|f| match GrpcBodyFieldRule::try_from(f) {
+                        Ok(rule) => Some(rule),
+                        Err(e) => {
+                            warn!("Ignore grpc_body_fields {:?}: {}", f, e);
+                            None
+                        }
+                    })
+                    .collect(),
+                http_body_fields: conf
+                    .processors
+                    .request_log
+                    .tag_extraction
+                    .http_body_fields
+                    .iter()
+                    .filter_map(|f| match HttpBodyFieldRule::try_from(f) {
+                        Ok(rule) => Some(rule),
+                        Err(e) => {
+                            warn!("Ignore http_body_fields {:?}: {}", f, e);
+                            None
+                        }
+                    })
+                    .collect(),
+                alerting: conf.processors.request_log.alerting,
+                l7_log_sampling: (&conf.processors.request_log.sampling).into(),
+                namespace_quotas: conf.outputs.flow_log.namespace_quotas.clone(),
+                otlp_exporter: (&conf.outputs.otlp_exporter).into(),
             },
             debug: DebugConfig {
                 agent_id: dynamic_config.agent_id() as u16,
@@ -1954,6 +2431,7 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                 listen_port: conf.global.self_monitoring.debug.local_udp_port,
                 controller_port: static_config.controller_port,
                 agent_mode: static_config.agent_mode,
+                local_api: conf.global.self_monitoring.debug.local_api,
             },
             log: LogConfig {
                 log_level: conf.global.self_monitoring.log.log_level,
@@ -2053,12 +2531,36 @@ impl TryFrom<(Config, UserConfig, DynamicConfig)> for ModuleConfig {
                 port: conf.inputs.integration.listen_port,
                 compressed: conf.inputs.integration.compression.trace,
                 profile_compressed: conf.inputs.integration.compression.profile,
+                prometheus_scrape: conf.inputs.integration.prometheus_scrape.clone(),
+                statsd: conf.inputs.integration.statsd.clone(),
+                tls: conf.inputs.integration.tls.clone(),
             },
             agent_type: conf.global.common.agent_type,
             port_config: PortConfig {
                 analyzer_port: conf.global.communication.ingester_port,
                 proxy_controller_port: conf.global.communication.proxy_controller_port,
             },
+            snmp: SnmpConfig {
+                agent_id: dynamic_config.agent_id() as u16,
+                snmp: conf.inputs.snmp.clone(),
+            },
+            xflow: XflowConfig {
+                agent_id: dynamic_config.agent_id() as u16,
+                sflow_ports: conf
+                    .inputs
+                    .cbpf
+                    .special_network
+                    .physical_switch
+                    .sflow_ports
+                    .clone(),
+                netflow_ports: conf
+                    .inputs
+                    .cbpf
+                    .special_network
+                    .physical_switch
+                    .netflow_ports
+                    .clone(),
+            },
         };
         Ok(config)
     }
@@ -2115,7 +2617,8 @@ impl ConfigHandler {
         }
     }
 
-    pub fn set_logger_handle(&mut self, handle: LoggerHandle) {
+    pub fn set_logger_handle(&mut self, handle: LoggerHandle, spec: &LogSpecification) {
+        LOG_LEVELS.set_logger_handle(handle.clone(), spec);
         self.logger_handle.replace(handle);
     }
 
@@ -2219,6 +2722,18 @@ impl ConfigHandler {
         )
     }
 
+    pub fn snmp(&self) -> SnmpAccess {
+        Map::new(self.current_config.clone(), |config| -> &SnmpConfig {
+            &config.snmp
+        })
+    }
+
+    pub fn xflow(&self) -> XflowAccess {
+        Map::new(self.current_config.clone(), |config| -> &XflowConfig {
+            &config.xflow
+        })
+    }
+
     pub fn port(&self) -> PortAccess {
         Map::new(self.current_config.clone(), |config| -> &PortConfig {
             &config.port_config
@@ -2388,17 +2903,11 @@ impl ConfigHandler {
         }
     }
 
-    fn set_log_level(logger_handle: &mut Option<LoggerHandle>, log_level: &Level) -> bool {
-        match logger_handle.as_mut() {
-            Some(h) => match h.parse_and_push_temp_spec(log_level.as_str().to_lowercase()) {
-                Ok(_) => true,
-                Err(e) => {
-                    warn!("failed to set log_level: {}", e);
-                    false
-                }
-            },
-            None => {
-                warn!("logger_handle not set");
+    fn set_log_level(log_level: &Level, module_log_levels: &[String]) -> bool {
+        match LOG_LEVELS.configure(*log_level, module_log_levels) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("failed to set log_level: {}", e);
                 false
             }
         }
@@ -2449,6 +2958,9 @@ impl ConfigHandler {
     fn set_debug(handler: &ConfigHandler, components: &mut AgentComponents) {
         if handler.candidate_config.debug.enabled {
             components.debugger.start();
+            components
+                .debugger
+                .set_local_api(&handler.candidate_config.debug.local_api);
         } else {
             components.debugger.stop();
         }
@@ -2491,6 +3003,10 @@ impl ConfigHandler {
             .metrics_server_component
             .external_metrics_server
             .set_port(handler.candidate_config.metric_server.port);
+        components
+            .metrics_server_component
+            .external_metrics_server
+            .set_tls(&handler.candidate_config.metric_server.tls);
     }
 
     fn set_npb(handler: &ConfigHandler, components: &mut AgentComponents) {
@@ -2606,8 +3122,8 @@ impl ConfigHandler {
                 "Update inputs.cbpf.af_packet.extra_bpf_filter from {:?} to {:?}.",
                 af_packet.extra_bpf_filter, new_af_packet.extra_bpf_filter
             );
+            // the filter is attached to the live sockets by dispatchers
             af_packet.extra_bpf_filter = new_af_packet.extra_bpf_filter.clone();
-            restart_agent = !first_run;
         }
         if af_packet.extra_netns_regex != new_af_packet.extra_netns_regex {
             info!(
@@ -2678,6 +3194,14 @@ impl ConfigHandler {
             tunning.packet_fanout_mode = new_tunning.packet_fanout_mode;
             restart_agent = !first_run;
         }
+        if tunning.hardware_timestamp_enabled != new_tunning.hardware_timestamp_enabled {
+            info!(
+                "Update inputs.cbpf.af_packet.tunning.hardware_timestamp_enabled from {:?} to {:?}.",
+                tunning.hardware_timestamp_enabled, new_tunning.hardware_timestamp_enabled
+            );
+            tunning.hardware_timestamp_enabled = new_tunning.hardware_timestamp_enabled;
+            restart_agent = !first_run;
+        }
         if tunning.ring_blocks != new_tunning.ring_blocks {
             info!(
                 "Update inputs.cbpf.af_packet.tunning.ring_blocks from {:?} to {:?}.",
@@ -2731,6 +3255,14 @@ impl ConfigHandler {
             physical_mirror.packet_dedup_disabled = new_physical_mirror.packet_dedup_disabled;
             restart_agent = !first_run;
         }
+        if physical_mirror.packet_dedup_window != new_physical_mirror.packet_dedup_window {
+            info!(
+                "Update inputs.cbpf.physical_mirror.packet_dedup_window from {:?} to {:?}.",
+                physical_mirror.packet_dedup_window, new_physical_mirror.packet_dedup_window
+            );
+            physical_mirror.packet_dedup_window = new_physical_mirror.packet_dedup_window;
+            restart_agent = !first_run;
+        }
         if physical_mirror.private_cloud_gateway_traffic
             != new_physical_mirror.private_cloud_gateway_traffic
         {
@@ -2764,6 +3296,21 @@ impl ConfigHandler {
                 new_preprocess.packet_segmentation_reassembly.clone();
             restart_agent = !first_run;
         }
+        if preprocess.ip_fragment_reassembly != new_preprocess.ip_fragment_reassembly {
+            info!(
+                "Update inputs.cbpf.preprocess.ip_fragment_reassembly from {:?} to {:?}.",
+                preprocess.ip_fragment_reassembly, new_preprocess.ip_fragment_reassembly
+            );
+            preprocess.ip_fragment_reassembly = new_preprocess.ip_fragment_reassembly;
+        }
+        if preprocess.packet_sampling != new_preprocess.packet_sampling {
+            info!(
+                "Update inputs.cbpf.preprocess.packet_sampling from {:?} to {:?}.",
+                preprocess.packet_sampling, new_preprocess.packet_sampling
+            );
+            preprocess.packet_sampling = new_preprocess.packet_sampling;
+            restart_agent = !first_run;
+        }
         if preprocess.tunnel_decap_protocols != new_preprocess.tunnel_decap_protocols {
             info!(
                 "Update inputs.cbpf.preprocess.tunnel_decap_protocols from {:?} to {:?}.",
@@ -2782,12 +3329,12 @@ impl ConfigHandler {
 
         let special_network = &mut config.inputs.cbpf.special_network;
         let new_special_network = &mut new_config.user_config.inputs.cbpf.special_network;
-        if special_network.dpdk.enabled != new_special_network.dpdk.enabled {
+        if special_network.dpdk != new_special_network.dpdk {
             info!(
-                "Update inputs.cbpf.special_network.dpdk.enabled from {:?} to {:?}.",
-                special_network.dpdk.enabled, new_special_network.dpdk.enabled
+                "Update inputs.cbpf.special_network.dpdk from {:?} to {:?}.",
+                special_network.dpdk, new_special_network.dpdk
             );
-            special_network.dpdk.enabled = new_special_network.dpdk.enabled;
+            special_network.dpdk = new_special_network.dpdk.clone();
             restart_agent = !first_run;
         }
         if special_network.libpcap.enabled != new_special_network.libpcap.enabled {
@@ -2822,6 +3369,22 @@ impl ConfigHandler {
                 new_special_network.vhost_user.vhost_socket_path.clone();
             restart_agent = !first_run;
         }
+        if special_network.af_xdp != new_special_network.af_xdp {
+            info!(
+                "Update inputs.cbpf.special_network.af_xdp from {:?} to {:?}.",
+                special_network.af_xdp, new_special_network.af_xdp
+            );
+            special_network.af_xdp = new_special_network.af_xdp;
+            restart_agent = !first_run;
+        }
+        if special_network.pcap_replay != new_special_network.pcap_replay {
+            info!(
+                "Update inputs.cbpf.special_network.pcap_replay from {:?} to {:?}.",
+                special_network.pcap_replay, new_special_network.pcap_replay
+            );
+            special_network.pcap_replay = new_special_network.pcap_replay.clone();
+            restart_agent = !first_run;
+        }
 
         let tunning = &mut config.inputs.cbpf.tunning;
         let new_tunning = &mut new_config.user_config.inputs.cbpf.tunning;
@@ -2833,6 +3396,14 @@ impl ConfigHandler {
             tunning.dispatcher_queue_enabled = new_tunning.dispatcher_queue_enabled;
             restart_agent = !first_run;
         }
+        if tunning.parallel_dispatcher_enabled != new_tunning.parallel_dispatcher_enabled {
+            info!(
+                "Update inputs.cbpf.tunning.parallel_dispatcher_enabled from {:?} to {:?}.",
+                tunning.parallel_dispatcher_enabled, new_tunning.parallel_dispatcher_enabled
+            );
+            tunning.parallel_dispatcher_enabled = new_tunning.parallel_dispatcher_enabled;
+            restart_agent = !first_run;
+        }
         if tunning.max_capture_packet_size != new_tunning.max_capture_packet_size {
             info!(
                 "Update inputs.cbpf.tunning.max_capture_packet_size from {:?} to {:?}.",
@@ -2894,6 +3465,23 @@ impl ConfigHandler {
             io_event.minimal_duration = new_io_event.minimal_duration;
             restart_agent = !first_run;
         }
+        let io_stats = &mut ebpf.file.io_stats;
+        let new_io_stats = &mut new_ebpf.file.io_stats;
+        if io_stats.enabled != new_io_stats.enabled {
+            info!(
+                "Update inputs.ebpf.file.io_stats.enabled from {:?} to {:?}.",
+                io_stats.enabled, new_io_stats.enabled
+            );
+            io_stats.enabled = new_io_stats.enabled;
+            restart_agent = !first_run;
+        }
+        if io_stats.path_filters != new_io_stats.path_filters {
+            info!(
+                "Update inputs.ebpf.file.io_stats.path_filters from {:?} to {:?}.",
+                io_stats.path_filters, new_io_stats.path_filters
+            );
+            io_stats.path_filters = new_io_stats.path_filters.clone();
+        }
         if ebpf.java_symbol_file_refresh_defer_interval
             != new_ebpf.java_symbol_file_refresh_defer_interval
         {
@@ -2952,6 +3540,22 @@ impl ConfigHandler {
             memory.report_interval = new_memory.report_interval;
             restart_agent = !first_run;
         }
+        if memory.sampling_interval != new_memory.sampling_interval {
+            info!(
+                "Update inputs.ebpf.profile.memory.sampling_interval from {:?} to {:?}.",
+                memory.sampling_interval, new_memory.sampling_interval
+            );
+            memory.sampling_interval = new_memory.sampling_interval;
+            restart_agent = !first_run;
+        }
+        if memory.max_samples_per_second != new_memory.max_samples_per_second {
+            info!(
+                "Update inputs.ebpf.profile.memory.max_samples_per_second from {:?} to {:?}.",
+                memory.max_samples_per_second, new_memory.max_samples_per_second
+            );
+            memory.max_samples_per_second = new_memory.max_samples_per_second;
+            restart_agent = !first_run;
+        }
 
         let off_cpu = &mut ebpf.profile.off_cpu;
         let new_off_cpu = &mut new_ebpf.profile.off_cpu;
@@ -3046,6 +3650,38 @@ impl ConfigHandler {
             kprobe.whitelist.ports = new_kprobe.whitelist.ports.clone();
             restart_agent = !first_run;
         }
+        if kprobe.unix_socket.enabled != new_kprobe.unix_socket.enabled {
+            info!(
+                "Update inputs.ebpf.socket.kprobe.unix_socket.enabled from {:?} to {:?}.",
+                kprobe.unix_socket.enabled, new_kprobe.unix_socket.enabled
+            );
+            kprobe.unix_socket.enabled = new_kprobe.unix_socket.enabled;
+            restart_agent = !first_run;
+        }
+        if kprobe.io_uring.enabled != new_kprobe.io_uring.enabled {
+            info!(
+                "Update inputs.ebpf.socket.kprobe.io_uring.enabled from {:?} to {:?}.",
+                kprobe.io_uring.enabled, new_kprobe.io_uring.enabled
+            );
+            kprobe.io_uring.enabled = new_kprobe.io_uring.enabled;
+            restart_agent = !first_run;
+        }
+        if kprobe.tcp_health.enabled != new_kprobe.tcp_health.enabled {
+            info!(
+                "Update inputs.ebpf.socket.kprobe.tcp_health.enabled from {:?} to {:?}.",
+                kprobe.tcp_health.enabled, new_kprobe.tcp_health.enabled
+            );
+            kprobe.tcp_health.enabled = new_kprobe.tcp_health.enabled;
+            restart_agent = !first_run;
+        }
+        if kprobe.drop_monitor.enabled != new_kprobe.drop_monitor.enabled {
+            info!(
+                "Update inputs.ebpf.socket.kprobe.drop_monitor.enabled from {:?} to {:?}.",
+                kprobe.drop_monitor.enabled, new_kprobe.drop_monitor.enabled
+            );
+            kprobe.drop_monitor.enabled = new_kprobe.drop_monitor.enabled;
+            restart_agent = !first_run;
+        }
 
         let uprobe = &mut ebpf.socket.uprobe;
         let new_uprobe = &mut new_ebpf.socket.uprobe;
@@ -3057,6 +3693,14 @@ impl ConfigHandler {
             uprobe.tls.enabled = new_uprobe.tls.enabled;
             restart_agent = !first_run;
         }
+        if uprobe.dns.enabled != new_uprobe.dns.enabled {
+            info!(
+                "Update inputs.ebpf.socket.uprobe.dns.enabled from {:?} to {:?}.",
+                uprobe.dns.enabled, new_uprobe.dns.enabled
+            );
+            uprobe.dns.enabled = new_uprobe.dns.enabled;
+            restart_agent = !first_run;
+        }
         let golang_uprobe = &mut uprobe.golang;
         let new_golang_uprobe = &mut new_uprobe.golang;
         if golang_uprobe.enabled != new_golang_uprobe.enabled {
@@ -3132,6 +3776,13 @@ impl ConfigHandler {
             tunning.max_capture_rate = new_tunning.max_capture_rate;
             restart_agent = !first_run;
         }
+        if tunning.process_attribution_disabled != new_tunning.process_attribution_disabled {
+            info!(
+                "Update inputs.ebpf.socket.tunning.process_attribution_disabled from {:?} to {:?}.",
+                tunning.process_attribution_disabled, new_tunning.process_attribution_disabled
+            );
+            tunning.process_attribution_disabled = new_tunning.process_attribution_disabled;
+        }
 
         let tunning = &mut ebpf.tunning;
         let new_tunning = &mut new_ebpf.tunning;
@@ -3167,6 +3818,29 @@ impl ConfigHandler {
             tunning.max_trace_entries = new_tunning.max_trace_entries;
             restart_agent = !first_run;
         }
+        if tunning.map_max_entries != new_tunning.map_max_entries {
+            info!(
+                "Update inputs.ebpf.tunning.map_max_entries from {:?} to {:?}.",
+                tunning.map_max_entries, new_tunning.map_max_entries
+            );
+            tunning.map_max_entries = new_tunning.map_max_entries.clone();
+            restart_agent = !first_run;
+        }
+        if tunning.map_pressure_threshold != new_tunning.map_pressure_threshold {
+            info!(
+                "Update inputs.ebpf.tunning.map_pressure_threshold from {:?} to {:?}.",
+                tunning.map_pressure_threshold, new_tunning.map_pressure_threshold
+            );
+            tunning.map_pressure_threshold = new_tunning.map_pressure_threshold;
+        }
+        if tunning.socket_data_buffer != new_tunning.socket_data_buffer {
+            info!(
+                "Update inputs.ebpf.tunning.socket_data_buffer from {:?} to {:?}.",
+                tunning.socket_data_buffer, new_tunning.socket_data_buffer
+            );
+            tunning.socket_data_buffer = new_tunning.socket_data_buffer;
+            restart_agent = !first_run;
+        }
         if tunning.perf_pages_count != new_tunning.perf_pages_count {
             info!(
                 "Update inputs.ebpf.tunning.perf_pages_count from {:?} to {:?}.",
@@ -3191,6 +3865,30 @@ impl ConfigHandler {
             tunning.userspace_worker_threads = new_tunning.userspace_worker_threads;
             restart_agent = !first_run;
         }
+        if ebpf.btf != new_ebpf.btf {
+            info!(
+                "Update inputs.ebpf.btf from {:?} to {:?}.",
+                ebpf.btf, new_ebpf.btf
+            );
+            ebpf.btf = new_ebpf.btf.clone();
+            restart_agent = !first_run;
+        }
+        if ebpf.overhead != new_ebpf.overhead {
+            info!(
+                "Update inputs.ebpf.overhead from {:?} to {:?}.",
+                ebpf.overhead, new_ebpf.overhead
+            );
+            ebpf.overhead = new_ebpf.overhead.clone();
+            restart_agent = !first_run;
+        }
+        if ebpf.process_events != new_ebpf.process_events {
+            info!(
+                "Update inputs.ebpf.process_events from {:?} to {:?}.",
+                ebpf.process_events, new_ebpf.process_events
+            );
+            ebpf.process_events = new_ebpf.process_events.clone();
+            restart_agent = !first_run;
+        }
 
         let integration = &mut config.inputs.integration;
         let new_integration = &mut new_config.user_config.inputs.integration;
@@ -3234,6 +3932,43 @@ impl ConfigHandler {
             integration.prometheus_extra_labels = new_integration.prometheus_extra_labels.clone();
             restart_agent = !first_run;
         }
+        if integration.prometheus_scrape != new_integration.prometheus_scrape {
+            info!(
+                "Update inputs.integration.prometheus_scrape from {:?} to {:?}.",
+                integration.prometheus_scrape, new_integration.prometheus_scrape
+            );
+            integration.prometheus_scrape = new_integration.prometheus_scrape.clone();
+        }
+        if integration.otlp != new_integration.otlp {
+            info!(
+                "Update inputs.integration.otlp from {:?} to {:?}.",
+                integration.otlp, new_integration.otlp
+            );
+            integration.otlp = new_integration.otlp.clone();
+            restart_agent = !first_run;
+        }
+        if integration.statsd != new_integration.statsd {
+            info!(
+                "Update inputs.integration.statsd from {:?} to {:?}.",
+                integration.statsd, new_integration.statsd
+            );
+            integration.statsd = new_integration.statsd.clone();
+        }
+        if integration.tls != new_integration.tls {
+            info!(
+                "Update inputs.integration.tls from {:?} to {:?}.",
+                integration.tls, new_integration.tls
+            );
+            integration.tls = new_integration.tls.clone();
+        }
+
+        if config.inputs.snmp != new_config.user_config.inputs.snmp {
+            info!(
+                "Update inputs.snmp from {:?} to {:?}.",
+                config.inputs.snmp, new_config.user_config.inputs.snmp
+            );
+            config.inputs.snmp = new_config.user_config.inputs.snmp.clone();
+        }
 
         let resources = &mut config.inputs.resources;
         let new_resources = &mut new_config.user_config.inputs.resources;
@@ -3617,6 +4352,13 @@ impl ConfigHandler {
             );
             communication.proxy_controller_port = new_communication.proxy_controller_port;
         }
+        if communication.tls != new_communication.tls {
+            info!(
+                "Update global.communication.tls from {:?} to {:?}.",
+                communication.tls, new_communication.tls
+            );
+            communication.tls = new_communication.tls.clone();
+        }
 
         let limits = &mut config.global.limits;
         let new_limits = &mut new_config.user_config.global.limits;
@@ -3713,6 +4455,13 @@ impl ConfigHandler {
             debug.local_udp_port = debug.local_udp_port;
             restart_agent = !first_run;
         }
+        if debug.local_api != new_debug.local_api {
+            info!(
+                "Update global.self_monitoring.debug.local_api from {:?} to {:?}.",
+                debug.local_api, new_debug.local_api
+            );
+            debug.local_api = new_debug.local_api;
+        }
 
         if self_monitoring.hostname != new_self_monitoring.hostname {
             info!(
@@ -3759,10 +4508,21 @@ impl ConfigHandler {
                 "Update global.self_monitoring.log.log_level from {:?} to {:?}.",
                 log.log_level, new_log.log_level
             );
-            if Self::set_log_level(logger_handle, &new_log.log_level) {
+        }
+        if log.module_log_levels != new_log.module_log_levels {
+            info!(
+                "Update global.self_monitoring.log.module_log_levels from {:?} to {:?}.",
+                log.module_log_levels, new_log.module_log_levels
+            );
+        }
+        if log.log_level != new_log.log_level || log.module_log_levels != new_log.module_log_levels
+        {
+            if Self::set_log_level(&new_log.log_level, &new_log.module_log_levels) {
                 log.log_level = new_log.log_level;
+                log.module_log_levels = new_log.module_log_levels.clone();
             } else {
                 new_log.log_level = log.log_level;
+                new_log.module_log_levels = log.module_log_levels.clone();
             }
         }
         if self_monitoring.profile.enabled != new_self_monitoring.profile.enabled {
@@ -3881,6 +4641,27 @@ impl ConfigHandler {
             );
             socket.npb_socket_type = new_socket.npb_socket_type;
         }
+        if socket.disk_buffer != new_socket.disk_buffer {
+            info!(
+                "Update outputs.socket.disk_buffer from {:?} to {:?}.",
+                socket.disk_buffer, new_socket.disk_buffer
+            );
+            socket.disk_buffer = new_socket.disk_buffer.clone();
+        }
+        if socket.compression != new_socket.compression {
+            info!(
+                "Update outputs.socket.compression from {:?} to {:?}.",
+                socket.compression, new_socket.compression
+            );
+            socket.compression = new_socket.compression.clone();
+        }
+        if socket.destinations != new_socket.destinations {
+            info!(
+                "Update outputs.socket.destinations from {:?} to {:?}.",
+                socket.destinations, new_socket.destinations
+            );
+            socket.destinations = new_socket.destinations.clone();
+        }
 
         let flow_log = &mut outputs.flow_log;
         let new_flow_log = &mut new_outputs.flow_log;
@@ -3934,6 +4715,30 @@ impl ConfigHandler {
             throttles.l7_throttle = new_throttles.l7_throttle;
         }
 
+        if flow_log.namespace_quotas != new_flow_log.namespace_quotas {
+            info!(
+                "Update outputs.flow_log.namespace_quotas from {:?} to {:?}.",
+                flow_log.namespace_quotas, new_flow_log.namespace_quotas
+            );
+            flow_log.namespace_quotas = new_flow_log.namespace_quotas.clone();
+        }
+
+        if flow_log.deduplication != new_flow_log.deduplication {
+            info!(
+                "Update outputs.flow_log.deduplication from {:?} to {:?}.",
+                flow_log.deduplication, new_flow_log.deduplication
+            );
+            flow_log.deduplication = new_flow_log.deduplication;
+        }
+
+        if flow_log.active_flow_report != new_flow_log.active_flow_report {
+            info!(
+                "Update outputs.flow_log.active_flow_report from {:?} to {:?}.",
+                flow_log.active_flow_report, new_flow_log.active_flow_report
+            );
+            flow_log.active_flow_report = new_flow_log.active_flow_report.clone();
+        }
+
         let tunning = &mut flow_log.tunning;
         let new_tunning = &mut new_flow_log.tunning;
         if tunning.collector_queue_count != new_tunning.collector_queue_count {
@@ -3998,6 +4803,13 @@ impl ConfigHandler {
             );
             filters.second_metrics = new_filters.second_metrics;
         }
+        if outputs.flow_metrics.top_talkers != new_outputs.flow_metrics.top_talkers {
+            info!(
+                "Update outputs.flow_metrics.top_talkers from {:?} to {:?}.",
+                outputs.flow_metrics.top_talkers, new_outputs.flow_metrics.top_talkers
+            );
+            outputs.flow_metrics.top_talkers = new_outputs.flow_metrics.top_talkers;
+        }
         let tunning = &mut outputs.flow_metrics.tunning;
         let new_tunning = &mut new_outputs.flow_metrics.tunning;
         if tunning.sender_queue_count != new_tunning.sender_queue_count {
@@ -4068,6 +4880,20 @@ impl ConfigHandler {
                     .set_npb_rate(new_config.sender.npb_bps_threshold);
             }
         }
+        if npb.max_tx_throughput_per_destination != new_npb.max_tx_throughput_per_destination {
+            info!(
+                "Update outputs.npb.max_tx_throughput_per_destination from {:?} to {:?}.",
+                npb.max_tx_throughput_per_destination, new_npb.max_tx_throughput_per_destination
+            );
+            npb.max_tx_throughput_per_destination = new_npb.max_tx_throughput_per_destination;
+        }
+        if npb.payload_truncation != new_npb.payload_truncation {
+            info!(
+                "Update outputs.npb.payload_truncation from {:?} to {:?}.",
+                npb.payload_truncation, new_npb.payload_truncation
+            );
+            npb.payload_truncation = new_npb.payload_truncation;
+        }
         if npb.raw_udp_vlan_tag != new_npb.raw_udp_vlan_tag {
             info!(
                 "Update outputs.npb.raw_udp_vlan_tag from {:?} to {:?}.",
@@ -4084,6 +4910,52 @@ impl ConfigHandler {
             restart_agent = !first_run;
         }
 
+        let otlp_exporter = &mut outputs.otlp_exporter;
+        let new_otlp_exporter = &mut new_outputs.otlp_exporter;
+        if otlp_exporter.queue_size != new_otlp_exporter.queue_size {
+            info!(
+                "Update outputs.otlp_exporter.queue_size from {:?} to {:?}.",
+                otlp_exporter.queue_size, new_otlp_exporter.queue_size
+            );
+            otlp_exporter.queue_size = new_otlp_exporter.queue_size;
+            restart_agent = !first_run;
+        }
+        if otlp_exporter != new_otlp_exporter {
+            info!(
+                "Update outputs.otlp_exporter from {:?} to {:?}.",
+                otlp_exporter, new_otlp_exporter
+            );
+            *otlp_exporter = new_otlp_exporter.clone();
+        }
+        if outputs.kafka != new_outputs.kafka {
+            info!(
+                "Update outputs.kafka from {:?} to {:?}.",
+                outputs.kafka, new_outputs.kafka
+            );
+            outputs.kafka = new_outputs.kafka.clone();
+        }
+        if outputs.clickhouse != new_outputs.clickhouse {
+            info!(
+                "Update outputs.clickhouse from {:?} to {:?}.",
+                outputs.clickhouse, new_outputs.clickhouse
+            );
+            outputs.clickhouse = new_outputs.clickhouse.clone();
+        }
+        if outputs.file_exporter != new_outputs.file_exporter {
+            info!(
+                "Update outputs.file_exporter from {:?} to {:?}.",
+                outputs.file_exporter, new_outputs.file_exporter
+            );
+            outputs.file_exporter = new_outputs.file_exporter.clone();
+        }
+        if outputs.syslog != new_outputs.syslog {
+            info!(
+                "Update outputs.syslog from {:?} to {:?}.",
+                outputs.syslog, new_outputs.syslog
+            );
+            outputs.syslog = new_outputs.syslog.clone();
+        }
+
         // plugins
         let plugins = &mut config.plugins;
         let new_plugins = &mut new_config.user_config.plugins;
@@ -4148,6 +5020,14 @@ impl ConfigHandler {
             pcap.total_buffer_size = new_pcap.total_buffer_size;
             restart_agent = !first_run;
         }
+        if pcap.format != new_pcap.format {
+            info!(
+                "Update processors.packet.pcap_stream.format from {:?} to {:?}.",
+                pcap.format, new_pcap.format
+            );
+            pcap.format = new_pcap.format;
+            restart_agent = !first_run;
+        }
 
         let policy = &mut packet.policy;
         let new_policy = &mut new_packet.policy;
@@ -4277,6 +5157,14 @@ impl ConfigHandler {
             flow_generation.server_ports = new_flow_generation.server_ports.clone();
             restart_agent = !first_run;
         }
+        if flow_generation.direction_heuristics != new_flow_generation.direction_heuristics {
+            info!(
+                "Update processors.flow_log.conntrack.flow_generation.direction_heuristics from {:?} to {:?}.",
+                flow_generation.direction_heuristics, new_flow_generation.direction_heuristics
+            );
+            flow_generation.direction_heuristics = new_flow_generation.direction_heuristics.clone();
+            restart_agent = !first_run;
+        }
 
         let timeouts = &mut conntrack.timeouts;
         let new_timeouts = &mut new_conntrack.timeouts;
@@ -4312,6 +5200,22 @@ impl ConfigHandler {
             timeouts.others = new_timeouts.others;
             restart_agent = !first_run;
         }
+        if conntrack.nat_correlation != new_conntrack.nat_correlation {
+            info!(
+                "Update processors.flow_log.conntrack.nat_correlation from {:?} to {:?}.",
+                conntrack.nat_correlation, new_conntrack.nat_correlation
+            );
+            conntrack.nat_correlation = new_conntrack.nat_correlation;
+            restart_agent = !first_run;
+        }
+
+        if flow_log.anomaly_detection != new_flow_log.anomaly_detection {
+            info!(
+                "Update processors.flow_log.anomaly_detection from {:?} to {:?}.",
+                flow_log.anomaly_detection, new_flow_log.anomaly_detection
+            );
+            flow_log.anomaly_detection = new_flow_log.anomaly_detection.clone();
+        }
 
         let time_window = &mut flow_log.time_window;
         let new_time_window = &mut new_flow_log.time_window;
@@ -4423,6 +5327,14 @@ impl ConfigHandler {
             filters.port_number_prefilters = new_filters.port_number_prefilters.clone();
             restart_agent = !first_run;
         }
+        if filters.port_number_exclusions != new_filters.port_number_exclusions {
+            info!(
+                "Update processors.request_log.filters.port_number_exclusions from {:?} to {:?}.",
+                filters.port_number_exclusions, new_filters.port_number_exclusions
+            );
+            filters.port_number_exclusions = new_filters.port_number_exclusions.clone();
+            restart_agent = !first_run;
+        }
         if filters.tag_filters != new_filters.tag_filters {
             info!(
                 "Update processors.request_log.filters.tag_filters from {:?} to {:?}.",
@@ -4465,6 +5377,36 @@ impl ConfigHandler {
             tag_extraction.obfuscate_protocols = new_tag_extraction.obfuscate_protocols.clone();
             restart_agent = !first_run;
         }
+        if tag_extraction.sql_obfuscation_mode != new_tag_extraction.sql_obfuscation_mode {
+            info!(
+                "Update processors.request_log.tag_extraction.sql_obfuscation_mode from {:?} to {:?}.",
+                tag_extraction.sql_obfuscation_mode, new_tag_extraction.sql_obfuscation_mode
+            );
+            tag_extraction.sql_obfuscation_mode = new_tag_extraction.sql_obfuscation_mode;
+            restart_agent = !first_run;
+        }
+        if tag_extraction.redis_key_patterns != new_tag_extraction.redis_key_patterns {
+            info!("Update processors.request_log.tag_extraction.redis_key_patterns from {:?} to {:?}.", 
+                tag_extraction.redis_key_patterns, new_tag_extraction.redis_key_patterns);
+            tag_extraction.redis_key_patterns = new_tag_extraction.redis_key_patterns.clone();
+            restart_agent = !first_run;
+        }
+        if tag_extraction.grpc_body_fields != new_tag_extraction.grpc_body_fields {
+            info!(
+                "Update processors.request_log.tag_extraction.grpc_body_fields from {:?} to {:?}.",
+                tag_extraction.grpc_body_fields, new_tag_extraction.grpc_body_fields
+            );
+            tag_extraction.grpc_body_fields = new_tag_extraction.grpc_body_fields.clone();
+            restart_agent = !first_run;
+        }
+        if tag_extraction.http_body_fields != new_tag_extraction.http_body_fields {
+            info!(
+                "Update processors.request_log.tag_extraction.http_body_fields from {:?} to {:?}.",
+                tag_extraction.http_body_fields, new_tag_extraction.http_body_fields
+            );
+            tag_extraction.http_body_fields = new_tag_extraction.http_body_fields.clone();
+            restart_agent = !first_run;
+        }
         if tag_extraction.tracing_tag != new_tag_extraction.tracing_tag {
             info!(
                 "Update processors.request_log.tag_extraction.tracing_tag from {:?} to {:?}.",
@@ -4473,6 +5415,33 @@ impl ConfigHandler {
             tag_extraction.tracing_tag = new_tag_extraction.tracing_tag.clone();
         }
 
+        let timeouts = &mut request_log.timeouts;
+        let new_timeouts = &mut new_request_log.timeouts;
+        if timeouts.session_aggregate != new_timeouts.session_aggregate {
+            info!(
+                "Update processors.request_log.timeouts.session_aggregate from {:?} to {:?}.",
+                timeouts.session_aggregate, new_timeouts.session_aggregate
+            );
+            timeouts.session_aggregate = new_timeouts.session_aggregate.clone();
+            restart_agent = !first_run;
+        }
+
+        if request_log.alerting != new_request_log.alerting {
+            info!(
+                "Update processors.request_log.alerting from {:?} to {:?}.",
+                request_log.alerting, new_request_log.alerting
+            );
+            request_log.alerting = new_request_log.alerting;
+        }
+
+        if request_log.sampling != new_request_log.sampling {
+            info!(
+                "Update processors.request_log.sampling from {:?} to {:?}.",
+                request_log.sampling, new_request_log.sampling
+            );
+            request_log.sampling = new_request_log.sampling.clone();
+        }
+
         let tunning = &mut request_log.tunning;
         let new_tunning = &mut new_request_log.tunning;
         if tunning.consistent_timestamp_in_l7_metrics
@@ -4497,6 +5466,12 @@ impl ConfigHandler {
             tunning.session_aggregate_slot_capacity = new_tunning.session_aggregate_slot_capacity;
             restart_agent = !first_run;
         }
+        if tunning.mysql_statement_cache_size != new_tunning.mysql_statement_cache_size {
+            info!("Update processors.request_log.tunning.mysql_statement_cache_size from {:?} to {:?}.", 
+                tunning.mysql_statement_cache_size, new_tunning.mysql_statement_cache_size);
+            tunning.mysql_statement_cache_size = new_tunning.mysql_statement_cache_size;
+            restart_agent = !first_run;
+        }
 
         if candidate_config.dispatcher != new_config.dispatcher {
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -4859,6 +5834,7 @@ impl YamlConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::config::RequestLogSamplingRule;
 
     #[test]
     fn test_new_trie() {
@@ -4921,6 +5897,28 @@ mod tests {
         assert_eq!(trie.find_matching_rule("/x/y/z"), 5); // the keep_segments for any rule that matches "" is 5
     }
 
+    #[test]
+    fn http_body_field_json_pointer() {
+        let cases = [
+            ("$.code", Some("/code")),
+            ("$.data.userId", Some("/data/userId")),
+            ("$.items[0].id", Some("/items/0/id")),
+            ("$[1].a/b", Some("/1/a~1b")),
+            ("$", Some("")),
+            ("code", None),
+            ("$.items[0", None),
+            ("$..code", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                HttpBodyFieldRule::json_pointer(path).ok().as_deref(),
+                expected,
+                "path {}",
+                path
+            );
+        }
+    }
+
     #[test]
     fn trace_type_id_parse() {
         let testcases = vec![
@@ -4960,4 +5958,47 @@ mod tests {
             assert_eq!(tt.decode_span_id(value).as_ref().map(|s| s.as_ref()), sid);
         }
     }
+
+    #[test]
+    fn l7_log_sampling_rules() {
+        let config = L7LogSamplingConfig::from(&RequestLogSampling {
+            enabled: true,
+            slow_threshold: Duration::from_secs(1),
+            sample_rate: 10,
+            rules: vec![
+                RequestLogSamplingRule {
+                    protocol: "HTTP".to_string(),
+                    server_port: 8080,
+                    sample_rate: 100,
+                },
+                RequestLogSamplingRule {
+                    protocol: "MySQL".to_string(),
+                    server_port: 0,
+                    sample_rate: 1,
+                },
+                RequestLogSamplingRule {
+                    protocol: "NotAProtocol".to_string(),
+                    server_port: 0,
+                    sample_rate: 1000,
+                },
+                RequestLogSamplingRule {
+                    protocol: "".to_string(),
+                    server_port: 6379,
+                    sample_rate: 0,
+                },
+            ],
+        });
+        assert_eq!(config.rules.len(), 3);
+        assert_eq!(config.sample_rate(L7Protocol::Http1, 8080), 100);
+        assert_eq!(config.sample_rate(L7Protocol::Http1, 80), 10);
+        assert_eq!(config.sample_rate(L7Protocol::MySQL, 3306), 1);
+        assert_eq!(config.sample_rate(L7Protocol::Redis, 6379), 1);
+        assert_eq!(config.sample_rate(L7Protocol::Kafka, 9092), 10);
+
+        let disabled = L7LogSamplingConfig {
+            enabled: false,
+            ..config
+        };
+        assert_eq!(disabled.sample_rate(L7Protocol::Kafka, 9092), 1);
+    }
 }
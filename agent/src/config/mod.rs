@@ -20,8 +20,18 @@ pub mod handler;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use config::ApiResources;
 pub use config::{
-    AgentIdType, Config, ConfigError, KubernetesPollerType, OracleConfig, PcapStream,
-    ProcessMatcher, PrometheusExtraLabels, RuntimeConfig, UserConfig, K8S_CA_CRT_PATH,
+    AfXdp, AgentIdType, ClickHouse, Config, ConfigError, DirectionHeuristic, DiskBuffer, Dpdk,
+    DpdkProcessType, EbpfBtf, EbpfOverhead, EbpfProcessEvents, FileExporter,
+    FileExporterCompression, FileExporterTarget, FlowAnomalyDetection, FlowLogDeduplication,
+    FlowLogDeduplicationMode, FlowPolicyRule, IpFragmentReassembly, Kafka, KafkaCompression,
+    KafkaEncoding, KafkaSaslMechanism, KubernetesPollerType, LocalApi, MutualTls,
+    NamespacePriority, NamespacePriorityClass, NamespaceQuota, NamespaceQuotas, OracleConfig,
+    OtlpIngest, PacketSampling, PacketSamplingMode, PcapReplay, PcapStream, ProcessMatcher,
+    PrometheusExtraLabels, PrometheusScrape, PrometheusScrapeTarget, RequestLogAlerting,
+    RuntimeConfig, SenderCompression, SenderCompressionAlgorithm, SenderDestination, Snmp,
+    SnmpAuthProtocol, SnmpDevice, SnmpPrivacyProtocol, SnmpSecurityLevel, SnmpVersion,
+    SocketDataBuffer, SqlObfuscationMode, Statsd, Syslog, SyslogFormat, SyslogTransport,
+    TopTalkers, UserConfig, XdpAttachMode, XdpBindMode, K8S_CA_CRT_PATH,
 };
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use handler::FlowAccess;
@@ -40,6 +40,9 @@ use super::{
     platform::{PlatformDebugger, PlatformMessage},
 };
 use super::{
+    local_api::LocalApi,
+    multicast::{MulticastDebugger, MulticastMessage},
+    neighbor::{NeighborDebugger, NeighborMessage},
     policy::{PolicyDebugger, PolicyMessage},
     rpc::{RpcDebugger, RpcMessage},
     Beacon, Message, Module, BEACON_INTERVAL, BEACON_INTERVAL_MIN, DEEPFLOW_AGENT_BEACON,
@@ -47,11 +50,12 @@ use super::{
 #[cfg(target_os = "linux")]
 use crate::platform::{ApiWatcher, GenericPoller};
 use crate::{
-    config::handler::DebugAccess,
+    config::{handler::DebugAccess, LocalApi as LocalApiConfig},
+    flow_generator::{MulticastGroupTable, NeighborTable},
     policy::PolicySetter,
     rpc::{Session, StaticConfig, Status},
     trident::AgentId,
-    utils::command::get_hostname,
+    utils::{command::get_hostname, stats},
 };
 use public::{
     consts::DEFAULT_CONTROLLER_PORT,
@@ -66,6 +70,8 @@ struct ModuleDebuggers {
     pub policy: PolicyDebugger,
     #[cfg(target_os = "linux")]
     pub ebpf: EbpfDebugger,
+    pub multicast: MulticastDebugger,
+    pub neighbor: NeighborDebugger,
 }
 
 pub struct Debugger {
@@ -74,6 +80,7 @@ pub struct Debugger {
     debuggers: Arc<ModuleDebuggers>,
     config: DebugAccess,
     override_os_hostname: Arc<Option<String>>,
+    local_api: LocalApi,
 }
 
 pub struct ConstructDebugCtx {
@@ -88,6 +95,9 @@ pub struct ConstructDebugCtx {
     pub agent_id: Arc<RwLock<AgentId>>,
     pub status: Arc<RwLock<Status>>,
     pub policy_setter: PolicySetter,
+    pub multicast_groups: Arc<MulticastGroupTable>,
+    pub neighbor_table: Arc<NeighborTable>,
+    pub stats_collector: Arc<stats::Collector>,
 }
 
 impl Debugger {
@@ -97,6 +107,7 @@ impl Debugger {
         if self.running.swap(true, Ordering::Relaxed) {
             return;
         }
+        self.local_api.start(&self.config.load().local_api);
 
         let running = self.running.clone();
         let debuggers = self.debuggers.clone();
@@ -549,6 +560,26 @@ impl Debugger {
                     _ => unreachable!(),
                 }
             }
+            Module::Multicast => {
+                let req: Message<MulticastMessage> =
+                    decode_from_std_read(&mut payload, serialize_conf)?;
+                let debugger = &debuggers.multicast;
+                let resp = match req.into_inner() {
+                    MulticastMessage::Groups(_) => debugger.groups(),
+                    _ => unreachable!(),
+                };
+                iter_send_to(conn.0, conn.1, resp.iter(), serialize_conf)?;
+            }
+            Module::Neighbor => {
+                let req: Message<NeighborMessage> =
+                    decode_from_std_read(&mut payload, serialize_conf)?;
+                let debugger = &debuggers.neighbor;
+                let resp = match req.into_inner() {
+                    NeighborMessage::Entries(_) => debugger.entries(),
+                    _ => unreachable!(),
+                };
+                iter_send_to(conn.0, conn.1, resp.iter(), serialize_conf)?;
+            }
             _ => warn!("invalid module or invalid request, skip it"),
         }
 
@@ -560,6 +591,11 @@ impl Debugger {
     /// 传入构造上下文
     pub fn new(context: ConstructDebugCtx) -> Self {
         let override_os_hostname = Arc::new(context.static_config.override_os_hostname.clone());
+        let local_api = LocalApi::new(
+            context.runtime.clone(),
+            context.stats_collector,
+            context.policy_setter,
+        );
         let debuggers = ModuleDebuggers {
             #[cfg(target_os = "linux")]
             platform: PlatformDebugger::new(context.api_watcher, context.poller),
@@ -574,6 +610,8 @@ impl Debugger {
             policy: PolicyDebugger::new(context.policy_setter),
             #[cfg(target_os = "linux")]
             ebpf: EbpfDebugger::new(),
+            multicast: MulticastDebugger::new(context.multicast_groups),
+            neighbor: NeighborDebugger::new(context.neighbor_table),
         };
 
         Self {
@@ -582,6 +620,14 @@ impl Debugger {
             debuggers: Arc::new(debuggers),
             config: context.config,
             override_os_hostname,
+            local_api,
+        }
+    }
+
+    // Restarts the local api if its config has changed, only when the debugger is running
+    pub fn set_local_api(&self, config: &LocalApiConfig) {
+        if self.running.load(Ordering::Relaxed) {
+            self.local_api.start(config);
         }
     }
 
@@ -594,6 +640,7 @@ impl Debugger {
             return None;
         }
 
+        self.local_api.stop();
         info!("notified debugger exit");
         self.thread.lock().unwrap().take()
     }
@@ -603,6 +650,7 @@ impl Debugger {
             return;
         }
 
+        self.local_api.stop();
         let _ = self.thread.lock().unwrap().take();
         info!("debugger exited");
     }
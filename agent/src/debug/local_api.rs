@@ -0,0 +1,536 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Server as HttpServer, StatusCode,
+};
+use log::{error, info};
+use serde::Serialize;
+use tokio::{
+    runtime::Runtime,
+    sync::{watch, Mutex as AsyncMutex},
+    task::JoinHandle,
+};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    config::LocalApi as LocalApiConfig,
+    flow_generator::{
+        flow_dump::{FlowDumpFilter, FLOW_DUMP},
+        packet_trace::{PacketTraceFilter, DEFAULT_TRACE_DURATION, PACKET_TRACE},
+    },
+    policy::PolicySetter,
+    utils::{
+        log_level::{LogLevelSpec, DEFAULT_OVERRIDE_DURATION, LOG_LEVELS},
+        stats::{self, Batch, CounterValue},
+    },
+};
+use public::proto::agent_debug::{
+    inspector_server::{Inspector, InspectorServer},
+    CounterGroup, CounterRequest, CounterResponse, Flow, FlowRequest, FlowResponse,
+    LogLevelRequest, LogLevelResponse, Metric, PacketTraceRequest, PacketTraceResponse, Plugin,
+    PluginRequest, PluginResponse, PolicyRequest, PolicyResponse, Queue, QueueRequest,
+    QueueResponse, Tag,
+};
+
+// flow maps submit dumps on their flush ticks, which happen every second
+const FLOW_DUMP_WAIT: Duration = Duration::from_secs(2);
+const DEFAULT_FLOW_LIMIT: usize = 1000;
+
+fn counter_value(value: CounterValue) -> f64 {
+    match value {
+        CounterValue::Signed(v) => v as f64,
+        CounterValue::Unsigned(v) => v as f64,
+        CounterValue::Float(v) => v,
+    }
+}
+
+fn metrics(batch: &Batch) -> Vec<Metric> {
+    batch
+        .points()
+        .iter()
+        .map(|(name, _, value)| Metric {
+            name: name.to_string(),
+            value: counter_value(*value),
+        })
+        .collect()
+}
+
+fn tag<'a>(batch: &'a Batch, key: &str) -> &'a str {
+    batch
+        .tags()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or_default()
+}
+
+fn metric(batch: &Batch, name: &str) -> u64 {
+    batch
+        .points()
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, _, v)| counter_value(*v) as u64)
+        .unwrap_or_default()
+}
+
+fn duration_or(secs: u32, default: Duration) -> Duration {
+    if secs == 0 {
+        default
+    } else {
+        Duration::from_secs(secs as u64)
+    }
+}
+
+fn unix_timestamp(time: Option<SystemTime>) -> u32 {
+    time.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+struct InspectorService {
+    stats_collector: Arc<stats::Collector>,
+    policy_setter: PolicySetter,
+    // flow dumps share one request, so they are served one at a time
+    flow_dump_lock: AsyncMutex<()>,
+}
+
+impl InspectorService {
+    fn batches(&self, module: &str) -> Vec<Arc<Batch>> {
+        let mut batches = self
+            .stats_collector
+            .snapshot()
+            .into_iter()
+            .filter(|b| b.module().starts_with(module))
+            .collect::<Vec<_>>();
+        batches.sort_by(|a, b| a.module().cmp(b.module()).then(a.tags().cmp(b.tags())));
+        batches
+    }
+}
+
+#[tonic::async_trait]
+impl Inspector for InspectorService {
+    async fn get_counters(
+        &self,
+        request: Request<CounterRequest>,
+    ) -> Result<Response<CounterResponse>, Status> {
+        let groups = self
+            .batches(&request.get_ref().module)
+            .iter()
+            .map(|b| CounterGroup {
+                module: b.module().to_owned(),
+                tags: b
+                    .tags()
+                    .iter()
+                    .map(|(k, v)| Tag {
+                        key: k.to_string(),
+                        value: v.clone(),
+                    })
+                    .collect(),
+                metrics: metrics(b),
+                timestamp: b.timestamp(),
+            })
+            .collect();
+        Ok(Response::new(CounterResponse { groups }))
+    }
+
+    async fn get_queues(
+        &self,
+        request: Request<QueueRequest>,
+    ) -> Result<Response<QueueResponse>, Status> {
+        let name = &request.get_ref().name;
+        let queues = self
+            .batches("queue")
+            .iter()
+            .filter(|b| b.module() == "queue" && tag(b, "module").contains(name.as_str()))
+            .map(|b| Queue {
+                name: tag(b, "module").to_owned(),
+                index: tag(b, "index").parse().unwrap_or_default(),
+                pending: metric(b, "pending"),
+                input: metric(b, "in"),
+                output: metric(b, "out"),
+                overwritten: metric(b, "overwritten"),
+                timestamp: b.timestamp(),
+            })
+            .collect();
+        Ok(Response::new(QueueResponse { queues }))
+    }
+
+    async fn get_flows(
+        &self,
+        request: Request<FlowRequest>,
+    ) -> Result<Response<FlowResponse>, Status> {
+        let request = request.into_inner();
+        let ip = if request.ip.is_empty() {
+            None
+        } else {
+            Some(
+                request
+                    .ip
+                    .parse::<IpAddr>()
+                    .map_err(|e| Status::invalid_argument(format!("invalid ip: {}", e)))?,
+            )
+        };
+        let port = if request.port == 0 {
+            None
+        } else {
+            Some(
+                u16::try_from(request.port)
+                    .map_err(|e| Status::invalid_argument(format!("invalid port: {}", e)))?,
+            )
+        };
+        let filter = FlowDumpFilter {
+            limit: if request.limit == 0 {
+                DEFAULT_FLOW_LIMIT
+            } else {
+                request.limit as usize
+            },
+            ip,
+            port,
+        };
+
+        let _guard = self.flow_dump_lock.lock().await;
+        let generation = FLOW_DUMP.request(filter);
+        tokio::time::sleep(FLOW_DUMP_WAIT).await;
+        let results = FLOW_DUMP.collect(generation);
+        Ok(Response::new(FlowResponse {
+            flow_maps: results.len() as u32,
+            flows: results
+                .into_iter()
+                .flat_map(|(_, flows)| flows)
+                .collect::<Vec<Flow>>(),
+        }))
+    }
+
+    async fn get_plugins(
+        &self,
+        _: Request<PluginRequest>,
+    ) -> Result<Response<PluginResponse>, Status> {
+        let plugins = self
+            .batches("plugin")
+            .iter()
+            .filter(|b| b.module() == "plugin")
+            .map(|b| Plugin {
+                flow_map_id: tag(b, "id").parse().unwrap_or_default(),
+                name: tag(b, "plugin_name").to_owned(),
+                plugin_type: tag(b, "plugin_type").to_owned(),
+                export_func: tag(b, "export_func").to_owned(),
+                metrics: metrics(b),
+            })
+            .collect();
+        Ok(Response::new(PluginResponse { plugins }))
+    }
+
+    async fn get_policies(
+        &self,
+        _: Request<PolicyRequest>,
+    ) -> Result<Response<PolicyResponse>, Status> {
+        let (first_hits, fast_hits) = self.policy_setter.get_hits();
+        let mut acls = self.policy_setter.get_acls().clone();
+        acls.sort_by_key(|x| x.id);
+        Ok(Response::new(PolicyResponse {
+            first_path_hits: first_hits as u64,
+            fast_path_hits: fast_hits as u64,
+            acls: acls.iter().map(|a| a.to_string()).collect(),
+        }))
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<LogLevelRequest>,
+    ) -> Result<Response<LogLevelResponse>, Status> {
+        let request = request.into_inner();
+        let spec = request
+            .levels
+            .parse::<LogLevelSpec>()
+            .map_err(Status::invalid_argument)?;
+        LOG_LEVELS
+            .set_temporary(
+                spec,
+                duration_or(request.duration_secs, DEFAULT_OVERRIDE_DURATION),
+            )
+            .map_err(Status::internal)?;
+        let (levels, expire_at) = LOG_LEVELS.current();
+        Ok(Response::new(LogLevelResponse {
+            levels: levels.to_string(),
+            expire_at: unix_timestamp(expire_at),
+        }))
+    }
+
+    async fn trace_packets(
+        &self,
+        request: Request<PacketTraceRequest>,
+    ) -> Result<Response<PacketTraceResponse>, Status> {
+        let request = request.into_inner();
+        if request.ip_0.is_empty() && request.ip_1.is_empty() {
+            PACKET_TRACE.stop(None);
+        } else {
+            let filter = PacketTraceFilter::parse(
+                &request.ip_0,
+                &request.port_0.to_string(),
+                &request.ip_1,
+                &request.port_1.to_string(),
+                &request.protocol,
+            )
+            .map_err(Status::invalid_argument)?;
+            PACKET_TRACE
+                .start(
+                    filter,
+                    duration_or(request.duration_secs, DEFAULT_TRACE_DURATION),
+                )
+                .map_err(Status::internal)?;
+        }
+        let current = PACKET_TRACE.current();
+        Ok(Response::new(PacketTraceResponse {
+            filter: current
+                .as_ref()
+                .map(|(f, _)| f.to_string())
+                .unwrap_or_default(),
+            expire_at: unix_timestamp(current.map(|(_, t)| t)),
+        }))
+    }
+}
+
+fn json_response<T: Serialize>(result: Result<Response<T>, Status>) -> hyper::Response<Body> {
+    let (status, body) = match result {
+        Ok(r) => match serde_json::to_vec(r.get_ref()) {
+            Ok(body) => (StatusCode::OK, body),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.to_string().into_bytes(),
+            ),
+        },
+        Err(s) => (StatusCode::BAD_REQUEST, s.message().as_bytes().to_vec()),
+    };
+    hyper::Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn handle_http(
+    service: Arc<InspectorService>,
+    req: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, Infallible> {
+    let path = req.uri().path().to_owned();
+    // routes changing the agent only accept POST
+    let method = match path.as_str() {
+        "/v1/log-level" | "/v1/packet-trace" => Method::POST,
+        _ => Method::GET,
+    };
+    if req.method() != method {
+        return Ok(hyper::Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(Body::empty())
+            .unwrap());
+    }
+    let query: HashMap<String, String> =
+        form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect();
+    let param = |key: &str| query.get(key).cloned().unwrap_or_default();
+    let response = match path.as_str() {
+        "/v1/counters" => json_response(
+            service
+                .get_counters(Request::new(CounterRequest {
+                    module: param("module"),
+                }))
+                .await,
+        ),
+        "/v1/queues" => json_response(
+            service
+                .get_queues(Request::new(QueueRequest {
+                    name: param("name"),
+                }))
+                .await,
+        ),
+        "/v1/flows" => json_response(
+            service
+                .get_flows(Request::new(FlowRequest {
+                    limit: param("limit").parse().unwrap_or_default(),
+                    ip: param("ip"),
+                    port: param("port").parse().unwrap_or_default(),
+                }))
+                .await,
+        ),
+        "/v1/plugins" => json_response(service.get_plugins(Request::new(PluginRequest {})).await),
+        "/v1/policies" => json_response(service.get_policies(Request::new(PolicyRequest {})).await),
+        "/v1/log-level" => json_response(
+            service
+                .set_log_level(Request::new(LogLevelRequest {
+                    levels: param("levels"),
+                    duration_secs: param("duration").parse().unwrap_or_default(),
+                }))
+                .await,
+        ),
+        "/v1/packet-trace" => json_response(
+            service
+                .trace_packets(Request::new(PacketTraceRequest {
+                    ip_0: param("ip_0"),
+                    port_0: param("port_0").parse().unwrap_or_default(),
+                    ip_1: param("ip_1"),
+                    port_1: param("port_1").parse().unwrap_or_default(),
+                    protocol: param("protocol"),
+                    duration_secs: param("duration").parse().unwrap_or_default(),
+                }))
+                .await,
+        ),
+        _ => hyper::Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap(),
+    };
+    Ok(response)
+}
+
+async fn serve_grpc(
+    addr: SocketAddr,
+    service: Arc<InspectorService>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    info!("local debug api listening on grpc://{}", addr);
+    if let Err(e) = Server::builder()
+        .add_service(InspectorServer::from_arc(service))
+        .serve_with_shutdown(addr, async move {
+            let _ = shutdown.changed().await;
+        })
+        .await
+    {
+        error!("local debug api on {} error: {}", addr, e);
+    }
+}
+
+async fn serve_http(
+    addr: SocketAddr,
+    service: Arc<InspectorService>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle_http(service.clone(), req))) }
+    });
+    let server = match HttpServer::try_bind(&addr) {
+        Ok(b) => b.serve(make_service),
+        Err(e) => {
+            error!("local debug api bind {} error: {}", addr, e);
+            return;
+        }
+    };
+    info!("local debug api listening on http://{}", addr);
+    if let Err(e) = server
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.changed().await;
+        })
+        .await
+    {
+        error!("local debug api on {} error: {}", addr, e);
+    }
+}
+
+struct Running {
+    config: LocalApiConfig,
+    shutdown: watch::Sender<bool>,
+    // completes after the listeners are closed
+    handle: JoinHandle<()>,
+}
+
+// Inspection api of the agent, listening on localhost only.
+//
+// Besides the read-only queries, log levels and packet tracing can be changed temporarily.
+// The same requests are served with gRPC and optionally with HTTP/JSON.
+pub struct LocalApi {
+    runtime: Arc<Runtime>,
+    service: Arc<InspectorService>,
+    running: Mutex<Option<Running>>,
+}
+
+impl LocalApi {
+    pub fn new(
+        runtime: Arc<Runtime>,
+        stats_collector: Arc<stats::Collector>,
+        policy_setter: PolicySetter,
+    ) -> Self {
+        Self {
+            runtime,
+            service: Arc::new(InspectorService {
+                stats_collector,
+                policy_setter,
+                flow_dump_lock: AsyncMutex::new(()),
+            }),
+            running: Mutex::new(None),
+        }
+    }
+
+    // Starts the listeners, or restarts them if the config has changed since they were started
+    pub fn start(&self, config: &LocalApiConfig) {
+        let mut running = self.running.lock().unwrap();
+        if running.as_ref().map(|r| &r.config) == Some(config) {
+            return;
+        }
+        let previous = running.take().map(|r| {
+            let _ = r.shutdown.send(true);
+            info!("local debug api stopped");
+            r.handle
+        });
+        if !config.enabled {
+            return;
+        }
+        let (tx, rx) = watch::channel(false);
+
+        let grpc_addr: SocketAddr = (Ipv4Addr::LOCALHOST, config.grpc_port).into();
+        let http_addr = if config.http_port > 0 {
+            Some(SocketAddr::from((Ipv4Addr::LOCALHOST, config.http_port)))
+        } else {
+            None
+        };
+        let service = self.service.clone();
+        let handle = self.runtime.spawn(async move {
+            // the ports may be reused, wait for the previous listeners to be closed
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            let grpc = tokio::spawn(serve_grpc(grpc_addr, service.clone(), rx.clone()));
+            if let Some(addr) = http_addr {
+                serve_http(addr, service, rx).await;
+            }
+            let _ = grpc.await;
+        });
+
+        *running = Some(Running {
+            config: *config,
+            shutdown: tx,
+            handle,
+        });
+    }
+
+    pub fn stop(&self) {
+        if let Some(r) = self.running.lock().unwrap().take() {
+            let _ = r.shutdown.send(true);
+            info!("local debug api stopped");
+        }
+    }
+}
@@ -17,6 +17,9 @@
 mod debugger;
 #[cfg(target_os = "linux")]
 mod ebpf;
+mod local_api;
+mod multicast;
+mod neighbor;
 #[cfg(target_os = "linux")]
 mod platform;
 mod policy;
@@ -26,6 +29,8 @@ use bincode::{Decode, Encode};
 pub use debugger::{Client, ConstructDebugCtx, Debugger};
 #[cfg(target_os = "linux")]
 pub use ebpf::EbpfMessage;
+pub use multicast::MulticastMessage;
+pub use neighbor::NeighborMessage;
 #[cfg(target_os = "linux")]
 pub use platform::PlatformMessage;
 pub use policy::PolicyMessage;
@@ -54,6 +59,8 @@ pub enum Module {
     Policy,
     #[cfg(target_os = "linux")]
     Ebpf,
+    Multicast,
+    Neighbor,
 }
 
 impl Default for Module {
@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use bincode::{Decode, Encode};
+
+use crate::flow_generator::MulticastGroupTable;
+
+#[derive(PartialEq, Eq, Debug, Encode, Decode)]
+pub enum MulticastMessage {
+    // (tap port, group, member, source, last report in seconds)
+    Groups(Option<Vec<(String, String, String, String, u64)>>),
+    Fin,
+}
+
+pub struct MulticastDebugger {
+    table: Arc<MulticastGroupTable>,
+}
+
+impl MulticastDebugger {
+    const ENTRIES_PER_MESSAGE: usize = 32;
+
+    pub(super) fn new(table: Arc<MulticastGroupTable>) -> Self {
+        Self { table }
+    }
+
+    pub(super) fn groups(&self) -> Vec<MulticastMessage> {
+        let entries = self
+            .table
+            .entries()
+            .into_iter()
+            .map(|(tap_port, group, m)| {
+                (
+                    tap_port.to_string(),
+                    group.to_string(),
+                    m.member.to_string(),
+                    m.source.to_string(),
+                    m.last_report.as_secs(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // split into multiple messages in case of exceeding MAX_BUF_SIZE
+        let mut msgs = entries
+            .chunks(Self::ENTRIES_PER_MESSAGE)
+            .map(|c| MulticastMessage::Groups(Some(c.to_vec())))
+            .collect::<Vec<_>>();
+        if msgs.is_empty() {
+            msgs.push(MulticastMessage::Groups(None));
+        }
+        msgs.push(MulticastMessage::Fin);
+        msgs
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use bincode::{Decode, Encode};
+
+use crate::flow_generator::NeighborTable;
+
+#[derive(PartialEq, Eq, Debug, Encode, Decode)]
+pub enum NeighborMessage {
+    // (tap type, ip, mac, last seen in seconds, conflicting mac, conflict count)
+    Entries(Option<Vec<(String, String, String, u64, String, u32)>>),
+    Fin,
+}
+
+pub struct NeighborDebugger {
+    table: Arc<NeighborTable>,
+}
+
+impl NeighborDebugger {
+    const ENTRIES_PER_MESSAGE: usize = 32;
+
+    pub(super) fn new(table: Arc<NeighborTable>) -> Self {
+        Self { table }
+    }
+
+    pub(super) fn entries(&self) -> Vec<NeighborMessage> {
+        let entries = self
+            .table
+            .entries()
+            .into_iter()
+            .map(|(tap_type, ip, e)| {
+                (
+                    tap_type.to_string(),
+                    ip.to_string(),
+                    e.mac.to_string(),
+                    e.last_seen.as_secs(),
+                    e.conflict_mac.map(|m| m.to_string()).unwrap_or_default(),
+                    e.conflict_count,
+                )
+            })
+            .collect::<Vec<_>>();
+        // split into multiple messages in case of exceeding MAX_BUF_SIZE
+        let mut msgs = entries
+            .chunks(Self::ENTRIES_PER_MESSAGE)
+            .map(|c| NeighborMessage::Entries(Some(c.to_vec())))
+            .collect::<Vec<_>>();
+        if msgs.is_empty() {
+            msgs.push(NeighborMessage::Entries(None));
+        }
+        msgs.push(NeighborMessage::Fin);
+        msgs
+    }
+}
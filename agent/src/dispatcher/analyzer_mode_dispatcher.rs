@@ -30,9 +30,10 @@ use nix::{
     sched::{sched_setaffinity, CpuSet},
     unistd::Pid,
 };
-use packet_dedup::PacketDedupMap;
 
 use super::base_dispatcher::BaseDispatcher;
+use super::dedup::PacketDedupMap;
+use super::sampler::PacketSampler;
 use super::Packet;
 use crate::{
     common::{
@@ -255,7 +256,8 @@ impl AnalyzerModeDispatcher {
         let counter = base.counter.clone();
         let analyzer_dedup_disabled = base.analyzer_dedup_disabled;
         let vm_mac_addrs = self.vm_mac_addrs.clone();
-        let mut dedup = PacketDedupMap::new();
+        let mut dedup = PacketDedupMap::new(base.packet_dedup_window);
+        let mut sampler = PacketSampler::new(&base.options.lock().unwrap().packet_sampling);
         let id = base.id;
         let pool_raw_size = self.pool_raw_size;
 
@@ -270,6 +272,10 @@ impl AnalyzerModeDispatcher {
         let collector_config = base.collector_config.clone();
         let packet_sequence_output_queue = base.packet_sequence_output_queue.clone(); // Enterprise Edition Feature: packet-sequence
         let stats = base.stats.clone();
+        let multicast_groups = base.multicast_groups.clone();
+        let neighbor_table = base.neighbor_table.clone();
+        let anomaly_detector = base.anomaly_detector.clone();
+        let app_alert_evaluator = base.app_alert_evaluator.clone();
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let cpu_set = base.options.lock().unwrap().cpu_set;
 
@@ -289,6 +295,10 @@ impl AnalyzerModeDispatcher {
                         ntp_diff,
                         &flow_map_config.load(),
                         Some(packet_sequence_output_queue), // Enterprise Edition Feature: packet-sequence
+                        Some(multicast_groups),
+                        Some(neighbor_table),
+                        Some(anomaly_detector),
+                        Some(app_alert_evaluator),
                         stats,
                         false, // !from_ebpf
                     );
@@ -368,6 +378,7 @@ impl AnalyzerModeDispatcher {
                                 && !analyzer_dedup_disabled
                                 && dedup.duplicate(overlay_packet.as_mut(), timestamp)
                             {
+                                counter.duplicated.fetch_add(1, Ordering::Relaxed);
                                 debug!("packet is duplicate");
                                 continue;
                             }
@@ -401,6 +412,10 @@ impl AnalyzerModeDispatcher {
                                 debug!("meta_packet update failed: {:?}", e);
                                 continue;
                             }
+                            if !sampler.sample(&mut meta_packet) {
+                                counter.sampling_dropped.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
 
                             if tunnel_info.tunnel_type != TunnelType::None {
                                 meta_packet.tunnel = Some(tunnel_info);
@@ -30,26 +30,27 @@ use log::{error, info, warn};
 
 use super::{
     error::{Error, Result},
-    recv_engine::{self, bpf, RecvEngine},
+    recv_engine::{self, bpf, Libpcap, RecvEngine},
     BpfOptions, Options, PacketCounter, Pipeline,
 };
 
-use special_recv_engine::Libpcap;
-
 use crate::config::handler::{CollectorAccess, LogParserAccess};
 use crate::{
     common::{
         decapsulate::{TunnelInfo, TunnelType, TunnelTypeBitmap},
         endpoint::FeatureFlags,
-        enums::{CaptureNetworkType, EthernetType},
+        enums::{CaptureNetworkType, EthernetType, LinkType},
         flow::L7Stats,
+        ieee80211::decapsulate_radiotap,
         CaptureNetworkTyper, MetaPacket, TaggedFlow, DEFAULT_CONTROLLER_PORT,
         DEFAULT_INGESTER_PORT, ETH_HEADER_SIZE, FIELD_OFFSET_ETH_TYPE, VLAN_HEADER_SIZE,
         VLAN_ID_MASK,
     },
     config::{handler::FlowAccess, DispatcherConfig},
     exception::ExceptionHandler,
-    flow_generator::AppProto,
+    flow_generator::{
+        AnomalyDetector, AppAlertEvaluator, AppProto, MulticastGroupTable, NeighborTable,
+    },
     handler::PacketHandlerBuilder,
     policy::PolicyGetter,
     rpc::get_timestamp,
@@ -97,6 +98,7 @@ pub(super) struct BaseDispatcher {
     pub(super) tap_interface_whitelist: TapInterfaceWhitelist,
 
     pub(super) analyzer_dedup_disabled: bool,
+    pub(super) packet_dedup_window: Duration,
 
     pub(super) flow_output_queue: DebugSender<Arc<BatchedBox<TaggedFlow>>>,
     pub(super) l7_stats_output_queue: DebugSender<BatchedBox<L7Stats>>,
@@ -109,6 +111,10 @@ pub(super) struct BaseDispatcher {
     pub(super) platform_poller: Arc<crate::platform::GenericPoller>,
 
     pub(super) policy_getter: PolicyGetter,
+    pub(super) multicast_groups: Arc<MulticastGroupTable>,
+    pub(super) neighbor_table: Arc<NeighborTable>,
+    pub(super) anomaly_detector: Arc<AnomalyDetector>,
+    pub(super) app_alert_evaluator: Arc<AppAlertEvaluator>,
     pub(super) exception_handler: ExceptionHandler,
     pub(super) ntp_diff: Arc<AtomicI64>,
 
@@ -261,7 +267,16 @@ impl BaseDispatcher {
             thread::sleep(Duration::from_millis(1));
             return None;
         }
-        let packet = packet.unwrap();
+        let mut packet = packet.unwrap();
+        if packet.link_type == LinkType::Ieee80211Radio {
+            // Only data frames with LLC/SNAP are delivered, encrypted frames are dropped
+            let Some(range) = decapsulate_radiotap(packet.data, None) else {
+                counter.invalid_packets.fetch_add(1, Ordering::Relaxed);
+                return None;
+            };
+            packet.capture_length -= (packet.data.len() - range.len()) as isize;
+            packet.data = &mut mem::take(&mut packet.data)[range];
+        }
         // Receiving incomplete eth header under some environments, unlikely to happen
         if packet.data.len() < ETH_HEADER_SIZE + VLAN_HEADER_SIZE {
             counter.invalid_packets.fetch_add(1, Ordering::Relaxed);
@@ -331,6 +346,18 @@ impl BaseDispatcher {
             // 最外层隧道封装，可能是ERSPAN或VXLAN
             EthernetType::IPV4 => tunnel_info.decapsulate(packet, l2_len, bitmap),
             EthernetType::IPV6 => tunnel_info.decapsulate_v6(packet, l2_len, bitmap),
+            EthernetType::MPLS_UNICAST | EthernetType::MPLS_MULTICAST => {
+                return Ok((
+                    tunnel_info.decapsulate_mpls(packet, l2_len, bitmap),
+                    tap_type,
+                ));
+            }
+            EthernetType::PPPOE_SESSION => {
+                return Ok((
+                    tunnel_info.decapsulate_pppoe(packet, l2_len, bitmap),
+                    tap_type,
+                ));
+            }
             _ => 0,
         };
         if offset == 0 {
@@ -400,7 +427,12 @@ impl BaseDispatcher {
             .set_bpf(vec![], &CString::new(bpf_options.get_bpf_syntax()).unwrap())
         {
             warn!("set_bpf failed: {}", e);
+            self.counter
+                .bpf_update_failures
+                .fetch_add(1, Ordering::Relaxed);
+            return;
         }
+        self.counter.bpf_updates.fetch_add(1, Ordering::Relaxed);
     }
 }
 
@@ -456,6 +488,18 @@ impl BaseDispatcher {
             // 最外层隧道封装，可能是ERSPAN或VXLAN
             EthernetType::IPV4 => tunnel_info.decapsulate(packet, l2_len, bitmap),
             EthernetType::IPV6 => tunnel_info.decapsulate_v6(packet, l2_len, bitmap),
+            EthernetType::MPLS_UNICAST | EthernetType::MPLS_MULTICAST => {
+                return Ok((
+                    tunnel_info.decapsulate_mpls(packet, l2_len, bitmap),
+                    tap_type,
+                ));
+            }
+            EthernetType::PPPOE_SESSION => {
+                return Ok((
+                    tunnel_info.decapsulate_pppoe(packet, l2_len, bitmap),
+                    tap_type,
+                ));
+            }
             _ => 0,
         };
         if offset == 0 {
@@ -535,7 +579,12 @@ impl BaseDispatcher {
                 tap_interfaces.len(),
                 e
             );
+            self.counter
+                .bpf_update_failures
+                .fetch_add(1, Ordering::Relaxed);
+            return;
         }
+        self.counter.bpf_updates.fetch_add(1, Ordering::Relaxed);
     }
 }
 
@@ -559,7 +608,9 @@ impl CaptureNetworkTypeHandler {
         let mut eth_type = read_u16_be(&packet[FIELD_OFFSET_ETH_TYPE..]);
         let mut tap_type = self.default_tap_type;
         let mut l2_opt_size = 0;
-        let (outer_vlan_tag, inner_vlan_tag) = if eth_type == EthernetType::DOT1Q
+        // the outer tag of 802.1ad QinQ is S-TAG (0x88a8) or DOT1Q
+        let (outer_vlan_tag, inner_vlan_tag) = if (eth_type == EthernetType::DOT1Q
+            || eth_type == EthernetType::QINQ)
             && packet.len() >= ETH_HEADER_SIZE + VLAN_HEADER_SIZE
         {
             let vlan_tag = read_u16_be(&packet[ETH_HEADER_SIZE..]);
@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{hash_map::DefaultHasher, HashMap, VecDeque};
+use std::hash::Hasher;
+use std::time::Duration;
+
+// limits memory usage on busy links, the oldest packets are evicted first
+const MAX_ENTRIES: usize = 1 << 20;
+
+const ETH_HEADER_SIZE: usize = 14;
+const VLAN_HEADER_SIZE: usize = 4;
+const ETH_TYPE_IPV4: u16 = 0x0800;
+const ETH_TYPE_IPV6: u16 = 0x86dd;
+const ETH_TYPE_DOT1Q: u16 = 0x8100;
+const ETH_TYPE_DOT1AD: u16 = 0x88a8;
+const IPV6_HEADER_SIZE: usize = 40;
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+// Drops a packet if a packet with the same key was seen within the window.
+//
+// The key is hashed over the 5-tuple of the (decapsulated) packet, IP ID and total length,
+// TCP seq, ack and flags, which stay the same when a packet is mirrored by two points but
+// change between retransmissions.
+pub struct PacketDedupMap {
+    window: Duration,
+    seen: HashMap<u64, Duration>,
+    expiry: VecDeque<(Duration, u64)>,
+}
+
+impl PacketDedupMap {
+    // zero window disables deduplication
+    pub fn new(window: Duration) -> Self {
+        PacketDedupMap {
+            window,
+            seen: HashMap::new(),
+            expiry: VecDeque::new(),
+        }
+    }
+
+    pub fn duplicate(&mut self, packet: &mut [u8], timestamp: Duration) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+        let Some(key) = packet_key(packet) else {
+            return false;
+        };
+        self.expire(timestamp);
+        if let Some(last) = self.seen.get(&key) {
+            if timestamp.saturating_sub(*last) <= self.window {
+                return true;
+            }
+        }
+        self.seen.insert(key, timestamp);
+        self.expiry.push_back((timestamp, key));
+        false
+    }
+
+    fn expire(&mut self, now: Duration) {
+        while let Some(&(timestamp, key)) = self.expiry.front() {
+            if now.saturating_sub(timestamp) <= self.window && self.seen.len() < MAX_ENTRIES {
+                break;
+            }
+            self.expiry.pop_front();
+            // the key may have been refreshed by a later packet
+            if self.seen.get(&key) == Some(&timestamp) {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        packet.get(offset..offset + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn packet_key(packet: &[u8]) -> Option<u64> {
+    let mut offset = ETH_HEADER_SIZE - 2;
+    let mut eth_type = read_u16(packet, offset)?;
+    while eth_type == ETH_TYPE_DOT1Q || eth_type == ETH_TYPE_DOT1AD {
+        offset += VLAN_HEADER_SIZE;
+        eth_type = read_u16(packet, offset)?;
+    }
+    offset += 2;
+
+    let mut hasher = DefaultHasher::new();
+    let (protocol, l4_offset) = match eth_type {
+        ETH_TYPE_IPV4 => {
+            let header = packet.get(offset..offset + 20)?;
+            let header_len = (header[0] & 0xf) as usize * 4;
+            // total length, id, flags and fragment offset
+            hasher.write(&header[2..8]);
+            // protocol, skipping ttl which is different if mirrored at different hops
+            hasher.write_u8(header[9]);
+            // source and destination address
+            hasher.write(&header[12..20]);
+            let fragment_offset = u16::from_be_bytes([header[6], header[7]]) & 0x1fff;
+            if fragment_offset != 0 {
+                return Some(hasher.finish());
+            }
+            (header[9], offset + header_len)
+        }
+        ETH_TYPE_IPV6 => {
+            let header = packet.get(offset..offset + IPV6_HEADER_SIZE)?;
+            // payload length and next header
+            hasher.write(&header[4..7]);
+            // source and destination address
+            hasher.write(&header[8..40]);
+            (header[6], offset + IPV6_HEADER_SIZE)
+        }
+        _ => return None,
+    };
+
+    match protocol {
+        IP_PROTOCOL_TCP => {
+            // ports, seq, ack, data offset and flags
+            hasher.write(packet.get(l4_offset..l4_offset + 14)?);
+        }
+        IP_PROTOCOL_UDP => {
+            // ports, length and checksum
+            hasher.write(packet.get(l4_offset..l4_offset + 8)?);
+        }
+        _ => (),
+    }
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp_packet(ip_id: u16, seq: u32) -> Vec<u8> {
+        let mut packet = vec![0u8; ETH_HEADER_SIZE + 20 + 20];
+        packet[12..14].copy_from_slice(&ETH_TYPE_IPV4.to_be_bytes());
+        let ip = &mut packet[ETH_HEADER_SIZE..];
+        ip[0] = 0x45;
+        ip[2..4].copy_from_slice(&40u16.to_be_bytes());
+        ip[4..6].copy_from_slice(&ip_id.to_be_bytes());
+        ip[8] = 64;
+        ip[9] = IP_PROTOCOL_TCP;
+        ip[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        let tcp = &mut ip[20..];
+        tcp[0..2].copy_from_slice(&12345u16.to_be_bytes());
+        tcp[2..4].copy_from_slice(&80u16.to_be_bytes());
+        tcp[4..8].copy_from_slice(&seq.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn dedup_window() {
+        let mut dedup = PacketDedupMap::new(Duration::from_millis(10));
+        let mut packet = tcp_packet(1, 1000);
+        assert!(!dedup.duplicate(&mut packet, Duration::from_millis(100)));
+
+        // mirrored at another hop, ttl decreased
+        let mut mirrored = tcp_packet(1, 1000);
+        mirrored[ETH_HEADER_SIZE + 8] = 63;
+        assert!(dedup.duplicate(&mut mirrored, Duration::from_millis(105)));
+
+        // retransmission has a new ip id
+        let mut retrans = tcp_packet(2, 1000);
+        assert!(!dedup.duplicate(&mut retrans, Duration::from_millis(106)));
+
+        // out of window
+        assert!(!dedup.duplicate(&mut packet, Duration::from_millis(120)));
+
+        // non-ip packets are never dropped
+        let mut arp = vec![0u8; 60];
+        arp[12..14].copy_from_slice(&0x0806u16.to_be_bytes());
+        assert!(!dedup.duplicate(&mut arp, Duration::from_millis(121)));
+        assert!(!dedup.duplicate(&mut arp, Duration::from_millis(121)));
+
+        let mut disabled = PacketDedupMap::new(Duration::ZERO);
+        assert!(!disabled.duplicate(&mut packet, Duration::from_millis(100)));
+        assert!(!disabled.duplicate(&mut packet, Duration::from_millis(100)));
+    }
+}
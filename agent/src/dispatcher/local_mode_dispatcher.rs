@@ -34,6 +34,7 @@ use regex::Regex;
 
 use super::base_dispatcher::{BaseDispatcher, BaseDispatcherListener};
 use super::error::Result;
+use super::sampler::PacketSampler;
 
 #[cfg(target_os = "linux")]
 use crate::platform::{GenericPoller, LibvirtXmlExtractor, Poller};
@@ -85,10 +86,15 @@ impl LocalModeDispatcher {
             base.ntp_diff.clone(),
             &base.flow_map_config.load(),
             Some(base.packet_sequence_output_queue.clone()), // Enterprise Edition Feature: packet-sequence
+            Some(base.multicast_groups.clone()),
+            Some(base.neighbor_table.clone()),
+            Some(base.anomaly_detector.clone()),
+            Some(base.app_alert_evaluator.clone()),
             base.stats.clone(),
             false, // !from_ebpf
         );
         let tunnel_type_trim_bitmap = base.tunnel_type_trim_bitmap.clone();
+        let mut sampler = PacketSampler::new(&base.options.lock().unwrap().packet_sampling);
 
         while !base.terminated.load(Ordering::Relaxed) {
             let config = Config {
@@ -228,6 +234,13 @@ impl LocalModeDispatcher {
                 .rx_bytes
                 .fetch_add(packet.data.len() as u64, Ordering::Relaxed);
 
+            if !sampler.sample(&mut meta_packet) {
+                base.counter
+                    .sampling_dropped
+                    .fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
             if base.tunnel_info.tunnel_type != TunnelType::None {
                 meta_packet.tunnel = Some(base.tunnel_info);
                 if base.tunnel_info.tunnel_type == TunnelType::TencentGre
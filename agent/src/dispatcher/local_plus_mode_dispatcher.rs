@@ -36,6 +36,7 @@ use nix::{
 use super::base_dispatcher::{BaseDispatcher, BaseDispatcherListener};
 use super::error::Result;
 use super::local_mode_dispatcher::{skip_by_blacklist, LocalModeDispatcherListener, MacRewriter};
+use super::sampler::PacketSampler;
 use super::Packet;
 
 #[cfg(target_os = "linux")]
@@ -106,6 +107,10 @@ impl LocalPlusModeDispatcher {
         let collector_config = base.collector_config.clone();
         let packet_sequence_output_queue = base.packet_sequence_output_queue.clone(); // Enterprise Edition Feature: packet-sequence
         let stats = base.stats.clone();
+        let multicast_groups = base.multicast_groups.clone();
+        let neighbor_table = base.neighbor_table.clone();
+        let anomaly_detector = base.anomaly_detector.clone();
+        let app_alert_evaluator = base.app_alert_evaluator.clone();
         let pipelines = base.pipelines.clone();
         let tunnel_type_bitmap = base.tunnel_type_bitmap.clone();
         let tap_type_handler = base.tap_type_handler.clone();
@@ -113,6 +118,7 @@ impl LocalPlusModeDispatcher {
         let npb_dedup_enabled = base.npb_dedup_enabled.clone();
         let pool_raw_size = self.pool_raw_size;
         let tunnel_type_trim_bitmap = base.tunnel_type_trim_bitmap.clone();
+        let mut sampler = PacketSampler::new(&base.options.lock().unwrap().packet_sampling);
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let cpu_set = base.options.lock().unwrap().cpu_set;
 
@@ -131,6 +137,10 @@ impl LocalPlusModeDispatcher {
                         ntp_diff,
                         &flow_map_config.load(),
                         Some(packet_sequence_output_queue), // Enterprise Edition Feature: packet-sequence
+                        Some(multicast_groups),
+                        Some(neighbor_table),
+                        Some(anomaly_detector),
+                        Some(app_alert_evaluator),
                         stats,
                         false, // !from_ebpf
                     );
@@ -240,6 +250,10 @@ impl LocalPlusModeDispatcher {
                                 warn!("meta_packet update failed: {:?}", e);
                                 continue;
                             }
+                            if !sampler.sample(&mut meta_packet) {
+                                counter.sampling_dropped.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
 
                             if tunnel_info.tunnel_type != TunnelType::None {
                                 meta_packet.tunnel = Some(tunnel_info);
@@ -34,6 +34,8 @@ use nix::{
     unistd::Pid,
 };
 
+use super::dedup::PacketDedupMap;
+use super::sampler::PacketSampler;
 use super::CaptureNetworkTypeHandler;
 use crate::common::decapsulate::TunnelTypeBitmap;
 #[cfg(target_os = "linux")]
@@ -56,7 +58,6 @@ use crate::{
     rpc::get_timestamp,
     utils::environment::is_tt_hyper_v_compute,
 };
-use packet_dedup::PacketDedupMap;
 use public::packet::Packet;
 use public::{
     proto::agent::{AgentType, IfMacSource},
@@ -346,6 +347,7 @@ pub fn handler(
     agent_type: AgentType,
     mac: u32,
     npb_dedup: bool,
+    sampler: &mut PacketSampler,
 ) -> Result<()> {
     let pipeline = get_pipeline(updated, pipelines, key, id, handler_builder);
 
@@ -358,6 +360,10 @@ pub fn handler(
         dst_local,
         original_length,
     )?;
+    if !sampler.sample(&mut meta_packet) {
+        counter.sampling_dropped.fetch_add(1, Ordering::Relaxed);
+        return Ok(());
+    }
 
     prepare_flow(
         &mut meta_packet,
@@ -556,9 +562,14 @@ impl MirrorModeDispatcher {
             self.base.ntp_diff.clone(),
             &self.base.flow_map_config.load(),
             Some(self.base.packet_sequence_output_queue.clone()), // Enterprise Edition Feature: packet-sequence
+            Some(self.base.multicast_groups.clone()),
+            Some(self.base.neighbor_table.clone()),
+            Some(self.base.anomaly_detector.clone()),
+            Some(self.base.app_alert_evaluator.clone()),
             self.base.stats.clone(),
             false, // !from_ebpf
         );
+        let mut sampler = PacketSampler::new(&self.base.options.lock().unwrap().packet_sampling);
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let cpu_set = self.base.options.lock().unwrap().cpu_set;
         #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -645,6 +656,7 @@ impl MirrorModeDispatcher {
             // Only virtual network traffic goes to remove duplicates
             #[cfg(any(target_os = "linux", target_os = "android"))]
             if self.dedup.duplicate(overlay_packet, timestamp) {
+                self.base.counter.duplicated.fetch_add(1, Ordering::Relaxed);
                 debug!("Packet is duplicate");
                 continue;
             }
@@ -675,6 +687,7 @@ impl MirrorModeDispatcher {
                     agent_type,
                     self.mac,
                     self.base.npb_dedup_enabled.load(Ordering::Relaxed),
+                    &mut sampler,
                 );
                 continue;
             }
@@ -702,6 +715,7 @@ impl MirrorModeDispatcher {
                         self.mac
                     },
                     self.base.npb_dedup_enabled.load(Ordering::Relaxed),
+                    &mut sampler,
                 );
             }
             if da_gateway_vmac > 0 {
@@ -727,6 +741,7 @@ impl MirrorModeDispatcher {
                         self.mac
                     },
                     self.base.npb_dedup_enabled.load(Ordering::Relaxed),
+                    &mut sampler,
                 );
             }
         }
@@ -33,9 +33,12 @@ use nix::{
     unistd::Pid,
 };
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::dedup::PacketDedupMap;
 use super::mirror_mode_dispatcher::{
     get_key as mirror_get_key, handler as mirror_handler, swap_last_timestamp,
 };
+use super::sampler::PacketSampler;
 use super::{CaptureNetworkTypeHandler, Packet};
 #[cfg(target_os = "linux")]
 use crate::platform::{GenericPoller, Poller};
@@ -280,6 +283,10 @@ impl MirrorPlusModeDispatcher {
         let collector_config = base.collector_config.clone();
         let packet_sequence_output_queue = base.packet_sequence_output_queue.clone(); // Enterprise Edition Feature: packet-sequence
         let stats = base.stats.clone();
+        let multicast_groups = base.multicast_groups.clone();
+        let neighbor_table = base.neighbor_table.clone();
+        let anomaly_detector = base.anomaly_detector.clone();
+        let app_alert_evaluator = base.app_alert_evaluator.clone();
         let tap_type_handler = base.tap_type_handler.clone();
         let tunnel_type_bitmap = base.tunnel_type_bitmap.clone();
         let tunnel_type_trim_bitmap = base.tunnel_type_trim_bitmap.clone();
@@ -289,10 +296,11 @@ impl MirrorPlusModeDispatcher {
         let mac = self.mac;
         let agent_type = self.agent_type.clone();
         let local_vm_mac_set = self.local_vm_mac_set.clone();
+        let mut sampler = PacketSampler::new(&base.options.lock().unwrap().packet_sampling);
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let cpu_set = base.options.lock().unwrap().cpu_set;
         #[cfg(any(target_os = "linux", target_os = "android"))]
-        let mut dedup = packet_dedup::PacketDedupMap::new();
+        let mut dedup = PacketDedupMap::new(base.packet_dedup_window);
 
         self.flow_generator_thread_handler.replace(
             thread::Builder::new()
@@ -308,6 +316,10 @@ impl MirrorPlusModeDispatcher {
                         ntp_diff,
                         &flow_map_config.load(),
                         Some(packet_sequence_output_queue), // Enterprise Edition Feature: packet-sequence
+                        Some(multicast_groups),
+                        Some(neighbor_table),
+                        Some(anomaly_detector),
+                        Some(app_alert_evaluator),
                         stats,
                         false, // !from_ebpf
                     );
@@ -379,6 +391,7 @@ impl MirrorPlusModeDispatcher {
                             // Only virtual network traffic goes to remove duplicates
                             #[cfg(any(target_os = "linux", target_os = "android"))]
                             if dedup.duplicate(overlay_packet, timestamp) {
+                                counter.duplicated.fetch_add(1, Ordering::Relaxed);
                                 debug!("Packet is duplicate");
                                 continue;
                             }
@@ -403,6 +416,7 @@ impl MirrorPlusModeDispatcher {
                                     agent_type,
                                     mac,
                                     npb_dedup_enabled.load(Ordering::Relaxed),
+                                    &mut sampler,
                                 );
                                 continue;
                             }
@@ -430,6 +444,7 @@ impl MirrorPlusModeDispatcher {
                                         mac
                                     },
                                     npb_dedup_enabled.load(Ordering::Relaxed),
+                                    &mut sampler,
                                 );
                             }
                             if da_gateway_vmac > 0 {
@@ -455,6 +470,7 @@ impl MirrorPlusModeDispatcher {
                                         mac
                                     },
                                     npb_dedup_enabled.load(Ordering::Relaxed),
+                                    &mut sampler,
                                 );
                             }
                         }
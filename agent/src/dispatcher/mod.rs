@@ -18,12 +18,14 @@ mod error;
 pub(crate) mod recv_engine;
 
 mod base_dispatcher;
+mod dedup;
 
 mod analyzer_mode_dispatcher;
 mod local_mode_dispatcher;
 mod local_plus_mode_dispatcher;
 mod mirror_mode_dispatcher;
 mod mirror_plus_mode_dispatcher;
+mod sampler;
 
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
@@ -40,19 +42,23 @@ use log::error;
 use log::{debug, info, warn};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use nix::sched::CpuSet;
-use packet_dedup::*;
 use public::debug::QueueDebugger;
-use special_recv_engine::Libpcap;
 #[cfg(target_os = "linux")]
-use special_recv_engine::{Dpdk, VhostUser};
+use special_recv_engine::{Dpdk, DpdkOptions, DpdkProcessType, VhostUser};
 
 use analyzer_mode_dispatcher::{AnalyzerModeDispatcher, AnalyzerModeDispatcherListener}; // Enterprise Edition Feature: analyzer_mode
 use base_dispatcher::{BaseDispatcher, CaptureNetworkTypeHandler};
+use dedup::PacketDedupMap;
 use error::{Error, Result};
 use local_mode_dispatcher::{LocalModeDispatcher, LocalModeDispatcherListener};
 use local_plus_mode_dispatcher::{LocalPlusModeDispatcher, LocalPlusModeDispatcherListener};
 use mirror_mode_dispatcher::{MirrorModeDispatcher, MirrorModeDispatcherListener};
 use mirror_plus_mode_dispatcher::{MirrorPlusModeDispatcher, MirrorPlusModeDispatcherListener};
+#[cfg(target_os = "linux")]
+use recv_engine::af_xdp::Xsk;
+use recv_engine::libpcap::Libpcap;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use recv_engine::pcap_replay::PcapReplay;
 pub use recv_engine::RecvEngine;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use recv_engine::{
@@ -65,6 +71,8 @@ use self::base_dispatcher::TapInterfaceWhitelist;
 
 use crate::common::decapsulate::TunnelTypeBitmap;
 #[cfg(target_os = "linux")]
+use crate::config::DpdkProcessType as DpdkProcessTypeConfig;
+#[cfg(target_os = "linux")]
 use crate::platform::LibvirtXmlExtractor;
 use crate::{
     common::{
@@ -73,10 +81,13 @@ use crate::{
     },
     config::{
         handler::{CollectorAccess, FlowAccess, LogParserAccess},
-        DispatcherConfig,
+        AfXdp, DispatcherConfig, Dpdk as DpdkConfig, PacketSampling,
+        PcapReplay as PcapReplayConfig,
     },
     exception::ExceptionHandler,
-    flow_generator::AppProto,
+    flow_generator::{
+        AnomalyDetector, AppAlertEvaluator, AppProto, MulticastGroupTable, NeighborTable,
+    },
     handler::{PacketHandler, PacketHandlerBuilder},
     policy::PolicyGetter,
     utils::{
@@ -551,6 +562,7 @@ pub struct Options {
     pub libpcap_enabled: bool,
     pub dispatcher_queue: bool,
     pub packet_fanout_mode: u32,
+    pub hardware_timestamp: bool,
     pub tap_mac_script: String,
     pub is_ipv6: bool,
     pub vxlan_flags: u8,
@@ -558,6 +570,14 @@ pub struct Options {
     pub controller_port: u16,
     pub controller_tls_port: u16,
     pub vhost_socket_path: String,
+    pub dpdk: DpdkConfig,
+    pub af_xdp: AfXdp,
+    pub pcap_replay: PcapReplayConfig,
+    // index of the parallel dispatchers of src_interface, None if not parallel. With AF_XDP
+    // it is the RX queue to capture from, with af_packet the dispatchers share the packets
+    // by flow hash
+    pub parallel_index: Option<u32>,
+    pub packet_sampling: PacketSampling,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub cpu_set: CpuSet,
 }
@@ -588,6 +608,15 @@ pub struct PacketCounter {
     invalid_packets: AtomicU64,
     get_token_failed: AtomicU64,
 
+    // filter attached to the live socket, no packet is lost
+    bpf_updates: AtomicU64,
+    bpf_update_failures: AtomicU64,
+
+    // packets dropped by deduplication
+    duplicated: AtomicU64,
+    // packets dropped by sampling
+    sampling_dropped: AtomicU64,
+
     retired: AtomicU64,
     kernel_counter: Arc<dyn stats::RefCountable>,
 }
@@ -606,6 +635,12 @@ impl PacketCounter {
             invalid_packets: AtomicU64::new(0),
             get_token_failed: AtomicU64::new(0),
 
+            bpf_updates: AtomicU64::new(0),
+            bpf_update_failures: AtomicU64::new(0),
+
+            duplicated: AtomicU64::new(0),
+            sampling_dropped: AtomicU64::new(0),
+
             retired: AtomicU64::new(0),
             kernel_counter,
         }
@@ -655,6 +690,26 @@ impl stats::RefCountable for PacketCounter {
                 stats::CounterType::Counted,
                 stats::CounterValue::Unsigned(get_token_failed),
             ),
+            (
+                "bpf_updates",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.bpf_updates.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "bpf_update_failures",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.bpf_update_failures.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "duplicated",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.duplicated.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sampling_dropped",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.sampling_dropped.swap(0, Ordering::Relaxed)),
+            ),
             (
                 "retired",
                 stats::CounterType::Counted,
@@ -679,6 +734,7 @@ pub struct DispatcherBuilder {
     mirror_traffic_pcp: Option<u16>,
     tap_typer: Option<Arc<CaptureNetworkTyper>>,
     analyzer_dedup_disabled: Option<bool>,
+    packet_dedup_window: Option<Duration>,
     #[cfg(target_os = "linux")]
     libvirt_xml_extractor: Option<Arc<LibvirtXmlExtractor>>,
     flow_output_queue: Option<DebugSender<Arc<BatchedBox<TaggedFlow>>>>,
@@ -691,6 +747,10 @@ pub struct DispatcherBuilder {
     log_parse_config: Option<LogParserAccess>,
     collector_config: Option<CollectorAccess>,
     policy_getter: Option<PolicyGetter>,
+    multicast_groups: Option<Arc<MulticastGroupTable>>,
+    neighbor_table: Option<Arc<NeighborTable>>,
+    anomaly_detector: Option<Arc<AnomalyDetector>>,
+    app_alert_evaluator: Option<Arc<AppAlertEvaluator>>,
     #[cfg(target_os = "linux")]
     platform_poller: Option<Arc<crate::platform::GenericPoller>>,
     exception_handler: Option<ExceptionHandler>,
@@ -771,6 +831,11 @@ impl DispatcherBuilder {
         self
     }
 
+    pub fn packet_dedup_window(mut self, v: Duration) -> Self {
+        self.packet_dedup_window = Some(v);
+        self
+    }
+
     #[cfg(target_os = "linux")]
     pub fn libvirt_xml_extractor(mut self, v: Arc<LibvirtXmlExtractor>) -> Self {
         self.libvirt_xml_extractor = Some(v);
@@ -826,6 +891,26 @@ impl DispatcherBuilder {
         self
     }
 
+    pub fn multicast_groups(mut self, v: Arc<MulticastGroupTable>) -> Self {
+        self.multicast_groups = Some(v);
+        self
+    }
+
+    pub fn neighbor_table(mut self, v: Arc<NeighborTable>) -> Self {
+        self.neighbor_table = Some(v);
+        self
+    }
+
+    pub fn anomaly_detector(mut self, v: Arc<AnomalyDetector>) -> Self {
+        self.anomaly_detector = Some(v);
+        self
+    }
+
+    pub fn app_alert_evaluator(mut self, v: Arc<AppAlertEvaluator>) -> Self {
+        self.app_alert_evaluator = Some(v);
+        self
+    }
+
     pub fn exception_handler(mut self, v: ExceptionHandler) -> Self {
         self.exception_handler = Some(v);
         self
@@ -899,6 +984,16 @@ impl DispatcherBuilder {
             &options,
             &queue_debugger,
         )?;
+        let ntp_diff = self
+            .ntp_diff
+            .take()
+            .ok_or(Error::ConfigIncomplete("no ntp_diff".into()))?;
+        // replaying with original timestamps, flows are generated in the time of the capture
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let ntp_diff = match &engine {
+            RecvEngine::PcapReplay(replay) => replay.clock().unwrap_or(ntp_diff),
+            _ => ntp_diff,
+        };
 
         let kernel_counter = engine.get_counter_handle();
         let id = self.id.ok_or(Error::ConfigIncomplete("no id".into()))?;
@@ -994,6 +1089,9 @@ impl DispatcherBuilder {
             analyzer_dedup_disabled: self
                 .analyzer_dedup_disabled
                 .ok_or(Error::ConfigIncomplete("no analyzer_dedup_disabled".into()))?,
+            packet_dedup_window: self
+                .packet_dedup_window
+                .ok_or(Error::ConfigIncomplete("no packet_dedup_window".into()))?,
 
             flow_output_queue: self
                 .flow_output_queue
@@ -1026,16 +1124,26 @@ impl DispatcherBuilder {
             policy_getter: self
                 .policy_getter
                 .ok_or(Error::ConfigIncomplete("no policy".into()))?,
+            multicast_groups: self.multicast_groups.take().unwrap_or_default(),
+            neighbor_table: self
+                .neighbor_table
+                .take()
+                .ok_or(Error::ConfigIncomplete("no neighbor table".into()))?,
+            anomaly_detector: self
+                .anomaly_detector
+                .take()
+                .ok_or(Error::ConfigIncomplete("no anomaly detector".into()))?,
+            app_alert_evaluator: self
+                .app_alert_evaluator
+                .take()
+                .ok_or(Error::ConfigIncomplete("no app alert evaluator".into()))?,
             #[cfg(target_os = "linux")]
             platform_poller: platform_poller.clone(),
             exception_handler: self
                 .exception_handler
                 .take()
                 .ok_or(Error::ConfigIncomplete("no exception handler".into()))?,
-            ntp_diff: self
-                .ntp_diff
-                .take()
-                .ok_or(Error::ConfigIncomplete("no ntp_diff".into()))?,
+            ntp_diff,
             // Enterprise Edition Feature: packet-sequence
             packet_sequence_output_queue: self
                 .packet_sequence_output_queue
@@ -1116,8 +1224,8 @@ impl DispatcherBuilder {
                     })
                 } else {
                     DispatcherFlavor::Mirror(MirrorModeDispatcher {
+                        dedup: PacketDedupMap::new(base.packet_dedup_window),
                         base,
-                        dedup: PacketDedupMap::new(),
                         local_vm_mac_set: Arc::new(RwLock::new(HashMap::new())),
                         local_segment_macs: vec![],
                         tap_bridge_macs: vec![],
@@ -1210,6 +1318,13 @@ impl DispatcherBuilder {
     ) -> Result<RecvEngine> {
         let options = options.lock().unwrap();
         match capture_mode {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            PacketCaptureType::Mirror if !options.pcap_replay.files.is_empty() => {
+                info!("Pcap replay init with {:?}", options.pcap_replay);
+                Ok(RecvEngine::PcapReplay(PcapReplay::new(
+                    &options.pcap_replay,
+                )))
+            }
             #[cfg(target_os = "linux")]
             PacketCaptureType::Mirror if !options.vhost_socket_path.is_empty() => {
                 Ok(RecvEngine::VhostUser(VhostUser::new(
@@ -1253,9 +1368,36 @@ impl DispatcherBuilder {
                 ));
                 #[cfg(not(target_arch = "s390x"))]
                 {
-                    Ok(RecvEngine::Dpdk(Dpdk::new(None, None, options.snap_len)))
+                    let dpdk = DpdkOptions {
+                        process_type: match options.dpdk.process_type {
+                            DpdkProcessTypeConfig::Primary => DpdkProcessType::Primary,
+                            DpdkProcessTypeConfig::Secondary => DpdkProcessType::Secondary,
+                        },
+                        eal_args: options.dpdk.eal_args.clone(),
+                        ring_name: options.dpdk.ring_name.clone(),
+                        mempool_name: options.dpdk.mempool_name.clone(),
+                    };
+                    info!("DPDK init with {:?}", dpdk);
+                    Ok(RecvEngine::Dpdk(Dpdk::new(&dpdk, options.snap_len)?))
                 }
             }
+            #[cfg(target_os = "linux")]
+            PacketCaptureType::Mirror | PacketCaptureType::Analyzer if options.af_xdp.enabled => {
+                let Some(iface) = src_interface.as_ref() else {
+                    return Err(Error::ConfigInvalid(
+                        "af_xdp requires a capture interface".into(),
+                    ));
+                };
+                info!(
+                    "AF_XDP init with {} queue {:?} {:?}",
+                    iface, options.parallel_index, options.af_xdp
+                );
+                Ok(RecvEngine::AfXdp(Xsk::new(
+                    iface,
+                    &options.af_xdp,
+                    options.parallel_index,
+                )?))
+            }
             #[cfg(any(target_os = "linux", target_os = "android"))]
             PacketCaptureType::Local | PacketCaptureType::Mirror | PacketCaptureType::Analyzer => {
                 let afp = af_packet::Options {
@@ -1271,9 +1413,14 @@ impl DispatcherBuilder {
                     iface: src_interface.as_ref().unwrap_or(&"".to_string()).clone(),
                     packet_fanout_mode: if options.capture_mode == PacketCaptureType::Local {
                         Some(options.packet_fanout_mode)
+                    } else if options.parallel_index.is_some() {
+                        // one socket for each parallel dispatcher, the kernel dispatches
+                        // packets by flow hash so that flows are not split across dispatchers
+                        Some(af_packet::tpacket::PACKET_FANOUT_HASH)
                     } else {
                         None
                     },
+                    hardware_timestamp: options.hardware_timestamp,
                     ..Default::default()
                 };
                 info!("Afpacket init with {:?}", afp);
@@ -18,6 +18,7 @@ use std::slice;
 use std::time::Duration;
 
 use libc::{c_uint, sockaddr_ll};
+use public::enums::LinkType;
 
 const TP_STATUS_KERNEL: u32 = 0;
 const TPACKET_ALIGNMENT: usize = 0x10;
@@ -34,6 +35,7 @@ pub trait Header {
     fn get_data(&self) -> &mut [u8];
     fn get_length(&self) -> isize;
     fn get_iface_index(&self) -> isize;
+    fn get_link_type(&self) -> LinkType;
     fn next(&mut self) -> bool;
 }
 
@@ -208,6 +210,14 @@ impl Header for *mut Tpacket2Hdr {
         }
     }
 
+    fn get_link_type(&self) -> LinkType {
+        let ptr = (*self) as *const Tpacket2Hdr as *const u8 as usize;
+        unsafe {
+            let ll = (ptr + to_align(std::mem::size_of::<Tpacket2Hdr>())) as *const sockaddr_ll;
+            return LinkType::from((*ll).sll_hatype);
+        }
+    }
+
     fn next(&mut self) -> bool {
         return false;
     }
@@ -304,6 +314,14 @@ impl Header for V3Wrapper {
         }
     }
 
+    fn get_link_type(&self) -> LinkType {
+        let ptr = self.v3_header as *const u8 as usize;
+        unsafe {
+            let ll = (ptr + to_align(std::mem::size_of::<Tpacket3Hdr>())) as *const sockaddr_ll;
+            return LinkType::from((*ll).sll_hatype);
+        }
+    }
+
     fn next(&mut self) -> bool {
         unsafe {
             self.used += 1;
@@ -81,6 +81,8 @@ pub struct Options {
     pub socket_type: OptSocketType,
     pub iface: String,
     pub packet_fanout_mode: Option<u32>,
+    // use NIC hardware timestamps if supported by the interface
+    pub hardware_timestamp: bool,
 }
 
 impl Default for Options {
@@ -96,6 +98,7 @@ impl Default for Options {
             socket_type: OptSocketType::SocketTypeRaw,
             iface: "".to_string(),
             packet_fanout_mode: None,
+            hardware_timestamp: false,
         }
     }
 }
@@ -42,6 +42,9 @@ const PACKET_VERSION: c_int = 10;
 const PACKET_RX_RING: c_int = 5;
 const PACKET_FANOUT: c_int = 18;
 const PACKET_STATISTICS: c_int = 6;
+const PACKET_TIMESTAMP: c_int = 17;
+// dispatch by the symmetric flow hash, both directions of a flow go to the same socket
+pub const PACKET_FANOUT_HASH: u32 = 0;
 const MILLI_SECONDS: u32 = 1000000;
 const MIN_KERNEL_VERSION_SUPPORT_PACKET_FANOUT: &'static str = "3.1";
 
@@ -101,18 +104,21 @@ impl Debug for Tpacket {
 unsafe impl Send for Tpacket {}
 
 impl Tpacket {
-    fn bind(&self) -> af_packet::Result<()> {
-        let mut if_index: i32 = 0;
-
-        if self.opts.iface != "" {
-            // 根据网卡名称获取网卡if_index
-            let link = link_by_name(self.opts.iface.clone()).map_err(|e| match e {
-                net::Error::LinkNotFound(s) => af_packet::Error::LinkError(s),
-                net::Error::NetlinkError(ne) => af_packet::Error::LinkError(ne.to_string()),
-                _ => unreachable!(),
-            })?;
-            if_index = link.if_index as i32;
+    fn if_index(&self) -> af_packet::Result<u32> {
+        if self.opts.iface == "" {
+            return Ok(0);
         }
+        // 根据网卡名称获取网卡if_index
+        let link = link_by_name(self.opts.iface.clone()).map_err(|e| match e {
+            net::Error::LinkNotFound(s) => af_packet::Error::LinkError(s),
+            net::Error::NetlinkError(ne) => af_packet::Error::LinkError(ne.to_string()),
+            _ => unreachable!(),
+        })?;
+        Ok(link.if_index)
+    }
+
+    fn bind(&self) -> af_packet::Result<()> {
+        let if_index = self.if_index()?;
         unsafe {
             let mut sa: sockaddr_ll = std::mem::zeroed();
             sa.sll_family = AF_PACKET as u16;
@@ -221,7 +227,7 @@ impl Tpacket {
             return Ok(());
         }
         let Some(packet_fanout_mode) = self.opts.packet_fanout_mode else {
-            info!("Packet fanout can only be set in PacketCaptureType::Local mode");
+            info!("Packet fanout is not enabled, skip the packet fanout setting");
             return Ok(());
         };
         // The first 16 bits encode the fanout group ID, and the second set of 16 bits encode the fanout mode and options.
@@ -230,6 +236,36 @@ impl Tpacket {
         self.setsockopt(SOL_PACKET, PACKET_FANOUT, fanout_arg)
     }
 
+    // Hardware timestamps are probed for each interface, the kernel falls back to software
+    // timestamps for sockets without PACKET_TIMESTAMP set
+    fn set_hardware_timestamp(&self) {
+        if !self.opts.hardware_timestamp {
+            return;
+        }
+        if self.opts.iface == "" {
+            info!("Hardware timestamp requires a capture interface, use software timestamp");
+            return;
+        }
+        if let Err(e) = net::enable_hardware_rx_timestamp(&self.opts.iface) {
+            info!(
+                "Hardware timestamp is unavailable on {}, use software timestamp: {}",
+                self.opts.iface, e
+            );
+            return;
+        }
+        match self.setsockopt(
+            SOL_PACKET,
+            PACKET_TIMESTAMP,
+            net::SOF_TIMESTAMPING_RAW_HARDWARE as c_int,
+        ) {
+            Ok(_) => info!("Hardware timestamp is enabled on {}", self.opts.iface),
+            Err(e) => warn!(
+                "Set hardware timestamp on {} failed, use software timestamp: {}",
+                self.opts.iface, e
+            ),
+        }
+    }
+
     fn mmap_ring(&mut self) -> af_packet::Result<()> {
         // 接收队列
         unsafe {
@@ -346,6 +382,7 @@ impl Tpacket {
                 timestamp: x.get_time(),
                 if_index: x.get_iface_index(),
                 data: x.get_data(),
+                link_type: x.get_link_type(),
                 capture_length: x.get_length(),
                 ..Default::default()
             };
@@ -408,6 +445,7 @@ impl Tpacket {
         };
         tpacket.bind()?;
         tpacket.set_version()?;
+        tpacket.set_hardware_timestamp();
         tpacket.set_ring()?;
         tpacket.mmap_ring()?;
         tpacket.set_fanout()?;
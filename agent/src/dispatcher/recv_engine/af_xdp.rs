@@ -0,0 +1,855 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex, Weak,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use libc::{
+    c_int, c_void, getsockopt, mmap, munmap, off_t, poll, pollfd, recvfrom, setsockopt, sockaddr,
+    socket, socklen_t, MAP_ANONYMOUS, MAP_FAILED, MAP_POPULATE, MAP_PRIVATE, MAP_SHARED,
+    MSG_DONTWAIT, POLLIN, PROT_READ, PROT_WRITE, SOCK_RAW,
+};
+use log::{info, warn};
+use public::enums::LinkType;
+use public::error::{Error, Result};
+use public::packet::Packet;
+use public::utils::net::link_by_name;
+
+use super::{rx_queue_count, POLL_TIMEOUT};
+use crate::config::{AfXdp, XdpAttachMode, XdpBindMode};
+use crate::utils::stats;
+
+// Reference https://docs.kernel.org/networking/af_xdp.html
+const AF_XDP: c_int = 44;
+const SOL_XDP: c_int = 283;
+const XDP_MMAP_OFFSETS: c_int = 1;
+const XDP_RX_RING: c_int = 2;
+const XDP_UMEM_REG: c_int = 4;
+const XDP_UMEM_FILL_RING: c_int = 5;
+const XDP_UMEM_COMPLETION_RING: c_int = 6;
+const XDP_STATISTICS: c_int = 7;
+const XDP_PGOFF_RX_RING: u64 = 0;
+const XDP_UMEM_PGOFF_FILL_RING: u64 = 0x100000000;
+const XDP_UMEM_PGOFF_COMPLETION_RING: u64 = 0x180000000;
+const XDP_COPY: u16 = 1 << 1;
+const XDP_ZEROCOPY: u16 = 1 << 2;
+const XDP_USE_NEED_WAKEUP: u16 = 1 << 3;
+const XDP_RING_NEED_WAKEUP: u32 = 1 << 0;
+
+// The chunk size of UMEM, packets larger than it minus the 256 bytes headroom reserved
+// by the kernel are dropped
+pub const XDP_FRAME_SIZE: u32 = 4096;
+
+// bpf(2) commands and types used to redirect packets to AF_XDP sockets
+const BPF_MAP_CREATE: c_int = 0;
+const BPF_MAP_UPDATE_ELEM: c_int = 2;
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_LINK_CREATE: c_int = 28;
+const BPF_MAP_TYPE_XSKMAP: u32 = 17;
+const BPF_PROG_TYPE_XDP: u32 = 6;
+const BPF_XDP: u32 = 37;
+const XDP_FLAGS_SKB_MODE: u32 = 1 << 1;
+const XDP_FLAGS_DRV_MODE: u32 = 1 << 2;
+
+const BPF_LDX_MEM_W: u8 = 0x61;
+const BPF_LD_IMM_DW: u8 = 0x18;
+const BPF_ALU64_MOV_K: u8 = 0xb7;
+const BPF_JMP_CALL: u8 = 0x85;
+const BPF_JMP_EXIT: u8 = 0x95;
+const BPF_PSEUDO_MAP_FD: u8 = 1;
+const BPF_FUNC_REDIRECT_MAP: i32 = 51;
+const XDP_PASS: i32 = 2;
+// offset of rx_queue_index in struct xdp_md
+const XDP_MD_RX_QUEUE_INDEX_OFFSET: i16 = 16;
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct XdpUmemReg {
+    addr: u64,
+    len: u64,
+    chunk_size: u32,
+    headroom: u32,
+    flags: u32,
+    tx_metadata_len: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct XdpRingOffset {
+    producer: u64,
+    consumer: u64,
+    desc: u64,
+    flags: u64,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct XdpMmapOffsets {
+    rx: XdpRingOffset,
+    tx: XdpRingOffset,
+    fr: XdpRingOffset,
+    cr: XdpRingOffset,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct SockaddrXdp {
+    family: u16,
+    flags: u16,
+    ifindex: u32,
+    queue_id: u32,
+    shared_umem_fd: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct XdpDesc {
+    addr: u64,
+    len: u32,
+    options: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct XdpStatistics {
+    rx_dropped: u64,
+    rx_invalid_descs: u64,
+    tx_invalid_descs: u64,
+    rx_ring_full: u64,
+    rx_fill_ring_empty_descs: u64,
+    tx_ring_empty_descs: u64,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BpfInsn {
+    code: u8,
+    // dst_reg in the low 4 bits, src_reg in the high 4 bits
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+impl BpfInsn {
+    const fn new(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Self {
+        Self {
+            code,
+            regs: src << 4 | dst,
+            off,
+            imm,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct BpfMapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct BpfMapUpdateAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct BpfProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct BpfLinkCreateAttr {
+    prog_fd: u32,
+    target_ifindex: u32,
+    attach_type: u32,
+    flags: u32,
+}
+
+fn bpf<T>(cmd: c_int, attr: &mut T) -> io::Result<OwnedFd> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut T as *mut c_void,
+            mem::size_of::<T>() as u32,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(ret as RawFd) })
+}
+
+// bpf_redirect_map(&xsks_map, ctx->rx_queue_index, XDP_PASS), packets of queues without
+// AF_XDP socket are passed to the kernel stack
+fn xdp_program(map_fd: RawFd) -> [BpfInsn; 6] {
+    [
+        BpfInsn::new(BPF_LDX_MEM_W, 2, 1, XDP_MD_RX_QUEUE_INDEX_OFFSET, 0),
+        // ld_imm64 takes two instructions
+        BpfInsn::new(BPF_LD_IMM_DW, 1, BPF_PSEUDO_MAP_FD, 0, map_fd),
+        BpfInsn::new(0, 0, 0, 0, 0),
+        BpfInsn::new(BPF_ALU64_MOV_K, 3, 0, 0, XDP_PASS),
+        BpfInsn::new(BPF_JMP_CALL, 0, 0, 0, BPF_FUNC_REDIRECT_MAP),
+        BpfInsn::new(BPF_JMP_EXIT, 0, 0, 0, 0),
+    ]
+}
+
+lazy_static! {
+    // XDP programs by if_index, shared by dispatchers capturing different queues of the
+    // same interface because only one program can be attached to an interface
+    static ref XDP_PROGRAMS: Mutex<HashMap<u32, Weak<XdpProgram>>> = Default::default();
+}
+
+// The XDP program and XSKMAP attached to the interface, the program is detached when
+// the link is closed
+struct XdpProgram {
+    map: OwnedFd,
+    _prog: OwnedFd,
+    _link: OwnedFd,
+}
+
+impl XdpProgram {
+    fn get_or_attach(
+        if_index: u32,
+        queue_count: u32,
+        mode: XdpAttachMode,
+    ) -> io::Result<Arc<Self>> {
+        let mut programs = XDP_PROGRAMS.lock().unwrap();
+        if let Some(program) = programs.get(&if_index).and_then(|p| p.upgrade()) {
+            return Ok(program);
+        }
+        let program = Arc::new(Self::attach(if_index, queue_count, mode)?);
+        programs.insert(if_index, Arc::downgrade(&program));
+        Ok(program)
+    }
+
+    fn attach(if_index: u32, queue_count: u32, mode: XdpAttachMode) -> io::Result<Self> {
+        let mut map_attr = BpfMapCreateAttr {
+            map_type: BPF_MAP_TYPE_XSKMAP,
+            key_size: mem::size_of::<u32>() as u32,
+            value_size: mem::size_of::<u32>() as u32,
+            max_entries: queue_count,
+            ..Default::default()
+        };
+        let map = bpf(BPF_MAP_CREATE, &mut map_attr)?;
+
+        let insns = xdp_program(map.as_raw_fd());
+        let license = b"GPL\0";
+        let mut prog_attr = BpfProgLoadAttr {
+            prog_type: BPF_PROG_TYPE_XDP,
+            insn_cnt: insns.len() as u32,
+            insns: insns.as_ptr() as u64,
+            license: license.as_ptr() as u64,
+            ..Default::default()
+        };
+        let prog = bpf(BPF_PROG_LOAD, &mut prog_attr)?;
+
+        let modes: &[u32] = match mode {
+            XdpAttachMode::Auto => &[XDP_FLAGS_DRV_MODE, XDP_FLAGS_SKB_MODE],
+            XdpAttachMode::Driver => &[XDP_FLAGS_DRV_MODE],
+            XdpAttachMode::Generic => &[XDP_FLAGS_SKB_MODE],
+        };
+        let mut last_error = None;
+        for &flags in modes {
+            let mut link_attr = BpfLinkCreateAttr {
+                prog_fd: prog.as_raw_fd() as u32,
+                target_ifindex: if_index,
+                attach_type: BPF_XDP,
+                flags,
+            };
+            match bpf(BPF_LINK_CREATE, &mut link_attr) {
+                Ok(link) => {
+                    info!(
+                        "xdp program attached to if_index {} in {} mode",
+                        if_index,
+                        if flags == XDP_FLAGS_DRV_MODE {
+                            "driver"
+                        } else {
+                            "generic"
+                        }
+                    );
+                    return Ok(Self {
+                        map,
+                        _prog: prog,
+                        _link: link,
+                    });
+                }
+                Err(e) => {
+                    warn!(
+                        "attach xdp program to if_index {} with flags {:#x} failed: {}",
+                        if_index, flags, e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    fn register(&self, queue_id: u32, xsk_fd: RawFd) -> io::Result<()> {
+        let key = queue_id;
+        let value = xsk_fd as u32;
+        let mut attr = BpfMapUpdateAttr {
+            map_fd: self.map.as_raw_fd() as u32,
+            key: &key as *const u32 as u64,
+            value: &value as *const u32 as u64,
+            ..Default::default()
+        };
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_bpf,
+                BPF_MAP_UPDATE_ELEM,
+                &mut attr as *mut BpfMapUpdateAttr as *mut c_void,
+                mem::size_of::<BpfMapUpdateAttr>() as u32,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+// Single producer single consumer ring shared with the kernel
+struct Ring<T> {
+    map: *mut c_void,
+    map_len: usize,
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    flags: *const AtomicU32,
+    descs: *mut T,
+    mask: u32,
+    // local copy of the index we own, published to the kernel on release
+    cached: u32,
+}
+
+impl<T: Copy> Ring<T> {
+    unsafe fn mmap(fd: RawFd, offset: &XdpRingOffset, size: u32, pgoff: u64) -> io::Result<Self> {
+        let map_len = offset.desc as usize + size as usize * mem::size_of::<T>();
+        let map = mmap(
+            ptr::null_mut(),
+            map_len,
+            PROT_READ | PROT_WRITE,
+            MAP_SHARED | MAP_POPULATE,
+            fd,
+            pgoff as off_t,
+        );
+        if map == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let base = map as *mut u8;
+        Ok(Self {
+            map,
+            map_len,
+            producer: base.add(offset.producer as usize) as *const AtomicU32,
+            consumer: base.add(offset.consumer as usize) as *const AtomicU32,
+            flags: base.add(offset.flags as usize) as *const AtomicU32,
+            descs: base.add(offset.desc as usize) as *mut T,
+            mask: size - 1,
+            cached: 0,
+        })
+    }
+
+    fn producer(&self) -> &AtomicU32 {
+        unsafe { &*self.producer }
+    }
+
+    fn consumer(&self) -> &AtomicU32 {
+        unsafe { &*self.consumer }
+    }
+
+    fn need_wakeup(&self) -> bool {
+        unsafe { &*self.flags }.load(Ordering::Relaxed) & XDP_RING_NEED_WAKEUP != 0
+    }
+
+    // consumer side
+    fn peek(&self) -> Option<T> {
+        if self.producer().load(Ordering::Acquire) == self.cached {
+            return None;
+        }
+        Some(unsafe { *self.descs.add((self.cached & self.mask) as usize) })
+    }
+
+    fn release(&mut self) {
+        self.cached = self.cached.wrapping_add(1);
+        self.consumer().store(self.cached, Ordering::Release);
+    }
+
+    // producer side, the caller ensures that the ring is not full
+    fn submit(&mut self, desc: T) {
+        unsafe { *self.descs.add((self.cached & self.mask) as usize) = desc };
+        self.cached = self.cached.wrapping_add(1);
+        self.producer().store(self.cached, Ordering::Release);
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.map, self.map_len);
+        }
+    }
+}
+
+struct XskSocket {
+    fd: OwnedFd,
+    queue_id: u32,
+    zero_copy: bool,
+    rx: Ring<XdpDesc>,
+    fill: Ring<u64>,
+    // required by the kernel although nothing is transmitted
+    _completion: Ring<u64>,
+    umem: *mut u8,
+    umem_len: usize,
+}
+
+impl XskSocket {
+    fn setsockopt<T>(fd: RawFd, name: c_int, value: &T) -> io::Result<()> {
+        let ret = unsafe {
+            setsockopt(
+                fd,
+                SOL_XDP,
+                name,
+                value as *const T as *const c_void,
+                mem::size_of::<T>() as socklen_t,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn bind(fd: RawFd, if_index: u32, queue_id: u32, flags: u16) -> io::Result<()> {
+        let sa = SockaddrXdp {
+            family: AF_XDP as u16,
+            flags: flags | XDP_USE_NEED_WAKEUP,
+            ifindex: if_index,
+            queue_id,
+            ..Default::default()
+        };
+        let ret = unsafe {
+            libc::bind(
+                fd,
+                &sa as *const SockaddrXdp as *const sockaddr,
+                mem::size_of::<SockaddrXdp>() as socklen_t,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn new(if_index: u32, queue_id: u32, config: &AfXdp) -> io::Result<Self> {
+        let fd = unsafe { socket(AF_XDP, SOCK_RAW, 0) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw_fd = fd.as_raw_fd();
+
+        let umem_len = config.frame_count as usize * XDP_FRAME_SIZE as usize;
+        let umem = unsafe {
+            mmap(
+                ptr::null_mut(),
+                umem_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS | MAP_POPULATE,
+                -1,
+                0,
+            )
+        };
+        if umem == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mut socket = Self::setup(fd, umem as *mut u8, umem_len, config).map_err(|e| {
+            unsafe { munmap(umem, umem_len) };
+            e
+        })?;
+        socket.queue_id = queue_id;
+
+        let flags: &[(u16, bool)] = match config.bind_mode {
+            XdpBindMode::Auto => &[(XDP_ZEROCOPY, true), (XDP_COPY, false)],
+            XdpBindMode::ZeroCopy => &[(XDP_ZEROCOPY, true)],
+            XdpBindMode::Copy => &[(XDP_COPY, false)],
+        };
+        let mut result = Ok(());
+        for &(flag, zero_copy) in flags {
+            result = Self::bind(raw_fd, if_index, queue_id, flag);
+            if result.is_ok() {
+                socket.zero_copy = zero_copy;
+                break;
+            }
+        }
+        result?;
+        Ok(socket)
+    }
+
+    fn setup(fd: OwnedFd, umem: *mut u8, umem_len: usize, config: &AfXdp) -> io::Result<Self> {
+        let raw_fd = fd.as_raw_fd();
+        let reg = XdpUmemReg {
+            addr: umem as u64,
+            len: umem_len as u64,
+            chunk_size: XDP_FRAME_SIZE,
+            headroom: 0,
+            ..Default::default()
+        };
+        Self::setsockopt(raw_fd, XDP_UMEM_REG, &reg)?;
+        // all frames are in the fill ring initially
+        Self::setsockopt(raw_fd, XDP_UMEM_FILL_RING, &config.frame_count)?;
+        Self::setsockopt(raw_fd, XDP_UMEM_COMPLETION_RING, &config.ring_size)?;
+        Self::setsockopt(raw_fd, XDP_RX_RING, &config.ring_size)?;
+
+        let mut offsets = XdpMmapOffsets::default();
+        let mut len = mem::size_of::<XdpMmapOffsets>() as socklen_t;
+        let ret = unsafe {
+            getsockopt(
+                raw_fd,
+                SOL_XDP,
+                XDP_MMAP_OFFSETS,
+                &mut offsets as *mut XdpMmapOffsets as *mut c_void,
+                &mut len,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let (rx, mut fill, completion) = unsafe {
+            (
+                Ring::mmap(raw_fd, &offsets.rx, config.ring_size, XDP_PGOFF_RX_RING)?,
+                Ring::mmap(
+                    raw_fd,
+                    &offsets.fr,
+                    config.frame_count,
+                    XDP_UMEM_PGOFF_FILL_RING,
+                )?,
+                Ring::mmap(
+                    raw_fd,
+                    &offsets.cr,
+                    config.ring_size,
+                    XDP_UMEM_PGOFF_COMPLETION_RING,
+                )?,
+            )
+        };
+        for i in 0..config.frame_count as u64 {
+            fill.submit(i * XDP_FRAME_SIZE as u64);
+        }
+
+        Ok(Self {
+            fd,
+            queue_id: 0,
+            zero_copy: false,
+            rx,
+            fill,
+            _completion: completion,
+            umem,
+            umem_len,
+        })
+    }
+
+    fn peek(&self) -> Option<XdpDesc> {
+        self.rx.peek()
+    }
+
+    // returns the frame of the last received packet to the kernel
+    fn release(&mut self, addr: u64) {
+        self.rx.release();
+        // in aligned mode the address may be offset by headroom
+        self.fill.submit(addr & !(XDP_FRAME_SIZE as u64 - 1));
+        if self.fill.need_wakeup() {
+            unsafe {
+                recvfrom(
+                    self.fd.as_raw_fd(),
+                    ptr::null_mut(),
+                    0,
+                    MSG_DONTWAIT,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                );
+            }
+        }
+    }
+}
+
+impl Drop for XskSocket {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.umem as *mut c_void, self.umem_len);
+        }
+    }
+}
+
+// AF_XDP capture with one socket and UMEM per RX queue of the interface
+pub struct Xsk {
+    iface: String,
+    if_index: u32,
+    sockets: Vec<XskSocket>,
+    // the socket to read next, sockets are read in turn
+    next: usize,
+    // (socket index, frame address) of the packet returned by the last read
+    current: Option<(usize, u64)>,
+    _program: Arc<XdpProgram>,
+}
+
+impl Debug for Xsk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "Xsk {{ iface: {}, if_index: {}, queues: {:?} }}",
+            self.iface,
+            self.if_index,
+            self.sockets
+                .iter()
+                .map(|s| (s.queue_id, s.zero_copy))
+                .collect::<Vec<_>>()
+        ))
+    }
+}
+
+// it's safe because umem and rings point to mmap'ed buffers owned by Xsk
+unsafe impl Send for Xsk {}
+unsafe impl Send for XskSocket {}
+
+impl Xsk {
+    // Captures all queues if rx_queue is None
+    pub fn new(iface: &str, config: &AfXdp, rx_queue: Option<u32>) -> Result<Self> {
+        config.check().map_err(Error::AfXdpError)?;
+        let if_index = link_by_name(iface.to_string())
+            .map_err(|e| Error::AfXdpError(e.to_string()))?
+            .if_index;
+        let rx_queues =
+            rx_queue_count(iface).map_err(|e| Error::AfXdpError(format!("{}: {}", iface, e)))?;
+        let queue_count = if config.queue_count == 0 {
+            rx_queues
+        } else {
+            (config.queue_count as u32).min(rx_queues)
+        };
+
+        let queues = match rx_queue {
+            Some(q) if q < queue_count => q..q + 1,
+            Some(q) => {
+                return Err(Error::AfXdpError(format!(
+                    "queue {} out of range, {} has {} queues",
+                    q, iface, queue_count
+                )))
+            }
+            None => 0..queue_count,
+        };
+
+        let program = XdpProgram::get_or_attach(if_index, queue_count, config.attach_mode)
+            .map_err(|e| Error::AfXdpError(format!("attach xdp program: {}", e)))?;
+        let mut sockets = Vec::with_capacity(queues.len());
+        for queue_id in queues {
+            let socket = XskSocket::new(if_index, queue_id, config).map_err(|e| {
+                Error::AfXdpError(format!("create xsk of queue {}: {}", queue_id, e))
+            })?;
+            program
+                .register(queue_id, socket.fd.as_raw_fd())
+                .map_err(|e| Error::AfXdpError(format!("update xskmap: {}", e)))?;
+            sockets.push(socket);
+        }
+        let xsk = Self {
+            iface: iface.to_string(),
+            if_index,
+            sockets,
+            next: 0,
+            current: None,
+            _program: program,
+        };
+        info!("{:?} created", xsk);
+        Ok(xsk)
+    }
+
+    fn poll(&self) -> bool {
+        let mut fds = self
+            .sockets
+            .iter()
+            .map(|s| pollfd {
+                fd: s.fd.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            })
+            .collect::<Vec<_>>();
+        let n = unsafe {
+            poll(
+                fds.as_mut_ptr(),
+                fds.len() as _,
+                POLL_TIMEOUT.as_millis() as c_int,
+            )
+        };
+        n > 0
+    }
+
+    // The data referenced in the packet points to UMEM. The life cycle of the packet
+    // cannot exceed the next call to the read function.
+    pub unsafe fn read(&mut self) -> Option<Packet> {
+        if let Some((index, addr)) = self.current.take() {
+            self.sockets[index].release(addr);
+        }
+        for polled in [false, true] {
+            if polled && !self.poll() {
+                return None;
+            }
+            let count = self.sockets.len();
+            for i in 0..count {
+                let index = (self.next + i) % count;
+                let socket = &self.sockets[index];
+                let Some(desc) = socket.peek() else {
+                    continue;
+                };
+                self.next = (index + 1) % count;
+                self.current = Some((index, desc.addr));
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                return Some(Packet {
+                    timestamp,
+                    if_index: self.if_index as isize,
+                    data: std::slice::from_raw_parts_mut(
+                        socket.umem.add(desc.addr as usize),
+                        desc.len as usize,
+                    ),
+                    link_type: LinkType::Ethernet,
+                    capture_length: desc.len as isize,
+                    ..Default::default()
+                });
+            }
+        }
+        None
+    }
+
+    pub fn get_counter_handle(&self) -> XskCounter {
+        XskCounter {
+            fds: self.sockets.iter().map(|s| s.fd.as_raw_fd()).collect(),
+        }
+    }
+}
+
+pub struct XskCounter {
+    fds: Vec<RawFd>,
+}
+
+impl stats::RefCountable for XskCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        let mut total = XdpStatistics::default();
+        for fd in self.fds.iter() {
+            let mut s = XdpStatistics::default();
+            let mut len = mem::size_of::<XdpStatistics>() as socklen_t;
+            let ret = unsafe {
+                getsockopt(
+                    *fd,
+                    SOL_XDP,
+                    XDP_STATISTICS,
+                    &mut s as *mut XdpStatistics as *mut c_void,
+                    &mut len,
+                )
+            };
+            if ret != 0 {
+                warn!("{:?}", io::Error::last_os_error());
+                return vec![];
+            }
+            total.rx_dropped += s.rx_dropped;
+            total.rx_invalid_descs += s.rx_invalid_descs;
+            total.rx_ring_full += s.rx_ring_full;
+            total.rx_fill_ring_empty_descs += s.rx_fill_ring_empty_descs;
+        }
+        vec![
+            (
+                "kernel_drops",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(total.rx_dropped),
+            ),
+            (
+                "invalid_descs",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(total.rx_invalid_descs),
+            ),
+            (
+                "rx_ring_full",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(total.rx_ring_full),
+            ),
+            (
+                "fill_ring_empty",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(total.rx_fill_ring_empty_descs),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_program() {
+        let insns = xdp_program(7);
+        // r2 = *(u32 *)(r1 + 16)
+        assert_eq!(insns[0].regs, 0x12);
+        assert_eq!(insns[0].off, 16);
+        // r1 = map fd
+        assert_eq!(insns[1].regs, 0x11);
+        assert_eq!(insns[1].imm, 7);
+        assert_eq!(insns[2], BpfInsn::new(0, 0, 0, 0, 0));
+        assert_eq!(insns[3].regs, 0x03);
+        assert_eq!(mem::size_of::<BpfInsn>(), 8);
+        assert_eq!(mem::size_of::<XdpUmemReg>(), 32);
+        assert_eq!(mem::size_of::<XdpMmapOffsets>(), 128);
+        assert_eq!(mem::size_of::<SockaddrXdp>(), 16);
+    }
+}
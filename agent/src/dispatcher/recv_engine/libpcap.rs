@@ -0,0 +1,412 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, RwLock,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use pcap::{Activated, Capture, Linktype};
+
+use public::debug::QueueDebugger;
+use public::enums::LinkType;
+use public::packet;
+use public::queue::{self, bounded_with_debug, DebugSender, Receiver};
+
+use super::{Error, Result, POLL_TIMEOUT};
+use crate::utils::stats;
+
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+const QUEUE_SIZE: usize = 1 << 14;
+
+#[derive(Default)]
+pub struct LibpcapCounter {
+    // deltas of pcap_stats() of all interfaces
+    kernel_packets: AtomicU64,
+    kernel_drops: AtomicU64,
+    if_drops: AtomicU64,
+
+    read_errors: AtomicU64,
+    filter_errors: AtomicU64,
+}
+
+impl stats::RefCountable for LibpcapCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "kernel_packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.kernel_packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "kernel_drops",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.kernel_drops.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "if_drops",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.if_drops.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "read_errors",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.read_errors.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "filter_errors",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.filter_errors.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct CapturedPacket {
+    timestamp: Duration,
+    if_index: isize,
+    capture_length: isize,
+    data: Vec<u8>,
+}
+
+impl fmt::Debug for CapturedPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapturedPacket")
+            .field("timestamp", &self.timestamp)
+            .field("if_index", &self.if_index)
+            .field("capture_length", &self.capture_length)
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+// (version, filter), version is increased on every update
+type SharedFilter = Arc<RwLock<(u64, String)>>;
+
+struct Reader<T: Activated + ?Sized> {
+    name: String,
+    if_index: isize,
+    capture: Capture<T>,
+    filter: SharedFilter,
+    filter_version: u64,
+    terminated: Arc<AtomicBool>,
+    counter: Arc<LibpcapCounter>,
+    sender: DebugSender<CapturedPacket>,
+    last_stats: (u32, u32, u32),
+}
+
+impl<T: Activated + ?Sized> Reader<T> {
+    fn update_filter(&mut self) {
+        let filter = self.filter.read().unwrap();
+        if filter.0 == self.filter_version {
+            return;
+        }
+        self.filter_version = filter.0;
+        if let Err(e) = self.capture.filter(&filter.1, true) {
+            warn!("set filter \"{}\" on {} failed: {}", filter.1, self.name, e);
+            self.counter.filter_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn update_stats(&mut self) {
+        let stats = match self.capture.stats() {
+            Ok(s) => s,
+            Err(_) => return,
+        };
+        let (received, dropped, if_dropped) = self.last_stats;
+        self.counter.kernel_packets.fetch_add(
+            stats.received.wrapping_sub(received) as u64,
+            Ordering::Relaxed,
+        );
+        self.counter.kernel_drops.fetch_add(
+            stats.dropped.wrapping_sub(dropped) as u64,
+            Ordering::Relaxed,
+        );
+        self.counter.if_drops.fetch_add(
+            stats.if_dropped.wrapping_sub(if_dropped) as u64,
+            Ordering::Relaxed,
+        );
+        self.last_stats = (stats.received, stats.dropped, stats.if_dropped);
+    }
+
+    fn run(mut self) {
+        info!("libpcap starts capturing on {}", self.name);
+        let mut last_stats = Instant::now();
+        while !self.terminated.load(Ordering::Relaxed) {
+            self.update_filter();
+            if last_stats.elapsed() >= STATS_INTERVAL {
+                self.update_stats();
+                last_stats = Instant::now();
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            let packet = self.capture.next();
+            #[cfg(target_os = "windows")]
+            let packet = self.capture.next_packet();
+            let packet = match packet {
+                Ok(p) => p,
+                Err(pcap::Error::TimeoutExpired) => continue,
+                // only returned by savefiles
+                Err(pcap::Error::NoMorePackets) => break,
+                Err(e) => {
+                    warn!("libpcap read from {} failed: {}", self.name, e);
+                    self.counter.read_errors.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(POLL_TIMEOUT);
+                    continue;
+                }
+            };
+            let packet = CapturedPacket {
+                timestamp: Duration::new(
+                    packet.header.ts.tv_sec as u64,
+                    packet.header.ts.tv_usec as u32 * 1000,
+                ),
+                if_index: self.if_index,
+                capture_length: packet.header.len as isize,
+                data: packet.data.to_vec(),
+            };
+            if self.sender.send(packet).is_err() {
+                break;
+            }
+        }
+        info!("libpcap stopped capturing on {}", self.name);
+    }
+}
+
+// Captures packets from interfaces with libpcap, or Npcap/WinPcap on Windows.
+//
+// Each interface is read by its own thread, because libpcap has no portable way of polling
+// multiple handles on Windows. Packets are copied to a queue read by the dispatcher.
+//
+// Packets are not associated with processes here, WFP based process association on Windows
+// is not supported.
+pub struct Libpcap {
+    receiver: Receiver<CapturedPacket>,
+    buffer: Vec<u8>,
+    filter: SharedFilter,
+    terminated: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+    counter: Arc<LibpcapCounter>,
+}
+
+impl Libpcap {
+    // packet_blocks is the kernel buffer size of each interface in MB
+    pub fn new(
+        src_ifaces: Vec<(&str, isize)>,
+        packet_blocks: usize,
+        snap_len: usize,
+        queue_debugger: &QueueDebugger,
+    ) -> Result<Self> {
+        if src_ifaces.is_empty() {
+            return Err(Error::LibpcapError("no interface to capture".into()));
+        }
+        let (sender, receiver, _) =
+            bounded_with_debug(QUEUE_SIZE, "0-libpcap-to-dispatcher", queue_debugger);
+        let filter: SharedFilter = Default::default();
+        let terminated = Arc::new(AtomicBool::new(false));
+        let counter = Arc::new(LibpcapCounter::default());
+
+        let mut readers = vec![];
+        for (name, if_index) in src_ifaces {
+            let capture = Capture::from_device(name)
+                .and_then(|c| {
+                    c.promisc(true)
+                        .snaplen(snap_len as i32)
+                        .buffer_size((packet_blocks << 20) as i32)
+                        .timeout(POLL_TIMEOUT.as_millis() as i32)
+                        .open()
+                })
+                .map_err(|e| Error::LibpcapError(format!("open {} failed: {}", name, e)))?;
+            if capture.get_datalink() != Linktype::ETHERNET {
+                warn!(
+                    "interface {} has unsupported link type {:?}, skipped",
+                    name,
+                    capture.get_datalink()
+                );
+                continue;
+            }
+            readers.push(Reader {
+                name: name.to_owned(),
+                if_index,
+                capture,
+                filter: filter.clone(),
+                filter_version: 0,
+                terminated: terminated.clone(),
+                counter: counter.clone(),
+                sender: sender.clone(),
+                last_stats: (0, 0, 0),
+            });
+        }
+        if readers.is_empty() {
+            return Err(Error::LibpcapError(
+                "no ethernet interface to capture".into(),
+            ));
+        }
+
+        let mut threads = vec![];
+        for reader in readers {
+            let handle = thread::Builder::new()
+                .name(format!("libpcap-{}", reader.if_index))
+                .spawn(move || reader.run())
+                .map_err(|e| Error::LibpcapError(e.to_string()))?;
+            threads.push(handle);
+        }
+
+        Ok(Self {
+            receiver,
+            buffer: vec![],
+            filter,
+            terminated,
+            threads,
+            counter,
+        })
+    }
+
+    pub fn read(&mut self) -> Result<packet::Packet> {
+        let packet = match self.receiver.recv(Some(POLL_TIMEOUT)) {
+            Ok(p) => p,
+            Err(queue::Error::Timeout) => return Err(Error::Timeout),
+            Err(_) => return Err(Error::LibpcapError("libpcap queue terminated".into())),
+        };
+        self.buffer = packet.data;
+        Ok(packet::Packet {
+            timestamp: packet.timestamp,
+            if_index: packet.if_index,
+            capture_length: packet.capture_length,
+            data: &mut self.buffer,
+            link_type: LinkType::Ethernet,
+            raw: None,
+        })
+    }
+
+    // The filter is applied by the capturing threads, errors are logged and counted
+    pub fn set_bpf(&mut self, syntax: &str) -> Result<()> {
+        let mut filter = self.filter.write().unwrap();
+        if filter.1 != syntax {
+            filter.0 += 1;
+            filter.1 = syntax.to_owned();
+        }
+        Ok(())
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<dyn stats::RefCountable> {
+        self.counter.clone()
+    }
+}
+
+impl Drop for Libpcap {
+    fn drop(&mut self) {
+        self.terminated.store(true, Ordering::Relaxed);
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PCAP: &str = "resources/test/flow_generator/client-keep-alive.pcap";
+
+    // reads the file with a reader thread as if it were an interface
+    fn replay(if_index: isize, syntax: &str) -> (Libpcap, JoinHandle<()>) {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) =
+            bounded_with_debug(QUEUE_SIZE, "0-libpcap-to-dispatcher", &queue_debugger);
+        let mut libpcap = Libpcap {
+            receiver,
+            buffer: vec![],
+            filter: Default::default(),
+            terminated: Default::default(),
+            threads: vec![],
+            counter: Default::default(),
+        };
+        libpcap.set_bpf(syntax).unwrap();
+        let reader = Reader {
+            name: PCAP.to_owned(),
+            if_index,
+            capture: Capture::from_file(PCAP).unwrap(),
+            filter: libpcap.filter.clone(),
+            filter_version: 0,
+            terminated: libpcap.terminated.clone(),
+            counter: libpcap.counter.clone(),
+            sender,
+            last_stats: (0, 0, 0),
+        };
+        (libpcap, thread::spawn(move || reader.run()))
+    }
+
+    #[test]
+    fn read_packets() {
+        let (mut libpcap, handle) = replay(3, "");
+        let mut lengths = vec![];
+        let mut last_timestamp = Duration::ZERO;
+        loop {
+            match libpcap.read() {
+                Ok(packet) => {
+                    assert_eq!(packet.if_index, 3);
+                    assert_eq!(packet.link_type, LinkType::Ethernet);
+                    assert_eq!(packet.capture_length as usize, packet.data.len());
+                    assert!(packet.timestamp >= last_timestamp);
+                    last_timestamp = packet.timestamp;
+                    lengths.push(packet.data.len());
+                }
+                Err(Error::Timeout) => continue,
+                Err(_) => break,
+            }
+        }
+        handle.join().unwrap();
+        assert_eq!(lengths, vec![74, 74, 66, 66, 102, 222, 66, 66, 66, 66]);
+        assert_eq!(last_timestamp, Duration::new(1595249, 389493000));
+        assert_eq!(libpcap.counter.read_errors.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn read_filtered_packets() {
+        let (mut libpcap, handle) = replay(1, "greater 100");
+        let mut lengths = vec![];
+        loop {
+            match libpcap.read() {
+                Ok(packet) => lengths.push(packet.data.len()),
+                Err(Error::Timeout) => continue,
+                Err(_) => break,
+            }
+        }
+        handle.join().unwrap();
+        assert_eq!(lengths, vec![102, 222]);
+        assert_eq!(libpcap.counter.filter_errors.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn invalid_filter() {
+        let (mut libpcap, handle) = replay(1, "not a filter");
+        let mut count = 0;
+        loop {
+            match libpcap.read() {
+                Ok(_) => count += 1,
+                Err(Error::Timeout) => continue,
+                Err(_) => break,
+            }
+        }
+        handle.join().unwrap();
+        // packets are captured without filter
+        assert_eq!(count, 10);
+        assert_eq!(libpcap.counter.filter_errors.load(Ordering::Relaxed), 1);
+    }
+}
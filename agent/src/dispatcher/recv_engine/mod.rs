@@ -15,14 +15,26 @@
  */
 
 pub mod af_packet;
+#[cfg(target_os = "linux")]
+pub mod af_xdp;
 pub(crate) mod bpf;
+pub mod libpcap;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod pcap_replay;
 
 use std::ffi::CStr;
 use std::sync::{atomic::AtomicU64, Arc};
 use std::time::Duration;
+#[cfg(target_os = "linux")]
+use std::{fs, io};
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use af_packet::{options::Options, tpacket::Tpacket};
+#[cfg(target_os = "linux")]
+use af_xdp::Xsk;
+pub use libpcap::{Libpcap, LibpcapCounter};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use pcap_replay::PcapReplay;
 pub use public::error::{Error, Result};
 use public::packet;
 
@@ -30,19 +42,31 @@ use crate::utils::stats;
 
 #[cfg(target_os = "linux")]
 pub use special_recv_engine::{Dpdk, VhostUser};
-pub use special_recv_engine::{Libpcap, LibpcapCounter};
 
 pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
 pub const FRAME_SIZE_MAX: usize = 1 << 16; // local and mirror
 pub const FRAME_SIZE_MIN: usize = 1 << 11; // analyzer
 pub const POLL_TIMEOUT: Duration = Duration::from_millis(100);
 
+#[cfg(target_os = "linux")]
+pub fn rx_queue_count(iface: &str) -> io::Result<u32> {
+    let count = fs::read_dir(format!("/sys/class/net/{}/queues", iface))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("rx-"))
+        .count();
+    Ok(count.max(1) as u32)
+}
+
 pub enum RecvEngine {
     #[cfg(any(target_os = "linux", target_os = "android"))]
     AfPacket(Tpacket),
     #[cfg(target_os = "linux")]
+    AfXdp(Xsk),
+    #[cfg(target_os = "linux")]
     Dpdk(Dpdk),
     Libpcap(Option<Libpcap>),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    PcapReplay(PcapReplay),
     #[cfg(target_os = "linux")]
     VhostUser(VhostUser),
 }
@@ -55,7 +79,11 @@ impl RecvEngine {
             #[cfg(any(target_os = "linux", target_os = "android"))]
             Self::AfPacket(_) => Ok(()),
             #[cfg(target_os = "linux")]
+            Self::AfXdp(_) => Ok(()),
+            #[cfg(target_os = "linux")]
             Self::Dpdk(_) => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(_) => Ok(()),
             Self::Libpcap(_) => Ok(()),
             #[cfg(target_os = "linux")]
             Self::VhostUser(_) => Ok(()),
@@ -80,6 +108,11 @@ impl RecvEngine {
                 None => Err(Error::Timeout),
             },
             #[cfg(target_os = "linux")]
+            Self::AfXdp(e) => match e.read() {
+                Some(p) => Ok(p),
+                None => Err(Error::Timeout),
+            },
+            #[cfg(target_os = "linux")]
             Self::Dpdk(d) => match d.read() {
                 Ok(p) => Ok(p),
                 _ => Err(Error::Timeout),
@@ -88,6 +121,11 @@ impl RecvEngine {
                 .as_mut()
                 .ok_or(Error::LibpcapError(Self::LIBPCAP_NONE.to_string()))
                 .and_then(|e| e.read()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(e) => match e.read() {
+                Some(p) => Ok(p),
+                None => Err(Error::Timeout),
+            },
             #[cfg(target_os = "linux")]
             Self::VhostUser(v) => match v.read() {
                 Ok(p) => Ok(p),
@@ -105,8 +143,15 @@ impl RecvEngine {
                 .as_mut()
                 .ok_or(Error::LibpcapError(Self::LIBPCAP_NONE.to_string()))
                 .and_then(|e| e.set_bpf(syntax.to_str().unwrap())),
+            // XDP program redirects all packets of the interface, filtering is not supported
+            #[cfg(target_os = "linux")]
+            Self::AfXdp(_) => Err(Error::AfXdpError(
+                "bpf filter is not supported, all packets of the interface are captured".into(),
+            )),
             #[cfg(target_os = "linux")]
             Self::Dpdk(_) => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(_) => Ok(()),
             #[cfg(target_os = "linux")]
             Self::VhostUser(_) => Ok(()),
         }
@@ -117,11 +162,15 @@ impl RecvEngine {
             #[cfg(any(target_os = "linux", target_os = "android"))]
             Self::AfPacket(e) => Arc::new(e.get_counter_handle()),
             #[cfg(target_os = "linux")]
+            Self::AfXdp(e) => Arc::new(e.get_counter_handle()),
+            #[cfg(target_os = "linux")]
             Self::Dpdk(d) => d.get_counter_handle(),
             Self::Libpcap(w) => match w {
                 Some(w) => w.get_counter_handle(),
                 None => Arc::new(LibpcapCounter::default()),
             },
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(e) => e.get_counter_handle(),
             #[cfg(target_os = "linux")]
             Self::VhostUser(v) => v.get_counter_handle(),
         }
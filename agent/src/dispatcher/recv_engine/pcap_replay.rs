@@ -0,0 +1,322 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicI64, AtomicU64, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use pcap::{Capture, Linktype, Offline};
+use public::enums::LinkType;
+use public::packet::Packet;
+
+use super::POLL_TIMEOUT;
+use crate::config::PcapReplay as PcapReplayConfig;
+use crate::utils::stats;
+
+const DLT_EN10MB: i32 = 1;
+const DLT_IEEE802_11_RADIO: i32 = 127;
+
+#[derive(Default)]
+pub struct PcapReplayCounter {
+    files: AtomicU64,
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    read_errors: AtomicU64,
+}
+
+impl stats::RefCountable for PcapReplayCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "files",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.files.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "bytes",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "read_errors",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.read_errors.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct PendingPacket {
+    timestamp: Duration,
+    // time to return the packet
+    release: Duration,
+    capture_length: isize,
+    len: usize,
+}
+
+// Reads packets from pcap/pcapng files instead of network interfaces.
+//
+// Packet timestamps are shifted to replay time, keeping the intervals between packets
+// (divided by speed) of each file, because FlowMap drops packets out of its time window.
+//
+// With original timestamps, the dispatcher uses the clock of the engine instead, which is
+// set to the timestamp of the last packet returned, so that flows are generated and flushed
+// in the time of the capture.
+pub struct PcapReplay {
+    files: VecDeque<String>,
+    speed: u32,
+    // time difference of the replay clock to the system time in nanoseconds,
+    // only with original timestamps
+    clock: Option<Arc<AtomicI64>>,
+    capture: Option<Capture<Offline>>,
+    link_type: LinkType,
+    // (timestamp of the first packet in file, replay time of the first packet)
+    base: Option<(Duration, Duration)>,
+    last_timestamp: Duration,
+    buffer: Vec<u8>,
+    pending: Option<PendingPacket>,
+    finished: bool,
+    counter: Arc<PcapReplayCounter>,
+}
+
+impl PcapReplay {
+    pub fn new(config: &PcapReplayConfig) -> Self {
+        let mut replay = Self {
+            files: config.files.iter().cloned().collect(),
+            speed: config.speed,
+            clock: None,
+            capture: None,
+            link_type: LinkType::Ethernet,
+            base: None,
+            last_timestamp: Duration::ZERO,
+            buffer: vec![],
+            pending: None,
+            finished: false,
+            counter: Default::default(),
+        };
+        if config.original_timestamps {
+            // the clock starts from the first packet, because FlowMap takes its start time
+            // from the clock when created and drops earlier packets
+            replay.clock = Some(Default::default());
+            replay.pending = replay.next_packet();
+            if let Some(p) = replay.pending.as_ref() {
+                replay.set_clock(p.timestamp);
+            }
+        }
+        replay
+    }
+
+    fn now() -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+    }
+
+    fn set_clock(&self, timestamp: Duration) {
+        if let Some(clock) = self.clock.as_ref() {
+            let diff = timestamp.as_nanos() as i64 - Self::now().as_nanos() as i64;
+            clock.store(diff, Ordering::Relaxed);
+        }
+    }
+
+    // Clock of the dispatcher in the format of ntp_diff, None if packet timestamps are
+    // shifted to the replay time
+    pub fn clock(&self) -> Option<Arc<AtomicI64>> {
+        self.clock.clone()
+    }
+
+    fn open_next(&mut self) -> bool {
+        while let Some(file) = self.files.pop_front() {
+            let capture = match Capture::from_file(&file) {
+                Ok(c) => c,
+                Err(e) => {
+                    warn!("open pcap file {} failed: {}", file, e);
+                    self.counter.read_errors.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            };
+            self.link_type = match capture.get_datalink() {
+                Linktype(DLT_EN10MB) => LinkType::Ethernet,
+                Linktype(DLT_IEEE802_11_RADIO) => LinkType::Ieee80211Radio,
+                Linktype(t) => {
+                    warn!(
+                        "pcap file {} has unsupported link type {}, skipped",
+                        file, t
+                    );
+                    self.counter.read_errors.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            };
+            info!("pcap replay starts reading {}", file);
+            self.counter.files.fetch_add(1, Ordering::Relaxed);
+            self.capture = Some(capture);
+            self.base = None;
+            return true;
+        }
+        false
+    }
+
+    fn next_packet(&mut self) -> Option<PendingPacket> {
+        if self.capture.is_none() && !self.open_next() {
+            if !self.finished {
+                info!("pcap replay finished");
+                self.finished = true;
+            }
+            thread::sleep(POLL_TIMEOUT);
+            return None;
+        }
+        let capture = self.capture.as_mut().unwrap();
+        let packet = match capture.next() {
+            Ok(p) => p,
+            Err(pcap::Error::NoMorePackets) => {
+                self.capture = None;
+                return None;
+            }
+            Err(e) => {
+                warn!("read pcap file failed: {}", e);
+                self.counter.read_errors.fetch_add(1, Ordering::Relaxed);
+                self.capture = None;
+                return None;
+            }
+        };
+        let ts = Duration::new(
+            packet.header.ts.tv_sec as u64,
+            packet.header.ts.tv_usec as u32 * 1000,
+        );
+        let release = if self.speed == 0 {
+            Self::now()
+        } else {
+            let (first, start) = *self
+                .base
+                .get_or_insert_with(|| (ts, Self::now().max(self.last_timestamp)));
+            start + ts.saturating_sub(first) / self.speed
+        };
+        self.buffer.clear();
+        self.buffer.extend_from_slice(packet.data);
+        Some(PendingPacket {
+            timestamp: if self.clock.is_some() { ts } else { release },
+            release,
+            capture_length: packet.header.len as isize,
+            len: packet.data.len(),
+        })
+    }
+
+    pub fn read(&mut self) -> Option<Packet> {
+        if self.pending.is_none() {
+            self.pending = Some(self.next_packet()?);
+        }
+        let pending = self.pending.as_ref().unwrap();
+        let now = Self::now();
+        if pending.release > now {
+            let wait = pending.release - now;
+            if wait > POLL_TIMEOUT {
+                // return to the dispatcher so that flows can be flushed while waiting
+                thread::sleep(POLL_TIMEOUT);
+                return None;
+            }
+            thread::sleep(wait);
+        }
+        let pending = self.pending.take().unwrap();
+        self.last_timestamp = pending.release;
+        self.set_clock(pending.timestamp);
+        self.counter.packets.fetch_add(1, Ordering::Relaxed);
+        self.counter
+            .bytes
+            .fetch_add(pending.len as u64, Ordering::Relaxed);
+        Some(Packet {
+            timestamp: pending.timestamp,
+            capture_length: pending.capture_length,
+            data: &mut self.buffer[..pending.len],
+            link_type: self.link_type,
+            ..Default::default()
+        })
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<PcapReplayCounter> {
+        self.counter.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_files() {
+        let mut replay = PcapReplay::new(&PcapReplayConfig {
+            files: vec![
+                "resources/test/flow_generator/non-existent.pcap".to_string(),
+                "resources/test/flow_generator/client-keep-alive.pcap".to_string(),
+            ],
+            speed: 100,
+            original_timestamps: false,
+        });
+        let mut timestamps = vec![];
+        while !replay.finished {
+            if let Some(packet) = replay.read() {
+                assert_eq!(packet.link_type, LinkType::Ethernet);
+                timestamps.push(packet.timestamp);
+            }
+        }
+        assert_eq!(timestamps.len(), 10);
+        assert!(timestamps.windows(2).all(|w| w[0] <= w[1]));
+        // about 1.28 seconds in the original capture
+        assert!(timestamps[9] - timestamps[0] < Duration::from_millis(100));
+        assert_eq!(replay.counter.files.load(Ordering::Relaxed), 1);
+        assert_eq!(replay.counter.read_errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn replay_original_timestamps() {
+        let file = "resources/test/flow_generator/client-keep-alive.pcap";
+        let mut capture = Capture::from_file(file).unwrap();
+        let mut expected = vec![];
+        while let Ok(p) = capture.next() {
+            expected.push(Duration::new(
+                p.header.ts.tv_sec as u64,
+                p.header.ts.tv_usec as u32 * 1000,
+            ));
+        }
+
+        let mut replay = PcapReplay::new(&PcapReplayConfig {
+            files: vec![file.to_string()],
+            speed: 0,
+            original_timestamps: true,
+        });
+        let clock = replay.clock().unwrap();
+        // the clock starts from the first packet before any packet is read
+        let diff = expected[0].as_nanos() as i64 - PcapReplay::now().as_nanos() as i64;
+        assert!((clock.load(Ordering::Relaxed) - diff).abs() < 1_000_000_000);
+
+        let mut timestamps = vec![];
+        while !replay.finished {
+            if let Some(packet) = replay.read() {
+                timestamps.push(packet.timestamp);
+            }
+        }
+        assert_eq!(timestamps, expected);
+        let diff = expected[9].as_nanos() as i64 - PcapReplay::now().as_nanos() as i64;
+        assert!((clock.load(Ordering::Relaxed) - diff).abs() < 1_000_000_000);
+    }
+}
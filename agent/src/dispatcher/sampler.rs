@@ -0,0 +1,155 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use public::enums::EthernetType;
+
+use crate::common::meta_packet::MetaPacket;
+use crate::config::{PacketSampling, PacketSamplingMode};
+
+// Decides which packets are processed by a dispatcher thread.
+//
+// Flow hash sampling hashes both endpoints in a fixed order so that packets of both
+// directions are sampled together, and flows on different dispatchers get the same result.
+#[derive(Clone, Debug)]
+pub struct PacketSampler {
+    mode: PacketSamplingMode,
+    rate: u32,
+    count: u32,
+}
+
+impl PacketSampler {
+    pub fn new(config: &PacketSampling) -> Self {
+        let mode = if config.rate <= 1 {
+            PacketSamplingMode::Disabled
+        } else {
+            config.mode
+        };
+        Self {
+            mode,
+            rate: config.rate.max(1),
+            count: 0,
+        }
+    }
+
+    fn flow_hash(meta_packet: &MetaPacket) -> u64 {
+        let key = &meta_packet.lookup_key;
+        let mut hasher = DefaultHasher::new();
+        u16::from(key.eth_type).hash(&mut hasher);
+        if key.eth_type == EthernetType::IPV4 || key.eth_type == EthernetType::IPV6 {
+            u8::from(key.proto).hash(&mut hasher);
+            if (key.src_ip, key.src_port) <= (key.dst_ip, key.dst_port) {
+                (key.src_ip, key.src_port, key.dst_ip, key.dst_port).hash(&mut hasher);
+            } else {
+                (key.dst_ip, key.dst_port, key.src_ip, key.src_port).hash(&mut hasher);
+            }
+        } else if key.src_mac <= key.dst_mac {
+            (key.src_mac, key.dst_mac).hash(&mut hasher);
+        } else {
+            (key.dst_mac, key.src_mac).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    // Returns false if the packet should be dropped, otherwise records the sampling rate
+    // in the packet
+    pub fn sample(&mut self, meta_packet: &mut MetaPacket) -> bool {
+        let sampled = match self.mode {
+            PacketSamplingMode::Disabled => return true,
+            PacketSamplingMode::Count => {
+                let sampled = self.count == 0;
+                self.count += 1;
+                if self.count >= self.rate {
+                    self.count = 0;
+                }
+                sampled
+            }
+            PacketSamplingMode::FlowHash => Self::flow_hash(meta_packet) % self.rate as u64 == 0,
+        };
+        if sampled {
+            meta_packet.sampling_rate = self.rate;
+        }
+        sampled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+
+    use crate::common::lookup_key::LookupKey;
+
+    fn packet(src: u8, src_port: u16, dst: u8, dst_port: u16) -> MetaPacket<'static> {
+        MetaPacket {
+            lookup_key: LookupKey {
+                src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, src)),
+                dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, dst)),
+                src_port,
+                dst_port,
+                eth_type: EthernetType::IPV4,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn count_sampling() {
+        let mut sampler = PacketSampler::new(&PacketSampling {
+            mode: PacketSamplingMode::Count,
+            rate: 4,
+        });
+        let mut sampled = 0;
+        for _ in 0..100 {
+            let mut p = packet(1, 1234, 2, 80);
+            if sampler.sample(&mut p) {
+                assert_eq!(p.sampling_rate, 4);
+                sampled += 1;
+            }
+        }
+        assert_eq!(sampled, 25);
+    }
+
+    #[test]
+    fn flow_hash_sampling() {
+        let mut sampler = PacketSampler::new(&PacketSampling {
+            mode: PacketSamplingMode::FlowHash,
+            rate: 8,
+        });
+        let mut sampled = 0;
+        for port in 1024..2048 {
+            let forward = sampler.sample(&mut packet(1, port, 2, 80));
+            let backward = sampler.sample(&mut packet(2, 80, 1, port));
+            assert_eq!(forward, backward);
+            if forward {
+                sampled += 1;
+            }
+        }
+        assert!(sampled > 64 && sampled < 192);
+
+        let mut sampler = PacketSampler::new(&PacketSampling {
+            mode: PacketSamplingMode::FlowHash,
+            rate: 1,
+        });
+        let mut p = packet(1, 1234, 2, 80);
+        assert!(sampler.sample(&mut p));
+        assert_eq!(p.sampling_rate, 0);
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * This code runs using bpf in the Linux kernel.
+ * Copyright 2022- The Yunshan Networks Authors.
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+ *
+ * SPDX-License-Identifier: GPL-2.0
+ */
+
+/*
+ * Latency and result of the libc resolver calls. The calls are measured from
+ * the uprobes, so lookups answered by nscd or systemd-resolved over UNIX
+ * domain sockets are covered as well as the ones sending DNS queries, which
+ * are counted in the calls by dns_resolver_count_query().
+ */
+
+// Return values of getaddrinfo()
+#define EAI_NONAME	-2
+#define EAI_AGAIN	-3
+#define EAI_NODATA	-5
+
+static __inline void dns_resolver_enter(enum dns_resolver_api api)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct dns_resolver_ctx resolver_ctx = {
+		.enter_ts = bpf_ktime_get_ns(),
+		.api = api,
+	};
+	dns_resolver_ctx_map__update(&id, &resolver_ctx);
+}
+
+static __inline void dns_resolver_exit(int ret, bool failed)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct dns_resolver_ctx *resolver_ctx =
+	    dns_resolver_ctx_map__lookup(&id);
+	if (resolver_ctx == NULL)
+		return;
+
+	__u64 latency =
+	    (bpf_ktime_get_ns() - resolver_ctx->enter_ts) / NS_PER_US;
+	struct dns_resolver_key key = {
+		.tgid = (__u32) (id >> 32),
+		.api = resolver_ctx->api,
+	};
+	struct dns_resolver_stats *stats = dns_resolver_stats_map__lookup(&key);
+	if (stats == NULL) {
+		struct dns_resolver_stats empty = { 0 };
+		bpf_get_current_comm(empty.comm, sizeof(empty.comm));
+		dns_resolver_stats_map__update(&key, &empty);
+		stats = dns_resolver_stats_map__lookup(&key);
+		if (stats == NULL)
+			goto out;
+	}
+
+	__sync_fetch_and_add(&stats->calls, 1);
+	__sync_fetch_and_add(&stats->latency_sum, latency);
+	if (latency > stats->latency_max)
+		stats->latency_max = latency;
+	if (resolver_ctx->queries == 0) {
+		__sync_fetch_and_add(&stats->no_query_calls, 1);
+	} else {
+		__sync_fetch_and_add(&stats->queries, resolver_ctx->queries);
+		__sync_fetch_and_add(&stats->loopback_queries,
+				     resolver_ctx->loopback_queries);
+	}
+
+	if (!failed)
+		goto out;
+	if (ret == EAI_AGAIN)
+		__sync_fetch_and_add(&stats->timeouts, 1);
+	else if (ret == EAI_NONAME || ret == EAI_NODATA || ret == 0)
+		__sync_fetch_and_add(&stats->client_errors, 1);
+	else
+		__sync_fetch_and_add(&stats->server_errors, 1);
+
+out:
+	dns_resolver_ctx_map__delete(&id);
+}
+
+// int getaddrinfo(const char *node, const char *service,
+//                 const struct addrinfo *hints, struct addrinfo **res);
+UPROG(dns_getaddrinfo_enter) (struct pt_regs *ctx)
+{
+	dns_resolver_enter(DNS_RESOLVER_GETADDRINFO);
+	return 0;
+}
+
+// int getaddrinfo(const char *node, const char *service,
+//                 const struct addrinfo *hints, struct addrinfo **res);
+UPROG(dns_getaddrinfo_exit) (struct pt_regs *ctx)
+{
+	int ret = (int)PT_REGS_RC(ctx);
+	dns_resolver_exit(ret, ret != 0);
+	return 0;
+}
+
+// struct hostent *gethostbyname(const char *name);
+UPROG(dns_gethostbyname_enter) (struct pt_regs *ctx)
+{
+	dns_resolver_enter(DNS_RESOLVER_GETHOSTBYNAME);
+	return 0;
+}
+
+// struct hostent *gethostbyname2(const char *name, int af);
+UPROG(dns_gethostbyname2_enter) (struct pt_regs *ctx)
+{
+	dns_resolver_enter(DNS_RESOLVER_GETHOSTBYNAME2);
+	return 0;
+}
+
+// The h_errno of a failed gethostbyname() is not available, a NULL result is
+// counted as a client error.
+UPROG(dns_gethostbyname_exit) (struct pt_regs *ctx)
+{
+	dns_resolver_exit(0, PT_REGS_RC(ctx) == 0);
+	return 0;
+}
@@ -211,8 +211,7 @@ static __inline void report_http2_header(struct pt_regs *ctx)
 	    (sizeof(stack->send_buffer.data) - 1);
 
 	if (send_size < SEND_SIZE_MAX && send_size > 0) {
-		bpf_perf_event_output(ctx, &NAME(socket_data),
-				      BPF_F_CURRENT_CPU, stack, 1 + send_size);
+		socket_data_output(ctx, stack, 1 + send_size);
 	}
 	return;
 }
@@ -393,6 +392,46 @@ static __inline void http2_fill_common_socket(struct http2_header_data *data,
 
 }
 
+// Record the goroutine sending the request of the client stream
+static __inline void save_http2_stream_goroutine(int fd, __u32 stream,
+						 struct __socket_data
+						 *send_buffer)
+{
+	if (!send_buffer->pid || !send_buffer->coroutine_id)
+		return;
+
+	struct http2_stream_key key = {
+		.tgid = send_buffer->tgid,
+		.fd = fd,
+		.stream = stream,
+	};
+	__u64 goid = send_buffer->coroutine_id;
+	bpf_map_update_elem(&http2_stream_goid_map, &key, &goid, BPF_ANY);
+}
+
+// The response is read by the read loop goroutine of the connection,
+// attribute it to the goroutine sending the request of the stream.
+static __inline void load_http2_stream_goroutine(int fd, __u32 stream,
+						 struct __socket_data
+						 *send_buffer)
+{
+	if (!send_buffer->pid)
+		return;
+
+	struct http2_stream_key key = {
+		.tgid = send_buffer->tgid,
+		.fd = fd,
+		.stream = stream,
+	};
+	__u64 *goid = bpf_map_lookup_elem(&http2_stream_goid_map, &key);
+	if (!goid)
+		return;
+
+	send_buffer->coroutine_id = *goid;
+	// The stream ends with the response headers
+	bpf_map_delete_elem(&http2_stream_goid_map, &key);
+}
+
 // 填充 buffer->send_buffer.data
 static __inline void
 http2_fill_buffer_and_send(struct http2_header_data *data,
@@ -472,6 +511,12 @@ static __inline int submit_http2_headers(struct http2_headers_data *headers,
 	struct __socket_data *send_buffer = &(stack->send_buffer);
 
 	http2_fill_common_socket(&data, send_buffer, offset);
+	if (!headers->read && headers->message_type == MSG_REQUEST)
+		save_http2_stream_goroutine(headers->fd, headers->stream,
+					    send_buffer);
+	else if (headers->read && headers->message_type == MSG_RESPONSE)
+		load_http2_stream_goroutine(headers->fd, headers->stream,
+					    send_buffer);
 
 	int idx;
 	struct go_http2_header_field *tmp;
@@ -542,6 +587,31 @@ static __inline bool skip_http2_uprobe(struct ebpf_proc_info *info)
 	return true;
 }
 
+// func (t *Transport) roundTrip(req *Request) (*Response, error)
+// func (t *Transport) RoundTripOpt(req *http.Request, opt RoundTripOpt) (*http.Response, error)
+//
+// The HTTP2 request may be written by a goroutine created for it, e.g.
+// http2clientStream.doRequest() since Go 1.18. Mark the calling goroutine to
+// represent the request unless one of its ancestors already does.
+UPROG(go_http_Transport_roundTrip) (struct pt_regs *ctx)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	pid_t pid = id >> 32;
+
+	struct ebpf_proc_info *info = bpf_map_lookup_elem(&proc_info_map, &pid);
+	if (skip_http2_uprobe(info)) {
+		return 0;
+	}
+
+	__u32 k0 = 0;
+	struct tracer_ctx_s *tracer_ctx = tracer_ctx_map__lookup(&k0);
+	if (tracer_ctx == NULL || tracer_ctx->go_tracing_timeout == 0)
+		return 0;
+
+	get_trace_key(tracer_ctx->go_tracing_timeout, true);
+	return 0;
+}
+
 // func (cc *http2ClientConn) writeHeader(name, value string)
 UPROG(go_http2ClientConn_writeHeader) (struct pt_regs *ctx)
 {
@@ -641,6 +711,7 @@ UPROG(go_http2ClientConn_writeHeaders) (struct pt_regs *ctx)
 	struct __socket_data *send_buffer = &(stack->send_buffer);
 
 	http2_fill_common_socket(&data, send_buffer, offset);
+	save_http2_stream_goroutine(data.fd, data.stream, send_buffer);
 	http2_fill_buffer_and_send(&data, buffer, send_buffer);
 
 	return 0;
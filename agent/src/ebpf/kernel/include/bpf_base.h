@@ -83,6 +83,12 @@ static long
 							   void *data,
 							   __u64 size) =
     (void *)25;
+static long
+    __attribute__ ((__unused__)) (*bpf_ringbuf_output) (void *ringbuf,
+							void *data,
+							__u64 size,
+							__u64 flags) =
+    (void *)130;
 static long
     __attribute__ ((__unused__)) (*bpf_probe_read_str) (void *dst, __u32 size,
 							const void *unsafe_ptr)
@@ -292,6 +298,9 @@ _Pragma("GCC error \"PT_GO_REGS_PARM\"");
 
 #define TP_SYSCALL_PROG(F) SEC("tracepoint/syscalls/sys_"__stringify(F)) int df_T_##F
 #define TP_SCHED_PROG(F) SEC("tracepoint/sched/sched_"__stringify(F)) int df_T_##F
+#define TP_TCP_PROG(F) SEC("tracepoint/tcp/tcp_"__stringify(F)) int df_T_tcp_##F
+#define TP_SOCK_PROG(F) SEC("tracepoint/sock/"__stringify(F)) int df_T_##F
+#define TP_SKB_PROG(F) SEC("tracepoint/skb/"__stringify(F)) int df_T_##F
 #define PROGTP(F) SEC("prog/tp/"__stringify(F)) int df_TP_##F
 #define PROGKP(F) SEC("prog/kp/"__stringify(F)) int df_KP_##F
 #define PROGPE(F) SEC("prog/pe/"__stringify(F)) int df_PE_##F
@@ -431,6 +440,19 @@ struct bpf_map_def SEC("maps") __ ## name = \
     __BPF_MAP_DEF(key_type, value_type, max_entries, feat), \
 };
 
+/*
+ * The size is in bytes, a power of 2 multiple of the page size (Linux 5.8+).
+ */
+#define MAP_RINGBUF(name, size, feat) \
+struct bpf_map_def SEC("maps") __ ## name = \
+{   \
+    .type = BPF_MAP_TYPE_RINGBUF, \
+    .key_size = 0, \
+    .value_size = 0, \
+    .max_entries = (size), \
+    .feat_flags = (feat), \
+};
+
 #define MAP_PROG_ARRAY(name, key_type, value_type, max_entries, feat) \
 struct bpf_map_def SEC("maps") __ ## name = \
 {   \
@@ -466,6 +488,32 @@ static_always_inline __attribute__((unused)) int name ## __delete(key_type *key)
     return bpf_map_delete_elem(& __##name, (const void *)key); \
 }
 
+/*
+ * The least recently used entries are evicted when the map is full, for the
+ * maps whose entries can not be deleted explicitly.
+ */
+#define MAP_LRU_HASH(name, key_type, value_type, max_entries, feat) \
+struct bpf_map_def SEC("maps") __##name = \
+{   \
+    .type = BPF_MAP_TYPE_LRU_HASH, \
+    __BPF_MAP_DEF(key_type, value_type, max_entries, feat), \
+}; \
+static_always_inline __attribute__((unused)) value_type * name ## __lookup(key_type *key) \
+{ \
+    return (value_type *) bpf_map_lookup_elem(& __##name, (const void *)key); \
+} \
+static_always_inline __attribute__((unused)) int name ## __update(key_type *key, value_type *value) \
+{ \
+    return bpf_map_update_elem(& __##name, (const void *)key, (const void *)value, BPF_ANY); \
+} \
+static_always_inline __attribute__((unused)) int name ## __delete(key_type *key) \
+{ \
+    return bpf_map_delete_elem(& __##name, (const void *)key); \
+}
+
+#define BPF_LRU_HASH(_name, _key_type, _leaf_type, _size, _feat) \
+  MAP_LRU_HASH(_name, _key_type, _leaf_type, _size, _feat)
+
 #define BPF_HASH3(_name, _key_type, _leaf_type) \
   MAP_HASH(_name, _key_type, _leaf_type, MAP_MAX_ENTRIES_DEF, 0)
 
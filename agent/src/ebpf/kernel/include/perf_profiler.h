@@ -37,11 +37,41 @@ typedef enum {
 	ERROR_IDX,		/* Count the number of failed push notifications. */
 	ENABLE_IDX,		/* Enable profiler sampling flag.
 				   0: disable sampling; 1: enable sampling. */
-	MINBLOCK_TIME_IDX,	/* The minimum blocking time, applied in the profiler extension.*/
+	MINBLOCK_TIME_IDX,	/* The minimum blocking time (microseconds) of off-CPU profiler. */
 	RT_KERN,                /* Indicates whether it is a real-time kernel.*/
+	SAMPLE_INTERVAL_IDX,	/* Memory profiler samples one of every N allocations. */
+	RATE_LIMIT_IDX,		/* Maximum memory profiler samples per second on each CPU,
+				   0 means no limit. */
 	PROFILER_CNT
 } profiler_idx;
 
+/*
+ * The off-CPU profiler records the time a matched thread is switched out,
+ * keyed by thread ID, and blocking events longer than this are not
+ * collected.
+ */
+#define OFFCPU_START_MAP_ENTRIES 65536
+#define OFFCPU_PIDS_MAP_ENTRIES 4096
+#define OFFCPU_MAX_BLOCK_NS (3600ULL * 1000000000ULL)
+
+/*
+ * The memory profiler keeps malloc() arguments between the entry and the
+ * return of the call, and the sampled addresses until they are freed.
+ */
+#define MEMORY_ALLOC_ARGS_MAP_ENTRIES 65536
+#define MEMORY_SAMPLED_ADDRS_MAP_ENTRIES 524288
+
+struct memory_alloc_args {
+	__u64 size;
+	__u64 func_addr;
+};
+
+struct memory_rate_limit {
+	__u64 alloc_count;
+	__u64 window_start;
+	__u64 window_samples;
+};
+
 #define JAVA_SYMBOL_MAX_LENGTH 128
 #define MAP_MEMORY_JAVA_SYMBOL_MAP_NAME "__memory_java_symbol_map"
 
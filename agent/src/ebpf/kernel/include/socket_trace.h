@@ -108,6 +108,27 @@ struct member_fields_offset {
 	__u32 struct_sock_sport_offset;	// offsetof(struct sock_common, skc_num)
 	__u32 struct_sock_skc_state_offset;	// offsetof(struct sock_common, skc_state)
 	__u32 struct_sock_common_ipv6only_offset;	// offsetof(struct sock_common, skc_flags)
+	/*
+	 * UNIX domain sockets, 0 if not found in BTF (e.g. built as a module),
+	 * the socket paths are not recorded in this case.
+	 */
+	__u32 struct_unix_sock_addr_offset;	// offsetof(struct unix_sock, addr)
+	__u32 struct_unix_sock_peer_offset;	// offsetof(struct unix_sock, peer)
+	__u32 struct_unix_address_name_offset;	// offsetof(struct unix_address, name)
+	/*
+	 * Packet headers of the dropped packets, 0 if not found in BTF, the
+	 * packet addresses are not recorded in this case.
+	 */
+	__u32 struct_sk_buff_len_offset;	// offsetof(struct sk_buff, len)
+	__u32 struct_sk_buff_head_offset;	// offsetof(struct sk_buff, head)
+	__u32 struct_sk_buff_network_header_offset;	// offsetof(struct sk_buff, network_header)
+	__u32 struct_sk_buff_transport_header_offset;	// offsetof(struct sk_buff, transport_header)
+	/*
+	 * File system of the files read and written, 0 if not found in BTF,
+	 * the file IO is not counted in this case.
+	 */
+	__u32 struct_inode_i_sb_offset;	// offsetof(struct inode, i_sb)
+	__u32 struct_super_block_s_dev_offset;	// offsetof(struct super_block, s_dev)
 
 };
 
@@ -297,6 +318,15 @@ struct data_args_t {
 	__u8 addr[16];
 } __attribute__ ((packed));
 
+/*
+ * Arguments of an io_uring IORING_OP_SEND/IORING_OP_RECV request, saved
+ * when the submission queue entry is prepared.
+ */
+struct io_uring_req_args {
+	__u32 fd;
+	const char *buf;
+};
+
 struct syscall_comm_enter_ctx {
 #ifdef LINUX_VER_RT
 	__u64 __pad_0;		/*     0     8 */
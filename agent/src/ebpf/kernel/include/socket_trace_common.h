@@ -39,6 +39,126 @@ struct __tuple_t {
 	__u16 num;
 };
 
+/*
+ * The process owning a connection, recorded when the connection is accepted
+ * or connected. Key is struct __tuple_t of the socket.
+ */
+struct socket_owner {
+	__u32 tgid;
+	__u32 role;		// enum endpoint_role
+	__u64 cgroup_id;	// 0 if not supported by the kernel
+	__u64 timestamp;	// Time of accept()/connect(), in nanoseconds since boot
+	__u8 comm[TASK_COMM_LEN];
+};
+
+#ifndef UNIX_PATH_MAX
+#define UNIX_PATH_MAX 108
+#endif
+
+/*
+ * Path of a UNIX domain socket, the bound address of the socket or of its
+ * peer. Abstract socket names start with '@'. Key is the socket ID.
+ */
+struct unix_socket_path {
+	char path[UNIX_PATH_MAX];
+};
+
+/*
+ * TCP health of a connection from the tcp tracepoints, cumulative since the
+ * connection is established. Key is struct __tuple_t of the socket.
+ */
+struct tcp_health {
+	__u32 retrans;		// Retransmitted segments
+	__u32 drops;		// Segments dropped by the TCP stack
+	__u32 srtt_us;		// Smoothed RTT in microseconds, 0 if unknown
+	__u32 state;		// TCP state, e.g. TCP_ESTABLISHED
+	__u64 timestamp;	// Last update, in nanoseconds since boot
+};
+
+/*
+ * Packets dropped by kfree_skb() (Linux 5.17+), aggregated by the drop reason
+ * and the packet addresses. The addresses are 0 if the headers are not parsed.
+ */
+struct skb_drop_key {
+	__u32 reason;		// enum skb_drop_reason of the kernel
+	__u8 addr_len;		// 4 for IPv4, 16 for IPv6, 0 if unknown
+	__u8 protocol;		// IPPROTO_TCP, IPPROTO_UDP, ...
+	__u16 dport;		// Destination port of TCP/UDP
+	__u8 saddr[16];
+	__u8 daddr[16];
+};
+
+struct skb_drop_value {
+	__u64 packets;
+	__u64 bytes;
+};
+
+enum dns_resolver_api {
+	DNS_RESOLVER_GETADDRINFO,
+	DNS_RESOLVER_GETHOSTBYNAME,
+	DNS_RESOLVER_GETHOSTBYNAME2,
+	DNS_RESOLVER_API_MAX,
+};
+
+/*
+ * An ongoing resolver call of a thread. The DNS queries sent by the thread
+ * before the call returns are counted in it. Key is pid_tgid.
+ */
+struct dns_resolver_ctx {
+	__u64 enter_ts;		// Nanoseconds since boot
+	__u32 api;		// enum dns_resolver_api
+	__u16 queries;		// DNS queries sent in the call
+	__u16 loopback_queries;	// Queries sent to a loopback stub resolver
+};
+
+/*
+ * Resolver calls of a process aggregated by the resolver API, collected and
+ * cleared by the agent periodically.
+ */
+struct dns_resolver_key {
+	__u32 tgid;
+	__u32 api;		// enum dns_resolver_api
+};
+
+struct dns_resolver_stats {
+	__u64 calls;
+	__u64 client_errors;	// EAI_NONAME, EAI_NODATA or no result
+	__u64 server_errors;	// EAI_FAIL and other failures
+	__u64 timeouts;		// EAI_AGAIN
+	__u64 latency_sum;	// Microseconds
+	__u64 latency_max;	// Microseconds
+	__u64 queries;		// DNS queries sent by the calls
+	__u64 loopback_queries;	// Queries sent to a loopback stub resolver
+	__u64 no_query_calls;	// Calls answered without sending DNS queries
+	__u8 comm[TASK_COMM_LEN];
+};
+
+/*
+ * An ongoing vfs_read()/vfs_write() of a thread. Key is pid_tgid.
+ */
+struct file_io_ctx {
+	__u64 enter_ts;		// Nanoseconds since boot
+	void *file;		// struct file *
+};
+
+/*
+ * Regular file reads and writes of a process aggregated by the device of the
+ * file system, collected and cleared by the agent periodically.
+ */
+struct file_io_key {
+	__u32 tgid;
+	__u32 dev;		// s_dev of the super block, MKDEV(major, minor) of the kernel
+	__u32 operation;	// 0: write 1: read, as the IO events
+};
+
+struct file_io_stats {
+	__u64 count;
+	__u64 bytes;
+	__u64 latency_sum;	// Microseconds
+	__u64 latency_max;	// Microseconds
+	__u8 comm[TASK_COMM_LEN];
+};
+
 struct __socket_data {
 	/* 进程/线程信息 */
 	__u32 pid;  // 表示线程号 如果'pid == tgid'表示一个进程, 否则是线程
@@ -65,7 +185,8 @@ struct __socket_data {
 	/* 追踪数据信息 */
 	__u64 timestamp;     // 数据捕获时间戳
 	__u8 direction: 1;  // bits[0]: 方向，值为T_EGRESS(0), T_INGRESS(1)
-	__u8 msg_type:  6;  // bits[1-6]: 信息类型，值为MSG_UNKNOWN(0), MSG_REQUEST(1), MSG_RESPONSE(2)
+	__u8 msg_type:  5;  // bits[1-5]: 信息类型，值为MSG_UNKNOWN(0), MSG_REQUEST(1), MSG_RESPONSE(2)
+	__u8 is_unix: 1;    // bits[6]: UNIX domain socket, the tuple is 127.0.0.1:0 on both sides
 	__u8 is_tls: 1;
 
 	__u64 syscall_len;   // 本次系统调用读、写数据的总长度
@@ -98,6 +219,7 @@ struct trace_stats {
 	__u64 period_event_max_delay; /**< The maximum latency for periodic data push. */
 	__u64 period_event_total_time; /**< The total elapsed time for periodic event. */
 	__u64 period_event_count; /**< The number of occurrences of periodic events. */
+	__u64 ringbuf_lost; /**< Socket data dropped as the ring buffer is full, never reset. */
 };
 
 struct socket_info_s {
@@ -186,6 +308,10 @@ struct tracer_ctx_s {
 	__u64 last_period_timestamp; /**< Record the timestamp of the last periodic check of the push buffer. */
 	__u64 period_timestamp; /**< Record the timestamp of the periodic check of the push buffer. */
 	bool disable_tracing;  /**< Disable tracing feature. */
+	bool unix_socket_enabled; /**< Trace UNIX domain stream sockets. */
+	__u16 tcp_probe_srtt_offset; /**< Offset of 'srtt' in tracepoint tcp/tcp_probe, 0 if unknown. */
+	__u16 kfree_skb_protocol_offset; /**< Offset of 'protocol' in tracepoint skb/kfree_skb. */
+	__u16 kfree_skb_reason_offset; /**< Offset of 'reason' in tracepoint skb/kfree_skb, 0 if unknown. */
 	struct socket_info_s sk_info; /**< Prevent stack overflow; this option is used as an alternative to stack allocation. */
 };
 
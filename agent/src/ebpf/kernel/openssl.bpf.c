@@ -29,6 +29,9 @@ struct ssl_ctx_struct {
 	// calculated based on the TCP seq at the end and the length of the
 	// message.
 	__u32 tcp_seq;
+	// size_t *written/readbytes of SSL_write_ex()/SSL_read_ex(), NULL
+	// for SSL_write()/SSL_read()
+	size_t *bytes;
 } __attribute__ ((packed));
 
 /* *INDENT-OFF* */
@@ -38,6 +41,43 @@ struct ssl_ctx_struct {
 BPF_HASH(ssl_ctx_map, __u64, struct ssl_ctx_struct, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_UPROBE_OPENSSL)
 /* *INDENT-ON* */
 
+/*
+ * Memory BIOs are not bound to a socket, the application moves the ciphertext
+ * between the BIO and the socket itself, e.g. Node.js creates its BIO with
+ * the BIO_TYPE_MEM type, and netty-tcnative uses a BIO pair.
+ */
+static __inline bool is_openssl_bio_type_mem(int type)
+{
+	static const int bio_type_mem = 1 | 0x0400;
+	static const int bio_type_bio = 19 | 0x0400;
+
+	return type == bio_type_mem || type == bio_type_bio;
+}
+
+static __inline bool is_openssl_mem_bio(void *rbio)
+{
+	static const int method_rbio_offset_v3 = 0x8;
+	static const int method_rbio_offset_v1_1 = 0x0;
+
+	void *method = NULL;
+	int type = 0;
+
+	bpf_probe_read_user(&method, sizeof(method),
+			    rbio + method_rbio_offset_v3);
+	if (method != NULL)
+		bpf_probe_read_user(&type, sizeof(type), method);
+	if (is_openssl_bio_type_mem(type))
+		return true;
+
+	method = NULL;
+	type = 0;
+	bpf_probe_read_user(&method, sizeof(method),
+			    rbio + method_rbio_offset_v1_1);
+	if (method != NULL)
+		bpf_probe_read_user(&type, sizeof(type), method);
+	return is_openssl_bio_type_mem(type);
+}
+
 static int get_fd_from_openssl_ssl(void *ssl)
 {
 	int fd;
@@ -51,6 +91,8 @@ static int get_fd_from_openssl_ssl(void *ssl)
 	// The openssl library generally does not have debug information, so
 	// here we use constants instead.
 	bpf_probe_read_user(&rbio, sizeof(rbio), ssl + rbio_ssl_offset);
+	if (rbio == NULL || is_openssl_mem_bio(rbio))
+		return -1;
 	bpf_probe_read_user(&fd, sizeof(fd), rbio + fd_rbio_offset_v3);
 	if (fd > 2)
 		return fd;
@@ -61,37 +103,61 @@ static int get_fd_from_openssl_ssl(void *ssl)
 	return fd;
 }
 
-// int SSL_write(SSL *ssl, const void *buf, int num);
-UPROG(openssl_write_enter) (struct pt_regs *ctx)
+static __inline void ssl_enter(struct pt_regs *ctx, enum traffic_direction dir,
+			       size_t *bytes)
 {
 	void *ssl = (void *)PT_REGS_PARM1(ctx);
 	int fd = get_fd_from_openssl_ssl(ssl);
 	__u64 id = bpf_get_current_pid_tgid();
+	if (fd <= 2) {
+		/*
+		 * Without a socket BIO, take the socket last used by the
+		 * thread. The ciphertext of SSL_read() has just been read
+		 * from it, while that of SSL_write() is written afterwards,
+		 * which is the same socket unless the thread serves other
+		 * connections in between.
+		 */
+		__u32 *last_fd = thread_socket_fd_map__lookup(&id);
+		if (last_fd == NULL)
+			return;
+		fd = (int)*last_fd;
+	}
 	struct ssl_ctx_struct ssl_ctx = {
 		.fd = fd,
 		.buf = (void *)PT_REGS_PARM2(ctx),
 		.num = (int)PT_REGS_PARM3(ctx),
-		.tcp_seq = get_tcp_write_seq(fd, NULL, NULL),
+		.bytes = bytes,
 	};
+	if (dir == T_EGRESS)
+		ssl_ctx.tcp_seq = get_tcp_write_seq(fd, NULL, NULL);
+	else
+		ssl_ctx.tcp_seq = get_tcp_read_seq(fd, NULL, NULL);
 	ssl_ctx_map__update(&id, &ssl_ctx);
-	return 0;
 }
 
-// int SSL_write(SSL *ssl, const void *buf, int num);
-UPROG(openssl_write_exit) (struct pt_regs *ctx)
+static __inline void ssl_exit(struct pt_regs *ctx, enum traffic_direction dir)
 {
 	__u64 id = bpf_get_current_pid_tgid();
 	struct ssl_ctx_struct *ssl_ctx = ssl_ctx_map__lookup(&id);
 	if (!ssl_ctx)
-		return 0;
+		return;
 
 	int size = (int)PT_REGS_RC(ctx);
+	if (ssl_ctx->bytes != NULL) {
+		// SSL_write_ex()/SSL_read_ex() return 1 on success and the
+		// number of bytes processed is written to the last argument.
+		size_t bytes = 0;
+		if (size == 1)
+			bpf_probe_read_user(&bytes, sizeof(bytes),
+					    ssl_ctx->bytes);
+		size = (int)bytes;
+	}
 	if (size <= 0) {
 		ssl_ctx_map__delete(&id);
-		return 0;
+		return;
 	}
 
-	struct data_args_t write_args = {
+	struct data_args_t args = {
 		.buf = ssl_ctx->buf,
 		.fd = ssl_ctx->fd,
 		.enter_ts = bpf_ktime_get_ns(),
@@ -106,71 +172,75 @@ UPROG(openssl_write_exit) (struct pt_regs *ctx)
 	};
 
 	ssl_ctx_map__delete(&id);
-	active_write_args_map__update(&id, &write_args);
-	if (!process_data((struct pt_regs *)ctx, id, T_EGRESS, &write_args,
-			  size, &extra)) {
+	if (dir == T_EGRESS)
+		active_write_args_map__update(&id, &args);
+	else
+		active_read_args_map__update(&id, &args);
+	if (!process_data((struct pt_regs *)ctx, id, dir, &args, size, &extra)) {
 #if !defined(LINUX_VER_KFUNC) && !defined(LINUX_VER_5_2_PLUS)
 		bpf_tail_call(ctx, &NAME(progs_jmp_kp_map),
 			      PROG_DATA_SUBMIT_KP_IDX);
 #endif
 	}
-	active_write_args_map__delete(&id);
+	if (dir == T_EGRESS)
+		active_write_args_map__delete(&id);
+	else
+		active_read_args_map__delete(&id);
+}
+
+// int SSL_write(SSL *ssl, const void *buf, int num);
+UPROG(openssl_write_enter) (struct pt_regs *ctx)
+{
+	ssl_enter(ctx, T_EGRESS, NULL);
+	return 0;
+}
+
+// int SSL_write(SSL *ssl, const void *buf, int num);
+UPROG(openssl_write_exit) (struct pt_regs *ctx)
+{
+	ssl_exit(ctx, T_EGRESS);
 	return 0;
 }
 
 // int SSL_read(SSL *ssl, void *buf, int num);
 UPROG(openssl_read_enter) (struct pt_regs *ctx)
 {
-	void *ssl = (void *)PT_REGS_PARM1(ctx);
-	int fd = get_fd_from_openssl_ssl(ssl);
-	__u64 id = bpf_get_current_pid_tgid();
-	struct ssl_ctx_struct ssl_ctx = {
-		.fd = fd,
-		.buf = (void *)PT_REGS_PARM2(ctx),
-		.num = (int)PT_REGS_PARM3(ctx),
-		.tcp_seq = get_tcp_read_seq(fd, NULL, NULL),
-	};
-	ssl_ctx_map__update(&id, &ssl_ctx);
+	ssl_enter(ctx, T_INGRESS, NULL);
 	return 0;
 }
 
 // int SSL_read(SSL *ssl, void *buf, int num);
 UPROG(openssl_read_exit) (struct pt_regs *ctx)
 {
-	__u64 id = bpf_get_current_pid_tgid();
-	struct ssl_ctx_struct *ssl_ctx = ssl_ctx_map__lookup(&id);
-	if (!ssl_ctx)
-		return 0;
+	ssl_exit(ctx, T_INGRESS);
+	return 0;
+}
 
-	int size = (int)PT_REGS_RC(ctx);
-	if (size <= 0) {
-		ssl_ctx_map__delete(&id);
-		return 0;
-	}
+// Used by CPython 3.10+ and OpenSSL 1.1.1+ applications
+// int SSL_write_ex(SSL *s, const void *buf, size_t num, size_t *written);
+UPROG(openssl_write_ex_enter) (struct pt_regs *ctx)
+{
+	ssl_enter(ctx, T_EGRESS, (size_t *)PT_REGS_PARM4(ctx));
+	return 0;
+}
 
-	struct data_args_t read_args = {
-		.buf = ssl_ctx->buf,
-		.fd = ssl_ctx->fd,
-		.enter_ts = bpf_ktime_get_ns(),
-		.sk = NULL,
-		.tcp_seq = ssl_ctx->tcp_seq,
-	};
+// int SSL_write_ex(SSL *s, const void *buf, size_t num, size_t *written);
+UPROG(openssl_write_ex_exit) (struct pt_regs *ctx)
+{
+	ssl_exit(ctx, T_EGRESS);
+	return 0;
+}
 
-	struct process_data_extra extra = {
-		.vecs = false,
-		.source = DATA_SOURCE_OPENSSL_UPROBE,
-		.is_go_process = false,
-	};
+// int SSL_read_ex(SSL *ssl, void *buf, size_t num, size_t *readbytes);
+UPROG(openssl_read_ex_enter) (struct pt_regs *ctx)
+{
+	ssl_enter(ctx, T_INGRESS, (size_t *)PT_REGS_PARM4(ctx));
+	return 0;
+}
 
-	ssl_ctx_map__delete(&id);
-	active_read_args_map__update(&id, &read_args);
-	if (!process_data((struct pt_regs *)ctx, id, T_INGRESS, &read_args,
-			  size, &extra)) {
-#if !defined(LINUX_VER_KFUNC) && !defined(LINUX_VER_5_2_PLUS)
-		bpf_tail_call(ctx, &NAME(progs_jmp_kp_map),
-			      PROG_DATA_SUBMIT_KP_IDX);
-#endif
-	}
-	active_read_args_map__delete(&id);
+// int SSL_read_ex(SSL *ssl, void *buf, size_t num, size_t *readbytes);
+UPROG(openssl_read_ex_exit) (struct pt_regs *ctx)
+{
+	ssl_exit(ctx, T_INGRESS);
 	return 0;
 }
@@ -81,6 +81,8 @@ typedef struct {
     struct bpf_map_def *custom_stack_map_b;
     struct bpf_map_def *profiler_output_a;
     struct bpf_map_def *profiler_output_b;
+    // Kernel stacks are not collected for profilers hooking user space functions.
+    bool user_stack_only;
 } map_group_t;
 
 #ifdef LINUX_VER_5_2_PLUS
@@ -428,7 +430,11 @@ static inline
         profiler_output = maps->profiler_output_b;
     }
 
-    key->kernstack = bpf_get_stackid(ctx, stack_map, KERN_STACKID_FLAGS);
+    if (maps->user_stack_only) {
+        key->kernstack = -1;
+    } else {
+        key->kernstack = bpf_get_stackid(ctx, stack_map, KERN_STACKID_FLAGS);
+    }
     if (!(key->flags & STACK_TRACE_FLAGS_DWARF)) {
         key->userstack = bpf_get_stackid(ctx, stack_map, USER_STACKID_FLAGS);
     }
@@ -549,6 +555,248 @@ PERF_EVENT_PROG(oncpu_profile)(struct bpf_perf_event_data *ctx) {
     return collect_stack_and_send_output(&ctx->regs, key, NULL, &oncpu_maps);
 }
 
+/*
+ * Off-CPU profiler
+ *
+ * The 'sched_switch' tracepoint runs in the context of the thread being
+ * switched out, where the start of its off-CPU time is recorded. When the
+ * thread is switched in again, 'finish_task_switch()' runs in its context,
+ * the stack is the one it blocked on, and the blocking time is output with
+ * the stack trace if it is not shorter than MINBLOCK_TIME_IDX.
+ *
+ * Only threads of processes in 'offcpu_pids_map' (maintained by user space
+ * according to the process matcher) are traced. It uses the same double
+ * buffering scheme as the on-CPU profiler with its own set of maps.
+ */
+MAP_PERF_EVENT(offcpu_output_a, int, __u32, MAX_CPU, FEATURE_FLAG_PROFILE_OFFCPU)
+MAP_PERF_EVENT(offcpu_output_b, int, __u32, MAX_CPU, FEATURE_FLAG_PROFILE_OFFCPU)
+
+MAP_STACK_TRACE(offcpu_stack_map_a, STACK_MAP_ENTRIES, FEATURE_FLAG_PROFILE_OFFCPU)
+MAP_STACK_TRACE(offcpu_stack_map_b, STACK_MAP_ENTRIES, FEATURE_FLAG_PROFILE_OFFCPU)
+
+MAP_ARRAY(offcpu_state_map, __u32, __u64, PROFILER_CNT, FEATURE_FLAG_PROFILE_OFFCPU)
+
+// tgid -> 1, processes matched by off-CPU profiler
+MAP_HASH(offcpu_pids_map, __u32, __u32, OFFCPU_PIDS_MAP_ENTRIES, FEATURE_FLAG_PROFILE_OFFCPU)
+// pid(thread ID) -> the time it was switched out
+BPF_LRU_HASH(offcpu_start_map, __u32, __u64, OFFCPU_START_MAP_ENTRIES, FEATURE_FLAG_PROFILE_OFFCPU)
+
+static map_group_t offcpu_maps = {
+    .state = &NAME(offcpu_state_map),
+    .stack_map_a = &NAME(offcpu_stack_map_a),
+    .stack_map_b = &NAME(offcpu_stack_map_b),
+    .profiler_output_a = &NAME(offcpu_output_a),
+    .profiler_output_b = &NAME(offcpu_output_b)
+};
+
+static inline __attribute__((always_inline)) bool offcpu_enabled(void) {
+    __u32 idx = ENABLE_IDX;
+    __u64 *enable_ptr = offcpu_state_map__lookup(&idx);
+    return enable_ptr != NULL && *enable_ptr != 0;
+}
+
+TP_SCHED_PROG(switch)(void *ctx) {
+    if (!offcpu_enabled())
+        return 0;
+
+    __u64 id = bpf_get_current_pid_tgid();
+    __u32 tgid = id >> 32;
+    __u32 pid = (__u32)id;
+    if (pid == 0 || offcpu_pids_map__lookup(&tgid) == NULL)
+        return 0;
+
+    __u64 ts = bpf_ktime_get_ns();
+    offcpu_start_map__update(&pid, &ts);
+    return 0;
+}
+
+KPROG(finish_task_switch)(struct pt_regs *ctx) {
+    __u64 id = bpf_get_current_pid_tgid();
+    __u32 pid = (__u32)id;
+    __u64 *start_ptr = offcpu_start_map__lookup(&pid);
+    if (start_ptr == NULL)
+        return 0;
+
+    __u64 ts = bpf_ktime_get_ns();
+    __u64 start = *start_ptr;
+    offcpu_start_map__delete(&pid);
+    if (!offcpu_enabled() || ts < start)
+        return 0;
+
+    __u32 idx = MINBLOCK_TIME_IDX;
+    __u64 *minblock_ptr = offcpu_state_map__lookup(&idx);
+    __u64 delta = ts - start;
+    if (minblock_ptr == NULL || delta < *minblock_ptr * 1000 || delta > OFFCPU_MAX_BLOCK_NS)
+        return 0;
+
+    struct stack_trace_key_t key = { 0 };
+    key.tgid = id >> 32;
+    key.pid = pid;
+    key.cpu = bpf_get_smp_processor_id();
+    bpf_get_current_comm(&key.comm, sizeof(key.comm));
+    key.timestamp = ts;
+    key.off_cpu.duration_ns = delta;
+
+    return collect_stack_and_send_output(ctx, &key, NULL, &offcpu_maps);
+}
+
+/*
+ * Memory profiler
+ *
+ * Allocations are sampled by uprobes on malloc() of libc and runtime.mallocgc()
+ * of Go (register based ABI, Go 1.17+), which are attached to the matched
+ * processes by user space. Allocation rate is limited by sampling one of every
+ * SAMPLE_INTERVAL_IDX allocations, and at most RATE_LIMIT_IDX samples per
+ * second on each CPU. The size of a sampled allocation is multiplied by the
+ * sampling interval.
+ *
+ * For malloc(), sampled addresses are kept in 'memory_sampled_addrs_map', and
+ * free() of them is output with zero size, so that user space can calculate
+ * the memory in use. Go allocations are output without address as memory is
+ * reclaimed by GC.
+ */
+MAP_PERF_EVENT(memory_output_a, int, __u32, MAX_CPU, FEATURE_FLAG_PROFILE_MEMORY)
+MAP_PERF_EVENT(memory_output_b, int, __u32, MAX_CPU, FEATURE_FLAG_PROFILE_MEMORY)
+
+MAP_STACK_TRACE(memory_stack_map_a, STACK_MAP_ENTRIES, FEATURE_FLAG_PROFILE_MEMORY)
+MAP_STACK_TRACE(memory_stack_map_b, STACK_MAP_ENTRIES, FEATURE_FLAG_PROFILE_MEMORY)
+
+MAP_ARRAY(memory_state_map, __u32, __u64, PROFILER_CNT, FEATURE_FLAG_PROFILE_MEMORY)
+MAP_PERARRAY(memory_rate_limit_map, __u32, struct memory_rate_limit, 1, FEATURE_FLAG_PROFILE_MEMORY)
+
+// pid(thread ID) -> arguments of sampled malloc() call
+MAP_HASH(memory_alloc_args_map, __u32, struct memory_alloc_args, MEMORY_ALLOC_ARGS_MAP_ENTRIES,
+         FEATURE_FLAG_PROFILE_MEMORY)
+// sampled address -> tgid
+BPF_LRU_HASH(memory_sampled_addrs_map, __u64, __u32, MEMORY_SAMPLED_ADDRS_MAP_ENTRIES,
+             FEATURE_FLAG_PROFILE_MEMORY)
+
+static map_group_t memory_maps = {
+    .state = &NAME(memory_state_map),
+    .stack_map_a = &NAME(memory_stack_map_a),
+    .stack_map_b = &NAME(memory_stack_map_b),
+    .profiler_output_a = &NAME(memory_output_a),
+    .profiler_output_b = &NAME(memory_output_b),
+    .user_stack_only = true
+};
+
+/*
+ * Returns the sampling interval if the allocation is sampled, otherwise 0.
+ */
+static inline __attribute__((always_inline)) __u64 memory_alloc_sample(void) {
+    __u32 idx = ENABLE_IDX;
+    __u64 *enable_ptr = memory_state_map__lookup(&idx);
+    idx = SAMPLE_INTERVAL_IDX;
+    __u64 *interval_ptr = memory_state_map__lookup(&idx);
+    idx = RATE_LIMIT_IDX;
+    __u64 *limit_ptr = memory_state_map__lookup(&idx);
+    idx = SAMPLE_CNT_DROP;
+    __u64 *drop_count_ptr = memory_state_map__lookup(&idx);
+    __u32 zero = 0;
+    struct memory_rate_limit *rl = memory_rate_limit_map__lookup(&zero);
+    if (enable_ptr == NULL || interval_ptr == NULL || limit_ptr == NULL || drop_count_ptr == NULL ||
+        rl == NULL || *enable_ptr == 0) {
+        return 0;
+    }
+
+    __u64 interval = *interval_ptr > 0 ? *interval_ptr : 1;
+    if (++rl->alloc_count < interval) {
+        return 0;
+    }
+    rl->alloc_count = 0;
+
+    __u64 ts = bpf_ktime_get_ns();
+    if (ts - rl->window_start >= 1000000000ULL) {
+        rl->window_start = ts;
+        rl->window_samples = 0;
+    }
+    if (*limit_ptr > 0 && rl->window_samples >= *limit_ptr) {
+        __sync_fetch_and_add(drop_count_ptr, 1);
+        return 0;
+    }
+    rl->window_samples++;
+
+    return interval;
+}
+
+static inline __attribute__((always_inline)) int memory_output(struct pt_regs *ctx, __u64 addr, __u64 size,
+                                                               __u64 func_addr) {
+    struct stack_trace_key_t key = { 0 };
+    __u64 id = bpf_get_current_pid_tgid();
+    key.tgid = id >> 32;
+    key.pid = (__u32)id;
+    key.cpu = bpf_get_smp_processor_id();
+    bpf_get_current_comm(&key.comm, sizeof(key.comm));
+    key.timestamp = bpf_ktime_get_ns();
+    key.memory.addr = addr;
+    key.memory.size = size;
+    if (func_addr != 0) {
+        // The stack in uretprobe does not have the frame of malloc()
+        key.flags |= STACK_TRACE_FLAGS_URETPROBE;
+        key.uprobe_addr = func_addr;
+    }
+
+    return collect_stack_and_send_output(ctx, &key, NULL, &memory_maps);
+}
+
+UPROG(memory_malloc_enter)(struct pt_regs *ctx) {
+    __u64 interval = memory_alloc_sample();
+    if (interval == 0)
+        return 0;
+
+    __u32 pid = (__u32)bpf_get_current_pid_tgid();
+    struct memory_alloc_args args = {
+        .size = (__u64)PT_REGS_PARM1(ctx) * interval,
+        .func_addr = PT_REGS_IP(ctx),
+    };
+    memory_alloc_args_map__update(&pid, &args);
+    return 0;
+}
+
+UPROG(memory_malloc_exit)(struct pt_regs *ctx) {
+    __u64 id = bpf_get_current_pid_tgid();
+    __u32 pid = (__u32)id;
+    struct memory_alloc_args *args_ptr = memory_alloc_args_map__lookup(&pid);
+    if (args_ptr == NULL)
+        return 0;
+
+    struct memory_alloc_args args = *args_ptr;
+    memory_alloc_args_map__delete(&pid);
+
+    __u64 addr = (__u64)PT_REGS_RC(ctx);
+    if (addr == 0)
+        return 0;
+
+    __u32 tgid = id >> 32;
+    memory_sampled_addrs_map__update(&addr, &tgid);
+    return memory_output(ctx, addr, args.size, args.func_addr);
+}
+
+UPROG(memory_free_enter)(struct pt_regs *ctx) {
+    __u64 addr = (__u64)PT_REGS_PARM1(ctx);
+    if (addr == 0)
+        return 0;
+
+    __u32 *tgid_ptr = memory_sampled_addrs_map__lookup(&addr);
+    if (tgid_ptr == NULL)
+        return 0;
+
+    __u32 tgid = bpf_get_current_pid_tgid() >> 32;
+    if (*tgid_ptr != tgid)
+        return 0;
+    memory_sampled_addrs_map__delete(&addr);
+
+    return memory_output(ctx, addr, 0, 0);
+}
+
+UPROG(memory_go_mallocgc_enter)(struct pt_regs *ctx) {
+    __u64 interval = memory_alloc_sample();
+    if (interval == 0)
+        return 0;
+
+    return memory_output(ctx, 0, (__u64)PT_GO_REGS_PARM1(ctx) * interval, 0);
+}
+
 #ifdef LINUX_VER_5_2_PLUS
 
 #define SHARD_BSEARCH_LOOPS 9  // 2^9 = 512 > UNWIND_SHARDS_PER_PROCESS
@@ -32,6 +32,20 @@
 #define NS_PER_SEC		1000000000ULL
 
 #define PROTO_INFER_CACHE_SIZE  80
+#define SOCKET_OWNER_MAP_ENTRIES 65536
+#define UNIX_SOCKET_PATH_MAP_ENTRIES 16384
+#define IO_URING_REQ_MAP_ENTRIES 65536
+#define TCP_HEALTH_MAP_ENTRIES 65536
+#define SKB_DROP_MAP_ENTRIES 16384
+#define DNS_RESOLVER_MAP_ENTRIES 16384
+#define FILE_IO_MAP_ENTRIES 16384
+
+#ifndef ETH_P_IP
+#define ETH_P_IP		0x0800
+#endif
+#ifndef ETH_P_IPV6
+#define ETH_P_IPV6		0x86DD
+#endif
 
 #define SUBMIT_OK		(0)
 #define SUBMIT_INVALID		(-1)
@@ -48,6 +62,15 @@
  */
 MAP_PERF_EVENT(socket_data, int, __u32, MAX_CPU, FEATURE_FLAG_SOCKET_TRACER)
 
+#ifdef LINUX_VER_RINGBUF
+/*
+ * Used instead of the perf buffers on Linux 5.8+ if enabled, shared by all
+ * CPUs. The size is reset by the agent before loading.
+ */
+#define SOCKET_DATA_RINGBUF_SIZE (8 << 20)
+MAP_RINGBUF(socket_data_ringbuf, SOCKET_DATA_RINGBUF_SIZE, FEATURE_FLAG_SOCKET_TRACER)
+#endif
+
 /*
  * Why use two Tail Calls jmp tables ?
  *
@@ -103,6 +126,25 @@ MAP_PERARRAY(tracer_ctx_map, __u32, struct tracer_ctx_s, 1, FEATURE_FLAG_SOCKET_
  */
 MAP_ARRAY(trace_stats_map, __u32, struct trace_stats, 1, FEATURE_FLAG_SOCKET_TRACER)
 
+/*
+ * Pass the data to user space through the perf buffer of the current CPU or
+ * the ring buffer.
+ */
+static __inline void socket_data_output(void *ctx, void *data, __u64 size)
+{
+#ifdef LINUX_VER_RINGBUF
+	if (bpf_ringbuf_output(&NAME(socket_data_ringbuf), data, size, 0)) {
+		__u32 k0 = 0;
+		struct trace_stats *stats = trace_stats_map__lookup(&k0);
+		if (stats)
+			__sync_fetch_and_add(&stats->ringbuf_lost, 1);
+	}
+#else
+	bpf_perf_event_output(ctx, &NAME(socket_data), BPF_F_CURRENT_CPU, data,
+			      size);
+#endif
+}
+
 // key: protocol id, value: is protocol enabled, size: PROTO_NUM
 MAP_ARRAY(protocol_filter, int, int, PROTO_NUM, FEATURE_FLAG_SOCKET_TRACER)
 
@@ -143,6 +185,63 @@ BPF_HASH(socket_info_map, __u64, struct socket_info_s, MAP_MAX_ENTRIES_DEF, FEAT
 // Key is {pid + fd}. value is role type
 BPF_HASH(socket_role_map, __u64, __u32, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER);
 
+// Owner process of all accepted and connected sockets, including the ones whose
+// data is not traced. Used by the agent to attribute packet captured flows to processes.
+// Key is struct __tuple_t. value is struct socket_owner
+BPF_LRU_HASH(socket_owner_map, struct __tuple_t, struct socket_owner, SOCKET_OWNER_MAP_ENTRIES, FEATURE_FLAG_SOCKET_TRACER)
+
+// Paths of the traced UNIX domain sockets, looked up by the agent with the socket ID.
+// Key is socket ID. value is struct unix_socket_path
+BPF_LRU_HASH(unix_socket_path_map, __u64, struct unix_socket_path, UNIX_SOCKET_PATH_MAP_ENTRIES, FEATURE_FLAG_SOCKET_TRACER)
+
+// TCP retransmissions, drops, RTT and state of the connections, looked up by the agent
+// to attach kernel TCP health to flows.
+// Key is struct __tuple_t. value is struct tcp_health
+BPF_LRU_HASH(tcp_health_map, struct __tuple_t, struct tcp_health, TCP_HEALTH_MAP_ENTRIES, FEATURE_FLAG_SOCKET_TRACER)
+
+// Packets dropped by kfree_skb(), collected and cleared by the agent periodically.
+// Key is struct skb_drop_key. value is struct skb_drop_value
+BPF_HASH(skb_drop_map, struct skb_drop_key, struct skb_drop_value, SKB_DROP_MAP_ENTRIES, FEATURE_FLAG_SOCKET_TRACER)
+
+// Ongoing getaddrinfo()/gethostbyname() calls, the DNS queries sent by the
+// calling thread are counted in them.
+// Key is pid_tgid. value is struct dns_resolver_ctx
+BPF_HASH(dns_resolver_ctx_map, __u64, struct dns_resolver_ctx, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_UPROBE_DNS_RESOLVER)
+
+// Resolver calls aggregated by process and API, collected and cleared by the agent periodically.
+// Key is struct dns_resolver_key. value is struct dns_resolver_stats
+BPF_HASH(dns_resolver_stats_map, struct dns_resolver_key, struct dns_resolver_stats, DNS_RESOLVER_MAP_ENTRIES, FEATURE_FLAG_UPROBE_DNS_RESOLVER)
+
+// Ongoing vfs_read()/vfs_write() calls.
+// Key is pid_tgid. value is struct file_io_ctx
+BPF_HASH(file_io_ctx_map, __u64, struct file_io_ctx, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER)
+
+// Regular file IO aggregated by process, device and operation, collected and cleared by the agent periodically.
+// Key is struct file_io_key. value is struct file_io_stats
+BPF_HASH(file_io_stats_map, struct file_io_key, struct file_io_stats, FILE_IO_MAP_ENTRIES, FEATURE_FLAG_SOCKET_TRACER)
+
+// Buffer for reading UNIX domain socket paths, which are too large for the stack.
+MAP_PERARRAY(unix_path_buf, __u32, struct unix_socket_path, 1, FEATURE_FLAG_SOCKET_TRACER)
+
+#if defined(LINUX_VER_KFUNC) || defined(LINUX_VER_5_2_PLUS)
+// Arguments of the prepared io_uring send/recv requests.
+// Key is the address of struct io_kiocb. value is struct io_uring_req_args
+BPF_LRU_HASH(io_uring_req_map, __u64, struct io_uring_req_args, IO_URING_REQ_MAP_ENTRIES, FEATURE_FLAG_SOCKET_TRACER)
+
+// io_send()/io_recv() input argument, used when sock_sendmsg()/sock_recvmsg() returns.
+// Key is {tgid, pid}.
+BPF_HASH(active_io_uring_args_map, __u64, struct data_args_t, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER)
+#endif
+
+// connect() syscall's socket fd, used when connect() returns.
+// Key is {tgid, pid}.
+BPF_HASH(active_connect_fd_map, __u64, __u32, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER)
+
+// The last TCP socket fd read or written by the thread, used by the openssl
+// uprobes when the SSL object is not bound to a socket, e.g. Node.js feeds
+// OpenSSL through memory BIOs. Key is {tgid, pid}.
+BPF_LRU_HASH(thread_socket_fd_map, __u64, __u32, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER)
+
 // Key is struct trace_key_t. value is trace_info_t
 BPF_HASH(trace_map, struct trace_key_t, struct trace_info_t, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER)
 
@@ -578,6 +677,13 @@ do {									\
 		f = PF_INET;						\
 } while(0)
 
+static __inline bool unix_socket_enabled(void)
+{
+	__u32 k0 = 0;
+	struct tracer_ctx_s *tracer_ctx = tracer_ctx_map__lookup(&k0);
+	return tracer_ctx != NULL && tracer_ctx->unix_socket_enabled;
+}
+
 static __inline int is_tcp_udp_data(void *sk,
 				    struct member_fields_offset *offset,
 				    struct conn_info_s *conn_info)
@@ -596,9 +702,6 @@ static __inline int is_tcp_udp_data(void *sk,
 	bpf_probe_read_kernel(&conn_info->skc_family,
 			      sizeof(conn_info->skc_family),
 			      sk + offset->struct_sock_family_offset);
-	/*
-	 * Without thinking about PF_UNIX.
-	 */
 	switch (conn_info->skc_family) {
 	case PF_INET:
 		break;
@@ -608,12 +711,23 @@ static __inline int is_tcp_udp_data(void *sk,
 						    offset);
 		}
 		break;
+	case PF_UNIX:
+		if (!unix_socket_enabled())
+			return SOCK_CHECK_TYPE_ERROR;
+		break;
 	default:
 		return SOCK_CHECK_TYPE_ERROR;
 	}
 
 	get_sock_flags(sk, offset, conn_info);
 
+	/*
+	 * Only UNIX domain stream sockets are traced, whose state is also
+	 * TCP_ESTABLISHED after connected.
+	 */
+	if (conn_info->skc_family == PF_UNIX && conn_info->sk_type != SOCK_STREAM)
+		return SOCK_CHECK_TYPE_ERROR;
+
 	if (conn_info->sk_type == SOCK_DGRAM) {
 		conn_info->tuple.l4_protocol = IPPROTO_UDP;
 		return SOCK_CHECK_TYPE_UDP;
@@ -676,9 +790,7 @@ static __inline bool get_socket_info(struct __socket_data *v, void *sk,
 	struct member_fields_offset *offset = members_offset__lookup(&k0);
 	if (!offset)
 		return false;
-	/*
-	 * Without thinking about PF_UNIX.
-	 */
+
 	switch (conn_info->skc_family) {
 	case PF_INET:
 		bpf_probe_read_kernel(v->tuple.rcv_saddr, 4,
@@ -700,6 +812,15 @@ static __inline bool get_socket_info(struct __socket_data *v, void *sk,
 		}
 		v->tuple.addr_len = 16;
 		break;
+	case PF_UNIX:
+		/*
+		 * UNIX domain sockets have no IP address, use a loopback
+		 * tuple, the socket path is recorded in unix_socket_path_map.
+		 */
+		*(__u32 *)v->tuple.rcv_saddr = __bpf_htonl(0x7f000001);
+		*(__u32 *)v->tuple.daddr = __bpf_htonl(0x7f000001);
+		v->tuple.addr_len = 4;
+		break;
 	default:
 		return false;
 	}
@@ -714,9 +835,6 @@ static __inline bool get_socket_info(struct __tuple_t *tuple, void *sk,
 		return false;
 
 	int saddr_off, daddr_off, ip6saddr_off, ip6daddr_off;
-	/*
-	 * Without thinking about PF_UNIX.
-	 */
 	switch (conn_info->skc_family) {
 	case PF_INET:
 		saddr_off = (int)((uintptr_t)
@@ -736,6 +854,11 @@ static __inline bool get_socket_info(struct __tuple_t *tuple, void *sk,
 		bpf_probe_read_kernel(tuple->daddr, 16, sk + ip6daddr_off);
 		tuple->addr_len = 16;
 		break;
+	case PF_UNIX:
+		*(__u32 *)tuple->rcv_saddr = __bpf_htonl(0x7f000001);
+		*(__u32 *)tuple->daddr = __bpf_htonl(0x7f000001);
+		tuple->addr_len = 4;
+		break;
 	default:
 		return false;
 	}
@@ -745,6 +868,157 @@ static __inline bool get_socket_info(struct __tuple_t *tuple, void *sk,
 #endif
 /* *INDENT-ON* */
 
+/*
+ * Record the path of the UNIX domain socket 'sk' for the agent. A connected
+ * client socket is usually not bound, the path of its peer is used instead.
+ */
+static __inline void record_unix_socket_path(void *sk, __u64 socket_id)
+{
+	__u32 k0 = 0;
+	struct member_fields_offset *offset = members_offset__lookup(&k0);
+	if (offset == NULL || offset->struct_unix_sock_addr_offset == 0)
+		return;
+
+	struct unix_socket_path *buf = unix_path_buf__lookup(&k0);
+	if (buf == NULL)
+		return;
+
+	void *addr = NULL;
+	bpf_probe_read_kernel(&addr, sizeof(addr),
+			      sk + offset->struct_unix_sock_addr_offset);
+	if (addr == NULL) {
+		void *peer = NULL;
+		bpf_probe_read_kernel(&peer, sizeof(peer),
+				      sk + offset->struct_unix_sock_peer_offset);
+		if (peer == NULL)
+			return;
+		bpf_probe_read_kernel(&addr, sizeof(addr),
+				      peer + offset->struct_unix_sock_addr_offset);
+		if (addr == NULL)
+			return;
+	}
+
+	// Skip sun_family of struct sockaddr_un
+	void *sun_path = addr + offset->struct_unix_address_name_offset +
+	    sizeof(__u16);
+	bpf_probe_read_kernel(buf->path, 1, sun_path);
+	if (buf->path[0] == '\0') {
+		buf->path[0] = '@';
+		bpf_probe_read_kernel_str(buf->path + 1,
+					  sizeof(buf->path) - 1, sun_path + 1);
+	} else {
+		bpf_probe_read_kernel_str(buf->path, sizeof(buf->path),
+					  sun_path);
+	}
+	unix_socket_path_map__update(&socket_id, buf);
+}
+
+/*
+ * Fill in the tuple of the TCP/UDP socket 'sk' as the key of socket_owner_map
+ * and tcp_health_map. IPv4-mapped IPv6 sockets use IPv4 addresses.
+ */
+static __inline bool get_socket_tuple(void *sk,
+				      struct member_fields_offset *offset,
+				      struct __tuple_t *key)
+{
+	struct skc_flags_t {
+		unsigned char skc_reuse:4;
+		unsigned char skc_reuseport:1;
+		unsigned char skc_ipv6only:1;
+		unsigned char skc_net_refcnt:1;
+	};
+	struct skc_flags_t skc_flags;
+	__u16 skc_family = 0;
+	bpf_probe_read_kernel(&skc_flags, sizeof(skc_flags),
+			      sk + offset->struct_sock_common_ipv6only_offset);
+	bpf_probe_read_kernel(&skc_family, sizeof(skc_family),
+			      sk + offset->struct_sock_family_offset);
+	if (skc_family != PF_INET && skc_family != PF_INET6)
+		return false;
+
+	if (skc_family == PF_INET6 && skc_flags.skc_ipv6only == 0) {
+		ipv4_mapped_on_ipv6_confirm(sk, skc_family, offset);
+	}
+
+	struct sock_flags_t {
+		unsigned int sk_padding:1;
+		unsigned int sk_kern_sock:1;
+		unsigned int sk_no_check_tx:1;
+		unsigned int sk_no_check_rx:1;
+		unsigned int sk_userlocks:4;
+		unsigned int sk_protocol:8;
+		unsigned int sk_type:16;
+	};
+	unsigned int flags = 0;
+	struct sock_flags_t *sk_flags = (struct sock_flags_t *)&flags;
+	bpf_probe_read_kernel(&flags, sizeof(flags), (void *)sk +
+			      offset->sock__flags_offset);
+
+	if (sk_flags->sk_type == SOCK_STREAM)
+		key->l4_protocol = IPPROTO_TCP;
+	else if (sk_flags->sk_type == SOCK_DGRAM)
+		key->l4_protocol = IPPROTO_UDP;
+	else
+		return false;
+
+	__be16 inet_dport;
+	__u16 inet_sport;
+	bpf_probe_read_kernel(&inet_dport, sizeof(inet_dport),
+			      sk + offset->struct_sock_dport_offset);
+	bpf_probe_read_kernel(&inet_sport, sizeof(inet_sport),
+			      sk + offset->struct_sock_sport_offset);
+	key->dport = __bpf_ntohs(inet_dport);
+	key->num = inet_sport;
+
+	if (skc_family == PF_INET) {
+		bpf_probe_read_kernel(key->rcv_saddr, 4,
+				      sk + offset->struct_sock_saddr_offset);
+		bpf_probe_read_kernel(key->daddr, 4,
+				      sk + offset->struct_sock_daddr_offset);
+		key->addr_len = 4;
+	} else {
+		bpf_probe_read_kernel(key->rcv_saddr, 16,
+				      sk + offset->struct_sock_ip6saddr_offset);
+		bpf_probe_read_kernel(key->daddr, 16,
+				      sk + offset->struct_sock_ip6daddr_offset);
+		key->addr_len = 16;
+	}
+
+	return true;
+}
+
+/*
+ * Record the process owning the socket 'fd' in socket_owner_map, after the
+ * socket is accepted or connected. The connection state is not checked as a
+ * non-blocking connect() returns before the connection is established, the
+ * tuple is already filled in at this point.
+ */
+static __inline void record_socket_owner(int fd, __u32 tgid,
+					 enum endpoint_role role)
+{
+	struct member_fields_offset *offset = retrieve_ready_kern_offset();
+	if (offset == NULL)
+		return;
+
+	void *sk = get_socket_from_fd(fd, offset);
+	if (sk == NULL)
+		return;
+
+	struct __tuple_t key = { 0 };
+	if (!get_socket_tuple(sk, offset, &key))
+		return;
+
+	struct socket_owner owner = { 0 };
+	owner.tgid = tgid;
+	owner.role = role;
+#if defined(LINUX_VER_KFUNC) || defined(LINUX_VER_5_2_PLUS)
+	owner.cgroup_id = bpf_get_current_cgroup_id();
+#endif
+	owner.timestamp = bpf_ktime_get_ns();
+	bpf_get_current_comm(owner.comm, sizeof(owner.comm));
+	socket_owner_map__update(&key, &owner);
+}
+
 #ifdef PROBE_CONN_SUBMIT
 static __inline void connect_submit(struct pt_regs *ctx, struct conn_info_s *v,
 				    int act)
@@ -760,8 +1034,7 @@ static __inline void connect_submit(struct pt_regs *ctx, struct conn_info_s *v,
 		return;
 	}
 
-	bpf_perf_event_output(ctx, &NAME(socket_data),
-			      BPF_F_CURRENT_CPU, v, 128);
+	socket_data_output(ctx, v, 128);
 }
 #endif
 
@@ -1247,6 +1520,42 @@ static __inline void trace_process(struct socket_info_s *socket_info_ptr,
 	}
 }
 
+static __inline bool is_loopback_tuple(struct __tuple_t *tuple)
+{
+	if (tuple->addr_len == 4)
+		return tuple->daddr[0] == 127;
+
+	// ::ffff:127.0.0.0/104
+	if (tuple->daddr[10] == 0xff && tuple->daddr[11] == 0xff)
+		return tuple->daddr[12] == 127;
+
+	// ::1
+	__u8 i, sum = 0;
+#pragma unroll
+	for (i = 0; i < 15; i++)
+		sum |= tuple->daddr[i];
+	return sum == 0 && tuple->daddr[15] == 1;
+}
+
+/*
+ * Count the DNS query in the ongoing getaddrinfo()/gethostbyname() call of
+ * the thread, so that the resolver latency can be told apart from the DNS
+ * traffic. With systemd-resolved, the queries are sent to the stub resolver
+ * on 127.0.0.53 and the upstream DNS traffic belongs to systemd-resolved.
+ */
+static __inline void dns_resolver_count_query(struct __tuple_t *tuple)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct dns_resolver_ctx *resolver_ctx =
+	    dns_resolver_ctx_map__lookup(&id);
+	if (resolver_ctx == NULL)
+		return;
+
+	resolver_ctx->queries++;
+	if (is_loopback_tuple(tuple))
+		resolver_ctx->loopback_queries++;
+}
+
 #if defined(LINUX_VER_KFUNC) || defined(LINUX_VER_5_2_PLUS)
 static __inline int
 __output_data_common(void *ctx, struct tracer_ctx_s *tracer_ctx,
@@ -1370,6 +1679,8 @@ __data_submit(struct pt_regs *ctx, struct conn_info_s *conn_info,
 		sk_info->no_trace = conn_info->no_trace;
 		sk_info->uid = tracer_ctx->socket_id + 1;
 		tracer_ctx->socket_id++;	// Ensure that socket_id is incremented.
+		if (conn_info->skc_family == PF_UNIX)
+			record_unix_socket_path(conn_info->sk, sk_info->uid);
 		sk_info->l7_proto = conn_info->protocol;
 		//Confirm whether data reassembly is required for this socket.
 		if (is_proto_reasm_enabled(conn_info->protocol)) {
@@ -1443,6 +1754,10 @@ __data_submit(struct pt_regs *ctx, struct conn_info_s *conn_info,
 		return SUBMIT_INVALID;
 	}
 #endif
+	if (conn_info->protocol == PROTO_DNS &&
+	    conn_info->direction == T_EGRESS)
+		dns_resolver_count_query(&v->tuple);
+
 	__u32 send_reasm_bytes = 0;
 	if (is_socket_info_valid(socket_info_ptr)) {
 		sk_info->uid = socket_info_ptr->uid;
@@ -1551,6 +1866,7 @@ __data_submit(struct pt_regs *ctx, struct conn_info_s *conn_info,
 	v->data_seq = sk_info->seq;
 	v->tgid = tgid;
 	v->is_tls = false;
+	v->is_unix = (conn_info->skc_family == PF_UNIX);
 	v->pid = (__u32) bpf_get_current_pid_tgid();
 
 	// For blocking reads, there is a significant deviation between the
@@ -1575,7 +1891,8 @@ __data_submit(struct pt_regs *ctx, struct conn_info_s *conn_info,
 
 	if ((extra->source == DATA_SOURCE_GO_TLS_UPROBE ||
 	     extra->source == DATA_SOURCE_OPENSSL_UPROBE) ||
-	    (conn_info->tuple.l4_protocol == IPPROTO_TCP)) {
+	    (conn_info->tuple.l4_protocol == IPPROTO_TCP &&
+	     conn_info->skc_family != PF_UNIX)) {
 		/*
 		 * If the current state is TCPF_CLOSE_WAIT, the FIN frame already has been received.
 		 * However, it cannot be confirmed that it has been processed by the syscall,
@@ -1778,6 +2095,17 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 	return -1;
 }
 
+static __inline void update_thread_socket_fd(__u64 id,
+					      struct data_args_t *args,
+					      ssize_t bytes_count)
+{
+	// The TCP sequence is only available on TCP sockets.
+	if (bytes_count > 0 && args->tcp_seq != 0) {
+		__u32 fd = args->fd;
+		thread_socket_fd_map__update(&id, &fd);
+	}
+}
+
 static __inline void process_syscall_data(struct pt_regs *ctx, __u64 id,
 					  const enum traffic_direction
 					  direction,
@@ -1790,6 +2118,7 @@ static __inline void process_syscall_data(struct pt_regs *ctx, __u64 id,
 		.is_go_process = is_current_go_process(),
 	};
 
+	update_thread_socket_fd(id, args, bytes_count);
 	if (!process_data(ctx, id, direction, args, bytes_count, &extra)) {
 #if !defined(LINUX_VER_KFUNC) && !defined(LINUX_VER_5_2_PLUS)
 		bpf_tail_call(ctx, &NAME(progs_jmp_tp_map),
@@ -1813,6 +2142,7 @@ static __inline void process_syscall_data_vecs(struct pt_regs *ctx, __u64 id,
 		.is_go_process = is_current_go_process(),
 	};
 
+	update_thread_socket_fd(id, args, bytes_count);
 	if (!process_data(ctx, id, direction, args, bytes_count, &extra)) {
 #if !defined(LINUX_VER_KFUNC) && !defined(LINUX_VER_5_2_PLUS)
 		bpf_tail_call(ctx, &NAME(progs_jmp_tp_map),
@@ -2498,6 +2828,509 @@ KRETFUNC_PROG(do_readv, unsigned long fd, const struct iovec __user * vec,
 	return 0;
 }
 
+#if defined(LINUX_VER_KFUNC) || defined(LINUX_VER_5_2_PLUS)
+/*
+ * io_uring send/recv requests (Linux 5.6+) do not go through the hooked
+ * syscalls. The fd and buffer are taken from the submission queue entry when
+ * the request is prepared, and the data is processed when sock_sendmsg() or
+ * sock_recvmsg() called by io_send()/io_recv() returns. Requests are executed
+ * in the submitting thread or in io-wq workers, both of which share the files
+ * of the process.
+ */
+
+// Offsets of struct io_uring_sqe members (include/uapi/linux/io_uring.h)
+#define IO_URING_SQE_OPCODE_OFFSET	0
+#define IO_URING_SQE_FLAGS_OFFSET	1
+#define IO_URING_SQE_FD_OFFSET		4
+#define IO_URING_SQE_ADDR_OFFSET	16
+
+#define IORING_OP_SEND			26
+#define IORING_OP_RECV			27
+
+#define IOSQE_FIXED_FILE		(1U << 0)
+#define IOSQE_BUFFER_SELECT		(1U << 5)
+
+static __inline void save_io_uring_req(void *req, void *sqe, __u8 op)
+{
+	__u8 opcode = 0, flags = 0;
+	__s32 fd = -1;
+	__u64 addr = 0;
+
+	bpf_probe_read_kernel(&opcode, sizeof(opcode),
+			      sqe + IO_URING_SQE_OPCODE_OFFSET);
+	if (opcode != op)
+		return;
+
+	bpf_probe_read_kernel(&flags, sizeof(flags),
+			      sqe + IO_URING_SQE_FLAGS_OFFSET);
+	// Registered files and provided buffers are not supported.
+	if (flags & (IOSQE_FIXED_FILE | IOSQE_BUFFER_SELECT))
+		return;
+
+	bpf_probe_read_kernel(&fd, sizeof(fd), sqe + IO_URING_SQE_FD_OFFSET);
+	bpf_probe_read_kernel(&addr, sizeof(addr),
+			      sqe + IO_URING_SQE_ADDR_OFFSET);
+	// Don't process FD 0-2 to avoid STDIN, STDOUT, STDERR.
+	if (fd <= 2)
+		return;
+
+	__u64 key = (__u64) req;
+	struct io_uring_req_args req_args = {
+		.fd = fd,
+		.buf = (const char *)addr,
+	};
+	io_uring_req_map__update(&key, &req_args);
+}
+
+static __inline void stash_io_uring_args(void *req,
+					 enum syscall_src_func source_fn)
+{
+	__u64 key = (__u64) req;
+	struct io_uring_req_args *req_args = io_uring_req_map__lookup(&key);
+	if (req_args == NULL)
+		return;
+
+	__u64 id = bpf_get_current_pid_tgid();
+	struct data_args_t args = {};
+	args.source_fn = source_fn;
+	args.fd = req_args->fd;
+	args.buf = req_args->buf;
+	args.enter_ts = bpf_ktime_get_ns();
+	__u64 conn_key = gen_conn_key_id((__u64) (id >> 32), (__u64) args.fd);
+	struct socket_info_s *socket_info_ptr =
+	    socket_info_map__lookup(&conn_key);
+	if (source_fn == SYSCALL_FUNC_SEND)
+		args.tcp_seq =
+		    get_tcp_write_seq(args.fd, &args.sk, socket_info_ptr);
+	else
+		args.tcp_seq =
+		    get_tcp_read_seq(args.fd, &args.sk, socket_info_ptr);
+	active_io_uring_args_map__update(&id, &args);
+}
+
+// int io_sendmsg_prep(struct io_kiocb *req, const struct io_uring_sqe *sqe)
+KPROG(io_sendmsg_prep) (struct pt_regs * ctx) {
+	save_io_uring_req((void *)PT_REGS_PARM1(ctx),
+			  (void *)PT_REGS_PARM2(ctx), IORING_OP_SEND);
+	return 0;
+}
+
+// int io_recvmsg_prep(struct io_kiocb *req, const struct io_uring_sqe *sqe)
+KPROG(io_recvmsg_prep) (struct pt_regs * ctx) {
+	save_io_uring_req((void *)PT_REGS_PARM1(ctx),
+			  (void *)PT_REGS_PARM2(ctx), IORING_OP_RECV);
+	return 0;
+}
+
+// int io_send(struct io_kiocb *req, unsigned int issue_flags)
+KPROG(io_send) (struct pt_regs * ctx) {
+	stash_io_uring_args((void *)PT_REGS_PARM1(ctx), SYSCALL_FUNC_SEND);
+	return 0;
+}
+
+// int io_recv(struct io_kiocb *req, unsigned int issue_flags)
+KPROG(io_recv) (struct pt_regs * ctx) {
+	stash_io_uring_args((void *)PT_REGS_PARM1(ctx), SYSCALL_FUNC_RECV);
+	return 0;
+}
+
+/*
+ * The request is completed or re-queued (-EAGAIN) when io_send()/io_recv()
+ * returns, the arguments of completed requests are removed by the LRU map.
+ */
+KRETPROG(io_send) (struct pt_regs * ctx) {
+	__u64 id = bpf_get_current_pid_tgid();
+	active_io_uring_args_map__delete(&id);
+	return 0;
+}
+
+KRETPROG(io_recv) (struct pt_regs * ctx) {
+	__u64 id = bpf_get_current_pid_tgid();
+	active_io_uring_args_map__delete(&id);
+	return 0;
+}
+
+// int sock_sendmsg(struct socket *sock, struct msghdr *msg)
+KRETPROG(sock_sendmsg) (struct pt_regs * ctx) {
+	ssize_t bytes_count = (ssize_t) PT_REGS_RC(ctx);
+	__u64 id = bpf_get_current_pid_tgid();
+	struct data_args_t *args = active_io_uring_args_map__lookup(&id);
+	if (args != NULL && args->source_fn == SYSCALL_FUNC_SEND) {
+		args->bytes_count = bytes_count;
+		process_syscall_data(ctx, id, T_EGRESS, args, bytes_count);
+		active_io_uring_args_map__delete(&id);
+	}
+
+	return 0;
+}
+
+// int sock_recvmsg(struct socket *sock, struct msghdr *msg, int flags)
+KRETPROG(sock_recvmsg) (struct pt_regs * ctx) {
+	ssize_t bytes_count = (ssize_t) PT_REGS_RC(ctx);
+	__u64 id = bpf_get_current_pid_tgid();
+	struct data_args_t *args = active_io_uring_args_map__lookup(&id);
+	if (args != NULL && args->source_fn == SYSCALL_FUNC_RECV) {
+		args->bytes_count = bytes_count;
+		process_syscall_data(ctx, id, T_INGRESS, args, bytes_count);
+		active_io_uring_args_map__delete(&id);
+	}
+
+	return 0;
+}
+#endif
+
+/*
+ * TCP health of the connections, from the tcp tracepoints (Linux 4.16+) and
+ * tcp_drop(). Entries are created when connections are established and kept
+ * after they are closed, until evicted by the LRU map, so that the agent can
+ * still find them when the flows end.
+ */
+static __inline struct tcp_health *lookup_tcp_health(void *sk, bool create)
+{
+	struct member_fields_offset *offset = retrieve_ready_kern_offset();
+	if (offset == NULL)
+		return NULL;
+
+	struct __tuple_t key = { 0 };
+	if (!get_socket_tuple(sk, offset, &key)
+	    || key.l4_protocol != IPPROTO_TCP)
+		return NULL;
+
+	struct tcp_health *health = tcp_health_map__lookup(&key);
+	if (health == NULL && create) {
+		struct tcp_health empty = { 0 };
+		tcp_health_map__update(&key, &empty);
+		health = tcp_health_map__lookup(&key);
+	}
+
+	return health;
+}
+
+// /sys/kernel/debug/tracing/events/sock/inet_sock_set_state/format
+struct inet_sock_set_state_ctx {
+	__u64 __pad_0;
+	const void *skaddr;	// offset:8
+	int oldstate;		// offset:16
+	int newstate;		// offset:20
+	__u16 sport;		// offset:24
+	__u16 dport;		// offset:26
+	__u16 family;		// offset:28
+	__u16 protocol;		// offset:30
+};
+
+TP_SOCK_PROG(inet_sock_set_state) (struct inet_sock_set_state_ctx * ctx) {
+	if (ctx->protocol != IPPROTO_TCP)
+		return 0;
+
+	// Connections are tracked from TCP_ESTABLISHED.
+	if ((1 << ctx->newstate) &
+	    (TCPF_SYN_SENT | TCPF_SYN_RECV | TCPF_LISTEN))
+		return 0;
+
+	bool established = (1 << ctx->newstate) == TCPF_ESTABLISHED;
+	struct tcp_health *health = lookup_tcp_health((void *)ctx->skaddr,
+						      established);
+	if (health == NULL)
+		return 0;
+
+	health->state = ctx->newstate;
+	health->timestamp = bpf_ktime_get_ns();
+	return 0;
+}
+
+// /sys/kernel/debug/tracing/events/tcp/tcp_retransmit_skb/format
+struct tcp_retransmit_skb_ctx {
+	__u64 __pad_0;
+	const void *skbaddr;	// offset:8
+	const void *skaddr;	// offset:16
+};
+
+TP_TCP_PROG(retransmit_skb) (struct tcp_retransmit_skb_ctx * ctx) {
+	struct tcp_health *health =
+	    lookup_tcp_health((void *)ctx->skaddr, true);
+	if (health == NULL)
+		return 0;
+
+	__sync_fetch_and_add(&health->retrans, 1);
+	health->timestamp = bpf_ktime_get_ns();
+	return 0;
+}
+
+static __inline void count_tcp_drop(void *sk)
+{
+	struct tcp_health *health = lookup_tcp_health(sk, true);
+	if (health == NULL)
+		return;
+
+	__sync_fetch_and_add(&health->drops, 1);
+	health->timestamp = bpf_ktime_get_ns();
+}
+
+// void tcp_drop(struct sock *sk, struct sk_buff *skb)
+KPROG(tcp_drop) (struct pt_regs * ctx) {
+	count_tcp_drop((void *)PT_REGS_PARM1(ctx));
+	return 0;
+}
+
+// Linux 5.17+: void tcp_drop_reason(struct sock *sk, struct sk_buff *skb,
+//                                   enum skb_drop_reason reason)
+KPROG(tcp_drop_reason) (struct pt_regs * ctx) {
+	count_tcp_drop((void *)PT_REGS_PARM1(ctx));
+	return 0;
+}
+
+/*
+ * /sys/kernel/debug/tracing/events/tcp/tcp_probe/format
+ * The addresses are struct sockaddr_in or struct sockaddr_in6, the offset of
+ * 'srtt' differs between kernel versions and is set by the agent.
+ */
+struct tcp_probe_ctx {
+	__u64 __pad_0;
+	__u8 saddr[28];		// offset:8
+	__u8 daddr[28];		// offset:36
+};
+
+static __inline void sockaddr_to_tuple(__u8 *saddr, __u8 *daddr,
+				       struct __tuple_t *key)
+{
+	__u16 family = *(__u16 *) saddr;
+	key->num = __bpf_ntohs(*(__u16 *) & saddr[2]);
+	key->dport = __bpf_ntohs(*(__u16 *) & daddr[2]);
+	key->l4_protocol = IPPROTO_TCP;
+	if (family == PF_INET) {
+		__builtin_memcpy(key->rcv_saddr, &saddr[4], 4);
+		__builtin_memcpy(key->daddr, &daddr[4], 4);
+		key->addr_len = 4;
+	} else if (*(__u32 *) & saddr[16] == 0xffff0000) {
+		// IPv4-mapped IPv6 address
+		__builtin_memcpy(key->rcv_saddr, &saddr[20], 4);
+		__builtin_memcpy(key->daddr, &daddr[20], 4);
+		key->addr_len = 4;
+	} else {
+		__builtin_memcpy(key->rcv_saddr, &saddr[8], 16);
+		__builtin_memcpy(key->daddr, &daddr[8], 16);
+		key->addr_len = 16;
+	}
+}
+
+TP_TCP_PROG(probe) (struct tcp_probe_ctx * ctx) {
+	__u32 k0 = 0;
+	struct tracer_ctx_s *tracer_ctx = tracer_ctx_map__lookup(&k0);
+	if (tracer_ctx == NULL || tracer_ctx->tcp_probe_srtt_offset == 0)
+		return 0;
+
+	__u8 saddr[28], daddr[28];
+	bpf_probe_read_kernel(saddr, sizeof(saddr), ctx->saddr);
+	bpf_probe_read_kernel(daddr, sizeof(daddr), ctx->daddr);
+	struct __tuple_t key = { 0 };
+	sockaddr_to_tuple(saddr, daddr, &key);
+
+	struct tcp_health *health = tcp_health_map__lookup(&key);
+	if (health == NULL)
+		return 0;
+
+	__u32 srtt = 0;
+	bpf_probe_read_kernel(&srtt, sizeof(srtt),
+			      (void *)ctx + tracer_ctx->tcp_probe_srtt_offset);
+	health->srtt_us = srtt;
+	return 0;
+}
+
+/*
+ * Read the addresses, protocol and destination port of a dropped packet from
+ * its network and transport headers.
+ */
+static __inline void skb_drop_fill_key(void *skb, __u16 eth_protocol,
+				       struct member_fields_offset *offset,
+				       struct skb_drop_key *key)
+{
+	if (offset == NULL || offset->struct_sk_buff_head_offset == 0)
+		return;
+
+	void *head = NULL;
+	__u16 network_header = 0, transport_header = 0;
+	bpf_probe_read_kernel(&head, sizeof(head),
+			      skb + offset->struct_sk_buff_head_offset);
+	bpf_probe_read_kernel(&network_header, sizeof(network_header),
+			      skb + offset->struct_sk_buff_network_header_offset);
+	bpf_probe_read_kernel(&transport_header, sizeof(transport_header),
+			      skb +
+			      offset->struct_sk_buff_transport_header_offset);
+	// The headers are (typeof(skb->network_header))~0U if not set.
+	if (head == NULL || network_header == 0xffff)
+		return;
+
+	__u8 hdr[40];
+	void *nh = head + network_header;
+	void *th = NULL;
+	if (eth_protocol == __bpf_htons(ETH_P_IP)) {
+		bpf_probe_read_kernel(hdr, 20, nh);
+		key->protocol = hdr[9];
+		__builtin_memcpy(key->saddr, &hdr[12], 4);
+		__builtin_memcpy(key->daddr, &hdr[16], 4);
+		key->addr_len = 4;
+		th = nh + ((hdr[0] & 0xf) << 2);
+	} else if (eth_protocol == __bpf_htons(ETH_P_IPV6)) {
+		bpf_probe_read_kernel(hdr, sizeof(hdr), nh);
+		// Extension headers are not walked.
+		key->protocol = hdr[6];
+		__builtin_memcpy(key->saddr, &hdr[8], 16);
+		__builtin_memcpy(key->daddr, &hdr[24], 16);
+		key->addr_len = 16;
+		th = nh + 40;
+	} else {
+		return;
+	}
+
+	if (key->protocol != IPPROTO_TCP && key->protocol != IPPROTO_UDP)
+		return;
+
+	if (transport_header != 0xffff && transport_header != network_header)
+		th = head + transport_header;
+
+	__u16 ports[2] = { 0 };
+	bpf_probe_read_kernel(ports, sizeof(ports), th);
+	key->dport = __bpf_ntohs(ports[1]);
+}
+
+/*
+ * /sys/kernel/debug/tracing/events/skb/kfree_skb/format
+ * The offsets of 'protocol' and 'reason' differ between kernel versions and
+ * are set by the agent, 'reason' is available since Linux 5.17.
+ */
+struct kfree_skb_ctx {
+	__u64 __pad_0;
+	void *skbaddr;		// offset:8
+};
+
+TP_SKB_PROG(kfree_skb) (struct kfree_skb_ctx * ctx) {
+	__u32 k0 = 0;
+	struct tracer_ctx_s *tracer_ctx = tracer_ctx_map__lookup(&k0);
+	if (tracer_ctx == NULL || tracer_ctx->kfree_skb_reason_offset == 0)
+		return 0;
+
+	struct skb_drop_key key = { 0 };
+	__u16 eth_protocol = 0;
+	bpf_probe_read_kernel(&key.reason, sizeof(key.reason),
+			      (void *)ctx + tracer_ctx->kfree_skb_reason_offset);
+	bpf_probe_read_kernel(&eth_protocol, sizeof(eth_protocol),
+			      (void *)ctx +
+			      tracer_ctx->kfree_skb_protocol_offset);
+
+	void *skb = ctx->skbaddr;
+	struct member_fields_offset *offset = retrieve_ready_kern_offset();
+	skb_drop_fill_key(skb, eth_protocol, offset, &key);
+
+	__u32 len = 0;
+	if (offset != NULL && offset->struct_sk_buff_len_offset != 0)
+		bpf_probe_read_kernel(&len, sizeof(len),
+				      skb + offset->struct_sk_buff_len_offset);
+
+	struct skb_drop_value *value = skb_drop_map__lookup(&key);
+	if (value == NULL) {
+		struct skb_drop_value empty = { 0 };
+		// Aggregate by the reason only if the map is full.
+		if (skb_drop_map__update(&key, &empty) != 0) {
+			__u32 reason = key.reason;
+			__builtin_memset(&key, 0, sizeof(key));
+			key.reason = reason;
+			skb_drop_map__update(&key, &empty);
+		}
+		value = skb_drop_map__lookup(&key);
+		if (value == NULL)
+			return 0;
+	}
+
+	__sync_fetch_and_add(&value->packets, 1);
+	__sync_fetch_and_add(&value->bytes, len);
+	return 0;
+}
+
+static __inline void file_io_enter(void *file)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct file_io_ctx io_ctx = {
+		.enter_ts = bpf_ktime_get_ns(),
+		.file = file,
+	};
+	file_io_ctx_map__update(&id, &io_ctx);
+}
+
+static __inline void file_io_exit(ssize_t ret, __u32 operation)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct file_io_ctx *io_ctx = file_io_ctx_map__lookup(&id);
+	if (io_ctx == NULL)
+		return;
+
+	struct member_fields_offset *offset = retrieve_ready_kern_offset();
+	if (ret < 0 || offset == NULL || offset->struct_inode_i_sb_offset == 0)
+		goto out;
+
+	void *inode = NULL, *sb = NULL;
+	bpf_probe_read_kernel(&inode, sizeof(inode),
+			      io_ctx->file + offset->struct_file_f_inode_offset);
+	if (inode == NULL)
+		goto out;
+	__u16 i_mode = 0;
+	bpf_probe_read_kernel(&i_mode, sizeof(i_mode),
+			      inode + offset->struct_inode_i_mode_offset);
+	if (!S_ISREG(i_mode))
+		goto out;
+	bpf_probe_read_kernel(&sb, sizeof(sb),
+			      inode + offset->struct_inode_i_sb_offset);
+	if (sb == NULL)
+		goto out;
+
+	struct file_io_key key = {
+		.tgid = (__u32) (id >> 32),
+		.operation = operation,
+	};
+	bpf_probe_read_kernel(&key.dev, sizeof(key.dev),
+			      sb + offset->struct_super_block_s_dev_offset);
+	__u64 latency = (bpf_ktime_get_ns() - io_ctx->enter_ts) / NS_PER_US;
+	struct file_io_stats *stats = file_io_stats_map__lookup(&key);
+	if (stats == NULL) {
+		struct file_io_stats empty = { 0 };
+		bpf_get_current_comm(empty.comm, sizeof(empty.comm));
+		file_io_stats_map__update(&key, &empty);
+		stats = file_io_stats_map__lookup(&key);
+		if (stats == NULL)
+			goto out;
+	}
+
+	__sync_fetch_and_add(&stats->count, 1);
+	__sync_fetch_and_add(&stats->bytes, ret);
+	__sync_fetch_and_add(&stats->latency_sum, latency);
+	if (latency > stats->latency_max)
+		stats->latency_max = latency;
+
+out:
+	file_io_ctx_map__delete(&id);
+}
+
+// ssize_t vfs_read(struct file *file, char __user *buf, size_t count, loff_t *pos)
+KPROG(vfs_read) (struct pt_regs * ctx) {
+	file_io_enter((void *)PT_REGS_PARM1(ctx));
+	return 0;
+}
+
+KRETPROG(vfs_read) (struct pt_regs * ctx) {
+	file_io_exit((ssize_t) PT_REGS_RC(ctx), T_INGRESS);
+	return 0;
+}
+
+// ssize_t vfs_write(struct file *file, const char __user *buf, size_t count, loff_t *pos)
+KPROG(vfs_write) (struct pt_regs * ctx) {
+	file_io_enter((void *)PT_REGS_PARM1(ctx));
+	return 0;
+}
+
+KRETPROG(vfs_write) (struct pt_regs * ctx) {
+	file_io_exit((ssize_t) PT_REGS_RC(ctx), T_EGRESS);
+	return 0;
+}
+
 #ifndef LINUX_VER_KFUNC
 static __inline void __push_close_event(__u64 pid_tgid, __u64 uid, __u64 seq,
 					struct member_fields_offset *offset,
@@ -2666,6 +3499,8 @@ KRETFUNC_PROG(__sys_accept4, int fd, struct sockaddr __user * upeer_sockaddr,
 	__u64 conn_key = gen_conn_key_id((__u64) tgid, (__u64) sockfd);
 	__u32 role = ROLE_SERVER;
 	socket_role_map__update(&conn_key, &role);
+	if (sockfd >= 0)
+		record_socket_owner(sockfd, tgid, ROLE_SERVER);
 	return 0;
 }
 
@@ -2677,6 +3512,8 @@ TP_SYSCALL_PROG(exit_accept4) (struct syscall_comm_exit_ctx * ctx) {
 	__u64 conn_key = gen_conn_key_id((__u64) tgid, (__u64) sockfd);
 	__u32 role = ROLE_SERVER;
 	socket_role_map__update(&conn_key, &role);
+	if (sockfd >= 0)
+		record_socket_owner(sockfd, tgid, ROLE_SERVER);
 	return 0;
 }
 #endif
@@ -2696,6 +3533,39 @@ KFUNC_PROG(__sys_connect, int fd, struct sockaddr __user * uservaddr,
 	__u64 conn_key = gen_conn_key_id((__u64) tgid, (__u64) sockfd);
 	__u32 role = ROLE_CLIENT;
 	socket_role_map__update(&conn_key, &role);
+#ifndef LINUX_VER_KFUNC
+	__u32 fd = (__u32) sockfd;
+	active_connect_fd_map__update(&pid_tgid, &fd);
+#endif
+	return 0;
+}
+
+/*
+ * Non-blocking connect() returns -EINPROGRESS, the local address and port are
+ * assigned by then.
+ */
+#define CONNECT_EINPROGRESS 115
+
+#ifndef LINUX_VER_KFUNC
+TP_SYSCALL_PROG(exit_connect) (struct syscall_comm_exit_ctx * ctx) {
+	int ret = ctx->ret;
+	__u64 pid_tgid = bpf_get_current_pid_tgid();
+	__u32 *fd_ptr = active_connect_fd_map__lookup(&pid_tgid);
+	if (fd_ptr == NULL)
+		return 0;
+	int sockfd = (int)*fd_ptr;
+	active_connect_fd_map__delete(&pid_tgid);
+#else
+KRETFUNC_PROG(__sys_connect, int fd, struct sockaddr __user * uservaddr,
+	      int addrlen, int ret)
+{
+	int sockfd = (int)fd;
+	__u64 pid_tgid = bpf_get_current_pid_tgid();
+#endif
+	if (ret != 0 && ret != -CONNECT_EINPROGRESS)
+		return 0;
+
+	record_socket_owner(sockfd, (__u32) (pid_tgid >> 32), ROLE_CLIENT);
 	return 0;
 }
 
@@ -2721,11 +3591,9 @@ static __inline int finalize_data_output(void *ctx,
 		 * Use 'buf_size + 1' instead of 'buf_size' to circumvent
 		 * (Linux 4.14.x) length checks.
 		 */
-		bpf_perf_event_output(ctx, &NAME(socket_data),
-				      BPF_F_CURRENT_CPU, v_buff, buf_size + 1);
+		socket_data_output(ctx, v_buff, buf_size + 1);
 	} else {
-		bpf_perf_event_output(ctx, &NAME(socket_data),
-				      BPF_F_CURRENT_CPU, v_buff,
+		socket_data_output(ctx, v_buff,
 				      sizeof(*v_buff));
 	}
 
@@ -3356,17 +4224,9 @@ PERF_EVENT_PROG(push_socket_data) (struct bpf_perf_event_data * ctx) {
 				 * Use 'buf_size + 1' instead of 'buf_size' to circumvent
 				 * (Linux 4.14.x) length checks.
 				 */
-				bpf_perf_event_output(ctx,
-						      &NAME
-						      (socket_data),
-						      BPF_F_CURRENT_CPU,
-						      v_buff, buf_size + 1);
+				socket_data_output(ctx, v_buff, buf_size + 1);
 			} else {
-				bpf_perf_event_output(ctx,
-						      &NAME
-						      (socket_data),
-						      BPF_F_CURRENT_CPU,
-						      v_buff, sizeof(*v_buff));
+				socket_data_output(ctx, v_buff, sizeof(*v_buff));
 			}
 
 			v_buff->events_num = 0;
@@ -3381,3 +4241,4 @@ PERF_EVENT_PROG(push_socket_data) (struct bpf_perf_event_data * ctx) {
 #include "go_tls.bpf.c"
 #include "go_http2.bpf.c"
 #include "openssl.bpf.c"
+#include "dns_resolver.bpf.c"
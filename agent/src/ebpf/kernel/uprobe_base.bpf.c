@@ -27,6 +27,12 @@ struct http2_tcp_seq_key {
 	__u32 tcp_seq_end;
 };
 
+struct http2_stream_key {
+	int tgid;
+	int fd;
+	__u32 stream;
+};
+
 /* *INDENT-OFF* */
 /*
  * In uprobe go_tls_read_exit()
@@ -102,6 +108,24 @@ struct bpf_map_def SEC("maps") pid_tgid_callerid_map = {
 	.feat_flags = FEATURE_FLAG_UPROBE_GOLANG,
 };
 
+/*
+ * The goroutine sending the request of an HTTP2 client stream
+ *
+ * The responses of all the streams multiplexed on a connection are read by
+ * the read loop goroutine of the connection, the responses are attributed to
+ * the requesting goroutine by looking up the stream.
+ *
+ * key: struct http2_stream_key
+ * value: goroutine ID
+ */
+struct bpf_map_def SEC("maps") http2_stream_goid_map = {
+	.type = BPF_MAP_TYPE_LRU_HASH,
+	.key_size = sizeof(struct http2_stream_key),
+	.value_size = sizeof(__u64),
+	.max_entries = HASH_ENTRIES_MAX,
+	.feat_flags = FEATURE_FLAG_UPROBE_GOLANG,
+};
+
 /*
  * Goroutines Map
  * key: {tgid, pid}
@@ -635,8 +659,7 @@ TP_SCHED_PROG(process_exit) (struct sched_comm_exit_ctx *ctx)
 		data.pid = pid;
 		data.meta.event_type = EVENT_TYPE_PROC_EXIT;
 		bpf_get_current_comm(data.name, sizeof(data.name));
-		bpf_perf_event_output(ctx, &NAME(socket_data),
-				      BPF_F_CURRENT_CPU, &data, sizeof(data));
+		socket_data_output(ctx, &data, sizeof(data));
 	}
 
 	bpf_map_delete_elem(&goroutines_map, &id);
@@ -675,8 +698,7 @@ static inline int kernel_clone_exit(bool is_kprobe, bool maybe_thread,
 		data.pid = pid;
 	data.maybe_thread = maybe_thread;
 	bpf_get_current_comm(data.name, sizeof(data.name));
-	bpf_perf_event_output(ctx, &NAME(socket_data),
-			      BPF_F_CURRENT_CPU, &data, sizeof(data));
+	socket_data_output(ctx, &data, sizeof(data));
 
 	return 0;
 }
@@ -722,8 +744,7 @@ TP_SCHED_PROG(process_exec) (struct sched_comm_exec_ctx *ctx)
 		data.pid = pid;
 		data.maybe_thread = false;
 		bpf_get_current_comm(data.name, sizeof(data.name));
-		bpf_perf_event_output(ctx, &NAME(socket_data),
-				      BPF_F_CURRENT_CPU, &data, sizeof(data));
+		socket_data_output(ctx, &data, sizeof(data));
 	}
 
 	return 0;
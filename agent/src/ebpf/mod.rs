@@ -22,6 +22,7 @@ pub use libc::c_int;
 pub use libc::c_uchar; // u8
 pub use libc::c_uint; // u32
 pub use libc::c_ulonglong;
+pub use libc::c_ushort; // u16
 pub use libc::c_void;
 use log::info;
 pub use std::ffi::CStr;
@@ -110,6 +111,8 @@ pub const FEATURE_PROFILE_ONCPU: c_int = 4;
 pub const FEATURE_PROFILE_OFFCPU: c_int = 5;
 #[allow(dead_code)]
 pub const FEATURE_PROFILE_MEMORY: c_int = 6;
+#[allow(dead_code)]
+pub const FEATURE_UPROBE_DNS_RESOLVER: c_int = 9;
 
 //L7层协议是否需要重新核实
 #[allow(dead_code)]
@@ -194,16 +197,9 @@ pub const EVENT_TYPE_PROC_EXIT: u32 = 1 << 6;
 pub const PROFILER_TYPE_UNKNOWN: u8 = 0;
 #[allow(dead_code)]
 pub const PROFILER_TYPE_ONCPU: u8 = 1;
-cfg_if::cfg_if! {
-    if #[cfg(feature = "extended_observability")] {
-        #[allow(dead_code)]
-        pub const PROFILER_TYPE_OFFCPU: u8 = 2;
-        #[allow(dead_code)]
-        pub const PROFILER_TYPE_MEMORY: u8 = 3;
-    }
-}
+pub const PROFILER_TYPE_OFFCPU: u8 = 2;
+pub const PROFILER_TYPE_MEMORY: u8 = 3;
 
-#[cfg(feature = "extended_observability")]
 pub const PROFILER_CTX_MEMORY_IDX: usize = 2;
 pub const PROFILER_CTX_NUM: usize = 3;
 
@@ -227,6 +223,229 @@ pub struct tuple_t {
     pub lport: u16,           // 本地端口
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SOCKET_OWNER_INFO {
+    pub process_id: u32, // tgid of the process which accepted or connected the socket
+    pub role: u32,       // 1: client, 2: server
+    pub cgroup_id: u64,  // 0 if not supported by the kernel
+    pub timestamp: u64,  // time of accept()/connect(), in nanoseconds
+    pub process_kname: [u8; 16usize], // comm in task_struct
+}
+
+// Converts the addresses to the format of the socket tuple in kernel: (local, remote, addr_len)
+fn socket_tuple_addrs(local: IpAddr, remote: IpAddr) -> Option<([u8; 16], [u8; 16], u8)> {
+    match (local, remote) {
+        (IpAddr::V4(l), IpAddr::V4(r)) => {
+            let (mut laddr, mut raddr) = ([0u8; 16], [0u8; 16]);
+            laddr[..4].copy_from_slice(&l.octets());
+            raddr[..4].copy_from_slice(&r.octets());
+            Some((laddr, raddr, 4))
+        }
+        (IpAddr::V6(l), IpAddr::V6(r)) => Some((l.octets(), r.octets(), 16)),
+        _ => None,
+    }
+}
+
+impl SOCKET_OWNER_INFO {
+    // Looks up the owner of the local socket `local` connected with `remote`
+    pub fn lookup(l4_protocol: u8, local: (IpAddr, u16), remote: (IpAddr, u16)) -> Option<Self> {
+        let (laddr, raddr, addr_len) = socket_tuple_addrs(local.0, remote.0)?;
+        let mut info = Self::default();
+        let found = unsafe {
+            socket_owner_lookup(
+                l4_protocol,
+                addr_len,
+                laddr.as_ptr(),
+                local.1,
+                raddr.as_ptr(),
+                remote.1,
+                &mut info,
+            )
+        };
+        found.then_some(info)
+    }
+
+    pub fn process_kname(&self) -> String {
+        let len = self
+            .process_kname
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.process_kname.len());
+        String::from_utf8_lossy(&self.process_kname[..len]).into_owned()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TCP_HEALTH_INFO {
+    pub retrans: u32,   // retransmitted segments
+    pub drops: u32,     // segments dropped by the TCP stack
+    pub srtt_us: u32,   // smoothed RTT in microseconds, 0 if unknown
+    pub state: u32,     // TCP state, e.g. 1 for TCP_ESTABLISHED
+    pub timestamp: u64, // last update, in nanoseconds
+}
+
+impl TCP_HEALTH_INFO {
+    // Looks up the TCP health of the local socket `local` connected with `remote`
+    pub fn lookup(local: (IpAddr, u16), remote: (IpAddr, u16)) -> Option<Self> {
+        let (laddr, raddr, addr_len) = socket_tuple_addrs(local.0, remote.0)?;
+        let mut info = Self::default();
+        let found = unsafe {
+            tcp_health_lookup(
+                addr_len,
+                laddr.as_ptr(),
+                local.1,
+                raddr.as_ptr(),
+                remote.1,
+                &mut info,
+            )
+        };
+        found.then_some(info)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SKB_DROP_INFO {
+    pub reason: [u8; 64usize], // drop reason, e.g. "NO_SOCKET"
+    pub addr_len: u8,          // 4 for IPv4, 16 for IPv6, 0 if unknown
+    pub protocol: u8,          // IP protocol
+    pub dport: u16,            // destination port of TCP/UDP
+    pub saddr: [u8; 16usize],  // source address in network byte order
+    pub daddr: [u8; 16usize],  // destination address in network byte order
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl SKB_DROP_INFO {
+    pub fn reason(&self) -> String {
+        let len = self
+            .reason
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.reason.len());
+        String::from_utf8_lossy(&self.reason[..len]).into_owned()
+    }
+
+    // Returns (source, destination), None if the packet headers are not parsed
+    pub fn addrs(&self) -> Option<(IpAddr, IpAddr)> {
+        match self.addr_len {
+            4 => {
+                let (mut s, mut d) = ([0u8; 4], [0u8; 4]);
+                s.copy_from_slice(&self.saddr[..4]);
+                d.copy_from_slice(&self.daddr[..4]);
+                Some((IpAddr::from(s), IpAddr::from(d)))
+            }
+            16 => Some((IpAddr::from(self.saddr), IpAddr::from(self.daddr))),
+            _ => None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DNS_RESOLVER_INFO {
+    pub pid: u32,
+    pub api: u32, // 0: getaddrinfo, 1: gethostbyname, 2: gethostbyname2
+    pub calls: u64,
+    pub client_errors: u64,    // EAI_NONAME, EAI_NODATA or no result
+    pub server_errors: u64,    // EAI_FAIL and other failures
+    pub timeouts: u64,         // EAI_AGAIN
+    pub latency_sum: u64,      // microseconds
+    pub latency_max: u64,      // microseconds
+    pub queries: u64,          // DNS queries sent by the calls
+    pub loopback_queries: u64, // queries sent to a loopback stub resolver, e.g. systemd-resolved
+    pub no_query_calls: u64,   // calls answered without sending DNS queries
+    pub comm: [u8; 16usize],
+}
+
+impl DNS_RESOLVER_INFO {
+    pub fn api_name(&self) -> &'static str {
+        match self.api {
+            0 => "getaddrinfo",
+            1 => "gethostbyname",
+            2 => "gethostbyname2",
+            _ => "unknown",
+        }
+    }
+
+    pub fn comm(&self) -> String {
+        let len = self
+            .comm
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.comm.len());
+        String::from_utf8_lossy(&self.comm[..len]).into_owned()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FILE_IO_INFO {
+    pub pid: u32,
+    pub major: u32, // device of the file system, as in /proc/<pid>/mountinfo
+    pub minor: u32,
+    pub operation: u32, // 0: write 1: read
+    pub count: u64,
+    pub bytes: u64,
+    pub latency_sum: u64, // microseconds
+    pub latency_max: u64, // microseconds
+    pub comm: [u8; 16usize],
+}
+
+impl FILE_IO_INFO {
+    pub fn comm(&self) -> String {
+        let len = self
+            .comm
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.comm.len());
+        String::from_utf8_lossy(&self.comm[..len]).into_owned()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct BPF_PROG_STATS {
+    pub tracer_name: [u8; 64usize],
+    pub prog_name: [u8; 128usize],
+    pub run_cnt: u64,     // number of times the program has run
+    pub run_time_ns: u64, // total running time in nanoseconds
+}
+
+impl BPF_PROG_STATS {
+    fn to_string(bytes: &[u8]) -> String {
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..len]).into_owned()
+    }
+
+    pub fn tracer_name(&self) -> String {
+        Self::to_string(&self.tracer_name)
+    }
+
+    pub fn prog_name(&self) -> String {
+        Self::to_string(&self.prog_name)
+    }
+}
+
+// Looks up the path of a traced UNIX domain socket, abstract socket names start with '@'
+pub fn lookup_unix_socket_path(socket_id: u64) -> Option<String> {
+    let mut path = [0u8; 108];
+    let found = unsafe {
+        unix_socket_path_lookup(
+            socket_id,
+            path.as_mut_ptr() as *mut c_char,
+            path.len() as c_int,
+        )
+    };
+    if !found {
+        return None;
+    }
+    let len = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+    Some(String::from_utf8_lossy(&path[..len]).into_owned())
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct SK_BPF_DATA {
@@ -259,6 +478,7 @@ pub struct SK_BPF_DATA {
     pub need_reconfirm: bool, // true: 表示eBPF程序对L7协议类型的判断并不确定需要上层重新核实。
     // false: 表示eBPF程序对L7协议类型的判断是有把握的不需要上层重新核实。
     pub is_tls: bool,
+    pub is_unix: bool, // UNIX domain socket, the tuple is 127.0.0.1:0 on both sides
 
     /* trace info */
     pub tcp_seq: u64, // 收发cap_data数据时TCP协议栈将会用到的TCP SEQ，可用于关联eBPF DATA与网络中的TCP Packet
@@ -421,6 +641,9 @@ pub struct SK_TRACE_STATS {
     pub period_push_avg_delay: u64, // The average latency time for periodic push events, in microseconds.
     pub proc_exec_event_count: u64, // The number of events for process execute.
     pub proc_exit_event_count: u64, // The number of events for process exits.
+
+    pub ringbuf_enabled: bool, // The socket data is passed through the ring buffer instead of the perf buffers.
+    pub kern_recv: u64, // The number of socket data received from the ring buffer or perf buffers.
 }
 
 #[repr(C)]
@@ -485,6 +708,111 @@ extern "C" {
      * @return the set maximum buffer size value on success, < 0 on failure.
      */
     pub fn set_data_limit_max(limit_size: c_int) -> c_int;
+    /*
+     * Look up the process owning a connection, recorded when the socket is
+     * accepted or connected, including the sockets whose data is not traced.
+     * Addresses are in network byte order, `addr_len` is 4 or 16.
+     *
+     * @return true if found.
+     */
+    pub fn socket_owner_lookup(
+        l4_protocol: c_uchar,
+        addr_len: c_uchar,
+        laddr: *const c_uchar,
+        lport: c_ushort,
+        raddr: *const c_uchar,
+        rport: c_ushort,
+        info: *mut SOCKET_OWNER_INFO,
+    ) -> bool;
+    /*
+     * Look up the path of a traced UNIX domain socket by the socket ID.
+     *
+     * @return true if found.
+     */
+    pub fn unix_socket_path_lookup(socket_id: u64, path: *mut c_char, size: c_int) -> bool;
+    /*
+     * Trace UNIX domain stream sockets with the syscall hooks, the data is
+     * reported with a loopback tuple and `is_unix` set.
+     */
+    pub fn set_unix_socket_tracing(enabled: bool) -> c_int;
+    /*
+     * Trace io_uring IORING_OP_SEND/IORING_OP_RECV requests (Linux 5.6+),
+     * must be called before running_socket_tracer().
+     */
+    pub fn set_io_uring_tracing(enabled: bool) -> c_int;
+    /*
+     * Look up the TCP retransmissions, drops, RTT and state of a connection,
+     * collected from the tcp tracepoints. Addresses are in network byte order,
+     * `addr_len` is 4 or 16.
+     *
+     * @return true if found.
+     */
+    pub fn tcp_health_lookup(
+        addr_len: c_uchar,
+        laddr: *const c_uchar,
+        lport: c_ushort,
+        raddr: *const c_uchar,
+        rport: c_ushort,
+        info: *mut TCP_HEALTH_INFO,
+    ) -> bool;
+    /*
+     * Collect TCP health with the tcp tracepoints (Linux 4.16+), must be
+     * called before running_socket_tracer().
+     */
+    pub fn set_tcp_health_tracing(enabled: bool) -> c_int;
+    /*
+     * Collect the packets dropped by kfree_skb() since the last call,
+     * aggregated by the drop reason and the packet addresses.
+     *
+     * @return the number of aggregated drops, < 0 on error.
+     */
+    pub fn collect_skb_drops(
+        callback: extern "C" fn(ctx: *mut c_void, info: *mut SKB_DROP_INFO),
+        ctx: *mut c_void,
+    ) -> c_int;
+    /*
+     * Count the packets dropped by kfree_skb() with the drop reasons
+     * (Linux 5.17+), must be called before running_socket_tracer().
+     */
+    pub fn set_skb_drop_monitor(enabled: bool) -> c_int;
+    /*
+     * Collect the regular file reads and writes since the last call,
+     * aggregated by process, device of the file system and operation.
+     *
+     * @return the number of aggregated entries, < 0 on error.
+     */
+    pub fn collect_file_io_stats(
+        callback: extern "C" fn(ctx: *mut c_void, info: *mut FILE_IO_INFO),
+        ctx: *mut c_void,
+    ) -> c_int;
+    /*
+     * Count the regular file IO of vfs_read()/vfs_write(), must be called
+     * before running_socket_tracer().
+     */
+    pub fn set_file_io_monitor(enabled: bool) -> c_int;
+    /*
+     * Pass the socket data through the ring buffer (Linux 5.8+) instead of
+     * the per-CPU perf buffers, falls back to the perf buffers if it is not
+     * supported. Must be called before running_socket_tracer().
+     */
+    pub fn set_ringbuf_enabled(enabled: bool) -> c_int;
+    /*
+     * Set the max entries of an auxiliary map of the socket tracer, e.g.
+     * __socket_owner_map, must be called before running_socket_tracer().
+     *
+     * @return 0 on success, < 0 if the map can not be resized.
+     */
+    pub fn set_socket_map_max_entries(name: *const c_char, entries: c_int) -> c_int;
+    /*
+     * Collect the getaddrinfo()/gethostbyname() calls since the last call,
+     * aggregated by process and resolver API.
+     *
+     * @return the number of aggregated entries, < 0 on error.
+     */
+    pub fn collect_dns_resolver_stats(
+        callback: extern "C" fn(ctx: *mut c_void, info: *mut DNS_RESOLVER_INFO),
+        ctx: *mut c_void,
+    ) -> c_int;
     pub fn set_go_tracing_timeout(timeout: c_int) -> c_int;
     pub fn set_io_event_collect_mode(mode: c_int) -> c_int;
     pub fn set_io_event_minimal_duration(duration: c_ulonglong) -> c_int;
@@ -547,6 +875,7 @@ extern "C" {
 
     pub fn set_uprobe_golang_enabled(enabled: bool) -> c_void;
     pub fn set_uprobe_openssl_enabled(enabled: bool) -> c_void;
+    pub fn set_uprobe_dns_resolver_enabled(enabled: bool) -> c_void;
 
     // 获取socket_tracer的这种统计数据的接口
     pub fn socket_tracer_stats() -> SK_TRACE_STATS;
@@ -565,6 +894,24 @@ extern "C" {
     //            false Define a map without preallocated memory
     pub fn set_bpf_map_prealloc(enabled: bool) -> c_void;
 
+    // Set the BTF file used when the kernel has no BTF of its own (/sys/kernel/btf/vmlinux)
+    // @path : raw BTF or ELF with a .BTF section
+    // @return 0 is success, if not 0 is failed
+    pub fn set_btf_custom_path(path: *const c_char) -> c_int;
+
+    // Enable or disable the kernel statistics of eBPF programs (/proc/sys/kernel/bpf_stats_enabled, Linux 5.1+)
+    // @enabled : true the run count and run time of each program are accumulated
+    // @return 0 is success, if not 0 is failed
+    pub fn set_bpf_stats_enabled(enabled: bool) -> c_int;
+
+    // Collect the accumulated run count and run time of the loaded eBPF programs
+    // @callback : called for each loaded program
+    // @return the number of programs, < 0 on error
+    pub fn collect_bpf_prog_stats(
+        callback: extern "C" fn(ctx: *mut c_void, stats: *mut BPF_PROG_STATS),
+        ctx: *mut c_void,
+    ) -> c_int;
+
     // 参数说明：
     // callback: 回调接口 rust -> C
     // thread_nr: 工作线程数，是指用户态有多少线程参与数据处理。
@@ -714,22 +1061,37 @@ extern "C" {
     pub fn get_dwarf_shard_map_size() -> c_int;
     pub fn set_dwarf_shard_map_size(size: c_int) -> c_void;
 
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "extended_observability")] {
-            pub fn enable_offcpu_profiler() -> c_int;
+    pub fn enable_offcpu_profiler() -> c_int;
 
-            pub fn disable_offcpu_profiler() -> c_int;
+    pub fn disable_offcpu_profiler() -> c_int;
 
-            pub fn set_offcpu_cpuid_aggregation(flag: c_int) -> c_int;
+    /*
+     * Whether CPUID is included in the aggregation of off-CPU stack trace data.
+     * @flag 0: not included, 1: included.
+     */
+    pub fn set_offcpu_cpuid_aggregation(flag: c_int) -> c_int;
 
-            pub fn set_offcpu_minblock_time(
-                block_time: c_uint,
-            ) -> c_int;
+    /*
+     * Set the minimum blocking time of off-CPU profiler, shorter blocking events
+     * are discarded in kernel.
+     * @block_time in microseconds, 0 means no limit.
+     */
+    pub fn set_offcpu_minblock_time(block_time: c_uint) -> c_int;
 
-            pub fn enable_memory_profiler() -> c_int;
+    pub fn enable_memory_profiler() -> c_int;
 
-            pub fn disable_memory_profiler() -> c_int;
+    pub fn disable_memory_profiler() -> c_int;
 
+    /*
+     * Set the sampling of memory profiler.
+     * @interval Sample one of every `interval` allocations, the sampled size is
+     *   multiplied by `interval`.
+     * @max_samples_per_sec The maximum samples per second on each CPU, 0 means no limit.
+     */
+    pub fn set_memory_profiler_sampling(interval: c_uint, max_samples_per_sec: c_uint) -> c_int;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "extended_observability")] {
             /**
              * @brief **set_dpdk_trace_enabled()** DPDK tracing feature enable switch.
              *
@@ -284,6 +284,44 @@ out:
 	return err;
 }
 
+/*
+ * BTF file provided by the agent for kernels without CONFIG_DEBUG_INFO_BTF,
+ * e.g. downloaded from a BTF hub. Both raw BTF and ELF with a .BTF section
+ * are accepted.
+ */
+static char btf_custom_path[PATH_MAX];
+
+int set_btf_custom_path(const char *path)
+{
+	if (path == NULL || strlen(path) >= sizeof(btf_custom_path))
+		return ETR_INVAL;
+
+	snprintf(btf_custom_path, sizeof(btf_custom_path), "%s", path);
+	ebpf_info("Set BTF custom path: %s\n", btf_custom_path);
+	return ETR_OK;
+}
+
+static struct btf *load_custom_btf(void)
+{
+	struct btf *btf;
+
+	if (btf_custom_path[0] == '\0' || access(btf_custom_path, R_OK))
+		return NULL;
+
+	btf = btf__parse_raw(btf_custom_path);
+	if (DF_IS_ERR_OR_NULL(btf))
+		btf = btf__parse_elf(btf_custom_path, NULL);
+
+	if (DF_IS_ERR_OR_NULL(btf)) {
+		ebpf_warning("Failed to parse BTF custom file: %s\n",
+			     btf_custom_path);
+		return NULL;
+	}
+
+	ebpf_info("BTF vmlinux file: %s\n", btf_custom_path);
+	return btf;
+}
+
 static struct btf *ebpf__load_vmlinux_btf(void)
 {
 	/*
@@ -309,6 +347,16 @@ static struct btf *ebpf__load_vmlinux_btf(void)
 	int i;
 
 	for (i = 0; i < ARRAY_SIZE(path_fmt_array); i++) {
+		/*
+		 * The BTF of the running kernel takes precedence, then the
+		 * custom file provided by the agent.
+		 */
+		if (i == 1) {
+			btf = load_custom_btf();
+			if (btf)
+				return btf;
+		}
+
 		snprintf(path, PATH_MAX, path_fmt_array[i], sysinfo.release);
 		if (access(path, R_OK))
 			continue;
@@ -89,6 +89,7 @@ struct btf_ext_info_sec {
 #define BTF_INFO_KFLAG(info)    ((info) >> 31)
 #define BTF_MEM_OFFSET(T, O)    (BTF_INFO_KFLAG((T)) ? BTF_MEMBER_BIT_OFFSET((O)) : (O))
 
+int set_btf_custom_path(const char *path);
 int ebpf_obj__load_vmlinux_btf(struct ebpf_object *obj);
 int kernel_struct_field_offset(struct ebpf_object *obj, const char *struct_name,
 			       const char *field_name);
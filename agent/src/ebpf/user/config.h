@@ -24,8 +24,15 @@
 // eBPF Map Name
 #define MAP_MEMBERS_OFFSET_NAME         "__members_offset"
 #define MAP_SOCKET_INFO_NAME            "__socket_info_map"
+#define MAP_SOCKET_OWNER_NAME           "__socket_owner_map"
+#define MAP_TCP_HEALTH_NAME             "__tcp_health_map"
+#define MAP_SKB_DROP_NAME               "__skb_drop_map"
+#define MAP_DNS_RESOLVER_STATS_NAME     "__dns_resolver_stats_map"
+#define MAP_FILE_IO_STATS_NAME          "__file_io_stats_map"
+#define MAP_UNIX_SOCKET_PATH_NAME       "__unix_socket_path_map"
 #define MAP_TRACE_NAME                  "__trace_map"
 #define MAP_PERF_SOCKET_DATA_NAME       "__socket_data"
+#define MAP_RINGBUF_SOCKET_DATA_NAME    "__socket_data_ringbuf"
 #define MAP_TRACER_CTX_NAME             "__tracer_ctx_map"
 #define MAP_TRACE_STATS_NAME            "__trace_stats_map"
 #define MAP_PROTO_FILTER_NAME		"__protocol_filter"
@@ -117,6 +124,8 @@ enum cfg_feature_idx {
 	FEATURE_PROFILE_MEMORY,
 	FEATURE_SOCKET_TRACER,
 	FEATURE_DWARF_UNWINDING,
+	// getaddrinfo()/gethostbyname() uprobe
+	FEATURE_UPROBE_DNS_RESOLVER,
 	FEATURE_MAX,
 };
 
@@ -128,6 +137,7 @@ enum cfg_feature_idx {
 #define FEATURE_FLAG_PROFILE_MEMORY		(1 << FEATURE_PROFILE_MEMORY)
 #define FEATURE_FLAG_SOCKET_TRACER		(1 << FEATURE_SOCKET_TRACER)
 #define FEATURE_FLAG_DWARF_UNWINDING		(1 << FEATURE_DWARF_UNWINDING)
+#define FEATURE_FLAG_UPROBE_DNS_RESOLVER	(1 << FEATURE_UPROBE_DNS_RESOLVER)
 
 /*
  * When the socket map is recycled, each socket message is recycled without sending
@@ -194,6 +204,25 @@ enum cfg_feature_idx {
 #define MAP_CUSTOM_STACK_B_NAME	"__custom_stack_map_b"
 #define MAP_PROFILER_STATE_NAME	"__profiler_state_map"
 
+/*
+ * off-cpu profiler
+ */
+#define MAP_OFFCPU_BUF_A_NAME		"__offcpu_output_a"
+#define MAP_OFFCPU_BUF_B_NAME		"__offcpu_output_b"
+#define MAP_OFFCPU_STACK_A_NAME		"__offcpu_stack_map_a"
+#define MAP_OFFCPU_STACK_B_NAME		"__offcpu_stack_map_b"
+#define MAP_OFFCPU_STATE_NAME		"__offcpu_state_map"
+#define MAP_OFFCPU_PIDS_NAME		"__offcpu_pids_map"
+
+/*
+ * memory profiler
+ */
+#define MAP_MEMORY_BUF_A_NAME		"__memory_output_a"
+#define MAP_MEMORY_BUF_B_NAME		"__memory_output_b"
+#define MAP_MEMORY_STACK_A_NAME		"__memory_stack_map_a"
+#define MAP_MEMORY_STACK_B_NAME		"__memory_stack_map_b"
+#define MAP_MEMORY_STATE_NAME		"__memory_state_map"
+
 #define STRINGIFIER_STACK_STR_HASH_BUCKETS_NUM	8192
 #define STRINGIFIER_STACK_STR_HASH_MEM_SZ	(1ULL << 30)	// 1Gbytes
 
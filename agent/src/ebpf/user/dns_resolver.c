@@ -0,0 +1,290 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/*
+ * Management of the libc resolver uprobes.
+ *
+ * The uprobes on getaddrinfo()/gethostbyname()/gethostbyname2() are attached
+ * to the processes matched by the feature "ebpf.socket.uprobe.dns" only. The
+ * calls are aggregated in the kernel by process and API, and the DNS queries
+ * sent by the calling thread are counted in them by the socket tracer.
+ */
+
+#include "dns_resolver.h"
+#include "tracer.h"
+#include "proc.h"
+#include "socket.h"
+#include "common.h"
+#include "config.h"
+#include "load.h"
+#include "log.h"
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define LOG_DNS_TAG "[DNS] "
+
+static proc_event_list_t proc_events;
+static bool dns_resolver_trace_enabled;
+
+/* *INDENT-OFF* */
+static struct symbol symbols[] = {
+	{
+		.type = DNS_RESOLVER_UPROBE,
+		.symbol = "getaddrinfo",
+		.probe_func = UPROBE_FUNC_NAME(dns_getaddrinfo_enter),
+		.is_probe_ret = false,
+		.exact_match = true,
+	},
+	{
+		.type = DNS_RESOLVER_UPROBE,
+		.symbol = "getaddrinfo",
+		.probe_func = UPROBE_FUNC_NAME(dns_getaddrinfo_exit),
+		.is_probe_ret = true,
+		.exact_match = true,
+	},
+	{
+		.type = DNS_RESOLVER_UPROBE,
+		.symbol = "gethostbyname",
+		.probe_func = UPROBE_FUNC_NAME(dns_gethostbyname_enter),
+		.is_probe_ret = false,
+		.exact_match = true,
+	},
+	{
+		.type = DNS_RESOLVER_UPROBE,
+		.symbol = "gethostbyname",
+		.probe_func = UPROBE_FUNC_NAME(dns_gethostbyname_exit),
+		.is_probe_ret = true,
+		.exact_match = true,
+	},
+	{
+		.type = DNS_RESOLVER_UPROBE,
+		.symbol = "gethostbyname2",
+		.probe_func = UPROBE_FUNC_NAME(dns_gethostbyname2_enter),
+		.is_probe_ret = false,
+		.exact_match = true,
+	},
+	{
+		.type = DNS_RESOLVER_UPROBE,
+		.symbol = "gethostbyname2",
+		.probe_func = UPROBE_FUNC_NAME(dns_gethostbyname_exit),
+		.is_probe_ret = true,
+		.exact_match = true,
+	},
+};
+/* *INDENT-ON* */
+
+static void dns_resolver_parse_and_register(int pid,
+					    struct tracer_probes_conf *conf)
+{
+	char *path = NULL;
+
+	if (pid <= 1)
+		return;
+
+	if (!is_user_process(pid))
+		return;
+
+	path = get_so_path_by_pid_and_name(pid, "c");
+	if (!path)
+		return;
+
+	ebpf_info(LOG_DNS_TAG "resolver uprobe, pid:%d, path:%s\n", pid, path);
+	add_probe_sym_to_tracer_probes(pid, path, conf, symbols,
+				       NELEMS(symbols));
+	free(path);
+}
+
+static void clear_dns_resolver_probes_by_pid(struct bpf_tracer *tracer,
+					     int pid)
+{
+	struct probe *probe;
+	struct list_head *p, *n;
+	struct symbol_uprobe *sym_uprobe;
+
+	list_for_each_safe(p, n, &tracer->probes_head) {
+		probe = container_of(p, struct probe, list);
+		if (!(probe->type == UPROBE && probe->private_data != NULL))
+			continue;
+		sym_uprobe = probe->private_data;
+
+		if (sym_uprobe->type != DNS_RESOLVER_UPROBE)
+			continue;
+
+		if (sym_uprobe->pid != pid)
+			continue;
+
+		if (probe_detach(probe)) {
+			ebpf_warning(LOG_DNS_TAG "probe_detach failed, "
+				     "path:%s, name:%s\n",
+				     sym_uprobe->binary_path, sym_uprobe->name);
+		}
+		free_probe_from_tracer(probe);
+	}
+}
+
+static void dns_resolver_process_exec(int pid)
+{
+	struct bpf_tracer *tracer = NULL;
+
+	if (!kernel_version_check())
+		return;
+
+	tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL)
+		return;
+
+	if (tracer->probes_count > OPEN_FILES_MAX) {
+		ebpf_warning(LOG_DNS_TAG "Probes count too many. The maximum "
+			     "is %d\n", OPEN_FILES_MAX);
+		return;
+	}
+
+	add_event_to_proc_list(&proc_events, tracer, pid, NULL);
+}
+
+static void dns_resolver_process_exit(int pid)
+{
+	struct bpf_tracer *tracer = NULL;
+
+	tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL)
+		return;
+
+	pthread_mutex_lock(&tracer->mutex_probes_lock);
+	clear_dns_resolver_probes_by_pid(tracer, pid);
+	pthread_mutex_unlock(&tracer->mutex_probes_lock);
+}
+
+void dns_resolver_events_handle(void)
+{
+	struct process_create_event *event = NULL;
+	struct bpf_tracer *tracer = NULL;
+	int count = 0;
+
+	if (!dns_resolver_trace_enabled)
+		return;
+
+	do {
+		event = get_first_event(&proc_events);
+		if (!event)
+			break;
+
+		if (get_sys_uptime() < event->expire_time)
+			break;
+
+		if (event->stime != get_process_starttime(event->pid))
+			goto next;
+
+		tracer = event->tracer;
+		if (tracer) {
+			pthread_mutex_lock(&tracer->mutex_probes_lock);
+			dns_resolver_parse_and_register(event->pid,
+							tracer->tps);
+			tracer_uprobes_update(tracer);
+			tracer_hooks_process(tracer, HOOK_ATTACH, &count);
+			pthread_mutex_unlock(&tracer->mutex_probes_lock);
+		}
+
+	next:
+		remove_event(&proc_events, event);
+		process_event_free(event);
+
+	} while (true);
+}
+
+void dns_resolver_trace_handle(int pid, enum match_pids_act act)
+{
+	if (!dns_resolver_trace_enabled)
+		return;
+
+	if (act == MATCH_PID_ADD) {
+		dns_resolver_process_exec(pid);
+	} else {
+		dns_resolver_process_exit(pid);
+	}
+}
+
+void dns_resolver_trace_init(void)
+{
+	init_list_head(&proc_events.head);
+	pthread_mutex_init(&proc_events.m, NULL);
+}
+
+void set_uprobe_dns_resolver_enabled(bool enabled)
+{
+	dns_resolver_trace_enabled = enabled;
+}
+
+bool is_dns_resolver_trace_enabled(void)
+{
+	return dns_resolver_trace_enabled;
+}
+
+int collect_dns_resolver_stats(dns_resolver_callback_t callback, void *ctx)
+{
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL)
+		return -1;
+
+	struct ebpf_map *map =
+	    ebpf_obj__get_map_by_name(t->obj, MAP_DNS_RESOLVER_STATS_NAME);
+	if (map == NULL) {
+		ebpf_warning(LOG_DNS_TAG "[%s] map(name:%s) is NULL.\n",
+			     __func__, MAP_DNS_RESOLVER_STATS_NAME);
+		return -1;
+	}
+	int map_fd = map->fd;
+
+	// Start from a key not in the map, tgid 0 is never traced.
+	struct dns_resolver_key key = { 0 }, next_key;
+	struct dns_resolver_stats value;
+	struct dns_resolver_info info;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+	int count = 0;
+
+	/*
+	 * The entries are deleted after the iteration, the calls returned in
+	 * between are lost.
+	 */
+	while (bpf_get_next_key(map_fd, &key, &next_key) == 0) {
+		key = next_key;
+		if (bpf_lookup_elem(map_fd, &key, &value) != 0)
+			continue;
+		insert_list(&key, sizeof(key), &clear_elem_head);
+
+		memset(&info, 0, sizeof(info));
+		info.pid = key.tgid;
+		info.api = key.api;
+		info.calls = value.calls;
+		info.client_errors = value.client_errors;
+		info.server_errors = value.server_errors;
+		info.timeouts = value.timeouts;
+		info.latency_sum = value.latency_sum;
+		info.latency_max = value.latency_max;
+		info.queries = value.queries;
+		info.loopback_queries = value.loopback_queries;
+		info.no_query_calls = value.no_query_calls;
+		memcpy(info.comm, value.comm, sizeof(info.comm));
+		info.comm[sizeof(info.comm) - 1] = '\0';
+		callback(ctx, &info);
+		count++;
+	}
+
+	__reclaim_map(map_fd, &clear_elem_head);
+	return count;
+}
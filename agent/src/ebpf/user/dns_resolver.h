@@ -0,0 +1,57 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#ifndef _BPF_DNS_RESOLVER_H_
+#define _BPF_DNS_RESOLVER_H_
+
+#include "tracer.h"
+#include "proc.h"
+
+struct dns_resolver_info {
+	uint32_t pid;
+	uint32_t api;		// 0: getaddrinfo, 1: gethostbyname, 2: gethostbyname2
+	uint64_t calls;
+	uint64_t client_errors;	// EAI_NONAME, EAI_NODATA or no result
+	uint64_t server_errors;	// EAI_FAIL and other failures
+	uint64_t timeouts;	// EAI_AGAIN
+	uint64_t latency_sum;	// Microseconds
+	uint64_t latency_max;	// Microseconds
+	uint64_t queries;	// DNS queries sent by the calls
+	uint64_t loopback_queries;	// Queries sent to a loopback stub resolver
+	uint64_t no_query_calls;	// Calls answered without sending DNS queries
+	char comm[TASK_COMM_LEN];
+};
+
+typedef void (*dns_resolver_callback_t) (void *ctx,
+					 struct dns_resolver_info *info);
+
+/**
+ * Collect the getaddrinfo()/gethostbyname() calls since the last call
+ *
+ * The calls are aggregated by process and resolver API.
+ *
+ * @callback called for each aggregated process and API
+ * @ctx passed to the callback
+ * @return the number of aggregated entries, < 0 on error
+ */
+int collect_dns_resolver_stats(dns_resolver_callback_t callback, void *ctx);
+
+void dns_resolver_events_handle(void);
+void dns_resolver_trace_handle(int pid, enum match_pids_act act);
+void dns_resolver_trace_init(void);
+void set_uprobe_dns_resolver_enabled(bool enabled);
+bool is_dns_resolver_trace_enabled(void);
+#endif
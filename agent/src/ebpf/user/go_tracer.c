@@ -265,6 +265,20 @@ static struct symbol syms[] = {
 		.probe_func = UPROBE_FUNC_NAME(go_http2clientConnReadLoop_handleResponse),
 		.is_probe_ret = false,
 	},
+	// http2 client, mark the goroutine sending the request, the
+	// request headers may be written by a goroutine created for it
+	{
+		.type = GO_UPROBE,
+		.symbol = "net/http.(*Transport).roundTrip",
+		.probe_func = UPROBE_FUNC_NAME(go_http_Transport_roundTrip),
+		.is_probe_ret = false,
+	},
+	{
+		.type = GO_UPROBE,
+		.symbol = "golang.org/x/net/http2.(*Transport).RoundTripOpt",
+		.probe_func = UPROBE_FUNC_NAME(go_http_Transport_roundTrip),
+		.is_probe_ret = false,
+	},
 	// http2 client, fetch request headers
 	// Send multiple header messages
 	{
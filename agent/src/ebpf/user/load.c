@@ -42,6 +42,7 @@
 #include "proc.h"
 #include "go_tracer.h"
 #include "ssl_tracer.h"
+#include "dns_resolver.h"
 #include "profile/perf_profiler.h"
 #include "unwind_tracer.h"
 
@@ -942,16 +943,26 @@ int ebpf_obj_load(struct ebpf_object *obj)
 		if (!is_openssl_trace_enabled()) {
 			enabled_feats &= ~FEATURE_FLAG_UPROBE_OPENSSL;
 		}
+		if (!is_dns_resolver_trace_enabled()) {
+			enabled_feats &= ~FEATURE_FLAG_UPROBE_DNS_RESOLVER;
+		}
 		if (!oncpu_profiler_enabled()) {
 			enabled_feats &= ~FEATURE_FLAG_PROFILE_ONCPU;
 		}
+		if (!offcpu_profiler_enabled()) {
+			enabled_feats &= ~FEATURE_FLAG_PROFILE_OFFCPU;
+		}
+		if (!memory_profiler_enabled()) {
+			enabled_feats &= ~FEATURE_FLAG_PROFILE_MEMORY;
+		}
 		if (!get_dwarf_enabled()) {
 			enabled_feats &= ~FEATURE_FLAG_DWARF_UNWINDING;
 		}
 		enabled_feats &= ~extended_feature_flags(map);
 		if (enabled_feats == 0 &&
 		    map->def.type != BPF_MAP_TYPE_PROG_ARRAY &&
-		    map->def.type != BPF_MAP_TYPE_PERF_EVENT_ARRAY) {
+		    map->def.type != BPF_MAP_TYPE_PERF_EVENT_ARRAY &&
+		    map->def.type != BPF_MAP_TYPE_RINGBUF) {
 			map->def.max_entries = 1;
 		}
 
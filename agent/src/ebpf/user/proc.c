@@ -548,10 +548,7 @@ static int config_symbolizer_proc_info(struct symbolizer_proc_info *p, int pid)
 	if (p->stime == 0)
 		return ETR_INVAL;
 
-	if (strcmp(p->comm, "java") == 0)
-		p->is_java = true;
-	else
-		p->is_java = false;
+	p->is_java = is_java_process(pid, p->comm);
 
 	if ((current_sys_time_secs() - (p->stime / 1000ULL)) >=
 	    PROC_INFO_VERIFY_TIME) {
@@ -639,10 +636,7 @@ void get_process_info_by_pid(pid_t pid, u64 * stime, u64 * netns_id, char *name,
 			memcpy(p->comm, comm, sizeof(p->comm));
 			p->comm[sizeof(p->comm) - 1] = '\0';
 
-			if (strcmp(p->comm, "java") == 0)
-				p->is_java = true;
-			else
-				p->is_java = false;
+			p->is_java = is_java_process(p->pid, p->comm);
 
 			p->verified = true;
 		}
@@ -748,6 +742,16 @@ void *get_symbol_cache(pid_t pid, bool new_cache)
 		symbolizer_proc_lock(p);
 		u64 curr_time = current_sys_time_secs();
 		if (p->verified) {
+			/*
+			 * Methods unloaded from the JVM code cache have been removed
+			 * from the symbol file, and their addresses may be reused by
+			 * other methods. Treat it as unknown symbols being found, so
+			 * that the symbol table is re-obtained in the same way.
+			 */
+			if (p->is_java && p->update_syms_table_time == 0
+			    && java_symbol_file_changed(pid))
+				p->unknown_syms_found = true;
+
 			/*
 			 * If an unknown frame appears during the process of symbolizing
 			 * the address of the Java process, we need to re-obtain the sy-
@@ -1083,14 +1087,13 @@ void remove_event(proc_event_list_t * list, struct process_create_event *event)
 }
 
 // https://github.com/iovisor/bcc/blob/15fccdb9a4dbdc3d41e669a7ad5be73d2ac44b00/src/cc/bcc_proc.c#L419
-static int which_so_in_process(const char *libname, int pid, char *libpath)
+// Find the first mapped shared object whose path contains search1 or search2
+static int find_so_in_process(int pid, const char *search1,
+			      const char *search2, char *libpath)
 {
 	int ret, found = 0;
 	char endline[4096], *mapname = NULL, *newline;
 	char mappings_file[128];
-	const size_t search_len = strlen(libname) + strlen("/lib.");
-	char search1[search_len + 1];
-	char search2[search_len + 1];
 
 	snprintf(mappings_file, sizeof(mappings_file), "/proc/%ld/maps",
 		 (long)pid);
@@ -1098,9 +1101,6 @@ static int which_so_in_process(const char *libname, int pid, char *libpath)
 	if (!fp)
 		return found;
 
-	snprintf(search1, search_len + 1, "/lib%s.", libname);
-	snprintf(search2, search_len + 1, "/lib%s-", libname);
-
 	do {
 		ret = fscanf(fp, "%*x-%*x %*s %*x %*s %*d");
 		if (!fgets(endline, sizeof(endline), fp))
@@ -1126,6 +1126,68 @@ static int which_so_in_process(const char *libname, int pid, char *libpath)
 	return found;
 }
 
+static int which_so_in_process(const char *libname, int pid, char *libpath)
+{
+	const size_t search_len = strlen(libname) + strlen("/lib.");
+	char search1[search_len + 1];
+	char search2[search_len + 1];
+
+	snprintf(search1, search_len + 1, "/lib%s.", libname);
+	snprintf(search2, search_len + 1, "/lib%s-", libname);
+
+	return find_so_in_process(pid, search1, search2, libpath);
+}
+
+// Get the path of a Python extension module such as `_ssl`, which is named
+// like _ssl.cpython-311-x86_64-linux-gnu.so or _ssl.so
+char *get_module_path_by_pid_and_name(int pid, const char *module_name)
+{
+	char so_path[PATH_MAX] = { 0 };
+	const size_t search_len = strlen(module_name) + strlen("/.");
+	char search[search_len + 1];
+
+	int offset = snprintf(so_path, sizeof(so_path), "/proc/%d/root", pid);
+	if (offset < 0 || offset >= sizeof(so_path))
+		return NULL;
+
+	snprintf(search, search_len + 1, "/%s.", module_name);
+	if (!find_so_in_process(pid, search, search, so_path + offset))
+		return NULL;
+	return strdup(so_path);
+}
+
+// Get the path of a library whose file name starts with `prefix`, such as
+// netty-tcnative, which is extracted to a temporary file named like
+// libnetty_tcnative_linux_x86_64<random digits>.so when loaded
+char *get_so_path_by_pid_and_prefix(int pid, const char *prefix)
+{
+	char so_path[PATH_MAX] = { 0 };
+	const size_t search_len = strlen(prefix) + strlen("/");
+	char search[search_len + 1];
+
+	int offset = snprintf(so_path, sizeof(so_path), "/proc/%d/root", pid);
+	if (offset < 0 || offset >= sizeof(so_path))
+		return NULL;
+
+	snprintf(search, search_len + 1, "/%s", prefix);
+	if (!find_so_in_process(pid, search, search, so_path + offset))
+		return NULL;
+	return strdup(so_path);
+}
+
+/*
+ * JVMs are not always started by the 'java' launcher, e.g. application
+ * servers and tools with their own launchers embedding the JVM, which are
+ * detected by the mapped 'libjvm.so'.
+ */
+bool is_java_process(int pid, const char *comm)
+{
+	if (comm != NULL && strcmp(comm, "java") == 0)
+		return true;
+
+	return check_so_path_by_pid_and_name(pid, "jvm");
+}
+
 bool check_so_path_by_pid_and_name(int pid, const char *so_name)
 {
 	char so_path[PATH_MAX] = { 0 };
@@ -1171,6 +1233,7 @@ struct bcc_elf_foreach_sym_payload {
 	const char *name;
 	const char *prefix;
 	const char *symbol_name;
+	bool exact_match;
 };
 
 static int bcc_elf_foreach_sym_callback(const char *name, uint64_t addr,
@@ -1178,7 +1241,14 @@ static int bcc_elf_foreach_sym_callback(const char *name, uint64_t addr,
 {
 	struct bcc_elf_foreach_sym_payload *p = payload;
 	char *pos;
-	if (p->name && (pos = strstr(name, p->name))) {
+	if (p->name && p->exact_match) {
+		if (!strcmp(name, p->name)) {
+			p->addr = addr;
+			p->size = size;
+			p->symbol_name = strdup(name);
+			return -1;
+		}
+	} else if (p->name && (pos = strstr(name, p->name))) {
 		if (pos[strlen(p->name)] == '\0') {
 			p->addr = addr;
 			p->size = size;
@@ -1213,6 +1283,7 @@ int add_probe_sym_to_tracer_probes(int pid, const char *path,
 		// Use memory on the stack, no need to allocate on the heap
 		payload.name = cur->symbol;
 		payload.prefix = cur->symbol_prefix;
+		payload.exact_match = cur->exact_match;
 		ret = bcc_elf_foreach_sym(path, bcc_elf_foreach_sym_callback,
 					  &bcc_elf_foreach_sym_option,
 					  &payload);
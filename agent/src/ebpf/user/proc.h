@@ -221,7 +221,10 @@ void remove_event(proc_event_list_t * list, struct process_create_event *event);
 struct process_create_event *get_first_event(proc_event_list_t * list);
 
 bool check_so_path_by_pid_and_name(int pid, const char *so_name);
+bool is_java_process(int pid, const char *comm);
 char *get_so_path_by_pid_and_name(int pid, const char *so_name);
+char *get_module_path_by_pid_and_name(int pid, const char *module_name);
+char *get_so_path_by_pid_and_prefix(int pid, const char *prefix);
 int add_probe_sym_to_tracer_probes(int pid, const char *path,
 				   struct tracer_probes_conf *conf,
 				   struct symbol symbols[], size_t n_symbols);
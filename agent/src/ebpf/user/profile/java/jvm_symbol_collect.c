@@ -503,6 +503,12 @@ static int update_java_perf_map_file(receiver_args_t * args, char *addr_str)
 				     errno);
 			return -1;
 		}
+		/*
+		 * A refresh requested by the symbolizer is followed by a
+		 * symbol cache update, otherwise notify the symbolizer.
+		 */
+		if (!args->task->need_refresh)
+			args->task->syms_file_changed = true;
 		ebpf_debug
 		    ("=== file update args->task->need_refresh %d pid %d unload_count %d\n",
 		     args->task->need_refresh, args->task->pid, unload_count);
@@ -1069,6 +1075,27 @@ static symbol_collect_task_t *get_task_by_pid(pid_t pid)
 	return task;
 }
 
+bool java_symbol_file_changed(pid_t pid)
+{
+	if (g_collect_pool == NULL)
+		return false;
+
+	bool changed = false;
+	symbol_collect_task_t *task;
+	pthread_mutex_lock(&g_collect_pool->lock);
+	for (int i = 0; i < g_collect_pool->thread_count; i++) {
+		task = g_collect_pool->threads[i].task;
+		if (task == NULL || task->pid != pid)
+			continue;
+		changed = task->syms_file_changed;
+		task->syms_file_changed = false;
+		break;
+	}
+	pthread_mutex_unlock(&g_collect_pool->lock);
+
+	return changed;
+}
+
 int start_java_symbol_collection(pid_t pid, const char *opts)
 {
 	// Initialize a thread pool for managing Java symbols.
@@ -73,6 +73,7 @@ struct task_s {
 	void *(*func) (void *);	/**< Callback function for task processing */
 	bool need_refresh;	/**< Whether the file needs to be refreshed */
 	int update_status;	/**< Symbol file update status */
+	volatile bool syms_file_changed; /**< Unloaded methods were removed from the symbol file, which makes the symbol cache stale */
 	pthread_mutex_t mutex;	/**< Mutex for protecting tasks */
 	pthread_cond_t cond;	/**< Condition variable for notifying updates to files */
 	receiver_args_t args;	/**< Parameters for task processing */
@@ -117,6 +118,19 @@ typedef struct {
  */
 int update_java_symbol_file(pid_t pid, bool *is_new_collector);
 
+/**
+ * @brief Checks if the Java symbol file has changed since the last call.
+ *
+ * The symbol file is rewritten when enough methods have been unloaded
+ * from the JVM code cache. The addresses of the unloaded methods may be
+ * reused by newly compiled methods, so the symbol cache of the process
+ * needs to be rebuilt even if no unknown symbols are found.
+ *
+ * @param pid The Java process ID.
+ * @return true if the symbol file has changed, otherwise false.
+ */
+bool java_symbol_file_changed(pid_t pid);
+
 /**
  * @brief Cleans up a single file in the target namespace.
  * 
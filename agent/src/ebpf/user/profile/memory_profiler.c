@@ -0,0 +1,307 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/*
+ * Management of the memory profiler uprobes.
+ *
+ * The uprobes on libc malloc()/free() and Go runtime.mallocgc() are attached
+ * to the processes matched by the memory profiler only, and detached when
+ * the processes are no longer matched.
+ *
+ * The interfaces are weak, the extended profiler overrides them when it is
+ * built in.
+ */
+
+#include <dirent.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include "../config.h"
+#include "../common.h"
+#include "../log.h"
+#include "../tracer.h"
+#include "../proc.h"
+#include "../go_tracer.h"
+#include "perf_profiler.h"
+#include "memory_profiler.h"
+
+#ifndef AARCH64_MUSL
+#define LOG_MEMORY_TAG "[MEMORY] "
+
+static proc_event_list_t proc_events;
+static bool proc_events_inited;
+
+/* *INDENT-OFF* */
+static struct symbol libc_symbols[] = {
+	{
+		.type = MEMPROF_UPROBE,
+		.symbol = "malloc",
+		.probe_func = UPROBE_FUNC_NAME(memory_malloc_enter),
+		.is_probe_ret = false,
+		.exact_match = true,
+	},
+	{
+		.type = MEMPROF_UPROBE,
+		.symbol = "malloc",
+		.probe_func = UPROBE_FUNC_NAME(memory_malloc_exit),
+		.is_probe_ret = true,
+		.exact_match = true,
+	},
+	{
+		.type = MEMPROF_UPROBE,
+		.symbol = "free",
+		.probe_func = UPROBE_FUNC_NAME(memory_free_enter),
+		.is_probe_ret = false,
+		.exact_match = true,
+	},
+};
+
+static struct symbol go_symbols[] = {
+	{
+		.type = MEMPROF_UPROBE,
+		.symbol = "runtime.mallocgc",
+		.probe_func = UPROBE_FUNC_NAME(memory_go_mallocgc_enter),
+		.is_probe_ret = false,
+		.exact_match = true,
+	},
+};
+/* *INDENT-ON* */
+
+/*
+ * The arguments of runtime.mallocgc() are read from registers, which
+ * requires the register-based calling convention of Go 1.17+.
+ */
+static bool go_register_abi_supported(const char *path)
+{
+	struct version_info go_ver = { 0 };
+	if (!fetch_go_elf_version(path, &go_ver))
+		return false;
+
+	return go_ver.major > 1 || (go_ver.major == 1 && go_ver.minor >= 17);
+}
+
+static void memory_parse_and_register(int pid, struct tracer_probes_conf *conf)
+{
+	char *path = NULL;
+
+	if (pid <= 1)
+		return;
+
+	if (!is_user_process(pid))
+		return;
+
+	path = get_so_path_by_pid_and_name(pid, "c");
+	if (path) {
+		ebpf_info(LOG_MEMORY_TAG "libc uprobe, pid:%d, path:%s\n",
+			  pid, path);
+		add_probe_sym_to_tracer_probes(pid, path, conf, libc_symbols,
+					       NELEMS(libc_symbols));
+		free(path);
+	}
+
+	if (!is_go_process(pid))
+		return;
+
+	path = get_elf_path_by_pid(pid);
+	if (!path)
+		return;
+
+	if (go_register_abi_supported(path)) {
+		ebpf_info(LOG_MEMORY_TAG "golang uprobe, pid:%d, path:%s\n",
+			  pid, path);
+		add_probe_sym_to_tracer_probes(pid, path, conf, go_symbols,
+					       NELEMS(go_symbols));
+	} else {
+		ebpf_info(LOG_MEMORY_TAG "golang uprobe skipped, go1.17+ is "
+			  "required, pid:%d, path:%s\n", pid, path);
+	}
+	free(path);
+}
+
+static void clear_memory_probes_by_pid(struct bpf_tracer *tracer, int pid)
+{
+	struct probe *probe;
+	struct list_head *p, *n;
+	struct symbol_uprobe *sym_uprobe;
+
+	list_for_each_safe(p, n, &tracer->probes_head) {
+		probe = container_of(p, struct probe, list);
+		if (!(probe->type == UPROBE && probe->private_data != NULL))
+			continue;
+		sym_uprobe = probe->private_data;
+
+		if (sym_uprobe->type != MEMPROF_UPROBE)
+			continue;
+
+		if (sym_uprobe->pid != pid)
+			continue;
+
+		if (probe_detach(probe)) {
+			ebpf_warning(LOG_MEMORY_TAG "probe_detach failed, "
+				     "path:%s, name:%s\n",
+				     sym_uprobe->binary_path, sym_uprobe->name);
+		}
+		free_probe_from_tracer(probe);
+	}
+}
+
+int __attribute__ ((weak)) collect_memory_uprobe_syms_from_procfs(struct
+								  tracer_probes_conf
+								  *conf)
+{
+	struct dirent *entry = NULL;
+	DIR *fddir = NULL;
+	int pid = 0;
+	char *path = NULL;
+
+	if (!memory_profiler_enabled())
+		return ETR_OK;
+
+	if (!is_feature_enabled(FEATURE_PROFILE_MEMORY))
+		return ETR_OK;
+
+	fddir = opendir("/proc/");
+	if (!fddir) {
+		ebpf_warning(LOG_MEMORY_TAG "Failed to open /proc/.\n");
+		return ETR_PROC_FAIL;
+	}
+
+	while ((entry = readdir(fddir))) {
+		if (entry->d_type != DT_DIR)
+			continue;
+		pid = atoi(entry->d_name);
+		if (!process_probing_check(pid))
+			continue;
+		path = get_elf_path_by_pid(pid);
+		if (is_feature_matched(FEATURE_PROFILE_MEMORY, pid, path)) {
+			memory_parse_and_register(pid, conf);
+		}
+		free(path);
+	}
+
+	closedir(fddir);
+	return ETR_OK;
+}
+
+static void memory_process_exec(int pid)
+{
+	struct bpf_tracer *tracer = get_profiler_tracer();
+	if (tracer == NULL)
+		return;
+
+	if (tracer->probes_count > OPEN_FILES_MAX) {
+		ebpf_warning(LOG_MEMORY_TAG "Probes count too many. The "
+			     "maximum is %d\n", OPEN_FILES_MAX);
+		return;
+	}
+
+	add_event_to_proc_list(&proc_events, tracer, pid, NULL);
+}
+
+static void memory_process_exit(int pid)
+{
+	struct bpf_tracer *tracer = get_profiler_tracer();
+	if (tracer == NULL)
+		return;
+
+	pthread_mutex_lock(&tracer->mutex_probes_lock);
+	clear_memory_probes_by_pid(tracer, pid);
+	pthread_mutex_unlock(&tracer->mutex_probes_lock);
+}
+
+void __attribute__ ((weak)) memory_events_handle(void)
+{
+	struct process_create_event *event = NULL;
+	struct bpf_tracer *tracer = NULL;
+	int count = 0;
+
+	if (!proc_events_inited)
+		return;
+
+	do {
+		event = get_first_event(&proc_events);
+		if (!event)
+			break;
+
+		if (get_sys_uptime() < event->expire_time)
+			break;
+
+		if (event->stime != get_process_starttime(event->pid))
+			goto next;
+
+		tracer = event->tracer;
+		if (tracer && tracer == get_profiler_tracer()) {
+			pthread_mutex_lock(&tracer->mutex_probes_lock);
+			memory_parse_and_register(event->pid, tracer->tps);
+			tracer_uprobes_update(tracer);
+			tracer_hooks_process(tracer, HOOK_ATTACH, &count);
+			pthread_mutex_unlock(&tracer->mutex_probes_lock);
+		}
+
+	next:
+		remove_event(&proc_events, event);
+		process_event_free(event);
+
+	} while (true);
+}
+
+void __attribute__ ((weak)) memory_match_pid_handle(int feat, int pid,
+						      enum match_pids_act act)
+{
+	if (feat != FEATURE_PROFILE_MEMORY || !memory_profiler_enabled()
+	    || !proc_events_inited)
+		return;
+
+	if (act == MATCH_PID_ADD) {
+		memory_process_exec(pid);
+	} else {
+		memory_process_exit(pid);
+	}
+}
+
+void __attribute__ ((weak)) memory_profiler_init(void)
+{
+	if (proc_events_inited)
+		return;
+
+	init_list_head(&proc_events.head);
+	pthread_mutex_init(&proc_events.m, NULL);
+	proc_events_inited = true;
+}
+
+#else /* defined AARCH64_MUSL */
+
+int __attribute__ ((weak)) collect_memory_uprobe_syms_from_procfs(struct
+								  tracer_probes_conf
+								  *conf)
+{
+	return 0;
+}
+
+void __attribute__ ((weak)) memory_events_handle(void)
+{
+}
+
+void __attribute__ ((weak)) memory_match_pid_handle(int feat, int pid,
+						      enum match_pids_act act)
+{
+}
+
+void __attribute__ ((weak)) memory_profiler_init(void)
+{
+}
+
+#endif /* AARCH64_MUSL */
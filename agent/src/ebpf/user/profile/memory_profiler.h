@@ -0,0 +1,31 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#ifndef DF_USER_MEMORY_PROFILER_H
+#define DF_USER_MEMORY_PROFILER_H
+
+#include "../tracer.h"
+
+// Scan /proc/ to attach uprobes to matched processes when the profiler starts
+int collect_memory_uprobe_syms_from_procfs(struct tracer_probes_conf *conf);
+
+// Attach uprobes to the processes queued by memory_match_pid_handle()
+void memory_events_handle(void);
+
+// Attach or detach uprobes when a process is matched or unmatched
+void memory_match_pid_handle(int feat, int pid, enum match_pids_act act);
+void memory_profiler_init(void);
+#endif /* DF_USER_MEMORY_PROFILER_H */
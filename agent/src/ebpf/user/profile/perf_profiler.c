@@ -47,6 +47,7 @@
 #include "java/config.h"
 #include "java/jvm_symbol_collect.h"
 #include "profile_common.h"
+#include "memory_profiler.h"
 #include "../proc.h"
 #include "../unwind_tracer.h"
 
@@ -56,20 +57,38 @@
 #define CP_PERF_PG_NUM	16
 #define ONCPU_PROFILER_NAME "oncpu"
 #define PROFILER_CTX_ONCPU_IDX THREAD_PROFILER_READER_IDX
+#define OFFCPU_PROFILER_NAME "offcpu"
+#define LOG_OFFCPU_TAG "[OFFCPU] "
+#define PROFILER_CTX_OFFCPU_IDX THREAD_OFFCPU_READER_IDX
+#define MEMORY_PROFILER_NAME "memory"
+#define LOG_MEMORY_TAG "[MEMORY] "
+#define PROFILER_CTX_MEMORY_IDX THREAD_MEMORY_READER_IDX
 #define DEEPFLOW_AGENT_NAME "deepflow-agent"
 
 extern int sys_cpus_count;
 extern int major, minor;
 struct profiler_context *g_ctx_array[PROFILER_CTX_NUM];
 static struct profiler_context oncpu_ctx;
+static struct profiler_context offcpu_ctx;
+static struct profiler_context memory_ctx;
 
 static bool g_enable_oncpu = true;
+static bool g_enable_offcpu = false;
+// The minimum blocking time of off-CPU profiler, in microseconds.
+static u64 g_offcpu_minblock_time = 50;
+static bool g_enable_memory = false;
+// The memory profiler samples one of every 'g_memory_sample_interval'
+// allocations, and at most 'g_memory_max_samples_per_sec' samples per
+// second on each CPU (0 means no limit).
+static u64 g_memory_sample_interval = 1;
+static u64 g_memory_max_samples_per_sec = 10000;
 
 /* Used for handling updates to JAVA symbol files */
 static pthread_t java_syms_update_thread;
 
 extern char linux_release[128];
 extern __thread uword thread_index;
+extern pids_match_hash_t pids_match_hash;
 
 struct bpf_tracer *profiler_tracer;
 
@@ -130,6 +149,67 @@ static void reader_raw_cb(void *cookie, void *raw, int raw_size)
 	atomic64_add(&tracer->recv, 1);
 }
 
+static void offcpu_reader_lost_cb_a(void *cookie, u64 lost)
+{
+	struct bpf_tracer *tracer = profiler_tracer;
+	atomic64_add(&tracer->lost, lost);
+	offcpu_ctx.perf_buf_lost_a_count++;
+}
+
+static void offcpu_reader_lost_cb_b(void *cookie, u64 lost)
+{
+	struct bpf_tracer *tracer = profiler_tracer;
+	atomic64_add(&tracer->lost, lost);
+	offcpu_ctx.perf_buf_lost_b_count++;
+}
+
+static void offcpu_reader_raw_cb(void *cookie, void *raw, int raw_size)
+{
+	if (unlikely(offcpu_ctx.profiler_stop == 1))
+		return;
+
+	struct stack_trace_key_t *v;
+	struct bpf_tracer *tracer = profiler_tracer;
+	v = (struct stack_trace_key_t *)raw;
+
+	int ret = VEC_OK;
+	vec_add1(offcpu_ctx.raw_stack_data, *v, ret);
+	if (ret != VEC_OK) {
+		ebpf_warning("vec add failed\n");
+	}
+
+	atomic64_add(&tracer->recv, 1);
+}
+
+static void memory_reader_lost_cb_a(void *cookie, u64 lost)
+{
+	struct bpf_tracer *tracer = profiler_tracer;
+	atomic64_add(&tracer->lost, lost);
+	memory_ctx.perf_buf_lost_a_count++;
+}
+
+static void memory_reader_lost_cb_b(void *cookie, u64 lost)
+{
+	struct bpf_tracer *tracer = profiler_tracer;
+	atomic64_add(&tracer->lost, lost);
+	memory_ctx.perf_buf_lost_b_count++;
+}
+
+static void memory_reader_raw_cb(void *cookie, void *raw, int raw_size)
+{
+	if (unlikely(memory_ctx.profiler_stop == 1))
+		return;
+
+	struct stack_trace_key_t *v = (struct stack_trace_key_t *)raw;
+	int ret = VEC_OK;
+	vec_add1(memory_ctx.raw_stack_data, *v, ret);
+	if (ret != VEC_OK) {
+		ebpf_warning(LOG_MEMORY_TAG "vec add failed\n");
+	}
+
+	atomic64_add(&profiler_tracer->recv, 1);
+}
+
 static int release_profiler(struct bpf_tracer *tracer)
 {
 	tracer_reader_lock(tracer);
@@ -281,6 +361,188 @@ exit:
 	pthread_exit(NULL);
 }
 
+static void offcpu_reader_work(void *arg)
+{
+	thread_index = THREAD_OFFCPU_READER_IDX;
+	struct bpf_tracer *t = profiler_tracer;
+
+	for (;;) {
+		if (unlikely(offcpu_ctx.profiler_stop == 1)) {
+			if (offcpu_ctx.enable_bpf_profile)
+				set_bpf_run_enabled(t, &offcpu_ctx, 0);
+
+			goto exit;
+		}
+
+		/* See oncpu_reader_work() */
+		if (unlikely(get_socket_tracer_state() != TRACER_RUNNING)) {
+			if (offcpu_ctx.enable_bpf_profile)
+				set_bpf_run_enabled(t, &offcpu_ctx, 0);
+			sleep(1);
+			continue;
+		}
+
+		if (unlikely(!offcpu_ctx.enable_bpf_profile))
+			set_bpf_run_enabled(t, &offcpu_ctx, 1);
+
+		process_bpf_stacktraces(&offcpu_ctx, t);
+	}
+
+exit:
+	output_profiler_status(t, (void *)&offcpu_ctx);
+
+	print_hash_stack_str(&offcpu_ctx.stack_str_hash);
+	if (likely(offcpu_ctx.stack_str_hash.buckets != NULL)) {
+		release_stack_str_hash(&offcpu_ctx.stack_str_hash);
+	}
+
+	print_hash_stack_trace_msg(&offcpu_ctx.msg_hash);
+	if (likely(offcpu_ctx.msg_hash.buckets != NULL)) {
+		push_and_release_stack_trace_msg(&offcpu_ctx,
+						 &offcpu_ctx.msg_hash, true);
+		stack_trace_msg_hash_free(&offcpu_ctx.msg_hash);
+	}
+
+	/* The symbol caches are released by the on-CPU reader if it runs. */
+	if (!g_enable_oncpu)
+		release_symbol_caches();
+
+	t->perf_workers[THREAD_OFFCPU_READER_IDX] = 0;
+	ebpf_info(LOG_OFFCPU_TAG "off-cpu profiler reader-thread exit.\n");
+
+	pthread_exit(NULL);
+}
+
+static void memory_reader_work(void *arg)
+{
+	thread_index = THREAD_MEMORY_READER_IDX;
+	struct bpf_tracer *t = profiler_tracer;
+
+	for (;;) {
+		if (unlikely(memory_ctx.profiler_stop == 1)) {
+			if (memory_ctx.enable_bpf_profile)
+				set_bpf_run_enabled(t, &memory_ctx, 0);
+
+			goto exit;
+		}
+
+		/* See oncpu_reader_work() */
+		if (unlikely(get_socket_tracer_state() != TRACER_RUNNING)) {
+			if (memory_ctx.enable_bpf_profile)
+				set_bpf_run_enabled(t, &memory_ctx, 0);
+			sleep(1);
+			continue;
+		}
+
+		if (unlikely(!memory_ctx.enable_bpf_profile))
+			set_bpf_run_enabled(t, &memory_ctx, 1);
+
+		process_bpf_stacktraces(&memory_ctx, t);
+	}
+
+exit:
+	output_profiler_status(t, (void *)&memory_ctx);
+
+	print_hash_stack_str(&memory_ctx.stack_str_hash);
+	if (likely(memory_ctx.stack_str_hash.buckets != NULL)) {
+		release_stack_str_hash(&memory_ctx.stack_str_hash);
+	}
+
+	print_hash_stack_trace_msg(&memory_ctx.msg_hash);
+	if (likely(memory_ctx.msg_hash.buckets != NULL)) {
+		push_and_release_stack_trace_msg(&memory_ctx,
+						 &memory_ctx.msg_hash, true);
+		stack_trace_msg_hash_free(&memory_ctx.msg_hash);
+	}
+
+	/* The symbol caches are released by the on/off-CPU reader if it runs. */
+	if (!g_enable_oncpu && !g_enable_offcpu)
+		release_symbol_caches();
+
+	t->perf_workers[THREAD_MEMORY_READER_IDX] = 0;
+	ebpf_info(LOG_MEMORY_TAG "memory profiler reader-thread exit.\n");
+
+	pthread_exit(NULL);
+}
+
+static void offcpu_pids_map_update(int pid, enum match_pids_act act)
+{
+	u32 val = 1;
+	if (act == MATCH_PID_ADD) {
+		if (!bpf_table_set_value(profiler_tracer, MAP_OFFCPU_PIDS_NAME,
+					 (u64) pid, &val))
+			ebpf_warning(LOG_OFFCPU_TAG "add pid %d failed.\n",
+				     pid);
+	} else {
+		bpf_table_delete_key(profiler_tracer, MAP_OFFCPU_PIDS_NAME,
+				     (u64) pid);
+	}
+}
+
+static int offcpu_pids_sync_cb(pids_match_hash_kv * kv, void *arg)
+{
+	if (kv->value & FEATURE_FLAG_PROFILE_OFFCPU)
+		offcpu_pids_map_update((int)kv->key, MATCH_PID_ADD);
+	return BIHASH_WALK_CONTINUE;
+}
+
+/*
+ * Keep the processes matched by the off-CPU profiler in sync with the
+ * eBPF map, which is used to filter the scheduling events in kernel.
+ *
+ * The off-CPU interfaces are weak, the extended profiler overrides them
+ * when it is built in.
+ */
+void __attribute__ ((weak)) offcpu_match_pid_handle(int feat, int pid,
+						      enum match_pids_act act)
+{
+	if (feat != FEATURE_PROFILE_OFFCPU || !g_enable_offcpu
+	    || profiler_tracer == NULL)
+		return;
+
+	offcpu_pids_map_update(pid, act);
+}
+
+static int offcpu_state_init(struct bpf_tracer *tracer)
+{
+	set_bpf_run_enabled(tracer, &offcpu_ctx, 0);
+	if (!bpf_table_set_value(tracer, MAP_OFFCPU_STATE_NAME,
+				 MINBLOCK_TIME_IDX, &g_offcpu_minblock_time)) {
+		ebpf_warning(LOG_OFFCPU_TAG "set minblock time failed.\n");
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	/* Processes matched before the profiler was loaded. */
+	pids_match_hash_foreach_key_value_pair(&pids_match_hash,
+					       offcpu_pids_sync_cb, NULL);
+	return ETR_OK;
+}
+
+static int memory_sampling_update(struct bpf_tracer *tracer)
+{
+	if (!bpf_table_set_value(tracer, MAP_MEMORY_STATE_NAME,
+				 SAMPLE_INTERVAL_IDX,
+				 &g_memory_sample_interval)) {
+		ebpf_warning(LOG_MEMORY_TAG "set sample interval failed.\n");
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	if (!bpf_table_set_value(tracer, MAP_MEMORY_STATE_NAME,
+				 RATE_LIMIT_IDX,
+				 &g_memory_max_samples_per_sec)) {
+		ebpf_warning(LOG_MEMORY_TAG "set rate limit failed.\n");
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	return ETR_OK;
+}
+
+static int memory_state_init(struct bpf_tracer *tracer)
+{
+	set_bpf_run_enabled(tracer, &memory_ctx, 0);
+	return memory_sampling_update(tracer);
+}
+
 static int stack_trace_map_capacity(struct bpf_tracer *tracer)
 {
 	/*
@@ -328,6 +590,22 @@ static int create_profiler(struct bpf_tracer *tracer)
 	if ((ret = maps_config(tracer, MAP_STACK_B_NAME, cap)))
 		return ret;
 
+	if (g_enable_offcpu) {
+		if ((ret = maps_config(tracer, MAP_OFFCPU_STACK_A_NAME, cap)))
+			return ret;
+
+		if ((ret = maps_config(tracer, MAP_OFFCPU_STACK_B_NAME, cap)))
+			return ret;
+	}
+
+	if (g_enable_memory) {
+		if ((ret = maps_config(tracer, MAP_MEMORY_STACK_A_NAME, cap)))
+			return ret;
+
+		if ((ret = maps_config(tracer, MAP_MEMORY_STACK_B_NAME, cap)))
+			return ret;
+	}
+
 	if (get_dwarf_enabled() && (major > 5 || (major == 5 && minor >= 2))) {
 		if ((ret = maps_config(tracer, MAP_CUSTOM_STACK_A_NAME, cap))) {
 			return ret;
@@ -417,6 +695,86 @@ static int create_profiler(struct bpf_tracer *tracer)
 		ebpf_info(LOG_CP_TAG "=== oncpu profiler disabled ===\n");
 	}
 
+	if (g_enable_offcpu) {
+		ebpf_info(LOG_OFFCPU_TAG "=== offcpu profiler enabled ===\n");
+		if (offcpu_state_init(tracer))
+			goto error;
+
+		struct bpf_perf_reader *reader_a, *reader_b;
+		reader_a = create_perf_buffer_reader(tracer,
+						     MAP_OFFCPU_BUF_A_NAME,
+						     offcpu_reader_raw_cb,
+						     offcpu_reader_lost_cb_a,
+						     PROFILE_PG_CNT_DEF, 1,
+						     PROFILER_READER_EPOLL_TIMEOUT);
+		if (reader_a == NULL)
+			return ETR_NORESOURCE;
+
+		reader_b = create_perf_buffer_reader(tracer,
+						     MAP_OFFCPU_BUF_B_NAME,
+						     offcpu_reader_raw_cb,
+						     offcpu_reader_lost_cb_b,
+						     PROFILE_PG_CNT_DEF, 1,
+						     PROFILER_READER_EPOLL_TIMEOUT);
+		if (reader_b == NULL) {
+			free_perf_buffer_reader(reader_a);
+			return ETR_NORESOURCE;
+		}
+
+		offcpu_ctx.r_a = reader_a;
+		offcpu_ctx.r_b = reader_b;
+
+		ret = enable_tracer_reader_work("offcpu_reader",
+						THREAD_OFFCPU_READER_IDX,
+						tracer,
+						(void *)&offcpu_reader_work);
+		if (ret) {
+			goto error;
+		}
+	} else {
+		ebpf_info(LOG_OFFCPU_TAG "=== offcpu profiler disabled ===\n");
+	}
+
+	if (g_enable_memory) {
+		ebpf_info(LOG_MEMORY_TAG "=== memory profiler enabled ===\n");
+		if (memory_state_init(tracer))
+			goto error;
+
+		struct bpf_perf_reader *reader_a, *reader_b;
+		reader_a = create_perf_buffer_reader(tracer,
+						     MAP_MEMORY_BUF_A_NAME,
+						     memory_reader_raw_cb,
+						     memory_reader_lost_cb_a,
+						     PROFILE_PG_CNT_DEF, 1,
+						     PROFILER_READER_EPOLL_TIMEOUT);
+		if (reader_a == NULL)
+			return ETR_NORESOURCE;
+
+		reader_b = create_perf_buffer_reader(tracer,
+						     MAP_MEMORY_BUF_B_NAME,
+						     memory_reader_raw_cb,
+						     memory_reader_lost_cb_b,
+						     PROFILE_PG_CNT_DEF, 1,
+						     PROFILER_READER_EPOLL_TIMEOUT);
+		if (reader_b == NULL) {
+			free_perf_buffer_reader(reader_a);
+			return ETR_NORESOURCE;
+		}
+
+		memory_ctx.r_a = reader_a;
+		memory_ctx.r_b = reader_b;
+
+		ret = enable_tracer_reader_work("memory_reader",
+						THREAD_MEMORY_READER_IDX,
+						tracer,
+						(void *)&memory_reader_work);
+		if (ret) {
+			goto error;
+		}
+	} else {
+		ebpf_info(LOG_MEMORY_TAG "=== memory profiler disabled ===\n");
+	}
+
 	if (tracer_probes_init(tracer))
 		return (-1);
 
@@ -610,6 +968,10 @@ void print_cp_tracer_status(void)
 	if (profiler_tracer == NULL)
 		return;
 	output_profiler_status(profiler_tracer, (void *)&oncpu_ctx);
+	if (g_enable_offcpu)
+		output_profiler_status(profiler_tracer, (void *)&offcpu_ctx);
+	if (g_enable_memory)
+		output_profiler_status(profiler_tracer, (void *)&memory_ctx);
 	extended_print_cp_tracer_status();
 }
 
@@ -754,6 +1116,24 @@ int start_continuous_profiler(int freq, int java_syms_update_delay,
 			      NANOSEC_PER_SEC / freq,
 			      cb_ctx[PROFILER_CTX_ONCPU_IDX]);
 	g_ctx_array[PROFILER_CTX_ONCPU_IDX] = &oncpu_ctx;
+	profiler_context_init(&offcpu_ctx, OFFCPU_PROFILER_NAME, LOG_OFFCPU_TAG,
+			      PROFILER_TYPE_OFFCPU, g_enable_offcpu,
+			      MAP_OFFCPU_STATE_NAME,
+			      MAP_OFFCPU_STACK_A_NAME, MAP_OFFCPU_STACK_B_NAME,
+			      "", "", true, true, 0,
+			      cb_ctx[PROFILER_CTX_OFFCPU_IDX]);
+	g_ctx_array[PROFILER_CTX_OFFCPU_IDX] = &offcpu_ctx;
+	/*
+	 * The count of memory profiler is the allocated size in bytes, and
+	 * a free() is output with zero size.
+	 */
+	profiler_context_init(&memory_ctx, MEMORY_PROFILER_NAME, LOG_MEMORY_TAG,
+			      PROFILER_TYPE_MEMORY, g_enable_memory,
+			      MAP_MEMORY_STATE_NAME,
+			      MAP_MEMORY_STACK_A_NAME, MAP_MEMORY_STACK_B_NAME,
+			      "", "", true, false, 0,
+			      cb_ctx[PROFILER_CTX_MEMORY_IDX]);
+	g_ctx_array[PROFILER_CTX_MEMORY_IDX] = &memory_ctx;
 
 	if ((java_syms_update_delay < JAVA_SYMS_UPDATE_DELAY_MIN) ||
 	    (java_syms_update_delay > JAVA_SYMS_UPDATE_DELAY_MAX))
@@ -799,6 +1179,30 @@ int start_continuous_profiler(int freq, int java_syms_update_delay,
 	memset(tps, 0, sizeof(*tps));
 	init_list_head(&tps->uprobe_syms_head);
 	CP_PROFILE_SET_PROBES(tps);
+	if (g_enable_offcpu) {
+		/*
+		 * 'finish_task_switch' may have a suffix such as '.isra.0'
+		 * added by the compiler optimization.
+		 */
+		if (kallsyms_lookup_name("finish_task_switch"))
+			probes_set_enter_symbol(tps, "finish_task_switch");
+		else if (kallsyms_lookup_name("finish_task_switch.isra.0"))
+			probes_set_enter_symbol(tps,
+						"finish_task_switch.isra.0");
+		else {
+			ebpf_warning(LOG_OFFCPU_TAG "finish_task_switch() not "
+				     "found, offcpu profiler disabled.\n");
+			g_enable_offcpu = false;
+			offcpu_ctx.profiler_stop = 1;
+		}
+
+		if (g_enable_offcpu)
+			tps_set_symbol(tps, "tracepoint/sched/sched_switch");
+	}
+	if (g_enable_memory) {
+		memory_profiler_init();
+		collect_memory_uprobe_syms_from_procfs(tps);
+	}
 	collect_extended_uprobe_syms_from_procfs(tps);
 
 	struct bpf_tracer *tracer =
@@ -970,6 +1374,112 @@ bool oncpu_profiler_enabled(void) {
 	return g_enable_oncpu;
 }
 
+int __attribute__ ((weak)) enable_offcpu_profiler(void)
+{
+	g_enable_offcpu = true;
+	ebpf_info(LOG_OFFCPU_TAG "Set offcpu profiler enable.\n");
+	return 0;
+}
+
+int __attribute__ ((weak)) disable_offcpu_profiler(void)
+{
+	g_enable_offcpu = false;
+	ebpf_info(LOG_OFFCPU_TAG "Set offcpu profiler disable.\n");
+	return 0;
+}
+
+bool __attribute__ ((weak)) offcpu_profiler_enabled(void) {
+	return g_enable_offcpu;
+}
+
+int __attribute__ ((weak)) set_offcpu_cpuid_aggregation(int flag)
+{
+	if (flag != 0 && flag != 1) {
+		ebpf_info(LOG_OFFCPU_TAG
+			  "Set 'cpu_aggregation_flag' parameter invalid.\n");
+		return (-1);
+	}
+
+	offcpu_ctx.cpu_aggregation_flag = (u64) flag;
+
+	ebpf_info(LOG_OFFCPU_TAG
+		  "Set 'cpu_aggregation_flag' successful, value %d\n", flag);
+	return (0);
+}
+
+/*
+ * Set the minimum blocking time of off-CPU profiler, blocking events
+ * shorter than it are discarded in kernel.
+ *
+ * @block_time The minimum blocking time in microseconds, 0 means no limit.
+ * @return 0 on success, and a negative value on failure.
+ */
+int __attribute__ ((weak)) set_offcpu_minblock_time(unsigned int block_time)
+{
+	g_offcpu_minblock_time = block_time;
+	if (profiler_tracer != NULL && g_enable_offcpu) {
+		if (!bpf_table_set_value(profiler_tracer, MAP_OFFCPU_STATE_NAME,
+					 MINBLOCK_TIME_IDX,
+					 &g_offcpu_minblock_time)) {
+			ebpf_warning(LOG_OFFCPU_TAG
+				     "Set 'minblock_time' failed.\n");
+			return (-1);
+		}
+	}
+
+	ebpf_info(LOG_OFFCPU_TAG "Set 'minblock_time' successful, value %uus\n",
+		  block_time);
+	return (0);
+}
+
+/*
+ * The memory profiler interfaces are weak, the extended profiler overrides
+ * them when it is built in.
+ */
+int __attribute__ ((weak)) enable_memory_profiler(void)
+{
+	g_enable_memory = true;
+	ebpf_info(LOG_MEMORY_TAG "Set memory profiler enable.\n");
+	return 0;
+}
+
+int __attribute__ ((weak)) disable_memory_profiler(void)
+{
+	g_enable_memory = false;
+	ebpf_info(LOG_MEMORY_TAG "Set memory profiler disable.\n");
+	return 0;
+}
+
+bool __attribute__ ((weak)) memory_profiler_enabled(void) {
+	return g_enable_memory;
+}
+
+/*
+ * Set the sampling of memory profiler.
+ *
+ * @interval Sample one of every 'interval' allocations, the sampled size
+ *           is multiplied by 'interval'. 0 is treated as 1.
+ * @max_samples_per_sec The maximum samples per second on each CPU,
+ *                      0 means no limit.
+ * @return 0 on success, and a negative value on failure.
+ */
+int __attribute__ ((weak)) set_memory_profiler_sampling(unsigned int interval,
+							  unsigned int
+							  max_samples_per_sec)
+{
+	g_memory_sample_interval = interval > 0 ? interval : 1;
+	g_memory_max_samples_per_sec = max_samples_per_sec;
+	if (profiler_tracer != NULL && g_enable_memory) {
+		if (memory_sampling_update(profiler_tracer))
+			return (-1);
+	}
+
+	ebpf_info(LOG_MEMORY_TAG "Set sampling successful, interval %lu "
+		  "max_samples_per_sec %lu\n", g_memory_sample_interval,
+		  g_memory_max_samples_per_sec);
+	return (0);
+}
+
 #else /* defined AARCH64_MUSL */
 #include "../tracer.h"
 #include "perf_profiler.h"
@@ -1036,6 +1546,56 @@ bool oncpu_profiler_enabled(void) {
 	return false;
 }
 
+int __attribute__ ((weak)) enable_offcpu_profiler(void)
+{
+	return 0;
+}
+
+int __attribute__ ((weak)) disable_offcpu_profiler(void)
+{
+	return 0;
+}
+
+bool __attribute__ ((weak)) offcpu_profiler_enabled(void) {
+	return false;
+}
+
+int __attribute__ ((weak)) set_offcpu_cpuid_aggregation(int flag)
+{
+	return (-1);
+}
+
+int __attribute__ ((weak)) set_offcpu_minblock_time(unsigned int block_time)
+{
+	return (-1);
+}
+
+void __attribute__ ((weak)) offcpu_match_pid_handle(int feat, int pid,
+						      enum match_pids_act act)
+{
+}
+
+int __attribute__ ((weak)) enable_memory_profiler(void)
+{
+	return 0;
+}
+
+int __attribute__ ((weak)) disable_memory_profiler(void)
+{
+	return 0;
+}
+
+bool __attribute__ ((weak)) memory_profiler_enabled(void) {
+	return false;
+}
+
+int __attribute__ ((weak)) set_memory_profiler_sampling(unsigned int interval,
+							  unsigned int
+							  max_samples_per_sec)
+{
+	return (-1);
+}
+
 void print_cp_tracer_status(void)
 {
 }
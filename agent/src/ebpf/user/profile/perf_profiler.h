@@ -208,6 +208,17 @@ int check_profiler_running_pid(int pid);
 int check_profiler_is_running(void);
 int write_profiler_running_pid(void);
 bool oncpu_profiler_enabled(void);
+bool offcpu_profiler_enabled(void);
+int enable_offcpu_profiler(void);
+int disable_offcpu_profiler(void);
+int set_offcpu_cpuid_aggregation(int flag);
+int set_offcpu_minblock_time(unsigned int block_time);
+void offcpu_match_pid_handle(int feat, int pid, enum match_pids_act act);
+bool memory_profiler_enabled(void);
+int enable_memory_profiler(void);
+int disable_memory_profiler(void);
+int set_memory_profiler_sampling(unsigned int interval,
+				 unsigned int max_samples_per_sec);
 void print_cp_tracer_status(void);
 void output_profiler_status(struct bpf_tracer *t, void *context);
 #endif /* DF_USER_PERF_PROFILER_H */
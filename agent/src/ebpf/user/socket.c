@@ -33,6 +33,8 @@
 #include "log.h"
 #include "go_tracer.h"
 #include "ssl_tracer.h"
+#include "dns_resolver.h"
+#include "profile/memory_profiler.h"
 #include "unwind_tracer.h"
 #include "load.h"
 #include "btf_core.h"
@@ -46,6 +48,8 @@
 #include "socket_trace_bpf_kylin.c"
 #include "socket_trace_bpf_kfunc.c"
 #include "socket_trace_bpf_rt.c"
+#include "socket_trace_bpf_5_2_plus_ringbuf.c"
+#include "socket_trace_bpf_kfunc_ringbuf.c"
 
 static enum linux_kernel_type g_k_type;
 static struct list_head events_list;	// Use for extra register events
@@ -108,6 +112,49 @@ static uint32_t socket_data_limit_max;
 
 static uint32_t go_tracing_timeout = GO_TRACING_TIMEOUT_DEFAULT;
 
+// Trace UNIX domain stream sockets, set by set_unix_socket_tracing()
+static bool unix_socket_enabled;
+
+// Trace io_uring send/recv requests, set by set_io_uring_tracing()
+static bool io_uring_enabled;
+
+// Collect TCP health of connections, set by set_tcp_health_tracing()
+static bool tcp_health_enabled;
+// Offset of 'srtt' in tracepoint tcp/tcp_probe, 0 if not attached
+static uint16_t tcp_probe_srtt_offset;
+
+// Collect packets dropped by kfree_skb(), set by set_skb_drop_monitor()
+static bool skb_drop_enabled;
+// Offsets of 'protocol' and 'reason' in tracepoint skb/kfree_skb, 0 if not attached
+static uint16_t kfree_skb_protocol_offset;
+static uint16_t kfree_skb_reason_offset;
+// Names of enum skb_drop_reason, from the format of tracepoint skb/kfree_skb
+#define SKB_DROP_REASON_MAX 256
+static char *skb_drop_reason_names[SKB_DROP_REASON_MAX];
+
+// Collect regular file IO of vfs_read()/vfs_write(), set by set_file_io_monitor()
+static bool file_io_enabled;
+
+// Pass the socket data through the ring buffer, set by set_ringbuf_enabled()
+static bool ringbuf_enabled;
+// The ring buffer is used, if enabled and supported by the kernel
+static bool ringbuf_used;
+// Socket data dropped by the ring buffer when the stats were last read
+static uint64_t ringbuf_lost_last;
+
+// Max entries of the auxiliary maps, set by set_socket_map_max_entries()
+static const char *resizable_map_names[] = {
+	MAP_SOCKET_OWNER_NAME,
+	MAP_UNIX_SOCKET_PATH_NAME,
+	MAP_TCP_HEALTH_NAME,
+	MAP_SKB_DROP_NAME,
+	MAP_DNS_RESOLVER_STATS_NAME,
+	MAP_FILE_IO_STATS_NAME,
+};
+#define RESIZABLE_MAP_NUM \
+	(sizeof(resizable_map_names) / sizeof(resizable_map_names[0]))
+static int resizable_map_entries[RESIZABLE_MAP_NUM];
+
 // 0: disable 1: during request 2: all
 static uint32_t io_event_collect_mode = 1;
 static uint64_t io_event_minimal_duration = 1000000;
@@ -214,7 +261,7 @@ static void config_probes_for_kfunc(struct tracer_probes_conf *tps)
 #endif
 	kfunc_set_symbol(tps, "__sys_socket", true);
 	kfunc_set_symbol(tps, "__sys_accept4", true);
-	kfunc_set_symbol(tps, "__sys_connect", false);
+	kfunc_set_sym_for_entry_and_exit(tps, "__sys_connect");
 	if (access(SYSCALL_FORK_TP_PATH, F_OK)) {
 		/*
 		 * Different CPU architectures have variations in system calls.
@@ -311,6 +358,7 @@ static void config_probes_for_kprobe_and_tracepoint(struct tracer_probes_conf
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_readv");
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_accept");
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_accept4");
+	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_connect");
 	// process execute
 	if (!access(SYSCALL_FORK_TP_PATH, F_OK))
 		tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_fork");
@@ -324,12 +372,221 @@ static void config_probes_for_kprobe_and_tracepoint(struct tracer_probes_conf
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_enter_close");
 }
 
+/*
+ * io_uring IORING_OP_SEND/IORING_OP_RECV are available since Linux 5.6, the
+ * programs are only built into the kfunc and 5.2+ eBPF binaries.
+ */
+static void config_probes_for_io_uring(struct tracer_probes_conf *tps)
+{
+	static const char *syms[] = {
+		"io_sendmsg_prep", "io_recvmsg_prep", "io_send", "io_recv",
+		"sock_sendmsg", "sock_recvmsg"
+	};
+	int i;
+
+	if (!io_uring_enabled)
+		return;
+
+	if (!(g_k_type == K_TYPE_KFUNC || g_k_type == K_TYPE_VER_5_2_PLUS)
+	    || k_version < KERNEL_VERSION(5, 6, 0)) {
+		ebpf_info("io_uring tracing requires Linux 5.6+, skipped.\n");
+		return;
+	}
+
+	for (i = 0; i < NELEMS(syms); i++) {
+		if (!kallsyms_lookup_name(syms[i])) {
+			ebpf_info("io_uring tracing skipped, the kernel function"
+				  " '%s' is not found.\n", syms[i]);
+			return;
+		}
+	}
+
+	probes_set_enter_symbol(tps, "io_sendmsg_prep");
+	probes_set_enter_symbol(tps, "io_recvmsg_prep");
+	probes_set_enter_symbol(tps, "io_send");
+	probes_set_enter_symbol(tps, "io_recv");
+	probes_set_exit_symbol(tps, "io_send");
+	probes_set_exit_symbol(tps, "io_recv");
+	probes_set_exit_symbol(tps, "sock_sendmsg");
+	probes_set_exit_symbol(tps, "sock_recvmsg");
+	ebpf_info("io_uring tracing is enabled.\n");
+}
+
+/*
+ * Get the offset of 'field' from the format file of a tracepoint, e.g.
+ * "field:__u32 srtt;	offset:84;	size:4;	signed:0;"
+ *
+ * @return offset on success, -1 if not found.
+ */
+static int get_tracepoint_field_offset(const char *format_path,
+				       const char *field)
+{
+	char line[256], name[64];
+	int offset = -1, off;
+	FILE *fp = fopen(format_path, "r");
+	if (fp == NULL)
+		return -1;
+
+	snprintf(name, sizeof(name), " %s;", field);
+	while (fgets(line, sizeof(line), fp)) {
+		char *p = strstr(line, name);
+		if (p == NULL)
+			continue;
+		p = strstr(p, "offset:");
+		if (p != NULL && sscanf(p, "offset:%d;", &off) == 1) {
+			offset = off;
+			break;
+		}
+	}
+
+	fclose(fp);
+	return offset;
+}
+
+static void config_probes_for_tcp_health(struct tracer_probes_conf *tps)
+{
+	int offset;
+
+	tcp_probe_srtt_offset = 0;
+	if (!tcp_health_enabled)
+		return;
+
+	if (access(TCP_RETRANSMIT_TP_PATH, F_OK)
+	    || access(INET_SOCK_SET_STATE_TP_PATH, F_OK)) {
+		ebpf_info("TCP health requires the tracepoints "
+			  "tcp/tcp_retransmit_skb and sock/inet_sock_set_state "
+			  "(Linux 4.16+), skipped.\n");
+		return;
+	}
+
+	tps_set_symbol(tps, "tracepoint/sock/inet_sock_set_state");
+	tps_set_symbol(tps, "tracepoint/tcp/tcp_retransmit_skb");
+
+	offset =
+	    get_tracepoint_field_offset(TCP_PROBE_TP_PATH "/format", "srtt");
+	if (offset > 0) {
+		tcp_probe_srtt_offset = offset;
+		tps_set_symbol(tps, "tracepoint/tcp/tcp_probe");
+	} else {
+		ebpf_info("TCP health: RTT is not collected, 'srtt' of the "
+			  "tracepoint tcp/tcp_probe is not found.\n");
+	}
+
+	if (kallsyms_lookup_name("tcp_drop"))
+		probes_set_enter_symbol(tps, "tcp_drop");
+	else if (kallsyms_lookup_name("tcp_drop_reason"))
+		probes_set_enter_symbol(tps, "tcp_drop_reason");
+	else
+		ebpf_info("TCP health: drops are not collected, the kernel "
+			  "function tcp_drop() is not found.\n");
+
+	ebpf_info("TCP health is enabled.\n");
+}
+
+/*
+ * The names of the drop reasons differ between kernel versions, load them
+ * from the print format of the tracepoint, e.g.
+ * "__print_symbolic(REC->reason, { 2, "NOT_SPECIFIED" }, { 3, "NO_SOCKET" }, ...)"
+ */
+static int load_skb_drop_reason_names(const char *format_path)
+{
+#define FORMAT_SIZE_MAX (64 * 1024)
+	char name[SKB_DROP_REASON_NAME_LEN];
+	unsigned int reason;
+	int count = 0;
+	FILE *fp = fopen(format_path, "r");
+	if (fp == NULL)
+		return -1;
+
+	char *buf = calloc(1, FORMAT_SIZE_MAX);
+	if (buf == NULL) {
+		fclose(fp);
+		return -1;
+	}
+	size_t len = fread(buf, 1, FORMAT_SIZE_MAX - 1, fp);
+	fclose(fp);
+	buf[len] = '\0';
+
+	char *p = strstr(buf, "REC->reason");
+	while (p != NULL && (p = strchr(p, '{')) != NULL) {
+		if (sscanf(p, "{ %u, \"%63[^\"]\" }", &reason, name) == 2
+		    && reason < SKB_DROP_REASON_MAX) {
+			free(skb_drop_reason_names[reason]);
+			skb_drop_reason_names[reason] = strdup(name);
+			count++;
+		}
+		p++;
+	}
+
+	free(buf);
+	return count;
+}
+
+static void config_probes_for_skb_drop(struct tracer_probes_conf *tps)
+{
+	int protocol_offset, reason_offset;
+
+	kfree_skb_protocol_offset = kfree_skb_reason_offset = 0;
+	if (!skb_drop_enabled)
+		return;
+
+	if (k_version < KERNEL_VERSION(5, 17, 0)) {
+		ebpf_info("Drop monitor requires the drop reasons of the "
+			  "tracepoint skb/kfree_skb (Linux 5.17+), skipped.\n");
+		return;
+	}
+
+	protocol_offset =
+	    get_tracepoint_field_offset(KFREE_SKB_TP_PATH "/format",
+					"protocol");
+	reason_offset =
+	    get_tracepoint_field_offset(KFREE_SKB_TP_PATH "/format", "reason");
+	if (protocol_offset <= 0 || reason_offset <= 0) {
+		ebpf_info("Drop monitor skipped, 'protocol' or 'reason' of the "
+			  "tracepoint skb/kfree_skb is not found.\n");
+		return;
+	}
+
+	if (load_skb_drop_reason_names(KFREE_SKB_TP_PATH "/format") <= 0)
+		ebpf_info("Drop monitor: the names of the drop reasons are "
+			  "not found, numbers are used.\n");
+
+	kfree_skb_protocol_offset = protocol_offset;
+	kfree_skb_reason_offset = reason_offset;
+	tps_set_symbol(tps, "tracepoint/skb/kfree_skb");
+	ebpf_info("Drop monitor is enabled.\n");
+}
+
+static void config_probes_for_file_io(struct tracer_probes_conf *tps)
+{
+	if (!file_io_enabled)
+		return;
+
+	if (!kallsyms_lookup_name("vfs_read")
+	    || !kallsyms_lookup_name("vfs_write")) {
+		ebpf_info("File IO monitor skipped, the kernel function "
+			  "vfs_read() or vfs_write() is not found.\n");
+		return;
+	}
+
+	probes_set_enter_symbol(tps, "vfs_read");
+	probes_set_exit_symbol(tps, "vfs_read");
+	probes_set_enter_symbol(tps, "vfs_write");
+	probes_set_exit_symbol(tps, "vfs_write");
+	ebpf_info("File IO monitor is enabled.\n");
+}
+
 static void socket_tracer_set_probes(struct tracer_probes_conf *tps)
 {
 	if (g_k_type == K_TYPE_KFUNC)
 		config_probes_for_kfunc(tps);
 	else
 		config_probes_for_kprobe_and_tracepoint(tps);
+
+	config_probes_for_io_uring(tps);
+	config_probes_for_tcp_health(tps);
+	config_probes_for_skb_drop(tps);
+	config_probes_for_file_io(tps);
 }
 
 /* ==========================================================
@@ -866,6 +1123,7 @@ static void reader_raw_cb(void *cookie, void *raw, int raw_size)
 		   fwd_info->cpu_id, fwd_info->queue_id);
 	struct bpf_tracer *tracer = g_tracer;
 	struct event_meta *ev_meta = raw;
+	atomic64_inc(&tracer->recv);
 
 	/*
 	 * If 0 < ev_meta->event_type < EVENT_TYPE_MIN is 'socket data buffer'
@@ -932,8 +1190,15 @@ static void reader_raw_cb(void *cookie, void *raw, int raw_size)
 			return;
 	}
 
-	/* Determine which queue to distribute to based on the first socket_data. */
-	q_idx = fwd_info->queue_id;
+	/*
+	 * Determine which queue to distribute to based on the first socket_data.
+	 * The data of all CPUs are in the ring buffer, so the data of a socket
+	 * always goes to the same queue in order.
+	 */
+	if (fwd_info->cpu_id < 0)
+		q_idx = sd->socket_id % tracer->dispatch_workers_nr;
+	else
+		q_idx = fwd_info->queue_id;
 	q = &tracer->queues[q_idx];
 
 	if (buf->events_num > MAX_EVENTS_BURST) {
@@ -1016,6 +1281,7 @@ static void reader_raw_cb(void *cookie, void *raw, int raw_size)
 			submit_data->thread_id = sd->pid;
 			submit_data->coroutine_id = sd->coroutine_id;
 			submit_data->is_tls = sd->is_tls;
+			submit_data->is_unix = sd->is_unix;
 			if (sd->source == DATA_SOURCE_GO_TLS_UPROBE ||
 			    sd->source == DATA_SOURCE_OPENSSL_UPROBE)
 				submit_data->is_tls = true;
@@ -1351,6 +1617,8 @@ static void process_events_handle_main(__unused void *arg)
 
 		go_process_events_handle();
 		ssl_events_handle();
+		dns_resolver_events_handle();
+		memory_events_handle();
 		extended_events_handle();
 		unwind_events_handle();
 		check_datadump_timeout();
@@ -1497,6 +1765,56 @@ static int update_offset_map_from_btf_vmlinux(struct bpf_tracer *t)
 	    kernel_struct_field_offset(obj, "sock_common", "skc_state");
 	int struct_sock_common_ipv6only_offset =
 	    struct_sock_skc_state_offset + 1;
+	/*
+	 * Optional, UNIX domain sockets are still traced without the paths if
+	 * not found, e.g. the unix module is not built into the kernel.
+	 */
+	int struct_unix_sock_addr_offset =
+	    kernel_struct_field_offset(obj, "unix_sock", "addr");
+	int struct_unix_sock_peer_offset =
+	    kernel_struct_field_offset(obj, "unix_sock", "peer");
+	int struct_unix_address_name_offset =
+	    kernel_struct_field_offset(obj, "unix_address", "name");
+	if (struct_unix_sock_addr_offset <= 0 ||
+	    struct_unix_sock_peer_offset <= 0 ||
+	    struct_unix_address_name_offset <= 0) {
+		struct_unix_sock_addr_offset = 0;
+		struct_unix_sock_peer_offset = 0;
+		struct_unix_address_name_offset = 0;
+	}
+	/*
+	 * Optional, the drop monitor only counts the drop reasons if not
+	 * found.
+	 */
+	int struct_sk_buff_len_offset =
+	    kernel_struct_field_offset(obj, "sk_buff", "len");
+	int struct_sk_buff_head_offset =
+	    kernel_struct_field_offset(obj, "sk_buff", "head");
+	int struct_sk_buff_network_header_offset =
+	    kernel_struct_field_offset(obj, "sk_buff", "network_header");
+	int struct_sk_buff_transport_header_offset =
+	    kernel_struct_field_offset(obj, "sk_buff", "transport_header");
+	if (struct_sk_buff_len_offset <= 0 ||
+	    struct_sk_buff_head_offset <= 0 ||
+	    struct_sk_buff_network_header_offset <= 0 ||
+	    struct_sk_buff_transport_header_offset <= 0) {
+		struct_sk_buff_len_offset = 0;
+		struct_sk_buff_head_offset = 0;
+		struct_sk_buff_network_header_offset = 0;
+		struct_sk_buff_transport_header_offset = 0;
+	}
+	/*
+	 * Optional, the file IO monitor counts nothing if not found.
+	 */
+	int struct_inode_i_sb_offset =
+	    kernel_struct_field_offset(obj, "inode", "i_sb");
+	int struct_super_block_s_dev_offset =
+	    kernel_struct_field_offset(obj, "super_block", "s_dev");
+	if (struct_inode_i_sb_offset <= 0 ||
+	    struct_super_block_s_dev_offset < 0) {
+		struct_inode_i_sb_offset = 0;
+		struct_super_block_s_dev_offset = 0;
+	}
 
 	if (copied_seq_offs < 0 || write_seq_offs < 0 || files_offs < 0 ||
 	    sk_flags_offs < 0 || struct_files_struct_fdt_offset < 0 ||
@@ -1547,6 +1865,24 @@ static int update_offset_map_from_btf_vmlinux(struct bpf_tracer *t)
 		  struct_sock_skc_state_offset);
 	ebpf_info("    struct_sock_common_ipv6only_offset: 0x%x\n",
 		  struct_sock_common_ipv6only_offset);
+	ebpf_info("    struct_unix_sock_addr_offset: 0x%x\n",
+		  struct_unix_sock_addr_offset);
+	ebpf_info("    struct_unix_sock_peer_offset: 0x%x\n",
+		  struct_unix_sock_peer_offset);
+	ebpf_info("    struct_unix_address_name_offset: 0x%x\n",
+		  struct_unix_address_name_offset);
+	ebpf_info("    struct_sk_buff_len_offset: 0x%x\n",
+		  struct_sk_buff_len_offset);
+	ebpf_info("    struct_sk_buff_head_offset: 0x%x\n",
+		  struct_sk_buff_head_offset);
+	ebpf_info("    struct_sk_buff_network_header_offset: 0x%x\n",
+		  struct_sk_buff_network_header_offset);
+	ebpf_info("    struct_sk_buff_transport_header_offset: 0x%x\n",
+		  struct_sk_buff_transport_header_offset);
+	ebpf_info("    struct_inode_i_sb_offset: 0x%x\n",
+		  struct_inode_i_sb_offset);
+	ebpf_info("    struct_super_block_s_dev_offset: 0x%x\n",
+		  struct_super_block_s_dev_offset);
 
 	struct bpf_offset_param offset;
 	offset.ready = 1;
@@ -1571,6 +1907,19 @@ static int update_offset_map_from_btf_vmlinux(struct bpf_tracer *t)
 	offset.struct_sock_skc_state_offset = struct_sock_skc_state_offset;
 	offset.struct_sock_common_ipv6only_offset =
 	    struct_sock_common_ipv6only_offset;
+	offset.struct_unix_sock_addr_offset = struct_unix_sock_addr_offset;
+	offset.struct_unix_sock_peer_offset = struct_unix_sock_peer_offset;
+	offset.struct_unix_address_name_offset =
+	    struct_unix_address_name_offset;
+	offset.struct_sk_buff_len_offset = struct_sk_buff_len_offset;
+	offset.struct_sk_buff_head_offset = struct_sk_buff_head_offset;
+	offset.struct_sk_buff_network_header_offset =
+	    struct_sk_buff_network_header_offset;
+	offset.struct_sk_buff_transport_header_offset =
+	    struct_sk_buff_transport_header_offset;
+	offset.struct_inode_i_sb_offset = struct_inode_i_sb_offset;
+	offset.struct_super_block_s_dev_offset =
+	    struct_super_block_s_dev_offset;
 
 	if (update_offsets_table(t, &offset) != ETR_OK) {
 		ebpf_warning("Update offsets map failed.\n");
@@ -1716,6 +2065,95 @@ int set_data_limit_max(int limit_size)
 	return set_val;
 }
 
+int set_unix_socket_tracing(bool enabled)
+{
+	unix_socket_enabled = enabled;
+
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL) {
+		return 0;
+	}
+
+	int cpu;
+	int nr_cpus = get_num_possible_cpus();
+	struct tracer_ctx_s values[nr_cpus];
+	memset(values, 0, sizeof(values));
+
+	if (!bpf_table_get_value(tracer, MAP_TRACER_CTX_NAME, 0, values)) {
+		ebpf_warning("Get map '%s' failed.\n", MAP_TRACER_CTX_NAME);
+		return ETR_NOTEXIST;
+	}
+
+	for (cpu = 0; cpu < nr_cpus; cpu++) {
+		values[cpu].unix_socket_enabled = enabled;
+	}
+
+	if (!bpf_table_set_value
+	    (tracer, MAP_TRACER_CTX_NAME, 0, (void *)&values)) {
+		ebpf_warning("Set '%s' failed\n", MAP_TRACER_CTX_NAME);
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	return 0;
+}
+
+int set_io_uring_tracing(bool enabled)
+{
+	/*
+	 * The probes are attached when the socket tracer starts, so this must
+	 * be called before running_socket_tracer().
+	 */
+	io_uring_enabled = enabled;
+	return 0;
+}
+
+int set_tcp_health_tracing(bool enabled)
+{
+	// Like set_io_uring_tracing(), called before running_socket_tracer().
+	tcp_health_enabled = enabled;
+	return 0;
+}
+
+int set_skb_drop_monitor(bool enabled)
+{
+	// Like set_io_uring_tracing(), called before running_socket_tracer().
+	skb_drop_enabled = enabled;
+	return 0;
+}
+
+int set_file_io_monitor(bool enabled)
+{
+	// Like set_io_uring_tracing(), called before running_socket_tracer().
+	file_io_enabled = enabled;
+	return 0;
+}
+
+int set_ringbuf_enabled(bool enabled)
+{
+	// Like set_io_uring_tracing(), called before running_socket_tracer().
+	ringbuf_enabled = enabled;
+	return 0;
+}
+
+int set_socket_map_max_entries(const char *name, int entries)
+{
+	/*
+	 * The maps are resized when the eBPF object is loaded, so this must
+	 * be called before running_socket_tracer(). The socket info map and
+	 * trace map are sized by the arguments of running_socket_tracer().
+	 */
+	int i;
+	for (i = 0; i < RESIZABLE_MAP_NUM; i++) {
+		if (strcmp(resizable_map_names[i], name) == 0) {
+			resizable_map_entries[i] = entries;
+			return 0;
+		}
+	}
+
+	ebpf_warning("Map '%s' can not be resized.\n", name);
+	return -EINVAL;
+}
+
 int set_go_tracing_timeout(int timeout)
 {
 	go_tracing_timeout = timeout;
@@ -1964,9 +2402,35 @@ static void perf_buffer_read(void *arg)
 	/* return NULL; */
 }
 
+static void ringbuf_read(void *arg)
+{
+	thread_index = THREAD_SOCK_READER_IDX_BASE;	// for bihash
+	if (thread_index > thread_index_max)
+		thread_index_max = thread_index;
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL) {
+		ebpf_warning("find_bpf_tracer() error\n");
+		return;
+	}
+
+	for (;;) {
+		if (ringbuf_reader_poll(tracer->ringbuf_reader) < 0)
+			ebpf_warning("ringbuf_reader_poll() failed, %s\n",
+				     strerror(errno));
+	}
+}
+
 static int perf_read_workers_setup(struct bpf_tracer *tracer)
 {
 	int i, ret;
+	/*
+	 * The ring buffer is shared by all CPUs and read by one thread,
+	 * the data is spread over the dispatch queues by socket.
+	 */
+	if (tracer->ringbuf_reader != NULL)
+		return enable_tracer_reader_work("sk-reader", 0, tracer,
+						 (void *)&ringbuf_read);
+
 	struct bpf_perf_reader *r = &tracer->readers[0];
 	for (i = 0; i < r->epoll_fds_count; i++) {
 		ret = enable_tracer_reader_work("sk-reader", i,
@@ -2076,9 +2540,18 @@ static int select_bpf_binary(char load_name[NAME_LEN], void **bin_buffer,
 		   && get_kfunc_params_num(TEST_KFUNC_NAME) ==
 		   TEST_KFUNC_PARAMS_NUM) {
 		g_k_type = K_TYPE_KFUNC;
-		snprintf(load_name, NAME_LEN, "socket-trace-bpf-linux-kfunc");
-		bpf_bin_buffer = (void *)socket_trace_kfunc_ebpf_data;
-		buffer_sz = sizeof(socket_trace_kfunc_ebpf_data);
+		if (ringbuf_used) {
+			snprintf(load_name, NAME_LEN,
+				 "socket-trace-bpf-linux-kfunc-ringbuf");
+			bpf_bin_buffer =
+			    (void *)socket_trace_kfunc_ringbuf_ebpf_data;
+			buffer_sz = sizeof(socket_trace_kfunc_ringbuf_ebpf_data);
+		} else {
+			snprintf(load_name, NAME_LEN,
+				 "socket-trace-bpf-linux-kfunc");
+			bpf_bin_buffer = (void *)socket_trace_kfunc_ebpf_data;
+			buffer_sz = sizeof(socket_trace_kfunc_ebpf_data);
+		}
 	} else if (strcmp(sys_type_str, "ky10") == 0) {
 		g_k_type = K_TYPE_KYLIN;
 		snprintf(load_name, NAME_LEN, "socket-trace-bpf-linux-kylin");
@@ -2086,10 +2559,20 @@ static int select_bpf_binary(char load_name[NAME_LEN], void **bin_buffer,
 		buffer_sz = sizeof(socket_trace_kylin_ebpf_data);
 	} else if (major > 5 || (major == 5 && minor >= 2)) {
 		g_k_type = K_TYPE_VER_5_2_PLUS;
-		snprintf(load_name, NAME_LEN,
-			 "socket-trace-bpf-linux-5.2_plus");
-		bpf_bin_buffer = (void *)socket_trace_5_2_plus_ebpf_data;
-		buffer_sz = sizeof(socket_trace_5_2_plus_ebpf_data);
+		if (ringbuf_used) {
+			snprintf(load_name, NAME_LEN,
+				 "socket-trace-bpf-linux-5.2_plus-ringbuf");
+			bpf_bin_buffer =
+			    (void *)socket_trace_5_2_plus_ringbuf_ebpf_data;
+			buffer_sz =
+			    sizeof(socket_trace_5_2_plus_ringbuf_ebpf_data);
+		} else {
+			snprintf(load_name, NAME_LEN,
+				 "socket-trace-bpf-linux-5.2_plus");
+			bpf_bin_buffer =
+			    (void *)socket_trace_5_2_plus_ebpf_data;
+			buffer_sz = sizeof(socket_trace_5_2_plus_ebpf_data);
+		}
 	} else if (major == 3 && minor == 10) {
 		g_k_type = K_TYPE_VER_3_10;
 		snprintf(load_name, NAME_LEN, "socket-trace-bpf-linux-3.10.0");
@@ -2102,6 +2585,10 @@ static int select_bpf_binary(char load_name[NAME_LEN], void **bin_buffer,
 		buffer_sz = sizeof(socket_trace_common_ebpf_data);
 	}
 
+	// Only the kfunc and 5.2+ binaries are built with the ring buffer.
+	if (g_k_type != K_TYPE_KFUNC && g_k_type != K_TYPE_VER_5_2_PLUS)
+		ringbuf_used = false;
+
 	*bin_buffer = bpf_bin_buffer;
 	*bin_buf_size = buffer_sz;
 	return 0;
@@ -2187,6 +2674,14 @@ int running_socket_tracer(tracer_callback_t handle,
 		return -EINVAL;
 	}
 
+	if (ringbuf_enabled) {
+		ringbuf_used = ringbuf_map_supported();
+		if (!ringbuf_used)
+			ebpf_warning("The ring buffer is not supported by the "
+				     "kernel (Linux 5.8+ required), use the "
+				     "perf buffers instead.\n");
+	}
+
 	select_bpf_binary(bpf_load_buffer_name, &bpf_bin_buffer, &buffer_sz,
 			  false);
 
@@ -2215,6 +2710,7 @@ int running_socket_tracer(tracer_callback_t handle,
 	socket_tracer_set_probes(tps);
 	golang_trace_init();
 	openssl_trace_init();
+	dns_resolver_trace_init();
 	create_and_init_proc_info_caches();
 
 	struct bpf_tracer *tracer =
@@ -2244,6 +2740,29 @@ int running_socket_tracer(tracer_callback_t handle,
 
 	conf_max_trace_entries = max_trace_entries;
 
+	int i;
+	for (i = 0; i < RESIZABLE_MAP_NUM; i++) {
+		if (resizable_map_entries[i] <= 0)
+			continue;
+		if ((ret = maps_config(tracer, resizable_map_names[i],
+				       resizable_map_entries[i])))
+			return ret;
+	}
+
+	/*
+	 * The ring buffer is shared by all CPUs, a quarter of the memory of the
+	 * perf buffers is used, but not less than the perf buffer of one CPU.
+	 */
+	uint32_t pages_cnt = perf_pages_cnt > 0 ?
+	    1 << min_log2(perf_pages_cnt) : BPF_PERF_READER_PAGE_CNT;
+	uint64_t ringbuf_size = (uint64_t) getpagesize() * pages_cnt;
+	if (sys_cpus_count > 4)
+		ringbuf_size *= 1 << min_log2(sys_cpus_count / 4);
+	if (ringbuf_used &&
+	    (ret = maps_config(tracer, MAP_RINGBUF_SOCKET_DATA_NAME,
+			       ringbuf_size)))
+		return ret;
+
 	bool has_attempted = false;
 retry_load:
 	if (tracer_bpf_load(tracer)) {
@@ -2259,17 +2778,25 @@ retry_load:
 	}
 
 	/*
-	 * create reader for read perf buffer data. 
+	 * create reader for read perf buffer or ring buffer data.
 	 */
-	struct bpf_perf_reader *reader;
-	reader = create_perf_buffer_reader(tracer,
-					   MAP_PERF_SOCKET_DATA_NAME,
-					   reader_raw_cb,
-					   reader_lost_cb,
-					   perf_pages_cnt,
-					   thread_nr, PERF_READER_TIMEOUT_DEF);
-	if (reader == NULL)
-		return -EINVAL;
+	if (ringbuf_used) {
+		if (create_ringbuf_reader(tracer, MAP_RINGBUF_SOCKET_DATA_NAME,
+					  reader_raw_cb,
+					  PERF_READER_TIMEOUT_DEF) == NULL)
+			return -EINVAL;
+	} else {
+		struct bpf_perf_reader *reader;
+		reader = create_perf_buffer_reader(tracer,
+						   MAP_PERF_SOCKET_DATA_NAME,
+						   reader_raw_cb,
+						   reader_lost_cb,
+						   perf_pages_cnt,
+						   thread_nr,
+						   PERF_READER_TIMEOUT_DEF);
+		if (reader == NULL)
+			return -EINVAL;
+	}
 
 	if (tracer_probes_init(tracer))
 		return -EINVAL;
@@ -2308,6 +2835,11 @@ retry_load:
 		t_conf[cpu].io_event_minimal_duration =
 		    io_event_minimal_duration;
 		t_conf[cpu].disable_tracing = g_disable_syscall_tracing;
+		t_conf[cpu].unix_socket_enabled = unix_socket_enabled;
+		t_conf[cpu].tcp_probe_srtt_offset = tcp_probe_srtt_offset;
+		t_conf[cpu].kfree_skb_protocol_offset =
+		    kfree_skb_protocol_offset;
+		t_conf[cpu].kfree_skb_reason_offset = kfree_skb_reason_offset;
 		if (!g_disable_syscall_tracing)
 			t_conf[cpu].go_tracing_timeout = go_tracing_timeout;
 	}
@@ -2470,6 +3002,7 @@ static bool bpf_stats_map_collect(struct bpf_tracer *tracer,
 	stats_total->period_event_max_delay = value.period_event_max_delay;
 	stats_total->period_event_total_time = value.period_event_total_time;
 	stats_total->period_event_count = value.period_event_count;
+	stats_total->ringbuf_lost = value.ringbuf_lost;
 	return true;
 }
 
@@ -2570,6 +3103,204 @@ static bool is_adapt_success(struct bpf_tracer *t)
 	return is_success;
 }
 
+bool socket_owner_lookup(uint8_t l4_protocol, uint8_t addr_len,
+			 const uint8_t * laddr, uint16_t lport,
+			 const uint8_t * raddr, uint16_t rport,
+			 struct socket_owner_info *info)
+{
+	if (addr_len != 4 && addr_len != 16)
+		return false;
+
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL)
+		return false;
+
+	struct __tuple_t key;
+	memset(&key, 0, sizeof(key));
+	memcpy(key.rcv_saddr, laddr, addr_len);
+	memcpy(key.daddr, raddr, addr_len);
+	key.addr_len = addr_len;
+	key.l4_protocol = l4_protocol;
+	key.num = lport;
+	key.dport = rport;
+
+	struct socket_owner owner;
+	if (!bpf_table_get(t, MAP_SOCKET_OWNER_NAME, &key, &owner))
+		return false;
+
+	info->tgid = owner.tgid;
+	info->role = owner.role;
+	info->cgroup_id = owner.cgroup_id;
+	info->timestamp = owner.timestamp + get_sysboot_time_ns();
+	memcpy(info->comm, owner.comm, sizeof(info->comm));
+	info->comm[sizeof(info->comm) - 1] = '\0';
+	return true;
+}
+
+bool tcp_health_lookup(uint8_t addr_len, const uint8_t * laddr,
+		       uint16_t lport, const uint8_t * raddr, uint16_t rport,
+		       struct tcp_health_info *info)
+{
+	if (addr_len != 4 && addr_len != 16)
+		return false;
+
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL)
+		return false;
+
+	struct __tuple_t key;
+	memset(&key, 0, sizeof(key));
+	memcpy(key.rcv_saddr, laddr, addr_len);
+	memcpy(key.daddr, raddr, addr_len);
+	key.addr_len = addr_len;
+	key.l4_protocol = IPPROTO_TCP;
+	key.num = lport;
+	key.dport = rport;
+
+	struct tcp_health health;
+	if (!bpf_table_get(t, MAP_TCP_HEALTH_NAME, &key, &health))
+		return false;
+
+	info->retrans = health.retrans;
+	info->drops = health.drops;
+	info->srtt_us = health.srtt_us;
+	info->state = health.state;
+	info->timestamp = health.timestamp + get_sysboot_time_ns();
+	return true;
+}
+
+int collect_skb_drops(skb_drop_callback_t callback, void *ctx)
+{
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL)
+		return -1;
+
+	struct ebpf_map *map =
+	    ebpf_obj__get_map_by_name(t->obj, MAP_SKB_DROP_NAME);
+	if (map == NULL) {
+		ebpf_warning("[%s] map(name:%s) is NULL.\n", __func__,
+			     MAP_SKB_DROP_NAME);
+		return -1;
+	}
+	int map_fd = map->fd;
+
+	// Start from a key not in the map, reason 0 may be a valid key.
+	struct skb_drop_key key = {.reason = ~0U }, next_key;
+	struct skb_drop_value value;
+	struct skb_drop_info info;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+	int count = 0;
+
+	/*
+	 * The entries are deleted after the iteration, the drops counted in
+	 * between are lost.
+	 */
+	while (bpf_get_next_key(map_fd, &key, &next_key) == 0) {
+		key = next_key;
+		if (bpf_lookup_elem(map_fd, &key, &value) != 0)
+			continue;
+		insert_list(&key, sizeof(key), &clear_elem_head);
+
+		const char *name = key.reason < SKB_DROP_REASON_MAX ?
+		    skb_drop_reason_names[key.reason] : NULL;
+		// Not dropped, e.g. freed by consume_skb() since Linux 6.3
+		if (name != NULL && (!strcmp(name, "NOT_DROPPED_YET") ||
+				     !strcmp(name, "CONSUMED")))
+			continue;
+
+		memset(&info, 0, sizeof(info));
+		if (name != NULL)
+			snprintf(info.reason, sizeof(info.reason), "%s", name);
+		else
+			snprintf(info.reason, sizeof(info.reason), "%u",
+				 key.reason);
+		info.addr_len = key.addr_len;
+		info.protocol = key.protocol;
+		info.dport = key.dport;
+		memcpy(info.saddr, key.saddr, sizeof(info.saddr));
+		memcpy(info.daddr, key.daddr, sizeof(info.daddr));
+		info.packets = value.packets;
+		info.bytes = value.bytes;
+		callback(ctx, &info);
+		count++;
+	}
+
+	__reclaim_map(map_fd, &clear_elem_head);
+	return count;
+}
+
+int collect_file_io_stats(file_io_callback_t callback, void *ctx)
+{
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL)
+		return -1;
+
+	struct ebpf_map *map =
+	    ebpf_obj__get_map_by_name(t->obj, MAP_FILE_IO_STATS_NAME);
+	if (map == NULL) {
+		ebpf_warning("[%s] map(name:%s) is NULL.\n", __func__,
+			     MAP_FILE_IO_STATS_NAME);
+		return -1;
+	}
+	int map_fd = map->fd;
+
+	// Start from a key not in the map, tgid 0 is the idle task.
+	struct file_io_key key = { 0 }, next_key;
+	struct file_io_stats value;
+	struct file_io_info info;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+	int count = 0;
+
+	/*
+	 * The entries are deleted after the iteration, the IO counted in
+	 * between is lost.
+	 */
+	while (bpf_get_next_key(map_fd, &key, &next_key) == 0) {
+		key = next_key;
+		if (bpf_lookup_elem(map_fd, &key, &value) != 0)
+			continue;
+		insert_list(&key, sizeof(key), &clear_elem_head);
+
+		memset(&info, 0, sizeof(info));
+		info.pid = key.tgid;
+		// MKDEV() of the kernel, MINORBITS is 20
+		info.major = key.dev >> 20;
+		info.minor = key.dev & ((1U << 20) - 1);
+		info.operation = key.operation;
+		info.count = value.count;
+		info.bytes = value.bytes;
+		info.latency_sum = value.latency_sum;
+		info.latency_max = value.latency_max;
+		memcpy(info.comm, value.comm, sizeof(info.comm));
+		info.comm[sizeof(info.comm) - 1] = '\0';
+		callback(ctx, &info);
+		count++;
+	}
+
+	__reclaim_map(map_fd, &clear_elem_head);
+	return count;
+}
+
+bool unix_socket_path_lookup(uint64_t socket_id, char *path, int size)
+{
+	if (size <= 0)
+		return false;
+
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL)
+		return false;
+
+	struct unix_socket_path value;
+	if (!bpf_table_get(t, MAP_UNIX_SOCKET_PATH_NAME, &socket_id, &value))
+		return false;
+
+	value.path[sizeof(value.path) - 1] = '\0';
+	snprintf(path, size, "%s", value.path);
+	return true;
+}
+
 struct socket_trace_stats socket_tracer_stats(void)
 {
 	struct socket_trace_stats stats;
@@ -2581,6 +3312,9 @@ struct socket_trace_stats socket_tracer_stats(void)
 
 	stats.kern_lost = atomic64_read(&t->lost);
 	atomic64_init(&t->lost);
+	stats.kern_recv = atomic64_read(&t->recv);
+	atomic64_init(&t->recv);
+	stats.ringbuf_enabled = ringbuf_used;
 	stats.worker_num = t->dispatch_workers_nr;
 	stats.perf_pages_cnt = t->readers[0].perf_pages_cnt;
 	stats.queue_capacity = t->queues[0].ring_size;
@@ -2605,6 +3339,10 @@ struct socket_trace_stats socket_tracer_stats(void)
 			stats.period_push_avg_delay =
 			    (stats_total.period_event_total_time /
 			     stats_total.period_event_count) / NS_IN_USEC;
+		// Never reset by the kernel, counted since the last read.
+		stats.kern_lost +=
+		    stats_total.ringbuf_lost - ringbuf_lost_last;
+		ringbuf_lost_last = stats_total.ringbuf_lost;
 		if (!bpf_stats_map_update(t, -1, -1, 0, 0, 0, 0)) {
 			ebpf_warning("Update trace statistics failed.\n");
 		}
@@ -3161,4 +3899,6 @@ void uprobe_match_pid_handle(int feat, int pid, enum match_pids_act act)
 		golang_trace_handle(pid, act);
 	else if (feat == FEATURE_UPROBE_OPENSSL)
 		openssl_trace_handle(pid, act);
+	else if (feat == FEATURE_UPROBE_DNS_RESOLVER)
+		dns_resolver_trace_handle(pid, act);
 }
@@ -25,6 +25,10 @@
 #define SYSCALL_FORK_TP_PATH "/sys/kernel/debug/tracing/events/syscalls/sys_exit_fork"
 #define SYSCALL_CLONE_TP_PATH "/sys/kernel/debug/tracing/events/syscalls/sys_exit_clone"
 #define FTRACE_SYSCALLS_PATH "/sys/kernel/debug/tracing/events/syscalls"
+#define TCP_RETRANSMIT_TP_PATH "/sys/kernel/debug/tracing/events/tcp/tcp_retransmit_skb"
+#define TCP_PROBE_TP_PATH "/sys/kernel/debug/tracing/events/tcp/tcp_probe"
+#define INET_SOCK_SET_STATE_TP_PATH "/sys/kernel/debug/tracing/events/sock/inet_sock_set_state"
+#define KFREE_SKB_TP_PATH "/sys/kernel/debug/tracing/events/skb/kfree_skb"
 /*
  * The `__sys_recvmmsg` interface underwent a change in its parameter list starting
  * from Linux kernel version 5.0. If earlier kernel versions support the `fentry/fexit`
@@ -73,6 +77,7 @@ struct socket_bpf_data {
 	uint8_t msg_type;	// 信息类型，值为MSG_UNKNOWN(0), MSG_REQUEST(1), MSG_RESPONSE(2)
 	bool need_reconfirm;	// 是否需要上层再确认 
 	bool is_tls;
+	bool is_unix;		// UNIX domain socket, the tuple is 127.0.0.1:0 on both sides
 
 	/* trace info */
 	uint64_t tcp_seq;	// 收发cap_data数据时TCP协议栈将会用到的TCP SEQ，可用于关联eBPF DATA与网络中的TCP Packet
@@ -178,6 +183,13 @@ struct socket_trace_stats {
 	 */
 	uint64_t proc_exec_event_count;
 	uint64_t proc_exit_event_count;
+
+	/*
+	 * Socket data passed through the ring buffer or the perf buffers,
+	 * kern_lost is the data dropped by either.
+	 */
+	bool ringbuf_enabled;
+	uint64_t kern_recv;
 };
 
 struct bpf_offset_param {
@@ -202,6 +214,15 @@ struct bpf_offset_param {
 	uint32_t struct_sock_sport_offset;	// offsetof(struct sock_common, skc_num)
 	uint32_t struct_sock_skc_state_offset;	// offsetof(struct sock_common, skc_state)
 	uint32_t struct_sock_common_ipv6only_offset;	// offsetof(struct sock_common, skc_flags)
+	uint32_t struct_unix_sock_addr_offset;	// offsetof(struct unix_sock, addr)
+	uint32_t struct_unix_sock_peer_offset;	// offsetof(struct unix_sock, peer)
+	uint32_t struct_unix_address_name_offset;	// offsetof(struct unix_address, name)
+	uint32_t struct_sk_buff_len_offset;	// offsetof(struct sk_buff, len)
+	uint32_t struct_sk_buff_head_offset;	// offsetof(struct sk_buff, head)
+	uint32_t struct_sk_buff_network_header_offset;	// offsetof(struct sk_buff, network_header)
+	uint32_t struct_sk_buff_transport_header_offset;	// offsetof(struct sk_buff, transport_header)
+	uint32_t struct_inode_i_sb_offset;	// offsetof(struct inode, i_sb)
+	uint32_t struct_super_block_s_dev_offset;	// offsetof(struct super_block, s_dev)
 };
 
 struct bpf_offset_param_array {
@@ -376,6 +397,140 @@ prefetch_and_process_data(struct bpf_tracer *t, int nb_rx, void **datas_burst)
 	}
 }
 
+struct socket_owner_info {
+	uint32_t tgid;
+	uint32_t role;		// enum endpoint_role
+	uint64_t cgroup_id;
+	uint64_t timestamp;	// Time of accept()/connect(), in nanoseconds
+	char comm[TASK_COMM_LEN];
+};
+
+/**
+ * Look up the process owning a connection
+ *
+ * All sockets accepted or connected after the socket tracer is loaded are
+ * recorded in the kernel, including the ones whose data is not traced.
+ *
+ * @l4_protocol IPPROTO_TCP or IPPROTO_UDP
+ * @addr_len 4 for IPv4, 16 for IPv6
+ * @laddr local address in network byte order
+ * @lport local port
+ * @raddr remote address in network byte order
+ * @rport remote port
+ * @info the owner if found
+ * @return true if found, otherwise false
+ */
+bool socket_owner_lookup(uint8_t l4_protocol, uint8_t addr_len,
+			 const uint8_t * laddr, uint16_t lport,
+			 const uint8_t * raddr, uint16_t rport,
+			 struct socket_owner_info *info);
+
+/**
+ * Look up the path of a traced UNIX domain socket
+ *
+ * @socket_id socket ID of the data with 'is_unix' set
+ * @path buffer for the path, abstract socket names start with '@'
+ * @size size of the buffer
+ * @return true if found, otherwise false
+ */
+bool unix_socket_path_lookup(uint64_t socket_id, char *path, int size);
+
+struct tcp_health_info {
+	uint32_t retrans;	// Retransmitted segments
+	uint32_t drops;		// Segments dropped by the TCP stack
+	uint32_t srtt_us;	// Smoothed RTT in microseconds, 0 if unknown
+	uint32_t state;		// TCP state, e.g. TCP_ESTABLISHED
+	uint64_t timestamp;	// Last update, in nanoseconds
+};
+
+/**
+ * Look up the TCP health of a connection
+ *
+ * Retransmissions, drops and state changes are counted since the connection
+ * is established, see set_tcp_health_tracing().
+ *
+ * @addr_len 4 for IPv4, 16 for IPv6
+ * @laddr local address in network byte order
+ * @lport local port
+ * @raddr remote address in network byte order
+ * @rport remote port
+ * @info the TCP health if found
+ * @return true if found, otherwise false
+ */
+bool tcp_health_lookup(uint8_t addr_len, const uint8_t * laddr,
+		       uint16_t lport, const uint8_t * raddr, uint16_t rport,
+		       struct tcp_health_info *info);
+
+#define SKB_DROP_REASON_NAME_LEN 64
+
+struct skb_drop_info {
+	char reason[SKB_DROP_REASON_NAME_LEN];	// e.g. "NO_SOCKET", "NETFILTER_DROP"
+	uint8_t addr_len;	// 4 for IPv4, 16 for IPv6, 0 if unknown
+	uint8_t protocol;	// IPPROTO_TCP, IPPROTO_UDP, ...
+	uint16_t dport;		// Destination port of TCP/UDP
+	uint8_t saddr[16];	// Source address in network byte order
+	uint8_t daddr[16];	// Destination address in network byte order
+	uint64_t packets;
+	uint64_t bytes;
+};
+
+typedef void (*skb_drop_callback_t) (void *ctx, struct skb_drop_info *info);
+
+/**
+ * Collect the packets dropped by kfree_skb() since the last call
+ *
+ * The drops are aggregated by the drop reason and the packet addresses, see
+ * set_skb_drop_monitor().
+ *
+ * @callback called for each aggregated drop
+ * @ctx passed to the callback
+ * @return the number of aggregated drops, < 0 on error
+ */
+int collect_skb_drops(skb_drop_callback_t callback, void *ctx);
+
+struct file_io_info {
+	uint32_t pid;
+	uint32_t major;		// Device of the file system, as in /proc/<pid>/mountinfo
+	uint32_t minor;
+	uint32_t operation;	// 0: write 1: read
+	uint64_t count;
+	uint64_t bytes;
+	uint64_t latency_sum;	// Microseconds
+	uint64_t latency_max;	// Microseconds
+	char comm[TASK_COMM_LEN];
+};
+
+typedef void (*file_io_callback_t) (void *ctx, struct file_io_info *info);
+
+/**
+ * Collect the regular file reads and writes since the last call
+ *
+ * The vfs_read()/vfs_write() calls are aggregated by process, device of the
+ * file system and operation, see set_file_io_monitor().
+ *
+ * @callback called for each aggregated process, device and operation
+ * @ctx passed to the callback
+ * @return the number of aggregated entries, < 0 on error
+ */
+int collect_file_io_stats(file_io_callback_t callback, void *ctx);
+
+int set_unix_socket_tracing(bool enabled);
+int set_io_uring_tracing(bool enabled);
+int set_tcp_health_tracing(bool enabled);
+int set_skb_drop_monitor(bool enabled);
+int set_file_io_monitor(bool enabled);
+/**
+ * Set the max entries of an auxiliary map of the socket tracer
+ *
+ * Must be called before running_socket_tracer().
+ *
+ * @name map name, one of __socket_owner_map, __unix_socket_path_map,
+ *       __tcp_health_map, __skb_drop_map and __dns_resolver_stats_map
+ * @entries max entries, <= 0 to use the size in the eBPF object
+ * @return 0 on success, < 0 if the map can not be resized
+ */
+int set_ringbuf_enabled(bool enabled);
+int set_socket_map_max_entries(const char *name, int entries);
 int set_data_limit_max(int limit_size);
 int set_go_tracing_timeout(int timeout);
 int set_io_event_collect_mode(uint32_t mode);
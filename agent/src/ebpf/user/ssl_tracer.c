@@ -57,9 +57,53 @@ static struct symbol symbols[] = {
 		.probe_func = UPROBE_FUNC_NAME(openssl_read_exit),
 		.is_probe_ret = true,
 	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_write_ex",
+		.probe_func = UPROBE_FUNC_NAME(openssl_write_ex_enter),
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_write_ex",
+		.probe_func = UPROBE_FUNC_NAME(openssl_write_ex_exit),
+		.is_probe_ret = true,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_read_ex",
+		.probe_func = UPROBE_FUNC_NAME(openssl_read_ex_enter),
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_read_ex",
+		.probe_func = UPROBE_FUNC_NAME(openssl_read_ex_exit),
+		.is_probe_ret = true,
+	},
 };
 /* *INDENT-ON* */
 
+/*
+ * Node.js links OpenSSL statically into the `node` executable and exports
+ * its symbols for native addons.
+ */
+static char *get_node_path_by_pid(int pid)
+{
+	const char *name;
+	char *path = get_elf_path_by_pid(pid);
+	if (!path)
+		return NULL;
+
+	name = strrchr(path, '/');
+	name = name ? name + 1 : path;
+	if (strcmp(name, "node") == 0 || strcmp(name, "nodejs") == 0)
+		return path;
+
+	free(path);
+	return NULL;
+}
+
 static void openssl_parse_and_register(int pid, struct tracer_probes_conf *conf)
 {
 	char *path = NULL;
@@ -71,6 +115,20 @@ static void openssl_parse_and_register(int pid, struct tracer_probes_conf *conf)
 		goto out;
 
 	path = get_so_path_by_pid_and_name(pid, "ssl");
+	/*
+	 * CPython distributions such as python-build-standalone link OpenSSL
+	 * statically into the `_ssl` extension module.
+	 */
+	if (!path)
+		path = get_module_path_by_pid_and_name(pid, "_ssl");
+	/*
+	 * The OpenSslEngine of Netty calls OpenSSL or BoringSSL linked
+	 * statically into the netty-tcnative JNI library.
+	 */
+	if (!path)
+		path = get_so_path_by_pid_and_prefix(pid, "libnetty_tcnative");
+	if (!path)
+		path = get_node_path_by_pid(pid);
 	if (!path)
 		goto out;
 
@@ -31,6 +31,7 @@ enum uprobe_type {
 	GO_UPROBE = 0,
 	OPENSSL_UPROBE,
 	MEMPROF_UPROBE,
+	DNS_RESOLVER_UPROBE,
 	OTHER_UPROBE
 };
 
@@ -45,6 +46,9 @@ struct symbol {
 	const char *symbol_prefix;
 	const char *probe_func;
 	bool is_probe_ret;
+	// Only match the symbol with exactly the same name, otherwise symbols
+	// ending with 'symbol' are also matched.
+	bool exact_match;
 };
 
 struct version_info {
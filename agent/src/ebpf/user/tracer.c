@@ -22,6 +22,7 @@
 #include <sys/prctl.h>
 #include <linux/version.h>
 #include <sys/epoll.h>
+#include <sys/mman.h>
 #include <bcc/bcc_proc.h>
 #include <bcc/bcc_elf.h>
 #include <bcc/libbpf.h>
@@ -43,6 +44,7 @@
 #include "unwind_tracer.h"
 #include "extended/extended.h"
 #include "profile/perf_profiler.h"
+#include "profile/memory_profiler.h"
 
 uint32_t k_version;
 // Linux kernel major version, minor version, revision version, and revision number.
@@ -518,6 +520,161 @@ void free_perf_buffer_reader(struct bpf_perf_reader *reader)
 	free_reader(reader);
 }
 
+#ifndef BPF_RINGBUF_BUSY_BIT
+#define BPF_RINGBUF_BUSY_BIT    (1U << 31)
+#define BPF_RINGBUF_DISCARD_BIT (1U << 30)
+#define BPF_RINGBUF_HDR_SZ      8
+#endif
+
+bool ringbuf_map_supported(void)
+{
+	if (check_kernel_version(5, 8) != 0)
+		return false;
+
+	int fd = bcc_create_map(BPF_MAP_TYPE_RINGBUF, "ringbuf_test", 0, 0,
+				getpagesize(), 0);
+	if (fd < 0)
+		return false;
+
+	close(fd);
+	return true;
+}
+
+struct bpf_ringbuf_reader *create_ringbuf_reader(struct bpf_tracer *t,
+						 const char *map_name,
+						 perf_reader_raw_cb raw_cb,
+						 int epoll_timeout)
+{
+	if (t == NULL || map_name == NULL || raw_cb == NULL) {
+		ebpf_error("create_ringbuf_reader() Invalid parameter."
+			   "t %p map_name %s raw_cb %p\n", t, map_name, raw_cb);
+		return NULL;
+	}
+
+	struct ebpf_map *map = ebpf_obj__get_map_by_name(t->obj, map_name);
+	if (map == NULL || map->fd < 0) {
+		ebpf_error("map(name:%s) is not found.\n", map_name);
+		return NULL;
+	}
+
+	struct bpf_ringbuf_reader *reader = calloc(1, sizeof(*reader));
+	if (reader == NULL) {
+		ebpf_error("calloc() failed.\n");
+		return NULL;
+	}
+
+	snprintf(reader->name, sizeof(reader->name), "%s", map_name);
+	reader->map = map;
+	reader->raw_cb = raw_cb;
+	reader->epoll_timeout = epoll_timeout;
+	reader->epoll_fd = -1;
+	reader->fwd_info.queue_id = 0;
+	reader->fwd_info.cpu_id = -1;
+	reader->fwd_info.tracer = t;
+	reader->tracer = t;
+
+	int page_size = getpagesize();
+	size_t size = map->def.max_entries;
+	reader->mask = size - 1;
+	reader->consumer_pos = mmap(NULL, page_size, PROT_READ | PROT_WRITE,
+				    MAP_SHARED, map->fd, 0);
+	if (reader->consumer_pos == MAP_FAILED) {
+		ebpf_error("mmap() consumer page failed, %s\n",
+			   strerror(errno));
+		goto failed;
+	}
+
+	/*
+	 * The data area is mapped twice in a row by the kernel, so a record
+	 * wrapping around the end can be read continuously.
+	 */
+	reader->producer_pos = mmap(NULL, page_size + 2 * size, PROT_READ,
+				    MAP_SHARED, map->fd, page_size);
+	if (reader->producer_pos == MAP_FAILED) {
+		ebpf_error("mmap() producer page failed, %s\n",
+			   strerror(errno));
+		munmap(reader->consumer_pos, page_size);
+		goto failed;
+	}
+	reader->data = (void *)reader->producer_pos + page_size;
+
+	struct epoll_event event = {
+		.events = EPOLLIN,
+		.data.ptr = reader,
+	};
+	reader->epoll_fd = epoll_create1(0);
+	if (reader->epoll_fd == -1 ||
+	    epoll_ctl(reader->epoll_fd, EPOLL_CTL_ADD, map->fd, &event) == -1) {
+		ebpf_error("epoll setup failed, %s\n", strerror(errno));
+		munmap(reader->producer_pos, page_size + 2 * size);
+		munmap(reader->consumer_pos, page_size);
+		if (reader->epoll_fd != -1)
+			close(reader->epoll_fd);
+		goto failed;
+	}
+
+	t->ringbuf_reader = reader;
+	ebpf_info("Ring buffer reader %s created, size %lu bytes.\n",
+		  reader->name, size);
+	return reader;
+
+failed:
+	free(reader);
+	return NULL;
+}
+
+static inline uint32_t ringbuf_record_size(uint32_t len)
+{
+	len &= ~(BPF_RINGBUF_BUSY_BIT | BPF_RINGBUF_DISCARD_BIT);
+	len += BPF_RINGBUF_HDR_SZ;
+	return (len + 7) & ~7;
+}
+
+/*
+ * Waits for the data and passes all the records committed by the producers
+ * to the callback.
+ *
+ * @return the number of records read, or a negative value on error.
+ */
+int ringbuf_reader_poll(struct bpf_ringbuf_reader *reader)
+{
+	struct epoll_event event;
+	int nfds = epoll_wait(reader->epoll_fd, &event, 1,
+			      reader->epoll_timeout);
+	if (nfds < 0 && errno != EINTR)
+		return -errno;
+
+	int count = 0;
+	unsigned long cons_pos, prod_pos;
+	bool got_new;
+	cons_pos = __atomic_load_n(reader->consumer_pos, __ATOMIC_ACQUIRE);
+	do {
+		got_new = false;
+		prod_pos =
+		    __atomic_load_n(reader->producer_pos, __ATOMIC_ACQUIRE);
+		while (cons_pos < prod_pos) {
+			void *hdr = reader->data + (cons_pos & reader->mask);
+			uint32_t len =
+			    __atomic_load_n((uint32_t *) hdr, __ATOMIC_ACQUIRE);
+			// The record is not committed yet.
+			if (len & BPF_RINGBUF_BUSY_BIT)
+				return count;
+
+			got_new = true;
+			cons_pos += ringbuf_record_size(len);
+			if (!(len & BPF_RINGBUF_DISCARD_BIT)) {
+				reader->raw_cb(&reader->fwd_info,
+					       hdr + BPF_RINGBUF_HDR_SZ, len);
+				count++;
+			}
+			__atomic_store_n(reader->consumer_pos, cons_pos,
+					 __ATOMIC_RELEASE);
+		}
+	} while (got_new);
+
+	return count;
+}
+
 static int map_resize_set(struct ebpf_object *obj, struct map_config *m_conf)
 {
 	struct ebpf_map *map = ebpf_obj__get_map_by_name(obj, m_conf->map_name);
@@ -1806,7 +1963,8 @@ static int print_match_pids_kvp_cb(pids_match_hash_kv * kv, void *arg)
 		  (kv->value & FEATURE_FLAG_UPROBE_OPENSSL) ? "OPENSSL" : "",
 		  (kv->value & FEATURE_FLAG_PROFILE_ONCPU) ? "ONCPU" : "",
 		  (kv->value & FEATURE_FLAG_PROFILE_OFFCPU) ? "OFFCPU" : "",
-		  (kv->value & FEATURE_FLAG_PROFILE_MEMORY) ? "OFFCPU" : "");
+		  (kv->value & FEATURE_FLAG_PROFILE_MEMORY) ? "MEMORY" : "",
+		  (kv->value & FEATURE_FLAG_UPROBE_DNS_RESOLVER) ? "DNS" : "");
 	return BIHASH_WALK_CONTINUE;
 }
 
@@ -1847,6 +2005,8 @@ static int add_pid_to_match_hash(int feature, int pid)
 	}
 
 	uprobe_match_pid_handle(feature, pid, MATCH_PID_ADD);
+	offcpu_match_pid_handle(feature, pid, MATCH_PID_ADD);
+	memory_match_pid_handle(feature, pid, MATCH_PID_ADD);
 	extended_match_pid_handle(feature, pid, MATCH_PID_ADD);
 	return ret;
 }
@@ -1882,6 +2042,8 @@ static int clear_pid_from_match_hash(int feature, int pid)
 	}
 
 	uprobe_match_pid_handle(feature, pid, MATCH_PID_DEL);
+	offcpu_match_pid_handle(feature, pid, MATCH_PID_DEL);
+	memory_match_pid_handle(feature, pid, MATCH_PID_DEL);
 	extended_match_pid_handle(feature, pid, MATCH_PID_DEL);
 	return ret;
 }
@@ -2156,6 +2318,70 @@ int bpf_tracer_init(const char *log_file, bool is_stdout)
 	return ETR_OK;
 }
 
+int set_bpf_stats_enabled(bool enabled)
+{
+	const char *path = "/proc/sys/kernel/bpf_stats_enabled";
+	FILE *fp = fopen(path, "w");
+	if (fp == NULL) {
+		ebpf_warning("fopen '%s' failed, error:%s\n", path,
+			     strerror(errno));
+		return ETR_INVAL;
+	}
+
+	int ret = fprintf(fp, "%d", enabled ? 1 : 0);
+	fclose(fp);
+	if (ret < 0) {
+		ebpf_warning("write '%s' failed, error:%s\n", path,
+			     strerror(errno));
+		return ETR_INVAL;
+	}
+
+	ebpf_info("eBPF program statistics %s\n",
+		  enabled ? "enabled" : "disabled");
+	return ETR_OK;
+}
+
+int collect_bpf_prog_stats(bpf_prog_stats_callback_t callback, void *ctx)
+{
+	if (tracers_lock == NULL)
+		return ETR_INVAL;
+
+	struct bpf_prog_stats stats;
+	struct bpf_prog_info info;
+	uint32_t info_len;
+	struct bpf_tracer *t;
+	struct ebpf_prog *prog;
+	int i, j, count = 0;
+
+	tracers_ctl_lock();
+	for (i = 0; i < BPF_TRACER_NUM_MAX; i++) {
+		t = &tracers[i];
+		if (!t->is_use || t->obj == NULL)
+			continue;
+		for (j = 0; j < t->obj->progs_cnt; j++) {
+			prog = &t->obj->progs[j];
+			if (prog->prog_fd < 0)
+				continue;
+			memset(&info, 0, sizeof(info));
+			info_len = sizeof(info);
+			if (bpf_obj_get_info(prog->prog_fd, &info, &info_len))
+				continue;
+			memset(&stats, 0, sizeof(stats));
+			snprintf(stats.tracer_name, sizeof(stats.tracer_name),
+				 "%s", t->name);
+			snprintf(stats.prog_name, sizeof(stats.prog_name),
+				 "%s", prog->name);
+			stats.run_cnt = info.run_cnt;
+			stats.run_time_ns = info.run_time_ns;
+			callback(ctx, &stats);
+			count++;
+		}
+	}
+	tracers_ctl_unlock();
+
+	return count;
+}
+
 bool is_rt_kernel(void)
 {
 	return (strstr(linux_release, ".rt") || strstr(linux_release, "-rt"));
@@ -377,6 +377,31 @@ struct bpf_perf_reader {
 	struct bpf_tracer *tracer;
 };
 
+struct reader_forward_info {
+	uint64_t queue_id;
+	int cpu_id;
+	struct bpf_tracer *tracer;
+};
+
+/*
+ * This is used to read data from a ring buffer (type: BPF_MAP_TYPE_RINGBUF,
+ * Linux 5.8+), which is shared by all CPUs and read by a single thread.
+ */
+struct bpf_ringbuf_reader {
+	char name[NAME_LEN];	// ring buffer map
+	struct ebpf_map *map;	// ebpf_map address
+	unsigned long mask;	// data area size - 1
+	unsigned long *consumer_pos;	// consumer page, read-write
+	unsigned long *producer_pos;	// producer page, read-only
+	void *data;		// data area, mapped twice in a row
+	perf_reader_raw_cb raw_cb;	// Used for ring buffer receive callback.
+	int epoll_timeout;	// poll timeout (ms)
+	int epoll_fd;
+	// cpu_id is -1 for the data of all CPUs
+	struct reader_forward_info fwd_info;
+	struct bpf_tracer *tracer;
+};
+
 struct bpf_tracer {
 	/*
 	 * tracer info
@@ -437,6 +462,12 @@ struct bpf_tracer {
 	struct bpf_perf_reader readers[PERF_READER_NUM_MAX];
 	int perf_readers_count;
 
+	/*
+	 * Used instead of the perf readers if the data is passed through
+	 * the ring buffer, NULL if not.
+	 */
+	struct bpf_ringbuf_reader *ringbuf_reader;
+
 	/*
 	 * statistics
 	 */
@@ -527,12 +558,6 @@ struct bpf_tracer_param_array {
 	struct bpf_tracer_param tracers[0];
 };
 
-struct reader_forward_info {
-	uint64_t queue_id;
-	int cpu_id;
-	struct bpf_tracer *tracer;
-};
-
 extern volatile uint32_t *tracers_lock;
 
 /*
@@ -672,6 +697,20 @@ struct bpf_perf_reader *create_perf_buffer_reader(struct bpf_tracer *t,
 						  int thread_nr,
 						  int epoll_timeout);
 void free_perf_buffer_reader(struct bpf_perf_reader *reader);
+bool ringbuf_map_supported(void);
+/**
+ * @brief create a ring buffer reader.
+ * @param t Tracer address
+ * @param map_name Ring buffer map name
+ * @param raw_cb Ring buffer reader raw data callback
+ * @param epoll_timeout poll timeout
+ * @return Ring buffer reader address on success, NULL on error
+ */
+struct bpf_ringbuf_reader *create_ringbuf_reader(struct bpf_tracer *t,
+						 const char *map_name,
+						 perf_reader_raw_cb raw_cb,
+						 int epoll_timeout);
+int ringbuf_reader_poll(struct bpf_ringbuf_reader *reader);
 int release_bpf_tracer(const char *name);
 void free_all_readers(struct bpf_tracer *t);
 int enable_tracer_reader_work(const char *name, int idx,
@@ -701,4 +740,34 @@ struct probe *create_probe(struct bpf_tracer *tracer,
 			   enum probe_type type, void *private,
 			   bool add_tracer);
 void free_probe_from_conf(struct probe *pb, struct tracer_probes_conf *conf);
+
+struct bpf_prog_stats {
+	char tracer_name[NAME_LEN];
+	char prog_name[PROBE_NAME_SZ];
+	uint64_t run_cnt;	// Number of times the program has run
+	uint64_t run_time_ns;	// Total running time in nanoseconds
+};
+
+typedef void (*bpf_prog_stats_callback_t) (void *ctx,
+					   struct bpf_prog_stats *stats);
+
+/**
+ * Enable or disable the kernel statistics of eBPF programs (Linux 5.1+)
+ *
+ * The statistics cost two clock reads on each run of the programs, so
+ * they are disabled by default in the kernel.
+ *
+ * @enabled true to enable, false to disable
+ * @return 0 on success, non-zero on error
+ */
+int set_bpf_stats_enabled(bool enabled);
+
+/**
+ * Collect the accumulated run count and run time of the loaded eBPF programs
+ *
+ * @callback called for each loaded program
+ * @ctx passed to the callback
+ * @return the number of programs, < 0 on error
+ */
+int collect_bpf_prog_stats(bpf_prog_stats_callback_t callback, void *ctx);
 #endif /* DF_USER_TRACER_H */
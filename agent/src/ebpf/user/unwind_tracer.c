@@ -184,7 +184,7 @@ static bool requires_dwarf_unwind_table(int pid) {
 
     char *exe_name = basename(path);
     // Java has JIT compiled code without DWARF info, not supported at the moment
-    if (strcmp(exe_name, "java") == 0) {
+    if (is_java_process(pid, exe_name)) {
         free(path);
         return false;
     }
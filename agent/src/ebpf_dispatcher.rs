@@ -37,14 +37,19 @@ fn main() {
 
  */
 
-#[cfg(feature = "extended_observability")]
+mod btf_hub;
+pub mod drop_monitor;
+pub mod file_io_monitor;
 pub mod memory_profile;
+pub mod overhead_monitor;
+pub mod process_tracker;
+pub mod resolver_monitor;
 
 use std::ffi::{CStr, CString};
 use std::ptr::{self, null_mut};
 use std::slice;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
@@ -64,11 +69,12 @@ use crate::common::meta_packet::{MetaPacket, SegmentFlags};
 use crate::common::proc_event::{BoxedProcEvents, EventType, ProcEvent};
 use crate::common::{FlowAclListener, FlowAclListenerId};
 use crate::config::handler::{CollectorAccess, EbpfAccess, EbpfConfig, LogParserAccess};
-use crate::config::FlowAccess;
+use crate::config::{FlowAccess, SocketDataBuffer};
 use crate::ebpf;
 use crate::exception::ExceptionHandler;
 use crate::flow_generator::{flow_map::Config, AppProto, FlowMap};
 use crate::integration_collector::Profile;
+use crate::metric::document::BoxedDocument;
 use crate::platform::ProcessData;
 use crate::policy::PolicyGetter;
 use crate::rpc::get_timestamp;
@@ -76,7 +82,7 @@ use crate::utils::{process::ProcessListener, stats};
 
 use public::{
     buffer::BatchedBox,
-    counter::{Countable, Counter, CounterType, CounterValue, OwnedCountable},
+    counter::{Countable, Counter, CounterType, CounterValue, OwnedCountable, RefCountable},
     debug::QueueDebugger,
     l7_protocol::{L7Protocol, L7ProtocolChecker},
     leaky_bucket::LeakyBucket,
@@ -112,6 +118,67 @@ pub struct EbpfCounter {
 
 pub struct SyncEbpfCounter {
     counter: Arc<EbpfCounter>,
+    config: EbpfAccess,
+    exception_handler: ExceptionHandler,
+}
+
+impl SyncEbpfCounter {
+    fn utilization(used: u32, max: u32) -> f64 {
+        if max == 0 {
+            return 0.0;
+        }
+        used as f64 * 100.0 / max as f64
+    }
+
+    // Raises the exception if the socket info map or trace map is about to overflow, or data is
+    // lost in the perf buffers or the user space queues
+    fn check_map_pressure(&self, stats: &ebpf::SK_TRACE_STATS) {
+        let threshold = self.config.load().ebpf.tunning.map_pressure_threshold as f64;
+        let mut pressure = false;
+        for (name, used, max) in [
+            (
+                "socket info map",
+                stats.kern_socket_map_used,
+                stats.kern_socket_map_max,
+            ),
+            (
+                "trace map",
+                stats.kern_trace_map_used,
+                stats.kern_trace_map_max,
+            ),
+        ] {
+            if Self::utilization(used, max) >= threshold {
+                warn!(
+                    "ebpf {} used {} of {} entries, exceeding {}%, increase its max entries",
+                    name, used, max, threshold
+                );
+                pressure = true;
+            }
+        }
+        if stats.kern_lost > 0 {
+            warn!(
+                "ebpf {} lost {} data, increase perf_pages_count {}",
+                if stats.ringbuf_enabled {
+                    "ring buffer"
+                } else {
+                    "perf buffers"
+                },
+                stats.kern_lost,
+                stats.perf_pages_count
+            );
+            pressure = true;
+        }
+        if stats.user_enqueue_lost > 0 {
+            warn!(
+                "ebpf user space queues lost {} data, increase kernel_ring_size {}",
+                stats.user_enqueue_lost, stats.queue_capacity
+            );
+            pressure = true;
+        }
+        if pressure {
+            self.exception_handler.set(Exception::EbpfMapPressure);
+        }
+    }
 }
 
 impl OwnedCountable for SyncEbpfCounter {
@@ -119,6 +186,7 @@ impl OwnedCountable for SyncEbpfCounter {
         let rx = self.counter.rx.swap(0, Ordering::Relaxed);
         let get_token_failed = self.counter.get_token_failed.swap(0, Ordering::Relaxed);
         let ebpf_counter = unsafe { ebpf::socket_tracer_stats() };
+        self.check_map_pressure(&ebpf_counter);
 
         vec![
             (
@@ -136,6 +204,16 @@ impl OwnedCountable for SyncEbpfCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(ebpf_counter.perf_pages_count as u64),
             ),
+            (
+                "ringbuf_enabled",
+                CounterType::Gauged,
+                CounterValue::Unsigned(ebpf_counter.ringbuf_enabled as u64),
+            ),
+            (
+                "kern_recv",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.kern_recv),
+            ),
             (
                 "kern_lost",
                 CounterType::Counted,
@@ -151,6 +229,14 @@ impl OwnedCountable for SyncEbpfCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(ebpf_counter.kern_socket_map_used as u64),
             ),
+            (
+                "kern_socket_map_utilization",
+                CounterType::Gauged,
+                CounterValue::Float(Self::utilization(
+                    ebpf_counter.kern_socket_map_used,
+                    ebpf_counter.kern_socket_map_max,
+                )),
+            ),
             (
                 "kern_trace_map_max",
                 CounterType::Counted,
@@ -161,6 +247,14 @@ impl OwnedCountable for SyncEbpfCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(ebpf_counter.kern_trace_map_used as u64),
             ),
+            (
+                "kern_trace_map_utilization",
+                CounterType::Gauged,
+                CounterValue::Float(Self::utilization(
+                    ebpf_counter.kern_trace_map_used,
+                    ebpf_counter.kern_trace_map_max,
+                )),
+            ),
             (
                 "socket_map_max_reclaim",
                 CounterType::Counted,
@@ -269,6 +363,7 @@ struct EbpfDispatcher {
     config: EbpfAccess,
     output: DebugSender<Box<AppProto>>, // Send AppProtos to the AppProtoLogsParser
     l7_stats_output: DebugSender<BatchedBox<L7Stats>>, // Send L7Stats to the QuadrupleGenerator
+    metrics_output: DebugSender<BoxedDocument>, // Send kernel drop, resolver and file IO metrics to the UniformSender
     stats_collector: Arc<stats::Collector>,
 }
 
@@ -378,13 +473,66 @@ impl EbpfDispatcher {
             self.time_diff.clone(),
             &self.flow_map_config.load(),
             None, // Enterprise Edition Feature: packet-sequence
+            None,
+            None,
+            None,
+            None,
             self.stats_collector.clone(),
             true, // from_ebpf
         );
         let leaky_bucket = LeakyBucket::new(Some(ebpf_config.ebpf.socket.tunning.max_capture_rate));
+        let mut drop_monitor = ebpf_config
+            .ebpf
+            .socket
+            .kprobe
+            .drop_monitor
+            .enabled
+            .then(|| drop_monitor::DropMonitor::new(self.metrics_output.clone()));
+        let mut resolver_monitor = ebpf_config.ebpf.socket.uprobe.dns.enabled.then(|| {
+            resolver_monitor::ResolverMonitor::new(self.metrics_output.clone(), self.policy_getter)
+        });
+        if let Some(m) = resolver_monitor.as_ref() {
+            self.stats_collector.register_countable(
+                &stats::NoTagModule("ebpf-dns-resolver"),
+                Countable::Ref(Arc::downgrade(&m.get_counter_handle()) as Weak<dyn RefCountable>),
+            );
+        }
+        let mut file_io_monitor = ebpf_config.ebpf.file.io_stats.enabled.then(|| {
+            file_io_monitor::FileIoMonitor::new(self.metrics_output.clone(), self.policy_getter)
+        });
+        let mut overhead_monitor = ebpf_config.ebpf.overhead.enabled.then(|| {
+            overhead_monitor::OverheadMonitor::new(
+                &ebpf_config.ebpf.overhead,
+                self.stats_collector.clone(),
+            )
+        });
         const QUEUE_BATCH_SIZE: usize = 1024;
         let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
         while unsafe { SWITCH } {
+            if let Some(m) = drop_monitor.as_mut() {
+                m.flush(
+                    get_timestamp(self.time_diff.load(Ordering::Relaxed)),
+                    self.config.load().agent_id,
+                );
+            }
+            if let Some(m) = resolver_monitor.as_mut() {
+                m.flush(
+                    get_timestamp(self.time_diff.load(Ordering::Relaxed)),
+                    self.config.load().agent_id,
+                );
+            }
+            if let Some(m) = file_io_monitor.as_mut() {
+                let config = self.config.load();
+                m.flush(
+                    get_timestamp(self.time_diff.load(Ordering::Relaxed)),
+                    config.agent_id,
+                    &config.ebpf.file.io_stats.path_filters,
+                );
+            }
+            if let Some(m) = overhead_monitor.as_mut() {
+                m.flush();
+            }
+
             let config = Config {
                 flow: &self.flow_map_config.load(),
                 log_parser: &self.log_parser_config.load(),
@@ -452,7 +600,6 @@ impl FlowAclListener for SyncEbpfDispatcher {
 
 #[derive(Default)]
 struct ConfigHandle {
-    #[cfg(feature = "extended_observability")]
     memory_profile_settings: Option<memory_profile::MemoryContextSettings>,
 }
 
@@ -494,9 +641,15 @@ impl EbpfCollector {
             // converted to microseconds.
             (*sd).timestamp = (*sd).timestamp / 1000;
 
-            let container_id =
+            let mut container_id =
                 CStr::from_ptr(ptr::addr_of!((*sd).container_id) as *const libc::c_char)
                     .to_string_lossy();
+            if container_id.is_empty() {
+                // the process may have exited before its container was read by the kernel
+                if let Some(id) = process_tracker::container_id((*sd).process_id) {
+                    container_id = id.into();
+                }
+            }
             let event_type = EventType::from(ptr::addr_of!((*sd).source).read_unaligned());
             if event_type != EventType::OtherEvent {
                 // EbpfType like TracePoint, TlsUprobe, GoHttp2Uprobe belong to other events
@@ -529,17 +682,13 @@ impl EbpfCollector {
         }
     }
 
-    extern "C" fn ebpf_profiler_callback(
-        #[allow(unused)] ctx: *mut c_void,
-        data: *mut ebpf::stack_profile_data,
-    ) {
+    extern "C" fn ebpf_profiler_callback(ctx: *mut c_void, data: *mut ebpf::stack_profile_data) {
         unsafe {
             if !SWITCH || EBPF_PROFILE_SENDER.is_none() {
                 return;
             }
             let data = &mut *data;
 
-            #[cfg(feature = "extended_observability")]
             if data.profiler_type == ebpf::PROFILER_TYPE_MEMORY {
                 let mut ts_nanos = data.timestamp;
                 if let Some(time_diff) = TIME_DIFF.as_ref() {
@@ -573,7 +722,6 @@ impl EbpfCollector {
                 }
             }
             profile.event_type = match data.profiler_type {
-                #[cfg(feature = "extended_observability")]
                 ebpf::PROFILER_TYPE_OFFCPU => metric::ProfileEventType::EbpfOffCpu.into(),
                 _ => metric::ProfileEventType::EbpfOnCpu.into(),
             };
@@ -646,6 +794,12 @@ impl EbpfCollector {
         // ebpf core modules init
         #[allow(unused_mut)]
         let mut handle = ConfigHandle::default();
+        if let Some(path) = btf_hub::prepare_kernel_btf(&config.ebpf.btf) {
+            let path = CString::new(path.to_string_lossy().as_bytes()).unwrap();
+            if ebpf::set_btf_custom_path(path.as_ptr()) != 0 {
+                warn!("ebpf set_btf_custom_path error: {:?}", path);
+            }
+        }
         ebpf::set_uprobe_golang_enabled(config.ebpf.socket.uprobe.golang.enabled);
         if config.ebpf.socket.uprobe.golang.enabled {
             let feature = "ebpf.socket.uprobe.golang";
@@ -705,6 +859,34 @@ impl EbpfCollector {
             info!("ebpf openssl uprobe proc regexp is empty, skip set")
         }
 
+        ebpf::set_uprobe_dns_resolver_enabled(config.ebpf.socket.uprobe.dns.enabled);
+        if config.ebpf.socket.uprobe.dns.enabled {
+            let feature = "ebpf.socket.uprobe.dns";
+            process_listener.register(feature, set_feature_uprobe_dns);
+
+            let uprobe_proc_regexp = config
+                .process_matcher
+                .iter()
+                .find(|p| {
+                    p.enabled_features
+                        .iter()
+                        .find(|f| f.eq_ignore_ascii_case(feature))
+                        .is_some()
+                })
+                .map(|p| p.match_regex.as_str())
+                .unwrap_or_default();
+            info!("ebpf set dns uprobe proc regexp: {}", uprobe_proc_regexp);
+            ebpf::set_feature_regex(
+                ebpf::FEATURE_UPROBE_DNS_RESOLVER,
+                CString::new(uprobe_proc_regexp.as_bytes())
+                    .unwrap()
+                    .as_c_str()
+                    .as_ptr(),
+            );
+        } else {
+            info!("ebpf dns uprobe proc regexp is empty, skip set")
+        }
+
         if config.symbol_table.golang_specific.enabled {
             let feature = "proc.golang_symbol_table";
             process_listener.register(feature, set_feature_uprobe_golang_symbol);
@@ -779,6 +961,15 @@ impl EbpfCollector {
             return Err(Error::EbpfInitError);
         }
 
+        ebpf::set_unix_socket_tracing(config.ebpf.socket.kprobe.unix_socket.enabled);
+        ebpf::set_io_uring_tracing(config.ebpf.socket.kprobe.io_uring.enabled);
+        ebpf::set_tcp_health_tracing(config.ebpf.socket.kprobe.tcp_health.enabled);
+        ebpf::set_skb_drop_monitor(config.ebpf.socket.kprobe.drop_monitor.enabled);
+        ebpf::set_file_io_monitor(config.ebpf.file.io_stats.enabled);
+        ebpf::set_ringbuf_enabled(
+            config.ebpf.tunning.socket_data_buffer == SocketDataBuffer::RingBuffer,
+        );
+
         if ebpf::set_go_tracing_timeout(
             config.ebpf.socket.uprobe.golang.tracing_timeout.as_secs() as c_int
         ) != 0
@@ -840,6 +1031,15 @@ impl EbpfCollector {
         }
 
         ebpf::set_bpf_map_prealloc(!config.ebpf.socket.tunning.map_prealloc_disabled);
+        for (name, entries) in config.ebpf.tunning.map_max_entries.iter() {
+            let c_name = CString::new(name.as_bytes()).unwrap();
+            if ebpf::set_socket_map_max_entries(c_name.as_ptr(), *entries as c_int) != 0 {
+                warn!(
+                    "ebpf set_socket_map_max_entries error: {} {}",
+                    name, entries
+                );
+            }
+        }
 
         if ebpf::running_socket_tracer(
             Self::ebpf_l7_callback,                              /* 回调接口 rust -> C */
@@ -859,9 +1059,7 @@ impl EbpfCollector {
         let off_cpu = &ebpf_conf.profile.off_cpu;
         let memory = &ebpf_conf.profile.memory;
 
-        let profiler_enabled = !on_cpu.disabled
-            || (cfg!(feature = "extended_observability")
-                && (!off_cpu.disabled || !memory.disabled));
+        let profiler_enabled = !on_cpu.disabled || !off_cpu.disabled || !memory.disabled;
         if profiler_enabled {
             if !on_cpu.disabled {
                 ebpf::enable_oncpu_profiler();
@@ -882,33 +1080,30 @@ impl EbpfCollector {
                 config.ebpf.profile.unwinding.dwarf_shard_map_size as i32,
             );
 
-            #[cfg(feature = "extended_observability")]
-            {
-                if !off_cpu.disabled {
-                    ebpf::enable_offcpu_profiler();
-                } else {
-                    ebpf::disable_offcpu_profiler();
-                }
+            if !off_cpu.disabled {
+                ebpf::enable_offcpu_profiler();
+            } else {
+                ebpf::disable_offcpu_profiler();
+            }
 
-                if !memory.disabled {
-                    ebpf::enable_memory_profiler();
-                } else {
-                    ebpf::disable_memory_profiler();
-                }
+            if !memory.disabled {
+                ebpf::enable_memory_profiler();
+            } else {
+                ebpf::disable_memory_profiler();
             }
+            ebpf::set_memory_profiler_sampling(
+                memory.sampling_interval,
+                memory.max_samples_per_second,
+            );
 
-            #[allow(unused_mut)]
             let mut contexts: [*mut c_void; 3] = [ptr::null_mut(); 3];
-            #[cfg(feature = "extended_observability")]
-            {
-                let mp_ctx = memory_profile::MemoryContext::new(
-                    memory.report_interval,
-                    ebpf_conf.profile.preprocess.stack_compression,
-                );
-                handle.memory_profile_settings = Some(mp_ctx.settings());
-                contexts[ebpf::PROFILER_CTX_MEMORY_IDX] =
-                    Box::into_raw(Box::new(mp_ctx)) as *mut c_void;
-            }
+            let mp_ctx = memory_profile::MemoryContext::new(
+                memory.report_interval,
+                ebpf_conf.profile.preprocess.stack_compression,
+            );
+            handle.memory_profile_settings = Some(mp_ctx.settings());
+            contexts[ebpf::PROFILER_CTX_MEMORY_IDX] =
+                Box::into_raw(Box::new(mp_ctx)) as *mut c_void;
 
             if ebpf::start_continuous_profiler(
                 on_cpu.sampling_frequency as i32,
@@ -948,9 +1143,10 @@ impl EbpfCollector {
                 ebpf::set_profiler_cpu_aggregation(on_cpu.aggregate_by_cpu as i32);
             }
 
-            #[cfg(feature = "extended_observability")]
-            {
+            if !off_cpu.disabled {
                 let feature = "ebpf.profile.off_cpu";
+                process_listener.register(feature, set_feature_off_cpu);
+
                 let off_cpu_regexp = config
                     .process_matcher
                     .iter()
@@ -962,44 +1158,40 @@ impl EbpfCollector {
                     })
                     .map(|p| p.match_regex.as_str())
                     .unwrap_or_default();
-                if !off_cpu.disabled {
-                    process_listener.register(feature, set_feature_off_cpu);
-
-                    ebpf::set_feature_regex(
-                        ebpf::FEATURE_PROFILE_ONCPU,
-                        CString::new(off_cpu_regexp.as_bytes())
-                            .unwrap()
-                            .as_c_str()
-                            .as_ptr(),
-                    );
+                ebpf::set_feature_regex(
+                    ebpf::FEATURE_PROFILE_OFFCPU,
+                    CString::new(off_cpu_regexp.as_bytes())
+                        .unwrap()
+                        .as_c_str()
+                        .as_ptr(),
+                );
 
-                    ebpf::set_offcpu_cpuid_aggregation(off_cpu.aggregate_by_cpu as i32);
-                    ebpf::set_offcpu_minblock_time(off_cpu.min_blocking_time.as_micros() as u32);
-                }
+                ebpf::set_offcpu_cpuid_aggregation(off_cpu.aggregate_by_cpu as i32);
+                ebpf::set_offcpu_minblock_time(off_cpu.min_blocking_time.as_micros() as u32);
+            }
 
-                if !memory.disabled {
-                    let feature = "ebpf.profile.memory";
-                    process_listener.register(feature, set_feature_memory);
+            if !memory.disabled {
+                let feature = "ebpf.profile.memory";
+                process_listener.register(feature, set_feature_memory);
 
-                    let memory_cpu_regexp = config
-                        .process_matcher
-                        .iter()
-                        .find(|p| {
-                            p.enabled_features
-                                .iter()
-                                .find(|f| f.eq_ignore_ascii_case("ebpf.profile.memory"))
-                                .is_some()
-                        })
-                        .map(|p| p.match_regex.as_str())
-                        .unwrap_or_default();
-                    ebpf::set_feature_regex(
-                        ebpf::FEATURE_PROFILE_MEMORY,
-                        CString::new(memory_cpu_regexp.as_bytes())
-                            .unwrap()
-                            .as_c_str()
-                            .as_ptr(),
-                    );
-                }
+                let memory_regexp = config
+                    .process_matcher
+                    .iter()
+                    .find(|p| {
+                        p.enabled_features
+                            .iter()
+                            .find(|f| f.eq_ignore_ascii_case(feature))
+                            .is_some()
+                    })
+                    .map(|p| p.match_regex.as_str())
+                    .unwrap_or_default();
+                ebpf::set_feature_regex(
+                    ebpf::FEATURE_PROFILE_MEMORY,
+                    CString::new(memory_regexp.as_bytes())
+                        .unwrap()
+                        .as_c_str()
+                        .as_ptr(),
+                );
             }
         }
 
@@ -1073,6 +1265,7 @@ impl EbpfCollector {
         policy_getter: PolicyGetter,
         output: DebugSender<Box<AppProto>>,
         l7_stats_output: DebugSender<BatchedBox<L7Stats>>,
+        metrics_output: DebugSender<BoxedDocument>,
         proc_event_output: DebugSender<BoxedProcEvents>,
         ebpf_profile_sender: DebugSender<Profile>,
         queue_debugger: &QueueDebugger,
@@ -1086,6 +1279,11 @@ impl EbpfCollector {
             return Err(Error::EbpfDisabled);
         }
         info!("ebpf collector init...");
+        process_tracker::start(
+            &ebpf_config.ebpf.process_events,
+            process_listener,
+            &stats_collector,
+        );
         let queue_name = "0-ebpf-to-ebpf-collector";
         let (sender, receiver, counter) =
             bounded_with_debug(ebpf_config.queue_size, queue_name, queue_debugger);
@@ -1119,6 +1317,7 @@ impl EbpfCollector {
                 log_parser_config,
                 output,
                 l7_stats_output,
+                metrics_output,
                 flow_map_config,
                 stats_collector,
                 collector_config,
@@ -1138,6 +1337,8 @@ impl EbpfCollector {
     pub fn get_sync_counter(&self) -> SyncEbpfCounter {
         SyncEbpfCounter {
             counter: self.counter.clone(),
+            config: self.thread_dispatcher.config.clone(),
+            exception_handler: self.exception_handler.clone(),
         }
     }
 
@@ -1171,8 +1372,7 @@ impl EbpfCollector {
                 ebpf::stop_continuous_profiler(
                     &mut contexts as *mut [*mut c_void; ebpf::PROFILER_CTX_NUM],
                 );
-                #[cfg(feature = "extended_observability")]
-                {
+                if !contexts[ebpf::PROFILER_CTX_MEMORY_IDX].is_null() {
                     std::mem::drop(Box::from_raw(
                         contexts[ebpf::PROFILER_CTX_MEMORY_IDX]
                             as *mut memory_profile::MemoryContext,
@@ -1186,9 +1386,12 @@ impl EbpfCollector {
                     return;
                 }
             }
-            #[cfg(feature = "extended_observability")]
             if let Some(s) = self.config_handle.memory_profile_settings.as_ref() {
                 s.set_report_interval(ecfg.memory.report_interval);
+                ebpf::set_memory_profiler_sampling(
+                    ecfg.memory.sampling_interval,
+                    ecfg.memory.max_samples_per_second,
+                );
             }
         }
         if config.l7_log_enabled() {
@@ -1220,6 +1423,8 @@ impl EbpfCollector {
 
         debug!("ebpf collector starting ebpf-kernel.");
         Self::ebpf_start();
+        self.process_listener
+            .set_event_driven(process_tracker::is_running());
         info!("ebpf collector started");
     }
 
@@ -1232,6 +1437,7 @@ impl EbpfCollector {
             SWITCH = false;
         }
         Self::ebpf_stop();
+        self.process_listener.set_event_driven(false);
 
         info!("notified ebpf collector stopping thread.");
         self.thread_handle.take()
@@ -1246,6 +1452,7 @@ impl EbpfCollector {
             SWITCH = false;
         }
         Self::ebpf_stop();
+        self.process_listener.set_event_driven(false);
 
         info!("ebpf collector stopping thread.");
         if let Some(handler) = self.thread_handle.take() {
@@ -1261,62 +1468,42 @@ impl Drop for EbpfCollector {
     }
 }
 
-pub fn set_feature_uprobe_golang(pids: &Vec<u32>, _: &Vec<ProcessData>) {
+fn set_feature_pids(feature: c_int, pids: &[u32]) {
+    // probes shed by the overhead monitor are not attached again
+    let pids = if overhead_monitor::is_shed(feature) {
+        &[]
+    } else {
+        pids
+    };
     unsafe {
-        ebpf::set_feature_pids(
-            ebpf::FEATURE_UPROBE_GOLANG,
-            pids.as_ptr() as *const i32,
-            pids.len() as i32,
-        );
+        ebpf::set_feature_pids(feature, pids.as_ptr() as *const i32, pids.len() as i32);
     }
 }
 
+pub fn set_feature_uprobe_golang(pids: &Vec<u32>, _: &Vec<ProcessData>) {
+    set_feature_pids(ebpf::FEATURE_UPROBE_GOLANG, pids);
+}
+
 pub fn set_feature_uprobe_golang_symbol(pids: &Vec<u32>, _: &Vec<ProcessData>) {
-    unsafe {
-        ebpf::set_feature_pids(
-            ebpf::FEATURE_UPROBE_GOLANG_SYMBOL,
-            pids.as_ptr() as *const i32,
-            pids.len() as i32,
-        );
-    }
+    set_feature_pids(ebpf::FEATURE_UPROBE_GOLANG_SYMBOL, pids);
 }
 
 pub fn set_feature_uprobe_tls(pids: &Vec<u32>, _: &Vec<ProcessData>) {
-    unsafe {
-        ebpf::set_feature_pids(
-            ebpf::FEATURE_UPROBE_OPENSSL,
-            pids.as_ptr() as *const i32,
-            pids.len() as i32,
-        );
-    }
+    set_feature_pids(ebpf::FEATURE_UPROBE_OPENSSL, pids);
+}
+
+pub fn set_feature_uprobe_dns(pids: &Vec<u32>, _: &Vec<ProcessData>) {
+    set_feature_pids(ebpf::FEATURE_UPROBE_DNS_RESOLVER, pids);
 }
 
 pub fn set_feature_on_cpu(pids: &Vec<u32>, _: &Vec<ProcessData>) {
-    unsafe {
-        ebpf::set_feature_pids(
-            ebpf::FEATURE_PROFILE_ONCPU,
-            pids.as_ptr() as *const i32,
-            pids.len() as i32,
-        );
-    }
+    set_feature_pids(ebpf::FEATURE_PROFILE_ONCPU, pids);
 }
 
 pub fn set_feature_off_cpu(pids: &Vec<u32>, _: &Vec<ProcessData>) {
-    unsafe {
-        ebpf::set_feature_pids(
-            ebpf::FEATURE_PROFILE_OFFCPU,
-            pids.as_ptr() as *const i32,
-            pids.len() as i32,
-        );
-    }
+    set_feature_pids(ebpf::FEATURE_PROFILE_OFFCPU, pids);
 }
 
 pub fn set_feature_memory(pids: &Vec<u32>, _: &Vec<ProcessData>) {
-    unsafe {
-        ebpf::set_feature_pids(
-            ebpf::FEATURE_PROFILE_MEMORY,
-            pids.as_ptr() as *const i32,
-            pids.len() as i32,
-        );
-    }
+    set_feature_pids(ebpf::FEATURE_PROFILE_MEMORY, pids);
 }
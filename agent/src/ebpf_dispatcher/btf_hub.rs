@@ -0,0 +1,223 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use flate2::read::GzDecoder;
+use log::{info, warn};
+use nix::sys::utsname::uname;
+
+use crate::config::EbpfBtf;
+
+const KERNEL_BTF_PATH: &str = "/sys/kernel/btf/vmlinux";
+const OS_RELEASE_PATHS: [&str; 2] = ["/proc/1/root/etc/os-release", "/etc/os-release"];
+
+// Values of the placeholders in the hub location
+#[derive(Debug, Default, PartialEq, Eq)]
+struct KernelTarget {
+    // ID and VERSION_ID in os-release, e.g. `centos` and `7`
+    id: String,
+    version_id: String,
+    // `x86_64` or `arm64`, as the directories of BTFhub
+    arch: String,
+    // kernel release, e.g. `3.10.0-1160.el7.x86_64`
+    release: String,
+}
+
+impl KernelTarget {
+    fn current() -> Self {
+        let mut target = Self {
+            arch: match std::env::consts::ARCH {
+                "aarch64" => "arm64".to_owned(),
+                arch => arch.to_owned(),
+            },
+            release: uname().release().trim().to_owned(),
+            ..Default::default()
+        };
+        if let Some(content) = OS_RELEASE_PATHS
+            .iter()
+            .find_map(|p| fs::read_to_string(p).ok())
+        {
+            target.parse_os_release(&content);
+        }
+        target
+    }
+
+    fn parse_os_release(&mut self, content: &str) {
+        for line in content.lines() {
+            if let Some(id) = line.strip_prefix("ID=") {
+                self.id = id.replace('"', "");
+            } else if let Some(version_id) = line.strip_prefix("VERSION_ID=") {
+                self.version_id = version_id.replace('"', "");
+            }
+        }
+    }
+
+    fn expand(&self, location: &str) -> String {
+        location
+            .replace("{id}", &self.id)
+            .replace("{version_id}", &self.version_id)
+            .replace("{arch}", &self.arch)
+            .replace("{release}", &self.release)
+    }
+}
+
+fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn download(url: &str, timeout: Duration) -> io::Result<Vec<u8>> {
+    let to_io_error = |e: reqwest::Error| io::Error::new(io::ErrorKind::Other, e);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(to_io_error)?;
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .map_err(to_io_error)?;
+    Ok(response.bytes().map_err(to_io_error)?.to_vec())
+}
+
+// Writes the BTF file into cache, decompressed if gzipped
+fn save(data: &[u8], gzipped: bool, path: &Path) -> io::Result<()> {
+    let data = if gzipped {
+        let mut decompressed = vec![];
+        GzDecoder::new(data).read_to_end(&mut decompressed)?;
+        decompressed
+    } else {
+        data.to_vec()
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // written to a temporary file first so that a partial file is never used
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)
+}
+
+fn fetch(location: &str, config: &EbpfBtf, release: &str) -> io::Result<PathBuf> {
+    let gzipped = location.ends_with(".gz");
+    if !is_remote(location) && !gzipped {
+        return if Path::new(location).is_file() {
+            Ok(PathBuf::from(location))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", location),
+            ))
+        };
+    }
+
+    let cache_path = Path::new(&config.cache_dir).join(format!("{}.btf", release));
+    if fs::metadata(&cache_path)
+        .map(|m| m.len() > 0)
+        .unwrap_or(false)
+    {
+        return Ok(cache_path);
+    }
+    let data = if is_remote(location) {
+        // reqwest blocking client can not be used in async context, run it in a new thread
+        let (url, timeout) = (location.to_owned(), config.download_timeout);
+        thread::Builder::new()
+            .name("btf-download".to_owned())
+            .spawn(move || download(&url, timeout))?
+            .join()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "download thread panicked"))??
+    } else {
+        let mut data = vec![];
+        File::open(location)?.read_to_end(&mut data)?;
+        data
+    };
+    save(&data, gzipped, &cache_path)?;
+    Ok(cache_path)
+}
+
+// Returns the BTF file from the configured hub if the kernel has no BTF of its own, so that
+// CO-RE programs can be relocated and kernel offsets resolved on BTF-less kernels.
+//
+// The hub location is a local path or HTTP(S) URL with placeholders `{id}`, `{version_id}`,
+// `{arch}` and `{release}`, e.g. `https://example.com/btfhub/{id}/{version_id}/{arch}/{release}.btf`.
+// Files ending with `.gz` are decompressed, remote files are cached in `cache_dir`.
+pub fn prepare_kernel_btf(config: &EbpfBtf) -> Option<PathBuf> {
+    if config.hub_location.is_empty() || Path::new(KERNEL_BTF_PATH).exists() {
+        return None;
+    }
+    let target = KernelTarget::current();
+    let location = target.expand(&config.hub_location);
+    match fetch(&location, config, &target.release) {
+        Ok(path) => {
+            info!("kernel BTF from hub {}: {}", location, path.display());
+            Some(path)
+        }
+        Err(e) => {
+            warn!(
+                "get kernel BTF from hub {} failed: {}, eBPF falls back to offset inference",
+                location, e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    #[test]
+    fn expand_location() {
+        let mut target = KernelTarget {
+            arch: "x86_64".to_owned(),
+            release: "3.10.0-1160.el7.x86_64".to_owned(),
+            ..Default::default()
+        };
+        target.parse_os_release("NAME=\"CentOS Linux\"\nID=\"centos\"\nVERSION_ID=\"7\"\n");
+        assert_eq!(
+            target.expand("https://hub/{id}/{version_id}/{arch}/{release}.btf"),
+            "https://hub/centos/7/x86_64/3.10.0-1160.el7.x86_64.btf"
+        );
+    }
+
+    #[test]
+    fn fetch_gzipped() {
+        let dir = std::env::temp_dir().join(format!("btf-hub-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("vmlinux.btf.gz");
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"btf data").unwrap();
+        fs::write(&source, encoder.finish().unwrap()).unwrap();
+
+        let config = EbpfBtf {
+            hub_location: source.to_string_lossy().into_owned(),
+            cache_dir: dir.join("cache").to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let path = fetch(&config.hub_location, &config, "1.0.0").unwrap();
+        assert_eq!(path, dir.join("cache").join("1.0.0.btf"));
+        assert_eq!(fs::read(&path).unwrap(), b"btf data");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use libc::c_void;
+use log::{debug, warn};
+
+use public::queue::DebugSender;
+
+use crate::common::enums::IpProtocol;
+use crate::ebpf;
+use crate::metric::{
+    document::{BoxedDocument, Code, Document, DocumentFlag, Tagger},
+    meter::{DropMeter, Meter},
+};
+
+const SECONDS_PER_MINUTE: u64 = 60;
+
+extern "C" fn drop_callback(ctx: *mut c_void, info: *mut ebpf::SKB_DROP_INFO) {
+    unsafe {
+        let Some(drops) = (ctx as *mut Vec<ebpf::SKB_DROP_INFO>).as_mut() else {
+            return;
+        };
+        drops.push(*info);
+    }
+}
+
+// Reports the packets dropped inside the host stack, which are counted by the eBPF program
+// on tracepoint skb/kfree_skb, as per minute documents tagged with the drop reason.
+pub struct DropMonitor {
+    output: DebugSender<BoxedDocument>,
+    // start of the minute in which the drops are being counted, in seconds
+    current_minute: u64,
+}
+
+impl DropMonitor {
+    pub fn new(output: DebugSender<BoxedDocument>) -> Self {
+        Self {
+            output,
+            current_minute: 0,
+        }
+    }
+
+    fn to_document(info: &ebpf::SKB_DROP_INFO, timestamp: u32, agent_id: u16) -> Document {
+        let mut tagger = Tagger {
+            code: Code::IP_PATH
+                | Code::PROTOCOL
+                | Code::SERVER_PORT
+                | Code::VTAP_ID
+                | Code::DROP_REASON,
+            agent_id,
+            drop_reason: Some(info.reason()),
+            ..Default::default()
+        };
+        if let Some((src, dst)) = info.addrs() {
+            tagger.ip = src;
+            tagger.ip1 = dst;
+            tagger.is_ipv6 = src.is_ipv6();
+            tagger.protocol = IpProtocol::from(info.protocol);
+            tagger.server_port = info.dport;
+        }
+
+        let mut doc = Document::new(Meter::Drop(DropMeter {
+            packets: info.packets,
+            bytes: info.bytes,
+        }));
+        doc.timestamp = timestamp;
+        doc.tagger = tagger;
+        doc.flags = DocumentFlag::NONE;
+        doc
+    }
+
+    // Collects the drops and sends them when a minute ends
+    pub fn flush(&mut self, now: Duration, agent_id: u16) {
+        let minute = now.as_secs() / SECONDS_PER_MINUTE * SECONDS_PER_MINUTE;
+        if minute <= self.current_minute {
+            return;
+        }
+        let timestamp = self.current_minute as u32;
+        let first_flush = self.current_minute == 0;
+        self.current_minute = minute;
+
+        let mut drops: Vec<ebpf::SKB_DROP_INFO> = vec![];
+        let n =
+            unsafe { ebpf::collect_skb_drops(drop_callback, &mut drops as *mut _ as *mut c_void) };
+        if n < 0 {
+            warn!("ebpf collect_skb_drops error: {}", n);
+            return;
+        }
+        // drops before the agent starts are not counted in a whole minute
+        if first_flush || drops.is_empty() {
+            return;
+        }
+        debug!(
+            "{} aggregated kernel drops in minute {}",
+            drops.len(),
+            timestamp
+        );
+
+        let mut docs = drops
+            .iter()
+            .map(|info| BoxedDocument(Box::new(Self::to_document(info, timestamp, agent_id))))
+            .collect::<Vec<_>>();
+        if let Err(e) = self.output.send_all(&mut docs) {
+            warn!("send kernel drop documents failed: {:?}", e);
+        }
+    }
+}
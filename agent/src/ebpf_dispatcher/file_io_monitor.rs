@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use libc::c_void;
+use log::{debug, warn};
+use procfs::process::Process;
+
+use public::queue::DebugSender;
+
+use super::process_tracker;
+use crate::common::flow::SignalSource;
+use crate::ebpf;
+use crate::metric::{
+    document::{BoxedDocument, Code, Document, DocumentFlag, Tagger},
+    meter::{FileIoMeter, Meter},
+};
+use crate::platform::get_container_id;
+use crate::policy::PolicyGetter;
+
+const SECONDS_PER_MINUTE: u64 = 60;
+// operation of FILE_IO_INFO, same as the IO events
+const OPERATION_WRITE: u32 = 0;
+
+extern "C" fn file_io_callback(ctx: *mut c_void, info: *mut ebpf::FILE_IO_INFO) {
+    unsafe {
+        let Some(ios) = (ctx as *mut Vec<ebpf::FILE_IO_INFO>).as_mut() else {
+            return;
+        };
+        ios.push(*info);
+    }
+}
+
+// Finds the mount point of the device for the path filters, in the mount namespace of the process.
+//
+// The eBPF program only knows the device of the file system, so a filter matches the mount point
+// on which the filtered path lives, e.g. `/var/lib/mysql` matches the mount point `/` if it is not
+// mounted separately. With no filters, the shortest mount point of the device is used.
+fn match_mount_point(
+    mounts: &[(String, PathBuf)],
+    majmin: &str,
+    filters: &[String],
+) -> Option<String> {
+    let mount_points = mounts
+        .iter()
+        .filter(|(m, _)| m == majmin)
+        .map(|(_, p)| p.as_path());
+    if filters.is_empty() {
+        return mount_points
+            .min_by_key(|p| p.as_os_str().len())
+            .map(|p| p.to_string_lossy().into_owned());
+    }
+    mount_points
+        .filter(|p| filters.iter().any(|f| Path::new(f).starts_with(p)))
+        .max_by_key(|p| p.as_os_str().len())
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+// Reports the vfs_read()/vfs_write() calls on regular files, which are counted by the eBPF
+// kprobes, as per minute documents of the processes tagged with the mount point.
+pub struct FileIoMonitor {
+    output: DebugSender<BoxedDocument>,
+    policy_getter: PolicyGetter,
+    // start of the minute in which the IO is being counted, in seconds
+    current_minute: u64,
+}
+
+impl FileIoMonitor {
+    pub fn new(output: DebugSender<BoxedDocument>, policy_getter: PolicyGetter) -> Self {
+        Self {
+            output,
+            policy_getter,
+            current_minute: 0,
+        }
+    }
+
+    fn lookup_pod_id(&self, pid: u32) -> u32 {
+        process_tracker::container_id(pid)
+            .or_else(|| {
+                Process::new(pid as i32)
+                    .ok()
+                    .and_then(|p| get_container_id(&p))
+            })
+            .map(|id| self.policy_getter.lookup_pod_id(&id))
+            .unwrap_or_default()
+    }
+
+    fn to_document(
+        &self,
+        pid: u32,
+        comm: String,
+        mount_point: String,
+        meter: FileIoMeter,
+        timestamp: u32,
+        agent_id: u16,
+    ) -> Document {
+        let tagger = Tagger {
+            code: Code::IP | Code::VTAP_ID | Code::MOUNT_POINT,
+            agent_id,
+            signal_source: SignalSource::EBPF,
+            otel_service: Some(comm),
+            otel_instance: Some(pid.to_string()),
+            mount_point: Some(mount_point),
+            pod_id: self.lookup_pod_id(pid),
+            ..Default::default()
+        };
+
+        let mut doc = Document::new(Meter::FileIo(meter));
+        doc.timestamp = timestamp;
+        doc.tagger = tagger;
+        doc.flags = DocumentFlag::NONE;
+        doc
+    }
+
+    // Collects the file IO and sends it when a minute ends
+    pub fn flush(&mut self, now: Duration, agent_id: u16, path_filters: &[String]) {
+        let minute = now.as_secs() / SECONDS_PER_MINUTE * SECONDS_PER_MINUTE;
+        if minute <= self.current_minute {
+            return;
+        }
+        let timestamp = self.current_minute as u32;
+        let first_flush = self.current_minute == 0;
+        self.current_minute = minute;
+
+        let mut ios: Vec<ebpf::FILE_IO_INFO> = vec![];
+        let n = unsafe {
+            ebpf::collect_file_io_stats(file_io_callback, &mut ios as *mut _ as *mut c_void)
+        };
+        if n < 0 {
+            warn!("ebpf collect_file_io_stats error: {}", n);
+            return;
+        }
+        // IO before the agent starts is not counted in a whole minute
+        if first_flush || ios.is_empty() {
+            return;
+        }
+        debug!("{} aggregated file IO in minute {}", ios.len(), timestamp);
+
+        // (major:minor, mount point) of the processes, exited processes have none
+        let mut mounts: HashMap<u32, Vec<(String, PathBuf)>> = HashMap::new();
+        // reads and writes of a process on a mount point are in the same document
+        let mut meters: HashMap<(u32, String), (String, FileIoMeter)> = HashMap::new();
+        for info in ios.iter() {
+            let process_mounts = mounts.entry(info.pid).or_insert_with(|| {
+                Process::new(info.pid as i32)
+                    .and_then(|p| p.mountinfo())
+                    .map(|ms| ms.into_iter().map(|m| (m.majmin, m.mount_point)).collect())
+                    .unwrap_or_default()
+            });
+            let majmin = format!("{}:{}", info.major, info.minor);
+            let Some(mount_point) = match_mount_point(process_mounts, &majmin, path_filters) else {
+                continue;
+            };
+            let (_, meter) = meters
+                .entry((info.pid, mount_point))
+                .or_insert_with(|| (info.comm(), FileIoMeter::default()));
+            if info.operation == OPERATION_WRITE {
+                meter.write_count += info.count;
+                meter.write_bytes += info.bytes;
+                meter.write_latency_sum += info.latency_sum;
+                meter.write_latency_max = meter.write_latency_max.max(info.latency_max);
+            } else {
+                meter.read_count += info.count;
+                meter.read_bytes += info.bytes;
+                meter.read_latency_sum += info.latency_sum;
+                meter.read_latency_max = meter.read_latency_max.max(info.latency_max);
+            }
+        }
+
+        let mut docs = meters
+            .into_iter()
+            .map(|((pid, mount_point), (comm, meter))| {
+                BoxedDocument(Box::new(self.to_document(
+                    pid,
+                    comm,
+                    mount_point,
+                    meter,
+                    timestamp,
+                    agent_id,
+                )))
+            })
+            .collect::<Vec<_>>();
+        if docs.is_empty() {
+            return;
+        }
+        if let Err(e) = self.output.send_all(&mut docs) {
+            warn!("send file io documents failed: {:?}", e);
+        }
+    }
+}
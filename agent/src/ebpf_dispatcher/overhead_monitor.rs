@@ -0,0 +1,380 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::ptr;
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::time::{Duration, Instant};
+
+use libc::{c_int, c_void};
+use log::{info, warn};
+
+use super::memory_profile;
+use crate::config::EbpfOverhead;
+use crate::ebpf;
+use crate::utils::stats::{self, Countable, RefCountable};
+
+const BPF_STATS_ENABLED_PATH: &str = "/proc/sys/kernel/bpf_stats_enabled";
+const PROFILER_TRACER_NAME: &str = "continuous_profiler";
+
+// Bitmap of the FEATURE_* shed by the monitor, probes of which are not attached again
+static SHED_FEATURES: AtomicU32 = AtomicU32::new(0);
+
+pub fn is_shed(feature: c_int) -> bool {
+    SHED_FEATURES.load(Ordering::Relaxed) & (1 << feature) != 0
+}
+
+// Probes which can be detached without affecting the socket tracing, in the order of shedding
+// if their costs are not considered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum OptionalProbes {
+    Profiler,
+    DnsResolver,
+    Openssl,
+    Golang,
+}
+
+impl OptionalProbes {
+    // Uprobe programs are named `df_U_<function>` or `df_UR_<function>`
+    fn of(tracer_name: &str, prog_name: &str) -> Option<Self> {
+        if tracer_name == PROFILER_TRACER_NAME {
+            return Some(Self::Profiler);
+        }
+        let function = prog_name
+            .strip_prefix("df_UR_")
+            .or_else(|| prog_name.strip_prefix("df_U_"))?;
+        if function.starts_with("memory_") {
+            Some(Self::Profiler)
+        } else if function.starts_with("dns_") {
+            Some(Self::DnsResolver)
+        } else if function.starts_with("openssl_") {
+            Some(Self::Openssl)
+        } else if function.starts_with("go_")
+            || function.starts_with("golang_")
+            || function.contains("runtime_")
+        {
+            Some(Self::Golang)
+        } else {
+            None
+        }
+    }
+
+    fn features(&self) -> &'static [c_int] {
+        match self {
+            Self::Profiler => &[
+                ebpf::FEATURE_PROFILE_ONCPU,
+                ebpf::FEATURE_PROFILE_OFFCPU,
+                ebpf::FEATURE_PROFILE_MEMORY,
+            ],
+            Self::DnsResolver => &[ebpf::FEATURE_UPROBE_DNS_RESOLVER],
+            Self::Openssl => &[ebpf::FEATURE_UPROBE_OPENSSL],
+            Self::Golang => &[
+                ebpf::FEATURE_UPROBE_GOLANG,
+                ebpf::FEATURE_UPROBE_GOLANG_SYMBOL,
+            ],
+        }
+    }
+
+    fn shed(&self) {
+        for feature in self.features() {
+            SHED_FEATURES.fetch_or(1 << feature, Ordering::Relaxed);
+        }
+        unsafe {
+            if *self == Self::Profiler {
+                if !ebpf::continuous_profiler_running() {
+                    return;
+                }
+                let mut contexts: [*mut c_void; 3] = [ptr::null_mut(); 3];
+                ebpf::stop_continuous_profiler(
+                    &mut contexts as *mut [*mut c_void; ebpf::PROFILER_CTX_NUM],
+                );
+                if !contexts[ebpf::PROFILER_CTX_MEMORY_IDX].is_null() {
+                    std::mem::drop(Box::from_raw(
+                        contexts[ebpf::PROFILER_CTX_MEMORY_IDX]
+                            as *mut memory_profile::MemoryContext,
+                    ));
+                }
+                return;
+            }
+            for feature in self.features() {
+                ebpf::set_feature_pids(*feature, ptr::null(), 0);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ProgramCounter {
+    run_count: AtomicU64,
+    run_time: AtomicU64,
+}
+
+impl RefCountable for ProgramCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "run_count",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.run_count.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "run_time_ns",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.run_time.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Default)]
+pub struct OverheadCounter {
+    // CPU time of all eBPF programs in percentage of one core, in f64 bits
+    cpu_usage: AtomicU64,
+    // number of optional probe groups shed
+    shed_level: AtomicU64,
+}
+
+impl RefCountable for OverheadCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "cpu_usage",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Float(f64::from_bits(self.cpu_usage.load(Ordering::Relaxed))),
+            ),
+            (
+                "shed_level",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.shed_level.load(Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Program {
+    probes: Option<OptionalProbes>,
+    last_run_count: u64,
+    last_run_time: u64,
+    counter: Arc<ProgramCounter>,
+}
+
+extern "C" fn prog_stats_callback(ctx: *mut c_void, stats: *mut ebpf::BPF_PROG_STATS) {
+    unsafe {
+        let Some(programs) = (ctx as *mut Vec<ebpf::BPF_PROG_STATS>).as_mut() else {
+            return;
+        };
+        programs.push(*stats);
+    }
+}
+
+// Measures the run count and run time of each eBPF program with the kernel statistics, and
+// detaches the optional probes when the total CPU time of eBPF exceeds the budget, profiling
+// first and then the most expensive uprobes.
+//
+// Shedding lasts until the agent restarts.
+pub struct OverheadMonitor {
+    cpu_budget: u32,
+    interval: Duration,
+    last_check: Option<Instant>,
+    // stats were disabled in the kernel before the monitor enables it
+    restore_bpf_stats: bool,
+    programs: HashMap<(String, String), Program>,
+    shed: Vec<OptionalProbes>,
+    stats_collector: Arc<stats::Collector>,
+    counter: Arc<OverheadCounter>,
+}
+
+impl OverheadMonitor {
+    pub fn new(config: &EbpfOverhead, stats_collector: Arc<stats::Collector>) -> Self {
+        let enabled = fs::read_to_string(BPF_STATS_ENABLED_PATH)
+            .map(|s| s.trim() != "0")
+            .unwrap_or(false);
+        let restore_bpf_stats = !enabled && unsafe { ebpf::set_bpf_stats_enabled(true) } == 0;
+        let counter = Arc::new(OverheadCounter::default());
+        stats_collector.register_countable(
+            &stats::NoTagModule("ebpf-overhead"),
+            Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+        );
+        Self {
+            cpu_budget: config.cpu_budget,
+            interval: config.check_interval,
+            last_check: None,
+            restore_bpf_stats,
+            programs: HashMap::new(),
+            shed: vec![],
+            stats_collector,
+            counter,
+        }
+    }
+
+    // Returns the run time of the programs since the last call
+    fn collect(&mut self) -> HashMap<Option<OptionalProbes>, u64> {
+        let mut stats: Vec<ebpf::BPF_PROG_STATS> = vec![];
+        let n = unsafe {
+            ebpf::collect_bpf_prog_stats(prog_stats_callback, &mut stats as *mut _ as *mut c_void)
+        };
+        if n < 0 {
+            warn!("ebpf collect_bpf_prog_stats error: {}", n);
+        }
+
+        let mut run_times = HashMap::new();
+        for s in stats.iter() {
+            let (tracer_name, prog_name) = (s.tracer_name(), s.prog_name());
+            let program = self
+                .programs
+                .entry((tracer_name.clone(), prog_name.clone()))
+                .or_insert_with(|| {
+                    let counter = Arc::new(ProgramCounter::default());
+                    self.stats_collector.register_countable(
+                        &stats::SingleTagModule("ebpf-program", "name", &prog_name),
+                        Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+                    );
+                    Program {
+                        probes: OptionalProbes::of(&tracer_name, &prog_name),
+                        last_run_count: s.run_cnt,
+                        last_run_time: s.run_time_ns,
+                        counter,
+                    }
+                });
+            let run_count = s.run_cnt.saturating_sub(program.last_run_count);
+            let run_time = s.run_time_ns.saturating_sub(program.last_run_time);
+            program.last_run_count = s.run_cnt;
+            program.last_run_time = s.run_time_ns;
+            program
+                .counter
+                .run_count
+                .fetch_add(run_count, Ordering::Relaxed);
+            program
+                .counter
+                .run_time
+                .fetch_add(run_time, Ordering::Relaxed);
+            *run_times.entry(program.probes).or_default() += run_time;
+        }
+        run_times
+    }
+
+    // Profiling is shed first, then the uprobes costing the most
+    fn next_to_shed(
+        &self,
+        run_times: &HashMap<Option<OptionalProbes>, u64>,
+    ) -> Option<OptionalProbes> {
+        if !self.shed.contains(&OptionalProbes::Profiler)
+            && unsafe { ebpf::continuous_profiler_running() }
+        {
+            return Some(OptionalProbes::Profiler);
+        }
+        run_times
+            .iter()
+            .filter_map(|(probes, run_time)| {
+                probes
+                    .filter(|p| *p != OptionalProbes::Profiler && !self.shed.contains(p))
+                    .filter(|_| *run_time > 0)
+                    .map(|p| (p, *run_time))
+            })
+            .max_by_key(|(_, run_time)| *run_time)
+            .map(|(p, _)| p)
+    }
+
+    pub fn flush(&mut self) {
+        let now = Instant::now();
+        let elapsed = match self.last_check {
+            Some(last) if now.duration_since(last) < self.interval => return,
+            Some(last) => now.duration_since(last),
+            None => {
+                // the first collection sets the baseline of the programs
+                self.last_check = Some(now);
+                self.collect();
+                return;
+            }
+        };
+        self.last_check = Some(now);
+
+        let run_times = self.collect();
+        let total = run_times.values().sum::<u64>();
+        let cpu_usage = total as f64 * 100.0 / elapsed.as_nanos() as f64;
+        self.counter
+            .cpu_usage
+            .store(cpu_usage.to_bits(), Ordering::Relaxed);
+        if self.cpu_budget == 0 || cpu_usage <= self.cpu_budget as f64 {
+            return;
+        }
+
+        let Some(probes) = self.next_to_shed(&run_times) else {
+            warn!(
+                "eBPF CPU usage {:.2}% exceeds budget {}%, no optional probes left to shed",
+                cpu_usage, self.cpu_budget
+            );
+            return;
+        };
+        warn!(
+            "eBPF CPU usage {:.2}% exceeds budget {}%, shedding {:?} probes until agent restarts",
+            cpu_usage, self.cpu_budget, probes
+        );
+        probes.shed();
+        self.shed.push(probes);
+        self.counter
+            .shed_level
+            .store(self.shed.len() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for OverheadMonitor {
+    fn drop(&mut self) {
+        if self.restore_bpf_stats {
+            unsafe {
+                ebpf::set_bpf_stats_enabled(false);
+            }
+        }
+        info!(
+            "ebpf overhead monitor stopped, shed probes: {:?}",
+            self.shed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_programs() {
+        assert_eq!(
+            OptionalProbes::of("continuous_profiler", "df_PE_oncpu"),
+            Some(OptionalProbes::Profiler)
+        );
+        assert_eq!(
+            OptionalProbes::of("socket-trace", "df_UR_dns_getaddrinfo_exit"),
+            Some(OptionalProbes::DnsResolver)
+        );
+        assert_eq!(
+            OptionalProbes::of("socket-trace", "df_U_openssl_write_ex_enter"),
+            Some(OptionalProbes::Openssl)
+        );
+        assert_eq!(
+            OptionalProbes::of("socket-trace", "df_U_enter_runtime_newproc1"),
+            Some(OptionalProbes::Golang)
+        );
+        assert_eq!(
+            OptionalProbes::of("socket-trace", "df_U_go_tls_write_enter"),
+            Some(OptionalProbes::Golang)
+        );
+        assert_eq!(OptionalProbes::of("socket-trace", "df_T_enter_write"), None);
+    }
+}
@@ -0,0 +1,262 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock, Weak,
+};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use procfs::process::Process;
+
+use crate::config::EbpfProcessEvents;
+use crate::ebpf;
+use crate::platform::get_container_id;
+use crate::utils::{
+    process::ProcessListener,
+    stats::{self, Countable, RefCountable},
+};
+
+lazy_static! {
+    static ref TRACKER: RwLock<Option<ProcessTracker>> = RwLock::new(None);
+}
+
+#[derive(Default)]
+pub struct ProcessTrackerCounter {
+    exec: AtomicU64,
+    exit: AtomicU64,
+    // processes exited before their container could be read from /proc
+    unresolved: AtomicU64,
+    // events dropped because the table is full
+    dropped: AtomicU64,
+    entries: AtomicU64,
+}
+
+impl RefCountable for ProcessTrackerCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "exec",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.exec.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "exit",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.exit.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "unresolved",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.unresolved.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "entries",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.entries.load(Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct TrackedProcess {
+    container_id: String,
+    exited_at: Option<Instant>,
+}
+
+// Keeps the container of each process from its exec event until a while after its exit, so
+// that data captured from short-lived processes can still be attributed to their pods.
+struct ProcessTracker {
+    processes: HashMap<u32, TrackedProcess>,
+    max_entries: usize,
+    exited_retention: Duration,
+    process_listener: Weak<ProcessListener>,
+    counter: Arc<ProcessTrackerCounter>,
+}
+
+impl ProcessTracker {
+    fn purge(&mut self, now: Instant) {
+        let retention = self.exited_retention;
+        self.processes.retain(|_, p| match p.exited_at {
+            Some(exited_at) => now.duration_since(exited_at) < retention,
+            None => true,
+        });
+    }
+
+    fn on_exec(&mut self, pid: u32, container_id: Option<String>, now: Instant) {
+        self.counter.exec.fetch_add(1, Ordering::Relaxed);
+        if self.processes.len() >= self.max_entries && !self.processes.contains_key(&pid) {
+            self.purge(now);
+            if self.processes.len() >= self.max_entries {
+                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        let container_id = container_id.unwrap_or_else(|| {
+            self.counter.unresolved.fetch_add(1, Ordering::Relaxed);
+            String::new()
+        });
+        self.processes.insert(
+            pid,
+            TrackedProcess {
+                container_id,
+                exited_at: None,
+            },
+        );
+    }
+
+    fn on_exit(&mut self, pid: u32, now: Instant) {
+        self.counter.exit.fetch_add(1, Ordering::Relaxed);
+        if let Some(p) = self.processes.get_mut(&pid) {
+            p.exited_at = Some(now);
+        }
+    }
+
+    fn container_id(&self, pid: u32) -> Option<&str> {
+        self.processes
+            .get(&pid)
+            .map(|p| p.container_id.as_str())
+            .filter(|id| !id.is_empty())
+    }
+}
+
+extern "C" fn process_event_callback(data: *mut ebpf::PROCESS_EVENT) {
+    let (event_type, pid) = unsafe { ((*data).event_type, (*data).pid) };
+    let now = Instant::now();
+    // read before locking the table, the process may be short-lived
+    let container_id = if event_type & ebpf::EVENT_TYPE_PROC_EXEC != 0 {
+        Process::new(pid as i32)
+            .ok()
+            .and_then(|p| get_container_id(&p))
+    } else {
+        None
+    };
+
+    let mut guard = TRACKER.write().unwrap();
+    let Some(tracker) = guard.as_mut() else {
+        return;
+    };
+    if event_type & ebpf::EVENT_TYPE_PROC_EXEC != 0 {
+        tracker.on_exec(pid, container_id, now);
+    } else if event_type & ebpf::EVENT_TYPE_PROC_EXIT != 0 {
+        tracker.on_exit(pid, now);
+    }
+    tracker
+        .counter
+        .entries
+        .store(tracker.processes.len() as u64, Ordering::Relaxed);
+    // processes synchronized to the controller are updated in the next second
+    if let Some(listener) = tracker.process_listener.upgrade() {
+        listener.request_rescan();
+    }
+}
+
+// Tracks process exec/exit with the sched_process_exec/exit tracepoints of the socket tracer.
+//
+// Must be called before the socket tracer starts. While the events are received, the process
+// listener scans /proc on events and only occasionally otherwise.
+pub fn start(
+    config: &EbpfProcessEvents,
+    process_listener: &Arc<ProcessListener>,
+    stats_collector: &stats::Collector,
+) {
+    if !config.enabled {
+        return;
+    }
+    let counter = Arc::new(ProcessTrackerCounter::default());
+    stats_collector.register_countable(
+        &stats::NoTagModule("ebpf-process-tracker"),
+        Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+    );
+    *TRACKER.write().unwrap() = Some(ProcessTracker {
+        processes: HashMap::new(),
+        max_entries: config.max_entries as usize,
+        exited_retention: config.exited_retention,
+        process_listener: Arc::downgrade(process_listener),
+        counter,
+    });
+    unsafe {
+        if ebpf::register_event_handle(
+            ebpf::EVENT_TYPE_PROC_EXEC | ebpf::EVENT_TYPE_PROC_EXIT,
+            process_event_callback,
+        ) != 0
+        {
+            warn!("ebpf register_event_handle for process events failed");
+            *TRACKER.write().unwrap() = None;
+            return;
+        }
+    }
+    info!(
+        "ebpf process tracker started, max entries: {} exited retention: {:?}",
+        config.max_entries, config.exited_retention
+    );
+}
+
+pub fn is_running() -> bool {
+    TRACKER.read().unwrap().is_some()
+}
+
+// Returns the container of the process, which may have exited recently
+pub fn container_id(pid: u32) -> Option<String> {
+    TRACKER
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|t| t.container_id(pid).map(str::to_owned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_tracker(max_entries: usize) -> ProcessTracker {
+        ProcessTracker {
+            processes: HashMap::new(),
+            max_entries,
+            exited_retention: Duration::from_secs(60),
+            process_listener: Weak::new(),
+            counter: Default::default(),
+        }
+    }
+
+    #[test]
+    fn exited_processes_retained() {
+        let mut tracker = new_tracker(2);
+        let now = Instant::now();
+        tracker.on_exec(1, Some("c1".to_owned()), now);
+        tracker.on_exec(2, None, now);
+        tracker.on_exit(1, now);
+        assert_eq!(tracker.container_id(1), Some("c1"));
+        assert_eq!(tracker.container_id(2), None);
+
+        // full before the retention expires
+        tracker.on_exec(3, Some("c3".to_owned()), now + Duration::from_secs(30));
+        assert_eq!(tracker.container_id(3), None);
+        assert_eq!(tracker.counter.dropped.load(Ordering::Relaxed), 1);
+
+        tracker.on_exec(3, Some("c3".to_owned()), now + Duration::from_secs(61));
+        assert_eq!(tracker.container_id(1), None);
+        assert_eq!(tracker.container_id(3), Some("c3"));
+    }
+}
@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use libc::c_void;
+use log::{debug, warn};
+use procfs::process::Process;
+
+use public::queue::DebugSender;
+
+use super::process_tracker;
+use crate::common::flow::{L7Protocol, SignalSource};
+use crate::ebpf;
+use crate::metric::{
+    document::{BoxedDocument, Code, Direction, Document, DocumentFlag, Tagger},
+    meter::{AppAnomaly, AppLatency, AppMeter, AppTraffic, Meter},
+};
+use crate::platform::get_container_id;
+use crate::policy::PolicyGetter;
+use crate::utils::stats;
+
+const SECONDS_PER_MINUTE: u64 = 60;
+
+// How the resolver calls relate to the DNS traffic of the processes
+#[derive(Default)]
+pub struct ResolverMonitorCounter {
+    calls: AtomicU64,
+    // DNS queries sent by the calling threads before the calls return
+    queries: AtomicU64,
+    // queries sent to a stub resolver on loopback, e.g. systemd-resolved on 127.0.0.53
+    loopback_queries: AtomicU64,
+    // calls answered by /etc/hosts, nscd or systemd-resolved over UNIX sockets
+    no_query_calls: AtomicU64,
+}
+
+impl stats::RefCountable for ResolverMonitorCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "calls",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.calls.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "queries",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.queries.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "loopback_queries",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.loopback_queries.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "no_query_calls",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.no_query_calls.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+extern "C" fn resolver_callback(ctx: *mut c_void, info: *mut ebpf::DNS_RESOLVER_INFO) {
+    unsafe {
+        let Some(calls) = (ctx as *mut Vec<ebpf::DNS_RESOLVER_INFO>).as_mut() else {
+            return;
+        };
+        calls.push(*info);
+    }
+}
+
+// Reports the getaddrinfo()/gethostbyname() calls, which are measured by the eBPF uprobes,
+// as per minute DNS application metrics of the calling processes.
+pub struct ResolverMonitor {
+    output: DebugSender<BoxedDocument>,
+    policy_getter: PolicyGetter,
+    // start of the minute in which the calls are being counted, in seconds
+    current_minute: u64,
+    counter: Arc<ResolverMonitorCounter>,
+}
+
+impl ResolverMonitor {
+    pub fn new(output: DebugSender<BoxedDocument>, policy_getter: PolicyGetter) -> Self {
+        Self {
+            output,
+            policy_getter,
+            current_minute: 0,
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<ResolverMonitorCounter> {
+        self.counter.clone()
+    }
+
+    fn lookup_pod_id(&self, pid: u32) -> u32 {
+        process_tracker::container_id(pid)
+            .or_else(|| {
+                Process::new(pid as i32)
+                    .ok()
+                    .and_then(|p| get_container_id(&p))
+            })
+            .map(|id| self.policy_getter.lookup_pod_id(&id))
+            .unwrap_or_default()
+    }
+
+    fn to_document(
+        &self,
+        info: &ebpf::DNS_RESOLVER_INFO,
+        timestamp: u32,
+        agent_id: u16,
+    ) -> Document {
+        let tagger = Tagger {
+            code: Code::IP | Code::VTAP_ID | Code::DIRECTION | Code::L7_PROTOCOL,
+            agent_id,
+            direction: Direction::ClientProcessToServer,
+            l7_protocol: L7Protocol::DNS,
+            signal_source: SignalSource::EBPF,
+            otel_service: Some(info.comm()),
+            otel_instance: Some(info.pid.to_string()),
+            endpoint: Some(info.api_name().to_string()),
+            pod_id: self.lookup_pod_id(info.pid),
+            ..Default::default()
+        };
+
+        let calls = info.calls as u32;
+        let mut doc = Document::new(Meter::App(AppMeter {
+            traffic: AppTraffic {
+                request: calls,
+                response: calls,
+                ..Default::default()
+            },
+            latency: AppLatency {
+                rrt_max: info.latency_max as u32,
+                rrt_sum: info.latency_sum,
+                rrt_count: calls,
+            },
+            anomaly: AppAnomaly {
+                client_error: info.client_errors as u32,
+                server_error: info.server_errors as u32,
+                timeout: info.timeouts as u32,
+            },
+            ..Default::default()
+        }));
+        doc.timestamp = timestamp;
+        doc.tagger = tagger;
+        doc.flags = DocumentFlag::NONE;
+        doc
+    }
+
+    // Collects the resolver calls and sends them when a minute ends
+    pub fn flush(&mut self, now: Duration, agent_id: u16) {
+        let minute = now.as_secs() / SECONDS_PER_MINUTE * SECONDS_PER_MINUTE;
+        if minute <= self.current_minute {
+            return;
+        }
+        let timestamp = self.current_minute as u32;
+        let first_flush = self.current_minute == 0;
+        self.current_minute = minute;
+
+        let mut calls: Vec<ebpf::DNS_RESOLVER_INFO> = vec![];
+        let n = unsafe {
+            ebpf::collect_dns_resolver_stats(resolver_callback, &mut calls as *mut _ as *mut c_void)
+        };
+        if n < 0 {
+            warn!("ebpf collect_dns_resolver_stats error: {}", n);
+            return;
+        }
+        // calls before the agent starts are not counted in a whole minute
+        if first_flush || calls.is_empty() {
+            return;
+        }
+        debug!(
+            "{} aggregated resolver calls in minute {}",
+            calls.len(),
+            timestamp
+        );
+
+        for info in calls.iter() {
+            self.counter.calls.fetch_add(info.calls, Ordering::Relaxed);
+            self.counter
+                .queries
+                .fetch_add(info.queries, Ordering::Relaxed);
+            self.counter
+                .loopback_queries
+                .fetch_add(info.loopback_queries, Ordering::Relaxed);
+            self.counter
+                .no_query_calls
+                .fetch_add(info.no_query_calls, Ordering::Relaxed);
+        }
+        let mut docs = calls
+            .iter()
+            .map(|info| BoxedDocument(Box::new(self.to_document(info, timestamp, agent_id))))
+            .collect::<Vec<_>>();
+        if let Err(e) = self.output.send_all(&mut docs) {
+            warn!("send resolver documents failed: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,702 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bitflags::bitflags;
+use ipnet::IpNet;
+use log::{debug, warn};
+use prost::Message;
+
+use public::{
+    proto::alert_event,
+    queue::DebugSender,
+    sender::{SendMessageType, Sendable},
+};
+
+use crate::common::{
+    enums::{CaptureNetworkType, IpProtocol, TcpFlags},
+    meta_packet::{MetaPacket, ProtocolData},
+    port_range::PortRange,
+    Timestamp,
+};
+use crate::config::{FlowAnomalyDetection, FlowPolicyRule};
+use crate::sender::syslog_exporter::SyslogExporter;
+use crate::utils::stats::{self, Counter, CounterType, CounterValue};
+
+// Sources or servers not seen before are not counted when the tables are full
+const ANOMALY_TABLE_CAPACITY: usize = 1 << 16;
+
+// policy_type and event_level of the alert events, see the alert_event table of deepflow-server
+pub(crate) const ALERT_POLICY_TYPE_SYSTEM: u32 = 1;
+pub(crate) const ALERT_EVENT_LEVEL_ERROR: u32 = 2;
+pub(crate) const ALERT_EVENT_LEVEL_WARN: u32 = 3;
+
+bitflags! {
+    // anomalies a flow is part of
+    #[derive(Default)]
+    pub struct FlowAnomaly: u8 {
+        const PORT_SCAN = 1 << 0;
+        const SYN_FLOOD = 1 << 1;
+        const CONNECTION_STORM = 1 << 2;
+        // marked by the AppAlertEvaluator while the server endpoint breaches the thresholds
+        const APP_ERROR_RATE = 1 << 3;
+        const APP_LATENCY = 1 << 4;
+        const POLICY_VIOLATION = 1 << 5;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SecurityEventType {
+    PortScan = 1,
+    SynFlood = 2,
+    ConnectionStorm = 3,
+    // found by the syslog exporter, not sent to deepflow-server
+    NewListeningPort = 4,
+    PolicyViolation = 5,
+}
+
+impl SecurityEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PortScan => "port_scan",
+            Self::SynFlood => "syn_flood",
+            Self::ConnectionStorm => "connection_storm",
+            Self::NewListeningPort => "new_listening_port",
+            Self::PolicyViolation => "policy_violation",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SecurityEvent {
+    pub time: Timestamp,
+    pub event_type: SecurityEventType,
+    pub tap_type: CaptureNetworkType,
+    pub ip: IpAddr,
+    pub port: u16,
+    pub count: u32,
+    pub threshold: u32,
+    pub window: u32,
+    // destination of the policy violation
+    pub peer_ip: Option<IpAddr>,
+    pub protocol: IpProtocol,
+    // name of the violated policy rule
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct BoxedSecurityEvent(pub Box<SecurityEvent>);
+
+impl Sendable for BoxedSecurityEvent {
+    fn encode(self, buf: &mut Vec<u8>) -> Result<usize, prost::EncodeError> {
+        let e = self.0;
+        let mut target_tags = format!("ip={}", e.ip);
+        let mut tag_str_keys = vec!["ip".to_owned()];
+        let mut tag_str_values = vec![e.ip.to_string()];
+        if let Some(peer_ip) = e.peer_ip {
+            target_tags.push_str(&format!(",peer_ip={}", peer_ip));
+            tag_str_keys.push("peer_ip".to_owned());
+            tag_str_values.push(peer_ip.to_string());
+        }
+        if e.port != 0 {
+            target_tags.push_str(&format!(",port={}", e.port));
+        }
+        if !e.detail.is_empty() {
+            tag_str_keys.push("policy_rule".to_owned());
+            tag_str_values.push(e.detail);
+        }
+        let tag_ints = [
+            ("capture_network_type_id", u16::from(e.tap_type) as i64),
+            ("port", e.port as i64),
+            ("protocol", u8::from(e.protocol) as i64),
+            ("threshold", e.threshold as i64),
+            ("window", e.window as i64),
+        ];
+        let pb_event = alert_event::AlertEvent {
+            time: Some(e.time.as_secs() as u32),
+            policy_type: Some(ALERT_POLICY_TYPE_SYSTEM),
+            alert_policy: Some(e.event_type.as_str().to_owned()),
+            metric_value: Some(e.count as f64),
+            event_level: Some(match e.event_type {
+                SecurityEventType::SynFlood => ALERT_EVENT_LEVEL_ERROR,
+                _ => ALERT_EVENT_LEVEL_WARN,
+            }),
+            target_tags: Some(target_tags),
+            tag_str_keys,
+            tag_str_values,
+            tag_int_keys: tag_ints.iter().map(|(k, _)| k.to_string()).collect(),
+            tag_int_values: tag_ints.iter().map(|(_, v)| *v).collect(),
+            ..Default::default()
+        };
+        pb_event.encode(buf).map(|_| pb_event.encoded_len())
+    }
+
+    // carried as alert events of the deepflow-server, with the event type as the alert policy
+    fn message_type(&self) -> SendMessageType {
+        SendMessageType::AlarmEvent
+    }
+}
+
+#[derive(Default)]
+pub struct AnomalyCounter {
+    port_scan: AtomicU64,
+    syn_flood: AtomicU64,
+    connection_storm: AtomicU64,
+    policy_violation: AtomicU64,
+    // new sources or servers not counted as the tables are full
+    table_full: AtomicU64,
+}
+
+impl stats::RefCountable for AnomalyCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "port_scan",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.port_scan.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "syn_flood",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.syn_flood.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "connection_storm",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.connection_storm.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "policy_violation",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.policy_violation.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "table_full",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.table_full.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Default)]
+struct SourceState {
+    // distinct destination ports, not more than the threshold
+    ports: HashSet<u16>,
+    flows: u32,
+    flags: FlowAnomaly,
+}
+
+#[derive(Default)]
+struct ServerState {
+    syn: u32,
+    established: u32,
+    flooded: bool,
+}
+
+impl ServerState {
+    fn half_open(&self) -> u32 {
+        self.syn.saturating_sub(self.established)
+    }
+}
+
+// A compiled FlowPolicyRule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyRule {
+    pub name: String,
+    src_nets: Vec<IpNet>,
+    dst_nets: Vec<IpNet>,
+    // (min, max) of the ranges
+    dst_ports: Vec<(u16, u16)>,
+    protocol: Option<IpProtocol>,
+}
+
+impl TryFrom<&FlowPolicyRule> for PolicyRule {
+    type Error = String;
+
+    fn try_from(rule: &FlowPolicyRule) -> Result<Self, Self::Error> {
+        let parse_nets = |cidrs: &[String]| {
+            cidrs
+                .iter()
+                .map(|cidr| {
+                    cidr.parse::<IpNet>()
+                        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|_| format!("invalid cidr {}", cidr))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let mut dst_ports = vec![];
+        for port in rule.dst_ports.split(',').map(str::trim) {
+            if port.is_empty() {
+                continue;
+            }
+            let range = PortRange::try_from(port)?;
+            dst_ports.push((range.min(), range.max()));
+        }
+        let protocol = match rule.protocol.to_lowercase().as_str() {
+            "" => None,
+            "tcp" => Some(IpProtocol::TCP),
+            "udp" => Some(IpProtocol::UDP),
+            other => return Err(format!("invalid protocol {}", other)),
+        };
+        Ok(Self {
+            name: rule.name.clone(),
+            src_nets: parse_nets(&rule.src_cidrs)?,
+            dst_nets: parse_nets(&rule.dst_cidrs)?,
+            dst_ports,
+            protocol,
+        })
+    }
+}
+
+impl PolicyRule {
+    fn matches(&self, protocol: IpProtocol, src: IpAddr, dst: IpAddr, dst_port: u16) -> bool {
+        self.protocol.map(|p| p == protocol).unwrap_or(true)
+            && (self.src_nets.is_empty() || self.src_nets.iter().any(|n| n.contains(&src)))
+            && (self.dst_nets.is_empty() || self.dst_nets.iter().any(|n| n.contains(&dst)))
+            && (self.dst_ports.is_empty()
+                || self
+                    .dst_ports
+                    .iter()
+                    .any(|(min, max)| (*min..=*max).contains(&dst_port)))
+    }
+}
+
+#[derive(Default)]
+struct Window {
+    start: Timestamp,
+    sources: HashMap<(CaptureNetworkType, IpAddr), SourceState>,
+    servers: HashMap<(CaptureNetworkType, IpAddr, u16), ServerState>,
+    // (tap_type, src, dst, dst_port, rule) reported in the window
+    violations: HashSet<(CaptureNetworkType, IpAddr, IpAddr, u16, usize)>,
+}
+
+// Counts the new flows of the sources and servers in fixed time windows, flags the flows
+// that are part of a port scan, SYN flood or connection storm, and sends a security event
+// when a threshold is reached in a window. New flows matching a policy rule are flagged and
+// reported once in a window. Shared by the flow maps of all dispatchers.
+pub struct AnomalyDetector {
+    window: Mutex<Window>,
+    output: DebugSender<BoxedSecurityEvent>,
+    syslog: Option<Arc<SyslogExporter>>,
+    counter: Arc<AnomalyCounter>,
+}
+
+impl AnomalyDetector {
+    pub fn new(
+        output: DebugSender<BoxedSecurityEvent>,
+        syslog: Option<Arc<SyslogExporter>>,
+    ) -> Self {
+        Self {
+            window: Default::default(),
+            output,
+            syslog,
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<AnomalyCounter> {
+        self.counter.clone()
+    }
+
+    fn send(&self, event: SecurityEvent) {
+        let counter = match event.event_type {
+            SecurityEventType::PortScan => &self.counter.port_scan,
+            SecurityEventType::SynFlood => &self.counter.syn_flood,
+            SecurityEventType::ConnectionStorm => &self.counter.connection_storm,
+            SecurityEventType::PolicyViolation => &self.counter.policy_violation,
+            SecurityEventType::NewListeningPort => unreachable!(),
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        debug!("flow anomaly detected: {:?}", event);
+        if let Some(syslog) = self.syslog.as_ref() {
+            syslog.export(&event);
+        }
+        if let Err(e) = self.output.send(BoxedSecurityEvent(Box::new(event))) {
+            warn!("send security event failed: {:?}", e);
+        }
+    }
+
+    // Counts the first packet of a flow captured from packets, returns the anomalies the flow
+    // is part of. Only SYN starts a TCP flow, as the client is unknown for other packets.
+    pub fn new_flow(
+        &self,
+        config: &FlowAnomalyDetection,
+        policy_rules: &[PolicyRule],
+        meta_packet: &MetaPacket,
+    ) -> FlowAnomaly {
+        let key = &meta_packet.lookup_key;
+        let syn = match (&key.proto, &meta_packet.protocol_data) {
+            (IpProtocol::TCP, ProtocolData::TcpHeader(tcp)) => {
+                if tcp.flags & TcpFlags::MASK != TcpFlags::SYN {
+                    return FlowAnomaly::empty();
+                }
+                true
+            }
+            _ => false,
+        };
+        let now = key.timestamp;
+        let window_secs = config.window.as_secs().max(1);
+        let window_start = now.round_to(Timestamp::from_secs(window_secs));
+
+        let mut events = vec![];
+        let mut violation = None;
+        let mut flags = FlowAnomaly::empty();
+        {
+            let mut window = self.window.lock().unwrap();
+            if window.start < window_start {
+                window.start = window_start;
+                window.sources.clear();
+                window.servers.clear();
+                window.violations.clear();
+            }
+
+            if let Some(i) = policy_rules
+                .iter()
+                .position(|r| r.matches(key.proto, key.src_ip, key.dst_ip, key.dst_port))
+            {
+                flags |= FlowAnomaly::POLICY_VIOLATION;
+                let violation_key = (key.tap_type, key.src_ip, key.dst_ip, key.dst_port, i);
+                if window.violations.contains(&violation_key) {
+                    // reported in the window
+                } else if window.violations.len() < ANOMALY_TABLE_CAPACITY {
+                    window.violations.insert(violation_key);
+                    violation = Some(&policy_rules[i]);
+                } else {
+                    self.counter.table_full.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            let source_key = (key.tap_type, key.src_ip);
+            let source = if window.sources.len() < ANOMALY_TABLE_CAPACITY
+                || window.sources.contains_key(&source_key)
+            {
+                Some(window.sources.entry(source_key).or_default())
+            } else {
+                self.counter.table_full.fetch_add(1, Ordering::Relaxed);
+                None
+            };
+            if let Some(source) = source {
+                source.flows += 1;
+                if config.port_scan_threshold > 0 && !source.flags.contains(FlowAnomaly::PORT_SCAN)
+                {
+                    source.ports.insert(key.dst_port);
+                    if source.ports.len() >= config.port_scan_threshold as usize {
+                        source.flags.insert(FlowAnomaly::PORT_SCAN);
+                        source.ports = HashSet::new();
+                        events.push((
+                            SecurityEventType::PortScan,
+                            key.src_ip,
+                            0,
+                            config.port_scan_threshold,
+                        ));
+                    }
+                }
+                if config.connection_storm_threshold > 0
+                    && !source.flags.contains(FlowAnomaly::CONNECTION_STORM)
+                    && source.flows >= config.connection_storm_threshold
+                {
+                    source.flags.insert(FlowAnomaly::CONNECTION_STORM);
+                    events.push((
+                        SecurityEventType::ConnectionStorm,
+                        key.src_ip,
+                        0,
+                        source.flows,
+                    ));
+                }
+                flags |= source.flags;
+            }
+
+            if syn && config.syn_flood_threshold > 0 {
+                let server_key = (key.tap_type, key.dst_ip, key.dst_port);
+                if window.servers.len() < ANOMALY_TABLE_CAPACITY
+                    || window.servers.contains_key(&server_key)
+                {
+                    let server = window.servers.entry(server_key).or_default();
+                    server.syn += 1;
+                    if !server.flooded && server.half_open() >= config.syn_flood_threshold {
+                        server.flooded = true;
+                        events.push((
+                            SecurityEventType::SynFlood,
+                            key.dst_ip,
+                            key.dst_port,
+                            server.half_open(),
+                        ));
+                    }
+                    if server.flooded {
+                        flags |= FlowAnomaly::SYN_FLOOD;
+                    }
+                } else {
+                    self.counter.table_full.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        for (event_type, ip, port, count) in events {
+            let threshold = match event_type {
+                SecurityEventType::SynFlood => config.syn_flood_threshold,
+                SecurityEventType::ConnectionStorm => config.connection_storm_threshold,
+                _ => config.port_scan_threshold,
+            };
+            self.send(SecurityEvent {
+                time: now,
+                event_type,
+                tap_type: key.tap_type,
+                ip,
+                port,
+                count,
+                threshold,
+                window: window_secs as u32,
+                peer_ip: None,
+                protocol: key.proto,
+                detail: String::new(),
+            });
+        }
+        if let Some(rule) = violation {
+            self.send(SecurityEvent {
+                time: now,
+                event_type: SecurityEventType::PolicyViolation,
+                tap_type: key.tap_type,
+                ip: key.src_ip,
+                port: key.dst_port,
+                count: 1,
+                threshold: 0,
+                window: window_secs as u32,
+                peer_ip: Some(key.dst_ip),
+                protocol: key.proto,
+                detail: rule.name.clone(),
+            });
+        }
+        flags
+    }
+
+    // Counts a TCP handshake completed with the server, which is no longer half-open
+    pub fn established(
+        &self,
+        tap_type: CaptureNetworkType,
+        server_ip: IpAddr,
+        server_port: u16,
+        now: Timestamp,
+    ) {
+        let mut window = self.window.lock().unwrap();
+        if window.start > now {
+            return;
+        }
+        if let Some(server) = window.servers.get_mut(&(tap_type, server_ip, server_port)) {
+            server.established += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use public::{debug::QueueDebugger, queue};
+
+    use crate::common::meta_packet::MetaPacketTcpHeader;
+
+    fn packet<'a>(
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        dst_port: u16,
+        flags: TcpFlags,
+        secs: u64,
+    ) -> MetaPacket<'a> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        packet.lookup_key.src_ip = src.into();
+        packet.lookup_key.dst_ip = dst.into();
+        packet.lookup_key.dst_port = dst_port;
+        packet.lookup_key.timestamp = Timestamp::from_secs(secs);
+        packet.protocol_data = ProtocolData::TcpHeader(MetaPacketTcpHeader {
+            flags,
+            ..Default::default()
+        });
+        packet
+    }
+
+    fn config() -> FlowAnomalyDetection {
+        FlowAnomalyDetection {
+            enabled: true,
+            window: Duration::from_secs(10),
+            port_scan_threshold: 10,
+            syn_flood_threshold: 5,
+            connection_storm_threshold: 20,
+            policy_rules: vec![],
+        }
+    }
+
+    #[test]
+    fn port_scan_and_connection_storm() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let detector = AnomalyDetector::new(sender, None);
+        let config = config();
+        let scanner = Ipv4Addr::new(10, 0, 0, 1);
+        let target = Ipv4Addr::new(10, 0, 0, 2);
+
+        for port in 1..10 {
+            let flags = detector.new_flow(
+                &config,
+                &[],
+                &packet(scanner, target, port, TcpFlags::SYN, 100),
+            );
+            assert!(flags.is_empty());
+        }
+        let flags = detector.new_flow(
+            &config,
+            &[],
+            &packet(scanner, target, 10, TcpFlags::SYN, 101),
+        );
+        assert_eq!(flags, FlowAnomaly::PORT_SCAN);
+        let event = receiver.recv(None).unwrap();
+        assert_eq!(event.0.event_type, SecurityEventType::PortScan);
+        assert_eq!(event.0.ip, IpAddr::from(scanner));
+
+        // repeated connections to the same port
+        for _ in 0..10 {
+            detector.new_flow(
+                &config,
+                &[],
+                &packet(scanner, target, 10, TcpFlags::SYN, 102),
+            );
+        }
+        let event = receiver.recv(None).unwrap();
+        assert_eq!(event.0.event_type, SecurityEventType::ConnectionStorm);
+        assert_eq!(event.0.count, 20);
+
+        // packets other than SYN do not start a TCP flow, and the window is reset
+        let flags = detector.new_flow(
+            &config,
+            &[],
+            &packet(scanner, target, 11, TcpFlags::ACK, 110),
+        );
+        assert!(flags.is_empty());
+        let flags = detector.new_flow(
+            &config,
+            &[],
+            &packet(scanner, target, 11, TcpFlags::SYN, 110),
+        );
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn syn_flood() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let detector = AnomalyDetector::new(sender, None);
+        let config = config();
+        let server = Ipv4Addr::new(10, 0, 0, 2);
+
+        for i in 0..4 {
+            let client = Ipv4Addr::new(192, 168, 0, i);
+            detector.new_flow(
+                &config,
+                &[],
+                &packet(client, server, 80, TcpFlags::SYN, 100),
+            );
+        }
+        // completed handshakes are not half-open
+        detector.established(
+            CaptureNetworkType::default(),
+            server.into(),
+            80,
+            Timestamp::from_secs(100),
+        );
+        let client = Ipv4Addr::new(192, 168, 0, 4);
+        let flags = detector.new_flow(
+            &config,
+            &[],
+            &packet(client, server, 80, TcpFlags::SYN, 100),
+        );
+        assert!(flags.is_empty());
+        let client = Ipv4Addr::new(192, 168, 0, 5);
+        let flags = detector.new_flow(
+            &config,
+            &[],
+            &packet(client, server, 80, TcpFlags::SYN, 100),
+        );
+        assert_eq!(flags, FlowAnomaly::SYN_FLOOD);
+        let event = receiver.recv(None).unwrap();
+        assert_eq!(event.0.event_type, SecurityEventType::SynFlood);
+        assert_eq!(event.0.port, 80);
+        assert_eq!(event.0.count, 5);
+    }
+
+    #[test]
+    fn policy_violation() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let detector = AnomalyDetector::new(sender, None);
+        let config = config();
+        let rule = FlowPolicyRule {
+            name: "no-ssh".to_owned(),
+            dst_cidrs: vec!["10.0.0.0/24".to_owned()],
+            dst_ports: "22, 3000-3010".to_owned(),
+            protocol: "TCP".to_owned(),
+            ..Default::default()
+        };
+        let rules = vec![PolicyRule::try_from(&rule).unwrap()];
+        let client = Ipv4Addr::new(192, 168, 0, 1);
+        let server = Ipv4Addr::new(10, 0, 0, 2);
+
+        let flags = detector.new_flow(
+            &config,
+            &rules,
+            &packet(client, server, 22, TcpFlags::SYN, 100),
+        );
+        assert_eq!(flags, FlowAnomaly::POLICY_VIOLATION);
+        let event = receiver.recv(None).unwrap();
+        assert_eq!(event.0.event_type, SecurityEventType::PolicyViolation);
+        assert_eq!(event.0.peer_ip, Some(IpAddr::from(server)));
+        assert_eq!(event.0.detail, "no-ssh");
+
+        // reported once in a window
+        let flags = detector.new_flow(
+            &config,
+            &rules,
+            &packet(client, server, 22, TcpFlags::SYN, 101),
+        );
+        assert_eq!(flags, FlowAnomaly::POLICY_VIOLATION);
+        assert!(receiver.recv(Some(Duration::from_millis(10))).is_err());
+
+        let flags = detector.new_flow(
+            &config,
+            &rules,
+            &packet(client, server, 80, TcpFlags::SYN, 101),
+        );
+        assert!(flags.is_empty());
+
+        let invalid = FlowPolicyRule {
+            dst_ports: "ssh".to_owned(),
+            ..Default::default()
+        };
+        assert!(PolicyRule::try_from(&invalid).is_err());
+    }
+}
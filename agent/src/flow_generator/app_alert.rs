@@ -0,0 +1,476 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use log::{debug, warn};
+use prost::Message;
+
+use public::{
+    l7_protocol::L7Protocol,
+    proto::alert_event,
+    queue::DebugSender,
+    sender::{SendMessageType, Sendable},
+};
+
+use super::anomaly::{FlowAnomaly, ALERT_EVENT_LEVEL_WARN, ALERT_POLICY_TYPE_SYSTEM};
+use crate::common::{enums::CaptureNetworkType, flow::L7PerfStats, Timestamp};
+use crate::config::RequestLogAlerting;
+use crate::utils::stats::{self, Counter, CounterType, CounterValue};
+
+// Endpoints not seen before are not evaluated when the table is full
+const APP_ALERT_TABLE_CAPACITY: usize = 1 << 16;
+// The rolling window is made of slots, the oldest slot is dropped as time goes by
+const SLOT_COUNT: usize = 6;
+// p95 latency is above the threshold when more than 5% of the responses are slower
+const P95_SLOW_PERCENT: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AppAlertType {
+    ErrorRate = 1,
+    Latency = 2,
+}
+
+impl AppAlertType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ErrorRate => "app_error_rate",
+            Self::Latency => "app_p95_latency",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AppServiceKey {
+    pub tap_type: CaptureNetworkType,
+    pub l3_epc_id: i32,
+    pub ip: IpAddr,
+    pub port: u16,
+    pub l7_protocol: L7Protocol,
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct AppAlertEvent {
+    pub time: Timestamp,
+    pub alert_type: AppAlertType,
+    pub key: AppServiceKey,
+    pub request_count: u32,
+    pub error_count: u32,
+    pub rrt_count: u32,
+    pub slow_count: u32,
+    pub threshold: u32,
+    pub window: u32,
+}
+
+#[derive(Debug)]
+pub struct BoxedAppAlertEvent(pub Box<AppAlertEvent>);
+
+impl Sendable for BoxedAppAlertEvent {
+    fn encode(self, buf: &mut Vec<u8>) -> Result<usize, prost::EncodeError> {
+        let e = self.0;
+        // percentage of the requests with errors, or of the responses slower than the threshold
+        let metric_value = match e.alert_type {
+            AppAlertType::ErrorRate if e.request_count > 0 => {
+                e.error_count as f64 * 100.0 / e.request_count as f64
+            }
+            AppAlertType::Latency if e.rrt_count > 0 => {
+                e.slow_count as f64 * 100.0 / e.rrt_count as f64
+            }
+            _ => 0.0,
+        };
+        let mut target_tags = format!("ip={},port={}", e.key.ip, e.key.port);
+        let mut tag_str_keys = vec!["ip".to_owned()];
+        let mut tag_str_values = vec![e.key.ip.to_string()];
+        if let Some(endpoint) = e.key.endpoint {
+            target_tags.push_str(&format!(",endpoint={}", endpoint));
+            tag_str_keys.push("endpoint".to_owned());
+            tag_str_values.push(endpoint);
+        }
+        let tag_ints = [
+            ("capture_network_type_id", u16::from(e.key.tap_type) as i64),
+            ("l3_epc_id", e.key.l3_epc_id as i64),
+            ("server_port", e.key.port as i64),
+            ("l7_protocol", e.key.l7_protocol as i64),
+            ("request", e.request_count as i64),
+            ("error", e.error_count as i64),
+            ("rrt_count", e.rrt_count as i64),
+            ("slow_count", e.slow_count as i64),
+            ("threshold", e.threshold as i64),
+            ("window", e.window as i64),
+        ];
+        let pb_event = alert_event::AlertEvent {
+            time: Some(e.time.as_secs() as u32),
+            policy_type: Some(ALERT_POLICY_TYPE_SYSTEM),
+            alert_policy: Some(e.alert_type.as_str().to_owned()),
+            metric_value: Some(metric_value),
+            event_level: Some(ALERT_EVENT_LEVEL_WARN),
+            target_tags: Some(target_tags),
+            tag_str_keys,
+            tag_str_values,
+            tag_int_keys: tag_ints.iter().map(|(k, _)| k.to_string()).collect(),
+            tag_int_values: tag_ints.iter().map(|(_, v)| *v).collect(),
+            ..Default::default()
+        };
+        pb_event.encode(buf).map(|_| pb_event.encoded_len())
+    }
+
+    // carried as alert events of the deepflow-server, with the alert type as the alert policy
+    fn message_type(&self) -> SendMessageType {
+        SendMessageType::AlarmEvent
+    }
+}
+
+#[derive(Default)]
+pub struct AppAlertCounter {
+    error_rate: AtomicU64,
+    latency: AtomicU64,
+    // new endpoints not evaluated as the table is full
+    table_full: AtomicU64,
+}
+
+impl stats::RefCountable for AppAlertCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "error_rate",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.error_rate.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "latency",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.latency.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "table_full",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.table_full.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    index: u64,
+    requests: u32,
+    errors: u32,
+    rrt_count: u32,
+    slow_count: u32,
+}
+
+#[derive(Default)]
+struct ServiceState {
+    slots: [Slot; SLOT_COUNT],
+    last_index: u64,
+    flags: FlowAnomaly,
+}
+
+impl ServiceState {
+    // Sums the slots in the window ending with the slot of index
+    fn sum(&self, index: u64) -> Slot {
+        let mut sum = Slot::default();
+        for slot in self.slots.iter() {
+            if slot.index > index || index - slot.index >= SLOT_COUNT as u64 {
+                continue;
+            }
+            sum.requests += slot.requests;
+            sum.errors += slot.errors;
+            sum.rrt_count += slot.rrt_count;
+            sum.slow_count += slot.slow_count;
+        }
+        sum
+    }
+}
+
+#[derive(Default)]
+struct Services {
+    states: HashMap<AppServiceKey, ServiceState>,
+    // slot index of the last time the stale services are removed
+    last_clean: u64,
+}
+
+// Tracks the error rate and p95 latency of the server endpoints in a rolling window, marks the
+// flows of the endpoints breaching the thresholds, and sends an alert event when a threshold
+// is breached. Shared by the flow maps of all dispatchers.
+pub struct AppAlertEvaluator {
+    services: Mutex<Services>,
+    output: DebugSender<BoxedAppAlertEvent>,
+    counter: Arc<AppAlertCounter>,
+}
+
+impl AppAlertEvaluator {
+    pub fn new(output: DebugSender<BoxedAppAlertEvent>) -> Self {
+        Self {
+            services: Default::default(),
+            output,
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<AppAlertCounter> {
+        self.counter.clone()
+    }
+
+    fn slot_len(config: &RequestLogAlerting) -> u64 {
+        (config.window / SLOT_COUNT as u32)
+            .max(Duration::from_secs(1))
+            .as_nanos() as u64
+    }
+
+    fn send(&self, event: AppAlertEvent) {
+        let counter = match event.alert_type {
+            AppAlertType::ErrorRate => &self.counter.error_rate,
+            AppAlertType::Latency => &self.counter.latency,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        debug!("application alert: {:?}", event);
+        if let Err(e) = self.output.send(BoxedAppAlertEvent(Box::new(event))) {
+            warn!("send application alert event failed: {:?}", e);
+        }
+    }
+
+    // Counts the requests, server errors, timeouts and response latency of a parsed message,
+    // returns the thresholds the endpoint is breaching. A message has at most one response
+    // in most protocols, so all the latency samples are slow if the max one is.
+    pub fn update(
+        &self,
+        config: &RequestLogAlerting,
+        key: &AppServiceKey,
+        stats: &L7PerfStats,
+        now: Timestamp,
+    ) -> FlowAnomaly {
+        let errors = stats.err_server_count + stats.err_timeout;
+        if stats.request_count == 0 && errors == 0 && stats.rrt_count == 0 {
+            return FlowAnomaly::empty();
+        }
+        let latency_threshold = config.p95_latency_threshold.as_micros() as u32;
+        let slow_count = if latency_threshold > 0 && stats.rrt_max > latency_threshold {
+            stats.rrt_count
+        } else {
+            0
+        };
+        let slot_len = Self::slot_len(config);
+        let index = now.as_nanos() / slot_len;
+
+        let mut events = vec![];
+        let flags = {
+            let mut services = self.services.lock().unwrap();
+            if services.states.len() >= APP_ALERT_TABLE_CAPACITY
+                && services.last_clean < index
+                && !services.states.contains_key(key)
+            {
+                services.last_clean = index;
+                services
+                    .states
+                    .retain(|_, s| index < s.last_index + SLOT_COUNT as u64);
+            }
+            let state = if services.states.len() < APP_ALERT_TABLE_CAPACITY {
+                services.states.entry(key.clone()).or_default()
+            } else if let Some(state) = services.states.get_mut(key) {
+                state
+            } else {
+                self.counter.table_full.fetch_add(1, Ordering::Relaxed);
+                return FlowAnomaly::empty();
+            };
+
+            let slot = &mut state.slots[(index % SLOT_COUNT as u64) as usize];
+            if slot.index < index {
+                *slot = Slot {
+                    index,
+                    ..Default::default()
+                };
+            }
+            slot.requests += stats.request_count;
+            slot.errors += errors;
+            slot.rrt_count += stats.rrt_count;
+            slot.slow_count += slow_count;
+            state.last_index = state.last_index.max(index);
+
+            let sum = state.sum(state.last_index);
+            let error_rate_breached = config.error_rate_threshold > 0
+                && sum.requests >= config.min_requests
+                && sum.errors as u64 * 100
+                    > sum.requests as u64 * config.error_rate_threshold as u64;
+            let latency_breached = latency_threshold > 0
+                && sum.rrt_count >= config.min_requests
+                && sum.slow_count as u64 * 100 > sum.rrt_count as u64 * P95_SLOW_PERCENT;
+
+            let mut flags = FlowAnomaly::empty();
+            flags.set(FlowAnomaly::APP_ERROR_RATE, error_rate_breached);
+            flags.set(FlowAnomaly::APP_LATENCY, latency_breached);
+            let breached = flags - state.flags;
+            state.flags = flags;
+            if breached.contains(FlowAnomaly::APP_ERROR_RATE) {
+                events.push((
+                    AppAlertType::ErrorRate,
+                    sum,
+                    config.error_rate_threshold as u32,
+                ));
+            }
+            if breached.contains(FlowAnomaly::APP_LATENCY) {
+                events.push((AppAlertType::Latency, sum, latency_threshold));
+            }
+            flags
+        };
+
+        for (alert_type, sum, threshold) in events {
+            self.send(AppAlertEvent {
+                time: now,
+                alert_type,
+                key: key.clone(),
+                request_count: sum.requests,
+                error_count: sum.errors,
+                rrt_count: sum.rrt_count,
+                slow_count: sum.slow_count,
+                threshold,
+                window: config.window.as_secs() as u32,
+            });
+        }
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use public::{debug::QueueDebugger, queue};
+
+    fn config() -> RequestLogAlerting {
+        RequestLogAlerting {
+            enabled: true,
+            window: Duration::from_secs(60),
+            min_requests: 20,
+            error_rate_threshold: 10,
+            p95_latency_threshold: Duration::from_millis(100),
+        }
+    }
+
+    fn key() -> AppServiceKey {
+        AppServiceKey {
+            tap_type: CaptureNetworkType::default(),
+            l3_epc_id: 1,
+            ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+            port: 8080,
+            l7_protocol: L7Protocol::Http1,
+            endpoint: Some("/api/v1/users".to_owned()),
+        }
+    }
+
+    fn request() -> L7PerfStats {
+        L7PerfStats {
+            request_count: 1,
+            ..Default::default()
+        }
+    }
+
+    fn response(rrt: u32, server_error: bool) -> L7PerfStats {
+        L7PerfStats {
+            response_count: 1,
+            err_server_count: server_error as u32,
+            rrt_count: 1,
+            rrt_sum: rrt as u64,
+            rrt_max: rrt,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn error_rate() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let evaluator = AppAlertEvaluator::new(sender);
+        let config = config();
+        let key = key();
+
+        // 2 errors in 20 requests is not above 10%
+        for i in 0..20 {
+            let now = Timestamp::from_secs(100 + i);
+            assert!(evaluator.update(&config, &key, &request(), now).is_empty());
+            let flags = evaluator.update(&config, &key, &response(1000, i < 2), now);
+            assert!(flags.is_empty());
+        }
+        evaluator.update(&config, &key, &request(), Timestamp::from_secs(121));
+        let flags = evaluator.update(
+            &config,
+            &key,
+            &response(1000, true),
+            Timestamp::from_secs(121),
+        );
+        assert_eq!(flags, FlowAnomaly::APP_ERROR_RATE);
+        let event = receiver.recv(None).unwrap();
+        assert_eq!(event.0.alert_type, AppAlertType::ErrorRate);
+        assert_eq!(event.0.request_count, 21);
+        assert_eq!(event.0.error_count, 3);
+
+        // the errors are out of the window
+        for i in 0..20 {
+            let now = Timestamp::from_secs(200 + i);
+            evaluator.update(&config, &key, &request(), now);
+            let flags = evaluator.update(&config, &key, &response(1000, false), now);
+            assert!(flags.is_empty());
+        }
+        assert!(receiver.recv(Some(Duration::from_millis(10))).is_err());
+    }
+
+    #[test]
+    fn p95_latency() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let evaluator = AppAlertEvaluator::new(sender);
+        let config = config();
+        let key = key();
+
+        let now = Timestamp::from_secs(100);
+        for _ in 0..19 {
+            evaluator.update(&config, &key, &request(), now);
+            evaluator.update(&config, &key, &response(10_000, false), now);
+        }
+        evaluator.update(&config, &key, &request(), now);
+        // 1 slow response in 20 is not above 5%
+        let flags = evaluator.update(&config, &key, &response(200_000, false), now);
+        assert!(flags.is_empty());
+        evaluator.update(&config, &key, &request(), now);
+        let flags = evaluator.update(&config, &key, &response(200_000, false), now);
+        assert_eq!(flags, FlowAnomaly::APP_LATENCY);
+        let event = receiver.recv(None).unwrap();
+        assert_eq!(event.0.alert_type, AppAlertType::Latency);
+        assert_eq!(event.0.rrt_count, 21);
+        assert_eq!(event.0.slow_count, 2);
+        assert_eq!(event.0.threshold, 100_000);
+
+        // other endpoints are evaluated separately
+        let other = AppServiceKey {
+            endpoint: Some("/api/v1/orders".to_owned()),
+            ..key
+        };
+        let flags = evaluator.update(&config, &other, &response(200_000, false), now);
+        assert!(flags.is_empty());
+    }
+}
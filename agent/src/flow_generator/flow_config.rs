@@ -19,7 +19,7 @@ use std::{
     sync::Arc,
 };
 
-use crate::common::Timestamp;
+use crate::common::{enums::CAPTURE_NETWORK_TYPE_MAX, Timestamp};
 use public::proto::agent::AgentType;
 
 pub const TIMEOUT_OTHERS: Timestamp = Timestamp::from_secs(5);
@@ -116,7 +116,7 @@ pub struct FlowMapConfig {
     pub agent_type: AgentType,
     pub cloud_gateway_traffic: bool,
     pub collector_enabled: bool,
-    pub tap_types: [bool; 256],
+    pub tap_types: [bool; CAPTURE_NETWORK_TYPE_MAX],
 
     pub packet_delay: Timestamp,
     pub flush_interval: Timestamp,
@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+
+use super::{FlowNode, FLOW_METRICS_PEER_DST, FLOW_METRICS_PEER_SRC};
+use public::proto::agent_debug;
+
+lazy_static! {
+    pub static ref FLOW_DUMP: FlowDump = FlowDump::default();
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FlowDumpFilter {
+    pub limit: usize,
+    pub ip: Option<IpAddr>,
+    pub port: Option<u16>,
+}
+
+impl FlowDumpFilter {
+    fn matches(&self, node: &FlowNode) -> bool {
+        let key = &node.tagged_flow.flow.flow_key;
+        if let Some(ip) = self.ip {
+            if key.ip_src != ip && key.ip_dst != ip {
+                return false;
+            }
+        }
+        if let Some(port) = self.port {
+            if key.port_src != port && key.port_dst != port {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Dumps of active flows requested by the local debug api.
+//
+// Flow maps are owned by their threads, so a request only bumps the generation, and each flow
+// map submits flows matching the filter on its next flush tick.
+#[derive(Default)]
+pub struct FlowDump {
+    generation: AtomicU64,
+    filter: Mutex<FlowDumpFilter>,
+    results: Mutex<HashMap<u32, (u64, Vec<agent_debug::Flow>)>>,
+}
+
+impl FlowDump {
+    pub fn request(&self, filter: FlowDumpFilter) -> u64 {
+        *self.filter.lock().unwrap() = filter;
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    // Flows submitted for the request of `generation`, grouped by flow map id
+    pub fn collect(&self, generation: u64) -> Vec<(u32, Vec<agent_debug::Flow>)> {
+        let mut results = self.results.lock().unwrap();
+        let ids = results
+            .iter()
+            .filter(|(_, (g, _))| *g == generation)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut flows = ids
+            .into_iter()
+            .filter_map(|id| results.remove(&id).map(|(_, f)| (id, f)))
+            .collect::<Vec<_>>();
+        flows.sort_by_key(|(id, _)| *id);
+        flows
+    }
+
+    pub(super) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn submit<'a, I>(&self, id: u32, generation: u64, nodes: I)
+    where
+        I: Iterator<Item = &'a FlowNode>,
+    {
+        let filter = self.filter.lock().unwrap().clone();
+        let flows = nodes
+            .filter(|n| filter.matches(n))
+            .take(filter.limit)
+            .map(|n| flow_summary(id, n))
+            .collect();
+        self.results.lock().unwrap().insert(id, (generation, flows));
+    }
+}
+
+fn flow_summary(id: u32, node: &FlowNode) -> agent_debug::Flow {
+    let flow = &node.tagged_flow.flow;
+    let key = &flow.flow_key;
+    let src = &flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
+    let dst = &flow.flow_metrics_peers[FLOW_METRICS_PEER_DST];
+    agent_debug::Flow {
+        flow_map_id: id,
+        flow_id: flow.flow_id,
+        capture_network_type: key.tap_type.to_string(),
+        ip_src: key.ip_src.to_string(),
+        ip_dst: key.ip_dst.to_string(),
+        port_src: key.port_src as u32,
+        port_dst: key.port_dst as u32,
+        protocol: u8::from(key.proto).to_string(),
+        signal_source: format!("{:?}", flow.signal_source),
+        state: format!("{:?}", node.flow_state),
+        start_time: flow.start_time.as_nanos(),
+        recent_time: node.recent_time.as_nanos(),
+        packet_tx: src.total_packet_count,
+        packet_rx: dst.total_packet_count,
+        byte_tx: src.total_byte_count,
+        byte_rx: dst.total_byte_count,
+    }
+}
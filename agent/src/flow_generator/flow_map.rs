@@ -35,9 +35,16 @@ use log::{debug, warn};
 use lru::LruCache;
 
 use super::{
+    anomaly::{AnomalyDetector, FlowAnomaly},
+    app_alert::{AppAlertEvaluator, AppServiceKey},
     app_table::AppTable,
     error::Error,
+    flow_dump::FLOW_DUMP,
     flow_state::{StateMachine, StateValue},
+    fragment::FragmentCache,
+    multicast::MulticastGroupTable,
+    neighbor::NeighborTable,
+    packet_trace::PACKET_TRACE,
     perf::{tcp::TcpPerf, FlowLog, FlowPerfCounter, L7ProtocolChecker},
     pool::MemoryPool,
     protocol_logs::{
@@ -53,6 +60,7 @@ use super::{
 
 use crate::{
     common::{
+        decapsulate::TunnelType,
         ebpf::EbpfType,
         endpoint::{EndpointData, EndpointDataPov, EndpointInfo, EPC_DEEPFLOW, EPC_INTERNET},
         enums::{CaptureNetworkType, EthernetType, HeaderType, IpProtocol, TcpFlags},
@@ -65,14 +73,16 @@ use crate::{
             L7PerfCache, L7ProtocolBitmap, L7ProtocolParser, L7ProtocolParserInterface,
         },
         lookup_key::LookupKey,
-        meta_packet::{MetaPacket, MetaPacketTcpHeader, ProtocolData},
+        meta_packet::{
+            IcmpData, IcmpError, MetaPacket, MetaPacketTcpHeader, ProtocolData, SctpChunkFlags,
+        },
         tagged_flow::TaggedFlow,
         tap_port::TapPort,
         Timestamp,
     },
     config::{
         handler::{CollectorConfig, LogParserConfig, PluginConfig},
-        FlowConfig, ModuleConfig, UserConfig,
+        FlowConfig, ModuleConfig, RequestLogAlerting, UserConfig,
     },
     flow_generator::{protocol_logs::PseudoAppProto, LogMessageType},
     metric::document::TapSide,
@@ -82,7 +92,7 @@ use crate::{
     utils::stats::{self, Countable, StatsOption},
 };
 #[cfg(any(target_os = "linux", target_os = "android"))]
-use crate::{config::handler::EbpfConfig, plugin::c_ffi::SoPluginFunc};
+use crate::{config::handler::EbpfConfig, ebpf, plugin::c_ffi::SoPluginFunc};
 use public::{
     buffer::{Allocator, BatchedBox},
     counter::{Counter, CounterType, CounterValue, RefCountable},
@@ -98,6 +108,11 @@ use packet_segmentation_reassembly::PacketSegmentationReassembly;
 use packet_sequence_block::PacketSequenceBlock;
 
 const DEFAULT_SOCKET_CLOSE_TIMEOUT: Timestamp = Timestamp::from_secs(1);
+// lookups of local processes on flow creation and the first flow stats outputs
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const PROCESS_LOOKUP_ATTEMPTS: u8 = 3;
+#[cfg(target_os = "linux")]
+const NAT_LOOKUP_ATTEMPTS: u8 = 3;
 
 pub struct Config<'a> {
     pub flow: &'a FlowConfig,
@@ -165,6 +180,10 @@ pub struct FlowMap {
     service_table: ServiceTable,
     app_table: AppTable,
     policy_getter: PolicyGetter,
+    multicast_groups: Option<Arc<MulticastGroupTable>>,
+    neighbor_table: Option<Arc<NeighborTable>>,
+    anomaly_detector: Option<Arc<AnomalyDetector>>,
+    app_alert_evaluator: Option<Arc<AppAlertEvaluator>>,
     start_time: Duration,    // 时间桶中的最早时间
     start_time_in_unit: u64, // 时间桶中的最早时间，以TIME_SLOT_UNIT为单位
     hash_slots: usize,
@@ -189,12 +208,16 @@ pub struct FlowMap {
     packet_sequence_queue: Option<DebugSender<Box<PacketSequenceBlock>>>, // Enterprise Edition Feature: packet-sequence
     packet_sequence_enabled: bool,
     stats_counter: Arc<FlowMapCounter>,
+    fragments: FragmentCache,
     system_time: Duration,
 
     l7_protocol_checker: L7ProtocolChecker,
 
     time_key_buffer: Option<Vec<(u64, FlowMapKey)>>,
 
+    // last flow dump request handled
+    flow_dump_generation: u64,
+
     // for change detection
     plugin_digest: u64,
     wasm_vm: Rc<RefCell<Option<WasmVm>>>,
@@ -220,6 +243,10 @@ impl FlowMap {
         ntp_diff: Arc<AtomicI64>,
         config: &FlowConfig,
         packet_sequence_queue: Option<DebugSender<Box<PacketSequenceBlock>>>, // Enterprise Edition Feature: packet-sequence
+        multicast_groups: Option<Arc<MulticastGroupTable>>,
+        neighbor_table: Option<Arc<NeighborTable>>,
+        anomaly_detector: Option<Arc<AnomalyDetector>>,
+        app_alert_evaluator: Option<Arc<AppAlertEvaluator>>,
         stats_collector: Arc<stats::Collector>,
         from_ebpf: bool,
     ) -> Self {
@@ -241,6 +268,18 @@ impl FlowMap {
             &stats::SingleTagModule("flow-perf", "id", id),
             Countable::Ref(Arc::downgrade(&flow_perf_counter) as Weak<dyn RefCountable>),
         );
+        let service_table = ServiceTable::new(
+            SERVICE_TABLE_IPV4_CAPACITY,
+            SERVICE_TABLE_IPV6_CAPACITY,
+            &config.server_ports,
+        )
+        .with_heuristics(config.direction_heuristics);
+        stats_collector.register_countable(
+            &stats::SingleTagModule("flow-direction", "id", id),
+            Countable::Ref(
+                Arc::downgrade(&service_table.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
         let system_time = get_timestamp(ntp_diff.load(Ordering::Relaxed));
         let start_time = system_time - config.packet_delay - Duration::from_secs(1);
         let time_set_slot_size = config.hash_slots as usize / time_window_size;
@@ -253,16 +292,16 @@ impl FlowMap {
             id,
             state_machine_master: StateMachine::new_master(&config.flow_timeout),
             state_machine_slave: StateMachine::new_slave(&config.flow_timeout),
-            service_table: ServiceTable::new(
-                SERVICE_TABLE_IPV4_CAPACITY,
-                SERVICE_TABLE_IPV6_CAPACITY,
-                &config.server_ports,
-            ),
+            service_table,
             app_table: AppTable::new(
                 config.l7_protocol_inference_max_fail_count,
                 config.l7_protocol_inference_ttl,
             ),
             policy_getter,
+            multicast_groups,
+            neighbor_table,
+            anomaly_detector,
+            app_alert_evaluator,
             start_time,
             start_time_in_unit: start_time.as_secs(),
             hash_slots: config.hash_slots as usize,
@@ -304,6 +343,10 @@ impl FlowMap {
             ntp_diff,
             packet_sequence_queue, // Enterprise Edition Feature: packet-sequence
             packet_sequence_enabled,
+            fragments: FragmentCache::new(
+                config.ip_fragment_reassembly.max_datagrams,
+                stats_counter.clone(),
+            ),
             stats_counter,
             system_time,
             l7_protocol_checker: L7ProtocolChecker::new(
@@ -320,6 +363,7 @@ impl FlowMap {
             ),
             time_key_buffer: None,
             plugin_digest: 0, // force initial load
+            flow_dump_generation: FLOW_DUMP.generation(),
             wasm_vm: Default::default(),
             #[cfg(any(target_os = "linux", target_os = "android"))]
             so_plugin: Default::default(),
@@ -514,7 +558,26 @@ impl FlowMap {
         left as usize
     }
 
+    fn dump_flows(&mut self) {
+        let generation = FLOW_DUMP.generation();
+        if generation == self.flow_dump_generation {
+            return;
+        }
+        self.flow_dump_generation = generation;
+        if let Some((node_map, _)) = self.node_map.as_ref() {
+            FLOW_DUMP.submit(
+                self.id,
+                generation,
+                node_map
+                    .values()
+                    .flat_map(|nodes| nodes.iter().map(|n| n.as_ref())),
+            );
+        }
+    }
+
     pub fn inject_flush_ticker(&mut self, config: &Config, mut timestamp: Duration) -> bool {
+        self.dump_flows();
+
         let is_tick = timestamp.is_zero();
         if is_tick {
             timestamp = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
@@ -674,7 +737,101 @@ impl FlowMap {
         (self.policy_getter).lookup(meta_packet, self.id as usize, local_epc_id);
     }
 
+    // ICMP errors carry the header of the packet triggering them, count them on the flow of that packet.
+    // The count is exported along with the next statistics of the flow.
+    fn correlate_icmp_error(
+        node_map: &mut AHashMap<FlowMapKey, Vec<Box<FlowNode>>>,
+        meta_packet: &MetaPacket,
+        icmp_type: u8,
+        icmp_code: u8,
+        error: &IcmpError,
+    ) {
+        let lookup_key = LookupKey {
+            src_ip: error.src_ip,
+            dst_ip: error.dst_ip,
+            src_port: error.src_port,
+            dst_port: error.dst_port,
+            proto: error.proto,
+            eth_type: meta_packet.lookup_key.eth_type,
+            tap_type: meta_packet.lookup_key.tap_type,
+            ..Default::default()
+        };
+        let Some(nodes) = node_map.get_mut(&FlowMapKey::new(&lookup_key, meta_packet.tap_port))
+        else {
+            return;
+        };
+        for node in nodes.iter_mut() {
+            let flow = &mut node.tagged_flow.flow;
+            let key = &flow.flow_key;
+            if key.proto != error.proto {
+                continue;
+            }
+            let forward = key.ip_src == error.src_ip
+                && key.ip_dst == error.dst_ip
+                && key.port_src == error.src_port
+                && key.port_dst == error.dst_port;
+            let backward = key.ip_src == error.dst_ip
+                && key.ip_dst == error.src_ip
+                && key.port_src == error.dst_port
+                && key.port_dst == error.src_port;
+            if forward || backward {
+                flow.icmp_error_count += 1;
+                flow.icmp_error_type = icmp_type;
+                flow.icmp_error_code = icmp_code;
+                return;
+            }
+        }
+    }
+
     pub fn inject_meta_packet(&mut self, config: &Config, meta_packet: &mut MetaPacket) {
+        let reassembled = if config.flow.ip_fragment_reassembly.enabled
+            && meta_packet.lookup_key.proto == IpProtocol::UDP
+        {
+            self.fragments
+                .inject(&config.flow.ip_fragment_reassembly, meta_packet)
+        } else {
+            None
+        };
+        self.inject_packet(config, meta_packet);
+        if PACKET_TRACE.enabled() {
+            PACKET_TRACE.trace(self.id, meta_packet);
+        }
+        if let Some(mut packet) = reassembled {
+            self.collect_reassembled_metric(config, &mut packet);
+        }
+    }
+
+    // L7 protocols of the reassembled datagram are parsed in the flow of its first fragment
+    fn collect_reassembled_metric(&mut self, config: &Config, packet: &mut MetaPacket) {
+        if !config.flow.collector_enabled {
+            return;
+        }
+        let Some((mut node_map, time_set)) = self.node_map.take() else {
+            warn!("cannot get node map and time set");
+            return;
+        };
+        let pkt_key = FlowMapKey::new(&packet.lookup_key, packet.tap_port);
+        let flow_config = config.flow;
+        let node = node_map.get_mut(&pkt_key).and_then(|nodes| {
+            nodes.iter_mut().find(|node| {
+                node.match_node(
+                    packet,
+                    flow_config.ignore_l2_end,
+                    flow_config.ignore_tor_mac,
+                    flow_config.ignore_idc_vlan,
+                    flow_config.agent_type,
+                )
+            })
+        });
+        if let Some(node) = node {
+            packet.flow_id = node.tagged_flow.flow.flow_id;
+            let direction = packet.lookup_key.direction == PacketDirection::ClientToServer;
+            self.collect_metric(config, node, packet, direction, false);
+        }
+        self.node_map.replace((node_map, time_set));
+    }
+
+    fn inject_packet(&mut self, config: &Config, meta_packet: &mut MetaPacket) {
         if !self.inject_flush_ticker(config, meta_packet.lookup_key.timestamp.into()) {
             self.lookup_without_flow(config, meta_packet);
             return;
@@ -691,6 +848,22 @@ impl FlowMap {
             return;
         };
 
+        if let ProtocolData::IcmpData(IcmpData {
+            icmp_type,
+            icmp_code,
+            error: Some(error),
+            ..
+        }) = &meta_packet.protocol_data
+        {
+            Self::correlate_icmp_error(&mut node_map, meta_packet, *icmp_type, *icmp_code, error);
+        }
+        if let Some(multicast_groups) = self.multicast_groups.as_ref() {
+            multicast_groups.update(meta_packet);
+        }
+        if let Some(neighbor_table) = self.neighbor_table.as_ref() {
+            neighbor_table.update(meta_packet);
+        }
+
         let pkt_timestamp = meta_packet.lookup_key.timestamp;
         let mut max_depth = 1;
         match node_map.get_mut(&pkt_key) {
@@ -759,6 +932,7 @@ impl FlowMap {
                 let flow_closed = match meta_packet.lookup_key.proto {
                     IpProtocol::TCP => self.update_tcp_node(config, node, meta_packet),
                     IpProtocol::UDP => self.update_udp_node(config, node, meta_packet),
+                    IpProtocol::SCTP => self.update_sctp_node(config, node, meta_packet),
                     _ => self.update_other_node(config, node, meta_packet),
                 };
 
@@ -908,7 +1082,7 @@ impl FlowMap {
             if node.residual_request == 0 {
                 node.timeout = flow_config.flow_timeout.opening;
             } else {
-                node.timeout = config.log_parser.l7_log_session_aggr_timeout.into();
+                node.timeout = config.log_parser.l7_log_session_aggr_max_timeout().into();
             }
         }
 
@@ -957,6 +1131,59 @@ impl FlowMap {
         false
     }
 
+    fn update_sctp_node(
+        &mut self,
+        config: &Config,
+        node: &mut FlowNode,
+        meta_packet: &mut MetaPacket,
+    ) -> bool {
+        let flow_config = config.flow;
+        self.update_flow(config, node, meta_packet);
+        node.update_sctp_verification_tags(meta_packet);
+        meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
+        if flow_config.collector_enabled {
+            self.collect_metric(
+                config,
+                node,
+                meta_packet,
+                meta_packet.lookup_key.direction == PacketDirection::ClientToServer,
+                false,
+            );
+        }
+
+        if meta_packet.need_reverse_flow {
+            self.update_l4_direction(meta_packet, node, false);
+        }
+
+        let ProtocolData::SctpData(sctp_data) = &meta_packet.protocol_data else {
+            return false;
+        };
+        // ABORT or SHUTDOWN_COMPLETE ends the association immediately
+        if sctp_data.chunks.contains(SctpChunkFlags::ABORT) {
+            node.flow_state = FlowState::Reset;
+            return true;
+        }
+        if sctp_data.chunks.contains(SctpChunkFlags::SHUTDOWN_COMPLETE) {
+            node.flow_state = FlowState::Closed;
+            return true;
+        }
+        if sctp_data
+            .chunks
+            .intersects(SctpChunkFlags::SHUTDOWN | SctpChunkFlags::SHUTDOWN_ACK)
+        {
+            node.timeout = flow_config.flow_timeout.closing;
+        } else {
+            let peers = &node.tagged_flow.flow.flow_metrics_peers;
+            if peers[FLOW_METRICS_PEER_SRC].packet_count > 0
+                && peers[FLOW_METRICS_PEER_DST].packet_count > 0
+            {
+                node.timeout = flow_config.flow_timeout.established;
+            }
+        }
+
+        false
+    }
+
     fn update_other_node(
         &mut self,
         config: &Config,
@@ -1023,7 +1250,22 @@ impl FlowMap {
             return false;
         }
 
-        self.update_flow_state_machine(flow_config, node, pkt_tcp_flags, direction)
+        let last_state = node.flow_state;
+        let closed = self.update_flow_state_machine(flow_config, node, pkt_tcp_flags, direction);
+        if last_state != FlowState::Established && node.flow_state == FlowState::Established {
+            if let Some(detector) = self.anomaly_detector.as_ref() {
+                if flow_config.anomaly_detection.enabled {
+                    let flow_key = &node.tagged_flow.flow.flow_key;
+                    detector.established(
+                        flow_key.tap_type,
+                        flow_key.ip_dst,
+                        flow_key.port_dst,
+                        meta_packet.lookup_key.timestamp,
+                    );
+                }
+            }
+        }
+        closed
     }
 
     // 协议参考：https://datatracker.ietf.org/doc/html/rfc1122#section-4.2.3.6
@@ -1131,9 +1373,11 @@ impl FlowMap {
     fn l7_log_parse_enabled(config: &FlowConfig, lookup_key: &LookupKey) -> bool {
         // parse tap_type any or tap_type in config
         config.app_proto_log_enabled
-            && (lookup_key.proto == IpProtocol::TCP || lookup_key.proto == IpProtocol::UDP)
+            && (lookup_key.proto == IpProtocol::TCP
+                || lookup_key.proto == IpProtocol::UDP
+                || lookup_key.proto == IpProtocol::SCTP)
             && (config.l7_log_tap_types[u16::from(CaptureNetworkType::Any) as usize]
-                || lookup_key.tap_type <= CaptureNetworkType::Max
+                || lookup_key.tap_type < CaptureNetworkType::Max
                     && config.l7_log_tap_types[u16::from(lookup_key.tap_type) as usize])
     }
 
@@ -1203,6 +1447,12 @@ impl FlowMap {
                     tier: tunnel.tier,
                     tunnel_type: tunnel.tunnel_type,
                     is_ipv6: tunnel.is_ipv6,
+                    gwlbe_id: tunnel.geneve_options.gwlbe_id,
+                    gwlb_attachment_id: tunnel.geneve_options.attachment_id,
+                    gwlb_flow_cookie: tunnel.geneve_options.flow_cookie,
+                    nsh_spi: tunnel.nsh.spi,
+                    nsh_si: tunnel.nsh.si,
+                    mpls: tunnel.mpls,
                     ..Default::default()
                 }
             } else {
@@ -1216,6 +1466,29 @@ impl FlowMap {
             start_time: lookup_key.timestamp.into(),
             flow_stat_time: lookup_key.timestamp.round_to(TIME_UNIT.into()),
             vlan: meta_packet.vlan,
+            outer_vlan: meta_packet.outer_vlan,
+            multicast_source: self
+                .multicast_groups
+                .as_ref()
+                .map(|t| t.lookup(meta_packet.tap_port, &lookup_key.dst_ip))
+                .unwrap_or_default(),
+            ip_conflict: self
+                .neighbor_table
+                .as_ref()
+                .map(|t| {
+                    t.is_conflict(
+                        lookup_key.tap_type,
+                        &lookup_key.src_ip,
+                        lookup_key.timestamp,
+                    ) || t.is_conflict(
+                        lookup_key.tap_type,
+                        &lookup_key.dst_ip,
+                        lookup_key.timestamp,
+                    )
+                })
+                .unwrap_or_default(),
+            ipv6_ext_headers: meta_packet.ipv6_ext_headers.bits(),
+            sampling_rate: meta_packet.sampling_rate,
             eth_type: lookup_key.eth_type,
             queue_hash: meta_packet.queue_hash,
             is_new_flow: true,
@@ -1241,6 +1514,12 @@ impl FlowMap {
             ..Default::default()
         };
         tagged_flow.flow = flow;
+        // the path is recorded by eBPF before the first data of the socket is sent
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if meta_packet.is_unix_socket() {
+            tagged_flow.flow.unix_socket_path =
+                ebpf::lookup_unix_socket_path(meta_packet.socket_id).unwrap_or_default();
+        }
 
         // FlowMap信息
         let mut policy_in_tick = [false; 2];
@@ -1261,11 +1540,15 @@ impl FlowMap {
         node.meta_flow_log = None;
         node.next_tcp_seq0 = 0;
         node.next_tcp_seq1 = 0;
+        node.sctp_verification_tags = [0; 2];
         node.last_cap_seq = meta_packet.cap_end_seq as u32;
         node.policy_data_cache = Default::default();
         node.endpoint_data_cache = Default::default();
         node.packet_sequence_block = None; // Enterprise Edition Feature: packet-sequence
         node.residual_request = 0;
+        node.process_lookup_attempts = 0;
+        node.tcp_health_lookup_time = None;
+        node.nat_lookup_attempts = 0;
 
         if PacketSegmentationReassembly::does_support()
             && meta_packet.lookup_key.proto == IpProtocol::TCP
@@ -1301,6 +1584,24 @@ impl FlowMap {
         node.tagged_flow.flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC].gpid = meta_packet.gpid_0;
         node.tagged_flow.flow.flow_metrics_peers[FLOW_METRICS_PEER_DST].gpid = meta_packet.gpid_1;
 
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if flow_config.process_attribution_enabled
+            && meta_packet.signal_source == SignalSource::Packet
+            && (meta_packet.lookup_key.proto == IpProtocol::TCP
+                || meta_packet.lookup_key.proto == IpProtocol::UDP)
+        {
+            node.process_lookup_attempts = PROCESS_LOOKUP_ATTEMPTS;
+            node.lookup_process();
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if flow_config.tcp_health_enabled
+            && meta_packet.signal_source == SignalSource::Packet
+            && meta_packet.lookup_key.proto == IpProtocol::TCP
+        {
+            // the connection is not established yet, looked up when flow stats are output
+            node.tcp_health_lookup_time = Some(Duration::ZERO);
+        }
+
         let nat_source = meta_packet.lookup_key.get_nat_source();
         meta_packet.tap_port.set_nat_source(nat_source);
         node.tagged_flow
@@ -1308,6 +1609,15 @@ impl FlowMap {
             .flow_key
             .tap_port
             .set_nat_source(nat_source);
+        #[cfg(target_os = "linux")]
+        if flow_config.conntrack_nat_enabled
+            && meta_packet.signal_source == SignalSource::Packet
+            && (meta_packet.lookup_key.proto == IpProtocol::TCP
+                || meta_packet.lookup_key.proto == IpProtocol::UDP)
+        {
+            node.nat_lookup_attempts = NAT_LOOKUP_ATTEMPTS;
+            node.lookup_kernel_nat();
+        }
 
         // direction rectify
         if meta_packet.signal_source == SignalSource::EBPF {
@@ -1422,7 +1732,7 @@ impl FlowMap {
                 Rc::clone(&self.so_plugin),
                 self.stats_counter.clone(),
                 match meta_packet.lookup_key.proto {
-                    IpProtocol::TCP => flow_config.rrt_tcp_timeout,
+                    IpProtocol::TCP | IpProtocol::SCTP => flow_config.rrt_tcp_timeout,
                     IpProtocol::UDP => flow_config.rrt_udp_timeout,
                     _ => 0,
                 },
@@ -1452,6 +1762,7 @@ impl FlowMap {
             node.packet_in_tick = true;
             flow.flow_stat_time = pkt_timestamp.round_to(STATISTICAL_INTERVAL.into());
         }
+        flow.ipv6_ext_headers |= meta_packet.ipv6_ext_headers.bits();
 
         if !node.policy_in_tick[meta_packet.lookup_key.direction as usize] {
             node.policy_in_tick[meta_packet.lookup_key.direction as usize] = true;
@@ -1561,6 +1872,9 @@ impl FlowMap {
         if meta_packet.vlan > 0 {
             flow.vlan = meta_packet.vlan;
         }
+        if meta_packet.outer_vlan > 0 {
+            flow.outer_vlan = meta_packet.outer_vlan;
+        }
         if let Some(tunnel) = meta_packet.tunnel {
             match meta_packet.lookup_key.direction {
                 PacketDirection::ClientToServer => {
@@ -1581,6 +1895,18 @@ impl FlowMap {
             flow.tunnel.tier = tunnel.tier;
             flow.tunnel.tunnel_type = tunnel.tunnel_type;
             flow.tunnel.is_ipv6 = tunnel.is_ipv6;
+            if tunnel.geneve_options.flow_cookie > 0 {
+                flow.tunnel.gwlbe_id = tunnel.geneve_options.gwlbe_id;
+                flow.tunnel.gwlb_attachment_id = tunnel.geneve_options.attachment_id;
+                flow.tunnel.gwlb_flow_cookie = tunnel.geneve_options.flow_cookie;
+            }
+            if tunnel.tunnel_type == TunnelType::VxlanGpe {
+                flow.tunnel.nsh_spi = tunnel.nsh.spi;
+                flow.tunnel.nsh_si = tunnel.nsh.si;
+            }
+            if tunnel.tunnel_type == TunnelType::Mpls {
+                flow.tunnel.mpls = tunnel.mpls;
+            }
         }
         // 这里需要查询策略，建立ARP表
         if meta_packet.is_ndp_response() {
@@ -1601,7 +1927,9 @@ impl FlowMap {
         node: &mut FlowNode,
         meta_flow_log: &mut Box<FlowLog>,
         l7_info: &L7ProtocolInfo,
+        payload_len: u32,
         consistent_timestamp_in_l7_metrics: bool,
+        alerting: &RequestLogAlerting,
         time_in_micros: u64,
     ) {
         if let Some(flow_perf_stats) = node.tagged_flow.flow.flow_perf_stats.as_mut() {
@@ -1610,9 +1938,28 @@ impl FlowMap {
                 .perf_cache
                 .borrow_mut()
                 .pop_timeout_count(flow_id, false); // TODO: flow_end is most likely false, but may also be true
-            let (l7_perf_stats, _l7_protocol) =
+            let (mut l7_perf_stats, l7_protocol) =
                 meta_flow_log.copy_and_reset_l7_perf_data(l7_timeout_count as u32);
             let app_proto_head = l7_info.app_proto_head().unwrap();
+            if !l7_info.is_on_blacklist() {
+                let payload_size = l7_info.get_payload_size().unwrap_or(payload_len);
+                match app_proto_head.msg_type {
+                    LogMessageType::Request => l7_perf_stats.payload_size.add_request(payload_size),
+                    LogMessageType::Response => {
+                        l7_perf_stats.payload_size.add_response(payload_size)
+                    }
+                    _ => (),
+                }
+            }
+            // rrt_count is 1 for a response in most protocols, the average is used otherwise
+            if l7_perf_stats.rrt_count == 1 {
+                l7_perf_stats.rrt_sketch.add(l7_perf_stats.rrt_max);
+            } else if l7_perf_stats.rrt_count > 1 {
+                let average = l7_perf_stats.rrt_sum / l7_perf_stats.rrt_count as u64;
+                for _ in 0..l7_perf_stats.rrt_count {
+                    l7_perf_stats.rrt_sketch.add(average as u32);
+                }
+            }
             let time_span = if consistent_timestamp_in_l7_metrics
                 && app_proto_head.msg_type == LogMessageType::Response
                 && app_proto_head.rrt != 0
@@ -1636,6 +1983,27 @@ impl FlowMap {
                 time_span: time_span as u32,
             };
 
+            if alerting.enabled {
+                if let Some(evaluator) = self.app_alert_evaluator.as_ref() {
+                    let flow_key = &node.tagged_flow.flow.flow_key;
+                    let service_key = AppServiceKey {
+                        tap_type: flow_key.tap_type,
+                        l3_epc_id: node.tagged_flow.flow.flow_metrics_peers[1].l3_epc_id,
+                        ip: flow_key.ip_dst,
+                        port: flow_key.port_dst,
+                        l7_protocol,
+                        endpoint: key.endpoint.clone(),
+                    };
+                    let anomaly = evaluator.update(
+                        alerting,
+                        &service_key,
+                        &l7_perf_stats,
+                        Timestamp::from_micros(time_in_micros),
+                    );
+                    node.tagged_flow.flow.anomaly_flags |= anomaly.bits();
+                }
+            }
+
             if let Some(l7_perf) = flow_perf_stats.l7.get_mut(&key) {
                 l7_perf.sequential_merge(&l7_perf_stats);
             } else {
@@ -1653,6 +2021,13 @@ impl FlowMap {
         is_first_packet: bool,
     ) {
         let flow_config = &config.flow;
+        // fragments are parsed after reassembly
+        if meta_packet.ip_fragment.is_some()
+            && flow_config.ip_fragment_reassembly.enabled
+            && meta_packet.lookup_key.proto == IpProtocol::UDP
+        {
+            return;
+        }
         let log_parser_config = &config.log_parser;
         let consistent_timestamp_in_l7_metrics = config.flow.consistent_timestamp_in_l7_metrics;
         if let Some(mut log) = node.meta_flow_log.take() {
@@ -1698,7 +2073,9 @@ impl FlowMap {
                                     node,
                                     &mut log,
                                     &s,
+                                    packet.get_captured_byte() as u32,
                                     consistent_timestamp_in_l7_metrics,
+                                    &log_parser_config.alerting,
                                     timestamp,
                                 );
                                 self.write_to_app_proto_log(flow_config, node, &packet, s);
@@ -1710,7 +2087,9 @@ impl FlowMap {
                                         node,
                                         &mut log,
                                         &i,
+                                        packet.get_captured_byte() as u32,
                                         consistent_timestamp_in_l7_metrics,
+                                        &log_parser_config.alerting,
                                         timestamp,
                                     );
                                     self.write_to_app_proto_log(flow_config, node, &packet, i);
@@ -1745,7 +2124,7 @@ impl FlowMap {
                 node.timeout = DEFAULT_SOCKET_CLOSE_TIMEOUT;
             } else {
                 // Initialize a timeout long enough for eBPF Flow to enable successful session aggregation.
-                node.timeout = config.log_parser.l7_log_session_aggr_timeout.into();
+                node.timeout = config.log_parser.l7_log_session_aggr_max_timeout().into();
             }
         } else {
             reverse = self.update_l4_direction(meta_packet, &mut node, true);
@@ -1826,6 +2205,23 @@ impl FlowMap {
         node
     }
 
+    fn new_sctp_node(&mut self, config: &Config, meta_packet: &mut MetaPacket) -> Box<FlowNode> {
+        let flow_config = config.flow;
+        let mut node = self.init_flow(config, meta_packet);
+        meta_packet.flow_id = node.tagged_flow.flow.flow_id;
+        meta_packet.second_in_minute =
+            (node.tagged_flow.flow.start_time.as_secs() % SECONDS_IN_MINUTE) as u8;
+        node.flow_state = FlowState::Established;
+        node.timeout = flow_config.flow_timeout.opening; // use opening timeout
+        let reverse = self.update_l4_direction(meta_packet, &mut node, true);
+        node.update_sctp_verification_tags(meta_packet);
+        meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
+        if flow_config.collector_enabled {
+            self.collect_metric(config, &mut node, meta_packet, !reverse, true);
+        }
+        node
+    }
+
     fn new_other_node(&mut self, config: &Config, meta_packet: &mut MetaPacket) -> Box<FlowNode> {
         let mut node = self.init_flow(config, meta_packet);
         meta_packet.flow_id = node.tagged_flow.flow.flow_id;
@@ -1857,6 +2253,7 @@ impl FlowMap {
         let mut node = match meta_packet.lookup_key.proto {
             IpProtocol::TCP => self.new_tcp_node(config, meta_packet),
             IpProtocol::UDP => self.new_udp_node(config, meta_packet),
+            IpProtocol::SCTP => self.new_sctp_node(config, meta_packet),
             _ => self.new_other_node(config, meta_packet),
         };
 
@@ -1869,6 +2266,17 @@ impl FlowMap {
                 self.stats_counter.closed.fetch_add(1, Ordering::Relaxed);
                 return None;
             }
+        } else if config.flow.anomaly_detection.enabled {
+            if let Some(detector) = self.anomaly_detector.as_ref() {
+                let anomaly = detector.new_flow(
+                    &config.flow.anomaly_detection,
+                    &config.flow.policy_rules,
+                    meta_packet,
+                );
+                if anomaly != FlowAnomaly::empty() {
+                    node.tagged_flow.flow.anomaly_flags |= anomaly.bits();
+                }
+            }
         }
 
         self.size += 1;
@@ -1994,6 +2402,14 @@ impl FlowMap {
 
         // 统计数据输出前矫正流方向
         self.update_flow_direction(&mut node, meta_packet);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let recent_time = node.recent_time.into();
+            node.lookup_process();
+            node.lookup_tcp_health(recent_time, true);
+        }
+        #[cfg(target_os = "linux")]
+        node.lookup_kernel_nat();
 
         let mut flow = &mut node.tagged_flow.flow;
         if flow.signal_source == SignalSource::EBPF {
@@ -2021,6 +2437,7 @@ impl FlowMap {
         if config.flow.collector_enabled
             && (flow.flow_key.proto == IpProtocol::TCP
                 || flow.flow_key.proto == IpProtocol::UDP
+                || flow.flow_key.proto == IpProtocol::SCTP
                 || flow.flow_key.proto == IpProtocol::ICMPV4
                 || flow.flow_key.proto == IpProtocol::ICMPV6)
         {
@@ -2066,6 +2483,13 @@ impl FlowMap {
                 || timestamp < node.tagged_flow.flow.flow_stat_time)
         {
             self.update_flow_direction(node, meta_packet); // 每个流统计数据输出前矫正流方向
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            {
+                node.lookup_process();
+                node.lookup_tcp_health(timestamp, false);
+            }
+            #[cfg(target_os = "linux")]
+            node.lookup_kernel_nat();
             node.tagged_flow.flow.close_type = CloseType::ForcedReport;
             let flow = &mut node.tagged_flow.flow;
             if !config.collector_enabled {
@@ -2076,6 +2500,7 @@ impl FlowMap {
             let mut collect_stats = false;
             if flow.flow_key.proto == IpProtocol::TCP
                 || flow.flow_key.proto == IpProtocol::UDP
+                || flow.flow_key.proto == IpProtocol::SCTP
                 || flow.flow_key.proto == IpProtocol::ICMPV4
                 || flow.flow_key.proto == IpProtocol::ICMPV6
             {
@@ -2201,7 +2626,8 @@ impl FlowMap {
                     flow_dst_key,
                 )
             }
-            IpProtocol::UDP => self.service_table.get_udp_score(
+            // SCTP has no handshake flags visible to the service table, score it like UDP
+            IpProtocol::UDP | IpProtocol::SCTP => self.service_table.get_udp_score(
                 is_first_packet,
                 meta_packet.need_reverse_flow,
                 lookup_key.direction,
@@ -2283,7 +2709,7 @@ impl FlowMap {
                     flow_dst_key,
                 )
             }
-            IpProtocol::UDP => self.service_table.get_udp_score(
+            IpProtocol::UDP | IpProtocol::SCTP => self.service_table.get_udp_score(
                 false,
                 false,
                 PacketDirection::ClientToServer,
@@ -2313,6 +2739,7 @@ impl FlowMap {
         }
         node.tagged_flow.flow.reverse(is_first_packet);
         node.tagged_flow.tag.reverse();
+        node.sctp_verification_tags.swap(0, 1);
         if let Some(tcp_segments) = node.tcp_segments.as_mut() {
             tcp_segments.reverse();
         }
@@ -2453,6 +2880,10 @@ pub struct FlowMapCounter {
     time_set_shrinks: AtomicU64,         // the total number of time_set HashSet shrinks
     pub l7_perf_cache_len: AtomicU64,    // the number of struct L7PerfCache::rrt_cache length
     pub l7_timeout_cache_len: AtomicU64, // the number of struct L7PerfCache::timeout_cache length
+    pub(super) fragment_reassembled: AtomicU64, // the number of reassembled UDP datagrams
+    pub(super) fragment_timeout: AtomicU64,     // datagrams evicted by reassembly timeout
+    pub(super) fragment_evicted: AtomicU64,     // datagrams evicted by reassembly capacity
+    pub(super) fragment_dropped: AtomicU64, // datagrams dropped for overlapping, truncated or oversized fragments
 }
 
 impl RefCountable for FlowMapCounter {
@@ -2527,6 +2958,26 @@ impl RefCountable for FlowMapCounter {
                 CounterType::Gauged,
                 CounterValue::Unsigned(self.l7_timeout_cache_len.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "fragment_reassembled",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.fragment_reassembled.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "fragment_timeout",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.fragment_timeout.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "fragment_evicted",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.fragment_evicted.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "fragment_dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.fragment_dropped.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 }
@@ -2596,6 +3047,10 @@ pub fn _new_flow_map_and_receiver(
         Arc::new(AtomicI64::new(0)),
         &config.flow,
         Some(packet_sequence_queue), // Enterprise Edition Feature: packet-sequence
+        None,
+        None,
+        None,
+        None,
         Arc::new(stats::Collector::new("", Arc::new(AtomicI64::new(0)))),
         false,
     );
@@ -14,7 +14,7 @@
  * limitations under the License.
  */
 
-use std::{net::IpAddr, sync::Arc};
+use std::{net::IpAddr, sync::Arc, time::Duration};
 
 use ahash::AHashMap;
 
@@ -25,10 +25,14 @@ use crate::common::{
     enums::{CaptureNetworkType, EthernetType, TcpFlags},
     flow::{FlowMetricsPeer, PacketDirection, SignalSource, TcpPerfStats},
     lookup_key::LookupKey,
-    meta_packet::MetaPacket,
+    meta_packet::{MetaPacket, ProtocolData},
     tagged_flow::TaggedFlow,
     TapPort, Timestamp,
 };
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::ebpf::{SOCKET_OWNER_INFO, TCP_HEALTH_INFO};
+#[cfg(target_os = "linux")]
+use crate::platform::{conntrack, ipvs};
 use crate::utils::environment::{is_tt_hyper_v, is_tt_pod};
 use public::{proto::agent::AgentType, utils::net::MacAddr};
 
@@ -36,6 +40,9 @@ use npb_pcap_policy::PolicyData;
 use packet_segmentation_reassembly::PacketSegmentationReassembly;
 use packet_sequence_block::PacketSequenceBlock;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TCP_HEALTH_LOOKUP_INTERVAL: Duration = Duration::from_secs(10);
+
 #[repr(u8)]
 enum MatchMac {
     None,
@@ -138,6 +145,8 @@ pub struct FlowNode {
     pub next_tcp_seq0: u32,
     pub next_tcp_seq1: u32,
     pub last_cap_seq: u32,
+    // Only for SCTP Flow, the verification tag carried by packets of each direction, 0 if unknown.
+    pub sctp_verification_tags: [u32; 2],
 
     // 当前统计周期（目前是自然秒）是否更新策略
     pub policy_in_tick: [bool; 2],
@@ -149,6 +158,15 @@ pub struct FlowNode {
 
     // tcp segments
     pub tcp_segments: Option<PacketSegmentationReassembly>,
+
+    // Remaining attempts to attribute the flow to local processes, see lookup_process()
+    pub process_lookup_attempts: u8,
+
+    // Time of the last TCP health lookup, None if not looked up, see lookup_tcp_health()
+    pub tcp_health_lookup_time: Option<Duration>,
+
+    // Remaining attempts to find the NAT translation in kernel, see lookup_kernel_nat()
+    pub nat_lookup_attempts: u8,
 }
 
 impl FlowNode {
@@ -158,6 +176,7 @@ impl FlowNode {
         let flow = &mut self.tagged_flow.flow;
         flow.flow_stat_time = Default::default();
         flow.is_new_flow = false;
+        flow.icmp_error_count = 0;
         let flow_metrics_peer_src = &mut flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
         flow_metrics_peer_src.packet_count = 0;
         flow_metrics_peer_src.byte_count = 0;
@@ -178,6 +197,133 @@ impl FlowNode {
         }
     }
 
+    // Fills in the local processes of both sides with the sockets recorded by eBPF.
+    // Sockets are recorded when accept() or connect() returns, which is usually after the
+    // first packets of the connection, so the lookup is retried when flow stats are output.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn lookup_process(&mut self) {
+        if self.process_lookup_attempts == 0 {
+            return;
+        }
+        self.process_lookup_attempts -= 1;
+
+        let flow = &mut self.tagged_flow.flow;
+        let key = &flow.flow_key;
+        let proto = u8::from(key.proto);
+        let (src, dst) = ((key.ip_src, key.port_src), (key.ip_dst, key.port_dst));
+        if flow.process_id_0 == 0 {
+            if let Some(owner) = SOCKET_OWNER_INFO::lookup(proto, src, dst) {
+                flow.process_id_0 = owner.process_id;
+                flow.process_kname_0 = owner.process_kname();
+            }
+        }
+        if flow.process_id_1 == 0 {
+            if let Some(owner) = SOCKET_OWNER_INFO::lookup(proto, dst, src) {
+                flow.process_id_1 = owner.process_id;
+                flow.process_kname_1 = owner.process_kname();
+            }
+        }
+        if flow.process_id_0 > 0 && flow.process_id_1 > 0 {
+            self.process_lookup_attempts = 0;
+        }
+    }
+
+    // Fills in the TCP health of the local socket collected by eBPF tracepoints. The counters
+    // are cumulative in kernel, so the lookup is done at most once in TCP_HEALTH_LOOKUP_INTERVAL
+    // when flow stats are output, unless `force` is set when the flow ends.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn lookup_tcp_health(&mut self, now: Duration, force: bool) {
+        let Some(last) = self.tcp_health_lookup_time else {
+            return;
+        };
+        if !force && now < last + TCP_HEALTH_LOOKUP_INTERVAL {
+            return;
+        }
+        self.tcp_health_lookup_time = Some(now);
+
+        let flow = &mut self.tagged_flow.flow;
+        let key = &flow.flow_key;
+        let (src, dst) = ((key.ip_src, key.port_src), (key.ip_dst, key.port_dst));
+        let Some(health) =
+            TCP_HEALTH_INFO::lookup(src, dst).or_else(|| TCP_HEALTH_INFO::lookup(dst, src))
+        else {
+            return;
+        };
+        flow.kernel_tcp_retrans = health.retrans;
+        flow.kernel_tcp_drops = health.drops;
+        flow.kernel_tcp_srtt = health.srtt_us;
+        flow.kernel_tcp_state = health.state as u8;
+    }
+
+    // Fills in the real addresses of the flow translated by the kernel conntrack or IPVS, so
+    // that the flows captured before and after SNAT/DNAT have the same real addresses. The
+    // translations are known after the first packet passes netfilter, and IPVS connections
+    // are read periodically, so the lookup is retried when flow stats are output.
+    #[cfg(target_os = "linux")]
+    pub(super) fn lookup_kernel_nat(&mut self) {
+        if self.nat_lookup_attempts == 0 {
+            return;
+        }
+        self.nat_lookup_attempts -= 1;
+
+        let flow = &mut self.tagged_flow.flow;
+        let key = &flow.flow_key;
+        let (proto, src, dst) = (
+            u8::from(key.proto),
+            (key.ip_src, key.port_src),
+            (key.ip_dst, key.port_dst),
+        );
+        let translations = [
+            (
+                TapPort::NAT_SOURCE_CONNTRACK,
+                conntrack::lookup_nat(proto, src, dst),
+            ),
+            (TapPort::NAT_SOURCE_IPVS, ipvs::lookup_nat(proto, src, dst)),
+        ];
+        for (nat_source, addrs) in translations {
+            let Some(addrs) = addrs else {
+                continue;
+            };
+            self.nat_lookup_attempts = 0;
+            for (peer, real) in [
+                (FLOW_METRICS_PEER_SRC, addrs.src),
+                (FLOW_METRICS_PEER_DST, addrs.dst),
+            ] {
+                let metrics = &mut flow.flow_metrics_peers[peer];
+                let Some((ip, port)) = real else {
+                    continue;
+                };
+                if nat_source <= metrics.nat_source {
+                    continue;
+                }
+                metrics.nat_source = nat_source;
+                metrics.nat_real_ip = ip;
+                metrics.nat_real_port = port;
+            }
+            if nat_source > flow.flow_key.tap_port.get_nat_source() {
+                flow.flow_key.tap_port.set_nat_source(nat_source);
+            }
+        }
+    }
+
+    pub(super) fn update_sctp_verification_tags(&mut self, meta_packet: &MetaPacket) {
+        let ProtocolData::SctpData(sctp_data) = &meta_packet.protocol_data else {
+            return;
+        };
+        let direction = meta_packet.lookup_key.direction;
+        let tags = &mut self.sctp_verification_tags;
+        if tags[direction as usize] == 0
+            && sctp_data.verification_tag != 0
+            && !sctp_data.tag_reflected
+        {
+            tags[direction as usize] = sctp_data.verification_tag;
+        }
+        // the initiate tag of INIT or INIT_ACK is the tag the peer will send with
+        if tags[direction.reversed() as usize] == 0 && sctp_data.initiate_tag != 0 {
+            tags[direction.reversed() as usize] = sctp_data.initiate_tag;
+        }
+    }
+
     // reset l7 parser and l7 perf stats on plugin reload to avoid inconsistency
     pub fn reset_on_plugin_reload(&mut self) {
         if let Some(stats) = self.tagged_flow.flow.flow_perf_stats.as_mut() {
@@ -283,7 +429,7 @@ impl FlowNode {
         {
             // l3 protocols, such as icmp, can determine the direction of packets according
             // to icmp type, so there is no need to correct the direction of packets
-            if meta_lookup_key.is_tcp() || meta_lookup_key.is_udp() {
+            if meta_lookup_key.is_tcp() || meta_lookup_key.is_udp() || meta_lookup_key.is_sctp() {
                 meta_packet.lookup_key.direction = PacketDirection::ClientToServer;
             }
         } else if flow_key.ip_src == meta_lookup_key.dst_ip
@@ -291,12 +437,23 @@ impl FlowNode {
             && flow_key.port_src == meta_lookup_key.dst_port
             && flow_key.port_dst == meta_lookup_key.src_port
         {
-            if meta_lookup_key.is_tcp() || meta_lookup_key.is_udp() {
+            if meta_lookup_key.is_tcp() || meta_lookup_key.is_udp() || meta_lookup_key.is_sctp() {
                 meta_packet.lookup_key.direction = PacketDirection::ServerToClient;
             }
         } else {
             return false;
         }
+        // SCTP associations between the same ports are told apart by verification tag
+        if let ProtocolData::SctpData(sctp_data) = &meta_packet.protocol_data {
+            let tag = self.sctp_verification_tags[meta_packet.lookup_key.direction as usize];
+            if tag != 0
+                && sctp_data.verification_tag != 0
+                && sctp_data.verification_tag != tag
+                && !sctp_data.tag_reflected
+            {
+                return false;
+            }
+        }
         Self::endpoint_match_with_direction(&flow.flow_metrics_peers, meta_packet)
             && Self::mac_match_with_direction(
                 meta_packet,
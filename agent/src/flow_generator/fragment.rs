@@ -0,0 +1,283 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    net::IpAddr,
+    num::NonZeroUsize,
+    ops::Range,
+    sync::{atomic::Ordering, Arc},
+};
+
+use lru::LruCache;
+
+use super::flow_map::FlowMapCounter;
+use crate::common::{
+    consts::{ETH_HEADER_SIZE, IPV4_HEADER_SIZE, IPV6_HEADER_SIZE, UDP_HEADER_SIZE},
+    enums::EthernetType,
+    meta_packet::{MetaPacket, RawPacket},
+    Timestamp,
+};
+use crate::config::IpFragmentReassembly;
+
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    id: u32,
+}
+
+#[derive(Default)]
+struct Datagram {
+    // the first fragment, which carries the L4 header
+    first: Option<Box<MetaPacket<'static>>>,
+    // IP payload of the datagram, starting with the L4 header
+    payload: Vec<u8>,
+    received: Vec<Range<usize>>,
+    received_len: usize,
+    // known after the last fragment arrives
+    total_len: Option<usize>,
+    last_seen: Timestamp,
+}
+
+impl Datagram {
+    // Returns false if the fragment conflicts with received ones
+    fn insert(&mut self, range: Range<usize>, data: &[u8], last: bool) -> bool {
+        if let Some(total_len) = self.total_len {
+            if range.end > total_len || (last && range.end != total_len) {
+                return false;
+            }
+        }
+        if last {
+            if self.received.iter().any(|r| r.end > range.end) {
+                return false;
+            }
+            self.total_len = Some(range.end);
+        }
+        for r in self.received.iter() {
+            if *r == range {
+                // retransmission
+                return true;
+            }
+            // overlapping fragments are invalid, see RFC 5722
+            if r.start < range.end && range.start < r.end {
+                return false;
+            }
+        }
+        if self.payload.len() < range.end {
+            self.payload.resize(range.end, 0);
+        }
+        self.payload[range.clone()].copy_from_slice(data);
+        self.received_len += range.len();
+        self.received.push(range);
+        true
+    }
+
+    fn is_complete(&self) -> bool {
+        self.first.is_some() && Some(self.received_len) == self.total_len
+    }
+}
+
+// Reassembles fragmented UDP datagrams for L7 protocol parsing, fragments are still
+// counted in their own flows
+pub struct FragmentCache {
+    datagrams: LruCache<FragmentKey, Datagram>,
+    counter: Arc<FlowMapCounter>,
+}
+
+impl FragmentCache {
+    pub fn new(capacity: usize, counter: Arc<FlowMapCounter>) -> Self {
+        Self {
+            datagrams: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+            counter,
+        }
+    }
+
+    fn purge(&mut self, config: &IpFragmentReassembly, now: Timestamp) {
+        if let Some(capacity) = NonZeroUsize::new(config.max_datagrams) {
+            if capacity != self.datagrams.cap() {
+                self.datagrams.resize(capacity);
+            }
+        }
+        while let Some((_, d)) = self.datagrams.peek_lru() {
+            if d.last_seen + config.timeout >= now {
+                break;
+            }
+            self.datagrams.pop_lru();
+            self.counter
+                .fragment_timeout
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Returns the reassembled packet when the last missing fragment arrives
+    pub fn inject(
+        &mut self,
+        config: &IpFragmentReassembly,
+        meta_packet: &MetaPacket,
+    ) -> Option<Box<MetaPacket<'static>>> {
+        let fragment = meta_packet.ip_fragment?;
+        let now = meta_packet.lookup_key.timestamp;
+        self.purge(config, now);
+
+        let key = FragmentKey {
+            src_ip: meta_packet.lookup_key.src_ip,
+            dst_ip: meta_packet.lookup_key.dst_ip,
+            id: fragment.id,
+        };
+        let Some(data) = meta_packet.get_l3_payload() else {
+            // truncated by capture, the datagram can not be reassembled
+            if self.datagrams.pop(&key).is_some() {
+                self.counter
+                    .fragment_dropped
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            return None;
+        };
+        let range = fragment.offset as usize..fragment.offset as usize + data.len();
+        if range.end > MAX_DATAGRAM_SIZE {
+            if self.datagrams.pop(&key).is_some() {
+                self.counter
+                    .fragment_dropped
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            return None;
+        }
+
+        if !self.datagrams.contains(&key) {
+            if let Some((evicted, _)) = self.datagrams.push(key, Datagram::default()) {
+                if evicted != key {
+                    self.counter
+                        .fragment_evicted
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        let datagram = self.datagrams.get_mut(&key).unwrap();
+        datagram.last_seen = now;
+        if !datagram.insert(range, data, !fragment.more_fragments) {
+            self.datagrams.pop(&key);
+            self.counter
+                .fragment_dropped
+                .fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        if fragment.offset == 0 && datagram.first.is_none() {
+            datagram.first = Some(Box::new(meta_packet.to_owned_packet()));
+        }
+        if !datagram.is_complete() {
+            return None;
+        }
+
+        let datagram = self.datagrams.pop(&key).unwrap();
+        self.counter
+            .fragment_reassembled
+            .fetch_add(1, Ordering::Relaxed);
+        Some(Self::reassemble(datagram, now))
+    }
+
+    fn reassemble(datagram: Datagram, now: Timestamp) -> Box<MetaPacket<'static>> {
+        let mut packet = datagram.first.unwrap();
+        let ip_header_size = if packet.lookup_key.eth_type == EthernetType::IPV6 {
+            IPV6_HEADER_SIZE
+        } else {
+            IPV4_HEADER_SIZE
+        };
+        let l3_start = ETH_HEADER_SIZE + ip_header_size + packet.l2_l3_opt_size as usize;
+        let mut raw = packet.raw.as_ref().unwrap()[..l3_start].to_vec();
+        raw.extend_from_slice(&datagram.payload);
+
+        let payload_len = datagram.payload.len().saturating_sub(UDP_HEADER_SIZE);
+        packet.packet_len = raw.len() as u32;
+        packet.payload_len = payload_len as u16;
+        packet.raw = Some(RawPacket::from(raw));
+        packet.lookup_key.timestamp = now;
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::common::enums::IpProtocol;
+
+    fn fragment(id: u16, offset: u16, more: bool, payload: &[u8]) -> Vec<u8> {
+        let total_len = (IPV4_HEADER_SIZE + payload.len()) as u16;
+        let frag = (offset >> 3) | if more { 0x2000 } else { 0 };
+        let mut packet = vec![
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x02, // dst mac
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // src mac
+            0x08, 0x00, // ipv4
+        ];
+        packet.extend_from_slice(&[0x45, 0x00]);
+        packet.extend_from_slice(&total_len.to_be_bytes());
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&frag.to_be_bytes());
+        packet.extend_from_slice(&[0x40, 0x11, 0x00, 0x00, 10, 0, 0, 2, 10, 0, 0, 1]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn inject(cache: &mut FragmentCache, packet: &[u8]) -> Option<Box<MetaPacket<'static>>> {
+        let config = IpFragmentReassembly {
+            enabled: true,
+            max_datagrams: 2,
+            timeout: Duration::from_secs(5),
+        };
+        let mut meta_packet = MetaPacket::default();
+        meta_packet
+            .update(packet, true, true, Duration::ZERO, packet.len())
+            .unwrap();
+        cache.inject(&config, &meta_packet)
+    }
+
+    #[test]
+    fn reassemble_udp() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let mut cache = FragmentCache::new(2, counter.clone());
+        let mut udp = vec![0x00, 0x35, 0xc3, 0x50, 0x00, 0x00, 0x00, 0x00]; // 53 -> 50000
+        udp.extend((0..40).map(|i| i as u8));
+
+        // out of order
+        assert!(inject(&mut cache, &fragment(7, 32, false, &udp[32..])).is_none());
+        assert!(inject(&mut cache, &fragment(7, 16, true, &udp[16..32])).is_none());
+        // overlapping fragment of another datagram is dropped
+        assert!(inject(&mut cache, &fragment(8, 0, true, &udp[..24])).is_none());
+        assert!(inject(&mut cache, &fragment(8, 16, false, &udp[16..])).is_none());
+        assert_eq!(counter.fragment_dropped.load(Ordering::Relaxed), 1);
+
+        let packet = inject(&mut cache, &fragment(7, 0, true, &udp[..16])).unwrap();
+        assert_eq!(packet.lookup_key.proto, IpProtocol::UDP);
+        assert_eq!(packet.lookup_key.src_port, 53);
+        assert_eq!(packet.get_l4_payload().unwrap(), &udp[UDP_HEADER_SIZE..]);
+        assert!(packet.ip_fragment.is_none());
+        assert_eq!(counter.fragment_reassembled.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn evict_by_capacity() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let mut cache = FragmentCache::new(2, counter.clone());
+        for id in 0..3 {
+            assert!(inject(&mut cache, &fragment(id, 0, true, &[0; 16])).is_none());
+        }
+        assert_eq!(counter.fragment_evicted.load(Ordering::Relaxed), 1);
+    }
+}
@@ -14,28 +14,40 @@
  * limitations under the License.
  */
 
+pub mod anomaly;
+pub mod app_alert;
 mod app_table;
 mod error;
 mod flow_config;
+pub mod flow_dump;
 pub mod flow_map;
 pub(crate) mod flow_node;
 pub(crate) mod flow_state;
+mod fragment;
+pub mod multicast;
+pub mod neighbor;
 mod packet_sequence; // Enterprise Edition Feature: packet-sequence
+pub mod packet_trace;
 pub mod perf;
 mod pool;
 pub mod protocol_logs;
 mod service_table;
 
+pub use anomaly::{AnomalyDetector, PolicyRule};
+pub use app_alert::AppAlertEvaluator;
 pub use error::{Error, Result};
 pub use flow_config::{FlowTimeout, TcpTimeout};
 pub use flow_map::FlowMap;
 use flow_node::{FlowMapKey, FlowNode};
 pub use flow_state::FlowState;
+pub use multicast::MulticastGroupTable;
+pub use neighbor::NeighborTable;
 pub use packet_sequence::PacketSequenceParser; // Enterprise Edition Feature: packet-sequence
 pub use protocol_logs::{
-    AppProto, AppProtoHead, DnsLog, HttpLog, LogMessageType, MemcachedLog, MetaAppProto, OracleLog,
-    TlsLog,
+    AppProto, AppProtoHead, DiameterLog, DnsLog, HttpLog, KerberosLog, LogMessageType,
+    MemcachedLog, MetaAppProto, ModbusLog, OracleLog, QuicLog, TlsLog,
 };
+pub use service_table::DirectionHeuristics;
 
 use std::time::Duration;
 
@@ -0,0 +1,313 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::RwLock,
+};
+
+use crate::common::{
+    consts::{ETH_HEADER_SIZE, ICMPV6_TYPE_OFFSET, IPV4_ADDR_LEN, IPV4_HEADER_SIZE, IPV6_ADDR_LEN},
+    enums::{EthernetType, IpProtocol},
+    flow::MulticastSource,
+    meta_packet::MetaPacket,
+    tap_port::TapPort,
+    Timestamp,
+};
+use crate::utils::bytes::read_u16_be;
+
+// IGMP, reference RFC 1112, RFC 2236 and RFC 3376
+const IGMP_V1_MEMBERSHIP_REPORT: u8 = 0x12;
+const IGMP_V2_MEMBERSHIP_REPORT: u8 = 0x16;
+const IGMP_V2_LEAVE_GROUP: u8 = 0x17;
+const IGMP_V3_MEMBERSHIP_REPORT: u8 = 0x22;
+const IGMP_GROUP_OFFSET: usize = 4;
+const IGMP_V3_RECORD_HEADER_SIZE: usize = 4 + IPV4_ADDR_LEN;
+
+// MLD, reference RFC 2710 and RFC 3810
+const MLD_V1_LISTENER_REPORT: u8 = 131;
+const MLD_V1_LISTENER_DONE: u8 = 132;
+const MLD_V2_LISTENER_REPORT: u8 = 143;
+const MLD_V1_GROUP_OFFSET: usize = 8;
+const MLD_V2_RECORD_HEADER_SIZE: usize = 4 + IPV6_ADDR_LEN;
+
+// IGMPv3 and MLDv2 reports share the layout except for the address length
+const V3_REPORT_RECORD_COUNT_OFFSET: usize = 6;
+const V3_REPORT_RECORDS_OFFSET: usize = 8;
+const MODE_IS_INCLUDE: u8 = 1;
+const MODE_IS_EXCLUDE: u8 = 2;
+const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+const ALLOW_NEW_SOURCES: u8 = 5;
+
+// Group Membership Interval with the default robustness variable and query interval
+const MEMBERSHIP_TIMEOUT: Timestamp = Timestamp::from_secs(260);
+
+#[derive(Debug, PartialEq)]
+struct GroupChange {
+    group: IpAddr,
+    join: bool,
+}
+
+fn v3_record_join(record_type: u8, source_count: u16) -> Option<bool> {
+    match record_type {
+        MODE_IS_EXCLUDE | CHANGE_TO_EXCLUDE_MODE => Some(true),
+        // INCLUDE mode with an empty source list means leaving the group
+        MODE_IS_INCLUDE | CHANGE_TO_INCLUDE_MODE => Some(source_count > 0),
+        ALLOW_NEW_SOURCES if source_count > 0 => Some(true),
+        // BLOCK_OLD_SOURCES does not change whether the host is a member
+        _ => None,
+    }
+}
+
+fn parse_v3_records(
+    payload: &[u8],
+    addr_len: usize,
+    record_header_size: usize,
+    changes: &mut Vec<GroupChange>,
+) {
+    if payload.len() < V3_REPORT_RECORDS_OFFSET {
+        return;
+    }
+    let record_count = read_u16_be(&payload[V3_REPORT_RECORD_COUNT_OFFSET..]);
+    let mut offset = V3_REPORT_RECORDS_OFFSET;
+    for _ in 0..record_count {
+        let Some(record) = payload.get(offset..offset + record_header_size) else {
+            return;
+        };
+        let (record_type, aux_len) = (record[0], record[1] as usize * 4);
+        let source_count = read_u16_be(&record[2..]);
+        let group = &record[4..4 + addr_len];
+        let group = if addr_len == IPV4_ADDR_LEN {
+            IpAddr::from(<[u8; 4]>::try_from(group).unwrap())
+        } else {
+            IpAddr::from(<[u8; 16]>::try_from(group).unwrap())
+        };
+        if let Some(join) = v3_record_join(record_type, source_count) {
+            changes.push(GroupChange { group, join });
+        }
+        offset += record_header_size + source_count as usize * addr_len + aux_len;
+    }
+}
+
+fn parse_igmp(payload: &[u8]) -> Option<(MulticastSource, Vec<GroupChange>)> {
+    let igmp_type = *payload.first()?;
+    let mut changes = vec![];
+    let source = match igmp_type {
+        IGMP_V1_MEMBERSHIP_REPORT | IGMP_V2_MEMBERSHIP_REPORT | IGMP_V2_LEAVE_GROUP => {
+            let group = payload.get(IGMP_GROUP_OFFSET..IGMP_GROUP_OFFSET + IPV4_ADDR_LEN)?;
+            changes.push(GroupChange {
+                group: Ipv4Addr::from(<[u8; 4]>::try_from(group).unwrap()).into(),
+                join: igmp_type != IGMP_V2_LEAVE_GROUP,
+            });
+            if igmp_type == IGMP_V1_MEMBERSHIP_REPORT {
+                MulticastSource::IgmpV1
+            } else {
+                MulticastSource::IgmpV2
+            }
+        }
+        IGMP_V3_MEMBERSHIP_REPORT => {
+            parse_v3_records(
+                payload,
+                IPV4_ADDR_LEN,
+                IGMP_V3_RECORD_HEADER_SIZE,
+                &mut changes,
+            );
+            MulticastSource::IgmpV3
+        }
+        _ => return None,
+    };
+    Some((source, changes))
+}
+
+fn parse_mld(payload: &[u8]) -> Option<(MulticastSource, Vec<GroupChange>)> {
+    let mld_type = *payload.first()?;
+    let mut changes = vec![];
+    let source = match mld_type {
+        MLD_V1_LISTENER_REPORT | MLD_V1_LISTENER_DONE => {
+            let group = payload.get(MLD_V1_GROUP_OFFSET..MLD_V1_GROUP_OFFSET + IPV6_ADDR_LEN)?;
+            changes.push(GroupChange {
+                group: Ipv6Addr::from(<[u8; 16]>::try_from(group).unwrap()).into(),
+                join: mld_type == MLD_V1_LISTENER_REPORT,
+            });
+            MulticastSource::MldV1
+        }
+        MLD_V2_LISTENER_REPORT => {
+            parse_v3_records(
+                payload,
+                IPV6_ADDR_LEN,
+                MLD_V2_RECORD_HEADER_SIZE,
+                &mut changes,
+            );
+            MulticastSource::MldV2
+        }
+        _ => return None,
+    };
+    Some((source, changes))
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupMember {
+    pub member: IpAddr,
+    pub source: MulticastSource,
+    pub last_report: Timestamp,
+}
+
+// Multicast group membership of each capture interface, learned from IGMP and MLD reports
+#[derive(Default)]
+pub struct MulticastGroupTable {
+    interfaces: RwLock<BTreeMap<TapPort, HashMap<IpAddr, Vec<GroupMember>>>>,
+}
+
+impl MulticastGroupTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Updates the table if the packet is an IGMP or MLD report
+    pub fn update(&self, meta_packet: &MetaPacket) {
+        let Some(raw) = meta_packet.raw.as_ref() else {
+            return;
+        };
+        let key = &meta_packet.lookup_key;
+        let report = match (key.eth_type, key.proto) {
+            (EthernetType::IPV4, IpProtocol::IGMP) => raw
+                .get(ETH_HEADER_SIZE + IPV4_HEADER_SIZE + meta_packet.l2_l3_opt_size as usize..)
+                .and_then(parse_igmp),
+            (EthernetType::IPV6, IpProtocol::ICMPV6) => raw
+                .get(ICMPV6_TYPE_OFFSET + meta_packet.l2_l3_opt_size as usize..)
+                .and_then(parse_mld),
+            _ => None,
+        };
+        let Some((source, changes)) = report else {
+            return;
+        };
+
+        let now = key.timestamp;
+        let mut interfaces = self.interfaces.write().unwrap();
+        let groups = interfaces.entry(meta_packet.tap_port).or_default();
+        for change in changes {
+            let members = groups.entry(change.group).or_default();
+            members.retain(|m| m.member != key.src_ip);
+            if change.join {
+                members.push(GroupMember {
+                    member: key.src_ip,
+                    source,
+                    last_report: now,
+                });
+            }
+        }
+        // members not refreshed within the membership interval have left silently
+        groups.retain(|_, members| {
+            members.retain(|m| m.last_report + MEMBERSHIP_TIMEOUT >= now);
+            !members.is_empty()
+        });
+        if groups.is_empty() {
+            interfaces.remove(&meta_packet.tap_port);
+        }
+    }
+
+    // Returns the protocol of the latest report joining `group` on the interface
+    pub fn lookup(&self, tap_port: TapPort, group: &IpAddr) -> MulticastSource {
+        if !group.is_multicast() {
+            return MulticastSource::None;
+        }
+        let interfaces = self.interfaces.read().unwrap();
+        interfaces
+            .get(&tap_port)
+            .and_then(|groups| groups.get(group))
+            .and_then(|members| members.iter().max_by_key(|m| m.last_report))
+            .map(|m| m.source)
+            .unwrap_or_default()
+    }
+
+    pub fn entries(&self) -> Vec<(TapPort, IpAddr, GroupMember)> {
+        let interfaces = self.interfaces.read().unwrap();
+        let mut entries = vec![];
+        for (tap_port, groups) in interfaces.iter() {
+            for (group, members) in groups.iter() {
+                for member in members.iter() {
+                    entries.push((*tap_port, *group, member.clone()));
+                }
+            }
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn igmp_reports() {
+        let v2_join = [0x16, 0x00, 0x00, 0x00, 239, 1, 1, 1];
+        assert_eq!(
+            parse_igmp(&v2_join),
+            Some((
+                MulticastSource::IgmpV2,
+                vec![GroupChange {
+                    group: Ipv4Addr::new(239, 1, 1, 1).into(),
+                    join: true,
+                }]
+            ))
+        );
+
+        let v3_report = [
+            0x22, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, // 3 records
+            0x04, 0x00, 0x00, 0x00, 239, 1, 1, 1, // CHANGE_TO_EXCLUDE with no source
+            0x01, 0x00, 0x00, 0x01, 239, 2, 2, 2, // MODE_IS_INCLUDE with 1 source
+            10, 0, 0, 1, //
+            0x03, 0x00, 0x00, 0x00, 239, 3, 3, 3, // CHANGE_TO_INCLUDE with no source
+        ];
+        let (source, changes) = parse_igmp(&v3_report).unwrap();
+        assert_eq!(source, MulticastSource::IgmpV3);
+        assert_eq!(
+            changes.iter().map(|c| c.join).collect::<Vec<_>>(),
+            vec![true, true, false]
+        );
+        assert_eq!(changes[2].group, IpAddr::from(Ipv4Addr::new(239, 3, 3, 3)));
+
+        // truncated records are ignored
+        let (_, changes) = parse_igmp(&v3_report[..20]).unwrap();
+        assert_eq!(changes.len(), 1);
+    }
+
+    #[test]
+    fn mld_reports() {
+        let group: Ipv6Addr = "ff05::1:3".parse().unwrap();
+        let mut done = vec![132, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        done.extend_from_slice(&group.octets());
+        assert_eq!(
+            parse_mld(&done),
+            Some((
+                MulticastSource::MldV1,
+                vec![GroupChange {
+                    group: group.into(),
+                    join: false,
+                }]
+            ))
+        );
+
+        let mut v2_report = vec![143, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        v2_report.extend_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+        v2_report.extend_from_slice(&group.octets());
+        let (source, changes) = parse_mld(&v2_report).unwrap();
+        assert_eq!(source, MulticastSource::MldV2);
+        assert!(changes[0].join);
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use log::warn;
+
+use crate::common::{
+    consts::{ETH_HEADER_SIZE, ICMPV6_TYPE_OFFSET, MAC_ADDR_LEN},
+    enums::{CaptureNetworkType, EthernetType, IpProtocol},
+    meta_packet::MetaPacket,
+    Timestamp,
+};
+use crate::exception::ExceptionHandler;
+use public::{proto::agent::Exception, utils::net::MacAddr};
+
+const ARP_SHA_OFFSET: usize = ETH_HEADER_SIZE + 8;
+
+// Neighbor Discovery, reference RFC 4861
+const NDP_NEIGHBOR_SOLICITATION: u8 = 135;
+const NDP_NEIGHBOR_ADVERTISEMENT: u8 = 136;
+const NDP_TARGET_OFFSET: usize = 8;
+const NDP_OPTIONS_OFFSET: usize = 24;
+const NDP_OPTION_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+const NDP_OPTION_TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+
+// Entries not refreshed within the timeout are replaced silently by a new claim
+const NEIGHBOR_TIMEOUT: Timestamp = Timestamp::from_secs(300);
+const NEIGHBOR_TABLE_CAPACITY: usize = 1 << 16;
+
+fn ndp_link_layer_address(options: &[u8], option_type: u8) -> Option<MacAddr> {
+    let mut offset = 0;
+    while offset + 2 <= options.len() {
+        // the length is in units of 8 bytes, including type and length
+        let len = options[offset + 1] as usize * 8;
+        if len == 0 {
+            return None;
+        }
+        if options[offset] == option_type {
+            let addr = options.get(offset + 2..offset + 2 + MAC_ADDR_LEN)?;
+            return MacAddr::try_from(addr).ok();
+        }
+        offset += len;
+    }
+    None
+}
+
+// Returns the (ip, mac) pair claimed by ARP or NDP
+fn parse_claim(meta_packet: &MetaPacket) -> Option<(IpAddr, MacAddr)> {
+    let raw = meta_packet.raw.as_ref()?;
+    let key = &meta_packet.lookup_key;
+    match (key.eth_type, key.proto) {
+        (EthernetType::ARP, _) => {
+            // ARP probes use 0.0.0.0 as the sender address
+            if key.src_ip.is_unspecified() {
+                return None;
+            }
+            let offset = ARP_SHA_OFFSET + meta_packet.vlan_tag_size as usize;
+            let mac = raw.get(offset..offset + MAC_ADDR_LEN)?;
+            Some((key.src_ip, MacAddr::try_from(mac).ok()?))
+        }
+        (EthernetType::IPV6, IpProtocol::ICMPV6) => {
+            let icmp = raw.get(ICMPV6_TYPE_OFFSET + meta_packet.l2_l3_opt_size as usize..)?;
+            let options = icmp.get(NDP_OPTIONS_OFFSET..)?;
+            match *icmp.first()? {
+                NDP_NEIGHBOR_SOLICITATION if !key.src_ip.is_unspecified() => {
+                    let mac =
+                        ndp_link_layer_address(options, NDP_OPTION_SOURCE_LINK_LAYER_ADDRESS)?;
+                    Some((key.src_ip, mac))
+                }
+                NDP_NEIGHBOR_ADVERTISEMENT => {
+                    let target = icmp.get(NDP_TARGET_OFFSET..NDP_OPTIONS_OFFSET)?;
+                    let target = Ipv6Addr::from(<[u8; 16]>::try_from(target).unwrap());
+                    let mac =
+                        ndp_link_layer_address(options, NDP_OPTION_TARGET_LINK_LAYER_ADDRESS)?;
+                    Some((target.into(), mac))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_gratuitous_arp(meta_packet: &MetaPacket) -> bool {
+    let key = &meta_packet.lookup_key;
+    key.eth_type == EthernetType::ARP && key.src_ip == key.dst_ip
+}
+
+#[derive(Clone, Debug)]
+pub struct NeighborEntry {
+    pub mac: MacAddr,
+    pub last_seen: Timestamp,
+    pub gratuitous: bool,
+    // the last MAC claiming the same IP while this entry is alive
+    pub conflict_mac: Option<MacAddr>,
+    pub conflict_time: Timestamp,
+    pub conflict_count: u32,
+}
+
+impl NeighborEntry {
+    fn in_conflict(&self, now: Timestamp) -> bool {
+        self.conflict_mac.is_some() && self.conflict_time + NEIGHBOR_TIMEOUT >= now
+    }
+}
+
+// IP to MAC bindings learned from ARP and NDP of each capture network type
+pub struct NeighborTable {
+    entries: RwLock<HashMap<(CaptureNetworkType, IpAddr), NeighborEntry>>,
+    // skips table lookup of flows when no conflict is detected recently
+    last_conflict: AtomicU64,
+    exception_handler: ExceptionHandler,
+}
+
+impl NeighborTable {
+    pub fn new(exception_handler: ExceptionHandler) -> Self {
+        Self {
+            entries: Default::default(),
+            last_conflict: AtomicU64::new(0),
+            exception_handler,
+        }
+    }
+
+    pub fn update(&self, meta_packet: &MetaPacket) {
+        let Some((ip, mac)) = parse_claim(meta_packet) else {
+            return;
+        };
+        let now = meta_packet.lookup_key.timestamp;
+        let key = (meta_packet.lookup_key.tap_type, ip);
+        let gratuitous = is_gratuitous_arp(meta_packet);
+
+        let mut entries = self.entries.write().unwrap();
+        if let Some(entry) = entries.get_mut(&key) {
+            if entry.mac != mac && entry.last_seen + NEIGHBOR_TIMEOUT >= now {
+                entry.conflict_mac = Some(entry.mac);
+                entry.conflict_time = now;
+                entry.conflict_count += 1;
+                self.last_conflict
+                    .fetch_max(now.as_nanos(), Ordering::Relaxed);
+                self.exception_handler.set(Exception::IpConflictDetected);
+                warn!(
+                    "{} {} is claimed by {} and {}{}",
+                    key.0,
+                    ip,
+                    mac,
+                    entry.mac,
+                    if gratuitous { " (gratuitous ARP)" } else { "" }
+                );
+            }
+            entry.mac = mac;
+            entry.last_seen = now;
+            entry.gratuitous = gratuitous;
+            return;
+        }
+
+        if entries.len() >= NEIGHBOR_TABLE_CAPACITY {
+            entries.retain(|_, e| e.last_seen + NEIGHBOR_TIMEOUT >= now || e.in_conflict(now));
+            if entries.len() >= NEIGHBOR_TABLE_CAPACITY {
+                return;
+            }
+        }
+        entries.insert(
+            key,
+            NeighborEntry {
+                mac,
+                last_seen: now,
+                gratuitous,
+                conflict_mac: None,
+                conflict_time: Timestamp::ZERO,
+                conflict_count: 0,
+            },
+        );
+    }
+
+    // Returns whether the IP is claimed by more than one MAC recently
+    pub fn is_conflict(&self, tap_type: CaptureNetworkType, ip: &IpAddr, now: Timestamp) -> bool {
+        let last_conflict = Timestamp::from_nanos(self.last_conflict.load(Ordering::Relaxed));
+        if last_conflict.is_zero() || last_conflict + NEIGHBOR_TIMEOUT < now {
+            return false;
+        }
+        let entries = self.entries.read().unwrap();
+        entries
+            .get(&(tap_type, *ip))
+            .map(|e| e.in_conflict(now))
+            .unwrap_or_default()
+    }
+
+    pub fn entries(&self) -> Vec<(CaptureNetworkType, IpAddr, NeighborEntry)> {
+        let entries = self.entries.read().unwrap();
+        let mut entries = entries
+            .iter()
+            .map(|((tap_type, ip), e)| (*tap_type, *ip, e.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(tap_type, ip, _)| (*tap_type, *ip));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use crate::common::{
+        consts::{ARP_SPA_OFFSET, ARP_TPA_OFFSET},
+        meta_packet::RawPacket,
+    };
+
+    fn arp_packet<'a>(sha: [u8; 6], spa: Ipv4Addr, tpa: Ipv4Addr, secs: u64) -> MetaPacket<'a> {
+        let mut raw = vec![0u8; ETH_HEADER_SIZE + 28];
+        raw[12..14].copy_from_slice(&[0x08, 0x06]);
+        raw[ARP_SHA_OFFSET..ARP_SHA_OFFSET + MAC_ADDR_LEN].copy_from_slice(&sha);
+        raw[ARP_SPA_OFFSET..ARP_SPA_OFFSET + 4].copy_from_slice(&spa.octets());
+        raw[ARP_TPA_OFFSET..ARP_TPA_OFFSET + 4].copy_from_slice(&tpa.octets());
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.eth_type = EthernetType::ARP;
+        packet.lookup_key.src_ip = spa.into();
+        packet.lookup_key.dst_ip = tpa.into();
+        packet.lookup_key.timestamp = Timestamp::from_secs(secs);
+        packet.raw = Some(RawPacket::OwnedVec(raw));
+        packet
+    }
+
+    #[test]
+    fn arp_conflict() {
+        let exception_handler = ExceptionHandler::default();
+        let table = NeighborTable::new(exception_handler.clone());
+        let ip = Ipv4Addr::new(10, 0, 0, 1);
+        let tap_type = CaptureNetworkType::default();
+
+        table.update(&arp_packet(
+            [0, 0, 0, 0, 0, 1],
+            ip,
+            Ipv4Addr::new(10, 0, 0, 2),
+            100,
+        ));
+        assert!(!table.is_conflict(tap_type, &ip.into(), Timestamp::from_secs(100)));
+
+        // gratuitous ARP from another host claiming the same IP
+        table.update(&arp_packet([0, 0, 0, 0, 0, 2], ip, ip, 110));
+        assert!(table.is_conflict(tap_type, &ip.into(), Timestamp::from_secs(110)));
+        assert!(exception_handler.has(Exception::IpConflictDetected));
+        let (_, _, entry) = table.entries().pop().unwrap();
+        assert!(entry.gratuitous);
+        assert_eq!(entry.conflict_count, 1);
+        assert_eq!(entry.conflict_mac, Some(MacAddr::from([0, 0, 0, 0, 0, 1])));
+
+        // the conflict expires, and stale entries are replaced without conflict
+        assert!(!table.is_conflict(tap_type, &ip.into(), Timestamp::from_secs(1000)));
+        table.update(&arp_packet([0, 0, 0, 0, 0, 3], ip, ip, 1000));
+        assert_eq!(table.entries().pop().unwrap().2.conflict_count, 1);
+    }
+
+    #[test]
+    fn ndp_options() {
+        let options = [
+            0x0e, 0x01, 0, 0, 0, 0, 0, 0, // nonce option
+            0x02, 0x01, 0x52, 0x54, 0x00, 0x12, 0x34, 0x56, // target link-layer address
+        ];
+        assert_eq!(
+            ndp_link_layer_address(&options, NDP_OPTION_TARGET_LINK_LAYER_ADDRESS),
+            Some(MacAddr::from([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]))
+        );
+        assert!(ndp_link_layer_address(&options, NDP_OPTION_SOURCE_LINK_LAYER_ADDRESS).is_none());
+        assert!(
+            ndp_link_layer_address(&[0x02, 0x00, 0, 0], NDP_OPTION_TARGET_LINK_LAYER_ADDRESS)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwapOption;
+use lazy_static::lazy_static;
+use log::info;
+
+use crate::common::{
+    enums::IpProtocol,
+    lookup_key::LookupKey,
+    meta_packet::{MetaPacket, ProtocolData},
+};
+
+pub const DEFAULT_TRACE_DURATION: Duration = Duration::from_secs(300);
+// packets logged at most in a trace, to protect the log files and the log backhaul
+const MAX_TRACED_PACKETS: u64 = 10000;
+
+lazy_static! {
+    pub static ref PACKET_TRACE: PacketTrace = PacketTrace::default();
+}
+
+// Packets between two endpoints in either direction, ports and protocol match all if not set
+#[derive(Clone, Debug, PartialEq)]
+pub struct PacketTraceFilter {
+    pub ip_0: IpAddr,
+    pub ip_1: IpAddr,
+    pub port_0: Option<u16>,
+    pub port_1: Option<u16>,
+    pub protocol: Option<IpProtocol>,
+}
+
+impl PacketTraceFilter {
+    // Parses the filter from texts, empty or zero ports match all
+    pub fn parse(
+        ip_0: &str,
+        port_0: &str,
+        ip_1: &str,
+        port_1: &str,
+        protocol: &str,
+    ) -> Result<Self, String> {
+        let ip = |s: &str| IpAddr::from_str(s).map_err(|_| format!("invalid ip `{}`", s));
+        let port = |s: &str| match s {
+            "" | "0" => Ok(None),
+            s => u16::from_str(s)
+                .map(Some)
+                .map_err(|_| format!("invalid port `{}`", s)),
+        };
+        Ok(Self {
+            ip_0: ip(ip_0)?,
+            ip_1: ip(ip_1)?,
+            port_0: port(port_0)?,
+            port_1: port(port_1)?,
+            protocol: parse_protocol(protocol)?,
+        })
+    }
+
+    fn matches(&self, key: &LookupKey) -> bool {
+        if self.protocol.map(|p| p != key.proto).unwrap_or(false) {
+            return false;
+        }
+        let port_matches = |port: Option<u16>, p: u16| port.map(|port| port == p).unwrap_or(true);
+        (key.src_ip == self.ip_0
+            && key.dst_ip == self.ip_1
+            && port_matches(self.port_0, key.src_port)
+            && port_matches(self.port_1, key.dst_port))
+            || (key.src_ip == self.ip_1
+                && key.dst_ip == self.ip_0
+                && port_matches(self.port_1, key.src_port)
+                && port_matches(self.port_0, key.dst_port))
+    }
+}
+
+impl fmt::Display for PacketTraceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let port = |p: Option<u16>| p.map(|p| p.to_string()).unwrap_or("*".to_owned());
+        write!(
+            f,
+            "{}:{} <> {}:{} proto {}",
+            self.ip_0,
+            port(self.port_0),
+            self.ip_1,
+            port(self.port_1),
+            self.protocol
+                .map(|p| u8::from(p).to_string())
+                .unwrap_or("*".to_owned())
+        )
+    }
+}
+
+// Parses protocols in names or numbers
+pub fn parse_protocol(s: &str) -> Result<Option<IpProtocol>, String> {
+    match s.to_lowercase().as_str() {
+        "" | "*" | "any" => Ok(None),
+        "tcp" => Ok(Some(IpProtocol::TCP)),
+        "udp" => Ok(Some(IpProtocol::UDP)),
+        "icmp" => Ok(Some(IpProtocol::ICMPV4)),
+        "icmpv6" => Ok(Some(IpProtocol::ICMPV6)),
+        "sctp" => Ok(Some(IpProtocol::SCTP)),
+        p => u8::from_str(p)
+            .map(|p| Some(IpProtocol::from(p)))
+            .map_err(|_| format!("invalid protocol `{}`", s)),
+    }
+}
+
+struct Trace {
+    filter: PacketTraceFilter,
+    expire_at: SystemTime,
+    generation: u64,
+}
+
+// Time-boxed trace logging of packets injected into flow maps.
+//
+// Checking packets costs only an atomic load when no trace is running.
+#[derive(Default)]
+pub struct PacketTrace {
+    enabled: AtomicBool,
+    trace: ArcSwapOption<Trace>,
+    traced: AtomicU64,
+    generation: AtomicU64,
+}
+
+impl PacketTrace {
+    // Starts tracing packets matching `filter` for `duration`, replacing the running one
+    pub fn start(
+        &'static self,
+        filter: PacketTraceFilter,
+        duration: Duration,
+    ) -> Result<(), String> {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        info!("packet trace of {} started for {:?}", filter, duration);
+        self.trace.store(Some(Arc::new(Trace {
+            filter,
+            expire_at: SystemTime::now() + duration,
+            generation,
+        })));
+        self.traced.store(0, Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Release);
+
+        thread::Builder::new()
+            .name("packet-trace-stop".to_owned())
+            .spawn(move || {
+                thread::sleep(duration);
+                self.stop(Some(generation));
+            })
+            .map(|_| ())
+            .map_err(|e| {
+                self.stop(Some(generation));
+                format!("failed to schedule packet trace stop: {}", e)
+            })
+    }
+
+    // Stops the trace, only if it is still the one of `generation` if specified
+    pub fn stop(&self, generation: Option<u64>) {
+        let trace = self.trace.load();
+        let Some(t) = trace.as_ref() else {
+            return;
+        };
+        if generation.map(|g| g != t.generation).unwrap_or(false) {
+            return;
+        }
+        self.enabled.store(false, Ordering::Release);
+        self.trace.store(None);
+        info!(
+            "packet trace of {} stopped after {} packets",
+            t.filter,
+            self.traced.load(Ordering::Relaxed)
+        );
+    }
+
+    // Running trace filter and its expire time
+    pub fn current(&self) -> Option<(PacketTraceFilter, SystemTime)> {
+        self.trace
+            .load()
+            .as_ref()
+            .map(|t| (t.filter.clone(), t.expire_at))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn trace(&self, flow_map_id: u32, packet: &MetaPacket) {
+        let trace = self.trace.load();
+        let Some(t) = trace.as_ref() else {
+            return;
+        };
+        if !t.filter.matches(&packet.lookup_key) {
+            return;
+        }
+        let traced = self.traced.fetch_add(1, Ordering::Relaxed);
+        if traced >= MAX_TRACED_PACKETS {
+            if traced == MAX_TRACED_PACKETS {
+                info!(
+                    "packet trace of {} reached {} packets, the rest are not logged",
+                    t.filter, MAX_TRACED_PACKETS
+                );
+            }
+            return;
+        }
+        match &packet.protocol_data {
+            ProtocolData::TcpHeader(tcp) if packet.lookup_key.proto == IpProtocol::TCP => info!(
+                "packet trace flow_map#{} flow_id {} len {}: {} tcp: {:?}",
+                flow_map_id, packet.flow_id, packet.packet_len, packet.lookup_key, tcp
+            ),
+            _ => info!(
+                "packet trace flow_map#{} flow_id {} len {}: {}",
+                flow_map_id, packet.flow_id, packet.packet_len, packet.lookup_key
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn match_both_directions() {
+        let filter = PacketTraceFilter {
+            ip_0: Ipv4Addr::new(10, 0, 0, 1).into(),
+            ip_1: Ipv4Addr::new(10, 0, 0, 2).into(),
+            port_0: None,
+            port_1: Some(3306),
+            protocol: parse_protocol("tcp").unwrap(),
+        };
+        assert_eq!(
+            PacketTraceFilter::parse("10.0.0.1", "0", "10.0.0.2", "3306", "TCP"),
+            Ok(filter.clone())
+        );
+        assert!(PacketTraceFilter::parse("10.0.0.1", "", "10.0.0.2", "65536", "").is_err());
+        let mut key = LookupKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+            dst_ip: Ipv4Addr::new(10, 0, 0, 2).into(),
+            src_port: 40000,
+            dst_port: 3306,
+            proto: IpProtocol::TCP,
+            ..Default::default()
+        };
+        assert!(filter.matches(&key));
+
+        std::mem::swap(&mut key.src_ip, &mut key.dst_ip);
+        std::mem::swap(&mut key.src_port, &mut key.dst_port);
+        assert!(filter.matches(&key));
+
+        key.src_port = 3307;
+        assert!(!filter.matches(&key));
+
+        key.src_port = 3306;
+        key.proto = IpProtocol::UDP;
+        assert!(!filter.matches(&key));
+    }
+}
@@ -15,6 +15,7 @@
  */
 
 pub(crate) mod icmp;
+pub(crate) mod sctp;
 mod stats;
 pub mod tcp;
 pub(crate) mod udp;
@@ -61,7 +62,7 @@ use crate::{
     config::{handler::LogParserConfig, FlowConfig},
 };
 
-use {icmp::IcmpPerf, tcp::TcpPerf, udp::UdpPerf};
+use {icmp::IcmpPerf, sctp::SctpPerf, tcp::TcpPerf, udp::UdpPerf};
 
 pub use stats::FlowPerfCounter;
 
@@ -90,6 +91,7 @@ pub enum L4FlowPerfTable {
     Tcp(Box<TcpPerf>),
     Udp(UdpPerf),
     Icmp(IcmpPerf),
+    Sctp(SctpPerf),
 }
 
 impl L4FlowPerf for L4FlowPerfTable {
@@ -98,6 +100,7 @@ impl L4FlowPerf for L4FlowPerfTable {
             Self::Tcp(p) => p.parse(packet, direction),
             Self::Udp(p) => p.parse(packet, direction),
             Self::Icmp(p) => p.parse(packet, direction),
+            Self::Sctp(p) => p.parse(packet, direction),
         }
     }
 
@@ -106,6 +109,7 @@ impl L4FlowPerf for L4FlowPerfTable {
             Self::Tcp(p) => p.data_updated(),
             Self::Udp(p) => p.data_updated(),
             Self::Icmp(p) => p.data_updated(),
+            Self::Sctp(p) => p.data_updated(),
         }
     }
 
@@ -114,39 +118,77 @@ impl L4FlowPerf for L4FlowPerfTable {
             Self::Tcp(p) => p.copy_and_reset_data(flow_reversed),
             Self::Udp(p) => p.copy_and_reset_data(flow_reversed),
             Self::Icmp(p) => p.copy_and_reset_data(flow_reversed),
+            Self::Sctp(p) => p.copy_and_reset_data(flow_reversed),
         }
     }
 }
 
-pub type L7ProtocolTuple = (L7Protocol, Option<Bitmap>);
-
-// None in Vec means all ports
 pub struct L7ProtocolChecker {
-    tcp: Vec<L7ProtocolTuple>,
-    udp: Vec<L7ProtocolTuple>,
+    tcp: Vec<L7Protocol>,
+    udp: Vec<L7Protocol>,
+    sctp: Vec<L7Protocol>,
+    // protocols to check on each port, compiled from port bitmaps as an index into port_bitmaps
+    port_table: Vec<u16>,
+    port_bitmaps: Vec<L7ProtocolBitmap>,
 }
 
 impl L7ProtocolChecker {
+    // protocols without port bitmap are checked on all ports
     pub fn new(
         protocol_bitmap: &L7ProtocolBitmap,
         port_bitmap: &HashMap<L7Protocol, Bitmap>,
     ) -> Self {
         let mut tcp = vec![];
         let mut udp = vec![];
+        let mut sctp = vec![];
+        let mut all_ports = L7ProtocolBitmap::default();
+        let mut restricted = vec![];
         for parser in get_all_protocol() {
             let protocol = parser.protocol();
             if !protocol_bitmap.is_enabled(protocol) {
                 continue;
             }
             if parser.parsable_on_tcp() {
-                tcp.push((protocol, port_bitmap.get(&protocol).map(|m| m.clone())));
+                tcp.push(protocol);
             }
             if parser.parsable_on_udp() {
-                udp.push((protocol, port_bitmap.get(&protocol).map(|m| m.clone())));
+                udp.push(protocol);
+            }
+            if parser.parsable_on_sctp() {
+                sctp.push(protocol);
+            }
+            match port_bitmap.get(&protocol) {
+                Some(b) => restricted.push((protocol, b)),
+                None => all_ports.set_enabled(protocol),
+            }
+        }
+
+        let mut port_table = Vec::with_capacity(u16::MAX as usize + 1);
+        let mut port_bitmaps: Vec<L7ProtocolBitmap> = vec![];
+        for port in 0..=u16::MAX as usize {
+            let mut bitmap = all_ports;
+            for (protocol, b) in restricted.iter() {
+                if b.get(port).unwrap_or_default() {
+                    bitmap.set_enabled(*protocol);
+                }
             }
+            let index = match port_bitmaps.iter().position(|b| *b == bitmap) {
+                Some(index) => index,
+                None => {
+                    port_bitmaps.push(bitmap);
+                    port_bitmaps.len() - 1
+                }
+            };
+            port_table.push(index as u16);
         }
 
-        L7ProtocolChecker { tcp, udp }
+        L7ProtocolChecker {
+            tcp,
+            udp,
+            sctp,
+            port_table,
+            port_bitmaps,
+        }
     }
 
     pub fn possible_protocols(
@@ -158,30 +200,25 @@ impl L7ProtocolChecker {
             iter: match l4_protocol {
                 L4Protocol::Tcp => self.tcp.iter(),
                 L4Protocol::Udp => self.udp.iter(),
+                L4Protocol::Sctp => self.sctp.iter(),
                 _ => [].iter(),
             },
-            port,
+            bitmap: self.port_bitmaps[self.port_table[port as usize] as usize],
         }
     }
 }
 
 pub struct L7ProtocolCheckerIterator<'a> {
-    iter: slice::Iter<'a, L7ProtocolTuple>,
-    port: u16,
+    iter: slice::Iter<'a, L7Protocol>,
+    bitmap: L7ProtocolBitmap,
 }
 
 impl<'a> Iterator for L7ProtocolCheckerIterator<'a> {
     type Item = &'a L7Protocol;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some((proto, bitmap)) = self.iter.next() {
-            match bitmap {
-                // if bitmap is not None and does not has port in it, check next protocol
-                Some(b) if !b.get(self.port as usize).unwrap_or_default() => continue,
-                _ => return Some(proto),
-            }
-        }
-        None
+        let bitmap = self.bitmap;
+        self.iter.find(|p| bitmap.is_enabled(**p))
     }
 }
 
@@ -516,6 +553,7 @@ impl FlowLog {
                 )),
                 L4Protocol::Udp => Some(L4FlowPerfTable::Udp(UdpPerf::new())),
                 L4Protocol::Icmp => Some(L4FlowPerfTable::Icmp(IcmpPerf::new())),
+                L4Protocol::Sctp => Some(L4FlowPerfTable::Sctp(SctpPerf::new())),
                 _ => None,
             }
         } else {
@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cmp::max;
+
+use crate::common::{
+    flow::{FlowPerfStats, L4Protocol, PacketDirection},
+    meta_packet::{MetaPacket, ProtocolData, SctpChunkFlags},
+    Timestamp,
+};
+use crate::flow_generator::error::{Error, Result};
+
+use super::{L4FlowPerf, ART_MAX};
+
+// ART is calculated between DATA chunks like UDP, retransmissions are detected by TSN
+#[derive(Debug, Default)]
+pub struct SctpPerf {
+    req_timestamp: Timestamp,
+    art_max: Timestamp,
+    art_sum: Timestamp,
+    art_count: u32,
+    last_pkt_direction: PacketDirection,
+
+    // the next expected TSN of each peer, indexed by first packet direction or not
+    next_tsn: [Option<u32>; 2],
+    retrans: [u32; 2],
+
+    data_update_flag: bool,
+}
+
+impl SctpPerf {
+    pub fn new() -> Self {
+        SctpPerf::default()
+    }
+
+    fn is_retrans(&mut self, tsn: u32, fpd: bool) -> bool {
+        let index = if fpd { 0 } else { 1 };
+        match self.next_tsn[index] {
+            // TSN uses serial number arithmetic
+            Some(next) if (next.wrapping_sub(tsn) as i32) > 0 => {
+                self.retrans[index] += 1;
+                true
+            }
+            _ => {
+                self.next_tsn[index] = Some(tsn.wrapping_add(1));
+                false
+            }
+        }
+    }
+}
+
+impl L4FlowPerf for SctpPerf {
+    fn parse(&mut self, header: &MetaPacket, fpd: bool) -> Result<()> {
+        let ProtocolData::SctpData(sctp_data) = &header.protocol_data else {
+            return Err(Error::InvalidIpProtocol);
+        };
+        if !sctp_data.chunks.contains(SctpChunkFlags::DATA) || header.payload_len == 0 {
+            return Err(Error::ZeroPayloadLen);
+        }
+        if self.is_retrans(sctp_data.tsn, fpd) {
+            self.data_update_flag = true;
+            return Err(Error::RetransPacket);
+        }
+
+        let pkt_timestamp = header.lookup_key.timestamp;
+        if header.lookup_key.direction == PacketDirection::ClientToServer {
+            self.req_timestamp = pkt_timestamp.into();
+        } else if self.req_timestamp != Timestamp::ZERO
+            && self.req_timestamp <= pkt_timestamp
+            && header.lookup_key.direction != self.last_pkt_direction
+        {
+            let art = Timestamp::from(pkt_timestamp - self.req_timestamp);
+            if art <= ART_MAX {
+                self.art_max = max(self.art_max, art);
+                self.art_sum += art;
+                self.art_count += 1;
+                self.data_update_flag = true;
+            }
+        }
+
+        self.last_pkt_direction = header.lookup_key.direction;
+
+        Ok(())
+    }
+
+    fn data_updated(&self) -> bool {
+        self.data_update_flag
+    }
+
+    fn copy_and_reset_data(&mut self, flow_reversed: bool) -> FlowPerfStats {
+        let mut stats = FlowPerfStats::default();
+        stats.l4_protocol = L4Protocol::Sctp;
+        stats.tcp.art_max = self.art_max.as_micros() as u32;
+        stats.tcp.art_sum = self.art_sum.as_micros() as u32;
+        stats.tcp.art_count = self.art_count;
+        stats.tcp.counts_peers[0].retrans_count = self.retrans[0];
+        stats.tcp.counts_peers[1].retrans_count = self.retrans[1];
+        stats.tcp.total_retrans_count = self.retrans[0] + self.retrans[1];
+        if flow_reversed {
+            stats.tcp.counts_peers.swap(0, 1);
+        }
+
+        // TSN tracking lasts for the whole association
+        *self = SctpPerf {
+            next_tsn: self.next_tsn,
+            ..Default::default()
+        };
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::meta_packet::SctpData;
+
+    fn data_packet<'a>(direction: PacketDirection, tsn: u32, timestamp_us: u64) -> MetaPacket<'a> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = direction;
+        packet.lookup_key.timestamp = Timestamp::from_micros(timestamp_us);
+        packet.payload_len = 100;
+        packet.protocol_data = ProtocolData::SctpData(SctpData {
+            chunks: SctpChunkFlags::DATA,
+            tsn,
+            data_len: 100,
+            ..Default::default()
+        });
+        packet
+    }
+
+    #[test]
+    fn sctp_art_and_retrans() {
+        let mut perf = SctpPerf::new();
+        let req = data_packet(PacketDirection::ClientToServer, 10, 1000);
+        assert!(perf.parse(&req, true).is_ok());
+        let retrans = data_packet(PacketDirection::ClientToServer, 10, 1500);
+        assert!(perf.parse(&retrans, true).is_err());
+        let resp = data_packet(PacketDirection::ServerToClient, 500, 3000);
+        assert!(perf.parse(&resp, false).is_ok());
+        assert!(perf.data_updated());
+
+        let stats = perf.copy_and_reset_data(false);
+        assert_eq!(stats.l4_protocol, L4Protocol::Sctp);
+        assert_eq!(stats.tcp.art_count, 1);
+        assert_eq!(stats.tcp.art_sum, 2000);
+        assert_eq!(stats.tcp.counts_peers[0].retrans_count, 1);
+        assert_eq!(stats.tcp.counts_peers[1].retrans_count, 0);
+
+        // TSN state survives the reset
+        let next = data_packet(PacketDirection::ClientToServer, 11, 4000);
+        assert!(perf.parse(&next, true).is_ok());
+        assert!(!perf.data_updated());
+    }
+}
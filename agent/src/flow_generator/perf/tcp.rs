@@ -25,7 +25,7 @@ use super::{FlowPerfCounter, L4FlowPerf, ART_MAX};
 use crate::{
     common::{
         enums::TcpFlags,
-        flow::{FlowPerfStats, L4Protocol},
+        flow::{FlowPerfStats, L4Protocol, TcpPerfStats},
         lookup_key::LookupKey,
         meta_packet::{MetaPacket, MetaPacketTcpHeader, ProtocolData},
         Timestamp,
@@ -92,6 +92,10 @@ pub(crate) struct SessionPeer {
     payload_len: u32,
     win_size: u16,
     win_scale: u8,
+    mss: u16,
+    sack_permitted: bool,
+    // when the last zero window was advertised, zero if the window is open
+    zero_win_timestamp: Timestamp,
 
     syn_transmitted: bool,
 
@@ -343,6 +347,14 @@ impl SessionPeer {
         }
     }
 
+    // MSS and SACK permitted are only valid in SYN and SYN/ACK
+    fn update_handshake_options(&mut self, header: &MetaPacketTcpHeader) {
+        if header.mss > 0 {
+            self.mss = header.mss;
+        }
+        self.sack_permitted = header.sack_permitted;
+    }
+
     // 在TCP_STATE_ESTABLISHED阶段更新数据
     fn update_data(&mut self, p: &MetaPacket) {
         let tcp_data = if let ProtocolData::TcpHeader(tcp_data) = &p.protocol_data {
@@ -371,6 +383,25 @@ impl fmt::Display for SessionPeer {
 #[derive(Default)]
 pub(crate) struct PerfControl(SessionPeer, SessionPeer);
 
+impl PerfControl {
+    // options negotiated in the handshake are reported in every statistical period
+    fn update_perf_stats(&self, stats: &mut TcpPerfStats, flow_reversed: bool) {
+        let (src, dst) = if flow_reversed {
+            (&self.1, &self.0)
+        } else {
+            (&self.0, &self.1)
+        };
+        let win_scale_negotiated = src.win_scale & dst.win_scale & WIN_SCALE_FLAG > 0;
+        for (counts, peer) in stats.counts_peers.iter_mut().zip([src, dst]) {
+            counts.mss = peer.mss;
+            if win_scale_negotiated {
+                counts.win_scale = peer.win_scale & WIN_SCALE_MASK;
+            }
+        }
+        stats.sack_permitted = src.sack_permitted && dst.sack_permitted;
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Eq)]
 struct TimeStats {
     pub count: u32,
@@ -419,6 +450,16 @@ pub(crate) struct PerfData {
 
     zero_win_count_0: u32,
     zero_win_count_1: u32,
+    zero_win_duration_0: Timestamp,
+    zero_win_duration_1: Timestamp,
+
+    // segments below the highest one received, e.g. filling a hole
+    out_of_order_0: u32,
+    out_of_order_1: u32,
+
+    // packets carrying SACK blocks
+    sack_count_0: u32,
+    sack_count_1: u32,
 
     // SYN SYN_ACK count
     syn: u32,
@@ -498,6 +539,33 @@ impl PerfData {
         self.updated = true;
     }
 
+    fn calc_zero_win_duration(&mut self, d: Timestamp, fpd: bool) {
+        if fpd {
+            self.zero_win_duration_0 += d;
+        } else {
+            self.zero_win_duration_1 += d;
+        }
+        self.updated = true;
+    }
+
+    fn calc_out_of_order(&mut self, fpd: bool) {
+        if fpd {
+            self.out_of_order_0 += 1;
+        } else {
+            self.out_of_order_1 += 1;
+        }
+        self.updated = true;
+    }
+
+    fn calc_sack(&mut self, fpd: bool) {
+        if fpd {
+            self.sack_count_0 += 1;
+        } else {
+            self.sack_count_1 += 1;
+        }
+        self.updated = true;
+    }
+
     fn calc_psh_urg(&mut self, fpd: bool) {
         if fpd {
             self.psh_urg_count_0 += 1;
@@ -544,6 +612,12 @@ impl PerfData {
         stats.total_retrans_count = self.retrans_sum;
         stats.counts_peers[0].zero_win_count = self.zero_win_count_0;
         stats.counts_peers[1].zero_win_count = self.zero_win_count_1;
+        stats.counts_peers[0].zero_win_duration = self.zero_win_duration_0.as_micros() as u32;
+        stats.counts_peers[1].zero_win_duration = self.zero_win_duration_1.as_micros() as u32;
+        stats.counts_peers[0].out_of_order_count = self.out_of_order_0;
+        stats.counts_peers[1].out_of_order_count = self.out_of_order_1;
+        stats.counts_peers[0].sack_count = self.sack_count_0;
+        stats.counts_peers[1].sack_count = self.sack_count_1;
 
         stats.syn_count = self.syn;
         stats.synack_count = self.synack;
@@ -679,6 +753,8 @@ impl TcpPerf {
             return (false, false);
         }
 
+        // seq_list is in descending order, the first segment is the highest one
+        let below_highest = same_dir.seq_list_len > 0 && tcp_data.seq < same_dir.seq_list[0].seq;
         // 连接建立后，即ESTABLISHED阶段，用SeqArray判断包重传
         match same_dir.assert_seq_number(tcp_data, p.payload_len) {
             PacketSeqType::Retrans => {
@@ -692,7 +768,13 @@ impl TcpPerf {
                     .fetch_add(1, Ordering::Relaxed);
                 (true, false)
             }
-            _ => (false, false),
+            PacketSeqType::NotCare => (false, false),
+            _ => {
+                if below_highest {
+                    self.perf_data.calc_out_of_order(fpd);
+                }
+                (false, false)
+            }
         }
     }
 
@@ -888,9 +970,21 @@ impl TcpPerf {
         if same_dir.win_scale & oppo_dir.win_scale & WIN_SCALE_FLAG > 0 {
             win_size <<= (same_dir.win_scale & WIN_SCALE_MASK) as u32;
         }
+        // the duration is counted until the next packet in the same direction
+        let now = p.lookup_key.timestamp;
+        if !same_dir.zero_win_timestamp.is_zero() {
+            self.perf_data
+                .calc_zero_win_duration(now - same_dir.zero_win_timestamp, fpd);
+        }
         // win_size == 0 or zero window
         if win_size == 0 {
             self.perf_data.calc_zero_win(fpd);
+            same_dir.zero_win_timestamp = now;
+        } else {
+            same_dir.zero_win_timestamp = Timestamp::ZERO;
+        }
+        if tcp_data.sack.is_some() {
+            self.perf_data.calc_sack(fpd);
         }
 
         // PSH/URG
@@ -942,6 +1036,16 @@ impl TcpPerf {
             self.flow_established(p, fpd);
         }
 
+        if p.is_syn() || p.is_syn_ack() {
+            if let ProtocolData::TcpHeader(tcp_data) = &p.protocol_data {
+                if fpd {
+                    self.ctrl_info.0.update_handshake_options(tcp_data);
+                } else {
+                    self.ctrl_info.1.update_handshake_options(tcp_data);
+                }
+            }
+        }
+
         // calculate syn/synack count
         if p.is_syn() {
             // calculate established state retran syn
@@ -1044,6 +1148,8 @@ impl L4FlowPerf for TcpPerf {
         let mut stats = FlowPerfStats::default();
         stats.l4_protocol = L4Protocol::Tcp;
         self.perf_data.update_perf_stats(&mut stats, flow_reversed);
+        self.ctrl_info
+            .update_perf_stats(&mut stats.tcp, flow_reversed);
         self.perf_data = Default::default();
         stats
     }
@@ -1877,6 +1983,8 @@ mod tests {
             rtt_full: Timestamp::from_secs(11),
             zero_win_count_0: 2,
             zero_win_count_1: 5,
+            zero_win_duration_0: Timestamp::from_secs(104),
+            zero_win_duration_1: Timestamp::from_secs(206),
             syn: 1,
             synack: 1,
             updated: true,
@@ -2094,6 +2202,93 @@ mod tests {
         );
     }
 
+    #[test]
+    fn zero_win_out_of_order_and_options() {
+        fn packet<'a>(
+            flags: TcpFlags,
+            seq: u32,
+            timestamp: u64,
+            payload_len: u16,
+            header: MetaPacketTcpHeader,
+        ) -> MetaPacket<'a> {
+            let mut packet: MetaPacket = MiniMetaPacket {
+                data_offset: 20,
+                flags,
+                seq,
+                timestamp,
+                payload_len,
+                ..Default::default()
+            }
+            .into();
+            if let ProtocolData::TcpHeader(tcp_data) = &mut packet.protocol_data {
+                tcp_data.win_size = header.win_size;
+                tcp_data.mss = header.mss;
+                tcp_data.win_scale = header.win_scale;
+                tcp_data.sack_permitted = header.sack_permitted;
+                tcp_data.sack = header.sack;
+            }
+            packet
+        }
+        let window = MetaPacketTcpHeader {
+            win_size: 100,
+            ..Default::default()
+        };
+
+        let mut perf = TcpPerf::new(Arc::new(FlowPerfCounter::default()));
+        let syn = MetaPacketTcpHeader {
+            mss: 1460,
+            win_scale: 7,
+            sack_permitted: true,
+            ..window.clone()
+        };
+        perf.parse(&packet(TcpFlags::SYN, 111, 1, 0, syn), true)
+            .unwrap();
+        let syn_ack = MetaPacketTcpHeader {
+            mss: 1400,
+            win_scale: 5,
+            sack_permitted: true,
+            ..window.clone()
+        };
+        perf.parse(&packet(TcpFlags::SYN_ACK, 1111, 2, 0, syn_ack), false)
+            .unwrap();
+        perf.parse(&packet(TcpFlags::ACK, 112, 3, 0, window.clone()), true)
+            .unwrap();
+
+        // the segment at 212 is received after 312
+        for (seq, timestamp) in [(112, 4), (312, 5), (212, 6)] {
+            perf.parse(
+                &packet(TcpFlags::PSH_ACK, seq, timestamp, 100, window.clone()),
+                true,
+            )
+            .unwrap();
+        }
+
+        // the server advertises a zero window for 3 seconds
+        let zero_window = MetaPacketTcpHeader {
+            sack: Some(vec![0; 8]),
+            ..Default::default()
+        };
+        perf.parse(&packet(TcpFlags::ACK, 1112, 7, 0, zero_window), false)
+            .unwrap();
+        perf.parse(&packet(TcpFlags::ACK, 1112, 10, 0, window.clone()), false)
+            .unwrap();
+
+        let stats = perf.copy_and_reset_data(false).tcp;
+        let (client, server) = (&stats.counts_peers[0], &stats.counts_peers[1]);
+        assert_eq!(client.out_of_order_count, 1);
+        assert_eq!((client.mss, client.win_scale), (1460, 7));
+        assert_eq!(server.zero_win_count, 1);
+        assert_eq!(server.zero_win_duration, 3_000_000);
+        assert_eq!(server.sack_count, 1);
+        assert_eq!((server.mss, server.win_scale), (1400, 5));
+        assert!(stats.sack_permitted);
+
+        // options are reported in the following periods
+        let stats = perf.copy_and_reset_data(true).tcp;
+        assert_eq!(stats.counts_peers[0].mss, 1400);
+        assert_eq!(stats.counts_peers[0].zero_win_count, 0);
+    }
+
     fn update_test_helper<P: AsRef<Path>>(file: P, check_seq_list: bool) -> String {
         let mut output = String::new();
 
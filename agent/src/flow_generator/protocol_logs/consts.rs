@@ -439,10 +439,13 @@ use std::time::Duration;
 pub const PORT: u16 = 53;
 
 pub const DNS_TCP_PAYLOAD_OFFSET: usize = 2;
+// content type of DNS-over-HTTPS
+pub const DNS_MESSAGE_CONTENT_TYPE: &[u8] = b"application/dns-message";
 
 pub const DNS_HEADER_SIZE: usize = 12;
 pub const DNS_HEADER_FLAGS_OFFSET: usize = 2;
 pub const DNS_HEADER_QR_MASK: u8 = 0x80;
+pub const DNS_HEADER_TC_MASK: u8 = 0x02;
 pub const DNS_HEADER_RESPCODE_MASK: u8 = 0x0f;
 pub const DNS_OPCODE_REQUEST: u8 = 0x00;
 pub const DNS_OPCODE_RESPONSE: u8 = 0x80;
@@ -467,6 +470,7 @@ pub const DNS_NAME_MAX_SIZE: usize = 255;
 pub const DNS_HEADER_QDCOUNT_OFFSET: usize = 4;
 pub const DNS_HEADER_ANCOUNT_OFFSET: usize = 6;
 pub const DNS_HEADER_NSCOUNT_OFFSET: usize = 8;
+pub const DNS_HEADER_ARCOUNT_OFFSET: usize = 10;
 pub const QUESTION_CLASS_OFFSET: usize = 2;
 pub const QUESTION_CLASS_TYPE_SIZE: usize = 4;
 pub const RR_CLASS_OFFSET: usize = 2;
@@ -480,6 +484,13 @@ pub const DNS_TYPE_WKS: u16 = 11;
 pub const DNS_TYPE_PTR: u16 = 12;
 pub const DNS_TYPE_AAAA: u16 = 28;
 pub const DNS_TYPE_DNAME: u16 = 39;
+pub const DNS_TYPE_OPT: u16 = 41;
 pub const DNS_TYPE_WKS_LENGTH: usize = 5;
 pub const DNS_TYPE_PTR_LENGTH: usize = 2;
 pub const DOMAIN_NAME_SPLIT: char = ';';
+// A/AAAA addresses or CNAME targets kept in attributes
+pub const DNS_ANSWER_RECORDS_MAX: usize = 16;
+pub const EDNS_OPTION_HEADER_SIZE: usize = 4;
+pub const EDNS_OPTION_CLIENT_SUBNET: u16 = 8;
+pub const EDNS_FAMILY_IPV4: u16 = 1;
+pub const EDNS_FAMILY_IPV6: u16 = 2;
@@ -0,0 +1,850 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Diameter base protocol over TCP or SCTP.
+// Reference: https://www.rfc-editor.org/rfc/rfc6733
+
+use std::str;
+
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{
+    set_captured_byte, swap_if, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType,
+};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+    utils::bytes::read_u32_be,
+};
+use public::l7_protocol::L7Protocol;
+
+const DIAMETER_VERSION: u8 = 1;
+const HEADER_LEN: usize = 20;
+const AVP_HEADER_LEN: usize = 8;
+const AVP_VENDOR_ID_LEN: usize = 4;
+// larger messages are possible but not expected for signalling
+const MAX_MESSAGE_LEN: usize = 65535;
+
+const FLAG_REQUEST: u8 = 0x80;
+const FLAG_ERROR: u8 = 0x20;
+const FLAG_RETRANSMITTED: u8 = 0x10;
+const FLAG_RESERVED: u8 = 0x0f;
+const AVP_FLAG_VENDOR: u8 = 0x80;
+
+const AVP_AUTH_APPLICATION_ID: u32 = 258;
+const AVP_ACCT_APPLICATION_ID: u32 = 259;
+const AVP_SESSION_ID: u32 = 263;
+const AVP_ORIGIN_HOST: u32 = 264;
+const AVP_RESULT_CODE: u32 = 268;
+const AVP_ERROR_MESSAGE: u32 = 281;
+const AVP_DESTINATION_REALM: u32 = 283;
+const AVP_DESTINATION_HOST: u32 = 293;
+const AVP_ORIGIN_REALM: u32 = 296;
+const AVP_EXPERIMENTAL_RESULT: u32 = 297;
+const AVP_EXPERIMENTAL_RESULT_CODE: u32 = 298;
+
+const DIAMETER_UNABLE_TO_COMPLY: u32 = 5012;
+
+fn command_name(command_code: u32) -> Option<&'static str> {
+    let name = match command_code {
+        257 => "Capabilities-Exchange",
+        258 => "Re-Auth",
+        271 => "Accounting",
+        272 => "Credit-Control",
+        274 => "Abort-Session",
+        275 => "Session-Termination",
+        280 => "Device-Watchdog",
+        282 => "Disconnect-Peer",
+        // 3GPP interfaces
+        265 => "AA",
+        268 => "Diameter-EAP",
+        300 => "User-Authorization",
+        301 => "Server-Assignment",
+        302 => "Location-Info",
+        303 => "Multimedia-Auth",
+        304 => "Registration-Termination",
+        305 => "Push-Profile",
+        306 => "User-Data",
+        307 => "Profile-Update",
+        308 => "Subscribe-Notifications",
+        309 => "Push-Notification",
+        316 => "Update-Location",
+        317 => "Cancel-Location",
+        318 => "Authentication-Information",
+        319 => "Insert-Subscriber-Data",
+        320 => "Delete-Subscriber-Data",
+        321 => "Purge-UE",
+        322 => "Reset",
+        323 => "Notify",
+        8388620 => "Provide-Location",
+        8388622 => "Location-Report",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn application_name(application_id: u32) -> Option<&'static str> {
+    let name = match application_id {
+        0 => "Diameter Common Messages",
+        3 => "Diameter Base Accounting",
+        4 => "Diameter Credit Control",
+        16777216 => "3GPP Cx",
+        16777217 => "3GPP Sh",
+        16777236 => "3GPP Rx",
+        16777238 => "3GPP Gx",
+        16777251 => "3GPP S6a",
+        16777252 => "3GPP S13",
+        16777255 => "3GPP SLg",
+        16777267 => "3GPP S9",
+        16777272 => "3GPP S6b",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn result_code_name(result_code: u32) -> &'static str {
+    match result_code {
+        1001 => "DIAMETER_MULTI_ROUND_AUTH",
+        2001 => "DIAMETER_SUCCESS",
+        2002 => "DIAMETER_LIMITED_SUCCESS",
+        3001 => "DIAMETER_COMMAND_UNSUPPORTED",
+        3002 => "DIAMETER_UNABLE_TO_DELIVER",
+        3003 => "DIAMETER_REALM_NOT_SERVED",
+        3004 => "DIAMETER_TOO_BUSY",
+        3005 => "DIAMETER_LOOP_DETECTED",
+        3006 => "DIAMETER_REDIRECT_INDICATION",
+        3007 => "DIAMETER_APPLICATION_UNSUPPORTED",
+        3008 => "DIAMETER_INVALID_HDR_BITS",
+        3009 => "DIAMETER_INVALID_AVP_BITS",
+        3010 => "DIAMETER_UNKNOWN_PEER",
+        4001 => "DIAMETER_AUTHENTICATION_REJECTED",
+        4002 => "DIAMETER_OUT_OF_SPACE",
+        4003 => "ELECTION_LOST",
+        4010 => "DIAMETER_END_USER_SERVICE_DENIED",
+        4011 => "DIAMETER_CREDIT_CONTROL_NOT_APPLICABLE",
+        4012 => "DIAMETER_CREDIT_LIMIT_REACHED",
+        5001 => "DIAMETER_AVP_UNSUPPORTED",
+        5002 => "DIAMETER_UNKNOWN_SESSION_ID",
+        5003 => "DIAMETER_AUTHORIZATION_REJECTED",
+        5004 => "DIAMETER_INVALID_AVP_VALUE",
+        5005 => "DIAMETER_MISSING_AVP",
+        5006 => "DIAMETER_RESOURCES_EXCEEDED",
+        5007 => "DIAMETER_CONTRADICTING_AVPS",
+        5008 => "DIAMETER_AVP_NOT_ALLOWED",
+        5009 => "DIAMETER_AVP_OCCURS_TOO_MANY_TIMES",
+        5010 => "DIAMETER_NO_COMMON_APPLICATION",
+        5011 => "DIAMETER_UNSUPPORTED_VERSION",
+        DIAMETER_UNABLE_TO_COMPLY => "DIAMETER_UNABLE_TO_COMPLY",
+        5013 => "DIAMETER_INVALID_BIT_IN_HEADER",
+        5014 => "DIAMETER_INVALID_AVP_LENGTH",
+        5015 => "DIAMETER_INVALID_MESSAGE_LENGTH",
+        5016 => "DIAMETER_INVALID_AVP_BIT_COMBO",
+        5017 => "DIAMETER_NO_COMMON_SECURITY",
+        5030 => "DIAMETER_USER_UNKNOWN",
+        5031 => "DIAMETER_RATING_FAILED",
+        _ => "",
+    }
+}
+
+// 1xxx and 2xxx are successful, 3xxx are protocol errors raised by agents and servers,
+// 4xxx and 5xxx are failures caused by the request in most cases
+fn result_code_status(result_code: u32) -> L7ResponseStatus {
+    match result_code {
+        3000..=3999 | DIAMETER_UNABLE_TO_COMPLY => L7ResponseStatus::ServerError,
+        4000..=5999 => L7ResponseStatus::ClientError,
+        _ => L7ResponseStatus::Ok,
+    }
+}
+
+struct Header {
+    flags: u8,
+    command_code: u32,
+    application_id: u32,
+    hop_by_hop_id: u32,
+    end_to_end_id: u32,
+    len: usize,
+}
+
+impl Header {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() < HEADER_LEN || payload[0] != DIAMETER_VERSION {
+            return None;
+        }
+        let len = read_u32_be(payload) as usize & 0xffffff;
+        let flags = payload[4];
+        // the message length is always a multiple of 4
+        if len < HEADER_LEN || len > MAX_MESSAGE_LEN || len % 4 != 0 || flags & FLAG_RESERVED != 0 {
+            return None;
+        }
+        Some(Self {
+            flags,
+            command_code: read_u32_be(&payload[4..]) & 0xffffff,
+            application_id: read_u32_be(&payload[8..]),
+            hop_by_hop_id: read_u32_be(&payload[12..]),
+            end_to_end_id: read_u32_be(&payload[16..]),
+            len,
+        })
+    }
+
+    fn is_request(&self) -> bool {
+        self.flags & FLAG_REQUEST != 0
+    }
+}
+
+struct Avp<'a> {
+    code: u32,
+    data: &'a [u8],
+}
+
+// iterates AVPs, stops at the first malformed or truncated one
+struct AvpIter<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Iterator for AvpIter<'a> {
+    type Item = Avp<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.payload.len() < AVP_HEADER_LEN {
+            return None;
+        }
+        let code = read_u32_be(self.payload);
+        let flags = self.payload[4];
+        let len = read_u32_be(&self.payload[4..]) as usize & 0xffffff;
+        let data_offset = if flags & AVP_FLAG_VENDOR != 0 {
+            AVP_HEADER_LEN + AVP_VENDOR_ID_LEN
+        } else {
+            AVP_HEADER_LEN
+        };
+        if len < data_offset || len > self.payload.len() {
+            self.payload = &[];
+            return None;
+        }
+        let data = &self.payload[data_offset..len];
+        // AVPs are padded to 4 bytes
+        self.payload = &self.payload[((len + 3) & !3).min(self.payload.len())..];
+        Some(Avp { code, data })
+    }
+}
+
+fn avps(payload: &[u8]) -> AvpIter<'_> {
+    AvpIter { payload }
+}
+
+fn avp_u32(data: &[u8]) -> Option<u32> {
+    (data.len() == 4).then(|| read_u32_be(data))
+}
+
+fn avp_string(data: &[u8]) -> String {
+    str::from_utf8(data).unwrap_or_default().to_owned()
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct DiameterInfo {
+    #[serde(skip)]
+    msg_type: LogMessageType,
+    #[serde(skip)]
+    is_tls: bool,
+    rrt: u64,
+
+    pub hop_by_hop_id: u32,
+    pub end_to_end_id: u32,
+    pub command_code: u32,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub command: &'static str,
+    pub application_id: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub retransmitted: bool,
+
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    pub session_id: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    pub origin_host: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub origin_realm: String,
+    #[serde(rename = "endpoint", skip_serializing_if = "value_is_default")]
+    pub destination_host: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub destination_realm: String,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub result_code: Option<u32>,
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    #[serde(rename = "request_length", skip_serializing_if = "Option::is_none")]
+    pub req_len: Option<u32>,
+    #[serde(rename = "response_length", skip_serializing_if = "Option::is_none")]
+    pub resp_len: Option<u32>,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl DiameterInfo {
+    fn new(header: &Header, payload: &[u8]) -> Self {
+        let mut info = DiameterInfo {
+            hop_by_hop_id: header.hop_by_hop_id,
+            end_to_end_id: header.end_to_end_id,
+            command_code: header.command_code,
+            command: command_name(header.command_code).unwrap_or_default(),
+            application_id: header.application_id,
+            retransmitted: header.flags & FLAG_RETRANSMITTED != 0,
+            ..Default::default()
+        };
+        if header.is_request() {
+            info.msg_type = LogMessageType::Request;
+            info.req_len = Some(header.len as u32);
+        } else {
+            info.msg_type = LogMessageType::Response;
+            info.resp_len = Some(header.len as u32);
+        }
+
+        let mut error_message = None;
+        for avp in avps(&payload[HEADER_LEN..header.len.min(payload.len())]) {
+            match avp.code {
+                AVP_SESSION_ID => info.session_id = avp_string(avp.data),
+                AVP_ORIGIN_HOST => info.origin_host = avp_string(avp.data),
+                AVP_ORIGIN_REALM => info.origin_realm = avp_string(avp.data),
+                AVP_DESTINATION_HOST => info.destination_host = avp_string(avp.data),
+                AVP_DESTINATION_REALM => info.destination_realm = avp_string(avp.data),
+                // relay agents use application id 0xffffffff in the header
+                AVP_AUTH_APPLICATION_ID | AVP_ACCT_APPLICATION_ID if info.application_id == 0 => {
+                    info.application_id = avp_u32(avp.data).unwrap_or_default();
+                }
+                AVP_RESULT_CODE => info.result_code = avp_u32(avp.data),
+                AVP_EXPERIMENTAL_RESULT if info.result_code.is_none() => {
+                    info.result_code = avps(avp.data)
+                        .find(|a| a.code == AVP_EXPERIMENTAL_RESULT_CODE)
+                        .and_then(|a| avp_u32(a.data));
+                }
+                AVP_ERROR_MESSAGE => error_message = Some(avp_string(avp.data)),
+                _ => (),
+            }
+        }
+
+        if info.msg_type == LogMessageType::Response {
+            match info.result_code {
+                Some(code) => {
+                    info.status = result_code_status(code);
+                    info.exception =
+                        error_message.unwrap_or_else(|| result_code_name(code).to_owned());
+                }
+                // answers with the E bit set always carry a protocol error
+                None if header.flags & FLAG_ERROR != 0 => {
+                    info.status = L7ResponseStatus::ServerError;
+                    info.exception = error_message.unwrap_or_default();
+                }
+                None => info.status = L7ResponseStatus::Ok,
+            }
+            if info.status == L7ResponseStatus::Ok {
+                info.exception.clear();
+            }
+        }
+        info
+    }
+
+    fn merge(&mut self, other: &mut Self) {
+        swap_if!(self, resp_len, is_none, other);
+        swap_if!(self, result_code, is_none, other);
+        swap_if!(self, exception, is_empty, other);
+        swap_if!(self, session_id, is_empty, other);
+        if other.status != L7ResponseStatus::default() {
+            self.status = other.status;
+        }
+        if other.captured_request_byte > 0 {
+            self.captured_request_byte = other.captured_request_byte;
+        }
+        if other.captured_response_byte > 0 {
+            self.captured_response_byte = other.captured_response_byte;
+        }
+        if other.is_on_blacklist {
+            self.is_on_blacklist = other.is_on_blacklist;
+        }
+    }
+
+    fn endpoint(&self) -> &str {
+        if !self.destination_host.is_empty() {
+            &self.destination_host
+        } else {
+            &self.destination_realm
+        }
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::Diameter) {
+            self.is_on_blacklist = t.request_type.is_on_blacklist(self.command)
+                || t.request_domain.is_on_blacklist(&self.origin_host)
+                || t.endpoint.is_on_blacklist(self.endpoint())
+                || t.request_resource.is_on_blacklist(&self.session_id);
+        }
+    }
+}
+
+impl L7ProtocolInfoInterface for DiameterInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.hop_by_hop_id)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::DiameterInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::Diameter,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    fn get_endpoint(&self) -> Option<String> {
+        let endpoint = self.endpoint();
+        (!endpoint.is_empty()).then(|| endpoint.to_owned())
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.origin_host.clone()
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.session_id.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+
+    fn get_payload_size(&self) -> Option<u32> {
+        match self.msg_type {
+            LogMessageType::Request => self.req_len,
+            _ => self.resp_len,
+        }
+    }
+}
+
+impl From<DiameterInfo> for L7ProtocolSendLog {
+    fn from(f: DiameterInfo) -> Self {
+        let flags = if f.is_tls {
+            EbpfFlags::TLS.bits()
+        } else {
+            EbpfFlags::NONE.bits()
+        };
+        let endpoint = f.endpoint().to_owned();
+        let mut attributes = vec![
+            KeyVal {
+                key: "diameter_command_code".to_string(),
+                val: f.command_code.to_string(),
+            },
+            KeyVal {
+                key: "diameter_application_id".to_string(),
+                val: f.application_id.to_string(),
+            },
+        ];
+        if let Some(name) = application_name(f.application_id) {
+            attributes.push(KeyVal {
+                key: "diameter_application".to_string(),
+                val: name.to_string(),
+            });
+        }
+        attributes.push(KeyVal {
+            key: "diameter_end_to_end_id".to_string(),
+            val: f.end_to_end_id.to_string(),
+        });
+        for (key, val) in [
+            ("diameter_origin_realm", f.origin_realm),
+            ("diameter_destination_realm", f.destination_realm),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        if f.retransmitted {
+            attributes.push(KeyVal {
+                key: "diameter_retransmitted".to_string(),
+                val: "true".to_string(),
+            });
+        }
+
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req_len: f.req_len,
+            resp_len: f.resp_len,
+            req: L7Request {
+                req_type: f.command.to_owned(),
+                domain: f.origin_host,
+                resource: f.session_id,
+                endpoint,
+            },
+            resp: L7Response {
+                status: f.status,
+                code: f.result_code.map(|c| c as i32),
+                exception: f.exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.hop_by_hop_id),
+                attributes: Some(attributes),
+                ..Default::default()
+            }),
+            flags,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DiameterLog {
+    perf_stats: Option<L7PerfStats>,
+    last_is_on_blacklist: bool,
+}
+
+impl L7ProtocolParserInterface for DiameterLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol()
+            || (param.l4_protocol != IpProtocol::TCP && param.l4_protocol != IpProtocol::SCTP)
+        {
+            return false;
+        }
+        let Some(header) = Header::parse(payload) else {
+            return false;
+        };
+        if !header.is_request() || command_name(header.command_code).is_none() {
+            return false;
+        }
+        // every request carries Origin-Host, and AVPs must be well formed up to it
+        avps(&payload[HEADER_LEN..header.len.min(payload.len())])
+            .any(|avp| avp.code == AVP_ORIGIN_HOST)
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+
+        // a TCP segment may carry several messages
+        let mut infos = vec![];
+        let mut payload = payload;
+        while let Some(header) = Header::parse(payload) {
+            infos.push(DiameterInfo::new(&header, payload));
+            payload = &payload[header.len.min(payload.len())..];
+        }
+        if infos.is_empty() {
+            return Err(Error::L7ProtocolUnknown);
+        }
+
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match info.msg_type {
+                    LogMessageType::Request => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    _ => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    }
+                }
+                match info.status {
+                    L7ResponseStatus::ClientError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                    L7ResponseStatus::ServerError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    }
+                    _ => (),
+                }
+                info.cal_rrt(param).map(|rrt| {
+                    info.rrt = rrt;
+                    self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                });
+            }
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+
+        if !param.parse_log {
+            Ok(L7ParseResult::None)
+        } else if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::DiameterInfo(
+                infos.remove(0),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                infos
+                    .into_iter()
+                    .map(L7ProtocolInfo::DiameterInfo)
+                    .collect(),
+            ))
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::Diameter
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn parsable_on_sctp(&self) -> bool {
+        true
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn avp(code: u32, data: &[u8]) -> Vec<u8> {
+        let len = AVP_HEADER_LEN + data.len();
+        let mut buf = code.to_be_bytes().to_vec();
+        buf.extend_from_slice(&(0x40000000 | len as u32).to_be_bytes());
+        buf.extend_from_slice(data);
+        buf.resize((len + 3) & !3, 0);
+        buf
+    }
+
+    fn message(flags: u8, command_code: u32, hop_by_hop_id: u32, avps: &[Vec<u8>]) -> Vec<u8> {
+        let body = avps.concat();
+        let mut buf = vec![DIAMETER_VERSION];
+        buf.extend_from_slice(&((HEADER_LEN + body.len()) as u32).to_be_bytes()[1..]);
+        buf.push(flags);
+        buf.extend_from_slice(&command_code.to_be_bytes()[1..]);
+        buf.extend_from_slice(&4u32.to_be_bytes());
+        buf.extend_from_slice(&hop_by_hop_id.to_be_bytes());
+        buf.extend_from_slice(&0x1234u32.to_be_bytes());
+        buf.extend_from_slice(&body);
+        buf
+    }
+
+    fn parse(
+        diameter: &mut DiameterLog,
+        cache: &Rc<RefCell<L7PerfCache>>,
+        payload: &[u8],
+        direction: PacketDirection,
+        proto: IpProtocol,
+    ) -> Vec<DiameterInfo> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = direction;
+        packet.lookup_key.proto = proto;
+        let param = &mut ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        if direction == PacketDirection::ClientToServer {
+            assert!(diameter.check_payload(payload, param));
+        }
+        let infos = match diameter.parse_payload(payload, param).unwrap() {
+            L7ParseResult::Single(i) => vec![i],
+            L7ParseResult::Multi(m) => m,
+            L7ParseResult::None => vec![],
+        };
+        infos
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::DiameterInfo(i) => i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn credit_control() {
+        let mut diameter = DiameterLog::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+
+        let ccr = message(
+            FLAG_REQUEST | 0x40,
+            272,
+            0xabcd,
+            &[
+                avp(AVP_SESSION_ID, b"pcef.example.com;1;2"),
+                avp(AVP_ORIGIN_HOST, b"pcef.example.com"),
+                avp(AVP_ORIGIN_REALM, b"example.com"),
+                avp(AVP_DESTINATION_REALM, b"ocs.example.com"),
+            ],
+        );
+        let infos = parse(
+            &mut diameter,
+            &cache,
+            &ccr,
+            PacketDirection::ClientToServer,
+            IpProtocol::SCTP,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].session_id(), Some(0xabcd));
+        assert_eq!(infos[0].command, "Credit-Control");
+        assert_eq!(infos[0].session_id, "pcef.example.com;1;2");
+        assert_eq!(infos[0].get_request_domain(), "pcef.example.com");
+        assert_eq!(infos[0].get_endpoint(), Some("ocs.example.com".to_owned()));
+        assert_eq!(infos[0].req_len, Some(ccr.len() as u32));
+
+        let mut experimental_result = avp(266, &10415u32.to_be_bytes());
+        experimental_result.extend(avp(AVP_EXPERIMENTAL_RESULT_CODE, &5030u32.to_be_bytes()));
+        let cca = message(
+            0x40,
+            272,
+            0xabcd,
+            &[
+                avp(AVP_SESSION_ID, b"pcef.example.com;1;2"),
+                avp(AVP_ORIGIN_HOST, b"ocs.example.com"),
+                avp(AVP_EXPERIMENTAL_RESULT, &experimental_result),
+            ],
+        );
+        let infos = parse(
+            &mut diameter,
+            &cache,
+            &cca,
+            PacketDirection::ServerToClient,
+            IpProtocol::SCTP,
+        );
+        assert_eq!(infos[0].result_code, Some(5030));
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].exception, "DIAMETER_USER_UNKNOWN");
+
+        let perf_stats = diameter.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 1);
+        assert_eq!(perf_stats.response_count, 1);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+
+    #[test]
+    fn watchdog_pipelined() {
+        let mut diameter = DiameterLog::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+
+        let dwr = [
+            message(
+                FLAG_REQUEST,
+                280,
+                1,
+                &[avp(AVP_ORIGIN_HOST, b"a.example.com")],
+            ),
+            message(
+                FLAG_REQUEST,
+                280,
+                2,
+                &[avp(AVP_ORIGIN_HOST, b"a.example.com")],
+            ),
+        ]
+        .concat();
+        let infos = parse(
+            &mut diameter,
+            &cache,
+            &dwr,
+            PacketDirection::ClientToServer,
+            IpProtocol::TCP,
+        );
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[1].session_id(), Some(2));
+        assert_eq!(infos[1].command, "Device-Watchdog");
+
+        let dwa = message(
+            0,
+            280,
+            1,
+            &[
+                avp(AVP_ORIGIN_HOST, b"b.example.com"),
+                avp(AVP_RESULT_CODE, &2001u32.to_be_bytes()),
+            ],
+        );
+        let infos = parse(
+            &mut diameter,
+            &cache,
+            &dwa,
+            PacketDirection::ServerToClient,
+            IpProtocol::TCP,
+        );
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+        assert_eq!(infos[0].exception, "");
+
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        let param = ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        // not on UDP, and answers or unknown commands are not accepted
+        assert!(!diameter.check_payload(&dwr, &param));
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let param = ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        assert!(!diameter.check_payload(&dwa, &param));
+        assert!(!diameter.check_payload(
+            &message(FLAG_REQUEST, 999, 1, &[avp(AVP_ORIGIN_HOST, b"a")]),
+            &param
+        ));
+    }
+}
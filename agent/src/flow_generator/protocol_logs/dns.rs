@@ -14,9 +14,12 @@
  * limitations under the License.
  */
 
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use std::net::IpAddr;
+
 use serde::Serialize;
 
-use super::pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response};
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
 use super::{consts::*, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
 use crate::common::flow::L7PerfStats;
 use crate::common::l7_protocol_log::L7ParseResult;
@@ -67,13 +70,28 @@ pub struct DnsInfo {
     is_tls: bool,
     rrt: u64,
 
+    // stream of DNS-over-HTTPS over HTTP/2, transaction id is usually 0 in DoH
+    #[serde(skip)]
+    stream_id: Option<u32>,
+
+    // answer section of response, deduplicated and capped by DNS_ANSWER_RECORDS_MAX
+    #[serde(skip)]
+    answer_addresses: Vec<String>,
+    #[serde(skip)]
+    answer_cnames: Vec<String>,
+    // EDNS Client Subnet, see https://www.rfc-editor.org/rfc/rfc7871
+    #[serde(skip)]
+    client_subnet: Option<String>,
+    #[serde(skip)]
+    truncated: bool,
+
     #[serde(skip)]
     is_on_blacklist: bool,
 }
 
 impl L7ProtocolInfoInterface for DnsInfo {
     fn session_id(&self) -> Option<u32> {
-        Some(self.trans_id as u32)
+        self.stream_id.or(Some(self.trans_id as u32))
     }
 
     fn merge_log(
@@ -117,6 +135,29 @@ impl DnsInfo {
     const QUERY_IPV4: u16 = 1;
     const QUERY_IPV6: u16 = 28;
 
+    // parse DNS message carried by HTTP body or url, see https://www.rfc-editor.org/rfc/rfc8484
+    pub(crate) fn from_doh_message(payload: &[u8], param: &ParseParam) -> Result<Self> {
+        let mut info = DnsInfo::default();
+        DnsLog::default().decode_payload(payload, param, &mut info)?;
+        info.is_tls = param.is_tls();
+        if let Some(config) = param.parse_config {
+            info.set_is_on_blacklist(config);
+        }
+        Ok(info)
+    }
+
+    // DNS message in `dns` parameter of GET request, encoded by base64url without padding
+    pub(crate) fn doh_get_message(path: &str) -> Option<Vec<u8>> {
+        let (_, query) = path.split_once('?')?;
+        let value = query.split('&').find_map(|kv| kv.strip_prefix("dns="))?;
+        BASE64_URL_SAFE_NO_PAD.decode(value).ok()
+    }
+
+    pub(crate) fn set_doh_session(&mut self, stream_id: Option<u32>, rrt: u64) {
+        self.stream_id = stream_id;
+        self.rrt = rrt;
+    }
+
     pub fn merge(&mut self, other: &mut Self) {
         std::mem::swap(&mut self.answers, &mut other.answers);
         if other.status != L7ResponseStatus::default() {
@@ -129,6 +170,12 @@ impl DnsInfo {
             }
         }
         self.captured_response_byte = other.captured_response_byte;
+        std::mem::swap(&mut self.answer_addresses, &mut other.answer_addresses);
+        std::mem::swap(&mut self.answer_cnames, &mut other.answer_cnames);
+        if self.client_subnet.is_none() {
+            self.client_subnet = other.client_subnet.take();
+        }
+        self.truncated |= other.truncated;
         if other.is_on_blacklist {
             self.is_on_blacklist = other.is_on_blacklist;
         }
@@ -155,6 +202,46 @@ impl DnsInfo {
         }
     }
 
+    fn add_answer_record(&mut self, domain_type: u16, record: String) {
+        let records = if domain_type == DNS_TYPE_CNAME {
+            &mut self.answer_cnames
+        } else {
+            &mut self.answer_addresses
+        };
+        if records.len() < DNS_ANSWER_RECORDS_MAX && !records.contains(&record) {
+            records.push(record);
+        }
+    }
+
+    fn attributes(&mut self) -> Vec<KeyVal> {
+        let mut attributes = vec![];
+        if !self.answer_addresses.is_empty() {
+            attributes.push(KeyVal {
+                key: "dns_answer_addresses".to_string(),
+                val: self.answer_addresses.join(","),
+            });
+        }
+        if !self.answer_cnames.is_empty() {
+            attributes.push(KeyVal {
+                key: "dns_answer_cnames".to_string(),
+                val: self.answer_cnames.join(","),
+            });
+        }
+        if let Some(subnet) = self.client_subnet.take() {
+            attributes.push(KeyVal {
+                key: "dns_client_subnet".to_string(),
+                val: subnet,
+            });
+        }
+        if self.truncated {
+            attributes.push(KeyVal {
+                key: "dns_truncated".to_string(),
+                val: "true".to_string(),
+            });
+        }
+        attributes
+    }
+
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::DNS) {
             self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.query_name)
@@ -166,8 +253,9 @@ impl DnsInfo {
 }
 
 impl From<DnsInfo> for L7ProtocolSendLog {
-    fn from(f: DnsInfo) -> Self {
+    fn from(mut f: DnsInfo) -> Self {
         let req_type = String::from(f.get_domain_str());
+        let attributes = f.attributes();
         let flags = if f.is_tls {
             EbpfFlags::TLS.bits()
         } else {
@@ -195,6 +283,11 @@ impl From<DnsInfo> for L7ProtocolSendLog {
             },
             ext_info: Some(ExtendedInfo {
                 request_id: Some(f.trans_id as u32),
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
                 ..Default::default()
             }),
             flags,
@@ -369,6 +462,7 @@ impl DnsLog {
         &mut self,
         payload: &[u8],
         g_offset: usize,
+        in_answer: bool,
         info: &mut DnsInfo,
     ) -> Result<usize> {
         let (_, offset) = self.decode_name(payload, g_offset)?;
@@ -388,11 +482,92 @@ impl DnsLog {
         let data_length = read_u16_be(&payload[offset + RR_DATALENGTH_OFFSET..]) as usize;
         if data_length != 0 {
             self.decode_rdata(payload, offset + RR_RDATA_OFFSET, data_length, info)?;
+            if in_answer {
+                self.decode_answer_record(payload, offset + RR_RDATA_OFFSET, data_length, info);
+            }
         }
 
         Ok(offset + RR_RDATA_OFFSET + data_length)
     }
 
+    // rdata is already checked by decode_rdata
+    fn decode_answer_record(
+        &self,
+        payload: &[u8],
+        g_offset: usize,
+        data_length: usize,
+        info: &mut DnsInfo,
+    ) {
+        let record = match info.domain_type {
+            DNS_TYPE_A | DNS_TYPE_AAAA => {
+                parse_ip_slice(&payload[g_offset..g_offset + data_length]).map(|ip| ip.to_string())
+            }
+            DNS_TYPE_CNAME => self
+                .decode_name(payload, g_offset)
+                .ok()
+                .map(|(name, _)| name),
+            _ => None,
+        };
+        if let Some(record) = record {
+            info.add_answer_record(info.domain_type, record);
+        }
+    }
+
+    // only the OPT pseudo-record is concerned, see https://www.rfc-editor.org/rfc/rfc6891
+    fn decode_additional_record(
+        &self,
+        payload: &[u8],
+        g_offset: usize,
+        info: &mut DnsInfo,
+    ) -> Result<usize> {
+        let (_, offset) = self.decode_name(payload, g_offset)?;
+        let rdata_offset = offset + RR_RDATA_OFFSET;
+        if payload.len() < rdata_offset {
+            let err_msg = format!("additional record length error: {}", payload.len());
+            return Err(Error::DNSLogParseFailed(err_msg));
+        }
+        let data_length = read_u16_be(&payload[offset + RR_DATALENGTH_OFFSET..]) as usize;
+        if payload.len() < rdata_offset + data_length {
+            let err_msg = format!("additional record length error: {}", payload.len());
+            return Err(Error::DNSLogParseFailed(err_msg));
+        }
+        if read_u16_be(&payload[offset..]) == DNS_TYPE_OPT {
+            Self::decode_edns_options(&payload[rdata_offset..rdata_offset + data_length], info);
+        }
+        Ok(rdata_offset + data_length)
+    }
+
+    fn decode_edns_options(mut options: &[u8], info: &mut DnsInfo) {
+        while options.len() >= EDNS_OPTION_HEADER_SIZE {
+            let code = read_u16_be(options);
+            let end = EDNS_OPTION_HEADER_SIZE + read_u16_be(&options[2..]) as usize;
+            let Some(data) = options.get(EDNS_OPTION_HEADER_SIZE..end) else {
+                return;
+            };
+            // FAMILY, SOURCE PREFIX-LENGTH, SCOPE PREFIX-LENGTH and truncated ADDRESS
+            if code == EDNS_OPTION_CLIENT_SUBNET && data.len() >= 4 {
+                let address = &data[4..];
+                let ip = match read_u16_be(data) {
+                    EDNS_FAMILY_IPV4 if address.len() <= IPV4_ADDR_LEN => {
+                        let mut octets = [0u8; IPV4_ADDR_LEN];
+                        octets[..address.len()].copy_from_slice(address);
+                        Some(IpAddr::from(octets))
+                    }
+                    EDNS_FAMILY_IPV6 if address.len() <= IPV6_ADDR_LEN => {
+                        let mut octets = [0u8; IPV6_ADDR_LEN];
+                        octets[..address.len()].copy_from_slice(address);
+                        Some(IpAddr::from(octets))
+                    }
+                    _ => None,
+                };
+                if let Some(ip) = ip {
+                    info.client_subnet = Some(format!("{}/{}", ip, data[2]));
+                }
+            }
+            options = &options[end..];
+        }
+    }
+
     fn decode_rdata(
         &mut self,
         payload: &[u8],
@@ -504,6 +679,8 @@ impl DnsLog {
         let qd_count = read_u16_be(&payload[DNS_HEADER_QDCOUNT_OFFSET..]);
         let an_count = read_u16_be(&payload[DNS_HEADER_ANCOUNT_OFFSET..]);
         let ns_count = read_u16_be(&payload[DNS_HEADER_NSCOUNT_OFFSET..]);
+        let ar_count = read_u16_be(&payload[DNS_HEADER_ARCOUNT_OFFSET..]);
+        info.truncated = payload[DNS_HEADER_FLAGS_OFFSET] & DNS_HEADER_TC_MASK != 0;
 
         let mut g_offset = DNS_HEADER_SIZE;
         for _i in 0..qd_count {
@@ -514,11 +691,11 @@ impl DnsLog {
             info.query_type = 1;
 
             for _i in 0..an_count {
-                g_offset = self.decode_resource_record(payload, g_offset, info)?;
+                g_offset = self.decode_resource_record(payload, g_offset, true, info)?;
             }
 
             for _i in 0..ns_count {
-                g_offset = self.decode_resource_record(payload, g_offset, info)?;
+                g_offset = self.decode_resource_record(payload, g_offset, false, info)?;
             }
 
             let mut is_unconcerned = false;
@@ -532,6 +709,16 @@ impl DnsLog {
             }
             info.msg_type = LogMessageType::Response;
         }
+
+        // records of additional section are skipped in requests with answers or authorities
+        if info.msg_type == LogMessageType::Response || (an_count == 0 && ns_count == 0) {
+            for _i in 0..ar_count {
+                match self.decode_additional_record(payload, g_offset, info) {
+                    Ok(offset) => g_offset = offset,
+                    Err(_) => break,
+                }
+            }
+        }
         set_captured_byte!(info, param);
 
         Ok(())
@@ -659,6 +846,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn answer_records_and_edns() {
+        let mut payload = vec![
+            0x12, 0x34, 0x83, 0x80, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01,
+        ];
+        // question www.example.com A
+        payload.extend_from_slice(b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+        // www.example.com CNAME cdn.example.net
+        payload.extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x00\x3c\x00\x11");
+        payload.extend_from_slice(b"\x03cdn\x07example\x03net\x00");
+        // cdn.example.net A, with a duplicated address
+        for address in [[1, 2, 3, 4], [5, 6, 7, 8], [1, 2, 3, 4]] {
+            payload.extend_from_slice(b"\xc0\x2d\x00\x01\x00\x01\x00\x00\x00\x3c\x00\x04");
+            payload.extend_from_slice(&address);
+        }
+        // OPT with client subnet 10.0.1.0/24
+        payload.extend_from_slice(b"\x00\x00\x29\x10\x00\x00\x00\x00\x00\x00\x0b");
+        payload.extend_from_slice(b"\x00\x08\x00\x07\x00\x01\x18\x00\x0a\x00\x01");
+
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        packet.lookup_key.proto = IpProtocol::UDP;
+        let param = &ParseParam::new(
+            &packet,
+            Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY))),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let mut dns = DnsLog::default();
+        let L7ProtocolInfo::DnsInfo(info) =
+            dns.parse_payload(&payload, param).unwrap().unwrap_single()
+        else {
+            unreachable!()
+        };
+        assert_eq!(info.answers, "1.2.3.4;5.6.7.8;1.2.3.4");
+        assert!(info.truncated);
+
+        let log = L7ProtocolSendLog::from(info);
+        assert_eq!(
+            log.ext_info.unwrap().attributes.unwrap(),
+            vec![
+                KeyVal {
+                    key: "dns_answer_addresses".to_string(),
+                    val: "1.2.3.4,5.6.7.8".to_string(),
+                },
+                KeyVal {
+                    key: "dns_answer_cnames".to_string(),
+                    val: "cdn.example.net".to_string(),
+                },
+                KeyVal {
+                    key: "dns_client_subnet".to_string(),
+                    val: "10.0.1.0/24".to_string(),
+                },
+                KeyVal {
+                    key: "dns_truncated".to_string(),
+                    val: "true".to_string(),
+                },
+            ]
+        );
+    }
+
     #[test]
     fn check_perf() {
         let expected = vec![(
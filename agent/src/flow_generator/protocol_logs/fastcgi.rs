@@ -53,6 +53,10 @@ const FCGI_GET_VALUES_RESULT: u8 = 10;
 const FCGI_UNKNOWN_TYPE: u8 = 11;
 const FCGI_MAXTYPE: u8 = FCGI_UNKNOWN_TYPE;
 
+// FCGI_EndRequestBody: appStatus (4 bytes), protocolStatus (1 byte), reserved (3 bytes)
+const FCGI_END_REQUEST_PROTOCOL_STATUS_OFFSET: usize = 4;
+const FCGI_REQUEST_COMPLETE: u8 = 0;
+
 #[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct FastCGIInfo {
     version: u8,
@@ -370,6 +374,51 @@ impl FastCGILog {
             info.status = L7ResponseStatus::Ok;
         }
     }
+
+    // returns false if the record does not start with headers or the status is invalid
+    fn fill_from_stdout(
+        &mut self,
+        payload: &[u8],
+        config: Option<&L7LogDynamicConfig>,
+        info: &mut FastCGIInfo,
+    ) -> Result<bool> {
+        let mut is_hdr = false;
+        for i in parse_v1_headers(payload) {
+            let Some(col_index) = i.find(':') else {
+                break;
+            };
+
+            if col_index + 1 >= i.len() {
+                break;
+            }
+
+            is_hdr = true;
+            let key = &i[..col_index];
+            let value = &i[col_index + 1..];
+            info.on_param(
+                key.as_bytes(),
+                value.as_bytes(),
+                PacketDirection::ServerToClient,
+                config,
+            )?;
+
+            // Status: 404 Not Found
+            if key == "Status" {
+                if let Some(Ok(status_code)) = value.trim_start().get(..3).map(|c| c.parse::<u16>())
+                {
+                    info.status_code = Some(status_code as i32);
+                    self.set_status(status_code, info);
+                }
+                return Ok(info.status_code.is_some());
+            }
+        }
+
+        if is_hdr && info.status_code.is_none() {
+            info.status_code = Some(200);
+            self.set_status(200, info);
+        }
+        Ok(is_hdr)
+    }
 }
 
 struct RecordIter<'a> {
@@ -447,111 +496,108 @@ impl L7ProtocolParserInterface for FastCGILog {
             self.perf_stats = Some(L7PerfStats::default())
         }
 
-        let mut info = FastCGIInfo::default();
-
+        // records of different requests are interleaved if the connection is multiplexed
+        let mut infos: Vec<FastCGIInfo> = vec![];
         match param.direction {
             PacketDirection::ClientToServer => {
-                info.msg_type = LogMessageType::Request;
                 for (record, record_payload, off) in RecordIter::new(payload) {
-                    if record.record_type == FCGI_PARAMS {
-                        info.request_id = record.request_id as u32;
-                        info.version = record.version;
-                        if record.content_len > 0 {
-                            info.fill_from_param(record_payload, param.direction, config)?;
-                            info.seq_off = off as u32;
-                            break;
-                        }
+                    // params of one request may span several records, only the first one is parsed
+                    if record.record_type != FCGI_PARAMS
+                        || record.content_len == 0
+                        || infos
+                            .iter()
+                            .any(|i| i.request_id == record.request_id as u32)
+                    {
+                        continue;
+                    }
+                    let mut info = FastCGIInfo {
+                        version: record.version,
+                        request_id: record.request_id as u32,
+                        msg_type: LogMessageType::Request,
+                        seq_off: off as u32,
+                        ..Default::default()
+                    };
+                    info.fill_from_param(record_payload, param.direction, config)?;
+                    if !info.method.is_empty() {
+                        infos.push(info);
                     }
-                }
-
-                if info.method.is_empty() {
-                    return Err(Error::L7ProtocolUnknown);
                 }
             }
             PacketDirection::ServerToClient => {
-                info.msg_type = LogMessageType::Response;
-
-                'l: for (record, record_payload, off) in RecordIter::new(payload) {
-                    info.seq_off = off as u32;
-                    if record.record_type == FCGI_STDOUT {
-                        info.request_id = record.request_id as u32;
-                        info.version = record.version;
-                        let mut is_hdr = false;
-
-                        for i in parse_v1_headers(record_payload) {
-                            let Some(col_index) = i.find(':') else {
-                                break;
-                            };
-
-                            if col_index + 1 >= i.len() {
-                                break;
-                            }
-
-                            is_hdr = true;
-                            let key = &i[..col_index];
-                            let value = &i[col_index + 1..];
-                            info.on_param(
-                                key.as_bytes(),
-                                value.as_bytes(),
-                                PacketDirection::ServerToClient,
-                                config,
-                            )?;
-
-                            if key == "Status" {
-                                if value.len() < 4 {
-                                    break 'l;
-                                }
-                                if let Ok(status_code) = &value[1..4].parse::<u16>() {
-                                    info.status_code = Some(*status_code as i32);
-                                    self.set_status(*status_code, &mut info);
-                                };
-                                break 'l;
+                for (record, record_payload, off) in RecordIter::new(payload) {
+                    if infos
+                        .iter()
+                        .any(|i| i.request_id == record.request_id as u32)
+                    {
+                        continue;
+                    }
+                    let mut info = FastCGIInfo {
+                        version: record.version,
+                        request_id: record.request_id as u32,
+                        msg_type: LogMessageType::Response,
+                        seq_off: off as u32,
+                        ..Default::default()
+                    };
+                    match record.record_type {
+                        FCGI_STDOUT => {
+                            if !self.fill_from_stdout(record_payload, config, &mut info)? {
+                                continue;
                             }
                         }
-
-                        if !is_hdr {
-                            continue;
-                        }
-
-                        if info.status_code.is_none() {
-                            info.status_code = Some(200);
-                            self.set_status(200, &mut info);
+                        // the request is rejected without any output
+                        FCGI_END_REQUEST
+                            if record_payload.len() > FCGI_END_REQUEST_PROTOCOL_STATUS_OFFSET
+                                && record_payload[FCGI_END_REQUEST_PROTOCOL_STATUS_OFFSET]
+                                    != FCGI_REQUEST_COMPLETE =>
+                        {
+                            info.status = L7ResponseStatus::ServerError;
                         }
-                        break 'l;
+                        _ => continue,
                     }
-                }
-
-                if info.status_code.is_none() {
-                    return Err(Error::L7ProtocolUnknown);
+                    infos.push(info);
                 }
             }
         }
-        info.is_tls = param.is_tls();
-        set_captured_byte!(info, param);
-        if let Some(config) = param.parse_config {
-            info.set_is_on_blacklist(config);
+        if infos.is_empty() {
+            return Err(Error::L7ProtocolUnknown);
         }
-        if !info.is_on_blacklist && !self.last_is_on_blacklist {
-            match param.direction {
-                PacketDirection::ClientToServer => {
-                    self.perf_stats.as_mut().map(|p| p.inc_req());
-                }
-                PacketDirection::ServerToClient => {
-                    self.perf_stats.as_mut().map(|p| p.inc_resp());
-                    if info.status == L7ResponseStatus::ClientError {
-                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
-                    } else if info.status == L7ResponseStatus::ServerError {
-                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match param.direction {
+                    PacketDirection::ClientToServer => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    PacketDirection::ServerToClient => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                        if info.status == L7ResponseStatus::ClientError {
+                            self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                        } else if info.status == L7ResponseStatus::ServerError {
+                            self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                        }
                     }
                 }
+                info.cal_rrt(param).map(|rrt| {
+                    info.rrt = rrt;
+                    self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                });
             }
-            info.cal_rrt(param).map(|rrt| {
-                info.rrt = rrt;
-                self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
-            });
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+        if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::FastCGIInfo(
+                infos.remove(0),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                infos.into_iter().map(L7ProtocolInfo::FastCGIInfo).collect(),
+            ))
         }
-        self.last_is_on_blacklist = info.is_on_blacklist;
-        Ok(L7ParseResult::Single(L7ProtocolInfo::FastCGIInfo(info)))
     }
 
     fn protocol(&self) -> L7Protocol {
@@ -643,13 +689,15 @@ mod test {
 
     use crate::common::flow::{L7PerfStats, PacketDirection};
     use crate::common::l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface};
-    use crate::common::l7_protocol_log::{L7PerfCache, L7ProtocolParserInterface, ParseParam};
+    use crate::common::l7_protocol_log::{
+        L7ParseResult, L7PerfCache, L7ProtocolParserInterface, ParseParam,
+    };
     use crate::flow_generator::protocol_logs::fastcgi::FastCGILog;
     use crate::flow_generator::protocol_logs::L7ResponseStatus;
     use crate::flow_generator::LogMessageType;
     use crate::{flow_generator::L7_RRT_CACHE_CAPACITY, utils::test::Capture};
 
-    use super::FastCGIInfo;
+    use super::{FastCGIInfo, FCGI_END_REQUEST, FCGI_REQUEST_COMPLETE, FCGI_STDOUT};
 
     const FILE_DIR: &str = "resources/test/flow_generator/fastcgi";
 
@@ -693,6 +741,75 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_multiplexed_response() {
+        fn record(record_type: u8, request_id: u16, content: &[u8]) -> Vec<u8> {
+            let mut r = vec![1, record_type];
+            r.extend_from_slice(&request_id.to_be_bytes());
+            r.extend_from_slice(&(content.len() as u16).to_be_bytes());
+            r.extend_from_slice(&[0, 0]);
+            r.extend_from_slice(content);
+            r
+        }
+        let payload = [
+            record(
+                FCGI_STDOUT,
+                1,
+                b"Status: 404 Not Found\r\nContent-type: text/html\r\n\r\n",
+            ),
+            record(FCGI_STDOUT, 2, b"Content-type: text/html\r\n\r\nhello"),
+            record(
+                FCGI_END_REQUEST,
+                1,
+                &[0, 0, 0, 0, FCGI_REQUEST_COMPLETE, 0, 0, 0],
+            ),
+            // FCGI_OVERLOADED
+            record(FCGI_END_REQUEST, 3, &[0, 0, 0, 0, 2, 0, 0, 0]),
+        ]
+        .concat();
+
+        let capture = Capture::load_pcap(Path::new(FILE_DIR).join("fastcgi.pcap"), None);
+        let mut p = capture.as_meta_packets();
+        p[1].lookup_key.direction = PacketDirection::ServerToClient;
+        let param = &ParseParam::new(
+            &p[1],
+            Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY))),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let mut parser = FastCGILog::default();
+        let L7ParseResult::Multi(infos) = parser.parse_payload(&payload, param).unwrap() else {
+            unreachable!()
+        };
+        let infos = infos
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::FastCGIInfo(i) => (i.request_id, i.status_code, i.status),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            infos,
+            vec![
+                (1, Some(404), L7ResponseStatus::ClientError),
+                (2, Some(200), L7ResponseStatus::Ok),
+                (3, None, L7ResponseStatus::ServerError),
+            ]
+        );
+        let perf = parser.perf_stats.unwrap();
+        assert_eq!(
+            (
+                perf.response_count,
+                perf.err_client_count,
+                perf.err_server_count
+            ),
+            (3, 1, 1)
+        );
+    }
+
     fn check_and_parse(file_name: &str) -> (FastCGIInfo, L7PerfStats) {
         let pcap_file = Path::new(FILE_DIR).join(file_name);
         let capture = Capture::load_pcap(pcap_file, None);
@@ -0,0 +1,317 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// GraphQL operations carried by HTTP POST requests, only the executable operation is located
+// in the document, fields and arguments are not parsed.
+// Reference: https://spec.graphql.org/October2021/#sec-Language and https://graphql.org/learn/serving-over-http/
+
+use std::str;
+
+use serde::Deserialize;
+
+use super::pb_adapter::KeyVal;
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Name(&'a str),
+    Punctuator(u8),
+}
+
+struct Lexer<'a> {
+    document: &'a str,
+}
+
+impl<'a> Lexer<'a> {
+    // skips a block whose opening punctuator is consumed
+    fn skip_block(&mut self, open: u8, close: u8) -> Option<()> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Punctuator(p) if p == open => depth += 1,
+                Token::Punctuator(p) if p == close => depth -= 1,
+                _ => (),
+            }
+        }
+        Some(())
+    }
+
+    fn skip_string(&mut self) {
+        let bytes = self.document.as_bytes();
+        if bytes.starts_with(b"\"\"\"") {
+            self.document = match self.document[3..].find("\"\"\"") {
+                Some(end) => &self.document[end + 6..],
+                None => "",
+            };
+            return;
+        }
+        let mut i = 1;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' => i += 2,
+                b'"' | b'\n' => break,
+                _ => i += 1,
+            }
+        }
+        self.document = self.document.get(i + 1..).unwrap_or_default();
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // commas are insignificant like white spaces
+            self.document = self
+                .document
+                .trim_start_matches(|c: char| c.is_whitespace() || c == ',' || c == '\u{feff}');
+            let first = *self.document.as_bytes().first()?;
+            match first {
+                b'#' => {
+                    let end = self.document.find('\n').unwrap_or(self.document.len());
+                    self.document = &self.document[end..];
+                }
+                b'"' => self.skip_string(),
+                b'_' | b'a'..=b'z' | b'A'..=b'Z' => {
+                    let end = self
+                        .document
+                        .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                        .unwrap_or(self.document.len());
+                    let (name, rest) = self.document.split_at(end);
+                    self.document = rest;
+                    return Some(Token::Name(name));
+                }
+                _ => {
+                    // `...` is returned as one `.`
+                    let len = if self.document.starts_with("...") {
+                        3
+                    } else {
+                        // only ascii punctuators are used in GraphQL, skip the whole char otherwise
+                        self.document.chars().next()?.len_utf8()
+                    };
+                    self.document = &self.document[len..];
+                    return Some(Token::Punctuator(first));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct GraphqlOperation {
+    // query, mutation or subscription
+    pub operation_type: &'static str,
+    pub name: String,
+    // first top level field of the selection set, alias is ignored
+    pub field: String,
+}
+
+impl GraphqlOperation {
+    // The body is a JSON object with `query` and `operationName` in `application/json` requests,
+    // or the document itself in `application/graphql` requests.
+    // Only the first operation of batched requests is used.
+    pub fn parse_request(body: &[u8]) -> Option<Self> {
+        let start = body.iter().position(|b| !b.is_ascii_whitespace())?;
+        let body = &body[start..];
+        // both JSON objects and query shorthands start with `{`
+        let is_json_object = body[0] == b'{'
+            && body[1..]
+                .iter()
+                .find(|b| !b.is_ascii_whitespace())
+                .map(|b| *b == b'"' || *b == b'}')
+                .unwrap_or(true);
+        let request = match body[0] {
+            b'{' if is_json_object => serde_json::Deserializer::from_slice(body)
+                .into_iter::<Request>()
+                .next()?
+                .ok()?,
+            b'[' => serde_json::Deserializer::from_slice(body)
+                .into_iter::<Vec<Request>>()
+                .next()?
+                .ok()?
+                .into_iter()
+                .next()?,
+            _ => {
+                return Self::parse_document(str::from_utf8(body).ok()?, None);
+            }
+        };
+        Self::parse_document(&request.query?, request.operation_name.as_deref())
+    }
+
+    // locates the operation named `operation_name`, or the first operation if not specified
+    pub fn parse_document(document: &str, operation_name: Option<&str>) -> Option<Self> {
+        let operation_name = operation_name.filter(|n| !n.is_empty());
+        let mut lexer = Lexer { document };
+        loop {
+            let (operation_type, name) = match lexer.next()? {
+                // query shorthand
+                Token::Punctuator(b'{') => ("query", ""),
+                Token::Name(t) if t == "query" || t == "mutation" || t == "subscription" => {
+                    let operation_type = match t {
+                        "query" => "query",
+                        "mutation" => "mutation",
+                        _ => "subscription",
+                    };
+                    let mut name = "";
+                    // name, variable definitions and directives before the selection set
+                    loop {
+                        match lexer.next()? {
+                            Token::Punctuator(b'{') => break,
+                            Token::Punctuator(b'(') => lexer.skip_block(b'(', b')')?,
+                            Token::Punctuator(b'@') => {
+                                lexer.next()?;
+                            }
+                            Token::Name(n) if name.is_empty() => name = n,
+                            _ => (),
+                        }
+                    }
+                    (operation_type, name)
+                }
+                Token::Name("fragment") => {
+                    while lexer.next()? != Token::Punctuator(b'{') {}
+                    lexer.skip_block(b'{', b'}')?;
+                    continue;
+                }
+                _ => return None,
+            };
+            if operation_name.is_some() && operation_name != Some(name) {
+                lexer.skip_block(b'{', b'}')?;
+                continue;
+            }
+            let field = match lexer.next() {
+                Some(Token::Name(field)) => match lexer.next() {
+                    // alias
+                    Some(Token::Punctuator(b':')) => match lexer.next() {
+                        Some(Token::Name(field)) => field,
+                        _ => "",
+                    },
+                    _ => field,
+                },
+                _ => "",
+            };
+            return Some(Self {
+                operation_type,
+                name: name.to_owned(),
+                field: field.to_owned(),
+            });
+        }
+    }
+
+    // e.g. `query GetUser`, or `query user` for anonymous operations
+    pub fn endpoint(&self) -> String {
+        let name = if self.name.is_empty() {
+            &self.field
+        } else {
+            &self.name
+        };
+        if name.is_empty() {
+            self.operation_type.to_owned()
+        } else {
+            format!("{} {}", self.operation_type, name)
+        }
+    }
+
+    pub fn attributes(&self) -> Vec<KeyVal> {
+        [
+            ("graphql_operation_type", self.operation_type),
+            ("graphql_operation_name", self.name.as_str()),
+            ("graphql_field", self.field.as_str()),
+        ]
+        .into_iter()
+        .filter(|(_, val)| !val.is_empty())
+        .map(|(key, val)| KeyVal {
+            key: key.to_string(),
+            val: val.to_string(),
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(operation_type: &'static str, name: &str, field: &str) -> GraphqlOperation {
+        GraphqlOperation {
+            operation_type,
+            name: name.to_string(),
+            field: field.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_document() {
+        let cases = [
+            ("{ user(id: 1) { name } }", None, Some(operation("query", "", "user"))),
+            (
+                "query GetUser($id: ID! = \"a{\") @cached(ttl: 60) { me: user(id: $id) { name } }",
+                None,
+                Some(operation("query", "GetUser", "user")),
+            ),
+            (
+                "# comment {\nfragment F on User { id }\nmutation { createUser(name: \"\"\"x}\"\"\") { ...F } }",
+                None,
+                Some(operation("mutation", "", "createUser")),
+            ),
+            (
+                "query A { a } subscription B { onEvent { id } }",
+                Some("B"),
+                Some(operation("subscription", "B", "onEvent")),
+            ),
+            ("query A { a }", Some("C"), None),
+            ("query A { a", Some("C"), None),
+            ("not graphql", None, None),
+        ];
+        for (document, name, expected) in cases {
+            assert_eq!(
+                GraphqlOperation::parse_document(document, name),
+                expected,
+                "document {}",
+                document
+            );
+        }
+    }
+
+    #[test]
+    fn parse_request() {
+        let body = br#"{"query":"query GetUser($id: ID!) { user(id: $id) { name } }","operationName":"GetUser","variables":{"id":1}}"#;
+        let op = GraphqlOperation::parse_request(body).unwrap();
+        assert_eq!(op, operation("query", "GetUser", "user"));
+        assert_eq!(op.endpoint(), "query GetUser");
+        assert_eq!(op.attributes().len(), 3);
+
+        let body =
+            br#"[{"query":"mutation { addItem(id: 1) }","operationName":null},{"query":"{ a }"}]"#;
+        let op = GraphqlOperation::parse_request(body).unwrap();
+        assert_eq!(op.endpoint(), "mutation addItem");
+
+        let op = GraphqlOperation::parse_request(b"\n{ items { id } }").unwrap();
+        assert_eq!(op.endpoint(), "query items");
+
+        // truncated JSON
+        assert_eq!(
+            GraphqlOperation::parse_request(br#"{"query":"query GetUser"#),
+            None
+        );
+    }
+}
@@ -15,17 +15,24 @@
  */
 
 use std::collections::HashSet;
+use std::num::NonZeroUsize;
 use std::str;
 use std::sync::Arc;
 
 use hpack::Decoder;
+use lru::LruCache;
 use nom::{AsBytes, ParseTo};
 use public::l7_protocol::L7ProtocolChecker;
 use serde::Serialize;
 
+use super::dns::DnsInfo;
+use super::graphql::GraphqlOperation;
+use super::ntlm::NtlmMessage;
 use super::pb_adapter::{
     ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, TraceInfo,
 };
+use super::soap::{self, SoapBody};
+use super::websocket::WebSocketSession;
 use super::{consts::*, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
 
 use crate::plugin::CustomInfo;
@@ -39,9 +46,9 @@ use crate::{
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
         meta_packet::EbpfFlags,
     },
-    config::handler::{L7LogDynamicConfig, LogParserConfig},
+    config::handler::{GrpcBodyFieldRule, HttpBodyFieldRule, L7LogDynamicConfig, LogParserConfig},
     flow_generator::error::{Error, Result},
-    flow_generator::protocol_logs::{set_captured_byte, L7ProtoRawDataType},
+    flow_generator::protocol_logs::{protobuf, set_captured_byte, L7ProtoRawDataType},
     utils::bytes::{read_u32_be, read_u32_le},
 };
 
@@ -262,6 +269,22 @@ pub struct HttpInfo {
 
     #[serde(skip)]
     attributes: Vec<KeyVal>,
+    // application error found in the response body, e.g. `code=1001` or SOAP Fault reason
+    #[serde(skip)]
+    body_error: Option<String>,
+    // operation of SOAP or GraphQL requests, used as endpoint since they share one url
+    #[serde(skip)]
+    api_operation: Option<String>,
+    #[serde(skip)]
+    is_graphql: bool,
+    // `Upgrade: websocket` in request or response
+    #[serde(skip)]
+    is_websocket_upgrade: bool,
+    // DNS-over-HTTPS, the DNS message is reported instead of the HTTP session
+    #[serde(skip)]
+    is_dns_message: bool,
+    #[serde(skip)]
+    dns_message: Option<DnsInfo>,
 
     #[serde(skip)]
     is_on_blacklist: bool,
@@ -373,6 +396,14 @@ impl L7ProtocolInfoInterface for HttpInfo {
         }
     }
 
+    fn get_payload_size(&self) -> Option<u32> {
+        match self.msg_type {
+            LogMessageType::Request => self.req_content_length,
+            LogMessageType::Response => self.resp_content_length,
+            _ => None,
+        }
+    }
+
     fn tcp_seq_offset(&self) -> u32 {
         self.headers_offset.unwrap_or_default()
     }
@@ -611,6 +642,10 @@ pub struct HttpLog {
     perf_stats: Option<L7PerfStats>,
     http2_req_decoder: Option<Decoder<'static>>,
     http2_resp_decoder: Option<Decoder<'static>>,
+    // gRPC service of each stream, used for response body field extraction
+    grpc_services: Option<LruCache<u32, String>>,
+    // frames after `101 Switching Protocols` are parsed as WebSocket
+    websocket: Option<WebSocketSession>,
 }
 
 impl L7ProtocolParserInterface for HttpLog {
@@ -659,6 +694,10 @@ impl L7ProtocolParserInterface for HttpLog {
             return Err(Error::NoParseConfig);
         };
 
+        if let Some(websocket) = self.websocket.as_mut().filter(|w| w.upgraded) {
+            return websocket.parse_payload(payload, param);
+        }
+
         let mut info = HttpInfo::default();
         info.proto = self.proto;
         info.is_tls = param.is_tls();
@@ -709,12 +748,31 @@ impl L7ProtocolParserInterface for HttpLog {
         }
         info.service_name = info.grpc_package_service_name();
         if !config.http_endpoint_disabled && info.path.len() > 0 {
-            // Priority use of info.endpoint, because info.endpoint may be set by the wasm plugin
-            let path = match info.endpoint.as_ref() {
-                Some(p) if !p.is_empty() => p,
-                _ => &info.path,
+            info.endpoint = match (info.endpoint.as_ref(), info.api_operation.take()) {
+                // Priority use of info.endpoint, because info.endpoint may be set by the wasm plugin
+                (Some(p), _) if !p.is_empty() => Some(handle_endpoint(config, p)),
+                // SOAP and GraphQL services share one url, use the operation instead
+                (_, Some(operation)) => Some(operation),
+                _ => Some(handle_endpoint(config, &info.path)),
             };
-            info.endpoint = Some(handle_endpoint(config, path));
+        }
+        if self.proto == L7Protocol::Http1 {
+            match param.direction {
+                PacketDirection::ClientToServer if info.is_websocket_upgrade => {
+                    self.websocket = Some(WebSocketSession::new(
+                        info.path.clone(),
+                        info.host.clone(),
+                        info.endpoint.clone(),
+                    ));
+                }
+                PacketDirection::ServerToClient
+                    if info.is_websocket_upgrade && info.status_code == 101 =>
+                {
+                    // the upgrade request may be missed
+                    self.websocket.get_or_insert_with(Default::default).upgraded = true;
+                }
+                _ => (),
+            }
         }
         info.set_is_on_blacklist(config);
         if !info.is_on_blacklist && !self.last_is_on_blacklist {
@@ -730,6 +788,15 @@ impl L7ProtocolParserInterface for HttpLog {
                             } else {
                                 self.set_status(info.status_code, &mut info);
                             }
+                            if let Some(error) = info.body_error.take() {
+                                if info.status == L7ResponseStatus::Ok {
+                                    self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                                    info.status = L7ResponseStatus::ServerError;
+                                }
+                                if info.custom_exception.is_none() {
+                                    info.custom_exception = Some(error);
+                                }
+                            }
                             self.perf_stats.as_mut().map(|p| p.inc_resp());
                         }
                     }
@@ -794,6 +861,25 @@ impl L7ProtocolParserInterface for HttpLog {
             }
         }
         self.last_is_on_blacklist = info.is_on_blacklist;
+        if let Some(mut dns) = info.dns_message.take() {
+            if !info.is_on_blacklist && info.status == L7ResponseStatus::Ok {
+                match dns.status {
+                    L7ResponseStatus::ClientError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                    L7ResponseStatus::ServerError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    }
+                    _ => (),
+                }
+            }
+            dns.set_doh_session(info.stream_id, info.rrt);
+            return if param.parse_log {
+                Ok(L7ParseResult::Single(L7ProtocolInfo::DnsInfo(dns)))
+            } else {
+                Ok(L7ParseResult::None)
+            };
+        }
         if param.parse_log {
             Ok(L7ParseResult::Single(L7ProtocolInfo::HttpInfo(info)))
         } else {
@@ -826,6 +912,8 @@ impl L7ProtocolParserInterface for HttpLog {
         new_log.perf_stats = self.perf_stats.take();
         new_log.http2_req_decoder = self.http2_req_decoder.take();
         new_log.http2_resp_decoder = self.http2_resp_decoder.take();
+        new_log.grpc_services = self.grpc_services.take();
+        new_log.websocket = self.websocket.take();
         *self = new_log;
     }
 
@@ -835,6 +923,10 @@ impl L7ProtocolParserInterface for HttpLog {
 }
 
 impl HttpLog {
+    const GRPC_SERVICES_CAPACITY: usize = 64;
+    // compressed flag and message length
+    const GRPC_MESSAGE_HEADER_LENGTH: usize = 5;
+
     pub fn new_v1() -> Self {
         Self {
             proto: L7Protocol::Http1,
@@ -990,10 +1082,8 @@ impl HttpLog {
         param: &ParseParam,
         info: &mut HttpInfo,
     ) -> Result<()> {
-        let (direction, config) = (
-            param.direction,
-            &param.parse_config.as_ref().unwrap().l7_log_dynamic,
-        );
+        let parse_config = param.parse_config.as_ref().unwrap();
+        let (direction, config) = (param.direction, &parse_config.l7_log_dynamic);
         if !is_http_v1_payload(payload) {
             return Err(Error::HttpHeaderParseFailed);
         }
@@ -1061,6 +1151,48 @@ impl HttpLog {
             }
         }
 
+        if let Some(index) = payload.windows(4).position(|w| w == b"\r\n\r\n") {
+            let body = Self::skip_chunk_size(&payload[index + 4..]);
+            if !parse_config.http_body_fields.is_empty() {
+                Self::extract_http_body_fields(
+                    &parse_config.http_body_fields,
+                    body,
+                    direction,
+                    info,
+                );
+            }
+            if direction == PacketDirection::ClientToServer
+                && (info.is_graphql || info.path.contains("graphql"))
+                && info.api_operation.is_none()
+            {
+                if let Some(operation) = GraphqlOperation::parse_request(body) {
+                    info.attributes.extend(operation.attributes());
+                    info.api_operation = Some(operation.endpoint());
+                }
+            }
+            if let Some(soap) = SoapBody::parse(body) {
+                match direction {
+                    PacketDirection::ClientToServer if info.api_operation.is_none() => {
+                        info.api_operation = Some(soap.operation);
+                    }
+                    PacketDirection::ServerToClient if info.body_error.is_none() => {
+                        info.body_error = soap.fault;
+                    }
+                    _ => (),
+                }
+            }
+            if info.is_dns_message {
+                let message = match direction {
+                    PacketDirection::ClientToServer if body.is_empty() => {
+                        DnsInfo::doh_get_message(&info.path)
+                    }
+                    _ => None,
+                };
+                info.dns_message =
+                    DnsInfo::from_doh_message(message.as_deref().unwrap_or(body), param).ok();
+            }
+        }
+
         set_captured_byte!(info, param);
         // 当解析完所有Header仍未找到Content-Length，则认为该字段值为0
         if direction == PacketDirection::ServerToClient {
@@ -1071,6 +1203,62 @@ impl HttpLog {
         Ok(())
     }
 
+    // a chunked body is parsed from its first chunk
+    fn skip_chunk_size(body: &[u8]) -> &[u8] {
+        match body.windows(2).position(|w| w == b"\r\n") {
+            Some(index) if index > 0 && body[..index].iter().all(u8::is_ascii_hexdigit) => {
+                &body[index + 2..]
+            }
+            _ => body,
+        }
+    }
+
+    // Only JSON bodies are supported, compressed or truncated bodies are ignored.
+    fn extract_http_body_fields(
+        rules: &[HttpBodyFieldRule],
+        body: &[u8],
+        direction: PacketDirection,
+        info: &mut HttpInfo,
+    ) {
+        let in_response = direction == PacketDirection::ServerToClient;
+        if !rules.iter().any(|r| r.in_response == in_response) {
+            return;
+        }
+        let Some(start) = body.iter().position(|b| !b.is_ascii_whitespace()) else {
+            return;
+        };
+        let body = &body[start..];
+        if body[0] != b'{' && body[0] != b'[' {
+            return;
+        }
+        // trailing data such as the next chunk is ignored
+        let Some(Ok(value)) = serde_json::Deserializer::from_slice(body)
+            .into_iter::<serde_json::Value>()
+            .next()
+        else {
+            return;
+        };
+        for rule in rules.iter().filter(|r| r.in_response == in_response) {
+            let val = match value.pointer(&rule.json_pointer) {
+                None | Some(serde_json::Value::Null) => continue,
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(v) => v.to_string(),
+            };
+            if in_response
+                && (200..300).contains(&info.status_code)
+                && !rule.success_values.is_empty()
+                && !rule.success_values.contains(&val)
+                && info.body_error.is_none()
+            {
+                info.body_error = Some(format!("{}={}", rule.field_name, val));
+            }
+            info.attributes.push(KeyVal {
+                key: rule.field_name.clone(),
+                val,
+            });
+        }
+    }
+
     fn has_magic(payload: &[u8]) -> bool {
         if payload.len() < HTTPV2_MAGIC_LENGTH {
             return false;
@@ -1155,10 +1343,8 @@ impl HttpLog {
         param: &ParseParam,
         info: &mut HttpInfo,
     ) -> Result<()> {
-        let (direction, config) = (
-            param.direction,
-            &param.parse_config.as_ref().unwrap().l7_log_dynamic,
-        );
+        let parse_config = param.parse_config.as_ref().unwrap();
+        let (direction, config) = (param.direction, &parse_config.l7_log_dynamic);
         let mut content_length: Option<u32> = None;
         let mut header_frame_parsed = false;
         let mut is_httpv2 = false;
@@ -1236,15 +1422,33 @@ impl HttpLog {
 
                 header_frame_parsed = true;
 
+                if info.is_dns_message && direction == PacketDirection::ClientToServer {
+                    if let Some(message) = DnsInfo::doh_get_message(&info.path) {
+                        info.dns_message = DnsInfo::from_doh_message(&message, param).ok();
+                    }
+                }
+
                 if self.proto == L7Protocol::Grpc {
                     info.method =
                         Method::from_frame_type(httpv2_header.frame_type, param.direction);
+                    if !parse_config.grpc_body_fields.is_empty() {
+                        if let Some(service) = info.grpc_package_service_name() {
+                            self.grpc_services
+                                .get_or_insert_with(|| {
+                                    LruCache::new(
+                                        NonZeroUsize::new(Self::GRPC_SERVICES_CAPACITY).unwrap(),
+                                    )
+                                })
+                                .put(httpv2_header.stream_id, service);
+                        }
+                    }
                 }
                 if info.headers_offset.is_none() || info.grpc_status_code.is_some() {
                     info.headers_offset = Some(headers_offset as u32);
                 }
 
-                if content_length.is_some() {
+                // the DNS message of DNS-over-HTTPS is in the following data frame
+                if content_length.is_some() && !info.is_dns_message {
                     is_httpv2 = true;
                     break;
                 }
@@ -1268,10 +1472,41 @@ impl HttpLog {
                     }
                 }
 
+                if info.is_dns_message && info.dns_message.is_none() {
+                    let start = if httpv2_header.flags & FLAG_HEADERS_PADDED != 0 {
+                        1
+                    } else {
+                        0
+                    };
+                    let end = frame_payload.len().min(httpv2_header.frame_length as usize);
+                    if start < end {
+                        info.dns_message =
+                            DnsInfo::from_doh_message(&frame_payload[start..end], param).ok();
+                    }
+                }
+
                 if self.proto != L7Protocol::Grpc {
                     break;
                 }
 
+                if !parse_config.grpc_body_fields.is_empty() {
+                    let start = if httpv2_header.flags & FLAG_HEADERS_PADDED != 0 {
+                        1
+                    } else {
+                        0
+                    };
+                    let end = frame_payload.len().min(httpv2_header.frame_length as usize);
+                    if start < end {
+                        self.extract_grpc_body_fields(
+                            &parse_config.grpc_body_fields,
+                            &frame_payload[start..end],
+                            httpv2_header.stream_id,
+                            direction,
+                            info,
+                        );
+                    }
+                }
+
                 is_httpv2 = true;
                 if info.method.is_none() {
                     info.method =
@@ -1321,6 +1556,44 @@ impl HttpLog {
         Err(Error::HttpHeaderParseFailed)
     }
 
+    fn extract_grpc_body_fields(
+        &mut self,
+        rules: &[GrpcBodyFieldRule],
+        data: &[u8],
+        stream_id: u32,
+        direction: PacketDirection,
+        info: &mut HttpInfo,
+    ) {
+        // only uncompressed messages can be decoded
+        if data.len() <= Self::GRPC_MESSAGE_HEADER_LENGTH || data[0] != 0 {
+            return;
+        }
+        let length = read_u32_be(&data[1..]) as usize;
+        let message = &data[Self::GRPC_MESSAGE_HEADER_LENGTH..]
+            [..length.min(data.len() - Self::GRPC_MESSAGE_HEADER_LENGTH)];
+        let service = info.grpc_package_service_name().or_else(|| {
+            self.grpc_services
+                .as_mut()
+                .and_then(|s| s.get(&stream_id).cloned())
+        });
+        let in_response = direction == PacketDirection::ServerToClient;
+        for rule in rules {
+            if rule.in_response != in_response
+                || (!rule.service.is_empty() && service.as_ref() != Some(&rule.service))
+                // keep the first value if there are multiple messages
+                || info.attributes.iter().any(|a| a.key == rule.field_name)
+            {
+                continue;
+            }
+            if let Some(val) = protobuf::extract_field(message, &rule.field_path) {
+                info.attributes.push(KeyVal {
+                    key: rule.field_name.clone(),
+                    val,
+                });
+            }
+        }
+    }
+
     fn parse_http_v2(
         &mut self,
         payload: &[u8],
@@ -1362,11 +1635,38 @@ impl HttpLog {
                 let code = val.parse_to().unwrap_or_default();
                 info.grpc_status_code = Some(code);
             }
+            "authorization" | "proxy-authorization" => {
+                if let Some(ntlm) = NtlmMessage::parse_http_auth(val) {
+                    info.attributes.extend(ntlm.attributes());
+                }
+            }
             "content-type" => {
                 // change to grpc protocol
                 if val.starts_with(b"application/grpc") {
                     self.proto = L7Protocol::Grpc;
                     info.proto = L7Protocol::Grpc;
+                } else if val.starts_with(b"application/graphql") {
+                    info.is_graphql = true;
+                } else if val.starts_with(DNS_MESSAGE_CONTENT_TYPE) {
+                    info.is_dns_message = true;
+                } else if val.starts_with(b"application/soap+xml") {
+                    if let Some(operation) = soap::content_type_operation(val) {
+                        info.api_operation = Some(operation);
+                    }
+                }
+            }
+            "soapaction" => {
+                if let Some(operation) = soap::action_operation(val) {
+                    info.api_operation = Some(operation);
+                }
+            }
+            // GET requests of DNS-over-HTTPS have no content-type
+            "accept" if val.starts_with(DNS_MESSAGE_CONTENT_TYPE) => info.is_dns_message = true,
+            "upgrade" => info.is_websocket_upgrade = val.eq_ignore_ascii_case(b"websocket"),
+            // the subprotocol selected by server
+            "sec-websocket-protocol" if direction == PacketDirection::ServerToClient => {
+                if let Some(websocket) = self.websocket.as_mut() {
+                    websocket.subprotocol = String::from_utf8_lossy(val).into_owned();
                 }
             }
             _ => {}
@@ -2215,4 +2515,223 @@ mod tests {
         let expected_output = "/api/v1"; // prefixes match, but the keep_segments is 0, use the default value 2 segments
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
     }
+
+    #[test]
+    fn test_grpc_body_fields() {
+        let rules = vec![
+            GrpcBodyFieldRule {
+                service: "shop.OrderService".to_string(),
+                field_name: "order_id".to_string(),
+                field_path: vec![2, 1],
+                in_response: false,
+            },
+            GrpcBodyFieldRule {
+                service: "shop.UserService".to_string(),
+                field_name: "user".to_string(),
+                field_path: vec![1],
+                in_response: false,
+            },
+            GrpcBodyFieldRule {
+                service: "".to_string(),
+                field_name: "result".to_string(),
+                field_path: vec![1],
+                in_response: true,
+            },
+        ];
+        // message CreateOrderRequest { string user = 1; Order order = 2; }
+        // message Order { int64 id = 1; }
+        let request = [0x0a, 0x02, b'u', b'1', 0x12, 0x03, 0x08, 0x96, 0x01];
+        let mut data = vec![0];
+        data.extend_from_slice(&(request.len() as u32).to_be_bytes());
+        data.extend_from_slice(&request);
+
+        let mut http = HttpLog::new_v2(true);
+        let mut info = HttpInfo {
+            proto: L7Protocol::Grpc,
+            path: "/shop.OrderService/Create".to_string(),
+            ..Default::default()
+        };
+        http.extract_grpc_body_fields(&rules, &data, 1, PacketDirection::ClientToServer, &mut info);
+        assert_eq!(
+            info.attributes,
+            vec![KeyVal {
+                key: "order_id".to_string(),
+                val: "150".to_string(),
+            }]
+        );
+
+        // the service of the response is from the request headers
+        http.grpc_services = Some(LruCache::new(NonZeroUsize::new(1).unwrap()));
+        http.grpc_services
+            .as_mut()
+            .unwrap()
+            .put(1, "shop.OrderService".to_string());
+        let response = [0x0a, 0x02, b'o', b'k'];
+        let mut data = vec![0];
+        data.extend_from_slice(&(response.len() as u32).to_be_bytes());
+        data.extend_from_slice(&response);
+        let mut info = HttpInfo {
+            proto: L7Protocol::Grpc,
+            ..Default::default()
+        };
+        http.extract_grpc_body_fields(&rules, &data, 1, PacketDirection::ServerToClient, &mut info);
+        assert_eq!(
+            info.attributes,
+            vec![KeyVal {
+                key: "result".to_string(),
+                val: "ok".to_string(),
+            }]
+        );
+
+        // compressed messages are skipped
+        data[0] = 1;
+        let mut info = HttpInfo::default();
+        http.extract_grpc_body_fields(&rules, &data, 1, PacketDirection::ServerToClient, &mut info);
+        assert!(info.attributes.is_empty());
+    }
+
+    #[test]
+    fn test_http_body_fields() {
+        let rules = vec![
+            HttpBodyFieldRule {
+                field_name: "user_id".to_string(),
+                json_pointer: "/data/userId".to_string(),
+                in_response: false,
+                success_values: vec![],
+            },
+            HttpBodyFieldRule {
+                field_name: "code".to_string(),
+                json_pointer: "/code".to_string(),
+                in_response: true,
+                success_values: vec!["0".to_string()],
+            },
+        ];
+
+        let mut info = HttpInfo::default();
+        let body = br#"{"data": {"userId": "u1", "items": [1, 2]}}"#;
+        HttpLog::extract_http_body_fields(&rules, body, PacketDirection::ClientToServer, &mut info);
+        assert_eq!(
+            info.attributes,
+            vec![KeyVal {
+                key: "user_id".to_string(),
+                val: "u1".to_string(),
+            }]
+        );
+        assert_eq!(info.body_error, None);
+
+        // chunked body with an application error code
+        let mut info = HttpInfo {
+            status_code: 200,
+            ..Default::default()
+        };
+        let body = b"15\r\n{\"code\": 1001, \"x\":1}\r\n0\r\n\r\n";
+        HttpLog::extract_http_body_fields(
+            &rules,
+            HttpLog::skip_chunk_size(body),
+            PacketDirection::ServerToClient,
+            &mut info,
+        );
+        assert_eq!(
+            info.attributes,
+            vec![KeyVal {
+                key: "code".to_string(),
+                val: "1001".to_string(),
+            }]
+        );
+        assert_eq!(info.body_error.as_deref(), Some("code=1001"));
+
+        let mut info = HttpInfo {
+            status_code: 200,
+            ..Default::default()
+        };
+        HttpLog::extract_http_body_fields(
+            &rules,
+            br#"{"code": 0}"#,
+            PacketDirection::ServerToClient,
+            &mut info,
+        );
+        assert_eq!(info.body_error, None);
+
+        // non-JSON and truncated bodies are ignored
+        for body in [&b"<html></html>"[..], br#"{"code": 10"#] {
+            let mut info = HttpInfo::default();
+            HttpLog::extract_http_body_fields(
+                &rules,
+                body,
+                PacketDirection::ServerToClient,
+                &mut info,
+            );
+            assert!(info.attributes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_dns_over_https() {
+        // query example.com A
+        let query = b"\x00\x00\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        let mut response = query.to_vec();
+        // SERVFAIL
+        response[2..4].copy_from_slice(&[0x81, 0x82]);
+        let payloads = [
+            (
+                PacketDirection::ClientToServer,
+                [
+                    &b"POST /dns-query HTTP/1.1\r\nHost: dns.example\r\nContent-Type: application/dns-message\r\nContent-Length: 29\r\n\r\n"[..],
+                    &query[..],
+                ]
+                .concat(),
+            ),
+            (
+                PacketDirection::ClientToServer,
+                b"GET /dns-query?dns=AAABAAABAAAAAAAAB2V4YW1wbGUDY29tAAABAAE HTTP/1.1\r\nHost: dns.example\r\nAccept: application/dns-message\r\n\r\n".to_vec(),
+            ),
+            (
+                PacketDirection::ServerToClient,
+                [
+                    &b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\nContent-Length: 29\r\n\r\n"[..],
+                    &response[..],
+                ]
+                .concat(),
+            ),
+        ];
+
+        let config = LogParserConfig::default();
+        let rrt_cache = Rc::new(RefCell::new(L7PerfCache::new(100)));
+        let mut http = HttpLog::new_v1();
+        for (direction, payload) in payloads {
+            let mut packet = MetaPacket::default();
+            packet.lookup_key.direction = direction;
+            packet.lookup_key.proto = IpProtocol::TCP;
+            let param = &mut ParseParam::new(
+                &packet,
+                rrt_cache.clone(),
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            param.set_log_parse_config(&config);
+            let Ok(L7ParseResult::Single(L7ProtocolInfo::DnsInfo(dns))) =
+                http.parse_payload(&payload, param)
+            else {
+                panic!(
+                    "DNS message not found in {:?}",
+                    String::from_utf8_lossy(&payload)
+                );
+            };
+            assert_eq!(dns.query_name, "example.com");
+            match direction {
+                PacketDirection::ClientToServer => assert_eq!(
+                    dns.app_proto_head().unwrap().msg_type,
+                    LogMessageType::Request
+                ),
+                PacketDirection::ServerToClient => {
+                    assert_eq!(dns.status, L7ResponseStatus::ServerError);
+                    assert_eq!(dns.status_code, Some(2));
+                }
+            }
+        }
+        assert_eq!(http.perf_stats.unwrap().err_server_count, 1);
+    }
 }
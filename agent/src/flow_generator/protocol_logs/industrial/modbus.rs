@@ -0,0 +1,581 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Modbus/TCP, the MBAP header followed by a Modbus PDU.
+// Reference: https://modbus.org/docs/Modbus_Messaging_Implementation_Guide_V1_0b.pdf
+//            https://modbus.org/docs/Modbus_Application_Protocol_V1_1b3.pdf
+
+use serde::Serialize;
+
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, L7Protocol, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::{
+        error::{Error, Result},
+        protocol_logs::{
+            pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
+            set_captured_byte, swap_if, value_is_default, AppProtoHead, L7ResponseStatus,
+            LogMessageType,
+        },
+    },
+};
+use public::bytes::read_u16_be;
+
+// transaction id, protocol id, length and unit id
+const MBAP_HEADER_LEN: usize = 7;
+const MODBUS_PROTOCOL_ID: u16 = 0;
+// unit id and the PDU, which is at most 253 bytes
+const MIN_LENGTH: usize = 2;
+const MAX_LENGTH: usize = 254;
+
+const EXCEPTION_FLAG: u8 = 0x80;
+
+const READ_COILS: u8 = 1;
+const READ_DISCRETE_INPUTS: u8 = 2;
+const READ_HOLDING_REGISTERS: u8 = 3;
+const READ_INPUT_REGISTERS: u8 = 4;
+const WRITE_SINGLE_COIL: u8 = 5;
+const WRITE_SINGLE_REGISTER: u8 = 6;
+const WRITE_MULTIPLE_COILS: u8 = 15;
+const WRITE_MULTIPLE_REGISTERS: u8 = 16;
+const MASK_WRITE_REGISTER: u8 = 22;
+const READ_WRITE_MULTIPLE_REGISTERS: u8 = 23;
+const READ_FIFO_QUEUE: u8 = 24;
+
+const ILLEGAL_FUNCTION: u8 = 1;
+const ILLEGAL_DATA_ADDRESS: u8 = 2;
+const ILLEGAL_DATA_VALUE: u8 = 3;
+// the request is accepted and being processed
+const ACKNOWLEDGE: u8 = 5;
+
+fn function_name(function_code: u8) -> Option<&'static str> {
+    let name = match function_code {
+        READ_COILS => "ReadCoils",
+        READ_DISCRETE_INPUTS => "ReadDiscreteInputs",
+        READ_HOLDING_REGISTERS => "ReadHoldingRegisters",
+        READ_INPUT_REGISTERS => "ReadInputRegisters",
+        WRITE_SINGLE_COIL => "WriteSingleCoil",
+        WRITE_SINGLE_REGISTER => "WriteSingleRegister",
+        7 => "ReadExceptionStatus",
+        8 => "Diagnostics",
+        11 => "GetCommEventCounter",
+        12 => "GetCommEventLog",
+        WRITE_MULTIPLE_COILS => "WriteMultipleCoils",
+        WRITE_MULTIPLE_REGISTERS => "WriteMultipleRegisters",
+        17 => "ReportServerID",
+        20 => "ReadFileRecord",
+        21 => "WriteFileRecord",
+        MASK_WRITE_REGISTER => "MaskWriteRegister",
+        READ_WRITE_MULTIPLE_REGISTERS => "ReadWriteMultipleRegisters",
+        READ_FIFO_QUEUE => "ReadFIFOQueue",
+        43 => "EncapsulatedInterfaceTransport",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn exception_name(exception_code: u8) -> &'static str {
+    match exception_code {
+        ILLEGAL_FUNCTION => "IllegalFunction",
+        ILLEGAL_DATA_ADDRESS => "IllegalDataAddress",
+        ILLEGAL_DATA_VALUE => "IllegalDataValue",
+        4 => "ServerDeviceFailure",
+        ACKNOWLEDGE => "Acknowledge",
+        6 => "ServerDeviceBusy",
+        8 => "MemoryParityError",
+        0x0a => "GatewayPathUnavailable",
+        0x0b => "GatewayTargetDeviceFailedToRespond",
+        _ => "Unknown",
+    }
+}
+
+struct Adu<'a> {
+    transaction_id: u16,
+    unit_id: u8,
+    function_code: u8,
+    // PDU data after function code, may be truncated
+    data: &'a [u8],
+    // length of the whole ADU
+    len: usize,
+}
+
+impl<'a> Adu<'a> {
+    fn parse(payload: &'a [u8]) -> Option<Self> {
+        if payload.len() <= MBAP_HEADER_LEN {
+            return None;
+        }
+        if read_u16_be(&payload[2..]) != MODBUS_PROTOCOL_ID {
+            return None;
+        }
+        let length = read_u16_be(&payload[4..]) as usize;
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+            return None;
+        }
+        let function_code = payload[MBAP_HEADER_LEN];
+        function_name(function_code & !EXCEPTION_FLAG)?;
+        // length counts from unit id
+        let len = MBAP_HEADER_LEN - 1 + length;
+        Some(Self {
+            transaction_id: read_u16_be(payload),
+            unit_id: payload[MBAP_HEADER_LEN - 1],
+            function_code,
+            data: &payload[MBAP_HEADER_LEN + 1..len.min(payload.len())],
+            len,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ModbusInfo {
+    #[serde(skip)]
+    msg_type: LogMessageType,
+    #[serde(skip)]
+    is_tls: bool,
+    rrt: u64,
+
+    pub transaction_id: u16,
+    pub unit_id: u8,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub function: &'static str,
+    // starting address of read and write functions
+    #[serde(rename = "request_resource", skip_serializing_if = "Option::is_none")]
+    pub address: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<u16>,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub exception_code: Option<u8>,
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    #[serde(rename = "request_length", skip_serializing_if = "Option::is_none")]
+    pub req_len: Option<u32>,
+    #[serde(rename = "response_length", skip_serializing_if = "Option::is_none")]
+    pub resp_len: Option<u32>,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl ModbusInfo {
+    fn new(adu: &Adu, direction: PacketDirection) -> Self {
+        let function_code = adu.function_code & !EXCEPTION_FLAG;
+        let mut info = ModbusInfo {
+            transaction_id: adu.transaction_id,
+            unit_id: adu.unit_id,
+            function: function_name(function_code).unwrap_or_default(),
+            ..Default::default()
+        };
+        match direction {
+            PacketDirection::ClientToServer => {
+                info.msg_type = LogMessageType::Request;
+                info.req_len = Some(adu.len as u32);
+                let field = |offset: usize| adu.data.get(offset..offset + 2).map(read_u16_be);
+                match function_code {
+                    READ_COILS
+                    | READ_DISCRETE_INPUTS
+                    | READ_HOLDING_REGISTERS
+                    | READ_INPUT_REGISTERS
+                    | WRITE_MULTIPLE_COILS
+                    | WRITE_MULTIPLE_REGISTERS
+                    | READ_WRITE_MULTIPLE_REGISTERS => {
+                        info.address = field(0);
+                        info.quantity = field(2);
+                    }
+                    WRITE_SINGLE_COIL
+                    | WRITE_SINGLE_REGISTER
+                    | MASK_WRITE_REGISTER
+                    | READ_FIFO_QUEUE => info.address = field(0),
+                    _ => (),
+                }
+            }
+            PacketDirection::ServerToClient => {
+                info.msg_type = LogMessageType::Response;
+                info.resp_len = Some(adu.len as u32);
+                if adu.function_code & EXCEPTION_FLAG != 0 {
+                    if let Some(code) = adu.data.first() {
+                        info.exception_code = Some(*code);
+                        info.exception = exception_name(*code).to_owned();
+                        info.status = match *code {
+                            ACKNOWLEDGE => L7ResponseStatus::Ok,
+                            ILLEGAL_FUNCTION | ILLEGAL_DATA_ADDRESS | ILLEGAL_DATA_VALUE => {
+                                L7ResponseStatus::ClientError
+                            }
+                            _ => L7ResponseStatus::ServerError,
+                        };
+                    }
+                }
+            }
+        }
+        info
+    }
+
+    fn merge(&mut self, other: &mut Self) {
+        swap_if!(self, resp_len, is_none, other);
+        swap_if!(self, exception_code, is_none, other);
+        swap_if!(self, exception, is_empty, other);
+        if other.status != L7ResponseStatus::default() {
+            self.status = other.status;
+        }
+        if other.captured_request_byte > 0 {
+            self.captured_request_byte = other.captured_request_byte;
+        }
+        if other.captured_response_byte > 0 {
+            self.captured_response_byte = other.captured_response_byte;
+        }
+        if other.is_on_blacklist {
+            self.is_on_blacklist = other.is_on_blacklist;
+        }
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::Modbus) {
+            self.is_on_blacklist = t.request_type.is_on_blacklist(self.function)
+                || t.endpoint.is_on_blacklist(self.function)
+                || self
+                    .address
+                    .map(|a| t.request_resource.is_on_blacklist(&a.to_string()))
+                    .unwrap_or_default();
+        }
+    }
+}
+
+impl L7ProtocolInfoInterface for ModbusInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.transaction_id as u32)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::ModbusInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::Modbus,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    fn get_endpoint(&self) -> Option<String> {
+        Some(self.function.to_owned())
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl From<ModbusInfo> for L7ProtocolSendLog {
+    fn from(f: ModbusInfo) -> Self {
+        let flags = if f.is_tls {
+            EbpfFlags::TLS.bits()
+        } else {
+            EbpfFlags::NONE.bits()
+        };
+        let mut attributes = vec![KeyVal {
+            key: "modbus_unit_id".to_string(),
+            val: f.unit_id.to_string(),
+        }];
+        if let Some(quantity) = f.quantity {
+            attributes.push(KeyVal {
+                key: "modbus_quantity".to_string(),
+                val: quantity.to_string(),
+            });
+        }
+
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req_len: f.req_len,
+            resp_len: f.resp_len,
+            req: L7Request {
+                req_type: f.function.to_owned(),
+                resource: f.address.map(|a| a.to_string()).unwrap_or_default(),
+                endpoint: f.function.to_owned(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: f.status,
+                code: f.exception_code.map(|c| c as i32),
+                exception: f.exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.transaction_id as u32),
+                attributes: Some(attributes),
+                ..Default::default()
+            }),
+            flags,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ModbusLog {
+    perf_stats: Option<L7PerfStats>,
+    last_is_on_blacklist: bool,
+}
+
+impl L7ProtocolParserInterface for ModbusLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
+            return false;
+        }
+        if param.direction != PacketDirection::ClientToServer {
+            return false;
+        }
+        // the MBAP header is short, requires ADUs to fill the payload exactly
+        let mut payload = payload;
+        while !payload.is_empty() {
+            match Adu::parse(payload) {
+                Some(adu) if adu.len <= payload.len() => payload = &payload[adu.len..],
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+
+        let mut infos = vec![];
+        let mut payload = payload;
+        while let Some(adu) = Adu::parse(payload) {
+            infos.push(ModbusInfo::new(&adu, param.direction));
+            payload = &payload[adu.len.min(payload.len())..];
+        }
+        if infos.is_empty() {
+            return Err(Error::L7ProtocolUnknown);
+        }
+
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match param.direction {
+                    PacketDirection::ClientToServer => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    PacketDirection::ServerToClient => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    }
+                }
+                match info.status {
+                    L7ResponseStatus::ClientError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                    L7ResponseStatus::ServerError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    }
+                    _ => (),
+                }
+                info.cal_rrt(param).map(|rrt| {
+                    info.rrt = rrt;
+                    self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                });
+            }
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+
+        if !param.parse_log {
+            Ok(L7ParseResult::None)
+        } else if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::ModbusInfo(
+                infos.remove(0),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                infos.into_iter().map(L7ProtocolInfo::ModbusInfo).collect(),
+            ))
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::Modbus
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    use crate::{
+        common::{l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn parse(
+        modbus: &mut ModbusLog,
+        cache: &Rc<RefCell<L7PerfCache>>,
+        payload: &[u8],
+        direction: PacketDirection,
+    ) -> Vec<ModbusInfo> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = direction;
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let param = &mut ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        if direction == PacketDirection::ClientToServer {
+            assert!(modbus.check_payload(payload, param));
+        }
+        let infos = match modbus.parse_payload(payload, param).unwrap() {
+            L7ParseResult::Single(i) => vec![i],
+            L7ParseResult::Multi(m) => m,
+            L7ParseResult::None => vec![],
+        };
+        infos
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::ModbusInfo(i) => i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn read_holding_registers() {
+        let mut modbus = ModbusLog::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+
+        // two pipelined requests
+        let req = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, //
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x06, 0x11, 0x06, 0x00, 0x01, 0x00, 0x03,
+        ];
+        let infos = parse(&mut modbus, &cache, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].session_id(), Some(1));
+        assert_eq!(infos[0].function, "ReadHoldingRegisters");
+        assert_eq!(infos[0].unit_id, 0x11);
+        assert_eq!((infos[0].address, infos[0].quantity), (Some(107), Some(3)));
+        assert_eq!(infos[0].req_len, Some(12));
+        assert_eq!(infos[1].function, "WriteSingleRegister");
+        assert_eq!((infos[1].address, infos[1].quantity), (Some(1), None));
+
+        let resp = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x09, 0x11, 0x03, 0x06, 0x02, 0x2b, 0x00, 0x00, 0x00,
+            0x64,
+        ];
+        let infos = parse(&mut modbus, &cache, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos[0].session_id(), Some(1));
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+        assert_eq!(infos[0].resp_len, Some(15));
+
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let param = ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        // wrong length, protocol id or function code
+        assert!(!modbus.check_payload(&req[..11], &param));
+        assert!(!modbus.check_payload(
+            &[0x00, 0x01, 0x00, 0x01, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03],
+            &param
+        ));
+        assert!(!modbus.check_payload(
+            &[0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11, 0x63, 0x00, 0x6b, 0x00, 0x03],
+            &param
+        ));
+    }
+
+    #[test]
+    fn exception() {
+        let mut modbus = ModbusLog::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+
+        let infos = parse(
+            &mut modbus,
+            &cache,
+            &[
+                0x12, 0x34, 0x00, 0x00, 0x00, 0x06, 0x01, 0x01, 0xff, 0x00, 0x00, 0x10,
+            ],
+            PacketDirection::ClientToServer,
+        );
+        let mut req = L7ProtocolInfo::ModbusInfo(infos.into_iter().next().unwrap());
+        let infos = parse(
+            &mut modbus,
+            &cache,
+            &[0x12, 0x34, 0x00, 0x00, 0x00, 0x03, 0x01, 0x81, 0x02],
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].function, "ReadCoils");
+        let mut resp = L7ProtocolInfo::ModbusInfo(infos.into_iter().next().unwrap());
+        req.merge_log(&mut resp).unwrap();
+        let L7ProtocolInfo::ModbusInfo(info) = req else {
+            unreachable!()
+        };
+        assert_eq!(info.exception_code, Some(2));
+        assert_eq!(info.exception, "IllegalDataAddress");
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+        assert_eq!(info.address, Some(0xff00));
+
+        let perf_stats = modbus.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 1);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+}
@@ -0,0 +1,856 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{set_captured_byte, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+    utils::bytes::read_u32_be,
+};
+use public::l7_protocol::L7Protocol;
+
+const KRB5_PVNO: i64 = 5;
+
+// message types, also used as the APPLICATION tag number of each message
+const KRB_AS_REQ: u8 = 10;
+const KRB_AS_REP: u8 = 11;
+const KRB_TGS_REQ: u8 = 12;
+const KRB_TGS_REP: u8 = 13;
+const KRB_ERROR: u8 = 30;
+
+const DER_TAG_INTEGER: u8 = 0x02;
+const DER_TAG_GENERAL_STRING: u8 = 0x1b;
+const DER_TAG_SEQUENCE: u8 = 0x30;
+const DER_CLASS_MASK: u8 = 0xe0;
+const DER_APPLICATION_CONSTRUCTED: u8 = 0x60;
+const DER_CONTEXT_CONSTRUCTED: u8 = 0xa0;
+const DER_TAG_NUMBER_MASK: u8 = 0x1f;
+
+// 4 bytes record mark before each message on TCP, the highest bit is reserved
+const TCP_RECORD_MARK_LEN: usize = 4;
+const TCP_RECORD_MARK_RESERVED: u32 = 0x8000_0000;
+
+const KDC_ERR_PREAUTH_REQUIRED: i32 = 25;
+const KDC_ERR_SVC_UNAVAILABLE: i32 = 29;
+const KRB_ERR_RESPONSE_TOO_BIG: i32 = 52;
+const KRB_ERR_GENERIC: i32 = 60;
+
+// error names used by MIT krb5 and wireshark
+fn error_code_str(code: i32) -> &'static str {
+    match code {
+        0 => "KDC_ERR_NONE",
+        1 => "KRB5KDC_ERR_NAME_EXP",
+        2 => "KRB5KDC_ERR_SERVICE_EXP",
+        3 => "KRB5KDC_ERR_BAD_PVNO",
+        4 => "KRB5KDC_ERR_C_OLD_MAST_KVNO",
+        5 => "KRB5KDC_ERR_S_OLD_MAST_KVNO",
+        6 => "KRB5KDC_ERR_C_PRINCIPAL_UNKNOWN",
+        7 => "KRB5KDC_ERR_S_PRINCIPAL_UNKNOWN",
+        8 => "KRB5KDC_ERR_PRINCIPAL_NOT_UNIQUE",
+        9 => "KRB5KDC_ERR_NULL_KEY",
+        10 => "KRB5KDC_ERR_CANNOT_POSTDATE",
+        11 => "KRB5KDC_ERR_NEVER_VALID",
+        12 => "KRB5KDC_ERR_POLICY",
+        13 => "KRB5KDC_ERR_BADOPTION",
+        14 => "KRB5KDC_ERR_ETYPE_NOSUPP",
+        15 => "KRB5KDC_ERR_SUMTYPE_NOSUPP",
+        16 => "KRB5KDC_ERR_PADATA_TYPE_NOSUPP",
+        17 => "KRB5KDC_ERR_TRTYPE_NOSUPP",
+        18 => "KRB5KDC_ERR_CLIENT_REVOKED",
+        19 => "KRB5KDC_ERR_SERVICE_REVOKED",
+        20 => "KRB5KDC_ERR_TGT_REVOKED",
+        21 => "KRB5KDC_ERR_CLIENT_NOTYET",
+        22 => "KRB5KDC_ERR_SERVICE_NOTYET",
+        23 => "KRB5KDC_ERR_KEY_EXP",
+        24 => "KRB5KDC_ERR_PREAUTH_FAILED",
+        25 => "KRB5KDC_ERR_PREAUTH_REQUIRED",
+        26 => "KRB5KDC_ERR_SERVER_NOMATCH",
+        27 => "KRB5KDC_ERR_MUST_USE_USER2USER",
+        28 => "KRB5KDC_ERR_PATH_NOT_ACCEPTED",
+        29 => "KRB5KDC_ERR_SVC_UNAVAILABLE",
+        31 => "KRB5KRB_AP_ERR_BAD_INTEGRITY",
+        32 => "KRB5KRB_AP_ERR_TKT_EXPIRED",
+        33 => "KRB5KRB_AP_ERR_TKT_NYV",
+        34 => "KRB5KRB_AP_ERR_REPEAT",
+        35 => "KRB5KRB_AP_ERR_NOT_US",
+        36 => "KRB5KRB_AP_ERR_BADMATCH",
+        37 => "KRB5KRB_AP_ERR_SKEW",
+        38 => "KRB5KRB_AP_ERR_BADADDR",
+        39 => "KRB5KRB_AP_ERR_BADVERSION",
+        40 => "KRB5KRB_AP_ERR_MSG_TYPE",
+        41 => "KRB5KRB_AP_ERR_MODIFIED",
+        42 => "KRB5KRB_AP_ERR_BADORDER",
+        44 => "KRB5KRB_AP_ERR_BADKEYVER",
+        45 => "KRB5KRB_AP_ERR_NOKEY",
+        46 => "KRB5KRB_AP_ERR_MUT_FAIL",
+        47 => "KRB5KRB_AP_ERR_BADDIRECTION",
+        48 => "KRB5KRB_AP_ERR_METHOD",
+        49 => "KRB5KRB_AP_ERR_BADSEQ",
+        50 => "KRB5KRB_AP_ERR_INAPP_CKSUM",
+        51 => "KRB5KRB_AP_PATH_NOT_ACCEPTED",
+        52 => "KRB5KRB_ERR_RESPONSE_TOO_BIG",
+        60 => "KRB5KRB_ERR_GENERIC",
+        61 => "KRB5KRB_ERR_FIELD_TOOLONG",
+        62 => "KRB5KDC_ERR_CLIENT_NOT_TRUSTED",
+        63 => "KRB5KDC_ERR_KDC_NOT_TRUSTED",
+        64 => "KRB5KDC_ERR_INVALID_SIG",
+        65 => "KRB5KDC_ERR_DH_KEY_PARAMETERS_NOT_ACCEPTED",
+        68 => "KRB5KDC_ERR_WRONG_REALM",
+        _ => "",
+    }
+}
+
+fn msg_type_str(msg_type: u8) -> &'static str {
+    match msg_type {
+        KRB_AS_REQ => "AS-REQ",
+        KRB_AS_REP => "AS-REP",
+        KRB_TGS_REQ => "TGS-REQ",
+        KRB_TGS_REP => "TGS-REP",
+        KRB_ERROR => "KRB-ERROR",
+        _ => "",
+    }
+}
+
+// returns tag, value and the remaining bytes of a DER encoded TLV, the value is truncated to
+// the available bytes as messages such as AS-REP may be cut by capture length
+fn read_tlv(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, buf) = buf.split_first()?;
+    let (&first, buf) = buf.split_first()?;
+    let (len, buf) = if first & 0x80 == 0 {
+        (first as usize, buf)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || buf.len() < n {
+            return None;
+        }
+        let len = buf[..n].iter().fold(0, |l, b| l << 8 | *b as usize);
+        (len, &buf[n..])
+    };
+    let len = len.min(buf.len());
+    Some((tag, &buf[..len], &buf[len..]))
+}
+
+fn read_int(tag: u8, value: &[u8]) -> Option<i64> {
+    if tag != DER_TAG_INTEGER || value.is_empty() || value.len() > 8 {
+        return None;
+    }
+    let init = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Some(value.iter().fold(init, |n, b| n << 8 | *b as i64))
+}
+
+fn read_string(tag: u8, value: &[u8]) -> Option<String> {
+    if tag != DER_TAG_GENERAL_STRING {
+        return None;
+    }
+    Some(String::from_utf8_lossy(value).into_owned())
+}
+
+// PrincipalName ::= SEQUENCE { name-type [0] Int32, name-string [1] SEQUENCE OF KerberosString }
+fn read_principal(tag: u8, value: &[u8]) -> Option<String> {
+    if tag != DER_TAG_SEQUENCE {
+        return None;
+    }
+    let (_, tag, mut names) = Fields(value).find(|(n, _, _)| *n == 1)?;
+    if tag != DER_TAG_SEQUENCE {
+        return None;
+    }
+    let mut principal = String::new();
+    while let Some((tag, name, remain)) = read_tlv(names) {
+        if !principal.is_empty() {
+            principal.push('/');
+        }
+        principal.push_str(&read_string(tag, name)?);
+        names = remain;
+    }
+    Some(principal)
+}
+
+// iterates over the `[n] EXPLICIT` fields of a SEQUENCE, yields field number, tag and value
+// of the inner TLV
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = (u8, u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tag, value, remain) = read_tlv(self.0)?;
+        if tag & DER_CLASS_MASK != DER_CONTEXT_CONSTRUCTED {
+            self.0 = &[];
+            return None;
+        }
+        self.0 = remain;
+        let (inner_tag, inner, _) = read_tlv(value)?;
+        Some((tag & DER_TAG_NUMBER_MASK, inner_tag, inner))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct KrbMessage {
+    msg_type: u8,
+    realm: String,
+    sname: String,
+    crealm: String,
+    cname: String,
+    error_code: Option<i32>,
+    e_text: String,
+}
+
+impl KrbMessage {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let (tag, value, _) = read_tlv(payload)?;
+        if tag & DER_CLASS_MASK != DER_APPLICATION_CONSTRUCTED {
+            return None;
+        }
+        let (tag, seq, _) = read_tlv(value)?;
+        if tag != DER_TAG_SEQUENCE {
+            return None;
+        }
+        let mut msg = KrbMessage {
+            msg_type: tag_number(payload[0]),
+            ..Default::default()
+        };
+        let (mut pvno, mut msg_type) = (None, None);
+        match msg.msg_type {
+            // KDC-REQ ::= SEQUENCE { pvno [1], msg-type [2], padata [3], req-body [4] }
+            KRB_AS_REQ | KRB_TGS_REQ => {
+                for (n, tag, value) in Fields(seq) {
+                    match n {
+                        1 => pvno = read_int(tag, value),
+                        2 => msg_type = read_int(tag, value),
+                        4 if tag == DER_TAG_SEQUENCE => msg.parse_req_body(value),
+                        _ => (),
+                    }
+                }
+            }
+            // KDC-REP ::= SEQUENCE { pvno [0], msg-type [1], padata [2], crealm [3], cname [4], ... }
+            KRB_AS_REP | KRB_TGS_REP => {
+                for (n, tag, value) in Fields(seq) {
+                    match n {
+                        0 => pvno = read_int(tag, value),
+                        1 => msg_type = read_int(tag, value),
+                        3 => msg.crealm = read_string(tag, value).unwrap_or_default(),
+                        4 => msg.cname = read_principal(tag, value).unwrap_or_default(),
+                        _ if n > 4 => break,
+                        _ => (),
+                    }
+                }
+            }
+            KRB_ERROR => {
+                for (n, tag, value) in Fields(seq) {
+                    match n {
+                        0 => pvno = read_int(tag, value),
+                        1 => msg_type = read_int(tag, value),
+                        6 => msg.error_code = read_int(tag, value).map(|c| c as i32),
+                        7 => msg.crealm = read_string(tag, value).unwrap_or_default(),
+                        8 => msg.cname = read_principal(tag, value).unwrap_or_default(),
+                        9 => msg.realm = read_string(tag, value).unwrap_or_default(),
+                        10 => msg.sname = read_principal(tag, value).unwrap_or_default(),
+                        11 => msg.e_text = read_string(tag, value).unwrap_or_default(),
+                        _ => (),
+                    }
+                }
+            }
+            _ => return None,
+        }
+        if pvno != Some(KRB5_PVNO) || msg_type != Some(msg.msg_type as i64) {
+            return None;
+        }
+        Some(msg)
+    }
+
+    // KDC-REQ-BODY ::= SEQUENCE { kdc-options [0], cname [1], realm [2], sname [3], ... }
+    fn parse_req_body(&mut self, body: &[u8]) {
+        for (n, tag, value) in Fields(body) {
+            match n {
+                1 => self.cname = read_principal(tag, value).unwrap_or_default(),
+                2 => self.realm = read_string(tag, value).unwrap_or_default(),
+                3 => self.sname = read_principal(tag, value).unwrap_or_default(),
+                _ if n > 3 => break,
+                _ => (),
+            }
+        }
+    }
+
+    fn is_request(&self) -> bool {
+        self.msg_type == KRB_AS_REQ || self.msg_type == KRB_TGS_REQ
+    }
+}
+
+fn tag_number(tag: u8) -> u8 {
+    tag & DER_TAG_NUMBER_MASK
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct KerberosInfo {
+    msg_type: LogMessageType,
+
+    // AS-REQ or TGS-REQ
+    #[serde(skip_serializing_if = "value_is_default")]
+    request_type: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    realm: String,
+    // service principal, such as `krbtgt/EXAMPLE.COM`
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    sname: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    cname: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    crealm: String,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    error_code: Option<i32>,
+    #[serde(rename = "response_status")]
+    status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    exception: String,
+    #[serde(rename = "response_result", skip_serializing_if = "value_is_default")]
+    e_text: String,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+    rrt: u64,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for KerberosInfo {
+    fn session_id(&self) -> Option<u32> {
+        None
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::KerberosInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::Kerberos,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.realm.clone()
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.sname.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl KerberosInfo {
+    fn merge(&mut self, other: &mut Self) {
+        // TGS-REQ carries no client name, which is only known from the reply
+        if self.cname.is_empty() {
+            std::mem::swap(&mut self.cname, &mut other.cname);
+            std::mem::swap(&mut self.crealm, &mut other.crealm);
+        }
+        if self.realm.is_empty() {
+            std::mem::swap(&mut self.realm, &mut other.realm);
+        }
+        if self.sname.is_empty() {
+            std::mem::swap(&mut self.sname, &mut other.sname);
+        }
+        self.error_code = other.error_code;
+        self.status = other.status;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+        std::mem::swap(&mut self.e_text, &mut other.e_text);
+        self.captured_response_byte = other.captured_response_byte;
+        self.is_on_blacklist |= other.is_on_blacklist;
+    }
+
+    fn client_principal(&self) -> String {
+        if self.cname.is_empty() {
+            return String::new();
+        }
+        let realm = if self.crealm.is_empty() {
+            &self.realm
+        } else {
+            &self.crealm
+        };
+        if realm.is_empty() {
+            self.cname.clone()
+        } else {
+            format!("{}@{}", self.cname, realm)
+        }
+    }
+
+    fn set_status(&mut self, error_code: i32) {
+        self.error_code = Some(error_code);
+        self.exception = error_code_str(error_code).to_string();
+        self.status = match error_code {
+            // normal steps of the exchange, the client retries with pre-authentication or TCP
+            KDC_ERR_PREAUTH_REQUIRED | KRB_ERR_RESPONSE_TOO_BIG => L7ResponseStatus::Ok,
+            KDC_ERR_SVC_UNAVAILABLE | KRB_ERR_GENERIC => L7ResponseStatus::ServerError,
+            _ => L7ResponseStatus::ClientError,
+        };
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::Kerberos) {
+            self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.sname)
+                || t.request_type.is_on_blacklist(&self.request_type)
+                || t.request_domain.is_on_blacklist(&self.realm);
+        }
+    }
+}
+
+impl From<KrbMessage> for KerberosInfo {
+    fn from(m: KrbMessage) -> Self {
+        let mut info = KerberosInfo {
+            realm: m.realm,
+            sname: m.sname,
+            crealm: m.crealm,
+            cname: m.cname,
+            e_text: m.e_text,
+            ..Default::default()
+        };
+        if m.is_request() {
+            info.msg_type = LogMessageType::Request;
+            info.request_type = msg_type_str(m.msg_type).to_string();
+        } else {
+            info.msg_type = LogMessageType::Response;
+            info.status = L7ResponseStatus::Ok;
+            if let Some(code) = m.error_code {
+                info.set_status(code);
+            }
+        }
+        info
+    }
+}
+
+impl From<KerberosInfo> for L7ProtocolSendLog {
+    fn from(f: KerberosInfo) -> Self {
+        let client_principal = f.client_principal();
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req: L7Request {
+                req_type: f.request_type,
+                domain: f.realm,
+                resource: f.sname,
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: f.status,
+                code: f.error_code,
+                exception: f.exception,
+                result: f.e_text,
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes: if client_principal.is_empty() {
+                    None
+                } else {
+                    Some(vec![KeyVal {
+                        key: "client_principal".to_string(),
+                        val: client_principal,
+                    }])
+                },
+                ..Default::default()
+            }),
+            flags: EbpfFlags::NONE.bits(),
+            ..Default::default()
+        }
+    }
+}
+
+// AS and TGS exchanges with the KDC, AP exchanges are embedded in other protocols and not
+// parsed here
+#[derive(Default)]
+pub struct KerberosLog {
+    perf_stats: Option<L7PerfStats>,
+    last_is_on_blacklist: bool,
+}
+
+impl L7ProtocolParserInterface for KerberosLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() {
+            return false;
+        }
+        let Some(payload) = Self::strip_record_mark(payload, param.l4_protocol) else {
+            return false;
+        };
+        KrbMessage::parse(payload)
+            .map(|m| m.is_request())
+            .unwrap_or_default()
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let msg = Self::strip_record_mark(payload, param.l4_protocol)
+            .and_then(KrbMessage::parse)
+            .ok_or_else(|| Error::L7LogParseFailed {
+                proto: L7Protocol::Kerberos,
+                reason: "invalid kerberos message".into(),
+            })?;
+        let mut info = KerberosInfo::from(msg);
+        set_captured_byte!(info, param);
+        if let Some(config) = param.parse_config {
+            info.set_is_on_blacklist(config);
+        }
+        if !info.is_on_blacklist && !self.last_is_on_blacklist {
+            match info.msg_type {
+                LogMessageType::Request => {
+                    self.perf_stats.as_mut().map(|p| p.inc_req());
+                }
+                _ => {
+                    self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    match info.status {
+                        L7ResponseStatus::ClientError => {
+                            self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                        }
+                        L7ResponseStatus::ServerError => {
+                            self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            info.cal_rrt(param).map(|rrt| {
+                info.rrt = rrt;
+                self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+            });
+        }
+        self.last_is_on_blacklist = info.is_on_blacklist;
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::KerberosInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::Kerberos
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl KerberosLog {
+    fn strip_record_mark(payload: &[u8], l4_protocol: IpProtocol) -> Option<&[u8]> {
+        if l4_protocol != IpProtocol::TCP {
+            return Some(payload);
+        }
+        if payload.len() <= TCP_RECORD_MARK_LEN {
+            return None;
+        }
+        let len = read_u32_be(payload);
+        if len == 0 || len & TCP_RECORD_MARK_RESERVED != 0 {
+            return None;
+        }
+        Some(&payload[TCP_RECORD_MARK_LEN..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut buf = vec![tag];
+        if value.len() < 0x80 {
+            buf.push(value.len() as u8);
+        } else {
+            buf.push(0x82);
+            buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        }
+        buf.extend_from_slice(value);
+        buf
+    }
+
+    fn field(n: u8, value: &[u8]) -> Vec<u8> {
+        tlv(DER_CONTEXT_CONSTRUCTED | n, value)
+    }
+
+    fn int(n: i64) -> Vec<u8> {
+        tlv(DER_TAG_INTEGER, &[n as u8])
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        tlv(DER_TAG_GENERAL_STRING, s.as_bytes())
+    }
+
+    fn principal(names: &[&str]) -> Vec<u8> {
+        let names: Vec<u8> = names.iter().flat_map(|n| string(n)).collect();
+        tlv(
+            DER_TAG_SEQUENCE,
+            &[field(0, &int(1)), field(1, &tlv(DER_TAG_SEQUENCE, &names))].concat(),
+        )
+    }
+
+    fn message(msg_type: u8, fields: &[Vec<u8>]) -> Vec<u8> {
+        tlv(
+            DER_APPLICATION_CONSTRUCTED | msg_type,
+            &tlv(DER_TAG_SEQUENCE, &fields.concat()),
+        )
+    }
+
+    fn kdc_req(msg_type: u8, cname: Option<&[&str]>, realm: &str, sname: &[&str]) -> Vec<u8> {
+        let mut body = vec![field(0, &tlv(0x03, &[0x00, 0x40, 0x81, 0x00, 0x10]))];
+        if let Some(cname) = cname {
+            body.push(field(1, &principal(cname)));
+        }
+        body.push(field(2, &string(realm)));
+        body.push(field(3, &principal(sname)));
+        body.push(field(7, &int(0x1234)));
+        message(
+            msg_type,
+            &[
+                field(1, &int(KRB5_PVNO)),
+                field(2, &int(msg_type as i64)),
+                // PA-ENC-TIMESTAMP with an opaque value
+                field(3, &tlv(DER_TAG_SEQUENCE, &[0x30, 0x03, 0xa1, 0x01, 0x02])),
+                field(4, &tlv(DER_TAG_SEQUENCE, &body.concat())),
+            ],
+        )
+    }
+
+    fn krb_error(error_code: i64, cname: &[&str], realm: &str, sname: &[&str]) -> Vec<u8> {
+        message(
+            KRB_ERROR,
+            &[
+                field(0, &int(KRB5_PVNO)),
+                field(1, &int(KRB_ERROR as i64)),
+                field(4, &tlv(0x18, b"20240101000000Z")),
+                field(5, &int(1)),
+                field(6, &int(error_code)),
+                field(7, &string(realm)),
+                field(8, &principal(cname)),
+                field(9, &string(realm)),
+                field(10, &principal(sname)),
+            ],
+        )
+    }
+
+    fn kdc_rep(msg_type: u8, crealm: &str, cname: &[&str]) -> Vec<u8> {
+        message(
+            msg_type,
+            &[
+                field(0, &int(KRB5_PVNO)),
+                field(1, &int(msg_type as i64)),
+                field(3, &string(crealm)),
+                field(4, &principal(cname)),
+                field(5, &tlv(DER_APPLICATION_CONSTRUCTED | 1, &[0u8; 200])),
+            ],
+        )
+    }
+
+    fn with_record_mark(msg: &[u8]) -> Vec<u8> {
+        [&(msg.len() as u32).to_be_bytes()[..], msg].concat()
+    }
+
+    fn parse(
+        parser: &mut KerberosLog,
+        payload: &[u8],
+        proto: IpProtocol,
+        direction: PacketDirection,
+    ) -> (bool, KerberosInfo) {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = proto;
+        packet.lookup_key.direction = direction;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        let is_kerberos = KerberosLog::default().check_payload(payload, &param);
+        match parser.parse_payload(payload, &param) {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::KerberosInfo(info))) => (is_kerberos, info),
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn as_exchange() {
+        let mut parser = KerberosLog::default();
+        let req = kdc_req(
+            KRB_AS_REQ,
+            Some(&["alice"]),
+            "EXAMPLE.COM",
+            &["krbtgt", "EXAMPLE.COM"],
+        );
+        let (is_kerberos, mut info) = parse(
+            &mut parser,
+            &req,
+            IpProtocol::UDP,
+            PacketDirection::ClientToServer,
+        );
+        assert!(is_kerberos);
+        assert_eq!(info.msg_type, LogMessageType::Request);
+        assert_eq!(info.request_type, "AS-REQ");
+        assert_eq!(info.realm, "EXAMPLE.COM");
+        assert_eq!(info.sname, "krbtgt/EXAMPLE.COM");
+        assert_eq!(info.client_principal(), "alice@EXAMPLE.COM");
+
+        let resp = krb_error(24, &["alice"], "EXAMPLE.COM", &["krbtgt", "EXAMPLE.COM"]);
+        let (is_kerberos, mut resp) = parse(
+            &mut parser,
+            &resp,
+            IpProtocol::UDP,
+            PacketDirection::ServerToClient,
+        );
+        assert!(!is_kerberos);
+        assert_eq!(resp.msg_type, LogMessageType::Response);
+        info.merge(&mut resp);
+        assert_eq!(info.error_code, Some(24));
+        assert_eq!(info.exception, "KRB5KDC_ERR_PREAUTH_FAILED");
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+
+        let perf_stats = parser.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 1);
+        assert_eq!(perf_stats.response_count, 1);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+
+    #[test]
+    fn preauth_required() {
+        let mut parser = KerberosLog::default();
+        let resp = krb_error(25, &["alice"], "EXAMPLE.COM", &["krbtgt", "EXAMPLE.COM"]);
+        let (_, info) = parse(
+            &mut parser,
+            &resp,
+            IpProtocol::UDP,
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(info.exception, "KRB5KDC_ERR_PREAUTH_REQUIRED");
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+
+        let resp = krb_error(29, &["alice"], "EXAMPLE.COM", &["krbtgt", "EXAMPLE.COM"]);
+        let (_, info) = parse(
+            &mut parser,
+            &resp,
+            IpProtocol::UDP,
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+    }
+
+    #[test]
+    fn tgs_exchange_on_tcp() {
+        let mut parser = KerberosLog::default();
+        let req = with_record_mark(&kdc_req(
+            KRB_TGS_REQ,
+            None,
+            "EXAMPLE.COM",
+            &["HTTP", "web.example.com"],
+        ));
+        let (is_kerberos, mut info) = parse(
+            &mut parser,
+            &req,
+            IpProtocol::TCP,
+            PacketDirection::ClientToServer,
+        );
+        assert!(is_kerberos);
+        assert_eq!(info.request_type, "TGS-REQ");
+        assert_eq!(info.sname, "HTTP/web.example.com");
+        assert_eq!(info.client_principal(), "");
+
+        // reply truncated in the middle of the ticket
+        let mut resp = with_record_mark(&kdc_rep(KRB_TGS_REP, "EXAMPLE.COM", &["alice"]));
+        resp.truncate(resp.len() - 100);
+        let (_, mut resp) = parse(
+            &mut parser,
+            &resp,
+            IpProtocol::TCP,
+            PacketDirection::ServerToClient,
+        );
+        info.merge(&mut resp);
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+        assert_eq!(info.error_code, None);
+        assert_eq!(info.client_principal(), "alice@EXAMPLE.COM");
+
+        // missing record mark
+        let req = kdc_req(KRB_TGS_REQ, None, "EXAMPLE.COM", &["HTTP", "web"]);
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        assert!(!KerberosLog::default().check_payload(&req, &param));
+    }
+
+    #[test]
+    fn invalid_message() {
+        // AS-REQ with wrong protocol version
+        let req = message(
+            KRB_AS_REQ,
+            &[field(1, &int(4)), field(2, &int(KRB_AS_REQ as i64))],
+        );
+        assert_eq!(KrbMessage::parse(&req), None);
+        // msg-type mismatch
+        let req = message(
+            KRB_AS_REQ,
+            &[
+                field(1, &int(KRB5_PVNO)),
+                field(2, &int(KRB_TGS_REQ as i64)),
+            ],
+        );
+        assert_eq!(KrbMessage::parse(&req), None);
+        assert_eq!(KrbMessage::parse(&[0x6a, 0x84, 0xff]), None);
+        assert_eq!(KrbMessage::parse(b"GET / HTTP/1.1\r\n"), None);
+    }
+}
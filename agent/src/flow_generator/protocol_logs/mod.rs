@@ -15,35 +15,53 @@
  */
 
 pub mod consts;
+pub(crate) mod diameter;
 pub(crate) mod dns;
 pub(crate) mod fastcgi;
+pub(crate) mod graphql;
 pub(crate) mod http;
+pub(crate) mod industrial;
+pub(crate) mod kerberos;
 pub(crate) mod mq;
+pub(crate) mod nfs;
+pub(crate) mod ntlm;
 mod parser;
 pub mod pb_adapter;
 pub(crate) mod plugin;
+pub(crate) mod protobuf;
+pub(crate) mod quic;
 pub(crate) mod rpc;
+pub(crate) mod smb;
+pub(crate) mod soap;
 pub(crate) mod sql;
 pub(crate) mod tls;
+pub(crate) mod websocket;
 pub use self::http::{check_http_method, parse_v1_headers, HttpInfo, HttpLog};
 use self::pb_adapter::L7ProtocolSendLog;
 
+pub use diameter::{DiameterInfo, DiameterLog};
 pub use dns::{DnsInfo, DnsLog};
+pub use industrial::{ModbusInfo, ModbusLog};
+pub use kerberos::{KerberosInfo, KerberosLog};
 pub use mq::{
-    AmqpInfo, AmqpLog, KafkaInfo, KafkaLog, MqttInfo, MqttLog, NatsInfo, NatsLog, OpenWireInfo,
-    OpenWireLog, PulsarInfo, PulsarLog, ZmtpInfo, ZmtpLog,
+    Amqp10Info, AmqpInfo, AmqpLog, KafkaInfo, KafkaLog, MqttInfo, MqttLog, NatsInfo, NatsLog,
+    OpenWireInfo, OpenWireLog, PulsarInfo, PulsarLog, StompInfo, StompLog, ZmtpInfo, ZmtpLog,
 };
+pub use nfs::{NfsInfo, NfsLog};
 use num_enum::TryFromPrimitive;
 pub use parser::{AppProto, MetaAppProto, PseudoAppProto, SessionAggregator, SLOT_WIDTH};
+pub use quic::{QuicInfo, QuicLog};
 pub use rpc::{
     decode_new_rpc_trace_context_with_type, BrpcInfo, BrpcLog, DubboInfo, DubboLog, SofaRpcInfo,
     SofaRpcLog, SomeIpInfo, SomeIpLog, TarsInfo, TarsLog, SOFA_NEW_RPC_TRACE_CTX_KEY,
 };
+pub use smb::{SmbInfo, SmbLog};
 pub use sql::{
     MemcachedInfo, MemcachedLog, MongoDBInfo, MongoDBLog, MysqlInfo, MysqlLog, OracleInfo,
     OracleLog, PostgreInfo, PostgresqlLog, RedisInfo, RedisLog,
 };
 pub use tls::{TlsInfo, TlsLog};
+pub use websocket::WebSocketInfo;
 
 #[cfg(test)]
 pub use self::plugin::wasm::{get_wasm_parser, WasmLog};
@@ -164,6 +182,8 @@ pub struct AppProtoLogsBaseInfo {
     pub tap_type: CaptureNetworkType,
     pub tap_side: TapSide,
     pub biz_type: u8,
+    // number of logs this log stands for after sampling, 1 if not sampled
+    pub sample_rate: u32,
     #[serde(flatten)]
     pub head: AppProtoHead,
 
@@ -221,6 +241,8 @@ pub struct AppProtoLogsBaseInfo {
     pub syscall_cap_seq_0: u32,
     #[serde(skip_serializing_if = "value_is_default")]
     pub syscall_cap_seq_1: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub unix_socket_path: String,
 
     pub protocol: IpProtocol,
     #[serde(skip)]
@@ -315,6 +337,8 @@ impl From<AppProtoLogsBaseInfo> for flow_log::AppProtoLogsBaseInfo {
             pod_id_0: f.pod_id_0,
             pod_id_1: f.pod_id_1,
             biz_type: f.biz_type as u32,
+            unix_socket_path: f.unix_socket_path,
+            sample_rate: f.sample_rate,
         }
     }
 }
@@ -405,6 +429,10 @@ impl Sendable for BoxAppProtoLogsData {
         "l7_flow_log"
     }
 
+    fn flow_id(&self) -> Option<u64> {
+        Some(self.0.base_info.flow_id)
+    }
+
     fn message_type(&self) -> SendMessageType {
         SendMessageType::ProtocolLog
     }
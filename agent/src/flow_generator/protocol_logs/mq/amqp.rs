@@ -20,6 +20,7 @@ use serde_json::{value::Value, Map, Number};
 const AMQPHEADER: &[u8] = b"AMQP\x00\x00\x09\x01";
 const AMQPVERSION: &[u8] = b"v0.9.1";
 
+use super::amqp10::Amqp10Log;
 use crate::{
     common::{
         enums::IpProtocol,
@@ -717,6 +718,8 @@ pub struct AmqpLog {
     perf_stats: Option<L7PerfStats>,
     vhost: Option<String>,
     last_is_on_blacklist: bool,
+    // set once the flow is found to be AMQP 1.0
+    amqp10: Option<Amqp10Log>,
 }
 
 impl From<AmqpInfo> for L7ProtocolSendLog {
@@ -836,10 +839,16 @@ impl L7ProtocolParserInterface for AmqpLog {
         if param.l4_protocol != IpProtocol::TCP {
             return false;
         }
-        return payload.starts_with(AMQPHEADER);
+        return payload.starts_with(AMQPHEADER) || Amqp10Log::check_payload(payload);
     }
 
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.amqp10.is_none() && Amqp10Log::check_payload(payload) {
+            self.amqp10 = Some(Amqp10Log::default());
+        }
+        if let Some(amqp10) = self.amqp10.as_mut() {
+            return amqp10.parse_payload(payload, param);
+        }
         if self.perf_stats.is_none() && param.parse_perf {
             self.perf_stats = Some(L7PerfStats::default())
         };
@@ -997,10 +1006,14 @@ impl L7ProtocolParserInterface for AmqpLog {
         s.last_is_on_blacklist = self.last_is_on_blacklist;
         s.vhost = self.vhost.take();
         s.perf_stats = self.perf_stats.take();
+        s.amqp10 = self.amqp10.take();
         *self = s;
     }
 
     fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        if let Some(amqp10) = self.amqp10.as_mut() {
+            return amqp10.perf_stats();
+        }
         self.perf_stats.take()
     }
 
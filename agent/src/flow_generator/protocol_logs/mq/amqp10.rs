@@ -0,0 +1,924 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// AMQP 1.0 as used by Azure Service Bus, ActiveMQ Artemis and Qpid. It is not wire compatible
+// with AMQP 0-9-1 but shares the same protocol, AmqpLog hands the flow over to Amqp10Log once
+// an AMQP 1.0 header or frame is seen.
+// Reference: https://docs.oasis-open.org/amqp/core/v1.0/os/amqp-core-transport-v1.0-os.html
+
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+use serde::Serialize;
+
+use crate::{
+    common::{
+        flow::{L7PerfStats, L7Protocol, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::{
+        error::{Error, Result},
+        protocol_logs::{
+            pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
+            set_captured_byte, swap_if, value_is_default, AppProtoHead, L7ResponseStatus,
+            LogMessageType,
+        },
+    },
+    utils::bytes::{read_u16_be, read_u32_be, read_u64_be},
+};
+
+const VERSION: &str = "1.0";
+
+// "AMQP" followed by protocol id (0 AMQP, 2 TLS, 3 SASL) and version 1.0.0
+const PROTOCOL_HEADER_LEN: usize = 8;
+const FRAME_HEADER_LEN: usize = 8;
+const FRAME_TYPE_AMQP: u8 = 0;
+const FRAME_TYPE_SASL: u8 = 1;
+
+const OPEN: u64 = 0x10;
+const BEGIN: u64 = 0x11;
+const ATTACH: u64 = 0x12;
+const FLOW: u64 = 0x13;
+const TRANSFER: u64 = 0x14;
+const DISPOSITION: u64 = 0x15;
+const DETACH: u64 = 0x16;
+const END: u64 = 0x17;
+const CLOSE: u64 = 0x18;
+const SASL_MECHANISMS: u64 = 0x40;
+const SASL_INIT: u64 = 0x41;
+const SASL_OUTCOME: u64 = 0x44;
+
+const ERROR: u64 = 0x1d;
+const RECEIVED: u64 = 0x23;
+const ACCEPTED: u64 = 0x24;
+const REJECTED: u64 = 0x25;
+const RELEASED: u64 = 0x26;
+const MODIFIED: u64 = 0x27;
+const SOURCE: u64 = 0x28;
+const TARGET: u64 = 0x29;
+
+// set in session ids of deliveries sent by the server, delivery ids of both directions
+// are numbered independently
+const SERVER_DELIVERY_FLAG: u32 = 1 << 31;
+
+fn performative_name(code: u64) -> Option<&'static str> {
+    let name = match code {
+        OPEN => "open",
+        BEGIN => "begin",
+        ATTACH => "attach",
+        FLOW => "flow",
+        TRANSFER => "transfer",
+        DISPOSITION => "disposition",
+        DETACH => "detach",
+        END => "end",
+        CLOSE => "close",
+        SASL_MECHANISMS => "sasl-mechanisms",
+        SASL_INIT => "sasl-init",
+        0x42 => "sasl-challenge",
+        0x43 => "sasl-response",
+        SASL_OUTCOME => "sasl-outcome",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value<'a> {
+    Null,
+    Bool(bool),
+    Uint(u64),
+    // string, symbol or binary
+    Bytes(&'a [u8]),
+    List(List<'a>),
+    // descriptor code with the encoded value
+    Described(u64, &'a [u8]),
+    Other,
+}
+
+impl<'a> Value<'a> {
+    fn as_str(&self) -> &'a str {
+        match self {
+            Value::Bytes(b) => std::str::from_utf8(b).unwrap_or_default(),
+            _ => "",
+        }
+    }
+
+    fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::Uint(v) => u32::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> bool {
+        matches!(self, Value::Bool(true))
+    }
+
+    // fields of a described list such as performatives, terminus, delivery states and errors
+    fn as_described_list(&self) -> Option<(u64, List<'a>)> {
+        let Value::Described(code, value) = self else {
+            return None;
+        };
+        match decode(value)?.0 {
+            Value::List(list) => Some((*code, list)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct List<'a> {
+    count: usize,
+    items: &'a [u8],
+}
+
+impl<'a> List<'a> {
+    // trailing null fields may be omitted by the encoder
+    fn field(&self, index: usize) -> Value<'a> {
+        let mut items = self.items;
+        for i in 0..self.count.min(index + 1) {
+            let Some((value, len)) = decode(items) else {
+                break;
+            };
+            if i == index {
+                return value;
+            }
+            items = &items[len..];
+        }
+        Value::Null
+    }
+}
+
+fn sized(body: &[u8], width: usize) -> Option<usize> {
+    match width {
+        1 => body.first().map(|b| *b as usize),
+        _ => body.get(..4).map(|b| read_u32_be(b) as usize),
+    }
+}
+
+// decodes the value at the start of buf, returns the value and its encoded length
+fn decode(buf: &[u8]) -> Option<(Value, usize)> {
+    let code = *buf.first()?;
+    let body = &buf[1..];
+    let fixed = |width: usize| body.get(..width);
+    let value = match code {
+        0x00 => {
+            // nested descriptors are not used by the specification
+            if body.first() == Some(&0x00) {
+                return None;
+            }
+            let (descriptor, len) = decode(body)?;
+            let descriptor = match descriptor {
+                Value::Uint(c) => c,
+                _ => u64::MAX,
+            };
+            let rest = &body[len..];
+            if rest.first() == Some(&0x00) {
+                return None;
+            }
+            let (_, value_len) = decode(rest)?;
+            return Some((
+                Value::Described(descriptor, &rest[..value_len]),
+                1 + len + value_len,
+            ));
+        }
+        0x40 => (Value::Null, 1),
+        0x41 => (Value::Bool(true), 1),
+        0x42 => (Value::Bool(false), 1),
+        0x43 | 0x44 => (Value::Uint(0), 1),
+        0x56 => (Value::Bool(fixed(1)?[0] == 1), 2),
+        0x50 | 0x52 | 0x53 => (Value::Uint(fixed(1)?[0] as u64), 2),
+        0x60 => (Value::Uint(read_u16_be(fixed(2)?) as u64), 3),
+        0x70 => (Value::Uint(read_u32_be(fixed(4)?) as u64), 5),
+        0x80 => (Value::Uint(read_u64_be(fixed(8)?)), 9),
+        0x51 | 0x54 | 0x55 => (Value::Other, 2),
+        0x61 => (Value::Other, 3),
+        0x71..=0x74 => (Value::Other, 5),
+        0x81..=0x84 => (Value::Other, 9),
+        0x94 | 0x98 => (Value::Other, 17),
+        0xa0 | 0xa1 | 0xa3 | 0xb0 | 0xb1 | 0xb3 => {
+            let width = if code < 0xb0 { 1 } else { 4 };
+            let len = sized(body, width)?;
+            let bytes = body.get(width..width.checked_add(len)?)?;
+            (Value::Bytes(bytes), 1 + width + len)
+        }
+        0x45 => (
+            Value::List(List {
+                count: 0,
+                items: &[],
+            }),
+            1,
+        ),
+        0xc0 | 0xd0 => {
+            let width = if code == 0xc0 { 1 } else { 4 };
+            let size = sized(body, width)?;
+            let count = sized(body.get(width..)?, width)?;
+            let items = body.get(2 * width..width.checked_add(size)?)?;
+            (Value::List(List { count, items }), 1 + width + size)
+        }
+        // maps and arrays are skipped
+        0xc1 | 0xe0 => (Value::Other, 2 + sized(body, 1)?),
+        0xd1 | 0xf0 => (Value::Other, 5usize.checked_add(sized(body, 4)?)?),
+        _ => return None,
+    };
+    if value.1 > buf.len() {
+        return None;
+    }
+    Some(value)
+}
+
+// error := condition symbol, description string, info map
+fn decode_error(value: &Value) -> Option<String> {
+    let fields = match value.as_described_list()? {
+        (ERROR, fields) => fields,
+        _ => return None,
+    };
+    let condition = fields.field(0).as_str();
+    let description = fields.field(1).as_str();
+    match (condition.is_empty(), description.is_empty()) {
+        (true, true) => None,
+        (false, true) => Some(condition.to_owned()),
+        (true, false) => Some(description.to_owned()),
+        (false, false) => Some(format!("{}: {}", condition, description)),
+    }
+}
+
+// address of a source or target terminus
+fn terminus_address(value: &Value) -> String {
+    match value.as_described_list() {
+        Some((SOURCE | TARGET, fields)) => fields.field(0).as_str().to_owned(),
+        _ => String::new(),
+    }
+}
+
+struct Frame<'a> {
+    frame_type: u8,
+    channel: u16,
+    performative: u64,
+    fields: List<'a>,
+    // frame size, or to the end of a truncated payload
+    len: usize,
+}
+
+impl<'a> Frame<'a> {
+    // returns Ok(None) for empty frames used as heartbeats
+    fn parse(payload: &'a [u8]) -> Option<Option<Self>> {
+        let header = payload.get(..FRAME_HEADER_LEN)?;
+        let size = read_u32_be(&header[..4]) as usize;
+        let doff = header[4] as usize * 4;
+        let frame_type = header[5];
+        if size < FRAME_HEADER_LEN
+            || doff < FRAME_HEADER_LEN
+            || doff > size
+            || (frame_type != FRAME_TYPE_AMQP && frame_type != FRAME_TYPE_SASL)
+        {
+            return None;
+        }
+        let len = size.min(payload.len());
+        if size == doff {
+            return Some(None);
+        }
+        // performatives are described lists, transfer frames carry the message after it
+        let (value, _) = decode(payload.get(doff..len)?)?;
+        let (performative, fields) = value.as_described_list()?;
+        let name = performative_name(performative)?;
+        if (frame_type == FRAME_TYPE_SASL) != name.starts_with("sasl") {
+            return None;
+        }
+        Some(Some(Frame {
+            frame_type,
+            channel: read_u16_be(&header[6..8]),
+            performative,
+            fields,
+            len,
+        }))
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Amqp10Info {
+    msg_type: LogMessageType,
+    #[serde(skip)]
+    is_tls: bool,
+    rrt: u64,
+
+    #[serde(rename = "request_type")]
+    performative: &'static str,
+    channel: u16,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    hostname: String,
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    link_name: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delivery_id: Option<u32>,
+    // delivery sent by the server, either a transfer from it or a disposition for one
+    #[serde(skip)]
+    server_delivery: bool,
+    req_len: Option<u32>,
+    resp_len: Option<u32>,
+
+    #[serde(rename = "response_status")]
+    status: L7ResponseStatus,
+    #[serde(rename = "response_result", skip_serializing_if = "value_is_default")]
+    outcome: &'static str,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    error: String,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+    #[serde(skip)]
+    endpoint: Option<String>,
+}
+
+impl Amqp10Info {
+    fn set_error(&mut self, value: &Value, direction: PacketDirection) {
+        if let Some(error) = decode_error(value) {
+            self.error = error;
+            self.status = match direction {
+                PacketDirection::ClientToServer => L7ResponseStatus::ClientError,
+                PacketDirection::ServerToClient => L7ResponseStatus::ServerError,
+            };
+        }
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::AMQP) {
+            self.is_on_blacklist = t.request_type.is_on_blacklist(self.performative)
+                || t.request_domain.is_on_blacklist(&self.hostname)
+                || t.request_resource.is_on_blacklist(&self.link_name)
+                || self
+                    .endpoint
+                    .as_ref()
+                    .map(|p| t.endpoint.is_on_blacklist(p))
+                    .unwrap_or_default();
+        }
+    }
+}
+
+impl From<Amqp10Info> for L7ProtocolSendLog {
+    fn from(info: Amqp10Info) -> Self {
+        let flags = match info.is_tls {
+            true => EbpfFlags::TLS.bits(),
+            false => EbpfFlags::NONE.bits(),
+        };
+        L7ProtocolSendLog {
+            captured_request_byte: info.captured_request_byte,
+            captured_response_byte: info.captured_response_byte,
+            flags,
+            version: Some(VERSION.to_string()),
+            req_len: info.req_len,
+            resp_len: info.resp_len,
+            req: L7Request {
+                req_type: info.performative.to_string(),
+                domain: info.hostname,
+                resource: info.link_name,
+                endpoint: info.endpoint.unwrap_or_default(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: info.status,
+                result: info.outcome.to_string(),
+                exception: info.error,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: info.delivery_id,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl L7ProtocolInfoInterface for Amqp10Info {
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    // unsettled transfers are matched with dispositions by delivery id
+    fn session_id(&self) -> Option<u32> {
+        self.delivery_id.map(|id| match self.server_delivery {
+            true => id | SERVER_DELIVERY_FLAG,
+            false => id & !SERVER_DELIVERY_FLAG,
+        })
+    }
+
+    fn get_endpoint(&self) -> Option<String> {
+        self.endpoint.clone()
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let (req, L7ProtocolInfo::Amqp10Info(rsp)) = (self, other) {
+            req.resp_len = rsp.resp_len;
+            req.captured_response_byte = rsp.captured_response_byte;
+            req.status = rsp.status;
+            req.outcome = rsp.outcome;
+            swap_if!(req, error, is_empty, rsp);
+            swap_if!(req, hostname, is_empty, rsp);
+            swap_if!(req, link_name, is_empty, rsp);
+            swap_if!(req, address, is_empty, rsp);
+            swap_if!(req, endpoint, is_none, rsp);
+            if rsp.is_on_blacklist {
+                req.is_on_blacklist = rsp.is_on_blacklist;
+            }
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::AMQP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.hostname.clone()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+pub struct Amqp10Log {
+    perf_stats: Option<L7PerfStats>,
+    // from open and sasl-init
+    hostname: String,
+    // link name and address of attached links by (attached by client, channel, handle)
+    links: LruCache<(bool, u16, u32), (String, String)>,
+    last_is_on_blacklist: bool,
+}
+
+impl Default for Amqp10Log {
+    fn default() -> Self {
+        Self {
+            perf_stats: None,
+            hostname: String::new(),
+            links: LruCache::new(NonZeroUsize::new(Self::MAX_LINKS_PER_FLOW).unwrap()),
+            last_is_on_blacklist: false,
+        }
+    }
+}
+
+impl Amqp10Log {
+    const MAX_LINKS_PER_FLOW: usize = 64;
+
+    pub fn check_payload(payload: &[u8]) -> bool {
+        if payload.len() >= PROTOCOL_HEADER_LEN && payload.starts_with(b"AMQP") {
+            return matches!(payload[4..PROTOCOL_HEADER_LEN], [0 | 2 | 3, 1, 0, 0]);
+        }
+        matches!(Frame::parse(payload), Some(Some(_)))
+    }
+
+    fn new_info(&mut self, frame: &Frame, direction: PacketDirection) -> Option<Amqp10Info> {
+        let from_client = direction == PacketDirection::ClientToServer;
+        let fields = &frame.fields;
+        let mut info = Amqp10Info {
+            performative: performative_name(frame.performative)?,
+            channel: frame.channel,
+            msg_type: match direction {
+                PacketDirection::ClientToServer => LogMessageType::Request,
+                PacketDirection::ServerToClient => LogMessageType::Response,
+            },
+            ..Default::default()
+        };
+        match frame.performative {
+            OPEN => info.hostname = fields.field(1).as_str().to_owned(),
+            BEGIN => (),
+            ATTACH => {
+                let handle = fields.field(1).as_u32()?;
+                // role false is sender, which publishes to the target
+                let address = match fields.field(2).as_bool() {
+                    true => terminus_address(&fields.field(5)),
+                    false => terminus_address(&fields.field(6)),
+                };
+                info.link_name = fields.field(0).as_str().to_owned();
+                info.address = address.clone();
+                self.links.put(
+                    (from_client, frame.channel, handle),
+                    (info.link_name.clone(), address),
+                );
+            }
+            DETACH => {
+                if let Some(handle) = fields.field(0).as_u32() {
+                    if let Some((name, address)) =
+                        self.links.peek(&(from_client, frame.channel, handle))
+                    {
+                        info.link_name = name.clone();
+                        info.address = address.clone();
+                    }
+                }
+                info.set_error(&fields.field(2), direction);
+            }
+            END | CLOSE => info.set_error(&fields.field(0), direction),
+            TRANSFER => {
+                let handle = fields.field(0).as_u32()?;
+                // continuation frames of a multi-frame delivery omit the delivery id
+                info.delivery_id = Some(fields.field(1).as_u32()?);
+                info.server_delivery = !from_client;
+                info.msg_type = match fields.field(4).as_bool() {
+                    true => LogMessageType::Session,
+                    false => LogMessageType::Request,
+                };
+                if let Some((name, address)) = self.links.get(&(from_client, frame.channel, handle))
+                {
+                    info.link_name = name.clone();
+                    info.address = address.clone();
+                }
+            }
+            DISPOSITION => {
+                // role true is receiver, which settles deliveries sent by its peer
+                let receiver = fields.field(0).as_bool();
+                info.server_delivery = receiver == from_client;
+                info.delivery_id = Some(fields.field(1).as_u32()?);
+                info.msg_type = LogMessageType::Response;
+                match fields.field(4).as_described_list() {
+                    Some((ACCEPTED, _)) => info.outcome = "accepted",
+                    Some((REJECTED, state)) => {
+                        info.outcome = "rejected";
+                        info.status = L7ResponseStatus::ClientError;
+                        info.error = decode_error(&state.field(0)).unwrap_or_default();
+                    }
+                    Some((RELEASED, _)) => info.outcome = "released",
+                    Some((MODIFIED, _)) => info.outcome = "modified",
+                    Some((RECEIVED, _)) => info.outcome = "received",
+                    _ => (),
+                }
+            }
+            SASL_INIT => {
+                info.msg_type = LogMessageType::Request;
+                info.hostname = fields.field(2).as_str().to_owned();
+            }
+            SASL_OUTCOME => {
+                info.msg_type = LogMessageType::Response;
+                info.status = match fields.field(0) {
+                    Value::Uint(0) => L7ResponseStatus::Ok,
+                    Value::Uint(1) => L7ResponseStatus::ClientError,
+                    _ => L7ResponseStatus::ServerError,
+                };
+            }
+            // flow control and the other sasl exchanges are not logged
+            _ => return None,
+        }
+        if !info.address.is_empty() {
+            info.endpoint = Some(info.address.clone());
+        } else if !info.link_name.is_empty() {
+            info.endpoint = Some(info.link_name.clone());
+        }
+        Some(info)
+    }
+
+    pub fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+
+        let mut vec = Vec::new();
+        let mut payload = payload;
+        let mut parsed = false;
+        if payload.starts_with(b"AMQP") && payload.len() >= PROTOCOL_HEADER_LEN {
+            payload = &payload[PROTOCOL_HEADER_LEN..];
+            parsed = true;
+        }
+        while !payload.is_empty() {
+            let Some(frame) = Frame::parse(payload) else {
+                break;
+            };
+            parsed = true;
+            let Some(frame) = frame else {
+                payload = &payload[FRAME_HEADER_LEN..];
+                continue;
+            };
+            if let Some(mut info) = self.new_info(&frame, param.direction) {
+                match info.msg_type {
+                    LogMessageType::Request => info.req_len = Some(frame.len as u32),
+                    LogMessageType::Response => info.resp_len = Some(frame.len as u32),
+                    _ => (),
+                }
+                vec.push(info);
+            }
+            if frame.frame_type == FRAME_TYPE_SASL && frame.performative == SASL_OUTCOME {
+                // the amqp protocol header follows a successful sasl exchange
+                if let Some(rest) = payload.get(frame.len..) {
+                    if rest.starts_with(b"AMQP") && rest.len() >= PROTOCOL_HEADER_LEN {
+                        payload = &rest[PROTOCOL_HEADER_LEN..];
+                        continue;
+                    }
+                }
+            }
+            payload = &payload[frame.len..];
+        }
+        if !parsed {
+            return Err(Error::L7ProtocolUnknown);
+        }
+
+        for info in vec.iter_mut() {
+            if !info.hostname.is_empty() {
+                self.hostname = info.hostname.clone();
+            } else {
+                info.hostname = self.hostname.clone();
+            }
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match param.direction {
+                    PacketDirection::ClientToServer => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    PacketDirection::ServerToClient => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    }
+                }
+                match info.status {
+                    L7ResponseStatus::ClientError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                    L7ResponseStatus::ServerError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    }
+                    _ => (),
+                }
+                if info.msg_type != LogMessageType::Session {
+                    info.cal_rrt(param).map(|rrt| {
+                        info.rrt = rrt;
+                        self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                    });
+                }
+            }
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+
+        if !param.parse_log || vec.is_empty() {
+            Ok(L7ParseResult::None)
+        } else if vec.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::Amqp10Info(
+                vec.remove(0),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                vec.into_iter().map(L7ProtocolInfo::Amqp10Info).collect(),
+            ))
+        }
+    }
+
+    pub fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    use crate::{
+        common::{enums::IpProtocol, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn parse(
+        amqp: &mut Amqp10Log,
+        cache: &Rc<RefCell<L7PerfCache>>,
+        payload: &[u8],
+        direction: PacketDirection,
+    ) -> Vec<Amqp10Info> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = direction;
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let param = &mut ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        let infos = match amqp.parse_payload(payload, param).unwrap() {
+            L7ParseResult::Single(i) => vec![i],
+            L7ParseResult::Multi(m) => m,
+            L7ParseResult::None => vec![],
+        };
+        infos
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::Amqp10Info(i) => i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn str8(code: u8, s: &str) -> Vec<u8> {
+        [&[code, s.len() as u8][..], s.as_bytes()].concat()
+    }
+
+    fn list(fields: &[&[u8]]) -> Vec<u8> {
+        let items = fields.concat();
+        [
+            &[0xc0, items.len() as u8 + 1, fields.len() as u8][..],
+            &items,
+        ]
+        .concat()
+    }
+
+    fn described(code: u8, fields: &[&[u8]]) -> Vec<u8> {
+        [&[0x00, 0x53, code][..], &list(fields)].concat()
+    }
+
+    fn frame(frame_type: u8, performative: u8, fields: &[&[u8]], message: &[u8]) -> Vec<u8> {
+        let body = [&described(performative, fields)[..], message].concat();
+        let size = (FRAME_HEADER_LEN + body.len()) as u32;
+        [&size.to_be_bytes()[..], &[2, frame_type, 0, 1], &body].concat()
+    }
+
+    #[test]
+    fn parse_link_and_deliveries() {
+        let mut amqp = Amqp10Log::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+
+        let sasl_init = [
+            &b"AMQP\x03\x01\x00\x00"[..],
+            &frame(
+                FRAME_TYPE_SASL,
+                SASL_INIT as u8,
+                &[
+                    &str8(0xa3, "PLAIN"),
+                    &str8(0xa0, "\0user\0pass"),
+                    &str8(0xa1, "bus.example.com"),
+                ],
+                &[],
+            ),
+        ]
+        .concat();
+        assert!(Amqp10Log::check_payload(&sasl_init));
+        assert!(Amqp10Log::check_payload(&sasl_init[8..]));
+        assert!(!Amqp10Log::check_payload(b"AMQP\x00\x00\x09\x01"));
+        assert!(!Amqp10Log::check_payload(b"\x01\x00\x00\x00\x00\x00\x05"));
+
+        let infos = parse(
+            &mut amqp,
+            &cache,
+            &sasl_init,
+            PacketDirection::ClientToServer,
+        );
+        assert_eq!(infos[0].performative, "sasl-init");
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].hostname, "bus.example.com");
+
+        let sasl_outcome = [
+            &b"AMQP\x03\x01\x00\x00"[..],
+            &frame(
+                FRAME_TYPE_SASL,
+                SASL_MECHANISMS as u8,
+                &[&str8(0xa3, "PLAIN")],
+                &[],
+            ),
+            &frame(FRAME_TYPE_SASL, SASL_OUTCOME as u8, &[&[0x50, 0]], &[]),
+            &b"AMQP\x00\x01\x00\x00"[..],
+        ]
+        .concat();
+        let infos = parse(
+            &mut amqp,
+            &cache,
+            &sasl_outcome,
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Response);
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+
+        // open, begin and a sender link publishing to "orders"
+        let target = described(TARGET as u8, &[&str8(0xa1, "orders")]);
+        let attach = [
+            &b"AMQP\x00\x01\x00\x00"[..],
+            &frame(
+                FRAME_TYPE_AMQP,
+                OPEN as u8,
+                &[&str8(0xa1, "c1"), &str8(0xa1, "bus.example.com")],
+                &[],
+            ),
+            &frame(FRAME_TYPE_AMQP, BEGIN as u8, &[&[0x40], &[0x43]], &[]),
+            &frame(
+                FRAME_TYPE_AMQP,
+                ATTACH as u8,
+                &[
+                    &str8(0xa1, "sender-1"),
+                    &[0x43],
+                    &[0x42],
+                    &[0x40],
+                    &[0x40],
+                    &[0x40],
+                    &target,
+                ],
+                &[],
+            ),
+        ]
+        .concat();
+        let infos = parse(&mut amqp, &cache, &attach, PacketDirection::ClientToServer);
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[2].performative, "attach");
+        assert_eq!(infos[2].link_name, "sender-1");
+        assert_eq!(infos[2].endpoint.as_deref(), Some("orders"));
+
+        // an unsettled transfer followed by a heartbeat and a flow frame
+        let transfer = [
+            &frame(
+                FRAME_TYPE_AMQP,
+                TRANSFER as u8,
+                &[&[0x43], &[0x52, 5], &str8(0xa0, "t"), &[0x43], &[0x42]],
+                b"\x00\x53\x77\xa1\x05hello",
+            ),
+            &b"\x00\x00\x00\x08\x02\x00\x00\x00"[..],
+            &frame(FRAME_TYPE_AMQP, FLOW as u8, &[&[0x43]], &[]),
+        ]
+        .concat();
+        let infos = parse(
+            &mut amqp,
+            &cache,
+            &transfer,
+            PacketDirection::ClientToServer,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].link_name, "sender-1");
+        assert_eq!(infos[0].endpoint.as_deref(), Some("orders"));
+        assert_eq!(infos[0].hostname, "bus.example.com");
+        assert_eq!(infos[0].session_id(), Some(5));
+
+        // the broker rejects the delivery as receiver
+        let error = described(
+            ERROR as u8,
+            &[&str8(0xa3, "amqp:not-allowed"), &str8(0xa1, "bad")],
+        );
+        let rejected = described(REJECTED as u8, &[&error]);
+        let disposition = frame(
+            FRAME_TYPE_AMQP,
+            DISPOSITION as u8,
+            &[&[0x41], &[0x52, 5], &[0x40], &[0x41], &rejected],
+            &[],
+        );
+        let infos = parse(
+            &mut amqp,
+            &cache,
+            &disposition,
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].msg_type, LogMessageType::Response);
+        assert_eq!(infos[0].session_id(), Some(5));
+        assert_eq!(infos[0].outcome, "rejected");
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].error, "amqp:not-allowed: bad");
+
+        // deliveries sent by the broker are correlated separately
+        let transfer = frame(
+            FRAME_TYPE_AMQP,
+            TRANSFER as u8,
+            &[&[0x43], &[0x52, 5], &[0x40], &[0x43], &[0x41]],
+            &[],
+        );
+        let infos = parse(
+            &mut amqp,
+            &cache,
+            &transfer,
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].msg_type, LogMessageType::Session);
+        assert_eq!(infos[0].session_id(), Some(5 | SERVER_DELIVERY_FLAG));
+
+        let perf_stats = amqp.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 5);
+        assert_eq!(perf_stats.response_count, 3);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+}
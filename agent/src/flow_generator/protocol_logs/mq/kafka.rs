@@ -14,8 +14,9 @@
  * limitations under the License.
  */
 
-use std::num::NonZeroUsize;
+use std::{collections::HashMap, num::NonZeroUsize};
 
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use lru::LruCache;
 use serde::Serialize;
 
@@ -45,9 +46,12 @@ use crate::{
 
 const KAFKA_PRODUCE: u16 = 0;
 const KAFKA_FETCH: u16 = 1;
+const KAFKA_OFFSET_COMMIT: u16 = 8;
+const KAFKA_OFFSET_FETCH: u16 = 9;
 const KAFKA_JOIN_GROUP: u16 = 11;
 const KAFKA_LEAVE_GROUP: u16 = 13;
 const KAFKA_SYNC_GROUP: u16 = 14;
+const KAFKA_API_VERSIONS: u16 = 18;
 
 #[derive(Serialize, Debug, Default, Clone)]
 pub struct KafkaInfo {
@@ -71,12 +75,17 @@ pub struct KafkaInfo {
     pub api_key: u16,
     #[serde(skip)]
     pub client_id: String,
-    // Extract only from KAFKA_PRODUCE and KAFKA_FETCH
+    // Extract only from KAFKA_PRODUCE, KAFKA_FETCH and KAFKA_OFFSET_COMMIT
     #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
     pub topic_name: String,
     pub partition: i32,
     pub offset: i64,
     pub group_id: String,
+    // Records carried by the produce request or the fetch response
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub record_count: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub record_bytes: u32,
 
     // reponse
     #[serde(rename = "response_length", skip_serializing_if = "value_is_negative")]
@@ -161,6 +170,10 @@ impl KafkaInfo {
         if self.partition == 0 && other.partition > 0 {
             self.partition = other.partition;
         }
+        if self.record_bytes == 0 && other.record_bytes > 0 {
+            self.record_count = other.record_count;
+            self.record_bytes = other.record_bytes;
+        }
         self.msg_type = LogMessageType::Session;
         self.captured_response_byte = other.captured_response_byte;
         swap_if!(self, topic_name, is_empty, other);
@@ -288,6 +301,16 @@ impl From<KafkaInfo> for L7ProtocolSendLog {
                 val: f.group_id,
             });
         }
+        if f.record_bytes > 0 {
+            attributes.push(KeyVal {
+                key: "record_count".to_string(),
+                val: f.record_count.to_string(),
+            });
+            attributes.push(KeyVal {
+                key: "record_bytes".to_string(),
+                val: f.record_bytes.to_string(),
+            });
+        }
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
             captured_response_byte: f.captured_response_byte,
@@ -340,6 +363,8 @@ impl From<KafkaInfo> for L7ProtocolSendLog {
 pub struct KafkaLog {
     perf_stats: Option<L7PerfStats>,
     sessions: LruCache<u32, (u16, u16)>,
+    // max version of each api key advertised by the broker in ApiVersions response
+    api_versions: HashMap<u16, u16>,
     last_is_on_blacklist: bool,
 }
 
@@ -348,6 +373,7 @@ impl Default for KafkaLog {
         Self {
             perf_stats: None,
             sessions: LruCache::new(NonZeroUsize::new(Self::MAX_SESSION_PER_FLOW).unwrap()),
+            api_versions: HashMap::new(),
             last_is_on_blacklist: false,
         }
     }
@@ -393,6 +419,15 @@ impl L7ProtocolParserInterface for KafkaLog {
                     info.topic_name, info.partition, info.offset
                 ))
             }
+            (KAFKA_OFFSET_COMMIT, LogMessageType::Request)
+            | (KAFKA_OFFSET_COMMIT, LogMessageType::Session)
+                if !info.topic_name.is_empty() =>
+            {
+                Some(format!(
+                    "{}-{}:{}",
+                    info.topic_name, info.partition, info.offset
+                ))
+            }
             _ => None,
         };
 
@@ -440,7 +475,86 @@ impl KafkaLog {
     const MSG_LEN_SIZE: usize = 4;
     const MAX_TRACE_ID: usize = 255;
     const MAX_SESSION_PER_FLOW: usize = 32;
-    const MAX_VERSION: u16 = 12;
+    const MAX_VERSION: u16 = 16;
+    const TOPIC_ID_LEN: usize = 16;
+    // The first flexible version (KIP-482) of each api key, u16::MAX if the api is never flexible,
+    // apis added later are flexible since Version 0
+    const FLEXIBLE_VERSIONS: [u16; 52] = [
+        9,
+        12,
+        6,
+        9,
+        4,
+        2,
+        6,
+        3,
+        8,
+        6, // 10
+        3,
+        6,
+        4,
+        4,
+        4,
+        5,
+        3,
+        u16::MAX,
+        3,
+        5, // 20
+        4,
+        2,
+        2,
+        4,
+        3,
+        3,
+        3,
+        1,
+        3,
+        2, // 30
+        2,
+        2,
+        4,
+        2,
+        2,
+        2,
+        2,
+        2,
+        2,
+        2, // 40
+        2,
+        2,
+        2,
+        2,
+        1,
+        0,
+        0,
+        u16::MAX,
+        1,
+        1, // 50
+        0,
+        0,
+    ];
+
+    const RECORD_BATCH_LENGTH_OFFSET: usize = 8;
+    const RECORD_BATCH_MAGIC_OFFSET: usize = 16;
+    const RECORD_BATCH_RECORDS_COUNT_OFFSET: usize = 57;
+    const RECORD_BATCH_HEADER_LEN: usize = 61;
+    // base_offset and batch_length are not counted in batch_length
+    const RECORD_BATCH_LOG_OVERHEAD: usize = 12;
+
+    fn is_flexible(api_key: u16, api_version: u16) -> bool {
+        Self::FLEXIBLE_VERSIONS
+            .get(api_key as usize)
+            .map(|v| api_version >= *v)
+            .unwrap_or(true)
+    }
+
+    // The versions advertised by the broker take precedence over the default limit
+    fn max_version(&self, api_key: u16) -> u16 {
+        self.api_versions
+            .get(&api_key)
+            .copied()
+            .unwrap_or(Self::MAX_VERSION)
+    }
 
     fn decode_varint(buf: &[u8]) -> (usize, usize) {
         let mut shift = 0;
@@ -501,19 +615,19 @@ impl KafkaLog {
 
     fn decode_compact_string(payload: &[u8]) -> Option<(String, usize)> {
         let (total_len, header_len) = Self::decode_varint(payload);
-        if header_len == 0 || total_len == 0 || total_len < header_len {
+        if header_len == 0 || total_len == 0 {
             return None;
         }
 
-        let string_offset = header_len;
-        let string_len = total_len - header_len;
-        if string_len > payload[string_offset..].len() {
+        // COMPACT_STRING stores the length plus one as an UNSIGNED_VARINT
+        let string_len = total_len - 1;
+        if string_len > payload[header_len..].len() {
             return None;
         }
 
         Some((
             String::from_utf8_lossy(&payload[header_len..header_len + string_len]).into_owned(),
-            total_len,
+            header_len + string_len,
         ))
     }
 
@@ -526,6 +640,56 @@ impl KafkaLog {
         return Err(Error::KafkaLogParseFailed);
     }
 
+    fn skip_compact_nullable_string(payload: &[u8]) -> Option<usize> {
+        let (total_len, header_len) = Self::decode_varint(payload);
+        if header_len == 0 {
+            return None;
+        }
+        // null is encoded as 0, otherwise the length is stored plus one
+        let length = header_len + total_len.saturating_sub(1);
+        if length > payload.len() {
+            return None;
+        }
+
+        Some(length)
+    }
+
+    // TAG_BUFFER => [tag size data], with the count, tag and size as UNSIGNED_VARINT
+    fn skip_tagged_fields(payload: &[u8]) -> Option<usize> {
+        let (count, mut offset) = Self::decode_varint(payload);
+        if offset == 0 {
+            return None;
+        }
+        for _ in 0..count {
+            let (_, tag_len) = Self::decode_varint(&payload[offset..]);
+            if tag_len == 0 {
+                return None;
+            }
+            offset += tag_len;
+            let (size, size_len) = Self::decode_varint(&payload[offset..]);
+            if size_len == 0 || offset + size_len + size > payload.len() {
+                return None;
+            }
+            offset += size_len + size;
+        }
+
+        Some(offset)
+    }
+
+    // Fetch refers to topics by topic_id since Version 13, which is shown the same way as
+    // the kafka tools do in place of the topic name
+    fn decode_fetch_topic(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
+        if info.api_version < 13 {
+            return Self::decode_compact_topic_name(payload, info);
+        }
+        if Self::TOPIC_ID_LEN > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+        info.topic_name = BASE64_URL_SAFE_NO_PAD.encode(&payload[..Self::TOPIC_ID_LEN]);
+
+        Ok(Self::TOPIC_ID_LEN)
+    }
+
     fn decode_produce_request(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
         let mut offset = 0;
         match info.api_version {
@@ -545,6 +709,7 @@ impl KafkaLog {
                 }
 
                 offset += Self::decode_topic_name(&payload[offset..], info)?;
+                offset += Self::decode_produce_request_partition(&payload[offset..], info)?;
             }
             // Produce Request (Version: [3-8]) => transactional_id acks timeout_ms [topic_data]
             //   transactional_id => NULLABLE_STRING
@@ -566,8 +731,9 @@ impl KafkaLog {
                 }
 
                 offset += Self::decode_topic_name(&payload[offset..], info)?;
+                offset += Self::decode_produce_request_partition(&payload[offset..], info)?;
             }
-            // Produce Request (Version: 9) => transactional_id acks timeout_ms [topic_data] TAG_BUFFER
+            // Produce Request (Version: [9-11]) => transactional_id acks timeout_ms [topic_data] TAG_BUFFER
             //   transactional_id => COMPACT_NULLABLE_STRING
             //   acks => INT16
             //   timeout_ms => INT32
@@ -576,11 +742,11 @@ impl KafkaLog {
             //     partition_data => index records TAG_BUFFER
             //       index => INT32
             //       records => COMPACT_RECORDS
-            9..=10 => {
-                let Some((_, header_len)) = Self::decode_string(payload) else {
+            9..=11 => {
+                let Some(header_len) = Self::skip_compact_nullable_string(payload) else {
                     return Err(Error::KafkaLogParseFailed);
                 };
-                // topic_data: COMPACT_NULLABLE_STRING? + INT16 + INT32
+                // topic_data: COMPACT_NULLABLE_STRING + INT16 + INT32
                 offset = header_len + 2 + 4;
                 if offset > payload.len() {
                     return Err(Error::KafkaLogParseFailed);
@@ -592,6 +758,7 @@ impl KafkaLog {
                 offset += header_len;
 
                 offset += Self::decode_compact_topic_name(&payload[offset..], info)?;
+                offset += Self::decode_produce_request_partition(&payload[offset..], info)?;
             }
             // TODO
             _ => {}
@@ -600,6 +767,96 @@ impl KafkaLog {
         Ok(offset)
     }
 
+    // Only the first partition is decoded, the returned offset stops before the records
+    // so that the trace headers inside them can still be searched
+    fn decode_produce_request_partition(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
+        let mut offset = match info.api_version {
+            0..=8 => {
+                if 4 > payload.len() {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+                let partition_data_count = read_u32_be(payload);
+                if partition_data_count == 0 {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+
+                4
+            }
+            9..=11 => {
+                let (partition_data_count, partition_data_header_len) =
+                    Self::decode_varint(payload);
+                if partition_data_count == 0 {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+
+                partition_data_header_len
+            }
+            _ => return Err(Error::KafkaLogParseFailed),
+        };
+
+        if offset + 4 > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+        info.partition = read_i32_be(&payload[offset..]);
+        offset += 4;
+
+        Self::decode_records(&payload[offset..], info.api_version >= 9, info)?;
+
+        Ok(offset)
+    }
+
+    // records => RECORDS (NULLABLE_BYTES) or COMPACT_RECORDS (COMPACT_NULLABLE_BYTES)
+    //
+    // RecordBatch (magic 2) and the legacy MessageSet (magic 0 and 1) share the same prefix:
+    //   base_offset => INT64
+    //   batch_length => INT32
+    //   partition_leader_epoch => INT32
+    //   magic => INT8
+    // a RecordBatch keeps its records_count => INT32 at byte 57, a legacy message holds one record
+    fn decode_records(payload: &[u8], compact: bool, info: &mut KafkaInfo) -> Result<()> {
+        let (records_len, mut offset) = if compact {
+            let (records_len, header_len) = Self::decode_varint(payload);
+            if header_len == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            if records_len == 0 {
+                return Ok(());
+            }
+            (records_len - 1, header_len)
+        } else {
+            if 4 > payload.len() {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            let records_len = read_i32_be(payload);
+            if records_len < 0 {
+                return Ok(());
+            }
+            (records_len as usize, 4)
+        };
+        info.record_bytes = records_len as u32;
+
+        // records may be truncated, count only the batches whose header is captured
+        let end = (offset + records_len).min(payload.len());
+        while offset + Self::RECORD_BATCH_MAGIC_OFFSET < end {
+            let batch_len = read_i32_be(&payload[offset + Self::RECORD_BATCH_LENGTH_OFFSET..]);
+            if batch_len <= 0 {
+                break;
+            }
+            if payload[offset + Self::RECORD_BATCH_MAGIC_OFFSET] < 2 {
+                info.record_count += 1;
+            } else if offset + Self::RECORD_BATCH_HEADER_LEN <= end {
+                let records_count =
+                    read_i32_be(&payload[offset + Self::RECORD_BATCH_RECORDS_COUNT_OFFSET..]);
+                info.record_count += records_count.max(0) as u32;
+            } else {
+                break;
+            }
+            offset += Self::RECORD_BATCH_LOG_OVERHEAD + batch_len as usize;
+        }
+
+        Ok(())
+    }
+
     fn decode_produce_response_partition(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
         let mut offset = match info.api_version {
             0..=8 => {
@@ -613,7 +870,7 @@ impl KafkaLog {
 
                 4
             }
-            9..=10 => {
+            9..=11 => {
                 let (partition_respones_count, partition_respones_header_len) =
                     Self::decode_varint(payload);
                 if partition_respones_count == 0 {
@@ -668,25 +925,26 @@ impl KafkaLog {
 
                 Self::decode_produce_response_partition(&payload[4 + topic_name_len..], info)?;
             }
-            // Produce Response (Version: [9-10]) => [responses]
-            //   responses => name [partition_responses]
+            // Produce Response (Version: [9-11]) => [responses] throttle_time_ms TAG_BUFFER
+            //   responses => name [partition_responses] TAG_BUFFER
             //     name => COMPACT_STRING
             //     partition_responses => index error_code base_offset
             //       index => INT32
             //       error_code => INT16
             //       base_offset => INT64
-            9..=10 => {
-                if 2 > payload.len() {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-                let respones_count = read_u16_be(payload);
+            9..=11 => {
+                let (respones_count, respones_header_len) = Self::decode_varint(payload);
                 // topic name offset: [responses]
                 if respones_count == 0 {
                     return Err(Error::KafkaLogParseFailed);
                 }
-                let topic_name_len = Self::decode_compact_topic_name(&payload[2..], info)?;
+                let topic_name_len =
+                    Self::decode_compact_topic_name(&payload[respones_header_len..], info)?;
 
-                Self::decode_produce_response_partition(&payload[2 + topic_name_len..], info)?;
+                Self::decode_produce_response_partition(
+                    &payload[respones_header_len + topic_name_len..],
+                    info,
+                )?;
             }
             _ => return Err(Error::KafkaLogParseFailed),
         }
@@ -747,7 +1005,7 @@ impl KafkaLog {
 
                 offset
             }
-            12 => {
+            12..=16 => {
                 let (topic_count, mut offset) = Self::decode_varint(payload);
                 if topic_count == 0 {
                     return Err(Error::KafkaLogParseFailed);
@@ -789,16 +1047,13 @@ impl KafkaLog {
 
                 offset
             }
-            12 => {
-                if 2 > payload.len() {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-                let topic_count = read_u16_be(payload);
+            12..=16 => {
+                let (topic_count, topic_header_len) = Self::decode_varint(payload);
                 if topic_count == 0 {
                     return Err(Error::KafkaLogParseFailed);
                 }
 
-                2 + Self::decode_compact_topic_name(&payload[2..], info)?
+                topic_header_len + Self::decode_fetch_topic(&payload[topic_header_len..], info)?
             }
             _ => return Err(Error::KafkaLogParseFailed),
         };
@@ -868,7 +1123,7 @@ impl KafkaLog {
                 }
                 offset += Self::decode_fetch_request_topics(&payload[offset..], info)?;
             }
-            // Fetch Request (Version: [7-12]) => replica_id max_wait_ms min_bytes [topics]
+            // Fetch Request (Version: [7-14]) => replica_id max_wait_ms min_bytes [topics]
             //   replica_id => INT32
             //   max_wait_ms => INT32
             //   min_bytes => INT32
@@ -877,12 +1132,12 @@ impl KafkaLog {
             //   session_id => INT32
             //   session_epoch => INT32
             //   topics => topic [partitions]
-            //     topic => STRING
+            //     topic => STRING (COMPACT_STRING since Version 12, topic_id => UUID since Version 13)
             //     partitions => partition fetch_offset partition_max_bytes
             //       partition => INT32
             //       fetch_offset => INT64
             //       partition_max_bytes => INT32
-            7..=12 => {
+            7..=14 => {
                 // topics: INT32 + INT32 + INT32 + INT32 + INT8 + INT32 + INT32
                 offset = 4 + 4 + 4 + 4 + 1 + 4 + 4;
                 if offset > payload.len() {
@@ -890,6 +1145,16 @@ impl KafkaLog {
                 }
                 offset += Self::decode_fetch_request_topics(&payload[offset..], info)?;
             }
+            // Fetch Request (Version: [15-16]) => max_wait_ms min_bytes max_bytes isolation_level session_id session_epoch [topics] [forgotten_topics_data] rack_id TAG_BUFFER
+            //   replica_id moves into the replica_state tagged field
+            15..=16 => {
+                // topics: INT32 + INT32 + INT32 + INT8 + INT32 + INT32
+                offset = 4 + 4 + 4 + 1 + 4 + 4;
+                if offset > payload.len() {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+                offset += Self::decode_fetch_request_topics(&payload[offset..], info)?;
+            }
             // TODO
             _ => {}
         }
@@ -909,7 +1174,7 @@ impl KafkaLog {
 
                 4
             }
-            12 => {
+            12..=16 => {
                 let (partition_respones_count, partition_respones_header_len) =
                     Self::decode_varint(payload);
                 if partition_respones_count == 0 {
@@ -927,16 +1192,52 @@ impl KafkaLog {
         info.partition = read_i32_be(&payload[offset..]);
         offset += 4;
 
-        if info.status_code.is_some() {
-            return Ok(offset);
-        }
-
         if offset + 2 > payload.len() {
             return Err(Error::KafkaLogParseFailed);
         }
-        info.status_code = Some(read_i16_be(&payload[offset..]) as i32);
+        // Version 7 and above carry a top level error_code
+        if info.status_code.is_none() {
+            info.status_code = Some(read_i16_be(&payload[offset..]) as i32);
+        }
         offset += 2;
 
+        // high_watermark => INT64
+        // last_stable_offset => INT64 (Version: 4 and above)
+        // log_start_offset => INT64 (Version: 5 and above)
+        offset += match info.api_version {
+            0..=3 => 8,
+            4 => 16,
+            _ => 24,
+        };
+        // aborted_transactions => producer_id first_offset
+        //   producer_id => INT64
+        //   first_offset => INT64
+        if info.api_version >= 12 {
+            if offset > payload.len() {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            let (aborted_count, header_len) = Self::decode_varint(&payload[offset..]);
+            if header_len == 0 || aborted_count > payload.len() {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            // each aborted transaction ends with a TAG_BUFFER
+            offset += header_len + aborted_count.saturating_sub(1) * 17;
+        } else if info.api_version >= 4 {
+            if offset + 4 > payload.len() {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            let aborted_count = read_i32_be(&payload[offset..]);
+            offset += 4 + aborted_count.max(0) as usize * 16;
+        }
+        // preferred_read_replica => INT32 (Version: 11 and above)
+        if info.api_version >= 11 {
+            offset += 4;
+        }
+        if offset > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+        Self::decode_records(&payload[offset..], info.api_version >= 12, info)?;
+
         Ok(offset)
     }
 
@@ -1017,17 +1318,17 @@ impl KafkaLog {
 
                 Self::decode_fetch_partition_response(&payload[offset..], info)?;
             }
-            // Fetch Response (Version: 12) => throttle_time_ms error_code session_id [responses] TAG_BUFFER
+            // Fetch Response (Version: [12-16]) => throttle_time_ms error_code session_id [responses] TAG_BUFFER
             // throttle_time_ms => INT32
             // error_code => INT16
             // session_id => INT32
             // responses => topic [partitions] TAG_BUFFER
-            //   topic => COMPACT_STRING
+            //   topic => COMPACT_STRING (topic_id => UUID since Version 13)
             //   partitions => partition_index error_code high_watermark last_stable_offset log_start_offset [aborted_transactions] preferred_read_replica records TAG_BUFFER
             //     partition_index => INT32
             //     error_code => INT16
             //     high_watermark => INT64
-            12 => {
+            12..=16 => {
                 let mut offset = 4;
                 if offset + 2 > payload.len() {
                     return Err(Error::KafkaLogParseFailed);
@@ -1046,8 +1347,7 @@ impl KafkaLog {
                 }
                 offset += responses_header_len;
 
-                let topic_name_len = Self::decode_compact_topic_name(&payload[offset..], info)?;
-                offset += topic_name_len;
+                offset += Self::decode_fetch_topic(&payload[offset..], info)?;
 
                 Self::decode_fetch_partition_response(&payload[offset..], info)?;
             }
@@ -1079,12 +1379,6 @@ impl KafkaLog {
             //   group_instance_id => COMPACT_NULLABLE_STRING
             //   reason => COMPACT_NULLABLE_STRING
             4..=5 => {
-                // _tagged_fields
-                if payload.len() < 1 {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-                offset += 1;
-
                 if let Some((group_id, group_id_len)) =
                     Self::decode_compact_string(&payload[offset..])
                 {
@@ -1108,27 +1402,15 @@ impl KafkaLog {
                 }
                 info.status_code = Some(read_i16_be(payload) as i32);
             }
-            // LeaveGroup Response (Version: 1) => throttle_time_ms error_code
+            // LeaveGroup Response (Version: [1-5]) => throttle_time_ms error_code
             //   throttle_time_ms => INT32
             //   error_code => INT16
-            1..=3 => {
+            1..=5 => {
                 if 6 > payload.len() {
                     return Err(Error::KafkaLogParseFailed);
                 }
                 info.status_code = Some(read_i16_be(&payload[4..]) as i32);
             }
-            // LeaveGroup Response (Version: 4) => throttle_time_ms error_code
-            //   throttle_time_ms => INT32
-            //   error_code => INT16
-            4..=5 => {
-                // _tagged_field
-                let offset = 1;
-                if offset + 6 > payload.len() {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-
-                info.status_code = Some(read_i16_be(&payload[offset + 4..]) as i32);
-            }
             _ => return Err(Error::KafkaLogParseFailed),
         }
 
@@ -1164,12 +1446,6 @@ impl KafkaLog {
             //     metadata => COMPACT_BYTES
             //   reason => COMPACT_NULLABLE_STRING
             6..=9 => {
-                // _tagged_fields
-                if payload.len() < 1 {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-                offset += 1;
-
                 if let Some((group_id, group_id_len)) =
                     Self::decode_compact_string(&payload[offset..])
                 {
@@ -1200,7 +1476,7 @@ impl KafkaLog {
                 }
                 info.status_code = Some(read_i16_be(payload) as i32);
             }
-            // JoinGroup Response (Version: 2) => throttle_time_ms error_code generation_id protocol_name leader member_id [members]
+            // JoinGroup Response (Version: [2-9]) => throttle_time_ms error_code generation_id protocol_name leader member_id [members]
             //   throttle_time_ms => INT32
             //   error_code => INT16
             //   generation_id => INT32
@@ -1210,31 +1486,12 @@ impl KafkaLog {
             //   members => member_id metadata
             //     member_id => STRING
             //     metadata => BYTES
-            2..=5 => {
+            2..=9 => {
                 if 6 > payload.len() {
                     return Err(Error::KafkaLogParseFailed);
                 }
                 info.status_code = Some(read_i16_be(&payload[4..]) as i32);
             }
-            // JoinGroup Response (Version: 6) => throttle_time_ms error_code generation_id protocol_name leader member_id [members]
-            //   throttle_time_ms => INT32
-            //   error_code => INT16
-            //   generation_id => INT32
-            //   protocol_name => COMPACT_STRING
-            //   leader => COMPACT_STRING
-            //   member_id => COMPACT_STRING
-            //   members => member_id metadata
-            //     member_id => COMPACT_STRING
-            //     group_instance_id => COMPACT_NULLABLE_STRING
-            //     metadata => BYTES
-            6..=9 => {
-                // _tagged_field
-                let offset = 1;
-                if offset + 6 > payload.len() {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-                info.status_code = Some(read_i16_be(&payload[offset + 4..]) as i32);
-            }
             _ => return Err(Error::KafkaLogParseFailed),
         }
 
@@ -1269,12 +1526,6 @@ impl KafkaLog {
             //     member_id => COMPACT_STRING
             //     assignment => COMPACT_BYTES
             4..=5 => {
-                // _tagged_field
-                if 1 > payload.len() {
-                    return Err(Error::KafkaLogParseFailed);
-                }
-                offset += 1;
-
                 if let Some((group_id, group_id_len)) =
                     Self::decode_compact_string(&payload[offset..])
                 {
@@ -1299,45 +1550,323 @@ impl KafkaLog {
                 }
                 info.status_code = Some(read_i16_be(payload) as i32);
             }
-            // SyncGroup Response (Version: [1-3]) => throttle_time_ms error_code protocol_type protocol_name assignment TAG_BUFFER
+            // SyncGroup Response (Version: [1-5]) => throttle_time_ms error_code protocol_type protocol_name assignment TAG_BUFFER
             //   throttle_time_ms => INT32
             //   error_code => INT16
             //   protocol_type => COMPACT_NULLABLE_STRING
             //   protocol_name => COMPACT_NULLABLE_STRING
             //   assignment => COMPACT_BYTES
-            1..=3 => {
+            1..=5 => {
                 if 6 > payload.len() {
                     return Err(Error::KafkaLogParseFailed);
                 }
                 info.status_code = Some(read_i16_be(&payload[4..]) as i32);
             }
-            // SyncGroup Response (Version: [4-5]) => throttle_time_ms error_code protocol_type protocol_name assignment TAG_BUFFER
-            //   throttle_time_ms => INT32
-            //   error_code => INT16
-            //   protocol_type => COMPACT_NULLABLE_STRING
-            //   protocol_name => COMPACT_NULLABLE_STRING
-            //   assignment => COMPACT_BYTES
-            4..=5 => {
-                // _tagged_fields
-                let offset = 1;
-                if offset + 6 > payload.len() {
+            _ => return Err(Error::KafkaLogParseFailed),
+        }
+
+        Ok(())
+    }
+
+    fn decode_offset_commit_topics(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
+        let compact = info.api_version >= 8;
+        let mut offset = if compact {
+            let (topic_count, header_len) = Self::decode_varint(payload);
+            if topic_count == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            header_len + Self::decode_compact_topic_name(&payload[header_len..], info)?
+        } else {
+            if 4 > payload.len() || read_u32_be(payload) == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            4 + Self::decode_topic_name(&payload[4..], info)?
+        };
+
+        if compact {
+            let (partition_count, header_len) = Self::decode_varint(&payload[offset..]);
+            if partition_count == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            offset += header_len;
+        } else {
+            if offset + 4 > payload.len() || read_u32_be(&payload[offset..]) == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            offset += 4;
+        }
+
+        if offset + 12 > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+        info.partition = read_i32_be(&payload[offset..]);
+        info.offset = read_i64_be(&payload[offset + 4..]);
+
+        Ok(offset + 12)
+    }
+
+    fn decode_offset_commit_request(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
+        let mut offset = 0;
+        match info.api_version {
+            // OffsetCommit Request (Version: 0) => group_id [topics]
+            //   group_id => STRING
+            //   topics => name [partitions]
+            //     name => STRING
+            //     partitions => partition_index committed_offset committed_metadata
+            //       partition_index => INT32
+            //       committed_offset => INT64
+            //       committed_metadata => NULLABLE_STRING
+            // OffsetCommit Request (Version: 1) => group_id generation_id member_id [topics]
+            //   generation_id => INT32
+            //   member_id => STRING
+            // OffsetCommit Request (Version: [2-4]) => group_id generation_id member_id retention_time_ms [topics]
+            //   retention_time_ms => INT64
+            // OffsetCommit Request (Version: [5-7]) => group_id generation_id member_id group_instance_id [topics]
+            //   group_instance_id => NULLABLE_STRING (Version: 7)
+            0..=7 => {
+                let Some((group_id, group_id_len)) = Self::decode_string(payload) else {
+                    return Err(Error::KafkaLogParseFailed);
+                };
+                info.group_id = group_id;
+                offset = group_id_len;
+                if info.api_version >= 1 {
+                    // generation_id
+                    offset += 4;
+                    if offset > payload.len() {
+                        return Err(Error::KafkaLogParseFailed);
+                    }
+                    let Some((_, member_id_len)) = Self::decode_string(&payload[offset..]) else {
+                        return Err(Error::KafkaLogParseFailed);
+                    };
+                    offset += member_id_len;
+                }
+                if info.api_version == 7 {
+                    let Some((_, instance_id_len)) = Self::decode_string(&payload[offset..]) else {
+                        return Err(Error::KafkaLogParseFailed);
+                    };
+                    offset += instance_id_len;
+                }
+                if (2..=4).contains(&info.api_version) {
+                    // retention_time_ms
+                    offset += 8;
+                }
+                if offset > payload.len() {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+                offset += Self::decode_offset_commit_topics(&payload[offset..], info)?;
+            }
+            // OffsetCommit Request (Version: [8-9]) => group_id generation_id_or_member_epoch member_id group_instance_id [topics] TAG_BUFFER
+            //   group_id => COMPACT_STRING
+            //   generation_id_or_member_epoch => INT32
+            //   member_id => COMPACT_STRING
+            //   group_instance_id => COMPACT_NULLABLE_STRING
+            //   topics => name [partitions] TAG_BUFFER
+            //     name => COMPACT_STRING
+            //     partitions => partition_index committed_offset committed_leader_epoch committed_metadata TAG_BUFFER
+            //       partition_index => INT32
+            //       committed_offset => INT64
+            8..=9 => {
+                let Some((group_id, group_id_len)) =
+                    Self::decode_compact_string(&payload[offset..])
+                else {
+                    return Err(Error::KafkaLogParseFailed);
+                };
+                info.group_id = group_id;
+                // generation_id_or_member_epoch
+                offset += group_id_len + 4;
+                if offset > payload.len() {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+                let Some((_, member_id_len)) = Self::decode_compact_string(&payload[offset..])
+                else {
+                    return Err(Error::KafkaLogParseFailed);
+                };
+                offset += member_id_len;
+                // group_instance_id is null most of the time
+                let (instance_id_len, header_len) = Self::decode_varint(&payload[offset..]);
+                if header_len == 0 {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+                offset += header_len + instance_id_len.saturating_sub(1);
+                if offset > payload.len() {
+                    return Err(Error::KafkaLogParseFailed);
+                }
+                offset += Self::decode_offset_commit_topics(&payload[offset..], info)?;
+            }
+            _ => return Err(Error::KafkaLogParseFailed),
+        }
+
+        Ok(offset)
+    }
+
+    fn decode_offset_commit_response(payload: &[u8], info: &mut KafkaInfo) -> Result<()> {
+        // OffsetCommit Response (Version: [0-2]) => [topics]
+        //   topics => name [partitions]
+        //     name => STRING
+        //     partitions => partition_index error_code
+        //       partition_index => INT32
+        //       error_code => INT16
+        // OffsetCommit Response (Version: [3-7]) => throttle_time_ms [topics]
+        //   throttle_time_ms => INT32
+        // OffsetCommit Response (Version: [8-9]) => throttle_time_ms [topics] TAG_BUFFER
+        //   topics => name [partitions] TAG_BUFFER
+        //     name => COMPACT_STRING
+        let mut offset = match info.api_version {
+            0..=2 => 0,
+            3..=9 => 4,
+            _ => return Err(Error::KafkaLogParseFailed),
+        };
+        if offset > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+
+        if info.api_version >= 8 {
+            let (topic_count, header_len) = Self::decode_varint(&payload[offset..]);
+            if topic_count == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            offset += header_len;
+            offset += Self::decode_compact_topic_name(&payload[offset..], info)?;
+            let (partition_count, header_len) = Self::decode_varint(&payload[offset..]);
+            if partition_count == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            offset += header_len;
+        } else {
+            if offset + 4 > payload.len() || read_u32_be(&payload[offset..]) == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            offset += 4;
+            offset += Self::decode_topic_name(&payload[offset..], info)?;
+            if offset + 4 > payload.len() || read_u32_be(&payload[offset..]) == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            offset += 4;
+        }
+
+        if offset + 6 > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+        info.partition = read_i32_be(&payload[offset..]);
+        info.status_code = Some(read_i16_be(&payload[offset + 4..]) as i32);
+
+        Ok(())
+    }
+
+    fn decode_offset_fetch_request(payload: &[u8], info: &mut KafkaInfo) -> Result<usize> {
+        let mut offset = 0;
+        match info.api_version {
+            // OffsetFetch Request (Version: [0-5]) => group_id [topics]
+            //   group_id => STRING
+            //   topics => name [partition_indexes]
+            //     name => STRING
+            //     partition_indexes => INT32
+            0..=5 => {
+                if let Some((group_id, group_id_len)) = Self::decode_string(payload) {
+                    info.group_id = group_id;
+                    offset = group_id_len;
+                }
+            }
+            // OffsetFetch Request (Version: [6-7]) => group_id [topics] require_stable TAG_BUFFER
+            //   group_id => COMPACT_STRING
+            //   topics => name [partition_indexes] TAG_BUFFER
+            //     name => COMPACT_STRING
+            //     partition_indexes => INT32
+            //   require_stable => BOOLEAN
+            6..=7 => {
+                if let Some((group_id, group_id_len)) =
+                    Self::decode_compact_string(&payload[offset..])
+                {
+                    info.group_id = group_id;
+                    offset += group_id_len;
+                }
+            }
+            // OffsetFetch Request (Version: [8-9]) => [groups] require_stable TAG_BUFFER
+            //   groups => group_id member_id member_epoch [topics] TAG_BUFFER
+            //     group_id => COMPACT_STRING
+            8..=9 => {
+                let (group_count, header_len) = Self::decode_varint(&payload[offset..]);
+                if group_count == 0 {
                     return Err(Error::KafkaLogParseFailed);
                 }
-                info.status_code = Some(read_i16_be(&payload[offset + 4..]) as i32);
+                offset += header_len;
+
+                if let Some((group_id, group_id_len)) =
+                    Self::decode_compact_string(&payload[offset..])
+                {
+                    info.group_id = group_id;
+                    offset += group_id_len;
+                }
             }
             _ => return Err(Error::KafkaLogParseFailed),
         }
 
+        Ok(offset)
+    }
+
+    fn decode_api_versions_response(&mut self, payload: &[u8], info: &mut KafkaInfo) -> Result<()> {
+        // ApiVersions Response (Version: [0-2]) => error_code [api_keys] throttle_time_ms
+        //   error_code => INT16
+        //   api_keys => api_key min_version max_version
+        //     api_key => INT16
+        //     min_version => INT16
+        //     max_version => INT16
+        // ApiVersions Response (Version: [3-4]) => error_code [api_keys] throttle_time_ms TAG_BUFFER
+        //   api_keys => api_key min_version max_version TAG_BUFFER
+        if 2 > payload.len() {
+            return Err(Error::KafkaLogParseFailed);
+        }
+        let error_code = read_i16_be(payload);
+        info.status_code = Some(error_code as i32);
+
+        // UNSUPPORTED_VERSION is answered with Version 0 so that any client can parse it
+        let compact = info.api_version >= 3 && error_code == 0;
+        let (api_key_count, mut offset) = if compact {
+            let (api_key_count, header_len) = Self::decode_varint(&payload[2..]);
+            if header_len == 0 {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            (api_key_count.saturating_sub(1), 2 + header_len)
+        } else {
+            if 6 > payload.len() {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            (read_i32_be(&payload[2..]).max(0) as usize, 6)
+        };
+
+        for _ in 0..api_key_count {
+            if offset + 6 > payload.len() {
+                return Err(Error::KafkaLogParseFailed);
+            }
+            let api_key = read_u16_be(&payload[offset..]);
+            let max_version = read_u16_be(&payload[offset + 4..]);
+            if api_key <= KafkaInfo::API_KEY_MAX {
+                self.api_versions.insert(api_key, max_version);
+            }
+            offset += 6;
+
+            if compact {
+                let Some(tagged_fields_len) = Self::skip_tagged_fields(&payload[offset..]) else {
+                    return Err(Error::KafkaLogParseFailed);
+                };
+                offset += tagged_fields_len;
+            }
+        }
+
         Ok(())
     }
 
     fn decode_request_body(payload: &[u8], info: &mut KafkaInfo) {
         let offset = match info.api_key {
-            // Support Version Range: [0, 9]
+            // Support Version Range: [0, 11]
             KAFKA_PRODUCE => Self::decode_produce_request(payload, info),
-            // Support Version Range: [0, 12]
+            // Support Version Range: [0, 16]
             KAFKA_FETCH => Self::decode_fetch_request(payload, info),
             // Support Version Range: [0, 9]
+            KAFKA_OFFSET_COMMIT => Self::decode_offset_commit_request(payload, info),
+            // Support Version Range: [0, 9]
+            KAFKA_OFFSET_FETCH => Self::decode_offset_fetch_request(payload, info),
+            // Support Version Range: [0, 9]
             KAFKA_JOIN_GROUP => Self::decode_join_group_request(payload, info),
             // Support Version Range: [0, 5]
             KAFKA_LEAVE_GROUP => Self::decode_leave_group_request(payload, info),
@@ -1357,17 +1886,21 @@ impl KafkaLog {
         Self::decode_traceparent(&payload, info);
     }
 
-    fn decode_response_body(payload: &[u8], info: &mut KafkaInfo) {
+    fn decode_response_body(&mut self, payload: &[u8], info: &mut KafkaInfo) {
         match info.api_key {
-            // Support Version Range: [0, 10]
+            // Support Version Range: [0, 11]
             KAFKA_PRODUCE => {
                 let _ = Self::decode_produce_response(payload, info);
             }
-            // Support Version Range: [0, 12]
+            // Support Version Range: [0, 16]
             KAFKA_FETCH => {
                 let _ = Self::decode_fetch_response(payload, info);
             }
             // Support Version Range: [0, 9]
+            KAFKA_OFFSET_COMMIT => {
+                let _ = Self::decode_offset_commit_response(payload, info);
+            }
+            // Support Version Range: [0, 9]
             KAFKA_JOIN_GROUP => {
                 let _ = Self::decode_join_group_response(payload, info);
             }
@@ -1379,6 +1912,10 @@ impl KafkaLog {
             KAFKA_SYNC_GROUP => {
                 let _ = Self::decode_sync_group_response(payload, info);
             }
+            // Support Version Range: [0, 4]
+            KAFKA_API_VERSIONS => {
+                let _ = self.decode_api_versions_response(payload, info);
+            }
             _ => return,
         }
     }
@@ -1497,7 +2034,7 @@ impl KafkaLog {
         info.msg_type = LogMessageType::Request;
         info.api_key = read_u16_be(&payload[4..]);
         info.api_version = read_u16_be(&payload[6..]);
-        if info.api_version > Self::MAX_VERSION {
+        if info.api_version > self.max_version(info.api_key) {
             return Err(Error::KafkaLogParseFailed);
         }
         info.correlation_id = read_u32_be(&payload[8..]);
@@ -1508,7 +2045,16 @@ impl KafkaLog {
 
         self.sessions
             .push(info.correlation_id, (info.api_key, info.api_version));
-        Self::decode_request_body(&payload[client_id_len + KAFKA_REQ_HEADER_LEN..], info);
+
+        let mut offset = client_id_len + KAFKA_REQ_HEADER_LEN;
+        // Request Header v2 => request_api_key request_api_version correlation_id client_id TAG_BUFFER
+        if Self::is_flexible(info.api_key, info.api_version) {
+            let Some(tagged_fields_len) = Self::skip_tagged_fields(&payload[offset..]) else {
+                return Ok(());
+            };
+            offset += tagged_fields_len;
+        }
+        Self::decode_request_body(&payload[offset..], info);
         Ok(())
     }
 
@@ -1520,7 +2066,19 @@ impl KafkaLog {
         if let Some((key, version)) = self.sessions.peek(&info.correlation_id) {
             info.api_key = *key;
             info.api_version = *version;
-            Self::decode_response_body(&payload[KAFKA_RESP_HEADER_LEN..], info);
+
+            let mut offset = KAFKA_RESP_HEADER_LEN;
+            // Response Header v1 => correlation_id TAG_BUFFER
+            // ApiVersions always responds with Response Header v0 for the client to parse it
+            if info.api_key != KAFKA_API_VERSIONS
+                && Self::is_flexible(info.api_key, info.api_version)
+            {
+                let Some(tagged_fields_len) = Self::skip_tagged_fields(&payload[offset..]) else {
+                    return Ok(());
+                };
+                offset += tagged_fields_len;
+            }
+            self.decode_response_body(&payload[offset..], info);
 
             if let Some(status_code) = info.status_code {
                 if status_code == 0 {
@@ -1767,4 +2325,130 @@ mod tests {
             info.span_id
         );
     }
+
+    #[test]
+    fn offset_commit_and_records() {
+        let mut kafka = KafkaLog::default();
+
+        // OffsetCommit Request (Version: 2)
+        let mut payload = vec![];
+        payload.extend_from_slice(&KAFKA_OFFSET_COMMIT.to_be_bytes());
+        payload.extend_from_slice(&2u16.to_be_bytes());
+        payload.extend_from_slice(&5u32.to_be_bytes());
+        payload.extend_from_slice(b"\x00\x08consumer");
+        payload.extend_from_slice(b"\x00\x06group1");
+        payload.extend_from_slice(&1i32.to_be_bytes());
+        payload.extend_from_slice(b"\x00\x06member");
+        payload.extend_from_slice(&(-1i64).to_be_bytes());
+        payload.extend_from_slice(&1u32.to_be_bytes());
+        payload.extend_from_slice(b"\x00\x06topic1");
+        payload.extend_from_slice(&1u32.to_be_bytes());
+        payload.extend_from_slice(&3i32.to_be_bytes());
+        payload.extend_from_slice(&42i64.to_be_bytes());
+        payload.extend_from_slice(b"\xff\xff");
+        let mut request = (payload.len() as u32).to_be_bytes().to_vec();
+        request.extend_from_slice(&payload);
+
+        let mut info = KafkaInfo::default();
+        kafka
+            .request(&request, true, &mut info)
+            .expect("parse offset commit request failed");
+        assert_eq!(info.group_id, "group1");
+        assert_eq!(info.get_endpoint(), Some("topic1-3".to_string()));
+        assert_eq!(info.offset, 42);
+
+        // Fetch Response (Version: 4) carrying one RecordBatch with 3 records
+        let mut batch = vec![0u8; KafkaLog::RECORD_BATCH_HEADER_LEN];
+        batch[8..12].copy_from_slice(
+            &((KafkaLog::RECORD_BATCH_HEADER_LEN - KafkaLog::RECORD_BATCH_LOG_OVERHEAD) as i32)
+                .to_be_bytes(),
+        );
+        batch[16] = 2;
+        batch[57..61].copy_from_slice(&3i32.to_be_bytes());
+
+        let mut payload = vec![];
+        payload.extend_from_slice(&6u32.to_be_bytes());
+        payload.extend_from_slice(&0i32.to_be_bytes());
+        payload.extend_from_slice(&1u32.to_be_bytes());
+        payload.extend_from_slice(b"\x00\x06topic1");
+        payload.extend_from_slice(&1u32.to_be_bytes());
+        payload.extend_from_slice(&3i32.to_be_bytes());
+        payload.extend_from_slice(&0i16.to_be_bytes());
+        payload.extend_from_slice(&100i64.to_be_bytes());
+        payload.extend_from_slice(&100i64.to_be_bytes());
+        payload.extend_from_slice(&0i32.to_be_bytes());
+        payload.extend_from_slice(&(batch.len() as i32).to_be_bytes());
+        payload.extend_from_slice(&batch);
+        let mut response = (payload.len() as u32).to_be_bytes().to_vec();
+        response.extend_from_slice(&payload);
+
+        kafka.sessions.push(6, (KAFKA_FETCH, 4));
+        let mut info = KafkaInfo::default();
+        kafka
+            .response(&response, &mut info)
+            .expect("parse fetch response failed");
+        assert_eq!(info.get_endpoint(), Some("topic1-3".to_string()));
+        assert_eq!(info.status_code, Some(0));
+        assert_eq!(info.record_count, 3);
+        assert_eq!(info.record_bytes, KafkaLog::RECORD_BATCH_HEADER_LEN as u32);
+    }
+
+    #[test]
+    fn flexible_versions() {
+        let mut kafka = KafkaLog::default();
+
+        // ApiVersions Response (Version: 3) advertising Fetch up to Version 17
+        let mut payload = vec![];
+        payload.extend_from_slice(&1u32.to_be_bytes());
+        payload.extend_from_slice(&0i16.to_be_bytes());
+        payload.push(2);
+        payload.extend_from_slice(&KAFKA_FETCH.to_be_bytes());
+        payload.extend_from_slice(&0u16.to_be_bytes());
+        payload.extend_from_slice(&17u16.to_be_bytes());
+        payload.push(0);
+        payload.extend_from_slice(&0i32.to_be_bytes());
+        payload.push(0);
+        let mut response = (payload.len() as u32).to_be_bytes().to_vec();
+        response.extend_from_slice(&payload);
+
+        kafka.sessions.push(1, (KAFKA_API_VERSIONS, 3));
+        let mut info = KafkaInfo::default();
+        kafka
+            .response(&response, &mut info)
+            .expect("parse api versions response failed");
+        assert_eq!(info.status_code, Some(0));
+        assert_eq!(kafka.max_version(KAFKA_FETCH), 17);
+        assert_eq!(kafka.max_version(KAFKA_PRODUCE), KafkaLog::MAX_VERSION);
+
+        // Produce Request (Version: 9) with a tagged field in the request header,
+        // a transactional_id and a topic name longer than one varint byte
+        let topic = "t".repeat(130);
+        let mut payload = vec![];
+        payload.extend_from_slice(&KAFKA_PRODUCE.to_be_bytes());
+        payload.extend_from_slice(&9u16.to_be_bytes());
+        payload.extend_from_slice(&2u32.to_be_bytes());
+        payload.extend_from_slice(b"\x00\x08producer");
+        payload.extend_from_slice(b"\x01\x00\x02\xab\xcd");
+        payload.extend_from_slice(b"\x04txn");
+        payload.extend_from_slice(&(-1i16).to_be_bytes());
+        payload.extend_from_slice(&3000i32.to_be_bytes());
+        payload.push(2);
+        payload.extend_from_slice(&[0x83, 0x01]);
+        payload.extend_from_slice(topic.as_bytes());
+        payload.push(2);
+        payload.extend_from_slice(&1i32.to_be_bytes());
+        payload.push(0);
+        payload.push(0);
+        payload.push(0);
+        payload.push(0);
+        let mut request = (payload.len() as u32).to_be_bytes().to_vec();
+        request.extend_from_slice(&payload);
+
+        let mut info = KafkaInfo::default();
+        kafka
+            .request(&request, true, &mut info)
+            .expect("parse produce request failed");
+        assert_eq!(info.topic_name, topic);
+        assert_eq!(info.partition, 1);
+    }
 }
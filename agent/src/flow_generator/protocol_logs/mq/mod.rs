@@ -15,17 +15,21 @@
  */
 
 mod amqp;
+mod amqp10;
 mod kafka;
 pub mod mqtt;
 mod nats;
 mod openwire;
 mod pulsar;
+pub(crate) mod stomp;
 mod zmtp;
 
 pub use amqp::{AmqpInfo, AmqpLog};
+pub use amqp10::Amqp10Info;
 pub use kafka::{KafkaInfo, KafkaLog};
 pub use mqtt::{MqttInfo, MqttLog};
 pub use nats::{NatsInfo, NatsLog};
 pub use openwire::{OpenWireInfo, OpenWireLog};
 pub use pulsar::{PulsarInfo, PulsarLog};
+pub use stomp::{StompInfo, StompLog};
 pub use zmtp::{ZmtpInfo, ZmtpLog};
@@ -0,0 +1,657 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// STOMP 1.0/1.1/1.2 over TCP, also used by the WebSocket parser for STOMP subprotocols.
+// Reference: https://stomp.github.io/stomp-specification-1.2.html
+
+use serde::Serialize;
+use std::str;
+
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, L7Protocol, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::{L7LogDynamicConfig, LogParserConfig},
+    flow_generator::{
+        error::{Error, Result},
+        protocol_logs::{
+            pb_adapter::{
+                ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, TraceInfo,
+            },
+            set_captured_byte, swap_if, value_is_default, AppProtoHead, L7ResponseStatus,
+            LogMessageType,
+        },
+    },
+};
+
+const CLIENT_COMMANDS: [&str; 11] = [
+    "SEND",
+    "SUBSCRIBE",
+    "UNSUBSCRIBE",
+    "ACK",
+    "NACK",
+    "BEGIN",
+    "COMMIT",
+    "ABORT",
+    "DISCONNECT",
+    "CONNECT",
+    "STOMP",
+];
+const SERVER_COMMANDS: [&str; 4] = ["CONNECTED", "MESSAGE", "RECEIPT", "ERROR"];
+
+const HEADER_DESTINATION: &str = "destination";
+const HEADER_RECEIPT: &str = "receipt";
+const HEADER_RECEIPT_ID: &str = "receipt-id";
+const HEADER_MESSAGE: &str = "message";
+const HEADER_HOST: &str = "host";
+const HEADER_VERSION: &str = "version";
+const HEADER_CONTENT_LENGTH: &str = "content-length";
+const HEADER_SUBSCRIPTION: &str = "subscription";
+const HEADER_MESSAGE_ID: &str = "message-id";
+
+fn read_line(payload: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = payload.iter().position(|b| *b == b'\n')?;
+    let line = &payload[..end];
+    Some((
+        line.strip_suffix(b"\r").unwrap_or(line),
+        &payload[end + 1..],
+    ))
+}
+
+pub(crate) struct StompFrame<'a> {
+    pub command: &'static str,
+    // the first header wins if repeated, values are not unescaped
+    headers: Vec<(&'a str, &'a str)>,
+    // false if the payload ends before the blank line after headers
+    headers_complete: bool,
+    // length of the frame with body and NUL terminator, or to the end of a truncated payload
+    len: usize,
+}
+
+impl<'a> StompFrame<'a> {
+    pub fn parse(payload: &'a [u8]) -> Option<Self> {
+        let (command, mut rest) = read_line(payload)?;
+        let command = str::from_utf8(command).ok()?;
+        let command = *CLIENT_COMMANDS
+            .iter()
+            .chain(SERVER_COMMANDS.iter())
+            .find(|c| **c == command)?;
+        let mut frame = StompFrame {
+            command,
+            headers: vec![],
+            headers_complete: false,
+            len: payload.len(),
+        };
+        while let Some((line, next)) = read_line(rest) {
+            rest = next;
+            if line.is_empty() {
+                frame.headers_complete = true;
+                break;
+            }
+            let line = str::from_utf8(line).ok()?;
+            let (key, value) = line.split_once(':')?;
+            if frame.headers.iter().all(|(k, _)| *k != key) {
+                frame.headers.push((key, value));
+            }
+        }
+        if !frame.headers_complete {
+            return Some(frame);
+        }
+        let body_start = payload.len() - rest.len();
+        let body_len = match frame
+            .header(HEADER_CONTENT_LENGTH)
+            .and_then(|l| l.parse::<usize>().ok())
+        {
+            Some(len) => Some(len),
+            None => rest.iter().position(|b| *b == 0),
+        };
+        if let Some(body_len) = body_len {
+            frame.len = (body_start + body_len + 1).min(payload.len());
+        }
+        Some(frame)
+    }
+
+    pub fn header(&self, key: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn is_client_command(&self) -> bool {
+        CLIENT_COMMANDS.contains(&self.command)
+    }
+}
+
+// receipt ids are arbitrary strings, use the number itself if it is one
+fn receipt_session_id(receipt: &str) -> u32 {
+    receipt.parse::<u32>().unwrap_or_else(|_| {
+        // FNV-1a
+        receipt.bytes().fold(0x811c9dc5u32, |h, b| {
+            (h ^ b as u32).wrapping_mul(0x01000193)
+        })
+    })
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct StompInfo {
+    msg_type: LogMessageType,
+    #[serde(skip)]
+    is_tls: bool,
+    rrt: u64,
+
+    #[serde(skip_serializing_if = "value_is_default")]
+    version: String,
+    #[serde(rename = "request_type")]
+    command: &'static str,
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    destination: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receipt: Option<String>,
+    req_len: Option<u32>,
+    resp_len: Option<u32>,
+
+    #[serde(rename = "response_status")]
+    status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    error_message: String,
+
+    #[serde(rename = "trace_id", skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(rename = "span_id", skip_serializing_if = "Option::is_none")]
+    span_id: Option<String>,
+
+    #[serde(skip)]
+    attributes: Vec<KeyVal>,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+    #[serde(skip)]
+    endpoint: Option<String>,
+}
+
+impl StompInfo {
+    fn new(frame: &StompFrame, config: Option<&L7LogDynamicConfig>) -> Self {
+        let mut info = StompInfo {
+            command: frame.command,
+            destination: frame
+                .header(HEADER_DESTINATION)
+                .unwrap_or_default()
+                .to_owned(),
+            ..Default::default()
+        };
+        info.msg_type = match frame.command {
+            "CONNECT" | "STOMP" => LogMessageType::Request,
+            "CONNECTED" | "RECEIPT" | "ERROR" => LogMessageType::Response,
+            _ if frame.is_client_command() && frame.header(HEADER_RECEIPT).is_some() => {
+                LogMessageType::Request
+            }
+            _ => LogMessageType::Session,
+        };
+        info.receipt = match frame.command {
+            "RECEIPT" | "ERROR" => frame.header(HEADER_RECEIPT_ID),
+            _ if frame.is_client_command() => frame.header(HEADER_RECEIPT),
+            _ => None,
+        }
+        .map(|r| r.to_owned());
+        match frame.command {
+            "CONNECT" | "STOMP" => {
+                info.host = frame.header(HEADER_HOST).unwrap_or_default().to_owned();
+            }
+            "CONNECTED" => {
+                info.version = frame.header(HEADER_VERSION).unwrap_or_default().to_owned();
+            }
+            "ERROR" => {
+                info.status = L7ResponseStatus::ServerError;
+                info.error_message = frame
+                    .header(HEADER_MESSAGE)
+                    .unwrap_or(frame.command)
+                    .to_owned();
+            }
+            "MESSAGE" => {
+                for (key, name) in [
+                    (HEADER_SUBSCRIPTION, "stomp_subscription"),
+                    (HEADER_MESSAGE_ID, "stomp_message_id"),
+                ] {
+                    if let Some(val) = frame.header(key) {
+                        info.attributes.push(KeyVal {
+                            key: name.to_string(),
+                            val: val.to_string(),
+                        });
+                    }
+                }
+            }
+            _ => (),
+        }
+        if let Some(config) = config {
+            for (key, value) in frame.headers.iter() {
+                if config.is_trace_id(key) {
+                    if let Some(trace_type) = config.trace_types.iter().find(|t| t.check(key)) {
+                        info.trace_id = trace_type.decode_trace_id(value).map(|id| id.to_string());
+                    }
+                }
+                if config.is_span_id(key) {
+                    if let Some(span_type) = config.span_types.iter().find(|t| t.check(key)) {
+                        info.span_id = span_type.decode_span_id(value).map(|id| id.to_string());
+                    }
+                }
+            }
+        }
+        if !info.destination.is_empty() {
+            info.endpoint = Some(info.destination.clone());
+        }
+        info
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::STOMP) {
+            self.is_on_blacklist = t.request_type.is_on_blacklist(self.command)
+                || t.request_domain.is_on_blacklist(&self.host)
+                || t.request_resource.is_on_blacklist(&self.destination)
+                || self
+                    .endpoint
+                    .as_ref()
+                    .map(|p| t.endpoint.is_on_blacklist(p))
+                    .unwrap_or_default();
+        }
+    }
+}
+
+impl From<StompInfo> for L7ProtocolSendLog {
+    fn from(info: StompInfo) -> Self {
+        let flags = match info.is_tls {
+            true => EbpfFlags::TLS.bits(),
+            false => EbpfFlags::NONE.bits(),
+        };
+        L7ProtocolSendLog {
+            captured_request_byte: info.captured_request_byte,
+            captured_response_byte: info.captured_response_byte,
+            flags,
+            version: Some(info.version),
+            req_len: info.req_len,
+            resp_len: info.resp_len,
+            req: L7Request {
+                req_type: info.command.to_string(),
+                domain: info.host,
+                resource: info.destination,
+                endpoint: info.endpoint.unwrap_or_default(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: info.status,
+                exception: info.error_message,
+                ..Default::default()
+            },
+            trace_info: Some(TraceInfo {
+                trace_id: info.trace_id,
+                span_id: info.span_id,
+                ..Default::default()
+            }),
+            ext_info: Some(ExtendedInfo {
+                attributes: {
+                    if info.attributes.is_empty() {
+                        None
+                    } else {
+                        Some(info.attributes)
+                    }
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl L7ProtocolInfoInterface for StompInfo {
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    // requests asking for a receipt are matched with RECEIPT or ERROR frames by receipt id
+    fn session_id(&self) -> Option<u32> {
+        self.receipt.as_deref().map(receipt_session_id)
+    }
+
+    fn get_endpoint(&self) -> Option<String> {
+        self.endpoint.clone()
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let (req, L7ProtocolInfo::StompInfo(rsp)) = (self, other) {
+            req.resp_len = rsp.resp_len;
+            req.captured_response_byte = rsp.captured_response_byte;
+            req.status = rsp.status;
+            swap_if!(req, version, is_empty, rsp);
+            swap_if!(req, error_message, is_empty, rsp);
+            swap_if!(req, endpoint, is_none, rsp);
+            req.attributes.append(&mut rsp.attributes);
+            if rsp.is_on_blacklist {
+                req.is_on_blacklist = rsp.is_on_blacklist;
+            }
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::STOMP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.host.clone()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+#[derive(Default)]
+pub struct StompLog {
+    perf_stats: Option<L7PerfStats>,
+    // from CONNECT and CONNECTED frames
+    host: String,
+    version: String,
+    last_is_on_blacklist: bool,
+}
+
+impl L7ProtocolParserInterface for StompLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() {
+            return false;
+        }
+        if param.l4_protocol != IpProtocol::TCP {
+            return false;
+        }
+        // skip heart-beats
+        let start = payload
+            .iter()
+            .position(|b| *b != b'\n' && *b != b'\r')
+            .unwrap_or(payload.len());
+        StompFrame::parse(&payload[start..])
+            .map(|f| f.headers_complete)
+            .unwrap_or_default()
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+
+        let config = param.parse_config.map(|c| &c.l7_log_dynamic);
+        let mut vec = Vec::new();
+        let mut payload = payload;
+        loop {
+            // heart-beats are end of lines between frames
+            let start = payload
+                .iter()
+                .position(|b| *b != b'\n' && *b != b'\r')
+                .unwrap_or(payload.len());
+            payload = &payload[start..];
+            if payload.is_empty() {
+                break;
+            }
+            let Some(frame) = StompFrame::parse(payload) else {
+                break;
+            };
+            let mut info = StompInfo::new(&frame, config);
+            match info.msg_type {
+                LogMessageType::Request => info.req_len = Some(frame.len as u32),
+                LogMessageType::Response => info.resp_len = Some(frame.len as u32),
+                _ => (),
+            }
+            vec.push(info);
+            payload = &payload[frame.len..];
+        }
+        if vec.is_empty() && !payload.is_empty() {
+            return Err(Error::L7ProtocolUnknown);
+        }
+
+        for info in vec.iter_mut() {
+            if !info.host.is_empty() {
+                self.host = info.host.clone();
+            } else {
+                info.host = self.host.clone();
+            }
+            if !info.version.is_empty() {
+                self.version = info.version.clone();
+            } else {
+                info.version = self.version.clone();
+            }
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match param.direction {
+                    PacketDirection::ClientToServer => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    PacketDirection::ServerToClient => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    }
+                }
+                if info.status == L7ResponseStatus::ServerError {
+                    self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                }
+                if info.msg_type != LogMessageType::Session {
+                    info.cal_rrt(param).map(|rrt| {
+                        info.rrt = rrt;
+                        self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                    });
+                }
+            }
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+
+        if !param.parse_log || vec.is_empty() {
+            Ok(L7ParseResult::None)
+        } else if vec.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::StompInfo(
+                vec.remove(0),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                vec.into_iter().map(L7ProtocolInfo::StompInfo).collect(),
+            ))
+        }
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::STOMP
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn reset(&mut self) {
+        *self = Self {
+            perf_stats: self.perf_stats.take(),
+            host: std::mem::take(&mut self.host),
+            version: std::mem::take(&mut self.version),
+            last_is_on_blacklist: self.last_is_on_blacklist,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    use crate::{
+        common::{l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn parse(
+        stomp: &mut StompLog,
+        cache: &Rc<RefCell<L7PerfCache>>,
+        payload: &[u8],
+        direction: PacketDirection,
+    ) -> Vec<StompInfo> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = direction;
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let param = &mut ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        let infos = match stomp.parse_payload(payload, param).unwrap() {
+            L7ParseResult::Single(i) => vec![i],
+            L7ParseResult::Multi(m) => m,
+            L7ParseResult::None => vec![],
+        };
+        infos
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::StompInfo(i) => i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check() {
+        let mut stomp = StompLog::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+
+        let connect = b"CONNECT\naccept-version:1.2\nhost:broker.example.com\n\n\0";
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let param = ParseParam::new(
+            &packet,
+            cache.clone(),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        assert!(stomp.check_payload(connect, &param));
+        assert!(!stomp.check_payload(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n", &param));
+        assert!(!stomp.check_payload(b"\n\n", &param));
+
+        let infos = parse(&mut stomp, &cache, connect, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].host, "broker.example.com");
+        let infos = parse(
+            &mut stomp,
+            &cache,
+            b"CONNECTED\r\nversion:1.2\r\nheart-beat:0,0\r\n\r\n\0\n",
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].msg_type, LogMessageType::Response);
+        assert_eq!(infos[0].version, "1.2");
+
+        // a SEND asking for receipt and a SEND without one, body contains NUL with content-length
+        let payload = [
+            &b"SEND\ndestination:/queue/orders\nreceipt:77\ncontent-length:5\n\nab\0cd\0"[..],
+            &b"\nSEND\ndestination:/topic/prices\n\n{}\0"[..],
+        ]
+        .concat();
+        let infos = parse(
+            &mut stomp,
+            &cache,
+            &payload,
+            PacketDirection::ClientToServer,
+        );
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].session_id(), Some(77));
+        assert_eq!(infos[0].endpoint.as_deref(), Some("/queue/orders"));
+        assert_eq!(infos[0].host, "broker.example.com");
+        assert_eq!(infos[1].msg_type, LogMessageType::Session);
+        assert_eq!(infos[1].destination, "/topic/prices");
+
+        let infos = parse(
+            &mut stomp,
+            &cache,
+            b"RECEIPT\nreceipt-id:77\n\n\0",
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].session_id(), Some(77));
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+
+        let infos = parse(
+            &mut stomp,
+            &cache,
+            b"MESSAGE\nsubscription:0\nmessage-id:007\ndestination:/queue/a\n\nhello\0",
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].msg_type, LogMessageType::Session);
+        assert_eq!(infos[0].attributes.len(), 2);
+    }
+
+    #[test]
+    fn error() {
+        let mut stomp = StompLog::default();
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let infos = parse(
+            &mut stomp,
+            &cache,
+            b"SUBSCRIBE\nid:0\ndestination:/queue/secret\nreceipt:sub-0\n\n\0",
+            PacketDirection::ClientToServer,
+        );
+        let mut req = L7ProtocolInfo::StompInfo(infos.into_iter().next().unwrap());
+        let infos = parse(
+            &mut stomp,
+            &cache,
+            b"ERROR\nreceipt-id:sub-0\nmessage:Access refused\ncontent-type:text/plain\n\nno",
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(req.session_id(), infos[0].session_id());
+        let mut resp = L7ProtocolInfo::StompInfo(infos.into_iter().next().unwrap());
+        req.merge_log(&mut resp).unwrap();
+        let L7ProtocolInfo::StompInfo(info) = req else {
+            unreachable!()
+        };
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+        assert_eq!(info.error_message, "Access refused");
+        assert_eq!(info.endpoint.as_deref(), Some("/queue/secret"));
+
+        let perf_stats = stomp.perf_stats().unwrap();
+        assert_eq!(perf_stats.err_server_count, 1);
+    }
+}
@@ -0,0 +1,1227 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{set_captured_byte, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u32_be, read_u64_be},
+};
+use public::l7_protocol::L7Protocol;
+
+// record marking of ONC-RPC over TCP, the highest bit marks the last fragment
+const RECORD_MARK_LEN: usize = 4;
+const RECORD_LENGTH_MASK: u32 = 0x7fff_ffff;
+
+const RPC_CALL: u32 = 0;
+const RPC_REPLY: u32 = 1;
+const RPC_VERSION: u32 = 2;
+const MSG_ACCEPTED: u32 = 0;
+const MSG_DENIED: u32 = 1;
+const ACCEPT_SUCCESS: u32 = 0;
+const ACCEPT_SYSTEM_ERR: u32 = 5;
+const MAX_AUTH_LEN: usize = 400;
+
+const NFS_PROGRAM: u32 = 100003;
+const NFS_V3: u32 = 3;
+const NFS_V4: u32 = 4;
+const NFS_OK: u32 = 0;
+
+const NFS3_PROC_NULL: u32 = 0;
+const NFS3_PROC_LOOKUP: u32 = 3;
+const NFS3_PROC_CREATE: u32 = 8;
+const NFS3_PROC_MKDIR: u32 = 9;
+const NFS3_PROC_SYMLINK: u32 = 10;
+const NFS3_PROC_MKNOD: u32 = 11;
+const NFS3_PROC_REMOVE: u32 = 12;
+const NFS3_PROC_RMDIR: u32 = 13;
+const NFS3_PROC_RENAME: u32 = 14;
+const NFS3_PROC_LINK: u32 = 15;
+const NFS3_PROCS: [&'static str; 22] = [
+    "NULL",
+    "GETATTR",
+    "SETATTR",
+    "LOOKUP",
+    "ACCESS",
+    "READLINK",
+    "READ",
+    "WRITE",
+    "CREATE",
+    "MKDIR",
+    "SYMLINK",
+    "MKNOD",
+    "REMOVE",
+    "RMDIR",
+    "RENAME",
+    "LINK",
+    "READDIR",
+    "READDIRPLUS",
+    "FSSTAT",
+    "FSINFO",
+    "PATHCONF",
+    "COMMIT",
+];
+
+const NFS4_PROC_COMPOUND: u32 = 1;
+const NFS4_OP_ACCESS: u32 = 3;
+const NFS4_OP_CLOSE: u32 = 4;
+const NFS4_OP_COMMIT: u32 = 5;
+const NFS4_OP_GETATTR: u32 = 9;
+const NFS4_OP_GETFH: u32 = 10;
+const NFS4_OP_LOOKUP: u32 = 15;
+const NFS4_OP_LOOKUPP: u32 = 16;
+const NFS4_OP_OPEN: u32 = 18;
+const NFS4_OP_PUTFH: u32 = 22;
+const NFS4_OP_PUTPUBFH: u32 = 23;
+const NFS4_OP_PUTROOTFH: u32 = 24;
+const NFS4_OP_READ: u32 = 25;
+const NFS4_OP_READLINK: u32 = 27;
+const NFS4_OP_REMOVE: u32 = 28;
+const NFS4_OP_RESTOREFH: u32 = 31;
+const NFS4_OP_SAVEFH: u32 = 32;
+const NFS4_OP_SEQUENCE: u32 = 53;
+// operations from NFS4_OP_ACCESS to NFS4_OP_RECLAIM_COMPLETE
+const NFS4_OPS: [&'static str; 56] = [
+    "ACCESS",
+    "CLOSE",
+    "COMMIT",
+    "CREATE",
+    "DELEGPURGE",
+    "DELEGRETURN",
+    "GETATTR",
+    "GETFH",
+    "LINK",
+    "LOCK",
+    "LOCKT",
+    "LOCKU",
+    "LOOKUP",
+    "LOOKUPP",
+    "NVERIFY",
+    "OPEN",
+    "OPENATTR",
+    "OPEN_CONFIRM",
+    "OPEN_DOWNGRADE",
+    "PUTFH",
+    "PUTPUBFH",
+    "PUTROOTFH",
+    "READ",
+    "READDIR",
+    "READLINK",
+    "REMOVE",
+    "RENAME",
+    "RENEW",
+    "RESTOREFH",
+    "SAVEFH",
+    "SECINFO",
+    "SETATTR",
+    "SETCLIENTID",
+    "SETCLIENTID_CONFIRM",
+    "VERIFY",
+    "WRITE",
+    "RELEASE_LOCKOWNER",
+    "BACKCHANNEL_CTL",
+    "BIND_CONN_TO_SESSION",
+    "EXCHANGE_ID",
+    "CREATE_SESSION",
+    "DESTROY_SESSION",
+    "FREE_STATEID",
+    "GET_DIR_DELEGATION",
+    "GETDEVICEINFO",
+    "GETDEVICELIST",
+    "LAYOUTCOMMIT",
+    "LAYOUTGET",
+    "LAYOUTRETURN",
+    "SECINFO_NO_NAME",
+    "SEQUENCE",
+    "SET_SSV",
+    "TEST_STATEID",
+    "WANT_DELEGATION",
+    "DESTROY_CLIENTID",
+    "RECLAIM_COMPLETE",
+];
+const NFS4_STATEID_LEN: usize = 16;
+const NFS4_SESSIONID_LEN: usize = 16;
+const NFS4_VERIFIER_LEN: usize = 8;
+const NFS4_OPEN_CREATE: u32 = 1;
+const NFS4_CREATE_EXCLUSIVE: u32 = 2;
+const NFS4_CREATE_EXCLUSIVE4_1: u32 = 3;
+const NFS4_CLAIM_NULL: u32 = 0;
+const NFS4_MAX_OPS: u32 = 64;
+
+const NFSERR_IO: u32 = 5;
+const NFSERR_NOSPC: u32 = 28;
+const NFSERR_SERVERFAULT: u32 = 10006;
+const NFSERR_DELAY: u32 = 10008;
+const NFSERR_GRACE: u32 = 10013;
+
+fn v3_proc_str(procedure: u32) -> &'static str {
+    NFS3_PROCS
+        .get(procedure as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+fn v4_op_str(op: u32) -> &'static str {
+    op.checked_sub(NFS4_OP_ACCESS)
+        .and_then(|i| NFS4_OPS.get(i as usize).copied())
+        .unwrap_or_default()
+}
+
+// names shared by nfsstat3 and nfsstat4, without the NFS3ERR_ or NFS4ERR_ prefix
+fn nfs_status_str(status: u32) -> &'static str {
+    match status {
+        1 => "PERM",
+        2 => "NOENT",
+        5 => "IO",
+        6 => "NXIO",
+        13 => "ACCESS",
+        17 => "EXIST",
+        18 => "XDEV",
+        19 => "NODEV",
+        20 => "NOTDIR",
+        21 => "ISDIR",
+        22 => "INVAL",
+        27 => "FBIG",
+        28 => "NOSPC",
+        30 => "ROFS",
+        31 => "MLINK",
+        63 => "NAMETOOLONG",
+        66 => "NOTEMPTY",
+        69 => "DQUOT",
+        70 => "STALE",
+        71 => "REMOTE",
+        10001 => "BADHANDLE",
+        10002 => "NOT_SYNC",
+        10003 => "BAD_COOKIE",
+        10004 => "NOTSUPP",
+        10005 => "TOOSMALL",
+        10006 => "SERVERFAULT",
+        10007 => "BADTYPE",
+        10008 => "DELAY",
+        10009 => "SAME",
+        10010 => "DENIED",
+        10011 => "EXPIRED",
+        10012 => "LOCKED",
+        10013 => "GRACE",
+        10014 => "FHEXPIRED",
+        10015 => "SHARE_DENIED",
+        10016 => "WRONGSEC",
+        10017 => "CLID_INUSE",
+        10018 => "RESOURCE",
+        10019 => "MOVED",
+        10020 => "NOFILEHANDLE",
+        10021 => "MINOR_VERS_MISMATCH",
+        10022 => "STALE_CLIENTID",
+        10023 => "STALE_STATEID",
+        10024 => "OLD_STATEID",
+        10025 => "BAD_STATEID",
+        10026 => "BAD_SEQID",
+        10036 => "BADXDR",
+        10052 => "BADSESSION",
+        10053 => "BADSLOT",
+        10055 => "CONN_NOT_BOUND_TO_SESSION",
+        10063 => "SEQ_MISORDERED",
+        _ => "",
+    }
+}
+
+fn rpc_accept_stat_str(stat: u32) -> &'static str {
+    match stat {
+        1 => "PROG_UNAVAIL",
+        2 => "PROG_MISMATCH",
+        3 => "PROC_UNAVAIL",
+        4 => "GARBAGE_ARGS",
+        5 => "SYSTEM_ERR",
+        _ => "",
+    }
+}
+
+fn rpc_reject_stat_str(stat: u32) -> &'static str {
+    match stat {
+        0 => "RPC_MISMATCH",
+        1 => "AUTH_ERROR",
+        _ => "",
+    }
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+// XDR decoder, all items are 4 bytes aligned
+struct XdrReader<'a>(&'a [u8]);
+
+impl<'a> XdrReader<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let v = self.0.get(..4).map(read_u32_be)?;
+        self.0 = &self.0[4..];
+        Some(v)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let v = self.0.get(..8).map(read_u64_be)?;
+        self.0 = &self.0[8..];
+        Some(v)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.0 = self.0.get(n..)?;
+        Some(())
+    }
+
+    fn opaque(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        let v = self.0.get(..len)?;
+        self.0 = self.0.get((len + 3) & !3..).unwrap_or_default();
+        Some(v)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.opaque()
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+    }
+
+    fn bitmap(&mut self) -> Option<()> {
+        let n = self.u32()? as usize;
+        self.skip(n.checked_mul(4)?)
+    }
+
+    // opaque_auth of credential and verifier
+    fn auth(&mut self) -> Option<()> {
+        let _flavor = self.u32()?;
+        let len = self.u32()? as usize;
+        if len > MAX_AUTH_LEN {
+            return None;
+        }
+        self.skip((len + 3) & !3)
+    }
+}
+
+// NFS call waiting for the reply, replies carry only the xid
+#[derive(Debug, Clone, Default)]
+struct NfsCall {
+    version: u32,
+    procedure: u32,
+    path: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct NfsInfo {
+    msg_type: LogMessageType,
+
+    #[serde(rename = "request_id")]
+    xid: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    version: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    minor_version: u32,
+    // procedure of NFSv3, or the main operation of NFSv4 COMPOUND
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    procedure: String,
+    // file path when known, or the file handle in hex
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    resource: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    file_handle: String,
+    // all operations of NFSv4 COMPOUND, such as `SEQUENCE,PUTFH,READ`
+    #[serde(skip_serializing_if = "value_is_default")]
+    operations: String,
+
+    #[serde(skip)]
+    req_len: u32,
+    #[serde(skip)]
+    resp_len: u32,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    nfs_status: Option<u32>,
+    #[serde(rename = "response_status")]
+    status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    exception: String,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+    rrt: u64,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for NfsInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.xid)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::NfsInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::NFS,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.resource.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl NfsInfo {
+    fn merge(&mut self, other: &mut Self) {
+        self.resp_len = other.resp_len;
+        self.nfs_status = other.nfs_status;
+        self.status = other.status;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+        self.captured_response_byte = other.captured_response_byte;
+        self.is_on_blacklist |= other.is_on_blacklist;
+    }
+
+    fn set_nfs_status(&mut self, nfs_status: u32) {
+        self.nfs_status = Some(nfs_status);
+        if nfs_status == NFS_OK {
+            self.status = L7ResponseStatus::Ok;
+            return;
+        }
+        self.status = match nfs_status {
+            NFSERR_IO | NFSERR_NOSPC | NFSERR_SERVERFAULT | NFSERR_DELAY | NFSERR_GRACE => {
+                L7ResponseStatus::ServerError
+            }
+            _ => L7ResponseStatus::ClientError,
+        };
+        let prefix = if self.version == NFS_V4 {
+            "NFS4ERR_"
+        } else {
+            "NFS3ERR_"
+        };
+        self.exception = match nfs_status_str(nfs_status) {
+            "" => format!("{}{}", prefix, nfs_status),
+            s => format!("{}{}", prefix, s),
+        };
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::NFS) {
+            self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.resource)
+                || t.request_type.is_on_blacklist(&self.procedure);
+        }
+    }
+}
+
+impl From<NfsInfo> for L7ProtocolSendLog {
+    fn from(f: NfsInfo) -> Self {
+        let mut attributes = vec![];
+        for (key, val) in [("file_handle", f.file_handle), ("operations", f.operations)] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req_len: if f.req_len != 0 {
+                Some(f.req_len)
+            } else {
+                None
+            },
+            resp_len: if f.resp_len != 0 {
+                Some(f.resp_len)
+            } else {
+                None
+            },
+            version: match (f.version, f.minor_version) {
+                (0, _) => None,
+                (v, 0) => Some(v.to_string()),
+                (v, m) => Some(format!("{}.{}", v, m)),
+            },
+            req: L7Request {
+                req_type: f.procedure,
+                resource: f.resource,
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: f.status,
+                code: f.nfs_status.map(|s| s as i32),
+                exception: f.exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.xid),
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            flags: EbpfFlags::NONE.bits(),
+            ..Default::default()
+        }
+    }
+}
+
+// NFSv3 and NFSv4 over TCP.
+//
+// File handles are opaque, their paths are learned from LOOKUP and CREATE of NFSv3, or
+// LOOKUP and OPEN followed by GETFH of NFSv4, and remembered per flow.
+pub struct NfsLog {
+    perf_stats: Option<L7PerfStats>,
+    last_is_on_blacklist: bool,
+
+    calls: LruCache<u32, NfsCall>,
+    handles: LruCache<Vec<u8>, String>,
+}
+
+impl Default for NfsLog {
+    fn default() -> Self {
+        Self {
+            perf_stats: None,
+            last_is_on_blacklist: false,
+            calls: LruCache::new(NonZeroUsize::new(Self::MAX_CALL_PER_FLOW).unwrap()),
+            handles: LruCache::new(NonZeroUsize::new(Self::MAX_HANDLE_PER_FLOW).unwrap()),
+        }
+    }
+}
+
+impl L7ProtocolParserInterface for NfsLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
+            return false;
+        }
+        let Some(record) = payload.get(RECORD_MARK_LEN..) else {
+            return false;
+        };
+        let mut r = XdrReader(record);
+        let (Some(_xid), Some(RPC_CALL), Some(RPC_VERSION), Some(NFS_PROGRAM), Some(version)) =
+            (r.u32(), r.u32(), r.u32(), r.u32(), r.u32())
+        else {
+            return false;
+        };
+        version == NFS_V3 || version == NFS_V4
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+
+        let mut vec = self.parse(payload)?;
+        for info in vec.iter_mut() {
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match info.msg_type {
+                    LogMessageType::Request => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    _ => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    }
+                }
+                match info.status {
+                    L7ResponseStatus::ClientError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                    L7ResponseStatus::ServerError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    }
+                    _ => {}
+                }
+                info.cal_rrt(param).map(|rrt| {
+                    info.rrt = rrt;
+                    self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                });
+            }
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+
+        if !param.parse_log {
+            Ok(L7ParseResult::None)
+        } else if vec.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::NfsInfo(
+                vec.remove(0),
+            )))
+        } else if vec.len() > 1 {
+            Ok(L7ParseResult::Multi(
+                vec.into_iter().map(L7ProtocolInfo::NfsInfo).collect(),
+            ))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::NFS
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl NfsLog {
+    const MAX_CALL_PER_FLOW: usize = 64;
+    const MAX_HANDLE_PER_FLOW: usize = 256;
+
+    fn parse(&mut self, mut payload: &[u8]) -> Result<Vec<NfsInfo>> {
+        let mut infos = vec![];
+        while payload.len() > RECORD_MARK_LEN {
+            let len = (read_u32_be(payload) & RECORD_LENGTH_MASK) as usize;
+            let end = RECORD_MARK_LEN.saturating_add(len).min(payload.len());
+            let record = &payload[RECORD_MARK_LEN..end];
+            let Some(info) = self.parse_record(record) else {
+                break;
+            };
+            infos.push(info);
+            payload = &payload[end..];
+        }
+        if infos.is_empty() {
+            return Err(Error::L7LogParseFailed {
+                proto: L7Protocol::NFS,
+                reason: "invalid nfs rpc record".into(),
+            });
+        }
+        Ok(infos)
+    }
+
+    fn parse_record(&mut self, record: &[u8]) -> Option<NfsInfo> {
+        let mut r = XdrReader(record);
+        let xid = r.u32()?;
+        match r.u32()? {
+            RPC_CALL => self.parse_call(xid, record.len(), r),
+            RPC_REPLY => self.parse_reply(xid, record.len(), r),
+            _ => None,
+        }
+    }
+
+    fn parse_call(&mut self, xid: u32, len: usize, mut r: XdrReader) -> Option<NfsInfo> {
+        if r.u32()? != RPC_VERSION || r.u32()? != NFS_PROGRAM {
+            return None;
+        }
+        let version = r.u32()?;
+        if version != NFS_V3 && version != NFS_V4 {
+            return None;
+        }
+        let procedure = r.u32()?;
+        let mut info = NfsInfo {
+            msg_type: LogMessageType::Request,
+            xid,
+            version,
+            req_len: len as u32,
+            ..Default::default()
+        };
+        let mut call = NfsCall {
+            version,
+            procedure,
+            ..Default::default()
+        };
+        // arguments may be truncated, keep what is parsed
+        if r.auth().and_then(|_| r.auth()).is_some() {
+            if version == NFS_V3 {
+                self.parse_v3_args(procedure, r, &mut info, &mut call);
+            } else if procedure == NFS4_PROC_COMPOUND {
+                self.parse_v4_args(r, &mut info, &mut call);
+            }
+        }
+        if info.procedure.is_empty() {
+            info.procedure = if version == NFS_V3 {
+                v3_proc_str(procedure).to_string()
+            } else if procedure == NFS4_PROC_COMPOUND {
+                "COMPOUND".to_string()
+            } else {
+                "NULL".to_string()
+            };
+        }
+        self.calls.put(xid, call);
+        Some(info)
+    }
+
+    fn parse_v3_args(
+        &mut self,
+        procedure: u32,
+        mut r: XdrReader,
+        info: &mut NfsInfo,
+        call: &mut NfsCall,
+    ) {
+        info.procedure = v3_proc_str(procedure).to_string();
+        if procedure == NFS3_PROC_NULL {
+            return;
+        }
+        if procedure == NFS3_PROC_LINK && r.opaque().is_none() {
+            return;
+        }
+        let Some(fh) = r.opaque() else {
+            return;
+        };
+        info.file_handle = hex::encode(fh);
+        let dir = self.handles.get(fh).cloned();
+        match procedure {
+            NFS3_PROC_LOOKUP | NFS3_PROC_CREATE | NFS3_PROC_MKDIR | NFS3_PROC_SYMLINK
+            | NFS3_PROC_MKNOD | NFS3_PROC_REMOVE | NFS3_PROC_RMDIR | NFS3_PROC_RENAME
+            | NFS3_PROC_LINK => {
+                if let Some(name) = r.string() {
+                    call.path = join_path(dir.as_deref().unwrap_or_default(), &name);
+                    info.resource = call.path.clone();
+                }
+            }
+            _ => {
+                info.resource = dir.unwrap_or_else(|| info.file_handle.clone());
+            }
+        }
+    }
+
+    fn parse_v4_args(&mut self, mut r: XdrReader, info: &mut NfsInfo, call: &mut NfsCall) {
+        let _tag = r.opaque();
+        let Some(minor_version) = r.u32() else {
+            return;
+        };
+        info.minor_version = minor_version;
+        let Some(num_ops) = r.u32() else {
+            return;
+        };
+        let mut ops = vec![];
+        let mut path = String::new();
+        let mut fh = None;
+        for _ in 0..num_ops.min(NFS4_MAX_OPS) {
+            let Some(op) = r.u32() else {
+                break;
+            };
+            ops.push(op);
+            if self.parse_v4_op(op, &mut r, &mut path, &mut fh).is_none() {
+                break;
+            }
+        }
+
+        // the first operation other than setting up the current file handle
+        let main_op = ops
+            .iter()
+            .find(|op| {
+                !matches!(
+                    **op,
+                    NFS4_OP_SEQUENCE
+                        | NFS4_OP_PUTFH
+                        | NFS4_OP_PUTROOTFH
+                        | NFS4_OP_PUTPUBFH
+                        | NFS4_OP_GETFH
+                        | NFS4_OP_GETATTR
+                        | NFS4_OP_SAVEFH
+                        | NFS4_OP_RESTOREFH
+                )
+            })
+            .or(ops.last());
+        if let Some(op) = main_op {
+            info.procedure = v4_op_str(*op).to_string();
+        }
+        info.operations = ops
+            .iter()
+            .map(|op| v4_op_str(*op))
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Some(fh) = fh {
+            info.file_handle = hex::encode(fh);
+        }
+        info.resource = if path.is_empty() {
+            info.file_handle.clone()
+        } else {
+            path.clone()
+        };
+        call.procedure = main_op.copied().unwrap_or_default();
+        call.path = path;
+    }
+
+    // parses arguments of the operation, returns None for operations not supported
+    fn parse_v4_op<'a>(
+        &mut self,
+        op: u32,
+        r: &mut XdrReader<'a>,
+        path: &mut String,
+        fh: &mut Option<&'a [u8]>,
+    ) -> Option<()> {
+        match op {
+            NFS4_OP_PUTFH => {
+                let handle = r.opaque()?;
+                *path = self.handles.get(handle).cloned().unwrap_or_default();
+                *fh = Some(handle);
+            }
+            NFS4_OP_PUTROOTFH | NFS4_OP_PUTPUBFH => *path = "/".to_string(),
+            NFS4_OP_LOOKUP | NFS4_OP_REMOVE => *path = join_path(path, &r.string()?),
+            NFS4_OP_OPEN => {
+                // seqid, share_access, share_deny, and owner
+                r.skip(12)?;
+                r.u64()?;
+                r.opaque()?;
+                if r.u32()? == NFS4_OPEN_CREATE {
+                    match r.u32()? {
+                        NFS4_CREATE_EXCLUSIVE => r.skip(NFS4_VERIFIER_LEN)?,
+                        NFS4_CREATE_EXCLUSIVE4_1 => {
+                            r.skip(NFS4_VERIFIER_LEN)?;
+                            r.bitmap()?;
+                            r.opaque()?;
+                        }
+                        _ => {
+                            r.bitmap()?;
+                            r.opaque()?;
+                        }
+                    }
+                }
+                if r.u32()? != NFS4_CLAIM_NULL {
+                    return None;
+                }
+                *path = join_path(path, &r.string()?);
+            }
+            NFS4_OP_SEQUENCE => r.skip(NFS4_SESSIONID_LEN + 16)?,
+            NFS4_OP_GETFH | NFS4_OP_SAVEFH | NFS4_OP_RESTOREFH | NFS4_OP_READLINK => (),
+            NFS4_OP_GETATTR => r.bitmap()?,
+            NFS4_OP_ACCESS => r.skip(4)?,
+            NFS4_OP_READ => r.skip(NFS4_STATEID_LEN + 12)?,
+            NFS4_OP_CLOSE => r.skip(4 + NFS4_STATEID_LEN)?,
+            NFS4_OP_COMMIT => r.skip(12)?,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn parse_reply(&mut self, xid: u32, len: usize, mut r: XdrReader) -> Option<NfsInfo> {
+        let call = self.calls.pop(&xid);
+        let mut info = NfsInfo {
+            msg_type: LogMessageType::Response,
+            xid,
+            version: call.as_ref().map(|c| c.version).unwrap_or_default(),
+            resp_len: len as u32,
+            ..Default::default()
+        };
+        match r.u32()? {
+            MSG_ACCEPTED => {
+                r.auth()?;
+                let stat = r.u32()?;
+                if stat != ACCEPT_SUCCESS {
+                    info.status = if stat == ACCEPT_SYSTEM_ERR {
+                        L7ResponseStatus::ServerError
+                    } else {
+                        L7ResponseStatus::ClientError
+                    };
+                    info.exception = rpc_accept_stat_str(stat).to_string();
+                    return Some(info);
+                }
+            }
+            MSG_DENIED => {
+                info.status = L7ResponseStatus::ClientError;
+                info.exception = rpc_reject_stat_str(r.u32()?).to_string();
+                return Some(info);
+            }
+            _ => return None,
+        }
+        let Some(call) = call else {
+            // the status is unknown for NULL procedure without the call
+            return Some(info);
+        };
+        if call.procedure == NFS3_PROC_NULL && call.version == NFS_V3 {
+            return Some(info);
+        }
+        let Some(status) = r.u32() else {
+            return Some(info);
+        };
+        info.set_nfs_status(status);
+        if status == NFS_OK && !call.path.is_empty() {
+            if call.version == NFS_V3 {
+                self.parse_v3_result(&call, r);
+            } else {
+                self.parse_v4_result(&call, r);
+            }
+        }
+        Some(info)
+    }
+
+    fn parse_v3_result(&mut self, call: &NfsCall, mut r: XdrReader) -> Option<()> {
+        let fh = match call.procedure {
+            NFS3_PROC_LOOKUP => r.opaque()?,
+            // post_op_fh3
+            NFS3_PROC_CREATE | NFS3_PROC_MKDIR | NFS3_PROC_SYMLINK | NFS3_PROC_MKNOD => {
+                if r.u32()? == 0 {
+                    return None;
+                }
+                r.opaque()?
+            }
+            _ => return None,
+        };
+        self.handles.put(fh.to_vec(), call.path.clone());
+        Some(())
+    }
+
+    fn parse_v4_result(&mut self, call: &NfsCall, mut r: XdrReader) -> Option<()> {
+        let _tag = r.opaque()?;
+        let num_results = r.u32()?;
+        for _ in 0..num_results.min(NFS4_MAX_OPS) {
+            let op = r.u32()?;
+            if r.u32()? != NFS_OK {
+                return None;
+            }
+            match op {
+                NFS4_OP_GETFH => {
+                    let fh = r.opaque()?;
+                    self.handles.put(fh.to_vec(), call.path.clone());
+                    return Some(());
+                }
+                NFS4_OP_SEQUENCE => r.skip(NFS4_SESSIONID_LEN + 20)?,
+                NFS4_OP_PUTFH | NFS4_OP_PUTROOTFH | NFS4_OP_PUTPUBFH | NFS4_OP_LOOKUP
+                | NFS4_OP_LOOKUPP | NFS4_OP_SAVEFH | NFS4_OP_RESTOREFH => (),
+                NFS4_OP_GETATTR => {
+                    r.bitmap()?;
+                    r.opaque()?;
+                }
+                NFS4_OP_ACCESS => r.skip(8)?,
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    const ROOT_FH: &[u8] = &[0x01; 28];
+    const FILE_FH: &[u8] = &[0x02; 32];
+
+    #[derive(Default)]
+    struct XdrWriter(Vec<u8>);
+
+    impl XdrWriter {
+        fn u32(mut self, v: u32) -> Self {
+            self.0.extend_from_slice(&v.to_be_bytes());
+            self
+        }
+
+        fn u64(mut self, v: u64) -> Self {
+            self.0.extend_from_slice(&v.to_be_bytes());
+            self
+        }
+
+        fn opaque(mut self, v: &[u8]) -> Self {
+            self = self.u32(v.len() as u32);
+            self.0.extend_from_slice(v);
+            self.0.resize((self.0.len() + 3) & !3, 0);
+            self
+        }
+
+        fn bytes(mut self, v: &[u8]) -> Self {
+            self.0.extend_from_slice(v);
+            self
+        }
+
+        fn record(self) -> Vec<u8> {
+            let mark = 0x8000_0000 | self.0.len() as u32;
+            [&mark.to_be_bytes()[..], &self.0].concat()
+        }
+    }
+
+    fn call(xid: u32, version: u32, procedure: u32) -> XdrWriter {
+        XdrWriter::default()
+            .u32(xid)
+            .u32(RPC_CALL)
+            .u32(RPC_VERSION)
+            .u32(NFS_PROGRAM)
+            .u32(version)
+            .u32(procedure)
+            // AUTH_UNIX credential
+            .u32(1)
+            .opaque(&[0x5f, 0x5f, 0x5f, 0x5f, 0, 0, 0, 0, 0, 0, 0, 0])
+            // AUTH_NONE verifier
+            .u32(0)
+            .u32(0)
+    }
+
+    fn reply(xid: u32) -> XdrWriter {
+        XdrWriter::default()
+            .u32(xid)
+            .u32(RPC_REPLY)
+            .u32(MSG_ACCEPTED)
+            .u32(0)
+            .u32(0)
+            .u32(ACCEPT_SUCCESS)
+    }
+
+    fn parse(parser: &mut NfsLog, payload: &[u8], direction: PacketDirection) -> Vec<NfsInfo> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        packet.lookup_key.direction = direction;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        match parser.parse_payload(payload, &param) {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::NfsInfo(info))) => vec![info],
+            Ok(L7ParseResult::Multi(infos)) => infos
+                .into_iter()
+                .map(|i| match i {
+                    L7ProtocolInfo::NfsInfo(info) => info,
+                    _ => unreachable!(),
+                })
+                .collect(),
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+
+    fn check(payload: &[u8]) -> bool {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        NfsLog::default().check_payload(payload, &param)
+    }
+
+    #[test]
+    fn v3_lookup_and_read() {
+        let mut parser = NfsLog::default();
+        parser.handles.put(ROOT_FH.to_vec(), "/export".to_string());
+
+        let req = call(0x100, NFS_V3, NFS3_PROC_LOOKUP)
+            .opaque(ROOT_FH)
+            .opaque(b"data.bin")
+            .record();
+        assert!(check(&req));
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].procedure, "LOOKUP");
+        assert_eq!(infos[0].resource, "/export/data.bin");
+        assert_eq!(infos[0].session_id(), Some(0x100));
+
+        let resp = reply(0x100).u32(NFS_OK).opaque(FILE_FH).record();
+        assert!(!check(&resp));
+        let infos = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+        assert_eq!(infos[0].nfs_status, Some(NFS_OK));
+
+        // pipelined calls in one segment
+        let req = [
+            call(0x101, NFS_V3, 6)
+                .opaque(FILE_FH)
+                .u64(0)
+                .u32(4096)
+                .record(),
+            call(0x102, NFS_V3, 1).opaque(&[0x03; 8]).record(),
+        ]
+        .concat();
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].procedure, "READ");
+        assert_eq!(infos[0].resource, "/export/data.bin");
+        assert_eq!(infos[1].procedure, "GETATTR");
+        assert_eq!(infos[1].resource, "0303030303030303");
+
+        let resp = reply(0x102).u32(70).record();
+        let infos = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].exception, "NFS3ERR_STALE");
+
+        let perf_stats = parser.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 3);
+        assert_eq!(perf_stats.response_count, 2);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+
+    #[test]
+    fn v4_compound() {
+        let mut parser = NfsLog::default();
+        let sequence = [0x07; NFS4_SESSIONID_LEN + 16];
+        let req = call(0x200, NFS_V4, NFS4_PROC_COMPOUND)
+            .opaque(b"")
+            .u32(1)
+            .u32(5)
+            .u32(NFS4_OP_SEQUENCE)
+            .bytes(&sequence)
+            .u32(NFS4_OP_PUTROOTFH)
+            .u32(NFS4_OP_LOOKUP)
+            .opaque(b"home")
+            .u32(NFS4_OP_GETFH)
+            .u32(NFS4_OP_GETATTR)
+            .u32(2)
+            .u32(0x0010_011a)
+            .u32(0x00b0_a23a)
+            .record();
+        assert!(check(&req));
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].procedure, "LOOKUP");
+        assert_eq!(
+            infos[0].operations,
+            "SEQUENCE,PUTROOTFH,LOOKUP,GETFH,GETATTR"
+        );
+        assert_eq!(infos[0].resource, "/home");
+        assert_eq!(infos[0].minor_version, 1);
+
+        let resp = reply(0x200)
+            .u32(NFS_OK)
+            .opaque(b"")
+            .u32(5)
+            .u32(NFS4_OP_SEQUENCE)
+            .u32(NFS_OK)
+            .bytes(&[0x07; NFS4_SESSIONID_LEN + 20])
+            .u32(NFS4_OP_PUTROOTFH)
+            .u32(NFS_OK)
+            .u32(NFS4_OP_LOOKUP)
+            .u32(NFS_OK)
+            .u32(NFS4_OP_GETFH)
+            .u32(NFS_OK)
+            .opaque(FILE_FH)
+            .record();
+        parse(&mut parser, &resp, PacketDirection::ServerToClient);
+
+        // OPEN with CLAIM_NULL under the learned file handle
+        let req = call(0x201, NFS_V4, NFS4_PROC_COMPOUND)
+            .opaque(b"open")
+            .u32(1)
+            .u32(3)
+            .u32(NFS4_OP_SEQUENCE)
+            .bytes(&sequence)
+            .u32(NFS4_OP_PUTFH)
+            .opaque(FILE_FH)
+            .u32(NFS4_OP_OPEN)
+            .u32(0)
+            .u32(1)
+            .u32(0)
+            .u64(0x1234)
+            .opaque(b"open id")
+            .u32(0)
+            .u32(NFS4_CLAIM_NULL)
+            .opaque(b"notes.txt")
+            .record();
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].procedure, "OPEN");
+        assert_eq!(infos[0].resource, "/home/notes.txt");
+        assert_eq!(infos[0].file_handle, hex::encode(FILE_FH));
+
+        let resp = reply(0x201)
+            .u32(2)
+            .opaque(b"open")
+            .u32(3)
+            .u32(NFS4_OP_SEQUENCE)
+            .u32(NFS_OK)
+            .bytes(&[0x07; NFS4_SESSIONID_LEN + 20])
+            .u32(NFS4_OP_PUTFH)
+            .u32(NFS_OK)
+            .u32(NFS4_OP_OPEN)
+            .u32(2)
+            .record();
+        let infos = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos[0].exception, "NFS4ERR_NOENT");
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+    }
+
+    #[test]
+    fn rpc_errors() {
+        let mut parser = NfsLog::default();
+        let req = call(0x300, NFS_V3, 1).opaque(FILE_FH).record();
+        parse(&mut parser, &req, PacketDirection::ClientToServer);
+        // MSG_DENIED with AUTH_ERROR
+        let resp = XdrWriter::default()
+            .u32(0x300)
+            .u32(RPC_REPLY)
+            .u32(MSG_DENIED)
+            .u32(1)
+            .u32(1)
+            .record();
+        let infos = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos[0].exception, "AUTH_ERROR");
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+
+        let mut info = NfsInfo {
+            version: NFS_V3,
+            ..Default::default()
+        };
+        info.set_nfs_status(NFSERR_NOSPC);
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+        assert_eq!(info.exception, "NFS3ERR_NOSPC");
+
+        // MOUNT program is not NFS
+        let req = XdrWriter::default()
+            .u32(1)
+            .u32(RPC_CALL)
+            .u32(RPC_VERSION)
+            .u32(100005)
+            .u32(3)
+            .u32(1)
+            .record();
+        assert!(!check(&req));
+        assert!(NfsLog::default().parse(&[0x5a; 512]).is_err());
+    }
+}
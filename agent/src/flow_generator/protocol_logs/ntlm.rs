@@ -0,0 +1,238 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// NTLMSSP messages carried by other protocols, such as `Authorization: NTLM ...` in HTTP or
+// the security blob of SMB SESSION_SETUP
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+
+use super::pb_adapter::KeyVal;
+use crate::utils::bytes::{read_u16_le, read_u32_le};
+
+const NTLMSSP_SIGNATURE: &[u8] = b"NTLMSSP\0";
+
+pub const NTLM_NEGOTIATE: u32 = 1;
+pub const NTLM_CHALLENGE: u32 = 2;
+pub const NTLM_AUTHENTICATE: u32 = 3;
+
+const NTLMSSP_NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+
+// offsets of the fields in AUTHENTICATE_MESSAGE
+const AUTH_DOMAIN_OFFSET: usize = 28;
+const AUTH_USER_OFFSET: usize = 36;
+const AUTH_WORKSTATION_OFFSET: usize = 44;
+const AUTH_FLAGS_OFFSET: usize = 60;
+const AUTH_MIN_LEN: usize = 64;
+// offsets of the fields in CHALLENGE_MESSAGE
+const CHALLENGE_TARGET_OFFSET: usize = 12;
+const CHALLENGE_FLAGS_OFFSET: usize = 20;
+const CHALLENGE_MIN_LEN: usize = 32;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct NtlmMessage {
+    pub message_type: u32,
+    // target name from CHALLENGE_MESSAGE, or domain name from AUTHENTICATE_MESSAGE
+    pub domain: String,
+    pub user: String,
+    pub workstation: String,
+}
+
+impl NtlmMessage {
+    // the message may be wrapped in SPNEGO, search for the signature first
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let start = buf
+            .windows(NTLMSSP_SIGNATURE.len())
+            .position(|w| w == NTLMSSP_SIGNATURE)?;
+        let msg = &buf[start..];
+        if msg.len() < NTLMSSP_SIGNATURE.len() + 4 {
+            return None;
+        }
+        let message_type = read_u32_le(&msg[NTLMSSP_SIGNATURE.len()..]);
+        match message_type {
+            NTLM_NEGOTIATE => Some(Self {
+                message_type,
+                ..Default::default()
+            }),
+            NTLM_CHALLENGE if msg.len() >= CHALLENGE_MIN_LEN => {
+                let unicode =
+                    read_u32_le(&msg[CHALLENGE_FLAGS_OFFSET..]) & NTLMSSP_NEGOTIATE_UNICODE != 0;
+                Some(Self {
+                    message_type,
+                    domain: Self::read_field(msg, CHALLENGE_TARGET_OFFSET, unicode),
+                    ..Default::default()
+                })
+            }
+            NTLM_AUTHENTICATE if msg.len() >= AUTH_MIN_LEN => {
+                let unicode =
+                    read_u32_le(&msg[AUTH_FLAGS_OFFSET..]) & NTLMSSP_NEGOTIATE_UNICODE != 0;
+                Some(Self {
+                    message_type,
+                    domain: Self::read_field(msg, AUTH_DOMAIN_OFFSET, unicode),
+                    user: Self::read_field(msg, AUTH_USER_OFFSET, unicode),
+                    workstation: Self::read_field(msg, AUTH_WORKSTATION_OFFSET, unicode),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // value of HTTP `Authorization` or `WWW-Authenticate` headers, such as `NTLM TlRMTVNTUAAB...`
+    pub fn parse_http_auth(value: &[u8]) -> Option<Self> {
+        let token = value
+            .strip_prefix(b"NTLM ")
+            .or_else(|| value.strip_prefix(b"Negotiate "))?;
+        let token = BASE64_STANDARD.decode(token.trim_ascii()).ok()?;
+        Self::parse(&token)
+    }
+
+    // fields are described by (length: u16, max length: u16, offset: u32) relative to the
+    // start of the message, the payload may be truncated
+    fn read_field(msg: &[u8], field_offset: usize, unicode: bool) -> String {
+        let len = read_u16_le(&msg[field_offset..]) as usize;
+        let offset = read_u32_le(&msg[field_offset + 4..]) as usize;
+        let Some(value) = msg.get(offset..offset.saturating_add(len).min(msg.len())) else {
+            return String::new();
+        };
+        if unicode {
+            let value: Vec<u16> = value
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&value)
+        } else {
+            String::from_utf8_lossy(value).into_owned()
+        }
+    }
+
+    pub fn is_authenticate(&self) -> bool {
+        self.message_type == NTLM_AUTHENTICATE
+    }
+
+    // `DOMAIN\user`, empty for anonymous authentication
+    pub fn account(&self) -> String {
+        match (self.domain.is_empty(), self.user.is_empty()) {
+            (_, true) => String::new(),
+            (true, false) => self.user.clone(),
+            (false, false) => format!("{}\\{}", self.domain, self.user),
+        }
+    }
+
+    pub fn attributes(&self) -> Vec<KeyVal> {
+        if !self.is_authenticate() {
+            return vec![];
+        }
+        let mut attributes = vec![];
+        for (key, val) in [
+            ("ntlm_user", self.account()),
+            ("ntlm_workstation", self.workstation.clone()),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        attributes
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    pub(crate) fn authenticate_message(domain: &str, user: &str, workstation: &str) -> Vec<u8> {
+        let fields = [
+            vec![0u8; 24],
+            vec![0u8; 24],
+            utf16(domain),
+            utf16(user),
+            utf16(workstation),
+        ];
+        let mut msg = NTLMSSP_SIGNATURE.to_vec();
+        msg.extend_from_slice(&NTLM_AUTHENTICATE.to_le_bytes());
+        let mut offset = AUTH_MIN_LEN + 8;
+        for f in fields.iter() {
+            msg.extend_from_slice(&(f.len() as u16).to_le_bytes());
+            msg.extend_from_slice(&(f.len() as u16).to_le_bytes());
+            msg.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += f.len();
+        }
+        // empty session key
+        msg.extend_from_slice(&[0u8; 8]);
+        msg.extend_from_slice(&NTLMSSP_NEGOTIATE_UNICODE.to_le_bytes());
+        // version
+        msg.extend_from_slice(&[0u8; 8]);
+        for f in fields.iter() {
+            msg.extend_from_slice(f);
+        }
+        msg
+    }
+
+    #[test]
+    fn authenticate() {
+        let msg = authenticate_message("CORP", "alice", "WS01");
+        let header = format!("NTLM {}", BASE64_STANDARD.encode(&msg));
+        let ntlm = NtlmMessage::parse_http_auth(header.as_bytes()).unwrap();
+        assert!(ntlm.is_authenticate());
+        assert_eq!(ntlm.account(), "CORP\\alice");
+        assert_eq!(ntlm.workstation, "WS01");
+        assert_eq!(
+            ntlm.attributes(),
+            vec![
+                KeyVal {
+                    key: "ntlm_user".to_string(),
+                    val: "CORP\\alice".to_string(),
+                },
+                KeyVal {
+                    key: "ntlm_workstation".to_string(),
+                    val: "WS01".to_string(),
+                },
+            ]
+        );
+
+        // wrapped in SPNEGO NegTokenResp
+        let mut spnego = vec![
+            0xa1, 0x81, 0x90, 0x30, 0x81, 0x8d, 0xa2, 0x81, 0x8a, 0x04, 0x81,
+        ];
+        spnego.push(msg.len() as u8);
+        spnego.extend_from_slice(&msg);
+        assert_eq!(NtlmMessage::parse(&spnego), Some(ntlm));
+
+        // truncated in the middle of the payload
+        let ntlm = NtlmMessage::parse(&msg[..msg.len() - 6]).unwrap();
+        assert_eq!(ntlm.account(), "CORP\\alice");
+        assert_eq!(ntlm.workstation, "W");
+    }
+
+    #[test]
+    fn negotiate() {
+        let header = b"NTLM TlRMTVNTUAABAAAAB4IIogAAAAAAAAAAAAAAAAAAAAAKAGFKAAAADw==";
+        let ntlm = NtlmMessage::parse_http_auth(header).unwrap();
+        assert_eq!(ntlm.message_type, NTLM_NEGOTIATE);
+        assert!(ntlm.attributes().is_empty());
+
+        assert_eq!(
+            NtlmMessage::parse_http_auth(b"Basic YWxpY2U6cGFzcw=="),
+            None
+        );
+        assert_eq!(NtlmMessage::parse_http_auth(b"NTLM !!!"), None);
+    }
+}
@@ -16,6 +16,7 @@
 
 use std::{
     cmp::min,
+    collections::HashMap,
     fmt,
     num::NonZeroUsize,
     sync::{
@@ -33,7 +34,10 @@ use lru::LruCache;
 use rand::prelude::{Rng, SeedableRng, SmallRng};
 use serde::Serialize;
 
-use super::{AppProtoHead, AppProtoLogsBaseInfo, BoxAppProtoLogsData, LogMessageType};
+use super::{
+    pb_adapter::L7ProtocolSendLog, AppProtoHead, AppProtoLogsBaseInfo, BoxAppProtoLogsData,
+    L7ResponseStatus, LogMessageType,
+};
 
 use crate::{
     common::{
@@ -46,7 +50,11 @@ use crate::{
     flow_generator::{error::Result, FLOW_METRICS_PEER_DST, FLOW_METRICS_PEER_SRC},
     metric::document::TapSide,
     rpc::get_timestamp,
-    utils::stats::{Counter, CounterType, CounterValue, RefCountable},
+    sender::otlp_exporter::OtlpExporter,
+    utils::{
+        namespace_quota::{Admission, NamespaceLimiter},
+        stats::{Counter, CounterType, CounterValue, RefCountable},
+    },
 };
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use public::utils::string::get_string_from_chars;
@@ -187,10 +195,12 @@ impl MetaAppProto {
             syscall_coroutine_1: 0,
             syscall_cap_seq_0: 0,
             syscall_cap_seq_1: 0,
+            unix_socket_path: flow.flow.unix_socket_path.clone(),
             ebpf_type: meta_packet.ebpf_type,
             pod_id_0: 0,
             pod_id_1: 0,
             biz_type: l7_info.get_biz_type(),
+            sample_rate: 1,
         };
 
         #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -313,6 +323,8 @@ pub struct SessionAggrCounter {
     cached: AtomicU64, // It is used to record the number of logs that exist in session queue
     cached_request_resource: AtomicU64, // It is used to record the cache request-resource occupation space, the unit is B
     throttle_drop: AtomicU64,
+    sampling_drop: AtomicU64,
+    namespace_quota_drop: AtomicU64,
     over_limit: AtomicU64, // It is used to record the number of logs that exceed the limit to the forced flush
 }
 
@@ -349,6 +361,16 @@ impl RefCountable for SessionAggrCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.throttle_drop.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "sampling-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.sampling_drop.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "namespace-quota-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.namespace_quota_drop.swap(0, Ordering::Relaxed)),
+            ),
             (
                 "over-limit",
                 CounterType::Counted,
@@ -408,15 +430,68 @@ impl Throttle {
     }
 }
 
+// Tail-based sampling of the sessions, which is decided when the session is complete:
+//   - sessions with error status, timeouts and sessions slower than the threshold are all kept
+//   - the other sessions are kept at 1 in sample_rate, and the kept ones stand for sample_rate sessions
+struct Sampler {
+    config: LogParserAccess,
+    small_rng: SmallRng,
+}
+
+impl Sampler {
+    fn new(config: LogParserAccess) -> Self {
+        Self {
+            config,
+            small_rng: SmallRng::from_entropy(),
+        }
+    }
+
+    // Returns the sample rate of the kept session, or None if it is dropped
+    fn sample(&mut self, item: &MetaAppProto) -> Option<u32> {
+        let config = self.config.load();
+        let sampling = &config.l7_log_sampling;
+        let head = &item.base_info.head;
+        let sample_rate = sampling.sample_rate(head.proto, item.base_info.port_dst);
+        if sample_rate <= 1 {
+            return Some(1);
+        }
+        // requests without response are timeouts
+        if head.msg_type == LogMessageType::Request
+            || head.rrt >= sampling.slow_threshold.as_micros() as u64
+        {
+            return Some(1);
+        }
+        // response status is only available in the send log of each protocol
+        let log: L7ProtocolSendLog = item.l7_info.clone().into();
+        if log.resp.status != L7ResponseStatus::Ok {
+            return Some(1);
+        }
+        if self.small_rng.gen_range(0..sample_rate) == 0 {
+            Some(sample_rate)
+        } else {
+            None
+        }
+    }
+}
+
 struct SessionQueue {
     aggregate_start_time: Duration,
     last_flush_time: Duration,
 
     window_size: usize,
+    // window sizes of protocols whose session timeout differs from the whole window
+    protocol_window_sizes: HashMap<L7Protocol, usize>,
+    default_window_size: usize,
+    // distinct window sizes shorter than the whole window, items in slots crossing
+    // them need to be flushed before the whole window slides over
+    short_window_sizes: Vec<usize>,
     l7_log_session_slot_capacity: usize,
     time_window: Option<Vec<LruCache<u64, Box<MetaAppProto>>>>,
 
     throttle: Throttle,
+    sampler: Sampler,
+    namespace_limiter: Arc<NamespaceLimiter>,
+    otlp_exporter: Arc<OtlpExporter>,
 
     counter: Arc<SessionAggrCounter>,
     output_queue: DebugSender<BoxAppProtoLogsData>,
@@ -430,16 +505,39 @@ impl SessionQueue {
         output_queue: DebugSender<BoxAppProtoLogsData>,
         config: LogParserAccess,
         ntp_diff: Arc<AtomicI64>,
+        namespace_limiter: Arc<NamespaceLimiter>,
+        otlp_exporter: Arc<OtlpExporter>,
     ) -> Self {
         let conf = config.load();
         //l7_log_session_timeout 20s-300s ，window_size = 4-60，所以 SessionQueue.time_window 预分配内存
-        let window_size = (conf.l7_log_session_aggr_timeout.as_secs() / SLOT_WIDTH) as usize;
+        // The window covers the longest protocol timeout, protocols with shorter timeouts
+        // are flushed when their slots fall out of their own window.
+        let default_window_size =
+            (conf.l7_log_session_aggr_timeout.as_secs() / SLOT_WIDTH) as usize;
+        let protocol_window_sizes: HashMap<L7Protocol, usize> = conf
+            .l7_log_session_aggr_protocol_timeouts
+            .iter()
+            .map(|(p, t)| (*p, ((t.as_secs() / SLOT_WIDTH) as usize).max(1)))
+            .collect();
+        let window_size = protocol_window_sizes
+            .values()
+            .copied()
+            .fold(default_window_size, usize::max);
+        let mut short_window_sizes: Vec<usize> = protocol_window_sizes
+            .values()
+            .copied()
+            .chain(std::iter::once(default_window_size))
+            .filter(|s| *s < window_size)
+            .collect();
+        short_window_sizes.sort_unstable();
+        short_window_sizes.dedup();
         let slot_capacity = conf.l7_log_session_slot_capacity;
         let mut time_window = Vec::new();
         for _ in 0..window_size {
             time_window.push(LruCache::new(NonZeroUsize::new(slot_capacity).unwrap()));
         }
         let throttle = Throttle::new(config.clone(), SLOT_WIDTH);
+        let sampler = Sampler::new(config.clone());
         Self {
             aggregate_start_time: Duration::ZERO,
             last_flush_time: Duration::ZERO,
@@ -447,9 +545,15 @@ impl SessionQueue {
             config,
             ntp_diff,
             window_size,
+            protocol_window_sizes,
+            default_window_size,
+            short_window_sizes,
             l7_log_session_slot_capacity: slot_capacity,
 
             throttle,
+            sampler,
+            namespace_limiter,
+            otlp_exporter,
 
             counter,
             output_queue,
@@ -582,6 +686,15 @@ impl SessionQueue {
             self.flush_window(slot_index - self.window_size + 1, &mut time_window);
             slot_index = self.window_size - 1;
         }
+        // the slot is out of the protocol's own aggregate window
+        if slot_index + self.protocol_window_size(item.base_info.head.proto) < self.window_size {
+            self.time_window.replace(time_window);
+            self.counter
+                .send_before_window
+                .fetch_add(1, Ordering::Relaxed);
+            self.send(item);
+            return;
+        }
 
         // 因为数组提前分配hashmap, slot < self.window_size 所以必然存在
         let slot = time_window.get_mut(slot_index).unwrap();
@@ -773,21 +886,88 @@ impl SessionQueue {
         let mut maps = time_window.drain(0..delete_num).collect();
         time_window.append(&mut maps);
 
+        // flush items in slots which just fell out of their protocol's window
+        for i in 0..self.short_window_sizes.len() {
+            let end = self.window_size - self.short_window_sizes[i];
+            for j in end.saturating_sub(n)..end {
+                self.flush_expired_in_slot(j, time_window.get_mut(j).unwrap());
+            }
+        }
+
         // update timestamp
         self.aggregate_start_time =
             Duration::from_secs(self.aggregate_start_time.as_secs() + n as u64 * SLOT_WIDTH);
     }
 
-    fn send(&mut self, item: Box<MetaAppProto>) {
+    fn protocol_window_size(&self, protocol: L7Protocol) -> usize {
+        self.protocol_window_sizes
+            .get(&protocol)
+            .copied()
+            .unwrap_or(self.default_window_size)
+    }
+
+    fn flush_expired_in_slot(&mut self, index: usize, slot: &mut LruCache<u64, Box<MetaAppProto>>) {
+        let expired = slot
+            .iter()
+            .filter(|(_, v)| {
+                index + self.protocol_window_size(v.base_info.head.proto) < self.window_size
+            })
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        for key in expired {
+            if let Some(item) = slot.pop(&key) {
+                self.counter.cached.fetch_sub(1, Ordering::Relaxed);
+                self.counter.cached_request_resource.fetch_sub(
+                    item.l7_info.get_request_resource_length() as u64,
+                    Ordering::Relaxed,
+                );
+                self.send(item);
+            }
+        }
+    }
+
+    fn send(&mut self, mut item: Box<MetaAppProto>) {
         if item.l7_info.skip_send() || item.l7_info.is_on_blacklist() {
             return;
         }
 
-        if !self.throttle.acquire(item.base_info.start_time.into()) {
-            self.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
-            return;
+        match self.sampler.sample(&item) {
+            Some(sample_rate) => item.base_info.sample_rate = sample_rate,
+            None => {
+                self.counter.sampling_drop.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        let base_info = &item.base_info;
+        let admission = self.namespace_limiter.acquire(
+            &self.config.load().namespace_quotas,
+            [
+                (base_info.l3_epc_id_src, base_info.ip_src),
+                (base_info.l3_epc_id_dst, base_info.ip_dst),
+            ],
+            0,
+            get_timestamp(self.ntp_diff.load(Ordering::Relaxed)),
+        );
+        match admission {
+            Admission::Dropped => {
+                self.counter
+                    .namespace_quota_drop
+                    .fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            // logs of system namespaces are not throttled
+            Admission::Prioritized => (),
+            Admission::Accepted => {
+                if !self.throttle.acquire(item.base_info.start_time.into()) {
+                    self.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
         }
 
+        self.otlp_exporter
+            .export(&self.config.load().otlp_exporter, &item);
         if let Err(e) = self.output_queue.send(BoxAppProtoLogsData(item)) {
             warn!("output queue failed to send data, because: {:?}", e);
         }
@@ -809,6 +989,8 @@ pub struct SessionAggregator {
     counter: Arc<SessionAggrCounter>,
     config: LogParserAccess,
     ntp_diff: Arc<AtomicI64>,
+    namespace_limiter: Arc<NamespaceLimiter>,
+    otlp_exporter: Arc<OtlpExporter>,
 }
 
 impl SessionAggregator {
@@ -818,6 +1000,8 @@ impl SessionAggregator {
         id: u32,
         config: LogParserAccess,
         ntp_diff: Arc<AtomicI64>,
+        namespace_limiter: Arc<NamespaceLimiter>,
+        otlp_exporter: Arc<OtlpExporter>,
     ) -> (Self, Arc<SessionAggrCounter>) {
         let counter: Arc<SessionAggrCounter> = Default::default();
         (
@@ -830,6 +1014,8 @@ impl SessionAggregator {
                 counter: counter.clone(),
                 config,
                 ntp_diff,
+                namespace_limiter,
+                otlp_exporter,
             },
             counter,
         )
@@ -847,12 +1033,20 @@ impl SessionAggregator {
 
         let config = self.config.clone();
         let ntp_diff = self.ntp_diff.clone();
+        let namespace_limiter = self.namespace_limiter.clone();
+        let otlp_exporter = self.otlp_exporter.clone();
 
         let thread = thread::Builder::new()
             .name("protocol-logs-parser".to_owned())
             .spawn(move || {
-                let mut session_queue =
-                    SessionQueue::new(counter, output_queue, config.clone(), ntp_diff);
+                let mut session_queue = SessionQueue::new(
+                    counter,
+                    output_queue,
+                    config.clone(),
+                    ntp_diff,
+                    namespace_limiter,
+                    otlp_exporter,
+                );
 
                 let mut batch_buffer = Vec::with_capacity(QUEUE_BATCH_SIZE);
 
@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Field extraction from protobuf messages without descriptors, by walking the wire format.
+// Reference: https://protobuf.dev/programming-guides/encoding/
+
+use std::{fmt, str};
+
+use crate::utils::bytes::{read_u32_le, read_u64_le};
+
+const WIRE_TYPE_VARINT: u8 = 0;
+const WIRE_TYPE_I64: u8 = 1;
+const WIRE_TYPE_LEN: u8 = 2;
+const WIRE_TYPE_SGROUP: u8 = 3;
+const WIRE_TYPE_EGROUP: u8 = 4;
+const WIRE_TYPE_I32: u8 = 5;
+
+const MAX_VARINT_LEN: usize = 10;
+
+#[derive(Debug, PartialEq)]
+enum Value<'a> {
+    Varint(u64),
+    I64(u64),
+    Len(&'a [u8]),
+    I32(u32),
+}
+
+// without the descriptor, varints are shown as int64, and length-delimited values are shown
+// as string if they are printable, otherwise as hex
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Varint(v) => write!(f, "{}", *v as i64),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::Len(v) => match str::from_utf8(v) {
+                Ok(s) if !s.chars().any(|c| c.is_control()) => write!(f, "{}", s),
+                _ => write!(f, "{}", hex::encode(v)),
+            },
+        }
+    }
+}
+
+fn read_varint(payload: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, b) in payload.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+struct FieldIterator<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Iterator for FieldIterator<'a> {
+    type Item = (u32, Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (tag, n) = read_varint(self.payload)?;
+            let payload = &self.payload[n..];
+            let (number, wire_type) = ((tag >> 3) as u32, (tag & 0x7) as u8);
+            if number == 0 {
+                return None;
+            }
+            let (value, n) = match wire_type {
+                WIRE_TYPE_VARINT => {
+                    let (v, n) = read_varint(payload)?;
+                    (Value::Varint(v), n)
+                }
+                WIRE_TYPE_I64 if payload.len() >= 8 => (Value::I64(read_u64_le(payload)), 8),
+                WIRE_TYPE_I32 if payload.len() >= 4 => (Value::I32(read_u32_le(payload)), 4),
+                WIRE_TYPE_LEN => {
+                    let (len, n) = read_varint(payload)?;
+                    let end = n.checked_add(len as usize)?;
+                    if end > payload.len() {
+                        // truncated, the value is incomplete
+                        return None;
+                    }
+                    (Value::Len(&payload[n..end]), end)
+                }
+                // deprecated groups carry no value themselves
+                WIRE_TYPE_SGROUP | WIRE_TYPE_EGROUP => {
+                    self.payload = payload;
+                    continue;
+                }
+                _ => return None,
+            };
+            self.payload = &payload[n..];
+            return Some((number, value));
+        }
+    }
+}
+
+// Returns the first value at `path`, a list of field numbers from the outermost message.
+// All fields in `path` except the last one must be embedded messages.
+pub fn extract_field(mut payload: &[u8], path: &[u32]) -> Option<String> {
+    let (last, parents) = path.split_last()?;
+    for number in parents {
+        payload = FieldIterator { payload }.find_map(|(n, v)| match v {
+            Value::Len(v) if n == *number => Some(v),
+            _ => None,
+        })?;
+    }
+    FieldIterator { payload }
+        .find(|(n, _)| n == last)
+        .map(|(_, v)| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // message Order { int64 id = 1; string name = 2; fixed32 count = 3; }
+    // message Request { string user = 1; Order order = 2; int64 delta = 4; }
+    fn request() -> Vec<u8> {
+        let order = [
+            &[0x08, 0x96, 0x01][..],         // id = 150
+            &[0x12, 0x03, b'a', b'b', b'c'], // name = "abc"
+            &[0x1d, 0x07, 0x00, 0x00, 0x00], // count = 7
+        ]
+        .concat();
+        let mut request = vec![0x0a, 0x02, b'u', b'1']; // user = "u1"
+        request.extend_from_slice(&[0x12, order.len() as u8]);
+        request.extend_from_slice(&order);
+        request.extend_from_slice(&[
+            0x20, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+        ]); // -1 as int64
+        request
+    }
+
+    #[test]
+    fn extract() {
+        let request = request();
+        let cases: [(&[u32], Option<&str>); 8] = [
+            (&[1], Some("u1")),
+            (&[2, 1], Some("150")),
+            (&[2, 2], Some("abc")),
+            (&[2, 3], Some("7")),
+            (&[4], Some("-1")),
+            (&[3], None),
+            (&[1, 1], None),
+            (&[], None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                extract_field(&request, path).as_deref(),
+                expected,
+                "path {:?}",
+                path
+            );
+        }
+        // embedded message as hex
+        assert_eq!(
+            extract_field(&[0x0a, 0x02, 0x08, 0x01], &[1]).unwrap(),
+            "0801"
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let request = request();
+        // the order is truncated, but the user is still readable
+        assert_eq!(extract_field(&request[..8], &[1]).as_deref(), Some("u1"));
+        assert_eq!(extract_field(&request[..8], &[2, 1]), None);
+        assert_eq!(extract_field(&request[..1], &[1]), None);
+    }
+}
@@ -0,0 +1,1153 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use ring::{aead, hkdf};
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{set_captured_byte, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u16_be, read_u32_be},
+};
+use public::l7_protocol::L7Protocol;
+
+const QUIC_V1: u32 = 0x0000_0001;
+const QUIC_V2: u32 = 0x6b33_43cf;
+const QUIC_DRAFT_29: u32 = 0xff00_001d;
+const QUIC_DRAFT_32: u32 = 0xff00_0020;
+
+// RFC 9001 5.2
+const V1_INITIAL_SALT: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad,
+    0xcc, 0xbb, 0x7f, 0x0a,
+];
+// RFC 9369 3.3.1
+const V2_INITIAL_SALT: [u8; 20] = [
+    0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93, 0x81, 0xbe, 0x6e, 0x26, 0x9d, 0xcb,
+    0xf9, 0xbd, 0x2e, 0xd9,
+];
+// draft-ietf-quic-tls-29 to 32
+const DRAFT_INITIAL_SALT: [u8; 20] = [
+    0xaf, 0xbf, 0xec, 0x28, 0x99, 0x93, 0xd2, 0x4c, 0x9e, 0x97, 0x86, 0xf1, 0x9c, 0x61, 0x11, 0xe0,
+    0x43, 0x90, 0xa8, 0x99,
+];
+
+const LONG_HEADER_FORM: u8 = 0x80;
+const FIXED_BIT: u8 = 0x40;
+const MAX_CID_LEN: usize = 20;
+// RFC 9000 7.2: the first Initial of a client carries a destination connection id of at least 8 bytes
+const MIN_INITIAL_DCID_LEN: usize = 8;
+const HP_SAMPLE_OFFSET: usize = 4;
+const HP_SAMPLE_LEN: usize = 16;
+const AEAD_TAG_LEN: usize = 16;
+
+const FRAME_PADDING: u8 = 0x00;
+const FRAME_PING: u8 = 0x01;
+const FRAME_ACK: u8 = 0x02;
+const FRAME_ACK_ECN: u8 = 0x03;
+const FRAME_CRYPTO: u8 = 0x06;
+const FRAME_CONNECTION_CLOSE: u8 = 0x1c;
+
+const TLS_HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const TLS_EXT_SERVER_NAME: u16 = 0;
+const TLS_EXT_ALPN: u16 = 16;
+
+// a ClientHello rarely spans more than 2 Initial packets, give up waiting after this
+const MAX_CLIENT_INITIALS: u8 = 3;
+const MAX_CRYPTO_BUFFER_SIZE: usize = 8192;
+
+fn initial_salt(version: u32) -> Option<&'static [u8]> {
+    match version {
+        QUIC_V1 => Some(&V1_INITIAL_SALT),
+        QUIC_V2 => Some(&V2_INITIAL_SALT),
+        QUIC_DRAFT_29..=QUIC_DRAFT_32 => Some(&DRAFT_INITIAL_SALT),
+        _ => None,
+    }
+}
+
+fn version_str(version: u32) -> String {
+    match version {
+        QUIC_V1 => "1".to_owned(),
+        QUIC_V2 => "2".to_owned(),
+        QUIC_DRAFT_29..=QUIC_DRAFT_32 => format!("draft-{}", version & 0xff),
+        _ => format!("{:#010x}", version),
+    }
+}
+
+// RFC 9000 16: variable-length integer, returns value and encoded length
+fn read_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let first = *buf.first()?;
+    let len = 1 << (first >> 6);
+    if buf.len() < len {
+        return None;
+    }
+    let mut value = (first & 0x3f) as u64;
+    for b in &buf[1..len] {
+        value = (value << 8) | *b as u64;
+    }
+    Some((value, len))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+    VersionNegotiation,
+}
+
+impl PacketType {
+    fn new(version: u32, first_byte: u8) -> Self {
+        if version == 0 {
+            return Self::VersionNegotiation;
+        }
+        // QUIC v2 shuffles the long header packet types, see RFC 9369 3.2
+        match ((first_byte >> 4) & 0x03, version == QUIC_V2) {
+            (0, false) | (1, true) => Self::Initial,
+            (1, false) | (2, true) => Self::ZeroRtt,
+            (2, false) | (3, true) => Self::Handshake,
+            _ => Self::Retry,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Initial => "Initial",
+            Self::ZeroRtt => "0-RTT",
+            Self::Handshake => "Handshake",
+            Self::Retry => "Retry",
+            Self::VersionNegotiation => "VersionNegotiation",
+        }
+    }
+
+    fn join(types: &[Self]) -> String {
+        types
+            .iter()
+            .map(|t| t.as_str())
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+struct LongHeader<'a> {
+    version: u32,
+    packet_type: PacketType,
+    dcid: &'a [u8],
+    scid: &'a [u8],
+    // offset of the packet number, only meaningful for Initial, 0-RTT and Handshake
+    pn_offset: usize,
+    // length of the whole packet, may exceed the payload if it was truncated
+    packet_len: usize,
+    // supported versions of Version Negotiation, or token and integrity tag of Retry
+    remain: &'a [u8],
+}
+
+impl<'a> LongHeader<'a> {
+    fn parse(payload: &'a [u8]) -> Option<Self> {
+        if payload.len() < 7 || payload[0] & LONG_HEADER_FORM == 0 {
+            return None;
+        }
+        let version = read_u32_be(&payload[1..]);
+        if version != 0 && payload[0] & FIXED_BIT == 0 {
+            return None;
+        }
+        let mut offset = 5;
+        let dcid_len = payload[offset] as usize;
+        if dcid_len > MAX_CID_LEN {
+            return None;
+        }
+        offset += 1;
+        let dcid = payload.get(offset..offset + dcid_len)?;
+        offset += dcid_len;
+        let scid_len = *payload.get(offset)? as usize;
+        if scid_len > MAX_CID_LEN {
+            return None;
+        }
+        offset += 1;
+        let scid = payload.get(offset..offset + scid_len)?;
+        offset += scid_len;
+
+        let mut header = LongHeader {
+            version,
+            packet_type: PacketType::new(version, payload[0]),
+            dcid,
+            scid,
+            pn_offset: 0,
+            packet_len: payload.len(),
+            remain: &payload[offset..],
+        };
+        match header.packet_type {
+            PacketType::VersionNegotiation | PacketType::Retry => return Some(header),
+            PacketType::Initial => {
+                let (token_len, n) = read_varint(&payload[offset..])?;
+                offset += n;
+                if token_len > (payload.len() - offset) as u64 {
+                    return None;
+                }
+                offset += token_len as usize;
+            }
+            _ => (),
+        }
+        let (length, n) = read_varint(&payload[offset..])?;
+        offset += n;
+        header.pn_offset = offset;
+        header.packet_len = offset.checked_add(usize::try_from(length).ok()?)?;
+        Some(header)
+    }
+}
+
+struct OkmLen(usize);
+
+impl hkdf::KeyType for OkmLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+// RFC 8446 7.1 HKDF-Expand-Label with empty context
+fn hkdf_expand_label(prk: &hkdf::Prk, label: &[u8], out: &mut [u8]) -> Option<()> {
+    const LABEL_PREFIX: &[u8] = b"tls13 ";
+    let out_len = (out.len() as u16).to_be_bytes();
+    let label_len = [(LABEL_PREFIX.len() + label.len()) as u8];
+    let info = [&out_len[..], &label_len[..], LABEL_PREFIX, label, &[0]];
+    prk.expand(&info, OkmLen(out.len())).ok()?.fill(out).ok()
+}
+
+// returns key, iv and header protection key of the client Initial packets
+fn client_initial_secrets(version: u32, dcid: &[u8]) -> Option<([u8; 16], [u8; 12], [u8; 16])> {
+    let salt = initial_salt(version)?;
+    let initial_secret = hkdf::Salt::new(hkdf::HKDF_SHA256, salt).extract(dcid);
+    let mut client_secret = [0u8; 32];
+    hkdf_expand_label(&initial_secret, b"client in", &mut client_secret)?;
+    let client_secret = hkdf::Prk::new_less_safe(hkdf::HKDF_SHA256, &client_secret);
+
+    let labels: [&[u8]; 3] = if version == QUIC_V2 {
+        [b"quicv2 key", b"quicv2 iv", b"quicv2 hp"]
+    } else {
+        [b"quic key", b"quic iv", b"quic hp"]
+    };
+    let (mut key, mut iv, mut hp) = ([0u8; 16], [0u8; 12], [0u8; 16]);
+    hkdf_expand_label(&client_secret, labels[0], &mut key)?;
+    hkdf_expand_label(&client_secret, labels[1], &mut iv)?;
+    hkdf_expand_label(&client_secret, labels[2], &mut hp)?;
+    Some((key, iv, hp))
+}
+
+struct InitialKeys {
+    key: aead::LessSafeKey,
+    iv: [u8; 12],
+    hp: aead::quic::HeaderProtectionKey,
+}
+
+impl InitialKeys {
+    fn client(version: u32, dcid: &[u8]) -> Option<Self> {
+        let (key, iv, hp) = client_initial_secrets(version, dcid)?;
+        Some(Self {
+            key: aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_128_GCM, &key).ok()?),
+            iv,
+            hp: aead::quic::HeaderProtectionKey::new(&aead::quic::AES_128, &hp).ok()?,
+        })
+    }
+
+    // Removes header protection and decrypts the packet in place, returns the frames.
+    //
+    // If the tail of the packet is cut off by payload truncation, the authentication tag
+    // is unavailable. AES-GCM is a counter mode, so the keystream of the visible part is
+    // produced by sealing zeros with the same nonce and the frames are recovered without
+    // being authenticated.
+    fn decrypt<'a>(
+        &self,
+        packet: &'a mut [u8],
+        pn_offset: usize,
+        packet_len: usize,
+    ) -> Option<&'a [u8]> {
+        let sample_offset = pn_offset + HP_SAMPLE_OFFSET;
+        let sample = packet.get(sample_offset..sample_offset + HP_SAMPLE_LEN)?;
+        let mask = self.hp.new_mask(sample).ok()?;
+        packet[0] ^= mask[0] & 0x0f;
+        let pn_len = (packet[0] & 0x03) as usize + 1;
+        let mut pn = 0u64;
+        for i in 0..pn_len {
+            packet[pn_offset + i] ^= mask[1 + i];
+            pn = (pn << 8) | packet[pn_offset + i] as u64;
+        }
+        let header_len = pn_offset + pn_len;
+        if packet_len < header_len + AEAD_TAG_LEN {
+            return None;
+        }
+
+        let mut nonce = self.iv;
+        for (n, p) in nonce[4..].iter_mut().zip(pn.to_be_bytes()) {
+            *n ^= p;
+        }
+        let nonce = aead::Nonce::assume_unique_for_key(nonce);
+
+        if packet_len <= packet.len() {
+            let (header, body) = packet[..packet_len].split_at_mut(header_len);
+            return self
+                .key
+                .open_in_place(nonce, aead::Aad::from(&header[..]), body)
+                .ok()
+                .map(|frames| &*frames);
+        }
+        let body_end = packet.len().min(packet_len - AEAD_TAG_LEN);
+        let body = &mut packet[header_len..body_end];
+        let mut keystream = vec![0u8; body.len()];
+        let _ = self
+            .key
+            .seal_in_place_separate_tag(nonce, aead::Aad::empty(), &mut keystream)
+            .ok()?;
+        for (b, k) in body.iter_mut().zip(keystream) {
+            *b ^= k;
+        }
+        Some(body)
+    }
+}
+
+// Walks through the frames of an Initial packet and calls `on_crypto` with offset and data
+// of each CRYPTO frame. Returns None if there are frames not allowed in Initial packets.
+fn walk_initial_frames(mut frames: &[u8], mut on_crypto: impl FnMut(u64, &[u8])) -> Option<()> {
+    while let Some(&frame_type) = frames.first() {
+        let mut offset = 1;
+        match frame_type {
+            FRAME_PADDING | FRAME_PING => (),
+            FRAME_ACK | FRAME_ACK_ECN => {
+                // largest acknowledged, ack delay, ack range count, first ack range
+                let mut fields = [0u64; 4];
+                for f in fields.iter_mut() {
+                    let (v, n) = read_varint(&frames[offset..])?;
+                    *f = v;
+                    offset += n;
+                }
+                let mut remain_fields = fields[2].checked_mul(2)?;
+                if frame_type == FRAME_ACK_ECN {
+                    remain_fields += 3;
+                }
+                if remain_fields > frames.len() as u64 {
+                    return None;
+                }
+                for _ in 0..remain_fields {
+                    let (_, n) = read_varint(&frames[offset..])?;
+                    offset += n;
+                }
+            }
+            FRAME_CRYPTO => {
+                let (crypto_offset, n) = read_varint(&frames[offset..])?;
+                offset += n;
+                let (length, n) = read_varint(&frames[offset..])?;
+                offset += n;
+                let end = frames.len().min(offset.saturating_add(length as usize));
+                on_crypto(crypto_offset, &frames[offset..end]);
+                offset = end;
+            }
+            FRAME_CONNECTION_CLOSE => return Some(()),
+            _ => return None,
+        }
+        frames = &frames[offset..];
+    }
+    Some(())
+}
+
+// reassembles CRYPTO frames, which may be split into several packets and arrive out of order
+#[derive(Default)]
+struct CryptoStream {
+    data: Vec<u8>,
+    pending: BTreeMap<u64, Vec<u8>>,
+}
+
+impl CryptoStream {
+    fn push(&mut self, offset: u64, data: &[u8]) {
+        let contiguous = self.data.len() as u64;
+        if offset > contiguous {
+            let pending_size: usize = self.pending.values().map(|p| p.len()).sum();
+            if pending_size + data.len() <= MAX_CRYPTO_BUFFER_SIZE {
+                self.pending.insert(offset, data.to_vec());
+            }
+            return;
+        }
+        self.append(offset, data);
+        while let Some((&offset, _)) = self.pending.first_key_value() {
+            if offset > self.data.len() as u64 {
+                break;
+            }
+            let data = self.pending.remove(&offset).unwrap();
+            self.append(offset, &data);
+        }
+    }
+
+    fn append(&mut self, offset: u64, data: &[u8]) {
+        let skip = (self.data.len() as u64 - offset) as usize;
+        if skip >= data.len() {
+            return;
+        }
+        let size = (data.len() - skip).min(MAX_CRYPTO_BUFFER_SIZE - self.data.len());
+        self.data.extend_from_slice(&data[skip..skip + size]);
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+        self.pending.clear();
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ClientHello {
+    server_name: String,
+    alpn: Vec<String>,
+    // whether the whole handshake message is available
+    complete: bool,
+}
+
+impl ClientHello {
+    // Parses as much as possible, the extensions in the available prefix are still extracted
+    // when the message is incomplete.
+    fn parse(hs: &[u8]) -> Option<Self> {
+        if hs.len() < 4 || hs[0] != TLS_HANDSHAKE_CLIENT_HELLO {
+            return None;
+        }
+        let length = (read_u32_be(hs) & 0xffffff) as usize;
+        let mut client_hello = ClientHello {
+            complete: hs.len() >= 4 + length,
+            ..Default::default()
+        };
+        let body = &hs[4..hs.len().min(4 + length)];
+
+        // legacy_version and random
+        let mut offset = 34;
+        // legacy_session_id
+        offset += 1 + *body.get(offset)? as usize;
+        // cipher_suites
+        offset += 2 + read_u16_be(body.get(offset..offset + 2)?) as usize;
+        // legacy_compression_methods
+        offset += 1 + *body.get(offset)? as usize;
+        let extensions_len = read_u16_be(body.get(offset..offset + 2)?) as usize;
+        offset += 2;
+        let end = body.len().min(offset + extensions_len);
+        while offset + 4 <= end {
+            let ext_type = read_u16_be(&body[offset..]);
+            let ext_len = read_u16_be(&body[offset + 2..]) as usize;
+            offset += 4;
+            let Some(ext) = body.get(offset..offset + ext_len) else {
+                break;
+            };
+            match ext_type {
+                TLS_EXT_SERVER_NAME => client_hello.server_name = Self::parse_server_name(ext),
+                TLS_EXT_ALPN => client_hello.alpn = Self::parse_alpn(ext),
+                _ => (),
+            }
+            offset += ext_len;
+        }
+        Some(client_hello)
+    }
+
+    fn parse_server_name(ext: &[u8]) -> String {
+        const NAME_TYPE_HOST_NAME: u8 = 0;
+        // server_name_list length
+        let mut offset = 2;
+        while offset + 3 <= ext.len() {
+            let name_type = ext[offset];
+            let name_len = read_u16_be(&ext[offset + 1..]) as usize;
+            offset += 3;
+            let Some(name) = ext.get(offset..offset + name_len) else {
+                break;
+            };
+            if name_type == NAME_TYPE_HOST_NAME {
+                return String::from_utf8_lossy(name).into_owned();
+            }
+            offset += name_len;
+        }
+        String::new()
+    }
+
+    fn parse_alpn(ext: &[u8]) -> Vec<String> {
+        let mut protocols = vec![];
+        // protocol_name_list length
+        let mut offset = 2;
+        while offset < ext.len() {
+            let len = ext[offset] as usize;
+            offset += 1;
+            let Some(name) = ext.get(offset..offset + len) else {
+                break;
+            };
+            protocols.push(String::from_utf8_lossy(name).into_owned());
+            offset += len;
+        }
+        protocols
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuicInfo {
+    msg_type: LogMessageType,
+
+    #[serde(skip_serializing_if = "value_is_default")]
+    version: u32,
+    // long header packet types in the datagram, such as `Initial|0-RTT`
+    #[serde(skip_serializing_if = "value_is_default")]
+    request_type: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    server_name: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    alpn: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    zero_rtt: bool,
+
+    // connection ids in hex, used to correlate flows across connection migration
+    #[serde(skip_serializing_if = "value_is_default")]
+    original_dcid: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    client_cid: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    server_cid: String,
+
+    #[serde(skip_serializing_if = "value_is_default")]
+    response_result: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    supported_versions: String,
+    #[serde(rename = "response_status")]
+    status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    exception: String,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+    rrt: u64,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for QuicInfo {
+    fn session_id(&self) -> Option<u32> {
+        None
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::QuicInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::QUIC,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.server_name.clone()
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.server_name.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl QuicInfo {
+    fn merge(&mut self, other: &mut Self) {
+        if self.version == 0 {
+            self.version = other.version;
+        }
+        std::mem::swap(&mut self.server_cid, &mut other.server_cid);
+        std::mem::swap(&mut self.response_result, &mut other.response_result);
+        std::mem::swap(&mut self.supported_versions, &mut other.supported_versions);
+        std::mem::swap(&mut self.exception, &mut other.exception);
+        self.status = other.status;
+        self.captured_response_byte = other.captured_response_byte;
+        self.is_on_blacklist |= other.is_on_blacklist;
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::QUIC) {
+            self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.server_name)
+                || t.request_type.is_on_blacklist(&self.request_type)
+                || t.request_domain.is_on_blacklist(&self.server_name);
+        }
+    }
+}
+
+impl From<QuicInfo> for L7ProtocolSendLog {
+    fn from(f: QuicInfo) -> Self {
+        let mut attributes = vec![];
+        for (key, val) in [
+            ("alpn", f.alpn),
+            ("original_dcid", f.original_dcid),
+            ("client_cid", f.client_cid),
+            ("server_cid", f.server_cid),
+            ("supported_versions", f.supported_versions),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        if f.zero_rtt {
+            attributes.push(KeyVal {
+                key: "zero_rtt".to_string(),
+                val: "true".to_string(),
+            });
+        }
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            version: if f.version != 0 {
+                Some(version_str(f.version))
+            } else {
+                None
+            },
+            req: L7Request {
+                req_type: f.request_type,
+                domain: f.server_name.clone(),
+                resource: f.server_name,
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: f.status,
+                result: f.response_result,
+                exception: f.exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            flags: EbpfFlags::NONE.bits(),
+            ..Default::default()
+        }
+    }
+}
+
+// QUIC payloads are encrypted except the long headers, only the handshake of each connection
+// is logged: client Initial (with SNI and ALPN from the ClientHello) as request, and the first
+// long header datagram from server as response. Short header packets are ignored.
+#[derive(Default)]
+pub struct QuicLog {
+    perf_stats: Option<L7PerfStats>,
+    last_is_on_blacklist: bool,
+
+    crypto: CryptoStream,
+    client_initials: u8,
+    zero_rtt: bool,
+    request_sent: bool,
+    response_sent: bool,
+}
+
+impl L7ProtocolParserInterface for QuicLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::UDP {
+            return false;
+        }
+        let Some(header) = LongHeader::parse(payload) else {
+            return false;
+        };
+        if header.packet_type != PacketType::Initial || header.dcid.len() < MIN_INITIAL_DCID_LEN {
+            return false;
+        }
+        let Some(keys) = InitialKeys::client(header.version, header.dcid) else {
+            return false;
+        };
+        let mut packet = payload.to_vec();
+        let Some(frames) = keys.decrypt(&mut packet, header.pn_offset, header.packet_len) else {
+            return false;
+        };
+        let mut has_crypto = false;
+        walk_initial_frames(frames, |_, _| has_crypto = true).is_some() && has_crypto
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let info = match param.direction {
+            PacketDirection::ClientToServer => self.parse_request(payload)?,
+            PacketDirection::ServerToClient => self.parse_response(payload)?,
+        };
+        let Some(mut info) = info else {
+            return Ok(L7ParseResult::None);
+        };
+        set_captured_byte!(info, param);
+        if let Some(config) = param.parse_config {
+            info.set_is_on_blacklist(config);
+        }
+        if !info.is_on_blacklist && !self.last_is_on_blacklist {
+            match info.msg_type {
+                LogMessageType::Request => {
+                    self.perf_stats.as_mut().map(|p| p.inc_req());
+                }
+                _ => {
+                    self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    if info.status == L7ResponseStatus::ClientError {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                }
+            }
+            info.cal_rrt(param).map(|rrt| {
+                info.rrt = rrt;
+                self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+            });
+        }
+        self.last_is_on_blacklist = info.is_on_blacklist;
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::QuicInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::QUIC
+    }
+
+    fn parsable_on_tcp(&self) -> bool {
+        false
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        true
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl QuicLog {
+    fn parse_error(reason: &'static str) -> Error {
+        Error::L7LogParseFailed {
+            proto: L7Protocol::QUIC,
+            reason: reason.into(),
+        }
+    }
+
+    // the client restarts the handshake after Retry or Version Negotiation
+    fn restart_handshake(&mut self) {
+        self.crypto.clear();
+        self.client_initials = 0;
+        self.zero_rtt = false;
+        self.request_sent = false;
+        self.response_sent = false;
+    }
+
+    fn parse_request(&mut self, payload: &[u8]) -> Result<Option<QuicInfo>> {
+        if self.request_sent || payload.is_empty() || payload[0] & LONG_HEADER_FORM == 0 {
+            return Ok(None);
+        }
+        let mut info = QuicInfo {
+            msg_type: LogMessageType::Request,
+            ..Default::default()
+        };
+        let mut packet_types = vec![];
+        let mut has_initial = false;
+        let mut truncated = false;
+        let mut offset = 0;
+        while offset < payload.len() {
+            // coalesced packets, the remaining bytes may also be padding
+            let Some(header) = LongHeader::parse(&payload[offset..]) else {
+                break;
+            };
+            if offset == 0 {
+                info.version = header.version;
+                info.original_dcid = hex::encode(header.dcid);
+                info.client_cid = hex::encode(header.scid);
+            }
+            match header.packet_type {
+                PacketType::Initial => {
+                    has_initial = true;
+                    truncated |= offset + header.packet_len > payload.len();
+                    let keys = InitialKeys::client(header.version, header.dcid);
+                    let mut packet = payload[offset..].to_vec();
+                    let frames = keys
+                        .as_ref()
+                        .and_then(|k| k.decrypt(&mut packet, header.pn_offset, header.packet_len));
+                    match frames {
+                        Some(frames) => {
+                            let crypto = &mut self.crypto;
+                            let _ = walk_initial_frames(frames, |o, d| crypto.push(o, d));
+                        }
+                        None => truncated = true,
+                    }
+                }
+                PacketType::ZeroRtt => self.zero_rtt = true,
+                PacketType::Handshake => (),
+                _ => return Err(Self::parse_error("unexpected packet type from client")),
+            }
+            if !packet_types.contains(&header.packet_type) {
+                packet_types.push(header.packet_type);
+            }
+            offset += header.packet_len;
+        }
+        if packet_types.is_empty() {
+            return Err(Self::parse_error("invalid long header"));
+        }
+        if !has_initial {
+            return Ok(None);
+        }
+        self.client_initials += 1;
+
+        let client_hello = ClientHello::parse(&self.crypto.data);
+        let complete = client_hello.as_ref().map(|c| c.complete).unwrap_or(false);
+        // wait for the rest of the ClientHello unless it will never arrive
+        if !complete && !truncated && self.client_initials < MAX_CLIENT_INITIALS {
+            return Ok(None);
+        }
+        if let Some(c) = client_hello {
+            info.server_name = c.server_name;
+            info.alpn = c.alpn.join(",");
+        }
+        info.request_type = PacketType::join(&packet_types);
+        info.zero_rtt = self.zero_rtt;
+        self.request_sent = true;
+        Ok(Some(info))
+    }
+
+    fn parse_response(&mut self, payload: &[u8]) -> Result<Option<QuicInfo>> {
+        if self.response_sent || payload.is_empty() || payload[0] & LONG_HEADER_FORM == 0 {
+            return Ok(None);
+        }
+        let mut info = QuicInfo {
+            msg_type: LogMessageType::Response,
+            ..Default::default()
+        };
+        let mut packet_types = vec![];
+        let mut offset = 0;
+        while offset < payload.len() {
+            let Some(header) = LongHeader::parse(&payload[offset..]) else {
+                break;
+            };
+            if offset == 0 {
+                info.version = header.version;
+                info.server_cid = hex::encode(header.scid);
+            }
+            match header.packet_type {
+                PacketType::VersionNegotiation => {
+                    info.supported_versions = header
+                        .remain
+                        .chunks_exact(4)
+                        .map(|v| version_str(read_u32_be(v)))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    info.status = L7ResponseStatus::ClientError;
+                    info.exception = "version negotiation".to_owned();
+                }
+                PacketType::ZeroRtt => {
+                    return Err(Self::parse_error("unexpected packet type from server"))
+                }
+                _ => (),
+            }
+            if !packet_types.contains(&header.packet_type) {
+                packet_types.push(header.packet_type);
+            }
+            offset += header.packet_len;
+        }
+        if packet_types.is_empty() {
+            return Err(Self::parse_error("invalid long header"));
+        }
+        info.response_result = PacketType::join(&packet_types);
+        if matches!(
+            packet_types[0],
+            PacketType::Retry | PacketType::VersionNegotiation
+        ) {
+            self.restart_handshake();
+        } else {
+            self.response_sent = true;
+        }
+        Ok(Some(info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{
+        common::{l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    const DCID: [u8; 8] = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+    const SCID: [u8; 4] = [0xc1, 0x01, 0x02, 0x03];
+
+    // TLS ClientHello with server_name and alpn extensions
+    fn client_hello(server_name: &str, alpn: &[&str]) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0x5a; 32]);
+        // empty session id, one cipher suite, null compression
+        body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+
+        let mut extensions = vec![];
+        let name = server_name.as_bytes();
+        extensions.extend_from_slice(&TLS_EXT_SERVER_NAME.to_be_bytes());
+        extensions.extend_from_slice(&(name.len() as u16 + 5).to_be_bytes());
+        extensions.extend_from_slice(&(name.len() as u16 + 3).to_be_bytes());
+        extensions.push(0);
+        extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(name);
+        let alpn_len: usize = alpn.iter().map(|a| a.len() + 1).sum();
+        extensions.extend_from_slice(&TLS_EXT_ALPN.to_be_bytes());
+        extensions.extend_from_slice(&(alpn_len as u16 + 2).to_be_bytes());
+        extensions.extend_from_slice(&(alpn_len as u16).to_be_bytes());
+        for a in alpn {
+            extensions.push(a.len() as u8);
+            extensions.extend_from_slice(a.as_bytes());
+        }
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut hs = vec![TLS_HANDSHAKE_CLIENT_HELLO];
+        hs.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        hs.extend_from_slice(&body);
+        hs
+    }
+
+    fn crypto_frame(offset: u64, data: &[u8]) -> Vec<u8> {
+        // 2 bytes varint for both offset and length
+        let mut frame = vec![FRAME_CRYPTO];
+        frame.extend_from_slice(&(0x4000 | offset as u16).to_be_bytes());
+        frame.extend_from_slice(&(0x4000 | data.len() as u16).to_be_bytes());
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    // builds a protected client Initial padded to 1200 bytes, with 2 bytes packet number
+    fn client_initial(version: u32, frames: &[u8], pn: u16) -> Vec<u8> {
+        let type_bits = if version == QUIC_V2 { 0x10 } else { 0x00 };
+        let mut packet = vec![LONG_HEADER_FORM | FIXED_BIT | type_bits | 0x01];
+        packet.extend_from_slice(&version.to_be_bytes());
+        packet.push(DCID.len() as u8);
+        packet.extend_from_slice(&DCID);
+        packet.push(SCID.len() as u8);
+        packet.extend_from_slice(&SCID);
+        // empty token
+        packet.push(0);
+        let length_offset = packet.len();
+        let pn_offset = length_offset + 2;
+        let payload_len = 1200 - pn_offset - 2 - AEAD_TAG_LEN;
+        let mut payload = frames.to_vec();
+        payload.resize(payload_len, FRAME_PADDING);
+        packet.extend_from_slice(&(0x4000 | (2 + payload_len + AEAD_TAG_LEN) as u16).to_be_bytes());
+        packet.extend_from_slice(&pn.to_be_bytes());
+
+        let (key, iv, hp) = client_initial_secrets(version, &DCID).unwrap();
+        let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_128_GCM, &key).unwrap());
+        let mut nonce = iv;
+        for (n, p) in nonce[4..].iter_mut().zip((pn as u64).to_be_bytes()) {
+            *n ^= p;
+        }
+        key.seal_in_place_append_tag(
+            aead::Nonce::assume_unique_for_key(nonce),
+            aead::Aad::from(&packet[..]),
+            &mut payload,
+        )
+        .unwrap();
+        packet.extend_from_slice(&payload);
+
+        let hp = aead::quic::HeaderProtectionKey::new(&aead::quic::AES_128, &hp).unwrap();
+        let sample_offset = pn_offset + HP_SAMPLE_OFFSET;
+        let mask = hp
+            .new_mask(&packet[sample_offset..sample_offset + HP_SAMPLE_LEN])
+            .unwrap();
+        packet[0] ^= mask[0] & 0x0f;
+        packet[pn_offset] ^= mask[1];
+        packet[pn_offset + 1] ^= mask[2];
+        assert_eq!(packet.len(), 1200);
+        packet
+    }
+
+    fn parse(
+        parser: &mut QuicLog,
+        payload: &[u8],
+        direction: PacketDirection,
+    ) -> (bool, Result<L7ParseResult>) {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = direction;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        let is_quic = QuicLog::default().check_payload(payload, &param);
+        (is_quic, parser.parse_payload(payload, &param))
+    }
+
+    fn unwrap_info(result: Result<L7ParseResult>) -> QuicInfo {
+        match result {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::QuicInfo(info))) => info,
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn initial_secrets() {
+        // RFC 9001 A.1 and RFC 9369 A.1
+        let (key, iv, hp) = client_initial_secrets(QUIC_V1, &DCID).unwrap();
+        assert_eq!(hex::encode(key), "1f369613dd76d5467730efcbe3b1a22d");
+        assert_eq!(hex::encode(iv), "fa044b2f42a3fd3b46fb255c");
+        assert_eq!(hex::encode(hp), "9f50449e04a0e810283a1e9933adedd2");
+
+        let (key, iv, hp) = client_initial_secrets(QUIC_V2, &DCID).unwrap();
+        assert_eq!(hex::encode(key), "8b1a0bc121284290a29e0971b5cd045d");
+        assert_eq!(hex::encode(iv), "91f73e2351d8fa91660e909f");
+        assert_eq!(hex::encode(hp), "45b95e15235d6f45a6b19cbcb0294ba9");
+
+        assert!(client_initial_secrets(0x0a0a0a0a, &DCID).is_none());
+    }
+
+    #[test]
+    fn client_initial_sni() {
+        for version in [QUIC_V1, QUIC_V2, QUIC_DRAFT_29] {
+            let hello = client_hello("example.com", &["h3", "h3-29"]);
+            let packet = client_initial(version, &crypto_frame(0, &hello), 0);
+
+            let mut parser = QuicLog::default();
+            let (is_quic, result) = parse(&mut parser, &packet, PacketDirection::ClientToServer);
+            assert!(is_quic);
+            let info = unwrap_info(result);
+            assert_eq!(info.version, version);
+            assert_eq!(info.request_type, "Initial");
+            assert_eq!(info.server_name, "example.com");
+            assert_eq!(info.alpn, "h3,h3-29");
+            assert_eq!(info.original_dcid, "8394c8f03e515708");
+            assert_eq!(info.client_cid, "c1010203");
+            assert!(!info.zero_rtt);
+
+            // retransmissions after the request are ignored
+            let (_, result) = parse(&mut parser, &packet, PacketDirection::ClientToServer);
+            assert!(matches!(result, Ok(L7ParseResult::None)));
+        }
+    }
+
+    #[test]
+    fn client_hello_split() {
+        let hello = client_hello("split.example.com", &["h3"]);
+        let (first, second) = hello.split_at(hello.len() / 2);
+        // the second half arrives first
+        let packets = [
+            client_initial(QUIC_V1, &crypto_frame(first.len() as u64, second), 0),
+            client_initial(QUIC_V1, &crypto_frame(0, first), 1),
+        ];
+
+        let mut parser = QuicLog::default();
+        let (is_quic, result) = parse(&mut parser, &packets[0], PacketDirection::ClientToServer);
+        assert!(is_quic);
+        assert!(matches!(result, Ok(L7ParseResult::None)));
+        let (_, result) = parse(&mut parser, &packets[1], PacketDirection::ClientToServer);
+        let info = unwrap_info(result);
+        assert_eq!(info.server_name, "split.example.com");
+        assert_eq!(info.alpn, "h3");
+    }
+
+    #[test]
+    fn truncated_initial() {
+        let hello = client_hello("truncated.example.com", &["h3"]);
+        let packet = client_initial(QUIC_V1, &crypto_frame(0, &hello), 7);
+
+        let mut parser = QuicLog::default();
+        let (is_quic, result) = parse(&mut parser, &packet[..512], PacketDirection::ClientToServer);
+        assert!(is_quic);
+        let info = unwrap_info(result);
+        assert_eq!(info.server_name, "truncated.example.com");
+    }
+
+    #[test]
+    fn version_negotiation() {
+        let hello = client_hello("example.com", &["h3"]);
+        let request = client_initial(QUIC_V1, &crypto_frame(0, &hello), 0);
+        let mut response = vec![LONG_HEADER_FORM | 0x2a, 0, 0, 0, 0];
+        response.push(SCID.len() as u8);
+        response.extend_from_slice(&SCID);
+        response.push(DCID.len() as u8);
+        response.extend_from_slice(&DCID);
+        response.extend_from_slice(&QUIC_V2.to_be_bytes());
+        response.extend_from_slice(&QUIC_DRAFT_29.to_be_bytes());
+
+        let mut parser = QuicLog::default();
+        let (_, result) = parse(&mut parser, &request, PacketDirection::ClientToServer);
+        unwrap_info(result);
+        let (is_quic, result) = parse(&mut parser, &response, PacketDirection::ServerToClient);
+        assert!(!is_quic);
+        let info = unwrap_info(result);
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+        assert_eq!(info.response_result, "VersionNegotiation");
+        assert_eq!(info.supported_versions, "2,draft-29");
+        assert_eq!(info.server_cid, "8394c8f03e515708");
+
+        // the client starts over with another version
+        let request = client_initial(QUIC_V2, &crypto_frame(0, &hello), 0);
+        let (_, result) = parse(&mut parser, &request, PacketDirection::ClientToServer);
+        assert_eq!(unwrap_info(result).version, QUIC_V2);
+    }
+
+    #[test]
+    fn short_header() {
+        let mut parser = QuicLog::default();
+        let payload = [
+            FIXED_BIT | 0x01,
+            0x83,
+            0x94,
+            0xc8,
+            0xf0,
+            0x3e,
+            0x51,
+            0x57,
+            0x08,
+        ];
+        let (is_quic, result) = parse(&mut parser, &payload, PacketDirection::ClientToServer);
+        assert!(!is_quic);
+        assert!(matches!(result, Ok(L7ParseResult::None)));
+    }
+}
@@ -26,6 +26,11 @@ use crate::{
 use prost::Message;
 use serde::Serialize;
 
+const ENOSERVICE: i32 = 1001; // Service not found
+const ENOMETHOD: i32 = 1002; // Method not found
+const EREQUEST: i32 = 1003; // Bad Request
+const ERPCAUTH: i32 = 1004; // Unauthorized
+
 #[derive(Serialize, Debug, Default, Clone)]
 pub struct BrpcInfo {
     msg_type: LogMessageType,
@@ -93,7 +98,7 @@ impl BrpcInfo {
             info.resp_code = resp.error_code;
             info.resp_exception = resp.error_text;
             info.resp_status = match resp.error_code {
-                Some(x) if x != 0 => L7ResponseStatus::ServerError,
+                Some(x) if x != 0 => Self::error_code_status(x),
                 _ => L7ResponseStatus::Ok,
             };
             info.resp_len = Some(body_size as u32 + 12);
@@ -127,6 +132,14 @@ impl BrpcInfo {
         Some((payload, info))
     }
 
+    // error codes defined in brpc/src/brpc/errno.proto, the rest are system errnos
+    fn error_code_status(error_code: i32) -> L7ResponseStatus {
+        match error_code {
+            ENOSERVICE | ENOMETHOD | EREQUEST | ERPCAUTH => L7ResponseStatus::ClientError,
+            _ => L7ResponseStatus::ServerError,
+        }
+    }
+
     fn get_request_id(&self) -> Option<u32> {
         /*
         file: brpc/src/bthread/id.cpp
@@ -248,6 +261,14 @@ impl L7ProtocolInfoInterface for BrpcInfo {
         .into()
     }
 
+    fn get_payload_size(&self) -> Option<u32> {
+        match self.msg_type {
+            LogMessageType::Request => self.req_len,
+            LogMessageType::Response => self.resp_len,
+            _ => None,
+        }
+    }
+
     fn is_on_blacklist(&self) -> bool {
         self.is_on_blacklist
     }
@@ -448,6 +469,45 @@ mod tests {
         output
     }
 
+    #[test]
+    fn check_error_code_status() {
+        for (error_code, status) in [
+            (0, L7ResponseStatus::Ok),
+            (ENOMETHOD, L7ResponseStatus::ClientError),
+            (ERPCAUTH, L7ResponseStatus::ClientError),
+            (1008, L7ResponseStatus::ServerError), // ERPCTIMEDOUT
+            (2001, L7ResponseStatus::ServerError), // EINTERNAL
+        ] {
+            let meta = RpcMeta {
+                response: Some(brpc_policy::RpcResponseMeta {
+                    error_code: Some(error_code),
+                    error_text: None,
+                }),
+                correlation_id: Some(1),
+                ..Default::default()
+            }
+            .encode_to_vec();
+            let mut payload = b"PRPC".to_vec();
+            payload.extend_from_slice(&(meta.len() as u32).to_be_bytes());
+            payload.extend_from_slice(&(meta.len() as u32).to_be_bytes());
+            payload.extend_from_slice(&meta);
+
+            let packet = MetaPacket::default();
+            let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+            let param = &ParseParam::new(
+                &packet,
+                log_cache,
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            let (_, info) = BrpcInfo::parse(&payload, param).unwrap();
+            assert_eq!(info.resp_status, status, "error_code {}", error_code);
+        }
+    }
+
     #[test]
     fn check() {
         let files = vec![
@@ -298,6 +298,14 @@ impl L7ProtocolInfoInterface for DubboInfo {
         }
     }
 
+    fn get_payload_size(&self) -> Option<u32> {
+        match self.msg_type {
+            LogMessageType::Request => self.req_msg_size,
+            LogMessageType::Response => self.resp_msg_size,
+            _ => None,
+        }
+    }
+
     fn get_request_domain(&self) -> String {
         self.service_name.clone()
     }
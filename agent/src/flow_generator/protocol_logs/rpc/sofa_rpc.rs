@@ -57,6 +57,31 @@ const CMD_CODE_RESP: u16 = 2;
 
 const CODE_C_HESSIAN: u8 = 1;
 
+// bolt response status, see com.alipay.remoting.ResponseStatus
+const RESP_STATUS_SUCCESS: u16 = 0x0;
+const RESP_STATUS_NO_PROCESSOR: u16 = 0x6;
+const RESP_STATUS_CLIENT_SEND_ERROR: u16 = 0x8;
+const RESP_STATUS_SERVER_DESERIAL_EXCEPTION: u16 = 0x12;
+
+fn resp_status_name(code: u16) -> &'static str {
+    match code {
+        RESP_STATUS_SUCCESS => "SUCCESS",
+        0x1 => "ERROR",
+        0x2 => "SERVER_EXCEPTION",
+        0x3 => "UNKNOWN",
+        0x4 => "SERVER_THREADPOOL_BUSY",
+        0x5 => "ERROR_COMM",
+        RESP_STATUS_NO_PROCESSOR => "NO_PROCESSOR",
+        0x7 => "TIMEOUT",
+        RESP_STATUS_CLIENT_SEND_ERROR => "CLIENT_SEND_ERROR",
+        0x9 => "CODEC_EXCEPTION",
+        0x10 => "CONNECTION_CLOSED",
+        0x11 => "SERVER_SERIAL_EXCEPTION",
+        RESP_STATUS_SERVER_DESERIAL_EXCEPTION => "SERVER_DESERIAL_EXCEPTION",
+        _ => "",
+    }
+}
+
 const HDR_SERVICE_KEY: &'static str = "sofa_head_target_service";
 const SERVICE_KEY: &'static str = "targetServiceUniqueName";
 const HDR_METHOD_KEY: &'static str = "sofa_head_method_name";
@@ -189,6 +214,7 @@ pub struct SofaRpcInfo {
 
     resp_code: u16,
     status: L7ResponseStatus,
+    exception: String,
 
     #[serde(skip)]
     is_on_blacklist: bool,
@@ -233,6 +259,7 @@ impl L7ProtocolInfoInterface for SofaRpcInfo {
             self.resp_len = s.resp_len;
             self.resp_code = s.resp_code;
             self.status = s.status;
+            std::mem::swap(&mut self.exception, &mut s.exception);
             self.captured_response_byte = s.captured_response_byte;
             swap_if!(self, endpoint, is_none, s);
             if s.is_on_blacklist {
@@ -262,6 +289,15 @@ impl L7ProtocolInfoInterface for SofaRpcInfo {
         }
     }
 
+    fn get_payload_size(&self) -> Option<u32> {
+        let size = match self.msg_type {
+            LogMessageType::Request => self.req_len,
+            LogMessageType::Response => self.resp_len,
+            _ => 0,
+        };
+        (size > 0).then_some(size)
+    }
+
     fn is_on_blacklist(&self) -> bool {
         self.is_on_blacklist
     }
@@ -288,6 +324,7 @@ impl From<SofaRpcInfo> for L7ProtocolSendLog {
             resp: L7Response {
                 status: s.status,
                 code: Some(s.resp_code as i32),
+                exception: s.exception,
                 ..Default::default()
             },
             trace_info: Some(TraceInfo {
@@ -400,10 +437,16 @@ impl SofaRpcLog {
                 payload = &payload[RESP_HDR_LEN..];
                 info.resp_code = hdr.resp_code;
                 info.resp_len = hdr.content_len + (hdr.hdr_len as u32) + (hdr.class_len as u32);
-                if info.resp_code == 8 {
-                    info.status = L7ResponseStatus::ClientError;
-                } else if info.resp_code != 0 {
-                    info.status = L7ResponseStatus::ServerError;
+                info.status = match info.resp_code {
+                    RESP_STATUS_SUCCESS => L7ResponseStatus::Ok,
+                    // the request can not be sent or handled by the server
+                    RESP_STATUS_NO_PROCESSOR
+                    | RESP_STATUS_CLIENT_SEND_ERROR
+                    | RESP_STATUS_SERVER_DESERIAL_EXCEPTION => L7ResponseStatus::ClientError,
+                    _ => L7ResponseStatus::ServerError,
+                };
+                if info.status != L7ResponseStatus::Ok {
+                    info.exception = resp_status_name(info.resp_code).to_string();
                 }
                 LogMessageType::Response
             }
@@ -656,9 +699,13 @@ mod test {
             flow::{L7PerfStats, PacketDirection},
             l7_protocol_info::L7ProtocolInfo,
             l7_protocol_log::{L7PerfCache, L7ProtocolParserInterface, ParseParam},
+            MetaPacket,
         },
         flow_generator::{
-            protocol_logs::rpc::sofa_rpc::{CMD_CODE_REQ, CMD_CODE_RESP, PROTO_BOLT_V1},
+            protocol_logs::{
+                rpc::sofa_rpc::{CMD_CODE_REQ, CMD_CODE_RESP, PROTO_BOLT_V1, TYPE_RESP},
+                L7ResponseStatus,
+            },
             LogMessageType, L7_RRT_CACHE_CAPACITY,
         },
         utils::test::Capture,
@@ -693,6 +740,49 @@ mod test {
         assert_eq!(ctx.parent_span_id, "");
     }
 
+    #[test]
+    fn test_resp_status() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        let param = &ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        for (code, status, exception) in [
+            (0x0, L7ResponseStatus::Ok, ""),
+            (0x6, L7ResponseStatus::ClientError, "NO_PROCESSOR"),
+            (0x8, L7ResponseStatus::ClientError, "CLIENT_SEND_ERROR"),
+            (0x4, L7ResponseStatus::ServerError, "SERVER_THREADPOOL_BUSY"),
+            (0x7, L7ResponseStatus::ServerError, "TIMEOUT"),
+        ] {
+            // response header without class name, header and content
+            let mut payload = vec![PROTO_BOLT_V1, TYPE_RESP];
+            payload.extend_from_slice(&CMD_CODE_RESP.to_be_bytes());
+            payload.push(1);
+            payload.extend_from_slice(&3u32.to_be_bytes());
+            payload.push(1);
+            payload.extend_from_slice(&(code as u16).to_be_bytes());
+            payload.extend_from_slice(&[0; 8]);
+
+            let info = SofaRpcLog::default()
+                .parse_payload(&payload, param)
+                .unwrap()
+                .unwrap_single();
+            let L7ProtocolInfo::SofaRpcInfo(info) = info else {
+                unreachable!()
+            };
+            assert_eq!(info.req_id, 3);
+            assert_eq!(info.status, status);
+            assert_eq!(info.exception, exception);
+        }
+    }
+
     #[test]
     fn test_sofarpc_old() {
         let pcap_file = Path::new("resources/test/flow_generator/sofarpc/sofa-old.pcap");
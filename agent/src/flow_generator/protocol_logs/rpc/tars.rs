@@ -476,6 +476,15 @@ impl L7ProtocolInfoInterface for TarsInfo {
         )
         .into()
     }
+
+    fn get_payload_size(&self) -> Option<u32> {
+        let size = match self.msg_type {
+            LogMessageType::Request => self.req_len,
+            LogMessageType::Response => self.resp_len,
+            _ => 0,
+        };
+        (size > 0).then_some(size)
+    }
 }
 
 #[cfg(test)]
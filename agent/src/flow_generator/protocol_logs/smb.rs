@@ -0,0 +1,1069 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+use serde::Serialize;
+
+use super::ntlm::NtlmMessage;
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{set_captured_byte, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u16_le, read_u32_be, read_u32_le, read_u64_le},
+};
+use public::l7_protocol::L7Protocol;
+
+// NetBIOS session service header, 1 byte type and 3 bytes length
+const NETBIOS_HEADER_LEN: usize = 4;
+const NETBIOS_SESSION_MESSAGE: u8 = 0;
+const NETBIOS_LENGTH_MASK: u32 = 0x00ff_ffff;
+
+const SMB2_MAGIC: &[u8] = b"\xfeSMB";
+const SMB2_HEADER_LEN: usize = 64;
+
+const SMB2_FLAGS_SERVER_TO_REDIR: u32 = 0x0000_0001;
+const SMB2_FLAGS_ASYNC_COMMAND: u32 = 0x0000_0002;
+const SMB2_FLAGS_RELATED_OPERATIONS: u32 = 0x0000_0004;
+
+const SMB2_NEGOTIATE: u16 = 0x00;
+const SMB2_SESSION_SETUP: u16 = 0x01;
+const SMB2_LOGOFF: u16 = 0x02;
+const SMB2_TREE_CONNECT: u16 = 0x03;
+const SMB2_TREE_DISCONNECT: u16 = 0x04;
+const SMB2_CREATE: u16 = 0x05;
+const SMB2_CLOSE: u16 = 0x06;
+const SMB2_FLUSH: u16 = 0x07;
+const SMB2_READ: u16 = 0x08;
+const SMB2_WRITE: u16 = 0x09;
+const SMB2_LOCK: u16 = 0x0a;
+const SMB2_IOCTL: u16 = 0x0b;
+const SMB2_CANCEL: u16 = 0x0c;
+const SMB2_ECHO: u16 = 0x0d;
+const SMB2_QUERY_DIRECTORY: u16 = 0x0e;
+const SMB2_CHANGE_NOTIFY: u16 = 0x0f;
+const SMB2_QUERY_INFO: u16 = 0x10;
+const SMB2_SET_INFO: u16 = 0x11;
+const SMB2_OPLOCK_BREAK: u16 = 0x12;
+
+// offsets relative to the start of the SMB2 header
+const TREE_CONNECT_PATH_OFFSET: usize = SMB2_HEADER_LEN + 4;
+const CREATE_NAME_OFFSET: usize = SMB2_HEADER_LEN + 44;
+const CREATE_RESPONSE_FILE_ID_OFFSET: usize = SMB2_HEADER_LEN + 64;
+const NEGOTIATE_RESPONSE_DIALECT_OFFSET: usize = SMB2_HEADER_LEN + 4;
+const FILE_ID_LEN: usize = 16;
+// file id of compounded related requests, refers to the file opened by the previous request
+const FILE_ID_RELATED: u128 = u128::MAX;
+
+const STATUS_SUCCESS: u32 = 0x0000_0000;
+const STATUS_PENDING: u32 = 0x0000_0103;
+const STATUS_BUFFER_OVERFLOW: u32 = 0x8000_0005;
+const STATUS_NO_MORE_FILES: u32 = 0x8000_0006;
+const STATUS_INVALID_PARAMETER: u32 = 0xc000_000d;
+const STATUS_NO_SUCH_FILE: u32 = 0xc000_000f;
+const STATUS_END_OF_FILE: u32 = 0xc000_0011;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+const STATUS_NO_MEMORY: u32 = 0xc000_0017;
+const STATUS_ACCESS_DENIED: u32 = 0xc000_0022;
+const STATUS_OBJECT_NAME_INVALID: u32 = 0xc000_0033;
+const STATUS_OBJECT_NAME_NOT_FOUND: u32 = 0xc000_0034;
+const STATUS_OBJECT_NAME_COLLISION: u32 = 0xc000_0035;
+const STATUS_OBJECT_PATH_NOT_FOUND: u32 = 0xc000_003a;
+const STATUS_SHARING_VIOLATION: u32 = 0xc000_0043;
+const STATUS_DELETE_PENDING: u32 = 0xc000_0056;
+const STATUS_LOGON_FAILURE: u32 = 0xc000_006d;
+const STATUS_ACCOUNT_RESTRICTION: u32 = 0xc000_006e;
+const STATUS_PASSWORD_EXPIRED: u32 = 0xc000_0071;
+const STATUS_ACCOUNT_DISABLED: u32 = 0xc000_0072;
+const STATUS_DISK_FULL: u32 = 0xc000_007f;
+const STATUS_INSUFFICIENT_RESOURCES: u32 = 0xc000_009a;
+const STATUS_FILE_IS_A_DIRECTORY: u32 = 0xc000_00ba;
+const STATUS_NOT_SUPPORTED: u32 = 0xc000_00bb;
+const STATUS_IO_TIMEOUT: u32 = 0xc000_00b5;
+const STATUS_NETWORK_NAME_DELETED: u32 = 0xc000_00c9;
+const STATUS_BAD_NETWORK_NAME: u32 = 0xc000_00cc;
+const STATUS_REQUEST_NOT_ACCEPTED: u32 = 0xc000_00d0;
+const STATUS_INTERNAL_ERROR: u32 = 0xc000_00e5;
+const STATUS_UNEXPECTED_IO_ERROR: u32 = 0xc000_00e9;
+const STATUS_DIRECTORY_NOT_EMPTY: u32 = 0xc000_0101;
+const STATUS_NOT_A_DIRECTORY: u32 = 0xc000_0103;
+const STATUS_CANCELLED: u32 = 0xc000_0120;
+const STATUS_FILE_CLOSED: u32 = 0xc000_0128;
+const STATUS_USER_SESSION_DELETED: u32 = 0xc000_0203;
+const STATUS_NOT_FOUND: u32 = 0xc000_0225;
+const STATUS_ACCOUNT_LOCKED_OUT: u32 = 0xc000_0234;
+const STATUS_NETWORK_SESSION_EXPIRED: u32 = 0xc000_035c;
+
+// the highest 2 bits of NTSTATUS is the severity, 3 means error
+const NT_STATUS_SEVERITY_ERROR: u32 = 0xc000_0000;
+
+fn command_str(command: u16) -> &'static str {
+    match command {
+        SMB2_NEGOTIATE => "NEGOTIATE",
+        SMB2_SESSION_SETUP => "SESSION_SETUP",
+        SMB2_LOGOFF => "LOGOFF",
+        SMB2_TREE_CONNECT => "TREE_CONNECT",
+        SMB2_TREE_DISCONNECT => "TREE_DISCONNECT",
+        SMB2_CREATE => "CREATE",
+        SMB2_CLOSE => "CLOSE",
+        SMB2_FLUSH => "FLUSH",
+        SMB2_READ => "READ",
+        SMB2_WRITE => "WRITE",
+        SMB2_LOCK => "LOCK",
+        SMB2_IOCTL => "IOCTL",
+        SMB2_CANCEL => "CANCEL",
+        SMB2_ECHO => "ECHO",
+        SMB2_QUERY_DIRECTORY => "QUERY_DIRECTORY",
+        SMB2_CHANGE_NOTIFY => "CHANGE_NOTIFY",
+        SMB2_QUERY_INFO => "QUERY_INFO",
+        SMB2_SET_INFO => "SET_INFO",
+        SMB2_OPLOCK_BREAK => "OPLOCK_BREAK",
+        _ => "",
+    }
+}
+
+// offset of FileId in requests referring to an opened file
+fn file_id_offset(command: u16) -> Option<usize> {
+    let offset = match command {
+        SMB2_CLOSE | SMB2_FLUSH | SMB2_LOCK | SMB2_IOCTL | SMB2_QUERY_DIRECTORY
+        | SMB2_CHANGE_NOTIFY | SMB2_OPLOCK_BREAK => 8,
+        SMB2_READ | SMB2_WRITE | SMB2_SET_INFO => 16,
+        SMB2_QUERY_INFO => 24,
+        _ => return None,
+    };
+    Some(SMB2_HEADER_LEN + offset)
+}
+
+fn dialect_str(dialect: u16) -> &'static str {
+    match dialect {
+        0x0202 => "2.0.2",
+        0x0210 => "2.1",
+        0x0300 => "3.0",
+        0x0302 => "3.0.2",
+        0x0311 => "3.1.1",
+        _ => "",
+    }
+}
+
+fn status_str(status: u32) -> &'static str {
+    match status {
+        STATUS_SUCCESS => "STATUS_SUCCESS",
+        STATUS_PENDING => "STATUS_PENDING",
+        STATUS_BUFFER_OVERFLOW => "STATUS_BUFFER_OVERFLOW",
+        STATUS_NO_MORE_FILES => "STATUS_NO_MORE_FILES",
+        STATUS_INVALID_PARAMETER => "STATUS_INVALID_PARAMETER",
+        STATUS_NO_SUCH_FILE => "STATUS_NO_SUCH_FILE",
+        STATUS_END_OF_FILE => "STATUS_END_OF_FILE",
+        STATUS_MORE_PROCESSING_REQUIRED => "STATUS_MORE_PROCESSING_REQUIRED",
+        STATUS_NO_MEMORY => "STATUS_NO_MEMORY",
+        STATUS_ACCESS_DENIED => "STATUS_ACCESS_DENIED",
+        STATUS_OBJECT_NAME_INVALID => "STATUS_OBJECT_NAME_INVALID",
+        STATUS_OBJECT_NAME_NOT_FOUND => "STATUS_OBJECT_NAME_NOT_FOUND",
+        STATUS_OBJECT_NAME_COLLISION => "STATUS_OBJECT_NAME_COLLISION",
+        STATUS_OBJECT_PATH_NOT_FOUND => "STATUS_OBJECT_PATH_NOT_FOUND",
+        STATUS_SHARING_VIOLATION => "STATUS_SHARING_VIOLATION",
+        STATUS_DELETE_PENDING => "STATUS_DELETE_PENDING",
+        STATUS_LOGON_FAILURE => "STATUS_LOGON_FAILURE",
+        STATUS_ACCOUNT_RESTRICTION => "STATUS_ACCOUNT_RESTRICTION",
+        STATUS_PASSWORD_EXPIRED => "STATUS_PASSWORD_EXPIRED",
+        STATUS_ACCOUNT_DISABLED => "STATUS_ACCOUNT_DISABLED",
+        STATUS_DISK_FULL => "STATUS_DISK_FULL",
+        STATUS_INSUFFICIENT_RESOURCES => "STATUS_INSUFFICIENT_RESOURCES",
+        STATUS_FILE_IS_A_DIRECTORY => "STATUS_FILE_IS_A_DIRECTORY",
+        STATUS_NOT_SUPPORTED => "STATUS_NOT_SUPPORTED",
+        STATUS_IO_TIMEOUT => "STATUS_IO_TIMEOUT",
+        STATUS_NETWORK_NAME_DELETED => "STATUS_NETWORK_NAME_DELETED",
+        STATUS_BAD_NETWORK_NAME => "STATUS_BAD_NETWORK_NAME",
+        STATUS_REQUEST_NOT_ACCEPTED => "STATUS_REQUEST_NOT_ACCEPTED",
+        STATUS_INTERNAL_ERROR => "STATUS_INTERNAL_ERROR",
+        STATUS_UNEXPECTED_IO_ERROR => "STATUS_UNEXPECTED_IO_ERROR",
+        STATUS_DIRECTORY_NOT_EMPTY => "STATUS_DIRECTORY_NOT_EMPTY",
+        STATUS_NOT_A_DIRECTORY => "STATUS_NOT_A_DIRECTORY",
+        STATUS_CANCELLED => "STATUS_CANCELLED",
+        STATUS_FILE_CLOSED => "STATUS_FILE_CLOSED",
+        STATUS_USER_SESSION_DELETED => "STATUS_USER_SESSION_DELETED",
+        STATUS_NOT_FOUND => "STATUS_NOT_FOUND",
+        STATUS_ACCOUNT_LOCKED_OUT => "STATUS_ACCOUNT_LOCKED_OUT",
+        STATUS_NETWORK_SESSION_EXPIRED => "STATUS_NETWORK_SESSION_EXPIRED",
+        _ => "",
+    }
+}
+
+// UTF-16LE string described by a (offset: u16, length: u16) pair at `field_offset`
+fn read_utf16(msg: &[u8], field_offset: usize) -> String {
+    let Some(field) = msg.get(field_offset..field_offset + 4) else {
+        return String::new();
+    };
+    let offset = read_u16_le(field) as usize;
+    let len = read_u16_le(&field[2..]) as usize;
+    let Some(value) = msg.get(offset..(offset + len).min(msg.len())) else {
+        return String::new();
+    };
+    let value: Vec<u16> = value
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&value)
+}
+
+fn read_file_id(msg: &[u8], offset: usize) -> Option<u128> {
+    msg.get(offset..offset + FILE_ID_LEN)
+        .map(|id| u128::from_le_bytes(id.try_into().unwrap()))
+}
+
+#[derive(Debug, PartialEq)]
+struct Smb2Header {
+    status: u32,
+    command: u16,
+    flags: u32,
+    next_command: u32,
+    message_id: u64,
+    // not available in async headers
+    tree_id: u32,
+}
+
+impl Smb2Header {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < SMB2_HEADER_LEN
+            || &buf[..4] != SMB2_MAGIC
+            || read_u16_le(&buf[4..]) as usize != SMB2_HEADER_LEN
+        {
+            return None;
+        }
+        let flags = read_u32_le(&buf[16..]);
+        Some(Self {
+            status: read_u32_le(&buf[8..]),
+            command: read_u16_le(&buf[12..]),
+            flags,
+            next_command: read_u32_le(&buf[20..]),
+            message_id: read_u64_le(&buf[24..]),
+            tree_id: if flags & SMB2_FLAGS_ASYNC_COMMAND == 0 {
+                read_u32_le(&buf[36..])
+            } else {
+                0
+            },
+        })
+    }
+
+    fn is_response(&self) -> bool {
+        self.flags & SMB2_FLAGS_SERVER_TO_REDIR != 0
+    }
+
+    fn is_related(&self) -> bool {
+        self.flags & SMB2_FLAGS_RELATED_OPERATIONS != 0
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SmbInfo {
+    msg_type: LogMessageType,
+
+    #[serde(rename = "request_id", skip_serializing_if = "value_is_default")]
+    message_id: u64,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    command: String,
+    // `\\server\share` of the tree connect
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    share: String,
+    // file path relative to the share
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    path: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    dialect: u16,
+
+    #[serde(skip)]
+    req_len: u32,
+    #[serde(skip)]
+    resp_len: u32,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    nt_status: Option<u32>,
+    #[serde(rename = "response_status")]
+    status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    exception: String,
+
+    #[serde(skip)]
+    attributes: Vec<KeyVal>,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+    rrt: u64,
+
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for SmbInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.message_id as u32)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::SmbInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::SMB,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.share.clone()
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.path.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl SmbInfo {
+    fn merge(&mut self, other: &mut Self) {
+        if self.share.is_empty() {
+            std::mem::swap(&mut self.share, &mut other.share);
+        }
+        if self.path.is_empty() {
+            std::mem::swap(&mut self.path, &mut other.path);
+        }
+        if other.dialect != 0 {
+            self.dialect = other.dialect;
+        }
+        self.resp_len = other.resp_len;
+        self.nt_status = other.nt_status;
+        self.status = other.status;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+        self.attributes.append(&mut other.attributes);
+        self.captured_response_byte = other.captured_response_byte;
+        self.is_on_blacklist |= other.is_on_blacklist;
+    }
+
+    fn set_status(&mut self, nt_status: u32) {
+        self.nt_status = Some(nt_status);
+        self.status = match nt_status {
+            // expected errors during normal operations
+            STATUS_MORE_PROCESSING_REQUIRED | STATUS_END_OF_FILE => L7ResponseStatus::Ok,
+            STATUS_NO_MEMORY
+            | STATUS_DISK_FULL
+            | STATUS_INSUFFICIENT_RESOURCES
+            | STATUS_IO_TIMEOUT
+            | STATUS_NETWORK_NAME_DELETED
+            | STATUS_REQUEST_NOT_ACCEPTED
+            | STATUS_INTERNAL_ERROR
+            | STATUS_UNEXPECTED_IO_ERROR => L7ResponseStatus::ServerError,
+            s if s & NT_STATUS_SEVERITY_ERROR == NT_STATUS_SEVERITY_ERROR => {
+                L7ResponseStatus::ClientError
+            }
+            // success, informational and warning
+            _ => L7ResponseStatus::Ok,
+        };
+        if self.status != L7ResponseStatus::Ok {
+            self.exception = match status_str(nt_status) {
+                "" => format!("0x{:08x}", nt_status),
+                s => s.to_string(),
+            };
+        }
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::SMB) {
+            self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.path)
+                || t.request_type.is_on_blacklist(&self.command)
+                || t.request_domain.is_on_blacklist(&self.share);
+        }
+    }
+}
+
+impl From<SmbInfo> for L7ProtocolSendLog {
+    fn from(f: SmbInfo) -> Self {
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req_len: if f.req_len != 0 {
+                Some(f.req_len)
+            } else {
+                None
+            },
+            resp_len: if f.resp_len != 0 {
+                Some(f.resp_len)
+            } else {
+                None
+            },
+            version: match dialect_str(f.dialect) {
+                "" => None,
+                d => Some(d.to_string()),
+            },
+            req: L7Request {
+                req_type: f.command,
+                domain: f.share,
+                resource: f.path,
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: f.status,
+                // NTSTATUS is defined as a signed 32 bits integer
+                code: f.nt_status.map(|s| s as i32),
+                exception: f.exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.message_id as u32),
+                attributes: if f.attributes.is_empty() {
+                    None
+                } else {
+                    Some(f.attributes)
+                },
+                ..Default::default()
+            }),
+            flags: EbpfFlags::NONE.bits(),
+            ..Default::default()
+        }
+    }
+}
+
+// SMB2 and SMB3 over direct TCP or NetBIOS, encrypted messages of SMB3 are ignored.
+//
+// Shares and files are only named in TREE_CONNECT and CREATE, the following requests refer
+// to them by TreeId and FileId, so the names are remembered per flow.
+pub struct SmbLog {
+    perf_stats: Option<L7PerfStats>,
+    last_is_on_blacklist: bool,
+
+    dialect: u16,
+    // share or file name of TREE_CONNECT and CREATE requests waiting for response
+    pending: LruCache<u64, String>,
+    trees: LruCache<u32, String>,
+    files: LruCache<u128, String>,
+}
+
+impl Default for SmbLog {
+    fn default() -> Self {
+        Self {
+            perf_stats: None,
+            last_is_on_blacklist: false,
+            dialect: 0,
+            pending: LruCache::new(NonZeroUsize::new(Self::MAX_PENDING_PER_FLOW).unwrap()),
+            trees: LruCache::new(NonZeroUsize::new(Self::MAX_TREE_PER_FLOW).unwrap()),
+            files: LruCache::new(NonZeroUsize::new(Self::MAX_FILE_PER_FLOW).unwrap()),
+        }
+    }
+}
+
+impl L7ProtocolParserInterface for SmbLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
+            return false;
+        }
+        if payload.len() < NETBIOS_HEADER_LEN || payload[0] != NETBIOS_SESSION_MESSAGE {
+            return false;
+        }
+        Smb2Header::parse(&payload[NETBIOS_HEADER_LEN..])
+            .map(|h| !h.is_response())
+            .unwrap_or_default()
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+
+        let mut vec = self.parse(payload)?;
+        for info in vec.iter_mut() {
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if !info.is_on_blacklist && !self.last_is_on_blacklist {
+                match info.msg_type {
+                    LogMessageType::Request => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req());
+                    }
+                    _ => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp());
+                    }
+                }
+                match info.status {
+                    L7ResponseStatus::ClientError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                    }
+                    L7ResponseStatus::ServerError => {
+                        self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    }
+                    _ => {}
+                }
+                info.cal_rrt(param).map(|rrt| {
+                    info.rrt = rrt;
+                    self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+                });
+            }
+            self.last_is_on_blacklist = info.is_on_blacklist;
+        }
+
+        if !param.parse_log {
+            Ok(L7ParseResult::None)
+        } else if vec.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::SmbInfo(
+                vec.remove(0),
+            )))
+        } else if vec.len() > 1 {
+            Ok(L7ParseResult::Multi(
+                vec.into_iter().map(L7ProtocolInfo::SmbInfo).collect(),
+            ))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::SMB
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl SmbLog {
+    const MAX_PENDING_PER_FLOW: usize = 32;
+    const MAX_TREE_PER_FLOW: usize = 32;
+    const MAX_FILE_PER_FLOW: usize = 256;
+
+    fn parse(&mut self, mut payload: &[u8]) -> Result<Vec<SmbInfo>> {
+        let mut infos = vec![];
+        let mut is_smb = false;
+        while payload.len() > NETBIOS_HEADER_LEN && payload[0] == NETBIOS_SESSION_MESSAGE {
+            let len = (read_u32_be(payload) & NETBIOS_LENGTH_MASK) as usize;
+            let end = (NETBIOS_HEADER_LEN + len).min(payload.len());
+            is_smb |= self.parse_compound(&payload[NETBIOS_HEADER_LEN..end], &mut infos);
+            payload = &payload[end..];
+        }
+        if !is_smb {
+            return Err(Error::L7LogParseFailed {
+                proto: L7Protocol::SMB,
+                reason: "invalid smb2 message".into(),
+            });
+        }
+        Ok(infos)
+    }
+
+    // a compounded message is a chain of messages linked by NextCommand
+    fn parse_compound(&mut self, mut msg: &[u8], infos: &mut Vec<SmbInfo>) -> bool {
+        let mut is_smb = false;
+        // share and path of the previous message for related operations
+        let mut previous: Option<(String, String)> = None;
+        while let Some(header) = Smb2Header::parse(msg) {
+            let next = header.next_command as usize;
+            // the next message can not start inside the header
+            if next != 0 && next < SMB2_HEADER_LEN {
+                break;
+            }
+            is_smb = true;
+            let end = if next != 0 {
+                next.min(msg.len())
+            } else {
+                msg.len()
+            };
+            let related = previous.take().filter(|_| header.is_related());
+            let info = if header.is_response() {
+                self.parse_response(&header, &msg[..end])
+            } else {
+                Some(self.parse_request(&header, &msg[..end], related))
+            };
+            if let Some(info) = info {
+                previous = Some((info.share.clone(), info.path.clone()));
+                infos.push(info);
+            }
+            if next == 0 || next >= msg.len() {
+                break;
+            }
+            msg = &msg[next..];
+        }
+        is_smb
+    }
+
+    fn parse_request(
+        &mut self,
+        header: &Smb2Header,
+        msg: &[u8],
+        related: Option<(String, String)>,
+    ) -> SmbInfo {
+        let mut info = SmbInfo {
+            msg_type: LogMessageType::Request,
+            message_id: header.message_id,
+            command: command_str(header.command).to_string(),
+            dialect: self.dialect,
+            req_len: msg.len() as u32,
+            ..Default::default()
+        };
+        let (related_share, related_path) = related.unwrap_or_default();
+        info.share = self
+            .trees
+            .get(&header.tree_id)
+            .cloned()
+            .unwrap_or(related_share);
+        match header.command {
+            SMB2_SESSION_SETUP => {
+                if let Some(ntlm) = msg.get(SMB2_HEADER_LEN..).and_then(NtlmMessage::parse) {
+                    info.attributes = ntlm.attributes();
+                }
+            }
+            SMB2_TREE_CONNECT => {
+                info.share = read_utf16(msg, TREE_CONNECT_PATH_OFFSET);
+                self.pending.put(header.message_id, info.share.clone());
+            }
+            SMB2_TREE_DISCONNECT => {
+                self.trees.pop(&header.tree_id);
+            }
+            SMB2_CREATE => {
+                info.path = read_utf16(msg, CREATE_NAME_OFFSET);
+                self.pending.put(header.message_id, info.path.clone());
+            }
+            command => {
+                let file_id = file_id_offset(command).and_then(|o| read_file_id(msg, o));
+                info.path = match file_id {
+                    Some(FILE_ID_RELATED) => related_path,
+                    Some(id) if command == SMB2_CLOSE => self.files.pop(&id).unwrap_or_default(),
+                    Some(id) => self.files.get(&id).cloned().unwrap_or_default(),
+                    None => String::new(),
+                };
+            }
+        }
+        info
+    }
+
+    fn parse_response(&mut self, header: &Smb2Header, msg: &[u8]) -> Option<SmbInfo> {
+        // interim response of async operations, the final response comes later with the
+        // same MessageId
+        if header.flags & SMB2_FLAGS_ASYNC_COMMAND != 0 && header.status == STATUS_PENDING {
+            return None;
+        }
+        let mut info = SmbInfo {
+            msg_type: LogMessageType::Response,
+            message_id: header.message_id,
+            command: command_str(header.command).to_string(),
+            resp_len: msg.len() as u32,
+            ..Default::default()
+        };
+        info.set_status(header.status);
+        let succeeded = header.status == STATUS_SUCCESS;
+        match header.command {
+            SMB2_NEGOTIATE if succeeded => {
+                if let Some(dialect) = msg
+                    .get(NEGOTIATE_RESPONSE_DIALECT_OFFSET..NEGOTIATE_RESPONSE_DIALECT_OFFSET + 2)
+                    .map(read_u16_le)
+                {
+                    self.dialect = dialect;
+                }
+            }
+            SMB2_TREE_CONNECT => {
+                if let Some(share) = self.pending.pop(&header.message_id) {
+                    if succeeded {
+                        self.trees.put(header.tree_id, share.clone());
+                    }
+                    info.share = share;
+                }
+            }
+            SMB2_CREATE => {
+                if let Some(path) = self.pending.pop(&header.message_id) {
+                    if let Some(id) = read_file_id(msg, CREATE_RESPONSE_FILE_ID_OFFSET) {
+                        if succeeded {
+                            self.files.put(id, path.clone());
+                        }
+                    }
+                    info.path = path;
+                }
+            }
+            _ => (),
+        }
+        if info.share.is_empty() {
+            if let Some(share) = self.trees.get(&header.tree_id) {
+                info.share = share.clone();
+            }
+        }
+        info.dialect = self.dialect;
+        Some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    const TREE_ID: u32 = 5;
+    const FILE_ID: [u8; 16] = [0x11; 16];
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn header(command: u16, message_id: u64, status: u32, flags: u32, tree_id: u32) -> Vec<u8> {
+        let mut h = SMB2_MAGIC.to_vec();
+        h.extend_from_slice(&(SMB2_HEADER_LEN as u16).to_le_bytes());
+        h.extend_from_slice(&1u16.to_le_bytes());
+        h.extend_from_slice(&status.to_le_bytes());
+        h.extend_from_slice(&command.to_le_bytes());
+        h.extend_from_slice(&1u16.to_le_bytes());
+        h.extend_from_slice(&flags.to_le_bytes());
+        // next command
+        h.extend_from_slice(&0u32.to_le_bytes());
+        h.extend_from_slice(&message_id.to_le_bytes());
+        // reserved
+        h.extend_from_slice(&0u32.to_le_bytes());
+        h.extend_from_slice(&tree_id.to_le_bytes());
+        // session id and signature
+        h.extend_from_slice(&0x4000_0001u64.to_le_bytes());
+        h.extend_from_slice(&[0u8; 16]);
+        h
+    }
+
+    fn request(command: u16, message_id: u64, tree_id: u32, body: &[u8]) -> Vec<u8> {
+        [&header(command, message_id, 0, 0, tree_id)[..], body].concat()
+    }
+
+    fn response(command: u16, message_id: u64, status: u32, tree_id: u32, body: &[u8]) -> Vec<u8> {
+        let h = header(
+            command,
+            message_id,
+            status,
+            SMB2_FLAGS_SERVER_TO_REDIR,
+            tree_id,
+        );
+        [&h[..], body].concat()
+    }
+
+    fn tree_connect_request(message_id: u64, path: &str) -> Vec<u8> {
+        let path = utf16(path);
+        let mut body = vec![];
+        body.extend_from_slice(&9u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&((SMB2_HEADER_LEN + 8) as u16).to_le_bytes());
+        body.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        body.extend_from_slice(&path);
+        request(SMB2_TREE_CONNECT, message_id, 0, &body)
+    }
+
+    fn create_request(message_id: u64, name: &str) -> Vec<u8> {
+        let name = utf16(name);
+        let mut body = vec![0u8; 56];
+        body[..2].copy_from_slice(&57u16.to_le_bytes());
+        body[44..46].copy_from_slice(&((SMB2_HEADER_LEN + 56) as u16).to_le_bytes());
+        body[46..48].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        body.extend_from_slice(&name);
+        request(SMB2_CREATE, message_id, TREE_ID, &body)
+    }
+
+    fn create_response(message_id: u64, status: u32) -> Vec<u8> {
+        let mut body = vec![0u8; 88];
+        body[..2].copy_from_slice(&89u16.to_le_bytes());
+        body[64..80].copy_from_slice(&FILE_ID);
+        response(SMB2_CREATE, message_id, status, TREE_ID, &body)
+    }
+
+    fn read_request(message_id: u64, file_id: &[u8; 16]) -> Vec<u8> {
+        let mut body = vec![0u8; 48];
+        body[..2].copy_from_slice(&49u16.to_le_bytes());
+        body[16..32].copy_from_slice(file_id);
+        request(SMB2_READ, message_id, TREE_ID, &body)
+    }
+
+    fn close_request(message_id: u64, file_id: &[u8; 16]) -> Vec<u8> {
+        let mut body = vec![0u8; 24];
+        body[..2].copy_from_slice(&24u16.to_le_bytes());
+        body[8..24].copy_from_slice(file_id);
+        request(SMB2_CLOSE, message_id, TREE_ID, &body)
+    }
+
+    fn netbios(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut compound = vec![];
+        for (i, m) in messages.iter().enumerate() {
+            let mut m = m.clone();
+            if i + 1 < messages.len() {
+                m.resize((m.len() + 7) & !7, 0);
+                let next = m.len() as u32;
+                m[20..24].copy_from_slice(&next.to_le_bytes());
+            }
+            compound.extend_from_slice(&m);
+        }
+        [&(compound.len() as u32).to_be_bytes()[..], &compound].concat()
+    }
+
+    fn parse(parser: &mut SmbLog, payload: &[u8], direction: PacketDirection) -> Vec<SmbInfo> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        packet.lookup_key.direction = direction;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.set_captured_byte(payload.len());
+        match parser.parse_payload(payload, &param) {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::SmbInfo(info))) => vec![info],
+            Ok(L7ParseResult::Multi(infos)) => infos
+                .into_iter()
+                .map(|i| match i {
+                    L7ProtocolInfo::SmbInfo(info) => info,
+                    _ => unreachable!(),
+                })
+                .collect(),
+            Ok(L7ParseResult::None) => vec![],
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+
+    fn check(payload: &[u8]) -> bool {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let param = ParseParam::new(
+            &packet,
+            log_cache,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        SmbLog::default().check_payload(payload, &param)
+    }
+
+    #[test]
+    fn tree_connect_and_create() {
+        let mut parser = SmbLog::default();
+        let negotiate = netbios(&[response(
+            SMB2_NEGOTIATE,
+            0,
+            STATUS_SUCCESS,
+            0,
+            &[0x41, 0x00, 0x01, 0x00, 0x11, 0x03],
+        )]);
+        parse(&mut parser, &negotiate, PacketDirection::ServerToClient);
+        assert_eq!(parser.dialect, 0x0311);
+
+        let req = netbios(&[tree_connect_request(3, "\\\\fs01\\public")]);
+        assert!(check(&req));
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].command, "TREE_CONNECT");
+        assert_eq!(infos[0].share, "\\\\fs01\\public");
+        let resp = netbios(&[response(
+            SMB2_TREE_CONNECT,
+            3,
+            STATUS_SUCCESS,
+            TREE_ID,
+            &[0u8; 16],
+        )]);
+        assert!(!check(&resp));
+        let infos = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+
+        let req = netbios(&[create_request(4, "docs\\report.txt")]);
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].share, "\\\\fs01\\public");
+        assert_eq!(infos[0].path, "docs\\report.txt");
+        assert_eq!(infos[0].session_id(), Some(4));
+        let resp = netbios(&[create_response(4, STATUS_SUCCESS)]);
+        parse(&mut parser, &resp, PacketDirection::ServerToClient);
+
+        // file names of later requests come from the FileId
+        let req = netbios(&[read_request(5, &FILE_ID)]);
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].command, "READ");
+        assert_eq!(infos[0].share, "\\\\fs01\\public");
+        assert_eq!(infos[0].path, "docs\\report.txt");
+        assert_eq!(infos[0].dialect, 0x0311);
+
+        let req = netbios(&[close_request(6, &FILE_ID)]);
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].path, "docs\\report.txt");
+        let req = netbios(&[read_request(7, &FILE_ID)]);
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].path, "");
+    }
+
+    #[test]
+    fn truncated_negotiate_response() {
+        for body in [
+            &[0x41, 0x00, 0x01, 0x00][..],
+            &[0x41, 0x00, 0x01, 0x00, 0x11],
+        ] {
+            let mut parser = SmbLog::default();
+            let negotiate = netbios(&[response(SMB2_NEGOTIATE, 0, STATUS_SUCCESS, 0, body)]);
+            let infos = parse(&mut parser, &negotiate, PacketDirection::ServerToClient);
+            assert_eq!(parser.dialect, 0);
+            assert!(infos.iter().all(|i| i.dialect == 0));
+        }
+    }
+
+    #[test]
+    fn invalid_next_command() {
+        let mut parser = SmbLog::default();
+        let mut req = request(SMB2_SESSION_SETUP, 1, 0, &[0u8; 24]);
+        req[20..24].copy_from_slice(&8u32.to_le_bytes());
+        let req = netbios(&[req]);
+        assert!(parser.parse(&req).is_err());
+    }
+
+    #[test]
+    fn compounded_requests() {
+        let mut parser = SmbLog::default();
+        parser.trees.put(TREE_ID, "\\\\fs01\\public".to_string());
+        let mut read = read_request(11, &[0xff; 16]);
+        let mut close = close_request(12, &[0xff; 16]);
+        read[16..20].copy_from_slice(&SMB2_FLAGS_RELATED_OPERATIONS.to_le_bytes());
+        close[16..20].copy_from_slice(&SMB2_FLAGS_RELATED_OPERATIONS.to_le_bytes());
+        let req = netbios(&[create_request(10, "a.txt"), read, close]);
+        let infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos.len(), 3);
+        assert_eq!(
+            infos
+                .iter()
+                .map(|i| (i.message_id, i.command.as_str(), i.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (10, "CREATE", "a.txt"),
+                (11, "READ", "a.txt"),
+                (12, "CLOSE", "a.txt")
+            ]
+        );
+
+        let resp = netbios(&[
+            create_response(10, STATUS_OBJECT_NAME_NOT_FOUND),
+            response(
+                SMB2_READ,
+                11,
+                STATUS_OBJECT_NAME_NOT_FOUND,
+                TREE_ID,
+                &[0; 8],
+            ),
+            response(
+                SMB2_CLOSE,
+                12,
+                STATUS_OBJECT_NAME_NOT_FOUND,
+                TREE_ID,
+                &[0; 8],
+            ),
+        ]);
+        let infos = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[0].path, "a.txt");
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].exception, "STATUS_OBJECT_NAME_NOT_FOUND");
+        assert_eq!(infos[0].nt_status, Some(STATUS_OBJECT_NAME_NOT_FOUND));
+        assert!(parser.files.is_empty());
+
+        let perf_stats = parser.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 3);
+        assert_eq!(perf_stats.response_count, 3);
+        assert_eq!(perf_stats.err_client_count, 3);
+    }
+
+    #[test]
+    fn session_setup_and_status() {
+        let mut parser = SmbLog::default();
+        let blob = super::super::ntlm::tests::authenticate_message("CORP", "bob", "WS02");
+        let mut body = vec![0u8; 24];
+        body[..2].copy_from_slice(&25u16.to_le_bytes());
+        body[12..14].copy_from_slice(&((SMB2_HEADER_LEN + 24) as u16).to_le_bytes());
+        body[14..16].copy_from_slice(&(blob.len() as u16).to_le_bytes());
+        body.extend_from_slice(&blob);
+        let req = netbios(&[request(SMB2_SESSION_SETUP, 2, 0, &body)]);
+        let mut infos = parse(&mut parser, &req, PacketDirection::ClientToServer);
+        assert_eq!(infos[0].attributes[0].val, "CORP\\bob");
+
+        let resp = netbios(&[response(
+            SMB2_SESSION_SETUP,
+            2,
+            STATUS_LOGON_FAILURE,
+            0,
+            &[0u8; 8],
+        )]);
+        let mut resp = parse(&mut parser, &resp, PacketDirection::ServerToClient);
+        infos[0].merge(&mut resp[0]);
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].exception, "STATUS_LOGON_FAILURE");
+
+        let mut info = SmbInfo::default();
+        info.set_status(STATUS_MORE_PROCESSING_REQUIRED);
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+        info.set_status(STATUS_INSUFFICIENT_RESOURCES);
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+        info.set_status(0xc000_9999);
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+        assert_eq!(info.exception, "0xc0009999");
+
+        // interim response is skipped
+        let pending = header(
+            SMB2_CHANGE_NOTIFY,
+            9,
+            STATUS_PENDING,
+            SMB2_FLAGS_SERVER_TO_REDIR | SMB2_FLAGS_ASYNC_COMMAND,
+            0,
+        );
+        let resp = netbios(&[[&pending[..], &[0u8; 9]].concat()]);
+        assert!(parse(&mut parser, &resp, PacketDirection::ServerToClient).is_empty());
+    }
+
+    #[test]
+    fn invalid_message() {
+        assert!(!check(
+            b"\x00\x00\x00\x10\xffSMBr\x00\x00\x00\x00\x18\x53\xc8\x00\x00"
+        ));
+        // continuation of a large WRITE
+        assert!(SmbLog::default().parse(&[0x5a; 1024]).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// SOAP envelopes carried by HTTP. The body is usually truncated, so tags are scanned one by one
+// instead of parsing the whole document.
+// Reference: https://www.w3.org/TR/2000/NOTE-SOAP-20000508/ and https://www.w3.org/TR/soap12-part1/
+
+use std::str;
+
+// stop scanning if the operation or fault reason is not found in the first tags
+const MAX_SCAN_TAGS: usize = 64;
+
+const FAULT: &[u8] = b"Fault";
+
+struct Tag<'a> {
+    // local name without namespace prefix
+    name: &'a [u8],
+    is_end: bool,
+    // text between this tag and the next one
+    text: &'a [u8],
+}
+
+struct Tags<'a> {
+    xml: &'a [u8],
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.xml.iter().position(|b| *b == b'<')?;
+            let xml = &self.xml[start + 1..];
+            // truncated tag
+            let close = xml.iter().position(|b| *b == b'>')?;
+            let (tag, rest) = (&xml[..close], &xml[close + 1..]);
+            let text_end = rest.iter().position(|b| *b == b'<').unwrap_or(rest.len());
+            self.xml = &rest[text_end..];
+            // xml declarations, comments and processing instructions
+            if tag.starts_with(b"?") || tag.starts_with(b"!") {
+                continue;
+            }
+            let (is_end, tag) = match tag.strip_prefix(b"/") {
+                Some(tag) => (true, tag),
+                None => (false, tag),
+            };
+            let name_end = tag
+                .iter()
+                .position(|b| b.is_ascii_whitespace() || *b == b'/')
+                .unwrap_or(tag.len());
+            let name = &tag[..name_end];
+            let name = match name.iter().rposition(|b| *b == b':') {
+                Some(i) => &name[i + 1..],
+                None => name,
+            };
+            return Some(Tag {
+                name,
+                is_end,
+                text: &rest[..text_end],
+            });
+        }
+    }
+}
+
+fn to_string(text: &[u8]) -> String {
+    String::from_utf8_lossy(text).trim().to_owned()
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SoapBody {
+    // local name of the first element in soap:Body
+    pub operation: String,
+    // faultstring in SOAP 1.1 or Reason/Text in SOAP 1.2, falls back to the fault code
+    pub fault: Option<String>,
+}
+
+impl SoapBody {
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let start = body.iter().position(|b| !b.is_ascii_whitespace())?;
+        if body[start] != b'<' {
+            return None;
+        }
+        let mut tags = Tags {
+            xml: &body[start..],
+        }
+        .filter(|t| !t.is_end)
+        .take(MAX_SCAN_TAGS);
+        if tags.next()?.name != b"Envelope" {
+            return None;
+        }
+        tags.find(|t| t.name == b"Body")?;
+        let operation = tags.next()?.name;
+        if operation.is_empty() {
+            return None;
+        }
+        let mut soap = SoapBody {
+            operation: to_string(operation),
+            fault: None,
+        };
+        if operation == FAULT {
+            let (mut code, mut reason) = (None, None);
+            for t in tags {
+                match t.name {
+                    b"faultstring" | b"Text" => {
+                        reason = Some(t.text);
+                        break;
+                    }
+                    b"faultcode" | b"Value" if code.is_none() => code = Some(t.text),
+                    _ => (),
+                }
+            }
+            soap.fault = Some(
+                reason
+                    .or(code)
+                    .map(to_string)
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or_else(|| to_string(FAULT)),
+            );
+        }
+        Some(soap)
+    }
+}
+
+// operation in SOAPAction header, e.g. `"http://tempuri.org/IUserService/GetUser"` or `urn:GetUser`
+pub fn action_operation(action: &[u8]) -> Option<String> {
+    let action = str::from_utf8(action).ok()?.trim().trim_matches('"');
+    let operation = action.rsplit(['/', '#', ':']).next()?;
+    if operation.is_empty() {
+        None
+    } else {
+        Some(operation.to_owned())
+    }
+}
+
+// SOAP 1.2 puts the action in content type, e.g. `application/soap+xml; action="urn:GetUser"`
+pub fn content_type_operation(content_type: &[u8]) -> Option<String> {
+    str::from_utf8(content_type)
+        .ok()?
+        .split(';')
+        .skip(1)
+        .find_map(|p| p.trim().strip_prefix("action="))
+        .and_then(|a| action_operation(a.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request() {
+        let body = br#"<?xml version="1.0" encoding="utf-8"?>
+<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+  <soap:Header><auth:Token xmlns:auth="urn:auth">abc</auth:Token></soap:Header>
+  <soap:Body>
+    <m:GetUser xmlns:m="http://tempuri.org/"><m:Id>10086</m:Id></m:GetUser>
+  </soap:Body>
+</soap:Envelope>"#;
+        assert_eq!(
+            SoapBody::parse(body),
+            Some(SoapBody {
+                operation: "GetUser".to_string(),
+                fault: None,
+            })
+        );
+        // truncated in the operation element
+        assert_eq!(
+            SoapBody::parse(&body[..body.len() - 60]).unwrap().operation,
+            "GetUser"
+        );
+        assert_eq!(SoapBody::parse(b"<html><body></body></html>"), None);
+        assert_eq!(SoapBody::parse(br#"{"code": 0}"#), None);
+    }
+
+    #[test]
+    fn parse_fault() {
+        let soap11 = br#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body>
+<s:Fault><faultcode>s:Server</faultcode><faultstring>User not found</faultstring></s:Fault>
+</s:Body></s:Envelope>"#;
+        assert_eq!(
+            SoapBody::parse(soap11).unwrap().fault.as_deref(),
+            Some("User not found")
+        );
+        let soap12 = br#"<env:Envelope xmlns:env="http://www.w3.org/2003/05/soap-envelope">
+<env:Body><env:Fault>
+<env:Code><env:Value>env:Sender</env:Value></env:Code>
+<env:Reason><env:Text xml:lang="en">Invalid id</env:Text></env:Reason>
+</env:Fault></env:Body></env:Envelope>"#;
+        assert_eq!(
+            SoapBody::parse(soap12).unwrap().fault.as_deref(),
+            Some("Invalid id")
+        );
+        // reason is truncated
+        assert_eq!(
+            SoapBody::parse(&soap12[..160]).unwrap().fault.as_deref(),
+            Some("env:Sender")
+        );
+    }
+
+    #[test]
+    fn action() {
+        assert_eq!(
+            action_operation(br#""http://tempuri.org/IUserService/GetUser""#).as_deref(),
+            Some("GetUser")
+        );
+        assert_eq!(action_operation(b"urn:GetUser").as_deref(), Some("GetUser"));
+        assert_eq!(action_operation(br#""""#), None);
+        assert_eq!(
+            content_type_operation(
+                br#"application/soap+xml; charset=utf-8; action="urn:example#GetUser""#
+            )
+            .as_deref(),
+            Some("GetUser")
+        );
+        assert_eq!(
+            content_type_operation(b"application/soap+xml; charset=utf-8"),
+            None
+        );
+    }
+}
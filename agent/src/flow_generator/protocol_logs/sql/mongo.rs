@@ -15,8 +15,9 @@
  */
 
 use std::ffi::CStr;
+use std::fmt::Write;
 
-use bson::{self, Document};
+use bson::{self, Bson, Document};
 use serde::Serialize;
 
 use super::super::{AppProtoHead, LogMessageType};
@@ -35,7 +36,7 @@ use crate::{
     },
     flow_generator::{
         protocol_logs::{
-            pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
+            pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
             value_is_default, L7ResponseStatus,
         },
         Error, Result,
@@ -65,6 +66,13 @@ pub struct MongoDBInfo {
     pub op_code_name: String,
     #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
     pub request: String,
+    // command name, database and obfuscated query filter of OP_MSG
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub command: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    pub database: String,
+    #[serde(skip)]
+    pub filter: String,
     #[serde(skip)]
     pub response: String,
     #[serde(rename = "response_code", skip_serializing_if = "value_is_default")]
@@ -130,6 +138,9 @@ impl MongoDBInfo {
                 std::mem::swap(&mut self.op_code_name, &mut other.op_code_name);
                 self.op_code = other.op_code;
                 std::mem::swap(&mut self.request, &mut other.request);
+                std::mem::swap(&mut self.command, &mut other.command);
+                std::mem::swap(&mut self.database, &mut other.database);
+                std::mem::swap(&mut self.filter, &mut other.filter);
                 self.request_id = other.request_id;
                 self.captured_request_byte = other.captured_request_byte;
             }
@@ -149,7 +160,16 @@ impl MongoDBInfo {
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::MongoDB) {
             self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.request)
-                || t.request_type.is_on_blacklist(&self.op_code_name);
+                || t.request_type.is_on_blacklist(self.request_type())
+                || t.request_domain.is_on_blacklist(&self.database);
+        }
+    }
+
+    fn request_type(&self) -> &str {
+        if self.command.is_empty() {
+            &self.op_code_name
+        } else {
+            &self.command
         }
     }
 }
@@ -161,12 +181,25 @@ impl From<MongoDBInfo> for L7ProtocolSendLog {
         } else {
             EbpfFlags::NONE.bits()
         };
+        let attributes = if f.filter.is_empty() {
+            None
+        } else {
+            Some(vec![KeyVal {
+                key: "filter".to_string(),
+                val: f.filter,
+            }])
+        };
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
             captured_response_byte: f.captured_response_byte,
             req_len: std::option::Option::<u32>::from(f.req_len),
             req: L7Request {
-                req_type: f.op_code_name,
+                req_type: if f.command.is_empty() {
+                    f.op_code_name
+                } else {
+                    f.command
+                },
+                domain: f.database,
                 resource: f.request,
                 ..Default::default()
             },
@@ -180,6 +213,7 @@ impl From<MongoDBInfo> for L7ProtocolSendLog {
             },
             ext_info: Some(ExtendedInfo {
                 request_id: Option::<u32>::from(f.request_id),
+                attributes,
                 ..Default::default()
             }),
             flags,
@@ -233,14 +267,20 @@ impl L7ProtocolParserInterface for MongoDBLog {
                 }
                 LogMessageType::Response => {
                     self.perf_stats.as_mut().map(|p| p.inc_resp());
-                    if info.response_code > 0 {
-                        self.perf_stats.as_mut().map(|p| p.inc_req_err());
-                    }
                 }
                 _ => {}
             }
-            if info.reply_false {
-                self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+            match info.status {
+                L7ResponseStatus::ClientError => {
+                    self.perf_stats.as_mut().map(|p| p.inc_req_err());
+                }
+                L7ResponseStatus::ServerError => {
+                    self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                }
+                _ if info.reply_false => {
+                    self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                }
+                _ => {}
             }
             info.cal_rrt(param).map(|rrt| {
                 info.rrt = rrt;
@@ -288,7 +328,6 @@ const _HEADER_SIZE: usize = 16;
 const _EXCEPTION_OFFSET: usize = 20;
 const _COLLECTION_NAME_OFFSET: usize = 20;
 const _QUERY_DOC_OFFSET: usize = _COLLECTION_NAME_OFFSET + 8; // 8 is sizeof(Number to skip + Number to Reture)
-const _MSG_DOC_SECTION_OFFSET: usize = _HEADER_SIZE + 4; // 4 is sizeof(Message Flags)
 
 impl MongoDBLog {
     // TODO: tracing
@@ -324,31 +363,13 @@ impl MongoDBLog {
 
         // command decode
         match info.op_code {
-            _OP_MSG if payload.len() > _MSG_DOC_SECTION_OFFSET => {
+            _OP_MSG if payload.len() > _HEADER_SIZE => {
                 // OP_MSG
                 let mut msg_body = MongoOpMsg::default();
-                // TODO: Message Flags
-                msg_body.decode(&payload[_MSG_DOC_SECTION_OFFSET..])?;
+                msg_body.decode(&payload[_HEADER_SIZE..])?;
                 match info.msg_type {
-                    LogMessageType::Response => {
-                        // The data structure of doc is Bson, which is a normal response when there is no errmsg in it
-                        if msg_body.sections.doc.get_str("errmsg").is_err() {
-                            info.response = msg_body.sections.doc.to_string();
-                        } else {
-                            info.exception =
-                                msg_body.sections.doc.get_str("errmsg").unwrap().to_string();
-                            // TODO: Distinguish error types
-                            info.status = L7ResponseStatus::ClientError;
-                        }
-                        if info.exception.len() == 0 {
-                            info.exception =
-                                msg_body.sections.c_string.unwrap_or(_UNKNOWN.to_string());
-                        }
-                        info.response_code = msg_body.sections.doc.get_i32("code").unwrap_or(0);
-                    }
-                    _ => {
-                        info.request = msg_body.sections.doc.to_string();
-                    }
+                    LogMessageType::Response => msg_body.fill_response(info),
+                    _ => msg_body.fill_request(info),
                 }
             }
             _OP_REPLY if payload.len() > _HEADER_SIZE => {
@@ -479,95 +500,230 @@ pub struct MongoOpCompressed {
     //char: String,
 }
 
-// TODO: support op msg
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct MongoOpMsg {
     flag: u32,
-    sections: Sections,
+    // kind 0: the command document
+    body: Document,
+    // kind 1: document sequences, e.g. `documents` of insert
+    sequences: Vec<(String, Vec<Document>)>,
     checksum: Option<u32>,
 }
 
 impl MongoOpMsg {
-    const _KIND_OFFSET: usize = 0;
-    const _KIND_LEN: usize = 1;
-    const _DOC_LENGTH_OFFSET: usize = Self::_KIND_OFFSET + Self::_KIND_LEN;
-    const _DOC_LENGTH_LEN: usize = 4;
+    const _FLAG_LEN: usize = 4;
+    const _CHECKSUM_LEN: usize = 4;
+    const _CHECKSUM_PRESENT: u32 = 1;
+    const _SIZE_LEN: usize = 4;
+
+    const _KIND_BODY: u8 = 0;
+    const _KIND_DOCUMENT_SEQUENCE: u8 = 1;
+
+    // commands whose first value is the collection name
+    const COLLECTION_COMMANDS: [&'static str; 18] = [
+        "aggregate",
+        "collMod",
+        "count",
+        "create",
+        "createIndexes",
+        "delete",
+        "distinct",
+        "drop",
+        "dropIndexes",
+        "find",
+        "findAndModify",
+        "findandmodify",
+        "insert",
+        "listIndexes",
+        "mapReduce",
+        "mapreduce",
+        "update",
+        "validate",
+    ];
+
+    // error codes caused by the server rather than the request, see
+    // https://www.mongodb.com/docs/manual/reference/error-codes/
+    const SERVER_ERROR_CODES: [i32; 16] = [
+        1,     // InternalError
+        6,     // HostUnreachable
+        7,     // HostNotFound
+        50,    // MaxTimeMSExpired
+        64,    // WriteConcernFailed
+        89,    // NetworkTimeout
+        91,    // ShutdownInProgress
+        133,   // FailedToSatisfyReadPreference
+        189,   // PrimarySteppedDown
+        262,   // ExceededTimeLimit
+        9001,  // SocketException
+        10107, // NotWritablePrimary
+        11600, // InterruptedAtShutdown
+        11602, // InterruptedDueToReplStateChange
+        13435, // NotPrimaryNoSecondaryOk
+        13436, // NotPrimaryOrSecondary
+    ];
 
     fn decode(&mut self, payload: &[u8]) -> Result<bool> {
-        if payload.len() < Self::_DOC_LENGTH_OFFSET + Self::_DOC_LENGTH_LEN {
+        if payload.len() < Self::_FLAG_LEN + 1 + Self::_SIZE_LEN {
             return Ok(false);
         }
-        let mut sections = Sections::default();
-        //sections.kind = payload[4];
-        let section_len = bytes::read_u32_le(
-            &payload[Self::_DOC_LENGTH_OFFSET..Self::_DOC_LENGTH_OFFSET + Self::_DOC_LENGTH_LEN],
-        );
-        if payload.len() < Self::_DOC_LENGTH_LEN + section_len as usize {
-            return Ok(false);
+        self.flag = bytes::read_u32_le(payload);
+        let mut end = payload.len();
+        if self.flag & Self::_CHECKSUM_PRESENT != 0 {
+            end -= Self::_CHECKSUM_LEN;
+            self.checksum = Some(bytes::read_u32_le(&payload[end..]));
+        }
+
+        let mut offset = Self::_FLAG_LEN;
+        while offset + 1 + Self::_SIZE_LEN <= end {
+            let kind = payload[offset];
+            offset += 1;
+            let size = bytes::read_u32_le(&payload[offset..]) as usize;
+            // stop at truncated sections
+            if size < Self::_SIZE_LEN || offset + size > end {
+                break;
+            }
+            let section = &payload[offset..offset + size];
+            offset += size;
+            match kind {
+                Self::_KIND_BODY => {
+                    self.body = Document::from_reader(section).unwrap_or_default();
+                }
+                Self::_KIND_DOCUMENT_SEQUENCE => {
+                    let section = &section[Self::_SIZE_LEN..];
+                    let identifier = CStr::from_bytes_until_nul(section)
+                        .map_err(|_| Error::L7ProtocolUnknown)?
+                        .to_string_lossy()
+                        .into_owned();
+                    let mut docs = vec![];
+                    let mut doc_offset = identifier.len() + 1;
+                    while doc_offset + Self::_SIZE_LEN <= section.len() {
+                        let doc_size = bytes::read_u32_le(&section[doc_offset..]) as usize;
+                        if doc_size < Self::_SIZE_LEN || doc_offset + doc_size > section.len() {
+                            break;
+                        }
+                        match Document::from_reader(&section[doc_offset..doc_offset + doc_size]) {
+                            Ok(doc) => docs.push(doc),
+                            Err(_) => break,
+                        }
+                        doc_offset += doc_size;
+                    }
+                    self.sequences.push((identifier, docs));
+                }
+                // kind 2 is used for internal purposes
+                _ => break,
+            }
         }
-        let _ = sections.decode(&payload);
-        self.sections = sections;
-        // todo: decode checksum
         Ok(true)
     }
-}
 
-#[derive(Clone, Debug, Default, Serialize)]
-struct Sections {
-    kind: u8,
-    kind_name: String,
-    // kind: 0 mean doc
-    doc: Document,
-    // kind: 1 mean body
-    size: Option<i32>,
-    c_string: Option<String>,
-}
+    fn fill_request(&self, info: &mut MongoDBInfo) {
+        let Some(command) = self.body.keys().next() else {
+            return;
+        };
+        info.command = command.clone();
+        info.database = self.body.get_str("$db").unwrap_or_default().to_string();
+        info.request = match command.as_str() {
+            "getMore" => self.body.get_str("collection"),
+            c if Self::COLLECTION_COMMANDS.contains(&c) => self.body.get_str(c),
+            _ => Ok(""),
+        }
+        .unwrap_or_default()
+        .to_string();
+        if let Some(filter) = self.filter(command) {
+            Self::obfuscate(filter, &mut info.filter);
+        }
+    }
 
-impl Sections {
-    pub fn decode(&mut self, payload: &[u8]) -> Result<bool> {
-        if payload.len() < 6 {
-            return Ok(false);
+    fn filter(&self, command: &str) -> Option<&Document> {
+        // the first statement of a batch, which is either in the body or in a document sequence
+        let first_statement = |identifier: &str| {
+            self.body
+                .get_array(identifier)
+                .ok()
+                .and_then(|a| a.first())
+                .and_then(|b| b.as_document())
+                .or_else(|| {
+                    self.sequences
+                        .iter()
+                        .find(|(i, _)| i == identifier)
+                        .and_then(|(_, docs)| docs.first())
+                })
+        };
+        match command {
+            "find" => self.body.get_document("filter").ok(),
+            "count" | "distinct" | "findAndModify" | "findandmodify" | "mapReduce"
+            | "mapreduce" => self.body.get_document("query").ok(),
+            "update" => first_statement("updates").and_then(|d| d.get_document("q").ok()),
+            "delete" => first_statement("deletes").and_then(|d| d.get_document("q").ok()),
+            "aggregate" => first_statement("pipeline").and_then(|d| d.get_document("$match").ok()),
+            _ => None,
         }
-        self.kind = payload[0];
-        // todo: decode doc
-        match self.kind {
-            0 => {
-                // Body
-                self.kind_name = "BODY".to_string();
-                let length = bytes::read_u32_le(&payload[1..5]);
-                // TODO: When ChecksumPresent is 1, there will be checksum in the payload
-                if length != payload.len() as u32 - 1 && length != payload.len() as u32 - 5 {
-                    return Ok(false);
+    }
+
+    // keep field names and operators, replace all values with `?`
+    fn obfuscate(doc: &Document, output: &mut String) {
+        output.push('{');
+        for (i, (key, value)) in doc.iter().enumerate() {
+            let _ = write!(output, "{}\"{}\": ", if i == 0 { " " } else { ", " }, key);
+            Self::obfuscate_value(value, output);
+        }
+        output.push_str(if doc.is_empty() { "}" } else { " }" });
+    }
+
+    fn obfuscate_value(value: &Bson, output: &mut String) {
+        match value {
+            Bson::Document(doc) => Self::obfuscate(doc, output),
+            Bson::Array(array) if array.iter().any(|v| v.as_document().is_some()) => {
+                output.push('[');
+                for (i, value) in array.iter().enumerate() {
+                    if i > 0 {
+                        output.push_str(", ");
+                    }
+                    Self::obfuscate_value(value, output);
                 }
-                self.doc = Document::from_reader(&payload[1..]).unwrap_or(Document::default());
+                output.push(']');
             }
-            1 => {
-                // Doc
-                self.kind_name = "DOC".to_string();
-                self.size =
-                    std::option::Option::<i32>::from(bytes::read_u32_le(&payload[1..5]) as i32);
-                self.c_string = Some(
-                    CStr::from_bytes_until_nul(&payload[5..])
-                        .map_err(|_| Error::L7ProtocolUnknown)?
-                        .to_string_lossy()
-                        .into_owned(),
-                );
+            Bson::Array(_) => output.push_str("[?]"),
+            _ => output.push('?'),
+        }
+    }
 
-                self.doc = Document::from_reader(&payload[1..]).unwrap_or(Document::default());
-            }
-            2 => {
-                // Internal
-                self.kind_name = "INTERNAL".to_string();
-                // This section is used for internal purposes.
-                return Ok(false);
-            }
-            _ => {
-                // Unknown
-                self.kind_name = _UNKNOWN.to_string();
-                return Ok(false);
-            }
+    fn fill_response(&self, info: &mut MongoDBInfo) {
+        let doc = &self.body;
+        let error = if !Self::is_ok(doc) {
+            Some(doc)
+        } else if let Ok(write_errors) = doc.get_array("writeErrors") {
+            write_errors.first().and_then(|e| e.as_document())
+        } else {
+            doc.get_document("writeConcernError").ok()
+        };
+        let Some(error) = error else {
+            info.status = L7ResponseStatus::Ok;
+            return;
+        };
+        info.exception = error.get_str("errmsg").unwrap_or_default().to_string();
+        info.response_code = match error.get("code") {
+            Some(Bson::Int32(c)) => *c,
+            Some(Bson::Int64(c)) => *c as i32,
+            Some(Bson::Double(c)) => *c as i32,
+            _ => 0,
+        };
+        info.status = if Self::SERVER_ERROR_CODES.contains(&info.response_code) {
+            L7ResponseStatus::ServerError
+        } else {
+            L7ResponseStatus::ClientError
+        };
+    }
+
+    fn is_ok(doc: &Document) -> bool {
+        match doc.get("ok") {
+            Some(Bson::Double(ok)) => *ok != 0.0,
+            Some(Bson::Int32(ok)) => *ok != 0,
+            Some(Bson::Int64(ok)) => *ok != 0,
+            Some(Bson::Boolean(ok)) => *ok,
+            // truncated or unknown reply
+            _ => true,
         }
-        Ok(true)
     }
 }
 
@@ -654,6 +810,8 @@ mod tests {
     use std::rc::Rc;
     use std::{cell::RefCell, fs};
 
+    use bson::doc;
+
     use super::*;
 
     use crate::{
@@ -717,6 +875,145 @@ mod tests {
         output
     }
 
+    fn op_msg(
+        request_id: u32,
+        response_to: u32,
+        sections: &[(u8, &str, Vec<Document>)],
+    ) -> Vec<u8> {
+        let mut body = vec![0u8; 4]; // flags
+        for (kind, identifier, docs) in sections {
+            body.push(*kind);
+            let start = body.len();
+            if *kind == 1 {
+                body.extend_from_slice(&[0; 4]);
+                body.extend_from_slice(identifier.as_bytes());
+                body.push(0);
+            }
+            for doc in docs {
+                doc.to_writer(&mut body).unwrap();
+            }
+            if *kind == 1 {
+                let size = (body.len() - start) as u32;
+                body[start..start + 4].copy_from_slice(&size.to_le_bytes());
+            }
+        }
+        let mut payload = vec![];
+        payload.extend_from_slice(&(body.len() as u32 + 16).to_le_bytes());
+        payload.extend_from_slice(&request_id.to_le_bytes());
+        payload.extend_from_slice(&response_to.to_le_bytes());
+        payload.extend_from_slice(&_OP_MSG.to_le_bytes());
+        payload.extend_from_slice(&body);
+        payload
+    }
+
+    fn parse_op_msg(payload: &[u8]) -> MongoDBInfo {
+        let mut info = MongoDBInfo::default();
+        MongoDBLog::default()
+            .parse(
+                payload,
+                IpProtocol::TCP,
+                PacketDirection::ClientToServer,
+                &mut info,
+            )
+            .unwrap();
+        info
+    }
+
+    #[test]
+    fn check_op_msg_request() {
+        let find = doc! {
+            "find": "users",
+            "filter": { "name": "alice", "age": { "$gt": 18 }, "tags": { "$in": ["a", "b"] } },
+            "$db": "shop",
+        };
+        let info = parse_op_msg(&op_msg(1, 0, &[(0, "", vec![find])]));
+        assert_eq!(info.command, "find");
+        assert_eq!(info.database, "shop");
+        assert_eq!(info.request, "users");
+        assert_eq!(
+            info.filter,
+            "{ \"name\": ?, \"age\": { \"$gt\": ? }, \"tags\": { \"$in\": [?] } }"
+        );
+
+        // statements of a batch in a document sequence
+        let update = doc! { "update": "orders", "ordered": true, "$db": "shop" };
+        let statements = vec![doc! {
+            "q": { "$or": [{ "status": "new" }, { "id": 1 }] },
+            "u": { "$set": { "status": "paid" } },
+        }];
+        let info = parse_op_msg(&op_msg(
+            2,
+            0,
+            &[(0, "", vec![update]), (1, "updates", statements)],
+        ));
+        assert_eq!(info.command, "update");
+        assert_eq!(info.request, "orders");
+        assert_eq!(
+            info.filter,
+            "{ \"$or\": [{ \"status\": ? }, { \"id\": ? }] }"
+        );
+
+        let aggregate = doc! {
+            "aggregate": "orders",
+            "pipeline": [{ "$match": { "status": "paid" } }, { "$group": { "_id": "$user" } }],
+            "$db": "shop",
+        };
+        let info = parse_op_msg(&op_msg(3, 0, &[(0, "", vec![aggregate])]));
+        assert_eq!(info.filter, "{ \"status\": ? }");
+
+        let ping = doc! { "ping": 1, "$db": "admin" };
+        let info = parse_op_msg(&op_msg(4, 0, &[(0, "", vec![ping])]));
+        assert_eq!(info.command, "ping");
+        assert_eq!(info.request, "");
+        assert_eq!(info.filter, "");
+    }
+
+    #[test]
+    fn check_op_msg_response() {
+        let cases = vec![
+            (doc! { "n": 1, "ok": 1.0 }, 0, "", L7ResponseStatus::Ok),
+            (
+                doc! { "ok": 0.0, "errmsg": "ns not found", "code": 26, "codeName": "NamespaceNotFound" },
+                26,
+                "ns not found",
+                L7ResponseStatus::ClientError,
+            ),
+            (
+                doc! { "ok": 0.0, "errmsg": "not primary", "code": 10107 },
+                10107,
+                "not primary",
+                L7ResponseStatus::ServerError,
+            ),
+            (
+                doc! {
+                    "n": 0,
+                    "writeErrors": [{ "index": 0, "code": 11000, "errmsg": "E11000 duplicate key error" }],
+                    "ok": 1.0,
+                },
+                11000,
+                "E11000 duplicate key error",
+                L7ResponseStatus::ClientError,
+            ),
+            (
+                doc! {
+                    "n": 1,
+                    "writeConcernError": { "code": 64, "errmsg": "waiting for replication timed out" },
+                    "ok": 1.0,
+                },
+                64,
+                "waiting for replication timed out",
+                L7ResponseStatus::ServerError,
+            ),
+        ];
+        for (reply, code, exception, status) in cases {
+            let info = parse_op_msg(&op_msg(11, 1, &[(0, "", vec![reply])]));
+            assert_eq!(info.msg_type, LogMessageType::Response);
+            assert_eq!(info.response_code, code);
+            assert_eq!(info.exception, exception);
+            assert_eq!(info.status, status);
+        }
+    }
+
     #[test]
     fn check() {
         let files = vec![("mongo.pcap", "mongo.result")];
@@ -16,14 +16,15 @@
 
 mod comment_parser;
 
-use std::str;
+use std::{num::NonZeroUsize, str};
 
 use log::{debug, trace};
+use lru::LruCache;
 use serde::Serialize;
 
 use super::super::{consts::*, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
 use super::sql_check::{is_mysql, is_valid_sql, trim_head_comment_and_get_first_word};
-use super::sql_obfuscate::attempt_obfuscation;
+use super::sql_obfuscate::obfuscate_statement;
 use super::ObfuscateCache;
 
 use crate::flow_generator::protocol_logs::set_captured_byte;
@@ -39,7 +40,7 @@ use crate::{
     flow_generator::{
         error::{Error, Result},
         protocol_logs::pb_adapter::{
-            ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response, TraceInfo,
+            ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, TraceInfo,
         },
     },
     utils::bytes,
@@ -69,6 +70,9 @@ pub struct MysqlInfo {
     pub command: u8,
     #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
     pub context: String,
+    // raw statement if both raw and obfuscated forms are stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_context: Option<String>,
     // response
     pub response_code: u8,
     #[serde(skip)]
@@ -144,6 +148,7 @@ impl MysqlInfo {
             LogMessageType::Request => {
                 self.command = other.command;
                 std::mem::swap(&mut self.context, &mut other.context);
+                std::mem::swap(&mut self.raw_context, &mut other.raw_context);
                 self.captured_request_byte = other.captured_request_byte;
             }
             LogMessageType::Response => {
@@ -219,21 +224,11 @@ impl MysqlInfo {
         if let Some(c) = config {
             self.extract_trace_and_span_id(&c.l7_log_dynamic, str::from_utf8(payload)?);
         }
-        let context = match attempt_obfuscation(obfuscate_cache, payload) {
-            Some(mut m) => {
-                let valid_len = match str::from_utf8(&m) {
-                    Ok(_) => m.len(),
-                    Err(e) => e.valid_up_to(),
-                };
-                m.truncate(valid_len);
-                unsafe {
-                    // SAFTY: str in m is checked to be valid utf8 up to `valid_len`
-                    String::from_utf8_unchecked(m)
-                }
-            }
-            _ => String::from_utf8_lossy(payload).to_string(),
-        };
-        self.context = context;
+        (self.context, self.raw_context) = obfuscate_statement(
+            obfuscate_cache,
+            config.map(|c| c.sql_obfuscation_mode).unwrap_or_default(),
+            payload,
+        );
         Ok(())
     }
 
@@ -329,6 +324,12 @@ impl From<MysqlInfo> for L7ProtocolSendLog {
             },
             ext_info: Some(ExtendedInfo {
                 request_id: f.statement_id.into(),
+                attributes: f.raw_context.map(|sql| {
+                    vec![KeyVal {
+                        key: "raw_sql".to_string(),
+                        val: sql,
+                    }]
+                }),
                 ..Default::default()
             }),
             trace_info: if f.trace_id.is_some() || f.span_id.is_some() {
@@ -357,6 +358,11 @@ pub struct MysqlLog {
     parameter_counter: u32,
     has_request: bool,
 
+    // SQL of the COM_STMT_PREPARE request waiting for the statement id in response
+    prepared_sql: Option<String>,
+    // statement id to SQL, created on the first COM_STMT_PREPARE
+    statements: Option<LruCache<u32, String>>,
+
     last_is_on_blacklist: bool,
 }
 
@@ -477,6 +483,8 @@ enum SqlState {
 }
 
 impl MysqlLog {
+    const DEFAULT_STATEMENT_CACHE_SIZE: usize = 256;
+
     fn reset_parameter_counter(&mut self) {
         self.parameter_counter = 0;
     }
@@ -613,7 +621,16 @@ impl MysqlLog {
         info.command = payload[COMMAND_OFFSET];
         let mut msg_type = LogMessageType::Request;
         match info.command {
-            COM_QUIT | COM_STMT_CLOSE => msg_type = LogMessageType::Session,
+            COM_QUIT => msg_type = LogMessageType::Session,
+            COM_STMT_CLOSE => {
+                msg_type = LogMessageType::Session;
+                if payload.len() >= STATEMENT_ID_OFFSET + STATEMENT_ID_LEN {
+                    let statement_id = read_u32_le(&payload[STATEMENT_ID_OFFSET..]);
+                    if let Some(statements) = self.statements.as_mut() {
+                        statements.pop(&statement_id);
+                    }
+                }
+            }
             COM_FIELD_LIST | COM_STMT_FETCH => (),
             COM_INIT_DB | COM_QUERY => {
                 info.request_string(
@@ -636,11 +653,22 @@ impl MysqlLog {
                         self.set_parameter_counter(info.context.as_bytes());
                     }
                 }
+                self.prepared_sql = Some(info.context.clone());
             }
             COM_STMT_EXECUTE => {
                 info.statement_id(&payload[STATEMENT_ID_OFFSET..]);
-                if payload.len() > EXECUTE_STATEMENT_PARAMS_OFFSET {
-                    self.get_parameters(&payload[EXECUTE_STATEMENT_PARAMS_OFFSET..], info);
+                let statement_id = info.statement_id;
+                let sql = self
+                    .statements
+                    .as_mut()
+                    .and_then(|s| s.get(&statement_id).cloned());
+                match sql {
+                    // log the SQL of the prepared statement instead of parameters
+                    Some(sql) => info.context = sql,
+                    None if payload.len() > EXECUTE_STATEMENT_PARAMS_OFFSET => {
+                        self.get_parameters(&payload[EXECUTE_STATEMENT_PARAMS_OFFSET..], info);
+                    }
+                    _ => (),
                 }
                 self.reset_parameter_counter();
             }
@@ -804,6 +832,22 @@ impl MysqlLog {
         }
     }
 
+    fn save_statement(&mut self, config: Option<&LogParserConfig>, statement_id: u32, sql: String) {
+        let cache_size = config
+            .map(|c| c.mysql_statement_cache_size)
+            .unwrap_or(Self::DEFAULT_STATEMENT_CACHE_SIZE);
+        let Some(cache_size) = NonZeroUsize::new(cache_size) else {
+            return;
+        };
+        let statements = self
+            .statements
+            .get_or_insert_with(|| LruCache::new(cache_size));
+        if statements.cap() != cache_size {
+            statements.resize(cache_size);
+        }
+        statements.put(statement_id, sql);
+    }
+
     // return is_greeting?
     fn parse(
         &mut self,
@@ -839,6 +883,11 @@ impl MysqlLog {
             LogMessageType::Response if self.has_request => {
                 self.response(&payload[offset..], info)?;
                 self.has_request = false;
+                if let Some(sql) = self.prepared_sql.take() {
+                    if info.response_code == MYSQL_RESPONSE_CODE_OK && info.statement_id > 0 {
+                        self.save_statement(config, info.statement_id, sql);
+                    }
+                }
             }
             LogMessageType::Other => {
                 self.greeting(&payload[offset..])?;
@@ -1334,6 +1383,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_statement_cache() {
+        fn packet(number: u8, body: &[u8]) -> Vec<u8> {
+            let mut p = (body.len() as u32).to_le_bytes()[..3].to_vec();
+            p.push(number);
+            p.extend_from_slice(body);
+            p
+        }
+        let sql = "SELECT * FROM t WHERE id = ?";
+        let mut prepare = vec![COM_STMT_PREPARE];
+        prepare.extend_from_slice(sql.as_bytes());
+        let prepare = packet(0, &prepare);
+        // OK, statement_id = 7, 1 column, 1 parameter
+        let prepare_ok = packet(1, &[0, 7, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0]);
+        let execute = packet(
+            0,
+            &[
+                COM_STMT_EXECUTE,
+                7,
+                0,
+                0,
+                0,
+                0,
+                1,
+                0,
+                0,
+                0,
+                0,
+                1,
+                3, // MYSQL_TYPE_LONG
+                0,
+                1,
+                0,
+                0,
+                0,
+            ],
+        );
+        let close = packet(0, &[COM_STMT_CLOSE, 7, 0, 0, 0]);
+
+        let execute_context = |log: &mut MysqlLog, config: &LogParserConfig| {
+            let mut info = MysqlInfo::default();
+            log.parse(
+                Some(config),
+                &execute,
+                IpProtocol::TCP,
+                PacketDirection::ClientToServer,
+                &mut info,
+            )
+            .unwrap();
+            info.context
+        };
+
+        for (cache_size, cached) in [(256, true), (0, false)] {
+            let config = LogParserConfig {
+                mysql_statement_cache_size: cache_size,
+                ..Default::default()
+            };
+            let mut log = MysqlLog::default();
+            for (payload, direction) in [
+                (&prepare, PacketDirection::ClientToServer),
+                (&prepare_ok, PacketDirection::ServerToClient),
+            ] {
+                log.parse(
+                    Some(&config),
+                    payload,
+                    IpProtocol::TCP,
+                    direction,
+                    &mut MysqlInfo::default(),
+                )
+                .unwrap();
+            }
+            assert_eq!(execute_context(&mut log, &config) == sql, cached);
+
+            log.parse(
+                Some(&config),
+                &close,
+                IpProtocol::TCP,
+                PacketDirection::ClientToServer,
+                &mut MysqlInfo::default(),
+            )
+            .unwrap();
+            assert_ne!(execute_context(&mut log, &config), sql);
+        }
+    }
+
     #[test]
     fn test_parse_parameter() {
         fn parse_parameter(field_type: FieldType, payload: Vec<u8>) -> String {
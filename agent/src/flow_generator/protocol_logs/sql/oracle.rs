@@ -17,6 +17,7 @@
 use serde::Serialize;
 
 use super::super::{value_is_default, LogMessageType};
+use super::{sql_obfuscate::obfuscate_statement, ObfuscateCache};
 use crate::config::handler::LogParserConfig;
 use crate::flow_generator::{
     protocol_logs::{
@@ -51,6 +52,9 @@ pub struct OracleInfo {
     // req
     #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
     pub sql: String,
+    // raw statement if both raw and obfuscated forms are stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_sql: Option<String>,
     #[serde(skip)]
     pub req_data_flags: DataFlags,
     #[serde(skip)]
@@ -86,6 +90,7 @@ impl OracleInfo {
     pub fn merge(&mut self, other: &mut Self) {
         self.packet_type = other.packet_type;
         swap_if!(self, sql, is_empty, other);
+        swap_if!(self, raw_sql, is_none, other);
         if other.req_data_flags.bits() > 0 {
             self.req_data_flags = other.req_data_flags;
         }
@@ -155,6 +160,12 @@ impl From<OracleInfo> for L7ProtocolSendLog {
     fn from(f: OracleInfo) -> Self {
         let mut attrs = vec![];
 
+        if let Some(raw_sql) = f.raw_sql {
+            attrs.push(KeyVal {
+                key: "raw_sql".to_string(),
+                val: raw_sql,
+            });
+        }
         if let Some(d) = &f.req_data_id {
             attrs.push(KeyVal {
                 key: "request_data_id".to_string(),
@@ -215,6 +226,7 @@ pub struct OracleLog {
     perf_stats: Option<L7PerfStats>,
     parser: OracleParser,
     last_is_on_blacklist: bool,
+    obfuscate_cache: Option<ObfuscateCache>,
 }
 
 impl L7ProtocolParserInterface for OracleLog {
@@ -248,11 +260,24 @@ impl L7ProtocolParserInterface for OracleLog {
             self.perf_stats = Some(L7PerfStats::default())
         };
 
+        let (sql, raw_sql) = if self.parser.sql.is_empty() {
+            (String::new(), None)
+        } else {
+            obfuscate_statement(
+                &self.obfuscate_cache,
+                param
+                    .parse_config
+                    .map(|c| c.sql_obfuscation_mode)
+                    .unwrap_or_default(),
+                self.parser.sql.as_bytes(),
+            )
+        };
         let mut log_info = OracleInfo {
             msg_type: param.direction.into(),
             is_tls: false,
             packet_type: self.parser.packet_type,
-            sql: self.parser.sql.clone(),
+            sql,
+            raw_sql,
             req_data_flags: self.parser.req_data_flags,
             req_data_id: self.parser.req_data_id.clone(),
             req_call_id: self.parser.req_call_id.clone(),
@@ -310,4 +335,8 @@ impl L7ProtocolParserInterface for OracleLog {
     fn parsable_on_udp(&self) -> bool {
         false
     }
+
+    fn set_obfuscate_cache(&mut self, obfuscate_cache: Option<ObfuscateCache>) {
+        self.obfuscate_cache = obfuscate_cache;
+    }
 }
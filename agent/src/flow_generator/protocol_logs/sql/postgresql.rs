@@ -28,10 +28,10 @@ use crate::{
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
         meta_packet::EbpfFlags,
     },
-    config::handler::LogParserConfig,
+    config::{handler::LogParserConfig, SqlObfuscationMode},
     flow_generator::{
         protocol_logs::{
-            pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
+            pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
             set_captured_byte, L7ResponseStatus,
         },
         AppProtoHead, Error, LogMessageType, Result,
@@ -42,7 +42,7 @@ use super::{
     super::value_is_default,
     postgre_convert::{get_code_desc, get_request_str},
     sql_check::is_postgresql,
-    sql_obfuscate::attempt_obfuscation,
+    sql_obfuscate::obfuscate_statement,
     ObfuscateCache,
 };
 
@@ -76,6 +76,9 @@ pub struct PostgreInfo {
     // request
     #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
     pub context: String,
+    // raw statement if both raw and obfuscated forms are stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_context: Option<String>,
     #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
     pub req_type: char,
 
@@ -127,6 +130,7 @@ impl L7ProtocolInfoInterface for PostgreInfo {
                 LogMessageType::Request => {
                     self.req_type = pg.req_type;
                     std::mem::swap(&mut self.context, &mut pg.context);
+                    std::mem::swap(&mut self.raw_context, &mut pg.raw_context);
                     self.captured_request_byte = pg.captured_request_byte;
                 }
                 LogMessageType::Response => {
@@ -185,6 +189,12 @@ impl From<PostgreInfo> for L7ProtocolSendLog {
                 ..Default::default()
             },
             ext_info: Some(ExtendedInfo {
+                attributes: p.raw_context.map(|sql| {
+                    vec![KeyVal {
+                        key: "raw_sql".to_string(),
+                        val: sql,
+                    }]
+                }),
                 ..Default::default()
             }),
             flags,
@@ -197,6 +207,7 @@ impl From<PostgreInfo> for L7ProtocolSendLog {
 pub struct PostgresqlLog {
     perf_stats: Option<L7PerfStats>,
     obfuscate_cache: Option<ObfuscateCache>,
+    sql_obfuscation_mode: SqlObfuscationMode,
     last_is_on_blacklist: bool,
 }
 
@@ -224,6 +235,9 @@ impl L7ProtocolParserInterface for PostgresqlLog {
         if self.perf_stats.is_none() && param.parse_perf {
             self.perf_stats = Some(L7PerfStats::default())
         };
+        if let Some(config) = param.parse_config {
+            self.sql_obfuscation_mode = config.sql_obfuscation_mode;
+        }
 
         self.parse(payload, &mut info)?;
         set_captured_byte!(info, param);
@@ -336,10 +350,8 @@ impl PostgresqlLog {
             'Q' => {
                 info.req_type = tag;
                 let payload = strip_string_end_with_zero(data)?;
-                info.context = attempt_obfuscation(&self.obfuscate_cache, payload)
-                    .map_or(String::from_utf8_lossy(payload).to_string(), |m| {
-                        String::from_utf8_lossy(&m).to_string()
-                    });
+                (info.context, info.raw_context) =
+                    obfuscate_statement(&self.obfuscate_cache, self.sql_obfuscation_mode, payload);
                 info.ignore = false;
                 Ok(true)
             }
@@ -358,10 +370,11 @@ impl PostgresqlLog {
                     if let Some(idx) = data.iter().position(|x| *x == 0x0) {
                         let payload = &data[..idx];
                         let postgresql = is_postgresql(payload);
-                        info.context = attempt_obfuscation(&self.obfuscate_cache, payload)
-                            .map_or(String::from_utf8_lossy(payload).to_string(), |m| {
-                                String::from_utf8_lossy(&m).to_string()
-                            });
+                        (info.context, info.raw_context) = obfuscate_statement(
+                            &self.obfuscate_cache,
+                            self.sql_obfuscation_mode,
+                            payload,
+                        );
                         if postgresql {
                             return Ok(true);
                         }
@@ -35,7 +35,7 @@ use crate::{
     flow_generator::{
         error::{Error, Result},
         protocol_logs::{
-            pb_adapter::{L7ProtocolSendLog, L7Request, L7Response},
+            pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
             set_captured_byte,
         },
     },
@@ -50,11 +50,17 @@ pub struct RedisInfo {
     is_tls: bool,
 
     #[serde(
-        rename = "request_resource",
+        rename = "command",
         skip_serializing_if = "value_is_default",
         serialize_with = "vec_u8_to_string"
     )]
     pub request: Vec<u8>, // 命令字段包括参数例如："set key value"
+    #[serde(
+        rename = "request_resource",
+        skip_serializing_if = "value_is_default",
+        serialize_with = "vec_u8_to_string"
+    )]
+    pub key: Vec<u8>, // 命令的第一个参数例如：命令为"set key value"，key 为："key"
     #[serde(
         skip_serializing_if = "value_is_default",
         serialize_with = "vec_u8_to_string"
@@ -76,6 +82,17 @@ pub struct RedisInfo {
     #[serde(rename = "response_status")]
     pub resp_status: L7ResponseStatus,
 
+    // MOVED or ASK redirection in cluster mode, for example: "-MOVED 3999 127.0.0.1:6381"
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub redirect_type: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub redirect_slot: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub redirect_node: String,
+    // commands from MULTI to EXEC or DISCARD share the same transaction id in a flow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<u32>,
+
     captured_request_byte: u32,
     captured_response_byte: u32,
 
@@ -110,7 +127,7 @@ impl L7ProtocolInfoInterface for RedisInfo {
     }
 
     fn get_request_resource_length(&self) -> usize {
-        self.request.len()
+        self.resource().len()
     }
 
     fn is_on_blacklist(&self) -> bool {
@@ -130,6 +147,9 @@ impl RedisInfo {
         std::mem::swap(&mut self.status, &mut other.status);
         std::mem::swap(&mut self.error, &mut other.error);
         self.resp_status = other.resp_status;
+        std::mem::swap(&mut self.redirect_type, &mut other.redirect_type);
+        std::mem::swap(&mut self.redirect_slot, &mut other.redirect_slot);
+        std::mem::swap(&mut self.redirect_node, &mut other.redirect_node);
         self.captured_response_byte = other.captured_response_byte;
         if other.is_on_blacklist {
             self.is_on_blacklist = other.is_on_blacklist;
@@ -137,11 +157,32 @@ impl RedisInfo {
         Ok(())
     }
 
+    // the key is used as resource, or the whole command line for commands without key
+    fn resource(&self) -> &[u8] {
+        if self.key.is_empty() {
+            &self.request
+        } else {
+            &self.key
+        }
+    }
+
+    fn normalize_key(&mut self, patterns: &[String]) {
+        if self.key.is_empty() {
+            return;
+        }
+        if let Some(p) = patterns
+            .iter()
+            .find(|p| wildcard_match(p.as_bytes(), &self.key))
+        {
+            self.key = p.as_bytes().to_vec();
+        }
+    }
+
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::Redis) {
             self.is_on_blacklist = t
                 .request_resource
-                .is_on_blacklist(str::from_utf8(&self.request).unwrap_or_default())
+                .is_on_blacklist(str::from_utf8(self.resource()).unwrap_or_default())
                 || t.request_type
                     .is_on_blacklist(str::from_utf8(&self.request_type).unwrap_or_default());
         }
@@ -180,12 +221,37 @@ impl From<RedisInfo> for L7ProtocolSendLog {
         } else {
             EbpfFlags::NONE.bits()
         };
+        let mut attributes = vec![];
+        if !f.key.is_empty() {
+            attributes.push(KeyVal {
+                key: "command".to_string(),
+                val: String::from_utf8_lossy(f.request.as_slice()).to_string(),
+            });
+        }
+        for (key, val) in [
+            ("redirect_type", f.redirect_type),
+            ("redirect_slot", f.redirect_slot),
+            ("redirect_node", f.redirect_node),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        if let Some(id) = f.transaction_id {
+            attributes.push(KeyVal {
+                key: "transaction_id".to_string(),
+                val: id.to_string(),
+            });
+        }
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
             captured_response_byte: f.captured_response_byte,
             req: L7Request {
                 req_type: String::from_utf8_lossy(f.request_type.as_slice()).to_string(),
-                resource: String::from_utf8_lossy(f.request.as_slice()).to_string(),
+                resource: String::from_utf8_lossy(f.resource()).to_string(),
                 ..Default::default()
             },
             resp: L7Response {
@@ -193,6 +259,14 @@ impl From<RedisInfo> for L7ProtocolSendLog {
                 exception: String::from_utf8_lossy(f.error.as_slice()).to_string(),
                 ..Default::default()
             },
+            ext_info: if attributes.is_empty() {
+                None
+            } else {
+                Some(ExtendedInfo {
+                    attributes: Some(attributes),
+                    ..Default::default()
+                })
+            },
             flags,
             ..Default::default()
         };
@@ -200,12 +274,46 @@ impl From<RedisInfo> for L7ProtocolSendLog {
     }
 }
 
+// glob style matching, only `*` is supported
+fn wildcard_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // position of the last `*` in pattern, and where it starts to match in s
+    let mut star = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((sp, si)) => {
+                    p = sp + 1;
+                    i = si + 1;
+                    star = Some((sp, si + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
 #[derive(Default)]
 pub struct RedisLog {
     has_request: bool,
     perf_stats: Option<L7PerfStats>,
     obfuscate: bool,
     last_is_on_blacklist: bool,
+
+    transaction_count: u32,
+    // id of the transaction started by MULTI
+    transaction_id: Option<u32>,
+    // transaction id of the last request, for the response
+    last_transaction_id: Option<u32>,
 }
 
 impl L7ProtocolParserInterface for RedisLog {
@@ -229,8 +337,17 @@ impl L7ProtocolParserInterface for RedisLog {
         self.parse(payload, param.l4_protocol, param.direction, &mut info)?;
         set_captured_byte!(info, param);
         if let Some(config) = param.parse_config {
+            info.normalize_key(&config.redis_key_patterns);
             info.set_is_on_blacklist(config);
         }
+        // pushes are sent by server without request
+        if info.msg_type == LogMessageType::Session {
+            return if param.parse_log {
+                Ok(L7ParseResult::Single(L7ProtocolInfo::RedisInfo(info)))
+            } else {
+                Ok(L7ParseResult::None)
+            };
+        }
         if !info.is_on_blacklist && !self.last_is_on_blacklist {
             match param.direction {
                 PacketDirection::ClientToServer => {
@@ -282,11 +399,44 @@ impl RedisLog {
         info.request_type = Vec::from(request.command());
         info.msg_type = LogMessageType::Request;
         info.request = request.stringify(self.obfuscate);
+        info.key = request.key().map(Vec::from).unwrap_or_default();
+        match request.cmd_upper.as_str() {
+            "MULTI" => {
+                self.transaction_count = self.transaction_count.wrapping_add(1);
+                self.transaction_id = Some(self.transaction_count);
+                info.transaction_id = self.transaction_id;
+            }
+            "EXEC" | "DISCARD" => info.transaction_id = self.transaction_id.take(),
+            _ => info.transaction_id = self.transaction_id,
+        }
+        self.last_transaction_id = info.transaction_id;
         self.has_request = true;
     }
 
+    fn fill_push(&mut self, payload: &[u8], info: &mut RedisInfo) -> Result<()> {
+        let (kind, channel) = stringifier::decode_push(payload)?;
+        info.msg_type = LogMessageType::Session;
+        info.request_type = kind;
+        info.key = channel;
+        Ok(())
+    }
+
+    // MOVED and ASK errors are replied by cluster nodes not serving the slot
+    // - MOVED <slot> <host>:<port>
+    // - ASK <slot> <host>:<port>
+    fn parse_redirection(context: &[u8]) -> Option<(&str, &str, &str)> {
+        let s = str::from_utf8(context.get(1..)?).ok()?;
+        let mut parts = s.split(' ');
+        let redirect_type = parts.next()?;
+        if redirect_type != "MOVED" && redirect_type != "ASK" {
+            return None;
+        }
+        Some((redirect_type, parts.next()?, parts.next()?))
+    }
+
     fn fill_response(&mut self, context: Vec<u8>, info: &mut RedisInfo) {
         info.msg_type = LogMessageType::Response;
+        info.transaction_id = self.last_transaction_id.take();
         self.has_request = false;
 
         info.resp_status = L7ResponseStatus::Ok;
@@ -295,11 +445,19 @@ impl RedisLog {
             return;
         }
         match context[0] {
-            b'+' => info.status = context,
-            b'-' | b'!' => {
-                info.error = context;
-                info.resp_status = L7ResponseStatus::ServerError;
-            }
+            b'+' | b'=' => info.status = context,
+            b'-' | b'!' => match Self::parse_redirection(&context) {
+                // redirections are expected in cluster mode and retried by clients
+                Some((redirect_type, slot, node)) => {
+                    info.redirect_type = redirect_type.to_string();
+                    info.redirect_slot = slot.to_string();
+                    info.redirect_node = node.to_string();
+                }
+                None => {
+                    info.error = context;
+                    info.resp_status = L7ResponseStatus::ServerError;
+                }
+            },
             _ => {}
         }
     }
@@ -323,6 +481,10 @@ impl RedisLog {
             PacketDirection::ClientToServer if payload.get(0) == Some(&b'*') => {
                 self.fill_request(CommandLine::new(payload)?, info)
             }
+            // RESP3 pushes are out of band data and do not consume the pending request
+            PacketDirection::ServerToClient if payload.get(0) == Some(&b'>') => {
+                self.fill_push(payload, info)?
+            }
             PacketDirection::ServerToClient if self.has_request => {
                 self.fill_response(stringifier::decode(payload, false)?, info)
             }
@@ -336,6 +498,8 @@ mod stringifier {
     use super::*;
 
     pub const NULL_STR: &'static str = "NULL";
+    const VERBATIM_ENCODING_LEN: usize = 4;
+    const VERBATIM_OUTPUT_LIMIT: usize = 32;
 
     // decode simple types that does not contain '\r' or '\n' but ends with "\r\n"
     fn decode_simple_type<'a, P>(
@@ -540,9 +704,24 @@ mod stringifier {
     }
 
     // =<length>\r\n<encoding>:<data>\r\n
-    fn validate_verbatim_string(payload: &[u8]) -> Result<&[u8]> {
+    fn decode_verbatim_string<'a>(
+        output: Option<&mut Vec<u8>>,
+        payload: &'a [u8],
+    ) -> Result<&'a [u8]> {
         assert_eq!(payload[0], b'=');
-        validate_bulk_type(payload)
+        let Some(output) = output else {
+            return validate_bulk_type(payload);
+        };
+        let mut data = Vec::new();
+        let result = decode_bulk_type(Some(&mut data), payload);
+        // skip the encoding prefix such as "txt:" and "mkd:"
+        output.push(payload[0]);
+        let data = data.get(VERBATIM_ENCODING_LEN..).unwrap_or_default();
+        output.extend_from_slice(&data[..data.len().min(VERBATIM_OUTPUT_LIMIT)]);
+        if data.len() > VERBATIM_OUTPUT_LIMIT {
+            output.extend_from_slice(b"...");
+        }
+        result
     }
 
     // %<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
@@ -588,7 +767,7 @@ mod stringifier {
             // for example: parsing b"*3\r\n+bbb\r\n" will call this function with empty payload
             return Err(Error::RedisLogParsePartial);
         }
-        // decode '+', '-' and '!' RESP types used in fill_response
+        // decode '+', '-', '!' and '=' RESP types used in fill_response
         // other types are only validated
         match payload[0] {
             b'+' => decode_simple_string(output, payload),
@@ -601,7 +780,7 @@ mod stringifier {
             b',' => validate_double(payload),
             b'(' => validate_big_number(payload),
             b'!' => decode_bulk_error(output, payload),
-            b'=' => validate_verbatim_string(payload),
+            b'=' => decode_verbatim_string(output, payload),
             b'%' => validate_map(payload),
             b'~' => validate_set(payload),
             b'>' => validate_push(payload),
@@ -609,12 +788,33 @@ mod stringifier {
         }
     }
 
+    // returns the kind and the channel of pushes, for example:
+    // - >3\r\n$7\r\nmessage\r\n$7\r\nchannel\r\n$5\r\nhello\r\n
+    // - >2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nkey\r\n
+    pub fn decode_push(payload: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        if payload.first() != Some(&b'>') {
+            return Err(Error::RedisLogParseFailed);
+        }
+        let (payload, length) = read_length(&payload[1..])?;
+        if length < 1 {
+            return Err(Error::RedisLogParseFailed);
+        }
+        let (payload, kind) = CommandLine::decode_bulk_string(payload)?;
+        let channel = match length {
+            1 => &[][..],
+            _ => CommandLine::decode_bulk_string(payload)
+                .map(|(_, c)| c)
+                .unwrap_or_default(),
+        };
+        Ok((kind.to_vec(), channel.to_vec()))
+    }
+
     pub fn decode(payload: &[u8], strict: bool) -> Result<Vec<u8>> {
         if payload.is_empty() {
             return Err(Error::RedisLogParseFailed);
         }
         let mut output = match payload[0] {
-            b'+' | b'-' | b'!' => Vec::with_capacity(payload.len()),
+            b'+' | b'-' | b'!' | b'=' => Vec::with_capacity(payload.len()),
             _ => Vec::new(),
         };
         match (strict, decode_resp_type(Some(&mut output), payload)) {
@@ -700,6 +900,77 @@ impl<'a> CommandLine<'a> {
         Self::decode_bulk_string(self.payload).unwrap().1
     }
 
+    // commands whose first argument is not a key
+    const KEYLESS_COMMANDS: [&'static str; 56] = [
+        "ACL",
+        "AUTH",
+        "BITOP",
+        "BLMPOP",
+        "BZMPOP",
+        "CLIENT",
+        "CLUSTER",
+        "COMMAND",
+        "CONFIG",
+        "DEBUG",
+        "ECHO",
+        "EVAL",
+        "EVALSHA",
+        "EVALSHA_RO",
+        "EVAL_RO",
+        "FAILOVER",
+        "FCALL",
+        "FCALL_RO",
+        "FLUSHALL",
+        "FLUSHDB",
+        "FUNCTION",
+        "HELLO",
+        "INFO",
+        "LATENCY",
+        "LMPOP",
+        "MEMORY",
+        "MIGRATE",
+        "MODULE",
+        "OBJECT",
+        "PING",
+        "PSUBSCRIBE",
+        "PSYNC",
+        "PUBLISH",
+        "PUBSUB",
+        "PUNSUBSCRIBE",
+        "REPLICAOF",
+        "SCAN",
+        "SCRIPT",
+        "SELECT",
+        "SHUTDOWN",
+        "SINTERCARD",
+        "SLAVEOF",
+        "SLOWLOG",
+        "SPUBLISH",
+        "SSUBSCRIBE",
+        "SUBSCRIBE",
+        "SUNSUBSCRIBE",
+        "SWAPDB",
+        "UNSUBSCRIBE",
+        "WAIT",
+        "XGROUP",
+        "XINFO",
+        "XREAD",
+        "XREADGROUP",
+        "ZINTERCARD",
+        "ZMPOP",
+    ];
+
+    // the first argument of commands operating on keys, such as "key" in "GET key"
+    fn key(&self) -> Option<&'a [u8]> {
+        if self.length < 2
+            || self.cmd_upper.is_empty()
+            || Self::KEYLESS_COMMANDS.contains(&self.cmd_upper.as_str())
+        {
+            return None;
+        }
+        self.iter().nth(1)
+    }
+
     fn stringify(&self, obfuscate: bool) -> Vec<u8> {
         let mut output = Vec::with_capacity(self.payload.len());
 
@@ -1074,7 +1345,7 @@ mod tests {
             // !<length>\r\n<error>\r\n
             (("!9\r\nabcdefghi\r\n", true), Some("!abcdefghi")),
             // =<length>\r\n<encoding>:<data>\r\n
-            (("=9\r\ntxt:abcde\r\n", true), Some("")),
+            (("=9\r\ntxt:abcde\r\n", true), Some("=abcde")),
             // %<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
             (("%1\r\n+key\r\n:123\r\n", true), Some("")),
             // ~<number-of-elements>\r\n<element-1>...<element-n>
@@ -1200,6 +1471,185 @@ mod tests {
         output
     }
 
+    fn parse_messages(
+        messages: &[(&[u8], PacketDirection)],
+        config: &LogParserConfig,
+    ) -> Vec<RedisInfo> {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut redis = RedisLog::default();
+        let mut infos = vec![];
+        for (payload, direction) in messages {
+            let mut packet = MetaPacket::default();
+            packet.lookup_key.proto = IpProtocol::TCP;
+            packet.lookup_key.direction = *direction;
+            let mut param = ParseParam::new(
+                &packet,
+                log_cache.clone(),
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            param.set_log_parse_config(config);
+            match redis.parse_payload(payload, &param) {
+                Ok(L7ParseResult::Single(L7ProtocolInfo::RedisInfo(info))) => infos.push(info),
+                _ => infos.push(RedisInfo::default()),
+            }
+        }
+        infos
+    }
+
+    #[test]
+    fn check_key_extraction() {
+        let config = LogParserConfig {
+            redis_key_patterns: vec!["user:*:profile".to_string(), "session:*".to_string()],
+            ..Default::default()
+        };
+        let commands = [
+            encode_redis_command("GET user:10086:profile"),
+            encode_redis_command("SET session:abc value"),
+            encode_redis_command("HGET item:1 name"),
+            encode_redis_command("AUTH password"),
+            encode_redis_command("PING"),
+        ];
+        let messages: Vec<_> = commands
+            .iter()
+            .map(|c| (c.as_slice(), PacketDirection::ClientToServer))
+            .collect();
+        let keys: Vec<_> = parse_messages(&messages, &config)
+            .iter()
+            .map(|i| String::from_utf8_lossy(i.resource()).to_string())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "user:*:profile",
+                "session:*",
+                "item:1",
+                "AUTH password",
+                "PING"
+            ]
+        );
+
+        assert!(wildcard_match(b"a*b*c", b"aXXbYYbc"));
+        assert!(wildcard_match(b"*", b""));
+        assert!(!wildcard_match(b"a*b", b"aXXbc"));
+        assert!(!wildcard_match(b"user:*", b"users:1"));
+    }
+
+    #[test]
+    fn check_cluster_redirection() {
+        let get = encode_redis_command("GET key");
+        let infos = parse_messages(
+            &[
+                (&get, PacketDirection::ClientToServer),
+                (
+                    b"-MOVED 3999 127.0.0.1:6381\r\n",
+                    PacketDirection::ServerToClient,
+                ),
+                (&get, PacketDirection::ClientToServer),
+                (
+                    b"-ASK 3999 127.0.0.1:6382\r\n",
+                    PacketDirection::ServerToClient,
+                ),
+                (&get, PacketDirection::ClientToServer),
+                (b"-ERR unknown\r\n", PacketDirection::ServerToClient),
+            ],
+            &LogParserConfig::default(),
+        );
+        assert_eq!(infos[1].resp_status, L7ResponseStatus::Ok);
+        assert_eq!(infos[1].redirect_type, "MOVED");
+        assert_eq!(infos[1].redirect_slot, "3999");
+        assert_eq!(infos[1].redirect_node, "127.0.0.1:6381");
+        assert_eq!(infos[3].redirect_type, "ASK");
+        assert_eq!(infos[3].redirect_node, "127.0.0.1:6382");
+        assert_eq!(infos[5].resp_status, L7ResponseStatus::ServerError);
+        assert!(infos[5].redirect_type.is_empty());
+    }
+
+    #[test]
+    fn check_resp3_push() {
+        let subscribe = encode_redis_command("SUBSCRIBE channel");
+        let infos = parse_messages(
+            &[
+                (&subscribe, PacketDirection::ClientToServer),
+                (
+                    b">3\r\n$9\r\nsubscribe\r\n$7\r\nchannel\r\n:1\r\n",
+                    PacketDirection::ServerToClient,
+                ),
+                (
+                    b">3\r\n$7\r\nmessage\r\n$7\r\nchannel\r\n$5\r\nhello\r\n",
+                    PacketDirection::ServerToClient,
+                ),
+            ],
+            &LogParserConfig::default(),
+        );
+        assert_eq!(infos[1].msg_type, LogMessageType::Session);
+        assert_eq!(infos[1].request_type, b"subscribe");
+        assert_eq!(infos[2].msg_type, LogMessageType::Session);
+        assert_eq!(infos[2].request_type, b"message");
+        assert_eq!(infos[2].key, b"channel");
+
+        // push does not consume the pending request
+        let get = encode_redis_command("CLIENT INFO");
+        let infos = parse_messages(
+            &[
+                (&get, PacketDirection::ClientToServer),
+                (
+                    b">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nkey\r\n",
+                    PacketDirection::ServerToClient,
+                ),
+                (
+                    b"=17\r\ntxt:id=3 addr=::1\r\n",
+                    PacketDirection::ServerToClient,
+                ),
+            ],
+            &LogParserConfig::default(),
+        );
+        assert_eq!(infos[1].request_type, b"invalidate");
+        assert_eq!(infos[2].msg_type, LogMessageType::Response);
+        assert_eq!(infos[2].status, b"=id=3 addr=::1");
+    }
+
+    #[test]
+    fn check_transaction() {
+        let commands = [
+            encode_redis_command("MULTI"),
+            encode_redis_command("INCR counter"),
+            encode_redis_command("EXEC"),
+            encode_redis_command("GET counter"),
+            encode_redis_command("MULTI"),
+            encode_redis_command("DISCARD"),
+        ];
+        let mut messages = vec![];
+        for c in commands.iter() {
+            messages.push((c.as_slice(), PacketDirection::ClientToServer));
+            messages.push((&b"+OK\r\n"[..], PacketDirection::ServerToClient));
+        }
+        let ids: Vec<_> = parse_messages(&messages, &LogParserConfig::default())
+            .iter()
+            .map(|i| i.transaction_id)
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                None,
+                None,
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(2)
+            ]
+        );
+    }
+
     #[test]
     fn check_obfuscation() {
         let testcases = [
@@ -24,6 +24,7 @@ use std::{
 use public::utils::hash::hash_to_u64;
 
 use super::{forward, ObfuscateCache, BLANK_SPACE, QUESTION_MARK};
+use crate::config::SqlObfuscationMode;
 
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum Token {
@@ -693,6 +694,38 @@ pub fn attempt_obfuscation<'a>(
     None
 }
 
+// Returns the statement to report as request resource, and the raw statement when both forms
+// are stored and obfuscation changed it. Used by all SQL parsers.
+pub fn obfuscate_statement(
+    obfuscate_cache: &Option<ObfuscateCache>,
+    mode: SqlObfuscationMode,
+    input: &[u8],
+) -> (String, Option<String>) {
+    let raw = || String::from_utf8_lossy(input).into_owned();
+    if mode == SqlObfuscationMode::Raw {
+        return (raw(), None);
+    }
+    match attempt_obfuscation(obfuscate_cache, input) {
+        Some(mut m) => {
+            // the input may be truncated in the middle of a character
+            let valid_len = match std::str::from_utf8(&m) {
+                Ok(_) => m.len(),
+                Err(e) => e.valid_up_to(),
+            };
+            m.truncate(valid_len);
+            let obfuscated = unsafe {
+                // SAFTY: str in m is checked to be valid utf8 up to `valid_len`
+                String::from_utf8_unchecked(m)
+            };
+            match mode {
+                SqlObfuscationMode::Both => (obfuscated, Some(raw())),
+                _ => (obfuscated, None),
+            }
+        }
+        None => (raw(), None),
+    }
+}
+
 fn has_digits(buffer: &[u8]) -> bool {
     for ch in buffer {
         if ch.is_ascii_digit() {
@@ -833,4 +866,33 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_obfuscation_mode() {
+        let obfuscate_cache = Some(Rc::new(RefCell::new(LruCache::new(
+            NonZeroUsize::new(OBFUSCATE_CACHE_SIZE).unwrap(),
+        ))));
+        let input = b"SELECT * FROM t WHERE id IN (1, 2, 3) AND name = 'foo'";
+        let raw = String::from_utf8_lossy(input).into_owned();
+
+        let (sql, raw_sql) = obfuscate_statement(&obfuscate_cache, SqlObfuscationMode::Raw, input);
+        assert_eq!(sql, raw);
+        assert_eq!(raw_sql, None);
+
+        let (sql, raw_sql) =
+            obfuscate_statement(&obfuscate_cache, SqlObfuscationMode::Obfuscated, input);
+        assert_ne!(sql, raw);
+        assert!(!sql.contains("foo"));
+        assert_eq!(raw_sql, None);
+
+        let (both, raw_sql) =
+            obfuscate_statement(&obfuscate_cache, SqlObfuscationMode::Both, input);
+        assert_eq!(both, sql);
+        assert_eq!(raw_sql, Some(raw.clone()));
+
+        // obfuscation disabled for the protocol
+        let (sql, raw_sql) = obfuscate_statement(&None, SqlObfuscationMode::Both, input);
+        assert_eq!(sql, raw);
+        assert_eq!(raw_sql, None);
+    }
 }
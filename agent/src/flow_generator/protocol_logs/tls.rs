@@ -17,6 +17,7 @@
 use std::fmt::Display;
 
 use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
 use serde::Serialize;
 
 use super::pb_adapter::{
@@ -36,7 +37,10 @@ use crate::{
     flow_generator::error::{Error, Result},
 };
 use l7::tls::TlsHeader;
-use public::l7_protocol::L7Protocol;
+use public::{
+    bytes::{read_u16_be, read_u32_be},
+    l7_protocol::L7Protocol,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum CipherSuite {
@@ -252,6 +256,21 @@ pub struct TlsInfo {
     pub client_cert_not_before: Timestamp,
     #[serde(skip)]
     pub client_cert_not_after: Timestamp,
+    // offered protocols in Client Hello, or the selected one in Server Hello
+    #[serde(skip)]
+    pub alpn: String,
+    #[serde(skip)]
+    pub ja3: String,
+    #[serde(skip)]
+    pub ja3s: String,
+    #[serde(skip)]
+    pub server_cert_subject: String,
+    #[serde(skip)]
+    pub server_cert_issuer: String,
+    #[serde(skip)]
+    pub client_cert_subject: String,
+    #[serde(skip)]
+    pub client_cert_issuer: String,
 
     captured_request_byte: u32,
     captured_response_byte: u32,
@@ -324,6 +343,13 @@ impl TlsInfo {
                     &mut self.client_cert_not_before,
                     &mut other.client_cert_not_before,
                 );
+                std::mem::swap(&mut self.alpn, &mut other.alpn);
+                std::mem::swap(&mut self.ja3, &mut other.ja3);
+                std::mem::swap(
+                    &mut self.client_cert_subject,
+                    &mut other.client_cert_subject,
+                );
+                std::mem::swap(&mut self.client_cert_issuer, &mut other.client_cert_issuer);
                 self.captured_request_byte = other.captured_request_byte;
             }
             LogMessageType::Response => {
@@ -341,6 +367,15 @@ impl TlsInfo {
                     &mut self.server_cert_not_before,
                     &mut other.server_cert_not_before,
                 );
+                if !other.alpn.is_empty() {
+                    std::mem::swap(&mut self.alpn, &mut other.alpn);
+                }
+                std::mem::swap(&mut self.ja3s, &mut other.ja3s);
+                std::mem::swap(
+                    &mut self.server_cert_subject,
+                    &mut other.server_cert_subject,
+                );
+                std::mem::swap(&mut self.server_cert_issuer, &mut other.server_cert_issuer);
                 self.captured_response_byte = other.captured_response_byte;
             }
             _ => {}
@@ -411,6 +446,22 @@ impl From<TlsInfo> for L7ProtocolSendLog {
                 val: valid_days,
             });
         }
+        for (key, val) in [
+            ("alpn", f.alpn),
+            ("ja3", f.ja3),
+            ("ja3s", f.ja3s),
+            ("server_cert_subject", f.server_cert_subject),
+            ("server_cert_issuer", f.server_cert_issuer),
+            ("client_cert_subject", f.client_cert_subject),
+            ("client_cert_issuer", f.client_cert_issuer),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
             captured_response_byte: f.captured_response_byte,
@@ -541,6 +592,13 @@ impl L7ProtocolParserInterface for TlsLog {
 impl TlsLog {
     const CHNAGE_CIPHER_SPEC_LIMIT: u8 = 2;
 
+    // JA3 and JA3S fingerprints are MD5 of the comma separated field strings
+    fn fingerprint(ja3: &str) -> String {
+        Md5::digest(ja3.as_bytes())
+            .into_iter()
+            .fold(String::new(), |s, c| s + &format!("{:02x}", c))
+    }
+
     fn parse(&mut self, payload: &[u8], info: &mut TlsInfo, param: &ParseParam) -> Result<()> {
         if self.perf_stats.is_none() && param.parse_perf {
             self.perf_stats = Some(L7PerfStats::default())
@@ -613,6 +671,16 @@ impl TlsLog {
                     }
                 });
 
+                let fields = HandshakeFields::parse(payload);
+                info.alpn = fields.alpn.join(",");
+                if let Some(ja3) = fields.ja3 {
+                    info.ja3 = Self::fingerprint(&ja3);
+                }
+                if let Some((subject, issuer)) = fields.certificate {
+                    info.client_cert_subject = subject;
+                    info.client_cert_issuer = issuer;
+                }
+
                 info.request_type = tls_headers
                     .iter()
                     .map(|i| i.to_string())
@@ -665,6 +733,16 @@ impl TlsLog {
                     }
                 });
 
+                let fields = HandshakeFields::parse(payload);
+                info.alpn = fields.alpn.join(",");
+                if let Some(ja3s) = fields.ja3 {
+                    info.ja3s = Self::fingerprint(&ja3s);
+                }
+                if let Some((subject, issuer)) = fields.certificate {
+                    info.server_cert_subject = subject;
+                    info.server_cert_issuer = issuer;
+                }
+
                 if let Version::Unknown(v) = info.version {
                     return Err(Error::TlsLogParseFailed(format!(
                         "Unknown tls version 0x{:x}",
@@ -685,6 +763,302 @@ impl TlsLog {
     }
 }
 
+const CONTENT_TYPE_CHANGE_CIPHER_SPEC: u8 = 20;
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+
+const HANDSHAKE_HEADER_LEN: usize = 4;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const HANDSHAKE_SERVER_HELLO: u8 = 2;
+const HANDSHAKE_CERTIFICATE: u8 = 11;
+
+const EXTENSION_SUPPORTED_GROUPS: u16 = 10;
+const EXTENSION_EC_POINT_FORMATS: u16 = 11;
+const EXTENSION_ALPN: u16 = 16;
+
+// version and random of Client Hello and Server Hello
+const HELLO_RANDOM_END: usize = 34;
+
+const DER_TAG_SEQUENCE: u8 = 0x30;
+const DER_TAG_VERSION: u8 = 0xa0;
+
+// Handshake fields of a packet which are not decoded by TlsHeader
+#[derive(Default)]
+struct HandshakeFields {
+    // offered protocols in Client Hello, or the selected one in Server Hello
+    alpn: Vec<String>,
+    // JA3 string of Client Hello or JA3S string of Server Hello
+    ja3: Option<String>,
+    // subject and issuer of the first certificate in RFC 4514 format
+    certificate: Option<(String, String)>,
+}
+
+impl HandshakeFields {
+    fn parse(payload: &[u8]) -> Self {
+        let mut fields = Self::default();
+        let mut records = payload;
+        while records.len() >= TlsHeader::HEADER_LEN {
+            let content_type = records[0];
+            // records after Change Cipher Spec are encrypted
+            if content_type == CONTENT_TYPE_CHANGE_CIPHER_SPEC || records[1] != 3 {
+                break;
+            }
+            let end =
+                (TlsHeader::HEADER_LEN + read_u16_be(&records[3..]) as usize).min(records.len());
+            if content_type == CONTENT_TYPE_HANDSHAKE {
+                fields.parse_handshakes(&records[TlsHeader::HEADER_LEN..end]);
+            }
+            records = &records[end..];
+        }
+        fields
+    }
+
+    fn parse_handshakes(&mut self, mut messages: &[u8]) {
+        while messages.len() >= HANDSHAKE_HEADER_LEN {
+            let len = (read_u32_be(messages) & 0xffffff) as usize;
+            let end = (HANDSHAKE_HEADER_LEN + len).min(messages.len());
+            // fingerprints of truncated hellos are incomplete, while the first certificate may
+            // be complete in a truncated certificate message
+            let complete = HANDSHAKE_HEADER_LEN + len <= messages.len();
+            let body = &messages[HANDSHAKE_HEADER_LEN..end];
+            match messages[0] {
+                HANDSHAKE_CLIENT_HELLO if complete => {
+                    self.parse_hello(body, true);
+                }
+                HANDSHAKE_SERVER_HELLO if complete => {
+                    self.parse_hello(body, false);
+                }
+                HANDSHAKE_CERTIFICATE if self.certificate.is_none() => {
+                    self.certificate = Self::parse_certificate(body);
+                }
+                _ => (),
+            }
+            messages = &messages[end..];
+        }
+    }
+
+    // GREASE values (RFC 8701) are ignored in JA3
+    fn is_grease(v: u16) -> bool {
+        (v & 0x0f0f) == 0x0a0a && (v >> 8) == (v & 0xff)
+    }
+
+    fn join_u16(list: &[u8]) -> String {
+        list.chunks_exact(2)
+            .map(read_u16_be)
+            .filter(|v| !Self::is_grease(*v))
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    fn parse_hello(&mut self, body: &[u8], is_client: bool) -> Option<()> {
+        let version = read_u16_be(body.get(..2)?);
+        let mut offset = HELLO_RANDOM_END;
+        offset += 1 + *body.get(offset)? as usize;
+        let ciphers = if is_client {
+            let len = read_u16_be(body.get(offset..offset + 2)?) as usize;
+            let ciphers = Self::join_u16(body.get(offset + 2..offset + 2 + len)?);
+            offset += 2 + len;
+            // compression methods
+            offset += 1 + *body.get(offset)? as usize;
+            ciphers
+        } else {
+            let cipher = read_u16_be(body.get(offset..offset + 2)?);
+            // cipher suite and compression method
+            offset += 3;
+            cipher.to_string()
+        };
+
+        let mut extensions = vec![];
+        let mut groups = String::new();
+        let mut point_formats = String::new();
+        if let Some(len) = body.get(offset..offset + 2).map(read_u16_be) {
+            let mut list = body.get(offset + 2..offset + 2 + len as usize)?;
+            while list.len() >= 4 {
+                let ext_type = read_u16_be(list);
+                let len = read_u16_be(&list[2..]) as usize;
+                let data = list.get(4..4 + len)?;
+                if !Self::is_grease(ext_type) {
+                    extensions.push(ext_type.to_string());
+                }
+                match ext_type {
+                    EXTENSION_ALPN => self.alpn = Self::parse_alpn(data),
+                    EXTENSION_SUPPORTED_GROUPS if data.len() >= 2 => {
+                        groups = Self::join_u16(&data[2..])
+                    }
+                    EXTENSION_EC_POINT_FORMATS if !data.is_empty() => {
+                        point_formats = data[1..]
+                            .iter()
+                            .map(|v| v.to_string())
+                            .collect::<Vec<_>>()
+                            .join("-")
+                    }
+                    _ => (),
+                }
+                list = &list[4 + len..];
+            }
+        }
+
+        let extensions = extensions.join("-");
+        self.ja3 = Some(if is_client {
+            format!(
+                "{},{},{},{},{}",
+                version, ciphers, extensions, groups, point_formats
+            )
+        } else {
+            format!("{},{},{}", version, ciphers, extensions)
+        });
+        Some(())
+    }
+
+    fn parse_alpn(data: &[u8]) -> Vec<String> {
+        let mut protocols = vec![];
+        let mut list = data.get(2..).unwrap_or_default();
+        while let Some((len, rest)) = list.split_first() {
+            let len = *len as usize;
+            if rest.len() < len {
+                break;
+            }
+            protocols.push(String::from_utf8_lossy(&rest[..len]).into_owned());
+            list = &rest[len..];
+        }
+        protocols
+    }
+
+    fn parse_certificate(body: &[u8]) -> Option<(String, String)> {
+        // length of the certificate list and length of the first certificate
+        let len = (read_u32_be(body.get(2..6)?) & 0xffffff) as usize;
+        let cert = &body[6..(6 + len).min(body.len())];
+
+        // Certificate and TBSCertificate may be truncated after the subject
+        let (tag, offset, _) = Self::der_header(cert)?;
+        if tag != DER_TAG_SEQUENCE {
+            return None;
+        }
+        let (tag, tbs_offset, _) = Self::der_header(&cert[offset..])?;
+        if tag != DER_TAG_SEQUENCE {
+            return None;
+        }
+        let mut tbs = &cert[offset + tbs_offset..];
+        let mut next = || {
+            let (tag, value, rest) = Self::der_next(tbs)?;
+            tbs = rest;
+            Some((tag, value))
+        };
+        let (tag, _) = next()?;
+        if tag == DER_TAG_VERSION {
+            let _serial_number = next()?;
+        }
+        let _signature = next()?;
+        let (_, issuer) = next()?;
+        let _validity = next()?;
+        let (_, subject) = next()?;
+        Some((
+            Self::distinguished_name(subject)?,
+            Self::distinguished_name(issuer)?,
+        ))
+    }
+
+    // returns tag, header length and value length of a DER value
+    fn der_header(buf: &[u8]) -> Option<(u8, usize, usize)> {
+        let first = *buf.get(1)? as usize;
+        if first & 0x80 == 0 {
+            return Some((buf[0], 2, first));
+        }
+        // indefinite length is not allowed in DER
+        let n = first & 0x7f;
+        if n == 0 || n > std::mem::size_of::<u32>() {
+            return None;
+        }
+        let len = buf
+            .get(2..2 + n)?
+            .iter()
+            .fold(0, |len, b| (len << 8) | *b as usize);
+        Some((buf[0], 2 + n, len))
+    }
+
+    // returns tag, value and the rest of a complete DER value
+    fn der_next(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+        let (tag, offset, len) = Self::der_header(buf)?;
+        let value = buf.get(offset..offset + len)?;
+        Some((tag, value, &buf[offset + len..]))
+    }
+
+    fn attribute_type(oid: &[u8]) -> String {
+        match oid {
+            [0x55, 0x04, 0x03] => "CN".to_owned(),
+            [0x55, 0x04, 0x06] => "C".to_owned(),
+            [0x55, 0x04, 0x07] => "L".to_owned(),
+            [0x55, 0x04, 0x08] => "ST".to_owned(),
+            [0x55, 0x04, 0x09] => "STREET".to_owned(),
+            [0x55, 0x04, 0x0a] => "O".to_owned(),
+            [0x55, 0x04, 0x0b] => "OU".to_owned(),
+            [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01] => "UID".to_owned(),
+            [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19] => "DC".to_owned(),
+            _ => {
+                // dotted decimal of other types
+                let mut arcs = vec![];
+                let mut arc = 0u64;
+                for b in oid {
+                    arc = (arc << 7) | (b & 0x7f) as u64;
+                    if b & 0x80 == 0 {
+                        if arcs.is_empty() {
+                            let first = (arc / 40).min(2);
+                            arcs.push(first);
+                            arcs.push(arc - first * 40);
+                        } else {
+                            arcs.push(arc);
+                        }
+                        arc = 0;
+                    }
+                }
+                arcs.iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            }
+        }
+    }
+
+    fn escape(value: &str) -> String {
+        let last = value.chars().count().saturating_sub(1);
+        let mut escaped = String::with_capacity(value.len());
+        for (i, c) in value.chars().enumerate() {
+            if matches!(c, '"' | '+' | ',' | ';' | '<' | '>' | '\\')
+                || (i == 0 && (c == '#' || c == ' '))
+                || (i == last && c == ' ')
+            {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    // RDNs of a Name in reverse order as RFC 4514
+    fn distinguished_name(mut name: &[u8]) -> Option<String> {
+        let mut rdns = vec![];
+        while !name.is_empty() {
+            let (_, mut set, rest) = Self::der_next(name)?;
+            name = rest;
+            let mut attributes = vec![];
+            while !set.is_empty() {
+                let (_, attribute, rest) = Self::der_next(set)?;
+                set = rest;
+                let (_, oid, value) = Self::der_next(attribute)?;
+                let (_, value, _) = Self::der_next(value)?;
+                attributes.push(format!(
+                    "{}={}",
+                    Self::attribute_type(oid),
+                    Self::escape(&String::from_utf8_lossy(value))
+                ));
+            }
+            rdns.push(attributes.join("+"));
+        }
+        rdns.reverse();
+        Some(rdns.join(","))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -776,6 +1150,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn handshake_fields() {
+        let capture = Capture::load_pcap(Path::new(FILE_DIR).join("client-extension.pcap"), None);
+        let packets = capture.as_meta_packets();
+        let payloads = packets
+            .iter()
+            .filter_map(|p| p.get_l4_payload())
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>();
+
+        let client_hello = HandshakeFields::parse(payloads[0]);
+        assert_eq!(client_hello.alpn, vec!["h2", "http/1.1"]);
+        assert_eq!(
+            TlsLog::fingerprint(client_hello.ja3.as_ref().unwrap()),
+            "836344e43fe2784cb1343fa43291739d"
+        );
+        assert!(client_hello.certificate.is_none());
+
+        let server_hello = HandshakeFields::parse(payloads[1]);
+        assert_eq!(server_hello.alpn, vec!["http/1.1"]);
+        assert_eq!(
+            server_hello.ja3.as_deref(),
+            Some("771,49199,35-65281-16-11")
+        );
+        assert_eq!(
+            server_hello.certificate,
+            Some((
+                "CN=baidu.com,O=Beijing Baidu Netcom Science Technology Co.\\, Ltd,L=beijing,ST=beijing,C=CN".to_string(),
+                "CN=GlobalSign RSA OV SSL CA 2018,O=GlobalSign nv-sa,C=BE".to_string()
+            ))
+        );
+    }
+
     #[test]
     fn check_perf() {
         let expected = vec![(
@@ -0,0 +1,652 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// WebSocket frames after an HTTP/1.1 upgrade, parsed by the HTTP parser of the flow.
+// Reference: https://datatracker.ietf.org/doc/html/rfc6455#section-5
+//
+// Each data message or close frame is sent as a session log, ping and pong frames are ignored.
+
+use std::str;
+
+use serde::Serialize;
+
+use super::mq::stomp::StompFrame;
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{set_captured_byte, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        flow::PacketDirection,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, ParseParam},
+        meta_packet::EbpfFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::Result,
+};
+use public::{
+    bytes::{read_u16_be, read_u64_be},
+    l7_protocol::L7Protocol,
+};
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+const FIN: u8 = 0x80;
+// set on compressed messages by permessage-deflate, RSV2 and RSV3 are not used by any extension
+const RSV1: u8 = 0x40;
+const RSV2_RSV3: u8 = 0x30;
+const OPCODE_MASK: u8 = 0x0f;
+const MASKED: u8 = 0x80;
+const PAYLOAD_LEN_MASK: u8 = 0x7f;
+const PAYLOAD_LEN_16: u8 = 126;
+const PAYLOAD_LEN_64: u8 = 127;
+const MAX_CONTROL_PAYLOAD_LEN: u64 = 125;
+const MASKING_KEY_LEN: usize = 4;
+
+// only the beginning of messages is unmasked for subprotocol parsing
+const MAX_INSPECT_LEN: usize = 256;
+
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_UNSUPPORTED_DATA: u16 = 1003;
+const CLOSE_INVALID_PAYLOAD: u16 = 1007;
+const CLOSE_POLICY_VIOLATION: u16 = 1008;
+const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+const CLOSE_MANDATORY_EXTENSION: u16 = 1010;
+const CLOSE_INTERNAL_ERROR: u16 = 1011;
+const CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+const CLOSE_BAD_GATEWAY: u16 = 1014;
+
+fn opcode_name(opcode: u8) -> &'static str {
+    match opcode {
+        OPCODE_TEXT => "TEXT",
+        OPCODE_BINARY => "BINARY",
+        OPCODE_CLOSE => "CLOSE",
+        _ => "",
+    }
+}
+
+struct FrameHeader {
+    fin: bool,
+    compressed: bool,
+    opcode: u8,
+    masking_key: Option<[u8; MASKING_KEY_LEN]>,
+    header_len: usize,
+    payload_len: u64,
+}
+
+impl FrameHeader {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 2 || payload[0] & RSV2_RSV3 != 0 {
+            return None;
+        }
+        let opcode = payload[0] & OPCODE_MASK;
+        let fin = payload[0] & FIN != 0;
+        let (payload_len, mut header_len) = match payload[1] & PAYLOAD_LEN_MASK {
+            PAYLOAD_LEN_16 if payload.len() >= 4 => (read_u16_be(&payload[2..]) as u64, 4),
+            PAYLOAD_LEN_64 if payload.len() >= 10 => (read_u64_be(&payload[2..]), 10),
+            PAYLOAD_LEN_16 | PAYLOAD_LEN_64 => return None,
+            len => (len as u64, 2),
+        };
+        match opcode {
+            OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => (),
+            // control frames must not be fragmented
+            OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG
+                if fin && payload_len <= MAX_CONTROL_PAYLOAD_LEN => {}
+            _ => return None,
+        }
+        let masking_key = if payload[1] & MASKED != 0 {
+            let key = payload.get(header_len..header_len + MASKING_KEY_LEN)?;
+            header_len += MASKING_KEY_LEN;
+            Some([key[0], key[1], key[2], key[3]])
+        } else {
+            None
+        };
+        Some(Self {
+            fin,
+            compressed: payload[0] & RSV1 != 0,
+            opcode,
+            masking_key,
+            header_len,
+            payload_len,
+        })
+    }
+
+    // unmasks the beginning of the frame payload
+    fn inspect(&self, data: &[u8]) -> Vec<u8> {
+        let data = &data[..data.len().min(MAX_INSPECT_LEN)];
+        match self.masking_key {
+            Some(key) => data
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b ^ key[i % MASKING_KEY_LEN])
+                .collect(),
+            None => data.to_vec(),
+        }
+    }
+}
+
+// Socket.IO EVENT packet in an Engine.IO MESSAGE packet, with optional namespace and ack id,
+// e.g. `42["chat",{}]`, `42/admin,["chat"]` or `4213["chat"]`
+fn socketio_event(text: &[u8]) -> Option<&str> {
+    let text = text.strip_prefix(b"42")?;
+    let start = text.iter().position(|b| *b == b'[')?;
+    let event = text[start + 1..].strip_prefix(b"\"")?;
+    let end = event.iter().position(|b| *b == b'"')?;
+    str::from_utf8(&event[..end]).ok()
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct WebSocketInfo {
+    msg_type: LogMessageType,
+    #[serde(skip)]
+    direction: Option<PacketDirection>,
+
+    #[serde(rename = "request_type")]
+    pub opcode: &'static str,
+    // path, host and endpoint of the upgrade request
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    pub path: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    pub host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub subprotocol: String,
+    // total payload length of all frames in the message
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub length: u64,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub compressed: bool,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub close_code: Option<u16>,
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub close_reason: String,
+
+    captured_request_byte: u32,
+    captured_response_byte: u32,
+
+    // fields from subprotocol payloads
+    #[serde(skip)]
+    attributes: Vec<KeyVal>,
+    #[serde(skip)]
+    is_tls: bool,
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for WebSocketInfo {
+    fn session_id(&self) -> Option<u32> {
+        None
+    }
+
+    fn merge_log(&mut self, _: &mut L7ProtocolInfo) -> Result<()> {
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::WebSocket,
+            msg_type: self.msg_type,
+            rrt: 0,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    fn get_endpoint(&self) -> Option<String> {
+        self.endpoint.clone()
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.host.clone()
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.path.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl WebSocketInfo {
+    fn set_close(&mut self, data: &[u8]) {
+        if data.len() < 2 {
+            return;
+        }
+        let code = read_u16_be(data);
+        self.close_code = Some(code);
+        self.close_reason = String::from_utf8_lossy(&data[2..]).into_owned();
+        // the peer sending the close frame reports an error of the other side
+        self.status = match (code, self.direction) {
+            (
+                CLOSE_PROTOCOL_ERROR
+                | CLOSE_UNSUPPORTED_DATA
+                | CLOSE_INVALID_PAYLOAD
+                | CLOSE_POLICY_VIOLATION
+                | CLOSE_MESSAGE_TOO_BIG,
+                Some(PacketDirection::ServerToClient),
+            ) => L7ResponseStatus::ClientError,
+            (CLOSE_MANDATORY_EXTENSION, Some(PacketDirection::ClientToServer)) => {
+                L7ResponseStatus::ServerError
+            }
+            (CLOSE_INTERNAL_ERROR..=CLOSE_BAD_GATEWAY, _) if code != CLOSE_TRY_AGAIN_LATER => {
+                L7ResponseStatus::ServerError
+            }
+            _ => L7ResponseStatus::Ok,
+        };
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::WebSocket) {
+            self.is_on_blacklist = t.request_type.is_on_blacklist(self.opcode)
+                || t.request_resource.is_on_blacklist(&self.path)
+                || t.request_domain.is_on_blacklist(&self.host)
+                || self
+                    .endpoint
+                    .as_ref()
+                    .map(|p| t.endpoint.is_on_blacklist(p))
+                    .unwrap_or_default();
+        }
+    }
+}
+
+impl From<WebSocketInfo> for L7ProtocolSendLog {
+    fn from(f: WebSocketInfo) -> Self {
+        let mut attributes = f.attributes;
+        if !f.subprotocol.is_empty() {
+            attributes.push(KeyVal {
+                key: "websocket_subprotocol".to_string(),
+                val: f.subprotocol,
+            });
+        }
+        if f.compressed {
+            attributes.push(KeyVal {
+                key: "websocket_compressed".to_string(),
+                val: "true".to_string(),
+            });
+        }
+        let length = Some(f.length.min(u32::MAX as u64) as u32);
+        let (req_len, resp_len) = match f.direction {
+            Some(PacketDirection::ServerToClient) => (None, length),
+            _ => (length, None),
+        };
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req_len,
+            resp_len,
+            req: L7Request {
+                req_type: f.opcode.to_string(),
+                resource: f.path,
+                domain: f.host,
+                endpoint: f.endpoint.unwrap_or_default(),
+            },
+            resp: L7Response {
+                status: f.status,
+                code: f.close_code.map(|c| c as i32),
+                exception: f.close_reason,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            flags: if f.is_tls {
+                EbpfFlags::TLS.bits()
+            } else {
+                EbpfFlags::NONE.bits()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+// State of an upgraded connection, kept by the HTTP parser of the flow.
+#[derive(Debug, Default)]
+pub struct WebSocketSession {
+    pub upgraded: bool,
+    pub path: String,
+    pub host: String,
+    pub endpoint: Option<String>,
+    pub subprotocol: String,
+    // bytes of the last frame in following packets, indexed by direction
+    remaining: [u64; 2],
+    // fragmented message waiting for its last frame, indexed by direction
+    fragmented: [Option<WebSocketInfo>; 2],
+}
+
+impl WebSocketSession {
+    pub fn new(path: String, host: String, endpoint: Option<String>) -> Self {
+        Self {
+            path,
+            host,
+            endpoint,
+            ..Default::default()
+        }
+    }
+
+    pub fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        let index = param.direction as usize;
+        // payload may be truncated by `l7_log_packet_size`, frames are counted in captured bytes
+        let truncated = (param.captured_byte as u64).saturating_sub(payload.len() as u64);
+        let skip = self.remaining[index].min(payload.len() as u64) as usize;
+        self.remaining[index] -= skip as u64;
+        let mut payload = &payload[skip..];
+        if payload.is_empty() {
+            self.remaining[index] = self.remaining[index].saturating_sub(truncated);
+        }
+
+        let mut infos = vec![];
+        while !payload.is_empty() {
+            // out of sync if packets are lost
+            let Some(header) = FrameHeader::parse(payload) else {
+                break;
+            };
+            let frame_len = (header.header_len as u64).saturating_add(header.payload_len);
+            let end = frame_len.min(payload.len() as u64) as usize;
+            self.remaining[index] = (frame_len - end as u64).saturating_sub(truncated);
+            let data = &payload[header.header_len.min(end)..end];
+            if let Some(info) = self.on_frame(&header, data, param.direction) {
+                infos.push(info);
+            }
+            payload = &payload[end..];
+        }
+
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+        }
+        infos.retain(|i| !i.is_on_blacklist);
+        if !param.parse_log || infos.is_empty() {
+            return Ok(L7ParseResult::None);
+        }
+        if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::WebSocketInfo(
+                infos.remove(0),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                infos
+                    .into_iter()
+                    .map(L7ProtocolInfo::WebSocketInfo)
+                    .collect(),
+            ))
+        }
+    }
+
+    fn on_frame(
+        &mut self,
+        header: &FrameHeader,
+        data: &[u8],
+        direction: PacketDirection,
+    ) -> Option<WebSocketInfo> {
+        let index = direction as usize;
+        match header.opcode {
+            OPCODE_PING | OPCODE_PONG => None,
+            OPCODE_CONTINUATION => {
+                let info = self.fragmented[index].as_mut()?;
+                info.length = info.length.saturating_add(header.payload_len);
+                if header.fin {
+                    self.fragmented[index].take()
+                } else {
+                    None
+                }
+            }
+            opcode => {
+                let mut info = WebSocketInfo {
+                    msg_type: LogMessageType::Session,
+                    direction: Some(direction),
+                    opcode: opcode_name(opcode),
+                    path: self.path.clone(),
+                    host: self.host.clone(),
+                    endpoint: self.endpoint.clone(),
+                    subprotocol: self.subprotocol.clone(),
+                    length: header.payload_len,
+                    compressed: header.compressed,
+                    ..Default::default()
+                };
+                match opcode {
+                    OPCODE_CLOSE => info.set_close(&header.inspect(data)),
+                    OPCODE_TEXT if !header.compressed => {
+                        self.on_text(&header.inspect(data), &mut info)
+                    }
+                    _ => (),
+                }
+                if header.fin || opcode == OPCODE_CLOSE {
+                    Some(info)
+                } else {
+                    self.fragmented[index] = Some(info);
+                    None
+                }
+            }
+        }
+    }
+
+    // subprotocol specific parsing of text messages
+    fn on_text(&self, text: &[u8], info: &mut WebSocketInfo) {
+        if self.path.contains("/socket.io/") {
+            if let Some(event) = socketio_event(text) {
+                info.attributes.push(KeyVal {
+                    key: "socketio_event".to_string(),
+                    val: event.to_string(),
+                });
+            }
+        } else if let Some(frame) = StompFrame::parse(text) {
+            // subprotocols `v10.stomp`, `v11.stomp` and `v12.stomp`, or STOMP over SockJS
+            info.attributes.push(KeyVal {
+                key: "stomp_command".to_string(),
+                val: frame.command.to_string(),
+            });
+            if let Some(destination) = frame.header("destination") {
+                info.attributes.push(KeyVal {
+                    key: "stomp_destination".to_string(),
+                    val: destination.to_string(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::common::l7_protocol_log::L7PerfCache;
+    use crate::common::meta_packet::MetaPacket;
+    use crate::flow_generator::L7_RRT_CACHE_CAPACITY;
+
+    fn frame(first: u8, masking_key: Option<[u8; 4]>, payload: &[u8]) -> Vec<u8> {
+        let mut f = vec![first];
+        let mask_bit = if masking_key.is_some() { MASKED } else { 0 };
+        if payload.len() < PAYLOAD_LEN_16 as usize {
+            f.push(mask_bit | payload.len() as u8);
+        } else {
+            f.push(mask_bit | PAYLOAD_LEN_16);
+            f.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        match masking_key {
+            Some(key) => {
+                f.extend_from_slice(&key);
+                f.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+            }
+            None => f.extend_from_slice(payload),
+        }
+        f
+    }
+
+    fn parse(
+        session: &mut WebSocketSession,
+        payload: &[u8],
+        direction: PacketDirection,
+    ) -> Vec<WebSocketInfo> {
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.direction = direction;
+        let param = ParseParam::new(
+            &packet,
+            Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY))),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let infos = match session.parse_payload(payload, &param).unwrap() {
+            L7ParseResult::Single(i) => vec![i],
+            L7ParseResult::Multi(m) => m,
+            L7ParseResult::None => vec![],
+        };
+        infos
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::WebSocketInfo(i) => i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn messages() {
+        let mut session = WebSocketSession::new(
+            "/socket.io/?EIO=4&transport=websocket".to_string(),
+            "chat.example.com".to_string(),
+            Some("/socket.io".to_string()),
+        );
+        session.upgraded = true;
+
+        // masked text with a ping in the same packet
+        let payload = [
+            frame(
+                FIN | OPCODE_TEXT,
+                Some([1, 2, 3, 4]),
+                br#"42["message","hi"]"#,
+            ),
+            frame(FIN | OPCODE_PING, Some([1, 2, 3, 4]), b""),
+        ]
+        .concat();
+        let infos = parse(&mut session, &payload, PacketDirection::ClientToServer);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].opcode, "TEXT");
+        assert_eq!(infos[0].length, 18);
+        assert_eq!(infos[0].endpoint.as_deref(), Some("/socket.io"));
+        assert_eq!(
+            infos[0].attributes,
+            vec![KeyVal {
+                key: "socketio_event".to_string(),
+                val: "message".to_string(),
+            }]
+        );
+
+        // a fragmented binary message, logged once the header of the last fragment is seen
+        let data = vec![0u8; 300];
+        let first = frame(OPCODE_BINARY, None, &data[..100]);
+        let last = frame(FIN | OPCODE_CONTINUATION, None, &data);
+        assert!(parse(&mut session, &first, PacketDirection::ServerToClient).is_empty());
+        let infos = parse(&mut session, &last[..150], PacketDirection::ServerToClient);
+        assert_eq!(infos.len(), 1);
+        assert_eq!((infos[0].opcode, infos[0].length), ("BINARY", 400));
+        // the rest of the last fragment is skipped in the next packet
+        let payload = [&last[150..], &frame(FIN | OPCODE_TEXT, None, b"ok")].concat();
+        let infos = parse(&mut session, &payload, PacketDirection::ServerToClient);
+        assert_eq!(infos.len(), 1);
+        assert_eq!((infos[0].opcode, infos[0].length), ("TEXT", 2));
+    }
+
+    #[test]
+    fn close() {
+        let mut session = WebSocketSession::default();
+        let mut payload = 1011u16.to_be_bytes().to_vec();
+        payload.extend_from_slice(b"internal error");
+        let infos = parse(
+            &mut session,
+            &frame(FIN | OPCODE_CLOSE, None, &payload),
+            PacketDirection::ServerToClient,
+        );
+        assert_eq!(infos[0].close_code, Some(1011));
+        assert_eq!(infos[0].close_reason, "internal error");
+        assert_eq!(infos[0].status, L7ResponseStatus::ServerError);
+
+        let payload = 1000u16.to_be_bytes();
+        let infos = parse(
+            &mut session,
+            &frame(FIN | OPCODE_CLOSE, Some([9, 8, 7, 6]), &payload),
+            PacketDirection::ClientToServer,
+        );
+        assert_eq!(infos[0].close_code, Some(1000));
+        assert_eq!(infos[0].status, L7ResponseStatus::Ok);
+    }
+
+    #[test]
+    fn stomp() {
+        let mut session = WebSocketSession {
+            subprotocol: "v12.stomp".to_string(),
+            ..Default::default()
+        };
+        let infos = parse(
+            &mut session,
+            &frame(
+                FIN | OPCODE_TEXT,
+                Some([1, 2, 3, 4]),
+                b"SEND\ndestination:/app/chat\n\nhello\0",
+            ),
+            PacketDirection::ClientToServer,
+        );
+        assert_eq!(
+            infos[0].attributes,
+            vec![
+                KeyVal {
+                    key: "stomp_command".to_string(),
+                    val: "SEND".to_string(),
+                },
+                KeyVal {
+                    key: "stomp_destination".to_string(),
+                    val: "/app/chat".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn socketio() {
+        assert_eq!(socketio_event(br#"42["chat",{"a":1}]"#), Some("chat"));
+        assert_eq!(socketio_event(br#"42/admin,["chat"]"#), Some("chat"));
+        assert_eq!(socketio_event(br#"4213["chat"]"#), Some("chat"));
+        assert_eq!(socketio_event(b"2"), None);
+        assert_eq!(socketio_event(br#"40{"sid":"x"}"#), None);
+    }
+}
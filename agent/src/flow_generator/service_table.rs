@@ -16,12 +16,91 @@
 
 use std::hash::{Hash, Hasher};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use ahash::{HashSet, HashSetExt};
+use bitflags::bitflags;
 use lru::LruCache;
 
 use crate::common::enums::TcpFlags;
 use crate::common::flow::PacketDirection;
+use crate::config::DirectionHeuristic;
+use crate::utils::stats::{self, Counter, CounterType, CounterValue};
+
+bitflags! {
+    // Heuristics inferring the server side of a flow, the scores learned from the enabled
+    // ones are shared by the flows of the same service
+    pub struct DirectionHeuristics: u8 {
+        // the side sending SYN|ACK or receiving SYN or TOA is the server
+        const TCP_HANDSHAKE = 1 << 0;
+        // the side with a port in the server port list is the server
+        const SERVER_PORTS = 1 << 1;
+        // the side with a port below 32768 is the server if the other is not
+        const EPHEMERAL_PORT = 1 << 2;
+        // the socket of eBPF accept() is the server, and connect() the client
+        const EBPF_SOCKET_ROLE = 1 << 3;
+    }
+}
+
+impl Default for DirectionHeuristics {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl From<&[DirectionHeuristic]> for DirectionHeuristics {
+    fn from(heuristics: &[DirectionHeuristic]) -> Self {
+        let mut flags = Self::empty();
+        for h in heuristics {
+            flags |= match h {
+                DirectionHeuristic::TcpHandshake => Self::TCP_HANDSHAKE,
+                DirectionHeuristic::ServerPorts => Self::SERVER_PORTS,
+                DirectionHeuristic::EphemeralPort => Self::EPHEMERAL_PORT,
+                DirectionHeuristic::EbpfSocketRole => Self::EBPF_SOCKET_ROLE,
+            };
+        }
+        flags
+    }
+}
+
+// Times the heuristics decide the score of a flow side
+#[derive(Default)]
+pub struct DirectionCounter {
+    tcp_handshake: AtomicU64,
+    server_ports: AtomicU64,
+    ephemeral_port: AtomicU64,
+    ebpf_socket_role: AtomicU64,
+}
+
+impl stats::RefCountable for DirectionCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "tcp_handshake",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tcp_handshake.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "server_ports",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.server_ports.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "ephemeral_port",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.ephemeral_port.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "ebpf_socket_role",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.ebpf_socket_role.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum ServiceKey {
@@ -85,6 +164,9 @@ pub struct ServiceTable {
     ipv6: LruCache<Ipv6Key, u8>,
 
     port_map: HashSet<u16>,
+
+    heuristics: DirectionHeuristics,
+    counter: Arc<DirectionCounter>,
 }
 
 impl ServiceTable {
@@ -105,6 +187,28 @@ impl ServiceTable {
             ipv4: LruCache::new(ipv4_capacity.try_into().unwrap()),
             ipv6: LruCache::new(ipv6_capacity.try_into().unwrap()),
             port_map,
+            heuristics: DirectionHeuristics::default(),
+            counter: Default::default(),
+        }
+    }
+
+    pub fn with_heuristics(mut self, heuristics: DirectionHeuristics) -> Self {
+        self.heuristics = heuristics;
+        self
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<DirectionCounter> {
+        self.counter.clone()
+    }
+
+    fn is_server_port(&self, port: u16) -> bool {
+        if self.heuristics.contains(DirectionHeuristics::SERVER_PORTS)
+            && self.port_map.contains(&port)
+        {
+            self.counter.server_ports.fetch_add(1, Ordering::Relaxed);
+            true
+        } else {
+            false
         }
     }
 
@@ -146,9 +250,11 @@ impl ServiceTable {
         flow_dst_key: ServiceKey,
     ) -> (u8, u8) {
         let (mut flow_src_score, mut flow_dst_score) = (Self::MIN_SCORE, Self::MIN_SCORE);
+        let tcp_handshake = self.heuristics.contains(DirectionHeuristics::TCP_HANDSHAKE);
 
-        if tcp_flags.contains(TcpFlags::SYN_ACK) || toa_sent_by_flow_dst {
+        if tcp_handshake && (tcp_flags.contains(TcpFlags::SYN_ACK) || toa_sent_by_flow_dst) {
             // 一旦发送SYN|ACK，即被认为是服务端，其对侧被认为不可能是服务端
+            self.counter.tcp_handshake.fetch_add(1, Ordering::Relaxed);
             flow_src_score = Self::MAX_SCORE;
             flow_dst_score = Self::MIN_SCORE;
             match (flow_src_key, flow_dst_key) {
@@ -163,8 +269,9 @@ impl ServiceTable {
                 _ => unimplemented!(),
             }
             (flow_src_score, flow_dst_score)
-        } else if tcp_flags.contains(TcpFlags::SYN) || toa_sent_by_flow_src {
+        } else if tcp_handshake && (tcp_flags.contains(TcpFlags::SYN) || toa_sent_by_flow_src) {
             // It must be the client when packet has SYN or TOA.
+            self.counter.tcp_handshake.fetch_add(1, Ordering::Relaxed);
             flow_src_score = Self::MIN_SCORE;
 
             match (flow_src_key, flow_dst_key) {
@@ -388,12 +495,12 @@ impl ServiceTable {
 
         match (flow_src_key, flow_dst_key) {
             (ServiceKey::V4(flow_src_key), ServiceKey::V4(flow_dst_key)) => {
-                if self.port_map.contains(&flow_src_key.port) {
+                if self.is_server_port(flow_src_key.port) {
                     flow_src_score = Self::MAX_SCORE_FROM_CONFIG;
                 } else if let Some(score) = self.ipv4.get(&flow_src_key) {
                     flow_src_score = *score;
                 }
-                if self.port_map.contains(&flow_dst_key.port) {
+                if self.is_server_port(flow_dst_key.port) {
                     flow_dst_score = Self::MAX_SCORE_FROM_CONFIG;
                 } else if let Some(score) = self.ipv4.get(&flow_dst_key) {
                     flow_dst_score = *score;
@@ -425,12 +532,12 @@ impl ServiceTable {
                 )
             }
             (ServiceKey::V6(flow_src_key), ServiceKey::V6(flow_dst_key)) => {
-                if self.port_map.contains(&flow_src_key.port) {
+                if self.is_server_port(flow_src_key.port) {
                     flow_src_score = Self::MAX_SCORE_FROM_CONFIG;
                 } else if let Some(score) = self.ipv6.get(&flow_src_key) {
                     flow_src_score = *score;
                 }
-                if self.port_map.contains(&flow_dst_key.port) {
+                if self.is_server_port(flow_dst_key.port) {
                     flow_dst_score = Self::MAX_SCORE_FROM_CONFIG;
                 } else if let Some(score) = self.ipv6.get(&flow_dst_key) {
                     flow_dst_score = *score;
@@ -473,10 +580,15 @@ impl ServiceTable {
             flow_dst_score - flow_src_score
         };
 
-        if diff_value < Self::SCORE_DIFF_THRESHOLD {
+        if diff_value < Self::SCORE_DIFF_THRESHOLD
+            && self
+                .heuristics
+                .contains(DirectionHeuristics::EPHEMERAL_PORT)
+        {
             // 两个端口一个小于32768，一个大于等于32768时进行校正
             // 参考：Many Linux kernels use the port range 32768–60999：https://en.wikipedia.org/wiki/Ephemeral_port
             if (flow_src_port ^ flow_dst_port) & Self::PORT_MSB != 0 {
+                self.counter.ephemeral_port.fetch_add(1, Ordering::Relaxed);
                 if flow_src_port & Self::PORT_MSB > 0 {
                     return (0, 1);
                 } else {
@@ -498,6 +610,19 @@ impl ServiceTable {
     ) -> (u8, bool) {
         let score;
         let mut need_reverse = false;
+        let socket_role = if self
+            .heuristics
+            .contains(DirectionHeuristics::EBPF_SOCKET_ROLE)
+        {
+            socket_role
+        } else {
+            0
+        };
+        if (socket_role == 1 || socket_role == 2) && (l2_end_0 || l2_end_1) {
+            self.counter
+                .ebpf_socket_role
+                .fetch_add(1, Ordering::Relaxed);
+        }
         match (flow_src_key, flow_dst_key) {
             (ServiceKey::V4(flow_src_key), ServiceKey::V4(flow_dst_key)) => {
                 // socket_role: 0:unkonwn 1:client(connect) 2:server(accept)
@@ -522,7 +647,7 @@ impl ServiceTable {
                     score = Self::MAX_SCORE;
                     need_reverse = true;
                 } else {
-                    let source_score = if self.port_map.contains(&flow_src_key.port) {
+                    let source_score = if self.is_server_port(flow_src_key.port) {
                         Self::MAX_SCORE_FROM_CONFIG
                     } else if let Some(s) = self.ipv4.get(&flow_src_key) {
                         *s
@@ -530,7 +655,7 @@ impl ServiceTable {
                         0
                     };
 
-                    let dest_score = if self.port_map.contains(&flow_dst_key.port) {
+                    let dest_score = if self.is_server_port(flow_dst_key.port) {
                         Self::MAX_SCORE_FROM_CONFIG
                     } else if let Some(s) = self.ipv4.get(&flow_dst_key) {
                         *s
@@ -562,7 +687,7 @@ impl ServiceTable {
                     score = Self::MAX_SCORE;
                     need_reverse = true;
                 } else {
-                    let source_score = if self.port_map.contains(&flow_src_key.port) {
+                    let source_score = if self.is_server_port(flow_src_key.port) {
                         Self::MAX_SCORE_FROM_CONFIG
                     } else if let Some(s) = self.ipv6.get(&flow_src_key) {
                         *s
@@ -570,7 +695,7 @@ impl ServiceTable {
                         0
                     };
 
-                    let dest_score = if self.port_map.contains(&flow_dst_key.port) {
+                    let dest_score = if self.is_server_port(flow_dst_key.port) {
                         Self::MAX_SCORE_FROM_CONFIG
                     } else if let Some(s) = self.ipv6.get(&flow_dst_key) {
                         *s
@@ -915,4 +1040,71 @@ mod tests {
         assert_eq!(score, ServiceTable::MAX_SCORE);
         assert_eq!(reverse, true);
     }
+
+    #[test]
+    fn direction_heuristics() {
+        let key = |ip: [u8; 4], port| ServiceKey::new(Ipv4Addr::from(ip).into(), 0, port);
+        let scores = |table: &mut ServiceTable| {
+            (
+                table.get_tcp_score(
+                    true,
+                    false,
+                    PacketDirection::ClientToServer,
+                    TcpFlags::PSH,
+                    false,
+                    false,
+                    key([192, 168, 1, 1], 8080),
+                    key([192, 168, 1, 2], 40000),
+                ),
+                table.get_tcp_score(
+                    true,
+                    false,
+                    PacketDirection::ClientToServer,
+                    TcpFlags::PSH,
+                    false,
+                    false,
+                    key([192, 168, 1, 3], 50000),
+                    key([192, 168, 1, 4], 80),
+                ),
+                table.get_tcp_score(
+                    true,
+                    false,
+                    PacketDirection::ClientToServer,
+                    TcpFlags::SYN,
+                    false,
+                    false,
+                    key([192, 168, 1, 5], 50001),
+                    key([192, 168, 1, 6], 9000),
+                ),
+            )
+        };
+
+        let mut table = ServiceTable::new(10, 10, &vec![80]);
+        assert_eq!(
+            scores(&mut table),
+            ((1, 0), (0, ServiceTable::MAX_SCORE_FROM_CONFIG), (0, 1))
+        );
+        let counter = table.get_counter_handle();
+        assert_eq!(counter.tcp_handshake.load(Ordering::Relaxed), 1);
+        assert_eq!(counter.server_ports.load(Ordering::Relaxed), 1);
+        assert_eq!(counter.ephemeral_port.load(Ordering::Relaxed), 1);
+
+        let mut table =
+            ServiceTable::new(10, 10, &vec![80]).with_heuristics(DirectionHeuristics::empty());
+        assert_eq!(scores(&mut table), ((0, 1), (0, 1), (0, 1)));
+        let counter = table.get_counter_handle();
+        assert_eq!(counter.tcp_handshake.load(Ordering::Relaxed), 0);
+        assert_eq!(counter.server_ports.load(Ordering::Relaxed), 0);
+        assert_eq!(counter.ephemeral_port.load(Ordering::Relaxed), 0);
+
+        let (score, reverse) = table.get_ebpf_tcp_score(
+            2,
+            true,
+            false,
+            key([192, 168, 1, 7], 80),
+            key([192, 168, 1, 8], 40000),
+        );
+        assert_eq!((score, reverse), (0, false));
+        assert_eq!(counter.ebpf_socket_role.load(Ordering::Relaxed), 0);
+    }
 }
@@ -105,6 +105,18 @@ impl<'a> MiniPacket<'a> {
         self.if_index
     }
 
+    // packet size with l4 payload truncated to payload_truncation bytes
+    fn npb_packet_size(&self, payload_truncation: usize) -> usize {
+        let packet_size = self.packet_size as usize;
+        if payload_truncation == 0 {
+            return packet_size;
+        }
+        let payload_offset = self.header_type.min_packet_size()
+            + self.l2_l3_opt_size as usize
+            + self.l4_opt_size as usize;
+        packet_size.min(payload_offset + payload_truncation)
+    }
+
     pub fn raw(&self) -> &[u8] {
         match &self.packet {
             RawPacket::Borrowed(r) => *r,
@@ -117,7 +129,8 @@ impl<'a> MiniPacket<'a> {
 pub enum PacketHandler {
     // pcap_assembler sender, use for send mini packet to assemble
     Pcap(DebugSender<packet::MiniPacket>),
-    Npb(NpbHandler),
+    // npb handler and maximum l4 payload size of forwarded packets, 0 for no truncation
+    Npb(NpbHandler, usize),
 }
 
 impl PacketHandler {
@@ -162,21 +175,23 @@ impl PacketHandler {
 
                 let mini_packet = packet::MiniPacket {
                     packet: packet.packet[..max_raw_len].to_vec(),
+                    packet_len: packet.packet_len,
                     flow_id: packet.flow_id,
                     timestamp: Duration::from_nanos(packet.timestamp),
                     acl_gids: Vec::from(acl_gids.list()),
                     second_in_minute: packet.second_in_minute,
+                    if_index: packet.if_index as u32,
                 };
                 if let Err(e) = sender.send(mini_packet) {
                     debug!("send mini packet to pcap assembler error: {e:?}");
                 }
             }
-            Self::Npb(n) => n.handle(
+            Self::Npb(n, payload_truncation) => n.handle(
                 packet.policy.as_ref(),
                 &packet.npb_mode,
                 packet.timestamp,
                 &packet.packet,
-                packet.packet_size as usize,
+                packet.npb_packet_size(*payload_truncation),
                 packet.l2_opt_size as usize,
                 packet.l3_opt_size as usize,
                 packet.l4_opt_size as usize,
@@ -196,7 +211,9 @@ impl PacketHandlerBuilder {
     pub fn build_with(&self, id: usize, if_index: u32, mac: MacAddr) -> PacketHandler {
         match self {
             PacketHandlerBuilder::Pcap(s) => PacketHandler::Pcap(s.clone()),
-            PacketHandlerBuilder::Npb(b) => PacketHandler::Npb(b.build_with(id, if_index, mac)),
+            PacketHandlerBuilder::Npb(b) => {
+                PacketHandler::Npb(b.build_with(id, if_index, mac), b.payload_truncation())
+            }
         }
     }
 
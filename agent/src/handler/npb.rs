@@ -78,6 +78,7 @@ pub struct NpbBuilder {
     underlay_has_vlan: bool,
     ignore_overlay_vlan: bool,
     overlay_vlan_mode: VlanMode,
+    payload_truncation: usize,
 
     sender: DebugSender<(u64, usize, Vec<u8>)>,
 
@@ -231,6 +232,7 @@ impl NpbBuilder {
         self.underlay_is_ipv6 = config.underlay_is_ipv6;
         self.underlay_has_vlan = config.output_vlan > 0;
         self.overlay_vlan_mode = config.vlan_mode;
+        self.payload_truncation = config.payload_truncation;
         self.pseudo_tunnel_header = [
             Self::create_pseudo_vxlan_packet(config),
             Self::create_pseudo_erspan_packet(config),
@@ -273,6 +275,7 @@ impl NpbBuilder {
             underlay_has_vlan: config.output_vlan > 0,
             overlay_vlan_mode: config.vlan_mode,
             ignore_overlay_vlan: config.ignore_overlay_vlan,
+            payload_truncation: config.payload_truncation,
             sender,
             npb_packet_sender: Some(Arc::new(NpbPacketSender::new(
                 id,
@@ -330,6 +333,10 @@ impl NpbBuilder {
         )
     }
 
+    pub fn payload_truncation(&self) -> usize {
+        self.payload_truncation
+    }
+
     pub fn start(&mut self) {
         if self.npb_packet_sender.is_some() && self.npb_packet_sender.as_ref().unwrap().is_running()
         {
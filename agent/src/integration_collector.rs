@@ -29,7 +29,7 @@ use http::header::{CONTENT_ENCODING, CONTENT_TYPE};
 use http::HeaderMap;
 use hyper::{
     body::{aggregate, Buf},
-    server::conn::AddrStream,
+    server::conn::AddrIncoming,
     service::{make_service_fn, service_fn},
     Body, Method, Request, Response, Server, StatusCode,
 };
@@ -54,11 +54,12 @@ use crate::{
         lookup_key::LookupKey,
         TaggedFlow, Timestamp,
     },
-    config::{handler::LogParserConfig, PrometheusExtraLabels},
+    config::{handler::LogParserConfig, MutualTls, OtlpIngest, PrometheusExtraLabels},
     exception::ExceptionHandler,
     flow_generator::protocol_logs::{http::handle_endpoint, L7ResponseStatus},
     metric::document::{Direction, TapSide},
     policy::PolicyGetter,
+    utils::tls::{self, ServerStream, TlsContext, TlsCounter},
 };
 
 use integration_skywalking::{
@@ -83,6 +84,12 @@ use public::{
     utils::net::ipv6_enabled,
 };
 
+mod otlp;
+mod statsd;
+
+use otlp::{OtlpCounter, OtlpReceiver, Signal};
+pub use statsd::StatsdReceiver;
+
 type GenericError = Box<dyn std::error::Error + Send + Sync>;
 
 const NOT_FOUND: &[u8] = b"Not Found";
@@ -128,6 +135,16 @@ pub struct PrometheusExtra {
     extra_label_values: Vec<String>,
 }
 
+impl PrometheusExtra {
+    pub fn new(metrics: Vec<u8>) -> Self {
+        Self {
+            metrics,
+            extra_label_names: vec![],
+            extra_label_values: vec![],
+        }
+    }
+}
+
 impl Debug for PrometheusExtra {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.write_fmt(format_args!(
@@ -251,8 +268,31 @@ fn decode_otel_trace_data(
     flow_id: Arc<AtomicU64>,
     log_parser_config: Arc<LogParserConfig>,
 ) -> Result<(Vec<u8>, Vec<BatchedBox<L7Stats>>), GenericError> {
-    let mut l7_stats: Vec<BatchedBox<L7Stats>> = vec![];
     let mut d = TracesData::decode(data.as_slice())?;
+    let l7_stats = fill_otel_trace_data(
+        peer_addr,
+        &mut d,
+        local_epc_id,
+        policy_getter,
+        time_diff,
+        flow_id,
+        log_parser_config,
+    );
+    let sdk_data = d.encode_to_vec();
+    debug!("send otel sdk traces_data to sender: {:?}", d);
+    return Ok((sdk_data, l7_stats));
+}
+
+fn fill_otel_trace_data(
+    peer_addr: SocketAddr,
+    d: &mut TracesData,
+    local_epc_id: u32,
+    policy_getter: Arc<PolicyGetter>,
+    time_diff: i64,
+    flow_id: Arc<AtomicU64>,
+    log_parser_config: Arc<LogParserConfig>,
+) -> Vec<BatchedBox<L7Stats>> {
+    let mut l7_stats: Vec<BatchedBox<L7Stats>> = vec![];
     // 因为collector传过来traceData的全部resource都有"app.host.ip"的属性，所以只检查第一个resource有没有“app.host.ip”即可，
     // sdk传过来的traceData因没有该属性则要补上(key: “app.host.ip”, value: 对端IP)属性值
     // =======================================================================
@@ -332,9 +372,7 @@ fn decode_otel_trace_data(
         }
         flow_id.store(id, Ordering::Relaxed); // FIXME: flow_id may conflict
     }
-    let sdk_data = d.encode_to_vec();
-    debug!("send otel sdk traces_data to sender: {:?}", d);
-    return Ok((sdk_data, l7_stats));
+    l7_stats
 }
 
 fn fill_l7_stats(
@@ -497,7 +535,7 @@ fn fill_l7_stats(
     } else {
         0
     };
-    let stats = L7PerfStats {
+    let mut stats = L7PerfStats {
         request_count: 1,
         response_count: 1, // otel data is all session logs, so the number of requests is the same as the number of responses
         err_client_count: if status == L7ResponseStatus::ClientError {
@@ -516,6 +554,9 @@ fn fill_l7_stats(
         rrt_max: if rrt > 0 { rrt as u32 } else { 0 },
         ..Default::default()
     };
+    if rrt > 0 {
+        stats.rrt_sketch.add(rrt as u32);
+    }
     let flow_perf_stats = FlowPerfStats {
         tcp: Default::default(),
         l7: AHashMap::new(),
@@ -587,6 +628,42 @@ fn compress_data(input: Vec<u8>) -> std::io::Result<Vec<u8>> {
     e.finish()
 }
 
+fn send_otel_trace_data(
+    data: Vec<u8>,
+    l7_stats: &mut Vec<BatchedBox<L7Stats>>,
+    compressed: bool,
+    counter: &CompressedMetric,
+    otel_sender: &DebugSender<OpenTelemetry>,
+    compressed_otel_sender: &DebugSender<OpenTelemetryCompressed>,
+    otel_l7_stats_sender: &DebugSender<BatchedBox<L7Stats>>,
+) -> std::io::Result<()> {
+    if !l7_stats.is_empty() {
+        if let Err(e) = otel_l7_stats_sender.send_all(l7_stats) {
+            warn!("otel_l7_stats_sender failed to send data, because {:?}", e);
+        }
+    }
+    if compressed {
+        counter
+            .uncompressed
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        let compressed_data = compress_data(data)?;
+        counter
+            .compressed
+            .fetch_add(compressed_data.len() as u64, Ordering::Relaxed);
+        if let Err(e) = compressed_otel_sender.send(OpenTelemetryCompressed(compressed_data)) {
+            warn!(
+                "compressed_otel_sender failed to send data, because {:?}",
+                e
+            );
+        }
+    } else {
+        if let Err(e) = otel_sender.send(OpenTelemetry(data)) {
+            warn!("otel_sender failed to send data, because {:?}", e);
+        }
+    }
+    Ok(())
+}
+
 /// 接收metric server发送的请求，根据路由处理分发
 async fn handler(
     peer_addr: SocketAddr,
@@ -609,6 +686,7 @@ async fn handler(
     prometheus_extra_config: Arc<PrometheusExtraLabels>,
     log_parser_config: Arc<LogParserConfig>,
     flow_id: Arc<AtomicU64>,
+    otlp_receiver: Arc<OtlpReceiver>,
     external_profile_integration_disabled: bool,
     external_trace_integration_disabled: bool,
     external_metric_integration_disabled: bool,
@@ -649,34 +727,109 @@ async fn handler(
                 debug!("decode otel trace data error: {}", e);
                 e
             })?;
-            if !decode_data.1.is_empty() {
-                if let Err(e) = otel_l7_stats_sender.send_all(&mut decode_data.1) {
-                    warn!("otel_l7_stats_sender failed to send data, because {:?}", e);
-                }
+            send_otel_trace_data(
+                decode_data.0,
+                &mut decode_data.1,
+                compressed,
+                &counter,
+                &otel_sender,
+                &compressed_otel_sender,
+                &otel_l7_stats_sender,
+            )?;
+
+            Ok(Response::builder().body(Body::empty()).unwrap())
+        }
+        // OpenTelemetry OTLP/HTTP and OTLP/gRPC integration
+        (&Method::POST, path) if Signal::route(path).is_some() => {
+            let (signal, grpc) = Signal::route(path).unwrap();
+            let disabled = match signal {
+                Signal::Traces => external_trace_integration_disabled,
+                Signal::Metrics => external_metric_integration_disabled,
+                Signal::Logs => external_log_integration_disabled,
+            };
+            if disabled || !otlp_receiver.enabled() {
+                return Ok(otlp::response(signal, grpc));
             }
-            if compressed {
-                counter
-                    .uncompressed
-                    .fetch_add(decode_data.0.len() as u64, Ordering::Relaxed);
-                let compressed_data = compress_data(decode_data.0)?;
-                counter
-                    .compressed
-                    .fetch_add(compressed_data.len() as u64, Ordering::Relaxed);
-                if let Err(e) =
-                    compressed_otel_sender.send(OpenTelemetryCompressed(compressed_data))
-                {
-                    warn!(
-                        "compressed_otel_sender failed to send data, because {:?}",
-                        e
+            let (part, body) = req.into_parts();
+            let mut whole_body =
+                match aggregate_with_catch_exception(body, &exception_handler).await {
+                    Ok(b) => b,
+                    Err(e) => {
+                        return Ok(e);
+                    }
+                };
+            let data = if grpc {
+                let mut data = vec![0u8; whole_body.remaining()];
+                whole_body.copy_to_slice(data.as_mut_slice());
+                otlp::decode_grpc_message(data, &part.headers)
+            } else {
+                decode_metric(whole_body, &part.headers)
+            };
+            let data = match data {
+                Ok(d) => d,
+                Err(e) => {
+                    return Ok(otlp::error_response(
+                        grpc,
+                        StatusCode::BAD_REQUEST,
+                        e.to_string(),
+                    ))
+                }
+            };
+            let peer_ip = get_ip(peer_addr.ip());
+            match signal {
+                Signal::Traces => {
+                    let mut traces = match otlp_receiver.traces(&data, &part.headers, peer_ip, grpc)
+                    {
+                        Ok(t) => t,
+                        Err(e) => return Ok(e),
+                    };
+                    let mut l7_stats = fill_otel_trace_data(
+                        peer_addr,
+                        &mut traces,
+                        local_epc_id,
+                        policy_getter,
+                        time_diff.load(Ordering::Relaxed),
+                        flow_id.clone(),
+                        log_parser_config.clone(),
                     );
+                    send_otel_trace_data(
+                        traces.encode_to_vec(),
+                        &mut l7_stats,
+                        compressed,
+                        &counter,
+                        &otel_sender,
+                        &compressed_otel_sender,
+                        &otel_l7_stats_sender,
+                    )?;
                 }
-            } else {
-                if let Err(e) = otel_sender.send(OpenTelemetry(decode_data.0)) {
-                    warn!("otel_sender failed to send data, because {:?}", e);
+                Signal::Metrics => {
+                    let metrics = match otlp_receiver.metrics(&data, &part.headers, peer_ip, grpc) {
+                        Ok(m) => m,
+                        Err(e) => return Ok(e),
+                    };
+                    if let Err(e) = prometheus_sender.send(BoxedPrometheusExtra(Box::new(
+                        PrometheusExtra::new(metrics),
+                    ))) {
+                        warn!("prometheus_sender failed to send data, because {:?}", e);
+                    }
+                }
+                Signal::Logs => {
+                    let logs = match otlp_receiver.logs(&data, &part.headers, peer_ip, grpc) {
+                        Ok(l) => l,
+                        Err(e) => return Ok(e),
+                    };
+                    if let Some(logs) = logs {
+                        if let Err(e) = application_log_sender.send(ApplicationLog(logs)) {
+                            warn!(
+                                "application_log_sender failed to send data, because {:?}",
+                                e
+                            );
+                        }
+                    }
                 }
             }
 
-            Ok(Response::builder().body(Body::empty()).unwrap())
+            Ok(otlp::response(signal, grpc))
         }
         // Prometheus integration
         (&Method::POST, "/api/v1/prometheus") => {
@@ -913,6 +1066,7 @@ struct CompressedMetric {
 #[derive(Default)]
 pub struct IntegrationCounter {
     metrics: Arc<CompressedMetric>,
+    otlp: Arc<OtlpCounter>,
 }
 
 impl OwnedCountable for IntegrationCounter {
@@ -921,7 +1075,7 @@ impl OwnedCountable for IntegrationCounter {
             self.metrics.compressed.swap(0, Ordering::Relaxed),
             self.metrics.uncompressed.swap(0, Ordering::Relaxed),
         );
-        vec![
+        let mut counters = vec![
             (
                 "compressed",
                 CounterType::Counted,
@@ -942,7 +1096,9 @@ impl OwnedCountable for IntegrationCounter {
                     uncomressed as f64 / compressed as f64
                 }),
             ),
-        ]
+        ];
+        counters.append(&mut self.otlp.get_counters());
+        counters
     }
 
     fn closed(&self) -> bool {
@@ -974,6 +1130,8 @@ pub struct MetricServer {
     time_diff: Arc<AtomicI64>,
     prometheus_extra_config: Arc<PrometheusExtraLabels>,
     log_parser_config: Arc<LogParserConfig>,
+    otlp_receiver: Arc<OtlpReceiver>,
+    tls: Arc<TlsContext>,
     external_profile_integration_disabled: bool,
     external_trace_integration_disabled: bool,
     external_metric_integration_disabled: bool,
@@ -1000,12 +1158,18 @@ impl MetricServer {
         time_diff: Arc<AtomicI64>,
         prometheus_extra_config: PrometheusExtraLabels,
         log_parser_config: LogParserConfig,
+        otlp_config: OtlpIngest,
+        tls: MutualTls,
         external_profile_integration_disabled: bool,
         external_trace_integration_disabled: bool,
         external_metric_integration_disabled: bool,
         external_log_integration_disabled: bool,
     ) -> (Self, IntegrationCounter) {
-        let counter = IntegrationCounter::default();
+        let otlp_receiver = OtlpReceiver::new(otlp_config);
+        let counter = IntegrationCounter {
+            metrics: Default::default(),
+            otlp: otlp_receiver.get_counter_handle(),
+        };
         (
             Self {
                 running: Default::default(),
@@ -1029,6 +1193,8 @@ impl MetricServer {
                 time_diff,
                 prometheus_extra_config: Arc::new(prometheus_extra_config),
                 log_parser_config: Arc::new(log_parser_config),
+                otlp_receiver: Arc::new(otlp_receiver),
+                tls: Arc::new(TlsContext::new("integration", tls)),
                 otel_l7_stats_sender,
                 external_profile_integration_disabled,
                 external_trace_integration_disabled,
@@ -1047,6 +1213,15 @@ impl MetricServer {
         self.profile_compressed.store(enable, Ordering::Relaxed);
     }
 
+    // Certificates are applied to new connections
+    pub fn set_tls(&self, tls: &MutualTls) {
+        self.tls.update(tls);
+    }
+
+    pub fn get_tls_counter_handle(&self) -> Arc<TlsCounter> {
+        self.tls.get_counter_handle()
+    }
+
     pub fn set_port(&self, port: u16) {
         if self.port.swap(port, Ordering::Release) != port {
             // port changes, resets server
@@ -1083,6 +1258,8 @@ impl MetricServer {
         let time_diff = self.time_diff.clone();
         let prometheus_extra_config = self.prometheus_extra_config.clone();
         let log_parser_config = self.log_parser_config.clone();
+        let otlp_receiver = self.otlp_receiver.clone();
+        let tls = self.tls.clone();
         let external_profile_integration_disabled = self.external_profile_integration_disabled;
         let external_trace_integration_disabled = self.external_trace_integration_disabled;
         let external_metric_integration_disabled = self.external_metric_integration_disabled;
@@ -1099,7 +1276,7 @@ impl MetricServer {
                 info!("integration collector starting");
                 while running.load(Ordering::Relaxed) {
                     let mut max_tries = 0;
-                    let (server_builder, addr) = loop {
+                    let (incoming, addr) = loop {
                         if !running.load(Ordering::Relaxed) {
                             return;
                         }
@@ -1110,7 +1287,7 @@ impl MetricServer {
                         } else {
                             (Ipv4Addr::UNSPECIFIED, port).into()
                         };
-                        match Server::try_bind(&addr) {
+                        match AddrIncoming::bind(&addr) {
                             Ok(s) => {
                                 monitor_port.store(port, Ordering::Release);
                                 break (s, addr);
@@ -1152,7 +1329,8 @@ impl MetricServer {
                     let time_diff = time_diff.clone();
                     let prometheus_extra_config = prometheus_extra_config.clone();
                     let log_parser_config = log_parser_config.clone();
-                    let service = make_service_fn(move |conn: &AddrStream| {
+                    let otlp_receiver = otlp_receiver.clone();
+                    let service = make_service_fn(move |conn: &ServerStream| {
                         let otel_sender = otel_sender.clone();
                         let compressed_otel_sender = compressed_otel_sender.clone();
                         let otel_l7_stats_sender = otel_l7_stats_sender.clone();
@@ -1171,6 +1349,7 @@ impl MetricServer {
                         let time_diff = time_diff.clone();
                         let prometheus_extra_config = prometheus_extra_config.clone();
                         let log_parser_config = log_parser_config.clone();
+                        let otlp_receiver = otlp_receiver.clone();
                         let flow_id = Arc::new(AtomicU64::new(0));
                         async move {
                             Ok::<_, GenericError>(service_fn(move |req| {
@@ -1195,6 +1374,7 @@ impl MetricServer {
                                     prometheus_extra_config.clone(),
                                     log_parser_config.clone(),
                                     flow_id.clone(),
+                                    otlp_receiver.clone(),
                                     external_profile_integration_disabled,
                                     external_trace_integration_disabled,
                                     external_metric_integration_disabled,
@@ -1204,12 +1384,18 @@ impl MetricServer {
                         }
                     });
 
-                    let server = server_builder.serve(service).with_graceful_shutdown(async {
-                        let _ = rx.recv().await;
-                    });
+                    let server = Server::builder(tls::incoming(tls.clone(), incoming))
+                        .serve(service)
+                        .with_graceful_shutdown(async {
+                            let _ = rx.recv().await;
+                        });
 
                     info!("integration collector started");
-                    info!("integration collector listening on http://{}", addr);
+                    info!(
+                        "integration collector listening on {}://{}",
+                        if tls.enabled() { "https" } else { "http" },
+                        addr
+                    );
                     if let Err(e) = server.await {
                         error!("external metric collector error: {}", e);
                         exception_handler.set(Exception::IntegrationSocketError);
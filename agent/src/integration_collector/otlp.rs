@@ -0,0 +1,843 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use flate2::read::GzDecoder;
+use http::{
+    header::{HeaderValue, CONTENT_TYPE},
+    HeaderMap,
+};
+use hyper::{Body, Response, StatusCode};
+use log::debug;
+use prost::Message;
+use serde_json::{json, Map, Value};
+
+use super::GenericError;
+use crate::config::OtlpIngest;
+use crate::prometheus_scraper::{snappy_encode, time_series, TimeSeries, WriteRequest};
+use crate::utils::environment::get_k8s_node_name;
+use public::{
+    counter::{Counter, CounterType, CounterValue},
+    proto::integration::opentelemetry::proto::{
+        collector::{
+            logs::v1::{ExportLogsServiceRequest, ExportLogsServiceResponse},
+            metrics::v1::{ExportMetricsServiceRequest, ExportMetricsServiceResponse},
+            trace::v1::{ExportTraceServiceRequest, ExportTraceServiceResponse},
+        },
+        common::v1::{any_value::Value as AnyValueKind, AnyValue, KeyValue},
+        metrics::v1::{metric::Data, number_data_point, Metric},
+        resource::v1::Resource,
+        trace::v1::TracesData,
+    },
+};
+
+const GRPC_CONTENT_TYPE: &str = "application/grpc";
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+const GRPC_ENCODING: &str = "grpc-encoding";
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_MESSAGE: &str = "grpc-message";
+// see https://grpc.github.io/grpc/core/md_doc_statuscodes.html
+const GRPC_OK: u16 = 0;
+const GRPC_INVALID_ARGUMENT: u16 = 3;
+const GRPC_RESOURCE_EXHAUSTED: u16 = 8;
+// compressed flag and message length of length-prefixed messages
+const GRPC_FRAME_HEADER_LEN: usize = 5;
+
+const SERVICE_NAME: &str = "service.name";
+const SERVICE_NAMESPACE: &str = "service.namespace";
+const SERVICE_INSTANCE_ID: &str = "service.instance.id";
+const K8S_POD_NAME: &str = "k8s.pod.name";
+const K8S_POD_IP: &str = "k8s.pod.ip";
+const K8S_NODE_NAME: &str = "k8s.node.name";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Traces,
+    Metrics,
+    Logs,
+}
+
+impl Signal {
+    // Returns the signal and whether the request is OTLP/gRPC
+    pub fn route(path: &str) -> Option<(Self, bool)> {
+        match path {
+            "/v1/traces" => Some((Self::Traces, false)),
+            "/v1/metrics" => Some((Self::Metrics, false)),
+            "/v1/logs" => Some((Self::Logs, false)),
+            "/opentelemetry.proto.collector.trace.v1.TraceService/Export" => {
+                Some((Self::Traces, true))
+            }
+            "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export" => {
+                Some((Self::Metrics, true))
+            }
+            "/opentelemetry.proto.collector.logs.v1.LogsService/Export" => Some((Self::Logs, true)),
+            _ => None,
+        }
+    }
+
+    fn empty_response(&self) -> Vec<u8> {
+        match self {
+            Self::Traces => ExportTraceServiceResponse::default().encode_to_vec(),
+            Self::Metrics => ExportMetricsServiceResponse::default().encode_to_vec(),
+            Self::Logs => ExportLogsServiceResponse::default().encode_to_vec(),
+        }
+    }
+}
+
+// Unwraps the length-prefixed message of OTLP/gRPC requests
+pub fn decode_grpc_message(data: Vec<u8>, headers: &HeaderMap) -> Result<Vec<u8>, GenericError> {
+    if data.len() < GRPC_FRAME_HEADER_LEN {
+        return Err(format!("grpc message too short: {} bytes", data.len()).into());
+    }
+    let compressed = data[0] == 1;
+    let length = u32::from_be_bytes(data[1..GRPC_FRAME_HEADER_LEN].try_into().unwrap()) as usize;
+    let Some(message) = data.get(GRPC_FRAME_HEADER_LEN..GRPC_FRAME_HEADER_LEN + length) else {
+        return Err(format!(
+            "grpc message truncated: {} of {} bytes",
+            data.len() - GRPC_FRAME_HEADER_LEN,
+            length
+        )
+        .into());
+    };
+    if !compressed {
+        return Ok(message.to_vec());
+    }
+    match headers.get(GRPC_ENCODING).and_then(|v| v.to_str().ok()) {
+        Some("gzip") => {
+            let mut decoded = vec![];
+            GzDecoder::new(message).read_to_end(&mut decoded)?;
+            Ok(decoded)
+        }
+        encoding => Err(format!("unsupported grpc encoding {:?}", encoding).into()),
+    }
+}
+
+pub fn response(signal: Signal, grpc: bool) -> Response<Body> {
+    let message = signal.empty_response();
+    if !grpc {
+        return Response::builder()
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .body(message.into())
+            .unwrap();
+    }
+
+    let mut frame = Vec::with_capacity(GRPC_FRAME_HEADER_LEN + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut trailers = HeaderMap::new();
+        trailers.insert(GRPC_STATUS, HeaderValue::from(GRPC_OK));
+        if sender.send_data(frame.into()).await.is_ok() {
+            let _ = sender.send_trailers(trailers).await;
+        }
+    });
+    Response::builder()
+        .header(CONTENT_TYPE, GRPC_CONTENT_TYPE)
+        .body(body)
+        .unwrap()
+}
+
+// Bad requests are answered with 400 and rate limited requests with 429 to OTLP/HTTP, and with
+// the Trailers-Only response of the corresponding status to OTLP/gRPC
+pub fn error_response(grpc: bool, status: StatusCode, message: String) -> Response<Body> {
+    if !grpc {
+        return Response::builder()
+            .status(status)
+            .body(message.into())
+            .unwrap();
+    }
+
+    let grpc_status = if status == StatusCode::TOO_MANY_REQUESTS {
+        GRPC_RESOURCE_EXHAUSTED
+    } else {
+        GRPC_INVALID_ARGUMENT
+    };
+    let mut builder = Response::builder()
+        .header(CONTENT_TYPE, GRPC_CONTENT_TYPE)
+        .header(GRPC_STATUS, grpc_status);
+    // grpc-message is percent-encoded, only keep the printable characters which need no escaping
+    let message: String = message
+        .chars()
+        .filter(|c| (c.is_ascii_graphic() || *c == ' ') && *c != '%')
+        .collect();
+    if let Ok(message) = HeaderValue::from_str(&message) {
+        builder = builder.header(GRPC_MESSAGE, message);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+#[derive(Default)]
+pub struct OtlpCounter {
+    spans: AtomicU64,
+    data_points: AtomicU64,
+    log_records: AtomicU64,
+    rate_limited: AtomicU64,
+    decode_failed: AtomicU64,
+}
+
+impl OtlpCounter {
+    pub fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "otlp_spans",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.spans.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_data_points",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.data_points.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_log_records",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.log_records.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_rate_limited",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rate_limited.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_decode_failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.decode_failed.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Default)]
+struct RateWindow {
+    second: u64,
+    counts: HashMap<String, u64>,
+}
+
+// Decodes and enriches the OTLP requests received by the integration collector, spans, data
+// points and log records are limited per tenant in windows of one second.
+pub struct OtlpReceiver {
+    config: OtlpIngest,
+    rate_limits: HashMap<String, u64>,
+    node_name: Option<String>,
+    window: Mutex<RateWindow>,
+    counter: Arc<OtlpCounter>,
+}
+
+impl OtlpReceiver {
+    pub fn new(config: OtlpIngest) -> Self {
+        Self {
+            rate_limits: config
+                .tenant_rate_limits
+                .iter()
+                .map(|l| (l.tenant.clone(), l.rate_limit))
+                .collect(),
+            config,
+            node_name: get_k8s_node_name(),
+            window: Default::default(),
+            counter: Default::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<OtlpCounter> {
+        self.counter.clone()
+    }
+
+    // The tenant is read from the configured header, requests without it are accounted to the
+    // peer IP
+    fn tenant(&self, headers: &HeaderMap, peer_ip: IpAddr) -> String {
+        if !self.config.tenant_header.is_empty() {
+            if let Some(tenant) = headers
+                .get(self.config.tenant_header.as_str())
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| !v.is_empty())
+            {
+                return tenant.to_owned();
+            }
+        }
+        peer_ip.to_string()
+    }
+
+    fn acquire(&self, tenant: String, items: u64) -> bool {
+        let limit = self
+            .rate_limits
+            .get(&tenant)
+            .copied()
+            .unwrap_or(self.config.tenant_rate_limit);
+        if limit == 0 {
+            return true;
+        }
+        let second = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut window = self.window.lock().unwrap();
+        if window.second != second {
+            window.second = second;
+            window.counts.clear();
+        }
+        let count = window.counts.entry(tenant).or_default();
+        // the first request of the window is always accepted, or batches larger than the limit
+        // would never get through
+        if *count > 0 && *count + items > limit {
+            return false;
+        }
+        *count += items;
+        true
+    }
+
+    fn decode<T: Message + Default>(&self, data: &[u8], grpc: bool) -> Result<T, Response<Body>> {
+        T::decode(data).map_err(|e| {
+            debug!("decode otlp request error: {}", e);
+            self.counter.decode_failed.fetch_add(1, Ordering::Relaxed);
+            error_response(grpc, StatusCode::BAD_REQUEST, e.to_string())
+        })
+    }
+
+    fn check_rate(
+        &self,
+        headers: &HeaderMap,
+        peer_ip: IpAddr,
+        items: u64,
+        grpc: bool,
+    ) -> Result<(), Response<Body>> {
+        let tenant = self.tenant(headers, peer_ip);
+        if self.acquire(tenant.clone(), items) {
+            return Ok(());
+        }
+        self.counter.rate_limited.fetch_add(1, Ordering::Relaxed);
+        Err(error_response(
+            grpc,
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("rate limit of tenant {} exceeded", tenant),
+        ))
+    }
+
+    // Adds the pod IP and node name to resources from SDKs in the cluster, attributes set by the
+    // SDK or the k8sattributes processor are kept
+    fn enrich(&self, resource: &mut Option<Resource>, peer_ip: IpAddr) {
+        let resource = resource.get_or_insert_with(Default::default);
+        set_default_attribute(&mut resource.attributes, K8S_POD_IP, peer_ip.to_string());
+        if let Some(node_name) = self.node_name.as_ref() {
+            set_default_attribute(&mut resource.attributes, K8S_NODE_NAME, node_name.clone());
+        }
+    }
+
+    pub fn traces(
+        &self,
+        data: &[u8],
+        headers: &HeaderMap,
+        peer_ip: IpAddr,
+        grpc: bool,
+    ) -> Result<TracesData, Response<Body>> {
+        let mut request: ExportTraceServiceRequest = self.decode(data, grpc)?;
+        let spans = request
+            .resource_spans
+            .iter()
+            .flat_map(|r| r.scope_spans.iter())
+            .map(|s| s.spans.len() as u64)
+            .sum();
+        self.check_rate(headers, peer_ip, spans, grpc)?;
+        self.counter.spans.fetch_add(spans, Ordering::Relaxed);
+        for resource_spans in request.resource_spans.iter_mut() {
+            self.enrich(&mut resource_spans.resource, peer_ip);
+        }
+        Ok(TracesData {
+            resource_spans: request.resource_spans,
+        })
+    }
+
+    // Converts the metrics to prometheus remote write requests, encoded and compressed
+    pub fn metrics(
+        &self,
+        data: &[u8],
+        headers: &HeaderMap,
+        peer_ip: IpAddr,
+        grpc: bool,
+    ) -> Result<Vec<u8>, Response<Body>> {
+        let mut request: ExportMetricsServiceRequest = self.decode(data, grpc)?;
+        let mut write_request = WriteRequest::default();
+        for resource_metrics in request.resource_metrics.iter_mut() {
+            self.enrich(&mut resource_metrics.resource, peer_ip);
+            let target = resource_labels(resource_metrics.resource.as_ref().unwrap());
+            for scope_metrics in resource_metrics.scope_metrics.iter() {
+                for metric in scope_metrics.metrics.iter() {
+                    metric_to_time_series(metric, &target, &mut write_request.timeseries);
+                }
+            }
+        }
+        self.check_rate(
+            headers,
+            peer_ip,
+            write_request.timeseries.len() as u64,
+            grpc,
+        )?;
+        self.counter
+            .data_points
+            .fetch_add(write_request.timeseries.len() as u64, Ordering::Relaxed);
+        Ok(snappy_encode(&write_request.encode_to_vec()))
+    }
+
+    // Converts the log records to the json array of application logs, returns None if there is
+    // no record with a message
+    pub fn logs(
+        &self,
+        data: &[u8],
+        headers: &HeaderMap,
+        peer_ip: IpAddr,
+        grpc: bool,
+    ) -> Result<Option<Vec<u8>>, Response<Body>> {
+        let mut request: ExportLogsServiceRequest = self.decode(data, grpc)?;
+        let records = request
+            .resource_logs
+            .iter()
+            .flat_map(|r| r.scope_logs.iter())
+            .map(|s| s.log_records.len() as u64)
+            .sum();
+        self.check_rate(headers, peer_ip, records, grpc)?;
+        self.counter
+            .log_records
+            .fetch_add(records, Ordering::Relaxed);
+
+        let mut logs = vec![];
+        for resource_logs in request.resource_logs.iter_mut() {
+            self.enrich(&mut resource_logs.resource, peer_ip);
+            let attributes = &resource_logs.resource.as_ref().unwrap().attributes;
+            let app_service = get_attribute(attributes, SERVICE_NAME).unwrap_or_default();
+            let pod_name = get_attribute(attributes, K8S_POD_NAME).unwrap_or_default();
+            let pod_ip = get_attribute(attributes, K8S_POD_IP).unwrap_or_default();
+            for record in resource_logs
+                .scope_logs
+                .iter()
+                .flat_map(|s| s.log_records.iter())
+            {
+                let message = record
+                    .body
+                    .as_ref()
+                    .map(any_value_to_string)
+                    .unwrap_or_default();
+                if message.is_empty() {
+                    continue;
+                }
+                let time = if record.time_unix_nano > 0 {
+                    record.time_unix_nano
+                } else {
+                    record.observed_time_unix_nano
+                };
+                let level = if record.severity_text.is_empty() {
+                    severity_number_to_level(record.severity_number).to_owned()
+                } else {
+                    record.severity_text.clone()
+                };
+                let mut fields: Map<String, Value> = record
+                    .attributes
+                    .iter()
+                    .map(|kv| (kv.key.clone(), any_value_to_json(kv.value.as_ref())))
+                    .collect();
+                if !record.trace_id.is_empty() {
+                    fields.insert("trace_id".to_owned(), hex_string(&record.trace_id).into());
+                }
+                if !record.span_id.is_empty() {
+                    fields.insert("span_id".to_owned(), hex_string(&record.span_id).into());
+                }
+                logs.push(json!({
+                    "kubernetes": {
+                        "pod_name": pod_name,
+                        "pod_ip": pod_ip,
+                    },
+                    "message": message,
+                    "json": fields,
+                    "level": level,
+                    "timestamp": Utc
+                        .timestamp_nanos(time as i64)
+                        .to_rfc3339_opts(SecondsFormat::Nanos, true),
+                    "app_service": app_service,
+                }));
+            }
+        }
+        if logs.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Value::Array(logs).to_string().into_bytes()))
+    }
+}
+
+fn set_default_attribute(attributes: &mut Vec<KeyValue>, key: &str, value: String) {
+    if attributes.iter().any(|kv| kv.key == key) {
+        return;
+    }
+    attributes.push(KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(AnyValueKind::StringValue(value)),
+        }),
+    });
+}
+
+fn get_attribute(attributes: &[KeyValue], key: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref())
+        .map(any_value_to_string)
+}
+
+fn any_value_to_json(value: Option<&AnyValue>) -> Value {
+    match value.and_then(|v| v.value.as_ref()) {
+        Some(AnyValueKind::StringValue(s)) => Value::from(s.as_str()),
+        Some(AnyValueKind::BoolValue(b)) => Value::from(*b),
+        Some(AnyValueKind::IntValue(i)) => Value::from(*i),
+        Some(AnyValueKind::DoubleValue(d)) => Value::from(*d),
+        Some(AnyValueKind::ArrayValue(a)) => Value::Array(
+            a.values
+                .iter()
+                .map(|v| any_value_to_json(Some(v)))
+                .collect(),
+        ),
+        Some(AnyValueKind::KvlistValue(kvs)) => Value::Object(
+            kvs.values
+                .iter()
+                .map(|kv| (kv.key.clone(), any_value_to_json(kv.value.as_ref())))
+                .collect(),
+        ),
+        Some(AnyValueKind::BytesValue(b)) => Value::from(hex_string(b)),
+        None => Value::Null,
+    }
+}
+
+fn any_value_to_string(value: &AnyValue) -> String {
+    match any_value_to_json(Some(value)) {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// see https://opentelemetry.io/docs/specs/otel/logs/data-model/#field-severitynumber
+fn severity_number_to_level(severity_number: i32) -> &'static str {
+    match severity_number {
+        1..=4 => "trace",
+        5..=8 => "debug",
+        9..=12 => "info",
+        13..=16 => "warn",
+        17..=20 => "error",
+        21..=24 => "fatal",
+        _ => "",
+    }
+}
+
+// Labels of the resource following the prometheus compatibility of opentelemetry, the
+// universal tags are looked up by the `pod` label or the pod IP in `instance` on the server
+fn resource_labels(resource: &Resource) -> BTreeMap<String, String> {
+    let attributes = &resource.attributes;
+    let mut labels = BTreeMap::new();
+    if let Some(name) = get_attribute(attributes, SERVICE_NAME) {
+        let job = match get_attribute(attributes, SERVICE_NAMESPACE) {
+            Some(namespace) => format!("{}/{}", namespace, name),
+            None => name,
+        };
+        labels.insert("job".to_owned(), job);
+    }
+    if let Some(instance) = get_attribute(attributes, SERVICE_INSTANCE_ID)
+        .or_else(|| get_attribute(attributes, K8S_POD_IP))
+    {
+        labels.insert("instance".to_owned(), instance);
+    }
+    if let Some(pod) = get_attribute(attributes, K8S_POD_NAME) {
+        labels.insert("pod".to_owned(), pod);
+    }
+    labels
+}
+
+pub(super) fn sanitize_name(name: &str, allow_colon: bool) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn point_labels(
+    name: &str,
+    attributes: &[KeyValue],
+    target: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut labels: BTreeMap<String, String> = attributes
+        .iter()
+        .map(|kv| {
+            (
+                sanitize_name(&kv.key, false),
+                kv.value
+                    .as_ref()
+                    .map(any_value_to_string)
+                    .unwrap_or_default(),
+            )
+        })
+        .collect();
+    labels.extend(target.iter().map(|(k, v)| (k.clone(), v.clone())));
+    labels.insert("__name__".to_owned(), name.to_owned());
+    labels
+}
+
+fn to_millis(time_unix_nano: u64) -> i64 {
+    (time_unix_nano / 1_000_000) as i64
+}
+
+fn metric_to_time_series(
+    metric: &Metric,
+    target: &BTreeMap<String, String>,
+    series: &mut Vec<TimeSeries>,
+) {
+    let name = sanitize_name(&metric.name, true);
+    match metric.data.as_ref() {
+        Some(Data::Gauge(gauge)) => {
+            for point in gauge.data_points.iter() {
+                let value = match point.value {
+                    Some(number_data_point::Value::AsDouble(v)) => v,
+                    Some(number_data_point::Value::AsInt(v)) => v as f64,
+                    None => continue,
+                };
+                series.push(time_series(
+                    point_labels(&name, &point.attributes, target),
+                    value,
+                    to_millis(point.time_unix_nano),
+                ));
+            }
+        }
+        Some(Data::Sum(sum)) => {
+            let name = if sum.is_monotonic && !name.ends_with("_total") {
+                format!("{}_total", name)
+            } else {
+                name
+            };
+            for point in sum.data_points.iter() {
+                let value = match point.value {
+                    Some(number_data_point::Value::AsDouble(v)) => v,
+                    Some(number_data_point::Value::AsInt(v)) => v as f64,
+                    None => continue,
+                };
+                series.push(time_series(
+                    point_labels(&name, &point.attributes, target),
+                    value,
+                    to_millis(point.time_unix_nano),
+                ));
+            }
+        }
+        Some(Data::Histogram(histogram)) => {
+            for point in histogram.data_points.iter() {
+                let timestamp = to_millis(point.time_unix_nano);
+                let bucket = format!("{}_bucket", name);
+                let mut cumulative = 0;
+                for (i, count) in point.bucket_counts.iter().enumerate() {
+                    cumulative += count;
+                    let le = match point.explicit_bounds.get(i) {
+                        Some(bound) => bound.to_string(),
+                        None => "+Inf".to_owned(),
+                    };
+                    let mut labels = point_labels(&bucket, &point.attributes, target);
+                    labels.insert("le".to_owned(), le);
+                    series.push(time_series(labels, cumulative as f64, timestamp));
+                }
+                if let Some(sum) = point.sum {
+                    series.push(time_series(
+                        point_labels(&format!("{}_sum", name), &point.attributes, target),
+                        sum,
+                        timestamp,
+                    ));
+                }
+                series.push(time_series(
+                    point_labels(&format!("{}_count", name), &point.attributes, target),
+                    point.count as f64,
+                    timestamp,
+                ));
+            }
+        }
+        // buckets of exponential histograms are not supported by remote write v1
+        Some(Data::ExponentialHistogram(histogram)) => {
+            for point in histogram.data_points.iter() {
+                let timestamp = to_millis(point.time_unix_nano);
+                if let Some(sum) = point.sum {
+                    series.push(time_series(
+                        point_labels(&format!("{}_sum", name), &point.attributes, target),
+                        sum,
+                        timestamp,
+                    ));
+                }
+                series.push(time_series(
+                    point_labels(&format!("{}_count", name), &point.attributes, target),
+                    point.count as f64,
+                    timestamp,
+                ));
+            }
+        }
+        Some(Data::Summary(summary)) => {
+            for point in summary.data_points.iter() {
+                let timestamp = to_millis(point.time_unix_nano);
+                for quantile in point.quantile_values.iter() {
+                    let mut labels = point_labels(&name, &point.attributes, target);
+                    labels.insert("quantile".to_owned(), quantile.quantile.to_string());
+                    series.push(time_series(labels, quantile.value, timestamp));
+                }
+                series.push(time_series(
+                    point_labels(&format!("{}_sum", name), &point.attributes, target),
+                    point.sum,
+                    timestamp,
+                ));
+                series.push(time_series(
+                    point_labels(&format!("{}_count", name), &point.attributes, target),
+                    point.count as f64,
+                    timestamp,
+                ));
+            }
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::proto::integration::opentelemetry::proto::metrics::v1::{
+        Histogram, HistogramDataPoint, NumberDataPoint, Sum,
+    };
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue {
+                value: Some(AnyValueKind::StringValue(value.to_owned())),
+            }),
+        }
+    }
+
+    fn names(series: &[TimeSeries]) -> Vec<String> {
+        series
+            .iter()
+            .map(|s| {
+                s.labels
+                    .iter()
+                    .filter(|l| l.name == "__name__" || l.name == "le")
+                    .map(|l| l.value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grpc_message() {
+        let mut data = vec![0, 0, 0, 0, 3, 1, 2, 3];
+        assert_eq!(
+            decode_grpc_message(data.clone(), &HeaderMap::new()).unwrap(),
+            vec![1, 2, 3]
+        );
+        data.truncate(6);
+        assert!(decode_grpc_message(data, &HeaderMap::new()).is_err());
+    }
+
+    #[test]
+    fn metrics_to_time_series() {
+        let resource = Resource {
+            attributes: vec![
+                string_attribute(SERVICE_NAME, "cart"),
+                string_attribute(K8S_POD_IP, "10.1.2.3"),
+            ],
+            ..Default::default()
+        };
+        let target = resource_labels(&resource);
+        assert_eq!(target.get("job").unwrap(), "cart");
+        assert_eq!(target.get("instance").unwrap(), "10.1.2.3");
+
+        let mut series = vec![];
+        let sum = Metric {
+            name: "http.server.requests".to_owned(),
+            data: Some(Data::Sum(Sum {
+                data_points: vec![NumberDataPoint {
+                    attributes: vec![string_attribute("http.method", "GET")],
+                    time_unix_nano: 1_700_000_000_000_000_000,
+                    value: Some(number_data_point::Value::AsInt(42)),
+                    ..Default::default()
+                }],
+                is_monotonic: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        metric_to_time_series(&sum, &target, &mut series);
+        assert_eq!(names(&series), vec!["http_server_requests_total"]);
+        assert_eq!(series[0].samples[0].value, 42.0);
+        assert_eq!(series[0].samples[0].timestamp, 1_700_000_000_000);
+        assert!(series[0]
+            .labels
+            .iter()
+            .any(|l| l.name == "http_method" && l.value == "GET"));
+
+        series.clear();
+        let histogram = Metric {
+            name: "latency".to_owned(),
+            data: Some(Data::Histogram(Histogram {
+                data_points: vec![HistogramDataPoint {
+                    count: 3,
+                    sum: Some(1.5),
+                    bucket_counts: vec![1, 2],
+                    explicit_bounds: vec![0.5],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        metric_to_time_series(&histogram, &target, &mut series);
+        assert_eq!(
+            names(&series),
+            vec![
+                "latency_bucket,0.5",
+                "latency_bucket,+Inf",
+                "latency_sum",
+                "latency_count"
+            ]
+        );
+        assert_eq!(series[1].samples[0].value, 3.0);
+    }
+}
@@ -0,0 +1,885 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::{
+    atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    Arc, Condvar, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use arc_swap::access::Access;
+use log::{debug, info, warn};
+use prost::Message;
+
+use super::{otlp::sanitize_name, BoxedPrometheusExtra, PrometheusExtra};
+use crate::config::{handler::MetricServerAccess, Statsd};
+use crate::prometheus_scraper::{snappy_encode, time_series, TimeSeries, WriteRequest};
+use crate::rpc::get_timestamp;
+use crate::utils::{
+    net::ipv6_enabled,
+    stats::{Counter, CounterType, CounterValue, RefCountable},
+};
+use public::queue::DebugSender;
+
+const MAX_PACKET_SIZE: usize = 65535;
+// receivers check the config and the running state in every timeout
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const MIN_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const SERIES_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+// samples of timers in a flush interval kept for quantiles, the rest are only counted
+const MAX_SAMPLES_PER_SERIES: usize = 10000;
+// same as max_samples_per_send of prometheus remote write
+const MAX_SERIES_PER_REQUEST: usize = 2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricType {
+    Counter,
+    Gauge,
+    // timers, histograms and distributions
+    Timer,
+    Set,
+}
+
+impl MetricType {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "c" => Some(Self::Counter),
+            "g" => Some(Self::Gauge),
+            "ms" | "h" | "d" => Some(Self::Timer),
+            "s" => Some(Self::Set),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct StatsdLine<'a> {
+    name: &'a str,
+    values: Vec<&'a str>,
+    metric_type: MetricType,
+    sample_rate: f64,
+    tags: Vec<(&'a str, &'a str)>,
+    // container of the sender by DogStatsD origin detection
+    container_id: Option<&'a str>,
+}
+
+// Parses a line of StatsD or DogStatsD:
+//     <name>:<value>[:<value>...]|<type>[|@<sample rate>][|#<tag>[:<value>],...][|c:<container id>][|T<timestamp>]
+// Fields unknown are ignored, the timestamp is ignored because samples are aggregated by the
+// flush interval.
+fn parse_line(line: &str) -> Option<StatsdLine> {
+    let mut fields = line.split('|');
+    let (name, values) = fields.next()?.split_once(':')?;
+    if name.is_empty() || values.is_empty() {
+        return None;
+    }
+    let metric_type = MetricType::parse(fields.next()?)?;
+    let mut parsed = StatsdLine {
+        name,
+        values: values.split(':').collect(),
+        metric_type,
+        sample_rate: 1.0,
+        tags: vec![],
+        container_id: None,
+    };
+    for field in fields {
+        if let Some(rate) = field.strip_prefix('@') {
+            parsed.sample_rate = rate.parse::<f64>().ok()?;
+            if !(parsed.sample_rate > 0.0 && parsed.sample_rate <= 1.0) {
+                return None;
+            }
+        } else if let Some(tags) = field.strip_prefix('#') {
+            parsed.tags = tags
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(|t| t.split_once(':').unwrap_or((t, "true")))
+                .collect();
+        } else if let Some(id) = field.strip_prefix("c:") {
+            parsed.container_id = Some(id);
+        }
+    }
+    Some(parsed)
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Counter(f64),
+    Gauge(f64),
+    Summary {
+        count: f64,
+        sum: f64,
+        samples: Vec<f64>,
+    },
+    Set(HashSet<String>),
+}
+
+impl Value {
+    fn new(metric_type: MetricType) -> Self {
+        match metric_type {
+            MetricType::Counter => Self::Counter(0.0),
+            MetricType::Gauge => Self::Gauge(0.0),
+            MetricType::Timer => Self::Summary {
+                count: 0.0,
+                sum: 0.0,
+                samples: vec![],
+            },
+            MetricType::Set => Self::Set(HashSet::new()),
+        }
+    }
+
+    fn metric_type(&self) -> MetricType {
+        match self {
+            Self::Counter(_) => MetricType::Counter,
+            Self::Gauge(_) => MetricType::Gauge,
+            Self::Summary { .. } => MetricType::Timer,
+            Self::Set(_) => MetricType::Set,
+        }
+    }
+
+    fn add(&mut self, value: &str, sample_rate: f64) -> bool {
+        if let Self::Set(set) = self {
+            set.insert(value.to_owned());
+            return true;
+        }
+        let Ok(v) = value.parse::<f64>() else {
+            return false;
+        };
+        if !v.is_finite() {
+            return false;
+        }
+        match self {
+            Self::Counter(c) => *c += v / sample_rate,
+            // a signed value adjusts the gauge
+            Self::Gauge(g) if value.starts_with(['+', '-']) => *g += v,
+            Self::Gauge(g) => *g = v,
+            Self::Summary {
+                count,
+                sum,
+                samples,
+            } => {
+                *count += 1.0 / sample_rate;
+                *sum += v / sample_rate;
+                if samples.len() < MAX_SAMPLES_PER_SERIES {
+                    samples.push(v);
+                }
+            }
+            Self::Set(_) => unreachable!(),
+        }
+        true
+    }
+}
+
+struct Series {
+    value: Value,
+    updated: bool,
+    last_update: Instant,
+}
+
+// Samples aggregated by the name and the labels in a flush interval
+struct Aggregator {
+    series: HashMap<BTreeMap<String, String>, Series>,
+}
+
+impl Aggregator {
+    fn new() -> Self {
+        Self {
+            series: HashMap::new(),
+        }
+    }
+
+    // returns the number of values added, or None if the series is dropped by the limit
+    fn add(
+        &mut self,
+        line: &StatsdLine,
+        labels: BTreeMap<String, String>,
+        max_series: usize,
+        now: Instant,
+    ) -> Option<usize> {
+        if !self.series.contains_key(&labels) && self.series.len() >= max_series {
+            return None;
+        }
+        let series = self.series.entry(labels).or_insert_with(|| Series {
+            value: Value::new(line.metric_type),
+            updated: false,
+            last_update: now,
+        });
+        // the type of a metric is changed by the sender
+        if series.value.metric_type() != line.metric_type {
+            series.value = Value::new(line.metric_type);
+        }
+        series.updated = true;
+        series.last_update = now;
+        Some(
+            line.values
+                .iter()
+                .filter(|v| series.value.add(v, line.sample_rate))
+                .count(),
+        )
+    }
+
+    fn flush(&mut self, percentiles: &[u8], timestamp: i64, now: Instant) -> Vec<TimeSeries> {
+        self.series
+            .retain(|_, s| now.duration_since(s.last_update) < SERIES_IDLE_TIMEOUT);
+
+        let mut flushed = vec![];
+        let with_name = |labels: &BTreeMap<String, String>, suffix: &str| {
+            let mut labels = labels.clone();
+            if let Some(name) = labels.get_mut("__name__") {
+                name.push_str(suffix);
+            }
+            labels
+        };
+        for (labels, series) in self.series.iter_mut() {
+            let updated = series.updated;
+            series.updated = false;
+            match &mut series.value {
+                Value::Counter(v) | Value::Gauge(v) => {
+                    flushed.push(time_series(labels.clone(), *v, timestamp));
+                }
+                Value::Summary {
+                    count,
+                    sum,
+                    samples,
+                } => {
+                    if !samples.is_empty() {
+                        samples.sort_unstable_by(|a, b| a.total_cmp(b));
+                        for p in percentiles.iter().filter(|p| (1..=100).contains(*p)) {
+                            // nearest rank
+                            let rank = (*p as usize * samples.len() + 99) / 100;
+                            let mut labels = labels.clone();
+                            labels.insert("quantile".to_owned(), (*p as f64 / 100.0).to_string());
+                            flushed.push(time_series(labels, samples[rank - 1], timestamp));
+                        }
+                        samples.clear();
+                    }
+                    flushed.push(time_series(with_name(labels, "_count"), *count, timestamp));
+                    flushed.push(time_series(with_name(labels, "_sum"), *sum, timestamp));
+                }
+                Value::Set(set) => {
+                    if updated {
+                        flushed.push(time_series(labels.clone(), set.len() as f64, timestamp));
+                        set.clear();
+                    }
+                }
+            }
+        }
+        flushed
+    }
+}
+
+// Labels of the sender, the universal tags are looked up by the `pod` label or the pod IP in
+// `instance` on the server
+#[derive(Default)]
+struct Origin {
+    peer_ip: Option<IpAddr>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pid: Option<u32>,
+}
+
+// Pod metadata of processes sending through the unix socket, cleared in every flush interval
+// because pids are reused
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Default)]
+struct ProcessLabels(HashMap<u32, Vec<(String, String)>>);
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl ProcessLabels {
+    fn get(&mut self, pid: u32) -> &[(String, String)] {
+        self.0.entry(pid).or_insert_with(|| {
+            let Ok(process) = procfs::process::Process::new(pid as i32) else {
+                return vec![];
+            };
+            let Some(container_id) = crate::platform::get_container_id(&process) else {
+                return vec![];
+            };
+            let mut labels = vec![];
+            // hostname of a kubernetes container is the name of the pod by default
+            if let Some(pod) = process
+                .environ()
+                .ok()
+                .and_then(|env| env.get(std::ffi::OsStr::new("HOSTNAME")).cloned())
+                .and_then(|h| h.into_string().ok())
+            {
+                labels.push(("pod".to_owned(), pod));
+            }
+            labels.push(("container_id".to_owned(), container_id));
+            labels
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct StatsdCounter {
+    packets: AtomicU64,
+    samples: AtomicU64,
+    invalid: AtomicU64,
+    // samples of new series dropped by max_series
+    series_drop: AtomicU64,
+    series: AtomicU64,
+    // series dropped because the queue is full
+    queue_drop: AtomicU64,
+}
+
+impl RefCountable for StatsdCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "packets",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "samples",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.samples.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "invalid",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.invalid.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "series-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.series_drop.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "series",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.series.load(Ordering::Relaxed)),
+            ),
+            (
+                "queue-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.queue_drop.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Receiver {
+    config: MetricServerAccess,
+    aggregator: Arc<Mutex<Aggregator>>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    process_labels: Arc<Mutex<ProcessLabels>>,
+    counter: Arc<StatsdCounter>,
+    running: Arc<AtomicBool>,
+}
+
+impl Receiver {
+    fn receive(&self, packet: &[u8], origin: Origin) {
+        self.counter.packets.fetch_add(1, Ordering::Relaxed);
+        let config = self.config.load();
+        let max_series = config.statsd.max_series;
+        let Ok(packet) = std::str::from_utf8(packet) else {
+            self.counter.invalid.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+
+        let mut origin_labels = vec![];
+        if let Some(ip) = origin.peer_ip.filter(|ip| !ip.is_loopback()) {
+            origin_labels.push(("instance".to_owned(), ip.to_string()));
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(pid) = origin.pid {
+            origin_labels.extend_from_slice(self.process_labels.lock().unwrap().get(pid));
+        }
+
+        let now = Instant::now();
+        let mut aggregator = self.aggregator.lock().unwrap();
+        for line in packet.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let Some(line) = parse_line(line) else {
+                debug!("invalid statsd line: {}", line);
+                self.counter.invalid.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            let mut labels: BTreeMap<String, String> = line
+                .tags
+                .iter()
+                .map(|(k, v)| (sanitize_name(k, false), v.to_string()))
+                .collect();
+            if let Some(id) = line.container_id {
+                labels.insert("container_id".to_owned(), id.to_owned());
+            }
+            // the labels of the sender are not overridden by the tags
+            labels.extend(origin_labels.iter().cloned());
+            labels.insert("__name__".to_owned(), sanitize_name(line.name, true));
+            match aggregator.add(&line, labels, max_series, now) {
+                Some(n) => {
+                    self.counter.samples.fetch_add(n as u64, Ordering::Relaxed);
+                    if n < line.values.len() {
+                        self.counter
+                            .invalid
+                            .fetch_add((line.values.len() - n) as u64, Ordering::Relaxed);
+                    }
+                }
+                None => {
+                    self.counter
+                        .series_drop
+                        .fetch_add(line.values.len() as u64, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    // waits for the socket config to be changed or the receiver to be stopped
+    fn wait_for_change<T: PartialEq>(&self, current: &T, get: impl Fn(&Statsd) -> T) {
+        while self.running.load(Ordering::Relaxed) {
+            let config = self.config.load();
+            if &get(&config.statsd) != current {
+                return;
+            }
+            thread::sleep(READ_TIMEOUT);
+        }
+    }
+
+    fn run_udp(&self) {
+        let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+        while self.running.load(Ordering::Relaxed) {
+            let port = {
+                let config = self.config.load();
+                config.statsd.enabled.then_some(config.statsd.listen_port)
+            };
+            let Some(port) = port.filter(|p| *p > 0) else {
+                self.wait_for_change(&port, |c| c.enabled.then_some(c.listen_port));
+                continue;
+            };
+            let addr: SocketAddr = if ipv6_enabled() {
+                (Ipv6Addr::UNSPECIFIED, port).into()
+            } else {
+                (Ipv4Addr::UNSPECIFIED, port).into()
+            };
+            let socket = match UdpSocket::bind(addr)
+                .and_then(|s| s.set_read_timeout(Some(READ_TIMEOUT)).map(|_| s))
+            {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("statsd receiver failed to bind udp {}: {}", addr, e);
+                    thread::sleep(Duration::from_secs(60));
+                    continue;
+                }
+            };
+            info!("statsd receiver listening on udp {}", addr);
+            while self.running.load(Ordering::Relaxed) {
+                match socket.recv_from(&mut buffer) {
+                    Ok((n, peer)) => self.receive(
+                        &buffer[..n],
+                        Origin {
+                            peer_ip: Some(peer.ip()),
+                            ..Default::default()
+                        },
+                    ),
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                        let config = self.config.load();
+                        if !config.statsd.enabled || config.statsd.listen_port != port {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("statsd receiver failed to receive from udp {}: {}", addr, e);
+                        break;
+                    }
+                }
+            }
+            info!("statsd receiver stopped listening on udp {}", addr);
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn run_unix(&self) {
+        use std::os::unix::{fs::PermissionsExt, io::AsRawFd, net::UnixDatagram};
+
+        use nix::{
+            cmsg_space,
+            errno::Errno,
+            sys::{
+                socket::{
+                    recvmsg, setsockopt, sockopt::PassCred, ControlMessageOwned, MsgFlags,
+                    UnixCredentials,
+                },
+                uio::IoVec,
+            },
+        };
+
+        let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+        let mut cmsg_buffer = cmsg_space!(UnixCredentials);
+        while self.running.load(Ordering::Relaxed) {
+            let path = {
+                let config = self.config.load();
+                config
+                    .statsd
+                    .enabled
+                    .then(|| config.statsd.unix_socket_path.clone())
+            };
+            let Some(path) = path.filter(|p| !p.is_empty()) else {
+                self.wait_for_change(&path, |c| c.enabled.then(|| c.unix_socket_path.clone()));
+                continue;
+            };
+            // remove the socket left by the last run
+            let _ = std::fs::remove_file(&path);
+            let socket = match UnixDatagram::bind(&path).and_then(|s| {
+                s.set_read_timeout(Some(READ_TIMEOUT))?;
+                setsockopt(s.as_raw_fd(), PassCred, &true)?;
+                // senders in containers are not root
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666))?;
+                Ok(s)
+            }) {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("statsd receiver failed to bind unix socket {}: {}", path, e);
+                    thread::sleep(Duration::from_secs(60));
+                    continue;
+                }
+            };
+            info!("statsd receiver listening on unix socket {}", path);
+            while self.running.load(Ordering::Relaxed) {
+                let iov = [IoVec::from_mut_slice(&mut buffer)];
+                let (n, pid) = match recvmsg(
+                    socket.as_raw_fd(),
+                    &iov,
+                    Some(&mut cmsg_buffer),
+                    MsgFlags::empty(),
+                ) {
+                    Ok(msg) => {
+                        let pid = msg.cmsgs().find_map(|c| match c {
+                            ControlMessageOwned::ScmCredentials(cred) => Some(cred.pid() as u32),
+                            _ => None,
+                        });
+                        (msg.bytes, pid)
+                    }
+                    Err(Errno::EAGAIN) | Err(Errno::EINTR) => {
+                        let config = self.config.load();
+                        if !config.statsd.enabled || config.statsd.unix_socket_path != path {
+                            break;
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "statsd receiver failed to receive from unix socket {}: {}",
+                            path, e
+                        );
+                        break;
+                    }
+                };
+                self.receive(
+                    &buffer[..n],
+                    Origin {
+                        pid,
+                        ..Default::default()
+                    },
+                );
+            }
+            drop(socket);
+            let _ = std::fs::remove_file(&path);
+            info!("statsd receiver stopped listening on unix socket {}", path);
+        }
+    }
+}
+
+struct Flusher {
+    config: MetricServerAccess,
+    aggregator: Arc<Mutex<Aggregator>>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    process_labels: Arc<Mutex<ProcessLabels>>,
+    sender: DebugSender<BoxedPrometheusExtra>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<StatsdCounter>,
+}
+
+impl Flusher {
+    fn send(&self, series: Vec<TimeSeries>) {
+        let count = series.len() as u64;
+        let request = WriteRequest { timeseries: series };
+        let metrics = snappy_encode(&request.encode_to_vec());
+        if let Err(e) = self
+            .sender
+            .send(BoxedPrometheusExtra(Box::new(PrometheusExtra::new(
+                metrics,
+            ))))
+        {
+            debug!("statsd receiver failed to send data, because {:?}", e);
+            self.counter.queue_drop.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self, config: &Statsd) {
+        let timestamp = get_timestamp(self.ntp_diff.load(Ordering::Relaxed)).as_millis() as i64;
+        let mut series = {
+            let mut aggregator = self.aggregator.lock().unwrap();
+            let flushed = aggregator.flush(&config.percentiles, timestamp, Instant::now());
+            self.counter
+                .series
+                .store(aggregator.series.len() as u64, Ordering::Relaxed);
+            flushed
+        };
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        self.process_labels.lock().unwrap().0.clear();
+
+        while series.len() > MAX_SERIES_PER_REQUEST {
+            let rest = series.split_off(MAX_SERIES_PER_REQUEST);
+            self.send(series);
+            series = rest;
+        }
+        if !series.is_empty() {
+            self.send(series);
+        }
+    }
+}
+
+// Receives StatsD and DogStatsD metrics on UDP and unix datagram socket, and forwards them as
+// prometheus remote write in every flush interval, the same as the samples received by the
+// integration collector.
+//
+// The samples are aggregated in memory by the name and the labels, counters and the count and
+// sum of timers are cumulative as prometheus counters, so that they are not affected by the
+// flush interval.
+pub struct StatsdReceiver {
+    config: MetricServerAccess,
+    sender: DebugSender<BoxedPrometheusExtra>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<StatsdCounter>,
+
+    receiving: Arc<AtomicBool>,
+    running: Arc<(Mutex<bool>, Condvar)>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl StatsdReceiver {
+    pub fn new(
+        config: MetricServerAccess,
+        sender: DebugSender<BoxedPrometheusExtra>,
+        ntp_diff: Arc<AtomicI64>,
+    ) -> Self {
+        Self {
+            config,
+            sender,
+            ntp_diff,
+            counter: Arc::new(StatsdCounter::default()),
+            receiving: Arc::new(AtomicBool::new(false)),
+            running: Arc::new((Mutex::new(false), Condvar::new())),
+            threads: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<StatsdCounter> {
+        self.counter.clone()
+    }
+
+    pub fn start(&self) {
+        {
+            let (started, _) = &*self.running;
+            let mut started = started.lock().unwrap();
+            if *started {
+                return;
+            }
+            *started = true;
+        }
+        self.receiving.store(true, Ordering::Relaxed);
+
+        let aggregator = Arc::new(Mutex::new(Aggregator::new()));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let process_labels = Arc::new(Mutex::new(ProcessLabels::default()));
+        let receiver = Arc::new(Receiver {
+            config: self.config.clone(),
+            aggregator: aggregator.clone(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            process_labels: process_labels.clone(),
+            counter: self.counter.clone(),
+            running: self.receiving.clone(),
+        });
+        let mut threads = self.threads.lock().unwrap();
+
+        let udp_receiver = receiver.clone();
+        threads.push(
+            thread::Builder::new()
+                .name("statsd-udp".to_owned())
+                .spawn(move || udp_receiver.run_udp())
+                .unwrap(),
+        );
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        threads.push(
+            thread::Builder::new()
+                .name("statsd-unix".to_owned())
+                .spawn(move || receiver.run_unix())
+                .unwrap(),
+        );
+
+        let flusher = Flusher {
+            config: self.config.clone(),
+            aggregator,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            process_labels,
+            sender: self.sender.clone(),
+            ntp_diff: self.ntp_diff.clone(),
+            counter: self.counter.clone(),
+        };
+        let running = self.running.clone();
+        threads.push(
+            thread::Builder::new()
+                .name("statsd-flusher".to_owned())
+                .spawn(move || loop {
+                    let config = flusher.config.load().statsd.clone();
+
+                    let (running, timer) = &*running;
+                    let mut running = running.lock().unwrap();
+                    if !*running {
+                        break;
+                    }
+                    running = timer
+                        .wait_timeout(running, config.flush_interval.max(MIN_FLUSH_INTERVAL))
+                        .unwrap()
+                        .0;
+                    let stopped = !*running;
+                    drop(running);
+                    // samples received before stopping are flushed
+                    flusher.flush(&config);
+                    if stopped {
+                        break;
+                    }
+                })
+                .unwrap(),
+        );
+        info!("statsd receiver started");
+    }
+
+    pub fn notify_stop(&self) -> Vec<JoinHandle<()>> {
+        let (stopped, timer) = &*self.running;
+        {
+            let mut stopped = stopped.lock().unwrap();
+            if !*stopped {
+                return vec![];
+            }
+            *stopped = false;
+        }
+        self.receiving.store(false, Ordering::Relaxed);
+        timer.notify_one();
+        info!("notified stopping statsd receiver");
+        self.threads.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(
+            parse_line("page.views:1|c"),
+            Some(StatsdLine {
+                name: "page.views",
+                values: vec!["1"],
+                metric_type: MetricType::Counter,
+                sample_rate: 1.0,
+                tags: vec![],
+                container_id: None,
+            })
+        );
+        assert_eq!(
+            parse_line("request.latency:320:180|ms|@0.5|#env:prod,canary|c:abc123|T1656581400"),
+            Some(StatsdLine {
+                name: "request.latency",
+                values: vec!["320", "180"],
+                metric_type: MetricType::Timer,
+                sample_rate: 0.5,
+                tags: vec![("env", "prod"), ("canary", "true")],
+                container_id: Some("abc123"),
+            })
+        );
+        assert_eq!(parse_line("users.online:-3|g").unwrap().values, vec!["-3"]);
+        assert!(parse_line("no_type:1").is_none());
+        assert!(parse_line("bad_type:1|x").is_none());
+        assert!(parse_line("bad_rate:1|c|@2").is_none());
+        assert!(parse_line(":1|c").is_none());
+        assert!(parse_line("_e{5,4}:title|text").is_none());
+    }
+
+    fn labels(name: &str) -> BTreeMap<String, String> {
+        [("__name__".to_owned(), name.to_owned())].into()
+    }
+
+    fn values(series: &[TimeSeries]) -> BTreeMap<String, f64> {
+        series
+            .iter()
+            .map(|s| {
+                let name = s
+                    .labels
+                    .iter()
+                    .map(|l| format!("{}={}", l.name, l.value))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                (name, s.samples[0].value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aggregate() {
+        let mut aggregator = Aggregator::new();
+        let now = Instant::now();
+        for line in [
+            "hits:1|c|@0.5",
+            "hits:2|c",
+            "temp:10|g",
+            "temp:+5|g",
+            "latency:1:2:3:4|ms",
+            "uniques:a|s",
+            "uniques:b|s",
+            "uniques:a|s",
+        ] {
+            let line = parse_line(line).unwrap();
+            let name = line.name.to_owned();
+            assert!(aggregator.add(&line, labels(&name), 10, now).is_some());
+        }
+        assert!(aggregator
+            .add(&parse_line("full:1|c").unwrap(), labels("full"), 4, now)
+            .is_none());
+
+        let flushed = values(&aggregator.flush(&[50, 100], 1000, now));
+        assert_eq!(
+            flushed,
+            [
+                ("__name__=hits", 4.0),
+                ("__name__=temp", 15.0),
+                ("__name__=latency,quantile=0.5", 2.0),
+                ("__name__=latency,quantile=1", 4.0),
+                ("__name__=latency_count", 4.0),
+                ("__name__=latency_sum", 10.0),
+                ("__name__=uniques", 2.0),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect()
+        );
+
+        // counters are cumulative, sets are sent only when updated
+        aggregator.add(&parse_line("hits:1|c").unwrap(), labels("hits"), 10, now);
+        let flushed = values(&aggregator.flush(&[50], 2000, now));
+        assert_eq!(flushed.get("__name__=hits"), Some(&5.0));
+        assert_eq!(flushed.get("__name__=latency_count"), Some(&4.0));
+        assert!(!flushed.contains_key("__name__=latency,quantile=0.5"));
+        assert!(!flushed.contains_key("__name__=uniques"));
+
+        assert!(aggregator
+            .flush(&[50], 3000, now + SERIES_IDLE_TIMEOUT)
+            .is_empty());
+    }
+}
@@ -35,10 +35,13 @@ mod monitor;
 mod platform;
 mod plugin;
 mod policy;
+mod prometheus_scraper;
 pub mod rpc;
 mod sender;
+mod snmp_poller;
 pub mod trident;
 pub mod utils;
+mod xflow_collector;
 
 // for benchmarks
 #[doc(hidden)]
@@ -145,6 +145,9 @@ bitflags! {
         const TAP_SIDE = 1<<48;
         const TAP_PORT = 1<<49;
         const L7_PROTOCOL = 1<<51;
+        const DROP_REASON = 1<<53;
+        const MOUNT_POINT = 1<<54;
+        const DEVICE = 1<<55;
 
         const TUNNEL_IP_ID = 1<<62;
     }
@@ -319,6 +322,11 @@ pub struct Tagger {
     pub biz_type: u8,
     pub signal_source: SignalSource,
     pub pod_id: u32,
+    pub drop_reason: Option<String>,
+    pub mount_point: Option<String>,
+    // network device polled by SNMP
+    pub device_name: Option<String>,
+    pub interface_name: Option<String>,
     // request-reponse time span
     pub time_span: u32,
 }
@@ -354,6 +362,10 @@ impl Default for Tagger {
             endpoint: None,
             signal_source: SignalSource::default(),
             pod_id: 0,
+            drop_reason: None,
+            mount_point: None,
+            device_name: None,
+            interface_name: None,
             biz_type: 0,
             time_span: 0,
         }
@@ -412,6 +424,10 @@ impl From<Tagger> for metric::MiniTag {
                 endpoint: t.endpoint.unwrap_or_default(),
                 pod_id: t.pod_id,
                 biz_type: t.biz_type as u32,
+                drop_reason: t.drop_reason.unwrap_or_default(),
+                mount_point: t.mount_point.unwrap_or_default(),
+                device_name: t.device_name.unwrap_or_default(),
+                interface_name: t.interface_name.unwrap_or_default(),
             }),
         }
     }
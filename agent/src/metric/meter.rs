@@ -23,12 +23,20 @@ use public::proto::metric;
 const FLOW_ID: u32 = 1;
 const USAGE_ID: u32 = 4;
 const APP_ID: u32 = 5;
+const DROP_ID: u32 = 6;
+const FILE_IO_ID: u32 = 7;
+const TALKER_ID: u32 = 8;
+const DEVICE_ID: u32 = 9;
 
 #[derive(Serialize, Debug, Clone, Copy)]
 pub enum Meter {
     Flow(FlowMeter),
     App(AppMeter),
     Usage(UsageMeter),
+    Drop(DropMeter),
+    FileIo(FileIoMeter),
+    Talker(TalkerMeter),
+    Device(DeviceMeter),
 }
 
 impl Meter {
@@ -41,12 +49,28 @@ impl Meter {
     pub fn new_usage() -> Self {
         Meter::Usage(UsageMeter::default())
     }
+    pub fn new_drop() -> Self {
+        Meter::Drop(DropMeter::default())
+    }
+    pub fn new_file_io() -> Self {
+        Meter::FileIo(FileIoMeter::default())
+    }
+    pub fn new_talker() -> Self {
+        Meter::Talker(TalkerMeter::default())
+    }
+    pub fn new_device() -> Self {
+        Meter::Device(DeviceMeter::default())
+    }
 
     pub fn sequential_merge(&mut self, other: &Meter) {
         match (self, other) {
             (Meter::Flow(m), Meter::Flow(n)) => m.sequential_merge(n),
             (Meter::App(m), Meter::App(n)) => m.sequential_merge(n),
             (Meter::Usage(m), Meter::Usage(n)) => m.sequential_merge(n),
+            (Meter::Drop(m), Meter::Drop(n)) => m.sequential_merge(n),
+            (Meter::FileIo(m), Meter::FileIo(n)) => m.sequential_merge(n),
+            (Meter::Talker(m), Meter::Talker(n)) => m.sequential_merge(n),
+            (Meter::Device(m), Meter::Device(n)) => m.sequential_merge(n),
             (m, n) => panic!("Meter merge {:?} and {:?} mismatch type.", m, n),
         }
     }
@@ -55,6 +79,11 @@ impl Meter {
             Meter::Flow(m) => m.reverse(),
             Meter::App(m) => m.reverse(),
             Meter::Usage(m) => m.reverse(),
+            // drops have no direction
+            Meter::Drop(_) => (),
+            Meter::FileIo(_) => (),
+            Meter::Talker(_) => (),
+            Meter::Device(_) => (),
         }
     }
 }
@@ -67,18 +96,70 @@ impl From<Meter> for metric::Meter {
                 flow: Some(f.into()),
                 app: None,
                 usage: None,
+                drop: None,
+                file_io: None,
+                talker: None,
+                device: None,
             },
             Meter::App(f) => metric::Meter {
                 meter_id: APP_ID,
                 flow: None,
                 app: Some(f.into()),
                 usage: None,
+                drop: None,
+                file_io: None,
+                talker: None,
+                device: None,
             },
             Meter::Usage(f) => metric::Meter {
                 meter_id: USAGE_ID,
                 flow: None,
                 app: None,
                 usage: Some(f.into()),
+                drop: None,
+                file_io: None,
+                talker: None,
+                device: None,
+            },
+            Meter::Drop(f) => metric::Meter {
+                meter_id: DROP_ID,
+                flow: None,
+                app: None,
+                usage: None,
+                drop: Some(f.into()),
+                file_io: None,
+                talker: None,
+                device: None,
+            },
+            Meter::FileIo(f) => metric::Meter {
+                meter_id: FILE_IO_ID,
+                flow: None,
+                app: None,
+                usage: None,
+                drop: None,
+                file_io: Some(f.into()),
+                talker: None,
+                device: None,
+            },
+            Meter::Talker(f) => metric::Meter {
+                meter_id: TALKER_ID,
+                flow: None,
+                app: None,
+                usage: None,
+                drop: None,
+                file_io: None,
+                talker: Some(f.into()),
+                device: None,
+            },
+            Meter::Device(f) => metric::Meter {
+                meter_id: DEVICE_ID,
+                flow: None,
+                app: None,
+                usage: None,
+                drop: None,
+                file_io: None,
+                talker: None,
+                device: Some(f.into()),
             },
         }
     }
@@ -434,6 +515,8 @@ pub struct AppMeter {
     pub traffic: AppTraffic,
     pub latency: AppLatency,
     pub anomaly: AppAnomaly,
+    pub payload_size: AppPayloadSize,
+    pub latency_sketch: AppLatencySketch,
 }
 
 impl AppMeter {
@@ -441,9 +524,12 @@ impl AppMeter {
         self.traffic.sequential_merge(&other.traffic);
         self.latency.sequential_merge(&other.latency);
         self.anomaly.sequential_merge(&other.anomaly);
+        self.payload_size.sequential_merge(&other.payload_size);
+        self.latency_sketch.sequential_merge(&other.latency_sketch);
     }
     pub fn reverse(&mut self) {
-        self.traffic.reverse()
+        self.traffic.reverse();
+        self.payload_size.reverse();
     }
 }
 
@@ -453,6 +539,8 @@ impl From<AppMeter> for metric::AppMeter {
             traffic: Some(m.traffic.into()),
             latency: Some(m.latency.into()),
             anomaly: Some(m.anomaly.into()),
+            payload_size: Some(m.payload_size.into()),
+            latency_sketch: Some(m.latency_sketch.into()),
         }
     }
 }
@@ -513,6 +601,116 @@ impl From<AppLatency> for metric::AppLatency {
     }
 }
 
+// ln(gamma) of the latency sketch, gamma = (1 + 5%) / (1 - 5%) for 5% relative accuracy
+const APP_LATENCY_SKETCH_LN_GAMMA: f64 = 0.10008345855698263;
+// buckets cover 600 times below the max latency, smaller latencies are collapsed into the first one
+const APP_LATENCY_SKETCH_BUCKETS: usize = 64;
+
+// DDSketch of the response times in us. Bucket i counts the latencies in
+// (gamma^(offset+i-1), gamma^(offset+i)], so the quantiles estimated from the buckets are
+// within 5% of the real ones, except for the quantiles in the collapsed first bucket.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppLatencySketch {
+    pub offset: i32,
+    #[serde(serialize_with = "serialize_sketch_buckets")]
+    pub buckets: [u32; APP_LATENCY_SKETCH_BUCKETS],
+    pub count: u32,
+}
+
+fn serialize_sketch_buckets<S: serde::Serializer>(
+    buckets: &[u32; APP_LATENCY_SKETCH_BUCKETS],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(buckets.iter())
+}
+
+impl Default for AppLatencySketch {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            buckets: [0; APP_LATENCY_SKETCH_BUCKETS],
+            count: 0,
+        }
+    }
+}
+
+impl AppLatencySketch {
+    fn index(rrt: u32) -> i32 {
+        ((rrt.max(1) as f64).ln() / APP_LATENCY_SKETCH_LN_GAMMA).ceil() as i32
+    }
+
+    // moves the buckets up for a larger max index, collapsing the lowest ones
+    fn shift_to(&mut self, max_index: i32) {
+        let new_offset = max_index - APP_LATENCY_SKETCH_BUCKETS as i32 + 1;
+        let d = (new_offset - self.offset) as usize;
+        if d >= APP_LATENCY_SKETCH_BUCKETS {
+            self.buckets = [0; APP_LATENCY_SKETCH_BUCKETS];
+            self.buckets[0] = self.count;
+        } else {
+            let collapsed = self.buckets[..=d].iter().sum();
+            self.buckets.copy_within(d.., 0);
+            self.buckets[APP_LATENCY_SKETCH_BUCKETS - d..].fill(0);
+            self.buckets[0] = collapsed;
+        }
+        self.offset = new_offset;
+    }
+
+    fn add_n(&mut self, index: i32, n: u32) {
+        if self.count == 0 {
+            self.offset = index - APP_LATENCY_SKETCH_BUCKETS as i32 + 1;
+        } else if index >= self.offset + APP_LATENCY_SKETCH_BUCKETS as i32 {
+            self.shift_to(index);
+        }
+        let pos = (index - self.offset).max(0) as usize;
+        self.buckets[pos] += n;
+        self.count += n;
+    }
+
+    pub fn add(&mut self, rrt: u32) {
+        self.add_n(Self::index(rrt), 1);
+    }
+
+    pub fn sequential_merge(&mut self, other: &AppLatencySketch) {
+        if other.count == 0 {
+            return;
+        }
+        for (i, n) in other.buckets.iter().enumerate().rev() {
+            if *n > 0 {
+                self.add_n(other.offset + i as i32, *n);
+            }
+        }
+    }
+
+    // estimated latency of quantile q in [0, 1], 0 if there are no latencies
+    pub fn quantile(&self, q: f64) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64) as u64;
+        let mut n = 0;
+        for (i, c) in self.buckets.iter().enumerate() {
+            n += *c as u64;
+            if n > rank {
+                let index = (self.offset + i as i32) as f64;
+                // the middle of the bucket in relative error
+                let gamma = APP_LATENCY_SKETCH_LN_GAMMA.exp();
+                return (2.0 * (index * APP_LATENCY_SKETCH_LN_GAMMA).exp() / (gamma + 1.0)).round()
+                    as u32;
+            }
+        }
+        0
+    }
+}
+
+impl From<AppLatencySketch> for metric::AppLatencySketch {
+    fn from(m: AppLatencySketch) -> Self {
+        metric::AppLatencySketch {
+            offset: m.offset,
+            buckets: m.buckets.to_vec(),
+        }
+    }
+}
+
 #[derive(Serialize, Debug, Default, Clone, Copy)]
 pub struct AppAnomaly {
     pub client_error: u32,
@@ -538,6 +736,70 @@ impl From<AppAnomaly> for metric::AppAnomaly {
     }
 }
 
+// upper bounds (inclusive) of payload size buckets in bytes, the last bucket counts larger sizes
+pub const APP_PAYLOAD_SIZE_BOUNDS: [u32; 8] = [64, 256, 1024, 4096, 16384, 65536, 262144, 1048576];
+const APP_PAYLOAD_SIZE_BUCKETS: usize = APP_PAYLOAD_SIZE_BOUNDS.len() + 1;
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AppPayloadSize {
+    pub request_buckets: [u32; APP_PAYLOAD_SIZE_BUCKETS],
+    pub response_buckets: [u32; APP_PAYLOAD_SIZE_BUCKETS],
+    pub request_sum: u64,
+    pub response_sum: u64,
+    pub request_max: u32,
+    pub response_max: u32,
+}
+
+impl AppPayloadSize {
+    fn bucket(size: u32) -> usize {
+        APP_PAYLOAD_SIZE_BOUNDS.partition_point(|b| *b < size)
+    }
+
+    pub fn add_request(&mut self, size: u32) {
+        self.request_buckets[Self::bucket(size)] += 1;
+        self.request_sum += size as u64;
+        self.request_max = self.request_max.max(size);
+    }
+
+    pub fn add_response(&mut self, size: u32) {
+        self.response_buckets[Self::bucket(size)] += 1;
+        self.response_sum += size as u64;
+        self.response_max = self.response_max.max(size);
+    }
+
+    pub fn sequential_merge(&mut self, other: &AppPayloadSize) {
+        for (a, b) in self.request_buckets.iter_mut().zip(other.request_buckets) {
+            *a += b;
+        }
+        for (a, b) in self.response_buckets.iter_mut().zip(other.response_buckets) {
+            *a += b;
+        }
+        self.request_sum += other.request_sum;
+        self.response_sum += other.response_sum;
+        self.request_max = self.request_max.max(other.request_max);
+        self.response_max = self.response_max.max(other.response_max);
+    }
+
+    pub fn reverse(&mut self) {
+        swap(&mut self.request_buckets, &mut self.response_buckets);
+        swap(&mut self.request_sum, &mut self.response_sum);
+        swap(&mut self.request_max, &mut self.response_max);
+    }
+}
+
+impl From<AppPayloadSize> for metric::AppPayloadSize {
+    fn from(m: AppPayloadSize) -> Self {
+        metric::AppPayloadSize {
+            request_buckets: m.request_buckets.to_vec(),
+            response_buckets: m.response_buckets.to_vec(),
+            request_sum: m.request_sum,
+            response_sum: m.response_sum,
+            request_max: m.request_max,
+            response_max: m.response_max,
+        }
+    }
+}
+
 #[derive(Serialize, Debug, Default, Clone, Copy)]
 pub struct UsageMeter {
     pub packet_tx: u64,
@@ -584,3 +846,211 @@ impl From<UsageMeter> for metric::UsageMeter {
         }
     }
 }
+
+// Packets dropped inside the host stack, counted by the eBPF drop monitor
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct DropMeter {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl DropMeter {
+    pub fn sequential_merge(&mut self, other: &DropMeter) {
+        self.packets += other.packets;
+        self.bytes += other.bytes;
+    }
+}
+
+impl From<DropMeter> for metric::DropMeter {
+    fn from(m: DropMeter) -> Self {
+        metric::DropMeter {
+            packets: m.packets,
+            bytes: m.bytes,
+        }
+    }
+}
+
+// Regular file reads and writes of a process on a file system, counted by the eBPF file IO monitor
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct FileIoMeter {
+    pub read_count: u64,
+    pub read_bytes: u64,
+    pub read_latency_sum: u64, // us
+    pub read_latency_max: u64, // us
+    pub write_count: u64,
+    pub write_bytes: u64,
+    pub write_latency_sum: u64, // us
+    pub write_latency_max: u64, // us
+}
+
+impl FileIoMeter {
+    pub fn sequential_merge(&mut self, other: &FileIoMeter) {
+        self.read_count += other.read_count;
+        self.read_bytes += other.read_bytes;
+        self.read_latency_sum += other.read_latency_sum;
+        self.read_latency_max = self.read_latency_max.max(other.read_latency_max);
+        self.write_count += other.write_count;
+        self.write_bytes += other.write_bytes;
+        self.write_latency_sum += other.write_latency_sum;
+        self.write_latency_max = self.write_latency_max.max(other.write_latency_max);
+    }
+}
+
+impl From<FileIoMeter> for metric::FileIoMeter {
+    fn from(m: FileIoMeter) -> Self {
+        metric::FileIoMeter {
+            read_count: m.read_count,
+            read_bytes: m.read_bytes,
+            read_latency_sum: m.read_latency_sum,
+            read_latency_max: m.read_latency_max,
+            write_count: m.write_count,
+            write_bytes: m.write_bytes,
+            write_latency_sum: m.write_latency_sum,
+            write_latency_max: m.write_latency_max,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TalkerType {
+    #[default]
+    Source = 0,
+    Destination = 1,
+    Service = 2,
+}
+
+// A top talker of the minute, estimated by the space-saving algorithm in the collector
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct TalkerMeter {
+    pub talker_type: TalkerType,
+    pub rank: u32, // starts from 1
+    pub bytes: u64,
+    pub packets: u64,
+    // upper bound of the overestimation, the talker has at least bytes - bytes_error bytes
+    pub bytes_error: u64,
+}
+
+impl TalkerMeter {
+    pub fn sequential_merge(&mut self, other: &TalkerMeter) {
+        self.bytes += other.bytes;
+        self.packets += other.packets;
+        self.bytes_error += other.bytes_error;
+    }
+}
+
+impl From<TalkerMeter> for metric::TalkerMeter {
+    fn from(m: TalkerMeter) -> Self {
+        metric::TalkerMeter {
+            talker_type: m.talker_type as u32,
+            rank: m.rank,
+            bytes: m.bytes,
+            packets: m.packets,
+            bytes_error: m.bytes_error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_sketch() {
+        let (mut sketch, mut low, mut high) = (
+            AppLatencySketch::default(),
+            AppLatencySketch::default(),
+            AppLatencySketch::default(),
+        );
+        for rrt in 1..=10000 {
+            sketch.add(rrt);
+            if rrt <= 5000 {
+                low.add(rrt);
+            } else {
+                high.add(rrt);
+            }
+        }
+        assert_eq!(sketch.count, 10000);
+        for (q, real) in [(0.5, 5000.0), (0.95, 9500.0), (0.99, 9900.0)] {
+            let estimated = sketch.quantile(q) as f64;
+            assert!((estimated - real).abs() <= real * 0.05);
+        }
+
+        low.sequential_merge(&high);
+        assert_eq!(low, sketch);
+        assert_eq!(AppLatencySketch::default().quantile(0.5), 0);
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DeviceMeterType {
+    #[default]
+    Health = 0,
+    Interface = 1,
+}
+
+// Interface counters or health of a network device polled by SNMP
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct DeviceMeter {
+    pub meter_type: DeviceMeterType,
+
+    // increments of the interface counters since the last poll
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_discards: u64,
+    pub tx_discards: u64,
+    pub speed: u64, // bps
+    pub oper_status: u32,
+
+    pub cpu_usage: u32, // percent
+    pub memory_used: u64,
+    pub memory_total: u64,
+    pub uptime: u64, // s
+}
+
+impl DeviceMeter {
+    pub fn sequential_merge(&mut self, other: &DeviceMeter) {
+        self.rx_bytes += other.rx_bytes;
+        self.tx_bytes += other.tx_bytes;
+        self.rx_packets += other.rx_packets;
+        self.tx_packets += other.tx_packets;
+        self.rx_errors += other.rx_errors;
+        self.tx_errors += other.tx_errors;
+        self.rx_discards += other.rx_discards;
+        self.tx_discards += other.tx_discards;
+        // gauges are the latest
+        self.speed = other.speed;
+        self.oper_status = other.oper_status;
+        self.cpu_usage = other.cpu_usage;
+        self.memory_used = other.memory_used;
+        self.memory_total = other.memory_total;
+        self.uptime = other.uptime;
+    }
+}
+
+impl From<DeviceMeter> for metric::DeviceMeter {
+    fn from(m: DeviceMeter) -> Self {
+        metric::DeviceMeter {
+            meter_type: m.meter_type as u32,
+            rx_bytes: m.rx_bytes,
+            tx_bytes: m.tx_bytes,
+            rx_packets: m.rx_packets,
+            tx_packets: m.tx_packets,
+            rx_errors: m.rx_errors,
+            tx_errors: m.tx_errors,
+            rx_discards: m.rx_discards,
+            tx_discards: m.tx_discards,
+            speed: m.speed,
+            oper_status: m.oper_status,
+            cpu_usage: m.cpu_usage,
+            memory_used: m.memory_used,
+            memory_total: m.memory_total,
+            uptime: m.uptime,
+        }
+    }
+}
@@ -0,0 +1,618 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, RwLock,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use libc::{
+    bind, c_void, recv, send, setsockopt, sockaddr, sockaddr_nl, socket, socklen_t, timeval,
+    AF_NETLINK, AF_UNSPEC, ENOBUFS, SOCK_CLOEXEC, SOCK_RAW, SOL_SOCKET, SO_RCVBUF, SO_RCVTIMEO,
+};
+use log::{debug, info, warn};
+
+use crate::utils::stats;
+
+const NETLINK_NETFILTER: i32 = 12;
+
+const NLMSG_HDRLEN: usize = 16;
+const NFGENMSG_LEN: usize = 4;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_DUMP: u16 = 0x300;
+
+const NFNL_SUBSYS_CTNETLINK: u16 = 1;
+const IPCTNL_MSG_CT_NEW: u16 = 0;
+const IPCTNL_MSG_CT_GET: u16 = 1;
+const IPCTNL_MSG_CT_DELETE: u16 = 2;
+// multicast groups, bit (group - 1) in nl_groups
+const NFNLGRP_CONNTRACK_NEW: u32 = 1;
+const NFNLGRP_CONNTRACK_DESTROY: u32 = 3;
+
+const NLA_TYPE_MASK: u16 = 0x3fff;
+const CTA_TUPLE_ORIG: u16 = 1;
+const CTA_TUPLE_REPLY: u16 = 2;
+const CTA_STATUS: u16 = 3;
+const CTA_TUPLE_IP: u16 = 1;
+const CTA_TUPLE_PROTO: u16 = 2;
+const CTA_IP_V4_SRC: u16 = 1;
+const CTA_IP_V4_DST: u16 = 2;
+const CTA_IP_V6_SRC: u16 = 3;
+const CTA_IP_V6_DST: u16 = 4;
+const CTA_PROTO_NUM: u16 = 1;
+const CTA_PROTO_SRC_PORT: u16 = 2;
+const CTA_PROTO_DST_PORT: u16 = 3;
+const IPS_SRC_NAT: u32 = 1 << 4;
+const IPS_DST_NAT: u32 = 1 << 5;
+
+const RECV_BUFFER_SIZE: usize = 64 << 10;
+const SOCKET_RCVBUF_SIZE: i32 = 8 << 20;
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Tuple {
+    proto: u8,
+    src: (IpAddr, u16),
+    dst: (IpAddr, u16),
+}
+
+// The addresses before NAT of a translated connection, None if not translated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NatRealAddrs {
+    pub src: Option<(IpAddr, u16)>,
+    pub dst: Option<(IpAddr, u16)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EventType {
+    New,
+    Delete,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ConntrackEvent {
+    event_type: EventType,
+    status: u32,
+    orig: Tuple,
+    reply: Tuple,
+}
+
+impl ConntrackEvent {
+    // Returns the tuples on the wire and the real addresses of them.
+    //
+    // For a connection from A:a to the virtual address V:v, which is translated to
+    // N:n -> B:b by SNAT and DNAT, packets are A:a -> V:v before NAT, e.g. on the pod veth,
+    // and N:n -> B:b after NAT, e.g. on the node NIC. Both are marked with the real
+    // addresses A:a -> B:b so that they can be linked.
+    fn translations(&self) -> Vec<(Tuple, NatRealAddrs)> {
+        let translated = Tuple {
+            proto: self.reply.proto,
+            src: self.reply.dst,
+            dst: self.reply.src,
+        };
+        let snat = self.status & IPS_SRC_NAT != 0 && translated.src != self.orig.src;
+        let dnat = self.status & IPS_DST_NAT != 0 && translated.dst != self.orig.dst;
+        let mut result = vec![];
+        if dnat {
+            result.push((
+                self.orig,
+                NatRealAddrs {
+                    src: None,
+                    dst: Some(translated.dst),
+                },
+            ));
+        }
+        if snat {
+            result.push((
+                translated,
+                NatRealAddrs {
+                    src: Some(self.orig.src),
+                    dst: None,
+                },
+            ));
+        }
+        result
+    }
+}
+
+fn nla_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+// Iterates netlink attributes as (type, payload)
+fn attributes(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 4 {
+            return None;
+        }
+        let len = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let nla_type = u16::from_ne_bytes([data[2], data[3]]) & NLA_TYPE_MASK;
+        if len < 4 || len > data.len() {
+            return None;
+        }
+        let payload = &data[4..len];
+        data = &data[nla_align(len).min(data.len())..];
+        Some((nla_type, payload))
+    })
+}
+
+fn parse_ip(payload: &[u8]) -> Option<IpAddr> {
+    match payload.len() {
+        4 => Some(IpAddr::from(Ipv4Addr::new(
+            payload[0], payload[1], payload[2], payload[3],
+        ))),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(payload);
+            Some(IpAddr::from(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+fn parse_tuple(data: &[u8]) -> Option<Tuple> {
+    let (mut src_ip, mut dst_ip) = (None, None);
+    let (mut proto, mut src_port, mut dst_port) = (None, 0, 0);
+    for (nla_type, payload) in attributes(data) {
+        match nla_type {
+            CTA_TUPLE_IP => {
+                for (t, p) in attributes(payload) {
+                    match t {
+                        CTA_IP_V4_SRC | CTA_IP_V6_SRC => src_ip = parse_ip(p),
+                        CTA_IP_V4_DST | CTA_IP_V6_DST => dst_ip = parse_ip(p),
+                        _ => (),
+                    }
+                }
+            }
+            CTA_TUPLE_PROTO => {
+                for (t, p) in attributes(payload) {
+                    match (t, p.len()) {
+                        (CTA_PROTO_NUM, 1) => proto = Some(p[0]),
+                        (CTA_PROTO_SRC_PORT, 2) => src_port = u16::from_be_bytes([p[0], p[1]]),
+                        (CTA_PROTO_DST_PORT, 2) => dst_port = u16::from_be_bytes([p[0], p[1]]),
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Some(Tuple {
+        proto: proto?,
+        src: (src_ip?, src_port),
+        dst: (dst_ip?, dst_port),
+    })
+}
+
+// Parses a ctnetlink message without the netlink header
+fn parse_conntrack(msg_type: u16, data: &[u8]) -> Option<ConntrackEvent> {
+    if msg_type >> 8 != NFNL_SUBSYS_CTNETLINK || data.len() < NFGENMSG_LEN {
+        return None;
+    }
+    let event_type = match msg_type & 0xff {
+        IPCTNL_MSG_CT_NEW => EventType::New,
+        IPCTNL_MSG_CT_DELETE => EventType::Delete,
+        _ => return None,
+    };
+    let (mut orig, mut reply, mut status) = (None, None, 0);
+    for (nla_type, payload) in attributes(&data[NFGENMSG_LEN..]) {
+        match (nla_type, payload.len()) {
+            (CTA_TUPLE_ORIG, _) => orig = parse_tuple(payload),
+            (CTA_TUPLE_REPLY, _) => reply = parse_tuple(payload),
+            (CTA_STATUS, 4) => {
+                status = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]])
+            }
+            _ => (),
+        }
+    }
+    Some(ConntrackEvent {
+        event_type,
+        status,
+        orig: orig?,
+        reply: reply?,
+    })
+}
+
+#[derive(Default)]
+pub struct ConntrackCounter {
+    events: AtomicU64,
+    entries: AtomicU64,
+    // translations not recorded because the table is full
+    dropped: AtomicU64,
+    // events lost because the socket buffer overflows, the table is dumped again
+    resyncs: AtomicU64,
+}
+
+impl stats::RefCountable for ConntrackCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "events",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.events.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "entries",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.entries.load(Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "resyncs",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.resyncs.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+lazy_static! {
+    // NAT translations of the kernel conntrack by the tuples on the wire
+    static ref NAT_TABLE: RwLock<HashMap<Tuple, NatRealAddrs>> = RwLock::new(HashMap::new());
+}
+
+// Looks up the real addresses of a connection translated by the kernel conntrack, the
+// tuple can be in either direction.
+pub fn lookup_nat(proto: u8, src: (IpAddr, u16), dst: (IpAddr, u16)) -> Option<NatRealAddrs> {
+    let table = NAT_TABLE.read().unwrap();
+    if table.is_empty() {
+        return None;
+    }
+    if let Some(addrs) = table.get(&Tuple { proto, src, dst }) {
+        return Some(*addrs);
+    }
+    table
+        .get(&Tuple {
+            proto,
+            src: dst,
+            dst: src,
+        })
+        .map(|addrs| NatRealAddrs {
+            src: addrs.dst,
+            dst: addrs.src,
+        })
+}
+
+fn update_table(event: &ConntrackEvent, max_entries: usize, counter: &ConntrackCounter) {
+    let translations = event.translations();
+    if translations.is_empty() {
+        return;
+    }
+    let mut table = NAT_TABLE.write().unwrap();
+    for (tuple, addrs) in translations {
+        match event.event_type {
+            EventType::New => {
+                if table.len() >= max_entries && !table.contains_key(&tuple) {
+                    counter.dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                table.insert(tuple, addrs);
+            }
+            EventType::Delete => {
+                table.remove(&tuple);
+            }
+        }
+    }
+    counter.entries.store(table.len() as u64, Ordering::Relaxed);
+}
+
+fn open_socket(groups: u32) -> io::Result<OwnedFd> {
+    unsafe {
+        let fd = socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, NETLINK_NETFILTER);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let mut addr: sockaddr_nl = mem::zeroed();
+        addr.nl_family = AF_NETLINK as u16;
+        addr.nl_groups = groups;
+        if bind(
+            fd.as_raw_fd(),
+            &addr as *const sockaddr_nl as *const sockaddr,
+            mem::size_of::<sockaddr_nl>() as socklen_t,
+        ) < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let timeout = timeval {
+            tv_sec: RECV_TIMEOUT.as_secs() as _,
+            tv_usec: 0,
+        };
+        setsockopt(
+            fd.as_raw_fd(),
+            SOL_SOCKET,
+            SO_RCVTIMEO,
+            &timeout as *const timeval as *const c_void,
+            mem::size_of::<timeval>() as socklen_t,
+        );
+        // failure is not fatal, events may be lost and the table is dumped again
+        setsockopt(
+            fd.as_raw_fd(),
+            SOL_SOCKET,
+            SO_RCVBUF,
+            &SOCKET_RCVBUF_SIZE as *const i32 as *const c_void,
+            mem::size_of::<i32>() as socklen_t,
+        );
+        Ok(fd)
+    }
+}
+
+// Receives netlink messages and calls f with (type, payload), returns false if the dump is done
+fn recv_messages<F: FnMut(u16, &[u8])>(
+    fd: &OwnedFd,
+    buffer: &mut [u8],
+    mut f: F,
+) -> io::Result<bool> {
+    let n = unsafe {
+        recv(
+            fd.as_raw_fd(),
+            buffer.as_mut_ptr() as *mut c_void,
+            buffer.len(),
+            0,
+        )
+    };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut data = &buffer[..n as usize];
+    while data.len() >= NLMSG_HDRLEN {
+        let len = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let msg_type = u16::from_ne_bytes([data[4], data[5]]);
+        if len < NLMSG_HDRLEN || len > data.len() {
+            break;
+        }
+        match msg_type {
+            NLMSG_DONE => return Ok(false),
+            NLMSG_ERROR => {
+                let errno = if len >= NLMSG_HDRLEN + 4 {
+                    i32::from_ne_bytes([data[16], data[17], data[18], data[19]])
+                } else {
+                    0
+                };
+                if errno != 0 {
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+            }
+            _ => f(msg_type, &data[NLMSG_HDRLEN..len]),
+        }
+        data = &data[nla_align(len).min(data.len())..];
+    }
+    Ok(true)
+}
+
+// Watches the NAT translations of the kernel conntrack with NFNETLINK, so that flows
+// captured before and after NAT can be linked by their real addresses.
+//
+// The table is dumped when started, and updated with the new and destroy events. Requires
+// CAP_NET_ADMIN and conntrack events enabled (sysctl net.netfilter.nf_conntrack_events).
+pub struct ConntrackWatcher {
+    max_entries: usize,
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    counter: Arc<ConntrackCounter>,
+}
+
+impl ConntrackWatcher {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            running: Default::default(),
+            thread: Mutex::new(None),
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<ConntrackCounter> {
+        self.counter.clone()
+    }
+
+    fn dump(max_entries: usize, counter: &ConntrackCounter) -> io::Result<()> {
+        let fd = open_socket(0)?;
+        let mut request = [0u8; NLMSG_HDRLEN + NFGENMSG_LEN];
+        request[0..4].copy_from_slice(&(request.len() as u32).to_ne_bytes());
+        request[4..6]
+            .copy_from_slice(&((NFNL_SUBSYS_CTNETLINK << 8) | IPCTNL_MSG_CT_GET).to_ne_bytes());
+        request[6..8].copy_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        request[8..12].copy_from_slice(&1u32.to_ne_bytes());
+        // nfgenmsg, family AF_UNSPEC dumps both IPv4 and IPv6
+        request[NLMSG_HDRLEN] = AF_UNSPEC as u8;
+        let n = unsafe {
+            send(
+                fd.as_raw_fd(),
+                request.as_ptr() as *const c_void,
+                request.len(),
+                0,
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        NAT_TABLE.write().unwrap().clear();
+        let mut buffer = vec![0u8; RECV_BUFFER_SIZE];
+        while recv_messages(&fd, &mut buffer, |msg_type, data| {
+            if let Some(event) = parse_conntrack(msg_type, data) {
+                update_table(&event, max_entries, counter);
+            }
+        })? {}
+        Ok(())
+    }
+
+    fn run(running: Arc<AtomicBool>, max_entries: usize, counter: Arc<ConntrackCounter>) {
+        let groups = (1 << (NFNLGRP_CONNTRACK_NEW - 1)) | (1 << (NFNLGRP_CONNTRACK_DESTROY - 1));
+        // subscribe before dumping so that no translation is missed in between
+        let fd = match open_socket(groups) {
+            Ok(fd) => fd,
+            Err(e) => {
+                warn!("conntrack netlink socket failed: {}", e);
+                return;
+            }
+        };
+        let mut need_dump = true;
+        let mut buffer = vec![0u8; RECV_BUFFER_SIZE];
+        while running.load(Ordering::Relaxed) {
+            if need_dump {
+                match Self::dump(max_entries, &counter) {
+                    Ok(_) => info!(
+                        "conntrack dumped {} nat translations",
+                        counter.entries.load(Ordering::Relaxed)
+                    ),
+                    Err(e) => warn!("conntrack dump failed: {}", e),
+                }
+                need_dump = false;
+            }
+            let result = recv_messages(&fd, &mut buffer, |msg_type, data| {
+                counter.events.fetch_add(1, Ordering::Relaxed);
+                if let Some(event) = parse_conntrack(msg_type, data) {
+                    update_table(&event, max_entries, &counter);
+                }
+            });
+            match result {
+                Ok(_) => (),
+                Err(e) if e.raw_os_error() == Some(ENOBUFS) => {
+                    debug!("conntrack events overflow, dump again");
+                    counter.resyncs.fetch_add(1, Ordering::Relaxed);
+                    need_dump = true;
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    warn!("conntrack netlink recv failed: {}", e);
+                    thread::sleep(RECV_TIMEOUT);
+                }
+            }
+        }
+        NAT_TABLE.write().unwrap().clear();
+        counter.entries.store(0, Ordering::Relaxed);
+    }
+
+    pub fn start(&self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let (running, max_entries, counter) =
+            (self.running.clone(), self.max_entries, self.counter.clone());
+        match thread::Builder::new()
+            .name("conntrack-watcher".to_owned())
+            .spawn(move || Self::run(running, max_entries, counter))
+        {
+            Ok(handle) => {
+                self.thread.lock().unwrap().replace(handle);
+                info!("conntrack watcher started");
+            }
+            Err(e) => {
+                warn!("conntrack watcher thread spawn failed: {}", e);
+                self.running.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn stop(&self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(handle) = self.thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+        info!("conntrack watcher stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nla(nla_type: u16, payload: &[u8]) -> Vec<u8> {
+        let mut v = ((payload.len() + 4) as u16).to_ne_bytes().to_vec();
+        v.extend_from_slice(&nla_type.to_ne_bytes());
+        v.extend_from_slice(payload);
+        v.resize(nla_align(v.len()), 0);
+        v
+    }
+
+    fn tuple(nla_type: u16, src: [u8; 4], sport: u16, dst: [u8; 4], dport: u16) -> Vec<u8> {
+        let mut ip = nla(CTA_IP_V4_SRC, &src);
+        ip.extend(nla(CTA_IP_V4_DST, &dst));
+        let mut proto = nla(CTA_PROTO_NUM, &[6]);
+        proto.extend(nla(CTA_PROTO_SRC_PORT, &sport.to_be_bytes()));
+        proto.extend(nla(CTA_PROTO_DST_PORT, &dport.to_be_bytes()));
+        let mut t = nla(CTA_TUPLE_IP | 0x8000, &ip);
+        t.extend(nla(CTA_TUPLE_PROTO | 0x8000, &proto));
+        nla(nla_type | 0x8000, &t)
+    }
+
+    #[test]
+    fn parse_snat_dnat() {
+        // pod 10.0.0.2:40000 -> cluster ip 172.16.0.10:80,
+        // translated to node 192.168.0.1:50000 -> backend 10.0.1.3:8080
+        let mut msg = vec![AF_UNSPEC as u8, 0, 0, 0];
+        msg.extend(tuple(
+            CTA_TUPLE_ORIG,
+            [10, 0, 0, 2],
+            40000,
+            [172, 16, 0, 10],
+            80,
+        ));
+        msg.extend(tuple(
+            CTA_TUPLE_REPLY,
+            [10, 0, 1, 3],
+            8080,
+            [192, 168, 0, 1],
+            50000,
+        ));
+        msg.extend(nla(CTA_STATUS, &(IPS_SRC_NAT | IPS_DST_NAT).to_be_bytes()));
+
+        let event =
+            parse_conntrack((NFNL_SUBSYS_CTNETLINK << 8) | IPCTNL_MSG_CT_NEW, &msg).unwrap();
+        assert_eq!(event.event_type, EventType::New);
+        let pod = (IpAddr::from([10, 0, 0, 2]), 40000);
+        let backend = (IpAddr::from([10, 0, 1, 3]), 8080);
+        let node = (IpAddr::from([192, 168, 0, 1]), 50000);
+        let translations = event.translations();
+        assert_eq!(translations.len(), 2);
+        assert_eq!(translations[0].1.dst, Some(backend));
+        assert_eq!(translations[1].0.src, node);
+        assert_eq!(translations[1].0.dst, backend);
+        assert_eq!(translations[1].1.src, Some(pod));
+
+        let counter = ConntrackCounter::default();
+        update_table(&event, 16, &counter);
+        let addrs = lookup_nat(6, backend, node).unwrap();
+        assert_eq!(addrs.src, None);
+        assert_eq!(addrs.dst, Some(pod));
+
+        let deleted = parse_conntrack((NFNL_SUBSYS_CTNETLINK << 8) | IPCTNL_MSG_CT_DELETE, &msg);
+        update_table(&deleted.unwrap(), 16, &counter);
+        assert!(lookup_nat(6, node, backend).is_none());
+    }
+}
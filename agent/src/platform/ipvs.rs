@@ -0,0 +1,387 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Condvar, Mutex, RwLock,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+
+use super::conntrack::NatRealAddrs;
+use crate::utils::stats;
+
+const IPVS_SERVICES_PATH: &str = "/proc/net/ip_vs";
+const IPVS_CONNECTIONS_PATH: &str = "/proc/net/ip_vs_conn";
+
+type Endpoint = (IpAddr, u16);
+
+fn parse_proto(s: &str) -> Option<u8> {
+    match s {
+        "TCP" => Some(6),
+        "UDP" => Some(17),
+        "SCTP" => Some(132),
+        _ => None,
+    }
+}
+
+// IPv4 addresses are in hex like `0A600001`, IPv6 addresses are like `2001:0db8:...`, with
+// brackets in the services table
+fn parse_ip(s: &str) -> Option<IpAddr> {
+    let s = s.trim_start_matches('[').trim_end_matches(']');
+    if s.contains(':') {
+        s.parse::<Ipv6Addr>().ok().map(IpAddr::from)
+    } else {
+        u32::from_str_radix(s, 16)
+            .ok()
+            .map(|ip| IpAddr::from(Ipv4Addr::from(ip)))
+    }
+}
+
+fn parse_port(s: &str) -> Option<u16> {
+    u16::from_str_radix(s, 16).ok()
+}
+
+// Parses `ADDR:PORT` of the services table
+fn parse_endpoint(s: &str) -> Option<Endpoint> {
+    let (ip, port) = s.rsplit_once(':')?;
+    Some((parse_ip(ip)?, parse_port(port)?))
+}
+
+#[derive(Default)]
+struct IpvsTable {
+    // (protocol, client, virtual server) -> real server chosen for the connection
+    connections: HashMap<(u8, Endpoint, Endpoint), Endpoint>,
+    // (protocol, virtual server) -> real server, for virtual servers with only one real server
+    services: HashMap<(u8, Endpoint), Endpoint>,
+}
+
+// Parses /proc/net/ip_vs like:
+//
+//   Prot LocalAddress:Port Scheduler Flags
+//     -> RemoteAddress:Port Forward Weight ActiveConn InActConn
+//   TCP  0A600001:0050 rr
+//     -> 0A000103:1F90      Masq    1      0          0
+fn parse_services(content: &str) -> HashMap<(u8, Endpoint), Endpoint> {
+    let mut real_servers: HashMap<(u8, Endpoint), Vec<Endpoint>> = HashMap::new();
+    let mut current = None;
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("->") => {
+                let (Some(key), Some(real)) = (current, fields.next().and_then(parse_endpoint))
+                else {
+                    continue;
+                };
+                real_servers.entry(key).or_default().push(real);
+            }
+            Some(proto) => {
+                current = parse_proto(proto).zip(fields.next().and_then(parse_endpoint));
+                if let Some(key) = current {
+                    real_servers.entry(key).or_default();
+                }
+            }
+            None => (),
+        }
+    }
+    real_servers
+        .into_iter()
+        .filter(|(_, reals)| reals.len() == 1)
+        .map(|(key, reals)| (key, reals[0]))
+        .collect()
+}
+
+// Parses /proc/net/ip_vs_conn like:
+//
+//   Pro FromIP   FPrt ToIP     TPrt DestIP   DPrt State       Expires PEName PEData
+//   TCP 0A000002 9C40 0A600001 0050 0A000103 1F90 ESTABLISHED     899
+fn parse_connections(
+    content: &str,
+    max_entries: usize,
+) -> (HashMap<(u8, Endpoint, Endpoint), Endpoint>, u64) {
+    let mut connections = HashMap::new();
+    let mut dropped = 0;
+    for line in content.lines().skip(1) {
+        let fields = line.split_whitespace().take(7).collect::<Vec<_>>();
+        if fields.len() < 7 {
+            continue;
+        }
+        let parsed = (|| {
+            let endpoint = |i: usize| Some((parse_ip(fields[i])?, parse_port(fields[i + 1])?));
+            Some((
+                parse_proto(fields[0])?,
+                endpoint(1)?,
+                endpoint(3)?,
+                endpoint(5)?,
+            ))
+        })();
+        let Some((proto, client, virtual_server, real_server)) = parsed else {
+            continue;
+        };
+        if connections.len() >= max_entries {
+            dropped += 1;
+            continue;
+        }
+        connections.insert((proto, client, virtual_server), real_server);
+    }
+    (connections, dropped)
+}
+
+lazy_static! {
+    static ref IPVS_TABLE: RwLock<IpvsTable> = RwLock::new(IpvsTable::default());
+}
+
+// Looks up the real server of a connection to an IPVS virtual server, the tuple can be in
+// either direction.
+pub fn lookup_nat(proto: u8, src: Endpoint, dst: Endpoint) -> Option<NatRealAddrs> {
+    let table = IPVS_TABLE.read().unwrap();
+    if let Some(real) = table.connections.get(&(proto, src, dst)) {
+        return Some(NatRealAddrs {
+            src: None,
+            dst: Some(*real),
+        });
+    }
+    if let Some(real) = table.connections.get(&(proto, dst, src)) {
+        return Some(NatRealAddrs {
+            src: Some(*real),
+            dst: None,
+        });
+    }
+    if let Some(real) = table.services.get(&(proto, dst)) {
+        return Some(NatRealAddrs {
+            src: None,
+            dst: Some(*real),
+        });
+    }
+    table.services.get(&(proto, src)).map(|real| NatRealAddrs {
+        src: Some(*real),
+        dst: None,
+    })
+}
+
+#[derive(Default)]
+pub struct IpvsCounter {
+    services: AtomicU64,
+    connections: AtomicU64,
+    // connections not recorded because the table is full
+    dropped: AtomicU64,
+    sync_errors: AtomicU64,
+}
+
+impl stats::RefCountable for IpvsCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "services",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.services.load(Ordering::Relaxed)),
+            ),
+            (
+                "connections",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.connections.load(Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sync_errors",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.sync_errors.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Reads the IPVS virtual servers and connections periodically, so that flows towards a
+// ClusterIP or LB VIP handled by IPVS can be marked with the real server chosen.
+//
+// IPVS sends no netlink events for connections, so the tables are read from procfs of the
+// agent's network namespace.
+pub struct IpvsWatcher {
+    max_entries: usize,
+    interval: Duration,
+    running: Arc<(Mutex<bool>, Condvar)>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    counter: Arc<IpvsCounter>,
+}
+
+impl IpvsWatcher {
+    pub fn new(max_entries: usize, interval: Duration) -> Self {
+        Self {
+            max_entries,
+            interval,
+            running: Default::default(),
+            thread: Mutex::new(None),
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<IpvsCounter> {
+        self.counter.clone()
+    }
+
+    fn sync(max_entries: usize, counter: &IpvsCounter) {
+        let services = match fs::read_to_string(IPVS_SERVICES_PATH) {
+            Ok(content) => parse_services(&content),
+            Err(e) => {
+                warn!("read {} failed: {}", IPVS_SERVICES_PATH, e);
+                counter.sync_errors.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        let (connections, dropped) = match fs::read_to_string(IPVS_CONNECTIONS_PATH) {
+            Ok(content) => parse_connections(&content, max_entries),
+            Err(e) => {
+                warn!("read {} failed: {}", IPVS_CONNECTIONS_PATH, e);
+                counter.sync_errors.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        counter
+            .services
+            .store(services.len() as u64, Ordering::Relaxed);
+        counter
+            .connections
+            .store(connections.len() as u64, Ordering::Relaxed);
+        counter.dropped.fetch_add(dropped, Ordering::Relaxed);
+        *IPVS_TABLE.write().unwrap() = IpvsTable {
+            connections,
+            services,
+        };
+    }
+
+    fn run(
+        running: Arc<(Mutex<bool>, Condvar)>,
+        max_entries: usize,
+        interval: Duration,
+        counter: Arc<IpvsCounter>,
+    ) {
+        let (lock, stop_notify) = &*running;
+        loop {
+            Self::sync(max_entries, &counter);
+            let guard = lock.lock().unwrap();
+            if !*stop_notify.wait_timeout(guard, interval).unwrap().0 {
+                break;
+            }
+        }
+        *IPVS_TABLE.write().unwrap() = IpvsTable::default();
+        counter.services.store(0, Ordering::Relaxed);
+        counter.connections.store(0, Ordering::Relaxed);
+    }
+
+    pub fn start(&self) {
+        {
+            let mut running = self.running.0.lock().unwrap();
+            if *running {
+                return;
+            }
+            *running = true;
+        }
+        let (running, max_entries, interval, counter) = (
+            self.running.clone(),
+            self.max_entries,
+            self.interval,
+            self.counter.clone(),
+        );
+        match thread::Builder::new()
+            .name("ipvs-watcher".to_owned())
+            .spawn(move || Self::run(running, max_entries, interval, counter))
+        {
+            Ok(handle) => {
+                self.thread.lock().unwrap().replace(handle);
+                info!("ipvs watcher started");
+            }
+            Err(e) => {
+                warn!("ipvs watcher thread spawn failed: {}", e);
+                *self.running.0.lock().unwrap() = false;
+            }
+        }
+    }
+
+    pub fn stop(&self) {
+        {
+            let mut running = self.running.0.lock().unwrap();
+            if !*running {
+                return;
+            }
+            *running = false;
+        }
+        self.running.1.notify_one();
+        if let Some(handle) = self.thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+        info!("ipvs watcher stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tables() {
+        let services = parse_services(
+            "IP Virtual Server version 1.2.1 (size=4096)
+Prot LocalAddress:Port Scheduler Flags
+  -> RemoteAddress:Port Forward Weight ActiveConn InActConn
+TCP  0A600001:0050 rr
+  -> 0A000103:1F90      Masq    1      0          0
+  -> 0A000104:1F90      Masq    1      0          0
+UDP  0A60000A:0035 rr
+  -> 0A000105:0035      Masq    1      0          0
+TCP  [fd00:0000:0000:0000:0000:0000:0000:0001]:01BB rr
+  -> [fd00:0000:0000:0000:0000:0000:0001:0002]:2000      Masq    1      0          0
+FWM  00000001 rr
+  -> 0A000106:0050      Masq    1      0          0
+",
+        );
+        assert_eq!(services.len(), 2);
+        assert_eq!(
+            services.get(&(17, (IpAddr::from([10, 96, 0, 10]), 53))),
+            Some(&(IpAddr::from([10, 0, 1, 5]), 53))
+        );
+        assert_eq!(
+            services.get(&(6, ("fd00::1".parse().unwrap(), 443))),
+            Some(&("fd00::1:2".parse().unwrap(), 0x2000))
+        );
+
+        let (connections, dropped) = parse_connections(
+            "Pro FromIP   FPrt ToIP     TPrt DestIP   DPrt State       Expires PEName PEData
+TCP 0A000002 9C40 0A600001 0050 0A000103 1F90 ESTABLISHED     899
+TCP 0A000002 9C41 0A600001 0050 0A000104 1F90 ESTABLISHED     899
+",
+            1,
+        );
+        assert_eq!(dropped, 1);
+        assert_eq!(
+            connections.get(&(
+                6,
+                (IpAddr::from([10, 0, 0, 2]), 40000),
+                (IpAddr::from([10, 96, 0, 1]), 80)
+            )),
+            Some(&(IpAddr::from([10, 0, 1, 3]), 8080))
+        );
+    }
+}
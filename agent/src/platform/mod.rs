@@ -17,8 +17,12 @@
 cfg_if::cfg_if! {
     if #[cfg(target_os = "linux")] {
         mod libvirt_xml_extractor;
+        pub mod conntrack;
+        pub mod ipvs;
         pub mod kubernetes;
 
+        pub use conntrack::ConntrackWatcher;
+        pub use ipvs::IpvsWatcher;
         pub use libvirt_xml_extractor::LibvirtXmlExtractor;
         pub use kubernetes::{ApiWatcher, GenericPoller, Poller};
     }
@@ -129,6 +129,8 @@ impl Synchronizer {
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn set_process_listener(&self, process_listener: &Arc<ProcessListener>) {
         process_listener.register("proc.gprocess_info", set_proc_scan_process_datas);
+        // sync the processes as soon as they change
+        process_listener.set_notifier("proc.gprocess_info", self.timer.clone());
     }
 
     #[cfg(target_os = "linux")]
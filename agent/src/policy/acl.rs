@@ -76,7 +76,8 @@ impl TryFrom<FlowAcl> for Acl {
     type Error = String;
 
     fn try_from(a: FlowAcl) -> Result<Self, Self::Error> {
-        let tap_type = CaptureNetworkType::try_from((a.tap_type.unwrap_or_default() & 0xff) as u16);
+        let tap_type =
+            CaptureNetworkType::try_from((a.tap_type.unwrap_or_default() & 0xffff) as u16);
         if tap_type.is_err() {
             return Err(format!(
                 "Acl tap_type parse error: {:?}.\n",
@@ -35,8 +35,6 @@ use crate::common::port_range::{PortRange, PortRangeList};
 use npb_pcap_policy::PolicyData;
 
 const MAX_ACL_PROTOCOL: usize = 255;
-const MAX_TAP_TYPE: usize = 256;
-const MAX_FAST_PATH: usize = MAX_TAP_TYPE * (super::MAX_QUEUE_COUNT + 1);
 const NET_IP_MAX: u32 = 32;
 const NET_IP_LEN: u32 = 16;
 const NET_IP_MASK: u32 = u32::MAX << NET_IP_LEN;
@@ -51,7 +49,9 @@ struct PolicyTableItem {
 
 pub struct FastPath {
     interest_table: RwLock<Vec<PortRange>>,
-    policy_table: Vec<Option<TableLruCache>>,
+    // Indexed by fast index and then tap type, the tables of each fast index grow
+    // on demand since tap types can be up to CAPTURE_NETWORK_TYPE_MAX
+    policy_table: Vec<Vec<Option<TableLruCache>>>,
     // Multi threaded access has thread safety issues, the ebpf
     // table must be accessed by an ebpf dispatcher thread.
     ebpf_table: LruCache<u128, Arc<EndpointData>>,
@@ -272,19 +272,20 @@ impl FastPath {
     }
 
     fn table_flush_check(&mut self, key: &LookupKey) -> bool {
-        let start_index = key.fast_index * MAX_TAP_TYPE;
+        let tables = &mut self.policy_table[key.fast_index];
         if self.policy_table_flush_flags[key.fast_index].load(Ordering::Relaxed) {
-            for i in 0..MAX_TAP_TYPE {
-                if let Some(t) = &mut self.policy_table[start_index + i] {
-                    t.clear();
-                }
+            for t in tables.iter_mut().flatten() {
+                t.clear();
             }
             self.policy_table_flush_flags[key.fast_index].store(false, Ordering::Relaxed);
         }
 
-        if self.policy_table[start_index + u16::from(key.tap_type) as usize].is_none() {
-            self.policy_table[start_index + u16::from(key.tap_type) as usize] =
-                Some(LruCache::new(self.map_size.try_into().unwrap()));
+        let tap_type = u16::from(key.tap_type) as usize;
+        if tables.len() <= tap_type {
+            tables.resize_with(tap_type + 1, || None);
+        }
+        if tables[tap_type].is_none() {
+            tables[tap_type] = Some(LruCache::new(self.map_size.try_into().unwrap()));
             return true;
         }
         false
@@ -299,12 +300,11 @@ impl FastPath {
         self.table_flush_check(packet);
         self.interest_table_map(packet);
 
-        let start_index = packet.fast_index * MAX_TAP_TYPE;
         let acl_id = policy.acl_id;
         let (key_0, key_1) = self.generate_map_key(packet);
         let proto = u8::from(packet.proto) as usize;
         let key = (key_0 as u128) << 64 | key_1 as u128;
-        let table = self.policy_table[start_index + u16::from(packet.tap_type) as usize]
+        let table = self.policy_table[packet.fast_index][u16::from(packet.tap_type) as usize]
             .as_mut()
             .unwrap();
 
@@ -385,10 +385,9 @@ impl FastPath {
         }
         self.interest_table_map(packet);
 
-        let start_index = packet.fast_index * MAX_TAP_TYPE;
         let (key_0, key_1) = self.generate_map_key(packet);
         let key = (key_0 as u128) << 64 | key_1 as u128;
-        let table = self.policy_table[start_index + u16::from(packet.tap_type) as usize]
+        let table = self.policy_table[packet.fast_index][u16::from(packet.tap_type) as usize]
             .as_mut()
             .unwrap();
         if let Some(item) = table.get(&key) {
@@ -496,13 +495,7 @@ impl FastPath {
                     .take(u16::MAX as usize + 1)
                     .collect::<Vec<PortRange>>(),
             ),
-            policy_table: {
-                let mut table = Vec::new();
-                for _i in 0..MAX_FAST_PATH {
-                    table.push(None);
-                }
-                table
-            },
+            policy_table: (0..=super::MAX_QUEUE_COUNT).map(|_| vec![]).collect(),
             ebpf_table: LruCache::new(map_size.try_into().unwrap()),
 
             policy_table_flush_flags: [FLUSH_FLAGS; super::MAX_QUEUE_COUNT + 1],
@@ -27,7 +27,9 @@ use super::fast_path::FastPath;
 use super::{Error as PError, Result as PResult};
 use crate::common::endpoint::{EndpointData, FeatureFlags};
 use crate::common::lookup_key::LookupKey;
-use crate::common::matched_field::{MatchedField, MatchedFieldN, MatchedFieldv4, MatchedFieldv6};
+use crate::common::matched_field::{
+    MatchedField, MatchedFieldN, MatchedFieldv4, MatchedFieldv6, MatchedFlag,
+};
 use crate::common::platform_data::PlatformData;
 use crate::common::policy::{Acl, Cidr, Fieldv4, Fieldv6, IpGroupData, IpSegment};
 use crate::utils::process::get_memory_rss;
@@ -248,6 +250,8 @@ pub struct FirstPath {
     fast: FastPath,
 
     fast_disable: bool,
+    // the fast path key does not contain VLAN, skip it when any acl matches VLAN
+    vlan_acl_exist: bool,
 
     memory_limit: AtomicU64,
 }
@@ -281,6 +285,7 @@ impl FirstPath {
 
             fast: FastPath::new(queue_count, map_size),
             fast_disable,
+            vlan_acl_exist: false,
             memory_limit: AtomicU64::new(0),
         }
     }
@@ -594,6 +599,7 @@ impl FirstPath {
                 valid_acl.reset();
                 valid_acls.push(valid_acl);
             }
+            self.vlan_acl_exist = valid_acls.iter().any(|acl| acl.vlan > 0);
             self.generate_first_table(&mut valid_acls)?;
         }
 
@@ -611,6 +617,7 @@ impl FirstPath {
         &self,
         field: &MatchedFieldv4,
         direction: DirectionType,
+        vlan_only: bool,
         policy: &mut PolicyData,
     ) {
         let index = field.get_table_index(
@@ -619,6 +626,9 @@ impl FirstPath {
             self.vector_4.max_bit,
         ) as usize;
         for item in &self.table_4.read().unwrap()[index] {
+            if vlan_only && item.field.mask.get(MatchedFlag::Vlan) == 0 {
+                continue;
+            }
             if field & &item.field.mask == item.field.field {
                 policy.merge_npb_actions(&item.policy.npb_actions, item.policy.acl_id, direction);
             }
@@ -629,6 +639,7 @@ impl FirstPath {
         &self,
         field: &MatchedFieldv6,
         direction: DirectionType,
+        vlan_only: bool,
         policy: &mut PolicyData,
     ) {
         let index = field.get_table_index(
@@ -637,6 +648,9 @@ impl FirstPath {
             self.vector_6.max_bit,
         ) as usize;
         for item in &self.table_6.read().unwrap()[index] {
+            if vlan_only && item.field.mask.get(MatchedFlag::Vlan) == 0 {
+                continue;
+            }
             if field & &item.field.mask == item.field.field {
                 policy.merge_npb_actions(&item.policy.npb_actions, item.policy.acl_id, direction);
             }
@@ -654,17 +668,34 @@ impl FirstPath {
             (endpoints.dst_info.l3_epc_id & 0xffff) as u16,
         );
 
+        self.get_policy_from_matched(key, false, policy);
+
+        // QinQ: acls with VLAN also match the outer tag
+        if self.vlan_acl_exist && key.outer_vlan > 0 && key.outer_vlan != key.vlan {
+            key.forward_matched
+                .as_mut()
+                .unwrap()
+                .set(MatchedFlag::Vlan, key.outer_vlan);
+            key.backward_matched
+                .as_mut()
+                .unwrap()
+                .set(MatchedFlag::Vlan, key.outer_vlan);
+            self.get_policy_from_matched(key, true, policy);
+        }
+    }
+
+    fn get_policy_from_matched(&self, key: &LookupKey, vlan_only: bool, policy: &mut PolicyData) {
         match (
             key.forward_matched.as_ref().unwrap(),
             key.backward_matched.as_ref().unwrap(),
         ) {
             (MatchedField::V4(forward), MatchedField::V4(backward)) => {
-                self.get_policy_from_table4(forward, DirectionType::FORWARD, policy);
-                self.get_policy_from_table4(backward, DirectionType::BACKWARD, policy);
+                self.get_policy_from_table4(forward, DirectionType::FORWARD, vlan_only, policy);
+                self.get_policy_from_table4(backward, DirectionType::BACKWARD, vlan_only, policy);
             }
             (MatchedField::V6(forward), MatchedField::V6(backward)) => {
-                self.get_policy_from_table6(forward, DirectionType::FORWARD, policy);
-                self.get_policy_from_table6(backward, DirectionType::BACKWARD, policy);
+                self.get_policy_from_table6(forward, DirectionType::FORWARD, vlan_only, policy);
+                self.get_policy_from_table6(backward, DirectionType::BACKWARD, vlan_only, policy);
             }
             _ => panic!("LookupKey({:?}) MatchedField version error.", key),
         }
@@ -727,7 +758,7 @@ impl FirstPath {
         &mut self,
         key: &mut LookupKey,
     ) -> Option<(Arc<PolicyData>, Arc<EndpointData>)> {
-        if self.fast_disable {
+        if self.fast_disable || self.vlan_acl_exist {
             return None;
         }
         if let Some((policy, endpoints)) = self.fast.get_policy(key) {
@@ -780,6 +811,7 @@ mod tests {
             valid_acl.reset();
             valid_acls.push(valid_acl);
         }
+        first.vlan_acl_exist = valid_acls.iter().any(|acl| acl.vlan > 0);
         first.generate_first_table(&mut valid_acls)?;
         first.fast.generate_interest_table(acls);
         Ok(())
@@ -923,4 +955,72 @@ mod tests {
         assert_eq!(policy.npb_actions.len(), 1);
         assert_eq!(policy.acl_id, 1);
     }
+
+    #[test]
+    fn test_qinq_vlan() {
+        let mut first = FirstPath::new(1, 8, 1 << 16, false);
+        let mut acl = Acl::new(
+            1,
+            vec![10],
+            vec![20],
+            vec![],
+            vec![],
+            NpbAction::new(
+                0,
+                100,
+                "192.168.1.100".parse::<IpAddr>().unwrap(),
+                1,
+                NpbTunnelType::VxLan,
+                TapSide::SRC,
+                DirectionType::ALL,
+                0,
+            ),
+        );
+        acl.vlan = 100;
+        update_ip_group(
+            &mut first,
+            &vec![
+                Arc::new(IpGroupData::new(10, 2, "192.168.2.1/32")),
+                Arc::new(IpGroupData::new(20, 20, "192.168.2.5/31")),
+            ],
+        );
+        update_acl(&mut first, &vec![Arc::new(acl)]).unwrap();
+        assert!(first.fast_get(&mut LookupKey::default()).is_none());
+
+        let endpotins = EndpointData {
+            src_info: EndpointInfo {
+                l3_epc_id: 2,
+                ..Default::default()
+            },
+            dst_info: EndpointInfo {
+                l3_epc_id: 20,
+                ..Default::default()
+            },
+        };
+        let mut key = LookupKey {
+            src_ip: "192.168.2.1".parse::<IpAddr>().unwrap(),
+            dst_ip: "192.168.2.5".parse::<IpAddr>().unwrap(),
+            src_port: 80,
+            dst_port: 100,
+            tap_type: CaptureNetworkType::Cloud,
+            ..Default::default()
+        };
+        // inner tag
+        key.vlan = 100;
+        let (policy, _) = first_get(&mut first, &mut key, endpotins).unwrap();
+        assert_eq!(policy.acl_id, 1);
+        assert_eq!(policy.npb_actions.len(), 1);
+
+        // outer tag
+        key.vlan = 200;
+        key.outer_vlan = 100;
+        let (policy, _) = first_get(&mut first, &mut key, endpotins).unwrap();
+        assert_eq!(policy.acl_id, 1);
+        assert_eq!(policy.npb_actions.len(), 1);
+
+        key.outer_vlan = 300;
+        let (policy, _) = first_get(&mut first, &mut key, endpotins).unwrap();
+        assert_eq!(policy.acl_id, 0);
+        assert!(policy.npb_actions.is_empty());
+    }
 }
@@ -0,0 +1,480 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::{
+    atomic::{AtomicI64, AtomicU64, Ordering},
+    Arc, Condvar, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use arc_swap::access::Access;
+use log::{debug, info, warn};
+use prost::Message;
+use reqwest::{blocking::Client, header::ACCEPT, Url};
+
+use crate::config::{handler::MetricServerAccess, PrometheusScrape, PrometheusScrapeTarget};
+use crate::integration_collector::{BoxedPrometheusExtra, PrometheusExtra};
+use crate::rpc::get_timestamp;
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+use public::queue::DebugSender;
+
+const SCRAPE_ACCEPT: &str = "text/plain;version=0.0.4";
+// same as max_samples_per_send of prometheus remote write
+const MAX_SERIES_PER_REQUEST: usize = 2000;
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+// Messages of prometheus remote write, see prompb/remote.proto and prompb/types.proto
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub(crate) timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub(crate) labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub(crate) samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Label {
+    #[prost(string, tag = "1")]
+    pub(crate) name: String,
+    #[prost(string, tag = "2")]
+    pub(crate) value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Sample {
+    #[prost(double, tag = "1")]
+    pub(crate) value: f64,
+    // in milliseconds
+    #[prost(int64, tag = "2")]
+    pub(crate) timestamp: i64,
+}
+
+// Remote write payloads are in the snappy block format, the data is stored as literals without
+// compression, which any snappy decoder accepts.
+pub(crate) fn snappy_encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len() + data.len() / 65536 * 3 + 8);
+    let mut len = data.len();
+    while len >= 0x80 {
+        encoded.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    encoded.push(len as u8);
+    for literal in data.chunks(65536) {
+        let n = literal.len() - 1;
+        if n < 60 {
+            encoded.push((n as u8) << 2);
+        } else if n < 256 {
+            encoded.push(60 << 2);
+            encoded.push(n as u8);
+        } else {
+            encoded.push(61 << 2);
+            encoded.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        encoded.extend_from_slice(literal);
+    }
+    encoded
+}
+
+#[derive(Debug, PartialEq)]
+struct ParsedSample {
+    name: String,
+    labels: Vec<(String, String)>,
+    value: f64,
+    timestamp: Option<i64>,
+}
+
+// Parses a sample line of the prometheus text exposition format:
+//     metric_name{label="value",...} value [timestamp]
+fn parse_sample(line: &str) -> Option<ParsedSample> {
+    let name_end = line.find(|c: char| c == '{' || c.is_ascii_whitespace())?;
+    let name = &line[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = &line[name_end..];
+    let mut labels = vec![];
+    if let Some(r) = rest.strip_prefix('{') {
+        rest = r;
+        loop {
+            rest = rest.trim_start();
+            if let Some(r) = rest.strip_prefix('}') {
+                rest = r;
+                break;
+            }
+            let eq = rest.find('=')?;
+            let label = rest[..eq].trim();
+            rest = rest[eq + 1..].trim_start().strip_prefix('"')?;
+            let mut value = String::new();
+            let mut chars = rest.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') => break i,
+                    (_, '\\') => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        c => value.push(c),
+                    },
+                    (_, c) => value.push(c),
+                }
+            };
+            labels.push((label.to_owned(), value));
+            rest = rest[end + 1..].trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+    }
+
+    let mut fields = rest.split_ascii_whitespace();
+    let value = fields.next()?.parse::<f64>().ok()?;
+    let timestamp = match fields.next() {
+        Some(t) => Some(t.parse::<i64>().ok()?),
+        None => None,
+    };
+    Some(ParsedSample {
+        name: name.to_owned(),
+        labels,
+        value,
+        timestamp,
+    })
+}
+
+// labels added to all the samples of the target, the server tags the samples with the pod or
+// service by the `pod` or `instance` label
+fn target_labels(target: &PrometheusScrapeTarget) -> BTreeMap<String, String> {
+    let mut labels = BTreeMap::new();
+    labels.insert("job".to_owned(), target.job_name.clone());
+    if let Ok(url) = Url::parse(&target.url) {
+        if let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) {
+            labels.insert("instance".to_owned(), format!("{}:{}", host, port));
+        }
+    }
+    for (k, v) in target.labels.iter() {
+        labels.insert(k.clone(), v.clone());
+    }
+    labels
+}
+
+pub(crate) fn time_series(
+    labels: BTreeMap<String, String>,
+    value: f64,
+    timestamp: i64,
+) -> TimeSeries {
+    TimeSeries {
+        labels: labels
+            .into_iter()
+            .map(|(name, value)| Label { name, value })
+            .collect(),
+        samples: vec![Sample { value, timestamp }],
+    }
+}
+
+#[derive(Default)]
+pub struct PrometheusScraperCounter {
+    scrapes: AtomicU64,
+    scrape_failed: AtomicU64,
+    samples: AtomicU64,
+    // samples dropped because the queue is full
+    queue_drop: AtomicU64,
+}
+
+impl RefCountable for PrometheusScraperCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "scrapes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.scrapes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "scrape-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.scrape_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "samples",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.samples.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "queue-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.queue_drop.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Scraper {
+    config: MetricServerAccess,
+    sender: DebugSender<BoxedPrometheusExtra>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<PrometheusScraperCounter>,
+}
+
+impl Scraper {
+    fn scrape(
+        &self,
+        client: &Client,
+        config: &PrometheusScrape,
+        target: &PrometheusScrapeTarget,
+        now: i64,
+        series: &mut Vec<TimeSeries>,
+    ) -> Result<(), String> {
+        let body = client
+            .get(&target.url)
+            .header(ACCEPT, SCRAPE_ACCEPT)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.text())
+            .map_err(|e| e.to_string())?;
+
+        let target_labels = target_labels(target);
+        let mut scraped = vec![];
+        for line in body.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some(sample) = parse_sample(line) else {
+                debug!("invalid sample from {}: {}", target.url, line);
+                continue;
+            };
+            if config.sample_limit > 0 && scraped.len() >= config.sample_limit {
+                return Err(format!("sample limit {} exceeded", config.sample_limit));
+            }
+            let mut labels: BTreeMap<String, String> = sample.labels.into_iter().collect();
+            labels.insert("__name__".to_owned(), sample.name);
+            labels.extend(target_labels.clone());
+            scraped.push(time_series(
+                labels,
+                sample.value,
+                sample.timestamp.unwrap_or(now),
+            ));
+        }
+        series.append(&mut scraped);
+        Ok(())
+    }
+
+    fn send(&self, series: Vec<TimeSeries>) {
+        let count = series.len() as u64;
+        let request = WriteRequest { timeseries: series };
+        let metrics = snappy_encode(&request.encode_to_vec());
+        if let Err(e) = self
+            .sender
+            .send(BoxedPrometheusExtra(Box::new(PrometheusExtra::new(
+                metrics,
+            ))))
+        {
+            debug!("prometheus scraper failed to send data, because {:?}", e);
+            self.counter.queue_drop.fetch_add(count, Ordering::Relaxed);
+            return;
+        }
+        self.counter.samples.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn scrape_all(&self, config: &PrometheusScrape) {
+        let client = match Client::builder().timeout(config.timeout).build() {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("prometheus scraper failed to build http client: {}", e);
+                return;
+            }
+        };
+        let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed)).as_millis() as i64;
+        let mut series = vec![];
+        for target in config.targets.iter() {
+            self.counter.scrapes.fetch_add(1, Ordering::Relaxed);
+            let up = match self.scrape(&client, config, target, now, &mut series) {
+                Ok(_) => 1.0,
+                Err(e) => {
+                    warn!("prometheus scraper failed to scrape {}: {}", target.url, e);
+                    self.counter.scrape_failed.fetch_add(1, Ordering::Relaxed);
+                    0.0
+                }
+            };
+            let mut labels = target_labels(target);
+            labels.insert("__name__".to_owned(), "up".to_owned());
+            series.push(time_series(labels, up, now));
+
+            while series.len() >= MAX_SERIES_PER_REQUEST {
+                let rest = series.split_off(MAX_SERIES_PER_REQUEST);
+                self.send(series);
+                series = rest;
+            }
+        }
+        if !series.is_empty() {
+            self.send(series);
+        }
+    }
+}
+
+// Scrapes the configured prometheus targets and forwards the samples as prometheus remote
+// write, the same as the samples received by the integration collector.
+//
+// Targets are scraped one by one in every interval, a slow target delays the others.
+pub struct PrometheusScraper {
+    config: MetricServerAccess,
+    sender: DebugSender<BoxedPrometheusExtra>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<PrometheusScraperCounter>,
+
+    running: Arc<(Mutex<bool>, Condvar)>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl PrometheusScraper {
+    pub fn new(
+        config: MetricServerAccess,
+        sender: DebugSender<BoxedPrometheusExtra>,
+        ntp_diff: Arc<AtomicI64>,
+    ) -> Self {
+        Self {
+            config,
+            sender,
+            ntp_diff,
+            counter: Arc::new(PrometheusScraperCounter::default()),
+            running: Arc::new((Mutex::new(false), Condvar::new())),
+            thread: Mutex::new(None),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<PrometheusScraperCounter> {
+        self.counter.clone()
+    }
+
+    pub fn start(&self) {
+        {
+            let (started, _) = &*self.running;
+            let mut started = started.lock().unwrap();
+            if *started {
+                return;
+            }
+            *started = true;
+        }
+
+        let scraper = Scraper {
+            config: self.config.clone(),
+            sender: self.sender.clone(),
+            ntp_diff: self.ntp_diff.clone(),
+            counter: self.counter.clone(),
+        };
+        let running = self.running.clone();
+        let thread = thread::Builder::new()
+            .name("prometheus-scraper".to_owned())
+            .spawn(move || loop {
+                let config = scraper.config.load().prometheus_scrape.clone();
+                if config.enabled {
+                    scraper.scrape_all(&config);
+                }
+
+                let (running, timer) = &*running;
+                let mut running = running.lock().unwrap();
+                if !*running {
+                    break;
+                }
+                running = timer
+                    .wait_timeout(running, config.interval.max(MIN_INTERVAL))
+                    .unwrap()
+                    .0;
+                if !*running {
+                    break;
+                }
+            })
+            .unwrap();
+        self.thread.lock().unwrap().replace(thread);
+        info!("prometheus scraper started");
+    }
+
+    pub fn notify_stop(&self) -> Option<JoinHandle<()>> {
+        let (stopped, timer) = &*self.running;
+        {
+            let mut stopped = stopped.lock().unwrap();
+            if !*stopped {
+                return None;
+            }
+            *stopped = false;
+        }
+        timer.notify_one();
+        info!("notified stopping prometheus scraper");
+        self.thread.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_samples() {
+        assert_eq!(
+            parse_sample(r#"http_requests_total{method="post",code="200"} 1027 1395066363000"#),
+            Some(ParsedSample {
+                name: "http_requests_total".to_owned(),
+                labels: vec![
+                    ("method".to_owned(), "post".to_owned()),
+                    ("code".to_owned(), "200".to_owned()),
+                ],
+                value: 1027.0,
+                timestamp: Some(1395066363000),
+            })
+        );
+        assert_eq!(
+            parse_sample(
+                r#"msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\"",} 1.458255915e9"#
+            ),
+            Some(ParsedSample {
+                name: "msdos_file_access_time_seconds".to_owned(),
+                labels: vec![
+                    ("path".to_owned(), r"C:\DIR\FILE.TXT".to_owned()),
+                    (
+                        "error".to_owned(),
+                        "Cannot find file:\n\"FILE.TXT\"".to_owned()
+                    ),
+                ],
+                value: 1.458255915e9,
+                timestamp: None,
+            })
+        );
+        let sample = parse_sample("go_goroutines +Inf").unwrap();
+        assert_eq!(sample.value, f64::INFINITY);
+        assert!(sample.labels.is_empty());
+
+        assert!(parse_sample(r#"broken{label="value} 1"#).is_none());
+        assert!(parse_sample("no_value").is_none());
+        assert!(parse_sample("bad_value abc").is_none());
+    }
+
+    #[test]
+    fn snappy_literals() {
+        let data: Vec<u8> = (0..70000u32).map(|i| i as u8).collect();
+        let encoded = snappy_encode(&data);
+        // varint of 70000
+        assert_eq!(encoded[..3], [0xf0, 0xa2, 0x04]);
+        // literal of 65536 bytes
+        assert_eq!(encoded[3..6], [61 << 2, 0xff, 0xff]);
+        assert_eq!(encoded[6..65542], data[..65536]);
+        // literal of 4464 bytes
+        assert_eq!(encoded[65542..65545], [61 << 2, 0x6f, 0x11]);
+        assert_eq!(encoded[65545..], data[65536..]);
+
+        assert_eq!(snappy_encode(b"abc"), [3, 2 << 2, b'a', b'b', b'c']);
+    }
+}
@@ -20,7 +20,7 @@ use std::{
     collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
     fmt::{self, Write as _},
     fs::File,
-    io::Write,
+    io::{self as stdio, Write},
     ops::Deref,
     os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
@@ -52,7 +52,12 @@ use tokio::{
 };
 
 use super::{Session, RPC_RETRY_INTERVAL};
-use crate::{exception::ExceptionHandler, trident::AgentId};
+use crate::{
+    exception::ExceptionHandler,
+    flow_generator::packet_trace::{PacketTraceFilter, DEFAULT_TRACE_DURATION, PACKET_TRACE},
+    trident::AgentId,
+    utils::log_level::{LogLevelSpec, DEFAULT_OVERRIDE_DURATION, LOG_LEVELS},
+};
 
 use public::{
     netns::{reset_netns, set_netns},
@@ -76,8 +81,62 @@ const KUBERNETES_POD_PARAM: &'static Parameter = &Parameter {
     param_type: ParamType::Text,
     description: "The Kubernetes pod to run the command in",
 };
+const LOG_LEVELS_PARAM: &'static Parameter = &Parameter {
+    name: "levels",
+    regex: Some("^[0-9A-Za-z_:=,]{1,512}$"),
+    required: false,
+    param_type: ParamType::Text,
+    description: "Log levels like `flow_generator::protocol_logs::mysql=debug`, empty to revert",
+};
+const DURATION_PARAM: &'static Parameter = &Parameter {
+    name: "duration",
+    regex: Some("^[0-9]{1,5}$"),
+    required: false,
+    param_type: ParamType::Text,
+    description: "Seconds before reverting automatically, 300 if empty",
+};
+const TRACE_IP_PARAMS: [&'static Parameter; 2] = [
+    &Parameter {
+        name: "ip_0",
+        regex: Some("^[0-9A-Fa-f.:]{2,45}$"),
+        required: false,
+        param_type: ParamType::Text,
+        description: "IP of one endpoint, empty with `ip_1` to stop tracing",
+    },
+    &Parameter {
+        name: "ip_1",
+        regex: Some("^[0-9A-Fa-f.:]{2,45}$"),
+        required: false,
+        param_type: ParamType::Text,
+        description: "IP of the other endpoint",
+    },
+];
+const TRACE_PORT_PARAMS: [&'static Parameter; 2] = [
+    &Parameter {
+        name: "port_0",
+        regex: Some("^[0-9]{1,5}$"),
+        required: false,
+        param_type: ParamType::Text,
+        description: "Port of `ip_0`, any if empty",
+    },
+    &Parameter {
+        name: "port_1",
+        regex: Some("^[0-9]{1,5}$"),
+        required: false,
+        param_type: ParamType::Text,
+        description: "Port of `ip_1`, any if empty",
+    },
+];
+const TRACE_PROTOCOL_PARAM: &'static Parameter = &Parameter {
+    name: "protocol",
+    regex: Some("^[0-9A-Za-z]{1,8}$"),
+    required: false,
+    param_type: ParamType::Text,
+    description: "tcp, udp, icmp, icmpv6, sctp or protocol number, any if empty",
+};
 const CMD_TYPE_SYSTEM: &'static str = "system";
 const CMD_TYPE_KUBERNETES: &'static str = "kubernetes";
+const CMD_TYPE_AGENT: &'static str = "agent";
 
 fn all_supported_commands() -> Vec<Command> {
     #[allow(unused_mut)]
@@ -145,6 +204,35 @@ fn all_supported_commands() -> Vec<Command> {
             }),
             ..Default::default()
         },
+        Command {
+            cmdline: "log-level $levels $duration",
+            output_format: OutputFormat::Text,
+            command_type: CMD_TYPE_AGENT,
+            params: vec![*LOG_LEVELS_PARAM, *DURATION_PARAM],
+            override_cmdline: Some(|params| {
+                let result = set_log_level(params);
+                Box::pin(async move { result })
+            }),
+            ..Default::default()
+        },
+        Command {
+            cmdline: "packet-trace $ip_0 $port_0 $ip_1 $port_1 $protocol $duration",
+            output_format: OutputFormat::Text,
+            command_type: CMD_TYPE_AGENT,
+            params: vec![
+                *TRACE_IP_PARAMS[0],
+                *TRACE_PORT_PARAMS[0],
+                *TRACE_IP_PARAMS[1],
+                *TRACE_PORT_PARAMS[1],
+                *TRACE_PROTOCOL_PARAM,
+                *DURATION_PARAM,
+            ],
+            override_cmdline: Some(|params| {
+                let result = trace_packets(params);
+                Box::pin(async move { result })
+            }),
+            ..Default::default()
+        },
     ];
     #[cfg(feature = "enterprise")]
     commands.extend(enterprise_utils::rpc::remote_exec::extra_commands());
@@ -1458,6 +1546,67 @@ async fn agent_ls_netns() -> Result<Vec<pb::LinuxNamespace>> {
         .collect())
 }
 
+fn text_output(text: String) -> Output {
+    Output {
+        status: Default::default(),
+        stdout: text.into_bytes(),
+        stderr: vec![],
+    }
+}
+
+fn param_duration(params: &Params, default: Duration) -> Result<Duration> {
+    match params.get(DURATION_PARAM.name) {
+        None | Some("") => Ok(default),
+        Some(d) => d
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|_| Error::ParamInvalid(DURATION_PARAM.name.to_owned())),
+    }
+}
+
+fn set_log_level(params: &Params) -> Result<Output> {
+    let spec = params
+        .get(LOG_LEVELS_PARAM.name)
+        .unwrap_or_default()
+        .parse::<LogLevelSpec>()
+        .map_err(|_| Error::ParamInvalid(LOG_LEVELS_PARAM.name.to_owned()))?;
+    let duration = param_duration(params, DEFAULT_OVERRIDE_DURATION)?;
+    LOG_LEVELS
+        .set_temporary(spec, duration)
+        .map_err(|e| Error::CmdExecFailed(stdio::Error::new(stdio::ErrorKind::Other, e)))?;
+    let (current, expire_at) = LOG_LEVELS.current();
+    Ok(text_output(match expire_at {
+        Some(t) => format!("log level: {} until {:?}\n", current, t),
+        None => format!("log level: {}\n", current),
+    }))
+}
+
+fn trace_packets(params: &Params) -> Result<Output> {
+    let param = |p: &'static Parameter| params.get(p.name).unwrap_or_default();
+    let (ip_0, ip_1) = (param(TRACE_IP_PARAMS[0]), param(TRACE_IP_PARAMS[1]));
+    if ip_0.is_empty() && ip_1.is_empty() {
+        PACKET_TRACE.stop(None);
+        return Ok(text_output("packet trace stopped\n".to_owned()));
+    }
+    let filter = PacketTraceFilter::parse(
+        ip_0,
+        param(TRACE_PORT_PARAMS[0]),
+        ip_1,
+        param(TRACE_PORT_PARAMS[1]),
+        param(TRACE_PROTOCOL_PARAM),
+    )
+    .map_err(|e| Error::CmdExecFailed(stdio::Error::new(stdio::ErrorKind::InvalidInput, e)))?;
+    let duration = param_duration(params, DEFAULT_TRACE_DURATION)?;
+    let text = format!(
+        "packet trace of {} started for {:?}, packets are logged in agent logs\n",
+        filter, duration
+    );
+    PACKET_TRACE
+        .start(filter, duration)
+        .map_err(|e| Error::CmdExecFailed(stdio::Error::new(stdio::ErrorKind::Other, e)))?;
+    Ok(text_output(text))
+}
+
 async fn lsns_command() -> Result<Output> {
     let mut output = vec![];
     write_namespace_table(&mut output, &lsns().await?)?;
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Weak,
@@ -24,17 +25,22 @@ use anyhow::{anyhow, Result};
 use log::{debug, error, info};
 use md5::{Digest, Md5};
 use parking_lot::RwLock;
-use tonic::transport::Channel;
+use tonic::transport::{Channel, Endpoint};
 
 use crate::{
     common::{DEFAULT_CONTROLLER_PORT, DEFAULT_CONTROLLER_TLS_PORT},
+    config::MutualTls,
     exception::ExceptionHandler,
     trident::AgentId,
-    utils::stats::{self, AtomicTimeStats},
+    utils::{
+        stats::{self, AtomicTimeStats},
+        tls::{GrpcConnector, TlsContext},
+    },
 };
 use grpc::dial as grpc_dial;
 
 use public::{
+    consts::{GRPC_DEFAULT_TIMEOUT, GRPC_SESSION_TIMEOUT},
     counter::{Countable, Counter, CounterType, CounterValue, RefCountable},
     proto::agent::{self, Exception, PluginType, Status},
     proto::trident,
@@ -113,6 +119,10 @@ impl Config {
 pub struct Session {
     config: Arc<RwLock<Config>>,
     controller_cert_file_prefix: String,
+    // the ca file of each controller is named by the prefix and the controller ip
+    controller_tls: MutualTls,
+    tls: Arc<TlsContext>,
+    tls_version: AtomicU64,
 
     server_dispatcher: RwLock<ServerDispatcher>,
 
@@ -225,6 +235,7 @@ impl Session {
         tls_port: u16,
         timeout: Duration,
         controller_cert_file_prefix: String,
+        controller_tls: MutualTls,
         controller_ips: Vec<String>,
         exception_handler: ExceptionHandler,
         stats_collector: &stats::Collector,
@@ -241,6 +252,11 @@ impl Session {
                 Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
             );
         }
+        let tls = Arc::new(TlsContext::new("controller", controller_tls.clone()));
+        stats_collector.register_countable(
+            &stats::SingleTagModule("tls", "channel", "controller"),
+            Countable::Ref(Arc::downgrade(&tls.get_counter_handle()) as Weak<dyn RefCountable>),
+        );
 
         let config = Arc::new(RwLock::new(Config {
             ips: controller_ips,
@@ -259,6 +275,9 @@ impl Session {
             exception_handler,
             counters,
             controller_cert_file_prefix,
+            controller_tls,
+            tls,
+            tls_version: AtomicU64::new(0),
             new_rpc,
         }
     }
@@ -274,8 +293,37 @@ impl Session {
         self.server_dispatcher.write().reset();
     }
 
+    async fn tls_dial(&self, remote: &str, remote_port: u16) -> Result<Channel, String> {
+        let mut controller_tls = self.controller_tls.clone();
+        controller_tls.ca_file = format!("{}.{}", self.controller_cert_file_prefix, remote);
+        self.tls.update(&controller_tls);
+        let connector = GrpcConnector::new(self.tls.clone(), remote)
+            .map_err(|e| format!("load controller tls certificates failed: {}", e))?;
+        let address = match remote.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, remote_port).to_string(),
+            Err(_) => format!("{}:{}", remote, remote_port),
+        };
+        let endpoint = Endpoint::from_shared(format!("https://{}", address))
+            .map_err(|e| format!("create endpoint https://{} failed {}", address, e))?;
+        let channel = endpoint
+            .connect_timeout(GRPC_DEFAULT_TIMEOUT)
+            .timeout(GRPC_SESSION_TIMEOUT)
+            .connect_with_connector(connector)
+            .await
+            .map_err(|e| format!("Dial server({} {}) failed: {}", remote, remote_port, e))?;
+        self.tls_version
+            .store(self.tls.version(), Ordering::Relaxed);
+        Ok(channel)
+    }
+
     async fn dial(&self, remote: &str, remote_port: u16, controller_cert_file_prefix: String) {
-        match grpc_dial(remote, remote_port, controller_cert_file_prefix).await {
+        let enable_tls = self.config.read().enable_tls;
+        let result = if enable_tls {
+            self.tls_dial(remote, remote_port).await
+        } else {
+            grpc_dial(remote, remote_port, controller_cert_file_prefix).await
+        };
+        match result {
             Ok(channel) => *self.client.write() = Some(channel),
             Err(e) => {
                 self.exception_handler.set(Exception::ControllerSocketError);
@@ -295,7 +343,10 @@ impl Session {
 
     pub async fn update_current_server(&self) -> bool {
         let changed = self.server_dispatcher.write().update_current_ip();
-        if changed || self.get_client().is_none() {
+        // redial with the rotated certificates
+        let tls_rotated = self.config.read().enable_tls
+            && self.tls.version() != self.tls_version.load(Ordering::Relaxed);
+        if changed || self.get_client().is_none() || tls_rotated {
             let (ip, port) = self.server_dispatcher.read().get_current_ip();
             self.dial(&ip, port, self.controller_cert_file_prefix.clone())
                 .await;
@@ -0,0 +1,310 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::{atomic::Ordering, Arc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use reqwest::blocking::{Body, Client};
+
+use super::uniform_sender::SenderCounter;
+use crate::config::ClickHouse;
+
+type GenericError = Box<dyn std::error::Error + Send + Sync>;
+
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+// (name, type) of the columns of the tables written by the agent
+const COLUMNS: [(&str, &str); 4] = [
+    ("time", "DateTime64(6)"),
+    ("agent_id", "UInt16"),
+    ("flow_id", "UInt64"),
+    ("data", "String"),
+];
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn write_string(buffer: &mut Vec<u8>, s: &str) {
+    write_varint(buffer, s.len() as u64);
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+// Rows buffered in columns, which are encoded as a block of the Native format
+#[derive(Default)]
+struct Block {
+    time: Vec<i64>,
+    agent_id: Vec<u16>,
+    flow_id: Vec<u64>,
+    data: Vec<String>,
+}
+
+impl Block {
+    fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    fn push(&mut self, time: i64, agent_id: u16, flow_id: u64, data: String) {
+        self.time.push(time);
+        self.agent_id.push(agent_id);
+        self.flow_id.push(flow_id);
+        self.data.push(data);
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.data.iter().map(|d| d.len() + 24).sum());
+        write_varint(&mut buffer, COLUMNS.len() as u64);
+        write_varint(&mut buffer, self.len() as u64);
+        for (i, (name, type_name)) in COLUMNS.iter().enumerate() {
+            write_string(&mut buffer, name);
+            write_string(&mut buffer, type_name);
+            match i {
+                0 => self
+                    .time
+                    .iter()
+                    .for_each(|v| buffer.extend_from_slice(&v.to_le_bytes())),
+                1 => self
+                    .agent_id
+                    .iter()
+                    .for_each(|v| buffer.extend_from_slice(&v.to_le_bytes())),
+                2 => self
+                    .flow_id
+                    .iter()
+                    .for_each(|v| buffer.extend_from_slice(&v.to_le_bytes())),
+                _ => self.data.iter().for_each(|v| write_string(&mut buffer, v)),
+            }
+        }
+        buffer
+    }
+}
+
+// Writes the data of a uniform sender to a ClickHouse table, for standalone deployments without
+// deepflow-server.
+//
+// The data is kept as json in the `data` column, the same as the data files of the standalone
+// mode, and inserted in blocks of the Native format through the HTTP interface. Failed inserts
+// are retried with exponential backoff in the sender thread, which blocks the sender and the data
+// is dropped by the queue of the sender when it is full.
+pub struct ClickHouseWriter {
+    name: &'static str,
+    table: String,
+    config: ClickHouse,
+    counter: Arc<SenderCounter>,
+
+    client: Option<Client>,
+    table_created: bool,
+
+    block: Block,
+    last_flush: Instant,
+}
+
+impl ClickHouseWriter {
+    pub fn new(name: &'static str, counter: Arc<SenderCounter>) -> Self {
+        Self {
+            name,
+            table: String::new(),
+            config: ClickHouse::default(),
+            counter,
+            client: None,
+            table_created: false,
+            block: Block::default(),
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn update_config(&mut self, config: &ClickHouse, table: &str) {
+        if &self.config == config && self.table == table {
+            return;
+        }
+        if self.client.is_some() {
+            info!(
+                "{} sender clickhouse config changed, write to {} table {}.{}",
+                self.name, config.endpoint, config.database, table
+            );
+        }
+        self.config = config.clone();
+        self.table = table.to_owned();
+        self.client = None;
+        self.table_created = false;
+    }
+
+    pub fn write(&mut self, agent_id: u16, flow_id: Option<u64>, data: String) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as i64;
+        self.block
+            .push(time, agent_id, flow_id.unwrap_or_default(), data);
+        if self.block.len() >= self.config.batch_size
+            || self.last_flush.elapsed() >= self.config.flush_interval
+        {
+            self.flush();
+        }
+    }
+
+    fn query(&self, query: String, body: Vec<u8>, insert: bool) -> Result<(), GenericError> {
+        let mut params = vec![("query", query)];
+        if insert && self.config.async_insert {
+            params.push(("async_insert", "1".to_owned()));
+            params.push(("wait_for_async_insert", "1".to_owned()));
+        }
+        let response = self
+            .client
+            .as_ref()
+            .unwrap()
+            .post(&self.config.endpoint)
+            .header("X-ClickHouse-User", &self.config.username)
+            .header("X-ClickHouse-Key", &self.config.password)
+            .query(&params)
+            .body(Body::from(body))
+            .send()?;
+        if !response.status().is_success() {
+            return Err(format!("{}: {}", response.status(), response.text()?.trim()).into());
+        }
+        Ok(())
+    }
+
+    fn create_table(&mut self) -> Result<(), GenericError> {
+        if self.client.is_none() {
+            self.client = Some(Client::builder().timeout(self.config.timeout).build()?);
+        }
+        if self.table_created {
+            return Ok(());
+        }
+        self.query(
+            format!("CREATE DATABASE IF NOT EXISTS {}", self.config.database),
+            vec![],
+            false,
+        )?;
+        let columns = COLUMNS
+            .iter()
+            .map(|(name, type_name)| format!("{} {}", name, type_name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.query(
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.{} ({}) ENGINE = MergeTree PARTITION BY toYYYYMMDD(time) ORDER BY (agent_id, time) TTL toDateTime(time) + INTERVAL {} SECOND",
+                self.config.database,
+                self.table,
+                columns,
+                self.config.ttl.as_secs().max(1)
+            ),
+            vec![],
+            false,
+        )?;
+        info!(
+            "{} sender writes to clickhouse {} table {}.{}",
+            self.name, self.config.endpoint, self.config.database, self.table
+        );
+        self.table_created = true;
+        Ok(())
+    }
+
+    fn insert(&mut self, block: Vec<u8>) -> Result<(), GenericError> {
+        self.create_table()?;
+        let columns = COLUMNS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.query(
+            format!(
+                "INSERT INTO {}.{} ({}) FORMAT Native",
+                self.config.database, self.table, columns
+            ),
+            block,
+            true,
+        )
+    }
+
+    pub fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.block.len() == 0 {
+            return;
+        }
+        let block = std::mem::take(&mut self.block);
+        let encoded = block.encode();
+
+        let mut backoff = self.config.retry_backoff;
+        let mut retries = 0;
+        loop {
+            let e = match self.insert(encoded.clone()) {
+                Ok(_) => {
+                    self.counter.tx.fetch_add(1, Ordering::Relaxed);
+                    self.counter
+                        .tx_bytes
+                        .fetch_add(encoded.len() as u64, Ordering::Relaxed);
+                    return;
+                }
+                Err(e) => e,
+            };
+            if retries >= self.config.max_retries {
+                if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                    warn!(
+                        "{} sender insert into clickhouse {} table {}.{} failed after {} retries: {}",
+                        self.name,
+                        self.config.endpoint,
+                        self.config.database,
+                        self.table,
+                        retries,
+                        e
+                    );
+                }
+                self.counter
+                    .dropped
+                    .fetch_add(block.len() as u64, Ordering::Relaxed);
+                // the table may have been dropped, check it in the next flush
+                self.table_created = false;
+                return;
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+            retries += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_native_block() {
+        let mut block = Block::default();
+        block.push(1, 2, 3, "{}".to_owned());
+        let encoded = block.encode();
+
+        let mut expected = vec![4, 1];
+        expected.extend_from_slice(b"\x04time\x0dDateTime64(6)");
+        expected.extend_from_slice(&1i64.to_le_bytes());
+        expected.extend_from_slice(b"\x08agent_id\x06UInt16");
+        expected.extend_from_slice(&2u16.to_le_bytes());
+        expected.extend_from_slice(b"\x07flow_id\x06UInt64");
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"\x04data\x06String\x02{}");
+        assert_eq!(encoded, expected);
+
+        let mut buffer = vec![];
+        write_varint(&mut buffer, 300);
+        assert_eq!(buffer, vec![0xac, 0x02]);
+    }
+}
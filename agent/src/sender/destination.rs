@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+
+use lazy_static::lazy_static;
+
+use super::uniform_sender::Connection;
+use crate::config::SenderDestination;
+
+lazy_static! {
+    // connections to configured destinations shared by the senders
+    static ref SHARED_CONNECTIONS: Mutex<HashMap<(String, u16), Weak<Mutex<Connection>>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn shared_connection(address: &str, port: u16) -> Arc<Mutex<Connection>> {
+    let mut connections = SHARED_CONNECTIONS.lock().unwrap();
+    connections.retain(|_, c| c.strong_count() > 0);
+    let key = (address.to_owned(), port);
+    if let Some(conn) = connections.get(&key).and_then(|c| c.upgrade()) {
+        return conn;
+    }
+    let conn = Arc::new(Mutex::new(Connection::with_destination(address, port)));
+    connections.insert(key, Arc::downgrade(&conn));
+    conn
+}
+
+struct Destination {
+    config: SenderDestination,
+    conn: Arc<Mutex<Connection>>,
+    // state of smooth weighted round robin
+    current_weight: i64,
+}
+
+// Destinations configured instead of the ingester assigned by the controller.
+//
+// Frames are sent to the available destinations with the lowest priority value, balanced by
+// their weights with smooth weighted round robin. A destination is unavailable after it fails
+// until its reconnect interval passes, then the next frame to it checks its health by
+// reconnecting, so that frames fail over to destinations with higher priority values and fail
+// back once the preferred ones recover.
+pub struct Destinations {
+    entries: Vec<Destination>,
+    private: bool,
+}
+
+impl Destinations {
+    // Destinations are connected with their own sockets if `private` is true, otherwise the
+    // sockets are shared by all senders
+    pub fn new(configs: &[SenderDestination], private: bool) -> Self {
+        Self {
+            entries: configs
+                .iter()
+                .map(|c| Destination {
+                    config: c.clone(),
+                    conn: if private {
+                        Arc::new(Mutex::new(Connection::with_destination(&c.address, c.port)))
+                    } else {
+                        shared_connection(&c.address, c.port)
+                    },
+                    current_weight: 0,
+                })
+                .collect(),
+            private,
+        }
+    }
+
+    pub fn matches(&self, configs: &[SenderDestination], private: bool) -> bool {
+        self.private == private
+            && self.entries.len() == configs.len()
+            && self
+                .entries
+                .iter()
+                .zip(configs)
+                .all(|(e, c)| &e.config == c)
+    }
+
+    pub fn connection(&self, index: usize) -> Arc<Mutex<Connection>> {
+        self.entries[index].conn.clone()
+    }
+
+    // Selects an available destination not in `excluded`
+    pub fn select(&mut self, excluded: &[usize], tls_version: u64) -> Option<usize> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO);
+        let available = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(i, e)| {
+                !excluded.contains(i) && e.conn.lock().unwrap().available(now, tls_version)
+            })
+            .map(|(i, e)| (i, e.config.priority))
+            .collect::<Vec<_>>();
+        let priority = available.iter().map(|(_, p)| *p).min()?;
+
+        let mut total = 0;
+        let mut selected: Option<usize> = None;
+        for (i, _) in available.iter().filter(|(_, p)| *p == priority) {
+            let weight = self.entries[*i].config.weight.max(1) as i64;
+            self.entries[*i].current_weight += weight;
+            total += weight;
+            let current = self.entries[*i].current_weight;
+            if selected
+                .map(|s| current > self.entries[s].current_weight)
+                .unwrap_or(true)
+            {
+                selected = Some(*i);
+            }
+        }
+        let selected = selected?;
+        self.entries[selected].current_weight -= total;
+        Some(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn destination(port: u16, priority: u32, weight: u32) -> SenderDestination {
+        SenderDestination {
+            address: "127.0.0.1".to_owned(),
+            port,
+            priority,
+            weight,
+        }
+    }
+
+    #[test]
+    fn weighted_failover() {
+        let mut destinations = Destinations::new(
+            &[
+                destination(1, 0, 2),
+                destination(2, 0, 1),
+                destination(3, 1, 1),
+            ],
+            true,
+        );
+        let selected = (0..6)
+            .map(|_| destinations.select(&[], 0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec![0, 1, 0, 0, 1, 0]);
+
+        // the lower priority destination is used when the preferred ones fail
+        assert_eq!(destinations.select(&[0, 1], 0), Some(2));
+        assert_eq!(destinations.select(&[0, 1, 2], 0), None);
+    }
+}
@@ -0,0 +1,535 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::fs::{self, create_dir_all, File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use log::{info, warn};
+
+use crate::config::DiskBuffer as DiskBufferConfig;
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+
+const SEGMENT_SUFFIX: &str = ".wal";
+// seq (u64) and len (u32) of a record
+const RECORD_HEADER_LEN: usize = 12;
+// frames are no larger than what the ingester accepts, a longer record means corruption
+const MAX_FRAME_LEN: usize = 16 << 20;
+
+#[derive(Debug, Default)]
+pub struct DiskBufferCounter {
+    pub buffered: AtomicU64,
+    pub replayed: AtomicU64,
+    pub dropped: AtomicU64,
+    pub size: AtomicU64,
+}
+
+impl RefCountable for DiskBufferCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "disk-buffered",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.buffered.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "disk-replayed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.replayed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "disk-dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "disk-buffer-bytes",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.size.load(Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    frames: usize,
+    size: u64,
+    modified: SystemTime,
+}
+
+// Reads the sequence and frame length of the next record, None at the end of the segment
+fn read_record_header<R: Read>(reader: &mut R) -> io::Result<Option<(u64, usize)>> {
+    let mut header = [0u8; RECORD_HEADER_LEN];
+    match reader.read_exact(&mut header) {
+        Ok(_) => (),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let seq = u64::from_le_bytes(header[..8].try_into().unwrap());
+    let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("record length {} exceeds {}", len, MAX_FRAME_LEN),
+        ));
+    }
+    Ok(Some((seq, len)))
+}
+
+// Counts the records of a segment and returns the sequence of the last one without reading
+// the frames, a truncated record at the tail is ignored
+fn scan_segment(path: &Path) -> io::Result<(usize, Option<u64>)> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let (mut frames, mut last_seq, mut offset) = (0, None, 0u64);
+    while let Some((seq, len)) = read_record_header(&mut reader)? {
+        offset += (RECORD_HEADER_LEN + len) as u64;
+        if offset > file_len {
+            break;
+        }
+        reader.seek_relative(len as i64)?;
+        frames += 1;
+        last_seq = Some(seq);
+    }
+    Ok((frames, last_seq))
+}
+
+// Reads the records of a segment, a truncated record at the tail is ignored
+fn read_segment(path: &Path) -> io::Result<Vec<(u64, Vec<u8>)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut records = vec![];
+    while let Some((seq, len)) = read_record_header(&mut reader)? {
+        let mut frame = vec![0u8; len];
+        match reader.read_exact(&mut frame) {
+            Ok(_) => records.push((seq, frame)),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(records)
+}
+
+// A bounded write-ahead log of the frames a uniform sender failed to send, replayed in order
+// after the connection recovers.
+//
+// The frames are appended to segment files named by the sequence of their first frame in
+// `<directory>/<sender name>/`. The oldest segments are removed when the total size exceeds
+// `max_size` or when they are not written for `max_age`, and a segment is removed after all
+// of its frames are replayed. Frames are only removed with the whole segment, so a restart may
+// replay some frames again. The replayed frames are flagged in their headers, and the ingester
+// drops those with sequences it has received recently from the same agent.
+pub struct DiskBuffer {
+    name: &'static str,
+    directory: PathBuf,
+    config: DiskBufferConfig,
+    counter: Arc<DiskBufferCounter>,
+
+    // sealed segments from the oldest, followed by the active one if `writer` is some
+    segments: VecDeque<Segment>,
+    writer: Option<File>,
+    total_size: u64,
+    next_sequence: u64,
+
+    // frames of the oldest segment loaded for replay
+    replaying: VecDeque<Vec<u8>>,
+}
+
+impl DiskBuffer {
+    const SEGMENT_SIZE: u64 = 4 << 20;
+
+    pub fn open(
+        name: &'static str,
+        config: &DiskBufferConfig,
+        counter: Arc<DiskBufferCounter>,
+    ) -> io::Result<Self> {
+        let directory = Path::new(&config.directory).join(name);
+        create_dir_all(&directory)?;
+
+        let mut paths = vec![];
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.ends_with(SEGMENT_SUFFIX))
+                .unwrap_or(false)
+            {
+                paths.push(path);
+            }
+        }
+        // the zero padded sequences in the names are sortable
+        paths.sort();
+
+        let mut buffer = Self {
+            name,
+            directory,
+            config: config.clone(),
+            counter,
+            segments: VecDeque::new(),
+            writer: None,
+            total_size: 0,
+            next_sequence: 0,
+            replaying: VecDeque::new(),
+        };
+        for path in paths {
+            let (frames, last_seq) = match scan_segment(&path) {
+                Ok((frames, Some(last_seq))) => (frames, last_seq),
+                Ok(_) => {
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+                Err(e) => {
+                    warn!(
+                        "{} sender read disk buffer segment {} failed: {}",
+                        name,
+                        path.display(),
+                        e
+                    );
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+            };
+            let metadata = fs::metadata(&path)?;
+            buffer.next_sequence = buffer.next_sequence.max(last_seq.wrapping_add(1));
+            buffer.total_size += metadata.len();
+            buffer.segments.push_back(Segment {
+                path,
+                frames,
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or_else(|_| SystemTime::now()),
+            });
+        }
+        if !buffer.segments.is_empty() {
+            info!(
+                "{} sender disk buffer opened with {} frames in {} bytes",
+                name,
+                buffer.frames(),
+                buffer.total_size
+            );
+        }
+        buffer.expire();
+        buffer.evict();
+        buffer.update_size();
+        Ok(buffer)
+    }
+
+    // The sequence following the last buffered frame, including those of previous runs
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    fn frames(&self) -> usize {
+        self.segments.iter().map(|s| s.frames).sum()
+    }
+
+    fn update_size(&self) {
+        self.counter.size.store(self.total_size, Ordering::Relaxed);
+    }
+
+    fn remove_oldest(&mut self) -> usize {
+        let Some(segment) = self.segments.pop_front() else {
+            return 0;
+        };
+        if self.segments.is_empty() {
+            self.writer = None;
+        }
+        if let Err(e) = fs::remove_file(&segment.path) {
+            warn!(
+                "{} sender remove disk buffer segment {} failed: {}",
+                self.name,
+                segment.path.display(),
+                e
+            );
+        }
+        self.total_size -= segment.size;
+        // the loaded frames of the oldest segment are already counted in it
+        let remaining = if self.replaying.is_empty() {
+            segment.frames
+        } else {
+            self.replaying.len()
+        };
+        self.replaying.clear();
+        remaining
+    }
+
+    fn evict(&mut self) {
+        let mut dropped = 0;
+        while self.total_size > self.config.max_size && !self.segments.is_empty() {
+            dropped += self.remove_oldest();
+        }
+        if dropped > 0 {
+            warn!(
+                "{} sender disk buffer exceeds {} bytes, {} frames dropped",
+                self.name, self.config.max_size, dropped
+            );
+            self.counter
+                .dropped
+                .fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn expire(&mut self) {
+        let Some(deadline) = SystemTime::now().checked_sub(self.config.max_age) else {
+            return;
+        };
+        let mut dropped = 0;
+        while self
+            .segments
+            .front()
+            .map(|s| s.modified < deadline)
+            .unwrap_or(false)
+        {
+            dropped += self.remove_oldest();
+        }
+        if dropped > 0 {
+            warn!(
+                "{} sender disk buffer frames older than {:?} expired, {} frames dropped",
+                self.name, self.config.max_age, dropped
+            );
+            self.counter
+                .dropped
+                .fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn append(&mut self, sequence: u64, frame: &[u8]) -> io::Result<()> {
+        let sealed = self
+            .segments
+            .back()
+            .map(|s| s.size >= Self::SEGMENT_SIZE)
+            .unwrap_or(true);
+        if self.writer.is_none() || sealed {
+            let path = self
+                .directory
+                .join(format!("{:020}{}", sequence, SEGMENT_SUFFIX));
+            self.writer = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+            self.segments.push_back(Segment {
+                path,
+                frames: 0,
+                size: 0,
+                modified: SystemTime::now(),
+            });
+        }
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + frame.len());
+        record.extend_from_slice(&sequence.to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(frame);
+        let result = self.writer.as_mut().unwrap().write_all(&record);
+        // a partially written record is ignored when read
+        let segment = self.segments.back_mut().unwrap();
+        segment.size += record.len() as u64;
+        segment.modified = SystemTime::now();
+        self.total_size += record.len() as u64;
+        result?;
+        segment.frames += 1;
+        Ok(())
+    }
+
+    pub fn push(&mut self, sequence: u64, frame: &[u8]) {
+        self.expire();
+        match self.append(sequence, frame) {
+            Ok(_) => {
+                self.counter.buffered.fetch_add(1, Ordering::Relaxed);
+                self.next_sequence = sequence.wrapping_add(1);
+            }
+            Err(e) => {
+                if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                    warn!(
+                        "{} sender write disk buffer in {} failed: {}",
+                        self.name,
+                        self.directory.display(),
+                        e
+                    );
+                }
+                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                // start a new segment with the next frame
+                self.writer = None;
+            }
+        }
+        self.evict();
+        self.update_size();
+    }
+
+    // The oldest buffered frame, which stays buffered until `consume` is called
+    pub fn peek(&mut self) -> Option<&[u8]> {
+        self.expire();
+        while self.replaying.is_empty() && !self.segments.is_empty() {
+            // the active segment is sealed before replayed
+            if self.segments.len() == 1 {
+                self.writer = None;
+            }
+            let path = &self.segments.front().unwrap().path;
+            match read_segment(path) {
+                Ok(records) if !records.is_empty() => {
+                    self.replaying = records.into_iter().map(|(_, frame)| frame).collect();
+                }
+                Ok(_) => {
+                    self.remove_oldest();
+                }
+                Err(e) => {
+                    warn!(
+                        "{} sender read disk buffer segment {} failed: {}",
+                        self.name,
+                        path.display(),
+                        e
+                    );
+                    let dropped = self.remove_oldest();
+                    self.counter
+                        .dropped
+                        .fetch_add(dropped as u64, Ordering::Relaxed);
+                }
+            }
+            self.update_size();
+        }
+        self.replaying.front().map(|f| f.as_slice())
+    }
+
+    pub fn consume(&mut self) {
+        if self.replaying.pop_front().is_none() {
+            return;
+        }
+        self.counter.replayed.fetch_add(1, Ordering::Relaxed);
+        if self.replaying.is_empty() {
+            self.remove_oldest();
+            self.update_size();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn config(dir: &Path, max_size: u64) -> DiskBufferConfig {
+        DiskBufferConfig {
+            enabled: true,
+            directory: dir.to_str().unwrap().to_owned(),
+            max_size,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+
+    fn replay(buffer: &mut DiskBuffer) -> Vec<Vec<u8>> {
+        let mut frames = vec![];
+        while let Some(frame) = buffer.peek() {
+            frames.push(frame.to_vec());
+            buffer.consume();
+        }
+        frames
+    }
+
+    #[test]
+    fn push_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let counter = Arc::new(DiskBufferCounter::default());
+        let mut buffer =
+            DiskBuffer::open("test", &config(dir.path(), 1 << 20), counter.clone()).unwrap();
+        assert!(buffer.is_empty());
+        for i in 0..3u8 {
+            buffer.push(10 + i as u64, &[i; 8]);
+        }
+        assert_eq!(buffer.next_sequence(), 13);
+        assert_eq!(buffer.peek(), Some(&[0u8; 8][..]));
+        // frames not consumed are kept after reopened
+        drop(buffer);
+
+        let mut buffer =
+            DiskBuffer::open("test", &config(dir.path(), 1 << 20), counter.clone()).unwrap();
+        assert_eq!(buffer.next_sequence(), 13);
+        // a truncated record is ignored
+        let path = buffer.segments.front().unwrap().path.clone();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0xff; 4])
+            .unwrap();
+        assert_eq!(
+            replay(&mut buffer),
+            vec![vec![0u8; 8], vec![1; 8], vec![2; 8]]
+        );
+        assert!(buffer.is_empty());
+        assert!(!path.exists());
+        assert_eq!(counter.buffered.load(Ordering::Relaxed), 3);
+        assert_eq!(counter.replayed.load(Ordering::Relaxed), 3);
+
+        buffer.push(13, &[3; 8]);
+        assert_eq!(replay(&mut buffer), vec![vec![3u8; 8]]);
+    }
+
+    #[test]
+    fn corrupted_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let counter = Arc::new(DiskBufferCounter::default());
+        let mut buffer =
+            DiskBuffer::open("test", &config(dir.path(), 1 << 20), counter.clone()).unwrap();
+        buffer.push(0, &[0; 8]);
+        let path = buffer.segments.front().unwrap().path.clone();
+        drop(buffer);
+
+        // a record longer than any frame is not allocated but regarded as corruption
+        let mut record = 1u64.to_le_bytes().to_vec();
+        record.extend_from_slice(&u32::MAX.to_le_bytes());
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&record)
+            .unwrap();
+        assert_eq!(
+            read_segment(&path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        let buffer =
+            DiskBuffer::open("test", &config(dir.path(), 1 << 20), counter.clone()).unwrap();
+        assert!(buffer.is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn evict_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let counter = Arc::new(DiskBufferCounter::default());
+        let frame = vec![0u8; (DiskBuffer::SEGMENT_SIZE / 2) as usize];
+        let mut buffer = DiskBuffer::open(
+            "test",
+            &config(dir.path(), DiskBuffer::SEGMENT_SIZE * 2 + 1024),
+            counter.clone(),
+        )
+        .unwrap();
+        for i in 0..6 {
+            buffer.push(i, &frame);
+        }
+        // two frames in each segment, the first segment is removed
+        assert_eq!(buffer.segments.len(), 2);
+        assert_eq!(counter.dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(replay(&mut buffer).len(), 4);
+        assert_eq!(counter.size.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::{self, create_dir_all, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::Local;
+use flate2::{write::GzEncoder, Compression};
+use log::{info, warn};
+
+use crate::config::{
+    FileExporter as FileExporterConfig, FileExporterCompression, FileExporterTarget,
+};
+
+fn suffix(compression: FileExporterCompression) -> &'static str {
+    match compression {
+        FileExporterCompression::None => ".json",
+        FileExporterCompression::Gzip => ".json.gz",
+    }
+}
+
+// Rotated files of `name` in `dir`, sorted from the oldest
+fn rotated_files(dir: &Path, name: &str, active: &Path) -> io::Result<Vec<PathBuf>> {
+    let prefix = format!("{}.", name);
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path == active {
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if file_name.starts_with(&prefix)
+            && (file_name.ends_with(".json") || file_name.ends_with(".json.gz"))
+        {
+            files.push(path);
+        }
+    }
+    // the timestamps in the names are sortable
+    files.sort();
+    Ok(files)
+}
+
+// Exports a copy of the data of a uniform sender as newline-delimited json, the same as the data
+// files of the standalone mode, to stdout or to local files rotated by size and time.
+//
+// The active file is `<name>.json` (or `<name>.json.gz` compressed) in the directory, and it is
+// renamed to `<name>.<local time>.json` when rotated. Only the latest `max_files` rotated files
+// are kept.
+pub struct FileExporter {
+    name: &'static str,
+    file_name: String,
+    config: FileExporterConfig,
+
+    writer: Option<Box<dyn Write + Send>>,
+    path: PathBuf,
+    written_size: u64,
+    opened_at: Instant,
+    failed: bool,
+}
+
+impl FileExporter {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            file_name: String::new(),
+            config: FileExporterConfig::default(),
+            writer: None,
+            path: PathBuf::new(),
+            written_size: 0,
+            opened_at: Instant::now(),
+            failed: false,
+        }
+    }
+
+    pub fn update_config(&mut self, config: &FileExporterConfig, file_name: &str) {
+        if &self.config == config && self.file_name == file_name {
+            return;
+        }
+        if self.writer.is_some() {
+            info!(
+                "{} sender file exporter config changed to {:?}",
+                self.name, config
+            );
+            self.close();
+        }
+        self.config = config.clone();
+        self.file_name = file_name.to_owned();
+        self.path = Path::new(&config.directory).join(format!(
+            "{}{}",
+            file_name,
+            suffix(config.compression)
+        ));
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        let writer: Box<dyn Write + Send> = match self.config.target {
+            FileExporterTarget::Stdout => Box::new(io::stdout()),
+            FileExporterTarget::File => {
+                create_dir_all(&self.config.directory)?;
+                let file = BufWriter::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&self.path)?,
+                );
+                match self.config.compression {
+                    FileExporterCompression::None => Box::new(file),
+                    // appending to a compressed file adds a gzip member, which is still valid
+                    FileExporterCompression::Gzip => {
+                        Box::new(GzEncoder::new(file, Compression::default()))
+                    }
+                }
+            }
+        };
+        self.writer = Some(writer);
+        self.written_size = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            // the gzip trailer is written when the encoder is dropped
+            let _ = writer.flush();
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.close();
+        if !self.path.exists() {
+            return Ok(());
+        }
+        let dir = Path::new(&self.config.directory);
+        let rotated = dir.join(format!(
+            "{}.{}{}",
+            self.file_name,
+            Local::now().format("%Y%m%d%H%M%S%3f"),
+            suffix(self.config.compression)
+        ));
+        fs::rename(&self.path, &rotated)?;
+
+        let files = rotated_files(dir, &self.file_name, &self.path)?;
+        if files.len() > self.config.max_files {
+            for file in &files[..files.len() - self.config.max_files] {
+                fs::remove_file(file)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn should_rotate(&self) -> bool {
+        self.config.target == FileExporterTarget::File
+            && self.written_size > 0
+            && (self.written_size >= self.config.max_file_size as u64
+                || self.opened_at.elapsed() >= self.config.rotation_interval)
+    }
+
+    fn on_error(&mut self, e: io::Error) {
+        if !self.failed {
+            warn!(
+                "{} sender export to {} failed: {}",
+                self.name,
+                self.path.display(),
+                e
+            );
+            self.failed = true;
+        }
+        // reopen the file in the next write
+        self.close();
+    }
+
+    fn try_write(&mut self, line: &str) -> io::Result<()> {
+        if self.writer.is_none() {
+            self.open()?;
+        }
+        self.writer.as_mut().unwrap().write_all(line.as_bytes())?;
+        self.written_size += line.len() as u64;
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    // `line` is a json ended with a newline
+    pub fn write(&mut self, line: &str) {
+        match self.try_write(line) {
+            Ok(_) => self.failed = false,
+            Err(e) => self.on_error(e),
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        let mut result = writer.flush();
+        if result.is_ok() && self.should_rotate() {
+            result = self.rotate();
+        }
+        if let Err(e) = result {
+            self.on_error(e);
+        }
+    }
+}
+
+impl Drop for FileExporter {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+    use std::time::Duration;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    fn config(dir: &Path, compression: FileExporterCompression) -> FileExporterConfig {
+        FileExporterConfig {
+            enabled: true,
+            target: FileExporterTarget::File,
+            directory: dir.to_str().unwrap().to_owned(),
+            max_file_size: 64,
+            rotation_interval: Duration::from_secs(3600),
+            max_files: 2,
+            compression,
+        }
+    }
+
+    #[test]
+    fn rotate_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exporter = FileExporter::new("test");
+        exporter.update_config(
+            &config(dir.path(), FileExporterCompression::None),
+            "l7_flow_log",
+        );
+        let line = format!("{{\"data\":\"{}\"}}\n", "x".repeat(30));
+        for _ in 0..10 {
+            exporter.write(&line);
+            // keep the names of rotated files unique
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        exporter.flush();
+
+        let active = dir.path().join("l7_flow_log.json");
+        let rotated = rotated_files(dir.path(), "l7_flow_log", &active).unwrap();
+        assert_eq!(rotated.len(), 2);
+        for file in rotated {
+            assert_eq!(fs::read_to_string(file).unwrap(), line.repeat(2));
+        }
+    }
+
+    #[test]
+    fn gzip_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exporter = FileExporter::new("test");
+        exporter.update_config(
+            &config(dir.path(), FileExporterCompression::Gzip),
+            "l4_flow_log",
+        );
+        exporter.write("{}\n");
+        drop(exporter);
+
+        let mut exporter = FileExporter::new("test");
+        exporter.update_config(
+            &config(dir.path(), FileExporterCompression::Gzip),
+            "l4_flow_log",
+        );
+        exporter.write("{}\n");
+        drop(exporter);
+
+        let mut decoded = String::new();
+        MultiGzDecoder::new(File::open(dir.path().join("l4_flow_log.json.gz")).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "{}\n{}\n");
+    }
+}
@@ -0,0 +1,308 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{hash_map::DefaultHasher, BTreeMap};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader};
+use std::sync::{atomic::Ordering, Arc};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use log::{info, warn};
+use rskafka::{
+    client::{
+        partition::{Compression, PartitionClient, UnknownTopicHandling},
+        ClientBuilder, Credentials, SaslConfig,
+    },
+    record::Record,
+};
+use tokio::{
+    runtime::{Builder, Runtime},
+    time::timeout,
+};
+
+use super::uniform_sender::SenderCounter;
+use crate::config::{Kafka, KafkaCompression, KafkaSaslMechanism};
+
+type GenericError = Box<dyn std::error::Error + Send + Sync>;
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+fn partition_of(flow_id: u64, partitions: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    flow_id.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+fn compression(config: &Kafka) -> Compression {
+    match config.compression {
+        KafkaCompression::None => Compression::NoCompression,
+        KafkaCompression::Gzip => Compression::Gzip,
+        KafkaCompression::Snappy => Compression::Snappy,
+    }
+}
+
+fn sasl_config(config: &Kafka) -> Option<SaslConfig> {
+    let credentials = Credentials::new(config.sasl.username.clone(), config.sasl.password.clone());
+    match config.sasl.mechanism {
+        KafkaSaslMechanism::None => None,
+        KafkaSaslMechanism::Plain => Some(SaslConfig::Plain(credentials)),
+        KafkaSaslMechanism::ScramSha256 => Some(SaslConfig::ScramSha256(credentials)),
+        KafkaSaslMechanism::ScramSha512 => Some(SaslConfig::ScramSha512(credentials)),
+    }
+}
+
+fn tls_config(config: &Kafka) -> Result<rustls::ClientConfig, GenericError> {
+    let mut roots = rustls::RootCertStore::empty();
+    if config.tls.ca_file.is_empty() {
+        for cert in rustls_native_certs::load_native_certs()? {
+            // some of the system certificates may not be parsable
+            let _ = roots.add(&rustls::Certificate(cert.0));
+        }
+    } else {
+        let mut reader = BufReader::new(File::open(&config.tls.ca_file)?);
+        for cert in rustls_pemfile::certs(&mut reader)? {
+            roots.add(&rustls::Certificate(cert))?;
+        }
+    }
+    Ok(rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+// Produces the records of a uniform sender to a kafka topic, records of the same flow are sent
+// to the same partition.
+//
+// Records are produced synchronously by the sender thread, a slow kafka cluster blocks the sender
+// and the records are dropped by the queue of the sender when it is full. Records are dropped if
+// the cluster is not reachable, the connection is retried every 10 seconds.
+pub struct KafkaProducer {
+    name: &'static str,
+    topic: String,
+    config: Kafka,
+    counter: Arc<SenderCounter>,
+
+    runtime: Runtime,
+    partitions: Vec<PartitionClient>,
+    last_connect: Option<Instant>,
+
+    pending: Vec<(Option<u64>, Record)>,
+    next_partition: usize,
+    last_flush: Instant,
+}
+
+impl KafkaProducer {
+    pub fn new(name: &'static str, counter: Arc<SenderCounter>) -> io::Result<Self> {
+        Ok(Self {
+            name,
+            topic: String::new(),
+            config: Kafka::default(),
+            counter,
+            runtime: Builder::new_current_thread().enable_all().build()?,
+            partitions: vec![],
+            last_connect: None,
+            pending: vec![],
+            next_partition: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn update_config(&mut self, config: &Kafka, topic: &str) {
+        if &self.config == config && self.topic == topic {
+            return;
+        }
+        if !self.partitions.is_empty() {
+            info!(
+                "{} sender kafka config changed, reconnect to {:?} topic {}",
+                self.name, config.brokers, topic
+            );
+        }
+        self.config = config.clone();
+        self.topic = topic.to_owned();
+        self.partitions.clear();
+        self.last_connect = None;
+    }
+
+    pub fn send(&mut self, flow_id: Option<u64>, value: Vec<u8>) {
+        let record = Record {
+            key: flow_id.map(|id| id.to_string().into_bytes()),
+            value: Some(value),
+            headers: BTreeMap::new(),
+            timestamp: Utc::now(),
+        };
+        self.pending.push((flow_id, record));
+        if self.pending.len() >= self.config.batch_size
+            || self.last_flush.elapsed() >= self.config.flush_interval
+        {
+            self.flush();
+        }
+    }
+
+    fn drop_records(&self, count: usize, e: GenericError) {
+        if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+            warn!(
+                "{} sender produce to kafka {:?} topic {} failed: {}",
+                self.name, self.config.brokers, self.topic, e
+            );
+        }
+        self.counter
+            .dropped
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn connect(&mut self) -> Result<(), GenericError> {
+        if self
+            .last_connect
+            .is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL)
+        {
+            return Err("waiting for reconnection".into());
+        }
+        self.last_connect = Some(Instant::now());
+        if self.config.brokers.is_empty() {
+            return Err("no broker configured".into());
+        }
+
+        let mut builder = ClientBuilder::new(self.config.brokers.clone());
+        if self.config.tls.enabled {
+            builder = builder.tls_config(Arc::new(tls_config(&self.config)?));
+        }
+        if let Some(sasl) = sasl_config(&self.config) {
+            builder = builder.sasl_config(sasl);
+        }
+        let topic = self.topic.clone();
+        let partitions = self
+            .runtime
+            .block_on(timeout(self.config.timeout, async move {
+                let client = builder.build().await?;
+                let Some(count) = client
+                    .list_topics()
+                    .await?
+                    .into_iter()
+                    .find(|t| t.name == topic)
+                    .map(|t| t.partitions.len())
+                else {
+                    return Err(format!("topic {} not found", topic).into());
+                };
+                let mut partitions = Vec::with_capacity(count);
+                for partition in 0..count as i32 {
+                    partitions.push(
+                        client
+                            .partition_client(topic.clone(), partition, UnknownTopicHandling::Error)
+                            .await?,
+                    );
+                }
+                Ok::<_, GenericError>(partitions)
+            }))??;
+        if partitions.is_empty() {
+            return Err(format!("topic {} has no partition", self.topic).into());
+        }
+        info!(
+            "{} sender connected to kafka {:?} topic {} with {} partitions",
+            self.name,
+            self.config.brokers,
+            self.topic,
+            partitions.len()
+        );
+        self.partitions = partitions;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() {
+            return;
+        }
+        let records = std::mem::take(&mut self.pending);
+        if self.partitions.is_empty() {
+            if let Err(e) = self.connect() {
+                self.drop_records(records.len(), e);
+                return;
+            }
+        }
+
+        let mut batches: Vec<Vec<Record>> = (0..self.partitions.len()).map(|_| vec![]).collect();
+        for (flow_id, record) in records {
+            let partition = match flow_id {
+                Some(id) => partition_of(id, batches.len()),
+                None => {
+                    self.next_partition = (self.next_partition + 1) % batches.len();
+                    self.next_partition
+                }
+            };
+            batches[partition].push(record);
+        }
+        let compression = compression(&self.config);
+        let (mut dropped, mut error) = (0, None);
+        for (partition, batch) in batches.into_iter().enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+            if error.is_some() {
+                dropped += batch.len();
+                continue;
+            }
+            let (count, bytes) = (
+                batch.len(),
+                batch
+                    .iter()
+                    .map(|r| r.value.as_ref().map(|v| v.len()).unwrap_or_default())
+                    .sum::<usize>(),
+            );
+            let result = self.runtime.block_on(timeout(
+                self.config.timeout,
+                self.partitions[partition].produce(batch, compression),
+            ));
+            match result {
+                Ok(Ok(_)) => {
+                    self.counter.tx.fetch_add(1, Ordering::Relaxed);
+                    self.counter
+                        .tx_bytes
+                        .fetch_add(bytes as u64, Ordering::Relaxed);
+                }
+                Ok(Err(e)) => {
+                    dropped += count;
+                    error = Some(GenericError::from(e));
+                }
+                Err(e) => {
+                    dropped += count;
+                    error = Some(GenericError::from(e));
+                }
+            }
+        }
+        if let Some(e) = error {
+            self.drop_records(dropped, e);
+            // reconnect in the next flush as the leaders of the partitions may have changed
+            self.partitions.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_by_flow() {
+        for flow_id in 0..1000 {
+            let partition = partition_of(flow_id, 12);
+            assert!(partition < 12);
+            assert_eq!(partition, partition_of(flow_id, 12));
+        }
+        assert_eq!(partition_of(12345, 1), 0);
+    }
+}
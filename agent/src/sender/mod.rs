@@ -17,7 +17,14 @@
 use std::sync::atomic::{AtomicU8, Ordering};
 
 // NpbBandwidthWatcher NewFragmenterBuilder NewCompressorBuilder NewPCapBuilder NewUniformCollectSender
+mod clickhouse_sender;
+mod destination;
+mod disk_buffer;
+mod file_exporter;
+mod kafka_sender;
 pub mod npb_sender;
+pub(crate) mod otlp_exporter;
+pub(crate) mod syslog_exporter;
 mod tcp_packet;
 pub(crate) mod uniform_sender;
 
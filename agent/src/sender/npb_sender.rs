@@ -483,6 +483,7 @@ pub struct NpbSenderCounter {
     pub tx: AtomicUsize,
     pub tx_bytes: AtomicUsize,
     pub tx_dropped: AtomicUsize,
+    pub tx_rate_limited: AtomicUsize,
 }
 
 pub struct StatsNpbSenderCounter(Weak<NpbSenderCounter>);
@@ -511,6 +512,11 @@ impl OwnedCountable for StatsNpbSenderCounter {
                         CounterType::Counted,
                         CounterValue::Unsigned(x.tx_dropped.swap(0, Ordering::Relaxed) as u64),
                     ),
+                    (
+                        "tx_rate_limited",
+                        CounterType::Counted,
+                        CounterValue::Unsigned(x.tx_rate_limited.swap(0, Ordering::Relaxed) as u64),
+                    ),
                 ]
             }
             None => vec![],
@@ -775,12 +781,53 @@ impl NpbArpTable {
     }
 }
 
+// Token bucket of one destination, tokens are refilled by packet timestamps so that no
+// timer thread is needed for each destination
+struct DestinationLimiter {
+    bps: u64,
+    tokens: u64,
+    last_refill: u64,
+}
+
+impl DestinationLimiter {
+    const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+    fn new(bps: u64) -> Self {
+        Self {
+            bps,
+            tokens: 0,
+            last_refill: 0,
+        }
+    }
+
+    fn acquire(&mut self, timestamp: u64, bits: u64) -> bool {
+        if timestamp > self.last_refill {
+            let refill =
+                (timestamp - self.last_refill) as u128 * self.bps as u128 / Self::NANOS_PER_SECOND;
+            // keep last_refill if the interval is too short to refill a token
+            if refill > 0 {
+                // allow bursts of up to one second
+                self.tokens = (self.tokens as u128 + refill).min(self.bps as u128) as u64;
+                self.last_refill = timestamp;
+            }
+        }
+        if self.tokens < bits {
+            return false;
+        }
+        self.tokens -= bits;
+        true
+    }
+}
+
 pub struct NpbConnectionPool {
     connections: HashMap<(u128, u8), NpbSender>,
     socket_type: SocketType,
     npb_port: u16,
     underlay_is_ipv6: bool,
 
+    destination_bps_threshold: u64,
+    limiters: HashMap<u128, DestinationLimiter>,
+
     counter: Arc<NpbSenderCounter>,
 
     arp: Arc<NpbArpTable>,
@@ -792,6 +839,7 @@ impl NpbConnectionPool {
         underlay_is_ipv6: bool,
         socket_type: SocketType,
         npb_port: u16,
+        destination_bps_threshold: u64,
         arp: Arc<NpbArpTable>,
         stats_collector: Arc<stats::Collector>,
     ) -> Self {
@@ -816,6 +864,8 @@ impl NpbConnectionPool {
             socket_type,
             npb_port,
             underlay_is_ipv6,
+            destination_bps_threshold,
+            limiters: HashMap::new(),
             counter,
             arp,
         }
@@ -849,13 +899,8 @@ impl NpbConnectionPool {
         }
     }
 
-    fn send_to(
-        &mut self,
-        timestamp: u64,
-        underlay_l2_opt_size: usize,
-        packet: Vec<u8>,
-    ) -> IOResult<usize> {
-        let (remote, key) = if self.underlay_is_ipv6 {
+    fn destination(&self, underlay_l2_opt_size: usize, packet: &[u8]) -> (IpAddr, (u128, u8)) {
+        if self.underlay_is_ipv6 {
             let offset = IPV6_DST_OFFSET + underlay_l2_opt_size;
             let ip = Ipv6Addr::from(
                 *<&[u8; 16]>::try_from(&packet[offset..offset + IPV6_ADDR_LEN]).unwrap(),
@@ -879,8 +924,17 @@ impl NpbConnectionPool {
                     packet[IPV4_PROTO_OFFSET + underlay_l2_opt_size],
                 ),
             )
-        };
+        }
+    }
 
+    fn send_to(
+        &mut self,
+        timestamp: u64,
+        remote: IpAddr,
+        key: (u128, u8),
+        underlay_l2_opt_size: usize,
+        packet: Vec<u8>,
+    ) -> IOResult<usize> {
         let mut conn = self.connections.get_mut(&key);
         if conn.is_some() {
             return conn
@@ -906,7 +960,19 @@ impl NpbConnectionPool {
         packet: Vec<u8>,
     ) -> IOResult<usize> {
         let bytes = packet.len();
-        let ret = self.send_to(timestamp, underlay_l2_opt_size, packet);
+        let (remote, key) = self.destination(underlay_l2_opt_size, &packet);
+        if self.destination_bps_threshold > 0 {
+            let threshold = self.destination_bps_threshold;
+            let limiter = self
+                .limiters
+                .entry(key.0)
+                .or_insert_with(|| DestinationLimiter::new(threshold));
+            if !limiter.acquire(timestamp, bytes as u64 * 8) {
+                self.counter.tx_rate_limited.fetch_add(1, Ordering::Relaxed);
+                return Ok(0);
+            }
+        }
+        let ret = self.send_to(timestamp, remote, key, underlay_l2_opt_size, packet);
         if ret.is_err() {
             self.counter.tx_dropped.fetch_add(1, Ordering::Relaxed);
             return ret;
@@ -942,6 +1008,7 @@ impl NpbPacketSender {
                 config.underlay_is_ipv6,
                 config.socket_type,
                 config.npb_port,
+                config.destination_bps_threshold,
                 arp.clone(),
                 stats_collector,
             )),
@@ -988,3 +1055,22 @@ impl NpbPacketSender {
         self.disable.store(true, Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destination_limiter() {
+        const SECOND: u64 = 1_000_000_000;
+        let mut limiter = DestinationLimiter::new(8000);
+        // bucket is full at start
+        assert!(limiter.acquire(SECOND, 8000));
+        assert!(!limiter.acquire(SECOND, 8));
+        assert!(limiter.acquire(SECOND + SECOND / 2, 4000));
+        assert!(!limiter.acquire(SECOND + SECOND / 2, 8));
+        // refill is capped at one second
+        assert!(!limiter.acquire(10 * SECOND, 8008));
+        assert!(limiter.acquire(10 * SECOND, 8000));
+    }
+}
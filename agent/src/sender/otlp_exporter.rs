@@ -0,0 +1,513 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use arc_swap::access::Access;
+use log::{debug, info, warn};
+use rand::{thread_rng, RngCore};
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, Endpoint};
+
+use super::QUEUE_BATCH_SIZE;
+use crate::config::handler::{LogParserAccess, OtlpExporterConfig};
+use crate::flow_generator::protocol_logs::{
+    pb_adapter::{KeyVal, L7ProtocolSendLog},
+    L7ResponseStatus, MetaAppProto,
+};
+use crate::metric::document::TapSide;
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+use public::{
+    l7_protocol::L7ProtocolChecker,
+    proto::integration::opentelemetry::proto::{
+        collector::trace::v1::{
+            trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+        },
+        common::v1::{
+            any_value::Value::{IntValue, StringValue},
+            AnyValue, InstrumentationScope, KeyValue,
+        },
+        resource::v1::Resource,
+        trace::v1::{span::SpanKind, status::StatusCode, ResourceSpans, ScopeSpans, Span, Status},
+    },
+    queue::{DebugSender, Error, Receiver},
+};
+
+const SCOPE_NAME: &str = "deepflow-agent";
+// spans are exported when the batch is full or every second
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct OtlpExporterCounter {
+    // spans dropped because the queue is full
+    queue_drop: AtomicU64,
+    exported: AtomicU64,
+    // spans dropped because the collector is unreachable or rejects them
+    export_failed: AtomicU64,
+}
+
+impl RefCountable for OtlpExporterCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "queue-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.queue_drop.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "exported",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.exported.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "export-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.export_failed.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Debug)]
+pub struct ExportedSpan {
+    // service.name of the resource, empty for the configured one
+    service_name: String,
+    span: Span,
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(StringValue(value)),
+        }),
+    }
+}
+
+fn int_attribute(key: &str, value: i64) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(IntValue(value)),
+        }),
+    }
+}
+
+// 64-bit FNV-1a with the given offset basis, stable across agents
+fn fnv1a(offset: u64, s: &str) -> u64 {
+    s.bytes().fold(offset, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// IDs in W3C format are hex strings of the same length, others (e.g. sw8 and custom headers)
+// are hashed so that the agents on both sides of a call get the same ID
+fn parse_id<const N: usize>(id: Option<&String>) -> Option<[u8; N]> {
+    let id = id.map(|s| s.trim()).filter(|s| !s.is_empty())?;
+    let mut bytes = [0u8; N];
+    if id.len() == N * 2 && hex::decode_to_slice(id, &mut bytes).is_ok() {
+        return Some(bytes);
+    }
+    for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+        let h = fnv1a(0xcbf2_9ce4_8422_2325 ^ i as u64, id).to_be_bytes();
+        chunk.copy_from_slice(&h[..chunk.len()]);
+    }
+    Some(bytes)
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+// Converts a completed session into a span
+//
+// The span ID in the request headers is the ID of the client span. So captured at the client
+// side, the span takes the extracted span ID and parent span ID, while at the server side, the
+// extracted span ID is the parent, and the span gets a new ID.
+fn to_span(item: &MetaAppProto) -> ExportedSpan {
+    let base = &item.base_info;
+    let log: L7ProtocolSendLog = item.l7_info.clone().into();
+    let trace_info = log.trace_info.unwrap_or_default();
+    let ext_info = log.ext_info.unwrap_or_default();
+
+    let tap_side = base.tap_side as u8;
+    let (kind, span_id, parent_span_id) = if tap_side & TapSide::Server as u8 != 0 {
+        (
+            SpanKind::Server,
+            None,
+            parse_id::<8>(trace_info.span_id.as_ref()),
+        )
+    } else {
+        (
+            if tap_side & TapSide::Client as u8 != 0 {
+                SpanKind::Client
+            } else {
+                SpanKind::Internal
+            },
+            parse_id::<8>(trace_info.span_id.as_ref()),
+            parse_id::<8>(trace_info.parent_span_id.as_ref()),
+        )
+    };
+
+    let protocol = ext_info
+        .protocol_str
+        .clone()
+        .unwrap_or_else(|| format!("{:?}", base.head.proto));
+    let req = &log.req;
+    let target = if req.endpoint.is_empty() {
+        &req.resource
+    } else {
+        &req.endpoint
+    };
+    let name = [req.req_type.as_str(), target.as_str()]
+        .iter()
+        .filter(|s| !s.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut attributes = vec![
+        string_attribute("network.protocol.name", protocol.clone()),
+        string_attribute("client.address", base.ip_src.to_string()),
+        int_attribute("client.port", base.port_src as i64),
+        string_attribute("server.address", base.ip_dst.to_string()),
+        int_attribute("server.port", base.port_dst as i64),
+        string_attribute("df.capture_side", format!("{:?}", base.tap_side)),
+        int_attribute("df.flow_id", base.flow_id as i64),
+    ];
+    for (key, value) in [
+        ("df.request_type", &req.req_type),
+        ("df.request_domain", &req.domain),
+        ("df.request_resource", &req.resource),
+        ("df.endpoint", &req.endpoint),
+        ("df.response_exception", &log.resp.exception),
+        ("df.response_result", &log.resp.result),
+    ] {
+        if !value.is_empty() {
+            attributes.push(string_attribute(key, value.clone()));
+        }
+    }
+    if let Some(code) = log.resp.code {
+        attributes.push(int_attribute("df.response_code", code as i64));
+    }
+    if let Some(rpc_service) = ext_info.rpc_service {
+        attributes.push(string_attribute("rpc.service", rpc_service));
+    }
+    if let Some(x_request_id) = ext_info.x_request_id_0 {
+        attributes.push(string_attribute("df.x_request_id_0", x_request_id));
+    }
+    if let Some(x_request_id) = ext_info.x_request_id_1 {
+        attributes.push(string_attribute("df.x_request_id_1", x_request_id));
+    }
+    if base.sample_rate > 1 {
+        attributes.push(int_attribute("df.sample_rate", base.sample_rate as i64));
+    }
+    for KeyVal { key, val } in ext_info.attributes.unwrap_or_default() {
+        attributes.push(string_attribute(&key, val));
+    }
+
+    let status = match log.resp.status {
+        L7ResponseStatus::Ok => Status {
+            code: StatusCode::Unset as i32,
+            ..Default::default()
+        },
+        _ => Status {
+            message: log.resp.exception.clone(),
+            code: StatusCode::Error as i32,
+            ..Default::default()
+        },
+    };
+
+    let start_time = base.start_time.as_nanos();
+    ExportedSpan {
+        service_name: ext_info.service_name.unwrap_or_default(),
+        span: Span {
+            trace_id: parse_id::<16>(trace_info.trace_id.as_ref())
+                .unwrap_or_else(random_id)
+                .to_vec(),
+            span_id: span_id.unwrap_or_else(random_id).to_vec(),
+            parent_span_id: parent_span_id.map(|id| id.to_vec()).unwrap_or_default(),
+            name: if name.is_empty() { protocol } else { name },
+            kind: kind as i32,
+            start_time_unix_nano: start_time,
+            end_time_unix_nano: base.end_time.as_nanos().max(start_time),
+            attributes,
+            status: Some(status),
+            ..Default::default()
+        },
+    }
+}
+
+// Exports the completed sessions as OTLP spans, the sessions are converted in the threads of
+// the session aggregators and exported in the exporter thread
+pub struct OtlpExporter {
+    sender: DebugSender<ExportedSpan>,
+    counter: Arc<OtlpExporterCounter>,
+}
+
+impl OtlpExporter {
+    pub fn new(sender: DebugSender<ExportedSpan>) -> Self {
+        Self {
+            sender,
+            counter: Arc::new(OtlpExporterCounter::default()),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<OtlpExporterCounter> {
+        self.counter.clone()
+    }
+
+    pub fn export(&self, config: &OtlpExporterConfig, item: &MetaAppProto) {
+        if !config.enabled
+            || !config
+                .export_protocols
+                .is_enabled(item.base_info.head.proto)
+        {
+            return;
+        }
+        if self.sender.send(to_span(item)).is_err() {
+            self.counter.queue_drop.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+struct Exporter {
+    input: Arc<Receiver<ExportedSpan>>,
+    config: LogParserAccess,
+    runtime: Arc<Runtime>,
+    running: Arc<AtomicBool>,
+    counter: Arc<OtlpExporterCounter>,
+
+    // endpoint of the connected client
+    endpoint: String,
+    client: Option<TraceServiceClient<Channel>>,
+}
+
+impl Exporter {
+    fn connect(&mut self, config: &OtlpExporterConfig) -> Option<TraceServiceClient<Channel>> {
+        if self.endpoint != config.endpoint {
+            self.client = None;
+        }
+        if self.client.is_none() {
+            let endpoint = match Endpoint::from_shared(config.endpoint.clone()) {
+                Ok(ep) => ep,
+                Err(e) => {
+                    warn!("invalid otlp exporter endpoint {}: {}", config.endpoint, e);
+                    return None;
+                }
+            };
+            let endpoint = endpoint
+                .connect_timeout(config.export_timeout)
+                .timeout(config.export_timeout);
+            match self.runtime.block_on(endpoint.connect()) {
+                Ok(channel) => {
+                    info!("otlp exporter connected to {}", config.endpoint);
+                    self.endpoint = config.endpoint.clone();
+                    self.client = Some(TraceServiceClient::new(channel));
+                }
+                Err(e) => {
+                    warn!("otlp exporter connect to {} failed: {}", config.endpoint, e);
+                    return None;
+                }
+            }
+        }
+        self.client.clone()
+    }
+
+    fn export(&mut self, config: &OtlpExporterConfig, spans: &mut Vec<ExportedSpan>) {
+        let count = spans.len() as u64;
+        let Some(mut client) = self.connect(config) else {
+            spans.clear();
+            self.counter
+                .export_failed
+                .fetch_add(count, Ordering::Relaxed);
+            return;
+        };
+
+        let mut services: HashMap<String, Vec<Span>> = HashMap::new();
+        for s in spans.drain(..) {
+            let service_name = if s.service_name.is_empty() {
+                config.service_name.clone()
+            } else {
+                s.service_name
+            };
+            services.entry(service_name).or_default().push(s.span);
+        }
+        let request = ExportTraceServiceRequest {
+            resource_spans: services
+                .into_iter()
+                .map(|(service_name, spans)| ResourceSpans {
+                    resource: Some(Resource {
+                        attributes: vec![string_attribute("service.name", service_name)],
+                        ..Default::default()
+                    }),
+                    scope_spans: vec![ScopeSpans {
+                        scope: Some(InstrumentationScope {
+                            name: SCOPE_NAME.to_owned(),
+                            ..Default::default()
+                        }),
+                        spans,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        match self.runtime.block_on(client.export(request)) {
+            Ok(_) => {
+                self.counter.exported.fetch_add(count, Ordering::Relaxed);
+            }
+            Err(e) => {
+                warn!("otlp exporter export {} spans failed: {}", count, e);
+                // reconnect in the next export
+                self.client = None;
+                self.counter
+                    .export_failed
+                    .fetch_add(count, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        let mut spans = vec![];
+        let mut last_flush = Instant::now();
+        while self.running.load(Ordering::Relaxed) {
+            match self.input.recv_all(&mut batch, Some(FLUSH_INTERVAL)) {
+                Ok(_) => spans.append(&mut batch),
+                Err(Error::Timeout) => (),
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+            let config = self.config.load().otlp_exporter.clone();
+            if spans.len() < config.batch_size && last_flush.elapsed() < FLUSH_INTERVAL {
+                continue;
+            }
+            last_flush = Instant::now();
+            if !config.enabled {
+                // spans queued before disabled
+                spans.clear();
+                continue;
+            }
+            while !spans.is_empty() {
+                let mut chunk = spans.split_off(spans.len().saturating_sub(config.batch_size));
+                debug!("otlp exporter export {} spans", chunk.len());
+                self.export(&config, &mut chunk);
+            }
+        }
+    }
+}
+
+pub struct OtlpExporterThread {
+    input: Arc<Receiver<ExportedSpan>>,
+    config: LogParserAccess,
+    runtime: Arc<Runtime>,
+    counter: Arc<OtlpExporterCounter>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl OtlpExporterThread {
+    pub fn new(
+        input: Receiver<ExportedSpan>,
+        config: LogParserAccess,
+        runtime: Arc<Runtime>,
+        exporter: &OtlpExporter,
+    ) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            runtime,
+            counter: exporter.get_counter_handle(),
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("otlp exporter already started, do nothing.");
+            return;
+        }
+
+        let mut exporter = Exporter {
+            input: self.input.clone(),
+            config: self.config.clone(),
+            runtime: self.runtime.clone(),
+            running: self.running.clone(),
+            counter: self.counter.clone(),
+            endpoint: String::new(),
+            client: None,
+        };
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("otlp-exporter".to_owned())
+                .spawn(move || exporter.process())
+                .unwrap(),
+        );
+        info!("otlp exporter started");
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("otlp exporter already stopped, do nothing.");
+            return None;
+        }
+        info!("notified stopping otlp exporter");
+        self.thread_handle.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ids() {
+        let trace_id = "0af7651916cd43dd8448eb211c80319c".to_owned();
+        assert_eq!(
+            parse_id::<16>(Some(&trace_id)).unwrap().to_vec(),
+            hex::decode(&trace_id).unwrap()
+        );
+        let span_id = "b7ad6b7169203331".to_owned();
+        assert_eq!(
+            parse_id::<8>(Some(&span_id)).unwrap().to_vec(),
+            hex::decode(&span_id).unwrap()
+        );
+
+        // non-W3C IDs are hashed consistently
+        let sw8_id = "a3b1f7c8.1.16563474296480001".to_owned();
+        let id = parse_id::<16>(Some(&sw8_id)).unwrap();
+        assert_eq!(parse_id::<16>(Some(&sw8_id)).unwrap(), id);
+        assert_ne!(id[..8], id[8..]);
+        assert_ne!(
+            parse_id::<16>(Some(&"a3b1f7c8.1.16563474296480002".to_owned())).unwrap(),
+            id
+        );
+
+        assert!(parse_id::<8>(None).is_none());
+        assert!(parse_id::<8>(Some(&" ".to_owned())).is_none());
+    }
+}
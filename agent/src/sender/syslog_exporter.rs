@@ -0,0 +1,657 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#[cfg(target_os = "linux")]
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+#[cfg(target_os = "linux")]
+use std::net::{IpAddr, Ipv4Addr};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::process;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use arc_swap::access::Access;
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{info, warn};
+
+use super::QUEUE_BATCH_SIZE;
+use crate::common::enums::IpProtocol;
+#[cfg(target_os = "linux")]
+use crate::common::{enums::CaptureNetworkType, Timestamp};
+use crate::config::{handler::SenderAccess, Syslog, SyslogFormat, SyslogTransport};
+use crate::flow_generator::anomaly::{SecurityEvent, SecurityEventType};
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+use public::queue::{DebugSender, Error, Receiver};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+const TCP_TIMEOUT: Duration = Duration::from_secs(3);
+const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+// the private enterprise number reserved for documentation, as deepflow has none
+const SD_ID: &str = "deepflow@32473";
+
+#[derive(Default)]
+pub struct SyslogExporterCounter {
+    // events dropped because the queue is full
+    queue_drop: AtomicU64,
+    exported: AtomicU64,
+    // events dropped over max_events_per_second
+    rate_limited: AtomicU64,
+    send_failed: AtomicU64,
+}
+
+impl RefCountable for SyslogExporterCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "queue-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.queue_drop.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "exported",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.exported.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rate-limited",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rate_limited.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "send-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.send_failed.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// An event to be exported, with the fields in the default names
+#[derive(Debug)]
+pub struct SyslogEvent {
+    event_type: SecurityEventType,
+    time: Duration,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl From<&SecurityEvent> for SyslogEvent {
+    fn from(e: &SecurityEvent) -> Self {
+        let mut fields = vec![];
+        match e.event_type {
+            SecurityEventType::PortScan | SecurityEventType::ConnectionStorm => {
+                fields.push(("src_ip", e.ip.to_string()));
+            }
+            SecurityEventType::SynFlood | SecurityEventType::NewListeningPort => {
+                fields.push(("dst_ip", e.ip.to_string()));
+                fields.push(("dst_port", e.port.to_string()));
+            }
+            SecurityEventType::PolicyViolation => {
+                fields.push(("src_ip", e.ip.to_string()));
+                if let Some(peer_ip) = e.peer_ip {
+                    fields.push(("dst_ip", peer_ip.to_string()));
+                }
+                fields.push(("dst_port", e.port.to_string()));
+            }
+        }
+        match e.protocol {
+            IpProtocol::TCP => fields.push(("protocol", "tcp".to_owned())),
+            IpProtocol::UDP => fields.push(("protocol", "udp".to_owned())),
+            p if u8::from(p) != 0 => fields.push(("protocol", u8::from(p).to_string())),
+            _ => (),
+        }
+        if e.window > 0 {
+            fields.push(("count", e.count.to_string()));
+        }
+        if e.threshold > 0 {
+            fields.push(("threshold", e.threshold.to_string()));
+        }
+        if e.window > 0 {
+            fields.push(("window", e.window.to_string()));
+        }
+        if e.event_type != SecurityEventType::NewListeningPort {
+            fields.push(("capture_network_type", e.tap_type.to_string()));
+        }
+        if !e.detail.is_empty() {
+            fields.push(("rule", e.detail.clone()));
+        }
+        Self {
+            event_type: e.event_type,
+            time: e.time.into(),
+            fields,
+        }
+    }
+}
+
+impl SyslogEvent {
+    // (syslog severity, CEF severity)
+    fn severity(&self) -> (u8, u8) {
+        match self.event_type {
+            SecurityEventType::SynFlood => (3, 8),
+            SecurityEventType::PolicyViolation => (4, 7),
+            SecurityEventType::PortScan | SecurityEventType::ConnectionStorm => (4, 6),
+            SecurityEventType::NewListeningPort => (5, 4),
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self.event_type {
+            SecurityEventType::PortScan => "Port scan detected",
+            SecurityEventType::SynFlood => "SYN flood detected",
+            SecurityEventType::ConnectionStorm => "Connection storm detected",
+            SecurityEventType::NewListeningPort => "New listening port",
+            SecurityEventType::PolicyViolation => "Flow policy violation",
+        }
+    }
+}
+
+// Key of a field in the format, None if omitted
+fn field_key<'a>(config: &'a Syslog, name: &'a str) -> Option<&'a str> {
+    if let Some(key) = config.field_mapping.get(name) {
+        return Some(key.as_str()).filter(|k| !k.is_empty());
+    }
+    if config.format == SyslogFormat::Rfc5424 {
+        return Some(name);
+    }
+    Some(match name {
+        "src_ip" => "src",
+        "dst_ip" => "dst",
+        "dst_port" => "dpt",
+        "protocol" => "proto",
+        "count" => "cnt",
+        "threshold" => "cn1",
+        "window" => "cn2",
+        "rule" => "cs1",
+        "capture_network_type" => "cs2",
+        other => other,
+    })
+}
+
+fn escape_sd_value(value: &str, output: &mut String) {
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+}
+
+fn escape_cef(value: &str, extension: bool, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '|' if !extension => output.push_str("\\|"),
+            '=' if extension => output.push_str("\\="),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            c => output.push(c),
+        }
+    }
+}
+
+// Formats an RFC 5424 message, CEF is carried as the MSG
+fn format_event(config: &Syslog, hostname: &str, event: &SyslogEvent) -> String {
+    let (severity, cef_severity) = event.severity();
+    let time =
+        DateTime::<Utc>::from(UNIX_EPOCH + event.time).to_rfc3339_opts(SecondsFormat::Micros, true);
+    let mut message = format!(
+        "<{}>1 {} {} {} {} {} ",
+        (config.facility.min(23) as u32) * 8 + severity as u32,
+        time,
+        if hostname.is_empty() { "-" } else { hostname },
+        if config.app_name.is_empty() {
+            "-"
+        } else {
+            &config.app_name
+        },
+        process::id(),
+        event.event_type.as_str(),
+    );
+    match config.format {
+        SyslogFormat::Rfc5424 => {
+            message.push('[');
+            message.push_str(SD_ID);
+            for (name, value) in event.fields.iter() {
+                let Some(key) = field_key(config, name) else {
+                    continue;
+                };
+                let _ = write!(message, " {}=\"", key);
+                escape_sd_value(value, &mut message);
+                message.push('"');
+            }
+            let _ = write!(message, "] {}", event.summary());
+        }
+        SyslogFormat::Cef => {
+            let _ = write!(
+                message,
+                "- CEF:0|DeepFlow|deepflow-agent|{}|{}|",
+                env!("CARGO_PKG_VERSION"),
+                event.event_type.as_str()
+            );
+            escape_cef(event.summary(), false, &mut message);
+            let _ = write!(message, "|{}|", cef_severity);
+            let mut first = true;
+            for (name, value) in event.fields.iter() {
+                let Some(key) = field_key(config, name) else {
+                    continue;
+                };
+                if !first {
+                    message.push(' ');
+                }
+                first = false;
+                let _ = write!(message, "{}=", key);
+                escape_cef(value, true, &mut message);
+                // labels of the custom fields
+                if matches!(key, "cn1" | "cn2" | "cn3" | "cs1" | "cs2" | "cs3" | "cs4") {
+                    let _ = write!(message, " {}Label={}", key, name);
+                }
+            }
+        }
+    }
+    message
+}
+
+// Exports the security events as syslog messages to a SIEM, the events are queued by the
+// anomaly detector and sent in the exporter thread
+pub struct SyslogExporter {
+    sender: DebugSender<SyslogEvent>,
+    // updated by the exporter thread, events are not queued if disabled
+    enabled: Arc<AtomicBool>,
+    counter: Arc<SyslogExporterCounter>,
+}
+
+impl SyslogExporter {
+    pub fn new(sender: DebugSender<SyslogEvent>) -> Self {
+        Self {
+            sender,
+            enabled: Default::default(),
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<SyslogExporterCounter> {
+        self.counter.clone()
+    }
+
+    pub fn export(&self, event: &SecurityEvent) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.sender.send(event.into()).is_err() {
+            self.counter.queue_drop.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    fn connect(config: &Syslog) -> io::Result<Self> {
+        let addr = config
+            .destination
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "destination not resolved"))?;
+        match config.transport {
+            SyslogTransport::Udp => {
+                let socket = match addr {
+                    SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0")?,
+                    SocketAddr::V6(_) => UdpSocket::bind("[::]:0")?,
+                };
+                socket.connect(addr)?;
+                Ok(Self::Udp(socket))
+            }
+            SyslogTransport::Tcp => {
+                let stream = TcpStream::connect_timeout(&addr, TCP_TIMEOUT)?;
+                stream.set_write_timeout(Some(TCP_TIMEOUT))?;
+                Ok(Self::Tcp(stream))
+            }
+        }
+    }
+
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        match self {
+            Self::Udp(socket) => socket.send(message.as_bytes()).map(|_| ()),
+            // messages are delimited by newlines on TCP
+            Self::Tcp(stream) => {
+                stream.write_all(message.as_bytes())?;
+                stream.write_all(b"\n")
+            }
+        }
+    }
+}
+
+// Listening TCP ports and bound UDP ports in the network namespace of the agent, an event is
+// generated for each one not in the previous scan
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+struct ListeningPorts {
+    ports: Option<HashSet<(IpProtocol, SocketAddr)>>,
+    last_scan: Option<Instant>,
+}
+
+#[cfg(target_os = "linux")]
+impl ListeningPorts {
+    fn scan() -> HashSet<(IpProtocol, SocketAddr)> {
+        use procfs::net::TcpState;
+
+        let mut ports = HashSet::new();
+        for entries in [procfs::net::tcp(), procfs::net::tcp6()] {
+            for e in entries.into_iter().flatten() {
+                if e.state == TcpState::Listen {
+                    ports.insert((IpProtocol::TCP, e.local_address));
+                }
+            }
+        }
+        for entries in [procfs::net::udp(), procfs::net::udp6()] {
+            for e in entries.into_iter().flatten() {
+                if e.remote_address.port() == 0 {
+                    ports.insert((IpProtocol::UDP, e.local_address));
+                }
+            }
+        }
+        ports
+    }
+
+    fn new_ports(&mut self, interval: Duration) -> Vec<SecurityEvent> {
+        if interval.is_zero() {
+            self.ports = None;
+            return vec![];
+        }
+        if self.last_scan.is_some_and(|t| t.elapsed() < interval) {
+            return vec![];
+        }
+        self.last_scan = Some(Instant::now());
+        let ports = Self::scan();
+        // the first scan is the baseline
+        let events = match self.ports.as_ref() {
+            Some(last) => ports
+                .difference(last)
+                .map(|(protocol, addr)| SecurityEvent {
+                    time: Timestamp::from(UNIX_EPOCH.elapsed().unwrap_or_default()),
+                    event_type: SecurityEventType::NewListeningPort,
+                    tap_type: CaptureNetworkType::default(),
+                    ip: match addr.ip() {
+                        IpAddr::V6(ip) if ip.is_unspecified() => Ipv4Addr::UNSPECIFIED.into(),
+                        ip => ip,
+                    },
+                    port: addr.port(),
+                    count: 0,
+                    threshold: 0,
+                    window: 0,
+                    peer_ip: None,
+                    protocol: *protocol,
+                    detail: String::new(),
+                })
+                .collect(),
+            None => vec![],
+        };
+        self.ports = Some(ports);
+        events
+    }
+}
+
+struct Exporter {
+    input: Arc<Receiver<SyslogEvent>>,
+    config: SenderAccess,
+    running: Arc<AtomicBool>,
+    enabled: Arc<AtomicBool>,
+    counter: Arc<SyslogExporterCounter>,
+
+    hostname: String,
+    connection: Option<Connection>,
+    last_connect: Option<Instant>,
+    // start of the current second and events sent in it
+    rate_window: (Instant, u32),
+    #[cfg(target_os = "linux")]
+    listening_ports: ListeningPorts,
+}
+
+impl Exporter {
+    fn rate_limited(&mut self, config: &Syslog) -> bool {
+        if config.max_events_per_second == 0 {
+            return false;
+        }
+        if self.rate_window.0.elapsed() >= Duration::from_secs(1) {
+            self.rate_window = (Instant::now(), 0);
+        }
+        if self.rate_window.1 >= config.max_events_per_second {
+            return true;
+        }
+        self.rate_window.1 += 1;
+        false
+    }
+
+    fn send(&mut self, config: &Syslog, event: &SyslogEvent) {
+        if self.rate_limited(config) {
+            self.counter.rate_limited.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        if self.connection.is_none() {
+            if self
+                .last_connect
+                .is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL)
+            {
+                self.counter.send_failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            self.last_connect = Some(Instant::now());
+            match Connection::connect(config) {
+                Ok(c) => self.connection = Some(c),
+                Err(e) => {
+                    warn!(
+                        "syslog exporter connect to {} failed: {}",
+                        config.destination, e
+                    );
+                    self.counter.send_failed.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+        let message = format_event(config, &self.hostname, event);
+        match self.connection.as_mut().unwrap().send(&message) {
+            Ok(_) => {
+                self.counter.exported.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                warn!(
+                    "syslog exporter send to {} failed: {}",
+                    config.destination, e
+                );
+                // reconnect in the next send
+                self.connection = None;
+                self.counter.send_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        let mut last_config = Syslog::default();
+        while self.running.load(Ordering::Relaxed) {
+            let config = self.config.load().syslog.clone();
+            let enabled = config.enabled && !config.destination.is_empty();
+            self.enabled.store(enabled, Ordering::Relaxed);
+            if config != last_config {
+                self.connection = None;
+                self.last_connect = None;
+                last_config = config.clone();
+            }
+
+            match self.input.recv_all(&mut batch, Some(QUEUE_READ_TIMEOUT)) {
+                Ok(_) | Err(Error::Timeout) => (),
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+            if !enabled {
+                // events queued before disabled
+                batch.clear();
+                #[cfg(target_os = "linux")]
+                {
+                    self.listening_ports = Default::default();
+                }
+                continue;
+            }
+            for event in batch.drain(..) {
+                self.send(&config, &event);
+            }
+            #[cfg(target_os = "linux")]
+            for event in self
+                .listening_ports
+                .new_ports(config.listening_port_scan_interval)
+            {
+                self.send(&config, &(&event).into());
+            }
+        }
+    }
+}
+
+pub struct SyslogExporterThread {
+    input: Arc<Receiver<SyslogEvent>>,
+    config: SenderAccess,
+    enabled: Arc<AtomicBool>,
+    counter: Arc<SyslogExporterCounter>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl SyslogExporterThread {
+    pub fn new(
+        input: Receiver<SyslogEvent>,
+        config: SenderAccess,
+        exporter: &SyslogExporter,
+    ) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            enabled: exporter.enabled.clone(),
+            counter: exporter.get_counter_handle(),
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("syslog exporter already started, do nothing.");
+            return;
+        }
+
+        let mut exporter = Exporter {
+            input: self.input.clone(),
+            config: self.config.clone(),
+            running: self.running.clone(),
+            enabled: self.enabled.clone(),
+            counter: self.counter.clone(),
+            hostname: hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_default(),
+            connection: None,
+            last_connect: None,
+            rate_window: (Instant::now(), 0),
+            #[cfg(target_os = "linux")]
+            listening_ports: Default::default(),
+        };
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("syslog-exporter".to_owned())
+                .spawn(move || exporter.process())
+                .unwrap(),
+        );
+        info!("syslog exporter started");
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("syslog exporter already stopped, do nothing.");
+            return None;
+        }
+        self.enabled.store(false, Ordering::Relaxed);
+        info!("notified stopping syslog exporter");
+        self.thread_handle.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::common::{enums::CaptureNetworkType, Timestamp};
+
+    fn event() -> SyslogEvent {
+        (&SecurityEvent {
+            time: Timestamp::from_secs(1700000000),
+            event_type: SecurityEventType::PolicyViolation,
+            tap_type: CaptureNetworkType::Cloud,
+            ip: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+            port: 22,
+            count: 1,
+            threshold: 0,
+            window: 10,
+            peer_ip: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))),
+            protocol: IpProtocol::TCP,
+            detail: "no=ssh \"x\"".to_owned(),
+        })
+            .into()
+    }
+
+    #[test]
+    fn format_rfc5424() {
+        let mut config = Syslog::default();
+        config
+            .field_mapping
+            .insert("capture_network_type".to_owned(), "".to_owned());
+        config
+            .field_mapping
+            .insert("rule".to_owned(), "policy".to_owned());
+        let message = format_event(&config, "node-1", &event());
+        assert_eq!(
+            message,
+            format!(
+                "<36>1 2023-11-14T22:13:20.000000Z node-1 deepflow-agent {} policy_violation [deepflow@32473 src_ip=\"192.168.0.1\" dst_ip=\"10.0.0.2\" dst_port=\"22\" protocol=\"tcp\" count=\"1\" window=\"10\" policy=\"no=ssh \\\"x\\\"\"] Flow policy violation",
+                process::id()
+            )
+        );
+    }
+
+    #[test]
+    fn format_cef() {
+        let config = Syslog {
+            format: SyslogFormat::Cef,
+            ..Default::default()
+        };
+        let message = format_event(&config, "", &event());
+        let cef = &message[message.find("CEF:").unwrap()..];
+        assert_eq!(
+            cef,
+            format!(
+                "CEF:0|DeepFlow|deepflow-agent|{}|policy_violation|Flow policy violation|7|src=192.168.0.1 dst=10.0.0.2 dpt=22 proto=tcp cnt=1 cn2=10 cn2Label=window cs2=tor cs2Label=capture_network_type cs1=no\\=ssh \"x\" cs1Label=rule",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+}
@@ -15,9 +15,9 @@
  */
 
 use std::fs::{create_dir_all, rename, File, OpenOptions};
-use std::io::{BufWriter, ErrorKind, Write};
+use std::io::{self, BufWriter, ErrorKind, Write};
 use std::marker::PhantomData;
-use std::net::{Shutdown, TcpStream};
+use std::net::TcpStream;
 use std::path::Path;
 use std::sync::Mutex;
 use std::sync::{
@@ -30,15 +30,28 @@ use std::time::{Duration, Instant, SystemTime};
 use arc_swap::access::Access;
 use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
+use lz4_flex::frame::FrameEncoder;
 use public::sender::{SendMessageType, Sendable};
 use rand::{thread_rng, RngCore};
 
-use super::{get_sender_id, QUEUE_BATCH_SIZE};
+use super::{
+    clickhouse_sender::ClickHouseWriter,
+    destination::Destinations,
+    disk_buffer::{DiskBuffer, DiskBufferCounter},
+    file_exporter::FileExporter,
+    get_sender_id,
+    kafka_sender::KafkaProducer,
+    QUEUE_BATCH_SIZE,
+};
 
-use crate::config::handler::SenderAccess;
+use crate::config::{
+    handler::SenderAccess, DiskBuffer as DiskBufferConfig, KafkaEncoding, MutualTls,
+    SenderCompressionAlgorithm,
+};
 use crate::exception::ExceptionHandler;
-use crate::utils::stats::{
-    self, Collector, Countable, Counter, CounterType, CounterValue, RefCountable,
+use crate::utils::{
+    stats::{self, Collector, Countable, Counter, CounterType, CounterValue, RefCountable},
+    tls::{Stream, TlsContext},
 };
 use public::proto::agent::{Exception, SocketType};
 use public::queue::{Error, Receiver};
@@ -51,6 +64,10 @@ pub struct SenderCounter {
     pub tx: AtomicU64,
     pub tx_bytes: AtomicU64,
     pub dropped: AtomicU64,
+    // payload bytes before and after compressed, and the time spent in nanoseconds
+    pub compress_in_bytes: AtomicU64,
+    pub compress_out_bytes: AtomicU64,
+    pub compress_time: AtomicU64,
 }
 
 impl RefCountable for SenderCounter {
@@ -76,6 +93,21 @@ impl RefCountable for SenderCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "compress-in-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.compress_in_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "compress-out-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.compress_out_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "compress-time-ns",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.compress_time.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 }
@@ -85,12 +117,38 @@ impl RefCountable for SenderCounter {
 // +----------+--------------------------------+----------+----------+----------+----------+
 // | frame_size                                | msg_type | version             | encoder  |
 // +----------+--------------------------------+----------+----------+----------+----------+
-// | team_id                                   | orgnization_id      | rsvd_1              |
+// | team_id                                   | orgnization_id      | sequence            |
 // +---------------------+----------+----------+---------------------+---------------------+
-// | agent_id            | rsvd_2   |
+// | agent_id            | flags    |
 // +--------------------------------+
 //
+// sequence: the lower 16 bits of the frame sequence of the sender, used for deduplicating
+//           the frames replayed from the disk buffer
+// flags: FLAG_REPLAYED is set if the frame is replayed from the disk buffer
+// encoder: the compression algorithm of the payload following the header
+//
 const HEADER_VESION: u16 = 0x8000;
+const HEADER_LEN: usize = 19;
+const HEADER_ENCODER_OFFSET: usize = 7;
+const HEADER_SEQUENCE_OFFSET: usize = 14;
+const HEADER_FLAGS_OFFSET: usize = 18;
+const FLAG_REPLAYED: u8 = 1;
+
+const ENCODER_NONE: u8 = 0;
+const ENCODER_ZSTD: u8 = 1;
+const ENCODER_LZ4: u8 = 2;
+
+fn compress(algorithm: SenderCompressionAlgorithm, level: i32, data: &[u8]) -> io::Result<Vec<u8>> {
+    match algorithm {
+        SenderCompressionAlgorithm::None => Ok(data.to_vec()),
+        SenderCompressionAlgorithm::Zstd => zstd::bulk::compress(data, level),
+        SenderCompressionAlgorithm::Lz4 => {
+            let mut encoder = FrameEncoder::new(Vec::with_capacity(data.len() / 2));
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Header {
@@ -100,9 +158,9 @@ struct Header {
     encoder: u8,
     team_id: u32,
     organization_id: u16,
-    reserved_1: u16,
+    sequence: u16,
     agent_id: u16,
-    reserved_2: u8,
+    flags: u8,
 }
 
 impl Header {
@@ -113,9 +171,9 @@ impl Header {
         buffer.push(self.encoder.into());
         buffer.extend_from_slice(self.team_id.to_le_bytes().as_slice());
         buffer.extend_from_slice(self.organization_id.to_le_bytes().as_slice());
-        buffer.extend_from_slice(self.reserved_1.to_le_bytes().as_slice());
+        buffer.extend_from_slice(self.sequence.to_le_bytes().as_slice());
         buffer.extend_from_slice(self.agent_id.to_le_bytes().as_slice());
-        buffer.push(self.reserved_2.into());
+        buffer.push(self.flags);
     }
 }
 
@@ -140,8 +198,8 @@ impl<T: Sendable> Encoder<T> {
                 team_id: 0,
                 organization_id: 0,
                 agent_id: agent_id,
-                reserved_1: 0,
-                reserved_2: 0,
+                sequence: 0,
+                flags: 0,
                 encoder: 0,
             },
             _marker: PhantomData,
@@ -177,6 +235,56 @@ impl<T: Sendable> Encoder<T> {
         self.buffer[0..4].copy_from_slice(frame_size.to_be_bytes().as_slice());
     }
 
+    pub fn set_header_sequence(&mut self, sequence: u64) {
+        self.buffer[HEADER_SEQUENCE_OFFSET..HEADER_SEQUENCE_OFFSET + 2]
+            .copy_from_slice((sequence as u16).to_le_bytes().as_slice());
+    }
+
+    pub fn set_header_flags(&mut self, flags: u8) {
+        self.buffer[HEADER_FLAGS_OFFSET] |= flags;
+    }
+
+    // The payload is kept uncompressed if the compression fails or does not reduce the size
+    pub fn compress(
+        &mut self,
+        algorithm: SenderCompressionAlgorithm,
+        zstd_level: i32,
+        counter: &SenderCounter,
+    ) {
+        let encoder = match algorithm {
+            SenderCompressionAlgorithm::None => return,
+            SenderCompressionAlgorithm::Zstd => ENCODER_ZSTD,
+            SenderCompressionAlgorithm::Lz4 => ENCODER_LZ4,
+        };
+        if self.buffer.len() <= HEADER_LEN || self.buffer[HEADER_ENCODER_OFFSET] != ENCODER_NONE {
+            return;
+        }
+        let start = Instant::now();
+        let payload = &self.buffer[HEADER_LEN..];
+        let compressed = match compress(algorithm, zstd_level, payload) {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                debug!("compress payload with {:?} failed: {}", algorithm, e);
+                return;
+            }
+        };
+        counter
+            .compress_time
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        counter
+            .compress_in_bytes
+            .fetch_add(payload.len() as u64, Ordering::Relaxed);
+        counter
+            .compress_out_bytes
+            .fetch_add(compressed.len() as u64, Ordering::Relaxed);
+        if compressed.len() >= payload.len() {
+            return;
+        }
+        self.buffer.truncate(HEADER_LEN);
+        self.buffer.extend_from_slice(&compressed);
+        self.buffer[HEADER_ENCODER_OFFSET] = encoder;
+    }
+
     pub fn update_header(&mut self, name: &str, id: usize, config: &SenderAccess) {
         let config = config.load();
         if self.header.agent_id != config.agent_id
@@ -209,6 +317,26 @@ impl<T: Sendable> Encoder<T> {
     }
 }
 
+// Writes a whole frame, returns false if the sender is stopped before finished
+fn write_frame(stream: &mut Stream, frame: &[u8], running: &AtomicBool) -> io::Result<bool> {
+    let mut write_offset = 0usize;
+    while running.load(Ordering::Relaxed) {
+        match stream.write(&frame[write_offset..]) {
+            Ok(size) => {
+                write_offset += size;
+                if write_offset == frame.len() {
+                    return Ok(true);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                debug!("sender tcp stream write data block {}", e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
+
 pub struct UniformSenderThread<T> {
     id: usize,
     name: &'static str,
@@ -303,6 +431,9 @@ impl<T: Sendable> UniformSenderThread<T> {
 
 lazy_static! {
     static ref GLOBAL_CONNECTION: Arc<Mutex<Connection>> = Arc::new(Mutex::new(Connection::new()));
+    // shared by the connections of all senders
+    pub static ref SENDER_TLS: Arc<TlsContext> =
+        Arc::new(TlsContext::new("sender", MutualTls::default()));
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -313,7 +444,9 @@ enum ConnectionType {
 }
 
 pub struct Connection {
-    tcp_stream: Option<TcpStream>,
+    tcp_stream: Option<Stream>,
+    // version of the tls certificates of the stream
+    tls_version: u64,
 
     reconnect_interval: u8,
 
@@ -328,6 +461,7 @@ impl Connection {
     pub fn new() -> Self {
         Self {
             tcp_stream: None,
+            tls_version: 0,
             reconnect_interval: 10,
             dest_ip: "127.0.0.1".to_string(),
             dest_port: 30033,
@@ -335,6 +469,31 @@ impl Connection {
             last_reconnect: Duration::ZERO,
         }
     }
+
+    pub(super) fn with_destination(dest_ip: &str, dest_port: u16) -> Self {
+        Self {
+            dest_ip: dest_ip.to_owned(),
+            dest_port,
+            ..Self::new()
+        }
+    }
+
+    // Whether the connection is usable or can be reconnected without waiting
+    pub(super) fn available(&self, now: Duration, tls_version: u64) -> bool {
+        if self.tcp_stream.is_some() && !self.reconnect && self.tls_version == tls_version {
+            return true;
+        }
+        self.last_reconnect > now
+            || self.last_reconnect + Duration::from_secs(self.reconnect_interval as u64) <= now
+    }
+}
+
+enum SendResult {
+    Sent,
+    // waiting for the reconnect interval
+    Throttled,
+    Failed,
+    Stopped,
 }
 
 pub struct UniformSender<T> {
@@ -345,10 +504,12 @@ pub struct UniformSender<T> {
     counter: Arc<SenderCounter>,
 
     encoder: Encoder<T>,
-    private_conn: Mutex<Connection>,
+    private_conn: Arc<Mutex<Connection>>,
     private_shared_conn: Option<Arc<Mutex<Connection>>>,
     global_shared_conn: Arc<Mutex<Connection>>,
     connection_type: ConnectionType,
+    // configured destinations used instead of the connection of `connection_type`
+    destinations: Option<Destinations>,
     multiple_sockets_to_ingester: bool,
     dest_ip: String,
     dest_port: u16,
@@ -363,6 +524,15 @@ pub struct UniformSender<T> {
     file_path: String,
     pre_file_path: String,
     written_size: u64,
+    kafka: Option<KafkaProducer>,
+    clickhouse: Option<ClickHouseWriter>,
+    file_exporter: Option<FileExporter>,
+    disk_buffer: Option<DiskBuffer>,
+    disk_buffer_config: DiskBufferConfig,
+    disk_buffer_counter: Arc<DiskBufferCounter>,
+    // sequence of the frame being sent
+    sequence: u64,
+    compression_unsupported_warned: bool,
 
     cached: bool,
 }
@@ -371,6 +541,7 @@ impl<T: Sendable> UniformSender<T> {
     const TCP_WRITE_TIMEOUT: u64 = 3; // s
     const QUEUE_READ_TIMEOUT: u64 = 3; // s
     const DEFAULT_RECONNECT_INTERVAL: u8 = 10; // s
+    const REPLAY_BATCH_SIZE: usize = 16;
 
     pub fn new(
         id: usize,
@@ -390,10 +561,11 @@ impl<T: Sendable> UniformSender<T> {
             counter: Arc::new(SenderCounter::default()),
             encoder: Encoder::new(0, SendMessageType::TaggedFlow, cfg.agent_id),
             config,
-            private_conn: Mutex::new(Connection::new()),
+            private_conn: Arc::new(Mutex::new(Connection::new())),
             private_shared_conn,
             global_shared_conn: GLOBAL_CONNECTION.clone(),
             connection_type: ConnectionType::Global,
+            destinations: None,
             multiple_sockets_to_ingester: false,
             dest_ip: "127.0.0.1".to_string(),
             dest_port: cfg.dest_port,
@@ -406,12 +578,25 @@ impl<T: Sendable> UniformSender<T> {
             file_path: String::new(),
             pre_file_path: String::new(),
             written_size: 0,
+            kafka: None,
+            clickhouse: None,
+            file_exporter: None,
+            disk_buffer: None,
+            disk_buffer_config: DiskBufferConfig::default(),
+            disk_buffer_counter: Arc::new(DiskBufferCounter::default()),
+            sequence: 0,
+            compression_unsupported_warned: false,
             cached: true,
         }
     }
 
     fn update_connection(&mut self) {
         let cfg = self.config.load();
+        SENDER_TLS.update(&cfg.tls);
+        if self.disk_buffer_config != cfg.disk_buffer {
+            self.disk_buffer_config = cfg.disk_buffer.clone();
+            self.update_disk_buffer();
+        }
 
         if self.multiple_sockets_to_ingester != cfg.multiple_sockets_to_ingester
             || self.dest_ip != cfg.dest_ip
@@ -463,29 +648,172 @@ impl<T: Sendable> UniformSender<T> {
                 new_conn.last_reconnect = Duration::ZERO;
             }
         }
+
+        let destinations_changed = match self.destinations.as_ref() {
+            Some(d) => !d.matches(&cfg.destinations, cfg.multiple_sockets_to_ingester),
+            None => !cfg.destinations.is_empty(),
+        };
+        if destinations_changed {
+            if cfg.destinations.is_empty() {
+                info!(
+                    "{} sender destinations removed, send to {}:{}",
+                    self.name, self.dest_ip, self.dest_port
+                );
+                self.destinations = None;
+            } else {
+                info!(
+                    "{} sender update destinations to {:?}",
+                    self.name, cfg.destinations
+                );
+                if self.destinations.is_none() {
+                    let conn = match self.connection_type {
+                        ConnectionType::Global => &self.global_shared_conn,
+                        ConnectionType::PrivateShared => self.private_shared_conn.as_ref().unwrap(),
+                        ConnectionType::Private => &self.private_conn,
+                    };
+                    conn.lock().unwrap().tcp_stream.take();
+                }
+                self.destinations = Some(Destinations::new(
+                    &cfg.destinations,
+                    cfg.multiple_sockets_to_ingester,
+                ));
+            }
+        }
+    }
+
+    fn update_disk_buffer(&mut self) {
+        // frames buffered are kept on disk and replayed when enabled again
+        self.disk_buffer.take();
+        if !self.disk_buffer_config.enabled {
+            return;
+        }
+        match DiskBuffer::open(
+            self.name,
+            &self.disk_buffer_config,
+            self.disk_buffer_counter.clone(),
+        ) {
+            Ok(buffer) => {
+                self.sequence = self.sequence.max(buffer.next_sequence());
+                self.disk_buffer = Some(buffer);
+                info!(
+                    "{} sender disk buffer enabled in {}",
+                    self.name, self.disk_buffer_config.directory
+                );
+            }
+            Err(e) => warn!(
+                "{} sender open disk buffer in {} failed: {}",
+                self.name, self.disk_buffer_config.directory, e
+            ),
+        }
     }
 
     fn flush_encoder(&mut self) {
         self.cached = true;
         if self.encoder.buffer_len() > 0 {
+            let (algorithm, zstd_level) = {
+                let config = self.config.load();
+                let msg_type = self.encoder.header.msg_type.to_string();
+                let algorithm = config.compression.algorithm(&msg_type);
+                // ingesters not advertising the algorithm would read the compressed frames as raw
+                if algorithm != SenderCompressionAlgorithm::None
+                    && !config.ingester_compression_algorithms.contains(&algorithm)
+                {
+                    if !self.compression_unsupported_warned {
+                        warn!(
+                            "{} sender compression {:?} is not supported by the ingester, frames are sent uncompressed",
+                            self.name, algorithm
+                        );
+                        self.compression_unsupported_warned = true;
+                    }
+                    (
+                        SenderCompressionAlgorithm::None,
+                        config.compression.zstd_level,
+                    )
+                } else {
+                    self.compression_unsupported_warned = false;
+                    (algorithm, config.compression.zstd_level)
+                }
+            };
+            self.encoder.compress(algorithm, zstd_level, &self.counter);
             self.encoder.set_header_frame_size();
+            self.encoder.set_header_sequence(self.sequence);
             self.send_buffer();
+            self.sequence = self.sequence.wrapping_add(1);
             self.encoder.reset_buffer();
+        } else if self
+            .disk_buffer
+            .as_ref()
+            .map(|b| !b.is_empty())
+            .unwrap_or(false)
+        {
+            // replay the disk buffer without new frames
+            self.send_buffer();
         }
     }
 
+    // Returns false if the frame is not buffered and should be dropped
+    fn buffer_frame(
+        disk_buffer: &mut Option<DiskBuffer>,
+        encoder: &mut Encoder<T>,
+        sequence: u64,
+    ) -> bool {
+        if encoder.buffer_len() == 0 {
+            return true;
+        }
+        let Some(disk_buffer) = disk_buffer.as_mut() else {
+            return false;
+        };
+        encoder.set_header_flags(FLAG_REPLAYED);
+        disk_buffer.push(sequence, encoder.get_buffer());
+        true
+    }
+
+    // The connection to the ingester assigned by the controller, or to one of the configured
+    // destinations with its index
+    fn select_connection(
+        &mut self,
+        excluded: &[usize],
+        tls_version: u64,
+    ) -> Option<(Option<usize>, Arc<Mutex<Connection>>)> {
+        if let Some(destinations) = self.destinations.as_mut() {
+            let index = destinations.select(excluded, tls_version)?;
+            return Some((Some(index), destinations.connection(index)));
+        }
+        let conn = match self.connection_type {
+            ConnectionType::Global => self.global_shared_conn.clone(),
+            ConnectionType::PrivateShared => self.private_shared_conn.clone().unwrap(),
+            ConnectionType::Private => self.private_conn.clone(),
+        };
+        Some((None, conn))
+    }
+
     fn send_buffer(&mut self) {
-        let mut conn = match self.connection_type {
-            ConnectionType::Global => self.global_shared_conn.lock().unwrap(),
-            ConnectionType::PrivateShared => {
-                self.private_shared_conn.as_mut().unwrap().lock().unwrap()
+        let tls_version = SENDER_TLS.version();
+        let mut excluded = vec![];
+        let mut failed = false;
+        while let Some((index, conn)) = self.select_connection(&excluded, tls_version) {
+            match self.send_to(&conn, tls_version) {
+                SendResult::Sent => return,
+                SendResult::Stopped => break,
+                SendResult::Throttled => (),
+                SendResult::Failed => failed = true,
             }
-            ConnectionType::Private => self.private_conn.lock().unwrap(),
-        };
+            // fail over to other destinations
+            match index {
+                Some(index) => excluded.push(index),
+                None => break,
+            }
+        }
+        if !Self::buffer_frame(&mut self.disk_buffer, &mut self.encoder, self.sequence) && failed {
+            self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
 
-        if conn.reconnect || conn.tcp_stream.is_none() {
-            if let Some(t) = conn.tcp_stream.take() {
-                if let Err(e) = t.shutdown(Shutdown::Both) {
+    fn send_to(&mut self, conn: &Mutex<Connection>, tls_version: u64) -> SendResult {
+        let mut conn = conn.lock().unwrap();
+        if conn.reconnect || conn.tcp_stream.is_none() || conn.tls_version != tls_version {
+            if let Some(mut t) = conn.tcp_stream.take() {
+                if let Err(e) = t.shutdown() {
                     debug!("{} sender tcp stream shutdown failed {}", self.name, e);
                 }
             }
@@ -497,12 +825,12 @@ impl<T: Sendable> UniformSender<T> {
                 conn.last_reconnect = now;
             }
             if conn.last_reconnect + Duration::from_secs(conn.reconnect_interval as u64) > now {
-                return;
+                return SendResult::Throttled;
             }
 
             conn.last_reconnect = now;
-            conn.tcp_stream = TcpStream::connect((conn.dest_ip.clone(), conn.dest_port)).ok();
-            if let Some(tcp_stream) = conn.tcp_stream.as_mut() {
+            let tcp_stream = TcpStream::connect((conn.dest_ip.clone(), conn.dest_port)).ok();
+            if let Some(tcp_stream) = tcp_stream {
                 if let Err(e) =
                     tcp_stream.set_write_timeout(Some(Duration::from_secs(Self::TCP_WRITE_TIMEOUT)))
                 {
@@ -510,9 +838,24 @@ impl<T: Sendable> UniformSender<T> {
                         "{} sender tcp stream set write timeout failed {}",
                         self.name, e
                     );
-                    conn.tcp_stream.take();
-                    return;
+                    return SendResult::Throttled;
                 }
+                match SENDER_TLS.connect(tcp_stream, &conn.dest_ip) {
+                    Ok(stream) => conn.tcp_stream = Some(stream),
+                    Err(e) => {
+                        if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                            self.exception_handler.set(Exception::AnalyzerSocketError);
+                            error!(
+                                "{} sender tls connection to {}:{} failed: {}",
+                                self.name, conn.dest_ip, conn.dest_port, e
+                            );
+                        }
+                        conn.reconnect_interval =
+                            Self::DEFAULT_RECONNECT_INTERVAL + (thread_rng().next_u64() % 5) as u8;
+                        return SendResult::Failed;
+                    }
+                }
+                conn.tls_version = tls_version;
                 info!(
                     "{} sender tcp connection to {}:{} succeed.",
                     self.name, conn.dest_ip, conn.dest_port
@@ -531,48 +874,69 @@ impl<T: Sendable> UniformSender<T> {
                         );
                     }
                 }
-                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
                 // reconnect after waiting 10 seconds + random 5 seconds to prevent frequent reconnection
                 conn.reconnect_interval =
                     Self::DEFAULT_RECONNECT_INTERVAL + (thread_rng().next_u64() % 5) as u8;
-                return;
+                return SendResult::Failed;
             }
         }
 
         let tcp_stream = conn.tcp_stream.as_mut().unwrap();
-        let buffer = &self.encoder.get_buffer();
-        let mut write_offset = 0usize;
-        while self.running.load(Ordering::Relaxed) {
-            let result = tcp_stream.write(&buffer[write_offset..]);
-            match result {
-                Ok(size) => {
-                    write_offset += size;
-                    if write_offset == buffer.len() {
-                        self.counter.tx.fetch_add(1, Ordering::Relaxed);
-                        self.counter
-                            .tx_bytes
-                            .fetch_add(buffer.len() as u64, Ordering::Relaxed);
-                        break;
-                    }
+        let buffer = self.encoder.get_buffer();
+        let result = if buffer.is_empty() {
+            Ok(true)
+        } else {
+            write_frame(tcp_stream, buffer, &self.running)
+        };
+        match result {
+            Ok(true) if !buffer.is_empty() => {
+                self.counter.tx.fetch_add(1, Ordering::Relaxed);
+                self.counter
+                    .tx_bytes
+                    .fetch_add(buffer.len() as u64, Ordering::Relaxed);
+            }
+            Ok(true) => (),
+            Ok(false) => return SendResult::Stopped,
+            Err(e) => {
+                if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                    self.exception_handler.set(Exception::AnalyzerSocketError);
+                    error!(
+                        "{} sender tcp stream write data to {}:{} failed: {}",
+                        self.name, conn.dest_ip, conn.dest_port, e
+                    );
                 }
-                Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                    debug!("{} sender tcp stream write data block {}", self.name, e);
-                    continue;
+                conn.tcp_stream.take();
+                return SendResult::Failed;
+            }
+        }
+
+        // replay a batch of the buffered frames after each frame sent
+        let Some(disk_buffer) = self.disk_buffer.as_mut() else {
+            return SendResult::Sent;
+        };
+        for _ in 0..Self::REPLAY_BATCH_SIZE {
+            let Some(frame) = disk_buffer.peek() else {
+                break;
+            };
+            let len = frame.len() as u64;
+            match write_frame(tcp_stream, frame, &self.running) {
+                Ok(true) => {
+                    self.counter.tx.fetch_add(1, Ordering::Relaxed);
+                    self.counter.tx_bytes.fetch_add(len, Ordering::Relaxed);
+                    disk_buffer.consume();
                 }
+                Ok(false) => break,
                 Err(e) => {
-                    if self.counter.dropped.load(Ordering::Relaxed) == 0 {
-                        self.exception_handler.set(Exception::AnalyzerSocketError);
-                        error!(
-                            "{} sender tcp stream write data to {}:{} failed: {}",
-                            self.name, conn.dest_ip, conn.dest_port, e
-                        );
-                    }
-                    self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                    debug!(
+                        "{} sender replay disk buffer to {}:{} failed: {}",
+                        self.name, conn.dest_ip, conn.dest_port, e
+                    );
                     conn.tcp_stream.take();
                     break;
                 }
-            };
+            }
         }
+        SendResult::Sent
     }
 
     fn check_or_register_counterable(&mut self, message_type: SendMessageType) {
@@ -583,6 +947,10 @@ impl<T: Sendable> UniformSender<T> {
             &stats::SingleTagModule("collect_sender", "type", message_type),
             Countable::Ref(Arc::downgrade(&self.counter) as Weak<dyn RefCountable>),
         );
+        self.stats.register_countable(
+            &stats::SingleTagModule("collect_sender_disk_buffer", "type", message_type),
+            Countable::Ref(Arc::downgrade(&self.disk_buffer_counter) as Weak<dyn RefCountable>),
+        );
         self.stats_registered = true;
     }
 
@@ -592,6 +960,9 @@ impl<T: Sendable> UniformSender<T> {
         let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
         while self.running.load(Ordering::Relaxed) {
             let socket_type = self.config.load().collector_socket_type;
+            let kafka_enabled = self.config.load().kafka.enabled;
+            let clickhouse_enabled = self.config.load().clickhouse.enabled;
+            let file_exporter_enabled = self.config.load().file_exporter.enabled;
             match self.input.recv_all(
                 &mut batch,
                 Some(Duration::from_secs(Self::QUEUE_READ_TIMEOUT)),
@@ -612,8 +983,33 @@ impl<T: Sendable> UniformSender<T> {
                             "{} sender send item {}: {:?}",
                             self.name, message_type, send_item
                         );
-                        let result = match socket_type {
-                            SocketType::File => self.handle_target_file(send_item, &mut kv_string),
+                        // a copy is exported, the data files of the standalone mode are the same
+                        if file_exporter_enabled
+                            && socket_type != SocketType::File
+                            && matches!(
+                                message_type,
+                                SendMessageType::TaggedFlow | SendMessageType::ProtocolLog
+                            )
+                        {
+                            self.export_to_file(&send_item, &mut kv_string);
+                        }
+                        let result = match (socket_type, message_type) {
+                            (SocketType::File, _) => {
+                                self.handle_target_file(send_item, &mut kv_string)
+                            }
+                            (_, SendMessageType::TaggedFlow | SendMessageType::ProtocolLog)
+                                if kafka_enabled =>
+                            {
+                                self.handle_target_kafka(send_item, &mut kv_string)
+                            }
+                            (
+                                _,
+                                SendMessageType::TaggedFlow
+                                | SendMessageType::ProtocolLog
+                                | SendMessageType::Metrics,
+                            ) if clickhouse_enabled => {
+                                self.handle_target_clickhouse(send_item, &mut kv_string)
+                            }
                             _ => self.handle_target_server(send_item),
                         };
                         if let Err(e) = result {
@@ -635,12 +1031,20 @@ impl<T: Sendable> UniformSender<T> {
                         self.update_connection();
                         self.encoder.update_header(self.name, self.id, &self.config);
                         self.flush_encoder();
+                        self.flush_kafka();
+                        self.flush_clickhouse();
+                        self.flush_file_exporter();
                     }
                 },
                 Err(Error::Terminated(..)) => {
                     match socket_type {
                         SocketType::File => self.flush_writer(),
-                        _ => self.flush_encoder(),
+                        _ => {
+                            self.flush_encoder();
+                            self.flush_kafka();
+                            self.flush_clickhouse();
+                            self.flush_file_exporter();
+                        }
                     }
                     break;
                 }
@@ -700,6 +1104,98 @@ impl<T: Sendable> UniformSender<T> {
         Ok(())
     }
 
+    fn flush_kafka(&mut self) {
+        if let Some(kafka) = self.kafka.as_mut() {
+            kafka.flush();
+        }
+    }
+
+    pub fn handle_target_kafka(
+        &mut self,
+        send_item: T,
+        kv_string: &mut String,
+    ) -> std::io::Result<()> {
+        let message_type = send_item.message_type();
+        if self.kafka.is_none() {
+            self.check_or_register_counterable(message_type);
+            self.kafka = Some(KafkaProducer::new(self.name, self.counter.clone())?);
+        }
+        let config = self.config.load();
+        let topic = match message_type {
+            SendMessageType::TaggedFlow => &config.kafka.l4_flow_log_topic,
+            _ => &config.kafka.l7_flow_log_topic,
+        };
+        let kafka = self.kafka.as_mut().unwrap();
+        kafka.update_config(&config.kafka, topic);
+
+        let flow_id = send_item.flow_id();
+        let value = match config.kafka.encoding {
+            KafkaEncoding::Protobuf => {
+                let mut buffer = vec![];
+                send_item
+                    .encode(&mut buffer)
+                    .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+                buffer
+            }
+            KafkaEncoding::Json => {
+                send_item.to_kv_string(kv_string);
+                let value = kv_string.trim_end().as_bytes().to_vec();
+                kv_string.truncate(0);
+                value
+            }
+        };
+        kafka.send(flow_id, value);
+        Ok(())
+    }
+
+    fn flush_clickhouse(&mut self) {
+        if let Some(clickhouse) = self.clickhouse.as_mut() {
+            clickhouse.flush();
+        }
+    }
+
+    pub fn handle_target_clickhouse(
+        &mut self,
+        send_item: T,
+        kv_string: &mut String,
+    ) -> std::io::Result<()> {
+        if self.clickhouse.is_none() {
+            self.check_or_register_counterable(send_item.message_type());
+            self.clickhouse = Some(ClickHouseWriter::new(self.name, self.counter.clone()));
+        }
+        let config = self.config.load();
+        let clickhouse = self.clickhouse.as_mut().unwrap();
+        clickhouse.update_config(&config.clickhouse, send_item.file_name());
+
+        send_item.to_kv_string(kv_string);
+        if kv_string.is_empty() {
+            return Ok(());
+        }
+        let data = kv_string.trim_end().to_owned();
+        kv_string.truncate(0);
+        clickhouse.write(config.agent_id, send_item.flow_id(), data);
+        Ok(())
+    }
+
+    fn flush_file_exporter(&mut self) {
+        if let Some(exporter) = self.file_exporter.as_mut() {
+            exporter.flush();
+        }
+    }
+
+    fn export_to_file(&mut self, send_item: &T, kv_string: &mut String) {
+        send_item.to_kv_string(kv_string);
+        if kv_string.is_empty() {
+            return;
+        }
+        let exporter = self
+            .file_exporter
+            .get_or_insert_with(|| FileExporter::new(self.name));
+        exporter.update_config(&self.config.load().file_exporter, send_item.file_name());
+        exporter.write(kv_string);
+        kv_string.truncate(0);
+    }
+
     pub fn handle_target_server(&mut self, send_item: T) -> std::io::Result<()> {
         self.encoder.cache_to_sender(send_item);
         if !self.cached || self.encoder.buffer_len() > Encoder::<T>::BUFFER_LEN {
@@ -711,3 +1207,29 @@ impl<T: Sendable> UniformSender<T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use lz4_flex::frame::FrameDecoder;
+
+    use super::*;
+
+    #[test]
+    fn compress_payload() {
+        let data = b"deepflow".repeat(1024);
+
+        let zstd = compress(SenderCompressionAlgorithm::Zstd, 1, &data).unwrap();
+        assert!(zstd.len() < data.len());
+        assert_eq!(zstd::bulk::decompress(&zstd, data.len()).unwrap(), data);
+
+        let lz4 = compress(SenderCompressionAlgorithm::Lz4, 1, &data).unwrap();
+        assert!(lz4.len() < data.len());
+        let mut decompressed = vec![];
+        FrameDecoder::new(lz4.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+}
@@ -0,0 +1,1034 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod message;
+mod usm;
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{
+    atomic::{AtomicI64, AtomicU64, Ordering},
+    Arc, Condvar, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use arc_swap::access::Access;
+use log::{debug, info, warn};
+use thiserror::Error;
+
+use crate::config::{handler::SnmpAccess, Snmp, SnmpDevice, SnmpSecurityLevel, SnmpVersion};
+use crate::metric::{
+    document::{BoxedDocument, Code, Document, DocumentFlag, Tagger},
+    meter::{DeviceMeter, DeviceMeterType, Meter},
+};
+use crate::rpc::get_timestamp;
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+use message::{
+    decode_scoped_pdu, decode_v2c, decode_v3, encode_scoped_pdu, encode_v2c, encode_v3, Oid, Pdu,
+    UsmParams, Value, AUTH_PARAMS_LEN, FLAG_AUTH, FLAG_PRIV, FLAG_REPORTABLE, GET_BULK_REQUEST,
+    GET_REQUEST, MAX_MESSAGE_SIZE, REPORT, RESPONSE,
+};
+use public::queue::DebugSender;
+
+const SNMP_PORT: u16 = 161;
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+const SECONDS_PER_MINUTE: u64 = 60;
+// limits the rows of a table in case of misbehaving agents
+const MAX_TABLE_ROWS: usize = 65536;
+
+// SNMPv2-MIB
+const SYS_UP_TIME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3, 0];
+const SYS_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 5, 0];
+// IF-MIB ifTable
+const IF_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 2];
+const IF_SPEED: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 5];
+const IF_OPER_STATUS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 8];
+const IF_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 10];
+const IF_IN_UCAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 11];
+const IF_IN_DISCARDS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 13];
+const IF_IN_ERRORS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 14];
+const IF_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 16];
+const IF_OUT_UCAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 17];
+const IF_OUT_DISCARDS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 19];
+const IF_OUT_ERRORS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 20];
+// IF-MIB ifXTable
+const IF_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 1];
+const IF_HC_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 6];
+const IF_HC_IN_UCAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 7];
+const IF_HC_IN_MULTICAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 8];
+const IF_HC_IN_BROADCAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 9];
+const IF_HC_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 10];
+const IF_HC_OUT_UCAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 11];
+const IF_HC_OUT_MULTICAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 12];
+const IF_HC_OUT_BROADCAST_PKTS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 13];
+const IF_HIGH_SPEED: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 15];
+// HOST-RESOURCES-MIB
+const HR_STORAGE_TYPE: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 2, 3, 1, 2];
+const HR_STORAGE_ALLOCATION_UNITS: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 2, 3, 1, 4];
+const HR_STORAGE_SIZE: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 2, 3, 1, 5];
+const HR_STORAGE_USED: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 2, 3, 1, 6];
+const HR_STORAGE_RAM: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 2, 1, 2];
+const HR_PROCESSOR_LOAD: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 3, 3, 1, 2];
+// SNMP-USER-BASED-SM-MIB usmStats
+const USM_STATS_NOT_IN_TIME_WINDOWS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 2, 0];
+const USM_STATS_UNKNOWN_ENGINE_IDS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 4, 0];
+
+const INTERFACE_COLUMNS: [&[u32]; 21] = [
+    IF_DESCR,
+    IF_SPEED,
+    IF_OPER_STATUS,
+    IF_IN_OCTETS,
+    IF_IN_UCAST_PKTS,
+    IF_IN_DISCARDS,
+    IF_IN_ERRORS,
+    IF_OUT_OCTETS,
+    IF_OUT_UCAST_PKTS,
+    IF_OUT_DISCARDS,
+    IF_OUT_ERRORS,
+    IF_NAME,
+    IF_HC_IN_OCTETS,
+    IF_HC_IN_UCAST_PKTS,
+    IF_HC_IN_MULTICAST_PKTS,
+    IF_HC_IN_BROADCAST_PKTS,
+    IF_HC_OUT_OCTETS,
+    IF_HC_OUT_UCAST_PKTS,
+    IF_HC_OUT_MULTICAST_PKTS,
+    IF_HC_OUT_BROADCAST_PKTS,
+    IF_HIGH_SPEED,
+];
+
+#[derive(Debug, Error)]
+pub enum SnmpError {
+    #[error("malformed message: {0}")]
+    Malformed(&'static str),
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("request timeout")]
+    Timeout,
+    #[error("error status {0} at index {1}")]
+    ErrorStatus(i64, i64),
+    #[error("authentication failed")]
+    AuthenticationFailure,
+    #[error("report {0:?}")]
+    Report(Oid),
+    #[error("invalid address {0}")]
+    InvalidAddress(String),
+}
+
+type Result<T> = std::result::Result<T, SnmpError>;
+
+// State of the authoritative engine of SNMPv3, discovered by a request without authentication
+struct Engine {
+    id: Vec<u8>,
+    boots: u32,
+    time: u32,
+    synced_at: Instant,
+    auth_key: Vec<u8>,
+    priv_key: Vec<u8>,
+}
+
+impl Engine {
+    fn time(&self) -> u32 {
+        self.time
+            .saturating_add(self.synced_at.elapsed().as_secs() as u32)
+    }
+}
+
+// A session to the SNMP agent of a device
+struct Session {
+    device: SnmpDevice,
+    socket: UdpSocket,
+    retries: usize,
+    max_repetitions: u32,
+    request_id: i32,
+    engine: Option<Engine>,
+    salt: u64,
+    buffer: Vec<u8>,
+}
+
+impl Session {
+    fn new(device: &SnmpDevice, config: &Snmp) -> Result<Self> {
+        let address = if device.address.parse::<IpAddr>().is_ok() {
+            format!("{}:{}", device.address, SNMP_PORT)
+        } else {
+            device.address.clone()
+        };
+        let address = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut a| a.next())
+            .ok_or_else(|| SnmpError::InvalidAddress(device.address.clone()))?;
+        let local: SocketAddr = if address.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        socket.set_read_timeout(Some(config.timeout))?;
+        Ok(Self {
+            device: device.clone(),
+            socket,
+            retries: config.retries,
+            max_repetitions: config.max_repetitions.max(1),
+            request_id: rand::random::<i32>() & i32::MAX,
+            engine: None,
+            salt: rand::random(),
+            buffer: vec![0; MAX_MESSAGE_SIZE],
+        })
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        self.socket.peer_addr().ok().map(|a| a.ip())
+    }
+
+    fn next_request_id(&mut self) -> i32 {
+        self.request_id = self.request_id.wrapping_add(1) & i32::MAX;
+        self.request_id
+    }
+
+    // Sends the request and waits for the response with the same id, `decode` returns None for
+    // messages of other requests
+    fn exchange<T>(
+        &mut self,
+        request: &[u8],
+        mut decode: impl FnMut(&[u8]) -> Option<Result<T>>,
+    ) -> Result<T> {
+        for _ in 0..=self.retries {
+            self.socket.send(request)?;
+            loop {
+                let n = match self.socket.recv(&mut self.buffer) {
+                    Ok(n) => n,
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                        break
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                if let Some(result) = decode(&self.buffer[..n]) {
+                    return result;
+                }
+            }
+        }
+        Err(SnmpError::Timeout)
+    }
+
+    fn request(&mut self, pdu_type: u8, oids: &[Oid]) -> Result<Vec<(Oid, Value)>> {
+        let pdu = match self.device.version {
+            SnmpVersion::V2c => {
+                let request_id = self.next_request_id();
+                let request = Pdu::new_request(pdu_type, request_id, self.max_repetitions, oids);
+                let message = encode_v2c(&self.device.community, &request);
+                self.exchange(&message, |data| match decode_v2c(data) {
+                    Ok(pdu) if pdu.request_id == request_id => Some(Ok(pdu)),
+                    Ok(_) => None,
+                    Err(e) => Some(Err(e)),
+                })?
+            }
+            SnmpVersion::V3 => self.request_v3(pdu_type, oids, true)?,
+        };
+        if pdu.pdu_type == REPORT {
+            let oid = pdu.varbinds.into_iter().next().map(|v| v.0);
+            return Err(SnmpError::Report(oid.unwrap_or_default()));
+        }
+        if pdu.pdu_type != RESPONSE {
+            return Err(SnmpError::Malformed("unexpected pdu type"));
+        }
+        if pdu.error_status != 0 {
+            return Err(SnmpError::ErrorStatus(pdu.error_status, pdu.error_index));
+        }
+        Ok(pdu.varbinds)
+    }
+
+    fn discover(&mut self) -> Result<()> {
+        let msg_id = self.next_request_id();
+        let request = Pdu::new_request(GET_REQUEST, msg_id, 0, &[]);
+        let scoped = encode_scoped_pdu(&[], "", &request);
+        let (message, _) = encode_v3(msg_id, FLAG_REPORTABLE, &UsmParams::default(), &scoped);
+        let usm = self.exchange(&message, |data| match decode_v3(data) {
+            Ok(m) if m.msg_id == msg_id => Some(Ok(m.usm)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })?;
+        if usm.engine_id.is_empty() {
+            return Err(SnmpError::Malformed("empty engine id"));
+        }
+
+        let (auth_key, priv_key) = if self.device.security_level == SnmpSecurityLevel::NoAuthNoPriv
+        {
+            (vec![], vec![])
+        } else {
+            let protocol = self.device.auth_protocol;
+            (
+                usm::password_to_key(protocol, &self.device.auth_password, &usm.engine_id),
+                usm::password_to_key(protocol, &self.device.privacy_password, &usm.engine_id),
+            )
+        };
+        debug!(
+            "snmp engine of {} discovered, id {} boots {} time {}",
+            self.device.address,
+            hex::encode(&usm.engine_id),
+            usm.engine_boots,
+            usm.engine_time
+        );
+        self.engine = Some(Engine {
+            id: usm.engine_id,
+            boots: usm.engine_boots,
+            time: usm.engine_time,
+            synced_at: Instant::now(),
+            auth_key,
+            priv_key,
+        });
+        Ok(())
+    }
+
+    fn request_v3(&mut self, pdu_type: u8, oids: &[Oid], resync: bool) -> Result<Pdu> {
+        if self.engine.is_none() {
+            self.discover()?;
+        }
+        let flags = match self.device.security_level {
+            SnmpSecurityLevel::NoAuthNoPriv => 0,
+            SnmpSecurityLevel::AuthNoPriv => FLAG_AUTH,
+            SnmpSecurityLevel::AuthPriv => FLAG_AUTH | FLAG_PRIV,
+        };
+        let msg_id = self.next_request_id();
+        let request = Pdu::new_request(pdu_type, msg_id, self.max_repetitions, oids);
+        self.salt = self.salt.wrapping_add(1);
+        let salt = self.salt;
+        let device = &self.device;
+        let engine = self.engine.as_ref().unwrap();
+        let engine_time = engine.time();
+
+        let mut usm = UsmParams {
+            engine_id: engine.id.clone(),
+            engine_boots: engine.boots,
+            engine_time,
+            user_name: device.username.as_bytes().to_vec(),
+            ..Default::default()
+        };
+        let mut scoped = encode_scoped_pdu(&engine.id, &device.context_name, &request);
+        if flags & FLAG_PRIV != 0 {
+            let (encrypted, priv_params) =
+                usm::encrypt(&engine.priv_key, engine.boots, engine_time, salt, &scoped);
+            scoped = encrypted;
+            usm.priv_params = priv_params;
+        }
+        if flags & FLAG_AUTH != 0 {
+            usm.auth_params = vec![0; AUTH_PARAMS_LEN];
+        }
+        let (mut message, auth_offset) = encode_v3(msg_id, flags | FLAG_REPORTABLE, &usm, &scoped);
+        if flags & FLAG_AUTH != 0 {
+            usm::sign(
+                device.auth_protocol,
+                &engine.auth_key,
+                &mut message,
+                auth_offset,
+            );
+        }
+
+        let auth_protocol = device.auth_protocol;
+        let auth_key = engine.auth_key.clone();
+        let priv_key = engine.priv_key.clone();
+        let (response, pdu) = self.exchange(&message, |data| {
+            let response = match decode_v3(data) {
+                Ok(m) if m.msg_id == msg_id => m,
+                Ok(_) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            if response.flags & FLAG_AUTH != 0
+                && !usm::verify(auth_protocol, &auth_key, data, response.auth_offset)
+            {
+                return Some(Err(SnmpError::AuthenticationFailure));
+            }
+            let scoped = if response.flags & FLAG_PRIV != 0 {
+                match usm::decrypt(
+                    &priv_key,
+                    response.usm.engine_boots,
+                    response.usm.engine_time,
+                    &response.usm.priv_params,
+                    &response.data,
+                ) {
+                    Some(s) => s,
+                    None => return Some(Err(SnmpError::Malformed("invalid privacy parameters"))),
+                }
+            } else {
+                response.data.clone()
+            };
+            Some(decode_scoped_pdu(&scoped).map(|pdu| (response, pdu)))
+        })?;
+
+        if pdu.pdu_type == REPORT && resync {
+            // unauthenticated reports of the engine state, see RFC 3414 3.2
+            let oid = pdu.varbinds.first().map(|v| v.0.as_slice());
+            if oid == Some(USM_STATS_NOT_IN_TIME_WINDOWS) {
+                if let Some(engine) = self.engine.as_mut() {
+                    engine.boots = response.usm.engine_boots;
+                    engine.time = response.usm.engine_time;
+                    engine.synced_at = Instant::now();
+                }
+                return self.request_v3(pdu_type, oids, false);
+            } else if oid == Some(USM_STATS_UNKNOWN_ENGINE_IDS) {
+                self.engine = None;
+                return self.request_v3(pdu_type, oids, false);
+            }
+        } else if response.flags & FLAG_AUTH != 0 {
+            if let Some(engine) = self.engine.as_mut() {
+                engine.boots = response.usm.engine_boots;
+                engine.time = response.usm.engine_time;
+                engine.synced_at = Instant::now();
+            }
+        }
+        Ok(pdu)
+    }
+
+    fn get(&mut self, oids: &[&[u32]]) -> Result<Vec<Value>> {
+        let oids = oids.iter().map(|o| o.to_vec()).collect::<Vec<_>>();
+        let varbinds = self.request(GET_REQUEST, &oids)?;
+        Ok(oids
+            .iter()
+            .map(|oid| {
+                varbinds
+                    .iter()
+                    .find(|(o, _)| o == oid)
+                    .map(|(_, v)| v.clone())
+                    .unwrap_or(Value::NoSuchObject)
+            })
+            .collect())
+    }
+
+    // Walks the columns of a table indexed by an integer with GetBulkRequest, the values of each
+    // column are returned by the index
+    fn walk_columns(&mut self, columns: &[&[u32]]) -> Result<Vec<BTreeMap<u32, Value>>> {
+        let mut tables = vec![BTreeMap::new(); columns.len()];
+        let mut cursors = columns.iter().map(|c| Some(c.to_vec())).collect::<Vec<_>>();
+        let mut rows = 0;
+        loop {
+            let active = (0..columns.len())
+                .filter(|i| cursors[*i].is_some())
+                .collect::<Vec<_>>();
+            if active.is_empty() || rows >= MAX_TABLE_ROWS {
+                break;
+            }
+            let oids = active
+                .iter()
+                .map(|i| cursors[*i].clone().unwrap())
+                .collect::<Vec<_>>();
+            let varbinds = self.request(GET_BULK_REQUEST, &oids)?;
+            if varbinds.is_empty() {
+                break;
+            }
+            let mut next: Vec<Option<Oid>> = vec![None; active.len()];
+            let mut done = vec![false; active.len()];
+            // the varbinds are the repetitions of the requested columns
+            for (k, (oid, value)) in varbinds.into_iter().enumerate() {
+                let j = k % active.len();
+                let column = columns[active[j]];
+                let last = next[j].as_ref().or(cursors[active[j]].as_ref()).unwrap();
+                if done[j]
+                    || value == Value::EndOfMibView
+                    || oid.len() != column.len() + 1
+                    || !oid.starts_with(column)
+                    || oid <= *last
+                {
+                    done[j] = true;
+                    continue;
+                }
+                tables[active[j]].insert(oid[column.len()], value);
+                next[j] = Some(oid);
+                rows += 1;
+            }
+            for (j, i) in active.into_iter().enumerate() {
+                cursors[i] = if done[j] { None } else { next[j].take() };
+            }
+        }
+        Ok(tables)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct InterfaceCounters {
+    rx_bytes: u64,
+    tx_bytes: u64,
+    rx_packets: u64,
+    tx_packets: u64,
+    rx_errors: u64,
+    tx_errors: u64,
+    rx_discards: u64,
+    tx_discards: u64,
+    // counters of ifTable are 32 bits, which wrap quickly on fast interfaces
+    high_capacity: bool,
+}
+
+impl InterfaceCounters {
+    fn delta(&self, last: &InterfaceCounters) -> InterfaceCounters {
+        let wrapping = !(self.high_capacity && last.high_capacity);
+        let delta = |current: u64, last: u64| {
+            if current >= last {
+                current - last
+            } else if wrapping && last <= u32::MAX as u64 {
+                current + (1 << 32) - last
+            } else {
+                // reset
+                current
+            }
+        };
+        // errors and discards are always 32 bits
+        let delta32 = |current: u64, last: u64| {
+            if current >= last {
+                current - last
+            } else {
+                current + (1 << 32) - last
+            }
+        };
+        InterfaceCounters {
+            rx_bytes: delta(self.rx_bytes, last.rx_bytes),
+            tx_bytes: delta(self.tx_bytes, last.tx_bytes),
+            rx_packets: delta(self.rx_packets, last.rx_packets),
+            tx_packets: delta(self.tx_packets, last.tx_packets),
+            rx_errors: delta32(self.rx_errors, last.rx_errors),
+            tx_errors: delta32(self.tx_errors, last.tx_errors),
+            rx_discards: delta32(self.rx_discards, last.rx_discards),
+            tx_discards: delta32(self.tx_discards, last.tx_discards),
+            high_capacity: self.high_capacity,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Interface {
+    name: String,
+    speed: u64,
+    oper_status: u32,
+    counters: InterfaceCounters,
+}
+
+fn interfaces(tables: &[BTreeMap<u32, Value>]) -> BTreeMap<u32, Interface> {
+    let column = |oid: &[u32]| {
+        let i = INTERFACE_COLUMNS.iter().position(|c| *c == oid).unwrap();
+        &tables[i]
+    };
+    let value = |oid: &[u32], index: u32| column(oid).get(&index).and_then(Value::as_u64);
+    let sum = |oids: &[&[u32]], index: u32| {
+        oids.iter()
+            .map(|o| value(*o, index))
+            .try_fold(0, |s, v| v.map(|v| s + v))
+    };
+
+    let mut interfaces = BTreeMap::new();
+    for (index, descr) in column(IF_DESCR) {
+        let name = column(IF_NAME)
+            .get(index)
+            .and_then(Value::as_string)
+            .filter(|n| !n.is_empty())
+            .or_else(|| descr.as_string())
+            .unwrap_or_else(|| index.to_string());
+        let speed = match value(IF_HIGH_SPEED, *index) {
+            Some(s) if s > 0 => s * 1000000,
+            _ => value(IF_SPEED, *index).unwrap_or_default(),
+        };
+        let hc_rx_packets = sum(
+            &[
+                IF_HC_IN_UCAST_PKTS,
+                IF_HC_IN_MULTICAST_PKTS,
+                IF_HC_IN_BROADCAST_PKTS,
+            ],
+            *index,
+        );
+        let hc_tx_packets = sum(
+            &[
+                IF_HC_OUT_UCAST_PKTS,
+                IF_HC_OUT_MULTICAST_PKTS,
+                IF_HC_OUT_BROADCAST_PKTS,
+            ],
+            *index,
+        );
+        let counters = match (
+            value(IF_HC_IN_OCTETS, *index),
+            value(IF_HC_OUT_OCTETS, *index),
+            hc_rx_packets,
+            hc_tx_packets,
+        ) {
+            (Some(rx_bytes), Some(tx_bytes), Some(rx_packets), Some(tx_packets)) => {
+                InterfaceCounters {
+                    rx_bytes,
+                    tx_bytes,
+                    rx_packets,
+                    tx_packets,
+                    high_capacity: true,
+                    ..Default::default()
+                }
+            }
+            _ => InterfaceCounters {
+                rx_bytes: value(IF_IN_OCTETS, *index).unwrap_or_default(),
+                tx_bytes: value(IF_OUT_OCTETS, *index).unwrap_or_default(),
+                rx_packets: value(IF_IN_UCAST_PKTS, *index).unwrap_or_default(),
+                tx_packets: value(IF_OUT_UCAST_PKTS, *index).unwrap_or_default(),
+                high_capacity: false,
+                ..Default::default()
+            },
+        };
+        interfaces.insert(
+            *index,
+            Interface {
+                name,
+                speed,
+                oper_status: value(IF_OPER_STATUS, *index).unwrap_or_default() as u32,
+                counters: InterfaceCounters {
+                    rx_errors: value(IF_IN_ERRORS, *index).unwrap_or_default(),
+                    tx_errors: value(IF_OUT_ERRORS, *index).unwrap_or_default(),
+                    rx_discards: value(IF_IN_DISCARDS, *index).unwrap_or_default(),
+                    tx_discards: value(IF_OUT_DISCARDS, *index).unwrap_or_default(),
+                    ..counters
+                },
+            },
+        );
+    }
+    interfaces
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Health {
+    uptime: u64,
+    cpu_usage: u32,
+    memory_used: u64,
+    memory_total: u64,
+}
+
+// State of a polled device, the counters are kept to calculate the increments
+struct Device {
+    session: Session,
+    name: String,
+    uptime: u64,
+    interfaces: HashMap<u32, InterfaceCounters>,
+}
+
+impl Device {
+    fn health(&mut self) -> Result<Health> {
+        let values = self.session.get(&[SYS_UP_TIME, SYS_NAME])?;
+        let uptime = values[0].as_u64().unwrap_or_default() / 100;
+        if self.name.is_empty() {
+            self.name = values[1].as_string().unwrap_or_default();
+        }
+        let mut health = Health {
+            uptime,
+            ..Default::default()
+        };
+
+        // devices without HOST-RESOURCES-MIB have no cpu and memory
+        let processors = self.session.walk_columns(&[HR_PROCESSOR_LOAD])?;
+        let loads = processors[0]
+            .values()
+            .filter_map(Value::as_u64)
+            .collect::<Vec<_>>();
+        if !loads.is_empty() {
+            health.cpu_usage = (loads.iter().sum::<u64>() / loads.len() as u64) as u32;
+        }
+        let storages = self.session.walk_columns(&[
+            HR_STORAGE_TYPE,
+            HR_STORAGE_ALLOCATION_UNITS,
+            HR_STORAGE_SIZE,
+            HR_STORAGE_USED,
+        ])?;
+        for (index, storage_type) in storages[0].iter() {
+            if storage_type != &Value::Oid(HR_STORAGE_RAM.to_vec()) {
+                continue;
+            }
+            let value = |i: usize| storages[i].get(index).and_then(Value::as_u64);
+            let unit = value(1).unwrap_or_default();
+            health.memory_total += value(2).unwrap_or_default() * unit;
+            health.memory_used += value(3).unwrap_or_default() * unit;
+        }
+        Ok(health)
+    }
+
+    fn poll(&mut self, agent_id: u16, timestamp: u32) -> Result<Vec<BoxedDocument>> {
+        let health = self.health()?;
+        let tables = self.session.walk_columns(&INTERFACE_COLUMNS)?;
+        let interfaces = interfaces(&tables);
+
+        let ip = self
+            .session
+            .peer_ip()
+            .unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+        let name = if self.device_name().is_empty() {
+            ip.to_string()
+        } else {
+            self.device_name().to_owned()
+        };
+        let document = |interface_name: Option<String>, meter: DeviceMeter| {
+            let mut doc = Document::new(Meter::Device(meter));
+            doc.timestamp = timestamp;
+            doc.tagger = Tagger {
+                code: Code::IP | Code::VTAP_ID | Code::DEVICE,
+                ip,
+                is_ipv6: ip.is_ipv6(),
+                agent_id,
+                device_name: Some(name.clone()),
+                interface_name,
+                ..Default::default()
+            };
+            doc.flags = DocumentFlag::NONE;
+            BoxedDocument(Box::new(doc))
+        };
+
+        let mut documents = vec![document(
+            None,
+            DeviceMeter {
+                meter_type: DeviceMeterType::Health,
+                cpu_usage: health.cpu_usage,
+                memory_used: health.memory_used,
+                memory_total: health.memory_total,
+                uptime: health.uptime,
+                ..Default::default()
+            },
+        )];
+        // the counters are reset when the device restarts
+        let restarted = health.uptime < self.uptime;
+        self.uptime = health.uptime;
+        let mut last_counters = std::mem::take(&mut self.interfaces);
+        for (index, interface) in interfaces {
+            self.interfaces.insert(index, interface.counters);
+            let Some(last) = last_counters.remove(&index) else {
+                continue;
+            };
+            if restarted {
+                continue;
+            }
+            let delta = interface.counters.delta(&last);
+            documents.push(document(
+                Some(interface.name),
+                DeviceMeter {
+                    meter_type: DeviceMeterType::Interface,
+                    rx_bytes: delta.rx_bytes,
+                    tx_bytes: delta.tx_bytes,
+                    rx_packets: delta.rx_packets,
+                    tx_packets: delta.tx_packets,
+                    rx_errors: delta.rx_errors,
+                    tx_errors: delta.tx_errors,
+                    rx_discards: delta.rx_discards,
+                    tx_discards: delta.tx_discards,
+                    speed: interface.speed,
+                    oper_status: interface.oper_status,
+                    ..Default::default()
+                },
+            ));
+        }
+        Ok(documents)
+    }
+
+    fn device_name(&self) -> &str {
+        if self.session.device.name.is_empty() {
+            &self.name
+        } else {
+            &self.session.device.name
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SnmpPollerCounter {
+    polls: AtomicU64,
+    poll_failed: AtomicU64,
+    documents: AtomicU64,
+    // documents dropped because the queue is full
+    queue_drop: AtomicU64,
+}
+
+impl RefCountable for SnmpPollerCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "polls",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.polls.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "poll-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.poll_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "documents",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.documents.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "queue-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.queue_drop.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Poller {
+    config: SnmpAccess,
+    output: DebugSender<BoxedDocument>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<SnmpPollerCounter>,
+    // by the address of the devices
+    devices: HashMap<String, Device>,
+}
+
+impl Poller {
+    fn poll_all(&mut self, agent_id: u16, config: &Snmp) {
+        // sessions of removed or changed devices are closed
+        self.devices.retain(|address, device| {
+            config
+                .devices
+                .iter()
+                .any(|d| &d.address == address && d == &device.session.device)
+        });
+
+        let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed)).as_secs();
+        let timestamp = (now / SECONDS_PER_MINUTE * SECONDS_PER_MINUTE) as u32;
+        let mut documents = vec![];
+        for device_config in config.devices.iter() {
+            self.counter.polls.fetch_add(1, Ordering::Relaxed);
+            if !self.devices.contains_key(&device_config.address) {
+                match Session::new(device_config, config) {
+                    Ok(session) => {
+                        self.devices.insert(
+                            device_config.address.clone(),
+                            Device {
+                                session,
+                                name: String::new(),
+                                uptime: 0,
+                                interfaces: HashMap::new(),
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        warn!(
+                            "snmp poller failed to connect {}: {}",
+                            device_config.address, e
+                        );
+                        self.counter.poll_failed.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                }
+            }
+            let device = self.devices.get_mut(&device_config.address).unwrap();
+            match device.poll(agent_id, timestamp) {
+                Ok(mut docs) => documents.append(&mut docs),
+                Err(e) => {
+                    warn!(
+                        "snmp poller failed to poll {}: {}",
+                        device_config.address, e
+                    );
+                    self.counter.poll_failed.fetch_add(1, Ordering::Relaxed);
+                    // the engine may have been restarted
+                    device.session.engine = None;
+                }
+            }
+        }
+
+        if documents.is_empty() {
+            return;
+        }
+        let count = documents.len() as u64;
+        if let Err(e) = self.output.send_all(&mut documents) {
+            debug!("snmp poller failed to send documents: {:?}", e);
+            self.counter.queue_drop.fetch_add(count, Ordering::Relaxed);
+            return;
+        }
+        self.counter.documents.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+// Polls interface counters and health of network devices by SNMP, and sends them as per minute
+// documents, so that switches and routers appear alongside the hosts.
+//
+// Interface counters are sent as the increments since the last poll, the first poll of a device
+// only sends its health. Devices are polled one by one in every interval, a slow device delays
+// the others.
+pub struct SnmpPoller {
+    config: SnmpAccess,
+    output: DebugSender<BoxedDocument>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<SnmpPollerCounter>,
+
+    running: Arc<(Mutex<bool>, Condvar)>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SnmpPoller {
+    pub fn new(
+        config: SnmpAccess,
+        output: DebugSender<BoxedDocument>,
+        ntp_diff: Arc<AtomicI64>,
+    ) -> Self {
+        Self {
+            config,
+            output,
+            ntp_diff,
+            counter: Arc::new(SnmpPollerCounter::default()),
+            running: Arc::new((Mutex::new(false), Condvar::new())),
+            thread: Mutex::new(None),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<SnmpPollerCounter> {
+        self.counter.clone()
+    }
+
+    pub fn start(&self) {
+        {
+            let (started, _) = &*self.running;
+            let mut started = started.lock().unwrap();
+            if *started {
+                return;
+            }
+            *started = true;
+        }
+
+        let mut poller = Poller {
+            config: self.config.clone(),
+            output: self.output.clone(),
+            ntp_diff: self.ntp_diff.clone(),
+            counter: self.counter.clone(),
+            devices: HashMap::new(),
+        };
+        let running = self.running.clone();
+        let thread = thread::Builder::new()
+            .name("snmp-poller".to_owned())
+            .spawn(move || loop {
+                let (agent_id, config) = {
+                    let config = poller.config.load();
+                    (config.agent_id, config.snmp.clone())
+                };
+                if config.enabled {
+                    poller.poll_all(agent_id, &config);
+                } else {
+                    poller.devices.clear();
+                }
+
+                let (running, timer) = &*running;
+                let mut running = running.lock().unwrap();
+                if !*running {
+                    break;
+                }
+                running = timer
+                    .wait_timeout(running, config.interval.max(MIN_INTERVAL))
+                    .unwrap()
+                    .0;
+                if !*running {
+                    break;
+                }
+            })
+            .unwrap();
+        self.thread.lock().unwrap().replace(thread);
+        info!("snmp poller started");
+    }
+
+    pub fn notify_stop(&self) -> Option<JoinHandle<()>> {
+        let (stopped, timer) = &*self.running;
+        {
+            let mut stopped = stopped.lock().unwrap();
+            if !*stopped {
+                return None;
+            }
+            *stopped = false;
+        }
+        timer.notify_one();
+        info!("notified stopping snmp poller");
+        self.thread.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface_counters() {
+        let mut tables = vec![BTreeMap::new(); INTERFACE_COLUMNS.len()];
+        let mut set = |oid: &[u32], index: u32, value: Value| {
+            let i = INTERFACE_COLUMNS.iter().position(|c| *c == oid).unwrap();
+            tables[i].insert(index, value);
+        };
+        set(
+            IF_DESCR,
+            1,
+            Value::OctetString(b"GigabitEthernet0/1".to_vec()),
+        );
+        set(IF_NAME, 1, Value::OctetString(b"Gi0/1".to_vec()));
+        set(IF_SPEED, 1, Value::Gauge32(u32::MAX));
+        set(IF_HIGH_SPEED, 1, Value::Gauge32(10000));
+        set(IF_OPER_STATUS, 1, Value::Integer(1));
+        set(IF_HC_IN_OCTETS, 1, Value::Counter64(1 << 40));
+        set(IF_HC_OUT_OCTETS, 1, Value::Counter64(1 << 41));
+        for oid in [
+            IF_HC_IN_UCAST_PKTS,
+            IF_HC_IN_MULTICAST_PKTS,
+            IF_HC_IN_BROADCAST_PKTS,
+            IF_HC_OUT_UCAST_PKTS,
+            IF_HC_OUT_MULTICAST_PKTS,
+            IF_HC_OUT_BROADCAST_PKTS,
+        ] {
+            set(oid, 1, Value::Counter64(10));
+        }
+        set(IF_IN_ERRORS, 1, Value::Counter32(3));
+        // no ifXTable
+        set(IF_DESCR, 2, Value::OctetString(b"eth1".to_vec()));
+        set(IF_SPEED, 2, Value::Gauge32(100000000));
+        set(IF_IN_OCTETS, 2, Value::Counter32(100));
+
+        let interfaces = interfaces(&tables);
+        assert_eq!(
+            interfaces[&1],
+            Interface {
+                name: "Gi0/1".to_owned(),
+                speed: 10000000000,
+                oper_status: 1,
+                counters: InterfaceCounters {
+                    rx_bytes: 1 << 40,
+                    tx_bytes: 1 << 41,
+                    rx_packets: 30,
+                    tx_packets: 30,
+                    rx_errors: 3,
+                    high_capacity: true,
+                    ..Default::default()
+                },
+            }
+        );
+        assert_eq!(interfaces[&2].name, "eth1");
+        assert_eq!(interfaces[&2].speed, 100000000);
+        assert!(!interfaces[&2].counters.high_capacity);
+
+        let last = InterfaceCounters {
+            rx_bytes: u32::MAX as u64 - 10,
+            ..Default::default()
+        };
+        let current = InterfaceCounters {
+            rx_bytes: 5,
+            ..Default::default()
+        };
+        assert_eq!(current.delta(&last).rx_bytes, 16);
+        let last = InterfaceCounters {
+            rx_bytes: 1 << 40,
+            high_capacity: true,
+            ..Default::default()
+        };
+        let current = InterfaceCounters {
+            rx_bytes: 5,
+            high_capacity: true,
+            ..Default::default()
+        };
+        assert_eq!(current.delta(&last).rx_bytes, 5);
+    }
+}
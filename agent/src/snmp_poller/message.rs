@@ -0,0 +1,559 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// BER encoding of SNMPv2c (RFC 3416) and SNMPv3 (RFC 3412, RFC 3414) messages, only the types
+// used by the poller are supported.
+
+use std::net::Ipv4Addr;
+
+use super::SnmpError;
+
+type Result<T> = std::result::Result<T, SnmpError>;
+
+pub(super) type Oid = Vec<u32>;
+
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const IP_ADDRESS: u8 = 0x40;
+const COUNTER32: u8 = 0x41;
+const GAUGE32: u8 = 0x42;
+const TIME_TICKS: u8 = 0x43;
+const COUNTER64: u8 = 0x46;
+const NO_SUCH_OBJECT: u8 = 0x80;
+const NO_SUCH_INSTANCE: u8 = 0x81;
+const END_OF_MIB_VIEW: u8 = 0x82;
+
+pub(super) const GET_REQUEST: u8 = 0xa0;
+pub(super) const RESPONSE: u8 = 0xa2;
+pub(super) const GET_BULK_REQUEST: u8 = 0xa5;
+pub(super) const REPORT: u8 = 0xa8;
+
+const VERSION_2C: i64 = 1;
+const VERSION_3: i64 = 3;
+const USM_SECURITY_MODEL: i64 = 3;
+// max size of udp payload
+pub(super) const MAX_MESSAGE_SIZE: usize = 65507;
+
+pub(super) const FLAG_AUTH: u8 = 0x01;
+pub(super) const FLAG_PRIV: u8 = 0x02;
+pub(super) const FLAG_REPORTABLE: u8 = 0x04;
+
+// length of the truncated HMAC of usmHMACMD5AuthProtocol and usmHMACSHAAuthProtocol
+pub(super) const AUTH_PARAMS_LEN: usize = 12;
+
+fn write_length(buffer: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        buffer.push(len as u8);
+        return;
+    }
+    let bytes = len.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count();
+    buffer.push(0x80 | (bytes.len() - skip) as u8);
+    buffer.extend_from_slice(&bytes[skip..]);
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(content.len() + 6);
+    buffer.push(tag);
+    write_length(&mut buffer, content.len());
+    buffer.extend_from_slice(content);
+    buffer
+}
+
+fn integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    // the minimal two's complement
+    let mut skip = 0;
+    while skip < bytes.len() - 1
+        && ((bytes[skip] == 0 && bytes[skip + 1] & 0x80 == 0)
+            || (bytes[skip] == 0xff && bytes[skip + 1] & 0x80 != 0))
+    {
+        skip += 1;
+    }
+    tlv(INTEGER, &bytes[skip..])
+}
+
+fn object_identifier(oid: &[u32]) -> Vec<u8> {
+    let mut content = vec![];
+    let (first, rest) = match oid {
+        [a, b, rest @ ..] => (a * 40 + b, rest),
+        [a] => (a * 40, &[][..]),
+        [] => (0, &[][..]),
+    };
+    for sub_id in std::iter::once(&first).chain(rest) {
+        let mut bytes = vec![(sub_id & 0x7f) as u8];
+        let mut v = sub_id >> 7;
+        while v > 0 {
+            bytes.push((v & 0x7f) as u8 | 0x80);
+            v >>= 7;
+        }
+        content.extend(bytes.iter().rev());
+    }
+    tlv(OBJECT_IDENTIFIER, &content)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn read(&mut self) -> Result<(u8, &'a [u8])> {
+        let [tag, first, rest @ ..] = self.data else {
+            return Err(SnmpError::Malformed("truncated header"));
+        };
+        let (len, rest) = if first & 0x80 == 0 {
+            (*first as usize, rest)
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 || rest.len() < n {
+                return Err(SnmpError::Malformed("invalid length"));
+            }
+            let len = rest[..n]
+                .iter()
+                .fold(0usize, |len, b| len << 8 | *b as usize);
+            (len, &rest[n..])
+        };
+        if rest.len() < len {
+            return Err(SnmpError::Malformed("truncated content"));
+        }
+        self.data = &rest[len..];
+        Ok((*tag, &rest[..len]))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<&'a [u8]> {
+        match self.read()? {
+            (tag, content) if tag == expected => Ok(content),
+            _ => Err(SnmpError::Malformed("unexpected tag")),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64> {
+        decode_integer(self.expect(INTEGER)?)
+    }
+}
+
+fn decode_integer(content: &[u8]) -> Result<i64> {
+    if content.is_empty() || content.len() > 8 {
+        return Err(SnmpError::Malformed("invalid integer"));
+    }
+    let init = if content[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(content.iter().fold(init, |v, b| v << 8 | *b as i64))
+}
+
+fn decode_unsigned(content: &[u8]) -> Result<u64> {
+    // leading zero of the positive values with the highest bit set
+    let content = match content {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => content,
+    };
+    if content.is_empty() || content.len() > 8 {
+        return Err(SnmpError::Malformed("invalid unsigned"));
+    }
+    Ok(content.iter().fold(0, |v, b| v << 8 | *b as u64))
+}
+
+fn decode_oid(content: &[u8]) -> Result<Oid> {
+    let mut sub_ids = vec![];
+    let mut v: u32 = 0;
+    for b in content {
+        if v > u32::MAX >> 7 {
+            return Err(SnmpError::Malformed("sub-identifier overflow"));
+        }
+        v = v << 7 | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            sub_ids.push(v);
+            v = 0;
+        }
+    }
+    let Some(first) = sub_ids.first().copied() else {
+        return Err(SnmpError::Malformed("empty oid"));
+    };
+    let mut oid = Vec::with_capacity(sub_ids.len() + 1);
+    oid.push((first / 40).min(2));
+    oid.push(first - oid[0] * 40);
+    oid.extend_from_slice(&sub_ids[1..]);
+    Ok(oid)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    Oid(Oid),
+    IpAddress([u8; 4]),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+    Unsupported,
+}
+
+impl Value {
+    fn decode(tag: u8, content: &[u8]) -> Result<Self> {
+        Ok(match tag {
+            INTEGER => Self::Integer(decode_integer(content)?),
+            OCTET_STRING => Self::OctetString(content.to_vec()),
+            NULL => Self::Null,
+            OBJECT_IDENTIFIER => Self::Oid(decode_oid(content)?),
+            IP_ADDRESS => Self::IpAddress(
+                content
+                    .try_into()
+                    .map_err(|_| SnmpError::Malformed("invalid ip address"))?,
+            ),
+            COUNTER32 => Self::Counter32(decode_unsigned(content)? as u32),
+            GAUGE32 => Self::Gauge32(decode_unsigned(content)? as u32),
+            TIME_TICKS => Self::TimeTicks(decode_unsigned(content)? as u32),
+            COUNTER64 => Self::Counter64(decode_unsigned(content)?),
+            NO_SUCH_OBJECT => Self::NoSuchObject,
+            NO_SUCH_INSTANCE => Self::NoSuchInstance,
+            END_OF_MIB_VIEW => Self::EndOfMibView,
+            _ => Self::Unsupported,
+        })
+    }
+
+    pub(super) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Integer(v) if *v >= 0 => Some(*v as u64),
+            Self::Counter32(v) | Self::Gauge32(v) | Self::TimeTicks(v) => Some(*v as u64),
+            Self::Counter64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_string(&self) -> Option<String> {
+        match self {
+            Self::OctetString(v) => Some(String::from_utf8_lossy(v).trim().to_owned()),
+            Self::IpAddress(v) => Some(Ipv4Addr::from(*v).to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Pdu {
+    pub(super) pdu_type: u8,
+    pub(super) request_id: i32,
+    // non-repeaters of GetBulkRequest
+    pub(super) error_status: i64,
+    // max-repetitions of GetBulkRequest
+    pub(super) error_index: i64,
+    pub(super) varbinds: Vec<(Oid, Value)>,
+}
+
+impl Pdu {
+    pub(super) fn new_request(
+        pdu_type: u8,
+        request_id: i32,
+        max_repetitions: u32,
+        oids: &[Oid],
+    ) -> Self {
+        Self {
+            pdu_type,
+            request_id,
+            error_status: 0,
+            error_index: if pdu_type == GET_BULK_REQUEST {
+                max_repetitions as i64
+            } else {
+                0
+            },
+            // values of requests are null
+            varbinds: oids.iter().map(|o| (o.clone(), Value::Null)).collect(),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut varbinds = vec![];
+        for (oid, _) in self.varbinds.iter() {
+            let mut varbind = object_identifier(oid);
+            varbind.extend_from_slice(&[NULL, 0]);
+            varbinds.extend(tlv(SEQUENCE, &varbind));
+        }
+        let mut content = integer(self.request_id as i64);
+        content.extend(integer(self.error_status));
+        content.extend(integer(self.error_index));
+        content.extend(tlv(SEQUENCE, &varbinds));
+        tlv(self.pdu_type, &content)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self> {
+        let (pdu_type, content) = reader.read()?;
+        let mut reader = Reader::new(content);
+        let request_id = reader.integer()? as i32;
+        let error_status = reader.integer()?;
+        let error_index = reader.integer()?;
+        let mut list = Reader::new(reader.expect(SEQUENCE)?);
+        let mut varbinds = vec![];
+        while !list.is_empty() {
+            let mut varbind = Reader::new(list.expect(SEQUENCE)?);
+            let oid = decode_oid(varbind.expect(OBJECT_IDENTIFIER)?)?;
+            let (tag, value) = varbind.read()?;
+            varbinds.push((oid, Value::decode(tag, value)?));
+        }
+        Ok(Self {
+            pdu_type,
+            request_id,
+            error_status,
+            error_index,
+            varbinds,
+        })
+    }
+}
+
+pub(super) fn encode_v2c(community: &str, pdu: &Pdu) -> Vec<u8> {
+    let mut content = integer(VERSION_2C);
+    content.extend(tlv(OCTET_STRING, community.as_bytes()));
+    content.extend(pdu.encode());
+    tlv(SEQUENCE, &content)
+}
+
+pub(super) fn decode_v2c(data: &[u8]) -> Result<Pdu> {
+    let mut message = Reader::new(Reader::new(data).expect(SEQUENCE)?);
+    if message.integer()? != VERSION_2C {
+        return Err(SnmpError::Malformed("unexpected version"));
+    }
+    let _community = message.expect(OCTET_STRING)?;
+    Pdu::decode(&mut message)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(super) struct UsmParams {
+    pub(super) engine_id: Vec<u8>,
+    pub(super) engine_boots: u32,
+    pub(super) engine_time: u32,
+    pub(super) user_name: Vec<u8>,
+    pub(super) auth_params: Vec<u8>,
+    pub(super) priv_params: Vec<u8>,
+}
+
+pub(super) fn encode_scoped_pdu(
+    context_engine_id: &[u8],
+    context_name: &str,
+    pdu: &Pdu,
+) -> Vec<u8> {
+    let mut content = tlv(OCTET_STRING, context_engine_id);
+    content.extend(tlv(OCTET_STRING, context_name.as_bytes()));
+    content.extend(pdu.encode());
+    tlv(SEQUENCE, &content)
+}
+
+pub(super) fn decode_scoped_pdu(data: &[u8]) -> Result<Pdu> {
+    let mut scoped = Reader::new(Reader::new(data).expect(SEQUENCE)?);
+    let _context_engine_id = scoped.expect(OCTET_STRING)?;
+    let _context_name = scoped.expect(OCTET_STRING)?;
+    Pdu::decode(&mut scoped)
+}
+
+// Encodes a message with the scoped pdu, or the encrypted scoped pdu if FLAG_PRIV is set.
+//
+// If FLAG_AUTH is set, the authentication parameters are zeros to be replaced by the HMAC of the
+// whole message, and the offset of them in the message is returned.
+pub(super) fn encode_v3(
+    msg_id: i32,
+    flags: u8,
+    usm: &UsmParams,
+    scoped_pdu: &[u8],
+) -> (Vec<u8>, usize) {
+    let mut global = integer(msg_id as i64);
+    global.extend(integer(MAX_MESSAGE_SIZE as i64));
+    global.extend(tlv(OCTET_STRING, &[flags]));
+    global.extend(integer(USM_SECURITY_MODEL));
+
+    let mut params = tlv(OCTET_STRING, &usm.engine_id);
+    params.extend(integer(usm.engine_boots as i64));
+    params.extend(integer(usm.engine_time as i64));
+    params.extend(tlv(OCTET_STRING, &usm.user_name));
+    // tag and length of the authentication parameters shorter than 128 bytes
+    let mut auth_offset = params.len() + 2;
+    params.extend(tlv(OCTET_STRING, &usm.auth_params));
+    params.extend(tlv(OCTET_STRING, &usm.priv_params));
+    let sequence = tlv(SEQUENCE, &params);
+    auth_offset += sequence.len() - params.len();
+    let params = tlv(OCTET_STRING, &sequence);
+    auth_offset += params.len() - sequence.len();
+
+    let mut content = integer(VERSION_3);
+    content.extend(tlv(SEQUENCE, &global));
+    auth_offset += content.len();
+    content.extend(params);
+    if flags & FLAG_PRIV != 0 {
+        content.extend(tlv(OCTET_STRING, scoped_pdu));
+    } else {
+        content.extend_from_slice(scoped_pdu);
+    }
+    let message = tlv(SEQUENCE, &content);
+    auth_offset += message.len() - content.len();
+    (message, auth_offset)
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct V3Message {
+    pub(super) msg_id: i32,
+    pub(super) flags: u8,
+    pub(super) usm: UsmParams,
+    // offset of the authentication parameters in the message
+    pub(super) auth_offset: usize,
+    // the scoped pdu, which is encrypted if FLAG_PRIV is set
+    pub(super) data: Vec<u8>,
+}
+
+pub(super) fn decode_v3(data: &[u8]) -> Result<V3Message> {
+    let mut message = Reader::new(Reader::new(data).expect(SEQUENCE)?);
+    if message.integer()? != VERSION_3 {
+        return Err(SnmpError::Malformed("unexpected version"));
+    }
+    let mut global = Reader::new(message.expect(SEQUENCE)?);
+    let msg_id = global.integer()? as i32;
+    let _max_size = global.integer()?;
+    let flags = *global
+        .expect(OCTET_STRING)?
+        .first()
+        .ok_or(SnmpError::Malformed("empty flags"))?;
+    if global.integer()? != USM_SECURITY_MODEL {
+        return Err(SnmpError::Malformed("unexpected security model"));
+    }
+
+    let mut params = Reader::new(Reader::new(message.expect(OCTET_STRING)?).expect(SEQUENCE)?);
+    let engine_id = params.expect(OCTET_STRING)?.to_vec();
+    let engine_boots = params.integer()? as u32;
+    let engine_time = params.integer()? as u32;
+    let user_name = params.expect(OCTET_STRING)?.to_vec();
+    let auth_params = params.expect(OCTET_STRING)?;
+    let auth_offset = auth_params.as_ptr() as usize - data.as_ptr() as usize;
+    let auth_params = auth_params.to_vec();
+    let priv_params = params.expect(OCTET_STRING)?.to_vec();
+
+    let data = if flags & FLAG_PRIV != 0 {
+        message.expect(OCTET_STRING)?.to_vec()
+    } else {
+        message.data.to_vec()
+    };
+    Ok(V3Message {
+        msg_id,
+        flags,
+        usm: UsmParams {
+            engine_id,
+            engine_boots,
+            engine_time,
+            user_name,
+            auth_params,
+            priv_params,
+        },
+        auth_offset,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_basic_types() {
+        assert_eq!(integer(0), [INTEGER, 1, 0]);
+        assert_eq!(integer(127), [INTEGER, 1, 0x7f]);
+        assert_eq!(integer(128), [INTEGER, 2, 0, 0x80]);
+        assert_eq!(integer(-129), [INTEGER, 2, 0xff, 0x7f]);
+        assert_eq!(
+            object_identifier(&[1, 3, 6, 1, 2, 1, 1, 5, 0]),
+            [OBJECT_IDENTIFIER, 8, 0x2b, 6, 1, 2, 1, 1, 5, 0]
+        );
+        assert_eq!(
+            object_identifier(&[1, 3, 6, 1, 4, 1, 2021]),
+            [OBJECT_IDENTIFIER, 7, 0x2b, 6, 1, 4, 1, 0x8f, 0x65]
+        );
+        assert_eq!(
+            decode_oid(&[0x2b, 6, 1, 4, 1, 0x8f, 0x65]).unwrap(),
+            vec![1, 3, 6, 1, 4, 1, 2021]
+        );
+
+        let mut long = vec![];
+        write_length(&mut long, 300);
+        assert_eq!(long, [0x82, 0x01, 0x2c]);
+        assert_eq!(
+            decode_unsigned(&[0, 0xff, 0xff, 0xff, 0xff]).unwrap(),
+            0xffffffff
+        );
+        assert_eq!(decode_integer(&[0xff, 0x7f]).unwrap(), -129);
+    }
+
+    #[test]
+    fn v2c_round_trip() {
+        let request = Pdu::new_request(GET_REQUEST, 1, 0, &[vec![1, 3, 6, 1, 2, 1, 1, 3, 0]]);
+        let encoded = encode_v2c("public", &request);
+        assert_eq!(
+            encoded,
+            [
+                0x30, 0x26, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa0,
+                0x19, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x0e, 0x30, 0x0c,
+                0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x03, 0x00, 0x05, 0x00,
+            ]
+        );
+        assert_eq!(decode_v2c(&encoded).unwrap(), request);
+
+        let response = [
+            0x30, 0x2e, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa2,
+            0x21, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x16, 0x30, 0x14,
+            0x06, 0x0b, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x1f, 0x01, 0x01, 0x01, 0x06, 0x01, 0x46,
+            0x05, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let pdu = decode_v2c(&response).unwrap();
+        assert_eq!(pdu.pdu_type, RESPONSE);
+        assert_eq!(
+            pdu.varbinds,
+            vec![(
+                vec![1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 6, 1],
+                Value::Counter64(0x100000000)
+            )]
+        );
+    }
+
+    #[test]
+    fn v3_auth_offset() {
+        let usm = UsmParams {
+            engine_id: vec![0x80, 0, 0x1f, 0x88, 4],
+            engine_boots: 1,
+            engine_time: 1000,
+            user_name: b"admin".to_vec(),
+            auth_params: vec![0; AUTH_PARAMS_LEN],
+            priv_params: vec![],
+        };
+        let pdu = Pdu::new_request(GET_BULK_REQUEST, 7, 10, &[vec![1, 3, 6, 1, 2, 1, 2, 2, 1]]);
+        let scoped = encode_scoped_pdu(&usm.engine_id, "", &pdu);
+        let (message, offset) = encode_v3(3, FLAG_AUTH | FLAG_REPORTABLE, &usm, &scoped);
+
+        let decoded = decode_v3(&message).unwrap();
+        assert_eq!(decoded.msg_id, 3);
+        assert_eq!(decoded.flags, FLAG_AUTH | FLAG_REPORTABLE);
+        assert_eq!(decoded.usm, usm);
+        assert_eq!(decoded.auth_offset, offset);
+        assert_eq!(decode_scoped_pdu(&decoded.data).unwrap(), pdu);
+    }
+}
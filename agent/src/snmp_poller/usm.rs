@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Authentication and privacy of the user-based security model, see RFC 3414 and RFC 3826
+
+use aes::{
+    cipher::{AsyncStreamCipher, KeyIvInit},
+    Aes128,
+};
+use md5::{Digest, Md5};
+use ring::digest;
+
+use super::message::AUTH_PARAMS_LEN;
+use crate::config::SnmpAuthProtocol;
+
+type Aes128CfbEnc = cfb_mode::Encryptor<Aes128>;
+type Aes128CfbDec = cfb_mode::Decryptor<Aes128>;
+
+// both MD5 and SHA-1 have 64 bytes blocks
+const HMAC_BLOCK_SIZE: usize = 64;
+const PASSWORD_EXPANDED_LEN: usize = 1048576;
+const AES_KEY_LEN: usize = 16;
+pub(super) const PRIV_PARAMS_LEN: usize = 8;
+
+fn hash(protocol: SnmpAuthProtocol, parts: &[&[u8]]) -> Vec<u8> {
+    match protocol {
+        SnmpAuthProtocol::Md5 => {
+            let mut hasher = Md5::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+        SnmpAuthProtocol::Sha => {
+            let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+            for part in parts {
+                context.update(part);
+            }
+            context.finish().as_ref().to_vec()
+        }
+    }
+}
+
+// The localized key of the password and the authoritative engine, see RFC 3414 A.2
+pub(super) fn password_to_key(
+    protocol: SnmpAuthProtocol,
+    password: &str,
+    engine_id: &[u8],
+) -> Vec<u8> {
+    let expanded = password
+        .as_bytes()
+        .iter()
+        .copied()
+        .cycle()
+        .take(PASSWORD_EXPANDED_LEN)
+        .collect::<Vec<_>>();
+    let key = hash(protocol, &[&expanded]);
+    hash(protocol, &[&key, engine_id, &key])
+}
+
+fn hmac(protocol: SnmpAuthProtocol, key: &[u8], data: &[u8]) -> Vec<u8> {
+    // localized keys are shorter than the block
+    let mut ipad = [0x36u8; HMAC_BLOCK_SIZE];
+    let mut opad = [0x5cu8; HMAC_BLOCK_SIZE];
+    for (i, k) in key.iter().enumerate().take(HMAC_BLOCK_SIZE) {
+        ipad[i] ^= k;
+        opad[i] ^= k;
+    }
+    let inner = hash(protocol, &[&ipad, data]);
+    hash(protocol, &[&opad, &inner])
+}
+
+// Fills the authentication parameters, which are zeros at `offset` in the message
+pub(super) fn sign(protocol: SnmpAuthProtocol, key: &[u8], message: &mut [u8], offset: usize) {
+    let mac = hmac(protocol, key, message);
+    message[offset..offset + AUTH_PARAMS_LEN].copy_from_slice(&mac[..AUTH_PARAMS_LEN]);
+}
+
+pub(super) fn verify(
+    protocol: SnmpAuthProtocol,
+    key: &[u8],
+    message: &[u8],
+    offset: usize,
+) -> bool {
+    if message.len() < offset + AUTH_PARAMS_LEN {
+        return false;
+    }
+    let mut zeroed = message.to_vec();
+    zeroed[offset..offset + AUTH_PARAMS_LEN].fill(0);
+    hmac(protocol, key, &zeroed)[..AUTH_PARAMS_LEN] == message[offset..offset + AUTH_PARAMS_LEN]
+}
+
+fn aes_iv(engine_boots: u32, engine_time: u32, salt: &[u8]) -> [u8; 16] {
+    let mut iv = [0; 16];
+    iv[..4].copy_from_slice(&engine_boots.to_be_bytes());
+    iv[4..8].copy_from_slice(&engine_time.to_be_bytes());
+    iv[8..].copy_from_slice(salt);
+    iv
+}
+
+// Encrypts the scoped pdu with AES-128 in CFB mode, the salt is the privacy parameters
+pub(super) fn encrypt(
+    key: &[u8],
+    engine_boots: u32,
+    engine_time: u32,
+    salt: u64,
+    data: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let salt = salt.to_be_bytes();
+    let mut encrypted = data.to_vec();
+    Aes128CfbEnc::new_from_slices(
+        &key[..AES_KEY_LEN],
+        &aes_iv(engine_boots, engine_time, &salt),
+    )
+    .unwrap()
+    .encrypt(&mut encrypted);
+    (encrypted, salt.to_vec())
+}
+
+pub(super) fn decrypt(
+    key: &[u8],
+    engine_boots: u32,
+    engine_time: u32,
+    priv_params: &[u8],
+    data: &[u8],
+) -> Option<Vec<u8>> {
+    if priv_params.len() != PRIV_PARAMS_LEN {
+        return None;
+    }
+    let mut decrypted = data.to_vec();
+    Aes128CfbDec::new_from_slices(
+        &key[..AES_KEY_LEN],
+        &aes_iv(engine_boots, engine_time, priv_params),
+    )
+    .ok()?
+    .decrypt(&mut decrypted);
+    Some(decrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // see RFC 3414 A.3
+    #[test]
+    fn localized_keys() {
+        let engine_id = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        assert_eq!(
+            hex::encode(password_to_key(
+                SnmpAuthProtocol::Md5,
+                "maplesyrup",
+                &engine_id
+            )),
+            "526f5eed9fcce26f8964c2930787d82b"
+        );
+        assert_eq!(
+            hex::encode(password_to_key(
+                SnmpAuthProtocol::Sha,
+                "maplesyrup",
+                &engine_id
+            )),
+            "6695febc9288e36282235fc7151f128497b38f3f"
+        );
+    }
+
+    // see RFC 2202
+    #[test]
+    fn hmac_digests() {
+        assert_eq!(
+            hex::encode(hmac(
+                SnmpAuthProtocol::Md5,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            )),
+            "750c783e6ab0b503eaa86e310a5db738"
+        );
+        assert_eq!(
+            hex::encode(hmac(
+                SnmpAuthProtocol::Sha,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            )),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+    }
+
+    #[test]
+    fn sign_and_encrypt() {
+        let key = password_to_key(SnmpAuthProtocol::Sha, "password", b"engine");
+        let mut message = vec![1u8; 64];
+        message[10..22].fill(0);
+        sign(SnmpAuthProtocol::Sha, &key, &mut message, 10);
+        assert!(verify(SnmpAuthProtocol::Sha, &key, &message, 10));
+        message[0] = 0;
+        assert!(!verify(SnmpAuthProtocol::Sha, &key, &message, 10));
+
+        let data = b"scoped pdu of any length".to_vec();
+        let (encrypted, salt) = encrypt(&key, 1, 100, 42, &data);
+        assert_ne!(encrypted, data);
+        assert_eq!(decrypt(&key, 1, 100, &salt, &encrypted), Some(data));
+    }
+}
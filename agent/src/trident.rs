@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::collections::HashMap;
 use std::env;
 use std::fmt;
 use std::fs;
@@ -33,7 +34,8 @@ use anyhow::{anyhow, Result};
 use arc_swap::access::Access;
 use dns_lookup::lookup_host;
 use flexi_logger::{
-    colored_opt_format, writers::LogWriter, Age, Cleanup, Criterion, FileSpec, Logger, Naming,
+    colored_opt_format, writers::LogWriter, Age, Cleanup, Criterion, FileSpec, LogSpecification,
+    Logger, Naming,
 };
 use log::{debug, info, warn};
 use tokio::runtime::{Builder, Runtime};
@@ -41,8 +43,8 @@ use tokio::sync::broadcast;
 
 use crate::{
     collector::{
-        flow_aggr::FlowAggrThread, quadruple_generator::QuadrupleGeneratorThread, CollectorThread,
-        MetricsType,
+        flow_aggr::FlowAggrThread, flow_correlator::FlowCorrelator,
+        quadruple_generator::QuadrupleGeneratorThread, CollectorThread, MetricsType,
     },
     collector::{
         l7_quadruple_generator::L7QuadrupleGeneratorThread, Collector, L7Collector,
@@ -59,7 +61,7 @@ use crate::{
     config::PcapStream,
     config::{
         handler::{ConfigHandler, DispatcherConfig, ModuleConfig},
-        Config, ConfigError, UserConfig,
+        Config, ConfigError, MutualTls, UserConfig,
     },
     debug::{ConstructDebugCtx, Debugger},
     dispatcher::{
@@ -67,23 +69,28 @@ use crate::{
     },
     exception::ExceptionHandler,
     flow_generator::{
-        protocol_logs::BoxAppProtoLogsData, protocol_logs::SessionAggregator, PacketSequenceParser,
-        TIME_UNIT,
+        anomaly::BoxedSecurityEvent, app_alert::BoxedAppAlertEvent,
+        protocol_logs::BoxAppProtoLogsData, protocol_logs::SessionAggregator, AnomalyDetector,
+        AppAlertEvaluator, MulticastGroupTable, NeighborTable, PacketSequenceParser, TIME_UNIT,
     },
     handler::{NpbBuilder, PacketHandlerBuilder},
     integration_collector::{
         ApplicationLog, BoxedPrometheusExtra, MetricServer, OpenTelemetry, OpenTelemetryCompressed,
-        Profile, TelegrafMetric,
+        Profile, StatsdReceiver, TelegrafMetric,
     },
     metric::document::BoxedDocument,
     monitor::Monitor,
     platform::synchronizer::Synchronizer as PlatformSynchronizer,
     policy::{Policy, PolicyGetter, PolicySetter},
+    prometheus_scraper::PrometheusScraper,
     rpc::{Session, Synchronizer, DEFAULT_TIMEOUT},
     sender::{
         npb_sender::NpbArpTable,
-        uniform_sender::{Connection, UniformSenderThread},
+        otlp_exporter::{OtlpExporter, OtlpExporterThread},
+        syslog_exporter::{SyslogExporter, SyslogExporterThread},
+        uniform_sender::{Connection, UniformSenderThread, SENDER_TLS},
     },
+    snmp_poller::SnmpPoller,
     utils::{
         cgroups::{is_kernel_available_for_cgroups, Cgroups},
         command::get_hostname,
@@ -94,9 +101,11 @@ use crate::{
         },
         guard::Guard,
         logger::{LogLevelWriter, LogWriterAdapter, RemoteLogWriter},
+        namespace_quota::NamespaceLimiter,
         npb_bandwidth_watcher::NpbBandwidthWatcher,
         stats::{self, Countable, QueueStats, RefCountable},
     },
+    xflow_collector::XflowCollector,
 };
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::{
@@ -108,7 +117,7 @@ use crate::{
 use crate::{
     platform::{
         kubernetes::{GenericPoller, Poller, SidecarPoller},
-        ApiWatcher, LibvirtXmlExtractor,
+        ApiWatcher, ConntrackWatcher, IpvsWatcher, LibvirtXmlExtractor,
     },
     utils::environment::{IN_CONTAINER, K8S_WATCH_POLICY},
 };
@@ -338,9 +347,8 @@ impl Trident {
             .to_owned();
 
         let (log_level_writer, log_level_counter) = LogLevelWriter::new();
-        let logger = Logger::try_with_env_or_str("info")
-            .unwrap()
-            .format(colored_opt_format);
+        let log_spec = LogSpecification::env_or_parse("info").unwrap();
+        let logger = Logger::with(log_spec.clone()).format(colored_opt_format);
         // check log folder permission
         let base_path = Path::new(&config.log_file).parent().unwrap();
         let write_to_file = if base_path.exists() {
@@ -394,7 +402,7 @@ impl Trident {
             logger
         };
         let logger_handle = logger.start()?;
-        config_handler.set_logger_handle(logger_handle);
+        config_handler.set_logger_handle(logger_handle, &log_spec);
 
         let config = &config_handler.static_config;
         // Use controller ip to replace analyzer ip before obtaining configuration
@@ -513,6 +521,21 @@ impl Trident {
                 .static_config
                 .controller_cert_file_prefix
                 .clone(),
+            MutualTls {
+                enabled: !config_handler
+                    .static_config
+                    .controller_cert_file_prefix
+                    .is_empty(),
+                cert_file: config_handler
+                    .static_config
+                    .controller_client_cert_file
+                    .clone(),
+                key_file: config_handler
+                    .static_config
+                    .controller_client_key_file
+                    .clone(),
+                ..Default::default()
+            },
             config_handler.static_config.controller_ips.clone(),
             exception_handler.clone(),
             &stats_collector,
@@ -1033,6 +1056,57 @@ fn get_listener_links(
     }
 }
 
+// Number of dispatchers to build for the link, as many as its RX queues if
+// inputs.cbpf.tunning.parallel_dispatcher_enabled is set in mirror or analyzer mode.
+// Each dispatcher generates flows by itself, so both directions of a flow must reach the
+// same dispatcher:
+// - af_packet sockets of the dispatchers join the fanout group of the process and are
+//   chosen by a symmetric flow hash. The group only accepts sockets bound to the same
+//   interface, so af_packet capture requires a single capture interface.
+// - AF_XDP sockets are bound to one RX queue each, which requires symmetric RSS of the NIC
+//   confirmed by inputs.cbpf.special_network.af_xdp.symmetric_rss.
+fn parallel_dispatcher_count(
+    user_config: &UserConfig,
+    capture_mode: PacketCaptureType,
+    link: &Link,
+    link_count: usize,
+) -> u32 {
+    let af_xdp = &user_config.inputs.cbpf.special_network.af_xdp;
+    if capture_mode == PacketCaptureType::Local
+        || !user_config.inputs.cbpf.tunning.parallel_dispatcher_enabled
+        || (!af_xdp.enabled && link_count != 1)
+    {
+        return 1;
+    }
+    if af_xdp.enabled && !af_xdp.symmetric_rss {
+        warn!(
+            "parallel dispatchers of {} are disabled, AF_XDP requires symmetric RSS confirmed by inputs.cbpf.special_network.af_xdp.symmetric_rss",
+            link.name
+        );
+        return 1;
+    }
+    #[cfg(target_os = "linux")]
+    match dispatcher::recv_engine::rx_queue_count(&link.name) {
+        Ok(count) => {
+            // AF_XDP only binds the first queue_count queues
+            if af_xdp.enabled && af_xdp.queue_count > 0 {
+                count.min(af_xdp.queue_count as u32).max(1)
+            } else {
+                count.max(1)
+            }
+        }
+        Err(e) => {
+            warn!("get rx queue count of {} failed: {}", link.name, e);
+            1
+        }
+    }
+    #[cfg(any(target_os = "windows", target_os = "android"))]
+    {
+        let _ = link;
+        1
+    }
+}
+
 fn component_on_config_change(
     config_handler: &ConfigHandler,
     components: &mut AgentComponents,
@@ -1081,6 +1155,7 @@ fn component_on_config_change(
                 match build_dispatchers(
                     components.last_dispatcher_component_id + 1,
                     links,
+                    None,
                     components.stats_collector.clone(),
                     config_handler,
                     components.debugger.clone_queue(),
@@ -1090,6 +1165,13 @@ fn component_on_config_change(
                     components.npb_arp_table.clone(),
                     components.rx_leaky_bucket.clone(),
                     components.policy_getter,
+                    components.multicast_groups.clone(),
+                    components.neighbor_table.clone(),
+                    components.anomaly_detector.clone(),
+                    components.app_alert_evaluator.clone(),
+                    components.namespace_limiter.clone(),
+                    components.flow_correlator.clone(),
+                    components.otlp_exporter.clone(),
                     components.exception_handler.clone(),
                     components.bpf_options.clone(),
                     components.packet_sequence_uniform_output.clone(),
@@ -1146,7 +1228,16 @@ fn component_on_config_change(
                     .vhost_user
                     .vhost_socket_path
                     .is_empty()
-                    || conf.dpdk_enabled)
+                    || conf.dpdk_enabled
+                    || !config_handler
+                        .candidate_config
+                        .user_config
+                        .inputs
+                        .cbpf
+                        .special_network
+                        .pcap_replay
+                        .files
+                        .is_empty())
             {
                 return;
             }
@@ -1165,10 +1256,19 @@ fn component_on_config_change(
 
             // By comparing current_interfaces and components.tap_interfaces, we can determine which
             // dispatcher_components should be closed and which dispatcher_components should be built
+            let user_config = &config_handler.candidate_config.user_config;
             let interfaces_to_build: Vec<_> = current_interfaces
                 .iter()
                 .filter(|i| !components.tap_interfaces.contains(i))
-                .cloned()
+                .flat_map(|i| {
+                    let count = parallel_dispatcher_count(
+                        user_config,
+                        conf.capture_mode,
+                        i,
+                        current_interfaces.len(),
+                    );
+                    (0..count).map(move |q| (i.clone(), if count > 1 { Some(q) } else { None }))
+                })
                 .collect();
 
             components.dispatcher_components.retain_mut(|d| {
@@ -1184,11 +1284,12 @@ fn component_on_config_change(
                 .policy_setter
                 .reset_queue_size(id + interfaces_to_build.len() + 1);
             let debugger_queue = components.debugger.clone_queue();
-            for i in interfaces_to_build {
+            for (i, parallel_index) in interfaces_to_build {
                 id += 1;
                 match build_dispatchers(
                     id,
                     vec![i],
+                    parallel_index,
                     components.stats_collector.clone(),
                     config_handler,
                     debugger_queue.clone(),
@@ -1198,6 +1299,13 @@ fn component_on_config_change(
                     components.npb_arp_table.clone(),
                     components.rx_leaky_bucket.clone(),
                     components.policy_getter,
+                    components.multicast_groups.clone(),
+                    components.neighbor_table.clone(),
+                    components.anomaly_detector.clone(),
+                    components.app_alert_evaluator.clone(),
+                    components.namespace_limiter.clone(),
+                    components.flow_correlator.clone(),
+                    components.otlp_exporter.clone(),
                     components.exception_handler.clone(),
                     components.bpf_options.clone(),
                     components.packet_sequence_uniform_output.clone(),
@@ -1553,12 +1661,20 @@ pub struct AgentComponents {
     pub kubernetes_poller: Arc<GenericPoller>,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub socket_synchronizer: SocketSynchronizer,
+    #[cfg(target_os = "linux")]
+    pub conntrack_watcher: Option<ConntrackWatcher>,
+    #[cfg(target_os = "linux")]
+    pub ipvs_watcher: Option<IpvsWatcher>,
     pub debugger: Debugger,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub ebpf_dispatcher_component: Option<EbpfDispatcherComponent>,
     pub running: AtomicBool,
     pub stats_collector: Arc<stats::Collector>,
     pub metrics_server_component: MetricsServerComponent,
+    pub prometheus_scraper: PrometheusScraper,
+    pub statsd_receiver: StatsdReceiver,
+    pub snmp_poller: SnmpPoller,
+    pub xflow_collector: XflowCollector,
     pub otel_uniform_sender: UniformSenderThread<OpenTelemetry>,
     pub prometheus_uniform_sender: UniformSenderThread<BoxedPrometheusExtra>,
     pub telegraf_uniform_sender: UniformSenderThread<TelegrafMetric>,
@@ -1579,6 +1695,17 @@ pub struct AgentComponents {
     pub pcap_batch_uniform_sender: UniformSenderThread<BoxedPcapBatch>,
     pub policy_setter: PolicySetter,
     pub policy_getter: PolicyGetter,
+    pub multicast_groups: Arc<MulticastGroupTable>,
+    pub neighbor_table: Arc<NeighborTable>,
+    pub anomaly_detector: Arc<AnomalyDetector>,
+    pub security_event_uniform_sender: UniformSenderThread<BoxedSecurityEvent>,
+    pub syslog_exporter_thread: SyslogExporterThread,
+    pub app_alert_evaluator: Arc<AppAlertEvaluator>,
+    pub app_alert_event_uniform_sender: UniformSenderThread<BoxedAppAlertEvent>,
+    pub namespace_limiter: Arc<NamespaceLimiter>,
+    pub flow_correlator: Arc<FlowCorrelator>,
+    pub otlp_exporter: Arc<OtlpExporter>,
+    pub otlp_exporter_thread: OtlpExporterThread,
     pub npb_bandwidth_watcher: Box<Arc<NpbBandwidthWatcher>>,
     pub npb_arp_table: Arc<NpbArpTable>,
     pub is_ce_version: bool, // Determine whether the current version is a ce version, CE-AGENT always set pcap-assembler disabled
@@ -1642,6 +1769,8 @@ impl AgentComponents {
         config_handler: &ConfigHandler,
         queue_debugger: &QueueDebugger,
         synchronizer: &Arc<Synchronizer>,
+        namespace_limiter: Arc<NamespaceLimiter>,
+        flow_correlator: Arc<FlowCorrelator>,
         agent_mode: RunningMode,
     ) -> CollectorThread {
         let config = &config_handler.candidate_config.user_config;
@@ -1678,6 +1807,8 @@ impl AgentComponents {
                 config_handler.collector(),
                 Duration::from_secs(flowgen_tolerable_delay),
                 synchronizer.ntp_diff(),
+                namespace_limiter,
+                flow_correlator,
             );
             l4_flow_aggr_outer = Some(l4_flow_aggr);
             stats_collector.register_countable(
@@ -1958,13 +2089,25 @@ impl AgentComponents {
             #[cfg(target_os = "linux")]
             &netns::NsFile::Root,
         );
+        // number of links whose entries are expanded to parallel dispatchers
+        let mut parallel_link_count = 0;
         if interfaces_and_ns.is_empty() && !links.is_empty() {
             if candidate_config.capture_mode != PacketCaptureType::Local {
+                parallel_link_count = links.len();
                 for l in links {
-                    #[cfg(target_os = "linux")]
-                    interfaces_and_ns.push((vec![l], netns::NsFile::Root));
-                    #[cfg(any(target_os = "windows", target_os = "android"))]
-                    interfaces_and_ns.push(vec![l]);
+                    // one entry for each parallel dispatcher, see parallel_dispatcher_count
+                    let count = parallel_dispatcher_count(
+                        user_config,
+                        candidate_config.capture_mode,
+                        &l,
+                        parallel_link_count,
+                    );
+                    for _ in 0..count {
+                        #[cfg(target_os = "linux")]
+                        interfaces_and_ns.push((vec![l.clone()], netns::NsFile::Root));
+                        #[cfg(any(target_os = "windows", target_os = "android"))]
+                        interfaces_and_ns.push(vec![l.clone()]);
+                    }
                 }
             } else {
                 for _ in 0..local_dispatcher_count {
@@ -1984,7 +2127,14 @@ impl AgentComponents {
                 .vhost_user
                 .vhost_socket_path
                 .is_empty()
-                || candidate_config.dispatcher.dpdk_enabled)
+                || candidate_config.dispatcher.dpdk_enabled
+                || !user_config
+                    .inputs
+                    .cbpf
+                    .special_network
+                    .pcap_replay
+                    .files
+                    .is_empty())
         {
             interfaces_and_ns = vec![(vec![], netns::NsFile::Root)];
         }
@@ -2037,6 +2187,8 @@ impl AgentComponents {
         );
         synchronizer.add_flow_acl_listener(Box::new(policy_setter));
         policy_setter.set_memory_limit(max_memory);
+        let multicast_groups = Arc::new(MulticastGroupTable::new());
+        let neighbor_table = Arc::new(NeighborTable::new(exception_handler.clone()));
 
         // TODO: collector enabled
         // TODO: packet handler builders
@@ -2068,6 +2220,9 @@ impl AgentComponents {
             status: synchronizer.status.clone(),
             config: config_handler.debug(),
             policy_setter,
+            multicast_groups: multicast_groups.clone(),
+            neighbor_table: neighbor_table.clone(),
+            stats_collector: stats_collector.clone(),
         };
         let debugger = Debugger::new(context);
         let queue_debugger = debugger.clone_queue();
@@ -2126,6 +2281,42 @@ impl AgentComponents {
             process_listener.clone(),
         );
 
+        #[cfg(target_os = "linux")]
+        let conntrack_watcher = {
+            let nat_correlation = &user_config.processors.flow_log.conntrack.nat_correlation;
+            if nat_correlation.enabled {
+                let watcher = ConntrackWatcher::new(nat_correlation.max_entries);
+                stats_collector.register_countable(
+                    &stats::NoTagModule("conntrack-nat"),
+                    Countable::Ref(
+                        Arc::downgrade(&watcher.get_counter_handle()) as Weak<dyn RefCountable>
+                    ),
+                );
+                Some(watcher)
+            } else {
+                None
+            }
+        };
+        #[cfg(target_os = "linux")]
+        let ipvs_watcher = {
+            let nat_correlation = &user_config.processors.flow_log.conntrack.nat_correlation;
+            if nat_correlation.enabled && nat_correlation.ipvs_enabled {
+                let watcher = IpvsWatcher::new(
+                    nat_correlation.max_entries,
+                    nat_correlation.ipvs_sync_interval,
+                );
+                stats_collector.register_countable(
+                    &stats::NoTagModule("ipvs-nat"),
+                    Countable::Ref(
+                        Arc::downgrade(&watcher.get_counter_handle()) as Weak<dyn RefCountable>
+                    ),
+                );
+                Some(watcher)
+            } else {
+                None
+            }
+        };
+
         let rx_leaky_bucket = Arc::new(LeakyBucket::new(match candidate_config.capture_mode {
             PacketCaptureType::Analyzer => None,
             _ => Some(
@@ -2329,7 +2520,131 @@ impl AgentComponents {
             bpf_syntax_str,
         }));
 
+        let security_event_queue_name = "1-security-event-to-sender";
+        let (security_event_sender, security_event_receiver, counter) = queue::bounded_with_debug(
+            user_config.outputs.flow_log.tunning.collector_queue_size,
+            security_event_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            &QueueStats {
+                module: security_event_queue_name,
+                ..Default::default()
+            },
+            Countable::Owned(Box::new(counter)),
+        );
+        let security_event_uniform_sender = UniformSenderThread::new(
+            security_event_queue_name,
+            Arc::new(security_event_receiver),
+            config_handler.sender(),
+            stats_collector.clone(),
+            exception_handler.clone(),
+            None,
+        );
+        let syslog_exporter_queue_name = "1-security-event-to-syslog-exporter";
+        let (syslog_exporter_sender, syslog_exporter_receiver, counter) = queue::bounded_with_debug(
+            user_config.outputs.flow_log.tunning.collector_queue_size,
+            syslog_exporter_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            &QueueStats {
+                module: syslog_exporter_queue_name,
+                ..Default::default()
+            },
+            Countable::Owned(Box::new(counter)),
+        );
+        let syslog_exporter = Arc::new(SyslogExporter::new(syslog_exporter_sender));
+        stats_collector.register_countable(
+            &stats::NoTagModule("syslog_exporter"),
+            Countable::Ref(
+                Arc::downgrade(&syslog_exporter.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+        let syslog_exporter_thread = SyslogExporterThread::new(
+            syslog_exporter_receiver,
+            config_handler.sender(),
+            &syslog_exporter,
+        );
+        let anomaly_detector = Arc::new(AnomalyDetector::new(
+            security_event_sender,
+            Some(syslog_exporter),
+        ));
+        stats_collector.register_countable(
+            &stats::NoTagModule("flow-anomaly"),
+            Countable::Ref(
+                Arc::downgrade(&anomaly_detector.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+
+        let app_alert_event_queue_name = "1-app-alert-event-to-sender";
+        let (app_alert_event_sender, app_alert_event_receiver, counter) = queue::bounded_with_debug(
+            user_config.outputs.flow_log.tunning.collector_queue_size,
+            app_alert_event_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            &QueueStats {
+                module: app_alert_event_queue_name,
+                ..Default::default()
+            },
+            Countable::Owned(Box::new(counter)),
+        );
+        let app_alert_event_uniform_sender = UniformSenderThread::new(
+            app_alert_event_queue_name,
+            Arc::new(app_alert_event_receiver),
+            config_handler.sender(),
+            stats_collector.clone(),
+            exception_handler.clone(),
+            None,
+        );
+        let app_alert_evaluator = Arc::new(AppAlertEvaluator::new(app_alert_event_sender));
+        stats_collector.register_countable(
+            &stats::NoTagModule("app-alert"),
+            Countable::Ref(
+                Arc::downgrade(&app_alert_evaluator.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+        let namespace_limiter = Arc::new(NamespaceLimiter::new(stats_collector.clone()));
+        synchronizer.add_flow_acl_listener(Box::new(namespace_limiter.clone()));
+        let flow_correlator = Arc::new(FlowCorrelator::default());
+        stats_collector.register_countable(
+            &stats::NoTagModule("flow_correlator"),
+            Countable::Ref(
+                Arc::downgrade(&flow_correlator.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+
+        let otlp_exporter_queue_name = "1-l7-session-to-otlp-exporter";
+        let (otlp_exporter_sender, otlp_exporter_receiver, counter) = queue::bounded_with_debug(
+            user_config.outputs.otlp_exporter.queue_size,
+            otlp_exporter_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            &QueueStats {
+                module: otlp_exporter_queue_name,
+                ..Default::default()
+            },
+            Countable::Owned(Box::new(counter)),
+        );
+        let otlp_exporter = Arc::new(OtlpExporter::new(otlp_exporter_sender));
+        stats_collector.register_countable(
+            &stats::NoTagModule("otlp_exporter"),
+            Countable::Ref(
+                Arc::downgrade(&otlp_exporter.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+        let otlp_exporter_thread = OtlpExporterThread::new(
+            otlp_exporter_receiver,
+            config_handler.log_parser(),
+            runtime.clone(),
+            &otlp_exporter,
+        );
+
         let mut tap_interfaces = vec![];
+        let mut parallel_indexes = HashMap::new();
+        let capture_mode = candidate_config.capture_mode;
         for (i, entry) in interfaces_and_ns.into_iter().enumerate() {
             #[cfg(target_os = "linux")]
             let links = entry.0;
@@ -2338,9 +2653,27 @@ impl AgentComponents {
             tap_interfaces.extend(links.clone());
             #[cfg(target_os = "linux")]
             let netns = entry.1;
+            // entries of the same link are its parallel dispatchers in order
+            let parallel_index = match links.as_slice() {
+                [link]
+                    if parallel_link_count > 0
+                        && parallel_dispatcher_count(
+                            user_config,
+                            capture_mode,
+                            link,
+                            parallel_link_count,
+                        ) > 1 =>
+                {
+                    let next = parallel_indexes.entry(link.name.clone()).or_insert(0u32);
+                    *next += 1;
+                    Some(*next - 1)
+                }
+                _ => None,
+            };
             let dispatcher_component = build_dispatchers(
                 i,
                 links,
+                parallel_index,
                 stats_collector.clone(),
                 config_handler,
                 queue_debugger.clone(),
@@ -2350,6 +2683,13 @@ impl AgentComponents {
                 npb_arp_table.clone(),
                 rx_leaky_bucket.clone(),
                 policy_getter,
+                multicast_groups.clone(),
+                neighbor_table.clone(),
+                anomaly_detector.clone(),
+                app_alert_evaluator.clone(),
+                namespace_limiter.clone(),
+                flow_correlator.clone(),
+                otlp_exporter.clone(),
                 exception_handler.clone(),
                 bpf_options.clone(),
                 packet_sequence_uniform_output.clone(),
@@ -2371,6 +2711,7 @@ impl AgentComponents {
             dispatcher_components.push(dispatcher_component);
         }
         tap_interfaces.sort();
+        tap_interfaces.dedup();
         let proc_event_queue_name = "1-proc-event-to-sender";
         #[allow(unused)]
         let (proc_event_sender, proc_event_receiver, counter) = queue::bounded_with_debug(
@@ -2512,6 +2853,8 @@ impl AgentComponents {
                 ebpf_dispatcher_id as u32,
                 config_handler.log_parser(),
                 synchronizer.ntp_diff(),
+                namespace_limiter.clone(),
+                otlp_exporter.clone(),
             );
             stats_collector.register_countable(
                 &stats::SingleTagModule("l7_session_aggr", "index", ebpf_dispatcher_id),
@@ -2538,6 +2881,7 @@ impl AgentComponents {
                 policy_getter,
                 log_sender,
                 l7_stats_sender,
+                metrics_sender.clone(),
                 proc_event_sender,
                 profile_sender.clone(),
                 &queue_debugger,
@@ -2647,6 +2991,50 @@ impl AgentComponents {
             exception_handler.clone(),
             Some(prometheus_telegraf_shared_connection.clone()),
         );
+        let prometheus_scraper = PrometheusScraper::new(
+            config_handler.metric_server(),
+            prometheus_sender.clone(),
+            synchronizer.ntp_diff(),
+        );
+        stats_collector.register_countable(
+            &stats::NoTagModule("prometheus_scraper"),
+            Countable::Ref(
+                Arc::downgrade(&prometheus_scraper.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+        let statsd_receiver = StatsdReceiver::new(
+            config_handler.metric_server(),
+            prometheus_sender.clone(),
+            synchronizer.ntp_diff(),
+        );
+        stats_collector.register_countable(
+            &stats::NoTagModule("integration_statsd"),
+            Countable::Ref(
+                Arc::downgrade(&statsd_receiver.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+        let snmp_poller = SnmpPoller::new(
+            config_handler.snmp(),
+            metrics_sender.clone(),
+            synchronizer.ntp_diff(),
+        );
+        stats_collector.register_countable(
+            &stats::NoTagModule("snmp_poller"),
+            Countable::Ref(
+                Arc::downgrade(&snmp_poller.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
+        let xflow_collector = XflowCollector::new(
+            config_handler.xflow(),
+            metrics_sender.clone(),
+            synchronizer.ntp_diff(),
+        );
+        stats_collector.register_countable(
+            &stats::NoTagModule("xflow_collector"),
+            Countable::Ref(
+                Arc::downgrade(&xflow_collector.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
 
         let telegraf_queue_name = "1-telegraf-to-sender";
         let (telegraf_sender, telegraf_receiver, counter) = queue::bounded_with_debug(
@@ -2723,6 +3111,8 @@ impl AgentComponents {
                 .prometheus_extra_labels
                 .clone(),
             candidate_config.log_parser.clone(),
+            user_config.inputs.integration.otlp.clone(),
+            candidate_config.metric_server.tls.clone(),
             user_config
                 .inputs
                 .integration
@@ -2749,6 +3139,20 @@ impl AgentComponents {
             &stats::NoTagModule("integration_collector"),
             Countable::Owned(Box::new(external_metrics_counter)),
         );
+        stats_collector.register_countable(
+            &stats::SingleTagModule("tls", "channel", "integration"),
+            Countable::Ref(
+                Arc::downgrade(&external_metrics_server.get_tls_counter_handle())
+                    as Weak<dyn RefCountable>,
+            ),
+        );
+
+        stats_collector.register_countable(
+            &stats::SingleTagModule("tls", "channel", "sender"),
+            Countable::Ref(
+                Arc::downgrade(&SENDER_TLS.get_counter_handle()) as Weak<dyn RefCountable>
+            ),
+        );
 
         let sender_config = config_handler.sender().load();
         let (npb_bandwidth_watcher, npb_bandwidth_watcher_counter) = NpbBandwidthWatcher::new(
@@ -2777,6 +3181,10 @@ impl AgentComponents {
             kubernetes_poller,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             socket_synchronizer,
+            #[cfg(target_os = "linux")]
+            conntrack_watcher,
+            #[cfg(target_os = "linux")]
+            ipvs_watcher,
             debugger,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             ebpf_dispatcher_component,
@@ -2786,6 +3194,10 @@ impl AgentComponents {
                 external_metrics_server,
                 l7_collector,
             },
+            prometheus_scraper,
+            statsd_receiver,
+            snmp_poller,
+            xflow_collector,
             exception_handler,
             max_memory,
             otel_uniform_sender,
@@ -2809,6 +3221,17 @@ impl AgentComponents {
             agent_mode,
             policy_setter,
             policy_getter,
+            multicast_groups,
+            neighbor_table,
+            anomaly_detector,
+            security_event_uniform_sender,
+            syslog_exporter_thread,
+            app_alert_evaluator,
+            app_alert_event_uniform_sender,
+            namespace_limiter,
+            flow_correlator,
+            otlp_exporter,
+            otlp_exporter_thread,
             npb_bandwidth_watcher,
             npb_arp_table,
             runtime,
@@ -2838,6 +3261,14 @@ impl AgentComponents {
         #[cfg(any(target_os = "linux", target_os = "android"))]
         self.socket_synchronizer.start();
         #[cfg(target_os = "linux")]
+        if let Some(watcher) = self.conntrack_watcher.as_ref() {
+            watcher.start();
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(watcher) = self.ipvs_watcher.as_ref() {
+            watcher.start();
+        }
+        #[cfg(target_os = "linux")]
         if crate::utils::environment::is_tt_pod(self.config.agent_type) {
             self.kubernetes_poller.start();
         }
@@ -2875,6 +3306,9 @@ impl AgentComponents {
         if let Some(ebpf_dispatcher_component) = self.ebpf_dispatcher_component.as_mut() {
             ebpf_dispatcher_component.start();
         }
+        // exports to the configured collector, which also works in standalone mode
+        self.otlp_exporter_thread.start();
+        self.syslog_exporter_thread.start();
         if matches!(self.agent_mode, RunningMode::Managed) {
             self.otel_uniform_sender.start();
             self.compressed_otel_uniform_sender.start();
@@ -2882,11 +3316,17 @@ impl AgentComponents {
             self.telegraf_uniform_sender.start();
             self.profile_uniform_sender.start();
             self.proc_event_uniform_sender.start();
+            self.security_event_uniform_sender.start();
+            self.app_alert_event_uniform_sender.start();
             self.application_log_uniform_sender.start();
             self.skywalking_uniform_sender.start();
             if self.config.metric_server.enabled {
                 self.metrics_server_component.start();
             }
+            self.prometheus_scraper.start();
+            self.statsd_receiver.start();
+            self.snmp_poller.start();
+            self.xflow_collector.start();
             self.pcap_batch_uniform_sender.start();
         }
 
@@ -2912,6 +3352,14 @@ impl AgentComponents {
         #[cfg(any(target_os = "linux", target_os = "android"))]
         self.socket_synchronizer.stop();
         #[cfg(target_os = "linux")]
+        if let Some(watcher) = self.conntrack_watcher.as_ref() {
+            watcher.stop();
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(watcher) = self.ipvs_watcher.as_ref() {
+            watcher.stop();
+        }
+        #[cfg(target_os = "linux")]
         self.kubernetes_poller.stop();
 
         if let Some(h) = self.l4_flow_uniform_sender.notify_stop() {
@@ -2932,6 +3380,14 @@ impl AgentComponents {
         }
 
         self.metrics_server_component.stop();
+        if let Some(h) = self.prometheus_scraper.notify_stop() {
+            join_handles.push(h);
+        }
+        join_handles.extend(self.statsd_receiver.notify_stop());
+        if let Some(h) = self.snmp_poller.notify_stop() {
+            join_handles.push(h);
+        }
+        join_handles.extend(self.xflow_collector.notify_stop());
         if let Some(h) = self.otel_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
@@ -2950,6 +3406,18 @@ impl AgentComponents {
         if let Some(h) = self.proc_event_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
+        if let Some(h) = self.security_event_uniform_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.app_alert_event_uniform_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.otlp_exporter_thread.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.syslog_exporter_thread.notify_stop() {
+            join_handles.push(h);
+        }
         if let Some(h) = self.pcap_batch_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
@@ -3077,6 +3545,7 @@ fn build_pcap_assembler(
         config.total_buffer_size,
         config.buffer_size_per_flow,
         config.flush_interval,
+        config.format,
         pcap_batch_sender,
         mini_packet_receiver,
         ntp_diff,
@@ -3098,6 +3567,7 @@ fn build_pcap_assembler(
 fn build_dispatchers(
     id: usize,
     links: Vec<Link>,
+    parallel_index: Option<u32>,
     stats_collector: Arc<stats::Collector>,
     config_handler: &ConfigHandler,
     queue_debugger: Arc<QueueDebugger>,
@@ -3107,6 +3577,13 @@ fn build_dispatchers(
     npb_arp_table: Arc<NpbArpTable>,
     rx_leaky_bucket: Arc<LeakyBucket>,
     policy_getter: PolicyGetter,
+    multicast_groups: Arc<MulticastGroupTable>,
+    neighbor_table: Arc<NeighborTable>,
+    anomaly_detector: Arc<AnomalyDetector>,
+    app_alert_evaluator: Arc<AppAlertEvaluator>,
+    namespace_limiter: Arc<NamespaceLimiter>,
+    flow_correlator: Arc<FlowCorrelator>,
+    otlp_exporter: Arc<OtlpExporter>,
     exception_handler: ExceptionHandler,
     bpf_options: Arc<Mutex<BpfOptions>>,
     packet_sequence_uniform_output: DebugSender<BoxedPacketSequenceBlock>,
@@ -3189,6 +3666,8 @@ fn build_dispatchers(
         id as u32,
         config_handler.log_parser(),
         synchronizer.ntp_diff(),
+        namespace_limiter.clone(),
+        otlp_exporter,
     );
     stats_collector.register_countable(
         &stats::SingleTagModule("l7_session_aggr", "index", id),
@@ -3278,6 +3757,12 @@ fn build_dispatchers(
             dpdk_enabled: dispatcher_config.dpdk_enabled,
             dispatcher_queue: dispatcher_config.dispatcher_queue,
             packet_fanout_mode: user_config.inputs.cbpf.af_packet.tunning.packet_fanout_mode,
+            hardware_timestamp: user_config
+                .inputs
+                .cbpf
+                .af_packet
+                .tunning
+                .hardware_timestamp_enabled,
             vhost_socket_path: user_config
                 .inputs
                 .cbpf
@@ -3285,6 +3770,11 @@ fn build_dispatchers(
                 .vhost_user
                 .vhost_socket_path
                 .clone(),
+            dpdk: user_config.inputs.cbpf.special_network.dpdk.clone(),
+            af_xdp: user_config.inputs.cbpf.special_network.af_xdp,
+            pcap_replay: user_config.inputs.cbpf.special_network.pcap_replay.clone(),
+            parallel_index,
+            packet_sampling: user_config.inputs.cbpf.preprocess.packet_sampling,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             cpu_set: dispatcher_config.cpu_set,
             ..Default::default()
@@ -3307,7 +3797,14 @@ fn build_dispatchers(
                 .vlan_pcp_in_physical_mirror_traffic,
         )
         .tap_typer(tap_typer.clone())
-        .analyzer_dedup_disabled(user_config.inputs.cbpf.tunning.dispatcher_queue_enabled)
+        .analyzer_dedup_disabled(
+            user_config
+                .inputs
+                .cbpf
+                .physical_mirror
+                .packet_dedup_disabled,
+        )
+        .packet_dedup_window(user_config.inputs.cbpf.physical_mirror.packet_dedup_window)
         .flow_output_queue(flow_sender.clone())
         .l7_stats_output_queue(l7_stats_sender.clone())
         .log_output_queue(log_sender.clone())
@@ -3317,6 +3814,10 @@ fn build_dispatchers(
         .log_parse_config(config_handler.log_parser())
         .collector_config(config_handler.collector())
         .policy_getter(policy_getter)
+        .multicast_groups(multicast_groups)
+        .neighbor_table(neighbor_table)
+        .anomaly_detector(anomaly_detector)
+        .app_alert_evaluator(app_alert_evaluator)
         .exception_handler(exception_handler.clone())
         .ntp_diff(synchronizer.ntp_diff())
         .src_interface(
@@ -3374,6 +3875,8 @@ fn build_dispatchers(
         config_handler,
         &queue_debugger,
         &synchronizer,
+        namespace_limiter,
+        flow_correlator,
         agent_mode,
     );
 
@@ -231,6 +231,12 @@ pub fn get_k8s_local_node_ip() -> Option<IpAddr> {
     None
 }
 
+pub fn get_k8s_node_name() -> Option<String> {
+    env::var(K8S_NODE_NAME_FOR_DEEPFLOW)
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
 pub fn running_in_container() -> bool {
     // Environment variable "IN_CONTAINTER" is set in dockerfile
     env::var_os(IN_CONTAINER).is_some()
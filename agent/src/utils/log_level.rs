@@ -0,0 +1,308 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use flexi_logger::{LogSpecification, LoggerHandle};
+use lazy_static::lazy_static;
+use log::{info, Level, LevelFilter};
+
+// module paths not starting with these crate names are relative to deepflow_agent
+const AGENT_CRATE: &str = "deepflow_agent";
+const WORKSPACE_CRATES: [&str; 4] = [AGENT_CRATE, "public", "enterprise_utils", "trace_utils"];
+
+pub const DEFAULT_OVERRIDE_DURATION: Duration = Duration::from_secs(300);
+
+lazy_static! {
+    pub static ref LOG_LEVELS: LogLevels = LogLevels::default();
+}
+
+// Log levels in the format of `debug` or `flow_generator::protocol_logs::mysql=debug,sender=trace`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogLevelSpec {
+    pub default: Option<LevelFilter>,
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogLevelSpec {
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.modules.is_empty()
+    }
+}
+
+fn parse_level(s: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(s.trim()).map_err(|_| format!("invalid log level `{}`", s.trim()))
+}
+
+impl FromStr for LogLevelSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut spec = Self::default();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((module, level)) = entry.split_once('=') else {
+                spec.default = Some(parse_level(entry)?);
+                continue;
+            };
+            let module = module.trim();
+            if module.is_empty()
+                || !module
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+            {
+                return Err(format!("invalid module path `{}`", module));
+            }
+            let crate_name = module.split("::").next().unwrap_or_default();
+            let module = if WORKSPACE_CRATES.contains(&crate_name) {
+                module.to_owned()
+            } else {
+                format!("{}::{}", AGENT_CRATE, module)
+            };
+            spec.modules.push((module, parse_level(level)?));
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for LogLevelSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut entries = vec![];
+        if let Some(level) = self.default {
+            entries.push(level.as_str().to_lowercase());
+        }
+        for (module, level) in self.modules.iter() {
+            entries.push(format!("{}={}", module, level.as_str().to_lowercase()));
+        }
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+struct Temporary {
+    spec: LogLevelSpec,
+    expire_at: SystemTime,
+}
+
+struct State {
+    handle: Option<LoggerHandle>,
+    level: Level,
+    modules: Vec<(String, LevelFilter)>,
+    temporary: Option<Temporary>,
+    generation: u64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            handle: None,
+            level: Level::Info,
+            modules: vec![],
+            temporary: None,
+            generation: 0,
+        }
+    }
+}
+
+impl State {
+    fn seed(&mut self, spec: &LogSpecification) {
+        self.modules.clear();
+        for filter in spec.module_filters() {
+            match filter.module_name.as_ref() {
+                Some(module) => self.modules.push((module.clone(), filter.level_filter)),
+                None => {
+                    if let Some(level) = filter.level_filter.to_level() {
+                        self.level = level;
+                    }
+                }
+            }
+        }
+    }
+
+    fn effective(&self) -> LogLevelSpec {
+        let mut spec = LogLevelSpec {
+            default: Some(self.level.to_level_filter()),
+            modules: self.modules.clone(),
+        };
+        if let Some(t) = self.temporary.as_ref() {
+            if t.spec.default.is_some() {
+                spec.default = t.spec.default;
+            }
+            for (module, level) in t.spec.modules.iter() {
+                spec.modules.retain(|(m, _)| m != module);
+                spec.modules.push((module.clone(), *level));
+            }
+        }
+        spec
+    }
+
+    fn apply(&self) -> Result<(), String> {
+        let Some(handle) = self.handle.as_ref() else {
+            return Err("logger handle not set".to_owned());
+        };
+        let spec = self.effective();
+        let mut builder = LogSpecification::builder();
+        builder.default(spec.default.unwrap_or(LevelFilter::Info));
+        for (module, level) in spec.modules.iter() {
+            builder.module(module, *level);
+        }
+        handle.set_new_spec(builder.build());
+        Ok(())
+    }
+}
+
+// Log levels of the agent.
+//
+// Levels configured by the controller can be overridden temporarily by remote commands or the
+// local debug api, and the overrides revert automatically when they expire.
+#[derive(Default)]
+pub struct LogLevels(Mutex<State>);
+
+impl LogLevels {
+    // `spec` is the initial specification of the logger from RUST_LOG or the default,
+    // which is in effect until the controller configures the levels
+    pub fn set_logger_handle(&self, handle: LoggerHandle, spec: &LogSpecification) {
+        let mut state = self.0.lock().unwrap();
+        state.handle.replace(handle);
+        state.seed(spec);
+    }
+
+    // Sets levels configured by the controller, `modules` are entries of `module=level`
+    pub fn configure(&self, level: Level, modules: &[String]) -> Result<(), String> {
+        let spec = modules.join(",").parse::<LogLevelSpec>()?;
+        if spec.default.is_some() {
+            return Err(format!("module path missing in {:?}", modules));
+        }
+        let mut state = self.0.lock().unwrap();
+        let (old_level, old_modules) = (state.level, state.modules.clone());
+        state.level = level;
+        state.modules = spec.modules;
+        if let Err(e) = state.apply() {
+            state.level = old_level;
+            state.modules = old_modules;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Overrides log levels for `duration`, or reverts the override if `spec` is empty
+    pub fn set_temporary(
+        &'static self,
+        spec: LogLevelSpec,
+        duration: Duration,
+    ) -> Result<(), String> {
+        if spec.is_empty() {
+            self.revert(None);
+            return Ok(());
+        }
+        let mut state = self.0.lock().unwrap();
+        let old = state.temporary.replace(Temporary {
+            spec: spec.clone(),
+            expire_at: SystemTime::now() + duration,
+        });
+        if let Err(e) = state.apply() {
+            state.temporary = old;
+            return Err(e);
+        }
+        state.generation += 1;
+        let generation = state.generation;
+        drop(state);
+
+        info!("log level set to {} for {:?}", spec, duration);
+        thread::Builder::new()
+            .name("log-level-revert".to_owned())
+            .spawn(move || {
+                thread::sleep(duration);
+                self.revert(Some(generation));
+            })
+            .map(|_| ())
+            .map_err(|e| format!("failed to schedule log level revert: {}", e))
+    }
+
+    // Reverts the override, only if it is still the one of `generation` if specified
+    fn revert(&self, generation: Option<u64>) {
+        let mut state = self.0.lock().unwrap();
+        if state.temporary.is_none() || generation.map(|g| g != state.generation).unwrap_or(false) {
+            return;
+        }
+        state.temporary = None;
+        let _ = state.apply();
+        info!("log level reverted to {}", state.effective());
+    }
+
+    // Effective levels and the expire time of the override if any
+    pub fn current(&self) -> (LogLevelSpec, Option<SystemTime>) {
+        let state = self.0.lock().unwrap();
+        (
+            state.effective(),
+            state.temporary.as_ref().map(|t| t.expire_at),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        let spec = "warn, flow_generator::protocol_logs::mysql=debug,public::queue=TRACE"
+            .parse::<LogLevelSpec>()
+            .unwrap();
+        assert_eq!(spec.default, Some(LevelFilter::Warn));
+        assert_eq!(
+            spec.modules,
+            vec![
+                (
+                    "deepflow_agent::flow_generator::protocol_logs::mysql".to_owned(),
+                    LevelFilter::Debug
+                ),
+                ("public::queue".to_owned(), LevelFilter::Trace),
+            ]
+        );
+        assert_eq!(
+            spec.to_string(),
+            "warn,deepflow_agent::flow_generator::protocol_logs::mysql=debug,public::queue=trace"
+        );
+
+        assert!("sender=verbose".parse::<LogLevelSpec>().is_err());
+        assert!("sender/uniform=debug".parse::<LogLevelSpec>().is_err());
+        assert!("".parse::<LogLevelSpec>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn seed_from_logger_spec() {
+        let mut state = State::default();
+        state.seed(&LogSpecification::parse("warn,deepflow_agent::sender=debug").unwrap());
+        assert_eq!(
+            state.effective().to_string(),
+            "warn,deepflow_agent::sender=debug"
+        );
+
+        state.temporary = Some(Temporary {
+            spec: "sender=trace".parse().unwrap(),
+            expire_at: SystemTime::now(),
+        });
+        assert_eq!(
+            state.effective().to_string(),
+            "warn,deepflow_agent::sender=trace"
+        );
+    }
+}
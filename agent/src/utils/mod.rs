@@ -19,12 +19,15 @@ pub(crate) mod command;
 pub(crate) mod environment;
 pub(crate) mod guard;
 pub(crate) mod hasher;
+pub(crate) mod log_level;
 pub(crate) mod logger;
 pub(crate) mod lru;
+pub(crate) mod namespace_quota;
 pub(crate) mod npb_bandwidth_watcher;
 pub(crate) mod possible_host;
 pub(crate) mod process;
 pub mod stats;
+pub(crate) mod tls;
 
 #[cfg(target_os = "linux")]
 pub(crate) mod pid_file;
@@ -0,0 +1,327 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering::Relaxed},
+    Arc, RwLock, Weak,
+};
+use std::time::Duration;
+
+use log::info;
+
+use crate::common::platform_data::PlatformData;
+use crate::common::policy::{Acl, Cidr, IpGroupData, PeerConnection};
+use crate::common::{FlowAclListener, FlowAclListenerId};
+use crate::config::{NamespacePriorityClass, NamespaceQuotas};
+use crate::utils::stats::{self, Countable, Counter, CounterType, CounterValue, RefCountable};
+use public::proto::agent::AgentType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Accepted,
+    // logs of system namespaces, which should not be dropped by throttles
+    Prioritized,
+    Dropped,
+}
+
+#[derive(Default)]
+pub struct NamespaceQuotaCounter {
+    logs: AtomicU64,
+    log_quota_drop: AtomicU64,
+    throughput_quota_drop: AtomicU64,
+}
+
+impl RefCountable for NamespaceQuotaCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "logs",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.logs.swap(0, Relaxed)),
+            ),
+            (
+                "log-quota-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.log_quota_drop.swap(0, Relaxed)),
+            ),
+            (
+                "throughput-quota-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.throughput_quota_drop.swap(0, Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Usage of a namespace in the current second, shared by all the threads sending logs
+#[derive(Default)]
+struct NamespaceUsage {
+    second: AtomicU64,
+    logs: AtomicU64,
+    bytes: AtomicU64,
+    counter: Arc<NamespaceQuotaCounter>,
+}
+
+#[derive(Default)]
+struct NamespaceTable {
+    // (l3_epc_id, ip) of pods to pod_ns_id
+    ips: HashMap<(i32, IpAddr), u32>,
+    usages: HashMap<u32, NamespaceUsage>,
+}
+
+// Per kubernetes namespace quotas of the flow logs and request logs, so that a noisy namespace
+// does not take up the throttles of the others.
+//
+// The log is charged to the namespace of the client, or the server if the client is not a pod.
+// Logs of other endpoints are not limited.
+pub struct NamespaceLimiter {
+    table: RwLock<NamespaceTable>,
+    stats_collector: Arc<stats::Collector>,
+}
+
+impl NamespaceLimiter {
+    pub fn new(stats_collector: Arc<stats::Collector>) -> Self {
+        Self {
+            table: RwLock::new(NamespaceTable::default()),
+            stats_collector,
+        }
+    }
+
+    fn update_namespaces(&self, platform_data: &Vec<Arc<PlatformData>>) {
+        let mut ips = HashMap::new();
+        for iface in platform_data.iter().filter(|p| p.pod_ns_id > 0) {
+            for ip in iface.ips.iter() {
+                ips.insert((iface.epc_id, ip.raw_ip), iface.pod_ns_id);
+            }
+        }
+
+        let mut table = self.table.write().unwrap();
+        let mut usages = HashMap::new();
+        for pod_ns_id in ips.values() {
+            if usages.contains_key(pod_ns_id) {
+                continue;
+            }
+            let usage = match table.usages.remove(pod_ns_id) {
+                Some(usage) => usage,
+                None => {
+                    let usage = NamespaceUsage::default();
+                    self.stats_collector.register_countable(
+                        &stats::SingleTagModule("namespace_quota", "pod_ns_id", *pod_ns_id),
+                        Countable::Ref(Arc::downgrade(&usage.counter) as Weak<dyn RefCountable>),
+                    );
+                    usage
+                }
+            };
+            usages.insert(*pod_ns_id, usage);
+        }
+        info!(
+            "Namespace limiter updated with {} namespaces of {} pod ips.",
+            usages.len(),
+            ips.len()
+        );
+        // counters of the removed namespaces are deregistered when dropped
+        table.ips = ips;
+        table.usages = usages;
+    }
+
+    // peers are (l3_epc_id, ip) of the client and the server, bytes are the traffic bytes of the
+    // log, which is 0 for request logs
+    pub fn acquire(
+        &self,
+        config: &NamespaceQuotas,
+        peers: [(i32, IpAddr); 2],
+        bytes: u64,
+        now: Duration,
+    ) -> Admission {
+        if !config.enabled {
+            return Admission::Accepted;
+        }
+        let table = self.table.read().unwrap();
+        let Some(pod_ns_id) = peers.iter().find_map(|p| table.ips.get(p)) else {
+            return Admission::Accepted;
+        };
+        let Some(usage) = table.usages.get(pod_ns_id) else {
+            return Admission::Accepted;
+        };
+
+        let now = now.as_secs();
+        let second = usage.second.load(Relaxed);
+        if now > second
+            && usage
+                .second
+                .compare_exchange(second, now, Relaxed, Relaxed)
+                .is_ok()
+        {
+            usage.logs.store(0, Relaxed);
+            usage.bytes.store(0, Relaxed);
+        }
+
+        let class = config.priority_class(*pod_ns_id);
+        let quota = config.quota(class);
+        let logs = usage.logs.fetch_add(1, Relaxed) + 1;
+        if quota.max_logs > 0 && logs > quota.max_logs {
+            usage.counter.log_quota_drop.fetch_add(1, Relaxed);
+            return Admission::Dropped;
+        }
+        let bytes = usage.bytes.fetch_add(bytes, Relaxed) + bytes;
+        // max_throughput is in bits per second
+        if quota.max_throughput > 0 && bytes << 3 > quota.max_throughput {
+            usage.counter.throughput_quota_drop.fetch_add(1, Relaxed);
+            return Admission::Dropped;
+        }
+        usage.counter.logs.fetch_add(1, Relaxed);
+
+        if class == NamespacePriorityClass::System {
+            Admission::Prioritized
+        } else {
+            Admission::Accepted
+        }
+    }
+}
+
+impl FlowAclListener for Arc<NamespaceLimiter> {
+    fn flow_acl_change(
+        &mut self,
+        _agent_type: AgentType,
+        _local_epc: i32,
+        _ip_groups: &Vec<Arc<IpGroupData>>,
+        platform_data: &Vec<Arc<PlatformData>>,
+        _peers: &Vec<Arc<PeerConnection>>,
+        _cidrs: &Vec<Arc<Cidr>>,
+        _acls: &Vec<Arc<Acl>>,
+    ) -> Result<(), String> {
+        self.update_namespaces(platform_data);
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        u16::from(FlowAclListenerId::NamespaceLimiter) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::platform_data::IpSubnet;
+    use crate::config::{NamespacePriority, NamespaceQuota};
+
+    fn pod(epc_id: i32, ip: &str, pod_ns_id: u32) -> Arc<PlatformData> {
+        Arc::new(PlatformData {
+            ips: vec![IpSubnet {
+                raw_ip: ip.parse().unwrap(),
+                ..Default::default()
+            }],
+            epc_id,
+            pod_ns_id,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn namespace_quotas() {
+        let limiter = NamespaceLimiter::new(Arc::new(stats::Collector::new("", Arc::default())));
+        limiter.update_namespaces(&vec![
+            pod(1, "10.0.0.1", 10),
+            pod(1, "10.0.0.2", 20),
+            pod(1, "10.0.0.3", 30),
+        ]);
+        let config = NamespaceQuotas {
+            enabled: true,
+            system: NamespaceQuota::default(),
+            normal: NamespaceQuota {
+                max_logs: 2,
+                max_throughput: 0,
+            },
+            low: NamespaceQuota {
+                max_logs: 0,
+                max_throughput: 800,
+            },
+            namespaces: vec![
+                NamespacePriority {
+                    pod_ns_id: 20,
+                    priority_class: NamespacePriorityClass::System,
+                },
+                NamespacePriority {
+                    pod_ns_id: 30,
+                    priority_class: NamespacePriorityClass::Low,
+                },
+            ],
+        };
+        let peers = |client: &str, server: &str| {
+            [
+                (1, client.parse::<IpAddr>().unwrap()),
+                (1, server.parse::<IpAddr>().unwrap()),
+            ]
+        };
+        let now = Duration::from_secs(100);
+
+        // charged to the client namespace
+        for _ in 0..2 {
+            assert_eq!(
+                limiter.acquire(&config, peers("10.0.0.1", "10.0.0.2"), 0, now),
+                Admission::Accepted
+            );
+        }
+        assert_eq!(
+            limiter.acquire(&config, peers("10.0.0.1", "10.0.0.2"), 0, now),
+            Admission::Dropped
+        );
+        // charged to the server namespace if the client is not a pod
+        for _ in 0..10 {
+            assert_eq!(
+                limiter.acquire(&config, peers("192.168.0.1", "10.0.0.2"), 0, now),
+                Admission::Prioritized
+            );
+        }
+        assert_eq!(
+            limiter.acquire(&config, peers("192.168.0.1", "192.168.0.2"), 0, now),
+            Admission::Accepted
+        );
+        // throughput quota
+        assert_eq!(
+            limiter.acquire(&config, peers("10.0.0.3", "192.168.0.2"), 100, now),
+            Admission::Accepted
+        );
+        assert_eq!(
+            limiter.acquire(&config, peers("10.0.0.3", "192.168.0.2"), 1, now),
+            Admission::Dropped
+        );
+        // quotas are reset every second
+        let now = Duration::from_secs(101);
+        assert_eq!(
+            limiter.acquire(&config, peers("10.0.0.1", "10.0.0.2"), 0, now),
+            Admission::Accepted
+        );
+        assert_eq!(
+            limiter.acquire(&config, peers("10.0.0.3", "192.168.0.2"), 100, now),
+            Admission::Accepted
+        );
+
+        let disabled = NamespaceQuotas {
+            enabled: false,
+            ..config
+        };
+        for _ in 0..10 {
+            assert_eq!(
+                limiter.acquire(&disabled, peers("10.0.0.1", "10.0.0.2"), 0, now),
+                Admission::Accepted
+            );
+        }
+    }
+}
@@ -24,7 +24,7 @@ use std::{
     process,
     sync::{
         atomic::{AtomicBool, Ordering::Relaxed},
-        Arc, Mutex, RwLock,
+        Arc, Condvar, Mutex, RwLock,
     },
     thread::{self, JoinHandle},
     time::Duration,
@@ -271,6 +271,12 @@ struct ProcessNode {
 
 pub struct ProcessListener {
     features: Arc<RwLock<HashMap<String, ProcessNode>>>,
+    // notified when the processes of the feature change
+    notifiers: Arc<RwLock<HashMap<String, Arc<Condvar>>>>,
+    // set by process exec/exit events to scan processes in the next second
+    rescan_requested: Arc<AtomicBool>,
+    // processes are scanned less frequently when exec/exit events are received
+    event_driven: Arc<AtomicBool>,
     running: Arc<AtomicBool>,
     proc_root: Arc<RwLock<String>>,
     user: Arc<RwLock<String>>,
@@ -281,6 +287,7 @@ pub struct ProcessListener {
 
 impl ProcessListener {
     const INTERVAL: usize = 10;
+    const EVENT_DRIVEN_INTERVAL: usize = 60;
 
     pub fn new(
         process_matcher: &Vec<ProcessMatcher>,
@@ -290,6 +297,9 @@ impl ProcessListener {
     ) -> Self {
         let listener = Self {
             features: Arc::new(RwLock::new(HashMap::new())),
+            notifiers: Arc::new(RwLock::new(HashMap::new())),
+            rescan_requested: Arc::new(AtomicBool::new(false)),
+            event_driven: Arc::new(AtomicBool::new(false)),
             running: Arc::new(AtomicBool::new(false)),
             thread_handle: Mutex::new(None),
             proc_root: Arc::new(RwLock::new(proc_root)),
@@ -360,6 +370,23 @@ impl ProcessListener {
         }
     }
 
+    pub fn set_notifier(&self, feature: &str, notifier: Arc<Condvar>) {
+        self.notifiers
+            .write()
+            .unwrap()
+            .insert(feature.to_string(), notifier);
+    }
+
+    // Scans processes in the next second instead of waiting for the interval
+    pub fn request_rescan(&self) {
+        self.rescan_requested.store(true, Relaxed);
+    }
+
+    // With process exec/exit events, the periodic scanning only covers the events missed
+    pub fn set_event_driven(&self, enabled: bool) {
+        self.event_driven.store(enabled, Relaxed);
+    }
+
     pub fn stop(&mut self) {
         self.running.store(false, Relaxed);
 
@@ -373,10 +400,11 @@ impl ProcessListener {
         features: &Arc<RwLock<HashMap<String, ProcessNode>>>,
         user: &String,
         command: &[String],
-    ) {
+    ) -> Vec<String> {
+        let mut changed = vec![];
         let mut features = features.write().unwrap();
         let Ok(processes) = all_processes_with_root(proc_root) else {
-            return;
+            return changed;
         };
         let tags_map = match get_os_app_tag_by_exec(user, command) {
             Ok(tags) => tags,
@@ -426,8 +454,10 @@ impl ProcessListener {
                 value.callback.as_ref().unwrap()(&pids, &process_datas);
                 value.pids = pids;
                 value.process_datas = process_datas;
+                changed.push(key.clone());
             }
         }
+        changed
     }
 
     pub fn start(&self) {
@@ -436,6 +466,9 @@ impl ProcessListener {
         }
         info!("Startting process listener ...");
         let features = self.features.clone();
+        let notifiers = self.notifiers.clone();
+        let rescan_requested = self.rescan_requested.clone();
+        let event_driven = self.event_driven.clone();
         let running = self.running.clone();
         let proc_root = self.proc_root.clone();
         let user = self.user.clone();
@@ -450,7 +483,12 @@ impl ProcessListener {
                     while running.load(Relaxed) {
                         thread::sleep(Duration::from_secs(1));
                         count += 1;
-                        if count < Self::INTERVAL {
+                        let interval = if event_driven.load(Relaxed) {
+                            Self::EVENT_DRIVEN_INTERVAL
+                        } else {
+                            Self::INTERVAL
+                        };
+                        if !rescan_requested.swap(false, Relaxed) && count < interval {
                             continue;
                         }
                         count = 0;
@@ -458,7 +496,14 @@ impl ProcessListener {
                         let user = user.read().unwrap().clone();
                         let command = command.read().unwrap().clone();
 
-                        Self::process(proc.as_str(), &features, &user, command.as_slice());
+                        let changed =
+                            Self::process(proc.as_str(), &features, &user, command.as_slice());
+                        let notifiers = notifiers.read().unwrap();
+                        for feature in changed.iter() {
+                            if let Some(notifier) = notifiers.get(feature) {
+                                notifier.notify_one();
+                            }
+                        }
                     }
                 })
                 .unwrap(),
@@ -52,6 +52,8 @@ struct Source {
     tags: Vec<(&'static str, String)>,
     // countdown to next metrics collection
     skip: i64,
+    // last collected batch kept for local inspection
+    latest: Option<Arc<Batch>>,
 }
 
 impl PartialEq for Source {
@@ -78,6 +80,22 @@ pub struct Batch {
 }
 
 impl Batch {
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    pub fn tags(&self) -> &[(&'static str, String)] {
+        &self.tags
+    }
+
+    pub fn points(&self) -> &[Counter] {
+        &self.points
+    }
+
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
     fn to_stats(&self) -> stats::Stats {
         let mut tag_names = vec![];
         let mut tag_values = vec![];
@@ -252,6 +270,7 @@ impl Collector {
             countable,
             tags: vec![],
             skip: 0,
+            latest: None,
         };
         for tag in module.tags() {
             match tag {
@@ -323,6 +342,17 @@ impl Collector {
         }
     }
 
+    // Batches last collected from all registered countables.
+    // Counters are reset on collection, so these are the values reported in the latest interval.
+    pub fn snapshot(&self) -> Vec<Arc<Batch>> {
+        self.sources
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|s| s.latest.clone())
+            .collect()
+    }
+
     pub fn register_pre_hook(&self, hook: Box<dyn FnMut() + Send>) {
         self.pre_hooks.lock().unwrap().push(hook);
     }
@@ -426,7 +456,8 @@ impl Collector {
                                         points,
                                         timestamp: now,
                                     });
-                                    if let Err(_) = sender.send(ArcBatch(batch.clone())) {
+                                    source.latest = Some(batch.clone());
+                                    if let Err(_) = sender.send(ArcBatch(batch)) {
                                         debug!(
                                         "stats to send queue failed because queue have terminated"
                                     );
@@ -0,0 +1,613 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Mutual TLS of the controller, ingester and integration channels
+//
+// The configs of rustls are built lazily and cached. The certificate files are checked every 10
+// seconds, when any of them is modified or the config is updated, the cache is dropped and the
+// version increases, so that long-lived connections can be reestablished with new certificates.
+
+use std::fs;
+use std::future::Future;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpStream};
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::{stream, StreamExt};
+use hyper::{
+    server::{
+        accept::{self, Accept},
+        conn::{AddrIncoming, AddrStream},
+    },
+    service::Service,
+    Uri,
+};
+use log::{debug, info, warn};
+use rustls::{
+    server::AllowAnyAuthenticatedClient, Certificate, ClientConfig, ClientConnection, PrivateKey,
+    RootCertStore, ServerConfig, ServerName, StreamOwned,
+};
+use rustls_pemfile::Item;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+
+use crate::config::MutualTls;
+use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
+
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PENDING_HANDSHAKES: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("read {0} failed: {1}")]
+    Io(String, io::Error),
+    #[error("{0} is not configured")]
+    Missing(&'static str),
+    #[error("invalid server name {0}")]
+    InvalidServerName(String),
+    #[error("handshake failed: {0}")]
+    Handshake(io::Error),
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+}
+
+#[derive(Debug, Default)]
+pub struct TlsCounter {
+    handshakes: AtomicU64,
+    handshake_failed: AtomicU64,
+    cert_loads: AtomicU64,
+    cert_load_failed: AtomicU64,
+}
+
+impl RefCountable for TlsCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "handshakes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.handshakes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "handshake-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.handshake_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "cert-loads",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.cert_loads.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "cert-load-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.cert_load_failed.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+fn modified_times(config: &MutualTls) -> Vec<Option<SystemTime>> {
+    [&config.ca_file, &config.cert_file, &config.key_file]
+        .iter()
+        .map(|f| {
+            if f.is_empty() {
+                None
+            } else {
+                fs::metadata(f).and_then(|m| m.modified()).ok()
+            }
+        })
+        .collect()
+}
+
+// The file takes precedence over the inline certificate, `None` if neither is configured
+fn read_pem(file: &str, inline: &str) -> Result<Option<Vec<u8>>, TlsError> {
+    if !file.is_empty() {
+        fs::read(file)
+            .map(Some)
+            .map_err(|e| TlsError::Io(file.to_owned(), e))
+    } else if !inline.is_empty() {
+        Ok(Some(inline.as_bytes().to_vec()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn certificates(pem: &[u8], name: &'static str) -> Result<Vec<Certificate>, TlsError> {
+    let certs = rustls_pemfile::certs(&mut &pem[..]).map_err(|e| TlsError::Io(name.into(), e))?;
+    if certs.is_empty() {
+        return Err(TlsError::Missing(name));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn private_key(pem: &[u8]) -> Result<PrivateKey, TlsError> {
+    let items =
+        rustls_pemfile::read_all(&mut &pem[..]).map_err(|e| TlsError::Io("key".into(), e))?;
+    for item in items {
+        match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => {
+                return Ok(PrivateKey(key))
+            }
+            _ => (),
+        }
+    }
+    Err(TlsError::Missing("key"))
+}
+
+fn root_certificates(pem: &[u8]) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in certificates(pem, "ca")? {
+        roots.add(&cert)?;
+    }
+    Ok(roots)
+}
+
+fn build_client_config(config: &MutualTls) -> Result<ClientConfig, TlsError> {
+    let roots = match read_pem(&config.ca_file, &config.ca)? {
+        Some(pem) => root_certificates(&pem)?,
+        None => {
+            let mut roots = RootCertStore::empty();
+            for cert in rustls_native_certs::load_native_certs()
+                .map_err(|e| TlsError::Io("native certificates".into(), e))?
+            {
+                // some of the system certificates may not be parsable
+                let _ = roots.add(&Certificate(cert.0));
+            }
+            roots
+        }
+    };
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    match (
+        read_pem(&config.cert_file, &config.cert)?,
+        read_pem(&config.key_file, &config.key)?,
+    ) {
+        (Some(cert), Some(key)) => {
+            Ok(builder.with_client_auth_cert(certificates(&cert, "cert")?, private_key(&key)?)?)
+        }
+        // the server does not authenticate the agent
+        (None, None) => Ok(builder.with_no_client_auth()),
+        (Some(_), None) => Err(TlsError::Missing("key")),
+        (None, Some(_)) => Err(TlsError::Missing("cert")),
+    }
+}
+
+fn build_server_config(config: &MutualTls) -> Result<ServerConfig, TlsError> {
+    let Some(ca) = read_pem(&config.ca_file, &config.ca)? else {
+        return Err(TlsError::Missing("ca"));
+    };
+    let Some(cert) = read_pem(&config.cert_file, &config.cert)? else {
+        return Err(TlsError::Missing("cert"));
+    };
+    let Some(key) = read_pem(&config.key_file, &config.key)? else {
+        return Err(TlsError::Missing("key"));
+    };
+    let mut server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(
+            AllowAnyAuthenticatedClient::new(root_certificates(&ca)?).boxed(),
+        )
+        .with_single_cert(certificates(&cert, "cert")?, private_key(&key)?)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+struct State {
+    config: MutualTls,
+    version: u64,
+    modified: Vec<Option<SystemTime>>,
+    last_check: Instant,
+    client: Option<Arc<ClientConfig>>,
+    server: Option<Arc<ServerConfig>>,
+}
+
+impl State {
+    fn reset(&mut self) {
+        self.version += 1;
+        self.modified = modified_times(&self.config);
+        self.last_check = Instant::now();
+        self.client = None;
+        self.server = None;
+    }
+
+    fn check_files(&mut self, channel: &str) {
+        if !self.config.enabled || self.last_check.elapsed() < FILE_CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+        if modified_times(&self.config) != self.modified {
+            info!("{} tls certificates modified, reloading", channel);
+            self.reset();
+        }
+    }
+}
+
+pub struct TlsContext {
+    channel: &'static str,
+    state: Mutex<State>,
+    counter: Arc<TlsCounter>,
+}
+
+impl TlsContext {
+    pub fn new(channel: &'static str, config: MutualTls) -> Self {
+        Self {
+            channel,
+            state: Mutex::new(State {
+                modified: modified_times(&config),
+                config,
+                version: 0,
+                last_check: Instant::now(),
+                client: None,
+                server: None,
+            }),
+            counter: Default::default(),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<TlsCounter> {
+        self.counter.clone()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.state.lock().unwrap().config.enabled
+    }
+
+    pub fn update(&self, config: &MutualTls) {
+        let mut state = self.state.lock().unwrap();
+        if state.config != *config {
+            info!("{} tls config changed to {:?}", self.channel, config);
+            state.config = config.clone();
+            state.reset();
+        }
+    }
+
+    // Increases when the certificates or the config change
+    pub fn version(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.check_files(self.channel);
+        state.version
+    }
+
+    fn load<T>(
+        &self,
+        get: impl Fn(&mut State) -> &mut Option<Arc<T>>,
+        build: impl Fn(&MutualTls) -> Result<T, TlsError>,
+    ) -> Result<Option<Arc<T>>, TlsError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.config.enabled {
+            return Ok(None);
+        }
+        state.check_files(self.channel);
+        if let Some(config) = get(&mut *state) {
+            return Ok(Some(config.clone()));
+        }
+        match build(&state.config) {
+            Ok(config) => {
+                self.counter.cert_loads.fetch_add(1, Ordering::Relaxed);
+                let config = Arc::new(config);
+                get(&mut *state).replace(config.clone());
+                Ok(Some(config))
+            }
+            Err(e) => {
+                self.counter
+                    .cert_load_failed
+                    .fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn client_config(&self) -> Result<Option<Arc<ClientConfig>>, TlsError> {
+        self.load(|s| &mut s.client, build_client_config)
+    }
+
+    pub fn server_config(&self) -> Result<Option<Arc<ServerConfig>>, TlsError> {
+        self.load(|s| &mut s.server, build_server_config)
+    }
+
+    fn server_name(&self, host: &str) -> Result<ServerName, TlsError> {
+        let state = self.state.lock().unwrap();
+        let name = if state.config.server_name.is_empty() {
+            host
+        } else {
+            &state.config.server_name
+        };
+        ServerName::try_from(name).map_err(|_| TlsError::InvalidServerName(name.to_owned()))
+    }
+
+    fn handshake_result<T>(&self, result: io::Result<T>) -> io::Result<T> {
+        match result.as_ref() {
+            Ok(_) => self.counter.handshakes.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self
+                .counter
+                .handshake_failed
+                .fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    // Handshakes on the connected stream, the stream is returned as is if tls is disabled
+    pub fn connect(&self, mut tcp_stream: TcpStream, host: &str) -> Result<Stream, TlsError> {
+        let Some(config) = self.client_config()? else {
+            return Ok(Stream::Plain(tcp_stream));
+        };
+        let mut conn = ClientConnection::new(config, self.server_name(host)?)?;
+        tcp_stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .map_err(TlsError::Handshake)?;
+        let mut result = Ok(());
+        while conn.is_handshaking() {
+            if let Err(e) = conn.complete_io(&mut tcp_stream) {
+                result = Err(e);
+                break;
+            }
+        }
+        self.handshake_result(result).map_err(TlsError::Handshake)?;
+        Ok(Stream::Tls(Box::new(StreamOwned::new(conn, tcp_stream))))
+    }
+
+    // Accepts a connection of the integration listener, the stream is returned as is if tls is
+    // disabled
+    pub async fn accept(&self, stream: AddrStream) -> io::Result<ServerStream> {
+        let config = match self.server_config() {
+            Ok(Some(config)) => config,
+            Ok(None) => return Ok(ServerStream::Plain(stream)),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+        };
+        let result =
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, TlsAcceptor::from(config).accept(stream))
+                .await
+            {
+                Ok(Ok(stream)) => Ok(ServerStream::Tls(Box::new(stream))),
+                // probes of the listening port close the connection without sending anything
+                Ok(Err(e)) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(e),
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "tls handshake timeout",
+                )),
+            };
+        self.handshake_result(result)
+    }
+}
+
+pub enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(s) => s.shutdown(Shutdown::Both),
+            Self::Tls(s) => {
+                s.conn.send_close_notify();
+                let _ = s.conn.complete_io(&mut s.sock);
+                s.sock.shutdown(Shutdown::Both)
+            }
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(s) => s.write(buf),
+            Self::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(s) => s.flush(),
+            Self::Tls(s) => s.flush(),
+        }
+    }
+}
+
+pub enum ServerStream {
+    Plain(AddrStream),
+    Tls(Box<server::TlsStream<AddrStream>>),
+}
+
+impl ServerStream {
+    pub fn remote_addr(&self) -> std::net::SocketAddr {
+        match self {
+            Self::Plain(s) => s.remote_addr(),
+            Self::Tls(s) => s.get_ref().0.remote_addr(),
+        }
+    }
+}
+
+impl AsyncRead for ServerStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            Self::Tls(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ServerStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            Self::Tls(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(s) => Pin::new(s).poll_flush(cx),
+            Self::Tls(s) => Pin::new(s.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            Self::Tls(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+// Handshakes of the incoming connections are done concurrently, failed connections are dropped
+// instead of stopping the server
+pub fn incoming(
+    context: Arc<TlsContext>,
+    mut incoming: AddrIncoming,
+) -> impl Accept<Conn = ServerStream, Error = io::Error> {
+    let streams = stream::poll_fn(move |cx| Pin::new(&mut incoming).poll_accept(cx))
+        .map(move |stream| {
+            let context = context.clone();
+            async move { context.accept(stream?).await }
+        })
+        .buffer_unordered(MAX_PENDING_HANDSHAKES)
+        .filter_map(|result| async move {
+            match result {
+                Ok(stream) => Some(Ok(stream)),
+                Err(e) => {
+                    debug!("integration collector accept failed: {}", e);
+                    None
+                }
+            }
+        });
+    accept::from_stream(streams)
+}
+
+// Connector of the grpc channel to the controller
+#[derive(Clone)]
+pub struct GrpcConnector {
+    context: Arc<TlsContext>,
+    config: Arc<ClientConfig>,
+    server_name: ServerName,
+}
+
+impl GrpcConnector {
+    pub fn new(context: Arc<TlsContext>, host: &str) -> Result<Self, TlsError> {
+        let Some(config) = context.client_config()? else {
+            return Err(TlsError::Missing("tls"));
+        };
+        let mut config = (*config).clone();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Ok(Self {
+            server_name: context.server_name(host)?,
+            config: Arc::new(config),
+            context,
+        })
+    }
+}
+
+impl Service<Uri> for GrpcConnector {
+    type Response = client::TlsStream<tokio::net::TcpStream>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.clone();
+        Box::pin(async move {
+            let address = match (uri.host(), uri.port_u16()) {
+                (Some(host), Some(port)) => format!("{}:{}", host, port),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid uri {}", uri),
+                    ))
+                }
+            };
+            let tcp_stream = tokio::net::TcpStream::connect(address).await?;
+            let result = TlsConnector::from(connector.config)
+                .connect(connector.server_name, tcp_stream)
+                .await;
+            if let Err(e) = result.as_ref() {
+                warn!("controller tls handshake failed: {}", e);
+            }
+            connector.context.handshake_result(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload() {
+        let dir = std::env::temp_dir().join(format!("tls-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ca_file = dir.join("ca.crt");
+        fs::write(&ca_file, "").unwrap();
+
+        let mut config = MutualTls {
+            enabled: true,
+            ca_file: ca_file.display().to_string(),
+            ..Default::default()
+        };
+        let context = TlsContext::new("test", config.clone());
+        assert!(matches!(
+            context.client_config(),
+            Err(TlsError::Missing("ca"))
+        ));
+        assert!(matches!(
+            context.server_config(),
+            Err(TlsError::Missing("cert"))
+        ));
+        assert_eq!(context.version(), 0);
+
+        // files are checked after the interval
+        fs::remove_file(&ca_file).unwrap();
+        assert_eq!(context.version(), 0);
+        context.state.lock().unwrap().last_check -= FILE_CHECK_INTERVAL;
+        assert_eq!(context.version(), 1);
+
+        context.update(&config);
+        assert_eq!(context.version(), 1);
+        config.enabled = false;
+        context.update(&config);
+        assert_eq!(context.version(), 2);
+        assert!(matches!(context.client_config(), Ok(None)));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert!(matches!(
+            context.connect(tcp_stream, "127.0.0.1"),
+            Ok(Stream::Plain(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,575 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod netflow;
+mod sflow;
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::{
+    atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    Arc, Condvar, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use arc_swap::access::Access;
+use log::{debug, info, warn};
+use thiserror::Error;
+
+use crate::common::{flow::SignalSource, TapPort};
+use crate::config::handler::XflowAccess;
+use crate::metric::{
+    document::{BoxedDocument, Code, Document, DocumentFlag, Tagger},
+    meter::{FlowMeter, Meter, Traffic},
+};
+use crate::rpc::get_timestamp;
+use crate::utils::{
+    net::ipv6_enabled,
+    stats::{Counter, CounterType, CounterValue, RefCountable},
+};
+use public::enums::CaptureNetworkType;
+use public::queue::DebugSender;
+
+const MAX_PACKET_SIZE: usize = 65535;
+// receivers check the running state in every timeout
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+const SECONDS_PER_MINUTE: u64 = 60;
+// limits the memory of the flows aggregated in a minute
+const MAX_FLOWS: usize = 1 << 20;
+
+#[derive(Debug, Error)]
+enum XflowError {
+    #[error("truncated message")]
+    Truncated,
+    #[error("malformed {0}")]
+    Malformed(&'static str),
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u32),
+}
+
+type Result<T> = std::result::Result<T, XflowError>;
+
+// Big endian fields of sFlow and NetFlow
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(XflowError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.bytes(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn ipv4(&mut self) -> Result<Ipv4Addr> {
+        Ok(<[u8; 4]>::try_from(self.bytes(4)?).unwrap().into())
+    }
+
+    fn ipv6(&mut self) -> Result<Ipv6Addr> {
+        Ok(<[u8; 16]>::try_from(self.bytes(16)?).unwrap().into())
+    }
+}
+
+// A flow or a sampled packet reported by the exporter
+#[derive(Clone, Debug, PartialEq)]
+struct FlowRecord {
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    src_port: u16,
+    dst_port: u16,
+    protocol: u8,
+    packets: u64,
+    bytes: u64,
+    // the record stands for `sampling_rate` times of its packets and bytes
+    sampling_rate: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Source {
+    Sflow,
+    Netflow,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FlowKey {
+    timestamp: u32,
+    source: Source,
+    exporter: IpAddr,
+    client: IpAddr,
+    server: IpAddr,
+    protocol: u8,
+    server_port: u16,
+}
+
+#[derive(Default)]
+struct Aggregator {
+    flows: HashMap<FlowKey, Traffic>,
+}
+
+impl Aggregator {
+    // Returns false if the record is dropped because of too many flows
+    fn add(&mut self, source: Source, exporter: IpAddr, timestamp: u32, r: &FlowRecord) -> bool {
+        // the lower port is taken as the server port, as the ephemeral ports of the clients
+        // are usually higher
+        let reversed = r.src_port < r.dst_port;
+        let key = FlowKey {
+            timestamp,
+            source,
+            exporter,
+            client: if reversed { r.dst_ip } else { r.src_ip },
+            server: if reversed { r.src_ip } else { r.dst_ip },
+            protocol: r.protocol,
+            server_port: r.src_port.min(r.dst_port),
+        };
+        if self.flows.len() >= MAX_FLOWS && !self.flows.contains_key(&key) {
+            return false;
+        }
+        let traffic = self.flows.entry(key).or_default();
+        let rate = r.sampling_rate.max(1) as u64;
+        if reversed {
+            traffic.packet_rx += r.packets * rate;
+            traffic.byte_rx += r.bytes * rate;
+        } else {
+            traffic.packet_tx += r.packets * rate;
+            traffic.byte_tx += r.bytes * rate;
+        }
+        true
+    }
+
+    // Takes the flows of the minutes before `timestamp`
+    fn flush(&mut self, agent_id: u16, timestamp: u32) -> Vec<BoxedDocument> {
+        let mut documents = vec![];
+        self.flows.retain(|key, traffic| {
+            if key.timestamp >= timestamp {
+                return true;
+            }
+            let exporter = match key.exporter {
+                IpAddr::V4(ip) => u32::from(ip),
+                IpAddr::V6(ip) => u32::from_be_bytes(ip.octets()[12..].try_into().unwrap()),
+            };
+            let mut doc = Document::new(Meter::Flow(FlowMeter {
+                traffic: *traffic,
+                ..Default::default()
+            }));
+            doc.timestamp = key.timestamp;
+            doc.tagger = Tagger {
+                code: Code::IP_PATH
+                    | Code::L3_EPC_PATH
+                    | Code::VTAP_ID
+                    | Code::PROTOCOL
+                    | Code::SERVER_PORT
+                    | Code::TAP_TYPE
+                    | Code::TAP_PORT,
+                ip: key.client,
+                ip1: key.server,
+                is_ipv6: key.client.is_ipv6(),
+                protocol: key.protocol.into(),
+                server_port: key.server_port,
+                agent_id,
+                tap_port: match key.source {
+                    Source::Sflow => TapPort::from_sflow(exporter),
+                    Source::Netflow => TapPort::from_netflow(exporter),
+                },
+                tap_type: CaptureNetworkType::Cloud,
+                signal_source: SignalSource::XFlow,
+                // l3 epcs are left as the internet, the exporters know nothing about the vpcs
+                ..Default::default()
+            };
+            doc.flags = DocumentFlag::NONE;
+            documents.push(BoxedDocument(Box::new(doc)));
+            false
+        });
+        documents
+    }
+}
+
+#[derive(Default)]
+pub struct XflowCollectorCounter {
+    packets: AtomicU64,
+    invalid: AtomicU64,
+    // data sets of NetFlow v9 and IPFIX arrived before their templates
+    missing_templates: AtomicU64,
+    records: AtomicU64,
+    flow_drop: AtomicU64,
+    documents: AtomicU64,
+    // documents dropped because the queue is full
+    queue_drop: AtomicU64,
+}
+
+impl RefCountable for XflowCollectorCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "packets",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "invalid",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.invalid.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "missing-templates",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.missing_templates.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "records",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.records.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "flow-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.flow_drop.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "documents",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.documents.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "queue-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.queue_drop.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Receiver {
+    source: Source,
+    port: u16,
+    aggregator: Arc<Mutex<Aggregator>>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<XflowCollectorCounter>,
+    running: Arc<AtomicBool>,
+}
+
+impl Receiver {
+    fn receive(&self, decoder: &mut netflow::Decoder, packet: &[u8], exporter: IpAddr) {
+        self.counter.packets.fetch_add(1, Ordering::Relaxed);
+        let records = match self.source {
+            Source::Sflow => sflow::parse(packet),
+            Source::Netflow => decoder.decode(exporter, packet),
+        };
+        self.counter.missing_templates.fetch_add(
+            std::mem::take(&mut decoder.missing_templates),
+            Ordering::Relaxed,
+        );
+        let records = match records {
+            Ok(r) => r,
+            Err(e) => {
+                debug!("invalid {:?} packet from {}: {}", self.source, exporter, e);
+                self.counter.invalid.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed)).as_secs();
+        let timestamp = (now / SECONDS_PER_MINUTE * SECONDS_PER_MINUTE) as u32;
+        let mut aggregator = self.aggregator.lock().unwrap();
+        for record in records.iter() {
+            if aggregator.add(self.source, exporter, timestamp, record) {
+                self.counter.records.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.counter.flow_drop.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn run(&self) {
+        let addr: SocketAddr = if ipv6_enabled() {
+            (Ipv6Addr::UNSPECIFIED, self.port).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, self.port).into()
+        };
+        let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+        // templates are kept until the receiver is stopped
+        let mut decoder = netflow::Decoder::default();
+        while self.running.load(Ordering::Relaxed) {
+            let socket = match UdpSocket::bind(addr)
+                .and_then(|s| s.set_read_timeout(Some(READ_TIMEOUT)).map(|_| s))
+            {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("xflow collector failed to bind udp {}: {}", addr, e);
+                    thread::sleep(Duration::from_secs(60));
+                    continue;
+                }
+            };
+            info!(
+                "xflow collector receiving {:?} on udp {}",
+                self.source, addr
+            );
+            while self.running.load(Ordering::Relaxed) {
+                match socket.recv_from(&mut buffer) {
+                    Ok((n, peer)) => {
+                        let exporter = match peer.ip() {
+                            IpAddr::V6(ip) => {
+                                ip.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip.into())
+                            }
+                            ip => ip,
+                        };
+                        self.receive(&mut decoder, &buffer[..n], exporter);
+                    }
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    Err(e) => {
+                        warn!("xflow collector failed to receive from udp {}: {}", addr, e);
+                        break;
+                    }
+                }
+            }
+        }
+        info!(
+            "xflow collector stopped receiving {:?} on udp {}",
+            self.source, addr
+        );
+    }
+}
+
+// Receives sFlow v5, NetFlow v5, NetFlow v9 and IPFIX from network devices, and sends the
+// sampled flows as per minute flow metrics between the clients and the servers, extending the
+// coverage to the routers and switches where the agent can not run.
+//
+// The packets and bytes are multiplied by the sampling rates. Records are aggregated by the
+// exporter, the ips, the protocol and the lower port as the server port, and are sent when
+// their minute is over.
+pub struct XflowCollector {
+    config: XflowAccess,
+    output: DebugSender<BoxedDocument>,
+    ntp_diff: Arc<AtomicI64>,
+    counter: Arc<XflowCollectorCounter>,
+
+    receiving: Arc<AtomicBool>,
+    running: Arc<(Mutex<bool>, Condvar)>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl XflowCollector {
+    pub fn new(
+        config: XflowAccess,
+        output: DebugSender<BoxedDocument>,
+        ntp_diff: Arc<AtomicI64>,
+    ) -> Self {
+        Self {
+            config,
+            output,
+            ntp_diff,
+            counter: Arc::new(XflowCollectorCounter::default()),
+            receiving: Arc::new(AtomicBool::new(false)),
+            running: Arc::new((Mutex::new(false), Condvar::new())),
+            threads: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<XflowCollectorCounter> {
+        self.counter.clone()
+    }
+
+    pub fn start(&self) {
+        // ports are only changed by restarting the agent
+        let ports = {
+            let config = self.config.load();
+            config
+                .sflow_ports
+                .iter()
+                .map(|p| (Source::Sflow, *p))
+                .chain(config.netflow_ports.iter().map(|p| (Source::Netflow, *p)))
+                .filter(|(_, p)| *p > 0)
+                .collect::<Vec<_>>()
+        };
+        if ports.is_empty() {
+            return;
+        }
+        {
+            let (started, _) = &*self.running;
+            let mut started = started.lock().unwrap();
+            if *started {
+                return;
+            }
+            *started = true;
+        }
+        self.receiving.store(true, Ordering::Relaxed);
+
+        let aggregator = Arc::new(Mutex::new(Aggregator::default()));
+        let mut threads = self.threads.lock().unwrap();
+        for (source, port) in ports {
+            let receiver = Receiver {
+                source,
+                port,
+                aggregator: aggregator.clone(),
+                ntp_diff: self.ntp_diff.clone(),
+                counter: self.counter.clone(),
+                running: self.receiving.clone(),
+            };
+            threads.push(
+                thread::Builder::new()
+                    .name(format!("xflow-{}", port))
+                    .spawn(move || receiver.run())
+                    .unwrap(),
+            );
+        }
+
+        let config = self.config.clone();
+        let output = self.output.clone();
+        let ntp_diff = self.ntp_diff.clone();
+        let counter = self.counter.clone();
+        let running = self.running.clone();
+        threads.push(
+            thread::Builder::new()
+                .name("xflow-flusher".to_owned())
+                .spawn(move || loop {
+                    let (running, timer) = &*running;
+                    let mut running = running.lock().unwrap();
+                    if !*running {
+                        break;
+                    }
+                    running = timer.wait_timeout(running, FLUSH_INTERVAL).unwrap().0;
+                    let stopped = !*running;
+                    drop(running);
+
+                    // flows of the current minute are also sent before stopping
+                    let timestamp = if stopped {
+                        u32::MAX
+                    } else {
+                        let now = get_timestamp(ntp_diff.load(Ordering::Relaxed)).as_secs();
+                        (now / SECONDS_PER_MINUTE * SECONDS_PER_MINUTE) as u32
+                    };
+                    let agent_id = config.load().agent_id;
+                    let mut documents = aggregator.lock().unwrap().flush(agent_id, timestamp);
+                    if !documents.is_empty() {
+                        let count = documents.len() as u64;
+                        match output.send_all(&mut documents) {
+                            Ok(_) => counter.documents.fetch_add(count, Ordering::Relaxed),
+                            Err(e) => {
+                                debug!("xflow collector failed to send documents: {:?}", e);
+                                counter.queue_drop.fetch_add(count, Ordering::Relaxed)
+                            }
+                        };
+                    }
+                    if stopped {
+                        break;
+                    }
+                })
+                .unwrap(),
+        );
+        info!("xflow collector started");
+    }
+
+    pub fn notify_stop(&self) -> Vec<JoinHandle<()>> {
+        let (stopped, timer) = &*self.running;
+        {
+            let mut stopped = stopped.lock().unwrap();
+            if !*stopped {
+                return vec![];
+            }
+            *stopped = false;
+        }
+        self.receiving.store(false, Ordering::Relaxed);
+        timer.notify_one();
+        info!("notified stopping xflow collector");
+        self.threads.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_flows() {
+        let exporter: IpAddr = Ipv4Addr::new(10, 0, 0, 254).into();
+        let client: IpAddr = Ipv4Addr::new(10, 0, 0, 1).into();
+        let server: IpAddr = Ipv4Addr::new(10, 0, 0, 2).into();
+        let request = FlowRecord {
+            src_ip: client,
+            dst_ip: server,
+            src_port: 40000,
+            dst_port: 80,
+            protocol: 6,
+            packets: 1,
+            bytes: 100,
+            sampling_rate: 512,
+        };
+        let response = FlowRecord {
+            src_ip: server,
+            dst_ip: client,
+            src_port: 80,
+            dst_port: 40000,
+            packets: 2,
+            bytes: 3000,
+            sampling_rate: 0,
+            ..request.clone()
+        };
+
+        let mut aggregator = Aggregator::default();
+        assert!(aggregator.add(Source::Sflow, exporter, 60, &request));
+        assert!(aggregator.add(Source::Sflow, exporter, 60, &response));
+        assert!(aggregator.add(Source::Sflow, exporter, 120, &request));
+        assert!(aggregator.flush(1, 60).is_empty());
+
+        let documents = aggregator.flush(1, 120);
+        assert_eq!(documents.len(), 1);
+        let doc = &documents[0].0;
+        assert_eq!(doc.timestamp, 60);
+        assert_eq!(doc.tagger.ip, client);
+        assert_eq!(doc.tagger.ip1, server);
+        assert_eq!(doc.tagger.server_port, 80);
+        assert_eq!(doc.tagger.tap_port, TapPort::from_sflow(0x0a0000fe));
+        let Meter::Flow(meter) = &doc.meter else {
+            panic!("unexpected meter {:?}", doc.meter);
+        };
+        assert_eq!(meter.traffic.packet_tx, 512);
+        assert_eq!(meter.traffic.byte_tx, 51200);
+        assert_eq!(meter.traffic.packet_rx, 2);
+        assert_eq!(meter.traffic.byte_rx, 3000);
+        assert_eq!(aggregator.flows.len(), 1);
+    }
+}
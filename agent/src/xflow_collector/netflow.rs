@@ -0,0 +1,586 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// NetFlow v5, NetFlow v9 (RFC 3954) and IPFIX (RFC 7011)
+//
+// Records of v9 and IPFIX are decoded by the templates received from the same exporter and
+// observation domain, data sets arriving before their templates are dropped. The sampling rate
+// is taken from the fields of the record, or from the options records of the exporter.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::{FlowRecord, Reader, Result, XflowError};
+
+const VERSION_5: u16 = 5;
+const VERSION_9: u16 = 9;
+const VERSION_IPFIX: u16 = 10;
+
+const V5_RECORD_LEN: usize = 48;
+const V5_SAMPLING_INTERVAL_MASK: u16 = 0x3fff;
+
+const V9_TEMPLATE: u16 = 0;
+const V9_OPTIONS_TEMPLATE: u16 = 1;
+const IPFIX_TEMPLATE: u16 = 2;
+const IPFIX_OPTIONS_TEMPLATE: u16 = 3;
+const MIN_DATA_SET_ID: u16 = 256;
+const SET_HEADER_LEN: usize = 4;
+
+const VARIABLE_LENGTH: u16 = 65535;
+const ENTERPRISE_BIT: u16 = 0x8000;
+
+// information elements, the same in v9 and IPFIX
+const OCTET_DELTA_COUNT: u16 = 1;
+const PACKET_DELTA_COUNT: u16 = 2;
+const PROTOCOL_IDENTIFIER: u16 = 4;
+const SOURCE_TRANSPORT_PORT: u16 = 7;
+const SOURCE_IPV4_ADDRESS: u16 = 8;
+const DESTINATION_TRANSPORT_PORT: u16 = 11;
+const DESTINATION_IPV4_ADDRESS: u16 = 12;
+const SOURCE_IPV6_ADDRESS: u16 = 27;
+const DESTINATION_IPV6_ADDRESS: u16 = 28;
+const SAMPLING_INTERVAL: u16 = 34;
+const SAMPLER_RANDOM_INTERVAL: u16 = 50;
+const OCTET_TOTAL_COUNT: u16 = 85;
+const PACKET_TOTAL_COUNT: u16 = 86;
+const SAMPLING_PACKET_INTERVAL: u16 = 305;
+const SAMPLING_PACKET_SPACE: u16 = 306;
+// scope fields and enterprise specific fields
+const IGNORED: u16 = 0;
+
+// limits the templates in case of misbehaving exporters
+const MAX_TEMPLATES: usize = 65536;
+
+// exporter, source id of v9 or observation domain of IPFIX, and template id
+type TemplateKey = (IpAddr, u32, u16);
+// ids and lengths of the fields
+type Template = Vec<(u16, u16)>;
+
+#[derive(Default)]
+struct Fields {
+    src_ip: Option<IpAddr>,
+    dst_ip: Option<IpAddr>,
+    src_port: u16,
+    dst_port: u16,
+    protocol: u8,
+    packets: u64,
+    bytes: u64,
+    sampling_interval: u32,
+    packet_interval: u32,
+    packet_space: u32,
+}
+
+impl Fields {
+    fn set(&mut self, id: u16, value: &[u8]) {
+        match id {
+            OCTET_DELTA_COUNT | OCTET_TOTAL_COUNT => self.bytes = unsigned(value),
+            PACKET_DELTA_COUNT | PACKET_TOTAL_COUNT => self.packets = unsigned(value),
+            PROTOCOL_IDENTIFIER => self.protocol = unsigned(value) as u8,
+            SOURCE_TRANSPORT_PORT => self.src_port = unsigned(value) as u16,
+            DESTINATION_TRANSPORT_PORT => self.dst_port = unsigned(value) as u16,
+            SOURCE_IPV4_ADDRESS | SOURCE_IPV6_ADDRESS => self.src_ip = ip(value),
+            DESTINATION_IPV4_ADDRESS | DESTINATION_IPV6_ADDRESS => self.dst_ip = ip(value),
+            SAMPLING_INTERVAL | SAMPLER_RANDOM_INTERVAL => {
+                self.sampling_interval = unsigned(value) as u32
+            }
+            SAMPLING_PACKET_INTERVAL => self.packet_interval = unsigned(value) as u32,
+            SAMPLING_PACKET_SPACE => self.packet_space = unsigned(value) as u32,
+            _ => (),
+        }
+    }
+
+    fn sampling_rate(&self) -> Option<u32> {
+        if self.packet_interval > 0 {
+            // `packet_interval` packets are selected in every `packet_interval + packet_space`
+            Some(
+                (self.packet_interval.saturating_add(self.packet_space) / self.packet_interval)
+                    .max(1),
+            )
+        } else if self.sampling_interval > 0 {
+            Some(self.sampling_interval)
+        } else {
+            None
+        }
+    }
+}
+
+// values of reduced size encoding are shorter than their types
+fn unsigned(value: &[u8]) -> u64 {
+    let value = &value[value.len().saturating_sub(8)..];
+    value.iter().fold(0, |v, b| v << 8 | *b as u64)
+}
+
+fn ip(value: &[u8]) -> Option<IpAddr> {
+    match value.len() {
+        4 => Some(Ipv4Addr::from(<[u8; 4]>::try_from(value).unwrap()).into()),
+        16 => Some(Ipv6Addr::from(<[u8; 16]>::try_from(value).unwrap()).into()),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Decoder {
+    templates: HashMap<TemplateKey, Template>,
+    // announced by options records, by the exporter and the source id or observation domain
+    sampling_rates: HashMap<(IpAddr, u32), u32>,
+    // data sets dropped because their templates are not received yet
+    pub(super) missing_templates: u64,
+}
+
+impl Decoder {
+    pub(super) fn decode(&mut self, exporter: IpAddr, data: &[u8]) -> Result<Vec<FlowRecord>> {
+        let mut reader = Reader::new(data);
+        match reader.u16()? {
+            VERSION_5 => Self::decode_v5(&mut reader),
+            VERSION_9 => {
+                // count, uptime, seconds and sequence number
+                reader.skip(14)?;
+                let source_id = reader.u32()?;
+                self.decode_sets(exporter, source_id, false, &mut reader)
+            }
+            VERSION_IPFIX => {
+                let length = reader.u16()? as usize;
+                // export time and sequence number
+                reader.skip(8)?;
+                let domain = reader.u32()?;
+                let mut sets = Reader::new(
+                    data.get(16..length)
+                        .ok_or(XflowError::Malformed("message length"))?,
+                );
+                self.decode_sets(exporter, domain, true, &mut sets)
+            }
+            version => Err(XflowError::UnsupportedVersion(version as u32)),
+        }
+    }
+
+    fn decode_v5(reader: &mut Reader) -> Result<Vec<FlowRecord>> {
+        let count = reader.u16()?;
+        // uptime, seconds, nanoseconds, sequence number, engine type and id
+        reader.skip(18)?;
+        let sampling_rate = (reader.u16()? & V5_SAMPLING_INTERVAL_MASK).max(1) as u32;
+        let mut records = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let mut record = Reader::new(reader.bytes(V5_RECORD_LEN)?);
+            let src_ip = IpAddr::V4(record.ipv4()?);
+            let dst_ip = IpAddr::V4(record.ipv4()?);
+            // next hop, input and output
+            record.skip(8)?;
+            let packets = record.u32()? as u64;
+            let bytes = record.u32()? as u64;
+            // first and last
+            record.skip(8)?;
+            let src_port = record.u16()?;
+            let dst_port = record.u16()?;
+            // pad and tcp flags
+            record.skip(2)?;
+            records.push(FlowRecord {
+                src_ip,
+                dst_ip,
+                src_port,
+                dst_port,
+                protocol: record.u8()?,
+                packets,
+                bytes,
+                sampling_rate,
+            });
+        }
+        Ok(records)
+    }
+
+    fn decode_sets(
+        &mut self,
+        exporter: IpAddr,
+        domain: u32,
+        ipfix: bool,
+        reader: &mut Reader,
+    ) -> Result<Vec<FlowRecord>> {
+        let mut records = vec![];
+        while !reader.is_empty() {
+            let id = reader.u16()?;
+            let length = reader.u16()? as usize;
+            if length < SET_HEADER_LEN {
+                return Err(XflowError::Malformed("set length"));
+            }
+            let mut set = Reader::new(reader.bytes(length - SET_HEADER_LEN)?);
+            match id {
+                V9_TEMPLATE if !ipfix => {
+                    self.decode_templates(exporter, domain, false, &mut set)?
+                }
+                IPFIX_TEMPLATE if ipfix => {
+                    self.decode_templates(exporter, domain, true, &mut set)?
+                }
+                V9_OPTIONS_TEMPLATE if !ipfix => {
+                    self.decode_v9_options_templates(exporter, domain, &mut set)?
+                }
+                IPFIX_OPTIONS_TEMPLATE if ipfix => {
+                    self.decode_ipfix_options_templates(exporter, domain, &mut set)?
+                }
+                id if id >= MIN_DATA_SET_ID => {
+                    self.decode_records(exporter, domain, id, &mut set, &mut records)?
+                }
+                _ => (),
+            }
+        }
+        Ok(records)
+    }
+
+    fn insert_template(&mut self, key: TemplateKey, template: Template) {
+        if self.templates.len() < MAX_TEMPLATES || self.templates.contains_key(&key) {
+            self.templates.insert(key, template);
+        }
+    }
+
+    fn read_field(set: &mut Reader, ipfix: bool) -> Result<(u16, u16)> {
+        let id = set.u16()?;
+        let length = set.u16()?;
+        if ipfix && id & ENTERPRISE_BIT != 0 {
+            // enterprise number
+            set.skip(4)?;
+            return Ok((IGNORED, length));
+        }
+        Ok((id, length))
+    }
+
+    fn decode_templates(
+        &mut self,
+        exporter: IpAddr,
+        domain: u32,
+        ipfix: bool,
+        set: &mut Reader,
+    ) -> Result<()> {
+        // the rest is padding
+        while set.len() >= SET_HEADER_LEN {
+            let template_id = set.u16()?;
+            let field_count = set.u16()?;
+            if template_id < MIN_DATA_SET_ID {
+                break;
+            }
+            if field_count == 0 {
+                // template withdrawal of IPFIX
+                self.templates.remove(&(exporter, domain, template_id));
+                continue;
+            }
+            let template = (0..field_count)
+                .map(|_| Self::read_field(set, ipfix))
+                .collect::<Result<Template>>()?;
+            self.insert_template((exporter, domain, template_id), template);
+        }
+        Ok(())
+    }
+
+    fn decode_v9_options_templates(
+        &mut self,
+        exporter: IpAddr,
+        domain: u32,
+        set: &mut Reader,
+    ) -> Result<()> {
+        while set.len() >= 6 {
+            let template_id = set.u16()?;
+            let scope_length = set.u16()? as usize;
+            let option_length = set.u16()? as usize;
+            if template_id < MIN_DATA_SET_ID {
+                break;
+            }
+            // types of the scope are not information elements
+            let mut template = (0..scope_length / 4)
+                .map(|_| Self::read_field(set, false).map(|(_, len)| (IGNORED, len)))
+                .collect::<Result<Template>>()?;
+            for _ in 0..option_length / 4 {
+                template.push(Self::read_field(set, false)?);
+            }
+            self.insert_template((exporter, domain, template_id), template);
+        }
+        Ok(())
+    }
+
+    fn decode_ipfix_options_templates(
+        &mut self,
+        exporter: IpAddr,
+        domain: u32,
+        set: &mut Reader,
+    ) -> Result<()> {
+        while set.len() >= 6 {
+            let template_id = set.u16()?;
+            let field_count = set.u16()?;
+            if template_id < MIN_DATA_SET_ID {
+                break;
+            }
+            if field_count == 0 {
+                self.templates.remove(&(exporter, domain, template_id));
+                continue;
+            }
+            let scope_count = set.u16()?;
+            let template = (0..field_count)
+                .map(|i| {
+                    Self::read_field(set, true)
+                        .map(|(id, len)| (if i < scope_count { IGNORED } else { id }, len))
+                })
+                .collect::<Result<Template>>()?;
+            self.insert_template((exporter, domain, template_id), template);
+        }
+        Ok(())
+    }
+
+    fn decode_records(
+        &mut self,
+        exporter: IpAddr,
+        domain: u32,
+        template_id: u16,
+        set: &mut Reader,
+        records: &mut Vec<FlowRecord>,
+    ) -> Result<()> {
+        let Some(template) = self.templates.get(&(exporter, domain, template_id)) else {
+            self.missing_templates += 1;
+            return Ok(());
+        };
+        // variable length fields have at least one byte
+        let min_length = template
+            .iter()
+            .map(|(_, len)| {
+                if *len == VARIABLE_LENGTH {
+                    1
+                } else {
+                    *len as usize
+                }
+            })
+            .sum::<usize>();
+        if min_length == 0 {
+            return Ok(());
+        }
+
+        // the rest is padding
+        while set.len() >= min_length {
+            let mut fields = Fields::default();
+            for (id, length) in template.iter() {
+                let length = match *length {
+                    VARIABLE_LENGTH => match set.u8()? {
+                        255 => set.u16()? as usize,
+                        len => len as usize,
+                    },
+                    len => len as usize,
+                };
+                fields.set(*id, set.bytes(length)?);
+            }
+
+            let sampling_rate = fields.sampling_rate();
+            match (fields.src_ip, fields.dst_ip) {
+                (Some(src_ip), Some(dst_ip)) => records.push(FlowRecord {
+                    src_ip,
+                    dst_ip,
+                    src_port: fields.src_port,
+                    dst_port: fields.dst_port,
+                    protocol: fields.protocol,
+                    packets: fields.packets,
+                    bytes: fields.bytes,
+                    sampling_rate: sampling_rate
+                        .or_else(|| self.sampling_rates.get(&(exporter, domain)).copied())
+                        .unwrap_or(1),
+                }),
+                _ => {
+                    if let Some(rate) = sampling_rate {
+                        self.sampling_rates.insert((exporter, domain), rate);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORTER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254));
+
+    fn push_u16(buffer: &mut Vec<u8>, values: &[u16]) {
+        for v in values {
+            buffer.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    fn set(id: u16, content: &[u8]) -> Vec<u8> {
+        let mut buffer = vec![];
+        push_u16(&mut buffer, &[id, (content.len() + SET_HEADER_LEN) as u16]);
+        buffer.extend_from_slice(content);
+        buffer
+    }
+
+    #[test]
+    fn v5_records() {
+        let mut message = vec![];
+        push_u16(&mut message, &[VERSION_5, 1]);
+        message.extend_from_slice(&[0; 18]);
+        push_u16(&mut message, &[0x4000 | 100]);
+        message.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        message.extend_from_slice(&[0; 8]);
+        message.extend_from_slice(&10u32.to_be_bytes());
+        message.extend_from_slice(&1500u32.to_be_bytes());
+        message.extend_from_slice(&[0; 8]);
+        push_u16(&mut message, &[40000, 443]);
+        message.extend_from_slice(&[0, 0x18, 6, 0]);
+        message.extend_from_slice(&[0; 8]);
+
+        let records = Decoder::default().decode(EXPORTER, &message).unwrap();
+        assert_eq!(
+            records,
+            vec![FlowRecord {
+                src_ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+                dst_ip: Ipv4Addr::new(10, 0, 0, 2).into(),
+                src_port: 40000,
+                dst_port: 443,
+                protocol: 6,
+                packets: 10,
+                bytes: 1500,
+                sampling_rate: 100,
+            }]
+        );
+    }
+
+    #[test]
+    fn v9_templates() {
+        let mut template = vec![];
+        push_u16(
+            &mut template,
+            &[
+                256,
+                6,
+                SOURCE_IPV4_ADDRESS,
+                4,
+                DESTINATION_IPV4_ADDRESS,
+                4,
+                SOURCE_TRANSPORT_PORT,
+                2,
+                DESTINATION_TRANSPORT_PORT,
+                2,
+                PROTOCOL_IDENTIFIER,
+                1,
+                OCTET_DELTA_COUNT,
+                4,
+            ],
+        );
+        let mut options_template = vec![];
+        push_u16(
+            &mut options_template,
+            &[257, 4, 4, 1, 4, SAMPLING_INTERVAL, 4],
+        );
+        let mut options = vec![0, 0, 0, 0];
+        options.extend_from_slice(&1000u32.to_be_bytes());
+        // padded to 4 bytes
+        let mut data = vec![192, 168, 0, 1, 192, 168, 0, 2];
+        push_u16(&mut data, &[53, 33333]);
+        data.push(17);
+        data.extend_from_slice(&200u32.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0]);
+
+        let message = |sets: &[Vec<u8>]| {
+            let mut message = vec![];
+            push_u16(&mut message, &[VERSION_9, sets.len() as u16]);
+            message.extend_from_slice(&[0; 12]);
+            message.extend_from_slice(&7u32.to_be_bytes());
+            for s in sets {
+                message.extend_from_slice(s);
+            }
+            message
+        };
+
+        let mut decoder = Decoder::default();
+        assert!(decoder
+            .decode(EXPORTER, &message(&[set(256, &data)]))
+            .unwrap()
+            .is_empty());
+        assert_eq!(decoder.missing_templates, 1);
+        let records = decoder
+            .decode(
+                EXPORTER,
+                &message(&[
+                    set(V9_TEMPLATE, &template),
+                    set(V9_OPTIONS_TEMPLATE, &options_template),
+                    set(257, &options),
+                    set(256, &data),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![FlowRecord {
+                src_ip: Ipv4Addr::new(192, 168, 0, 1).into(),
+                dst_ip: Ipv4Addr::new(192, 168, 0, 2).into(),
+                src_port: 53,
+                dst_port: 33333,
+                protocol: 17,
+                packets: 0,
+                bytes: 200,
+                sampling_rate: 1000,
+            }]
+        );
+    }
+
+    #[test]
+    fn ipfix_templates() {
+        let mut template = vec![];
+        push_u16(
+            &mut template,
+            &[
+                300,
+                6,
+                SOURCE_IPV6_ADDRESS,
+                16,
+                DESTINATION_IPV6_ADDRESS,
+                16,
+                PACKET_DELTA_COUNT,
+                8,
+                SAMPLING_PACKET_INTERVAL,
+                1,
+                SAMPLING_PACKET_SPACE,
+                2,
+                ENTERPRISE_BIT | 1,
+                VARIABLE_LENGTH,
+            ],
+        );
+        template.extend_from_slice(&9u32.to_be_bytes());
+        let mut data = vec![];
+        data.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        data.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets());
+        data.extend_from_slice(&5u64.to_be_bytes());
+        data.push(1);
+        push_u16(&mut data, &[9]);
+        data.extend_from_slice(&[3, b'a', b'b', b'c']);
+
+        let mut sets = set(IPFIX_TEMPLATE, &template);
+        sets.extend(set(300, &data));
+        let mut message = vec![];
+        push_u16(&mut message, &[VERSION_IPFIX, (sets.len() + 16) as u16]);
+        message.extend_from_slice(&[0; 8]);
+        message.extend_from_slice(&1u32.to_be_bytes());
+        message.extend_from_slice(&sets);
+
+        let records = Decoder::default().decode(EXPORTER, &message).unwrap();
+        assert_eq!(
+            records,
+            vec![FlowRecord {
+                src_ip: Ipv6Addr::LOCALHOST.into(),
+                dst_ip: Ipv6Addr::UNSPECIFIED.into(),
+                src_port: 0,
+                dst_port: 0,
+                protocol: 0,
+                packets: 5,
+                bytes: 0,
+                sampling_rate: 10,
+            }]
+        );
+    }
+}
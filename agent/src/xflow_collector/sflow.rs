@@ -0,0 +1,324 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Flow samples of sFlow version 5, see https://sflow.org/sflow_version_5.txt
+//
+// Counter samples are ignored, each flow sample is converted to a record of one packet, which
+// stands for `sampling_rate` packets.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use public::enums::{EthernetType, IpProtocol};
+
+use super::{FlowRecord, Reader, Result, XflowError};
+
+const VERSION: u32 = 5;
+const ADDRESS_IPV4: u32 = 1;
+const ADDRESS_IPV6: u32 = 2;
+
+// formats of the standard enterprise 0
+const FLOW_SAMPLE: u32 = 1;
+const EXPANDED_FLOW_SAMPLE: u32 = 3;
+const RAW_PACKET_HEADER: u32 = 1;
+const SAMPLED_IPV4: u32 = 3;
+const SAMPLED_IPV6: u32 = 4;
+
+const HEADER_PROTOCOL_ETHERNET: u32 = 1;
+const HEADER_PROTOCOL_IPV4: u32 = 11;
+const HEADER_PROTOCOL_IPV6: u32 = 12;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+
+pub(super) fn parse(data: &[u8]) -> Result<Vec<FlowRecord>> {
+    let mut reader = Reader::new(data);
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(XflowError::UnsupportedVersion(version));
+    }
+    match reader.u32()? {
+        ADDRESS_IPV4 => reader.skip(4)?,
+        ADDRESS_IPV6 => reader.skip(16)?,
+        _ => return Err(XflowError::Malformed("agent address type")),
+    }
+    // sub agent id, sequence number and uptime
+    reader.skip(12)?;
+
+    let mut records = vec![];
+    for _ in 0..reader.u32()? {
+        let format = reader.u32()?;
+        let length = reader.u32()? as usize;
+        let mut sample = Reader::new(reader.bytes(length)?);
+        let sampling_rate = match format {
+            FLOW_SAMPLE => {
+                // sequence number and source id
+                sample.skip(8)?;
+                let rate = sample.u32()?;
+                // sample pool, drops, input and output
+                sample.skip(16)?;
+                rate
+            }
+            EXPANDED_FLOW_SAMPLE => {
+                // sequence number, source id type and index
+                sample.skip(12)?;
+                let rate = sample.u32()?;
+                // sample pool, drops, formats and values of input and output
+                sample.skip(24)?;
+                rate
+            }
+            _ => continue,
+        };
+        if let Some(mut record) = parse_flow_records(&mut sample)? {
+            record.sampling_rate = sampling_rate.max(1);
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+// Only the first record describing the packet is used
+fn parse_flow_records(sample: &mut Reader) -> Result<Option<FlowRecord>> {
+    let mut record = None;
+    for _ in 0..sample.u32()? {
+        let format = sample.u32()?;
+        let length = sample.u32()? as usize;
+        let mut data = Reader::new(sample.bytes(length)?);
+        if record.is_some() {
+            continue;
+        }
+        record = match format {
+            RAW_PACKET_HEADER => parse_raw_packet_header(&mut data)?,
+            SAMPLED_IPV4 => {
+                let length = data.u32()?;
+                let protocol = data.u32()? as u8;
+                Some(FlowRecord {
+                    src_ip: IpAddr::V4(data.ipv4()?),
+                    dst_ip: IpAddr::V4(data.ipv4()?),
+                    src_port: data.u32()? as u16,
+                    dst_port: data.u32()? as u16,
+                    protocol,
+                    packets: 1,
+                    bytes: length as u64,
+                    sampling_rate: 1,
+                })
+            }
+            SAMPLED_IPV6 => {
+                let length = data.u32()?;
+                let protocol = data.u32()? as u8;
+                Some(FlowRecord {
+                    src_ip: IpAddr::V6(data.ipv6()?),
+                    dst_ip: IpAddr::V6(data.ipv6()?),
+                    src_port: data.u32()? as u16,
+                    dst_port: data.u32()? as u16,
+                    protocol,
+                    packets: 1,
+                    bytes: length as u64,
+                    sampling_rate: 1,
+                })
+            }
+            _ => None,
+        };
+    }
+    Ok(record)
+}
+
+fn parse_raw_packet_header(data: &mut Reader) -> Result<Option<FlowRecord>> {
+    let header_protocol = data.u32()?;
+    let frame_length = data.u32()?;
+    let stripped = data.u32()?;
+    let header_length = data.u32()? as usize;
+    let header = data.bytes(header_length)?;
+    let record = match header_protocol {
+        HEADER_PROTOCOL_ETHERNET => parse_ethernet(header),
+        HEADER_PROTOCOL_IPV4 | HEADER_PROTOCOL_IPV6 => parse_ip(header),
+        _ => None,
+    };
+    Ok(record.map(|r| FlowRecord {
+        bytes: frame_length.saturating_sub(stripped) as u64,
+        ..r
+    }))
+}
+
+fn parse_ethernet(header: &[u8]) -> Option<FlowRecord> {
+    let mut offset = ETHERNET_HEADER_LEN - 2;
+    loop {
+        let eth_type = header.get(offset..offset + 2)?;
+        offset += 2;
+        match EthernetType::from(u16::from_be_bytes([eth_type[0], eth_type[1]])) {
+            // the tag control information is followed by the inner type
+            EthernetType::DOT1Q | EthernetType::QINQ => offset += 2,
+            EthernetType::IPV4 | EthernetType::IPV6 => return parse_ip(&header[offset..]),
+            _ => return None,
+        }
+    }
+}
+
+// Ports are zero if the header is truncated or the packet is a fragment
+fn parse_ip(header: &[u8]) -> Option<FlowRecord> {
+    let (src_ip, dst_ip, protocol, l4) = match header.first()? >> 4 {
+        4 if header.len() >= IPV4_HEADER_LEN => {
+            let ihl = (header[0] & 0xf) as usize * 4;
+            let fragment_offset = u16::from_be_bytes([header[6], header[7]]) & 0x1fff;
+            let src: [u8; 4] = header[12..16].try_into().unwrap();
+            let dst: [u8; 4] = header[16..20].try_into().unwrap();
+            (
+                IpAddr::from(Ipv4Addr::from(src)),
+                IpAddr::from(Ipv4Addr::from(dst)),
+                header[9],
+                header.get(ihl..).filter(|_| fragment_offset == 0),
+            )
+        }
+        6 if header.len() >= IPV6_HEADER_LEN => {
+            let src: [u8; 16] = header[8..24].try_into().unwrap();
+            let dst: [u8; 16] = header[24..40].try_into().unwrap();
+            (
+                IpAddr::from(Ipv6Addr::from(src)),
+                IpAddr::from(Ipv6Addr::from(dst)),
+                header[6],
+                header.get(IPV6_HEADER_LEN..),
+            )
+        }
+        _ => return None,
+    };
+    let (src_port, dst_port) = match (IpProtocol::from(protocol), l4) {
+        (IpProtocol::TCP | IpProtocol::UDP | IpProtocol::SCTP, Some(l4)) if l4.len() >= 4 => (
+            u16::from_be_bytes([l4[0], l4[1]]),
+            u16::from_be_bytes([l4[2], l4[3]]),
+        ),
+        _ => (0, 0),
+    };
+    Some(FlowRecord {
+        src_ip,
+        dst_ip,
+        src_port,
+        dst_port,
+        protocol,
+        packets: 1,
+        bytes: 0,
+        sampling_rate: 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_u32(buffer: &mut Vec<u8>, values: &[u32]) {
+        for v in values {
+            buffer.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    fn datagram(samples: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut buffer = vec![];
+        push_u32(&mut buffer, &[VERSION, ADDRESS_IPV4]);
+        buffer.extend_from_slice(&[10, 0, 0, 254]);
+        push_u32(&mut buffer, &[0, 1, 1000, samples.len() as u32]);
+        for (format, sample) in samples {
+            push_u32(&mut buffer, &[*format, sample.len() as u32]);
+            buffer.extend_from_slice(sample);
+        }
+        buffer
+    }
+
+    #[test]
+    fn flow_samples() {
+        // ethernet, 802.1Q, ipv4 and tcp 10.0.0.1:40000 -> 10.0.0.2:80
+        let mut header = vec![0u8; 12];
+        header.extend_from_slice(&[0x81, 0x00, 0x00, 0x0a, 0x08, 0x00]);
+        let mut ip = vec![0x45, 0, 0, 40, 0, 0, 0x40, 0, 64, 6, 0, 0];
+        ip.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        header.extend_from_slice(&ip);
+        header.extend_from_slice(&40000u16.to_be_bytes());
+        header.extend_from_slice(&80u16.to_be_bytes());
+        header.extend_from_slice(&[0, 0]);
+
+        let mut raw = vec![];
+        push_u32(
+            &mut raw,
+            &[HEADER_PROTOCOL_ETHERNET, 1518, 4, header.len() as u32],
+        );
+        raw.extend_from_slice(&header);
+        let mut sample = vec![];
+        push_u32(
+            &mut sample,
+            &[
+                1,
+                3,
+                512,
+                1000,
+                0,
+                1,
+                2,
+                1,
+                RAW_PACKET_HEADER,
+                raw.len() as u32,
+            ],
+        );
+        sample.extend_from_slice(&raw);
+
+        let mut sampled_ipv6 = vec![];
+        push_u32(&mut sampled_ipv6, &[100, 17]);
+        sampled_ipv6.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        sampled_ipv6.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets());
+        push_u32(&mut sampled_ipv6, &[53, 5353, 0, 0]);
+        let mut expanded = vec![];
+        push_u32(
+            &mut expanded,
+            &[1, 0, 3, 0, 1000, 0, 0, 1, 0, 2, 1, SAMPLED_IPV6],
+        );
+        push_u32(&mut expanded, &[sampled_ipv6.len() as u32]);
+        expanded.extend_from_slice(&sampled_ipv6);
+
+        let records = parse(&datagram(&[
+            (FLOW_SAMPLE, sample),
+            (2, vec![0; 8]),
+            (EXPANDED_FLOW_SAMPLE, expanded),
+        ]))
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                FlowRecord {
+                    src_ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+                    dst_ip: Ipv4Addr::new(10, 0, 0, 2).into(),
+                    src_port: 40000,
+                    dst_port: 80,
+                    protocol: 6,
+                    packets: 1,
+                    bytes: 1514,
+                    sampling_rate: 512,
+                },
+                FlowRecord {
+                    src_ip: Ipv6Addr::LOCALHOST.into(),
+                    dst_ip: Ipv6Addr::UNSPECIFIED.into(),
+                    src_port: 53,
+                    dst_port: 5353,
+                    protocol: 17,
+                    packets: 1,
+                    bytes: 100,
+                    sampling_rate: 1,
+                },
+            ]
+        );
+
+        assert!(matches!(
+            parse(&[0, 0, 0, 4]),
+            Err(XflowError::UnsupportedVersion(4))
+        ));
+    }
+}
@@ -274,9 +274,11 @@ type OffCpuProfile struct {
 }
 
 type MemoryProfile struct {
-	Disabled       *bool   `yaml:"disabled,omitempty"`
-	Regex          *string `yaml:"regex,omitempty"`
-	ReportInterval *string `yaml:"report-interval,omitempty"`
+	Disabled            *bool   `yaml:"disabled,omitempty"`
+	Regex               *string `yaml:"regex,omitempty"`
+	ReportInterval      *string `yaml:"report-interval,omitempty"`
+	SamplingInterval    *int    `yaml:"sampling-interval,omitempty"`
+	MaxSamplesPerSecond *int    `yaml:"max-samples-per-second,omitempty"`
 }
 
 type Preprocess struct {
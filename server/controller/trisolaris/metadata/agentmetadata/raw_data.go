@@ -1226,6 +1226,7 @@ func (r *PlatformRawData) vInterfaceToProto(
 		RegionId:       proto.Uint32(uint32(regionID)),
 		PodClusterId:   proto.Uint32(uint32(device.PodClusterID)),
 		PodNodeId:      proto.Uint32(uint32(device.PodNodeID)),
+		PodNsId:        proto.Uint32(uint32(device.PodNamespaceID)),
 		IsVipInterface: proto.Bool(ipResourceData.isVipInterface),
 	}
 
@@ -34,6 +34,7 @@ import (
 	"github.com/deepflowio/deepflow/server/controller/trisolaris/pushmanager"
 	"github.com/deepflowio/deepflow/server/controller/trisolaris/utils"
 	"github.com/deepflowio/deepflow/server/controller/trisolaris/vtap"
+	"github.com/deepflowio/deepflow/server/libs/datatype"
 	"github.com/deepflowio/deepflow/server/libs/logger"
 )
 
@@ -113,6 +114,8 @@ func (e *AgentEvent) generateDynamicConfig(clusterID string, c *vtap.VTapCache)
 		AgentId:              proto.Uint32(uint32(c.GetVTapID())),
 		TeamId:               proto.Uint32(uint32(c.GetTeamID())),
 		OrganizeId:           proto.Uint32(uint32(c.GetOrganizeID())),
+
+		IngesterCompressionAlgorithms: datatype.SUPPORTED_ENCODER_NAMES,
 	}
 }
 
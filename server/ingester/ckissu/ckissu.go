@@ -107,6 +107,14 @@ type ColumnMod struct {
 	DropIndex     bool
 }
 
+type ColumnMods struct {
+	Dbs           []string
+	Tables        []string
+	ColumnNames   []string
+	NewColumnType ckdb.ColumnType
+	DropIndex     bool
+}
+
 type ColumnAdd struct {
 	Db           string
 	Table        string
@@ -1084,6 +1092,26 @@ func getColumnAdds(columnAdds *ColumnAdds) []*ColumnAdd {
 	return adds
 }
 
+func getColumnMods(columnModss []*ColumnMods) []*ColumnMod {
+	mods := []*ColumnMod{}
+	for _, columnMods := range columnModss {
+		for _, db := range columnMods.Dbs {
+			for _, tbl := range columnMods.Tables {
+				for _, clmn := range columnMods.ColumnNames {
+					mods = append(mods, &ColumnMod{
+						Db:            db,
+						Table:         tbl,
+						ColumnName:    clmn,
+						NewColumnType: columnMods.NewColumnType,
+						DropIndex:     columnMods.DropIndex,
+					})
+				}
+			}
+		}
+	}
+	return mods
+}
+
 func getIndexAdds(indexAddss []*IndexAdds) []*IndexAdd {
 	adds := []*IndexAdd{}
 	for _, indexAdds := range indexAddss {
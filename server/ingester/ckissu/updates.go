@@ -21,13 +21,13 @@ import (
 )
 
 var AllColumnAdds = [][]*ColumnAdds{ColumnAdd64, ColumnAdd65, ColumnAdd66}
-var AllIndexAdds = [][]*IndexAdd{getIndexAdds(IndexAdd64), getIndexAdds(IndexAdd65)}
-var AllColumnMods = [][]*ColumnMod{}
+var AllIndexAdds = [][]*IndexAdd{getIndexAdds(IndexAdd64), getIndexAdds(IndexAdd65), getIndexAdds(IndexAdd66)}
+var AllColumnMods = [][]*ColumnMod{getColumnMods(ColumnMod66)}
 var AllColumnRenames = [][]*ColumnRename{getColumnRenames(ColumnRename65)}
 var AllColumnDrops = [][]*ColumnDrop{getColumnDrops(nil)}
 var AllTableModTTLs = [][]*TableModTTL{}
 var AllTableRenames = []*TableRename{}
-var AllDatasourceAdds = [][]*ColumnDatasourceAdd{getColumnDatasourceAdds(ColumnDatasourceAdd65)}
+var AllDatasourceAdds = [][]*ColumnDatasourceAdd{getColumnDatasourceAdds(ColumnDatasourceAdd65), getColumnDatasourceAdds(ColumnDatasourceAdd66)}
 
 var ColumnAdd64 = []*ColumnAdds{
 	{
@@ -362,6 +362,39 @@ var ColumnDatasourceAdd65 = []*ColumnDatasourceAdds{
 	},
 }
 
+var ColumnDatasourceAdd66 = []*ColumnDatasourceAdds{
+	{
+		ColumnNames: []string{
+			"request_size_le_64", "request_size_le_256", "request_size_le_1k", "request_size_le_4k", "request_size_le_16k",
+			"request_size_le_64k", "request_size_le_256k", "request_size_le_1m", "request_size_gt_1m",
+			"response_size_le_64", "response_size_le_256", "response_size_le_1k", "response_size_le_4k", "response_size_le_16k",
+			"response_size_le_64k", "response_size_le_256k", "response_size_le_1m", "response_size_gt_1m",
+			"request_size_sum", "response_size_sum",
+		},
+		ColumnTypes: []ckdb.ColumnType{
+			ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64,
+			ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64, ckdb.UInt64,
+			ckdb.UInt64, ckdb.UInt64,
+		},
+		OnlyAppTable: true,
+		IsMetrics:    true,
+		IsSummable:   true,
+	},
+	{
+		ColumnNames:  []string{"request_size_max", "response_size_max"},
+		ColumnTypes:  []ckdb.ColumnType{ckdb.UInt64, ckdb.UInt64},
+		OnlyAppTable: true,
+		IsMetrics:    true,
+	},
+	{
+		ColumnNames:  []string{"rrt_p50", "rrt_p90", "rrt_p99"},
+		ColumnTypes:  []ckdb.ColumnType{ckdb.UInt32, ckdb.UInt32, ckdb.UInt32},
+		OnlyAppTable: true,
+		IsMetrics:    true,
+	},
+}
+
+// when adding a new column, you need to check whether `ColumnDatasourceAdd66` also needs to be added.
 var ColumnAdd66 = []*ColumnAdds{
 	{
 		Dbs:         []string{"profile"},
@@ -375,4 +408,120 @@ var ColumnAdd66 = []*ColumnAdds{
 		ColumnNames: []string{"auto_instance_type", "auto_service_type"},
 		ColumnType:  ckdb.UInt8,
 	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log", "l4_flow_log_local"},
+		ColumnNames: []string{"zero_win_duration_tx", "zero_win_duration_rx", "out_of_order_tx", "out_of_order_rx", "sack_tx", "sack_rx"},
+		ColumnType:  ckdb.UInt32,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log", "l4_flow_log_local"},
+		ColumnNames: []string{"mss_tx", "mss_rx"},
+		ColumnType:  ckdb.UInt16,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log", "l4_flow_log_local"},
+		ColumnNames: []string{"win_scale_tx", "win_scale_rx", "sack_permitted"},
+		ColumnType:  ckdb.UInt8,
+	},
+	{
+		Dbs: []string{"flow_metrics"},
+		Tables: []string{
+			"application.1m", "application.1m_local", "application.1s", "application.1s_local",
+			"application_map.1m", "application_map.1m_local", "application_map.1s", "application_map.1s_local",
+		},
+		ColumnNames: []string{
+			"request_size_le_64", "request_size_le_256", "request_size_le_1k", "request_size_le_4k", "request_size_le_16k",
+			"request_size_le_64k", "request_size_le_256k", "request_size_le_1m", "request_size_gt_1m",
+			"response_size_le_64", "response_size_le_256", "response_size_le_1k", "response_size_le_4k", "response_size_le_16k",
+			"response_size_le_64k", "response_size_le_256k", "response_size_le_1m", "response_size_gt_1m",
+			"request_size_sum", "response_size_sum", "request_size_max", "response_size_max",
+		},
+		ColumnType: ckdb.UInt64,
+	},
+	{
+		Dbs: []string{"flow_metrics"},
+		Tables: []string{
+			"application.1m", "application.1m_local", "application.1s", "application.1s_local",
+			"application_map.1m", "application_map.1m_local", "application_map.1s", "application_map.1s_local",
+		},
+		ColumnNames: []string{"rrt_p50", "rrt_p90", "rrt_p99"},
+		ColumnType:  ckdb.UInt32,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l7_flow_log", "l7_flow_log_local"},
+		ColumnNames: []string{"sample_rate"},
+		ColumnType:  ckdb.UInt32,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log", "l4_flow_log_local"},
+		ColumnNames: []string{"kernel_tcp_retrans", "kernel_tcp_drops", "kernel_tcp_srtt"},
+		ColumnType:  ckdb.UInt32,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log", "l4_flow_log_local"},
+		ColumnNames: []string{"kernel_tcp_state"},
+		ColumnType:  ckdb.UInt8,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log", "l4_flow_log_local"},
+		ColumnNames: []string{"duplicate_of_flow_id"},
+		ColumnType:  ckdb.UInt64,
+	},
+}
+
+// capture_network_type_id is widened to hold capture network types up to 4095. The index
+// has to be dropped before the column type can be changed, and is added back by `IndexAdd66`.
+// The aggregated 1h/1d datasource tables keep the column in their sort key, which cannot
+// be modified, so they only get the wider type when they are recreated.
+var ColumnMod66 = []*ColumnMods{
+	{
+		Dbs:           []string{"flow_log"},
+		Tables:        []string{"l4_flow_log", "l4_flow_log_local", "l7_flow_log", "l7_flow_log_local"},
+		ColumnNames:   []string{"capture_network_type_id"},
+		NewColumnType: ckdb.UInt16,
+		DropIndex:     true,
+	},
+	{
+		Dbs: []string{"flow_metrics"},
+		Tables: []string{
+			"application.1m", "application.1m_local", "application_map.1m", "application_map.1m_local",
+			"application.1s", "application.1s_local", "application_map.1s", "application_map.1s_local",
+			"network.1m", "network.1m_local", "network_map.1m", "network_map.1m_local",
+			"network.1s", "network.1s_local", "network_map.1s", "network_map.1s_local",
+		},
+		ColumnNames:   []string{"capture_network_type_id"},
+		NewColumnType: ckdb.UInt16,
+		DropIndex:     true,
+	},
+}
+
+var IndexAdd66 = []*IndexAdds{
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l7_flow_log_local"},
+		ColumnNames: []string{"capture_network_type_id"},
+		IndexType:   ckdb.IndexSet,
+	},
+	{
+		Dbs:         []string{"flow_log"},
+		Tables:      []string{"l4_flow_log_local"},
+		ColumnNames: []string{"capture_network_type_id"},
+		IndexType:   ckdb.IndexMinmax,
+	},
+	{
+		Dbs: []string{"flow_metrics"},
+		Tables: []string{
+			"application.1m_local", "application_map.1m_local", "application.1s_local", "application_map.1s_local",
+			"network.1m_local", "network_map.1m_local", "network.1s_local", "network_map.1s_local",
+		},
+		ColumnNames: []string{"capture_network_type_id"},
+		IndexType:   ckdb.IndexMinmax,
+	},
 }
@@ -175,6 +175,7 @@ type Config struct {
 	UDPReadBuffer            int             `yaml:"udp-read-buffer"`
 	TCPReadBuffer            int             `yaml:"tcp-read-buffer"`
 	TCPReaderBuffer          int             `yaml:"tcp-reader-buffer"`
+	TLS                      TLS             `yaml:"tls"`
 	CKDiskMonitor            CKDiskMonitor   `yaml:"ck-disk-monitor"`
 	ColdStorage              CKDBColdStorage `yaml:"ckdb-cold-storage"`
 	ckdbColdStorages         map[string]*ckdb.ColdStorage
@@ -190,6 +191,14 @@ type Config struct {
 	TraceIdWithIndex         TraceIdWithIndex
 }
 
+// mutual TLS of the TCP connections from agents, agents without TLS are still accepted
+type TLS struct {
+	Enabled  bool   `yaml:"enabled"`
+	CAFile   string `yaml:"ca-file"`
+	CertFile string `yaml:"cert-file"`
+	KeyFile  string `yaml:"key-file"`
+}
+
 type Location struct {
 	Start  int    `yaml:"start"`
 	Length int    `yaml:"length"`
@@ -129,6 +129,14 @@ var unsummableMaxFieldsMap = map[string]struct{}{
 	"art_max":        {},
 	"rrt_max":        {},
 	"cit_max":        {},
+
+	"request_size_max":  {},
+	"response_size_max": {},
+
+	// 分位数同样不可累加
+	"rrt_p50": {},
+	"rrt_p90": {},
+	"rrt_p99": {},
 }
 
 // 对于unsumable的sum列使用max,min聚合时, count列取相应的max,min列的值
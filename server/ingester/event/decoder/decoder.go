@@ -146,7 +146,8 @@ func (d *Decoder) Run() {
 					continue
 				}
 				decoder.Init(recvBytes.Buffer[recvBytes.Begin:recvBytes.End])
-				d.handleAlertEvent(decoder)
+				d.orgId, d.teamId = uint16(recvBytes.OrgID), uint16(recvBytes.TeamID)
+				d.handleAlertEvent(recvBytes.VtapID, decoder)
 				receiver.ReleaseRecvBuffer(recvBytes)
 			case common.K8S_EVENT:
 				recvBytes, ok := buffer[i].(*receiver.RecvBuffer)
@@ -417,7 +418,7 @@ func (d *Decoder) handleResourceEvent(event *eventapi.ResourceEvent) {
 	d.eventWriter.Write(s)
 }
 
-func (d *Decoder) handleAlertEvent(decoder *codec.SimpleDecoder) {
+func (d *Decoder) handleAlertEvent(vtapId uint16, decoder *codec.SimpleDecoder) {
 	for !decoder.IsEnd() {
 		bytes := decoder.ReadBytes()
 		if decoder.Failed() {
@@ -436,11 +437,11 @@ func (d *Decoder) handleAlertEvent(decoder *codec.SimpleDecoder) {
 			continue
 		}
 		d.counter.OutCount++
-		d.writeAlertEvent(pbAlertEvent)
+		d.writeAlertEvent(vtapId, pbAlertEvent)
 	}
 }
 
-func (d *Decoder) writeAlertEvent(event *alert_event.AlertEvent) {
+func (d *Decoder) writeAlertEvent(vtapId uint16, event *alert_event.AlertEvent) {
 	s := dbwriter.AcquireAlertEventStore()
 	s.Time = event.GetTime()
 	s.SetId(s.Time, d.platformData.QueryAnalyzerID())
@@ -463,5 +464,14 @@ func (d *Decoder) writeAlertEvent(event *alert_event.AlertEvent) {
 	s.TeamID = uint16(event.GetTeamId())
 	s.UserId = event.GetUserId()
 
+	// alert events reported by deepflow-agent do not carry the org and team, take them from the message header
+	if vtapId != 0 {
+		if event.OrgId == nil {
+			s.OrgId, s.TeamID = d.orgId, d.teamId
+		}
+		s.TagIntKeys = append(s.TagIntKeys, "agent_id")
+		s.TagIntValues = append(s.TagIntValues, int64(vtapId))
+	}
+
 	d.eventWriter.WriteAlertEvent(s)
 }
@@ -382,7 +382,7 @@ type FlowInfo struct {
 	CloseType    uint16 `json:"close_type" category:"$tag" sub:"flow_info" enumfile:"close_type"`
 	SignalSource uint16 `json:"signal_source" category:"$tag" sub:"capture_info" enumfile:"l4_signal_source"`
 	FlowID       uint64 `json:"flow_id" category:"$tag" sub:"flow_info"`
-	TapType      uint8  `json:"capture_network_type_id" category:"$tag" sub:"capture_info"`
+	TapType      uint16 `json:"capture_network_type_id" category:"$tag" sub:"capture_info"`
 	NatSource    uint8  `json:"nat_source" category:"$tag" sub:"capture_info" enumfile:"nat_source"`
 	TapPortType  uint8  `json:"capture_nic_type" category:"$tag" sub:"capture_info" enumfile:"capture_nic_type"` // 0: MAC, 1: IPv4, 2:IPv6, 3: ID
 	TapPort      uint32 `json:"capture_nic" category:"$tag" sub:"capture_info"`
@@ -408,6 +408,8 @@ type FlowInfo struct {
 
 	DirectionScore uint8  `json:"direction_score" category:"$metrics" sub:"l4_throughput"`
 	RequestDomain  string `json:"request_domain" category:"$tag" sub:"application_layer"`
+
+	DuplicateOfFlowID uint64 `json:"duplicate_of_flow_id" category:"$tag" sub:"flow_info"`
 }
 
 var FlowInfoColumns = []*ckdb.Column{
@@ -416,7 +418,7 @@ var FlowInfoColumns = []*ckdb.Column{
 	ckdb.NewColumn("close_type", ckdb.UInt16).SetIndex(ckdb.IndexSet),
 	ckdb.NewColumn("signal_source", ckdb.UInt16),
 	ckdb.NewColumn("flow_id", ckdb.UInt64).SetIndex(ckdb.IndexMinmax),
-	ckdb.NewColumn("capture_network_type_id", ckdb.UInt8),
+	ckdb.NewColumn("capture_network_type_id", ckdb.UInt16),
 	ckdb.NewColumn("nat_source", ckdb.UInt8),
 	ckdb.NewColumn("capture_nic_type", ckdb.UInt8),
 	ckdb.NewColumn("capture_nic", ckdb.UInt32),
@@ -440,6 +442,7 @@ var FlowInfoColumns = []*ckdb.Column{
 	ckdb.NewColumn("nat_real_port_1", ckdb.UInt16),
 	ckdb.NewColumn("direction_score", ckdb.UInt8).SetIndex(ckdb.IndexMinmax),
 	ckdb.NewColumn("request_domain", ckdb.String).SetIndex(ckdb.IndexBloomfilter),
+	ckdb.NewColumn("duplicate_of_flow_id", ckdb.UInt64).SetComment("同一采集器在其他观测点采集到的同一会话的flow_id, 0表示不是重复的流日志"),
 }
 
 func (f *FlowInfo) WriteBlock(block *ckdb.Block) {
@@ -470,7 +473,7 @@ func (f *FlowInfo) WriteBlock(block *ckdb.Block) {
 
 	block.WriteIPv4(f.NatRealIP0)
 	block.WriteIPv4(f.NatRealIP1)
-	block.Write(f.NatRealPort0, f.NatRealPort1, f.DirectionScore, f.RequestDomain)
+	block.Write(f.NatRealPort0, f.NatRealPort1, f.DirectionScore, f.RequestDomain, f.DuplicateOfFlowID)
 }
 
 type Metrics struct {
@@ -522,6 +525,23 @@ type Metrics struct {
 	L7ServerError   uint32 `json:"l7_server_error" category:"$metrics" sub:"application"`
 	L7ServerTimeout uint32 `json:"l7_server_timeout" category:"$metrics" sub:"application"`
 	L7Error         uint32 `json:"l7_error" category:"$metrics" sub:"application"`
+
+	ZeroWinDurationTx uint32 `json:"zero_win_duration_tx" category:"$metrics" sub:"tcp_slow"` // us
+	ZeroWinDurationRx uint32 `json:"zero_win_duration_rx" category:"$metrics" sub:"tcp_slow"` // us
+	OutOfOrderTx      uint32 `json:"out_of_order_tx" category:"$metrics" sub:"tcp_slow"`
+	OutOfOrderRx      uint32 `json:"out_of_order_rx" category:"$metrics" sub:"tcp_slow"`
+	SackTx            uint32 `json:"sack_tx" category:"$metrics" sub:"tcp_slow"`
+	SackRx            uint32 `json:"sack_rx" category:"$metrics" sub:"tcp_slow"`
+	MssTx             uint16 `json:"mss_tx" category:"$metrics" sub:"tcp_slow"`
+	MssRx             uint16 `json:"mss_rx" category:"$metrics" sub:"tcp_slow"`
+	WinScaleTx        uint8  `json:"win_scale_tx" category:"$metrics" sub:"tcp_slow"`
+	WinScaleRx        uint8  `json:"win_scale_rx" category:"$metrics" sub:"tcp_slow"`
+	SackPermitted     uint8  `json:"sack_permitted" category:"$metrics" sub:"tcp_slow"`
+
+	KernelTcpRetrans uint32 `json:"kernel_tcp_retrans" category:"$metrics" sub:"tcp_slow"`
+	KernelTcpDrops   uint32 `json:"kernel_tcp_drops" category:"$metrics" sub:"tcp_error"`
+	KernelTcpSrtt    uint32 `json:"kernel_tcp_srtt" category:"$metrics" sub:"delay"` // us
+	KernelTcpState   uint8  `json:"kernel_tcp_state" category:"$tag" sub:"transport_layer"`
 }
 
 var MetricsColumns = []*ckdb.Column{
@@ -574,6 +594,22 @@ var MetricsColumns = []*ckdb.Column{
 	ckdb.NewColumn("l7_server_error", ckdb.UInt32),
 	ckdb.NewColumn("l7_server_timeout", ckdb.UInt32),
 	ckdb.NewColumn("l7_error", ckdb.UInt32),
+
+	ckdb.NewColumn("zero_win_duration_tx", ckdb.UInt32).SetComment("单位: 微秒"),
+	ckdb.NewColumn("zero_win_duration_rx", ckdb.UInt32).SetComment("单位: 微秒"),
+	ckdb.NewColumn("out_of_order_tx", ckdb.UInt32),
+	ckdb.NewColumn("out_of_order_rx", ckdb.UInt32),
+	ckdb.NewColumn("sack_tx", ckdb.UInt32).SetComment("携带SACK块的包数"),
+	ckdb.NewColumn("sack_rx", ckdb.UInt32).SetComment("携带SACK块的包数"),
+	ckdb.NewColumn("mss_tx", ckdb.UInt16).SetComment("SYN中协商的MSS"),
+	ckdb.NewColumn("mss_rx", ckdb.UInt16).SetComment("SYN/ACK中协商的MSS"),
+	ckdb.NewColumn("win_scale_tx", ckdb.UInt8).SetComment("SYN中的窗口扩大因子, 未协商时为0"),
+	ckdb.NewColumn("win_scale_rx", ckdb.UInt8).SetComment("SYN/ACK中的窗口扩大因子, 未协商时为0"),
+	ckdb.NewColumn("sack_permitted", ckdb.UInt8).SetComment("握手时双方是否都允许SACK. 0: 否, 1: 是"),
+	ckdb.NewColumn("kernel_tcp_retrans", ckdb.UInt32).SetComment("eBPF采集的本地socket累计重传次数"),
+	ckdb.NewColumn("kernel_tcp_drops", ckdb.UInt32).SetComment("eBPF采集的本地socket累计丢包数"),
+	ckdb.NewColumn("kernel_tcp_srtt", ckdb.UInt32).SetComment("eBPF采集的本地socket平滑RTT, 单位: 微秒, 0表示未知"),
+	ckdb.NewColumn("kernel_tcp_state", ckdb.UInt8).SetComment("内核TCP状态. 1: ESTABLISHED, 2: SYN_SENT, ..., 7: CLOSE, 0: 未知"),
 }
 
 func (m *Metrics) WriteBlock(block *ckdb.Block) {
@@ -625,7 +661,24 @@ func (m *Metrics) WriteBlock(block *ckdb.Block) {
 		m.L7ClientError,
 		m.L7ServerError,
 		m.L7ServerTimeout,
-		m.L7Error)
+		m.L7Error,
+
+		m.ZeroWinDurationTx,
+		m.ZeroWinDurationRx,
+		m.OutOfOrderTx,
+		m.OutOfOrderRx,
+		m.SackTx,
+		m.SackRx,
+		m.MssTx,
+		m.MssRx,
+		m.WinScaleTx,
+		m.WinScaleRx,
+		m.SackPermitted,
+
+		m.KernelTcpRetrans,
+		m.KernelTcpDrops,
+		m.KernelTcpSrtt,
+		m.KernelTcpState)
 }
 
 func parseUint32EpcID(v uint32) int32 {
@@ -920,7 +973,7 @@ func (i *FlowInfo) Fill(f *pb.Flow) {
 	i.CloseType = uint16(f.CloseType)
 	i.SignalSource = uint16(f.SignalSource)
 	i.FlowID = f.FlowId
-	i.TapType = uint8(f.FlowKey.TapType)
+	i.TapType = uint16(f.FlowKey.TapType)
 	var natSource datatype.NATSource
 	i.TapPort, i.TapPortType, natSource, _ = datatype.TapPort(f.FlowKey.TapPort).SplitToPortTypeTunnel()
 	i.NatSource = uint8(natSource)
@@ -950,6 +1003,7 @@ func (i *FlowInfo) Fill(f *pb.Flow) {
 	i.NatRealPort1 = uint16(f.MetricsPeerDst.RealPort)
 	i.DirectionScore = uint8(f.DirectionScore)
 	i.RequestDomain = f.RequestDomain
+	i.DuplicateOfFlowID = f.DuplicateOfFlowId
 }
 
 func (m *Metrics) Fill(f *pb.Flow) {
@@ -967,6 +1021,11 @@ func (m *Metrics) Fill(f *pb.Flow) {
 	m.TotalByteTx = f.MetricsPeerSrc.TotalByteCount
 	m.TotalByteRx = f.MetricsPeerDst.TotalByteCount
 
+	m.KernelTcpRetrans = f.KernelTcpRetrans
+	m.KernelTcpDrops = f.KernelTcpDrops
+	m.KernelTcpSrtt = f.KernelTcpSrtt
+	m.KernelTcpState = uint8(f.KernelTcpState)
+
 	if f.HasPerfStats == 1 {
 		p := f.PerfStats
 		m.L7Request = p.L7.RequestCount
@@ -1002,10 +1061,23 @@ func (m *Metrics) Fill(f *pb.Flow) {
 		if p.Tcp.CountsPeerTx != nil {
 			m.RetransTx = p.Tcp.CountsPeerTx.RetransCount
 			m.ZeroWinTx = p.Tcp.CountsPeerTx.ZeroWinCount
+			m.ZeroWinDurationTx = p.Tcp.CountsPeerTx.ZeroWinDuration
+			m.OutOfOrderTx = p.Tcp.CountsPeerTx.OutOfOrderCount
+			m.SackTx = p.Tcp.CountsPeerTx.SackCount
+			m.MssTx = uint16(p.Tcp.CountsPeerTx.Mss)
+			m.WinScaleTx = uint8(p.Tcp.CountsPeerTx.WinScale)
 		}
 		if p.Tcp.CountsPeerRx != nil {
 			m.RetransRx = p.Tcp.CountsPeerRx.RetransCount
 			m.ZeroWinRx = p.Tcp.CountsPeerRx.ZeroWinCount
+			m.ZeroWinDurationRx = p.Tcp.CountsPeerRx.ZeroWinDuration
+			m.OutOfOrderRx = p.Tcp.CountsPeerRx.OutOfOrderCount
+			m.SackRx = p.Tcp.CountsPeerRx.SackCount
+			m.MssRx = uint16(p.Tcp.CountsPeerRx.Mss)
+			m.WinScaleRx = uint8(p.Tcp.CountsPeerRx.WinScale)
+		}
+		if p.Tcp.SackPermitted {
+			m.SackPermitted = 1
 		}
 		m.SynCount = p.Tcp.SynCount
 		m.SynackCount = p.Tcp.SynackCount
@@ -60,7 +60,7 @@ type L7Base struct {
 
 	// 流信息
 	FlowID       uint64 `json:"flow_id" category:"$tag" sub:"flow_info"`
-	TapType      uint8  `json:"capture_network_type_id" category:"$tag" sub:"capture_info"`
+	TapType      uint16 `json:"capture_network_type_id" category:"$tag" sub:"capture_info"`
 	NatSource    uint8  `json:"nat_source" category:"$tag" sub:"capture_info" enumfile:"nat_source"`
 	TapPortType  uint8  `json:"capture_nic_type category:"$tag" sub:"capture_info"`
 	SignalSource uint16 `json:"signal_source" category:"$tag" sub:"capture_info" enumfile:"l7_signal_source"`
@@ -76,6 +76,7 @@ type L7Base struct {
 	GPID0        uint32 `json:"gprocess_id_0" category:"$tag" sub:"universal_tag"`
 	GPID1        uint32 `json:"gprocess_id_1" category:"$tag" sub:"universal_tag"`
 	BizType      uint8  `json:"biz_type" category:"$tag" sub:"capture_info"`
+	SampleRate   uint32 `json:"sample_rate" category:"$tag" sub:"capture_info"`
 
 	ProcessID0             uint32 `json:"process_id_0" category:"$tag" sub:"service_info"`
 	ProcessID1             uint32 `json:"process_id_1" category:"$tag" sub:"service_info"`
@@ -113,7 +114,7 @@ func L7BaseColumns() []*ckdb.Column {
 
 		// 流信息
 		ckdb.NewColumn("flow_id", ckdb.UInt64).SetIndex(ckdb.IndexMinmax),
-		ckdb.NewColumn("capture_network_type_id", ckdb.UInt8).SetIndex(ckdb.IndexSet),
+		ckdb.NewColumn("capture_network_type_id", ckdb.UInt16).SetIndex(ckdb.IndexSet),
 		ckdb.NewColumn("nat_source", ckdb.UInt8).SetIndex(ckdb.IndexSet),
 		ckdb.NewColumn("capture_nic_type", ckdb.UInt8).SetIndex(ckdb.IndexNone),
 		ckdb.NewColumn("signal_source", ckdb.UInt16).SetIndex(ckdb.IndexNone),
@@ -128,6 +129,7 @@ func L7BaseColumns() []*ckdb.Column {
 		ckdb.NewColumn("gprocess_id_0", ckdb.UInt32).SetComment("全局客户端进程ID"),
 		ckdb.NewColumn("gprocess_id_1", ckdb.UInt32).SetComment("全局服务端进程ID"),
 		ckdb.NewColumn("biz_type", ckdb.UInt8).SetComment("Business Type"),
+		ckdb.NewColumn("sample_rate", ckdb.UInt32).SetComment("采样率, 一条日志代表的日志条数, 0或1表示未采样"),
 
 		ckdb.NewColumn("process_id_0", ckdb.Int32).SetComment("客户端进程ID"),
 		ckdb.NewColumn("process_id_1", ckdb.Int32).SetComment("服务端进程ID"),
@@ -176,6 +178,7 @@ func (f *L7Base) WriteBlock(block *ckdb.Block) {
 		f.GPID0,
 		f.GPID1,
 		f.BizType,
+		f.SampleRate,
 
 		int32(f.ProcessID0),
 		int32(f.ProcessID1),
@@ -578,7 +581,7 @@ func (b *L7Base) Fill(log *pb.AppProtoLogsData, platformData *grpc.PlatformInfoT
 	// 流信息
 	b.FlowID = l.FlowId
 
-	b.TapType = uint8(l.TapType)
+	b.TapType = uint16(l.TapType)
 	tunnelType := datatype.TunnelType(0)
 	var natSource datatype.NATSource
 	b.TapPort, b.TapPortType, natSource, tunnelType = datatype.TapPort(l.TapPort).SplitToPortTypeTunnel()
@@ -602,6 +605,7 @@ func (b *L7Base) Fill(log *pb.AppProtoLogsData, platformData *grpc.PlatformInfoT
 	b.GPID0 = l.Gpid_0
 	b.GPID1 = l.Gpid_1
 	b.BizType = uint8(l.BizType)
+	b.SampleRate = l.SampleRate
 
 	b.ProcessID0 = l.ProcessId_0
 	b.ProcessID1 = l.ProcessId_1
@@ -121,6 +121,12 @@ func DocumentExpand(doc app.Document, platformData *grpc.PlatformInfoTable) erro
 		return nil
 	}
 
+	// drops are counted inside the host stack of the agent, and network devices are polled by SNMP,
+	// there are no resources to fill
+	if id := doc.Meter().ID(); id == flow_metrics.DROP_ID || id == flow_metrics.DEVICE_ID {
+		return nil
+	}
+
 	myRegionID := uint16(platformData.QueryRegionID(t.OrgId))
 
 	if t.Code&flow_metrics.ServerPort == flow_metrics.ServerPort {
@@ -17,6 +17,7 @@
 package ingester
 
 import (
+	"crypto/tls"
 	"fmt"
 	"io"
 	"net"
@@ -93,7 +94,16 @@ func Start(configPath string, shared *servercommon.ControllerIngesterShared) []i
 	stats.SetRemoteType(stats.REMOTE_TYPE_DFSTATSD)
 	stats.SetDFRemote(net.JoinHostPort("127.0.0.1", strconv.Itoa(int(cfg.ListenPort))))
 
+	var tlsConfig *tls.Config
+	if cfg.TLS.Enabled {
+		var err error
+		if tlsConfig, err = receiver.LoadTLSConfig(cfg.TLS.CAFile, cfg.TLS.CertFile, cfg.TLS.KeyFile); err != nil {
+			log.Errorf("ingester tls config invalid: %s", err)
+			os.Exit(1)
+		}
+	}
 	receiver := receiver.NewReceiver(int(cfg.ListenPort), cfg.UDPReadBuffer, cfg.TCPReadBuffer, cfg.TCPReaderBuffer)
+	receiver.SetTLSConfig(tlsConfig)
 
 	ingesterOrgHandler := NewOrgHandler(cfg)
 	closers := []io.Closer{}
@@ -58,6 +58,34 @@ func DecodePB(decoder *codec.SimpleDecoder, pbDoc *pb.Document) (Document, error
 		doc.Tag.ReadFromPB(pbDoc.Tag)
 		doc.AppMeter.ReadFromPB(pbDoc.Meter.App)
 		return doc, nil
+	case flow_metrics.DROP_ID:
+		doc := AcquireDocumentDrop()
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.DropMeter.ReadFromPB(pbDoc.Meter.Drop)
+		return doc, nil
+	case flow_metrics.FILE_IO_ID:
+		doc := AcquireDocumentFileIo()
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.FileIoMeter.ReadFromPB(pbDoc.Meter.FileIo)
+		return doc, nil
+	case flow_metrics.TALKER_ID:
+		doc := AcquireDocumentTalker()
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.TalkerMeter.ReadFromPB(pbDoc.Meter.Talker)
+		return doc, nil
+	case flow_metrics.DEVICE_ID:
+		doc := AcquireDocumentDevice()
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.DeviceMeter.ReadFromPB(pbDoc.Meter.Device)
+		return doc, nil
 	default:
 		return nil, fmt.Errorf("Unknow meter ID %d", meterID)
 
@@ -95,6 +123,34 @@ func DecodeForQueueMonitor(decoder *codec.SimpleDecoder) (Document, error) {
 		doc.Tag.ReadFromPB(pbDoc.Tag)
 		doc.AppMeter.ReadFromPB(pbDoc.Meter.App)
 		return doc, nil
+	case flow_metrics.DROP_ID:
+		doc := &DocumentDrop{}
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.DropMeter.ReadFromPB(pbDoc.Meter.Drop)
+		return doc, nil
+	case flow_metrics.FILE_IO_ID:
+		doc := &DocumentFileIo{}
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.FileIoMeter.ReadFromPB(pbDoc.Meter.FileIo)
+		return doc, nil
+	case flow_metrics.TALKER_ID:
+		doc := &DocumentTalker{}
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.TalkerMeter.ReadFromPB(pbDoc.Meter.Talker)
+		return doc, nil
+	case flow_metrics.DEVICE_ID:
+		doc := &DocumentDevice{}
+		doc.Timestamp = pbDoc.Timestamp
+		doc.Flags = DocumentFlag(pbDoc.Flags)
+		doc.Tag.ReadFromPB(pbDoc.Tag)
+		doc.DeviceMeter.ReadFromPB(pbDoc.Meter.Device)
+		return doc, nil
 	default:
 		return nil, fmt.Errorf("Unknow meter ID %d", meterID)
 	}
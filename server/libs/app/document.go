@@ -107,6 +107,26 @@ type DocumentUsage struct {
 	flow_metrics.UsageMeter
 }
 
+type DocumentDrop struct {
+	DocumentBase
+	flow_metrics.DropMeter
+}
+
+type DocumentFileIo struct {
+	DocumentBase
+	flow_metrics.FileIoMeter
+}
+
+type DocumentTalker struct {
+	DocumentBase
+	flow_metrics.TalkerMeter
+}
+
+type DocumentDevice struct {
+	DocumentBase
+	flow_metrics.DeviceMeter
+}
+
 func (d *DocumentFlow) String() string {
 	return fmt.Sprintf("\n{\n\ttimestamp: %d\tFlags: b%b\n\ttag: %+v\n\tmeter: %#v\n}\n",
 		d.Timestamp, d.Flags, d.Tag, d.FlowMeter)
@@ -233,3 +253,167 @@ func (d *DocumentUsage) Meter() flow_metrics.Meter {
 func (d *DocumentUsage) GetFieldValueByOffsetAndKind(offset uintptr, kind reflect.Kind, dataType utils.DataType) interface{} {
 	return utils.GetValueByOffsetAndKind(uintptr(unsafe.Pointer(d)), offset, kind, dataType)
 }
+
+func (d *DocumentDrop) String() string {
+	return fmt.Sprintf("\n{\n\ttimestamp: %d\tFlags: b%b\n\ttag: %+v\n\tmeter: %#v\n}\n",
+		d.Timestamp, d.Flags, d.Tag, d.DropMeter)
+}
+
+var poolDocumentDrop = pool.NewLockFreePool(func() *DocumentDrop {
+	return &DocumentDrop{}
+})
+
+func AcquireDocumentDrop() *DocumentDrop {
+	d := poolDocumentDrop.Get()
+	d.ReferenceCount.Reset()
+	return d
+}
+
+func ReleaseDocumentDrop(doc *DocumentDrop) {
+	if doc == nil || doc.SubReferenceCount() {
+		return
+	}
+
+	*doc = DocumentDrop{}
+	poolDocumentDrop.Put(doc)
+}
+
+func (d *DocumentDrop) Release() {
+	ReleaseDocumentDrop(d)
+}
+
+func (d *DocumentDrop) WriteBlock(block *ckdb.Block) {
+	d.Tag.WriteBlock(block, d.Timestamp)
+	d.DropMeter.WriteBlock(block)
+}
+
+func (d *DocumentDrop) Meter() flow_metrics.Meter {
+	return &d.DropMeter
+}
+
+func (d *DocumentDrop) GetFieldValueByOffsetAndKind(offset uintptr, kind reflect.Kind, dataType utils.DataType) interface{} {
+	return utils.GetValueByOffsetAndKind(uintptr(unsafe.Pointer(d)), offset, kind, dataType)
+}
+
+func (d *DocumentFileIo) String() string {
+	return fmt.Sprintf("\n{\n\ttimestamp: %d\tFlags: b%b\n\ttag: %+v\n\tmeter: %#v\n}\n",
+		d.Timestamp, d.Flags, d.Tag, d.FileIoMeter)
+}
+
+var poolDocumentFileIo = pool.NewLockFreePool(func() *DocumentFileIo {
+	return &DocumentFileIo{}
+})
+
+func AcquireDocumentFileIo() *DocumentFileIo {
+	d := poolDocumentFileIo.Get()
+	d.ReferenceCount.Reset()
+	return d
+}
+
+func ReleaseDocumentFileIo(doc *DocumentFileIo) {
+	if doc == nil || doc.SubReferenceCount() {
+		return
+	}
+
+	*doc = DocumentFileIo{}
+	poolDocumentFileIo.Put(doc)
+}
+
+func (d *DocumentFileIo) Release() {
+	ReleaseDocumentFileIo(d)
+}
+
+func (d *DocumentFileIo) WriteBlock(block *ckdb.Block) {
+	d.Tag.WriteBlock(block, d.Timestamp)
+	d.FileIoMeter.WriteBlock(block)
+}
+
+func (d *DocumentFileIo) Meter() flow_metrics.Meter {
+	return &d.FileIoMeter
+}
+
+func (d *DocumentFileIo) GetFieldValueByOffsetAndKind(offset uintptr, kind reflect.Kind, dataType utils.DataType) interface{} {
+	return utils.GetValueByOffsetAndKind(uintptr(unsafe.Pointer(d)), offset, kind, dataType)
+}
+
+func (d *DocumentTalker) String() string {
+	return fmt.Sprintf("\n{\n\ttimestamp: %d\tFlags: b%b\n\ttag: %+v\n\tmeter: %#v\n}\n",
+		d.Timestamp, d.Flags, d.Tag, d.TalkerMeter)
+}
+
+var poolDocumentTalker = pool.NewLockFreePool(func() *DocumentTalker {
+	return &DocumentTalker{}
+})
+
+func AcquireDocumentTalker() *DocumentTalker {
+	d := poolDocumentTalker.Get()
+	d.ReferenceCount.Reset()
+	return d
+}
+
+func ReleaseDocumentTalker(doc *DocumentTalker) {
+	if doc == nil || doc.SubReferenceCount() {
+		return
+	}
+
+	*doc = DocumentTalker{}
+	poolDocumentTalker.Put(doc)
+}
+
+func (d *DocumentTalker) Release() {
+	ReleaseDocumentTalker(d)
+}
+
+func (d *DocumentTalker) WriteBlock(block *ckdb.Block) {
+	d.Tag.WriteBlock(block, d.Timestamp)
+	d.TalkerMeter.WriteBlock(block)
+}
+
+func (d *DocumentTalker) Meter() flow_metrics.Meter {
+	return &d.TalkerMeter
+}
+
+func (d *DocumentTalker) GetFieldValueByOffsetAndKind(offset uintptr, kind reflect.Kind, dataType utils.DataType) interface{} {
+	return utils.GetValueByOffsetAndKind(uintptr(unsafe.Pointer(d)), offset, kind, dataType)
+}
+
+func (d *DocumentDevice) String() string {
+	return fmt.Sprintf("\n{\n\ttimestamp: %d\tFlags: b%b\n\ttag: %+v\n\tmeter: %#v\n}\n",
+		d.Timestamp, d.Flags, d.Tag, d.DeviceMeter)
+}
+
+var poolDocumentDevice = pool.NewLockFreePool(func() *DocumentDevice {
+	return &DocumentDevice{}
+})
+
+func AcquireDocumentDevice() *DocumentDevice {
+	d := poolDocumentDevice.Get()
+	d.ReferenceCount.Reset()
+	return d
+}
+
+func ReleaseDocumentDevice(doc *DocumentDevice) {
+	if doc == nil || doc.SubReferenceCount() {
+		return
+	}
+
+	*doc = DocumentDevice{}
+	poolDocumentDevice.Put(doc)
+}
+
+func (d *DocumentDevice) Release() {
+	ReleaseDocumentDevice(d)
+}
+
+func (d *DocumentDevice) WriteBlock(block *ckdb.Block) {
+	d.Tag.WriteBlock(block, d.Timestamp)
+	d.DeviceMeter.WriteBlock(block)
+}
+
+func (d *DocumentDevice) Meter() flow_metrics.Meter {
+	return &d.DeviceMeter
+}
+
+func (d *DocumentDevice) GetFieldValueByOffsetAndKind(offset uintptr, kind reflect.Kind, dataType utils.DataType) interface{} {
+	return utils.GetValueByOffsetAndKind(uintptr(unsafe.Pointer(d)), offset, kind, dataType)
+}
@@ -197,22 +197,39 @@ type FlowHeaderOld struct {
 const (
 	LATEST_VERSION = 0x8000 // v6.5 version
 
-	VERSION_OFFSET   = 0
-	ENCODER_OFFSET   = VERSION_OFFSET + 2
-	TEAMID_OFFSET    = ENCODER_OFFSET + 1
-	ORGID_OFFSET     = TEAMID_OFFSET + 4
-	RESERVED1_OFFSET = ORGID_OFFSET + 2
-	AGENTID_OFFSET   = RESERVED1_OFFSET + 2
+	VERSION_OFFSET  = 0
+	ENCODER_OFFSET  = VERSION_OFFSET + 2
+	TEAMID_OFFSET   = ENCODER_OFFSET + 1
+	ORGID_OFFSET    = TEAMID_OFFSET + 4
+	SEQUENCE_OFFSET = ORGID_OFFSET + 2
+	AGENTID_OFFSET  = SEQUENCE_OFFSET + 2
+	FLAGS_OFFSET    = AGENTID_OFFSET + 2
+)
+
+// compression algorithms of the payload following the header
+const (
+	ENCODER_NONE uint8 = iota
+	ENCODER_ZSTD
+	ENCODER_LZ4
+)
+
+// names of the compression algorithms decompressed by the receiver, advertised to agents by the controller
+var SUPPORTED_ENCODER_NAMES = []string{"zstd", "lz4"}
+
+// flags of the header
+const (
+	// the frame is replayed from the disk buffer of the agent, and may have been received before
+	FLAG_REPLAYED uint8 = 1 << iota
 )
 
 type FlowHeader struct {
-	Version   uint16 // start with 0x8000
-	Encoder   uint8  // Flag whether to use compression etc.
-	TeamID    uint32
-	OrgID     uint16
-	Reserved1 uint16
-	AgentID   uint16
-	Reserved2 uint8
+	Version  uint16 // start with 0x8000
+	Encoder  uint8  // Flag whether to use compression etc.
+	TeamID   uint32
+	OrgID    uint16
+	Sequence uint16 // lower 16 bits of the frame sequence of the agent sender
+	AgentID  uint16
+	Flags    uint8
 }
 
 func (h *FlowHeader) Decode(buf []byte) {
@@ -221,14 +238,17 @@ func (h *FlowHeader) Decode(buf []byte) {
 		h.Encoder = buf[ENCODER_OFFSET]
 		h.TeamID = binary.LittleEndian.Uint32(buf[TEAMID_OFFSET:])
 		h.OrgID = binary.LittleEndian.Uint16(buf[ORGID_OFFSET:])
-		// reserved1
+		h.Sequence = binary.LittleEndian.Uint16(buf[SEQUENCE_OFFSET:])
 		h.AgentID = binary.LittleEndian.Uint16(buf[AGENTID_OFFSET:])
-		// reserved2
+		h.Flags = buf[FLAGS_OFFSET]
 	} else {
 		// decoding the header of the old version (version <= v6.5.8)
+		h.Encoder = ENCODER_NONE
 		h.TeamID = ckdb.DEFAULT_TEAM_ID
 		h.OrgID = ckdb.DEFAULT_ORG_ID
+		h.Sequence = 0
 		h.AgentID = binary.LittleEndian.Uint16(buf[FLOW_VTAPID_OFFSET:])
+		h.Flags = 0
 	}
 }
 
@@ -237,7 +257,7 @@ func (h *FlowHeader) Encode(chunk []byte) {
 	chunk[ENCODER_OFFSET] = h.Encoder
 	binary.LittleEndian.PutUint32(chunk[TEAMID_OFFSET:], h.TeamID)
 	binary.LittleEndian.PutUint16(chunk[ORGID_OFFSET:], h.OrgID)
-	// reserved1
+	binary.LittleEndian.PutUint16(chunk[SEQUENCE_OFFSET:], h.Sequence)
 	binary.LittleEndian.PutUint16(chunk[AGENTID_OFFSET:], h.AgentID)
-	// reserved2
+	chunk[FLAGS_OFFSET] = h.Flags
 }
@@ -151,6 +151,7 @@ const (
 	L7_PROTOCOL_UNKNOWN   L7Protocol = 0
 	L7_PROTOCOL_HTTP_1    L7Protocol = 20
 	L7_PROTOCOL_HTTP_2    L7Protocol = 21
+	L7_PROTOCOL_WEBSOCKET L7Protocol = 22
 	L7_PROTOCOL_DUBBO     L7Protocol = 40
 	L7_PROTOCOL_GRPC      L7Protocol = 41
 	L7_PROTOCOL_SOFARPC   L7Protocol = 43
@@ -164,6 +165,7 @@ const (
 	L7_PROTOCOL_REDIS     L7Protocol = 80
 	L7_PROTOCOL_MONGODB   L7Protocol = 81
 	L7_PROTOCOL_MEMCACHED L7Protocol = 82
+	L7_PROTOCOL_MODBUS    L7Protocol = 90
 	L7_PROTOCOL_KAFKA     L7Protocol = 100
 	L7_PROTOCOL_MQTT      L7Protocol = 101
 	L7_PROTOCOL_AMQP      L7Protocol = 102
@@ -171,8 +173,14 @@ const (
 	L7_PROTOCOL_NATS      L7Protocol = 104
 	L7_PROTOCOL_PULSAR    L7Protocol = 105
 	L7_PROTOCOL_ZMTP      L7Protocol = 106
+	L7_PROTOCOL_STOMP     L7Protocol = 107
 	L7_PROTOCOL_DNS       L7Protocol = 120
 	L7_PROTOCOL_TLS       L7Protocol = 121
+	L7_PROTOCOL_QUIC      L7Protocol = 122
+	L7_PROTOCOL_KERBEROS  L7Protocol = 123
+	L7_PROTOCOL_SMB       L7Protocol = 124
+	L7_PROTOCOL_NFS       L7Protocol = 125
+	L7_PROTOCOL_DIAMETER  L7Protocol = 126
 	L7_PROTOCOL_CUSTOM    L7Protocol = 127
 )
 
@@ -628,6 +636,12 @@ func (p L7Protocol) String(isTLS bool) string {
 		} else {
 			return "HTTP2"
 		}
+	case L7_PROTOCOL_WEBSOCKET:
+		if isTLS {
+			return "WebSocket_TLS"
+		} else {
+			return "WebSocket"
+		}
 	case L7_PROTOCOL_DUBBO:
 		if isTLS {
 			return "Dubbo_TLS"
@@ -706,6 +720,12 @@ func (p L7Protocol) String(isTLS bool) string {
 		} else {
 			return "Memcached"
 		}
+	case L7_PROTOCOL_MODBUS:
+		if isTLS {
+			return "Modbus_TLS"
+		} else {
+			return "Modbus"
+		}
 	case L7_PROTOCOL_KAFKA:
 		if isTLS {
 			return "Kafka_TLS"
@@ -748,6 +768,12 @@ func (p L7Protocol) String(isTLS bool) string {
 		} else {
 			return "ZMTP"
 		}
+	case L7_PROTOCOL_STOMP:
+		if isTLS {
+			return "STOMP_TLS"
+		} else {
+			return "STOMP"
+		}
 	case L7_PROTOCOL_DNS:
 		if isTLS {
 			return "DNS_TLS"
@@ -756,6 +782,16 @@ func (p L7Protocol) String(isTLS bool) string {
 		}
 	case L7_PROTOCOL_TLS:
 		return "TLS"
+	case L7_PROTOCOL_QUIC:
+		return "QUIC"
+	case L7_PROTOCOL_KERBEROS:
+		return "Kerberos"
+	case L7_PROTOCOL_SMB:
+		return "SMB"
+	case L7_PROTOCOL_NFS:
+		return "NFS"
+	case L7_PROTOCOL_DIAMETER:
+		return "Diameter"
 	case L7_PROTOCOL_CUSTOM:
 		if isTLS {
 			return "Custom_TLS"
@@ -768,31 +804,39 @@ func (p L7Protocol) String(isTLS bool) string {
 }
 
 var L7ProtocolStringMap = map[string]L7Protocol{
-	strings.ToLower(L7_PROTOCOL_HTTP_1.String(false)):   L7_PROTOCOL_HTTP_1,
-	strings.ToLower(L7_PROTOCOL_HTTP_2.String(false)):   L7_PROTOCOL_HTTP_2,
-	strings.ToLower(L7_PROTOCOL_DUBBO.String(false)):    L7_PROTOCOL_DUBBO,
-	strings.ToLower(L7_PROTOCOL_GRPC.String(false)):     L7_PROTOCOL_GRPC,
-	strings.ToLower(L7_PROTOCOL_SOFARPC.String(false)):  L7_PROTOCOL_SOFARPC,
-	strings.ToLower(L7_PROTOCOL_FASTCGI.String(false)):  L7_PROTOCOL_FASTCGI,
-	strings.ToLower(L7_PROTOCOL_BRPC.String(false)):     L7_PROTOCOL_BRPC,
-	strings.ToLower(L7_PROTOCOL_TARS.String(false)):     L7_PROTOCOL_TARS,
-	strings.ToLower(L7_PROTOCOL_SOME_IP.String(false)):  L7_PROTOCOL_SOME_IP,
-	strings.ToLower(L7_PROTOCOL_MYSQL.String(false)):    L7_PROTOCOL_MYSQL,
-	strings.ToLower(L7_PROTOCOL_POSTGRE.String(false)):  L7_PROTOCOL_POSTGRE,
-	strings.ToLower(L7_PROTOCOL_ORACLE.String(false)):   L7_PROTOCOL_ORACLE,
-	strings.ToLower(L7_PROTOCOL_REDIS.String(false)):    L7_PROTOCOL_REDIS,
-	strings.ToLower(L7_PROTOCOL_MONGODB.String(false)):  L7_PROTOCOL_MONGODB,
-	strings.ToLower(L7_PROTOCOL_KAFKA.String(false)):    L7_PROTOCOL_KAFKA,
-	strings.ToLower(L7_PROTOCOL_MQTT.String(false)):     L7_PROTOCOL_MQTT,
-	strings.ToLower(L7_PROTOCOL_AMQP.String(false)):     L7_PROTOCOL_AMQP,
-	strings.ToLower(L7_PROTOCOL_OPENWIRE.String(false)): L7_PROTOCOL_OPENWIRE,
-	strings.ToLower(L7_PROTOCOL_NATS.String(false)):     L7_PROTOCOL_NATS,
-	strings.ToLower(L7_PROTOCOL_PULSAR.String(false)):   L7_PROTOCOL_PULSAR,
-	strings.ToLower(L7_PROTOCOL_ZMTP.String(false)):     L7_PROTOCOL_ZMTP,
-	strings.ToLower(L7_PROTOCOL_DNS.String(false)):      L7_PROTOCOL_DNS,
-	strings.ToLower(L7_PROTOCOL_TLS.String(false)):      L7_PROTOCOL_TLS,
-	strings.ToLower(L7_PROTOCOL_CUSTOM.String(false)):   L7_PROTOCOL_CUSTOM,
-	strings.ToLower(L7_PROTOCOL_UNKNOWN.String(false)):  L7_PROTOCOL_UNKNOWN,
+	strings.ToLower(L7_PROTOCOL_HTTP_1.String(false)):    L7_PROTOCOL_HTTP_1,
+	strings.ToLower(L7_PROTOCOL_HTTP_2.String(false)):    L7_PROTOCOL_HTTP_2,
+	strings.ToLower(L7_PROTOCOL_WEBSOCKET.String(false)): L7_PROTOCOL_WEBSOCKET,
+	strings.ToLower(L7_PROTOCOL_DUBBO.String(false)):     L7_PROTOCOL_DUBBO,
+	strings.ToLower(L7_PROTOCOL_GRPC.String(false)):      L7_PROTOCOL_GRPC,
+	strings.ToLower(L7_PROTOCOL_SOFARPC.String(false)):   L7_PROTOCOL_SOFARPC,
+	strings.ToLower(L7_PROTOCOL_FASTCGI.String(false)):   L7_PROTOCOL_FASTCGI,
+	strings.ToLower(L7_PROTOCOL_BRPC.String(false)):      L7_PROTOCOL_BRPC,
+	strings.ToLower(L7_PROTOCOL_TARS.String(false)):      L7_PROTOCOL_TARS,
+	strings.ToLower(L7_PROTOCOL_SOME_IP.String(false)):   L7_PROTOCOL_SOME_IP,
+	strings.ToLower(L7_PROTOCOL_MYSQL.String(false)):     L7_PROTOCOL_MYSQL,
+	strings.ToLower(L7_PROTOCOL_POSTGRE.String(false)):   L7_PROTOCOL_POSTGRE,
+	strings.ToLower(L7_PROTOCOL_ORACLE.String(false)):    L7_PROTOCOL_ORACLE,
+	strings.ToLower(L7_PROTOCOL_REDIS.String(false)):     L7_PROTOCOL_REDIS,
+	strings.ToLower(L7_PROTOCOL_MONGODB.String(false)):   L7_PROTOCOL_MONGODB,
+	strings.ToLower(L7_PROTOCOL_MODBUS.String(false)):    L7_PROTOCOL_MODBUS,
+	strings.ToLower(L7_PROTOCOL_KAFKA.String(false)):     L7_PROTOCOL_KAFKA,
+	strings.ToLower(L7_PROTOCOL_MQTT.String(false)):      L7_PROTOCOL_MQTT,
+	strings.ToLower(L7_PROTOCOL_AMQP.String(false)):      L7_PROTOCOL_AMQP,
+	strings.ToLower(L7_PROTOCOL_OPENWIRE.String(false)):  L7_PROTOCOL_OPENWIRE,
+	strings.ToLower(L7_PROTOCOL_NATS.String(false)):      L7_PROTOCOL_NATS,
+	strings.ToLower(L7_PROTOCOL_PULSAR.String(false)):    L7_PROTOCOL_PULSAR,
+	strings.ToLower(L7_PROTOCOL_ZMTP.String(false)):      L7_PROTOCOL_ZMTP,
+	strings.ToLower(L7_PROTOCOL_STOMP.String(false)):     L7_PROTOCOL_STOMP,
+	strings.ToLower(L7_PROTOCOL_DNS.String(false)):       L7_PROTOCOL_DNS,
+	strings.ToLower(L7_PROTOCOL_TLS.String(false)):       L7_PROTOCOL_TLS,
+	strings.ToLower(L7_PROTOCOL_QUIC.String(false)):      L7_PROTOCOL_QUIC,
+	strings.ToLower(L7_PROTOCOL_KERBEROS.String(false)):  L7_PROTOCOL_KERBEROS,
+	strings.ToLower(L7_PROTOCOL_SMB.String(false)):       L7_PROTOCOL_SMB,
+	strings.ToLower(L7_PROTOCOL_NFS.String(false)):       L7_PROTOCOL_NFS,
+	strings.ToLower(L7_PROTOCOL_DIAMETER.String(false)):  L7_PROTOCOL_DIAMETER,
+	strings.ToLower(L7_PROTOCOL_CUSTOM.String(false)):    L7_PROTOCOL_CUSTOM,
+	strings.ToLower(L7_PROTOCOL_UNKNOWN.String(false)):   L7_PROTOCOL_UNKNOWN,
 }
 
 func (p *L4Protocol) String() string {
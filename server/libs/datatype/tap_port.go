@@ -49,9 +49,9 @@ type NATSource uint8
 
 const (
 	NAT_SOURCE_NONE NATSource = iota
-	_
+	NAT_SOURCE_IPVS
 	NAT_SOURCE_VIP
-	_
+	NAT_SOURCE_CONNTRACK
 	NAT_SOURCE_RTOA
 	_
 	NAT_SOURCE_TOA
@@ -61,8 +61,12 @@ func (n NATSource) String() string {
 	switch n {
 	case NAT_SOURCE_NONE:
 		return "NONE"
+	case NAT_SOURCE_IPVS:
+		return "IPVS"
 	case NAT_SOURCE_VIP:
 		return "VIP"
+	case NAT_SOURCE_CONNTRACK:
+		return "CONNTRACK"
 	case NAT_SOURCE_RTOA:
 		return "RTOA"
 	case NAT_SOURCE_TOA:
@@ -17,6 +17,7 @@
 package flow_metrics
 
 import (
+	"math"
 	"strconv"
 	"strings"
 
@@ -28,12 +29,15 @@ type AppMeter struct {
 	AppTraffic
 	AppLatency
 	AppAnomaly
+	AppPayloadSize
+	AppLatencySketch
 }
 
 func (m *AppMeter) Reverse() {
 	m.AppTraffic.Reverse()
 	m.AppLatency.Reverse()
 	m.AppAnomaly.Reverse()
+	m.AppPayloadSize.Reverse()
 }
 
 func (m *AppMeter) ID() uint8 {
@@ -67,12 +71,24 @@ func (m *AppMeter) WriteToPB(p *pb.AppMeter) {
 		p.Anomaly = &pb.AppAnomaly{}
 	}
 	m.AppAnomaly.WriteToPB(p.Anomaly)
+
+	if p.PayloadSize == nil {
+		p.PayloadSize = &pb.AppPayloadSize{}
+	}
+	m.AppPayloadSize.WriteToPB(p.PayloadSize)
+
+	if p.LatencySketch == nil {
+		p.LatencySketch = &pb.AppLatencySketch{}
+	}
+	m.AppLatencySketch.WriteToPB(p.LatencySketch)
 }
 
 func (m *AppMeter) ReadFromPB(p *pb.AppMeter) {
 	m.AppTraffic.ReadFromPB(p.Traffic)
 	m.AppLatency.ReadFromPB(p.Latency)
 	m.AppAnomaly.ReadFromPB(p.Anomaly)
+	m.AppPayloadSize.ReadFromPB(p.PayloadSize)
+	m.AppLatencySketch.ReadFromPB(p.LatencySketch)
 }
 
 func (m *AppMeter) ConcurrentMerge(other Meter) {
@@ -80,6 +96,8 @@ func (m *AppMeter) ConcurrentMerge(other Meter) {
 		m.AppTraffic.ConcurrentMerge(&pm.AppTraffic)
 		m.AppLatency.ConcurrentMerge(&pm.AppLatency)
 		m.AppAnomaly.ConcurrentMerge(&pm.AppAnomaly)
+		m.AppPayloadSize.ConcurrentMerge(&pm.AppPayloadSize)
+		m.AppLatencySketch.ConcurrentMerge(&pm.AppLatencySketch)
 	}
 }
 
@@ -88,6 +106,8 @@ func (m *AppMeter) SequentialMerge(other Meter) {
 		m.AppTraffic.SequentialMerge(&pm.AppTraffic)
 		m.AppLatency.SequentialMerge(&pm.AppLatency)
 		m.AppAnomaly.SequentialMerge(&pm.AppAnomaly)
+		m.AppPayloadSize.SequentialMerge(&pm.AppPayloadSize)
+		m.AppLatencySketch.SequentialMerge(&pm.AppLatencySketch)
 	}
 }
 
@@ -116,6 +136,16 @@ func (m *AppMeter) MarshalTo(b []byte) int {
 		b[offset] = ','
 		offset++
 	}
+	offset += m.AppPayloadSize.MarshalTo(b[offset:])
+	if offset > 0 && b[offset-1] != ',' {
+		b[offset] = ','
+		offset++
+	}
+	offset += m.AppLatencySketch.MarshalTo(b[offset:])
+	if offset > 0 && b[offset-1] != ',' {
+		b[offset] = ','
+		offset++
+	}
 
 	return offset
 }
@@ -125,6 +155,8 @@ func AppMeterColumns() []*ckdb.Column {
 	columns = append(columns, AppTrafficColumns()...)
 	columns = append(columns, AppLatencyColumns()...)
 	columns = append(columns, AppAnomalyColumns()...)
+	columns = append(columns, AppPayloadSizeColumns()...)
+	columns = append(columns, AppLatencySketchColumns()...)
 	return columns
 }
 
@@ -132,6 +164,8 @@ func (m *AppMeter) WriteBlock(block *ckdb.Block) {
 	m.AppTraffic.WriteBlock(block)
 	m.AppLatency.WriteBlock(block)
 	m.AppAnomaly.WriteBlock(block)
+	m.AppPayloadSize.WriteBlock(block)
+	m.AppLatencySketch.WriteBlock(block)
 }
 
 type AppTraffic struct {
@@ -320,6 +354,315 @@ func (a *AppAnomaly) WriteBlock(block *ckdb.Block) {
 	block.Write(uint64(a.ClientError), uint64(a.ServerError), uint64(a.Timeout), uint64(a.ClientError+a.ServerError))
 }
 
+// 请求、响应的载荷长度直方图, 各桶的上界(含)依次为64B, 256B, 1KB, 4KB, 16KB, 64KB, 256KB, 1MB, 最后一个桶统计更长的载荷
+type AppPayloadSize struct {
+	RequestSizeLe64   uint32 `json:"request_size_le_64" category:"$metrics" sub:"throughput"`
+	RequestSizeLe256  uint32 `json:"request_size_le_256" category:"$metrics" sub:"throughput"`
+	RequestSizeLe1K   uint32 `json:"request_size_le_1k" category:"$metrics" sub:"throughput"`
+	RequestSizeLe4K   uint32 `json:"request_size_le_4k" category:"$metrics" sub:"throughput"`
+	RequestSizeLe16K  uint32 `json:"request_size_le_16k" category:"$metrics" sub:"throughput"`
+	RequestSizeLe64K  uint32 `json:"request_size_le_64k" category:"$metrics" sub:"throughput"`
+	RequestSizeLe256K uint32 `json:"request_size_le_256k" category:"$metrics" sub:"throughput"`
+	RequestSizeLe1M   uint32 `json:"request_size_le_1m" category:"$metrics" sub:"throughput"`
+	RequestSizeGt1M   uint32 `json:"request_size_gt_1m" category:"$metrics" sub:"throughput"`
+
+	ResponseSizeLe64   uint32 `json:"response_size_le_64" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe256  uint32 `json:"response_size_le_256" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe1K   uint32 `json:"response_size_le_1k" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe4K   uint32 `json:"response_size_le_4k" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe16K  uint32 `json:"response_size_le_16k" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe64K  uint32 `json:"response_size_le_64k" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe256K uint32 `json:"response_size_le_256k" category:"$metrics" sub:"throughput"`
+	ResponseSizeLe1M   uint32 `json:"response_size_le_1m" category:"$metrics" sub:"throughput"`
+	ResponseSizeGt1M   uint32 `json:"response_size_gt_1m" category:"$metrics" sub:"throughput"`
+
+	RequestSizeSum  uint64 `json:"request_size_sum" category:"$metrics" sub:"throughput"`  // B
+	ResponseSizeSum uint64 `json:"response_size_sum" category:"$metrics" sub:"throughput"` // B
+	RequestSizeMax  uint32 `json:"request_size_max" category:"$metrics" sub:"throughput"`  // B
+	ResponseSizeMax uint32 `json:"response_size_max" category:"$metrics" sub:"throughput"` // B
+}
+
+const APP_PAYLOAD_SIZE_BUCKETS = 9
+
+func (s *AppPayloadSize) requestBuckets() [APP_PAYLOAD_SIZE_BUCKETS]*uint32 {
+	return [APP_PAYLOAD_SIZE_BUCKETS]*uint32{
+		&s.RequestSizeLe64, &s.RequestSizeLe256, &s.RequestSizeLe1K, &s.RequestSizeLe4K, &s.RequestSizeLe16K,
+		&s.RequestSizeLe64K, &s.RequestSizeLe256K, &s.RequestSizeLe1M, &s.RequestSizeGt1M,
+	}
+}
+
+func (s *AppPayloadSize) responseBuckets() [APP_PAYLOAD_SIZE_BUCKETS]*uint32 {
+	return [APP_PAYLOAD_SIZE_BUCKETS]*uint32{
+		&s.ResponseSizeLe64, &s.ResponseSizeLe256, &s.ResponseSizeLe1K, &s.ResponseSizeLe4K, &s.ResponseSizeLe16K,
+		&s.ResponseSizeLe64K, &s.ResponseSizeLe256K, &s.ResponseSizeLe1M, &s.ResponseSizeGt1M,
+	}
+}
+
+func (s *AppPayloadSize) Reverse() {
+	// 与agent一致, 交换请求和响应
+	s.RequestSizeLe64, s.ResponseSizeLe64 = s.ResponseSizeLe64, s.RequestSizeLe64
+	s.RequestSizeLe256, s.ResponseSizeLe256 = s.ResponseSizeLe256, s.RequestSizeLe256
+	s.RequestSizeLe1K, s.ResponseSizeLe1K = s.ResponseSizeLe1K, s.RequestSizeLe1K
+	s.RequestSizeLe4K, s.ResponseSizeLe4K = s.ResponseSizeLe4K, s.RequestSizeLe4K
+	s.RequestSizeLe16K, s.ResponseSizeLe16K = s.ResponseSizeLe16K, s.RequestSizeLe16K
+	s.RequestSizeLe64K, s.ResponseSizeLe64K = s.ResponseSizeLe64K, s.RequestSizeLe64K
+	s.RequestSizeLe256K, s.ResponseSizeLe256K = s.ResponseSizeLe256K, s.RequestSizeLe256K
+	s.RequestSizeLe1M, s.ResponseSizeLe1M = s.ResponseSizeLe1M, s.RequestSizeLe1M
+	s.RequestSizeGt1M, s.ResponseSizeGt1M = s.ResponseSizeGt1M, s.RequestSizeGt1M
+	s.RequestSizeSum, s.ResponseSizeSum = s.ResponseSizeSum, s.RequestSizeSum
+	s.RequestSizeMax, s.ResponseSizeMax = s.ResponseSizeMax, s.RequestSizeMax
+}
+
+func (s *AppPayloadSize) WriteToPB(p *pb.AppPayloadSize) {
+	p.RequestBuckets = p.RequestBuckets[:0]
+	for _, v := range s.requestBuckets() {
+		p.RequestBuckets = append(p.RequestBuckets, *v)
+	}
+	p.ResponseBuckets = p.ResponseBuckets[:0]
+	for _, v := range s.responseBuckets() {
+		p.ResponseBuckets = append(p.ResponseBuckets, *v)
+	}
+	p.RequestSum = s.RequestSizeSum
+	p.ResponseSum = s.ResponseSizeSum
+	p.RequestMax = s.RequestSizeMax
+	p.ResponseMax = s.ResponseSizeMax
+}
+
+// 老版本agent不发送payload_size, 此时p为nil
+func (s *AppPayloadSize) ReadFromPB(p *pb.AppPayloadSize) {
+	*s = AppPayloadSize{}
+	if p == nil {
+		return
+	}
+	for i, v := range s.requestBuckets() {
+		if i < len(p.RequestBuckets) {
+			*v = p.RequestBuckets[i]
+		}
+	}
+	for i, v := range s.responseBuckets() {
+		if i < len(p.ResponseBuckets) {
+			*v = p.ResponseBuckets[i]
+		}
+	}
+	s.RequestSizeSum = p.RequestSum
+	s.ResponseSizeSum = p.ResponseSum
+	s.RequestSizeMax = p.RequestMax
+	s.ResponseSizeMax = p.ResponseMax
+}
+
+func (s *AppPayloadSize) ConcurrentMerge(other *AppPayloadSize) {
+	buckets, otherBuckets := s.requestBuckets(), other.requestBuckets()
+	for i := range buckets {
+		*buckets[i] += *otherBuckets[i]
+	}
+	buckets, otherBuckets = s.responseBuckets(), other.responseBuckets()
+	for i := range buckets {
+		*buckets[i] += *otherBuckets[i]
+	}
+	s.RequestSizeSum += other.RequestSizeSum
+	s.ResponseSizeSum += other.ResponseSizeSum
+	if s.RequestSizeMax < other.RequestSizeMax {
+		s.RequestSizeMax = other.RequestSizeMax
+	}
+	if s.ResponseSizeMax < other.ResponseSizeMax {
+		s.ResponseSizeMax = other.ResponseSizeMax
+	}
+}
+
+func (s *AppPayloadSize) SequentialMerge(other *AppPayloadSize) {
+	s.ConcurrentMerge(other)
+}
+
+func (s *AppPayloadSize) MarshalTo(b []byte) int {
+	fields := make([]string, 0, len(appPayloadSizeColumns))
+	for _, c := range appPayloadSizeColumns {
+		fields = append(fields, c[0]+"=")
+	}
+	return marshalKeyValues(b, fields, s.values())
+}
+
+func (s *AppPayloadSize) values() []uint64 {
+	values := make([]uint64, 0, APP_PAYLOAD_SIZE_BUCKETS*2+4)
+	for _, v := range s.requestBuckets() {
+		values = append(values, uint64(*v))
+	}
+	for _, v := range s.responseBuckets() {
+		values = append(values, uint64(*v))
+	}
+	return append(values, s.RequestSizeSum, s.ResponseSizeSum, uint64(s.RequestSizeMax), uint64(s.ResponseSizeMax))
+}
+
+// Columns列和WriteBlock的列需要按顺序一一对应
+var appPayloadSizeColumns = [][2]string{
+	{"request_size_le_64", "请求载荷长度不超过64B的次数"},
+	{"request_size_le_256", "请求载荷长度在(64B, 256B]的次数"},
+	{"request_size_le_1k", "请求载荷长度在(256B, 1KB]的次数"},
+	{"request_size_le_4k", "请求载荷长度在(1KB, 4KB]的次数"},
+	{"request_size_le_16k", "请求载荷长度在(4KB, 16KB]的次数"},
+	{"request_size_le_64k", "请求载荷长度在(16KB, 64KB]的次数"},
+	{"request_size_le_256k", "请求载荷长度在(64KB, 256KB]的次数"},
+	{"request_size_le_1m", "请求载荷长度在(256KB, 1MB]的次数"},
+	{"request_size_gt_1m", "请求载荷长度超过1MB的次数"},
+
+	{"response_size_le_64", "响应载荷长度不超过64B的次数"},
+	{"response_size_le_256", "响应载荷长度在(64B, 256B]的次数"},
+	{"response_size_le_1k", "响应载荷长度在(256B, 1KB]的次数"},
+	{"response_size_le_4k", "响应载荷长度在(1KB, 4KB]的次数"},
+	{"response_size_le_16k", "响应载荷长度在(4KB, 16KB]的次数"},
+	{"response_size_le_64k", "响应载荷长度在(16KB, 64KB]的次数"},
+	{"response_size_le_256k", "响应载荷长度在(64KB, 256KB]的次数"},
+	{"response_size_le_1m", "响应载荷长度在(256KB, 1MB]的次数"},
+	{"response_size_gt_1m", "响应载荷长度超过1MB的次数"},
+
+	{"request_size_sum", "累计请求载荷长度(B)"},
+	{"response_size_sum", "累计响应载荷长度(B)"},
+	{"request_size_max", "请求载荷长度最大值(B)"},
+	{"response_size_max", "响应载荷长度最大值(B)"},
+}
+
+func AppPayloadSizeColumns() []*ckdb.Column {
+	return ckdb.NewColumnsWithComment(appPayloadSizeColumns, ckdb.UInt64)
+}
+
+// WriteBlock和PayloadSizeColumns的列需要按顺序一一对应
+func (s *AppPayloadSize) WriteBlock(block *ckdb.Block) {
+	for _, v := range s.values() {
+		block.Write(v)
+	}
+}
+
+const (
+	// gamma = (1 + 5%) / (1 - 5%), 与agent一致
+	APP_LATENCY_SKETCH_LN_GAMMA = 0.10008345855698263
+	APP_LATENCY_SKETCH_BUCKETS  = 64
+)
+
+// 响应时延(us)的DDSketch, 第i个桶统计(gamma^(offset+i-1), gamma^(offset+i)]内的时延,
+// 第一个桶同时统计所有更小的时延. 不直接存储, 写入时计算分位数
+type AppLatencySketch struct {
+	Offset  int32
+	Count   uint32
+	Buckets [APP_LATENCY_SKETCH_BUCKETS]uint32
+}
+
+func (_ *AppLatencySketch) Reverse() {
+	// 时延以客户端、服务端为视角，无需Reverse
+}
+
+func (l *AppLatencySketch) WriteToPB(p *pb.AppLatencySketch) {
+	p.Offset = l.Offset
+	p.Buckets = append(p.Buckets[:0], l.Buckets[:]...)
+}
+
+// 老版本agent不发送latency_sketch, 此时p为nil
+func (l *AppLatencySketch) ReadFromPB(p *pb.AppLatencySketch) {
+	*l = AppLatencySketch{}
+	if p == nil {
+		return
+	}
+	for i := len(p.Buckets) - 1; i >= 0; i-- {
+		if p.Buckets[i] > 0 {
+			l.addN(p.Offset+int32(i), p.Buckets[i])
+		}
+	}
+}
+
+// 最大的桶超出范围时上移所有桶, 合并最低的若干桶
+func (l *AppLatencySketch) shiftTo(maxIndex int32) {
+	newOffset := maxIndex - APP_LATENCY_SKETCH_BUCKETS + 1
+	d := int(newOffset - l.Offset)
+	if d >= APP_LATENCY_SKETCH_BUCKETS {
+		l.Buckets = [APP_LATENCY_SKETCH_BUCKETS]uint32{}
+		l.Buckets[0] = l.Count
+	} else {
+		collapsed := uint32(0)
+		for _, n := range l.Buckets[:d+1] {
+			collapsed += n
+		}
+		copy(l.Buckets[:], l.Buckets[d:])
+		for i := APP_LATENCY_SKETCH_BUCKETS - d; i < APP_LATENCY_SKETCH_BUCKETS; i++ {
+			l.Buckets[i] = 0
+		}
+		l.Buckets[0] = collapsed
+	}
+	l.Offset = newOffset
+}
+
+func (l *AppLatencySketch) addN(index int32, n uint32) {
+	if l.Count == 0 {
+		l.Offset = index - APP_LATENCY_SKETCH_BUCKETS + 1
+	} else if index >= l.Offset+APP_LATENCY_SKETCH_BUCKETS {
+		l.shiftTo(index)
+	}
+	pos := index - l.Offset
+	if pos < 0 {
+		pos = 0
+	}
+	l.Buckets[pos] += n
+	l.Count += n
+}
+
+func (l *AppLatencySketch) ConcurrentMerge(other *AppLatencySketch) {
+	if other.Count == 0 {
+		return
+	}
+	for i := APP_LATENCY_SKETCH_BUCKETS - 1; i >= 0; i-- {
+		if other.Buckets[i] > 0 {
+			l.addN(other.Offset+int32(i), other.Buckets[i])
+		}
+	}
+}
+
+func (l *AppLatencySketch) SequentialMerge(other *AppLatencySketch) {
+	l.ConcurrentMerge(other)
+}
+
+// q取值[0, 1], 没有时延时返回0
+func (l *AppLatencySketch) Quantile(q float64) uint32 {
+	if l.Count == 0 {
+		return 0
+	}
+	rank := uint64(math.Max(0, math.Min(1, q)) * float64(l.Count-1))
+	n := uint64(0)
+	for i, c := range l.Buckets {
+		n += uint64(c)
+		if n > rank {
+			// 取桶内相对误差的中点
+			index := float64(l.Offset + int32(i))
+			gamma := math.Exp(APP_LATENCY_SKETCH_LN_GAMMA)
+			return uint32(math.Round(2 * math.Exp(index*APP_LATENCY_SKETCH_LN_GAMMA) / (gamma + 1)))
+		}
+	}
+	return 0
+}
+
+func (l *AppLatencySketch) MarshalTo(b []byte) int {
+	fields := []string{"rrt_p50=", "rrt_p90=", "rrt_p99="}
+	values := []uint64{uint64(l.Quantile(0.5)), uint64(l.Quantile(0.9)), uint64(l.Quantile(0.99))}
+	return marshalKeyValues(b, fields, values)
+}
+
+const (
+	APPLATENCYSKETCH_RRT_P50 = iota
+	APPLATENCYSKETCH_RRT_P90
+	APPLATENCYSKETCH_RRT_P99
+)
+
+// Columns列和WriteBlock的列需要按顺序一一对应
+func AppLatencySketchColumns() []*ckdb.Column {
+	return ckdb.NewColumnsWithComment(
+		[][2]string{
+			APPLATENCYSKETCH_RRT_P50: {"rrt_p50", "请求响应时延P50(us), 相对误差5%"},
+			APPLATENCYSKETCH_RRT_P90: {"rrt_p90", "请求响应时延P90(us), 相对误差5%"},
+			APPLATENCYSKETCH_RRT_P99: {"rrt_p99", "请求响应时延P99(us), 相对误差5%"},
+		}, ckdb.UInt32)
+}
+
+// WriteBlock和LatencySketchColumns的列需要按顺序一一对应
+func (l *AppLatencySketch) WriteBlock(block *ckdb.Block) {
+	block.Write(l.Quantile(0.5), l.Quantile(0.9), l.Quantile(0.99))
+}
+
 func EncodeAppMeterToMetrics(meter *AppMeter) map[string]float64 {
 	if meter == nil {
 		return nil
@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package flow_metrics
+
+import (
+	"testing"
+
+	"github.com/deepflowio/deepflow/server/libs/flow-metrics/pb"
+)
+
+func TestAppPayloadSize(t *testing.T) {
+	if len(AppPayloadSizeColumns()) != len((&AppPayloadSize{}).values()) {
+		t.Fatalf("AppPayloadSizeColumns() and WriteBlock() mismatch")
+	}
+
+	s := &AppPayloadSize{}
+	s.ReadFromPB(&pb.AppPayloadSize{
+		RequestBuckets:  []uint32{1, 0, 2, 0, 0, 0, 0, 0, 3},
+		ResponseBuckets: []uint32{4},
+		RequestSum:      100,
+		RequestMax:      2000000,
+	})
+	if s.RequestSizeLe64 != 1 || s.RequestSizeLe1K != 2 || s.RequestSizeGt1M != 3 || s.ResponseSizeLe64 != 4 {
+		t.Errorf("ReadFromPB() buckets incorrect: %+v", s)
+	}
+
+	s.ConcurrentMerge(&AppPayloadSize{RequestSizeGt1M: 1, RequestSizeSum: 10, RequestSizeMax: 5})
+	if s.RequestSizeGt1M != 4 || s.RequestSizeSum != 110 || s.RequestSizeMax != 2000000 {
+		t.Errorf("ConcurrentMerge() incorrect: %+v", s)
+	}
+
+	s.Reverse()
+	if s.ResponseSizeGt1M != 4 || s.RequestSizeLe64 != 4 || s.ResponseSizeSum != 110 || s.RequestSizeSum != 0 {
+		t.Errorf("Reverse() incorrect: %+v", s)
+	}
+
+	s.ReadFromPB(nil)
+	if *s != (AppPayloadSize{}) {
+		t.Errorf("ReadFromPB(nil) should reset: %+v", s)
+	}
+}
+
+func TestAppLatencySketch(t *testing.T) {
+	if len(AppLatencySketchColumns()) != 3 {
+		t.Fatalf("AppLatencySketchColumns() and WriteBlock() mismatch")
+	}
+
+	// 1000us falls in bucket ceil(ln(1000) / ln(gamma)) = 70
+	l := &AppLatencySketch{}
+	l.ReadFromPB(&pb.AppLatencySketch{Offset: 70, Buckets: []uint32{10}})
+	if p := l.Quantile(0.5); p < 950 || p > 1050 {
+		t.Errorf("Quantile(0.5) = %d, expected about 1000", p)
+	}
+
+	// 1000000us falls in bucket 139, out of the range of bucket 70, the lower buckets collapse
+	other := &AppLatencySketch{}
+	other.ReadFromPB(&pb.AppLatencySketch{Offset: 139, Buckets: []uint32{90}})
+	l.ConcurrentMerge(other)
+	if l.Count != 100 {
+		t.Errorf("Count = %d, expected 100", l.Count)
+	}
+	if p := l.Quantile(0.99); p < 950000 || p > 1050000 {
+		t.Errorf("Quantile(0.99) = %d, expected about 1000000", p)
+	}
+
+	l.ReadFromPB(nil)
+	if l.Count != 0 || l.Quantile(0.5) != 0 {
+		t.Errorf("ReadFromPB(nil) should reset: %+v", l)
+	}
+}
@@ -31,6 +31,10 @@ const (
 	_      // PACKET_ID，已删除
 	ACL_ID // 目前meter的ACL_ID和PACKET_ID内容一致
 	APP_ID
+	DROP_ID
+	FILE_IO_ID
+	TALKER_ID
+	DEVICE_ID
 
 	MAX_APP_ID
 )
@@ -42,6 +46,10 @@ var MeterVTAPNames [MAX_APP_ID]string = [MAX_APP_ID]string{
 	"vtap_packet",
 	"vtap_acl",
 	"vtap_app",
+	"vtap_drop",
+	"vtap_file_io",
+	"vtap_talker",
+	"vtap_device",
 }
 
 var MeterNamesToID map[string]uint8
@@ -224,7 +224,7 @@ func (t *CustomTag) SetID(id string) {
 	t.id = id
 }
 
-func (t *CustomTag) GetTAPType() uint8 {
+func (t *CustomTag) GetTAPType() uint16 {
 	panic("not implemented")
 }
 
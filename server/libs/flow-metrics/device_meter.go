@@ -0,0 +1,231 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package flow_metrics
+
+import (
+	"strconv"
+
+	"github.com/deepflowio/deepflow/server/libs/ckdb"
+	"github.com/deepflowio/deepflow/server/libs/flow-metrics/pb"
+)
+
+// interface counters and health of a network device polled by SNMP on the agent
+type DeviceMeter struct {
+	MeterType uint8 `json:"meter_type" category:"$metrics" sub:"device"` // 0: health, 1: interface
+
+	// interface, the counters are increments since the last poll
+	RxBytes    uint64 `json:"rx_bytes" category:"$metrics" sub:"device"`
+	TxBytes    uint64 `json:"tx_bytes" category:"$metrics" sub:"device"`
+	RxPackets  uint64 `json:"rx_packets" category:"$metrics" sub:"device"`
+	TxPackets  uint64 `json:"tx_packets" category:"$metrics" sub:"device"`
+	RxErrors   uint64 `json:"rx_errors" category:"$metrics" sub:"device"`
+	TxErrors   uint64 `json:"tx_errors" category:"$metrics" sub:"device"`
+	RxDiscards uint64 `json:"rx_discards" category:"$metrics" sub:"device"`
+	TxDiscards uint64 `json:"tx_discards" category:"$metrics" sub:"device"`
+	Speed      uint64 `json:"speed" category:"$metrics" sub:"device"`       // bps
+	OperStatus uint8  `json:"oper_status" category:"$metrics" sub:"device"` // 1: up, 2: down
+
+	// health
+	CpuUsage    uint8  `json:"cpu_usage" category:"$metrics" sub:"device"`    // percent
+	MemoryUsed  uint64 `json:"memory_used" category:"$metrics" sub:"device"`  // bytes
+	MemoryTotal uint64 `json:"memory_total" category:"$metrics" sub:"device"` // bytes
+	Uptime      uint64 `json:"uptime" category:"$metrics" sub:"device"`       // s
+}
+
+func (m *DeviceMeter) Reverse() {
+	m.RxBytes, m.TxBytes = m.TxBytes, m.RxBytes
+	m.RxPackets, m.TxPackets = m.TxPackets, m.RxPackets
+	m.RxErrors, m.TxErrors = m.TxErrors, m.RxErrors
+	m.RxDiscards, m.TxDiscards = m.TxDiscards, m.RxDiscards
+}
+
+func (m *DeviceMeter) ID() uint8 {
+	return DEVICE_ID
+}
+
+func (m *DeviceMeter) Name() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *DeviceMeter) VTAPName() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *DeviceMeter) WriteToPB(p *pb.DeviceMeter) {
+	p.MeterType = uint32(m.MeterType)
+	p.RxBytes = m.RxBytes
+	p.TxBytes = m.TxBytes
+	p.RxPackets = m.RxPackets
+	p.TxPackets = m.TxPackets
+	p.RxErrors = m.RxErrors
+	p.TxErrors = m.TxErrors
+	p.RxDiscards = m.RxDiscards
+	p.TxDiscards = m.TxDiscards
+	p.Speed = m.Speed
+	p.OperStatus = uint32(m.OperStatus)
+	p.CpuUsage = uint32(m.CpuUsage)
+	p.MemoryUsed = m.MemoryUsed
+	p.MemoryTotal = m.MemoryTotal
+	p.Uptime = m.Uptime
+}
+
+func (m *DeviceMeter) ReadFromPB(p *pb.DeviceMeter) {
+	m.MeterType = uint8(p.MeterType)
+	m.RxBytes = p.RxBytes
+	m.TxBytes = p.TxBytes
+	m.RxPackets = p.RxPackets
+	m.TxPackets = p.TxPackets
+	m.RxErrors = p.RxErrors
+	m.TxErrors = p.TxErrors
+	m.RxDiscards = p.RxDiscards
+	m.TxDiscards = p.TxDiscards
+	m.Speed = p.Speed
+	m.OperStatus = uint8(p.OperStatus)
+	m.CpuUsage = uint8(p.CpuUsage)
+	m.MemoryUsed = p.MemoryUsed
+	m.MemoryTotal = p.MemoryTotal
+	m.Uptime = p.Uptime
+}
+
+func (m *DeviceMeter) SortKey() uint64 {
+	return m.RxBytes + m.TxBytes
+}
+
+func (m *DeviceMeter) ToKVString() string {
+	buffer := make([]byte, MAX_STRING_LENGTH)
+	size := m.MarshalTo(buffer)
+	return string(buffer[:size])
+}
+
+func (m *DeviceMeter) MarshalTo(b []byte) int {
+	offset := 0
+	offset += copy(b[offset:], "meter_type=")
+	offset += copy(b[offset:], strconv.FormatUint(uint64(m.MeterType), 10))
+	offset += copy(b[offset:], "i,rx_bytes=")
+	offset += copy(b[offset:], strconv.FormatUint(m.RxBytes, 10))
+	offset += copy(b[offset:], "i,tx_bytes=")
+	offset += copy(b[offset:], strconv.FormatUint(m.TxBytes, 10))
+	offset += copy(b[offset:], "i,rx_packets=")
+	offset += copy(b[offset:], strconv.FormatUint(m.RxPackets, 10))
+	offset += copy(b[offset:], "i,tx_packets=")
+	offset += copy(b[offset:], strconv.FormatUint(m.TxPackets, 10))
+	offset += copy(b[offset:], "i,rx_errors=")
+	offset += copy(b[offset:], strconv.FormatUint(m.RxErrors, 10))
+	offset += copy(b[offset:], "i,tx_errors=")
+	offset += copy(b[offset:], strconv.FormatUint(m.TxErrors, 10))
+	offset += copy(b[offset:], "i,rx_discards=")
+	offset += copy(b[offset:], strconv.FormatUint(m.RxDiscards, 10))
+	offset += copy(b[offset:], "i,tx_discards=")
+	offset += copy(b[offset:], strconv.FormatUint(m.TxDiscards, 10))
+	offset += copy(b[offset:], "i,speed=")
+	offset += copy(b[offset:], strconv.FormatUint(m.Speed, 10))
+	offset += copy(b[offset:], "i,oper_status=")
+	offset += copy(b[offset:], strconv.FormatUint(uint64(m.OperStatus), 10))
+	offset += copy(b[offset:], "i,cpu_usage=")
+	offset += copy(b[offset:], strconv.FormatUint(uint64(m.CpuUsage), 10))
+	offset += copy(b[offset:], "i,memory_used=")
+	offset += copy(b[offset:], strconv.FormatUint(m.MemoryUsed, 10))
+	offset += copy(b[offset:], "i,memory_total=")
+	offset += copy(b[offset:], strconv.FormatUint(m.MemoryTotal, 10))
+	offset += copy(b[offset:], "i,uptime=")
+	offset += copy(b[offset:], strconv.FormatUint(m.Uptime, 10))
+	b[offset] = 'i'
+	offset++
+
+	return offset
+}
+
+// Columns列和WriteBlock的列需要一一对应
+func DeviceMeterColumns() []*ckdb.Column {
+	columns := []*ckdb.Column{}
+	columns = append(columns, ckdb.NewColumn("meter_type", ckdb.UInt8).SetComment("0: 设备健康, 1: 接口"))
+	columns = append(columns, ckdb.NewColumnsWithComment(
+		[][2]string{
+			{"rx_bytes", "累计接收字节数"},
+			{"tx_bytes", "累计发送字节数"},
+			{"rx_packets", "累计接收包数"},
+			{"tx_packets", "累计发送包数"},
+			{"rx_errors", "累计接收错误包数"},
+			{"tx_errors", "累计发送错误包数"},
+			{"rx_discards", "累计接收丢弃包数"},
+			{"tx_discards", "累计发送丢弃包数"},
+			{"speed", "接口速率, 单位: bps"},
+		},
+		ckdb.UInt64)...)
+	columns = append(columns, ckdb.NewColumn("oper_status", ckdb.UInt8).SetComment("接口状态, 1: up, 2: down"))
+	columns = append(columns, ckdb.NewColumn("cpu_usage", ckdb.UInt8).SetComment("CPU使用率, 单位: %"))
+	columns = append(columns, ckdb.NewColumnsWithComment(
+		[][2]string{
+			{"memory_used", "已用内存, 单位: 字节"},
+			{"memory_total", "总内存, 单位: 字节"},
+			{"uptime", "运行时长, 单位: 秒"},
+		},
+		ckdb.UInt64)...)
+	return columns
+}
+
+// WriteBlock需要和Colums的列一一对应
+func (m *DeviceMeter) WriteBlock(block *ckdb.Block) {
+	block.Write(
+		m.MeterType,
+
+		m.RxBytes,
+		m.TxBytes,
+		m.RxPackets,
+		m.TxPackets,
+		m.RxErrors,
+		m.TxErrors,
+		m.RxDiscards,
+		m.TxDiscards,
+		m.Speed,
+
+		m.OperStatus,
+		m.CpuUsage,
+
+		m.MemoryUsed,
+		m.MemoryTotal,
+		m.Uptime,
+	)
+}
+
+func (m *DeviceMeter) Merge(other *DeviceMeter) {
+	m.RxBytes += other.RxBytes
+	m.TxBytes += other.TxBytes
+	m.RxPackets += other.RxPackets
+	m.TxPackets += other.TxPackets
+	m.RxErrors += other.RxErrors
+	m.TxErrors += other.TxErrors
+	m.RxDiscards += other.RxDiscards
+	m.TxDiscards += other.TxDiscards
+	// speed, status and health are gauges, keep the latest
+	m.Speed = other.Speed
+	m.OperStatus = other.OperStatus
+	m.CpuUsage = other.CpuUsage
+	m.MemoryUsed = other.MemoryUsed
+	m.MemoryTotal = other.MemoryTotal
+	m.Uptime = other.Uptime
+}
+
+func (m *DeviceMeter) ConcurrentMerge(other Meter) {
+	if other, ok := other.(*DeviceMeter); ok {
+		m.Merge(other)
+	}
+}
+
+func (m *DeviceMeter) SequentialMerge(other Meter) {
+	m.ConcurrentMerge(other)
+}
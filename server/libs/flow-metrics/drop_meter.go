@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package flow_metrics
+
+import (
+	"strconv"
+
+	"github.com/deepflowio/deepflow/server/libs/ckdb"
+	"github.com/deepflowio/deepflow/server/libs/flow-metrics/pb"
+)
+
+// packets dropped inside the host stack, counted by the agent on tracepoint skb/kfree_skb
+type DropMeter struct {
+	Packets uint64 `json:"packet" category:"$metrics" sub:"drop"`
+	Bytes   uint64 `json:"byte" category:"$metrics" sub:"drop"`
+}
+
+func (m *DropMeter) Reverse() {
+	// 丢包统计无方向
+}
+
+func (m *DropMeter) ID() uint8 {
+	return DROP_ID
+}
+
+func (m *DropMeter) Name() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *DropMeter) VTAPName() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *DropMeter) WriteToPB(p *pb.DropMeter) {
+	p.Packets = m.Packets
+	p.Bytes = m.Bytes
+}
+
+func (m *DropMeter) ReadFromPB(p *pb.DropMeter) {
+	m.Packets = p.Packets
+	m.Bytes = p.Bytes
+}
+
+func (m *DropMeter) SortKey() uint64 {
+	return m.Bytes
+}
+
+func (m *DropMeter) ToKVString() string {
+	buffer := make([]byte, MAX_STRING_LENGTH)
+	size := m.MarshalTo(buffer)
+	return string(buffer[:size])
+}
+
+func (m *DropMeter) MarshalTo(b []byte) int {
+	offset := 0
+	offset += copy(b[offset:], "packet=")
+	offset += copy(b[offset:], strconv.FormatUint(m.Packets, 10))
+	offset += copy(b[offset:], "i,byte=")
+	offset += copy(b[offset:], strconv.FormatUint(m.Bytes, 10))
+	b[offset] = 'i'
+	offset++
+
+	return offset
+}
+
+const (
+	DROP_PACKET = iota
+	DROP_BYTE
+)
+
+// Columns列和WriteBlock的列需要一一对应
+func DropMeterColumns() []*ckdb.Column {
+	return ckdb.NewColumnsWithComment(
+		[][2]string{
+			DROP_PACKET: {"packet", "累计丢包数"},
+			DROP_BYTE:   {"byte", "累计丢包字节数"},
+		},
+		ckdb.UInt64)
+}
+
+// WriteBlock需要和Colums的列一一对应
+func (m *DropMeter) WriteBlock(block *ckdb.Block) {
+	block.Write(
+		m.Packets,
+		m.Bytes,
+	)
+}
+
+func (m *DropMeter) Merge(other *DropMeter) {
+	m.Packets += other.Packets
+	m.Bytes += other.Bytes
+}
+
+func (m *DropMeter) ConcurrentMerge(other Meter) {
+	if other, ok := other.(*DropMeter); ok {
+		m.Merge(other)
+	}
+}
+
+func (m *DropMeter) SequentialMerge(other Meter) {
+	m.ConcurrentMerge(other)
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package flow_metrics
+
+import (
+	"strconv"
+
+	"github.com/deepflowio/deepflow/server/libs/ckdb"
+	"github.com/deepflowio/deepflow/server/libs/flow-metrics/pb"
+)
+
+// regular file reads and writes of a process on a file system, latencies are in microseconds
+type FileIoMeter struct {
+	ReadCount       uint64 `json:"read_count" category:"$metrics" sub:"file_io"`
+	ReadBytes       uint64 `json:"read_bytes" category:"$metrics" sub:"file_io"`
+	ReadLatencySum  uint64 `json:"read_latency_sum" category:"$metrics" sub:"file_io"`
+	ReadLatencyMax  uint64 `json:"read_latency_max" category:"$metrics" sub:"file_io"`
+	WriteCount      uint64 `json:"write_count" category:"$metrics" sub:"file_io"`
+	WriteBytes      uint64 `json:"write_bytes" category:"$metrics" sub:"file_io"`
+	WriteLatencySum uint64 `json:"write_latency_sum" category:"$metrics" sub:"file_io"`
+	WriteLatencyMax uint64 `json:"write_latency_max" category:"$metrics" sub:"file_io"`
+}
+
+func (m *FileIoMeter) Reverse() {
+	// 文件读写统计无方向
+}
+
+func (m *FileIoMeter) ID() uint8 {
+	return FILE_IO_ID
+}
+
+func (m *FileIoMeter) Name() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *FileIoMeter) VTAPName() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *FileIoMeter) WriteToPB(p *pb.FileIoMeter) {
+	p.ReadCount = m.ReadCount
+	p.ReadBytes = m.ReadBytes
+	p.ReadLatencySum = m.ReadLatencySum
+	p.ReadLatencyMax = m.ReadLatencyMax
+	p.WriteCount = m.WriteCount
+	p.WriteBytes = m.WriteBytes
+	p.WriteLatencySum = m.WriteLatencySum
+	p.WriteLatencyMax = m.WriteLatencyMax
+}
+
+func (m *FileIoMeter) ReadFromPB(p *pb.FileIoMeter) {
+	m.ReadCount = p.ReadCount
+	m.ReadBytes = p.ReadBytes
+	m.ReadLatencySum = p.ReadLatencySum
+	m.ReadLatencyMax = p.ReadLatencyMax
+	m.WriteCount = p.WriteCount
+	m.WriteBytes = p.WriteBytes
+	m.WriteLatencySum = p.WriteLatencySum
+	m.WriteLatencyMax = p.WriteLatencyMax
+}
+
+func (m *FileIoMeter) SortKey() uint64 {
+	return m.ReadBytes + m.WriteBytes
+}
+
+func (m *FileIoMeter) ToKVString() string {
+	buffer := make([]byte, MAX_STRING_LENGTH)
+	size := m.MarshalTo(buffer)
+	return string(buffer[:size])
+}
+
+func (m *FileIoMeter) MarshalTo(b []byte) int {
+	offset := 0
+	offset += copy(b[offset:], "read_count=")
+	offset += copy(b[offset:], strconv.FormatUint(m.ReadCount, 10))
+	offset += copy(b[offset:], "i,read_bytes=")
+	offset += copy(b[offset:], strconv.FormatUint(m.ReadBytes, 10))
+	offset += copy(b[offset:], "i,read_latency_sum=")
+	offset += copy(b[offset:], strconv.FormatUint(m.ReadLatencySum, 10))
+	offset += copy(b[offset:], "i,read_latency_max=")
+	offset += copy(b[offset:], strconv.FormatUint(m.ReadLatencyMax, 10))
+	offset += copy(b[offset:], "i,write_count=")
+	offset += copy(b[offset:], strconv.FormatUint(m.WriteCount, 10))
+	offset += copy(b[offset:], "i,write_bytes=")
+	offset += copy(b[offset:], strconv.FormatUint(m.WriteBytes, 10))
+	offset += copy(b[offset:], "i,write_latency_sum=")
+	offset += copy(b[offset:], strconv.FormatUint(m.WriteLatencySum, 10))
+	offset += copy(b[offset:], "i,write_latency_max=")
+	offset += copy(b[offset:], strconv.FormatUint(m.WriteLatencyMax, 10))
+	b[offset] = 'i'
+	offset++
+
+	return offset
+}
+
+const (
+	FILE_IO_READ_COUNT = iota
+	FILE_IO_READ_BYTES
+	FILE_IO_READ_LATENCY_SUM
+	FILE_IO_READ_LATENCY_MAX
+
+	FILE_IO_WRITE_COUNT
+	FILE_IO_WRITE_BYTES
+	FILE_IO_WRITE_LATENCY_SUM
+	FILE_IO_WRITE_LATENCY_MAX
+)
+
+// Columns列和WriteBlock的列需要一一对应
+func FileIoMeterColumns() []*ckdb.Column {
+	return ckdb.NewColumnsWithComment(
+		[][2]string{
+			FILE_IO_READ_COUNT:       {"read_count", "累计读次数"},
+			FILE_IO_READ_BYTES:       {"read_bytes", "累计读字节数"},
+			FILE_IO_READ_LATENCY_SUM: {"read_latency_sum", "累计读时延, 单位: 微秒"},
+			FILE_IO_READ_LATENCY_MAX: {"read_latency_max", "最大读时延, 单位: 微秒"},
+
+			FILE_IO_WRITE_COUNT:       {"write_count", "累计写次数"},
+			FILE_IO_WRITE_BYTES:       {"write_bytes", "累计写字节数"},
+			FILE_IO_WRITE_LATENCY_SUM: {"write_latency_sum", "累计写时延, 单位: 微秒"},
+			FILE_IO_WRITE_LATENCY_MAX: {"write_latency_max", "最大写时延, 单位: 微秒"},
+		},
+		ckdb.UInt64)
+}
+
+// WriteBlock需要和Colums的列一一对应
+func (m *FileIoMeter) WriteBlock(block *ckdb.Block) {
+	block.Write(
+		m.ReadCount,
+		m.ReadBytes,
+		m.ReadLatencySum,
+		m.ReadLatencyMax,
+
+		m.WriteCount,
+		m.WriteBytes,
+		m.WriteLatencySum,
+		m.WriteLatencyMax,
+	)
+}
+
+func (m *FileIoMeter) Merge(other *FileIoMeter) {
+	m.ReadCount += other.ReadCount
+	m.ReadBytes += other.ReadBytes
+	m.ReadLatencySum += other.ReadLatencySum
+	m.ReadLatencyMax = maxU64(m.ReadLatencyMax, other.ReadLatencyMax)
+	m.WriteCount += other.WriteCount
+	m.WriteBytes += other.WriteBytes
+	m.WriteLatencySum += other.WriteLatencySum
+	m.WriteLatencyMax = maxU64(m.WriteLatencyMax, other.WriteLatencyMax)
+}
+
+func (m *FileIoMeter) ConcurrentMerge(other Meter) {
+	if other, ok := other.(*FileIoMeter); ok {
+		m.Merge(other)
+	}
+}
+
+func (m *FileIoMeter) SequentialMerge(other Meter) {
+	m.ConcurrentMerge(other)
+}
@@ -24,7 +24,7 @@ type Tagger interface {
 	SetID(string)
 	GetCode() uint64
 	SetCode(uint64)
-	GetTAPType() uint8
+	GetTAPType() uint16
 	ToKVString() string
 	MarshalTo([]byte) int
 	String() string
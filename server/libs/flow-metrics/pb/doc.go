@@ -31,10 +31,16 @@ func NewDocument() *Document {
 			},
 			Usage: &UsageMeter{},
 			App: &AppMeter{
-				Traffic: &AppTraffic{},
-				Latency: &AppLatency{},
-				Anomaly: &AppAnomaly{},
+				Traffic:       &AppTraffic{},
+				Latency:       &AppLatency{},
+				Anomaly:       &AppAnomaly{},
+				PayloadSize:   &AppPayloadSize{},
+				LatencySketch: &AppLatencySketch{},
 			},
+			Drop:   &DropMeter{},
+			FileIo: &FileIoMeter{},
+			Talker: &TalkerMeter{},
+			Device: &DeviceMeter{},
 		},
 	}
 }
@@ -89,16 +95,35 @@ func (d *Document) ResetAll() {
 	appLatency.Reset()
 	appAnomaly := app.Anomaly
 	appAnomaly.Reset()
+	appPayloadSize := app.PayloadSize
+	appPayloadSize.Reset()
+	appLatencySketch := app.LatencySketch
+	appLatencySketch.Reset()
 
 	app.Reset()
 	app.Traffic = appTraffic
 	app.Latency = appLatency
 	app.Anomaly = appAnomaly
+	app.PayloadSize = appPayloadSize
+	app.LatencySketch = appLatencySketch
+
+	drop := meter.Drop
+	drop.Reset()
+	fileIo := meter.FileIo
+	fileIo.Reset()
+	talker := meter.Talker
+	talker.Reset()
+	device := meter.Device
+	device.Reset()
 
 	meter.Reset()
 	meter.Flow = flow
 	meter.Usage = usage
 	meter.App = app
+	meter.Drop = drop
+	meter.FileIo = fileIo
+	meter.Talker = talker
+	meter.Device = device
 
 	d.Reset()
 	d.Tag = miniTag
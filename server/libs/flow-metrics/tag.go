@@ -91,6 +91,9 @@ const (
 	IsKeyService
 	L7Protocol // also represents AppService,AppInstance,EndPoint,BizType
 	SignalSource
+	DropReason // 1 << 53
+	MountPoint // also represents AppService,AppInstance
+	Device     // represents DeviceName,InterfaceName
 )
 
 const (
@@ -231,10 +234,10 @@ func (d DirectionEnum) ToTAPSide() TAPSideEnum {
 //
 // Note: For historical reasons, we use the confusing term VTAP to refer
 // to deepflow-agent, and agent_id to represent the id of a deepflow-agent.
-type TAPTypeEnum uint8
+type TAPTypeEnum uint16
 
 const (
-	IDC_MIN TAPTypeEnum = 1 // 1~2, 4~255: IDC
+	IDC_MIN TAPTypeEnum = 1 // 1~2, 4~4095: IDC
 	CLOUD   TAPTypeEnum = 3
 )
 
@@ -331,6 +334,11 @@ type Field struct {
 	Endpoint     string              `json:"endpoint" category:"$tag" sub:"service_info" datasource:"a|am"`
 	BizType      uint8               `json:"biz_type" category:"$tag" sub:"capture_info" datasource:"a|am"`
 	SignalSource uint16              `json:"signal_source" category:"$tag" sub:"capture_info" enumfile:"l7_signal_source"` // FIXME: network,network_1m should use l4_signal_source for translate
+	DropReason   string              `json:"drop_reason" category:"$tag" sub:"network_layer"`
+	MountPoint   string              `json:"mount_point" category:"$tag" sub:"service_info"`
+
+	DeviceName    string `json:"device_name" category:"$tag" sub:"network_layer"`
+	InterfaceName string `json:"interface_name" category:"$tag" sub:"network_layer"`
 
 	TagSource, TagSource1 uint8
 
@@ -346,6 +354,10 @@ func newMetricsMinuteTable(id MetricsTableID, engine ckdb.EngineType, version, c
 		orderKeys = []string{timeKey, "l3_epc_id", "ip4", "ip6"}
 	} else if code&L3EpcIDPath != 0 {
 		orderKeys = []string{timeKey, "l3_epc_id_1", "ip4_1", "ip6_1", "l3_epc_id_0", "ip4_0", "ip6_0"}
+	} else if code&IP != 0 {
+		orderKeys = []string{timeKey, "ip4", "ip6"}
+	} else if code&IPPath != 0 {
+		orderKeys = []string{timeKey, "ip4_1", "ip6_1", "ip4_0", "ip6_0"}
 	} else if code&ACLGID != 0 {
 		orderKeys = []string{timeKey, "acl_gid"}
 	}
@@ -361,6 +373,14 @@ func newMetricsMinuteTable(id MetricsTableID, engine ckdb.EngineType, version, c
 		meterColumns = UsageMeterColumns()
 	case APPLICATION_1M, APPLICATION_MAP_1M:
 		meterColumns = AppMeterColumns()
+	case PACKET_DROP_1M:
+		meterColumns = DropMeterColumns()
+	case FILE_IO_1M:
+		meterColumns = FileIoMeterColumns()
+	case TOP_TALKER_1M:
+		meterColumns = TalkerMeterColumns()
+	case DEVICE_1M:
+		meterColumns = DeviceMeterColumns()
 	}
 
 	return &ckdb.Table{
@@ -417,6 +437,12 @@ func GetMetricsTables(engine ckdb.EngineType, version, cluster, storagePolicy, c
 		secondTables = append(secondTables, newMetricsSecondTable(minuteTables[i-NETWORK_1S], appSecondTtl, ckdb.GetColdStorage(coldStorages, ckdb.METRICS_DB, i.TableName())))
 	}
 	metricsTables = append(minuteTables, secondTables...)
+
+	// the tables below only have minute data, and their IDs are after the second tables
+	metricsTables = append(metricsTables, newMetricsMinuteTable(PACKET_DROP_1M, engine, version, cluster, storagePolicy, ckdbType, flowMinuteTtl, ckdb.GetColdStorage(coldStorages, ckdb.METRICS_DB, PACKET_DROP_1M.TableName())))
+	metricsTables = append(metricsTables, newMetricsMinuteTable(FILE_IO_1M, engine, version, cluster, storagePolicy, ckdbType, appMinuteTtl, ckdb.GetColdStorage(coldStorages, ckdb.METRICS_DB, FILE_IO_1M.TableName())))
+	metricsTables = append(metricsTables, newMetricsMinuteTable(TOP_TALKER_1M, engine, version, cluster, storagePolicy, ckdbType, flowMinuteTtl, ckdb.GetColdStorage(coldStorages, ckdb.METRICS_DB, TOP_TALKER_1M.TableName())))
+	metricsTables = append(metricsTables, newMetricsMinuteTable(DEVICE_1M, engine, version, cluster, storagePolicy, ckdbType, flowMinuteTtl, ckdb.GetColdStorage(coldStorages, ckdb.METRICS_DB, DEVICE_1M.TableName())))
 	return metricsTables
 }
 
@@ -437,6 +463,11 @@ const (
 	APPLICATION_1S
 	APPLICATION_MAP_1S
 
+	PACKET_DROP_1M
+	FILE_IO_1M
+	TOP_TALKER_1M
+	DEVICE_1M
+
 	METRICS_TABLE_ID_MAX
 )
 
@@ -462,6 +493,11 @@ var metricsTableNames = []string{
 
 	APPLICATION_1S:     "application.1s",
 	APPLICATION_MAP_1S: "application_map.1s",
+
+	PACKET_DROP_1M: "packet_drop.1m",
+	FILE_IO_1M:     "file_io.1m",
+	TOP_TALKER_1M:  "top_talker.1m",
+	DEVICE_1M:      "device.1m",
 }
 
 func MetricsTableNameToID(name string) MetricsTableID {
@@ -484,6 +520,11 @@ const (
 	APPLICATION_MAP = BasePathCode | BasePortCode | TAPPort | L7Protocol
 
 	TRAFFIC_POLICY = ACLGID | TunnelIPID | VTAPID
+
+	PACKET_DROP = IPPath | GPIDPath | Protocol | ServerPort | VTAPID | SignalSource | DropReason
+	FILE_IO     = AZID | HostID | IP | L3Device | PodClusterID | PodGroupID | PodID | PodNodeID | PodNSID | RegionID | SubnetID | VTAPID | ServiceID | Resource | GPID | SignalSource | MountPoint
+	TOP_TALKER  = AZID | HostID | IP | L3Device | L3EpcID | PodClusterID | PodGroupID | PodID | PodNodeID | PodNSID | RegionID | SubnetID | VTAPID | ServiceID | Resource | GPID | SignalSource | BasePortCode
+	DEVICE      = IP | GPID | VTAPID | SignalSource | Device
 )
 
 var metricsTableCodes = []Code{
@@ -500,6 +541,11 @@ var metricsTableCodes = []Code{
 
 	APPLICATION_1S:     APPLICATION,
 	APPLICATION_MAP_1S: APPLICATION_MAP,
+
+	PACKET_DROP_1M: PACKET_DROP,
+	FILE_IO_1M:     FILE_IO,
+	TOP_TALKER_1M:  TOP_TALKER,
+	DEVICE_1M:      DEVICE,
 }
 
 type Tag struct {
@@ -597,6 +643,11 @@ func (t *Tag) MarshalTo(b []byte) int {
 		offset += copy(b[offset:], strconv.FormatUint(uint64(t.AZID1), 10))
 	}
 
+	if t.Code&Device != 0 {
+		offset += copy(b[offset:], ",device_name="+t.DeviceName)
+		offset += copy(b[offset:], ",interface_name="+t.InterfaceName)
+	}
+
 	if t.Code&Direction != 0 {
 		if t.Role == ROLE_CLIENT {
 			offset += copy(b[offset:], ",role=c2s")
@@ -604,6 +655,9 @@ func (t *Tag) MarshalTo(b []byte) int {
 			offset += copy(b[offset:], ",role=s2c")
 		}
 	}
+	if t.Code&DropReason != 0 {
+		offset += copy(b[offset:], ",drop_reason="+t.DropReason)
+	}
 	if t.Code&GPID != 0 {
 		offset += copy(b[offset:], ",gprocess_id=")
 		offset += copy(b[offset:], strconv.FormatUint(uint64(t.GPID), 10))
@@ -706,6 +760,11 @@ func (t *Tag) MarshalTo(b []byte) int {
 		//offset += copy(b[offset:], ",mac_1=")
 		//offset += copy(b[offset:], utils.Uint64ToMac(t.MAC1).String())
 	}
+	if t.Code&MountPoint != 0 {
+		offset += copy(b[offset:], ",mount_point="+t.MountPoint)
+		offset += copy(b[offset:], ",app_service="+t.AppService)
+		offset += copy(b[offset:], ",app_instance="+t.AppInstance)
+	}
 
 	if t.Code&PodClusterID != 0 {
 		offset += copy(b[offset:], ",pod_cluster_id=")
@@ -922,9 +981,17 @@ func GenTagColumns(code Code) []*ckdb.Column {
 		columns = append(columns, ckdb.NewColumnWithGroupBy("az_id_1", ckdb.UInt16).SetComment("ip4/6_1对应的可用区ID"))
 	}
 
+	if code&Device != 0 {
+		columns = append(columns, ckdb.NewColumnWithGroupBy("device_name", ckdb.LowCardinalityString).SetComment("网络设备名称"))
+		columns = append(columns, ckdb.NewColumnWithGroupBy("interface_name", ckdb.LowCardinalityString).SetComment("网络设备接口名称, 为空时表示设备健康数据"))
+	}
+
 	if code&Direction != 0 {
 		columns = append(columns, ckdb.NewColumnWithGroupBy("role", ckdb.UInt8).SetComment("统计量对应的流方向. 0: ip为客户端, 1: ip为服务端"))
 	}
+	if code&DropReason != 0 {
+		columns = append(columns, ckdb.NewColumnWithGroupBy("drop_reason", ckdb.LowCardinalityString).SetComment("内核丢包原因"))
+	}
 
 	if code&GPID != 0 {
 		columns = append(columns, ckdb.NewColumnWithGroupBy("gprocess_id", ckdb.UInt32).SetComment("全局进程ID"))
@@ -996,6 +1063,11 @@ func GenTagColumns(code Code) []*ckdb.Column {
 		// columns = append(columns, ckdb.NewColumnWithGroupBy("mac_0", UInt64))
 		// columns = append(columns, ckdb.NewColumnWithGroupBy("mac_1", UInt64))
 	}
+	if code&MountPoint != 0 {
+		columns = append(columns, ckdb.NewColumnWithGroupBy("mount_point", ckdb.LowCardinalityString).SetComment("文件所在的挂载点"))
+		columns = append(columns, ckdb.NewColumnWithGroupBy("app_service", ckdb.LowCardinalityString).SetComment("进程名"))
+		columns = append(columns, ckdb.NewColumnWithGroupBy("app_instance", ckdb.LowCardinalityString).SetComment("进程ID"))
+	}
 
 	if code&PodClusterID != 0 {
 		columns = append(columns, ckdb.NewColumnWithGroupBy("pod_cluster_id", ckdb.UInt16).SetComment("ip对应的容器集群ID"))
@@ -1107,7 +1179,7 @@ func GenTagColumns(code Code) []*ckdb.Column {
 		columns = append(columns, ckdb.NewColumnWithGroupBy("observation_point", ckdb.LowCardinalityString).SetComment("流量采集位置(c: 客户端(0侧)采集, s: 服务端(1侧)采集)"))
 	}
 	if code&TAPType != 0 {
-		columns = append(columns, ckdb.NewColumnWithGroupBy("capture_network_type_id", ckdb.UInt8).SetComment("流量采集点(1-2,4-255: 接入网络流量, 3: 虚拟网络流量)"))
+		columns = append(columns, ckdb.NewColumnWithGroupBy("capture_network_type_id", ckdb.UInt16).SetComment("流量采集点(1-2,4-4095: 接入网络流量, 3: 虚拟网络流量)"))
 	}
 	if code&VTAPID != 0 {
 		columns = append(columns, ckdb.NewColumnWithGroupBy("agent_id", ckdb.UInt16).SetComment("采集器的ID"))
@@ -1135,9 +1207,16 @@ func (t *Tag) WriteBlock(block *ckdb.Block, time uint32) {
 		block.Write(t.AZID, t.AZID1)
 	}
 
+	if code&Device != 0 {
+		block.Write(t.DeviceName, t.InterfaceName)
+	}
+
 	if code&Direction != 0 {
 		block.Write(t.Role)
 	}
+	if code&DropReason != 0 {
+		block.Write(t.DropReason)
+	}
 
 	if code&GPID != 0 {
 		block.Write(t.GPID)
@@ -1201,6 +1280,11 @@ func (t *Tag) WriteBlock(block *ckdb.Block, time uint32) {
 		// 不存
 		// block.Writes(t.MAC, t.MAC1)
 	}
+	if code&MountPoint != 0 {
+		block.Write(t.MountPoint)
+		block.Write(t.AppService)
+		block.Write(t.AppInstance)
+	}
 
 	if code&PodClusterID != 0 {
 		block.Write(t.PodClusterID)
@@ -1300,7 +1384,7 @@ func (t *Tag) WriteBlock(block *ckdb.Block, time uint32) {
 		block.Write(t.TAPSide.String())
 	}
 	if code&TAPType != 0 {
-		block.Write(uint8(t.TAPType))
+		block.Write(uint16(t.TAPType))
 	}
 	if code&VTAPID != 0 {
 		block.Write(t.VTAPID)
@@ -1406,6 +1490,10 @@ func (t *Tag) ReadFromPB(p *pb.MiniTag) {
 		}
 	}
 	t.SignalSource = uint16(p.Field.SignalSource)
+	t.DropReason = p.Field.DropReason
+	t.MountPoint = p.Field.MountPoint
+	t.DeviceName = p.Field.DeviceName
+	t.InterfaceName = p.Field.InterfaceName
 
 	// tunnel_ip_id get from server_port field
 	t.TunnelIPID = uint16(p.Field.ServerPort)
@@ -1427,8 +1515,8 @@ func (t *Tag) SetTID(tid uint8) {
 	t.GlobalThreadID = tid
 }
 
-func (t *Tag) GetTAPType() uint8 {
-	return uint8(t.TAPType)
+func (t *Tag) GetTAPType() uint16 {
+	return uint16(t.TAPType)
 }
 
 const (
@@ -260,8 +260,8 @@ func (t *MiniTag) SetTID(tid uint8) {
 	t.GlobalThreadID = tid
 }
 
-func (t *MiniTag) GetTAPType() uint8 {
-	return uint8(t.TAPType)
+func (t *MiniTag) GetTAPType() uint16 {
+	return uint16(t.TAPType)
 }
 
 var miniFieldPool = pool.NewLockFreePool(func() *MiniField {
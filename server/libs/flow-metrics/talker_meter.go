@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package flow_metrics
+
+import (
+	"strconv"
+
+	"github.com/deepflowio/deepflow/server/libs/ckdb"
+	"github.com/deepflowio/deepflow/server/libs/flow-metrics/pb"
+)
+
+// a top talker of the minute estimated on the agent, the bytes may be overestimated by at most BytesError
+type TalkerMeter struct {
+	TalkerType uint8  `json:"talker_type" category:"$metrics" sub:"top_talker"` // 0: source, 1: destination, 2: service
+	Rank       uint32 `json:"rank" category:"$metrics" sub:"top_talker"`
+	Bytes      uint64 `json:"byte" category:"$metrics" sub:"top_talker"`
+	Packets    uint64 `json:"packet" category:"$metrics" sub:"top_talker"`
+	BytesError uint64 `json:"byte_error" category:"$metrics" sub:"top_talker"`
+}
+
+func (m *TalkerMeter) Reverse() {
+	// 源、目的由TalkerType区分，无需Reverse
+}
+
+func (m *TalkerMeter) ID() uint8 {
+	return TALKER_ID
+}
+
+func (m *TalkerMeter) Name() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *TalkerMeter) VTAPName() string {
+	return MeterVTAPNames[m.ID()]
+}
+
+func (m *TalkerMeter) WriteToPB(p *pb.TalkerMeter) {
+	p.TalkerType = uint32(m.TalkerType)
+	p.Rank = m.Rank
+	p.Bytes = m.Bytes
+	p.Packets = m.Packets
+	p.BytesError = m.BytesError
+}
+
+func (m *TalkerMeter) ReadFromPB(p *pb.TalkerMeter) {
+	m.TalkerType = uint8(p.TalkerType)
+	m.Rank = p.Rank
+	m.Bytes = p.Bytes
+	m.Packets = p.Packets
+	m.BytesError = p.BytesError
+}
+
+func (m *TalkerMeter) SortKey() uint64 {
+	return m.Bytes
+}
+
+func (m *TalkerMeter) ToKVString() string {
+	buffer := make([]byte, MAX_STRING_LENGTH)
+	size := m.MarshalTo(buffer)
+	return string(buffer[:size])
+}
+
+func (m *TalkerMeter) MarshalTo(b []byte) int {
+	offset := 0
+	offset += copy(b[offset:], "talker_type=")
+	offset += copy(b[offset:], strconv.FormatUint(uint64(m.TalkerType), 10))
+	offset += copy(b[offset:], "i,rank=")
+	offset += copy(b[offset:], strconv.FormatUint(uint64(m.Rank), 10))
+	offset += copy(b[offset:], "i,byte=")
+	offset += copy(b[offset:], strconv.FormatUint(m.Bytes, 10))
+	offset += copy(b[offset:], "i,packet=")
+	offset += copy(b[offset:], strconv.FormatUint(m.Packets, 10))
+	offset += copy(b[offset:], "i,byte_error=")
+	offset += copy(b[offset:], strconv.FormatUint(m.BytesError, 10))
+	b[offset] = 'i'
+	offset++
+
+	return offset
+}
+
+// Columns列和WriteBlock的列需要一一对应
+func TalkerMeterColumns() []*ckdb.Column {
+	columns := []*ckdb.Column{}
+	columns = append(columns, ckdb.NewColumn("talker_type", ckdb.UInt8).SetComment("0: 源, 1: 目的, 2: 服务"))
+	columns = append(columns, ckdb.NewColumn("rank", ckdb.UInt32).SetComment("当前分钟内按字节数的排名, 从1开始"))
+	columns = append(columns, ckdb.NewColumn("byte", ckdb.UInt64).SetComment("累计字节数"))
+	columns = append(columns, ckdb.NewColumn("packet", ckdb.UInt64).SetComment("开始统计后的累计包数"))
+	columns = append(columns, ckdb.NewColumn("byte_error", ckdb.UInt64).SetComment("字节数的最大高估值"))
+	return columns
+}
+
+// WriteBlock需要和Colums的列一一对应
+func (m *TalkerMeter) WriteBlock(block *ckdb.Block) {
+	block.Write(
+		m.TalkerType,
+		m.Rank,
+		m.Bytes,
+		m.Packets,
+		m.BytesError,
+	)
+}
+
+func (m *TalkerMeter) Merge(other *TalkerMeter) {
+	if other.Rank < m.Rank {
+		m.Rank = other.Rank
+	}
+	m.Bytes += other.Bytes
+	m.Packets += other.Packets
+	m.BytesError += other.BytesError
+}
+
+func (m *TalkerMeter) ConcurrentMerge(other Meter) {
+	if other, ok := other.(*TalkerMeter); ok {
+		m.Merge(other)
+	}
+}
+
+func (m *TalkerMeter) SequentialMerge(other Meter) {
+	m.ConcurrentMerge(other)
+}
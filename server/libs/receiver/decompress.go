@@ -0,0 +1,57 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package receiver
+
+import (
+	"bytes"
+	"fmt"
+	"io"
+
+	"github.com/klauspost/compress/zstd"
+	"github.com/pierrec/lz4/v4"
+
+	"github.com/deepflowio/deepflow/server/libs/datatype"
+)
+
+// DecodeAll of the decoder is safe for concurrent use
+var zstdDecoder, _ = zstd.NewReader(nil, zstd.WithDecoderMaxMemory(RECV_BUFSIZE_MAX))
+
+// decompress decodes the payload compressed by the agent sender into a buffer acquired from the pool,
+// End of the buffer is the length of the decoded payload
+func decompress(encoder uint8, data []byte, socketType ServerType) (*RecvBuffer, error) {
+	var decoded []byte
+	var err error
+	switch encoder {
+	case datatype.ENCODER_ZSTD:
+		decoded, err = zstdDecoder.DecodeAll(data, nil)
+	case datatype.ENCODER_LZ4:
+		decoded, err = io.ReadAll(io.LimitReader(lz4.NewReader(bytes.NewReader(data)), RECV_BUFSIZE_MAX+1))
+		if err == nil && len(decoded) > RECV_BUFSIZE_MAX {
+			err = fmt.Errorf("decompressed size exceeds %d", RECV_BUFSIZE_MAX)
+		}
+	default:
+		err = fmt.Errorf("unknown encoder %d", encoder)
+	}
+	if err != nil {
+		return nil, err
+	}
+
+	buffer, _ := AcquireRecvBuffer(len(decoded), socketType)
+	copy(buffer.Buffer, decoded)
+	buffer.End = len(decoded)
+	return buffer, nil
+}
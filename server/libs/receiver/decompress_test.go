@@ -0,0 +1,75 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package receiver
+
+import (
+	"bytes"
+	"testing"
+
+	"github.com/klauspost/compress/zstd"
+	"github.com/pierrec/lz4/v4"
+
+	"github.com/deepflowio/deepflow/server/libs/datatype"
+)
+
+func TestDecompress(t *testing.T) {
+	data := bytes.Repeat([]byte("deepflow"), 1024)
+
+	zstdEncoder, _ := zstd.NewWriter(nil)
+	zstdData := zstdEncoder.EncodeAll(data, nil)
+
+	var lz4Data bytes.Buffer
+	lz4Writer := lz4.NewWriter(&lz4Data)
+	if _, err := lz4Writer.Write(data); err != nil {
+		t.Fatal(err)
+	}
+	if err := lz4Writer.Close(); err != nil {
+		t.Fatal(err)
+	}
+
+	for _, c := range []struct {
+		name       string
+		encoder    uint8
+		compressed []byte
+	}{
+		{"zstd", datatype.ENCODER_ZSTD, zstdData},
+		{"lz4", datatype.ENCODER_LZ4, lz4Data.Bytes()},
+	} {
+		buffer, err := decompress(c.encoder, c.compressed, TCP)
+		if err != nil {
+			t.Errorf("decompress %s failed: %s", c.name, err)
+			continue
+		}
+		if !bytes.Equal(buffer.Buffer[:buffer.End], data) {
+			t.Errorf("decompress %s got wrong data", c.name)
+		}
+		ReleaseRecvBuffer(buffer)
+	}
+
+	if _, err := decompress(datatype.ENCODER_NONE, data, TCP); err == nil {
+		t.Error("decompress with ENCODER_NONE should fail")
+	}
+	if _, err := decompress(0xff, zstdData, TCP); err == nil {
+		t.Error("decompress with an unknown encoder should fail")
+	}
+	if _, err := decompress(datatype.ENCODER_ZSTD, data, TCP); err == nil {
+		t.Error("decompress invalid zstd data should fail")
+	}
+	if _, err := decompress(datatype.ENCODER_LZ4, data, TCP); err == nil {
+		t.Error("decompress invalid lz4 data should fail")
+	}
+}
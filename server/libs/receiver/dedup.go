@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package receiver
+
+import (
+	"sync"
+
+	"github.com/deepflowio/deepflow/server/libs/datatype"
+)
+
+// number of the latest frame sequences remembered for each sender of an agent,
+// 65536 must be a multiple of it so that the bits stay aligned after the sequence wraps
+const SEQUENCE_WINDOW_SIZE = 4096
+
+// sequenceWindow remembers which of the latest SEQUENCE_WINDOW_SIZE sequences are received
+type sequenceWindow struct {
+	latest uint16
+	seen   [SEQUENCE_WINDOW_SIZE / 64]uint64
+}
+
+func (w *sequenceWindow) isSet(seq uint16) bool {
+	i := seq % SEQUENCE_WINDOW_SIZE
+	return w.seen[i/64]&(1<<(i%64)) != 0
+}
+
+func (w *sequenceWindow) set(seq uint16) {
+	i := seq % SEQUENCE_WINDOW_SIZE
+	w.seen[i/64] |= 1 << (i % 64)
+}
+
+func (w *sequenceWindow) clear(seq uint16) {
+	i := seq % SEQUENCE_WINDOW_SIZE
+	w.seen[i/64] &^= 1 << (i % 64)
+}
+
+// receive records seq and returns whether it has been received before,
+// sequences older than the window are unknown and regarded as not received
+func (w *sequenceWindow) receive(seq uint16) bool {
+	diff := int16(seq - w.latest)
+	if diff > 0 {
+		if int(diff) >= SEQUENCE_WINDOW_SIZE {
+			w.seen = [SEQUENCE_WINDOW_SIZE / 64]uint64{}
+		} else {
+			for s := w.latest + 1; s != seq; s++ {
+				w.clear(s)
+			}
+			w.clear(seq)
+		}
+		w.latest = seq
+		w.set(seq)
+		return false
+	}
+	if -int(diff) >= SEQUENCE_WINDOW_SIZE {
+		return false
+	}
+	if w.isSet(seq) {
+		return true
+	}
+	w.set(seq)
+	return false
+}
+
+type sequenceKey struct {
+	orgID   uint16
+	agentID uint16
+	msgType datatype.MessageType
+}
+
+// SequenceDedup drops the frames an agent replays from its disk buffer which have been received.
+//
+// The agent replays the frames whose sending failed after reconnecting, and a restarted agent may
+// replay frames sent before its restart. Frames are tracked by the sequences in their flow headers
+// for each agent and message type, and only frames with FLAG_REPLAYED are checked. The windows are
+// kept in memory, so frames replayed to another server or after the server restarts are not deduplicated.
+type SequenceDedup struct {
+	sync.Mutex
+	windows map[sequenceKey]*sequenceWindow
+}
+
+func NewSequenceDedup() *SequenceDedup {
+	return &SequenceDedup{windows: make(map[sequenceKey]*sequenceWindow)}
+}
+
+// IsDuplicate records the frame and returns whether it is a replayed frame received before
+func (d *SequenceDedup) IsDuplicate(msgType datatype.MessageType, header *datatype.FlowHeader) bool {
+	// the sequence is not available in the old header or without an agent id
+	if header.Version != datatype.LATEST_VERSION || header.AgentID == 0 {
+		return false
+	}
+	key := sequenceKey{orgID: header.OrgID, agentID: header.AgentID, msgType: msgType}
+	d.Lock()
+	defer d.Unlock()
+	w, ok := d.windows[key]
+	if !ok {
+		w = &sequenceWindow{latest: header.Sequence}
+		w.set(header.Sequence)
+		d.windows[key] = w
+		return false
+	}
+	received := w.receive(header.Sequence)
+	return received && header.Flags&datatype.FLAG_REPLAYED != 0
+}
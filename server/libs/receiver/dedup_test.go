@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package receiver
+
+import (
+	"testing"
+
+	"github.com/deepflowio/deepflow/server/libs/datatype"
+)
+
+func TestSequenceDedup(t *testing.T) {
+	dedup := NewSequenceDedup()
+	header := func(seq uint16, flags uint8) *datatype.FlowHeader {
+		return &datatype.FlowHeader{Version: datatype.LATEST_VERSION, AgentID: 1, Sequence: seq, Flags: flags}
+	}
+	for seq := uint16(65530); seq != 10; seq++ {
+		if dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(seq, 0)) {
+			t.Errorf("frame %d is not a duplicate", seq)
+		}
+	}
+	// replayed frames received before are dropped, even across the sequence wrapping
+	for _, seq := range []uint16{65530, 0, 9} {
+		if !dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(seq, datatype.FLAG_REPLAYED)) {
+			t.Errorf("replayed frame %d is a duplicate", seq)
+		}
+	}
+	// frames not received, and frames of other message types are kept
+	if dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(10, datatype.FLAG_REPLAYED)) {
+		t.Error("replayed frame 10 is not a duplicate")
+	}
+	if dedup.IsDuplicate(datatype.MESSAGE_TYPE_PROTOCOLLOG, header(9, datatype.FLAG_REPLAYED)) {
+		t.Error("replayed frame 9 of l7_log is not a duplicate")
+	}
+	// sequences older than the window are unknown
+	old := uint16(10)
+	old -= SEQUENCE_WINDOW_SIZE
+	if dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(old, datatype.FLAG_REPLAYED)) {
+		t.Error("replayed frame older than the window is not a duplicate")
+	}
+	// the window slides over sequences not received
+	dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(10+SEQUENCE_WINDOW_SIZE-1, 0))
+	if dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(20, datatype.FLAG_REPLAYED)) {
+		t.Error("replayed frame 20 is not a duplicate")
+	}
+	if !dedup.IsDuplicate(datatype.MESSAGE_TYPE_TAGGEDFLOW, header(10, datatype.FLAG_REPLAYED)) {
+		t.Error("replayed frame 10 is a duplicate")
+	}
+}
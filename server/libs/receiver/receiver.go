@@ -18,6 +18,7 @@ package receiver
 
 import (
 	"bufio"
+	"crypto/tls"
 	"encoding/binary"
 	"fmt"
 	"io"
@@ -398,7 +399,9 @@ type Receiver struct {
 
 	counter *ReceiverCounter
 
-	status *AdapterStatus
+	status    *AdapterStatus
+	dedup     *SequenceDedup
+	tlsConfig *tls.Config
 }
 
 type ReceiverCounter struct {
@@ -411,6 +414,11 @@ type ReceiverCounter struct {
 	UDPDisorder     uint64 `statsd:"udp_disorder"`      // 乱序个数
 	UDPDisorderSize uint64 `statsd:"udp_disorder_size"` // 乱序最大范围
 	NewBufferCount  uint64 `statsd:"new_buffer_count"`  // If the received data is large, you need to alloc memory, record the times.
+	Decompressed    uint64 `statsd:"decompressed"`
+	DecompressError uint64 `statsd:"decompress_error"`
+	Duplicated      uint64 `statsd:"duplicated"` // frames replayed by agents which have been received
+	TLSHandshake    uint64 `statsd:"tls_handshake"`
+	TLSError        uint64 `statsd:"tls_error"` // failed handshakes, or TLS connections when TLS is not enabled
 }
 
 func NewReceiver(
@@ -427,6 +435,7 @@ func NewReceiver(
 		timeNow:         time.Now().Unix(),
 		counter:         &ReceiverCounter{},
 		status:          &AdapterStatus{},
+		dedup:           NewSequenceDedup(),
 	}
 	receiver.status.init()
 
@@ -470,6 +479,11 @@ func (r *Receiver) HandleSimpleCommand(op uint16, arg string) string {
 	return ret
 }
 
+// SetTLSConfig enables TLS on the TCP server, agents may connect with either TLS or plain TCP
+func (r *Receiver) SetTLSConfig(config *tls.Config) {
+	r.tlsConfig = config
+}
+
 func (r *Receiver) SetServerType(serverType ServerType) {
 	r.serverType = serverType
 }
@@ -851,6 +865,25 @@ func (r *Receiver) handleTCPConnection(conn net.Conn) {
 	flowHeader := &datatype.FlowHeader{}
 	flowHeaderBuffer := make([]byte, datatype.FLOW_HEADER_LEN)
 	reader := bufio.NewReaderSize(conn, r.TCPReaderBuffer)
+	if isTLS, err := isTLSHandshake(reader); err != nil {
+		log.Warningf("TCP client (%s) connection read error: %s", conn.RemoteAddr().String(), err.Error())
+		return
+	} else if isTLS {
+		if r.tlsConfig == nil {
+			atomic.AddUint64(&r.counter.TLSError, 1)
+			log.Warningf("TCP client (%s) connects with TLS, but TLS is not enabled", conn.RemoteAddr().String())
+			return
+		}
+		tlsConn := tls.Server(&bufferedConn{Conn: conn, reader: reader}, r.tlsConfig)
+		defer tlsConn.Close()
+		if err := tlsConn.Handshake(); err != nil {
+			atomic.AddUint64(&r.counter.TLSError, 1)
+			log.Warningf("TCP client (%s) TLS handshake failed: %s", conn.RemoteAddr().String(), err.Error())
+			return
+		}
+		atomic.AddUint64(&r.counter.TLSHandshake, 1)
+		reader = bufio.NewReaderSize(tlsConn, r.TCPReaderBuffer)
+	}
 	for !r.exit {
 		if err := ReadN(reader, baseHeaderBuffer); err != nil {
 			log.Warningf("TCP client (%s) connection read error: %s", conn.RemoteAddr().String(), err.Error())
@@ -911,6 +944,26 @@ func (r *Receiver) handleTCPConnection(conn net.Conn) {
 			return
 		}
 
+		if baseHeader.Type.HeaderType() == datatype.HEADER_TYPE_LT_VTAP && r.dedup.IsDuplicate(baseHeader.Type, flowHeader) {
+			atomic.AddUint64(&r.counter.Duplicated, 1)
+			ReleaseRecvBuffer(recvBuffer)
+			continue
+		}
+
+		if baseHeader.Type.HeaderType() == datatype.HEADER_TYPE_LT_VTAP && flowHeader.Encoder != datatype.ENCODER_NONE {
+			decompressed, err := decompress(flowHeader.Encoder, recvBuffer.Buffer[:dataLen], TCP)
+			ReleaseRecvBuffer(recvBuffer)
+			if err != nil {
+				if r.counter.DecompressError == 0 {
+					log.Warningf("TCP client (%s) decompress error: %s", conn.RemoteAddr().String(), err.Error())
+				}
+				atomic.AddUint64(&r.counter.DecompressError, 1)
+				continue
+			}
+			atomic.AddUint64(&r.counter.Decompressed, 1)
+			recvBuffer, dataLen = decompressed, decompressed.End
+		}
+
 		if baseHeader.Type == datatype.MESSAGE_TYPE_METRICS {
 			metricsTimestamp = r.getMetricsTimestamp(recvBuffer.Buffer)
 			r.updateCounter(metricsTimestamp)
@@ -924,7 +977,7 @@ func (r *Receiver) handleTCPConnection(conn net.Conn) {
 			ReleaseRecvBuffer(recvBuffer)
 		} else {
 			recvBuffer.Begin = 0
-			recvBuffer.End = int(baseHeader.FrameSize) - headerLen
+			recvBuffer.End = dataLen
 			recvBuffer.IP = ip
 			recvBuffer.VtapID = vtapID
 			recvBuffer.TeamID = teamID
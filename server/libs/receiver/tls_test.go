@@ -0,0 +1,52 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package receiver
+
+import (
+	"bufio"
+	"bytes"
+	"encoding/binary"
+	"testing"
+
+	"github.com/deepflowio/deepflow/server/libs/datatype"
+)
+
+func TestIsTLSHandshake(t *testing.T) {
+	frame := make([]byte, datatype.MESSAGE_HEADER_LEN)
+	binary.BigEndian.PutUint32(frame, 16<<20)
+	if ok, err := isTLSHandshake(bufio.NewReader(bytes.NewReader(frame))); ok || err != nil {
+		t.Errorf("plain frame regarded as TLS handshake, err: %v", err)
+	}
+
+	reader := bufio.NewReader(bytes.NewReader([]byte{TLS_HANDSHAKE_RECORD, 0x03, 0x01}))
+	if ok, err := isTLSHandshake(reader); !ok || err != nil {
+		t.Errorf("TLS handshake not detected, err: %v", err)
+	}
+	if reader.Buffered() != 3 {
+		t.Errorf("peeked bytes consumed, %d bytes left", reader.Buffered())
+	}
+
+	if _, err := isTLSHandshake(bufio.NewReader(bytes.NewReader(nil))); err == nil {
+		t.Error("no error on empty connection")
+	}
+}
+
+func TestLoadTLSConfigMissingFiles(t *testing.T) {
+	if _, err := LoadTLSConfig("/nonexistent/ca.pem", "/nonexistent/cert.pem", "/nonexistent/key.pem"); err == nil {
+		t.Error("no error on missing files")
+	}
+}